@@ -0,0 +1,215 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use crate::command::get::{find_control_file, get_property};
+use crate::command::install::{filter_contents, get_version};
+use crate::CommandExecute;
+use cargo_toml::Manifest;
+use eyre::{eyre, WrapErr};
+use owo_colors::OwoColorize;
+use pgx_pg_config::{PgConfig, PgConfigSelector, Pgx, SUPPORTED_MAJOR_VERSIONS};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Describe the extension, and the Postgres installations pgx manages, for build tooling
+#[derive(clap::Args, Debug)]
+#[clap(author)]
+pub(crate) struct Info {
+    /// Print the description as JSON
+    #[clap(long)]
+    json: bool,
+    /// Package to describe (see `cargo help pkgid`)
+    #[clap(long, short)]
+    package: Option<String>,
+    /// Path to Cargo.toml
+    #[clap(long, value_parser)]
+    manifest_path: Option<PathBuf>,
+    #[clap(from_global, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+impl CommandExecute for Info {
+    #[tracing::instrument(level = "error", skip(self))]
+    fn execute(self) -> eyre::Result<()> {
+        let metadata = crate::metadata::metadata(&Default::default(), self.manifest_path.as_ref())
+            .wrap_err("couldn't get cargo metadata")?;
+        crate::metadata::validate(&metadata)?;
+        let package_manifest_path =
+            crate::manifest::manifest_path(&metadata, self.package.as_ref())
+                .wrap_err("Couldn't get manifest path")?;
+
+        let info = ExtensionInfo::new(&package_manifest_path)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        } else {
+            info.print();
+        }
+        Ok(())
+    }
+}
+
+/// What `cargo pgx info --json` prints.  Everything in it comes from `Cargo.toml`, the control
+/// file, and `pg_config`, so nothing is built to find it out.
+#[derive(Debug, Serialize)]
+pub(crate) struct ExtensionInfo {
+    /// The extension's name, which is the control file's
+    extname: String,
+    /// The crate's name and version
+    package: String,
+    package_version: String,
+    /// The control file's `default_version`, with `@CARGO_VERSION@` replaced
+    default_version: String,
+    /// The file name the shared library is installed as, in `pg_config --pkglibdir`
+    so_filename: String,
+    /// Whether the file name has the version in it, which it does when the control file has no
+    /// `module_pathname`
+    versioned_so: bool,
+    control_file: ControlFileInfo,
+    /// The `pgXX` features the crate has, one for each Postgres major version it supports
+    pg_versions: Vec<String>,
+    /// The `pgXX` feature enabled by default, which is the version used when none is given
+    default_pg_version: Option<String>,
+    /// The SQL files installed to `pg_config --sharedir`'s `extension` directory:  the generated
+    /// one for `default_version`, then the upgrade scripts in `sql/`
+    sql_files: Vec<String>,
+    /// The Postgres installations `cargo pgx init` set up, or the one `PGX_PG_CONFIG_PATH` names
+    pg_configs: Vec<PgConfigInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct ControlFileInfo {
+    path: PathBuf,
+    /// What's installed, with `@CARGO_VERSION@` and `@GIT_HASH@` replaced
+    contents: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PgConfigInfo {
+    label: String,
+    pg_config: Option<PathBuf>,
+    version: String,
+    bindir: PathBuf,
+    pkglibdir: PathBuf,
+    sharedir: PathBuf,
+    extension_dir: PathBuf,
+    includedir_server: PathBuf,
+}
+
+impl ExtensionInfo {
+    #[tracing::instrument(level = "error", skip_all)]
+    pub(crate) fn new(package_manifest_path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let package_manifest_path = package_manifest_path.as_ref();
+        let manifest =
+            Manifest::from_path(package_manifest_path).wrap_err("Couldn't parse manifest")?;
+        let package = manifest.package.as_ref().ok_or(eyre!("no `[package]` section found"))?;
+        let (control_path, extname) = find_control_file(package_manifest_path)?;
+        let default_version = get_version(package_manifest_path)?;
+
+        // note: versioned so-name format must agree with `cargo pgx install`
+        let versioned_so = get_property(package_manifest_path, "module_pathname")?.is_none();
+        let so_filename = if versioned_so {
+            format!("{}-{}.so", extname, default_version)
+        } else {
+            format!("{}.so", extname)
+        };
+
+        let contents = std::fs::read_to_string(&control_path)
+            .wrap_err_with(|| format!("failed to read `{}`", control_path.display()))?;
+        let contents = filter_contents(package_manifest_path, contents)?;
+
+        let is_pg_version = |feature: &String| {
+            SUPPORTED_MAJOR_VERSIONS.iter().any(|major| feature == &format!("pg{}", major))
+        };
+        let mut pg_versions = manifest
+            .features
+            .keys()
+            .filter(|&feature| is_pg_version(feature))
+            .cloned()
+            .collect::<Vec<_>>();
+        pg_versions.sort_by_key(|feature| feature[2..].parse::<u16>().unwrap_or_default());
+        let default_pg_version = manifest
+            .features
+            .get("default")
+            .and_then(|defaults| defaults.iter().find(|&feature| is_pg_version(feature)))
+            .cloned();
+
+        let mut sql_files = vec![format!("{}--{}.sql", extname, default_version)];
+        let sql_dir = package_manifest_path.parent().unwrap_or(Path::new(".")).join("sql");
+        if let Ok(dir) = std::fs::read_dir(&sql_dir) {
+            let mut upgrades = dir
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| {
+                    name.starts_with(&format!("{}--", extname)) && name.ends_with(".sql")
+                })
+                .collect::<Vec<_>>();
+            upgrades.sort();
+            sql_files.extend(upgrades);
+        }
+
+        Ok(ExtensionInfo {
+            extname,
+            package: package.name.clone(),
+            package_version: package.version.clone(),
+            default_version,
+            so_filename,
+            versioned_so,
+            control_file: ControlFileInfo { path: control_path, contents },
+            pg_versions,
+            default_pg_version,
+            sql_files,
+            pg_configs: pg_configs()?,
+        })
+    }
+
+    fn print(&self) {
+        let field = |name: &str, value: &dyn std::fmt::Display| {
+            println!("{:>18} {}", name.bold().green(), value)
+        };
+        field("extname", &self.extname);
+        field("package", &format!("{} {}", self.package, self.package_version));
+        field("default_version", &self.default_version);
+        field("so_filename", &self.so_filename);
+        field("control_file", &self.control_file.path.display());
+        field("pg_versions", &self.pg_versions.join(", "));
+        field("default_pg_version", &self.default_pg_version.as_deref().unwrap_or("none"));
+        field("sql_files", &self.sql_files.join(", "));
+        for pg_config in &self.pg_configs {
+            let path = pg_config.pg_config.as_ref().map(|path| path.display().to_string());
+            field(
+                &pg_config.label,
+                &format!("{} ({})", path.as_deref().unwrap_or("pg_config"), pg_config.version),
+            );
+        }
+    }
+}
+
+/// The installations `cargo pgx init` set up, which there are none of if it hasn't been run
+fn pg_configs() -> eyre::Result<Vec<PgConfigInfo>> {
+    let pgx = match Pgx::from_config() {
+        Ok(pgx) => pgx,
+        Err(_) if !Pgx::config_toml()?.exists() => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    pgx.iter(PgConfigSelector::All).map(|pg_config| PgConfigInfo::new(&pg_config?)).collect()
+}
+
+impl PgConfigInfo {
+    fn new(pg_config: &PgConfig) -> eyre::Result<Self> {
+        Ok(PgConfigInfo {
+            label: pg_config.label()?,
+            pg_config: pg_config.path(),
+            version: pg_config.version()?,
+            bindir: pg_config.bin_dir()?,
+            pkglibdir: pg_config.pkglibdir()?,
+            sharedir: pg_config.sharedir()?,
+            extension_dir: pg_config.extension_dir()?,
+            includedir_server: pg_config.includedir_server()?,
+        })
+    }
+}
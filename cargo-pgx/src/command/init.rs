@@ -13,7 +13,8 @@ use crate::CommandExecute;
 use eyre::{eyre, WrapErr};
 use owo_colors::OwoColorize;
 use pgx_pg_config::{
-    get_c_locale_flags, prefix_path, PgConfig, PgConfigSelector, Pgx, SUPPORTED_MAJOR_VERSIONS,
+    get_c_locale_flags, prefix_path, PgConfig, PgConfigSelector, Pgx, PortRange,
+    SUPPORTED_MAJOR_VERSIONS,
 };
 use rayon::prelude::*;
 
@@ -66,6 +67,10 @@ pub(crate) struct Init {
     base_port: Option<u16>,
     #[clap(long, help = "Base testing port number")]
     base_testing_port: Option<u16>,
+    #[clap(long, help = "Range of ports the test framework may use, such as `32200-32299`")]
+    testing_port_range: Option<PortRange>,
+    #[clap(long, help = "Only accept connections over Unix sockets, never TCP/IP")]
+    socket_only: bool,
 }
 
 impl CommandExecute for Init {
@@ -410,6 +415,12 @@ fn write_config(pg_configs: &Vec<PgConfig>, init: &Init) -> eyre::Result<()> {
     if let Some(port) = init.base_testing_port {
         file.write_all(format!("base_testing_port = {}\n", port).as_bytes())?;
     }
+    if let Some(range) = init.testing_port_range {
+        file.write_all(format!("testing_port_range = \"{}\"\n", range).as_bytes())?;
+    }
+    if init.socket_only {
+        file.write_all(b"socket_only = true\n")?;
+    }
 
     file.write_all(b"[configs]\n")?;
     for pg_config in pg_configs {
@@ -319,8 +319,12 @@ fn copy_sql_files(
         features,
         Some(&dest),
         Option::<String>::None,
+        Option::<String>::None,
+        None,
         None,
         skip_build,
+        false,
+        false,
     )?;
 
     // now copy all the version upgrade files too
@@ -441,7 +445,10 @@ pub(crate) fn format_display_path(path: impl AsRef<Path>) -> eyre::Result<String
     Ok(out)
 }
 
-fn filter_contents(manifest_path: impl AsRef<Path>, mut input: String) -> eyre::Result<String> {
+pub(crate) fn filter_contents(
+    manifest_path: impl AsRef<Path>,
+    mut input: String,
+) -> eyre::Result<String> {
     if input.contains("@GIT_HASH@") {
         // avoid doing this if we don't actually have the token
         // the project might not be a git repo so running `git`
@@ -0,0 +1,106 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use crate::manifest::{get_package_manifest, pg_config_and_version};
+use crate::CommandExecute;
+use eyre::{eyre, WrapErr};
+use pgx_pg_config::Pgx;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Print the log of a pgx-managed Postgres instance
+#[derive(clap::Args, Debug)]
+#[clap(author)]
+pub(crate) struct Log {
+    /// The Postgres version whose log to print (`pg11`, `pg12`, `pg13`, `pg14`, `pg15`)
+    #[clap(env = "PG_VERSION")]
+    pg_version: Option<String>,
+    /// Keep printing what's appended to the log, until interrupted
+    #[clap(long, short)]
+    follow: bool,
+    /// Only print this many lines from the end of the log
+    #[clap(long, short = 'n')]
+    lines: Option<usize>,
+    /// Print the log of the instance `cargo pgx test` runs the tests in, rather than the one
+    /// `cargo pgx start` starts
+    #[clap(long)]
+    test: bool,
+    #[clap(from_global, action = ArgAction::Count)]
+    verbose: u8,
+    /// Package to determine default `pg_version` with (see `cargo help pkgid`)
+    #[clap(long, short)]
+    package: Option<String>,
+    /// Path to Cargo.toml
+    #[clap(long, value_parser)]
+    manifest_path: Option<PathBuf>,
+}
+
+impl CommandExecute for Log {
+    #[tracing::instrument(level = "error", skip(self))]
+    fn execute(self) -> eyre::Result<()> {
+        let pgx = Pgx::from_config()?;
+        let (package_manifest, _) = get_package_manifest(
+            &clap_cargo::Features::default(),
+            self.package.as_ref(),
+            self.manifest_path,
+        )?;
+        let (pg_config, _) =
+            pg_config_and_version(&pgx, &package_manifest, self.pg_version, None, false)?;
+
+        let logfile = if self.test { pg_config.test_log_file()? } else { pg_config.log_file()? };
+        if !logfile.exists() && !self.follow {
+            return Err(eyre!(
+                "Postgres v{} hasn't written a log to `{}` yet",
+                pg_config.major_version()?,
+                logfile.display()
+            ));
+        }
+
+        print_log(&logfile, self.lines, self.follow)
+    }
+}
+
+#[tracing::instrument(level = "error", skip_all, fields(logfile = %logfile.display()))]
+fn print_log(logfile: &Path, lines: Option<usize>, follow: bool) -> eyre::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    let mut position = 0;
+
+    if logfile.exists() {
+        let file = File::open(logfile)
+            .wrap_err_with(|| format!("couldn't open `{}`", logfile.display()))?;
+        let all_lines = BufReader::new(file).lines().collect::<Result<Vec<_>, _>>()?;
+        let skip = lines.map_or(0, |lines| all_lines.len().saturating_sub(lines));
+        for line in &all_lines[skip..] {
+            writeln!(stdout, "{}", line)?;
+        }
+        position = std::fs::metadata(logfile)?.len();
+    }
+
+    while follow {
+        stdout.flush()?;
+        std::thread::sleep(Duration::from_millis(250));
+
+        let mut file = match File::open(logfile) {
+            Ok(file) => file,
+            // it hasn't been created yet, or it's being rotated
+            Err(_) => continue,
+        };
+        let len = file.metadata()?.len();
+        if len < position {
+            // Postgres was restarted, and the log started over
+            position = 0;
+        }
+        file.seek(SeekFrom::Start(position))?;
+        position += std::io::copy(&mut (&mut file).take(len - position), &mut stdout)?;
+    }
+
+    Ok(())
+}
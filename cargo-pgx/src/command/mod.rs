@@ -10,8 +10,10 @@ Use of this source code is governed by the MIT license that can be found in the
 pub(crate) mod connect;
 pub(crate) mod cross;
 pub(crate) mod get;
+pub(crate) mod info;
 pub(crate) mod init;
 pub(crate) mod install;
+pub(crate) mod log;
 pub(crate) mod new;
 pub(crate) mod package;
 pub(crate) mod pgx;
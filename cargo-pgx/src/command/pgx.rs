@@ -31,6 +31,7 @@ enum CargoPgxSubCommands {
     Start(super::start::Start),
     Stop(super::stop::Stop),
     Status(super::status::Status),
+    Log(super::log::Log),
     New(super::new::New),
     Install(super::install::Install),
     Package(super::package::Package),
@@ -39,6 +40,7 @@ enum CargoPgxSubCommands {
     Connect(super::connect::Connect),
     Test(super::test::Test),
     Get(super::get::Get),
+    Info(super::info::Info),
     Cross(super::cross::Cross),
 }
 
@@ -51,6 +53,7 @@ impl CommandExecute for CargoPgxSubCommands {
             Start(c) => c.execute(),
             Stop(c) => c.execute(),
             Status(c) => c.execute(),
+            Log(c) => c.execute(),
             New(c) => c.execute(),
             Install(c) => c.execute(),
             Package(c) => c.execute(),
@@ -59,6 +62,7 @@ impl CommandExecute for CargoPgxSubCommands {
             Connect(c) => c.execute(),
             Test(c) => c.execute(),
             Get(c) => c.execute(),
+            Info(c) => c.execute(),
             Cross(c) => c.execute(),
         }
     }
@@ -144,7 +144,7 @@ pub(crate) fn exec_psql(pg_config: &PgConfig, dbname: &str, pgcli: bool) -> eyre
         .env_remove("PGPORT")
         .env_remove("PGUSER")
         .arg("-h")
-        .arg(pg_config.host())
+        .arg(pg_config.host()?)
         .arg("-p")
         .arg(pg_config.port()?.to_string())
         .arg(dbname);
@@ -66,11 +66,49 @@ pub(crate) struct Schema {
     /// A path to output a produced GraphViz DOT file
     #[clap(long, short, value_parser)]
     dot: Option<PathBuf>,
+    /// A path to output a C header declaring the `#[pg_export_abi]` functions, for other
+    /// extensions to call
+    #[clap(long, value_parser)]
+    emit_header: Option<PathBuf>,
+    /// Also write the SQL as a file for each Postgres schema or top-level Rust module, and an
+    /// index including them in order, for reviewing changes to it
+    #[clap(long, value_enum, requires = "split_review_dir")]
+    split_by: Option<SplitBy>,
+    /// The directory `--split-by` writes its files to, which has to be empty or only have the files
+    /// it wrote before, which are replaced
+    #[clap(long, value_parser, requires = "split_by")]
+    split_review_dir: Option<PathBuf>,
     #[clap(from_global, action = ArgAction::Count)]
     verbose: u8,
     /// Skip building a fresh extension shared object.
     #[clap(long)]
     skip_build: bool,
+    /// Check functions for contradictions between their attributes and what they do, like an
+    /// `immutable` function that uses SPI
+    #[clap(long)]
+    lint: bool,
+    /// Build only what generating the schema needs, by enabling the package's `sql-generation`
+    /// feature, which stubs out the functions' bodies
+    #[clap(long, conflicts_with = "skip_build")]
+    metadata_only: bool,
+}
+
+/// What `--split-by` puts in a file of its own
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SplitBy {
+    /// Each Postgres schema
+    Schema,
+    /// Each top-level Rust module
+    Module,
+}
+
+impl From<SplitBy> for pgx_sql_entity_graph::SplitBy {
+    fn from(split_by: SplitBy) -> Self {
+        match split_by {
+            SplitBy::Schema => pgx_sql_entity_graph::SplitBy::Schema,
+            SplitBy::Module => pgx_sql_entity_graph::SplitBy::Module,
+        }
+    }
 }
 
 impl CommandExecute for Schema {
@@ -116,8 +154,12 @@ impl CommandExecute for Schema {
             &self.features,
             self.out.as_ref(),
             self.dot,
+            self.emit_header,
+            self.split_by.map(Into::into).zip(self.split_review_dir.as_deref()),
             log_level,
             self.skip_build,
+            self.lint,
+            self.metadata_only,
         )
     }
 }
@@ -187,12 +229,16 @@ pub(crate) fn generate_schema(
     features: &clap_cargo::Features,
     path: Option<impl AsRef<std::path::Path>>,
     dot: Option<impl AsRef<std::path::Path>>,
+    header: Option<impl AsRef<std::path::Path>>,
+    split_review: Option<(pgx_sql_entity_graph::SplitBy, &Path)>,
     log_level: Option<String>,
     skip_build: bool,
+    lint: bool,
+    metadata_only: bool,
 ) -> eyre::Result<()> {
     check_rust_version()?;
     let manifest = Manifest::from_path(&package_manifest_path)?;
-    let (control_file, _extname) = find_control_file(&package_manifest_path)?;
+    let (control_file, extname) = find_control_file(&package_manifest_path)?;
     let package_name = &manifest
         .package
         .as_ref()
@@ -210,7 +256,20 @@ pub(crate) fn generate_schema(
 
     let flags = std::env::var("PGX_BUILD_FLAGS").unwrap_or_default();
 
-    let mut target_dir_with_profile = get_target_dir()?;
+    if metadata_only && !manifest.features.contains_key("sql-generation") {
+        return Err(eyre!(
+            "{}:  `--metadata-only` needs a `sql-generation` feature.  Please add `sql-generation = [\"pgx/sql-generation\"]` to its `[features]`.",
+            package_manifest_path.as_ref().display()
+        ));
+    }
+
+    // A metadata-only build gets its own target directory, so it doesn't replace the extension's
+    // real shared object, nor make the next real build start over
+    let mut target_dir = get_target_dir()?;
+    if metadata_only {
+        target_dir.push("sql-generation");
+    }
+    let mut target_dir_with_profile = target_dir.clone();
     target_dir_with_profile.push(profile.target_subdir());
 
     // First, build the SQL generator so we can get a look at the symbol table
@@ -241,7 +300,13 @@ pub(crate) fn generate_schema(
             command.env("RUST_LOG", log_level);
         }
 
-        let features_arg = features.features.join(" ");
+        let mut features_list = features.features.clone();
+        if metadata_only {
+            features_list.push("sql-generation".to_string());
+            command.arg("--target-dir");
+            command.arg(&target_dir);
+        }
+        let features_arg = features_list.join(" ");
         if !features_arg.trim().is_empty() {
             command.arg("--features");
             command.arg(&features_arg);
@@ -278,6 +343,49 @@ pub(crate) fn generate_schema(
         }
     };
 
+    let mut lib_so = target_dir_with_profile.clone();
+
+    let so_extension = if cfg!(target_os = "macos") { ".dylib" } else { ".so" };
+
+    lib_so.push(&format!("lib{}{}", package_name.replace('-', "_"), so_extension));
+
+    let lib_so_data = std::fs::read(&lib_so).wrap_err("couldn't read extension shared object")?;
+
+    // The SQL only depends on the shared object and how cargo-pgx generates it, so if neither has
+    // changed since we last wrote the SQL to the same place, we needn't stub the postmaster and
+    // load the shared object again
+    let schema_hash_file = target_dir_with_profile.join(format!(
+        "{}-pg{}.schema-hash",
+        package_name,
+        pg_config.major_version()?
+    ));
+    let schema_hash = path.as_ref().map(|out_path| {
+        let out_path = out_path.as_ref().to_string_lossy();
+        let parts: [&[u8]; 4] = [
+            env!("CARGO_PKG_VERSION").as_bytes(),
+            &lib_so_data,
+            out_path.as_bytes(),
+            &[versioned_so as u8],
+        ];
+        // each part ends with a NUL, so moving bytes between them changes the hash
+        let hash = parts.iter().fold(FNV_OFFSET_BASIS, |hash, part| fnv1a(fnv1a(hash, part), &[0]));
+        format!("{hash:016x}").into_bytes()
+    });
+    if let (Some(out_path), Some(schema_hash), None, None, None, false) =
+        (&path, &schema_hash, &dot, &header, &split_review, lint)
+    {
+        let out_path = out_path.as_ref();
+        if out_path.exists() && std::fs::read(&schema_hash_file).ok().as_ref() == Some(schema_hash)
+        {
+            eprintln!(
+                "{} SQL entities in {} are up to date",
+                "     Skipping".bold().green(),
+                format_display_path(out_path)?.cyan()
+            );
+            return Ok(());
+        }
+    }
+
     // Create stubbed `pgx_pg_sys` bindings for the generator to link with.
     let mut postmaster_stub_dir =
         Pgx::postmaster_stub_dir().wrap_err("couldn't get postmaster stub dir env")?;
@@ -300,13 +408,6 @@ pub(crate) fn generate_schema(
     let postmaster_stub_built = create_stub(&postmaster_path, &postmaster_stub_dir)?;
 
     // Inspect the symbol table for a list of `__pgx_internals` we should have the generator call
-    let mut lib_so = target_dir_with_profile.clone();
-
-    let so_extension = if cfg!(target_os = "macos") { ".dylib" } else { ".so" };
-
-    lib_so.push(&format!("lib{}{}", package_name.replace('-', "_"), so_extension));
-
-    let lib_so_data = std::fs::read(&lib_so).wrap_err("couldn't read extension shared object")?;
     let lib_so_obj_file =
         object::File::parse(&*lib_so_data).wrap_err("couldn't parse extension shared object")?;
     let lib_so_exports =
@@ -315,6 +416,7 @@ pub(crate) fn generate_schema(
     // Some users reported experiencing duplicate entries if we don't ensure `fns_to_call`
     // has unique entries.
     let mut fns_to_call = HashSet::new();
+    let mut abi_exports_to_call = HashSet::new();
     for export in lib_so_exports {
         let name = std::str::from_utf8(export.name())?.to_string();
         #[cfg(target_os = "macos")]
@@ -329,6 +431,8 @@ pub(crate) fn generate_schema(
 
         if name.starts_with("__pgx_internals") {
             fns_to_call.insert(name);
+        } else if name.starts_with("__pgx_abi_export_") {
+            abi_exports_to_call.insert(name);
         }
     }
     let mut seen_schemas = Vec::new();
@@ -336,10 +440,15 @@ pub(crate) fn generate_schema(
     let mut num_triggers = 0_usize;
     let mut num_types = 0_usize;
     let mut num_enums = 0_usize;
+    let mut num_domains = 0_usize;
     let mut num_sqls = 0_usize;
     let mut num_ords = 0_usize;
     let mut num_hashes = 0_usize;
     let mut num_aggregates = 0_usize;
+    let mut num_policies = 0_usize;
+    let mut num_casts = 0_usize;
+    let mut num_gin_opclasses = 0_usize;
+    let mut num_views = 0_usize;
     for func in &fns_to_call {
         if func.starts_with("__pgx_internals_schema_") {
             let schema = func
@@ -356,6 +465,8 @@ pub(crate) fn generate_schema(
             num_types += 1;
         } else if func.starts_with("__pgx_internals_enum_") {
             num_enums += 1;
+        } else if func.starts_with("__pgx_internals_domain_") {
+            num_domains += 1;
         } else if func.starts_with("__pgx_internals_sql_") {
             num_sqls += 1;
         } else if func.starts_with("__pgx_internals_ord_") {
@@ -364,11 +475,19 @@ pub(crate) fn generate_schema(
             num_hashes += 1;
         } else if func.starts_with("__pgx_internals_aggregate_") {
             num_aggregates += 1;
+        } else if func.starts_with("__pgx_internals_policy_") {
+            num_policies += 1;
+        } else if func.starts_with("__pgx_internals_cast_") {
+            num_casts += 1;
+        } else if func.starts_with("__pgx_internals_gin_opclass_") {
+            num_gin_opclasses += 1;
+        } else if func.starts_with("__pgx_internals_view_") {
+            num_views += 1;
         }
     }
 
     eprintln!(
-        "{} {} SQL entities: {} schemas ({} unique), {} functions, {} types, {} enums, {} sqls, {} ords, {} hashes, {} aggregates, {} triggers",
+        "{} {} SQL entities: {} schemas ({} unique), {} functions, {} types, {} enums, {} domains, {} sqls, {} ords, {} hashes, {} aggregates, {} triggers, {} policies, {} casts, {} gin opclasses, {} views",
         "  Discovered".bold().green(),
         fns_to_call.len().to_string().bold().cyan(),
         seen_schemas.iter().count().to_string().bold().cyan(),
@@ -376,15 +495,21 @@ pub(crate) fn generate_schema(
         num_funcs.to_string().bold().cyan(),
         num_types.to_string().bold().cyan(),
         num_enums.to_string().bold().cyan(),
+        num_domains.to_string().bold().cyan(),
         num_sqls.to_string().bold().cyan(),
         num_ords.to_string().bold().cyan(),
         num_hashes.to_string().bold().cyan(),
         num_aggregates.to_string().bold().cyan(),
         num_triggers.to_string().bold().cyan(),
+        num_policies.to_string().bold().cyan(),
+        num_casts.to_string().bold().cyan(),
+        num_gin_opclasses.to_string().bold().cyan(),
+        num_views.to_string().bold().cyan(),
     );
 
     tracing::debug!("Collecting {} SQL entities", fns_to_call.len());
     let mut entities = Vec::default();
+    let mut abi_exports = Vec::default();
 
     #[rustfmt::skip] // explicit extern "Rust" is more clear here
     unsafe {
@@ -425,15 +550,43 @@ pub(crate) fn generate_schema(
             let entity = symbol();
             entities.push(entity);
         }
+
+        if header.is_some() {
+            for symbol_to_call in abi_exports_to_call {
+                let symbol: libloading::os::unix::Symbol<unsafe extern "Rust" fn() -> pgx_sql_entity_graph::PgExportAbiEntity> =
+                    lib.get(symbol_to_call.as_bytes()).unwrap_or_else(|_|
+                        panic!("Couldn't call {:#?}", symbol_to_call));
+                abi_exports.push(symbol());
+            }
+        }
     };
 
     let pgx_sql = pgx_sql_entity_graph::PgxSql::build(
         entities.into_iter(),
         package_name.to_string(),
         versioned_so,
+        pg_config.major_version()?,
     )
     .wrap_err("SQL generation error")?;
 
+    if lint {
+        let lints = pgx_sql.lint();
+        for lint in &lints {
+            let level = match lint.level {
+                pgx_sql_entity_graph::LintLevel::Warning => lint.level.bold().yellow().to_string(),
+                pgx_sql_entity_graph::LintLevel::Error => lint.level.bold().red().to_string(),
+            };
+            eprintln!("{}: {}", level, lint);
+        }
+        let errors = lints
+            .iter()
+            .filter(|lint| lint.level == pgx_sql_entity_graph::LintLevel::Error)
+            .count();
+        if errors > 0 {
+            return Err(eyre!("schema lint found {} error(s)", errors));
+        }
+    }
+
     if let Some(out_path) = path {
         let out_path = out_path.as_ref();
 
@@ -449,6 +602,10 @@ pub(crate) fn generate_schema(
         pgx_sql
             .to_file(out_path)
             .wrap_err_with(|| eyre!("Could not write SQL to {}", out_path.display()))?;
+        if let Some(schema_hash) = schema_hash {
+            std::fs::write(&schema_hash_file, schema_hash)
+                .wrap_err("could not write the schema's hash")?;
+        }
     } else {
         eprintln!("{} SQL entities to {}", "     Writing".bold().green(), "/dev/stdout".cyan(),);
         pgx_sql
@@ -456,11 +613,39 @@ pub(crate) fn generate_schema(
             .wrap_err_with(|| eyre!("Could not write SQL to stdout"))?;
     }
 
+    if let Some((split_by, split_dir)) = split_review {
+        eprintln!(
+            "{} SQL entities split by {} to {}",
+            "     Writing".bold().green(),
+            split_by,
+            format_display_path(split_dir)?.cyan()
+        );
+        pgx_sql
+            .to_split_dir(split_dir, split_by)
+            .wrap_err_with(|| eyre!("Could not write split SQL to {}", split_dir.display()))?;
+    }
+
     if let Some(dot_path) = dot {
         let dot_path = dot_path.as_ref();
         tracing::info!(dot = %dot_path.display(), "Writing Graphviz DOT");
         pgx_sql.to_dot(dot_path)?;
     }
+
+    if let Some(header_path) = header {
+        let header_path = header_path.as_ref();
+        eprintln!(
+            "{} {} exported functions to {}",
+            "     Writing".bold().green(),
+            abi_exports.len().to_string().bold().cyan(),
+            format_display_path(header_path)?.cyan()
+        );
+        if let Some(parent) = header_path.parent() {
+            std::fs::create_dir_all(parent).wrap_err("Could not create parent directory")?
+        }
+        let c_header = pgx_sql_entity_graph::PgExportAbiEntity::c_header(&extname, &abi_exports);
+        std::fs::write(header_path, c_header)
+            .wrap_err_with(|| eyre!("Could not write C header to {}", header_path.display()))?;
+    }
     Ok(())
 }
 
@@ -563,3 +748,11 @@ fn create_stub(
 
     Ok(postmaster_stub_built)
 }
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, which unlike [`std::collections::hash_map::DefaultHasher`] is the same in every build
+/// of cargo-pgx, so a hash written by one is still understood by the next
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100_0000_01b3))
+}
@@ -13,7 +13,7 @@ use crate::manifest::{get_package_manifest, pg_config_and_version};
 use crate::CommandExecute;
 use eyre::eyre;
 use owo_colors::OwoColorize;
-use pgx_pg_config::{PgConfig, PgConfigSelector, Pgx};
+use pgx_pg_config::{rotate_log_file, PgConfig, PgConfigSelector, Pgx};
 use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
 use std::process::Stdio;
@@ -80,12 +80,27 @@ pub(crate) fn start_postgres(pg_config: &PgConfig) -> eyre::Result<()> {
         return Ok(());
     }
 
-    println!(
-        "{} Postgres v{} on port {}",
-        "    Starting".bold().green(),
-        pg_config.major_version()?,
-        port.to_string().bold().cyan()
-    );
+    // each start gets a log of its own, and the previous one is kept as `PGVER.log.1`
+    rotate_log_file(&logfile)?;
+
+    if pg_config.socket_only() {
+        println!(
+            "{} Postgres v{} on socket {}/.s.PGSQL.{}",
+            "    Starting".bold().green(),
+            pg_config.major_version()?,
+            pg_config.socket_dir()?.display(),
+            port.to_string().bold().cyan()
+        );
+    } else {
+        println!(
+            "{} Postgres v{} on port {}",
+            "    Starting".bold().green(),
+            pg_config.major_version()?,
+            port.to_string().bold().cyan()
+        );
+    }
+    // `-i` listens on every TCP/IP address, which socket-only mode must not do
+    let listen = if pg_config.socket_only() { "-c listen_addresses=''" } else { "-i" };
     let mut command = std::process::Command::new(format!("{}/pg_ctl", bindir.display()));
     // Unsafe block is for the pre_exec setsid call below
     //
@@ -97,7 +112,12 @@ pub(crate) fn start_postgres(pg_config: &PgConfig) -> eyre::Result<()> {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .arg("start")
-            .arg(format!("-o -i -p {} -c unix_socket_directories={}", port, Pgx::home()?.display()))
+            .arg(format!(
+                "-o {} -p {} -c unix_socket_directories={}",
+                listen,
+                port,
+                pg_config.socket_dir()?.display()
+            ))
             .arg("-D")
             .arg(&datadir)
             .arg("-l")
@@ -113,9 +133,10 @@ pub(crate) fn start_postgres(pg_config: &PgConfig) -> eyre::Result<()> {
 
     if !output.status.success() {
         return Err(eyre!(
-            "problem running pg_ctl: {}\n\n{}",
+            "problem running pg_ctl: {}\n\n{}\nIts log is in `{}`",
             command_str,
-            String::from_utf8(output.stderr).unwrap()
+            String::from_utf8(output.stderr).unwrap(),
+            logfile.display()
         ));
     }
 
@@ -49,6 +49,10 @@ impl CommandExecute for Status {
             } else {
                 println!("Postgres v{} is {}", pg_config.major_version()?, "stopped".bold().red())
             }
+            let logfile = pg_config.log_file()?;
+            if logfile.exists() {
+                println!("    {} {}", "log:".dimmed(), logfile.display());
+            }
         }
 
         Ok(())
@@ -89,7 +89,11 @@ pub(crate) fn stop_postgres(pg_config: &PgConfig) -> eyre::Result<()> {
     let output = command.output()?;
 
     if !output.status.success() {
-        Err(eyre!("{}", String::from_utf8(output.stderr)?,))
+        Err(eyre!(
+            "{}\nIts log is in `{}`",
+            String::from_utf8(output.stderr)?,
+            pg_config.log_file()?.display()
+        ))
     } else {
         Ok(())
     }
@@ -7,10 +7,12 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 
-use eyre::Context;
-use pgx_pg_config::{get_target_dir, PgConfig, PgConfigSelector, Pgx};
+use eyre::{eyre, Context};
+use owo_colors::OwoColorize;
+use pgx_pg_config::{get_target_dir, rotate_log_file, PgConfig, PgConfigSelector, Pgx};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
 use crate::manifest::{get_package_manifest, pg_config_and_version};
 use crate::profile::CargoProfile;
@@ -25,6 +27,10 @@ pub(crate) struct Test {
     pg_version: Option<String>,
     /// If specified, only run tests containing this string in their names
     testname: Option<String>,
+    /// Run against each of these comma-separated Postgres versions in turn, like `pg13,pg15`.
+    /// The first argument is then the test name.
+    #[clap(long, value_delimiter = ',')]
+    versions: Vec<String>,
     /// Package to build (see `cargo help pkgid`)
     #[clap(long, short)]
     package: Option<String>,
@@ -40,6 +46,10 @@ pub(crate) struct Test {
     /// Don't regenerate the schema
     #[clap(long, short)]
     no_schema: bool,
+    /// Write the current output of the SQL regression tests, and the current schema of schema
+    /// snapshot tests, to their expected files rather than comparing them
+    #[clap(long)]
+    bless: bool,
     #[clap(flatten)]
     features: clap_cargo::Features,
     #[clap(from_global, action = clap::ArgAction::Count)]
@@ -48,9 +58,9 @@ pub(crate) struct Test {
 
 impl CommandExecute for Test {
     #[tracing::instrument(level = "error", skip(self))]
-    fn execute(self) -> eyre::Result<()> {
+    fn execute(mut self) -> eyre::Result<()> {
         #[tracing::instrument(level = "error", skip(me))]
-        fn perform(me: Test, pgx: &Pgx) -> eyre::Result<()> {
+        fn perform(me: Test, pgx: &Pgx) -> eyre::Result<bool> {
             let mut features = me.features.clone();
             let (package_manifest, _package_manifest_path) =
                 get_package_manifest(&me.features, me.package.as_ref(), me.manifest_path.as_ref())?;
@@ -73,28 +83,84 @@ impl CommandExecute for Test {
                 me.package.as_ref(),
                 &profile,
                 me.no_schema,
+                me.bless,
                 &features,
                 me.testname,
-            )?;
-
-            Ok(())
+            )
         }
 
         let pgx = Pgx::from_config()?;
-        if self.pg_version == Some("all".to_string()) {
-            // run the tests for **all** the Postgres versions we know about
-            for v in pgx.iter(PgConfigSelector::All) {
-                let mut versioned_test = self.clone();
-                versioned_test.pg_version = Some(v?.label()?);
-                perform(versioned_test, &pgx)?;
+        let labels = if !self.versions.is_empty() {
+            if let Some(pg_version) = self.pg_version.take() {
+                if is_version_label(&pg_version) {
+                    return Err(eyre!(
+                        "`--versions` can't be combined with a Postgres version argument or $PG_VERSION, here `{}`",
+                        pg_version
+                    ));
+                }
+                if self.testname.is_some() {
+                    return Err(eyre!("with `--versions`, the only argument is the test name"));
+                }
+                self.testname = Some(pg_version);
             }
-
-            Ok(())
+            for label in &self.versions {
+                // fail now rather than after testing the versions before it
+                pgx.get(label)?;
+            }
+            std::mem::take(&mut self.versions)
+        } else if self.pg_version.as_deref() == Some("all") {
+            pgx.iter(PgConfigSelector::All).map(|v| v?.label()).collect::<eyre::Result<Vec<_>>>()?
         } else {
             // attempt to run the test for the Postgres version `run_test()` will figure out
-            perform(self, &pgx)
+            if !perform(self, &pgx)? {
+                // We explicitly do not want to return a spantraced error here.
+                std::process::exit(1)
+            }
+            return Ok(());
+        };
+
+        // each version is its own set of features, whose artifacts cargo keeps side by side in the
+        // one target directory, so only the first run for a version builds everything
+        let mut results = Vec::with_capacity(labels.len());
+        for label in labels {
+            let mut versioned_test = self.clone();
+            versioned_test.pg_version = Some(label.clone());
+            let started = Instant::now();
+            let outcome = perform(versioned_test, &pgx);
+            if let Err(e) = &outcome {
+                eprintln!("{} {}: {:?}", "      Failed".bold().red(), label, e);
+            }
+            results.push((label, outcome.unwrap_or(false), started.elapsed()));
+        }
+
+        if !print_summary(&results) {
+            std::process::exit(1)
         }
+        Ok(())
+    }
+}
+
+fn is_version_label(arg: &str) -> bool {
+    arg == "all" || arg.strip_prefix("pg").map_or(false, |major| major.parse::<u16>().is_ok())
+}
+
+/// Print whether each version's tests passed, and return if they all did
+fn print_summary(results: &[(String, bool, Duration)]) -> bool {
+    println!();
+    println!("{}", "Test results by Postgres version:".bold());
+    for (label, passed, elapsed) in results {
+        let outcome = if *passed {
+            "passed".bold().green().to_string()
+        } else {
+            "FAILED".bold().red().to_string()
+        };
+        println!("    {:<6} {} in {:.1}s", label, outcome, elapsed.as_secs_f64());
+    }
+    let failed = results.iter().filter(|(_, passed, _)| !passed).count();
+    if failed > 0 {
+        println!("{} of {} Postgres versions failed", failed, results.len());
     }
+    failed == 0
 }
 
 #[tracing::instrument(skip_all, fields(
@@ -108,14 +174,18 @@ pub fn test_extension(
     user_package: Option<&String>,
     profile: &CargoProfile,
     no_schema: bool,
+    bless: bool,
     features: &clap_cargo::Features,
     testname: Option<impl AsRef<str>>,
-) -> eyre::Result<()> {
+) -> eyre::Result<bool> {
     if let Some(ref testname) = testname {
         tracing::Span::current().record("testname", &tracing::field::display(&testname.as_ref()));
     }
     let target_dir = get_target_dir()?;
 
+    // the test framework appends to the test instance's log, which starts over for each run
+    rotate_log_file(&pg_config.test_log_file()?)?;
+
     let mut command = Command::new("cargo");
 
     let no_default_features_arg = features.no_default_features;
@@ -133,7 +203,13 @@ pub fn test_extension(
         .env("PGX_NO_DEFAULT_FEATURES", if no_default_features_arg { "true" } else { "false" })
         .env("PGX_ALL_FEATURES", if features.all_features { "true" } else { "false" })
         .env("PGX_BUILD_PROFILE", profile.name())
-        .env("PGX_NO_SCHEMA", if no_schema { "true" } else { "false" });
+        .env("PGX_NO_SCHEMA", if no_schema { "true" } else { "false" })
+        .env("PGX_TEST_LOG_ROTATED", "true");
+
+    if bless {
+        // read by pgx-tests' `run_sql_regress()` and `assert_schema_snapshot()`
+        command.env("UPDATE_SNAPSHOT", "1");
+    }
 
     if let Ok(rust_log) = std::env::var("RUST_LOG") {
         command.env("RUST_LOG", rust_log);
@@ -173,12 +249,5 @@ pub fn test_extension(
     tracing::debug!(command = ?command, "Running");
     let status = command.status().wrap_err("failed to run cargo test")?;
     tracing::trace!(status_code = %status, command = ?command, "Finished");
-    if !status.success() {
-        if !status.success() {
-            // We explicitly do not want to return a spantraced error here.
-            std::process::exit(1)
-        }
-    }
-
-    Ok(())
+    Ok(status.success())
 }
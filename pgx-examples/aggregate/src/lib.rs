@@ -141,6 +141,64 @@ impl Default for IntegerAvgState {
     }
 }
 
+/// Collects the non-null values into an `integer[]`, like `array_agg()` but in parallel.
+///
+/// The values are kept in a `Vec` which lives in the aggregate's memory context, and finally
+/// built into an array with Postgres' array builder.
+pub struct IntegerArray;
+
+#[pg_aggregate]
+impl Aggregate for IntegerArray {
+    type State = AggregateInternalState<Vec<i32>>;
+    type Args = pgx::name!(value, Option<i32>);
+    type Finalize = Vec<i32>;
+    const NAME: &'static str = "DEMOARRAY";
+
+    // Postgres only runs it in parallel with `combine`, `serial` and `deserial`.
+    const PARALLEL: Option<ParallelOption> = Some(ParallelOption::Safe);
+
+    fn state(
+        mut current: Self::State,
+        arg: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        let values = current.get_or_insert_default(fcinfo);
+        values.extend(arg);
+        current
+    }
+
+    // Each worker's state is moved into the leader's aggregate memory context.
+    fn combine(
+        current: Self::State,
+        other: Self::State,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.combine(other, fcinfo, |current, other| current.extend(other))
+    }
+
+    fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+        current.serialize(fcinfo)
+    }
+
+    fn deserial(
+        _current: Self::State,
+        buf: Vec<u8>,
+        mut internal: PgBox<Self::State>,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> PgBox<Self::State> {
+        *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+        internal
+    }
+
+    fn finalize(
+        mut current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        current.take(fcinfo).unwrap_or_default()
+    }
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pg_schema]
 mod tests {
@@ -172,6 +230,32 @@ mod tests {
         assert_eq!(retval, Ok(Some(2)));
         Ok(())
     }
+
+    #[pg_test]
+    fn test_integer_array_sql() -> Result<(), spi::Error> {
+        Spi::run("CREATE TABLE demo_table (value INTEGER);")?;
+        Spi::run("INSERT INTO demo_table (value) VALUES (1), (NULL), (3);")?;
+        let retval =
+            Spi::get_one::<Vec<i32>>("SELECT DEMOARRAY(value ORDER BY value) FROM demo_table;");
+        assert_eq!(retval, Ok(Some(vec![1, 3])));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_integer_array_parallel() -> Result<(), spi::Error> {
+        Spi::run(
+            "CREATE TABLE demo_table AS SELECT value FROM generate_series(1, 100000) AS value;",
+        )?;
+        Spi::run("SET LOCAL max_parallel_workers_per_gather = 4;")?;
+        Spi::run("SET LOCAL parallel_setup_cost = 0;")?;
+        Spi::run("SET LOCAL parallel_tuple_cost = 0;")?;
+        Spi::run("SET LOCAL min_parallel_table_scan_size = 0;")?;
+        let retval = Spi::get_one::<i64>(
+            "SELECT sum(value) FROM UNNEST((SELECT DEMOARRAY(value) FROM demo_table)) AS value;",
+        );
+        assert_eq!(retval, Ok(Some(5000050000)));
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -36,6 +36,12 @@ pub extern "C" fn _PG_init() {
         .set_argument(42i32.into_datum())
         .enable_spi_access()
         .load();
+
+    BackgroundWorkerBuilder::new("Vacuum Note Launcher")
+        .set_function("vacuum_note_launcher_main")
+        .set_library("bgworker")
+        .enable_spi_access()
+        .load();
 }
 
 #[pg_guard]
@@ -85,3 +91,67 @@ pub extern "C" fn background_worker_main(arg: pg_sys::Datum) {
 
     log!("Goodbye from inside the {} BGWorker! ", BackgroundWorker::get_name());
 }
+
+/*
+    The "vacuum note" launcher shows how to do work in every database of the cluster, in the style
+    of the autovacuum launcher.
+
+    A background worker can only connect to a single database, so the launcher connects to none
+    at all, which still lets it read the shared `pg_database` catalog.  Every minute it starts a
+    dynamic child worker for each database that accepts connections, and waits for it to finish
+    before moving on to the next.
+*/
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn vacuum_note_launcher_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(None, None);
+
+    while BackgroundWorker::wait_latch(Some(Duration::from_secs(60))) {
+        for database in BackgroundWorker::list_databases() {
+            if !database.allow_connections {
+                continue;
+            }
+
+            // the launcher must be the child's notify PID in order to wait for it
+            let child = BackgroundWorkerBuilder::new(&format!("Vacuum Note for {}", database.name))
+                .set_function("vacuum_note_main")
+                .set_library("bgworker")
+                .set_argument(database.oid.into_datum())
+                .enable_spi_access()
+                .set_notify_pid(unsafe { pg_sys::MyProcPid })
+                .load_dynamic();
+
+            match child.wait_for_shutdown() {
+                Ok(()) => log!("vacuum note: finished with database {}", database.name),
+                Err(status) => {
+                    log!("vacuum note: lost track of database {}: {:?}", database.name, status)
+                }
+            }
+        }
+    }
+}
+
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn vacuum_note_main(arg: pg_sys::Datum) {
+    let dboid = unsafe { pg_sys::Oid::from_datum(arg, false) }.expect("no database OID");
+
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi_by_oid(dboid, None);
+
+    let result: Result<_, pgx::spi::Error> = BackgroundWorker::transaction(|| {
+        Spi::get_two::<String, i64>(
+            "SELECT current_database()::text, count(*) FROM pg_stat_user_tables WHERE n_dead_tup > 0",
+        )
+    });
+    match result {
+        Ok((Some(dbname), Some(tables))) => {
+            log!("vacuum note: {} has {} tables with dead tuples", dbname, tables)
+        }
+        Ok(_) => unreachable!("count(*) is never NULL"),
+        Err(e) => panic!("got an error: {}", e),
+    }
+
+    // returning exits the worker with status 0, so Postgres won't restart it
+}
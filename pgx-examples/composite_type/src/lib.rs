@@ -25,15 +25,15 @@ use pgx::{opname, pg_operator, prelude::*, Aggregate};
 // All `pgx` extensions will do this:
 pgx::pg_module_magic!();
 
-/* Composite types must be defined either before they are used.
+/* Composite types must be defined before they are used.
 
-This means composite types must:
-* Be defined prior to loading the extension
-* Be defined by an `extension_sql!()` block which the function has been "ordered before" in
-    the generated SQL
+Every composite type used with `pgx::composite_type!()` must be declared by an `extension_sql!()`
+block with `declares = [Type(name)]`, and functions which use it are ordered after that block in
+the generated SQL. Generating the schema fails if a composite type isn't declared anywhere.
 
-If your extension depends on already existing composite types, then you don't need to do
-anything to define them. Just use them as if they exist.
+If your extension depends on already existing composite types, declare them from an
+`extension_sql!()` block which creates nothing, such as `extension_sql!("", name = "existing_types",
+declares = [Type(Existing)])`.
 
 If your extension defines the composite types itself, it's recommended to do that in an
 `extension_sql!()` which is set to be a `bootstrap`, and is ordered first in the generated SQL:
@@ -50,6 +50,7 @@ CREATE TYPE Cat AS (
     boops INT
 );",
     name = "create_composites",
+    declares = [Type(Dog), Type(Cat)],
     bootstrap
 );
 
@@ -65,6 +66,7 @@ CREATE TYPE CatAndDogFriendship AS (
     dog Dog
 );",
     name = "create_cat_and_dog_friendship",
+    declares = [Type(CatAndDogFriendship)],
 );
 
 // To assist with code reuse, consider setting your composite type names in constants:
@@ -0,0 +1,99 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::config_table::{self, ConfigTable, ConfigTableError};
+use pgx::prelude::*;
+use std::collections::HashMap;
+
+pgx::pg_module_magic!();
+
+/// The settings, as they were when this backend last read the `settings` table
+struct Settings {
+    greeting: String,
+    others: HashMap<String, String>,
+}
+
+pgx::pg_config_table_trigger!(settings_changed);
+
+extension_sql!(
+    r#"
+CREATE TABLE settings (
+    name text NOT NULL PRIMARY KEY,
+    value text NOT NULL
+);
+INSERT INTO settings (name, value) VALUES ('greeting', 'Hello');
+
+CREATE TRIGGER settings_changed
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON settings
+    FOR EACH STATEMENT EXECUTE FUNCTION settings_changed();
+"#,
+    name = "create_settings",
+    requires = [settings_changed]
+);
+
+thread_local! {
+    static SETTINGS: ConfigTable<Settings> = config_table::watch("settings", |rows| {
+        let mut others = rows
+            .map(|row| {
+                let name = row.get_by_name::<String, _>("name")?.unwrap_or_default();
+                Ok((name, row.get_by_name::<String, _>("value")?.unwrap_or_default()))
+            })
+            .collect::<Result<HashMap<_, _>, pgx::spi::Error>>()?;
+        let greeting = others.remove("greeting").unwrap_or_else(|| "Hello".into());
+        Ok(Settings { greeting, others })
+    });
+}
+
+/// Greet `name` the way the `settings` table says to
+#[pg_extern]
+fn greet(name: &str) -> Result<String, ConfigTableError> {
+    let settings = SETTINGS.with(|settings| settings.get())?;
+    Ok(format!("{}, {}", settings.greeting, name))
+}
+
+/// Any other setting
+#[pg_extern]
+fn setting(name: &str) -> Result<Option<String>, ConfigTableError> {
+    Ok(SETTINGS.with(|settings| settings.get())?.others.get(name).cloned())
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_greet() -> Result<(), spi::Error> {
+        assert_eq!(Spi::get_one("SELECT greet('world')")?, Some("Hello, world".to_string()));
+
+        Spi::run("UPDATE settings SET value = 'Goodbye' WHERE name = 'greeting'")?;
+        assert_eq!(Spi::get_one("SELECT greet('world')")?, Some("Goodbye, world".to_string()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_setting() -> Result<(), spi::Error> {
+        assert_eq!(Spi::get_one::<String>("SELECT setting('farewell')")?, None);
+
+        Spi::run("INSERT INTO settings (name, value) VALUES ('farewell', 'Bye')")?;
+        assert_eq!(Spi::get_one("SELECT setting('farewell')")?, Some("Bye".to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod pg_test {
+    pub fn setup(_options: Vec<&str>) {
+        // perform one-off initialization when the pg_test framework starts
+    }
+
+    pub fn postgresql_conf_options() -> Vec<&'static str> {
+        // return any postgresql.conf settings that are required for your tests
+        vec![]
+    }
+}
@@ -26,6 +26,9 @@ use serde::{Deserialize, Serialize};
 /// automatically generate a "_hash" function, and the necessary "opclass" (and family)
 /// so the type can also be used in indexes `USING hash`
 #[derive(PostgresHash)]
+
+/// the functions behind those operators only compare Strings, so they're safe to run in parallel
+#[pgx(parallel_safe)]
 pub struct Thing(String);
 
 // and there's no code to write!
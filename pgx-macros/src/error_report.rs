@@ -0,0 +1,222 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, LitStr, Token};
+
+pub(crate) fn impl_error_reportable(ast: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let container = ErrorReportArgs::from_attrs(&ast.attrs)?;
+    let default_code = container
+        .code
+        .clone()
+        .unwrap_or_else(|| Ident::new("ERRCODE_DATA_EXCEPTION", Span::call_site()));
+
+    let arms = match &ast.data {
+        Data::Struct(data) => vec![Arm::new(quote! { Self }, &data.fields, container)],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                let args = ErrorReportArgs::from_attrs(&variant.attrs)?;
+                Ok(Arm::new(quote! { Self::#ident }, &variant.fields, args))
+            })
+            .collect::<syn::Result<Vec<_>>>()?,
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                name.span(),
+                "#[derive(ErrorReportable)] can only be applied to enums and structs",
+            ))
+        }
+    };
+
+    let code_arms = arms.iter().map(|arm| {
+        let pattern = &arm.pattern;
+        let code = arm.args.code.as_ref().unwrap_or(&default_code);
+        quote! { #pattern => ::pgx::pg_sys::errcodes::PgSqlErrorCode::#code }
+    });
+    let message_arms = arms.iter().map(|arm| {
+        let pattern = &arm.pattern;
+        match &arm.args.message {
+            Some(message) => {
+                let message = arm.format(message);
+                quote! { #pattern => #message }
+            }
+            None => quote! { #pattern => ::std::string::ToString::to_string(self) },
+        }
+    });
+    let detail_arms = arms.iter().map(|arm| arm.optional_arm(arm.args.detail.as_ref()));
+    let hint_arms = arms.iter().map(|arm| arm.optional_arm(arm.args.hint.as_ref()));
+
+    Ok(quote! {
+        #[allow(unused_variables)]
+        impl #impl_generics ::pgx::error_report::ErrorReportable for #name #ty_generics #where_clause {
+            fn sql_error_code(&self) -> ::pgx::pg_sys::errcodes::PgSqlErrorCode {
+                match self {
+                    #(#code_arms,)*
+                }
+            }
+
+            fn message(&self) -> ::std::string::String {
+                match self {
+                    #(#message_arms,)*
+                }
+            }
+
+            fn detail(&self) -> ::std::option::Option<::std::string::String> {
+                match self {
+                    #(#detail_arms,)*
+                }
+            }
+
+            fn hint(&self) -> ::std::option::Option<::std::string::String> {
+                match self {
+                    #(#hint_arms,)*
+                }
+            }
+        }
+    })
+}
+
+/// How to report one variant of an enum, or a struct
+struct Arm {
+    /// Binds every field, by name or as `_0`, `_1`, etc
+    pattern: TokenStream2,
+    bindings: Vec<Ident>,
+    args: ErrorReportArgs,
+}
+
+impl Arm {
+    fn new(path: TokenStream2, fields: &Fields, args: ErrorReportArgs) -> Self {
+        let bindings = fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| match &field.ident {
+                Some(ident) => ident.clone(),
+                None => Ident::new(&format!("_{index}"), Span::call_site()),
+            })
+            .collect::<Vec<_>>();
+        let pattern = match fields {
+            Fields::Named(_) => quote! { #path { #(#bindings),* } },
+            Fields::Unnamed(_) => quote! { #path ( #(#bindings),* ) },
+            Fields::Unit => quote! { #path },
+        };
+        Self { pattern, bindings, args }
+    }
+
+    /// Formats `template` with whichever of this arm's bindings it names
+    fn format(&self, template: &LitStr) -> TokenStream2 {
+        let used = format_argument_names(&template.value());
+        let names = self
+            .bindings
+            .iter()
+            .filter(|binding| used.contains(&binding.to_string()))
+            .collect::<Vec<_>>();
+        let values = names.clone();
+        quote! { ::std::format!(#template, #(#names = #values),*) }
+    }
+
+    fn optional_arm(&self, template: Option<&LitStr>) -> TokenStream2 {
+        let pattern = &self.pattern;
+        match template {
+            Some(template) => {
+                let formatted = self.format(template);
+                quote! { #pattern => ::std::option::Option::Some(#formatted) }
+            }
+            None => quote! { #pattern => ::std::option::Option::None },
+        }
+    }
+}
+
+/// The names used by `{name}` and `{name:...}` arguments of a format string
+fn format_argument_names(template: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '{' {
+            continue;
+        }
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            continue;
+        }
+        let name = chars
+            .by_ref()
+            .take_while(|&c| c != '}')
+            .collect::<String>()
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// The arguments of `#[error_report(...)]`
+#[derive(Default)]
+struct ErrorReportArgs {
+    code: Option<Ident>,
+    message: Option<LitStr>,
+    detail: Option<LitStr>,
+    hint: Option<LitStr>,
+}
+
+impl ErrorReportArgs {
+    fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut args = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("error_report")) {
+            let parsed =
+                attr.parse_args_with(Punctuated::<ErrorReportArg, Token![,]>::parse_terminated)?;
+            for arg in parsed {
+                match arg {
+                    ErrorReportArg::Code(code) => args.code = Some(code),
+                    ErrorReportArg::Message(message) => args.message = Some(message),
+                    ErrorReportArg::Detail(detail) => args.detail = Some(detail),
+                    ErrorReportArg::Hint(hint) => args.hint = Some(hint),
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+enum ErrorReportArg {
+    Code(Ident),
+    Message(LitStr),
+    Detail(LitStr),
+    Hint(LitStr),
+}
+
+impl Parse for ErrorReportArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        let _eq: Token![=] = input.parse()?;
+        let found = match ident.to_string().as_str() {
+            "code" => Self::Code(input.parse()?),
+            "message" => Self::Message(input.parse()?),
+            "detail" => Self::Detail(input.parse()?),
+            "hint" => Self::Hint(input.parse()?),
+            other => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    &format!("Unknown error_report attribute: {}", other),
+                ))
+            }
+        };
+        Ok(found)
+    }
+}
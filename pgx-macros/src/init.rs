@@ -0,0 +1,71 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Expr, Ident, ItemFn, ReturnType, Token};
+
+/// The arguments of `#[pg_init(order = ..)]` and `#[pg_fini(order = ..)]`
+pub(crate) struct InitArgs {
+    order: Option<Expr>,
+}
+
+impl Parse for InitArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut order = None;
+        if !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key != "order" {
+                return Err(syn::Error::new(key.span(), "expected `order = <i32>`"));
+            }
+            input.parse::<Token![=]>()?;
+            order = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(InitArgs { order })
+    }
+}
+
+/// Registers `func` with `register`, one of `pgx::init::__register_init` or `__register_fini`
+pub(crate) fn impl_pg_init(
+    args: InitArgs,
+    func: ItemFn,
+    attribute: &str,
+    register: TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let sig = &func.sig;
+    if sig.abi.is_some()
+        || sig.unsafety.is_some()
+        || sig.asyncness.is_some()
+        || !sig.generics.params.is_empty()
+        || !sig.inputs.is_empty()
+        || !matches!(sig.output, ReturnType::Default)
+    {
+        return Err(syn::Error::new(
+            sig.span(),
+            format!(
+                "#[{}] functions must be plain `fn name()`, without arguments or a return type",
+                attribute
+            ),
+        ));
+    }
+
+    let name = &sig.ident;
+    let order = args.order.map(|order| quote! { #order }).unwrap_or_else(|| quote! { 0 });
+    Ok(quote! {
+        #func
+
+        ::pgx::__pgx_register_on_load!(#register(
+            #order,
+            concat!(module_path!(), "::", stringify!(#name)),
+            #name
+        ));
+    })
+}
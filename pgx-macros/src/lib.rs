@@ -15,18 +15,27 @@ use std::collections::HashSet;
 use proc_macro2::Ident;
 use quote::{quote, ToTokens};
 use syn::spanned::Spanned;
-use syn::{parse_macro_input, Attribute, Data, DeriveInput, Item, ItemImpl};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, Item, ItemImpl};
 
-use operators::{impl_postgres_eq, impl_postgres_hash, impl_postgres_ord};
+use error_report::impl_error_reportable;
+use init::{impl_pg_init, InitArgs};
+use operators::{
+    impl_pg_composite_ops, impl_postgres_eq, impl_postgres_hash, impl_postgres_ord, CompositeOps,
+};
 use pgx_sql_entity_graph::{
     parse_extern_attributes, CodeEnrichment, ExtensionSql, ExtensionSqlFile, ExternArgs,
-    PgAggregate, PgExtern, PostgresEnum, PostgresType, Schema,
+    PgAggregate, PgCast, PgConstant, PgExportAbi, PgExtern, PgGinOpclass, PgNotifyChannel,
+    PgPolicy, PgView, PostgresDomain, PostgresEnum, PostgresType, Schema,
 };
 
 use crate::rewriter::PgGuardRewriter;
+use table_row::impl_pg_table_row;
 
+mod error_report;
+mod init;
 mod operators;
 mod rewriter;
+mod table_row;
 
 /// Declare a function as `#[pg_guard]` to indicate that it is called from a Postgres `extern "C"`
 /// function so that Rust `panic!()`s (and Postgres `elog(ERROR)`s) will be properly handled by `pgx`
@@ -52,11 +61,112 @@ pub fn pg_guard(_attr: TokenStream, item: TokenStream) -> TokenStream {
     res.unwrap_or_else(|e| e.into_compile_error()).into()
 }
 
+/// Run a function when Postgres loads the extension, from the `_PG_init()` that
+/// `pg_module_magic!()` defines.
+///
+/// Any number of `#[pg_init]` functions can be declared, in any module.  They run in order of their
+/// optional `order = <i32>` argument, which defaults to `0`, and then of their full path, so the
+/// order is the same on every load.  A `_PG_init()` written by hand with `#[pg_guard]` runs
+/// before all of them.
+///
+/// ```rust,ignore
+/// use pgx::prelude::*;
+///
+/// static COUNTER: PgLwLock<i64> = PgLwLock::new();
+///
+/// #[pg_init]
+/// fn init_shmem() {
+///     if pgx::is_preloaded() {
+///         pg_shmem_init!(COUNTER);
+///     }
+/// }
+///
+/// #[pg_init(order = 10)]
+/// fn init_workers() {
+///     // runs after `init_shmem()`
+/// }
+/// ```
+///
+/// The function must be a plain `fn` without arguments.  It's called inside a `#[pg_guard]`, so it
+/// can `panic!()` or raise an `ERROR`.  Use `pgx::is_preloaded()` to tell whether the extension
+/// is being loaded through `shared_preload_libraries`.
+#[proc_macro_attribute]
+pub fn pg_init(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as InitArgs);
+    let func = parse_macro_input!(item as syn::ItemFn);
+    impl_pg_init(args, func, "pg_init", quote! { ::pgx::init::__register_init })
+        .unwrap_or_else(|e| e.into_compile_error())
+        .into()
+}
+
+/// Run a function from the `_PG_fini()` that `pg_module_magic!()` defines.
+///
+/// `#[pg_fini]` functions take the same `order = <i32>` argument as [`macro@pg_init`], and run
+/// in the reverse order:  the highest `order` first.  A `_PG_fini()` written by hand with
+/// `#[pg_guard]` runs after all of them.
+///
+/// Note that Postgres doesn't unload extension libraries, so on most versions `_PG_fini()`, and
+/// so these functions, are never called.  They're no substitute for cleaning up at the end of a
+/// transaction or when the backend exits.
+#[proc_macro_attribute]
+pub fn pg_fini(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as InitArgs);
+    let func = parse_macro_input!(item as syn::ItemFn);
+    impl_pg_init(args, func, "pg_fini", quote! { ::pgx::init::__register_fini })
+        .unwrap_or_else(|e| e.into_compile_error())
+        .into()
+}
+
 /// `#[pg_test]` functions are test functions (akin to `#[test]`), but they run in-process inside
 /// Postgres during `cargo pgx test`.
+///
+/// A test fails if it panics, raises an `ERROR`, or returns an `Err`, whose `Display` text is the
+/// failure message.  Return a [`pgx::testing::TestResult`] to use `?` on both `pgx::spi::Error`
+/// and your own error types.  Use `#[pg_test(error = "...")]` for a test that should fail with
+/// that message.
+///
+/// A test can also take a [`&pgx::testing::TestDb`] fixture, which creates a scratch schema for
+/// it and has helpers to run SQL:
+///
+/// ```rust,ignore
+/// use pgx::prelude::*;
+/// use pgx::testing::{TestDb, TestResult};
+///
+/// #[pg_test]
+/// fn test_scratch_table(db: &TestDb) -> TestResult {
+///     db.execute("CREATE TABLE t (x int)")?;
+///     assert_eq!(db.query_one::<i64>("SELECT count(*) FROM t")?, Some(0));
+///     Ok(())
+/// }
+/// ```
+///
+/// A test that depends on the time or on random numbers can be made repeatable:
+///
+/// - `freeze_time = "2023-01-01T00:00:00Z"` makes `pgx::clock`'s functions return that time for
+///   the whole test.  Add `shadow_now` to also make the SQL functions `now()`,
+///   `transaction_timestamp()`, `statement_timestamp()` and `clock_timestamp()` return it, which
+///   is done by putting a schema with functions of those names first in the `search_path`.
+/// - `seed = 42` seeds `random()` and `pgx::random::pg_random()` before the test runs.
+///
+/// ```rust,ignore
+/// #[pg_test(freeze_time = "2023-01-01T12:00:00Z", shadow_now, seed = 42)]
+/// fn test_daily_sample() -> Result<(), pgx::spi::Error> {
+///     let sample = Spi::get_one::<String>("SELECT now()::date || ': ' || random()")?;
+///     assert!(sample.unwrap().starts_with("2023-01-01: "));
+///     Ok(())
+/// }
+/// ```
+///
+/// Neither can reach what Postgres computes for itself, such as `CURRENT_TIMESTAMP`, a
+/// schema-qualified `pg_catalog.now()`, commit timestamps, or `std::time` and direct `pg_sys` calls.
+/// See [`pgx::testing::freeze_time()`] for the details.
 #[proc_macro_attribute]
 pub fn pg_test(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut stream = proc_macro2::TokenStream::new();
+    let (attr, determinism) = match take_test_determinism(attr.into()) {
+        Ok((attr, determinism)) => (TokenStream::from(attr), determinism),
+        Err(e) => return e.into_compile_error().into(),
+    };
     let args = parse_extern_attributes(proc_macro2::TokenStream::from(attr.clone()));
 
     let mut expected_error = None;
@@ -90,11 +200,50 @@ pub fn pg_test(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
 
             func.attrs = non_test_attributes;
-
-            stream.extend(proc_macro2::TokenStream::from(pg_extern(
-                attr,
-                Item::Fn(func.clone()).to_token_stream().into(),
-            )));
+            func.block.stmts.splice(0..0, determinism.statements());
+
+            match func.sig.inputs.len() {
+                0 => {
+                    stream.extend(proc_macro2::TokenStream::from(pg_extern(
+                        attr,
+                        Item::Fn(func.clone()).to_token_stream().into(),
+                    )));
+                }
+                1 if is_test_db_fixture(func.sig.inputs.first().unwrap()) => {
+                    // the test itself stays a plain function, and Postgres calls a wrapper by
+                    // the test's name, which runs it with a new `TestDb`
+                    let test_name = &func.sig.ident;
+                    let test_name_str = test_name.to_string();
+                    let wrapper_name =
+                        Ident::new(&format!("__pgx_test_db_{}", test_name), func.span());
+                    let output = &func.sig.output;
+                    let wrapper_attr: proc_macro2::TokenStream = attr.clone().into();
+                    let wrapper_attr = if wrapper_attr.is_empty() {
+                        quote! { name = #test_name_str }
+                    } else {
+                        quote! { #wrapper_attr, name = #test_name_str }
+                    };
+                    let wrapper = quote! {
+                        fn #wrapper_name() #output {
+                            ::pgx::testing::TestDb::with(#test_name_str, #test_name)
+                        }
+                    };
+
+                    stream.extend(func.to_token_stream());
+                    stream.extend(proc_macro2::TokenStream::from(pg_extern(
+                        wrapper_attr.into(),
+                        wrapper.into(),
+                    )));
+                }
+                _ => {
+                    return syn::Error::new(
+                        func.sig.inputs.span(),
+                        "#[pg_test] functions can't take arguments, other than a `&TestDb` fixture",
+                    )
+                    .into_compile_error()
+                    .into();
+                }
+            }
 
             let expected_error = match expected_error {
                 Some(msg) => quote! {Some(#msg)},
@@ -145,6 +294,108 @@ pub fn pg_test(attr: TokenStream, item: TokenStream) -> TokenStream {
     stream.into()
 }
 
+/// The `#[pg_test]` options which make a test repeatable, which `#[pg_extern]` doesn't know
+#[derive(Default)]
+struct TestDeterminism {
+    freeze_time: Option<syn::LitStr>,
+    shadow_now: bool,
+    seed: Option<syn::LitInt>,
+}
+
+impl TestDeterminism {
+    /// The statements the test starts with.  The frozen time's guard lives until the test returns.
+    fn statements(&self) -> Vec<syn::Stmt> {
+        let mut stmts: Vec<syn::Stmt> = Vec::new();
+        if let Some(at) = &self.freeze_time {
+            stmts.push(if self.shadow_now {
+                syn::parse_quote! { let __pgx_frozen_time = ::pgx::testing::freeze_sql_time(#at); }
+            } else {
+                syn::parse_quote! { let __pgx_frozen_time = ::pgx::testing::freeze_time(#at); }
+            });
+        }
+        if let Some(seed) = &self.seed {
+            stmts.push(syn::parse_quote! { ::pgx::testing::seed_random(#seed); });
+        }
+        stmts
+    }
+}
+
+/// Split the `freeze_time`, `shadow_now` and `seed` options out of a `#[pg_test]`'s, returning
+/// the rest, for `#[pg_extern]`
+fn take_test_determinism(
+    attr: proc_macro2::TokenStream,
+) -> syn::Result<(proc_macro2::TokenStream, TestDeterminism)> {
+    use proc_macro2::TokenTree;
+
+    let mut options: Vec<Vec<TokenTree>> = vec![Vec::new()];
+    for token in attr {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == ',' => options.push(Vec::new()),
+            token => options.last_mut().unwrap().push(token),
+        }
+    }
+
+    let mut determinism = TestDeterminism::default();
+    let mut rest = Vec::new();
+    for option in options.into_iter().filter(|option| !option.is_empty()) {
+        let name = match option.first() {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            _ => String::new(),
+        };
+        let tokens: proc_macro2::TokenStream = option.iter().cloned().collect();
+        match name.as_str() {
+            "freeze_time" => {
+                let nv = syn::parse2::<syn::MetaNameValue>(tokens)?;
+                match nv.lit {
+                    syn::Lit::Str(at) => determinism.freeze_time = Some(at),
+                    other => {
+                        return Err(syn::Error::new(
+                            other.span(),
+                            "expected `freeze_time = \"2023-01-01T00:00:00Z\"`",
+                        ))
+                    }
+                }
+            }
+            "seed" => {
+                let nv = syn::parse2::<syn::MetaNameValue>(tokens)?;
+                match nv.lit {
+                    syn::Lit::Int(seed) => determinism.seed = Some(seed),
+                    other => {
+                        return Err(syn::Error::new(other.span(), "expected `seed = <integer>`"))
+                    }
+                }
+            }
+            "shadow_now" if option.len() == 1 => determinism.shadow_now = true,
+            _ => rest.push(tokens),
+        }
+    }
+    if determinism.shadow_now && determinism.freeze_time.is_none() {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`shadow_now` needs a `freeze_time = \"..\"` to shadow `now()` with",
+        ));
+    }
+    Ok((quote! { #(#rest),* }, determinism))
+}
+
+/// Is `arg` a `&TestDb`, or a reference to some path ending in `TestDb`?
+fn is_test_db_fixture(arg: &syn::FnArg) -> bool {
+    match arg {
+        syn::FnArg::Typed(pat_type) => match &*pat_type.ty {
+            syn::Type::Reference(reference) if reference.mutability.is_none() => {
+                match &*reference.elem {
+                    syn::Type::Path(path) => {
+                        path.path.segments.last().map_or(false, |segment| segment.ident == "TestDb")
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        },
+        syn::FnArg::Receiver(_) => false,
+    }
+}
+
 /// Associated macro for `#[pg_test]` to provide context back to your test framework to indicate
 /// that the test system is being initialized
 #[proc_macro_attribute]
@@ -201,6 +452,103 @@ pub fn merges(_attr: TokenStream, item: TokenStream) -> TokenStream {
     item
 }
 
+/**
+Declare a [cast](https://www.postgresql.org/docs/current/sql-createcast.html) from the type of a
+`#[pg_extern]` function's one argument to the type it returns.
+
+The cast is part of the schema graph, so it's created after the function and both types, and
+`cargo pgx schema` fails if the function isn't a `#[pg_extern]`.  A function which doesn't take
+exactly one argument, or doesn't return a value, is a compile error.
+
+Options:
+
+* `implicit`: the cast may be applied in any context, `AS IMPLICIT`
+* `assignment`: the cast may be applied when assigning to a column, `AS ASSIGNMENT`
+* `binary`: the types are binary-coercible, so the cast is created `WITHOUT FUNCTION`.  The
+  function only names the types, and isn't a `#[pg_extern]`
+
+Without `implicit` or `assignment`, the cast is only applied when it's asked for, as with
+`value::jsonb`.
+
+```rust,ignore
+use pgx::prelude::*;
+
+#[pg_extern(immutable, parallel_safe)]
+#[pg_cast(implicit)]
+fn temperature_to_jsonb(value: Temperature) -> JsonB {
+    todo!()
+}
+
+#[pg_cast(binary, assignment)]
+fn label_as_text(label: Label) -> String {
+    label.0
+}
+```
+*/
+#[proc_macro_attribute]
+pub fn pg_cast(attr: TokenStream, item: TokenStream) -> TokenStream {
+    fn wrapped(attr: TokenStream, item: TokenStream) -> Result<TokenStream, syn::Error> {
+        let pg_cast_item = PgCast::new(attr.into(), item.into())?;
+        Ok(pg_cast_item.to_token_stream().into())
+    }
+
+    wrapped(attr, item).unwrap_or_else(|e| e.into_compile_error().into())
+}
+
+/**
+Create a default [GIN](https://www.postgresql.org/docs/current/gin.html) operator class for a type,
+from its `impl pgx::gin::GinIndexable`.
+
+The arguments name the `#[pg_operator]` functions the index answers, by their strategy number,
+which `GinIndexable::extract_query` and `GinIndexable::consistent` are given.  Each takes the type
+on its left and the `GinIndexable::Query` on its right.
+
+It creates the `extern "C"` support functions, named `{type}_gin_compare`, `{type}_gin_extract_value`
+and so on, which convert the `internal` arguments Postgres calls them with, and the operator class
+`{type}_gin_ops` which uses them, with the `GinIndexable::Key` type as its `STORAGE`.  The class is
+part of the schema graph, so it's created after the operators and both types, and `cargo pgx
+schema` fails if an operator isn't a `#[pg_operator]` on the type.
+
+A class whose queries match keys partially, with `GinQuery::partial`, also takes `partial_match`,
+such as `#[pg_gin_opclass(1 = tags_contain, partial_match)]`.  It adds the `{type}_gin_compare_partial`
+support function, from the type's `impl pgx::gin::GinPartialMatch`, which then has to exist.
+
+```rust,ignore
+use pgx::gin::{GinConsistent, GinIndexable, GinQuery};
+use pgx::prelude::*;
+
+#[pg_gin_opclass(1 = tags_contain, 2 = tags_overlap)]
+impl GinIndexable for Tags {
+    type Key = String;
+    type Query = Vec<String>;
+
+    fn extract_value(self) -> Vec<String> {
+        self.0
+    }
+
+    fn extract_query(tags: Vec<String>, _strategy: u16) -> GinQuery<String> {
+        GinQuery::new(tags)
+    }
+
+    fn consistent(strategy: u16, check: &[bool]) -> GinConsistent {
+        match strategy {
+            1 => check.iter().all(|&has| has).into(),
+            _ => check.iter().any(|&has| has).into(),
+        }
+    }
+}
+```
+*/
+#[proc_macro_attribute]
+pub fn pg_gin_opclass(attr: TokenStream, item: TokenStream) -> TokenStream {
+    fn wrapped(attr: TokenStream, item: TokenStream) -> Result<TokenStream, syn::Error> {
+        let pg_gin_opclass_item = PgGinOpclass::new(attr.into(), item.into())?;
+        Ok(pg_gin_opclass_item.to_token_stream().into())
+    }
+
+    wrapped(attr, item).unwrap_or_else(|e| e.into_compile_error().into())
+}
+
 /**
 Declare a Rust module and its contents to be in a schema.
 
@@ -227,15 +575,20 @@ mod dsl {
 
 File modules (like `mod name;`) aren't able to be supported due to [`rust/#54725`](https://github.com/rust-lang/rust/issues/54725).
 
+`#[pg_schema(owner = "role", grant = "role")]` follows the schema's creation with `ALTER SCHEMA ..
+OWNER TO` and `GRANT USAGE ON SCHEMA` statements, like [`#[pg_extern]`](macro@pg_extern)'s options
+of the same names.
+
 */
 #[proc_macro_attribute]
-pub fn pg_schema(_attr: TokenStream, input: TokenStream) -> TokenStream {
-    fn wrapped(input: TokenStream) -> Result<TokenStream, syn::Error> {
-        let pgx_schema: Schema = syn::parse(input)?;
+pub fn pg_schema(attr: TokenStream, input: TokenStream) -> TokenStream {
+    fn wrapped(attr: TokenStream, input: TokenStream) -> Result<TokenStream, syn::Error> {
+        let mut pgx_schema: Schema = syn::parse(input)?;
+        pgx_schema.privileges = syn::parse(attr)?;
         Ok(pgx_schema.to_token_stream().into())
     }
 
-    match wrapped(input) {
+    match wrapped(attr, input) {
         Ok(tokens) => tokens,
         Err(e) => {
             let msg = e.to_string();
@@ -255,8 +608,21 @@ Accepts a String literal, a `name` attribute, and optionally others:
 * `requires = [item, item_two]`: References to other `name`s or Rust items which this SQL should be present after.
 * `creates = [ Type(submod::Cust), Enum(Pre), Function(defined)]`: Communicates that this SQL block creates certain entities.
   Please note it **does not** create matching Rust types.
+* `declares = [Type(dog), Table(kennel), Function(walk)]`: Names SQL objects this block creates which
+  have no Rust counterpart.  Functions using `composite_type!("dog")`, or the row type of a declared
+  table, are ordered after this block, and every `composite_type!()` other than `"record"` must be
+  declared by some block.
+* `infer_requires`: Also require the functions, types, and declared objects the SQL uses by name, like
+  a function called in a column's `DEFAULT`.  Comments, string literals, and dollar-quoted function
+  bodies are skipped.
+* `config_dump = [settings, "myext.rules" = "WHERE NOT builtin"]`: Has `pg_dump` dump the rows of these
+  tables, which it otherwise leaves out as the extension creates them, by calling
+  `pg_extension_config_dump()` after this block's SQL.  A `WHERE` clause after `=` dumps only the rows
+  it matches.  Each table must also be in the block's `declares = [Table(..)]`.
 * `bootstrap` (**Unique**): Communicates that this is SQL intended to go before all other generated SQL.
 * `finalize` (**Unique**): Communicates that this is SQL intended to go after all other generated SQL.
+* `pg_version = "14.."`: Only generate this SQL for the Postgres major versions in the range, such as
+  `"14.."`, `"..15"`, `"13..=14"`, or `"15"`.  Otherwise it's left out as if it didn't exist.
 
 You can declare some SQL without any positioning information, meaning it can end up anywhere in the generated SQL:
 
@@ -326,6 +692,40 @@ extension_sql!(r#"
 );
 ```
 
+To require whatever the SQL uses that the extension defines, rather than listing it:
+
+```rust,ignore
+use pgx::prelude::*;
+
+#[pg_extern(stable)]
+fn next_order_id() -> i64 {
+    Spi::get_one("SELECT nextval('order_ids')").unwrap().unwrap()
+}
+
+#[pg_extern(immutable)]
+fn with_tax(subtotal: f64) -> f64 {
+    subtotal * 1.2
+}
+
+extension_sql!(r#"
+    CREATE SEQUENCE order_ids;
+    CREATE TABLE orders (
+        id bigint DEFAULT next_order_id(),
+        subtotal float8,
+        -- only the call is a requirement, not this comment's with_tax()
+        total float8 GENERATED ALWAYS AS (@extschema@.with_tax(subtotal)) STORED
+    );
+    "#,
+    name = "orders",
+    infer_requires,
+);
+```
+
+`cargo pgx schema` warns about a call qualified by one of the extension's schemas to a function it
+can't find, or one using a function's Rust name where its SQL name is different, since those are
+likely mistakes.  Names are only compared, so anything needed which isn't named in the SQL still has
+to be in `requires`.
+
 To declare the SQL defines some entity (**Caution:** This is not recommended usage):
 
 ```rust,ignore
@@ -369,6 +769,44 @@ extension_sql!(r#"\
     requires = ["create_complex_type", complex_in, complex_out],
 );
 
+```
+
+To keep the rows users add to a table the extension creates when the database is dumped and restored,
+but not the ones `CREATE EXTENSION` adds again:
+
+```rust,ignore
+use pgx_macros::extension_sql;
+
+extension_sql!(r#"
+    CREATE TABLE rules (pattern text PRIMARY KEY, builtin bool NOT NULL DEFAULT false);
+    INSERT INTO rules VALUES ('%.tmp', true);
+    "#,
+    name = "rules",
+    declares = [Table(rules)],
+    config_dump = [rules = "WHERE NOT builtin"],
+);
+```
+
+To generate different SQL for different Postgres versions, give blocks the same `name` but
+non-overlapping `pg_version` ranges, so whatever `requires` that name finds the one for the version
+`cargo pgx schema` is generating the schema for:
+
+```rust,ignore
+use pgx_macros::extension_sql;
+
+extension_sql!(r#"
+    CREATE STATISTICS orders_stats (dependencies) ON customer_id, region FROM orders;
+    "#,
+    name = "orders_stats",
+    pg_version = "..14",
+);
+
+extension_sql!(r#"
+    CREATE STATISTICS orders_stats (dependencies) ON customer_id, lower(region) FROM orders;
+    "#,
+    name = "orders_stats",
+    pg_version = "14..",
+);
 ```
 */
 #[proc_macro]
@@ -389,6 +827,193 @@ pub fn extension_sql(input: TokenStream) -> TokenStream {
     }
 }
 
+/**
+Declare a [row-level security policy](https://www.postgresql.org/docs/current/sql-createpolicy.html)
+whose expressions call `#[pg_extern]` functions.
+
+The functions are part of the schema graph, so the policy is always created after them, and
+`cargo pgx schema` fails if a function can't be found, doesn't return `bool`, or is given the wrong
+number of arguments.
+
+Options:
+
+* `name = "..."` (required): the name of the policy
+* `table = "..."` (required): the table the policy applies to, used verbatim as SQL
+* `command = all | select | insert | update | delete`: the command the policy applies to, `all`
+  if omitted
+* `restrictive`: create a restrictive, rather than permissive, policy
+* `to = ["role", ...]`: the roles the policy applies to, used verbatim as SQL
+* `using = function(args...)`: the `USING` expression
+* `with_check = function(args...)`: the `WITH CHECK` expression
+* `requires = [...]`: other items the policy requires, as with [`macro@extension_sql`], such as
+  the `extension_sql!()` that creates the table
+
+The arguments of `using` and `with_check` are identifiers, such as column names, or string
+literals of SQL, which are used verbatim.
+
+```rust,ignore
+use pgx::prelude::*;
+
+extension_sql!(
+    r#"
+    CREATE TABLE tenant_data (tenant_id int, payload text);
+    ALTER TABLE tenant_data ENABLE ROW LEVEL SECURITY;
+    "#,
+    name = "tenant_data",
+);
+
+#[pg_extern(stable)]
+fn tenant_visible(tenant_id: i32) -> bool {
+    todo!()
+}
+
+pg_policy!(
+    name = "tenant_isolation",
+    table = "tenant_data",
+    command = select,
+    to = ["PUBLIC"],
+    using = tenant_visible(tenant_id),
+    requires = ["tenant_data"],
+);
+```
+*/
+#[proc_macro]
+pub fn pg_policy(input: TokenStream) -> TokenStream {
+    fn wrapped(input: TokenStream) -> Result<TokenStream, syn::Error> {
+        let policy: CodeEnrichment<PgPolicy> = syn::parse(input)?;
+        Ok(policy.to_token_stream().into())
+    }
+
+    match wrapped(input) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let msg = e.to_string();
+            TokenStream::from(quote! {
+              compile_error!(#msg);
+            })
+        }
+    }
+}
+
+/**
+Declare a [`pgx::notify::Channel`](../pgx/notify/struct.Channel.html), and document it in the
+extension's schema, so a DBA can find out what it sends.
+
+The schema gets a comment naming the channel and its payload's type, followed by the channel's
+doc comment:
+
+```sql
+-- NOTIFY channel "orders"
+--   payload: Order as JSON
+--   New and updated orders
+```
+
+```rust,ignore
+use pgx::notify::Channel;
+use pgx::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Order {
+    id: i64,
+}
+
+pg_notify_channel!(
+    /// New and updated orders
+    pub static ORDERS: Channel<Order> = "orders";
+);
+```
+*/
+#[proc_macro]
+pub fn pg_notify_channel(input: TokenStream) -> TokenStream {
+    fn wrapped(input: TokenStream) -> Result<TokenStream, syn::Error> {
+        let channel: CodeEnrichment<PgNotifyChannel> = syn::parse(input)?;
+        Ok(channel.to_token_stream().into())
+    }
+
+    match wrapped(input) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let msg = e.to_string();
+            TokenStream::from(quote! {
+              compile_error!(#msg);
+            })
+        }
+    }
+}
+
+/**
+Declare a view over the extension's functions, types and other views, created in the schema of
+the module it's declared in.
+
+The view comes after whatever its query is found to use, like an
+[`macro@extension_sql`] block with `infer_requires`.  Anything else it needs can be listed in
+`requires = [..]`.
+
+```rust,ignore
+use pgx::prelude::*;
+
+#[pg_extern]
+fn dogs() -> TableIterator<'static, (name!(name, String), name!(good, bool))> {
+    TableIterator::new(vec![("Brandy".into(), true)].into_iter())
+}
+
+pg_view!("good_dogs", "SELECT name FROM dogs() WHERE good");
+```
+*/
+#[proc_macro]
+pub fn pg_view(input: TokenStream) -> TokenStream {
+    fn wrapped(input: TokenStream) -> Result<TokenStream, syn::Error> {
+        let view: CodeEnrichment<PgView> = syn::parse(input)?;
+        Ok(view.to_token_stream().into())
+    }
+
+    match wrapped(input) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let msg = e.to_string();
+            TokenStream::from(quote! {
+              compile_error!(#msg);
+            })
+        }
+    }
+}
+
+/**
+Declare a Rust `const` that SQL can read too, through an `IMMUTABLE` function of no arguments named
+after it in lower case, so the two can't disagree.
+
+```rust,ignore
+use pgx::prelude::*;
+
+pg_constant!(
+    /// How deep a tree may be
+    pub MAX_DEPTH: i32 = 16;
+);
+```
+
+```sql
+SELECT max_depth();
+```
+*/
+#[proc_macro]
+pub fn pg_constant(input: TokenStream) -> TokenStream {
+    fn wrapped(input: TokenStream) -> Result<TokenStream, syn::Error> {
+        let constant: CodeEnrichment<PgConstant> = syn::parse(input)?;
+        Ok(constant.to_token_stream().into())
+    }
+
+    match wrapped(input) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let msg = e.to_string();
+            TokenStream::from(quote! {
+              compile_error!(#msg);
+            })
+        }
+    }
+}
+
 /**
 Declare SQL (from a file) to be included in generated extension script.
 
@@ -436,6 +1061,8 @@ pub fn extension_sql_file(input: TokenStream) -> TokenStream {
 
 /// Associated macro for `#[pg_extern]` or `#[macro@pg_operator]`.  Used to set the `SEARCH_PATH` option
 /// on the `CREATE FUNCTION` statement.
+///
+/// It's shorthand for `#[pg_extern(set = "search_path = ..")]`, except the paths aren't quoted.
 #[proc_macro_attribute]
 pub fn search_path(_attr: TokenStream, item: TokenStream) -> TokenStream {
     item
@@ -449,15 +1076,63 @@ Optionally accepts the following attributes:
 * `immutable`: Corresponds to [`IMMUTABLE`](https://www.postgresql.org/docs/current/sql-createfunction.html).
 * `strict`: Corresponds to [`STRICT`](https://www.postgresql.org/docs/current/sql-createfunction.html).
   + In most cases, `#[pg_extern]` can detect when no `Option<T>`s are used, and automatically set this.
+* `called_on_null_input`: Corresponds to [`CALLED ON NULL INPUT`](https://www.postgresql.org/docs/current/sql-createfunction.html).
+  + Stops `#[pg_extern]` from setting `strict` automatically.  A `NULL` passed for an argument that isn't an `Option<T>` raises an error.
 * `stable`: Corresponds to [`STABLE`](https://www.postgresql.org/docs/current/sql-createfunction.html).
 * `volatile`: Corresponds to [`VOLATILE`](https://www.postgresql.org/docs/current/sql-createfunction.html).
 * `raw`: Corresponds to [`RAW`](https://www.postgresql.org/docs/current/sql-createfunction.html).
 * `parallel_safe`: Corresponds to [`PARALLEL SAFE`](https://www.postgresql.org/docs/current/sql-createfunction.html).
+  + With debug assertions, running an SPI query that isn't allowed in a parallel worker raises an error naming the function.
 * `parallel_unsafe`: Corresponds to [`PARALLEL UNSAFE`](https://www.postgresql.org/docs/current/sql-createfunction.html).
 * `parallel_restricted`: Corresponds to [`PARALLEL RESTRICTED`](https://www.postgresql.org/docs/current/sql-createfunction.html).
+* `barrier`: Shorthand for `volatile, parallel_unsafe`, which keeps the planner from evaluating the function early,
+  moving it into a parallel worker, or inlining a set-returning `sql_wrapper` around it.
+* `sql_wrapper = "SELECT * FROM real_fn($1)"`: Follow the function's creation with a `LANGUAGE sql` function with
+  this body, and the same arguments, result, and attributes as the function.
+  + It's named `{name}_sql`, unless `sql_wrapper_name = "name"` is given.
+  + It's created right after the function, so its body can call it.
 * `no_guard`: Do not use `#[pg_guard]` with the function.
+* `check_version`: Raise an `ERROR` before running the function if the extension's installed SQL isn't the version
+  of its library, because `ALTER EXTENSION .. UPDATE` hasn't been run.
+  + The extension then has a `<extension>.check_version` GUC, which can be set to `off` to skip the check.  See [`pgx::version_check`](https://docs.rs/pgx/latest/pgx/version_check/index.html).
+* `memoize`: Cache the function's results for the rest of the statement, so it's only run once for each
+  distinct set of arguments.  The function has to be `stable` or `immutable`.
+  + `memoize = 100` caches at most 100 results a statement.  See [`pgx::memoize`](https://docs.rs/pgx/latest/pgx/memoize/index.html).
+* `arena`: Give each row a set-returning function makes its own arena, which `pgx::arena::with_row_arena()` allocates
+  from, and which is reset once the row's made.  See [`pgx::arena`](https://docs.rs/pgx/latest/pgx/arena/index.html).
+* `legacy_symbol`: Also export the function's wrapper as `{name}_wrapper`, the symbol it had before wrappers were
+  namespaced by their module path, so an installed extension whose `AS` clauses still name it keeps working until
+  it's upgraded.  Two `legacy_symbol` functions can't have the same name, even in different modules.
+* `rows = 10`: Corresponds to [`ROWS 10`](https://www.postgresql.org/docs/current/sql-createfunction.html), how many rows
+  the planner expects a set-returning function to return, rather than 1000.
 * `sql`: Same arguments as [`#[pgx(sql = ..)]`](macro@pgx).
 * `name`: Specifies target function name. Defaults to Rust function name.
+* `transform = "type"`: Corresponds to [`TRANSFORM FOR TYPE type`](https://www.postgresql.org/docs/current/sql-createfunction.html), and may be repeated.
+  + The type has to be one the extension creates or its functions use.  Use `external_transform = "type"` for one from elsewhere, such as another extension.
+  + The transform has to exist, for `LANGUAGE c`, before the function is created, so it's usually `requires`'d.
+* `set = "name = value"`: Corresponds to [`SET name TO value`](https://www.postgresql.org/docs/current/sql-createfunction.html),
+  which sets a configuration parameter while the function runs, and may be repeated.
+  + `value` is single-quoted unless it already is, or is a plain word or number: `set = "work_mem = 256MB"`
+    and `set = "jit = off"` become `SET work_mem TO '256MB'` and `SET jit TO off`.
+  + Each item of a list is quoted on its own: `set = "search_path = pg_catalog, public"` becomes
+    `SET search_path TO 'pg_catalog', 'public'`.
+  + [`#[search_path(..)]`](macro@search_path) sets the `search_path` the same way.
+* `pg_version = "14.."`: Only create the function in the schema generated for the Postgres major
+  versions in the range.  Anything which `requires` it fails to generate for other versions, just
+  like if the function were behind a `#[cfg]`.
+* `owner = "role"`: Follow the function's creation with [`ALTER FUNCTION .. OWNER TO role`](https://www.postgresql.org/docs/current/sql-alterfunction.html).
+* `grant = "role"`: Follow the function's creation with [`GRANT EXECUTE ON FUNCTION .. TO role`](https://www.postgresql.org/docs/current/sql-grant.html), and may be repeated.
+  + The execute privilege `PUBLIC` has by default is revoked first, unless `"PUBLIC"` is one of the roles.
+  + Without `owner` or `grant`, those given to [`pg_module_magic!()`](https://docs.rs/pgx/latest/pgx/macro.pg_module_magic.html) are used.
+* `internal`: The function is only for the extension's own SQL to call, so create it in the `@extschema@_internal`
+  schema, unless it's given a `schema`, and revoke the execute privilege `PUBLIC` has by default.
+  + Only the roles it's `grant`ed to, or `SECURITY DEFINER` functions owned by the extension's owner, can call it.
+  + The extension can't be `relocatable`, because `@extschema@` is only substituted otherwise.
+  + `pg_module_magic!(functions = "internal")` makes every function but operators' internal by default, and `public`
+    opts one out again.
+
+`cargo pgx schema --lint` checks these attributes against what the function's body does, such as an
+`immutable` function that uses `Spi`.
 
 Functions can accept and return any type which `pgx` supports. `pgx` supports many PostgreSQL types by default.
 New types can be defined via [`macro@PostgresType`] or [`macro@PostgresEnum`].
@@ -550,7 +1225,12 @@ fn singular_floop() -> (name!(a, i32), name!(b, i32)) {
 }
 ```
 
-The `name!()` macro may only be used in return position inside the `T` of a `TableIterator<'a, T>`.
+A tuple of two or more values, optionally in an `Option` or a `Result`, is returned as a `record`
+whose columns are declared as `OUT` parameters.  Columns which aren't named with `name!()` are named
+`column1`, `column2`, and so on.  A `None` is a `NULL` record.
+
+The `name!()` macro may only be used in return position inside the `T` of a `TableIterator<'a, T>`,
+or in a returned tuple.
 
 It accepts 2 arguments:
 
@@ -584,15 +1264,49 @@ pub fn pg_extern(attr: TokenStream, item: TokenStream) -> TokenStream {
         Ok(pg_extern_item.to_token_stream().into())
     }
 
-    match wrapped(attr, item) {
-        Ok(tokens) => tokens,
-        Err(e) => {
-            let msg = e.to_string();
-            TokenStream::from(quote! {
-              compile_error!(#msg);
-            })
-        }
+    wrapped(attr, item).unwrap_or_else(|e| e.into_compile_error().into())
+}
+
+/**
+Export a function for other extensions to call directly, from C, rather than through SQL.
+
+The function keeps its Rust signature, and gets an `extern "C"` shim, named
+`<crate name>_<function name>` or by `name = "..."`, whose signature is the same for every
+exported function:
+
+```c
+int32 myext_add(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+```
+
+```rust,ignore
+use pgx::prelude::*;
+
+#[pg_export_abi]
+fn add(a: i32, b: Option<i32>) -> i32 {
+    a + b.unwrap_or(0)
+}
+
+#[pg_export_abi(name = "myext_describe_v2")]
+fn describe(name: &str) -> String {
+    format!("a dog named {}", name)
+}
+```
+
+Its arguments and result are converted from and into datums, and a `NULL` argument which isn't an
+`Option` is an `ERROR`.  `ERROR`s and panics don't unwind into the caller:  they're returned as
+the `PGX_ABI_ERROR` and `PGX_ABI_PANIC` statuses.
+
+`cargo pgx schema --emit-header out.h` writes the C header declaring them.  See
+[`pgx::export_abi`](../pgx/export_abi/index.html).
+*/
+#[proc_macro_attribute]
+pub fn pg_export_abi(attr: TokenStream, item: TokenStream) -> TokenStream {
+    fn wrapped(attr: TokenStream, item: TokenStream) -> Result<TokenStream, syn::Error> {
+        let export = PgExportAbi::new(attr.into(), item.into())?;
+        Ok(export.to_token_stream().into())
     }
+
+    wrapped(attr, item).unwrap_or_else(|e| e.into_compile_error().into())
 }
 
 /**
@@ -609,6 +1323,7 @@ enum DogNames {
 }
 ```
 
+Accepts the `sql`, `owner`, and `grant` options of [`#[pgx(..)]`](macro@pgx).
 */
 #[proc_macro_derive(PostgresEnum, attributes(requires, pgx))]
 pub fn postgres_enum(input: TokenStream) -> TokenStream {
@@ -683,6 +1398,107 @@ fn impl_postgres_enum(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream>
     Ok(stream)
 }
 
+/**
+Generate necessary bindings for using a newtype as a Postgres `DOMAIN` over the type it wraps.
+
+The domain is named after the struct and converts to and from Datums exactly like the wrapped type,
+so it can be used as an argument or return type of a `#[pg_extern]`, in arrays, and as an attribute
+of composite types.
+
+```rust,ignore
+use pgx::prelude::*;
+
+#[pg_extern]
+fn is_email(value: &str) -> bool {
+    value.contains('@')
+}
+
+#[derive(Debug, PostgresDomain)]
+#[pg_domain(check = is_email, not_null)]
+struct Email(String);
+```
+
+Optionally accepts the following attributes:
+
+* `pg_domain(check = some_fn)`: Add a `CHECK (some_fn(VALUE))` constraint.  `some_fn` must be a
+  `#[pg_extern]` taking one argument and returning `bool`, and is created before the domain.
+* `pg_domain(default = "sql")`: Add a `DEFAULT sql` clause.
+* `pg_domain(not_null)`: Add a `NOT NULL` constraint.
+* `sql`: Same arguments as [`#[pgx(sql = ..)]`](macro@pgx).
+*/
+#[proc_macro_derive(PostgresDomain, attributes(pg_domain, pgx))]
+pub fn postgres_domain(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as syn::DeriveInput);
+
+    impl_postgres_domain(ast).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+fn impl_postgres_domain(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut stream = proc_macro2::TokenStream::new();
+    let sql_graph_entity_ast = ast.clone();
+    let domain_ident = &ast.ident;
+    let domain_name = domain_ident.to_string();
+
+    // validate that we're only operating on a newtype
+    let base = match &ast.data {
+        Data::Struct(s) if s.fields.len() == 1 && matches!(s.fields, Fields::Unnamed(_)) => {
+            &s.fields.iter().next().unwrap().ty
+        }
+        _ => {
+            return Err(syn::Error::new(
+                ast.span(),
+                "#[derive(PostgresDomain)] can only be applied to structs with exactly one unnamed field",
+            ))
+        }
+    };
+
+    stream.extend(quote! {
+        impl ::pgx::datum::FromDatum for #domain_ident {
+            #[inline]
+            unsafe fn from_polymorphic_datum(datum: ::pgx::pg_sys::Datum, is_null: bool, typoid: ::pgx::pg_sys::Oid) -> Option<#domain_ident> {
+                <#base as ::pgx::datum::FromDatum>::from_polymorphic_datum(datum, is_null, typoid).map(#domain_ident)
+            }
+
+            #[inline]
+            unsafe fn from_datum_in_memory_context(
+                memory_context: ::pgx::memcxt::PgMemoryContexts,
+                datum: ::pgx::pg_sys::Datum,
+                is_null: bool,
+                typoid: ::pgx::pg_sys::Oid,
+            ) -> Option<#domain_ident> {
+                <#base as ::pgx::datum::FromDatum>::from_datum_in_memory_context(memory_context, datum, is_null, typoid).map(#domain_ident)
+            }
+        }
+
+        impl ::pgx::datum::IntoDatum for #domain_ident {
+            #[inline]
+            fn into_datum(self) -> Option<::pgx::pg_sys::Datum> {
+                <#base as ::pgx::datum::IntoDatum>::into_datum(self.0)
+            }
+
+            fn type_oid() -> ::pgx::pg_sys::Oid {
+                ::pgx::wrappers::regtypein(#domain_name)
+            }
+
+            fn is_compatible_with(other: ::pgx::pg_sys::Oid) -> bool {
+                // values of a domain are values of its base type, so whatever the base type accepts
+                // we accept too, including other domains over it
+                Self::type_oid() == other
+                    || <#base as ::pgx::datum::IntoDatum>::is_compatible_with(other)
+                    || (other != ::pgx::pg_sys::InvalidOid
+                        && <#base as ::pgx::datum::IntoDatum>::is_compatible_with(unsafe {
+                            ::pgx::pg_sys::getBaseType(other)
+                        }))
+            }
+        }
+    });
+
+    let sql_graph_entity_item = PostgresDomain::from_derive_input(sql_graph_entity_ast)?;
+    sql_graph_entity_item.to_tokens(&mut stream);
+
+    Ok(stream)
+}
+
 /**
 Generate necessary bindings for using the type with PostgreSQL.
 
@@ -706,9 +1522,20 @@ Optionally accepts the following attributes:
 
 * `inoutfuncs(some_in_fn, some_out_fn)`: Define custom in/out functions for the type.
 * `pgvarlena_inoutfuncs(some_in_fn, some_out_fn)`: Define custom in/out functions for the `PgVarlena` of this type.
+* `sendrecvfuncs`: Define binary send/receive functions for the type, by implementing `pgx::inoutfuncs::SendRecvFuncs`.
 * `sql`: Same arguments as [`#[pgx(sql = ..)]`](macro@pgx).
+* `owner` and `grant`: Same arguments as [`#[pgx(owner = .., grant = ..)]`](macro@pgx).
+* `storage`: The [`STORAGE`](https://www.postgresql.org/docs/current/sql-createtype.html) of the type,
+  one of `#[pgx(storage = "plain")]`, `"external"`, `"extended"` (the default), or `"main"`.
+* `alignment`: The `ALIGNMENT` of the type, one of `#[pgx(alignment = "char")]`, `"int2"`, `"int4"`,
+  or `"double"`.
+
+Values can also be compressed ahead of time, with [`pgx::toast::compress_varlena()`](https://docs.rs/pgx/latest/pgx/toast/fn.compress_varlena.html).
 */
-#[proc_macro_derive(PostgresType, attributes(inoutfuncs, pgvarlena_inoutfuncs, requires, pgx))]
+#[proc_macro_derive(
+    PostgresType,
+    attributes(inoutfuncs, pgvarlena_inoutfuncs, sendrecvfuncs, requires, pgx)
+)]
 pub fn postgres_type(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as syn::DeriveInput);
 
@@ -721,6 +1548,8 @@ fn impl_postgres_type(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream>
     let has_lifetimes = generics.lifetimes().next();
     let funcname_in = Ident::new(&format!("{}_in", name).to_lowercase(), name.span());
     let funcname_out = Ident::new(&format!("{}_out", name).to_lowercase(), name.span());
+    let funcname_send = Ident::new(&format!("{}_send", name).to_lowercase(), name.span());
+    let funcname_recv = Ident::new(&format!("{}_recv", name).to_lowercase(), name.span());
     let mut args = parse_postgres_type_args(&ast.attrs);
     let mut stream = proc_macro2::TokenStream::new();
 
@@ -740,7 +1569,9 @@ fn impl_postgres_type(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream>
         }
     }
 
-    if args.is_empty() {
+    if !args.contains(&PostgresTypeAttribute::InOutFuncs)
+        && !args.contains(&PostgresTypeAttribute::PgVarlenaInOutFuncs)
+    {
         // assume the user wants us to implement the InOutFuncs
         args.insert(PostgresTypeAttribute::Default);
     }
@@ -833,6 +1664,41 @@ fn impl_postgres_type(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream>
         });
     }
 
+    if args.contains(&PostgresTypeAttribute::SendRecvFuncs) {
+        // a PgVarlenaInOutFuncs type is stored as a PgVarlena, so it's sent and received as one
+        if args.contains(&PostgresTypeAttribute::PgVarlenaInOutFuncs) {
+            stream.extend(quote! {
+                #[doc(hidden)]
+                #[::pgx::pgx_macros::pg_extern(immutable,parallel_safe,strict)]
+                pub fn #funcname_recv #generics(input: ::pgx::datum::Internal) -> ::pgx::datum::PgVarlena<#name #generics> {
+                    let mut result = ::pgx::datum::PgVarlena::<#name #generics>::new();
+                    *result = ::pgx::inoutfuncs::__recv_from_internal(input, <#name as ::pgx::inoutfuncs::SendRecvFuncs>::recv);
+                    result
+                }
+
+                #[doc(hidden)]
+                #[::pgx::pgx_macros::pg_extern(immutable,parallel_safe)]
+                pub fn #funcname_send #generics(input: ::pgx::datum::PgVarlena<#name #generics>) -> Vec<u8> {
+                    ::pgx::inoutfuncs::SendRecvFuncs::send(&*input)
+                }
+            });
+        } else {
+            stream.extend(quote! {
+                #[doc(hidden)]
+                #[::pgx::pgx_macros::pg_extern(immutable,parallel_safe,strict)]
+                pub fn #funcname_recv #generics(input: ::pgx::datum::Internal) -> #name #generics {
+                    ::pgx::inoutfuncs::__recv_from_internal(input, <#name as ::pgx::inoutfuncs::SendRecvFuncs>::recv)
+                }
+
+                #[doc(hidden)]
+                #[::pgx::pgx_macros::pg_extern(immutable,parallel_safe)]
+                pub fn #funcname_send #generics(input: #name #generics) -> Vec<u8> {
+                    ::pgx::inoutfuncs::SendRecvFuncs::send(&input)
+                }
+            });
+        }
+    }
+
     let sql_graph_entity_item = PostgresType::from_derive_input(ast)?;
     sql_graph_entity_item.to_tokens(&mut stream);
 
@@ -930,6 +1796,7 @@ fn impl_guc_enum(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
 enum PostgresTypeAttribute {
     InOutFuncs,
     PgVarlenaInOutFuncs,
+    SendRecvFuncs,
     Default,
 }
 
@@ -948,6 +1815,10 @@ fn parse_postgres_type_args(attributes: &[Attribute]) -> HashSet<PostgresTypeAtt
                 categorized_attributes.insert(PostgresTypeAttribute::PgVarlenaInOutFuncs);
             }
 
+            "sendrecvfuncs" => {
+                categorized_attributes.insert(PostgresTypeAttribute::SendRecvFuncs);
+            }
+
             _ => {
                 // we can just ignore attributes we don't understand
             }
@@ -973,6 +1844,9 @@ enum DogNames {
 Optionally accepts the following attributes:
 
 * `sql`: Same arguments as [`#[pgx(sql = ..)]`](macro@pgx).
+* `immutable`, `stable`, or `volatile`, and `parallel_safe`, `parallel_restricted`, or `parallel_unsafe`:
+  The volatility and parallel safety of the generated functions, e.g. `#[pgx(parallel_safe)]`, which
+  call the type's `PartialEq` impl.  They're `immutable` unless declared otherwise, and `PARALLEL SAFE` only if declared to be.
 */
 #[proc_macro_derive(PostgresEq, attributes(pgx))]
 pub fn postgres_eq(input: TokenStream) -> TokenStream {
@@ -999,6 +1873,9 @@ enum DogNames {
 Optionally accepts the following attributes:
 
 * `sql`: Same arguments as [`#[pgx(sql = ..)]`](macro@pgx).
+* `immutable`, `stable`, or `volatile`, and `parallel_safe`, `parallel_restricted`, or `parallel_unsafe`:
+  The volatility and parallel safety of the generated functions, e.g. `#[pgx(parallel_safe)]`, which
+  call the type's `Ord` impl.  They're `immutable` unless declared otherwise, and `PARALLEL SAFE` only if declared to be.
 */
 #[proc_macro_derive(PostgresOrd, attributes(pgx))]
 pub fn postgres_ord(input: TokenStream) -> TokenStream {
@@ -1022,6 +1899,9 @@ enum DogNames {
 Optionally accepts the following attributes:
 
 * `sql`: Same arguments as [`#[pgx(sql = ..)]`](macro@pgx).
+* `immutable`, `stable`, or `volatile`, and `parallel_safe`, `parallel_restricted`, or `parallel_unsafe`:
+  The volatility and parallel safety of the generated functions, e.g. `#[pgx(parallel_safe)]`, which
+  call the type's `Hash` impl.  They're `immutable` unless declared otherwise, and `PARALLEL SAFE` only if declared to be.
 */
 #[proc_macro_derive(PostgresHash, attributes(pgx))]
 pub fn postgres_hash(input: TokenStream) -> TokenStream {
@@ -1029,10 +1909,119 @@ pub fn postgres_hash(input: TokenStream) -> TokenStream {
     impl_postgres_hash(ast).unwrap_or_else(syn::Error::into_compile_error).into()
 }
 
+/**
+Generate the `=`, `<>`, `<`, `<=`, `>`, and `>=` operators, and a default btree operator class, for
+a composite type created by the extension.
+
+```rust,ignore
+use pgx::prelude::*;
+
+extension_sql!(
+    "CREATE TYPE point3d AS (x float8, y float8, z float8);",
+    name = "create_point3d",
+    declares = [Type(point3d)],
+);
+
+pg_composite_ops!("point3d", requires = ["create_point3d"]);
+```
+
+The generated functions (`point3d_eq`, `point3d_cmp`, etc) take `composite_type!("point3d")`
+arguments, and compare them attribute by attribute exactly like Postgres' own record comparison
+does, using each attribute's default btree operator class.  Two `NULL` attributes are equal, and a
+`NULL` attribute sorts after any non-`NULL` one.
+
+`requires` is optional, and is passed on to each of the generated functions.  The functions are
+already ordered after the `extension_sql!()` declaring the composite type.
+*/
+#[proc_macro]
+pub fn pg_composite_ops(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as CompositeOps);
+    impl_pg_composite_ops(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/**
+Implement `pgx::ErrorReportable`, so that a `#[pg_extern]` function returning this error raises it
+with a specific SQLSTATE, message, `DETAIL`, and `HINT`.
+
+```rust,ignore
+use pgx::prelude::*;
+use pgx::ErrorReportable;
+
+#[derive(thiserror::Error, ErrorReportable, Debug)]
+enum LookupError {
+    #[error("no row with id {0}")]
+    #[error_report(code = ERRCODE_NO_DATA_FOUND, hint = "ids start at 1")]
+    NotFound(i64),
+    #[error("the table is locked")]
+    #[error_report(code = ERRCODE_LOCK_NOT_AVAILABLE, detail = "locked by {owner}")]
+    Locked { owner: String },
+}
+```
+
+Each variant, or the struct, optionally accepts `#[error_report(...)]` with:
+
+* `code`: the name of a `pgx::PgSqlErrorCode` variant.  On an enum itself, it's the default
+  for variants without one, which is otherwise `ERRCODE_DATA_EXCEPTION`.
+* `message`: the error message, which defaults to the error's `Display` representation.
+* `detail` and `hint`: the `DETAIL` and `HINT` lines, which are omitted by default.
+
+`message`, `detail`, and `hint` are format strings, in which fields are available by name, or as
+`_0`, `_1`, etc for tuple variants.
+*/
+#[proc_macro_derive(ErrorReportable, attributes(error_report))]
+pub fn error_reportable(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as syn::DeriveInput);
+    impl_error_reportable(ast).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/**
+Make a struct the row of a `TableIterator`, whose fields are the columns of its `RETURNS TABLE (...)`,
+in order.
+
+```rust,ignore
+use pgx::prelude::*;
+
+#[derive(PgTableRow)]
+struct Pup<'a> {
+    id: i64,
+    #[pgx(name = "pup_name")]
+    name: &'a str,
+    // a column which can be `NULL`
+    nickname: Option<String>,
+    #[pgx(composite_type = "Dog")]
+    dog: PgHeapTuple<'static, AllocatedByRust>,
+}
+
+#[pg_extern]
+fn pups<'a>(names: Vec<&'a str>) -> TableIterator<'a, Pup<'a>> {
+    TableIterator::new(names.into_iter().enumerate().map(|(i, name)| {
+        let mut dog = PgHeapTuple::new_composite_type("Dog").unwrap();
+        dog.set_by_name("name", name).unwrap();
+        Pup { id: i as i64, name, nickname: None, dog }
+    }))
+}
+```
+
+Each field optionally accepts `#[pgx(...)]` with:
+
+* `name`: the column's name, which is otherwise the field's.
+* `composite_type`: the composite type of a `PgHeapTuple` field, which may be in an `Option` or
+  `Vec`, as `composite_type!()` gives it to a `#[pg_extern]`'s arguments.
+
+The struct can be generic over lifetimes, but not types.
+*/
+#[proc_macro_derive(PgTableRow, attributes(pgx))]
+pub fn pg_table_row(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as syn::DeriveInput);
+    impl_pg_table_row(ast).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
 /**
 Declare a `pgx::Aggregate` implementation on a type as able to used by Postgres as an aggregate.
 
-Functions inside the `impl` may use the [`#[pgx]`](macro@pgx) attribute.
+Functions inside the `impl` may use the [`#[pgx]`](macro@pgx) attribute.  The `impl` itself
+accepts the `sql`, `owner`, and `grant` options of `#[pgx(..)]`, the latter generating
+`ALTER AGGREGATE .. OWNER TO` and `GRANT EXECUTE ON FUNCTION` statements.
 */
 #[proc_macro_attribute]
 pub fn pg_aggregate(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -1074,6 +2063,49 @@ Currently `sql` can be provided one of the following:
 * Call custom SQL generator function with `#[pgx(sql = path::to_function)]`
 * Render a specific fragment of SQL with a string `#[pgx(sql = "CREATE FUNCTION ...")]`
 
+The same options are accepted as `sql = ...` by [`#[pg_extern]`](macro@pg_extern) and
+[`#[pg_trigger]`](macro@pg_trigger).
+
+`#[pgx(pg_version = "14..")]` leaves the item out of the SQL generated for Postgres major versions
+outside of the range, as [`#[pg_extern(pg_version = ..)]`](macro@pg_extern) does.
+
+`#[pgx(owner = "role", grant = "role")]` on a type or aggregate follows its creation with
+`ALTER .. OWNER TO` and `GRANT` statements, as [`#[pg_extern(owner = .., grant = ..)]`](macro@pg_extern)
+does.
+
+`#[pgx(storage = "external", alignment = "int4")]` on a [`#[derive(PostgresType)]`](macro@PostgresType)
+sets the `STORAGE` and `ALIGNMENT` of its `CREATE TYPE`.
+
+A custom SQL generator function is given the entity being rendered, and the context of the whole
+SQL generation, and returns either an `eyre::Result<String>` or a
+`Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>`. It can call
+`SqlGraphEntity::to_default_sql` to decorate the SQL `pgx` would have generated, rather than
+replacing it:
+
+```rust,ignore
+use pgx::prelude::*;
+use pgx::pgx_sql_entity_graph::{PgxSql, SqlGraphEntity};
+
+#[pg_extern(sql = grant_execute)]
+fn everyone_can_call_me() -> i32 {
+    42
+}
+
+fn grant_execute(entity: &SqlGraphEntity, context: &PgxSql) -> eyre::Result<String> {
+    let default_sql = entity.to_default_sql(context)?;
+    match entity {
+        SqlGraphEntity::Function(func) => Ok(format!(
+            "{default_sql}\nGRANT EXECUTE ON FUNCTION \"{name}\"() TO PUBLIC;\n",
+            name = func.name,
+        )),
+        _ => Ok(default_sql),
+    }
+}
+```
+
+The entity types handed to these functions are generator internals: only rely on their
+identifying fields (such as `name` and `schema`), as the rest may change between `pgx` releases.
+
 */
 #[proc_macro_attribute]
 pub fn pgx(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -1085,6 +2117,9 @@ Create a [PostgreSQL trigger function](https://www.postgresql.org/docs/current/p
 
 Review the `pgx::trigger_support::PgTrigger` documentation for use.
 
+`#[pg_trigger(arena)]` gives each call its own arena, which `pgx::arena::with_row_arena()` allocates
+from, and which is reset once the trigger returns.  See [`pgx::arena`](https://docs.rs/pgx/latest/pgx/arena/index.html).
+
  */
 #[proc_macro_attribute]
 pub fn pg_trigger(attrs: TokenStream, input: TokenStream) -> TokenStream {
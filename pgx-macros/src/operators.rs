@@ -6,29 +6,57 @@ All rights reserved.
 
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
-use pgx_sql_entity_graph::{PostgresHash, PostgresOrd};
+use pgx_sql_entity_graph::{parse_operator_attributes, PostgresHash, PostgresOrd};
 
-use proc_macro2::Ident;
+use proc_macro2::{Ident, TokenStream};
 use quote::{quote, ToTokens};
-use syn::DeriveInput;
+use syn::parse::{Parse, ParseStream};
+use syn::{DeriveInput, LitStr, Token};
+
+/// The input of `pg_composite_ops!("name", requires = [..])`
+pub(crate) struct CompositeOps {
+    name: LitStr,
+    requires: Option<TokenStream>,
+}
+
+impl Parse for CompositeOps {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: LitStr = input.parse()?;
+        let mut requires = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key != "requires" {
+                return Err(syn::Error::new(key.span(), "expected `requires = [..]`"));
+            }
+            input.parse::<Token![=]>()?;
+            let content;
+            syn::bracketed!(content in input);
+            requires = Some(content.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(CompositeOps { name, requires })
+    }
+}
 
 pub(crate) fn impl_postgres_eq(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let mut stream = proc_macro2::TokenStream::new();
+    let attrs = parse_operator_attributes(&ast.attrs)?;
 
-    stream.extend(eq(&ast.ident));
-    stream.extend(ne(&ast.ident));
+    stream.extend(eq(&ast.ident, &attrs));
+    stream.extend(ne(&ast.ident, &attrs));
 
     Ok(stream)
 }
 
 pub(crate) fn impl_postgres_ord(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let mut stream = proc_macro2::TokenStream::new();
+    let attrs = parse_operator_attributes(&ast.attrs)?;
 
-    stream.extend(lt(&ast.ident));
-    stream.extend(gt(&ast.ident));
-    stream.extend(le(&ast.ident));
-    stream.extend(ge(&ast.ident));
-    stream.extend(cmp(&ast.ident));
+    stream.extend(lt(&ast.ident, &attrs));
+    stream.extend(gt(&ast.ident, &attrs));
+    stream.extend(le(&ast.ident, &attrs));
+    stream.extend(ge(&ast.ident, &attrs));
+    stream.extend(cmp(&ast.ident, &attrs));
 
     let sql_graph_entity_item = PostgresOrd::from_derive_input(ast)?;
     sql_graph_entity_item.to_tokens(&mut stream);
@@ -38,8 +66,9 @@ pub(crate) fn impl_postgres_ord(ast: DeriveInput) -> syn::Result<proc_macro2::To
 
 pub(crate) fn impl_postgres_hash(ast: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let mut stream = proc_macro2::TokenStream::new();
+    let attrs = parse_operator_attributes(&ast.attrs)?;
 
-    stream.extend(hash(&ast.ident));
+    stream.extend(hash(&ast.ident, &attrs));
 
     let sql_graph_entity_item = PostgresHash::from_derive_input(ast)?;
     sql_graph_entity_item.to_tokens(&mut stream);
@@ -47,11 +76,11 @@ pub(crate) fn impl_postgres_hash(ast: DeriveInput) -> syn::Result<proc_macro2::T
     Ok(stream)
 }
 
-pub fn eq(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn eq(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_eq", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_operator(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
         #[::pgx::pgx_macros::opname(=)]
         #[::pgx::pgx_macros::negator(<>)]
         #[::pgx::pgx_macros::restrict(eqsel)]
@@ -64,11 +93,11 @@ pub fn eq(type_name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
-pub fn ne(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn ne(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_ne", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_operator(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
         #[::pgx::pgx_macros::opname(<>)]
         #[::pgx::pgx_macros::negator(=)]
         #[::pgx::pgx_macros::restrict(neqsel)]
@@ -79,11 +108,11 @@ pub fn ne(type_name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
-pub fn lt(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn lt(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_lt", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_operator(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
         #[::pgx::pgx_macros::opname(<)]
         #[::pgx::pgx_macros::negator(>=)]
         #[::pgx::pgx_macros::commutator(>)]
@@ -96,11 +125,11 @@ pub fn lt(type_name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
-pub fn gt(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn gt(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_gt", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_operator(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
         #[::pgx::pgx_macros::opname(>)]
         #[::pgx::pgx_macros::negator(<=)]
         #[::pgx::pgx_macros::commutator(<)]
@@ -112,11 +141,11 @@ pub fn gt(type_name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
-pub fn le(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn le(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_le", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_operator(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
         #[::pgx::pgx_macros::opname(<=)]
         #[::pgx::pgx_macros::negator(>)]
         #[::pgx::pgx_macros::commutator(>=)]
@@ -128,11 +157,11 @@ pub fn le(type_name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
-pub fn ge(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn ge(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_ge", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_operator(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
         #[::pgx::pgx_macros::opname(>=)]
         #[::pgx::pgx_macros::negator(<)]
         #[::pgx::pgx_macros::commutator(<=)]
@@ -144,24 +173,150 @@ pub fn ge(type_name: &Ident) -> proc_macro2::TokenStream {
     }
 }
 
-pub fn cmp(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn cmp(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_cmp", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_extern(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_extern(#attrs)]
         fn #pg_name(left: #type_name, right: #type_name) -> i32 {
             left.cmp(&right) as i32
         }
     }
 }
 
-pub fn hash(type_name: &Ident) -> proc_macro2::TokenStream {
+pub fn hash(type_name: &Ident, attrs: &TokenStream) -> proc_macro2::TokenStream {
     let pg_name = Ident::new(&format!("{}_hash", type_name).to_lowercase(), type_name.span());
     quote! {
         #[allow(non_snake_case)]
-        #[::pgx::pgx_macros::pg_extern(immutable, parallel_safe)]
+        #[::pgx::pgx_macros::pg_extern(#attrs)]
         fn #pg_name(value: #type_name) -> i32 {
             ::pgx::misc::pgx_seahash(&value) as i32
         }
     }
 }
+
+pub(crate) fn impl_pg_composite_ops(input: CompositeOps) -> syn::Result<TokenStream> {
+    let sql_name = input.name.value();
+    let prefix = sql_name.to_lowercase();
+    let fn_name = |suffix: &str| -> syn::Result<Ident> {
+        syn::parse_str::<Ident>(&format!("{}_{}", prefix, suffix)).map_err(|_| {
+            syn::Error::new(
+                input.name.span(),
+                format!("`{}` can't be used to name the comparison functions", sql_name),
+            )
+        })
+    };
+    let (eq, ne, lt, le, gt, ge, cmp) = (
+        fn_name("eq")?,
+        fn_name("ne")?,
+        fn_name("lt")?,
+        fn_name("le")?,
+        fn_name("gt")?,
+        fn_name("ge")?,
+        fn_name("cmp")?,
+    );
+    let composite = &input.name;
+    let ty = quote! { ::pgx::composite_type!(#composite) };
+    let requires = input.requires.map(|requires| quote! { requires = [#requires] });
+    // these call `record_eq()` and `btrecordcmp()`, which Postgres declares IMMUTABLE PARALLEL SAFE
+    let attrs = match &requires {
+        Some(requires) => quote! { immutable, parallel_safe, #requires },
+        None => quote! { immutable, parallel_safe },
+    };
+    let opclass_sql = format!(
+        "\n\
+        CREATE OPERATOR FAMILY {prefix}_btree_ops USING btree;\n\
+        CREATE OPERATOR CLASS {prefix}_btree_ops DEFAULT FOR TYPE {name} USING btree FAMILY {prefix}_btree_ops AS\n\
+        \tOPERATOR 1 <,\n\
+        \tOPERATOR 2 <=,\n\
+        \tOPERATOR 3 =,\n\
+        \tOPERATOR 4 >=,\n\
+        \tOPERATOR 5 >,\n\
+        \tFUNCTION 1 {cmp}({name}, {name});\n",
+        prefix = prefix,
+        name = sql_name,
+        cmp = cmp,
+    );
+    let opclass_name = format!("{}_btree_ops", prefix);
+
+    Ok(quote! {
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
+        #[::pgx::pgx_macros::opname(=)]
+        #[::pgx::pgx_macros::negator(<>)]
+        #[::pgx::pgx_macros::commutator(=)]
+        #[::pgx::pgx_macros::restrict(eqsel)]
+        #[::pgx::pgx_macros::join(eqjoinsel)]
+        #[::pgx::pgx_macros::merges]
+        fn #eq(left: #ty, right: #ty) -> bool {
+            left.record_eq(&right)
+        }
+
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
+        #[::pgx::pgx_macros::opname(<>)]
+        #[::pgx::pgx_macros::negator(=)]
+        #[::pgx::pgx_macros::commutator(<>)]
+        #[::pgx::pgx_macros::restrict(neqsel)]
+        #[::pgx::pgx_macros::join(neqjoinsel)]
+        fn #ne(left: #ty, right: #ty) -> bool {
+            !left.record_eq(&right)
+        }
+
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
+        #[::pgx::pgx_macros::opname(<)]
+        #[::pgx::pgx_macros::negator(>=)]
+        #[::pgx::pgx_macros::commutator(>)]
+        #[::pgx::pgx_macros::restrict(scalarltsel)]
+        #[::pgx::pgx_macros::join(scalarltjoinsel)]
+        fn #lt(left: #ty, right: #ty) -> bool {
+            left.record_cmp(&right).is_lt()
+        }
+
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
+        #[::pgx::pgx_macros::opname(<=)]
+        #[::pgx::pgx_macros::negator(>)]
+        #[::pgx::pgx_macros::commutator(>=)]
+        #[::pgx::pgx_macros::restrict(scalarlesel)]
+        #[::pgx::pgx_macros::join(scalarlejoinsel)]
+        fn #le(left: #ty, right: #ty) -> bool {
+            left.record_cmp(&right).is_le()
+        }
+
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
+        #[::pgx::pgx_macros::opname(>)]
+        #[::pgx::pgx_macros::negator(<=)]
+        #[::pgx::pgx_macros::commutator(<)]
+        #[::pgx::pgx_macros::restrict(scalargtsel)]
+        #[::pgx::pgx_macros::join(scalargtjoinsel)]
+        fn #gt(left: #ty, right: #ty) -> bool {
+            left.record_cmp(&right).is_gt()
+        }
+
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_operator(#attrs)]
+        #[::pgx::pgx_macros::opname(>=)]
+        #[::pgx::pgx_macros::negator(<)]
+        #[::pgx::pgx_macros::commutator(<=)]
+        #[::pgx::pgx_macros::restrict(scalargesel)]
+        #[::pgx::pgx_macros::join(scalargejoinsel)]
+        fn #ge(left: #ty, right: #ty) -> bool {
+            left.record_cmp(&right).is_ge()
+        }
+
+        #[allow(non_snake_case)]
+        #[::pgx::pgx_macros::pg_extern(#attrs)]
+        fn #cmp(left: #ty, right: #ty) -> i32 {
+            left.record_cmp(&right) as i32
+        }
+
+        ::pgx::pgx_macros::extension_sql!(
+            #opclass_sql,
+            name = #opclass_name,
+            requires = [#eq, #ne, #lt, #le, #gt, #ge, #cmp],
+        );
+    })
+}
@@ -46,18 +46,27 @@ impl PgGuardRewriter {
         let input_func_name = func.sig.ident.to_string();
         let sig = func.sig.clone();
         let vis = func.vis.clone();
-        let attrs = func.attrs.clone();
+        let mut attrs = func.attrs.clone();
+        if input_func_name == "_PG_init" || input_func_name == "_PG_fini" {
+            attrs.retain(|attr| !attr.path.is_ident("no_mangle"));
+        }
+        if cfg!(feature = "sql-generation") {
+            // a library built only to generate its schema exports nothing for Postgres to call
+            attrs.retain(|attr| !attr.path.is_ident("no_mangle"));
+        }
 
         let generics = func.sig.generics.clone();
 
-        if attrs.iter().any(|attr| attr.path.is_ident("no_mangle"))
+        if attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("no_mangle") || attr.path.is_ident("export_name"))
             && generics.params.iter().any(|p| match p {
                 GenericParam::Type(_) => true,
                 GenericParam::Lifetime(_) => false,
                 GenericParam::Const(_) => true,
             })
         {
-            panic!("#[pg_guard] for function with generic parameters must not be combined with #[no_mangle]");
+            panic!("#[pg_guard] for function with generic parameters must not be combined with #[no_mangle] or #[export_name]");
         }
 
         // but for the inner function (the one we're wrapping) we don't need any kind of
@@ -80,9 +89,38 @@ impl PgGuardRewriter {
             // we do not want "no_mangle" on these functions
             quote! {}
         } else if input_func_name == "_PG_init" || input_func_name == "_PG_fini" {
+            // `pg_module_magic!()` exports the real `_PG_init()`/`_PG_fini()` symbols, so
+            // these aren't exported but registered to be called from those, ahead of every
+            // `#[pg_init]` and after every `#[pg_fini]` function
             quote! {
                 #[allow(non_snake_case)]
-                #[no_mangle]
+            }
+        } else {
+            quote! {}
+        };
+
+        let epilog = if input_func_name == "_PG_init" || input_func_name == "_PG_fini" {
+            let register = if input_func_name == "_PG_init" {
+                quote! { pgx::init::__register_init }
+            } else {
+                quote! { pgx::init::__register_fini }
+            };
+            let ident = &sig.ident;
+            quote! {
+                // NB: this is purposely not spelled `::pgx` as pgx itself uses #[pg_guard]
+                pgx::__pgx_register_on_load!(#register(
+                    i32::MIN,
+                    concat!(module_path!(), "::", stringify!(#ident)),
+                    {
+                        fn __pgx_hand_written() {
+                            #[allow(unused_unsafe)]
+                            unsafe {
+                                #ident()
+                            }
+                        }
+                        __pgx_hand_written
+                    }
+                ));
             }
         } else {
             quote! {}
@@ -116,6 +154,8 @@ impl PgGuardRewriter {
                     pgx::pg_sys::submodules::panic::pgx_extern_c_guard( || #body )
                 }
             }
+
+            #epilog
         })
     }
 
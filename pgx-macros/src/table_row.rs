@@ -0,0 +1,217 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx_sql_entity_graph::lifetimes::staticize_lifetimes;
+use pgx_sql_entity_graph::UsedType;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use std::collections::HashSet;
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, GenericParam, Lit, LitStr, Meta, NestedMeta,
+    PathArguments,
+};
+
+pub(crate) fn impl_pg_table_row(ast: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    // the columns' types are looked up by their `TypeId`, which a type parameter doesn't have
+    if let Some(param) =
+        ast.generics.params.iter().find(|param| !matches!(param, GenericParam::Lifetime(_)))
+    {
+        return Err(syn::Error::new(
+            param.span(),
+            "#[derive(PgTableRow)] structs can only be generic over lifetimes",
+        ));
+    }
+    let fields =
+        match &ast.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) if !fields.named.is_empty() => &fields.named,
+                _ => return Err(syn::Error::new(
+                    name.span(),
+                    "#[derive(PgTableRow)] needs a struct with named fields, which are its columns",
+                )),
+            },
+            _ => {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "#[derive(PgTableRow)] can only be applied to structs",
+                ))
+            }
+        };
+
+    let mut columns = Vec::with_capacity(fields.len());
+    let mut seen = HashSet::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("a named field");
+        let args = ColumnArgs::from_attrs(&field.attrs)?;
+        let column = match &args.name {
+            Some(name) => name.value(),
+            None => ident.unraw().to_string(),
+        };
+        if !seen.insert(column.clone()) {
+            let span = args.name.as_ref().map(|name| name.span()).unwrap_or_else(|| ident.span());
+            return Err(syn::Error::new(
+                span,
+                format!("there's already a column named `{column}`"),
+            ));
+        }
+        let sql_ty = match &args.composite_type {
+            Some(composite_type) => {
+                with_composite_type(&field.ty, composite_type).ok_or_else(|| {
+                    syn::Error::new(
+                        composite_type.span(),
+                        "`composite_type` is for a `PgHeapTuple` field, which may be in an `Option` or `Vec`",
+                    )
+                })?
+            }
+            None => field.ty.clone(),
+        };
+        let entity = UsedType::new(sql_ty)?.entity_tokens();
+        // like the `TypeId`s, only a `PgHeapTuple<'static, _>` is `SqlTranslatable`
+        let mut static_ty = field.ty.clone();
+        staticize_lifetimes(&mut static_ty);
+        columns.push((ident, column, static_ty, entity));
+    }
+
+    let count = columns.len();
+    let into_datums = columns.iter().enumerate().map(|(index, (ident, ..))| {
+        quote! {
+            match ::pgx::datum::IntoDatum::into_datum(self.#ident) {
+                Some(datum) => datums[#index] = datum,
+                None => nulls[#index] = true,
+            }
+        }
+    });
+    let items = columns.iter().map(|(_, column, _, entity)| {
+        quote! {
+            ::pgx::pgx_sql_entity_graph::PgExternReturnEntityIteratedItem {
+                ty: #entity,
+                name: Some(#column),
+            }
+        }
+    });
+    let return_sqls = columns.iter().map(|(_, _, ty, _)| {
+        quote! {
+            <#ty as ::pgx::pgx_sql_entity_graph::metadata::SqlTranslatable>::return_sql()
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::pgx::htup::IntoHeapTuple for #name #ty_generics #where_clause {
+            unsafe fn into_heap_tuple(
+                self,
+                tupdesc: ::pgx::pg_sys::TupleDesc,
+            ) -> *mut ::pgx::pg_sys::HeapTupleData {
+                let mut datums = [::pgx::pg_sys::Datum::from(0); #count];
+                let mut nulls = [false; #count];
+
+                #(#into_datums)*
+
+                // SAFETY:  the caller has asserted `tupdesc` is valid, and `datums` and `nulls`
+                // have a value for each of its columns
+                ::pgx::pg_sys::heap_form_tuple(tupdesc, datums.as_mut_ptr(), nulls.as_mut_ptr())
+            }
+        }
+
+        impl #impl_generics ::pgx::iter::PgTableRow for #name #ty_generics #where_clause {
+            fn table_row_columns(
+            ) -> ::std::vec::Vec<::pgx::pgx_sql_entity_graph::PgExternReturnEntityIteratedItem> {
+                vec![#(#items),*]
+            }
+
+            fn table_row_return_sql() -> ::std::vec::Vec<
+                ::std::result::Result<
+                    ::pgx::pgx_sql_entity_graph::metadata::Returns,
+                    ::pgx::pgx_sql_entity_graph::metadata::ReturnsError,
+                >,
+            > {
+                vec![#(#return_sqls),*]
+            }
+        }
+    })
+}
+
+/// The `#[pgx(name = "...", composite_type = "...")]` of a field
+#[derive(Default)]
+struct ColumnArgs {
+    name: Option<LitStr>,
+    composite_type: Option<LitStr>,
+}
+
+impl ColumnArgs {
+    fn from_attrs(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut args = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("pgx")) {
+            let list =
+                match attr.parse_meta()? {
+                    Meta::List(list) => list,
+                    other => return Err(syn::Error::new(
+                        other.span(),
+                        "expected `#[pgx(name = \"...\")]` or `#[pgx(composite_type = \"...\")]`",
+                    )),
+                };
+            for nested in list.nested {
+                let nv = match nested {
+                    NestedMeta::Meta(Meta::NameValue(nv)) => nv,
+                    other => {
+                        return Err(syn::Error::new(
+                            other.span(),
+                            "expected `name = \"...\"` or `composite_type = \"...\"`",
+                        ))
+                    }
+                };
+                let arg = if nv.path.is_ident("name") {
+                    &mut args.name
+                } else if nv.path.is_ident("composite_type") {
+                    &mut args.composite_type
+                } else {
+                    return Err(syn::Error::new(
+                        nv.path.span(),
+                        "unknown column option, expected `name` or `composite_type`",
+                    ));
+                };
+                let value = match nv.lit {
+                    Lit::Str(value) => value,
+                    other => return Err(syn::Error::new(other.span(), "expected a string")),
+                };
+                if arg.replace(value).is_some() {
+                    return Err(syn::Error::new(nv.path.span(), "may only be given once"));
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// `ty` with its `PgHeapTuple`, which may be in an `Option` or `Vec`, replaced by
+/// `composite_type!(composite_type)`, as `#[pg_extern]` would be given it
+fn with_composite_type(ty: &syn::Type, composite_type: &LitStr) -> Option<syn::Type> {
+    let mut path = match ty {
+        syn::Type::Path(path) => path.clone(),
+        _ => return None,
+    };
+    let segment = path.path.segments.last_mut()?;
+    match segment.ident.to_string().as_str() {
+        "PgHeapTuple" => Some(syn::parse_quote! { ::pgx::composite_type!(#composite_type) }),
+        "Option" | "Vec" => {
+            let inner = match &mut segment.arguments {
+                PathArguments::AngleBracketed(args) => match args.args.last_mut()? {
+                    GenericArgument::Type(inner) => inner,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            *inner = with_composite_type(inner, composite_type)?;
+            Some(syn::Type::Path(path))
+        }
+        _ => None,
+    }
+}
@@ -22,6 +22,75 @@ use url::Url;
 pub static BASE_POSTGRES_PORT_NO: u16 = 28800;
 pub static BASE_POSTGRES_TESTING_PORT_NO: u16 = 32200;
 
+/// A range of ports, such as `32200-32299`, that the test framework may start Postgres on.
+///
+/// It's read from `testing_port_range` in `$PGX_HOME/config.toml`, or from the
+/// `PGX_TEST_PORT_RANGE` environment variable, which takes precedence.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PortRange {
+    start: u16,
+    end: u16,
+}
+
+impl PortRange {
+    pub fn new(start: u16, end: u16) -> eyre::Result<Self> {
+        if start > end {
+            return Err(eyre!("port range `{}-{}` is empty", start, end));
+        }
+        Ok(PortRange { start, end })
+    }
+
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// Every port in the range, starting at `preferred` (if it's in the range) and wrapping around
+    pub fn ports_from(&self, preferred: u16) -> impl Iterator<Item = u16> {
+        let preferred =
+            if (self.start..=self.end).contains(&preferred) { preferred } else { self.start };
+        (preferred..=self.end).chain(self.start..preferred)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| eyre!("port range `{}` should look like `32200-32299`", s))?;
+        let parse = |port: &str| {
+            port.trim().parse::<u16>().wrap_err_with(|| format!("invalid port range `{}`", s))
+        };
+        PortRange::new(parse(start)?, parse(end)?)
+    }
+}
+
+impl TryFrom<String> for PortRange {
+    type Error = eyre::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PortRange> for String {
+    fn from(range: PortRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
 /// The flags to specify to get a "C.UTF-8" locale on this system, or "C" locale on systems without
 /// a "C.UTF-8" locale equivalent.
 pub fn get_c_locale_flags() -> &'static [&'static str] {
@@ -74,6 +143,9 @@ pub struct PgConfig {
     known_props: Option<BTreeMap<String, String>>,
     base_port: u16,
     base_testing_port: u16,
+    socket_only: bool,
+    testing_port_range: Option<PortRange>,
+    test_port: Option<u16>,
 }
 
 impl Display for PgConfig {
@@ -96,6 +168,9 @@ impl Default for PgConfig {
             known_props: None,
             base_port: BASE_POSTGRES_PORT_NO,
             base_testing_port: BASE_POSTGRES_TESTING_PORT_NO,
+            socket_only: false,
+            testing_port_range: None,
+            test_port: None,
         }
     }
 }
@@ -108,23 +183,11 @@ impl From<PgVersion> for PgConfig {
 
 impl PgConfig {
     pub fn new(pg_config: PathBuf, base_port: u16, base_testing_port: u16) -> Self {
-        PgConfig {
-            version: None,
-            pg_config: Some(pg_config),
-            known_props: None,
-            base_port,
-            base_testing_port,
-        }
+        PgConfig { pg_config: Some(pg_config), base_port, base_testing_port, ..Default::default() }
     }
 
     pub fn new_with_defaults(pg_config: PathBuf) -> Self {
-        PgConfig {
-            version: None,
-            pg_config: Some(pg_config),
-            known_props: None,
-            base_port: BASE_POSTGRES_PORT_NO,
-            base_testing_port: BASE_POSTGRES_TESTING_PORT_NO,
-        }
+        PgConfig { pg_config: Some(pg_config), ..Default::default() }
     }
 
     pub fn from_path() -> Self {
@@ -159,6 +222,9 @@ impl PgConfig {
                 known_props: Some(known_props),
                 base_port: 0,
                 base_testing_port: 0,
+                socket_only: socket_only_from_env()?.unwrap_or(false),
+                testing_port_range: testing_port_range_from_env()?,
+                test_port: None,
             })
         }
     }
@@ -249,12 +315,58 @@ impl PgConfig {
         Ok(self.base_port + self.major_version()?)
     }
 
+    /// The port of the test framework's Postgres instance.  That's the port it was started on, if
+    /// it was given with [`PgConfig::set_test_port`], or the first of [`PgConfig::test_ports`].
     pub fn test_port(&self) -> eyre::Result<u16> {
-        Ok(self.base_testing_port + self.major_version()?)
+        match self.test_port {
+            Some(port) => Ok(port),
+            None => Ok(self.test_ports()?[0]),
+        }
     }
 
-    pub fn host(&self) -> &'static str {
-        "localhost"
+    /// The ports the test framework may start Postgres on, in the order it should try them
+    pub fn test_ports(&self) -> eyre::Result<Vec<u16>> {
+        let preferred = self.base_testing_port + self.major_version()?;
+        match self.testing_port_range {
+            Some(range) => Ok(range.ports_from(preferred).collect()),
+            None => Ok(vec![preferred]),
+        }
+    }
+
+    /// Records the port the test framework's Postgres instance was started on
+    pub fn set_test_port(&mut self, port: u16) {
+        self.test_port = Some(port);
+    }
+
+    /// Do the Postgres instances pgx starts only accept connections over their Unix socket?
+    ///
+    /// This is `socket_only` in `$PGX_HOME/config.toml`, or the `PGX_SOCKET_ONLY` environment
+    /// variable, which takes precedence.
+    pub fn socket_only(&self) -> bool {
+        self.socket_only
+    }
+
+    /// The value of `listen_addresses` for the Postgres instances pgx starts
+    pub fn listen_addresses(&self) -> &'static str {
+        if self.socket_only {
+            ""
+        } else {
+            "localhost"
+        }
+    }
+
+    /// The directory the Postgres instances pgx starts create their Unix sockets in
+    pub fn socket_dir(&self) -> eyre::Result<PathBuf> {
+        Ok(Pgx::home()?)
+    }
+
+    /// The `host` to connect to: the socket directory in socket-only mode, otherwise `localhost`
+    pub fn host(&self) -> eyre::Result<String> {
+        if self.socket_only {
+            Ok(self.socket_dir()?.display().to_string())
+        } else {
+            Ok("localhost".to_string())
+        }
     }
 
     pub fn bin_dir(&self) -> eyre::Result<PathBuf> {
@@ -291,6 +403,12 @@ impl PgConfig {
         Ok(path)
     }
 
+    pub fn pg_dump_path(&self) -> eyre::Result<PathBuf> {
+        let mut path = self.bin_dir()?;
+        path.push("pg_dump");
+        Ok(path)
+    }
+
     pub fn data_dir(&self) -> eyre::Result<PathBuf> {
         let mut path = Pgx::home()?;
         path.push(format!("data-{}", self.major_version()?));
@@ -303,6 +421,14 @@ impl PgConfig {
         Ok(path)
     }
 
+    /// The log of the temporary Postgres instance `cargo pgx test` runs the tests in, which is
+    /// next to its data directory, `target/pgx-test-data-PGVER/`
+    pub fn test_log_file(&self) -> eyre::Result<PathBuf> {
+        let mut path = get_target_dir()?;
+        path.push(format!("pgx-test-data-{}.log", self.major_version()?));
+        Ok(path)
+    }
+
     pub fn includedir_server(&self) -> eyre::Result<PathBuf> {
         Ok(self.run("--includedir-server")?.into())
     }
@@ -366,6 +492,8 @@ pub struct Pgx {
     pg_configs: Vec<PgConfig>,
     base_port: u16,
     base_testing_port: u16,
+    socket_only: bool,
+    testing_port_range: Option<PortRange>,
 }
 
 impl Default for Pgx {
@@ -374,6 +502,8 @@ impl Default for Pgx {
             pg_configs: vec![],
             base_port: BASE_POSTGRES_PORT_NO,
             base_testing_port: BASE_POSTGRES_TESTING_PORT_NO,
+            socket_only: false,
+            testing_port_range: None,
         }
     }
 }
@@ -385,6 +515,33 @@ struct ConfigToml {
     base_port: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
     base_testing_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    socket_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    testing_port_range: Option<PortRange>,
+}
+
+/// `PGX_SOCKET_ONLY`, if it's set
+fn socket_only_from_env() -> eyre::Result<Option<bool>> {
+    match std::env::var("PGX_SOCKET_ONLY") {
+        Ok(value) => match value.as_str() {
+            "true" | "1" => Ok(Some(true)),
+            "false" | "0" | "" => Ok(Some(false)),
+            other => Err(eyre!("`PGX_SOCKET_ONLY` should be `true` or `false`, not `{}`", other)),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// `PGX_TEST_PORT_RANGE`, if it's set
+fn testing_port_range_from_env() -> eyre::Result<Option<PortRange>> {
+    match std::env::var("PGX_TEST_PORT_RANGE") {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .wrap_err("Could not parse the `PGX_TEST_PORT_RANGE` environment variable"),
+        Err(_) => Ok(None),
+    }
 }
 
 pub enum PgConfigSelector<'a> {
@@ -405,7 +562,7 @@ impl<'a> PgConfigSelector<'a> {
 
 impl Pgx {
     pub fn new(base_port: u16, base_testing_port: u16) -> Self {
-        Pgx { pg_configs: vec![], base_port, base_testing_port }
+        Pgx { pg_configs: vec![], base_port, base_testing_port, ..Default::default() }
     }
 
     pub fn from_config() -> eyre::Result<Self> {
@@ -413,6 +570,8 @@ impl Pgx {
             Ok(pg_config) => {
                 // we have an environment variable that tells us the pg_config to use
                 let mut pgx = Pgx::default();
+                pgx.socket_only = socket_only_from_env()?.unwrap_or(false);
+                pgx.testing_port_range = testing_port_range_from_env()?;
                 pgx.push(PgConfig::new(pg_config.into(), pgx.base_port, pgx.base_testing_port));
                 Ok(pgx)
             }
@@ -433,6 +592,10 @@ impl Pgx {
                             configs.base_port.unwrap_or(BASE_POSTGRES_PORT_NO),
                             configs.base_testing_port.unwrap_or(BASE_POSTGRES_TESTING_PORT_NO),
                         );
+                        pgx.socket_only =
+                            socket_only_from_env()?.or(configs.socket_only).unwrap_or(false);
+                        pgx.testing_port_range =
+                            testing_port_range_from_env()?.or(configs.testing_port_range);
 
                         for (_, v) in configs.configs {
                             pgx.push(PgConfig::new(v, pgx.base_port, pgx.base_testing_port));
@@ -447,7 +610,10 @@ impl Pgx {
         }
     }
 
-    pub fn push(&mut self, pg_config: PgConfig) {
+    /// Adds `pg_config`, which then shares this [`Pgx`]'s socket-only mode and testing port range
+    pub fn push(&mut self, mut pg_config: PgConfig) {
+        pg_config.socket_only = self.socket_only;
+        pg_config.testing_port_range = self.testing_port_range;
         self.pg_configs.push(pg_config);
     }
 
@@ -551,6 +717,18 @@ impl Pgx {
 
 pub const SUPPORTED_MAJOR_VERSIONS: &[u16] = &[11, 12, 13, 14, 15];
 
+/// Move the log file at `path` aside, to `path` with `.1` appended, replacing whichever log was
+/// moved there the last time, so that the next log written to `path` starts out empty
+pub fn rotate_log_file(path: &Path) -> eyre::Result<()> {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    match std::fs::rename(path, &rotated) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).wrap_err_with(|| format!("couldn't rotate `{}`", path.display())),
+    }
+}
+
 pub fn createdb(
     pg_config: &PgConfig,
     dbname: &str,
@@ -569,7 +747,7 @@ pub fn createdb(
         .env_remove("PGPORT")
         .env_remove("PGUSER")
         .arg("-h")
-        .arg(pg_config.host())
+        .arg(pg_config.host()?)
         .arg("-p")
         .arg(if is_test {
             pg_config.test_port()?.to_string()
@@ -610,7 +788,7 @@ fn does_db_exist(pg_config: &PgConfig, dbname: &str) -> eyre::Result<bool> {
         .arg("-XqAt")
         .env_remove("PGUSER")
         .arg("-h")
-        .arg(pg_config.host())
+        .arg(pg_config.host()?)
         .arg("-p")
         .arg(pg_config.port()?.to_string())
         .arg("template1")
@@ -672,6 +850,20 @@ fn parse_version() {
         PgConfig::parse_version_str("PostgresSQL .53").expect_err("Parsed invalid version string");
 }
 
+#[test]
+fn parse_port_range() -> eyre::Result<()> {
+    let range = PortRange::from_str("40000-40003")?;
+    assert_eq!((range.start(), range.end()), (40000, 40003));
+    assert_eq!(range.to_string(), "40000-40003");
+    assert_eq!(range.ports_from(40002).collect::<Vec<_>>(), vec![40002, 40003, 40000, 40001]);
+    assert_eq!(range.ports_from(32215).collect::<Vec<_>>(), vec![40000, 40001, 40002, 40003]);
+
+    assert!(PortRange::from_str("40000").is_err());
+    assert!(PortRange::from_str("40003-40000").is_err());
+    assert!(PortRange::from_str("40000-70000").is_err());
+    Ok(())
+}
+
 #[test]
 fn from_empty_env() -> eyre::Result<()> {
     // without "PGX_PG_CONFIG_AS_ENV" we can't get one of these
@@ -68,6 +68,41 @@ impl Datum {
         #[allow(unstable_name_collisions)]
         self.0.cast()
     }
+
+    /// A `smallint`, sign-extended as `Int16GetDatum()` does, so `DatumGetInt16()` gets it back
+    #[inline]
+    pub fn from_i16(val: i16) -> Datum {
+        Datum::from(isize::from(val))
+    }
+
+    /// A `bool`, as `BoolGetDatum()` makes it: `1` for true and `0` for false
+    #[inline]
+    pub fn from_bool(val: bool) -> Datum {
+        Datum::from(usize::from(val))
+    }
+
+    /// A `"char"`, sign-extended as `CharGetDatum()` does, so `DatumGetChar()` gets it back
+    #[inline]
+    pub fn from_char(val: i8) -> Datum {
+        Datum::from(isize::from(val))
+    }
+
+    /// A pointer to a pass-by-reference value, as `PointerGetDatum()` makes it
+    ///
+    /// With debug assertions, a non-null `ptr` which isn't aligned for a `T` panics, as Postgres
+    /// would misread what it points to.
+    #[inline]
+    pub fn from_pointer<T>(ptr: *const T) -> Datum {
+        #[allow(unstable_name_collisions)]
+        let addr = ptr.addr();
+        debug_assert!(
+            addr % core::mem::align_of::<T>() == 0,
+            "pointer {:#x} isn't aligned for a `{}`",
+            addr,
+            core::any::type_name::<T>()
+        );
+        Datum::from(ptr)
+    }
 }
 
 impl From<usize> for Datum {
@@ -240,4 +275,35 @@ mod test {
         let datum = Datum::from(val);
         assert_eq!(datum.value() as usize, val);
     }
+
+    #[test]
+    fn roundtrip_small_values() {
+        for val in [i16::MIN, -1, 0, 1, i16::MAX] {
+            assert_eq!(Datum::from_i16(val).value() as i16, val);
+        }
+        for val in [i8::MIN, -1, 0, b'x' as i8, i8::MAX] {
+            assert_eq!(Datum::from_char(val).value() as i8, val);
+        }
+        assert_eq!(Datum::from_bool(true).value(), 1);
+        assert_eq!(Datum::from_bool(false).value(), 0);
+    }
+
+    #[test]
+    fn pointers() {
+        let val = 42u64;
+        assert_eq!(
+            Datum::from_pointer(&val as *const u64).cast_mut_ptr::<u64>(),
+            &val as *const _ as *mut _
+        );
+        assert!(Datum::from_pointer(core::ptr::null::<u64>()).is_null());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "isn't aligned for a `u64`")]
+    fn misaligned_pointer() {
+        let bytes = [0u64; 2];
+        let misaligned = (bytes.as_ptr() as *const u8).wrapping_add(1) as *const u64;
+        Datum::from_pointer(misaligned);
+    }
 }
@@ -157,6 +157,11 @@ impl ErrorReportWithLevel {
         self.inner.sqlerrcode
     }
 
+    /// Returns the [ErrorReport] itself, without its level
+    pub fn into_error_report(self) -> ErrorReport {
+        self.inner
+    }
+
     /// Returns the error message of this error report
     pub fn message(&self) -> &str {
         self.inner.message()
@@ -235,6 +240,11 @@ impl ErrorReport {
         self
     }
 
+    /// Returns the sql error code of this error report
+    pub fn sql_error_code(&self) -> PgSqlErrorCode {
+        self.sqlerrcode
+    }
+
     /// Returns the error message of this error report
     pub fn message(&self) -> &str {
         &self.message
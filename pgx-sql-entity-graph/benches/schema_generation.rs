@@ -0,0 +1,142 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! How long it takes to build the graph and render the SQL of a synthetic extension, with many
+//! functions spread over a few schemas.
+//!
+//! Run with `cargo bench -p pgx-sql-entity-graph`, and `FUNCTIONS=n` to change the number of
+//! functions, 1000 by default.
+use pgx_sql_entity_graph::metadata::{
+    FunctionMetadataEntity, FunctionMetadataTypeEntity, Returns, SqlMapping,
+};
+use pgx_sql_entity_graph::{
+    ControlFile, ExternArgs, PgExternArgumentEntity, PgExternEntity, PgExternReturnEntity, PgxSql,
+    Privileges, SchemaEntity, SqlGraphEntity, ToSqlConfigEntity, UsedTypeEntity,
+};
+use std::any::TypeId;
+use std::time::{Duration, Instant};
+
+const SCHEMAS: usize = 10;
+const ITERATIONS: u32 = 10;
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+fn used_type<T: 'static>(rust: &'static str, sql: &str) -> UsedTypeEntity {
+    UsedTypeEntity {
+        ty_source: rust,
+        ty_id: TypeId::of::<T>(),
+        full_path: rust,
+        module_path: String::new(),
+        composite_type: None,
+        variadic: false,
+        default: None,
+        optional: false,
+        metadata: FunctionMetadataTypeEntity {
+            type_name: rust,
+            argument_sql: Ok(SqlMapping::As(sql.to_string())),
+            return_sql: Ok(Returns::One(SqlMapping::As(sql.to_string()))),
+            variadic: false,
+            optional: false,
+        },
+    }
+}
+
+fn entities(functions: usize) -> Vec<SqlGraphEntity> {
+    let mut entities = vec![SqlGraphEntity::ExtensionRoot(ControlFile {
+        comment: String::from("a synthetic extension"),
+        default_version: String::from("1.0"),
+        module_pathname: None,
+        relocatable: false,
+        superuser: true,
+        schema: Some(String::from("bench")),
+        privileges: Privileges::default(),
+        internal_functions: false,
+        safe_search_path: false,
+    })];
+    for schema in 0..SCHEMAS {
+        entities.push(SqlGraphEntity::Schema(SchemaEntity {
+            module_path: leak(format!("bench::schema_{}", schema)),
+            name: leak(format!("schema_{}", schema)),
+            file: "bench.rs",
+            line: 1,
+            privileges: Privileges::default(),
+        }));
+    }
+
+    for function in 0..functions {
+        let module_path = leak(format!("bench::schema_{}", function % SCHEMAS));
+        let name = leak(format!("function_{}", function));
+        let args = vec![used_type::<i32>("i32", "INT"), used_type::<String>("String", "TEXT")];
+        let ret = used_type::<i64>("i64", "bigint");
+        entities.push(SqlGraphEntity::Function(PgExternEntity {
+            name,
+            unaliased_name: name,
+            module_path,
+            full_path: leak(format!("{}::{}", module_path, name)),
+            metadata: FunctionMetadataEntity {
+                arguments: args.iter().map(|arg| arg.metadata.clone()).collect(),
+                retval: Some(ret.metadata.clone()),
+                path: name,
+            },
+            fn_args: args
+                .into_iter()
+                .zip(["a", "b"])
+                .map(|(used_ty, pattern)| PgExternArgumentEntity { pattern, used_ty })
+                .collect(),
+            fn_return: PgExternReturnEntity::Type { ty: ret },
+            schema: None,
+            file: "bench.rs",
+            line: function as u32,
+            extern_attrs: vec![ExternArgs::Immutable, ExternArgs::ParallelSafe],
+            settings: vec![],
+            operator: None,
+            to_sql_config: ToSqlConfigEntity {
+                enabled: true,
+                callback: None,
+                content: None,
+                pg_version: None,
+            },
+            facts: Vec::new(),
+        }));
+    }
+    entities
+}
+
+fn main() -> eyre::Result<()> {
+    let functions = std::env::var("FUNCTIONS")
+        .ok()
+        .and_then(|functions| functions.parse().ok())
+        .unwrap_or(1000);
+    let entities = entities(functions);
+
+    let mut build = Duration::ZERO;
+    let mut render = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let pgx_sql =
+            PgxSql::build(entities.clone().into_iter(), String::from("bench"), false, 15)?;
+        build += start.elapsed();
+
+        let start = Instant::now();
+        let sql = pgx_sql.to_sql()?;
+        render += start.elapsed();
+        assert!(sql.contains(&format!("\"function_{}\"", functions - 1)));
+    }
+
+    println!(
+        "{} functions:  build {:?}, to_sql {:?} (mean of {} iterations)",
+        functions,
+        build / ITERATIONS,
+        render / ITERATIONS,
+        ITERATIONS
+    );
+    Ok(())
+}
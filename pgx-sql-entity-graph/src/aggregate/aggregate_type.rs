@@ -15,7 +15,7 @@ to the `pgx` framework and very subject to change between versions. While you ma
 
 */
 use super::get_pgx_attr_macro;
-use crate::pg_extern::NameMacro;
+use crate::NameMacro;
 use crate::UsedType;
 
 use proc_macro2::TokenStream as TokenStream2;
@@ -20,7 +20,7 @@ use crate::metadata::SqlMapping;
 use crate::pgx_sql::PgxSql;
 use crate::to_sql::entity::ToSqlConfigEntity;
 use crate::to_sql::ToSql;
-use crate::{SqlGraphEntity, SqlGraphIdentifier, UsedTypeEntity};
+use crate::{Privileges, SqlGraphEntity, SqlGraphIdentifier, UsedTypeEntity};
 use core::any::TypeId;
 use eyre::{eyre, WrapErr};
 
@@ -144,6 +144,8 @@ pub struct PgAggregateEntity {
     /// Corresponds to `hypothetical` in [`pgx::aggregate::Aggregate`].
     pub hypothetical: bool,
     pub to_sql_config: ToSqlConfigEntity,
+    /// From the `owner` and `grant` options of `#[pgx(..)]`
+    pub privileges: Privileges,
 }
 
 impl From<PgAggregateEntity> for SqlGraphEntity {
@@ -288,6 +290,25 @@ impl ToSql for PgAggregateEntity {
         };
 
         let stype_sql = map_ty(&self.stype.used_ty).wrap_err("Mapping state type")?;
+        // with a safe `search_path`, the state type's own schema, as it needn't be the aggregate's,
+        // or be the extension's at all, and a composite type is qualified by the schema that
+        // declares it
+        let (stype_schema, stype_sql) = match self.stype.used_ty.metadata.argument_sql {
+            _ if !context.control.safe_search_path => (schema.clone(), stype_sql),
+            Ok(SqlMapping::Composite { array_brackets }) => {
+                let composite_type = self.stype.used_ty.composite_type.unwrap_or_default();
+                let composite_type = context.composite_type_sql(composite_type, &schema);
+                let brackets = if array_brackets { "[]" } else { "" };
+                (String::new(), format!("{composite_type}{brackets}"))
+            }
+            _ => (
+                context
+                    .type_index_of(&self.stype.used_ty.ty_id, self.stype.used_ty.full_path)
+                    .map(|index| context.schema_prefix_for(&index))
+                    .unwrap_or_default(),
+                stype_sql,
+            ),
+        };
 
         if let Some(value) = &self.mstype {
             let mstype_sql = map_ty(&value).wrap_err("Mapping moving state type")?;
@@ -316,7 +337,7 @@ impl ToSql for PgAggregateEntity {
                 CREATE AGGREGATE {schema}{name} ({direct_args}{maybe_order_by}{args})\n\
                 (\n\
                     \tSFUNC = {schema}\"{sfunc}\", /* {full_path}::state */\n\
-                    \tSTYPE = {schema}{stype}{maybe_comma_after_stype} /* {stype_full_path} */\
+                    \tSTYPE = {stype_schema}{stype}{maybe_comma_after_stype} /* {stype_full_path} */\
                     {optional_attributes}\
                 );\
             ",
@@ -327,22 +348,14 @@ impl ToSql for PgAggregateEntity {
             line = self.line,
             sfunc = self.sfunc,
             stype = stype_sql,
+            stype_schema = stype_schema,
             stype_full_path = self.stype.used_ty.full_path,
             maybe_comma_after_stype = if optional_attributes.len() == 0 { "" } else { "," },
             args = {
                 let mut args = Vec::new();
                 for (idx, arg) in self.args.iter().enumerate() {
                     let graph_index = context
-                        .graph
-                        .neighbors_undirected(self_index)
-                        .find(|neighbor| match &context.graph[*neighbor] {
-                            SqlGraphEntity::Type(ty) => ty.id_matches(&arg.used_ty.ty_id),
-                            SqlGraphEntity::Enum(en) => en.id_matches(&arg.used_ty.ty_id),
-                            SqlGraphEntity::BuiltinType(defined) => {
-                                defined == &arg.used_ty.full_path
-                            }
-                            _ => false,
-                        })
+                        .type_index_of(&arg.used_ty.ty_id, arg.used_ty.full_path)
                         .ok_or_else(|| {
                             eyre!("Could not find arg type in graph. Got: {:?}", arg.used_ty)
                         })?;
@@ -418,16 +431,7 @@ impl ToSql for PgAggregateEntity {
                 let mut args = Vec::new();
                 for (idx, arg) in direct_args.iter().enumerate() {
                     let graph_index = context
-                        .graph
-                        .neighbors_undirected(self_index)
-                        .find(|neighbor| match &context.graph[*neighbor] {
-                            SqlGraphEntity::Type(ty) => ty.id_matches(&arg.used_ty.ty_id),
-                            SqlGraphEntity::Enum(en) => en.id_matches(&arg.used_ty.ty_id),
-                            SqlGraphEntity::BuiltinType(defined) => {
-                                defined == &arg.used_ty.full_path
-                            }
-                            _ => false,
-                        })
+                        .type_index_of(&arg.used_ty.ty_id, arg.used_ty.full_path)
                         .ok_or_else(|| eyre!("Could not find arg type in graph. Got: {:?}", arg))?;
                     let needs_comma = idx < (direct_args.len() - 1);
                     let buf = format!(
@@ -457,6 +461,48 @@ impl ToSql for PgAggregateEntity {
                 + if optional_attributes.len() == 0 { "" } else { "\n" },
         );
         tracing::trace!(%sql);
-        Ok(sql)
+
+        // `ALTER AGGREGATE` separates an ordered-set aggregate's direct arguments from the others,
+        // where `GRANT .. ON FUNCTION` lists them all
+        let signature_ty = |used_ty: &UsedTypeEntity| -> eyre::Result<String> {
+            let graph_index = context
+                .type_index_of(&used_ty.ty_id, used_ty.full_path)
+                .ok_or_else(|| eyre!("Could not find arg type in graph. Got: {:?}", used_ty))?;
+            Ok(format!(
+                "{}{}{}",
+                if used_ty.variadic { "VARIADIC " } else { "" },
+                context.schema_prefix_for(&graph_index),
+                map_ty(used_ty)?
+            ))
+        };
+        let args = self
+            .args
+            .iter()
+            .map(|arg| signature_ty(&arg.used_ty))
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let direct_args = self
+            .direct_args
+            .iter()
+            .flatten()
+            .map(|arg| signature_ty(&arg.used_ty))
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let privileges = self.privileges.or(&context.control.privileges);
+        let alter_signature = if self.ordered_set && direct_args.is_empty() {
+            format!("ORDER BY {}", args.join(", "))
+        } else if self.ordered_set {
+            format!("{} ORDER BY {}", direct_args.join(", "), args.join(", "))
+        } else if args.is_empty() {
+            String::from("*")
+        } else {
+            args.join(", ")
+        };
+        let owner_sql = privileges
+            .owner_sql("AGGREGATE", &format!("{}{}({})", schema, self.name, alter_signature));
+        let grants_sql = privileges.grants_sql(
+            "FUNCTION",
+            "EXECUTE",
+            &format!("{}{}({})", schema, self.name, [direct_args, args].concat().join(", ")),
+        );
+        Ok(sql + &owner_sql + &grants_sql)
     }
 }
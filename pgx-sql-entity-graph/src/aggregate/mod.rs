@@ -34,7 +34,7 @@ use syn::{
     parse_quote, Expr, ImplItemConst, ImplItemMethod, ImplItemType, ItemFn, ItemImpl, Path, Type,
 };
 
-use crate::ToSqlConfig;
+use crate::{Privileges, ToSqlConfig};
 
 use super::UsedType;
 
@@ -104,12 +104,14 @@ pub struct PgAggregate {
     fn_moving_finalize: Option<Ident>,
     hypothetical: bool,
     to_sql_config: ToSqlConfig,
+    privileges: Privileges,
 }
 
 impl PgAggregate {
     pub fn new(mut item_impl: ItemImpl) -> Result<CodeEnrichment<Self>, syn::Error> {
         let to_sql_config =
             ToSqlConfig::from_attributes(item_impl.attrs.as_slice())?.unwrap_or_default();
+        let privileges = Privileges::from_attributes(item_impl.attrs.as_slice())?;
         let target_path = get_target_path(&item_impl)?;
         let target_ident = get_target_ident(&target_path)?;
 
@@ -179,6 +181,7 @@ impl PgAggregate {
             remap_self_to_target(&mut remapped, &target_ident);
             remapped
         };
+        let internal_state = is_aggregate_internal_state(&type_state_without_self);
         let type_stype = AggregateType {
             used_ty: UsedType::new(type_state_without_self.clone())?,
             name: Some("state".into()),
@@ -317,13 +320,16 @@ impl PgAggregate {
             let fn_name =
                 Ident::new(&format!("{}_combine", snake_case_target_ident), found.sig.ident.span());
             let pg_extern_attr = pg_extern_attr(found);
+            // A combined `AggregateInternalState` may be the one `deserial` made, which must outlive it
+            let into_aggregate_context =
+                internal_state.then(|| quote! { .into_aggregate_context(fcinfo) });
             pg_externs.push(parse_quote! {
                 #[allow(non_snake_case, clippy::too_many_arguments)]
                 #pg_extern_attr
                 fn #fn_name(this: #type_state_without_self, v: #type_state_without_self, fcinfo: ::pgx::pg_sys::FunctionCallInfo) -> #type_state_without_self {
                     <#target_path as ::pgx::aggregate::Aggregate>::in_memory_context(
                         fcinfo,
-                        move |_context| <#target_path as ::pgx::aggregate::Aggregate>::combine(this, v, fcinfo)
+                        move |_context| <#target_path as ::pgx::aggregate::Aggregate>::combine(this, v, fcinfo) #into_aggregate_context
                     )
                 }
             });
@@ -410,16 +416,36 @@ impl PgAggregate {
                 found.sig.ident.span(),
             );
             let pg_extern_attr = pg_extern_attr(found);
-            pg_externs.push(parse_quote! {
-                #[allow(non_snake_case, clippy::too_many_arguments)]
-                #pg_extern_attr
-                fn #fn_name(this: #type_state_without_self, buf: Vec<u8>, internal: ::pgx::pgbox::PgBox<#type_state_without_self>, fcinfo: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pgbox::PgBox<#type_state_without_self> {
-                    <#target_path as ::pgx::aggregate::Aggregate>::in_memory_context(
-                        fcinfo,
-                        move |_context| <#target_path as ::pgx::aggregate::Aggregate>::deserial(this, buf, internal, fcinfo)
-                    )
-                }
-            });
+            if internal_state {
+                // Postgres calls it as `deserial(bytea, internal) RETURNS internal`
+                pg_externs.push(parse_quote! {
+                    #[allow(non_snake_case, clippy::too_many_arguments)]
+                    #pg_extern_attr
+                    fn #fn_name(buf: Vec<u8>, _internal: ::pgx::datum::Internal, fcinfo: ::pgx::pg_sys::FunctionCallInfo) -> #type_state_without_self {
+                        <#target_path as ::pgx::aggregate::Aggregate>::in_memory_context(
+                            fcinfo,
+                            move |_context| {
+                                let mut state = <#type_state_without_self as ::core::default::Default>::default();
+                                // SAFETY: `state` outlives the box, which doesn't free what it points to
+                                let internal = unsafe { ::pgx::pgbox::PgBox::from_pg(&mut state) };
+                                let mut deserialized = <#target_path as ::pgx::aggregate::Aggregate>::deserial(::core::default::Default::default(), buf, internal, fcinfo);
+                                ::core::mem::take(&mut *deserialized)
+                            }
+                        )
+                    }
+                });
+            } else {
+                pg_externs.push(parse_quote! {
+                    #[allow(non_snake_case, clippy::too_many_arguments)]
+                    #pg_extern_attr
+                    fn #fn_name(this: #type_state_without_self, buf: Vec<u8>, internal: ::pgx::pgbox::PgBox<#type_state_without_self>, fcinfo: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pgbox::PgBox<#type_state_without_self> {
+                        <#target_path as ::pgx::aggregate::Aggregate>::in_memory_context(
+                            fcinfo,
+                            move |_context| <#target_path as ::pgx::aggregate::Aggregate>::deserial(this, buf, internal, fcinfo)
+                        )
+                    }
+                });
+            }
             Some(fn_name)
         } else {
             item_impl.items.push(parse_quote! {
@@ -590,6 +616,7 @@ impl PgAggregate {
                 false
             },
             to_sql_config,
+            privileges,
         }))
     }
 }
@@ -628,10 +655,13 @@ impl ToEntityGraphTokens for PgAggregate {
         let fn_moving_state_iter = self.fn_moving_state.iter();
         let fn_moving_state_inverse_iter = self.fn_moving_state_inverse.iter();
         let fn_moving_finalize_iter = self.fn_moving_finalize.iter();
+        let sql_graph_entity_fn_symbol =
+            crate::entity_symbol_tokens("aggregate", &snake_case_target_ident);
         let to_sql_config = &self.to_sql_config;
+        let privileges = &self.privileges;
 
         quote! {
-            #[no_mangle]
+            #[export_name = #sql_graph_entity_fn_symbol]
             #[doc(hidden)]
             pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
                 let submission = ::pgx::pgx_sql_entity_graph::PgAggregateEntity {
@@ -662,6 +692,7 @@ impl ToEntityGraphTokens for PgAggregate {
                     parallel: None #( .unwrap_or(#const_parallel_iter) )*,
                     hypothetical: #hypothetical,
                     to_sql_config: #to_sql_config,
+                    privileges: #privileges,
                 };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Aggregate(submission)
             }
@@ -671,7 +702,15 @@ impl ToEntityGraphTokens for PgAggregate {
 
 impl ToRustCodeTokens for PgAggregate {
     fn to_rust_code_tokens(&self) -> TokenStream2 {
-        let impl_item = &self.item_impl;
+        let mut impl_item = self.item_impl.clone();
+        if crate::enrich::SQL_GENERATION_ONLY {
+            for item in impl_item.items.iter_mut() {
+                if let syn::ImplItem::Method(method) = item {
+                    method.attrs.push(parse_quote! { #[allow(unused_variables)] });
+                    method.block = crate::enrich::sql_generation_stub_block();
+                }
+            }
+        }
         let pg_externs = self.pg_externs.iter();
         quote! {
             #impl_item
@@ -847,6 +886,21 @@ fn get_const_litstr<'a>(item: &'a ImplItemConst) -> syn::Result<Option<String>>
     }
 }
 
+/// Is the `State` a `pgx::aggregate::AggregateInternalState<T>`?
+///
+/// We don't actually have type resolution here, this is a "Best guess".
+fn is_aggregate_internal_state(ty: &syn::Type) -> bool {
+    match ty {
+        Type::Path(ty_path) => ty_path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident == "AggregateInternalState")
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
 fn remap_self_to_target(ty: &mut syn::Type, target: &syn::Ident) {
     match ty {
         Type::Path(ref mut ty_path) => {
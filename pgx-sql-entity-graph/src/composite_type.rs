@@ -0,0 +1,152 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+The names given to `composite_type!()`, which may be qualified by a schema, for Rust to SQL
+translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use std::fmt::{Display, Formatter};
+
+/// A composite type's name, like `dog`, `animals.dog`, or `"Animals"."Dog"[]`, split into its
+/// schema, its name, and any array brackets
+///
+/// Each part is kept as it was written, quotes and all, so it renders the same.  Parts are
+/// compared the way Postgres compares identifiers:  unquoted ones ignoring case, quoted ones
+/// exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompositeTypeName {
+    pub schema: Option<String>,
+    pub name: String,
+    pub array_brackets: usize,
+}
+
+impl CompositeTypeName {
+    pub fn parse(composite_type: &str) -> Self {
+        let mut rest = composite_type.trim();
+        let mut array_brackets = 0;
+        while let Some(stripped) = rest.strip_suffix("[]") {
+            rest = stripped.trim_end();
+            array_brackets += 1;
+        }
+
+        let mut parts = Vec::new();
+        let mut part = String::new();
+        let mut quoted = false;
+        for c in rest.chars() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    part.push(c);
+                }
+                '.' if !quoted => parts.push(std::mem::take(&mut part).trim().to_string()),
+                _ => part.push(c),
+            }
+        }
+        parts.push(part.trim().to_string());
+
+        // a name qualified by its database too only needs its schema
+        let name = parts.pop().unwrap_or_default();
+        let schema = parts.pop();
+        CompositeTypeName { schema, name, array_brackets }
+    }
+
+    pub fn is_qualified(&self) -> bool {
+        self.schema.is_some()
+    }
+
+    /// Is this the record pseudo-type, which isn't declared anywhere?
+    pub fn is_record(&self) -> bool {
+        self.schema.is_none() && same_identifier(&self.name, "record")
+    }
+
+    /// Do `self` and `other` name the same type, if they're in the same schema?
+    pub fn same_name(&self, other: &CompositeTypeName) -> bool {
+        same_identifier(&self.name, &other.name)
+    }
+
+    /// Is this in `schema`?
+    pub fn in_schema(&self, schema: &str) -> bool {
+        match &self.schema {
+            Some(own) => same_identifier(own, schema),
+            None => false,
+        }
+    }
+
+    /// The same name, qualified by `schema`, or left bare if `schema` is `None` or empty
+    pub fn qualified_by(&self, schema: Option<&str>) -> CompositeTypeName {
+        let schema = schema.map(|schema| schema.trim_end_matches('.')).filter(|s| !s.is_empty());
+        CompositeTypeName {
+            schema: schema.map(String::from),
+            name: self.name.clone(),
+            array_brackets: self.array_brackets,
+        }
+    }
+}
+
+impl Display for CompositeTypeName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(schema) = &self.schema {
+            write!(f, "{}.", schema)?;
+        }
+        write!(f, "{}{}", self.name, "[]".repeat(self.array_brackets))
+    }
+}
+
+/// Compare identifiers the way Postgres does:  a quoted one exactly, without its quotes, and an
+/// unquoted one folded to lower case
+pub(crate) fn same_identifier(a: &str, b: &str) -> bool {
+    fn normalize(ident: &str) -> String {
+        match ident.strip_prefix('"').and_then(|ident| ident.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\"\"", "\""),
+            None => ident.to_lowercase(),
+        }
+    }
+    normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompositeTypeName;
+
+    #[test]
+    fn parses_qualified_names() {
+        let bare = CompositeTypeName::parse("Dog");
+        assert_eq!(bare.schema, None);
+        assert_eq!(bare.to_string(), "Dog");
+
+        let qualified = CompositeTypeName::parse(" animals . dog [] ");
+        assert_eq!(qualified.schema.as_deref(), Some("animals"));
+        assert_eq!(qualified.array_brackets, 1);
+        assert_eq!(qualified.to_string(), "animals.dog[]");
+
+        let quoted = CompositeTypeName::parse("\"My.Animals\".\"Dog\"");
+        assert_eq!(quoted.schema.as_deref(), Some("\"My.Animals\""));
+        assert_eq!(quoted.to_string(), "\"My.Animals\".\"Dog\"");
+    }
+
+    #[test]
+    fn compares_like_postgres() {
+        let dog = CompositeTypeName::parse("animals.dog");
+        assert!(dog.same_name(&CompositeTypeName::parse("DOG")));
+        assert!(dog.same_name(&CompositeTypeName::parse("\"dog\"")));
+        assert!(!dog.same_name(&CompositeTypeName::parse("\"Dog\"")));
+        assert!(dog.in_schema("Animals"));
+        assert!(!dog.in_schema("\"Animals\""));
+        assert!(CompositeTypeName::parse("RECORD").is_record());
+        assert_eq!(
+            CompositeTypeName::parse("dog").qualified_by(Some("tests.")).to_string(),
+            "tests.dog"
+        );
+        assert_eq!(CompositeTypeName::parse("dog").qualified_by(Some("")).to_string(), "dog");
+    }
+}
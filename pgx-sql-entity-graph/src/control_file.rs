@@ -14,7 +14,7 @@ Use of this source code is governed by the MIT license that can be found in the
 to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
 
 */
-use super::{SqlGraphEntity, SqlGraphIdentifier, ToSql};
+use super::{Privileges, SqlGraphEntity, SqlGraphIdentifier, ToSql};
 use core::convert::TryFrom;
 use std::collections::HashMap;
 use tracing_error::SpanTrace;
@@ -38,6 +38,16 @@ pub struct ControlFile {
     pub relocatable: bool,
     pub superuser: bool,
     pub schema: Option<String>,
+    /// The owner and grants of the entities which don't give their own, from the options given
+    /// to `pg_module_magic!()`, rather than the `.control` file
+    pub privileges: Privileges,
+    /// Whether `#[pg_extern]` functions are `internal` unless they're marked `public`, from the
+    /// `functions = "internal"` option of `pg_module_magic!()`
+    pub internal_functions: bool,
+    /// Whether the script is run with `pg_catalog` first and `pg_temp` last on its `search_path`,
+    /// and names what it makes in the extension's schema as `@extschema@.name`, from the
+    /// `search_path = "safe"` option of `pg_module_magic!()`
+    pub safe_search_path: bool,
 }
 
 impl ControlFile {
@@ -93,6 +103,9 @@ impl ControlFile {
                 context: SpanTrace::capture(),
             })? == &"true",
             schema: temp.get("schema").map(|v| v.to_string()),
+            privileges: Privileges::default(),
+            internal_functions: false,
+            safe_search_path: false,
         })
     }
 }
@@ -132,9 +145,9 @@ impl TryFrom<&str> for ControlFile {
 }
 
 impl ToSql for ControlFile {
-    #[tracing::instrument(level = "debug", err, skip(self, _context))]
-    fn to_sql(&self, _context: &super::PgxSql) -> eyre::Result<String> {
-        let sql = format!(
+    #[tracing::instrument(level = "debug", err, skip(self, context))]
+    fn to_sql(&self, context: &super::PgxSql) -> eyre::Result<String> {
+        let mut sql = format!(
             "\
             /* \n\
             This file is auto generated by pgx.\n\
@@ -143,11 +156,39 @@ impl ToSql for ControlFile {
             */\
         "
         );
+        if self.safe_search_path {
+            sql.push_str(&format!(
+                "\n\n\
+                -- Search pg_catalog before the extension's schema, and never pg_temp first\n\
+                {};",
+                SAFE_SEARCH_PATH
+            ));
+        }
+        if context.externs.keys().any(|function| function.in_internal_schema(self)) {
+            sql.push_str(&format!(
+                "\n\n\
+                -- The extension's internal functions\n\
+                CREATE SCHEMA IF NOT EXISTS {schema};{privileges}",
+                schema = INTERNAL_SCHEMA,
+                privileges = self.privileges.to_sql("SCHEMA", "USAGE", INTERNAL_SCHEMA),
+            ));
+        }
         tracing::trace!(%sql);
         Ok(sql)
     }
 }
 
+/// Puts `pg_catalog` first and `pg_temp` last on the `search_path` the extension's script is run
+/// with, keeping the schemas Postgres put there, for the rest of the script.  `CREATE EXTENSION`
+/// and `ALTER EXTENSION .. UPDATE` reset it afterwards.
+pub const SAFE_SEARCH_PATH: &str = "SELECT pg_catalog.set_config('search_path', \
+    pg_catalog.concat_ws(', ', 'pg_catalog', pg_catalog.current_setting('search_path'), 'pg_temp'), \
+    true)";
+
+/// The schema of the extension's `internal` functions, which only its own SQL should call.
+/// Postgres replaces `@extschema@` in the scripts of extensions which aren't `relocatable`.
+pub const INTERNAL_SCHEMA: &str = "@extschema@_internal";
+
 impl SqlGraphIdentifier for ControlFile {
     fn dot_identifier(&self) -> String {
         format!("extension root")
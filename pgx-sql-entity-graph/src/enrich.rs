@@ -1,5 +1,6 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens, TokenStreamExt};
+use syn::parse_quote;
 
 pub struct CodeEnrichment<T>(pub T);
 
@@ -31,3 +32,67 @@ where
         tokens.append_all(self.0.to_rust_code_tokens());
     }
 }
+
+#[cfg(all(feature = "sql-generation", feature = "no-schema-generation"))]
+compile_error!("the `sql-generation` and `no-schema-generation` features can't both be enabled");
+
+/// Is the extension being built only so its schema can be generated, with the `sql-generation`
+/// feature?
+///
+/// Then its functions' bodies are replaced with stubs, and the wrappers Postgres would call them
+/// through aren't generated, so none of that needs compiling.  The entity graph only needs their
+/// signatures.  Nothing is exported for Postgres to call, either:  not the stubs, nor the magic
+/// block, without which Postgres refuses to load the library at all.
+pub(crate) const SQL_GENERATION_ONLY: bool = cfg!(feature = "sql-generation");
+
+/// `func`, with its body replaced by one which panics, for [`SQL_GENERATION_ONLY`] builds, and
+/// without any `#[no_mangle]` or `#[export_name]` which would export it
+pub(crate) fn sql_generation_stub(func: &syn::ItemFn) -> syn::ItemFn {
+    let mut stub = func.clone();
+    stub.attrs
+        .retain(|attr| !attr.path.is_ident("no_mangle") && !attr.path.is_ident("export_name"));
+    stub.attrs.push(parse_quote! { #[allow(unused_variables)] });
+    stub.block = Box::new(sql_generation_stub_block());
+    stub
+}
+
+/// A function body which panics, for [`SQL_GENERATION_ONLY`] builds
+pub(crate) fn sql_generation_stub_block() -> syn::Block {
+    parse_quote! {{
+        unimplemented!("this extension was built with the `sql-generation` feature, only to generate its schema")
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sql_generation_stub;
+    use quote::ToTokens;
+    use syn::parse_quote;
+
+    #[test]
+    fn stubs_are_not_exported() {
+        let func: syn::ItemFn = parse_quote! {
+            #[no_mangle]
+            #[doc = "adds"]
+            pub extern "C" fn add(a: i32, b: i32) -> i32 {
+                a + b
+            }
+        };
+        let stub = sql_generation_stub(&func);
+        assert!(!stub.attrs.iter().any(|attr| attr.path.is_ident("no_mangle")));
+        assert!(stub.attrs.iter().any(|attr| attr.path.is_ident("doc")));
+        assert_eq!(stub.sig, func.sig);
+        let body = stub.block.to_token_stream().to_string();
+        assert!(body.contains("unimplemented !"), "{}", body);
+        assert!(!body.contains("a + b"), "{}", body);
+
+        let func: syn::ItemFn = parse_quote! {
+            #[export_name = "sub"]
+            fn subtract(a: i32, b: i32) -> i32 { a - b }
+        };
+        assert!(!sql_generation_stub(&func)
+            .attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("export_name")));
+    }
+}
@@ -15,7 +15,8 @@ to the `pgx` framework and very subject to change between versions. While you ma
 
 
 */
-use crate::extension_sql::SqlDeclared;
+use crate::extension_sql::{ConfigDump, SqlDeclared, SqlObject};
+use crate::pg_version::PgVersionRange;
 use crate::pgx_sql::PgxSql;
 use crate::positioning_ref::PositioningRef;
 use crate::to_sql::ToSql;
@@ -35,13 +36,28 @@ pub struct ExtensionSqlEntity {
     pub bootstrap: bool,
     pub finalize: bool,
     pub requires: Vec<PositioningRef>,
+    /// Whether to require the entities the SQL uses, found by [`scan::references`], from its
+    /// `infer_requires` option
+    ///
+    /// [`scan::references`]: crate::extension_sql::scan::references
+    pub infer_requires: bool,
     pub creates: Vec<SqlDeclaredEntity>,
+    /// The SQL objects the block creates, from its `declares = [..]` option
+    pub declares: Vec<SqlObject>,
+    /// The declared tables whose rows `pg_dump` should dump, from the `config_dump = [..]` option
+    pub config_dump: Vec<ConfigDump>,
+    pub pg_version: Option<PgVersionRange>,
 }
 
 impl ExtensionSqlEntity {
     pub fn has_sql_declared_entity(&self, identifier: &SqlDeclared) -> Option<&SqlDeclaredEntity> {
         self.creates.iter().find(|created| created.has_sql_declared_entity(identifier))
     }
+
+    /// Does the block declare the composite type `composite_type!(name)` refers to?
+    pub fn declares_composite_type(&self, name: &str) -> bool {
+        self.declares.iter().any(|declared| declared.is_composite_type(name))
+    }
 }
 
 impl From<ExtensionSqlEntity> for SqlGraphEntity {
@@ -73,14 +89,22 @@ impl ToSql for ExtensionSqlEntity {
         let sql = format!(
             "\n\
                 -- {file}:{line}\n\
+                {pg_version}\
                 {bootstrap}\
                 {creates}\
+                {declares}\
                 {requires}\
+                {infer_requires}\
                 {finalize}\
                 {sql}\
+                {config_dump}\
                 ",
             file = self.file,
             line = self.line,
+            pg_version = match self.pg_version {
+                Some(range) => format!("-- pg_version: {}\n", range),
+                None => String::default(),
+            },
             bootstrap = if self.bootstrap { "-- bootstrap\n" } else { "" },
             creates = if !self.creates.is_empty() {
                 format!(
@@ -97,6 +121,21 @@ impl ToSql for ExtensionSqlEntity {
             } else {
                 "".to_string()
             },
+            declares = if !self.declares.is_empty() {
+                format!(
+                    "\
+                    -- declares:\n\
+                    {}\n\
+                ",
+                    self.declares
+                        .iter()
+                        .map(|i| format!("--   {}", i))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ) + "\n"
+            } else {
+                "".to_string()
+            },
             requires = if !self.requires.is_empty() {
                 format!(
                     "\
@@ -112,8 +151,14 @@ impl ToSql for ExtensionSqlEntity {
             } else {
                 "".to_string()
             },
+            infer_requires = if self.infer_requires { "-- infer_requires\n" } else { "" },
             finalize = if self.finalize { "-- finalize\n" } else { "" },
             sql = self.sql,
+            config_dump = self
+                .config_dump
+                .iter()
+                .map(|config_dump| format!("\n{}\n", config_dump.to_sql()))
+                .collect::<String>(),
         );
         tracing::trace!(%sql);
         Ok(sql)
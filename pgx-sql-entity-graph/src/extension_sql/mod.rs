@@ -16,12 +16,16 @@ to the `pgx` framework and very subject to change between versions. While you ma
 
 */
 pub mod entity;
+pub mod scan;
 
+use crate::composite_type::CompositeTypeName;
+use crate::pg_version::PgVersionRange;
 use crate::positioning_ref::PositioningRef;
 
 use crate::enrich::{CodeEnrichment, ToEntityGraphTokens, ToRustCodeTokens};
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
 use quote::{quote, ToTokens, TokenStreamExt};
+use std::fmt::Display;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::{LitStr, Token};
@@ -62,13 +66,23 @@ impl ToEntityGraphTokens for ExtensionSqlFile {
         let mut name = None;
         let mut bootstrap = false;
         let mut finalize = false;
+        let mut infer_requires = false;
         let mut requires = vec![];
         let mut creates = vec![];
+        let mut declares = vec![];
+        let mut config_dump = vec![];
+        let mut pg_version = None;
         for attr in &self.attrs {
             match attr {
                 ExtensionSqlAttribute::Creates(items) => {
                     creates.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
                 }
+                ExtensionSqlAttribute::Declares(items) => {
+                    declares.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
+                }
+                ExtensionSqlAttribute::ConfigDump(items) => {
+                    config_dump.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
+                }
                 ExtensionSqlAttribute::Requires(items) => {
                     requires.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
                 }
@@ -78,9 +92,15 @@ impl ToEntityGraphTokens for ExtensionSqlFile {
                 ExtensionSqlAttribute::Finalize => {
                     finalize = true;
                 }
+                ExtensionSqlAttribute::InferRequires => {
+                    infer_requires = true;
+                }
                 ExtensionSqlAttribute::Name(found_name) => {
                     name = Some(found_name.value());
                 }
+                ExtensionSqlAttribute::PgVersion(range) => {
+                    pg_version = Some(*range);
+                }
             }
         }
         let name = name.unwrap_or(
@@ -93,8 +113,10 @@ impl ToEntityGraphTokens for ExtensionSqlFile {
         );
         let requires_iter = requires.iter();
         let creates_iter = creates.iter();
-        let sql_graph_entity_fn_name =
-            syn::Ident::new(&format!("__pgx_internals_sql_{}", name.clone()), Span::call_site());
+        let declares_iter = declares.iter();
+        let config_dump_iter = config_dump.iter();
+        let sql_graph_entity_fn_name = sql_graph_entity_fn_name(&name, pg_version.as_ref());
+        let pg_version = pg_version_tokens(pg_version.as_ref());
         quote! {
             #[no_mangle]
             #[doc(hidden)]
@@ -112,7 +134,11 @@ impl ToEntityGraphTokens for ExtensionSqlFile {
                     bootstrap: #bootstrap,
                     finalize: #finalize,
                     requires: vec![#(#requires_iter),*],
+                    infer_requires: #infer_requires,
                     creates: vec![#(#creates_iter),*],
+                    declares: vec![#(#declares_iter),*],
+                    config_dump: vec![#(#config_dump_iter),*],
+                    pg_version: #pg_version,
                 };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::CustomSql(submission)
             }
@@ -124,9 +150,11 @@ impl ToRustCodeTokens for ExtensionSqlFile {}
 
 impl Parse for CodeEnrichment<ExtensionSqlFile> {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let span = input.span();
         let path = input.parse()?;
         let _after_sql_comma: Option<Token![,]> = input.parse()?;
         let attrs = input.parse_terminated(ExtensionSqlAttribute::parse)?;
+        check_config_dump(&attrs, span)?;
         Ok(CodeEnrichment(ExtensionSqlFile { path, attrs }))
     }
 }
@@ -167,8 +195,12 @@ impl ToEntityGraphTokens for ExtensionSql {
         let sql = &self.sql;
         let mut bootstrap = false;
         let mut finalize = false;
+        let mut infer_requires = false;
         let mut creates = vec![];
+        let mut declares = vec![];
+        let mut config_dump = vec![];
         let mut requires = vec![];
+        let mut pg_version = None;
         for attr in &self.attrs {
             match attr {
                 ExtensionSqlAttribute::Requires(items) => {
@@ -177,21 +209,35 @@ impl ToEntityGraphTokens for ExtensionSql {
                 ExtensionSqlAttribute::Creates(items) => {
                     creates.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
                 }
+                ExtensionSqlAttribute::Declares(items) => {
+                    declares.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
+                }
+                ExtensionSqlAttribute::ConfigDump(items) => {
+                    config_dump.append(&mut items.iter().map(|x| x.to_token_stream()).collect());
+                }
                 ExtensionSqlAttribute::Bootstrap => {
                     bootstrap = true;
                 }
                 ExtensionSqlAttribute::Finalize => {
                     finalize = true;
                 }
+                ExtensionSqlAttribute::InferRequires => {
+                    infer_requires = true;
+                }
                 ExtensionSqlAttribute::Name(_found_name) => (), // Already done
+                ExtensionSqlAttribute::PgVersion(range) => {
+                    pg_version = Some(*range);
+                }
             }
         }
         let requires_iter = requires.iter();
         let creates_iter = creates.iter();
+        let declares_iter = declares.iter();
+        let config_dump_iter = config_dump.iter();
         let name = &self.name;
 
-        let sql_graph_entity_fn_name =
-            syn::Ident::new(&format!("__pgx_internals_sql_{}", name.value()), Span::call_site());
+        let sql_graph_entity_fn_name = sql_graph_entity_fn_name(&name.value(), pg_version.as_ref());
+        let pg_version = pg_version_tokens(pg_version.as_ref());
         quote! {
             #[no_mangle]
             pub extern "Rust" fn  #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
@@ -208,7 +254,11 @@ impl ToEntityGraphTokens for ExtensionSql {
                     bootstrap: #bootstrap,
                     finalize: #finalize,
                     requires: vec![#(#requires_iter),*],
+                    infer_requires: #infer_requires,
                     creates: vec![#(#creates_iter),*],
+                    declares: vec![#(#declares_iter),*],
+                    config_dump: vec![#(#config_dump_iter),*],
+                    pg_version: #pg_version,
                 };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::CustomSql(submission)
             }
@@ -220,9 +270,11 @@ impl ToRustCodeTokens for ExtensionSql {}
 
 impl Parse for CodeEnrichment<ExtensionSql> {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let span = input.span();
         let sql = input.parse()?;
         let _after_sql_comma: Option<Token![,]> = input.parse()?;
         let attrs = input.parse_terminated(ExtensionSqlAttribute::parse)?;
+        check_config_dump(&attrs, span)?;
         let mut name = None;
         for attr in &attrs {
             match attr {
@@ -244,13 +296,71 @@ impl ToTokens for ExtensionSql {
     }
 }
 
+/// The name of the function returning an `extension_sql!()`'s entity.  Blocks limited to different
+/// Postgres versions may share a name, so one can stand in for another, and so their functions
+/// are told apart by their version range.
+fn sql_graph_entity_fn_name(name: &str, pg_version: Option<&PgVersionRange>) -> Ident {
+    let suffix = match pg_version {
+        None => String::new(),
+        Some(range) => format!(
+            "_pg{}_{}",
+            range.min.map(|min| min.to_string()).unwrap_or_default(),
+            range.max.map(|max| max.to_string()).unwrap_or_default()
+        ),
+    };
+    Ident::new(&format!("__pgx_internals_sql_{}{}", name, suffix), Span::call_site())
+}
+
+/// Ensure every table in a block's `config_dump = [..]` is one of the tables in its
+/// `declares = [..]`, so it's created before `pg_extension_config_dump()` marks it
+fn check_config_dump(
+    attrs: &Punctuated<ExtensionSqlAttribute, Token![,]>,
+    span: Span,
+) -> Result<(), syn::Error> {
+    let declared = attrs
+        .iter()
+        .filter_map(|attr| match attr {
+            ExtensionSqlAttribute::Declares(items) => Some(items.iter()),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    for attr in attrs {
+        if let ExtensionSqlAttribute::ConfigDump(items) = attr {
+            for config_dump in items {
+                if !declared.iter().any(|object| object.is_table(&config_dump.table)) {
+                    return Err(syn::Error::new(
+                        span,
+                        format!(
+                            "`config_dump` table `{}` must also be in this block's `declares = [Table({})]`",
+                            config_dump.table, config_dump.table
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn pg_version_tokens(pg_version: Option<&PgVersionRange>) -> TokenStream2 {
+    match pg_version {
+        Some(range) => quote! { Some(#range) },
+        None => quote! { None },
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ExtensionSqlAttribute {
     Requires(Punctuated<PositioningRef, Token![,]>),
     Creates(Punctuated<SqlDeclared, Token![,]>),
+    Declares(Punctuated<SqlObject, Token![,]>),
+    ConfigDump(Punctuated<ConfigDump, Token![,]>),
     Bootstrap,
     Finalize,
+    InferRequires,
     Name(LitStr),
+    PgVersion(PgVersionRange),
 }
 
 impl Parse for ExtensionSqlAttribute {
@@ -263,6 +373,18 @@ impl Parse for ExtensionSqlAttribute {
                 let _bracket = syn::bracketed!(content in input);
                 Self::Creates(content.parse_terminated(SqlDeclared::parse)?)
             }
+            "declares" => {
+                let _eq: syn::token::Eq = input.parse()?;
+                let content;
+                let _bracket = syn::bracketed!(content in input);
+                Self::Declares(content.parse_terminated(SqlObject::parse)?)
+            }
+            "config_dump" => {
+                let _eq: syn::token::Eq = input.parse()?;
+                let content;
+                let _bracket = syn::bracketed!(content in input);
+                Self::ConfigDump(content.parse_terminated(ConfigDump::parse)?)
+            }
             "requires" => {
                 let _eq: syn::token::Eq = input.parse()?;
                 let content;
@@ -271,10 +393,18 @@ impl Parse for ExtensionSqlAttribute {
             }
             "bootstrap" => Self::Bootstrap,
             "finalize" => Self::Finalize,
+            "infer_requires" => Self::InferRequires,
             "name" => {
                 let _eq: syn::token::Eq = input.parse()?;
                 Self::Name(input.parse()?)
             }
+            "pg_version" => {
+                let _eq: syn::token::Eq = input.parse()?;
+                let literal: LitStr = input.parse()?;
+                let range = PgVersionRange::parse(&literal.value())
+                    .map_err(|e| syn::Error::new(literal.span(), e))?;
+                Self::PgVersion(range)
+            }
             other => {
                 return Err(syn::Error::new(
                     ident.span(),
@@ -347,3 +477,189 @@ impl ToTokens for SqlDeclared {
         tokens.append_all(self.to_entity_graph_tokens())
     }
 }
+
+/// A SQL object an `extension_sql!()` block creates, from its `declares = [..]` option, like
+/// `Type(dog)`, `Table(dogs)`, or `Function("animals.bark")`
+///
+/// A `composite_type!()` is resolved to the block declaring a `Type` or `Table` of the same name,
+/// which is created before anything using it.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub enum SqlObject {
+    Type(String),
+    Table(String),
+    Function(String),
+}
+
+impl SqlObject {
+    pub fn name(&self) -> &str {
+        match self {
+            SqlObject::Type(name) | SqlObject::Table(name) | SqlObject::Function(name) => name,
+        }
+    }
+
+    /// Is this the composite type `composite_type!(name)` refers to?  A table's row type is one.
+    ///
+    /// Unquoted names are compared as Postgres folds them, ignoring case.  A name qualified by a
+    /// schema only matches one declared in the same schema, or declared without one.
+    pub fn is_composite_type(&self, name: &str) -> bool {
+        match self {
+            SqlObject::Type(declared) | SqlObject::Table(declared) => {
+                let declared = CompositeTypeName::parse(declared);
+                let used = CompositeTypeName::parse(name);
+                declared.same_name(&used)
+                    && match (&declared.schema, &used.schema) {
+                        (Some(declared_schema), Some(_)) => used.in_schema(declared_schema),
+                        _ => true,
+                    }
+            }
+            SqlObject::Function(_) => false,
+        }
+    }
+
+    /// Is this the table `name`?  Names are compared as in [`SqlObject::is_composite_type`].
+    pub fn is_table(&self, name: &str) -> bool {
+        matches!(self, SqlObject::Table(_)) && self.is_composite_type(name)
+    }
+}
+
+impl Display for SqlObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqlObject::Type(name) => write!(f, "Type({})", name),
+            SqlObject::Table(name) => write!(f, "Table({})", name),
+            SqlObject::Function(name) => write!(f, "Function({})", name),
+        }
+    }
+}
+
+impl Parse for SqlObject {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let variant: Ident = input.parse()?;
+        let content;
+        let _paren: syn::token::Paren = syn::parenthesized!(content in input);
+        let name = if content.peek(LitStr) {
+            content.parse::<LitStr>()?.value()
+        } else {
+            content.parse::<Ident>()?.to_string()
+        };
+        match variant.to_string().as_str() {
+            "Type" => Ok(SqlObject::Type(name)),
+            "Table" => Ok(SqlObject::Table(name)),
+            "Function" => Ok(SqlObject::Function(name)),
+            _ => Err(syn::Error::new(
+                variant.span(),
+                "declared SQL objects must be `Type(name)`, `Table(name)`, or `Function(name)`",
+            )),
+        }
+    }
+}
+
+impl ToTokens for SqlObject {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let variant = Ident::new(
+            match self {
+                SqlObject::Type(_) => "Type",
+                SqlObject::Table(_) => "Table",
+                SqlObject::Function(_) => "Function",
+            },
+            Span::call_site(),
+        );
+        let name = self.name();
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::SqlObject::#variant(String::from(#name))
+        });
+    }
+}
+
+/// A table whose rows `pg_dump` should dump, from an `extension_sql!()` block's
+/// `config_dump = [..]` option, like `settings` or `"myext.rules" = "WHERE NOT builtin"`
+///
+/// The rows of a table an extension creates are otherwise left out of dumps, as `CREATE EXTENSION`
+/// is expected to create them again.  The `filter`, a `WHERE` clause, dumps only the rows it
+/// matches, such as those which were added after the extension was created, and is empty to dump
+/// them all.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub struct ConfigDump {
+    pub table: String,
+    pub filter: String,
+}
+
+impl ConfigDump {
+    pub fn new(table: impl Into<String>, filter: impl Into<String>) -> Self {
+        ConfigDump { table: table.into(), filter: filter.into() }
+    }
+
+    /// The statement marking the table, after it's created
+    pub fn to_sql(&self) -> String {
+        format!(
+            "SELECT pg_catalog.pg_extension_config_dump('{}', '{}');",
+            self.table.replace('\'', "''"),
+            self.filter.replace('\'', "''"),
+        )
+    }
+}
+
+impl Parse for ConfigDump {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let table = if input.peek(LitStr) {
+            input.parse::<LitStr>()?.value()
+        } else {
+            input.parse::<Ident>()?.to_string()
+        };
+        let filter = if input.peek(Token![=]) {
+            let _eq: Token![=] = input.parse()?;
+            input.parse::<LitStr>()?.value()
+        } else {
+            String::new()
+        };
+        Ok(ConfigDump { table, filter })
+    }
+}
+
+impl ToTokens for ConfigDump {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let table = &self.table;
+        let filter = &self.filter;
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::ConfigDump::new(#table, #filter)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigDump, SqlObject};
+
+    #[test]
+    fn parses_declared_objects() {
+        let object: SqlObject = syn::parse_quote!(Type(Dog));
+        assert_eq!(object, SqlObject::Type("Dog".into()));
+        let object: SqlObject = syn::parse_quote!(Table("kennel"));
+        assert_eq!(object, SqlObject::Table("kennel".into()));
+        assert!(syn::parse_str::<SqlObject>("Enum(Dog)").is_err());
+    }
+
+    #[test]
+    fn matches_composite_types() {
+        assert!(SqlObject::Type("Dog".into()).is_composite_type("dog[]"));
+        assert!(SqlObject::Table("kennel".into()).is_composite_type("Kennel"));
+        assert!(!SqlObject::Function("walk".into()).is_composite_type("walk"));
+        assert!(!SqlObject::Type("Dog".into()).is_composite_type("Cat"));
+        assert!(SqlObject::Type("Dog".into()).is_composite_type("animals.dog"));
+        assert!(SqlObject::Type("animals.Dog".into()).is_composite_type("Animals.dog"));
+        assert!(!SqlObject::Type("animals.Dog".into()).is_composite_type("kennels.dog"));
+    }
+
+    #[test]
+    fn parses_config_dumps() {
+        let config_dump: ConfigDump = syn::parse_quote!(settings);
+        assert_eq!((config_dump.table.as_str(), config_dump.filter.as_str()), ("settings", ""));
+        let config_dump: ConfigDump = syn::parse_quote!("myext.rules" = "WHERE NOT builtin");
+        assert_eq!(
+            config_dump.to_sql(),
+            "SELECT pg_catalog.pg_extension_config_dump('myext.rules', 'WHERE NOT builtin');"
+        );
+        assert!(SqlObject::Table("Rules".into()).is_table("myext.rules"));
+        assert!(!SqlObject::Type("rules".into()).is_table("rules"));
+    }
+}
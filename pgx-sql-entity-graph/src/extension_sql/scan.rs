@@ -0,0 +1,367 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+Finding the names an `extension_sql!()` block's SQL uses, for its `infer_requires` option
+
+It isn't a SQL parser, only a tokenizer which knows enough to skip comments and string literals,
+including dollar-quoted ones, so the body of a function the SQL creates isn't scanned.
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+
+/// A name that SQL uses, like `my_fn` in `DEFAULT my_fn()`
+///
+/// The name and schema are as they were written, quotes and all, so they can be compared the
+/// way Postgres compares identifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlReference {
+    pub schema: Option<String>,
+    pub name: String,
+    /// Is it followed by `(`, as a function's name is when it's called?
+    pub called: bool,
+    /// Is it the name of what a `CREATE` statement creates, such as the table in `CREATE TABLE`?
+    pub created: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// An identifier or keyword, as it was written
+    Word(String),
+    Dot,
+    OpenParen,
+    Semicolon,
+    /// Anything else, like an operator, a number, or a string literal
+    Other,
+}
+
+/// The kinds of object whose name follows them in a `CREATE` statement
+const CREATED_KINDS: &[&str] = &[
+    "aggregate",
+    "domain",
+    "function",
+    "index",
+    "procedure",
+    "sequence",
+    "table",
+    "trigger",
+    "type",
+    "view",
+];
+
+/// The names that `sql` uses, outside of its comments and string literals, in order
+pub fn references(sql: &str) -> Vec<SqlReference> {
+    let tokens = tokenize(sql);
+    let mut references = Vec::new();
+    let mut statement_start = 0;
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match &tokens[idx] {
+            Token::Semicolon => statement_start = idx + 1,
+            Token::Word(_) => {
+                // a name, qualified by any number of others
+                let start = idx;
+                let mut parts = Vec::new();
+                while let Some(Token::Word(word)) = tokens.get(idx) {
+                    parts.push(word.clone());
+                    if tokens.get(idx + 1) == Some(&Token::Dot)
+                        && matches!(tokens.get(idx + 2), Some(Token::Word(_)))
+                    {
+                        idx += 2;
+                    } else {
+                        break;
+                    }
+                }
+                let called = tokens.get(idx + 1) == Some(&Token::OpenParen);
+                let created = created_name(&tokens[statement_start..])
+                    .map_or(false, |created| statement_start + created == start);
+                let name = parts.pop().unwrap_or_default();
+                let schema = parts.pop();
+                references.push(SqlReference { schema, name, called, created });
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    references
+}
+
+/// Where the name of what `statement` creates is in it, if it's a `CREATE` statement
+///
+/// That's after the first of the [`CREATED_KINDS`], and so not the function a `CREATE TRIGGER`
+/// executes, for example, and after any `IF NOT EXISTS`.
+fn created_name(statement: &[Token]) -> Option<usize> {
+    if !is_keyword(statement.first()?, "create") {
+        return None;
+    }
+    // in the words before anything else, so not the function in `CREATE CAST (..) WITH FUNCTION`
+    let kind = statement
+        .iter()
+        .take_while(|token| matches!(token, Token::Word(_)))
+        .position(|token| CREATED_KINDS.iter().any(|kind| is_keyword(token, kind)))?;
+    let if_not_exists = ["if", "not", "exists"];
+    let skip = match statement.get(kind + 1..kind + 4) {
+        Some(words) if words.iter().zip(if_not_exists).all(|(word, kw)| is_keyword(word, kw)) => 3,
+        _ => 0,
+    };
+    Some(kind + 1 + skip)
+}
+
+fn is_keyword(token: &Token, keyword: &str) -> bool {
+    match token {
+        Token::Word(word) => word.eq_ignore_ascii_case(keyword),
+        _ => false,
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let chars = sql.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        let next = chars.get(idx + 1).copied();
+        match c {
+            _ if c.is_whitespace() => idx += 1,
+            '-' if next == Some('-') => {
+                while idx < chars.len() && chars[idx] != '\n' {
+                    idx += 1;
+                }
+            }
+            '/' if next == Some('*') => {
+                // block comments nest
+                let mut depth = 0;
+                while idx < chars.len() {
+                    if chars[idx] == '/' && chars.get(idx + 1) == Some(&'*') {
+                        depth += 1;
+                        idx += 2;
+                    } else if chars[idx] == '*' && chars.get(idx + 1) == Some(&'/') {
+                        depth -= 1;
+                        idx += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        idx += 1;
+                    }
+                }
+            }
+            '\'' => {
+                idx = skip_string(&chars, idx, false);
+                tokens.push(Token::Other);
+            }
+            '"' => {
+                let start = idx;
+                idx += 1;
+                while idx < chars.len() {
+                    if chars[idx] == '"' {
+                        if chars.get(idx + 1) == Some(&'"') {
+                            idx += 2;
+                            continue;
+                        }
+                        idx += 1;
+                        break;
+                    }
+                    idx += 1;
+                }
+                tokens.push(Token::Word(chars[start..idx].iter().collect()));
+            }
+            '$' => match dollar_quote_tag(&chars, idx) {
+                Some(tag) => {
+                    idx += tag.len();
+                    while idx < chars.len() && !chars[idx..].starts_with(&tag) {
+                        idx += 1;
+                    }
+                    idx += tag.len();
+                    tokens.push(Token::Other);
+                }
+                // a parameter, like `$1`
+                None => {
+                    idx += 1;
+                    while idx < chars.len() && chars[idx].is_ascii_digit() {
+                        idx += 1;
+                    }
+                    tokens.push(Token::Other);
+                }
+            },
+            // `@extschema@`, and the like, which `CREATE EXTENSION` substitutes
+            '@' if next.map_or(false, is_identifier_start) => {
+                let start = idx;
+                idx += 1;
+                while idx < chars.len() && is_identifier_char(chars[idx]) {
+                    idx += 1;
+                }
+                if chars.get(idx) == Some(&'@') {
+                    idx += 1;
+                    tokens.push(Token::Word(chars[start..idx].iter().collect()));
+                } else {
+                    tokens.push(Token::Other);
+                }
+            }
+            _ if is_identifier_start(c) => {
+                let start = idx;
+                while idx < chars.len() && is_identifier_char(chars[idx]) {
+                    idx += 1;
+                }
+                let word = chars[start..idx].iter().collect::<String>();
+                match chars.get(idx) {
+                    // a string with a prefix, like `E'..'`, `B'..'`, or `U&'..'`
+                    Some('\'') => {
+                        idx = skip_string(&chars, idx, word.eq_ignore_ascii_case("e"));
+                        tokens.push(Token::Other);
+                    }
+                    Some('&') if word.eq_ignore_ascii_case("u") => {
+                        // `U&"..."` is a quoted identifier, which the next token will be
+                        idx += 1;
+                        if chars.get(idx) == Some(&'\'') {
+                            idx = skip_string(&chars, idx, false);
+                            tokens.push(Token::Other);
+                        }
+                    }
+                    _ => tokens.push(Token::Word(word)),
+                }
+            }
+            _ if c.is_ascii_digit() => {
+                while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_') {
+                    idx += 1;
+                }
+                tokens.push(Token::Other);
+            }
+            '.' => {
+                idx += 1;
+                tokens.push(Token::Dot);
+            }
+            '(' => {
+                idx += 1;
+                tokens.push(Token::OpenParen);
+            }
+            ';' => {
+                idx += 1;
+                tokens.push(Token::Semicolon);
+            }
+            _ => {
+                idx += 1;
+                tokens.push(Token::Other);
+            }
+        }
+    }
+    tokens
+}
+
+/// Skip the string literal whose opening quote is at `idx`, returning the index after it
+fn skip_string(chars: &[char], mut idx: usize, backslash_escapes: bool) -> usize {
+    idx += 1;
+    while idx < chars.len() {
+        match chars[idx] {
+            '\\' if backslash_escapes => idx += 2,
+            '\'' if chars.get(idx + 1) == Some(&'\'') => idx += 2,
+            '\'' => return idx + 1,
+            _ => idx += 1,
+        }
+    }
+    idx
+}
+
+/// The `$tag$` which starts a dollar-quoted string at `idx`, if one does
+fn dollar_quote_tag(chars: &[char], idx: usize) -> Option<Vec<char>> {
+    let mut end = idx + 1;
+    if chars.get(end).map_or(false, |&c| !is_identifier_start(c) && c != '$') {
+        return None;
+    }
+    while end < chars.len() && chars[end] != '$' {
+        if !is_identifier_char(chars[end]) {
+            return None;
+        }
+        end += 1;
+    }
+    (end < chars.len()).then(|| chars[idx..=end].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{references, SqlReference};
+
+    fn called(sql: &str) -> Vec<String> {
+        references(sql)
+            .into_iter()
+            .filter(|reference| reference.called && !reference.created)
+            .map(|reference| match reference.schema {
+                Some(schema) => format!("{}.{}", schema, reference.name),
+                None => reference.name,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_defaults_and_generated_columns() {
+        let sql = "CREATE TABLE orders (
+            id bigint DEFAULT next_order_id(),
+            total numeric GENERATED ALWAYS AS (@extschema@.with_tax(subtotal)) STORED
+        );";
+        // keywords can be followed by `(` too, but won't be the name of anything in the graph
+        assert_eq!(called(sql), vec!["next_order_id", "AS", "@extschema@.with_tax"]);
+
+        let orders = &references(sql)[2];
+        assert_eq!(
+            orders,
+            &SqlReference { schema: None, name: "orders".into(), called: true, created: true }
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        let sql = "-- DEFAULT commented_out()
+            /* a block comment, calling block() /* nested() */ still_comment() */
+            SELECT real_call(1);";
+        assert_eq!(called(sql), vec!["real_call"]);
+    }
+
+    #[test]
+    fn skips_string_literals() {
+        let sql = r#"SELECT 'quoted() and ''escaped()''', E'back\'slash()', $$dollar()$$,
+                $body$ SELECT tagged() $not_the_end$ $body$, U&'unicode()', "Quoted"(1), $1;"#;
+        assert_eq!(called(sql), vec!["\"Quoted\""]);
+    }
+
+    #[test]
+    fn ignores_parts_of_words() {
+        // `my_fn` isn't in `not_my_fn`, and neither is in `my_fn_2`
+        let names = references("SELECT not_my_fn(1), my_fn_2(2), my_fn$(3)")
+            .into_iter()
+            .map(|reference| reference.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["SELECT", "not_my_fn", "my_fn_2", "my_fn$"]);
+    }
+
+    #[test]
+    fn tells_created_from_used() {
+        let sql = "CREATE FUNCTION helper() RETURNS int AS 'SELECT 1' LANGUAGE sql;
+            ALTER FUNCTION helper() OWNER TO admin;
+            CREATE TABLE IF NOT EXISTS kennel (dog dog_type);
+            CREATE TRIGGER walked AFTER UPDATE ON kennel EXECUTE FUNCTION walk();
+            CREATE CAST (dog AS cat) WITH FUNCTION dog_to_cat(dog);";
+        let created = references(sql)
+            .into_iter()
+            .filter(|reference| reference.created)
+            .map(|reference| reference.name)
+            .collect::<Vec<_>>();
+        assert_eq!(created, vec!["helper", "kennel", "walked"]);
+        assert_eq!(called(sql), vec!["helper", "walk", "dog_to_cat"]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::pgx_attribute::{PgxArg, PgxAttribute};
 use crate::PositioningRef;
 use proc_macro2::{TokenStream, TokenTree};
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
@@ -8,6 +9,7 @@ pub enum ExternArgs {
     CreateOrReplace,
     Immutable,
     Strict,
+    CalledOnNullInput,
     Stable,
     Volatile,
     Raw,
@@ -19,7 +21,27 @@ pub enum ExternArgs {
     Schema(String),
     Name(String),
     Cost(String),
+    /// How many rows a set-returning function is estimated to return, instead of 1000
+    Rows(String),
     Requires(Vec<PositioningRef>),
+    /// A type whose `TRANSFORM` the function uses, which the extension creates or uses
+    Transform(String),
+    /// A type whose `TRANSFORM` the function uses, which is from elsewhere, such as another
+    /// extension
+    ExternalTransform(String),
+    /// The role which should own the function
+    Owner(String),
+    /// A role which should be granted `EXECUTE` on the function
+    Grant(String),
+    /// The body of a `LANGUAGE sql` function to create alongside the function
+    SqlWrapper(String),
+    /// The name of the `LANGUAGE sql` function created from `SqlWrapper`
+    SqlWrapperName(String),
+    /// Only for the extension's own SQL to call:  not executable by `PUBLIC`, and in the
+    /// extension's `@extschema@_internal` schema
+    Internal,
+    /// Not `Internal`, even when the extension's functions are by default
+    Public,
 }
 
 impl core::fmt::Display for ExternArgs {
@@ -28,6 +50,7 @@ impl core::fmt::Display for ExternArgs {
             ExternArgs::CreateOrReplace => write!(f, "CREATE OR REPLACE"),
             ExternArgs::Immutable => write!(f, "IMMUTABLE"),
             ExternArgs::Strict => write!(f, "STRICT"),
+            ExternArgs::CalledOnNullInput => write!(f, "CALLED ON NULL INPUT"),
             ExternArgs::Stable => write!(f, "STABLE"),
             ExternArgs::Volatile => write!(f, "VOLATILE"),
             ExternArgs::Raw => Ok(()),
@@ -39,7 +62,16 @@ impl core::fmt::Display for ExternArgs {
             ExternArgs::Schema(_) => Ok(()),
             ExternArgs::Name(_) => Ok(()),
             ExternArgs::Cost(cost) => write!(f, "COST {}", cost),
+            ExternArgs::Rows(rows) => write!(f, "ROWS {}", rows),
             ExternArgs::Requires(_) => Ok(()),
+            // all of a function's transforms are in one `TRANSFORM` clause
+            ExternArgs::Transform(_) | ExternArgs::ExternalTransform(_) => Ok(()),
+            // these are statements of their own, after the `CREATE FUNCTION`
+            ExternArgs::Owner(_) | ExternArgs::Grant(_) => Ok(()),
+            // a function of its own
+            ExternArgs::SqlWrapper(_) | ExternArgs::SqlWrapperName(_) => Ok(()),
+            // where the function is created, and a `REVOKE` after it
+            ExternArgs::Internal | ExternArgs::Public => Ok(()),
         }
     }
 }
@@ -50,6 +82,7 @@ impl ToTokens for ExternArgs {
             ExternArgs::CreateOrReplace => tokens.append(format_ident!("CreateOrReplace")),
             ExternArgs::Immutable => tokens.append(format_ident!("Immutable")),
             ExternArgs::Strict => tokens.append(format_ident!("Strict")),
+            ExternArgs::CalledOnNullInput => tokens.append(format_ident!("CalledOnNullInput")),
             ExternArgs::Stable => tokens.append(format_ident!("Stable")),
             ExternArgs::Volatile => tokens.append(format_ident!("Volatile")),
             ExternArgs::Raw => tokens.append(format_ident!("Raw")),
@@ -57,6 +90,8 @@ impl ToTokens for ExternArgs {
             ExternArgs::ParallelSafe => tokens.append(format_ident!("ParallelSafe")),
             ExternArgs::ParallelUnsafe => tokens.append(format_ident!("ParallelUnsafe")),
             ExternArgs::ParallelRestricted => tokens.append(format_ident!("ParallelRestricted")),
+            ExternArgs::Internal => tokens.append(format_ident!("Internal")),
+            ExternArgs::Public => tokens.append(format_ident!("Public")),
             ExternArgs::Error(_s) => {
                 tokens.append_all(
                     quote! {
@@ -89,6 +124,14 @@ impl ToTokens for ExternArgs {
                     .to_token_stream(),
                 );
             }
+            ExternArgs::Rows(_s) => {
+                tokens.append_all(
+                    quote! {
+                        Rows(String::from("#_s"))
+                    }
+                    .to_token_stream(),
+                );
+            }
             ExternArgs::Requires(items) => {
                 tokens.append_all(
                     quote! {
@@ -97,6 +140,54 @@ impl ToTokens for ExternArgs {
                     .to_token_stream(),
                 );
             }
+            ExternArgs::Transform(ty) => {
+                tokens.append_all(
+                    quote! {
+                        Transform(String::from(#ty))
+                    }
+                    .to_token_stream(),
+                );
+            }
+            ExternArgs::ExternalTransform(ty) => {
+                tokens.append_all(
+                    quote! {
+                        ExternalTransform(String::from(#ty))
+                    }
+                    .to_token_stream(),
+                );
+            }
+            ExternArgs::Owner(role) => {
+                tokens.append_all(
+                    quote! {
+                        Owner(String::from(#role))
+                    }
+                    .to_token_stream(),
+                );
+            }
+            ExternArgs::Grant(role) => {
+                tokens.append_all(
+                    quote! {
+                        Grant(String::from(#role))
+                    }
+                    .to_token_stream(),
+                );
+            }
+            ExternArgs::SqlWrapper(body) => {
+                tokens.append_all(
+                    quote! {
+                        SqlWrapper(String::from(#body))
+                    }
+                    .to_token_stream(),
+                );
+            }
+            ExternArgs::SqlWrapperName(name) => {
+                tokens.append_all(
+                    quote! {
+                        SqlWrapperName(String::from(#name))
+                    }
+                    .to_token_stream(),
+                );
+            }
         }
     }
 }
@@ -117,6 +208,7 @@ pub fn parse_extern_attributes(attr: TokenStream) -> HashSet<ExternArgs> {
                     "create_or_replace" => args.insert(ExternArgs::CreateOrReplace),
                     "immutable" => args.insert(ExternArgs::Immutable),
                     "strict" => args.insert(ExternArgs::Strict),
+                    "called_on_null_input" => args.insert(ExternArgs::CalledOnNullInput),
                     "stable" => args.insert(ExternArgs::Stable),
                     "volatile" => args.insert(ExternArgs::Volatile),
                     "raw" => args.insert(ExternArgs::Raw),
@@ -124,6 +216,12 @@ pub fn parse_extern_attributes(attr: TokenStream) -> HashSet<ExternArgs> {
                     "parallel_safe" => args.insert(ExternArgs::ParallelSafe),
                     "parallel_unsafe" => args.insert(ExternArgs::ParallelUnsafe),
                     "parallel_restricted" => args.insert(ExternArgs::ParallelRestricted),
+                    "internal" => args.insert(ExternArgs::Internal),
+                    "public" => args.insert(ExternArgs::Public),
+                    "barrier" => {
+                        args.insert(ExternArgs::Volatile);
+                        args.insert(ExternArgs::ParallelUnsafe)
+                    }
                     "error" => {
                         let _punc = itr.next().unwrap();
                         let literal = itr.next().unwrap();
@@ -170,11 +268,56 @@ pub fn parse_extern_attributes(attr: TokenStream) -> HashSet<ExternArgs> {
     args
 }
 
+/// The volatility and parallel safety a `#[pgx(..)]` on a `#[derive(PostgresEq)]`,
+/// `#[derive(PostgresOrd)]`, or `#[derive(PostgresHash)]` type declares for the functions it
+/// makes, e.g. `#[pgx(stable, parallel_safe)]`, as arguments for their `#[pg_operator]`s.
+///
+/// Those functions call the type's own `PartialEq`, `Ord`, or `Hash` impls, which can do anything,
+/// so they're `PARALLEL SAFE` only when declared to be.  They're `immutable` unless declared
+/// otherwise, as an index's operators must be.
+pub fn parse_operator_attributes(attrs: &[syn::Attribute]) -> syn::Result<TokenStream> {
+    const VOLATILITIES: [&str; 3] = ["immutable", "stable", "volatile"];
+    const PARALLELS: [&str; 3] = ["parallel_safe", "parallel_restricted", "parallel_unsafe"];
+
+    let mut volatility: Option<syn::Ident> = None;
+    let mut parallel: Option<syn::Ident> = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("pgx")) {
+        for arg in attr.parse_args::<PgxAttribute>()?.args {
+            let ident = match arg {
+                PgxArg::Path(path) => match path.get_ident() {
+                    Some(ident) => ident.clone(),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let name = ident.to_string();
+            let declared = if VOLATILITIES.contains(&name.as_str()) {
+                &mut volatility
+            } else if PARALLELS.contains(&name.as_str()) {
+                &mut parallel
+            } else {
+                continue;
+            };
+            if let Some(previous) = declared.replace(ident.clone()) {
+                let message = format!("`{}` conflicts with `{}`", ident, previous);
+                return Err(syn::Error::new(ident.span(), message));
+            }
+        }
+    }
+
+    let volatility = volatility.unwrap_or_else(|| format_ident!("immutable"));
+    Ok(match parallel {
+        Some(parallel) => quote! { #volatility, #parallel },
+        None => quote! { #volatility },
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use crate::{parse_extern_attributes, ExternArgs};
+    use crate::{parse_extern_attributes, parse_operator_attributes, ExternArgs};
+    use syn::parse_quote;
 
     #[test]
     fn parse_args() {
@@ -184,4 +327,33 @@ mod tests {
         let args = parse_extern_attributes(ts);
         assert!(args.contains(&ExternArgs::Error("syntax error at or near \"THIS\"".to_string())));
     }
+
+    #[test]
+    fn parse_visibility() {
+        let ts = proc_macro2::TokenStream::from_str("internal, grant = \"app\"").unwrap();
+        let args = parse_extern_attributes(ts);
+        assert!(args.contains(&ExternArgs::Internal));
+        assert!(args.contains(&ExternArgs::Grant("app".to_string())));
+
+        let ts = proc_macro2::TokenStream::from_str("public").unwrap();
+        assert!(parse_extern_attributes(ts).contains(&ExternArgs::Public));
+    }
+
+    #[test]
+    fn operator_attributes() {
+        let attrs: Vec<syn::Attribute> = vec![parse_quote! { #[pgx(sql = false)] }];
+        assert_eq!(parse_operator_attributes(&attrs).unwrap().to_string(), "immutable");
+
+        let attrs: Vec<syn::Attribute> =
+            vec![parse_quote! { #[pgx(stable)] }, parse_quote! { #[pgx(parallel_restricted)] }];
+        assert_eq!(
+            parse_operator_attributes(&attrs).unwrap().to_string(),
+            "stable , parallel_restricted"
+        );
+
+        let attrs: Vec<syn::Attribute> =
+            vec![parse_quote! { #[pgx(parallel_safe, parallel_unsafe)] }];
+        let error = parse_operator_attributes(&attrs).unwrap_err();
+        assert_eq!(error.to_string(), "`parallel_unsafe` conflicts with `parallel_safe`");
+    }
 }
@@ -18,22 +18,40 @@ pub use aggregate::entity::{AggregateTypeEntity, PgAggregateEntity};
 pub use aggregate::{
     AggregateType, AggregateTypeList, FinalizeModify, ParallelOption, PgAggregate,
 };
-pub use control_file::ControlFile;
+pub use composite_type::CompositeTypeName;
+pub use control_file::{ControlFile, INTERNAL_SCHEMA, SAFE_SEARCH_PATH};
 pub use enrich::CodeEnrichment;
 pub use extension_sql::entity::{ExtensionSqlEntity, SqlDeclaredEntity};
-pub use extension_sql::{ExtensionSql, ExtensionSqlFile, SqlDeclared};
-pub use extern_args::{parse_extern_attributes, ExternArgs};
+pub use extension_sql::{ConfigDump, ExtensionSql, ExtensionSqlFile, SqlDeclared, SqlObject};
+pub use extern_args::{parse_extern_attributes, parse_operator_attributes, ExternArgs};
+pub use lint::{Lint, LintLevel};
 pub use mapping::RustSqlMapping;
+pub use name_macro::{NameMacro, NamedType};
+pub use pg_cast::entity::PgCastEntity;
+pub use pg_cast::{CastContext, PgCast};
+pub use pg_constant::PgConstant;
+pub use pg_export_abi::entity::{PgExportAbiArgumentEntity, PgExportAbiEntity, PGX_ABI_VERSION};
+pub use pg_export_abi::{PgExportAbi, PgExportAbiArgument};
 pub use pg_extern::entity::{
-    PgExternArgumentEntity, PgExternEntity, PgExternReturnEntity, PgExternReturnEntityIteratedItem,
-    PgOperatorEntity,
+    OperatorLink, PgExternArgumentEntity, PgExternEntity, PgExternReturnEntity,
+    PgExternReturnEntityIteratedItem, PgOperatorEntity,
 };
-pub use pg_extern::{NameMacro, PgExtern, PgExternArgument, PgOperator};
+pub use pg_extern::{FunctionFact, PgExtern, PgExternArgument, PgOperator};
+pub use pg_gin_opclass::entity::PgGinOpclassEntity;
+pub use pg_gin_opclass::PgGinOpclass;
+pub use pg_notify_channel::PgNotifyChannel;
+pub use pg_policy::entity::{PgPolicyEntity, PolicyPredicateEntity};
+pub use pg_policy::{PgPolicy, PolicyCommand, PolicyPredicate};
 pub use pg_trigger::attribute::PgTriggerAttribute;
 pub use pg_trigger::entity::PgTriggerEntity;
 pub use pg_trigger::PgTrigger;
-pub use pgx_sql::PgxSql;
+pub use pg_version::PgVersionRange;
+pub use pg_view::entity::PgViewEntity;
+pub use pg_view::PgView;
+pub use pgx_sql::{PgxSql, SplitBy, SPLIT_INDEX_FILE};
 pub use positioning_ref::PositioningRef;
+pub use postgres_domain::entity::PostgresDomainEntity;
+pub use postgres_domain::PostgresDomain;
 pub use postgres_enum::entity::PostgresEnumEntity;
 pub use postgres_enum::PostgresEnum;
 pub use postgres_hash::entity::PostgresHashEntity;
@@ -41,7 +59,8 @@ pub use postgres_hash::PostgresHash;
 pub use postgres_ord::entity::PostgresOrdEntity;
 pub use postgres_ord::PostgresOrd;
 pub use postgres_type::entity::PostgresTypeEntity;
-pub use postgres_type::PostgresType;
+pub use postgres_type::{PostgresType, TypeStorage};
+pub use privileges::Privileges;
 pub use schema::entity::SchemaEntity;
 pub use schema::Schema;
 pub use to_sql::entity::ToSqlConfigEntity;
@@ -49,22 +68,35 @@ pub use to_sql::{ToSql, ToSqlConfig};
 pub use used_type::{UsedType, UsedTypeEntity};
 
 pub(crate) mod aggregate;
+pub(crate) mod composite_type;
 pub(crate) mod control_file;
 pub(crate) mod enrich;
 pub(crate) mod extension_sql;
 pub(crate) mod extern_args;
 pub mod lifetimes;
+pub(crate) mod lint;
 pub(crate) mod mapping;
 pub mod metadata;
+pub(crate) mod name_macro;
+pub(crate) mod pg_cast;
+pub(crate) mod pg_constant;
+pub(crate) mod pg_export_abi;
 pub(crate) mod pg_extern;
+pub(crate) mod pg_gin_opclass;
+pub(crate) mod pg_notify_channel;
+pub(crate) mod pg_policy;
 pub(crate) mod pg_trigger;
+pub(crate) mod pg_version;
+pub(crate) mod pg_view;
 pub(crate) mod pgx_attribute;
 pub(crate) mod pgx_sql;
 pub mod positioning_ref;
+pub(crate) mod postgres_domain;
 pub(crate) mod postgres_enum;
 pub(crate) mod postgres_hash;
 pub(crate) mod postgres_ord;
 pub(crate) mod postgres_type;
+pub(crate) mod privileges;
 pub(crate) mod schema;
 pub(crate) mod to_sql;
 pub(crate) mod used_type;
@@ -99,10 +131,15 @@ pub enum SqlGraphEntity {
     Type(PostgresTypeEntity),
     BuiltinType(String),
     Enum(PostgresEnumEntity),
+    Domain(PostgresDomainEntity),
     Ord(PostgresOrdEntity),
     Hash(PostgresHashEntity),
     Aggregate(PgAggregateEntity),
     Trigger(PgTriggerEntity),
+    Policy(PgPolicyEntity),
+    Cast(PgCastEntity),
+    GinOperatorClass(PgGinOpclassEntity),
+    View(PgViewEntity),
 }
 
 impl SqlGraphEntity {
@@ -132,10 +169,15 @@ impl SqlGraphIdentifier for SqlGraphEntity {
             SqlGraphEntity::Type(item) => item.dot_identifier(),
             SqlGraphEntity::BuiltinType(item) => format!("preexisting type {}", item),
             SqlGraphEntity::Enum(item) => item.dot_identifier(),
+            SqlGraphEntity::Domain(item) => item.dot_identifier(),
             SqlGraphEntity::Ord(item) => item.dot_identifier(),
             SqlGraphEntity::Hash(item) => item.dot_identifier(),
             SqlGraphEntity::Aggregate(item) => item.dot_identifier(),
             SqlGraphEntity::Trigger(item) => item.dot_identifier(),
+            SqlGraphEntity::Policy(item) => item.dot_identifier(),
+            SqlGraphEntity::Cast(item) => item.dot_identifier(),
+            SqlGraphEntity::GinOperatorClass(item) => item.dot_identifier(),
+            SqlGraphEntity::View(item) => item.dot_identifier(),
             SqlGraphEntity::ExtensionRoot(item) => item.dot_identifier(),
         }
     }
@@ -148,10 +190,15 @@ impl SqlGraphIdentifier for SqlGraphEntity {
             SqlGraphEntity::Type(item) => item.rust_identifier(),
             SqlGraphEntity::BuiltinType(item) => item.to_string(),
             SqlGraphEntity::Enum(item) => item.rust_identifier(),
+            SqlGraphEntity::Domain(item) => item.rust_identifier(),
             SqlGraphEntity::Ord(item) => item.rust_identifier(),
             SqlGraphEntity::Hash(item) => item.rust_identifier(),
             SqlGraphEntity::Aggregate(item) => item.rust_identifier(),
             SqlGraphEntity::Trigger(item) => item.rust_identifier(),
+            SqlGraphEntity::Policy(item) => item.rust_identifier(),
+            SqlGraphEntity::Cast(item) => item.rust_identifier(),
+            SqlGraphEntity::GinOperatorClass(item) => item.rust_identifier(),
+            SqlGraphEntity::View(item) => item.rust_identifier(),
             SqlGraphEntity::ExtensionRoot(item) => item.rust_identifier(),
         }
     }
@@ -164,10 +211,15 @@ impl SqlGraphIdentifier for SqlGraphEntity {
             SqlGraphEntity::Type(item) => item.file(),
             SqlGraphEntity::BuiltinType(_item) => None,
             SqlGraphEntity::Enum(item) => item.file(),
+            SqlGraphEntity::Domain(item) => item.file(),
             SqlGraphEntity::Ord(item) => item.file(),
             SqlGraphEntity::Hash(item) => item.file(),
             SqlGraphEntity::Aggregate(item) => item.file(),
             SqlGraphEntity::Trigger(item) => item.file(),
+            SqlGraphEntity::Policy(item) => item.file(),
+            SqlGraphEntity::Cast(item) => item.file(),
+            SqlGraphEntity::GinOperatorClass(item) => item.file(),
+            SqlGraphEntity::View(item) => item.file(),
             SqlGraphEntity::ExtensionRoot(item) => item.file(),
         }
     }
@@ -180,10 +232,15 @@ impl SqlGraphIdentifier for SqlGraphEntity {
             SqlGraphEntity::Type(item) => item.line(),
             SqlGraphEntity::BuiltinType(_item) => None,
             SqlGraphEntity::Enum(item) => item.line(),
+            SqlGraphEntity::Domain(item) => item.line(),
             SqlGraphEntity::Ord(item) => item.line(),
             SqlGraphEntity::Hash(item) => item.line(),
             SqlGraphEntity::Aggregate(item) => item.line(),
             SqlGraphEntity::Trigger(item) => item.line(),
+            SqlGraphEntity::Policy(item) => item.line(),
+            SqlGraphEntity::Cast(item) => item.line(),
+            SqlGraphEntity::GinOperatorClass(item) => item.line(),
+            SqlGraphEntity::View(item) => item.line(),
             SqlGraphEntity::ExtensionRoot(item) => item.line(),
         }
     }
@@ -192,6 +249,71 @@ impl SqlGraphIdentifier for SqlGraphEntity {
 impl ToSql for SqlGraphEntity {
     #[tracing::instrument(level = "debug", skip(self, context), fields(identifier = %self.rust_identifier()))]
     fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        if let Some(result) = self.to_sql_config().and_then(|config| config.to_sql(self, context)) {
+            return result;
+        }
+        self.to_default_sql(context)
+    }
+}
+
+impl SqlGraphEntity {
+    /// The `sql = ...` configuration of this entity, if it's a kind of entity that has one
+    pub fn to_sql_config(&self) -> Option<&ToSqlConfigEntity> {
+        match self {
+            SqlGraphEntity::Function(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Type(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Enum(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Domain(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Ord(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Hash(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Aggregate(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::Trigger(item) => Some(&item.to_sql_config),
+            SqlGraphEntity::ExtensionRoot(_)
+            | SqlGraphEntity::Schema(_)
+            | SqlGraphEntity::CustomSql(_)
+            | SqlGraphEntity::BuiltinType(_)
+            | SqlGraphEntity::Policy(_)
+            | SqlGraphEntity::Cast(_)
+            | SqlGraphEntity::GinOperatorClass(_)
+            | SqlGraphEntity::View(_) => None,
+        }
+    }
+
+    /// The `pg_version = ".."` range this entity's SQL is limited to, if any
+    pub fn pg_version(&self) -> Option<&PgVersionRange> {
+        match self {
+            SqlGraphEntity::CustomSql(item) => item.pg_version.as_ref(),
+            other => other.to_sql_config().and_then(|config| config.pg_version.as_ref()),
+        }
+    }
+
+    /// The Rust module this entity was declared in, unless it's the extension itself or a
+    /// builtin type
+    pub fn module_path(&self) -> Option<&'static str> {
+        match self {
+            SqlGraphEntity::Schema(item) => Some(item.module_path),
+            SqlGraphEntity::CustomSql(item) => Some(item.module_path),
+            SqlGraphEntity::Function(item) => Some(item.module_path),
+            SqlGraphEntity::Type(item) => Some(item.module_path),
+            SqlGraphEntity::Enum(item) => Some(item.module_path),
+            SqlGraphEntity::Domain(item) => Some(item.module_path),
+            SqlGraphEntity::Ord(item) => Some(item.module_path),
+            SqlGraphEntity::Hash(item) => Some(item.module_path),
+            SqlGraphEntity::Aggregate(item) => Some(item.module_path),
+            SqlGraphEntity::Trigger(item) => Some(item.module_path),
+            SqlGraphEntity::Policy(item) => Some(item.module_path),
+            SqlGraphEntity::Cast(item) => Some(item.module_path),
+            SqlGraphEntity::GinOperatorClass(item) => Some(item.module_path),
+            SqlGraphEntity::View(item) => Some(item.module_path),
+            SqlGraphEntity::ExtensionRoot(_) | SqlGraphEntity::BuiltinType(_) => None,
+        }
+    }
+
+    /// The SQL `pgx` would generate for this entity if it had no `sql = ...` configuration.
+    ///
+    /// This is intended for use by `#[pgx(sql = path::to::function)]` callbacks that want to
+    /// decorate the default SQL, for example by appending a `GRANT`, rather than replace it.
+    pub fn to_default_sql(&self, context: &PgxSql) -> eyre::Result<String> {
         match self {
             SqlGraphEntity::Schema(item) => {
                 if item.name != "public" && item.name != "pg_catalog" {
@@ -202,22 +324,15 @@ impl ToSql for SqlGraphEntity {
             }
             SqlGraphEntity::CustomSql(item) => item.to_sql(context),
             SqlGraphEntity::Function(item) => {
-                if let Some(result) = item.to_sql_config.to_sql(self, context) {
-                    return result;
-                }
                 if context.graph.neighbors_undirected(context.externs.get(item).unwrap().clone()).any(|neighbor| {
                     let neighbor_item = &context.graph[neighbor];
                     match neighbor_item {
-                        SqlGraphEntity::Type(PostgresTypeEntity { in_fn, in_fn_module_path, out_fn, out_fn_module_path, .. }) => {
-                            let is_in_fn = item.full_path.starts_with(in_fn_module_path) && item.full_path.ends_with(in_fn);
-                            if is_in_fn {
-                                tracing::trace!(r#type = %neighbor_item.dot_identifier(), "Skipping, is an in_fn.");
+                        SqlGraphEntity::Type(ty) => {
+                            let is_support_fn = ty.is_support_fn(item.full_path);
+                            if is_support_fn {
+                                tracing::trace!(r#type = %neighbor_item.dot_identifier(), "Skipping, is one of its support functions.");
                             }
-                            let is_out_fn = item.full_path.starts_with(out_fn_module_path) && item.full_path.ends_with(out_fn);
-                            if is_out_fn {
-                                tracing::trace!(r#type = %neighbor_item.dot_identifier(), "Skipping, is an out_fn.");
-                            }
-                            is_in_fn || is_out_fn
+                            is_support_fn
                         },
                         _ => false,
                     }
@@ -227,25 +342,18 @@ impl ToSql for SqlGraphEntity {
                     item.to_sql(context)
                 }
             }
-            SqlGraphEntity::Type(item) => {
-                item.to_sql_config.to_sql(self, context).unwrap_or_else(|| item.to_sql(context))
-            }
+            SqlGraphEntity::Type(item) => item.to_sql(context),
             SqlGraphEntity::BuiltinType(_) => Ok(String::default()),
-            SqlGraphEntity::Enum(item) => {
-                item.to_sql_config.to_sql(self, context).unwrap_or_else(|| item.to_sql(context))
-            }
-            SqlGraphEntity::Ord(item) => {
-                item.to_sql_config.to_sql(self, context).unwrap_or_else(|| item.to_sql(context))
-            }
-            SqlGraphEntity::Hash(item) => {
-                item.to_sql_config.to_sql(self, context).unwrap_or_else(|| item.to_sql(context))
-            }
-            SqlGraphEntity::Aggregate(item) => {
-                item.to_sql_config.to_sql(self, context).unwrap_or_else(|| item.to_sql(context))
-            }
-            SqlGraphEntity::Trigger(item) => {
-                item.to_sql_config.to_sql(self, context).unwrap_or_else(|| item.to_sql(context))
-            }
+            SqlGraphEntity::Enum(item) => item.to_sql(context),
+            SqlGraphEntity::Domain(item) => item.to_sql(context),
+            SqlGraphEntity::Ord(item) => item.to_sql(context),
+            SqlGraphEntity::Hash(item) => item.to_sql(context),
+            SqlGraphEntity::Aggregate(item) => item.to_sql(context),
+            SqlGraphEntity::Trigger(item) => item.to_sql(context),
+            SqlGraphEntity::Policy(item) => item.to_sql(context),
+            SqlGraphEntity::Cast(item) => item.to_sql(context),
+            SqlGraphEntity::GinOperatorClass(item) => item.to_sql(context),
+            SqlGraphEntity::View(item) => item.to_sql(context),
             SqlGraphEntity::ExtensionRoot(item) => item.to_sql(context),
         }
     }
@@ -281,3 +389,34 @@ pub fn ident_is_acceptable_to_postgres(ident: &syn::Ident) -> Result<(), syn::Er
 
     Ok(())
 }
+
+/// The symbol the `extern "C"` wrapper of the Rust function `ident`, in the module `module_path`,
+/// is exported as, which its SQL names in `AS 'MODULE_PATHNAME', '...'`
+///
+/// It's namespaced by the module path, so that functions of the same name in different modules
+/// don't collide when they're linked.  The macros export it with [`wrapper_symbol_tokens`].
+pub fn wrapper_symbol(module_path: &str, ident: &str) -> String {
+    format!("{module_path}::{ident}_wrapper")
+}
+
+/// The tokens of a `concat!()` making the [`wrapper_symbol`] of `ident`, in the module it's
+/// expanded in, prefixed with `prefix`
+///
+/// Postgres looks up the `Pg_finfo_record` of a wrapper by its symbol prefixed with `pg_finfo_`.
+pub fn wrapper_symbol_tokens(prefix: &str, ident: &syn::Ident) -> proc_macro2::TokenStream {
+    quote::quote! {
+        concat!(#prefix, module_path!(), "::", stringify!(#ident), "_wrapper")
+    }
+}
+
+/// The tokens of a `concat!()` making the symbol of the function which returns the entity of
+/// `ident`, in the module it's expanded in, which `cargo pgx schema` finds by its
+/// `__pgx_internals_{kind}_` prefix
+///
+/// Like the [`wrapper_symbol`], it's namespaced by the module path.
+pub fn entity_symbol_tokens(kind: &str, ident: &syn::Ident) -> proc_macro2::TokenStream {
+    let prefix = format!("__pgx_internals_{kind}_");
+    quote::quote! {
+        concat!(#prefix, module_path!(), "::", stringify!(#ident))
+    }
+}
@@ -0,0 +1,126 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+Schema lints, for `cargo pgx schema --lint`
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::{ExternArgs, FunctionFact, PgExternEntity};
+
+/// How bad a [`Lint`] is
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    /// Probably a mistake
+    Warning,
+    /// Gives wrong results
+    Error,
+}
+
+impl core::fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LintLevel::Warning => write!(f, "warning"),
+            LintLevel::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A contradiction between what a function is declared as, and what it does
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Lint {
+    pub file: &'static str,
+    pub line: u32,
+    pub level: LintLevel,
+    pub full_path: &'static str,
+    pub message: String,
+}
+
+impl core::fmt::Display for Lint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "`{}` {} ({}:{})", self.full_path, self.message, self.file, self.line)
+    }
+}
+
+impl Lint {
+    fn new(entity: &PgExternEntity, level: LintLevel, message: String) -> Self {
+        Lint { file: entity.file, line: entity.line, level, full_path: entity.full_path, message }
+    }
+}
+
+/// Check a `#[pg_extern]` function's attributes against the facts recorded about its body
+pub(crate) fn lint_extern(entity: &PgExternEntity) -> Vec<Lint> {
+    let declared = |arg: ExternArgs| entity.extern_attrs.contains(&arg);
+    let does = |fact: FunctionFact| entity.facts.contains(&fact);
+    let mut lints = Vec::new();
+
+    if declared(ExternArgs::Immutable) {
+        for (fact, level, instead) in [
+            (FunctionFact::UsesSpi, LintLevel::Error, "STABLE or VOLATILE"),
+            (FunctionFact::UsesRandom, LintLevel::Error, "VOLATILE"),
+            (FunctionFact::ReadsClock, LintLevel::Error, "VOLATILE"),
+            (FunctionFact::ReadsTransactionTime, LintLevel::Error, "STABLE"),
+            (FunctionFact::ReadsGuc, LintLevel::Warning, "STABLE"),
+        ] {
+            if does(fact) {
+                lints.push(Lint::new(
+                    entity,
+                    level,
+                    format!(
+                        "is IMMUTABLE but {}, so Postgres may fold calls to it into constants; declare it {}",
+                        fact, instead
+                    ),
+                ));
+            }
+        }
+    }
+
+    if declared(ExternArgs::Stable) {
+        for fact in [FunctionFact::UsesRandom, FunctionFact::ReadsClock] {
+            if does(fact) {
+                lints.push(Lint::new(
+                    entity,
+                    LintLevel::Warning,
+                    format!(
+                        "is STABLE but {}, so Postgres may reuse its result within a scan; declare it VOLATILE",
+                        fact
+                    ),
+                ));
+            }
+        }
+    }
+
+    if declared(ExternArgs::ParallelSafe) && does(FunctionFact::LocksExclusive) {
+        lints.push(Lint::new(
+            entity,
+            LintLevel::Warning,
+            format!(
+                "is PARALLEL SAFE but {} to modify shared state; declare it PARALLEL RESTRICTED or PARALLEL UNSAFE",
+                FunctionFact::LocksExclusive
+            ),
+        ));
+    }
+
+    if declared(ExternArgs::Strict) {
+        for arg in entity.fn_args.iter().filter(|arg| arg.used_ty.optional) {
+            lints.push(Lint::new(
+                entity,
+                LintLevel::Warning,
+                format!(
+                    "is STRICT but takes `{}` as an `Option`, which will never be `None`",
+                    arg.pattern
+                ),
+            ));
+        }
+    }
+
+    lints
+}
@@ -10,6 +10,11 @@ to the `pgx` framework and very subject to change between versions. While you ma
 use super::{FunctionMetadataEntity, PhantomDataExt, SqlTranslatable};
 use core::marker::PhantomData;
 
+/// The most arguments a Postgres function can have, which is Postgres' `FUNC_MAX_ARGS` unless it
+/// was built with a different one.  [`FunctionMetadata`] is implemented for functions with up to
+/// this many arguments.
+pub const FUNC_MAX_ARGS: usize = 100;
+
 /**
 Provide SQL generation related information on functions
 
@@ -76,7 +81,8 @@ impl FunctionMetadata<(), ()> for unsafe fn() {
         FunctionMetadataEntity { arguments: vec![], retval: None, path: self.path() }
     }
 }
-seq_macro::seq!(I in 0..=32 {
+// for 1 to `FUNC_MAX_ARGS` arguments
+seq_macro::seq!(I in 0..100 {
     #(
         seq_macro::seq!(N in 0..=I {
             impl<#(Input~N,)* Output> FunctionMetadata<(#(Input~N,)*), Output> for fn(#(Input~N,)*) -> Output
@@ -15,7 +15,7 @@ mod return_variant;
 mod sql_translatable;
 
 pub use entity::{FunctionMetadataEntity, FunctionMetadataTypeEntity};
-pub use function_metadata::FunctionMetadata;
+pub use function_metadata::{FunctionMetadata, FUNC_MAX_ARGS};
 pub use phantomdata_ext::PhantomDataExt;
 pub use return_variant::{Returns, ReturnsError};
 pub use sql_translatable::{ArgumentError, SqlMapping, SqlTranslatable};
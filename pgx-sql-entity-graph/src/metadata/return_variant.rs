@@ -18,6 +18,8 @@ pub enum Returns {
     One(SqlMapping),
     SetOf(SqlMapping),
     Table(Vec<SqlMapping>),
+    /// An anonymous `record`, from a Rust tuple, whose columns are the function's `OUT` parameters
+    Record(Vec<SqlMapping>),
 }
 
 #[derive(Clone, Copy, Debug, Hash, Ord, PartialOrd, PartialEq, Eq)]
@@ -28,6 +30,10 @@ pub enum ReturnsError {
     TableContainingSetOf,
     SetOfInArray,
     TableInArray,
+    RecordInArray,
+    NestedRecord,
+    SetOfContainingRecord,
+    TableContainingRecord,
     BareU8,
     SkipInArray,
     Datum,
@@ -54,6 +60,21 @@ impl std::fmt::Display for ReturnsError {
             ReturnsError::TableInArray => {
                 write!(f, "TableIterator inside Array is not valid")
             }
+            ReturnsError::RecordInArray => {
+                write!(f, "A tuple inside Array is not valid")
+            }
+            ReturnsError::NestedRecord => {
+                write!(f, "Nested tuple in return type")
+            }
+            ReturnsError::SetOfContainingRecord => {
+                write!(
+                    f,
+                    "SetOfIterator containing a tuple in return type, use TableIterator instead"
+                )
+            }
+            ReturnsError::TableContainingRecord => {
+                write!(f, "TableIterator containing a tuple in return type")
+            }
             ReturnsError::SkipInArray => {
                 write!(f, "SqlMapping::Skip inside Array is not valid")
             }
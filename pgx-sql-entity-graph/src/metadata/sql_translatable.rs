@@ -151,6 +151,38 @@ where
     }
 }
 
+// A tuple of two or more values is returned as an anonymous `record`, whose columns are the
+// function's `OUT` parameters.  It can't be an argument, as there's no way to declare the types of
+// a `record` argument's columns.
+seq_macro::seq!(I in 2..32 {
+    #(
+        seq_macro::seq!(N in 0..I {
+            unsafe impl<#(T~N,)*> SqlTranslatable for (#(T~N,)*)
+            where
+                #(
+                    T~N: SqlTranslatable,
+                )*
+            {
+                fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+                    Err(ArgumentError::NotValidAsArgument(Self::type_name()))
+                }
+                fn return_sql() -> Result<Returns, ReturnsError> {
+                    let mut columns = Vec::with_capacity(I);
+                    #(
+                        columns.push(match T~N::return_sql()? {
+                            Returns::One(sql) => sql,
+                            Returns::SetOf(_) => return Err(ReturnsError::NestedSetOf),
+                            Returns::Table(_) => return Err(ReturnsError::NestedTable),
+                            Returns::Record(_) => return Err(ReturnsError::NestedRecord),
+                        });
+                    )*
+                    Ok(Returns::Record(columns))
+                }
+            }
+        });
+    )*
+});
+
 unsafe impl<T, E> SqlTranslatable for Result<T, E>
 where
     T: SqlTranslatable,
@@ -204,6 +236,7 @@ where
                 Ok(Returns::One(SqlMapping::Skip)) => Ok(Returns::One(SqlMapping::Skip)),
                 Ok(Returns::SetOf(_)) => Err(ReturnsError::SetOfInArray),
                 Ok(Returns::Table(_)) => Err(ReturnsError::TableInArray),
+                Ok(Returns::Record(_)) => Err(ReturnsError::RecordInArray),
                 err @ Err(_) => err,
             },
         }
@@ -306,6 +339,33 @@ unsafe impl SqlTranslatable for i64 {
     }
 }
 
+unsafe impl SqlTranslatable for std::num::NonZeroI16 {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        i16::argument_sql()
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        i16::return_sql()
+    }
+}
+
+unsafe impl SqlTranslatable for std::num::NonZeroI32 {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        i32::argument_sql()
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        i32::return_sql()
+    }
+}
+
+unsafe impl SqlTranslatable for std::num::NonZeroI64 {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        i64::argument_sql()
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        i64::return_sql()
+    }
+}
+
 unsafe impl SqlTranslatable for bool {
     fn argument_sql() -> Result<SqlMapping, ArgumentError> {
         Ok(SqlMapping::literal("bool"))
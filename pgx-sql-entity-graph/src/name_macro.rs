@@ -0,0 +1,169 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`name!()` and `composite_type!()` detection for Rust to SQL translation, shared by arguments and
+returned columns
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::UsedType;
+use syn::parse::{Parse, ParseStream};
+use syn::Token;
+
+const MALFORMED_NAME: &str = "expected `name!(sql_name, Type)`";
+const NESTED_NAME: &str =
+    "`name!()` must wrap the whole type, as in `name!(owner, Option<composite_type!(\"person\")>)`";
+
+/// The name of the macro `ty` is, such as `name` for `name!(..)` or `pgx::name!(..)`, looking
+/// through any parentheses around it
+pub fn type_macro_archetype(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Macro(type_macro) => {
+            type_macro.mac.path.segments.last().map(|archetype| archetype.ident.to_string())
+        }
+        syn::Type::Paren(paren) => type_macro_archetype(&paren.elem),
+        syn::Type::Group(group) => type_macro_archetype(&group.elem),
+        _ => None,
+    }
+}
+
+/// Is `ty` a `composite_type!(..)`?
+pub fn is_composite_type(ty: &syn::Type) -> bool {
+    type_macro_archetype(ty).as_deref() == Some("composite_type")
+}
+
+/// The body of a `name!(sql_name, Type)`
+#[derive(Debug, Clone)]
+pub struct NameMacro {
+    pub ident: String,
+    pub used_ty: UsedType,
+}
+
+impl Parse for NameMacro {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let ident = input
+            .parse::<syn::Ident>()
+            .map(|v| v.to_string())
+            // Avoid making folks unable to use rust keywords.
+            .or_else(|_| input.parse::<syn::Token![type]>().map(|_| String::from("type")))
+            .or_else(|_| input.parse::<syn::Token![mod]>().map(|_| String::from("mod")))
+            .or_else(|_| input.parse::<syn::Token![extern]>().map(|_| String::from("extern")))
+            .or_else(|_| input.parse::<syn::Token![async]>().map(|_| String::from("async")))
+            .or_else(|_| input.parse::<syn::Token![crate]>().map(|_| String::from("crate")))
+            .or_else(|_| input.parse::<syn::Token![use]>().map(|_| String::from("use")))
+            .map_err(|_| syn::Error::new(input.span(), MALFORMED_NAME))?;
+        let _comma: Token![,] =
+            input.parse().map_err(|_| syn::Error::new(input.span(), MALFORMED_NAME))?;
+        let ty: syn::Type = input.parse()?;
+        reject_nested_names(&ty)?;
+
+        let used_ty = UsedType::new(ty)?;
+
+        Ok(Self { ident, used_ty })
+    }
+}
+
+/// The type of an argument or a returned column, with the SQL name it's given by a `name!()`
+#[derive(Debug, Clone)]
+pub struct NamedType {
+    pub name: Option<String>,
+    pub used_ty: UsedType,
+}
+
+impl NamedType {
+    /// Parse `ty`, which may be a `name!(sql_name, Type)` wrapping any type, including a
+    /// `composite_type!()` and the `Option`s and `Vec`s of one
+    pub fn new(ty: &syn::Type) -> syn::Result<Self> {
+        match ty {
+            syn::Type::Macro(type_macro) if type_macro_archetype(ty).as_deref() == Some("name") => {
+                let out: NameMacro = type_macro.mac.parse_body()?;
+                Ok(NamedType { name: Some(out.ident), used_ty: out.used_ty })
+            }
+            syn::Type::Paren(paren) if type_macro_archetype(ty).as_deref() == Some("name") => {
+                Self::new(&paren.elem)
+            }
+            _ => {
+                reject_nested_names(ty)?;
+                Ok(NamedType { name: None, used_ty: UsedType::new(ty.clone())? })
+            }
+        }
+    }
+}
+
+/// Fail on any `name!()` within `ty`, which can only name the whole of an argument or column
+fn reject_nested_names(ty: &syn::Type) -> syn::Result<()> {
+    match ty {
+        syn::Type::Macro(_) if type_macro_archetype(ty).as_deref() == Some("name") => {
+            Err(syn::Error::new_spanned(ty, NESTED_NAME))
+        }
+        syn::Type::Paren(paren) => reject_nested_names(&paren.elem),
+        syn::Type::Group(group) => reject_nested_names(&group.elem),
+        syn::Type::Reference(reference) => reject_nested_names(&reference.elem),
+        syn::Type::Slice(slice) => reject_nested_names(&slice.elem),
+        syn::Type::Array(array) => reject_nested_names(&array.elem),
+        syn::Type::Tuple(tuple) => tuple.elems.iter().try_for_each(reject_nested_names),
+        syn::Type::Path(path) => {
+            for segment in &path.path.segments {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    for arg in &args.args {
+                        if let syn::GenericArgument::Type(inner) = arg {
+                            reject_nested_names(inner)?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NamedType;
+
+    fn person() -> syn::Expr {
+        syn::parse_quote!("person")
+    }
+
+    #[test]
+    fn names_composite_types() {
+        let named =
+            NamedType::new(&syn::parse_quote!(name!(owner, composite_type!("person")))).unwrap();
+        assert_eq!(named.name.as_deref(), Some("owner"));
+        assert_eq!(named.used_ty.composite_type.unwrap().expr, person());
+
+        let named = NamedType::new(&syn::parse_quote!(pgx::name!(
+            owners,
+            Option<Vec<composite_type!("person")>>
+        )))
+        .unwrap();
+        assert_eq!(named.name.as_deref(), Some("owners"));
+        assert_eq!(named.used_ty.composite_type.unwrap().expr, person());
+        assert!(named.used_ty.optional.is_some());
+
+        let unnamed = NamedType::new(&syn::parse_quote!(composite_type!("person"))).unwrap();
+        assert_eq!(unnamed.name, None);
+    }
+
+    #[test]
+    fn rejects_nested_and_malformed_names() {
+        let nested = NamedType::new(&syn::parse_quote!(Option<name!(owner, i32)>)).unwrap_err();
+        assert!(nested.to_string().starts_with("`name!()` must wrap the whole type"));
+        let twice =
+            NamedType::new(&syn::parse_quote!(name!(owner, name!(person, i32)))).unwrap_err();
+        assert_eq!(twice.to_string(), nested.to_string());
+
+        let malformed = NamedType::new(&syn::parse_quote!(name!(i32))).unwrap_err();
+        assert_eq!(malformed.to_string(), "expected `name!(sql_name, Type)`");
+    }
+}
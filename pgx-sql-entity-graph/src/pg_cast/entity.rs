@@ -0,0 +1,138 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_cast]` related entities for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::pgx_sql::{find_extern_by_path, PgxSql};
+use crate::to_sql::ToSql;
+use crate::{
+    PgExternEntity, PgExternReturnEntity, SqlGraphEntity, SqlGraphIdentifier, UsedTypeEntity,
+};
+
+use eyre::eyre;
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+use super::CastContext;
+
+/// The output of a [`PgCast`](crate::PgCast) from `quote::ToTokens::to_tokens`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgCastEntity {
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub full_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub source: UsedTypeEntity,
+    pub target: UsedTypeEntity,
+    /// The `#[pg_extern]` function which converts a value, or `None` for a binary-coercible cast
+    pub function: Option<&'static str>,
+    pub context: CastContext,
+}
+
+impl PgCastEntity {
+    /// Find the `#[pg_extern]` function this cast calls, and ensure it takes one argument and
+    /// returns one value, or `None` if it's binary-coercible
+    pub fn validate_function<'a>(
+        &self,
+        externs: &'a HashMap<PgExternEntity, NodeIndex>,
+    ) -> eyre::Result<Option<(&'a PgExternEntity, NodeIndex)>> {
+        let path = match self.function {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let (function, index) = find_extern_by_path(path, externs)?.ok_or_else(|| {
+            eyre!(
+                "The cast `{}` ({}:{}) calls `{}`, which isn't a `#[pg_extern]` function; use `#[pg_cast(binary)]` for a cast without one",
+                self.name,
+                self.file,
+                self.line,
+                path,
+            )
+        })?;
+        if function.fn_args.len() != 1
+            || !matches!(function.fn_return, PgExternReturnEntity::Type { .. })
+        {
+            return Err(eyre!(
+                "The function of cast `{}` ({}:{}) must take exactly one argument and return one value: {}",
+                self.name,
+                self.file,
+                self.line,
+                function.full_path,
+            ));
+        }
+        Ok(Some((function, index)))
+    }
+}
+
+impl From<PgCastEntity> for SqlGraphEntity {
+    fn from(val: PgCastEntity) -> Self {
+        SqlGraphEntity::Cast(val)
+    }
+}
+
+impl SqlGraphIdentifier for PgCastEntity {
+    fn dot_identifier(&self) -> String {
+        format!("cast {}", self.name)
+    }
+    fn rust_identifier(&self) -> String {
+        self.full_path.to_string()
+    }
+
+    fn file(&self) -> Option<&'static str> {
+        Some(self.file)
+    }
+
+    fn line(&self) -> Option<u32> {
+        Some(self.line)
+    }
+}
+
+impl ToSql for PgCastEntity {
+    #[tracing::instrument(level = "debug", skip(self, context), fields(identifier = self.full_path))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        let cast = format!("cast `{}`", self.name);
+        let source = context.used_type_sql(&self.source, &cast)?;
+        let target = context.used_type_sql(&self.target, &cast)?;
+        let function = match self.validate_function(&context.externs)? {
+            Some((function, index)) => {
+                let schema = function
+                    .schema
+                    .map(|schema| format!("{}.", schema))
+                    .unwrap_or_else(|| context.schema_prefix_for(&index));
+                format!("WITH FUNCTION {schema}\"{name}\"({source})", name = function.name)
+            }
+            None => String::from("WITHOUT FUNCTION"),
+        };
+        let context = match self.context {
+            CastContext::Explicit => "",
+            CastContext::Assignment => "\n\tAS ASSIGNMENT",
+            CastContext::Implicit => "\n\tAS IMPLICIT",
+        };
+
+        let sql = format!(
+            "\n\
+            -- {file}:{line}\n\
+            -- {full_path}\n\
+            CREATE CAST ({source} AS {target})\n\
+                \t{function}{context};\n\
+            ",
+            file = self.file,
+            line = self.line,
+            full_path = self.full_path,
+        );
+        tracing::trace!(%sql);
+        Ok(sql)
+    }
+}
@@ -0,0 +1,202 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_cast]` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+pub mod entity;
+
+use crate::enrich::{CodeEnrichment, ToEntityGraphTokens, ToRustCodeTokens};
+use crate::pg_extern::Returning;
+use crate::UsedType;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens, TokenStreamExt};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Ident, Token};
+
+/// A parsed `#[pg_cast]` item.
+///
+/// It should be used with [`PgCast::new`].
+///
+/// Using [`quote::ToTokens`] will output the declaration for a [`PgCastEntity`][crate::PgCastEntity],
+/// followed by the function itself.
+///
+/// ```rust
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::PgCast;
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed = PgCast::new(
+///     quote! { implicit },
+///     quote! {
+///         #[pg_extern]
+///         fn celsius_to_kelvin(value: Celsius) -> Kelvin {
+///             unimplemented!()
+///         }
+///     },
+/// )?;
+/// let sql_graph_entity_tokens = parsed.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgCast {
+    func: syn::ItemFn,
+    context: CastContext,
+    binary: bool,
+    source: UsedType,
+    target: UsedType,
+}
+
+impl PgCast {
+    pub fn new(attr: TokenStream2, item: TokenStream2) -> Result<CodeEnrichment<Self>, syn::Error> {
+        let mut context = CastContext::Explicit;
+        let mut binary = false;
+        let options = Punctuated::<Ident, Token![,]>::parse_terminated.parse2(attr)?;
+        for option in options {
+            let found = match option.to_string().as_str() {
+                "implicit" => CastContext::Implicit,
+                "assignment" => CastContext::Assignment,
+                "binary" => {
+                    binary = true;
+                    continue;
+                }
+                other => {
+                    return Err(syn::Error::new(
+                        option.span(),
+                        format!("Unknown pg_cast attribute: {}", other),
+                    ))
+                }
+            };
+            if context != CastContext::Explicit && context != found {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "a cast can be `implicit` or `assignment`, but not both",
+                ));
+            }
+            context = found;
+        }
+
+        let func = syn::parse2::<syn::ItemFn>(item)?;
+        let is_extern = func.attrs.iter().any(|attr| {
+            attr.path
+                .segments
+                .last()
+                .map_or(false, |last| last.ident == "pg_extern" || last.ident == "pg_operator")
+        });
+        if binary && is_extern {
+            return Err(syn::Error::new(
+                func.sig.ident.span(),
+                "a `binary` cast reuses the value as it is, without calling a function, so it can't also be a `#[pg_extern]`",
+            ));
+        }
+
+        let source = match func.sig.inputs.iter().collect::<Vec<_>>().as_slice() {
+            [syn::FnArg::Typed(pat_ty)] => UsedType::new(*pat_ty.ty.clone())?,
+            _ => {
+                return Err(syn::Error::new(
+                    func.sig.paren_token.span,
+                    format!(
+                        "`{}` takes {} arguments, but a cast's function takes exactly one, the value to cast",
+                        func.sig.ident,
+                        func.sig.inputs.len()
+                    ),
+                ))
+            }
+        };
+        let target = match Returning::try_from(&func.sig.output)? {
+            Returning::Type(ty) if ty.resolved_ty != syn::parse_quote!(()) => ty,
+            Returning::None | Returning::Type(_) => {
+                return Err(syn::Error::new(
+                    func.sig.ident.span(),
+                    format!("`{}` has to return the type it casts to", func.sig.ident),
+                ))
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    func.sig.output.span(),
+                    "a cast's function returns one value, not a set or a tuple",
+                ))
+            }
+        };
+
+        Ok(CodeEnrichment(Self { func, context, binary, source, target }))
+    }
+}
+
+impl ToEntityGraphTokens for PgCast {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let ident = &self.func.sig.ident;
+        let source = self.source.entity_tokens();
+        let target = self.target.entity_tokens();
+        let context = &self.context;
+        let function = if self.binary {
+            quote! { None }
+        } else {
+            quote! { Some(concat!(core::module_path!(), "::", stringify!(#ident))) }
+        };
+
+        let sql_graph_entity_fn_name =
+            syn::Ident::new(&format!("__pgx_internals_cast_{}", ident), Span::call_site());
+        let sql_graph_entity_fn_symbol = crate::entity_symbol_tokens("cast", ident);
+        quote! {
+            #[export_name = #sql_graph_entity_fn_symbol]
+            #[doc(hidden)]
+            pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
+                extern crate alloc;
+                #[allow(unused_imports)]
+                use alloc::{vec, vec::Vec};
+                let submission = ::pgx::pgx_sql_entity_graph::PgCastEntity {
+                    name: stringify!(#ident),
+                    module_path: core::module_path!(),
+                    full_path: concat!(core::module_path!(), "::", stringify!(#ident)),
+                    file: file!(),
+                    line: line!(),
+                    source: #source,
+                    target: #target,
+                    function: #function,
+                    context: #context,
+                };
+                ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Cast(submission)
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PgCast {
+    fn to_rust_code_tokens(&self) -> TokenStream2 {
+        self.func.to_token_stream()
+    }
+}
+
+/// When Postgres may apply a cast without being asked to, as in `CREATE CAST ... AS context`
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CastContext {
+    /// Only by an explicit `CAST(x AS type)` or `x::type`
+    Explicit,
+    /// Also when assigning to a column, as in `INSERT` and `UPDATE`
+    Assignment,
+    /// In any context, such as to call a function or operator which takes the other type
+    Implicit,
+}
+
+impl ToTokens for CastContext {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let variant = Ident::new(&format!("{:?}", self), Span::call_site());
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::CastContext::#variant
+        });
+    }
+}
@@ -0,0 +1,145 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pgx::pg_constant!()` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::enrich::{ToEntityGraphTokens, ToRustCodeTokens};
+use crate::CodeEnrichment;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Expr, Ident, LitStr, Token, Type, Visibility};
+
+/// A parsed `pg_constant!()` item.
+///
+/// It should be used with [`syn::parse::Parse`] functions.
+///
+/// Using [`quote::ToTokens`] will output the `const` and the `#[pg_extern]` function SQL reads it
+/// through, which is what puts it in the entity graph.
+///
+/// ```rust
+/// use syn::{Macro, parse::Parse, parse_quote, parse};
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::{CodeEnrichment, PgConstant};
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed: Macro = parse_quote! {
+///     pg_constant!(
+///         /// How deep a tree may be
+///         pub MAX_DEPTH: i32 = 16;
+///     )
+/// };
+/// let inner_tokens = parsed.tokens;
+/// let inner: CodeEnrichment<PgConstant> = parse_quote! {
+///     #inner_tokens
+/// };
+/// let sql_graph_entity_tokens = inner.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgConstant {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    pub ty: Type,
+    pub value: Expr,
+}
+
+impl Parse for CodeEnrichment<PgConstant> {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let _const: Option<Token![const]> = input.parse()?;
+        let ident = input.parse()?;
+        let _colon: Token![:] = input.parse()?;
+        let ty = input.parse()?;
+        let _eq: Token![=] = input.parse()?;
+        let value = input.parse()?;
+        let _semi: Option<Token![;]> = input.parse()?;
+        Ok(CodeEnrichment(PgConstant { attrs, vis, ident, ty, value }))
+    }
+}
+
+impl PgConstant {
+    /// The name of the SQL function, which is the constant's name in lower case
+    pub fn sql_name(&self) -> String {
+        self.ident.to_string().to_lowercase()
+    }
+
+    /// The type the function returns, which is the constant's with any elided lifetimes `'static`
+    fn return_type(&self) -> Type {
+        let mut ty = self.ty.clone();
+        if let Type::Reference(reference) = &mut ty {
+            if reference.lifetime.is_none() {
+                reference.lifetime = Some(syn::Lifetime::new("'static", Span::call_site()));
+            }
+        }
+        ty
+    }
+}
+
+// `#[pg_extern]` makes the function's entity, when the schema is being generated
+impl ToEntityGraphTokens for PgConstant {}
+
+impl ToRustCodeTokens for PgConstant {
+    fn to_rust_code_tokens(&self) -> TokenStream2 {
+        let PgConstant { attrs, vis, ident, ty, value } = self;
+        let sql_name = LitStr::new(&self.sql_name(), ident.span());
+        let return_type = self.return_type();
+        let function = Ident::new(&format!("__pgx_constant_{}", self.sql_name()), ident.span());
+        quote! {
+            #(#attrs)*
+            #vis const #ident: #ty = #value;
+
+            #[doc(hidden)]
+            #[::pgx::pgx_macros::pg_extern(immutable, parallel_safe, name = #sql_name)]
+            fn #function() -> #return_type {
+                #ident
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PgConstant;
+    use crate::CodeEnrichment;
+    use quote::ToTokens;
+
+    fn parse(tokens: proc_macro2::TokenStream) -> syn::Result<PgConstant> {
+        syn::parse2::<CodeEnrichment<PgConstant>>(tokens).map(|constant| constant.0)
+    }
+
+    #[test]
+    fn parses_constants() {
+        let constant = parse(quote::quote!(MAX_DEPTH: i32 = 16)).unwrap();
+        assert_eq!(constant.sql_name(), "max_depth");
+
+        let constant = parse(quote::quote!(
+            /// The greeting
+            pub const GREETING: &str = "hello";
+        ))
+        .unwrap();
+        assert_eq!(constant.attrs.len(), 1);
+        assert_eq!(constant.return_type().to_token_stream().to_string(), "& 'static str");
+    }
+
+    #[test]
+    fn rejects_bad_constants() {
+        assert!(parse(quote::quote!(MAX_DEPTH = 16)).is_err());
+        assert!(parse(quote::quote!(MAX_DEPTH: i32)).is_err());
+        assert!(parse(quote::quote!(static MAX_DEPTH: i32 = 16)).is_err());
+    }
+}
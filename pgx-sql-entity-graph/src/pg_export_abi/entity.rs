@@ -0,0 +1,146 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_export_abi]` related entities for the C header of an extension's exported functions
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use std::fmt::Write as _;
+
+/// The version of the calling convention of the exported functions, which only changes if their
+/// signature or the header's types do
+pub const PGX_ABI_VERSION: i32 = 1;
+
+/// The output of a [`PgExportAbi`](crate::PgExportAbi) from `quote::ToTokens::to_tokens`.
+///
+/// These aren't part of the SQL entity graph:  they're gathered separately, by the
+/// `__pgx_abi_export_*` functions, into a C header.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgExportAbiEntity {
+    /// The C symbol of the exported function
+    pub symbol: &'static str,
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub full_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub args: Vec<PgExportAbiArgumentEntity>,
+    /// The Rust type the function returns
+    pub returns: &'static str,
+}
+
+/// An argument of a [`PgExportAbiEntity`]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgExportAbiArgumentEntity {
+    pub name: &'static str,
+    /// The Rust type of the argument
+    pub ty: &'static str,
+    /// Can the argument be `NULL`, because it's an `Option`?
+    pub nullable: bool,
+}
+
+impl PgExportAbiEntity {
+    /// Render the C header declaring the `exports` of the extension `extension_name`, which
+    /// `cargo pgx schema --emit-header` writes
+    pub fn c_header(extension_name: &str, exports: &[PgExportAbiEntity]) -> String {
+        let guard = format!("{}_PGX_ABI_H", c_identifier(extension_name).to_uppercase());
+        let mut exports = exports.iter().collect::<Vec<_>>();
+        exports.sort_by_key(|export| export.symbol);
+
+        let mut header = String::new();
+        let _ = write!(
+            header,
+            "\
+/*
+ * The functions `{extension_name}` exports for other extensions to call directly.
+ *
+ * Generated by `cargo pgx schema --emit-header`.  Do not edit.
+ *
+ * Each function takes its arguments as an array of `nargs` datums, and stores its result in
+ * `*result`.  It returns PGX_ABI_OK, or on failure another PGX_ABI_* status, with the failure's
+ * SQLSTATE and message, allocated in the caller's memory context, in `*error`.  A caught ERROR
+ * (PGX_ABI_ERROR) leaves the transaction in the same state PG_CATCH() does, so unless the call
+ * was made in a subtransaction, the caller must raise an ERROR of its own.
+ *
+ * The library is found with load_external_function(), for instance:
+ *
+ *     pgx_abi_fn f = (pgx_abi_fn) load_external_function(\"$libdir/{extension_name}\", \"symbol\", true, NULL);
+ */
+#ifndef {guard}
+#define {guard}
+
+#include \"postgres.h\"
+
+#ifndef PGX_ABI_VERSION
+#define PGX_ABI_VERSION {version}
+
+#define PGX_ABI_OK 0
+#define PGX_ABI_ERROR 1
+#define PGX_ABI_PANIC 2
+#define PGX_ABI_BAD_CALL 3
+
+typedef struct PgxAbiDatum
+{{
+	Datum		value;
+	bool		isnull;
+}} PgxAbiDatum;
+
+typedef struct PgxAbiError
+{{
+	int			sqlerrcode;
+	char	   *message;
+}} PgxAbiError;
+
+typedef int32 (*pgx_abi_fn) (const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+#endif
+
+#if PGX_ABI_VERSION != {version}
+#error \"{extension_name}'s functions need version {version} of the pgx ABI\"
+#endif
+",
+            version = PGX_ABI_VERSION,
+        );
+
+        for export in exports {
+            let args = export
+                .args
+                .iter()
+                .map(|arg| {
+                    let null = if arg.nullable { "" } else { " NOT NULL" };
+                    format!("{}: {}{}", arg.name, arg.ty, null)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(
+                header,
+                "
+/* {full_path}({args}) -> {returns} */
+#define {nargs_macro} {nargs}
+extern int32 {symbol}(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+",
+                full_path = export.full_path,
+                returns = export.returns,
+                nargs_macro = format!("{}_NARGS", export.symbol.to_uppercase()),
+                nargs = export.args.len(),
+                symbol = export.symbol,
+            );
+        }
+
+        let _ = write!(header, "\n#endif\t\t\t\t\t\t\t/* {guard} */\n");
+        header
+    }
+}
+
+/// `name` with everything that can't be in a C identifier replaced by `_`
+pub(crate) fn c_identifier(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
@@ -0,0 +1,315 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_export_abi]` related macro expansion for the C ABI other extensions call
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+pub mod entity;
+
+use crate::enrich::{CodeEnrichment, ToEntityGraphTokens, ToRustCodeTokens};
+use entity::c_identifier;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Ident, LitStr, Token};
+
+/// A parsed `#[pg_export_abi]` function.
+///
+/// Using [`quote::ToTokens`] will output the function, an `extern "C"` shim other extensions can
+/// call it through, and the declaration for a [`PgExportAbiEntity`][crate::PgExportAbiEntity].
+///
+/// ```rust
+/// use syn::parse_quote;
+/// use quote::ToTokens;
+/// use pgx_sql_entity_graph::PgExportAbi;
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed = PgExportAbi::new(
+///     quote::quote! { name = "myext_add" },
+///     quote::quote! { fn add(a: i32, b: Option<i32>) -> i32 { a + b.unwrap_or(0) } },
+/// )?;
+/// let tokens = parsed.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgExportAbi {
+    pub func: syn::ItemFn,
+    pub symbol: String,
+    pub args: Vec<PgExportAbiArgument>,
+}
+
+/// An argument of a [`PgExportAbi`] function
+#[derive(Debug, Clone)]
+pub struct PgExportAbiArgument {
+    pub pattern: Ident,
+    pub ty: syn::Type,
+    /// The `T` of an `Option<T>`, which is `None` when the argument is `NULL`
+    pub nullable: Option<syn::Type>,
+}
+
+impl PgExportAbi {
+    pub fn new(attr: TokenStream2, item: TokenStream2) -> Result<CodeEnrichment<Self>, syn::Error> {
+        let func = syn::parse2::<syn::ItemFn>(item)?;
+
+        let mut symbol = None;
+        let parser = Punctuated::<PgExportAbiAttribute, Token![,]>::parse_terminated;
+        for attr in parser.parse2(attr)? {
+            match attr {
+                PgExportAbiAttribute::Name(name) => {
+                    let value = name.value();
+                    if value.is_empty() || c_identifier(&value) != value {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            "`name` must be a valid C identifier",
+                        ));
+                    }
+                    symbol = Some(value);
+                }
+            }
+        }
+        // exported symbols share one namespace with every other library Postgres loads, so
+        // they're prefixed with the crate's name
+        let symbol = match symbol {
+            Some(symbol) => symbol,
+            None => {
+                let crate_name = std::env::var("CARGO_CRATE_NAME").map_err(|_| {
+                    syn::Error::new(
+                        Span::call_site(),
+                        "`CARGO_CRATE_NAME` isn't set, add `name = \"...\"`",
+                    )
+                })?;
+                format!("{}_{}", crate_name, func.sig.ident)
+            }
+        };
+
+        if let Some(asyncness) = &func.sig.asyncness {
+            return Err(syn::Error::new(asyncness.span(), "exported functions can't be `async`"));
+        }
+        if !func.sig.generics.params.is_empty() {
+            return Err(syn::Error::new(
+                func.sig.generics.span(),
+                "exported functions can't be generic",
+            ));
+        }
+        if let Some(variadic) = &func.sig.variadic {
+            return Err(syn::Error::new(variadic.span(), "exported functions can't be variadic"));
+        }
+
+        let mut args = Vec::new();
+        for input in &func.sig.inputs {
+            let pat_ty = match input {
+                syn::FnArg::Typed(pat_ty) => pat_ty,
+                syn::FnArg::Receiver(receiver) => {
+                    return Err(syn::Error::new(
+                        receiver.span(),
+                        "exported functions can't take `self`",
+                    ))
+                }
+            };
+            let pattern = match &*pat_ty.pat {
+                syn::Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+                pat => {
+                    return Err(syn::Error::new(
+                        pat.span(),
+                        "the arguments of exported functions must be named by an identifier",
+                    ))
+                }
+            };
+            let ty = (*pat_ty.ty).clone();
+            let nullable = option_inner(&ty);
+            args.push(PgExportAbiArgument { pattern, ty, nullable });
+        }
+
+        Ok(CodeEnrichment(PgExportAbi { func, symbol, args }))
+    }
+
+    fn shim_tokens(&self) -> TokenStream2 {
+        let func_ident = &self.func.sig.ident;
+        let shim_ident = Ident::new(&format!("__pgx_abi_{}", func_ident), func_ident.span());
+        let symbol = &self.symbol;
+        let nargs = self.args.len();
+        let func_name = func_ident.to_string();
+
+        let patterns = self.args.iter().map(|arg| &arg.pattern).collect::<Vec<_>>();
+        let arg_exprs = self.args.iter().enumerate().map(|(idx, arg)| {
+            let name = arg.pattern.to_string();
+            match &arg.nullable {
+                Some(inner) => quote! {
+                    unsafe { ::pgx::export_abi::__arg::<#inner>(__pgx_args, #idx) }
+                },
+                None => {
+                    let ty = &arg.ty;
+                    quote! {
+                        unsafe { ::pgx::export_abi::__arg::<#ty>(__pgx_args, #idx) }
+                            .unwrap_or_else(|| ::pgx::export_abi::__null_argument(#func_name, #name))
+                    }
+                }
+            }
+        });
+
+        quote! {
+            #[export_name = #symbol]
+            #[doc(hidden)]
+            pub unsafe extern "C" fn #shim_ident(
+                args: *const ::pgx::export_abi::PgxAbiDatum,
+                nargs: i32,
+                result: *mut ::pgx::export_abi::PgxAbiDatum,
+                error: *mut ::pgx::export_abi::PgxAbiError,
+            ) -> i32 {
+                ::pgx::export_abi::__call(#symbol, #nargs, args, nargs, result, error, |__pgx_args| {
+                    #( let #patterns = #arg_exprs; )*
+                    ::pgx::IntoDatum::into_datum(#func_ident(#(#patterns),*))
+                })
+            }
+        }
+    }
+}
+
+impl ToEntityGraphTokens for PgExportAbi {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let name = self.func.sig.ident.to_string();
+        let symbol = &self.symbol;
+        let returns = match &self.func.sig.output {
+            syn::ReturnType::Default => String::from("()"),
+            syn::ReturnType::Type(_, ty) => type_string(ty),
+        };
+        let args = self.args.iter().map(|arg| {
+            let name = arg.pattern.to_string();
+            let ty = type_string(&arg.ty);
+            let nullable = arg.nullable.is_some();
+            quote! {
+                ::pgx::pgx_sql_entity_graph::PgExportAbiArgumentEntity {
+                    name: #name,
+                    ty: #ty,
+                    nullable: #nullable,
+                }
+            }
+        });
+
+        let entity_fn_name =
+            Ident::new(&format!("__pgx_abi_export_{}", self.symbol), Span::call_site());
+        quote! {
+            #[no_mangle]
+            #[doc(hidden)]
+            pub extern "Rust" fn #entity_fn_name() -> ::pgx::pgx_sql_entity_graph::PgExportAbiEntity {
+                extern crate alloc;
+                use alloc::vec::Vec;
+                use alloc::vec;
+                ::pgx::pgx_sql_entity_graph::PgExportAbiEntity {
+                    symbol: #symbol,
+                    name: #name,
+                    module_path: module_path!(),
+                    full_path: concat!(module_path!(), "::", #name),
+                    file: file!(),
+                    line: line!(),
+                    args: vec![#(#args),*],
+                    returns: #returns,
+                }
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PgExportAbi {
+    fn to_rust_code_tokens(&self) -> TokenStream2 {
+        let func = &self.func;
+        if crate::enrich::SQL_GENERATION_ONLY {
+            return func.to_token_stream();
+        }
+        let shim = self.shim_tokens();
+        quote! {
+            #func
+            #shim
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PgExportAbiAttribute {
+    Name(LitStr),
+}
+
+impl syn::parse::Parse for PgExportAbiAttribute {
+    fn parse(input: syn::parse::ParseStream) -> Result<Self, syn::Error> {
+        let ident: Ident = input.parse()?;
+        let _eq: Token![=] = input.parse()?;
+        match ident.to_string().as_str() {
+            "name" => Ok(Self::Name(input.parse()?)),
+            other => Err(syn::Error::new(
+                ident.span(),
+                &format!("Unknown pg_export_abi attribute: {}", other),
+            )),
+        }
+    }
+}
+
+/// The `T` of an `Option<T>`
+fn option_inner(ty: &syn::Type) -> Option<syn::Type> {
+    let path = match ty {
+        syn::Type::Path(type_path) if type_path.qself.is_none() => &type_path.path,
+        _ => return None,
+    };
+    let last = path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    match &last.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+            match args.args.first()? {
+                syn::GenericArgument::Type(inner) => Some(inner.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// `ty` as it's written, without the spaces `quote` puts between every token
+fn type_string(ty: &syn::Type) -> String {
+    ty.to_token_stream()
+        .to_string()
+        .replace(" :: ", "::")
+        .replace(" <", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace("& ", "&")
+        .replace(" ,", ",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{type_string, PgExportAbi};
+
+    #[test]
+    fn parses_exported_functions() {
+        let parsed = PgExportAbi::new(
+            quote::quote! { name = "myext_describe" },
+            quote::quote! { fn describe(a: i32, name: Option<&str>) -> String { todo!() } },
+        )
+        .unwrap()
+        .0;
+        assert_eq!(parsed.symbol, "myext_describe");
+        assert!(parsed.args[0].nullable.is_none());
+        assert_eq!(type_string(parsed.args[1].nullable.as_ref().unwrap()), "&str");
+        assert_eq!(type_string(&parsed.args[1].ty), "Option<&str>");
+
+        let generic = PgExportAbi::new(quote::quote! {}, quote::quote! { fn describe<T>(a: T) {} });
+        assert!(generic.is_err());
+        let bad_name =
+            PgExportAbi::new(quote::quote! { name = "my-ext" }, quote::quote! { fn f() {} });
+        assert!(bad_name.is_err());
+    }
+}
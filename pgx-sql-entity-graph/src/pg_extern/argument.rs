@@ -14,11 +14,15 @@ Use of this source code is governed by the MIT license that can be found in the
 to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
 
 */
+use crate::name_macro::{type_macro_archetype, NameMacro, NamedType};
 use crate::UsedType;
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{quote, ToTokens, TokenStreamExt};
 use syn::{FnArg, Pat};
 
+/// The types an argument can borrow from its datum, such as the `str` of a `&str`
+const BORROWABLE_TYPES: &[&str] = &["str", "CStr", "StringInfo"];
+
 /// A parsed `#[pg_extern]` argument.
 ///
 /// It is created during [`PgExtern`](crate::PgExtern) parsing.
@@ -26,6 +30,8 @@ use syn::{FnArg, Pat};
 pub struct PgExternArgument {
     pub fn_arg: syn::FnArg,
     pub pat: syn::Ident,
+    /// The argument's name in SQL, if it's given one by a `name!()` rather than taking `pat`'s
+    pub name: Option<String>,
     pub used_ty: UsedType,
 }
 
@@ -52,18 +58,18 @@ impl PgExternArgument {
             _ => return Err(syn::Error::new(Span::call_site(), "Unable to parse FnArg")),
         };
 
-        let used_ty = UsedType::new(*value.ty)?;
+        let NamedType { name, used_ty } = NamedType::new(&value.ty)?;
 
-        Ok(PgExternArgument { fn_arg, pat: identifier, used_ty })
+        Ok(PgExternArgument { fn_arg, pat: identifier, name, used_ty })
     }
 
     pub fn entity_tokens(&self) -> TokenStream2 {
-        let pat = &self.pat;
+        let pattern = self.name.clone().unwrap_or_else(|| self.pat.to_string());
         let used_ty_entity = self.used_ty.entity_tokens();
 
         let quoted = quote! {
             ::pgx::pgx_sql_entity_graph::PgExternArgumentEntity {
-                pattern: stringify!(#pat),
+                pattern: #pattern,
                 used_ty: #used_ty_entity,
             }
         };
@@ -71,6 +77,81 @@ impl PgExternArgument {
     }
 }
 
+/// Check that `ty` is a type a `#[pg_extern]` function can take as an argument, so an unsupported
+/// one is reported on the argument, rather than as an error about a trait that isn't implemented
+/// deep inside the code the macro generates
+///
+/// `generics` are those of the function, whose type parameters Postgres can't pick a type for.
+pub fn validate_argument_type(ty: &syn::Type, generics: &syn::Generics) -> syn::Result<()> {
+    match ty {
+        syn::Type::Group(group) => validate_argument_type(&group.elem, generics),
+        syn::Type::Paren(paren) => validate_argument_type(&paren.elem, generics),
+        syn::Type::Reference(reference) => {
+            let borrowable = match &*reference.elem {
+                syn::Type::Path(path) => path.qself.is_none()
+                    && path.path.segments.last().map_or(false, |last| {
+                        BORROWABLE_TYPES.iter().any(|borrowable| last.ident == borrowable)
+                    }),
+                syn::Type::Slice(slice) => {
+                    matches!(&*slice.elem, syn::Type::Path(path) if path.path.is_ident("u8"))
+                }
+                _ => false,
+            };
+            if borrowable {
+                Ok(())
+            } else {
+                Err(syn::Error::new_spanned(
+                    ty,
+                    "only `&str`, `&[u8]`, `&CStr` and `&StringInfo` can be borrowed from an argument's datum; take the type itself instead",
+                ))
+            }
+        }
+        // `name!(owner, composite_type!("person"))` and the like, whose type is the one to check
+        syn::Type::Macro(type_macro) if type_macro_archetype(ty).as_deref() == Some("name") => {
+            let name_macro: NameMacro = type_macro.mac.parse_body()?;
+            validate_argument_type(&name_macro.used_ty.original_ty, generics)
+        }
+        syn::Type::ImplTrait(_) => Err(syn::Error::new_spanned(
+            ty,
+            "`impl Trait` can't be an argument's type, as Postgres has to know which type it is; name the type instead",
+        )),
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let last = match path.path.segments.last() {
+                Some(last) => last,
+                None => return Ok(()),
+            };
+            if last.ident == "Result" {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "`Result` is only supported in return position; take the `Ok` type, and return an error if it isn't valid",
+                ));
+            }
+            if path.path.segments.len() == 1
+                && generics.type_params().any(|param| param.ident == last.ident)
+            {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!(
+                        "`{}` is a generic parameter of the function, which an argument's type can't be, as Postgres has to know which type it is; write a `#[pg_extern]` function for each type",
+                        last.ident
+                    ),
+                ));
+            }
+            // `Option<T>`, `Vec<T>`, `Array<'a, T>` and the like
+            if let syn::PathArguments::AngleBracketed(args) = &last.arguments {
+                for arg in &args.args {
+                    if let syn::GenericArgument::Type(inner) = arg {
+                        validate_argument_type(inner, generics)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        // `UsedType` resolves `default!()`, `variadic!()`, `composite_type!()` and the like
+        _ => Ok(()),
+    }
+}
+
 impl ToTokens for PgExternArgument {
     fn to_tokens(&self, tokens: &mut TokenStream2) {
         let fn_arg = &self.fn_arg;
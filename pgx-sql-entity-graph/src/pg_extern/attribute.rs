@@ -14,6 +14,7 @@ Use of this source code is governed by the MIT license that can be found in the
 to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
 
 */
+use crate::pg_version::PgVersionRange;
 use crate::positioning_ref::PositioningRef;
 use crate::to_sql::ToSqlConfig;
 use proc_macro2::{Span, TokenStream as TokenStream2};
@@ -26,6 +27,7 @@ use syn::Token;
 pub enum Attribute {
     Immutable,
     Strict,
+    CalledOnNullInput,
     Stable,
     Volatile,
     Raw,
@@ -34,12 +36,39 @@ pub enum Attribute {
     ParallelSafe,
     ParallelUnsafe,
     ParallelRestricted,
+    /// `volatile` and `parallel_unsafe` together
+    Barrier,
+    /// Check the extension's installed SQL matches its library before every call
+    CheckVersion,
+    /// Only for the extension's own SQL to call, in its `@extschema@_internal` schema
+    Internal,
+    /// Callable by anyone, even when the extension's functions are internal by default
+    Public,
     Error(syn::LitStr),
     Schema(syn::LitStr),
     Name(syn::LitStr),
     Cost(syn::Expr),
+    /// How many rows a set-returning function is estimated to return
+    Rows(syn::Expr),
     Requires(Punctuated<PositioningRef, Token![,]>),
+    Transform(syn::LitStr),
+    ExternalTransform(syn::LitStr),
+    Owner(syn::LitStr),
+    Grant(syn::LitStr),
+    SqlWrapper(syn::LitStr),
+    SqlWrapperName(syn::LitStr),
+    /// A `SET name TO value` clause, from `set = "name = value"`
+    Set(syn::LitStr),
+    /// Cache the function's results for the rest of the statement, from `memoize` or
+    /// `memoize = max_entries`
+    Memoize(Option<syn::LitInt>),
+    /// Give each row a set-returning function makes its own `pgx::arena::Arena`
+    Arena,
+    /// Also export the wrapper as `{name}_wrapper`, the symbol it had before wrappers were
+    /// namespaced by their module path, from `legacy_symbol`
+    LegacySymbol,
     Sql(ToSqlConfig),
+    PgVersion(PgVersionRange),
 }
 
 impl Attribute {
@@ -49,6 +78,9 @@ impl Attribute {
                 quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Immutable }
             }
             Attribute::Strict => quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Strict },
+            Attribute::CalledOnNullInput => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::CalledOnNullInput }
+            }
             Attribute::Stable => quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Stable },
             Attribute::Volatile => {
                 quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Volatile }
@@ -79,12 +111,42 @@ impl Attribute {
             Attribute::Cost(s) => {
                 quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Cost(format!("{}", #s)) }
             }
+            Attribute::Rows(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Rows(format!("{}", #s)) }
+            }
             Attribute::Requires(items) => {
                 let items_iter = items.iter().map(|x| x.to_token_stream()).collect::<Vec<_>>();
                 quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Requires(vec![#(#items_iter),*],) }
             }
-            // This attribute is handled separately
-            Attribute::Sql(_) => {
+            Attribute::Transform(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Transform(String::from(#s)) }
+            }
+            Attribute::ExternalTransform(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::ExternalTransform(String::from(#s)) }
+            }
+            Attribute::Owner(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Owner(String::from(#s)) }
+            }
+            Attribute::Grant(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Grant(String::from(#s)) }
+            }
+            Attribute::SqlWrapper(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::SqlWrapper(String::from(#s)) }
+            }
+            Attribute::SqlWrapperName(s) => {
+                quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::SqlWrapperName(String::from(#s)) }
+            }
+            Attribute::Internal => quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Internal },
+            Attribute::Public => quote! { ::pgx::pgx_sql_entity_graph::ExternArgs::Public },
+            // These attributes are handled separately
+            Attribute::Barrier
+            | Attribute::CheckVersion
+            | Attribute::Set(_)
+            | Attribute::Memoize(_)
+            | Attribute::Arena
+            | Attribute::LegacySymbol
+            | Attribute::Sql(_)
+            | Attribute::PgVersion(_) => {
                 quote! {}
             }
         }
@@ -96,6 +158,7 @@ impl ToTokens for Attribute {
         let quoted = match self {
             Attribute::Immutable => quote! { immutable },
             Attribute::Strict => quote! { strict },
+            Attribute::CalledOnNullInput => quote! { called_on_null_input },
             Attribute::Stable => quote! { stable },
             Attribute::Volatile => quote! { volatile },
             Attribute::Raw => quote! { raw },
@@ -110,6 +173,10 @@ impl ToTokens for Attribute {
             Attribute::ParallelRestricted => {
                 quote! { parallel_restricted }
             }
+            Attribute::Barrier => quote! { barrier },
+            Attribute::CheckVersion => quote! { check_version },
+            Attribute::Internal => quote! { internal },
+            Attribute::Public => quote! { public },
             Attribute::Error(s) => {
                 quote! { error = #s }
             }
@@ -122,14 +189,46 @@ impl ToTokens for Attribute {
             Attribute::Cost(s) => {
                 quote! { cost = #s }
             }
+            Attribute::Rows(s) => {
+                quote! { rows = #s }
+            }
             Attribute::Requires(items) => {
                 let items_iter = items.iter().map(|x| x.to_token_stream()).collect::<Vec<_>>();
                 quote! { requires = [#(#items_iter),*] }
             }
+            Attribute::Transform(s) => {
+                quote! { transform = #s }
+            }
+            Attribute::ExternalTransform(s) => {
+                quote! { external_transform = #s }
+            }
+            Attribute::Owner(s) => {
+                quote! { owner = #s }
+            }
+            Attribute::Grant(s) => {
+                quote! { grant = #s }
+            }
+            Attribute::SqlWrapper(s) => {
+                quote! { sql_wrapper = #s }
+            }
+            Attribute::SqlWrapperName(s) => {
+                quote! { sql_wrapper_name = #s }
+            }
+            Attribute::Set(s) => {
+                quote! { set = #s }
+            }
+            Attribute::Memoize(None) => quote! { memoize },
+            Attribute::Memoize(Some(max_entries)) => quote! { memoize = #max_entries },
+            Attribute::Arena => quote! { arena },
+            Attribute::LegacySymbol => quote! { legacy_symbol },
             // This attribute is handled separately
             Attribute::Sql(to_sql_config) => {
                 quote! { sql = #to_sql_config }
             }
+            Attribute::PgVersion(range) => {
+                let range = range.to_string();
+                quote! { pg_version = #range }
+            }
         };
         tokens.append_all(quoted);
     }
@@ -141,6 +240,7 @@ impl Parse for Attribute {
         let found = match ident.to_string().as_str() {
             "immutable" => Self::Immutable,
             "strict" => Self::Strict,
+            "called_on_null_input" => Self::CalledOnNullInput,
             "stable" => Self::Stable,
             "volatile" => Self::Volatile,
             "raw" => Self::Raw,
@@ -149,6 +249,12 @@ impl Parse for Attribute {
             "parallel_safe" => Self::ParallelSafe,
             "parallel_unsafe" => Self::ParallelUnsafe,
             "parallel_restricted" => Self::ParallelRestricted,
+            "barrier" => Self::Barrier,
+            "check_version" => Self::CheckVersion,
+            "internal" => Self::Internal,
+            "public" => Self::Public,
+            "arena" => Self::Arena,
+            "legacy_symbol" => Self::LegacySymbol,
             "error" => {
                 let _eq: Token![=] = input.parse()?;
                 let literal: syn::LitStr = input.parse()?;
@@ -169,6 +275,54 @@ impl Parse for Attribute {
                 let literal: syn::Expr = input.parse()?;
                 Self::Cost(literal)
             }
+            "rows" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::Expr = input.parse()?;
+                Self::Rows(literal)
+            }
+            "transform" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::Transform(literal)
+            }
+            "external_transform" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::ExternalTransform(literal)
+            }
+            "owner" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::Owner(literal)
+            }
+            "grant" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::Grant(literal)
+            }
+            "sql_wrapper" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::SqlWrapper(literal)
+            }
+            "sql_wrapper_name" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::SqlWrapperName(literal)
+            }
+            "set" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                Self::Set(literal)
+            }
+            "memoize" => {
+                if input.peek(Token![=]) {
+                    let _eq: Token![=] = input.parse()?;
+                    Self::Memoize(Some(input.parse()?))
+                } else {
+                    Self::Memoize(None)
+                }
+            }
             "requires" => {
                 let _eq: syn::token::Eq = input.parse()?;
                 let content;
@@ -192,6 +346,13 @@ impl Parse for Attribute {
                     }
                 }
             }
+            "pg_version" => {
+                let _eq: Token![=] = input.parse()?;
+                let literal: syn::LitStr = input.parse()?;
+                let range = PgVersionRange::parse(&literal.value())
+                    .map_err(|e| syn::Error::new(literal.span(), e))?;
+                Self::PgVersion(range)
+            }
             e => {
                 return Err(syn::Error::new(
                     Span::call_site(),
@@ -19,17 +19,18 @@ mod operator;
 mod returning;
 
 pub use argument::PgExternArgumentEntity;
-pub use operator::PgOperatorEntity;
+pub use operator::{OperatorLink, PgOperatorEntity};
 pub use returning::{PgExternReturnEntity, PgExternReturnEntityIteratedItem};
 
 use crate::metadata::{Returns, SqlMapping};
 use crate::pgx_sql::PgxSql;
 use crate::to_sql::entity::ToSqlConfigEntity;
 use crate::to_sql::ToSql;
-use crate::ExternArgs;
-use crate::{SqlGraphEntity, SqlGraphIdentifier};
+use crate::{ControlFile, ExternArgs, Privileges};
+use crate::{SqlDeclaredEntity, SqlGraphEntity, SqlGraphIdentifier};
 
 use eyre::{eyre, WrapErr};
+use std::any::TypeId;
 
 /// The output of a [`PgExtern`](crate::pg_extern::PgExtern) from `quote::ToTokens::to_tokens`.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -45,9 +46,13 @@ pub struct PgExternEntity {
     pub file: &'static str,
     pub line: u32,
     pub extern_attrs: Vec<ExternArgs>,
-    pub search_path: Option<Vec<&'static str>>,
+    /// The configuration parameters set while the function runs, and their values, which are
+    /// quoted already if they need to be
+    pub settings: Vec<(&'static str, &'static str)>,
     pub operator: Option<PgOperatorEntity>,
     pub to_sql_config: ToSqlConfigEntity,
+    /// What the function's body was seen to do, for [`PgxSql::lint`]
+    pub facts: Vec<crate::FunctionFact>,
 }
 
 impl From<PgExternEntity> for SqlGraphEntity {
@@ -73,6 +78,336 @@ impl SqlGraphIdentifier for PgExternEntity {
     }
 }
 
+impl PgExternEntity {
+    /// Whether the function is declared `STRICT`, which it is when asked to be, or when none of its
+    /// arguments is an `Option<T>` (or `pgx::Internal`), as the function would never see a `NULL`,
+    /// unless it's asked to be `CALLED ON NULL INPUT`
+    ///
+    /// An operator calls the function, so `NULL op x` behaves exactly like the function does when
+    /// it's called with a `NULL`.  An argument is an `Option<T>` when either its type or the macro
+    /// says it is, as the function's wrapper unwraps the others.
+    pub fn is_strict(&self) -> bool {
+        if self.extern_attrs.contains(&ExternArgs::CalledOnNullInput) {
+            return false;
+        }
+        self.extern_attrs.iter().any(|attr| attr == &ExternArgs::Strict)
+            || !self
+                .metadata
+                .arguments
+                .iter()
+                .map(|arg| arg.optional)
+                .chain(self.fn_args.iter().map(|arg| arg.used_ty.optional))
+                .any(|optional| optional)
+    }
+
+    /// The symbol of the function's `extern "C"` wrapper, which is namespaced by its module
+    pub fn wrapper_symbol(&self) -> String {
+        crate::wrapper_symbol(self.module_path, self.unaliased_name)
+    }
+
+    /// Who should own the function and be able to execute it, from its `owner` and `grant` options
+    pub fn privileges(&self) -> Privileges {
+        let mut privileges = Privileges::default();
+        for attr in &self.extern_attrs {
+            match attr {
+                ExternArgs::Owner(role) => privileges.owner = Some(role.clone()),
+                ExternArgs::Grant(role) => privileges.grants.push(role.clone()),
+                _ => {}
+            }
+        }
+        privileges
+    }
+
+    /// Whether the function is only for the extension's own SQL to call, from its `internal` or
+    /// `public` option, or else the extension's `functions = "internal"` default, which doesn't
+    /// apply to operators' functions
+    pub fn is_internal(&self, control: &ControlFile) -> bool {
+        if self.extern_attrs.contains(&ExternArgs::Public) {
+            return false;
+        }
+        self.extern_attrs.contains(&ExternArgs::Internal)
+            || (control.internal_functions && self.operator.is_none())
+    }
+
+    /// Whether the function is created in [`INTERNAL_SCHEMA`](crate::INTERNAL_SCHEMA), which an
+    /// internal function is unless it's given a `schema` of its own
+    pub fn in_internal_schema(&self, control: &ControlFile) -> bool {
+        self.schema.is_none() && self.is_internal(control)
+    }
+
+    /// The `ALTER FUNCTION .. OWNER TO`, `GRANT` and `REVOKE` statements for `signature`.  An
+    /// internal function can't be executed by `PUBLIC`, only by the roles it's granted to.
+    fn privileges_sql(&self, context: &PgxSql, signature: &str) -> String {
+        let privileges = self.privileges().or(&context.control.privileges);
+        let mut sql = privileges.to_sql("FUNCTION", "EXECUTE", signature);
+        if self.is_internal(&context.control) && privileges.grants.is_empty() {
+            sql.push_str(&format!("\nREVOKE ALL ON FUNCTION {} FROM PUBLIC;", signature));
+        }
+        sql
+    }
+
+    /// The name and body of the `LANGUAGE sql` function to create alongside this one, from its
+    /// `sql_wrapper` option.  It's named by `sql_wrapper_name`, or else after this function, with a
+    /// `_sql` suffix.
+    pub fn sql_wrapper(&self) -> Option<(String, &str)> {
+        let body = self.extern_attrs.iter().find_map(|attr| match attr {
+            ExternArgs::SqlWrapper(body) => Some(body.as_str()),
+            _ => None,
+        })?;
+        let name = self
+            .extern_attrs
+            .iter()
+            .find_map(|attr| match attr {
+                ExternArgs::SqlWrapperName(name) => Some(name.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| format!("{}_sql", self.name));
+        Some((name, body))
+    }
+
+    /// The type of the operator's argument `index`, to compare with another operator's: its Rust
+    /// type and, as every `composite_type!()` is the same one, the composite type's name
+    fn operator_arg_type(&self, index: usize) -> Option<(TypeId, Option<String>)> {
+        let used_ty = &self.fn_args.get(index)?.used_ty;
+        Some((used_ty.ty_id, used_ty.composite_type.map(str::to_lowercase)))
+    }
+
+    /// The SQL type of the operator's argument `index`, to show in errors, or `None` if it doesn't
+    /// have one
+    fn operator_arg_sql(&self, index: usize) -> Option<String> {
+        let used_ty = &self.fn_args.get(index)?.used_ty;
+        let (sql, array_brackets) = match self.metadata.arguments.get(index)?.argument_sql {
+            Ok(SqlMapping::As(ref sql)) => (sql.clone(), false),
+            Ok(SqlMapping::Composite { array_brackets }) => {
+                (used_ty.composite_type?.to_string(), array_brackets)
+            }
+            Ok(SqlMapping::Source { array_brackets }) => {
+                (used_ty.ty_source.to_string(), array_brackets)
+            }
+            Ok(SqlMapping::Skip) | Err(_) => return None,
+        };
+        let sql = sql.to_lowercase();
+        Some(if array_brackets { format!("{sql}[]") } else { sql })
+    }
+
+    /// The operator of the extension which this operator's `COMMUTATOR` or `NEGATOR` names, of
+    /// those in `externs`, or `None` if it doesn't name one
+    ///
+    /// It's an error for the extension not to have it, as Postgres would leave a shell operator in
+    /// its place, which can't be used.  A commutator has the arguments of this operator, swapped,
+    /// and a negator has the same ones.  Of two such operators, the one in the same schema is
+    /// preferred.
+    pub fn linked_operator<'a>(
+        &self,
+        link: OperatorLink,
+        externs: impl IntoIterator<Item = &'a PgExternEntity>,
+    ) -> eyre::Result<Option<&'a PgExternEntity>> {
+        let (opname, linked) = match &self.operator {
+            Some(op) => match (op.opname, op.linked(link)) {
+                (Some(opname), Some(linked)) => (opname, linked),
+                _ => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let (left, right) = (self.operator_arg_type(0), self.operator_arg_type(1));
+        let wanted = match link {
+            OperatorLink::Commutator => (right, left),
+            OperatorLink::Negator => (left, right),
+        };
+        let (left_sql, right_sql) = (self.operator_arg_sql(0), self.operator_arg_sql(1));
+        let wanted_sql = match link {
+            OperatorLink::Commutator => (right_sql, left_sql),
+            OperatorLink::Negator => (left_sql, right_sql),
+        };
+        let display = |(left, right): &(Option<String>, Option<String>)| {
+            format!("({}, {})", left.as_deref().unwrap_or("?"), right.as_deref().unwrap_or("?"))
+        };
+
+        let named = externs
+            .into_iter()
+            .filter(|other| other.operator.as_ref().and_then(|op| op.opname) == Some(linked))
+            .collect::<Vec<_>>();
+        if named.is_empty() {
+            return Err(eyre!(
+                "`{}` is the {} of the `{}` operator `{}`, but the extension doesn't create a `{}` operator",
+                linked,
+                link.keyword(),
+                opname,
+                self.full_path,
+                linked,
+            ));
+        }
+        let mirrored = named
+            .iter()
+            .copied()
+            .filter(|other| (other.operator_arg_type(0), other.operator_arg_type(1)) == wanted);
+        let same_schema = |other: &&PgExternEntity| {
+            other.schema == self.schema && other.module_path == self.module_path
+        };
+        match mirrored.clone().find(same_schema).or_else(|| mirrored.clone().next()) {
+            Some(other) => Ok(Some(other)),
+            None => Err(eyre!(
+                "`{}` is the {} of the `{}` operator `{}` on {}, so it must be on {}, but the extension's `{}` operators are on {}",
+                linked,
+                link.keyword(),
+                opname,
+                self.full_path,
+                display(&(self.operator_arg_sql(0), self.operator_arg_sql(1))),
+                display(&wanted_sql),
+                linked,
+                named
+                    .iter()
+                    .map(|other| display(&(other.operator_arg_sql(0), other.operator_arg_sql(1))))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+        }
+    }
+
+    /// Whether this operator leaves its `COMMUTATOR` or `NEGATOR`, `other`, out of its
+    /// `CREATE OPERATOR`
+    ///
+    /// When two operators name each other, only the one created second names the first, which
+    /// exists by then, and Postgres links the first to it.  Naming an operator which doesn't exist
+    /// yet would make a shell of it instead, in whichever schema is first in the `search_path`.
+    pub fn leaves_out_linked_operator(&self, link: OperatorLink, other: &PgExternEntity) -> bool {
+        let opname = self.operator.as_ref().and_then(|op| op.opname);
+        let names_back = other.operator.as_ref().and_then(|op| op.linked(link)) == opname;
+        other != self && names_back && self < other
+    }
+
+    /// A function can only return a polymorphic type, such as `anyelement`, when one of its
+    /// arguments is of a polymorphic type of the same family, which is what Postgres resolves the
+    /// type it returns from
+    fn check_polymorphic_return(&self) -> eyre::Result<()> {
+        let returned = match self.metadata.retval.as_ref().map(|retval| &retval.return_sql) {
+            Some(Ok(Returns::One(SqlMapping::As(sql))))
+            | Some(Ok(Returns::SetOf(SqlMapping::As(sql)))) => sql,
+            _ => return Ok(()),
+        };
+        let family = match polymorphic_family(returned) {
+            Some(family) => family,
+            None => return Ok(()),
+        };
+        let has_polymorphic_argument = self.metadata.arguments.iter().any(|arg| {
+            matches!(&arg.argument_sql, Ok(SqlMapping::As(sql)) if polymorphic_family(sql) == Some(family))
+        });
+        if has_polymorphic_argument {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "`{}` returns `{}`, but none of its arguments is one of the types that resolve it: {}",
+                self.full_path,
+                returned,
+                family.join(", ")
+            ))
+        }
+    }
+
+    /// Only a set-returning function can say how many rows it returns, which Postgres otherwise
+    /// rejects with an error that doesn't say which function it's about
+    fn check_rows(&self) -> eyre::Result<()> {
+        let returns_set = matches!(
+            self.fn_return,
+            PgExternReturnEntity::SetOf { .. } | PgExternReturnEntity::Iterated { .. }
+        );
+        match self.extern_attrs.iter().find(|attr| matches!(attr, ExternArgs::Rows(_))) {
+            Some(ExternArgs::Rows(rows)) if !returns_set => Err(eyre!(
+                "`{}` has `rows = {}`, but only a function returning a `SetOfIterator` or `TableIterator` returns rows",
+                self.full_path,
+                rows,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The types whose `TRANSFORM`s the function uses, each of which has to be one the extension
+    /// creates or uses, unless it's an `external_transform`
+    fn transform_types(&self, context: &PgxSql) -> eyre::Result<Vec<&str>> {
+        let mut transform_types = Vec::new();
+        for attr in &self.extern_attrs {
+            match attr {
+                ExternArgs::Transform(ty) => {
+                    if !known_sql_types(context).contains(&ty.to_lowercase()) {
+                        return Err(eyre!(
+                            "`{}` uses the transform for type `{}`, which isn't a type the extension creates or uses.  \
+                            If it's from another extension, use `external_transform = \"{}\"` instead",
+                            self.full_path,
+                            ty,
+                            ty
+                        ));
+                    }
+                    transform_types.push(ty.as_str());
+                }
+                ExternArgs::ExternalTransform(ty) => transform_types.push(ty.as_str()),
+                _ => {}
+            }
+        }
+        Ok(transform_types)
+    }
+
+    /// The `OUT` parameters of a function which returns a tuple as a `record`.  Columns which
+    /// aren't named with `name!()` are named `column1`, `column2`, and so on, as Postgres would.
+    fn record_out_args(
+        &self,
+        context: &PgxSql,
+        schema_prefix: &str,
+        items: &[PgExternReturnEntityIteratedItem],
+    ) -> eyre::Result<Vec<String>> {
+        let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
+        let columns = match metadata_retval.return_sql {
+            Ok(Returns::Record(columns)) => columns,
+            Ok(_other) => {
+                return Err(eyre!(
+                    "Got non-record return variant SQL in what macro-expansion thought was a tuple"
+                ))
+            }
+            Err(err) => return Err(err).wrap_err("Error mapping return SQL"),
+        };
+
+        let mut out_args = Vec::with_capacity(items.len());
+        for (idx, (PgExternReturnEntityIteratedItem { ty, name }, column)) in
+            items.iter().zip(columns).enumerate()
+        {
+            let brackets = |array_brackets| if array_brackets { "[]" } else { "" };
+            let type_schema_prefix = context
+                .type_index_of(&ty.ty_id, ty.ty_source)
+                .map(|graph_index| context.schema_prefix_for(&graph_index))
+                .unwrap_or_default();
+            let (type_schema_prefix, sql_type) = match column {
+                SqlMapping::As(sql) => (type_schema_prefix, sql),
+                SqlMapping::Composite { array_brackets } => {
+                    let composite = ty.composite_type.ok_or_else(|| {
+                        eyre!("Macro expansion time suggested a composite_type!() in return")
+                    })?;
+                    let composite = context.composite_type_sql(composite, schema_prefix);
+                    (String::new(), format!("{}{}", composite, brackets(array_brackets)))
+                }
+                SqlMapping::Source { array_brackets } => {
+                    let source =
+                        context.source_only_to_sql_type(ty.ty_source).ok_or_else(|| {
+                            eyre!("Macro expansion time suggested a source only mapping in return")
+                        })?;
+                    (type_schema_prefix, format!("{}{}", source, brackets(array_brackets)))
+                }
+                SqlMapping::Skip => {
+                    return Err(eyre!("`{}` can't be a column of a returned tuple", ty.full_path))
+                }
+            };
+            out_args.push(format!(
+                "\tOUT {name} {type_schema_prefix}{sql_type}{maybe_comma}/* {ty_name} */",
+                name = match name {
+                    Some(name) => name.to_string(),
+                    None => format!("\"column{}\"", idx + 1),
+                },
+                maybe_comma = if idx + 1 < items.len() { ", " } else { " " },
+                ty_name = ty.full_path,
+            ));
+        }
+        Ok(out_args)
+    }
+}
+
 impl ToSql for PgExternEntity {
     #[tracing::instrument(
         level = "error",
@@ -81,192 +416,173 @@ impl ToSql for PgExternEntity {
     )]
     fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
         let self_index = context.externs[self];
-        let mut extern_attrs = self.extern_attrs.clone();
-        // if we already have a STRICT marker we do not need to add it
-        // presume we can upgrade, then disprove it
-        let mut strict_upgrade = !extern_attrs.iter().any(|i| i == &ExternArgs::Strict);
-        if strict_upgrade {
-            // It may be possible to infer a `STRICT` marker though.
-            // But we can only do that if the user hasn't used `Option<T>` or `pgx::Internal`
-            for arg in &self.metadata.arguments {
-                if arg.optional {
-                    strict_upgrade = false;
-                }
-            }
+        self.check_polymorphic_return()?;
+        self.check_rows()?;
+        if self.in_internal_schema(&context.control) && context.control.relocatable {
+            return Err(eyre!(
+                "`{}` is internal, but only an extension which isn't `relocatable` can create the `{}` schema for internal functions",
+                self.full_path,
+                crate::INTERNAL_SCHEMA,
+            ));
         }
-
-        if strict_upgrade {
+        let transform_types = self.transform_types(context)?;
+        let mut extern_attrs = self.extern_attrs.clone();
+        if self.is_strict() {
             extern_attrs.push(ExternArgs::Strict);
         }
         extern_attrs.sort();
         extern_attrs.dedup();
 
         let module_pathname = &context.get_module_pathname();
+        let schema_prefix = self
+            .schema
+            .map(|schema| format!("{}.", schema))
+            .unwrap_or_else(|| context.schema_prefix_for(&self_index));
 
-        let fn_sql = format!(
-            "\
-                CREATE {or_replace} FUNCTION {schema}\"{name}\"({arguments}) {returns}\n\
-                {extern_attrs}\
-                {search_path}\
-                LANGUAGE c /* Rust */\n\
-                AS '{module_pathname}', '{unaliased_name}_wrapper';\
-            ",
-            or_replace =
-                if extern_attrs.contains(&ExternArgs::CreateOrReplace) { "OR REPLACE" } else { "" },
-            schema = self
-                .schema
-                .map(|schema| format!("{}.", schema))
-                .unwrap_or_else(|| context.schema_prefix_for(&self_index)),
-            name = self.name,
-            module_pathname = module_pathname,
-            arguments = if !self.fn_args.is_empty() {
-                let mut args = Vec::new();
-                let metadata_without_arg_skips = &self
-                    .metadata
-                    .arguments
-                    .iter()
-                    .filter(|v| v.argument_sql != Ok(SqlMapping::Skip))
-                    .collect::<Vec<_>>();
-                for (idx, arg) in self.fn_args.iter().enumerate() {
-                    let graph_index = context
-                        .graph
-                        .neighbors_undirected(self_index)
-                        .find(|neighbor| match &context.graph[*neighbor] {
-                            SqlGraphEntity::Type(ty) => ty.id_matches(&arg.used_ty.ty_id),
-                            SqlGraphEntity::Enum(en) => en.id_matches(&arg.used_ty.ty_id),
-                            SqlGraphEntity::BuiltinType(defined) => {
-                                defined == arg.used_ty.full_path
-                            }
-                            _ => false,
-                        })
-                        .ok_or_else(|| eyre!("Could not find arg type in graph. Got: {:?}", arg))?;
-                    let needs_comma = idx < (metadata_without_arg_skips.len().saturating_sub(1));
-                    let metadata_argument = &self.metadata.arguments[idx];
-                    match metadata_argument.argument_sql {
-                        Ok(SqlMapping::As(ref argument_sql)) => {
-                            let buf = format!("\
+        // a function returning a tuple declares the tuple's columns as `OUT` parameters, after its
+        // arguments
+        let out_args = match &self.fn_return {
+            PgExternReturnEntity::Record { tys, .. } => {
+                self.record_out_args(context, &schema_prefix, tys)?
+            }
+            _ => Vec::new(),
+        };
+
+        // the argument types, which identify the function in `ALTER FUNCTION` and `GRANT`
+        let mut signature = Vec::new();
+        let arguments = if !self.fn_args.is_empty() || !out_args.is_empty() {
+            let mut args = Vec::new();
+            let metadata_without_arg_skips = &self
+                .metadata
+                .arguments
+                .iter()
+                .filter(|v| v.argument_sql != Ok(SqlMapping::Skip))
+                .collect::<Vec<_>>();
+            for (idx, arg) in self.fn_args.iter().enumerate() {
+                let graph_index = context
+                    .type_index_of(&arg.used_ty.ty_id, arg.used_ty.full_path)
+                    .ok_or_else(|| eyre!("Could not find arg type in graph. Got: {:?}", arg))?;
+                let needs_comma = idx < (metadata_without_arg_skips.len().saturating_sub(1))
+                    || !out_args.is_empty();
+                let metadata_argument = &self.metadata.arguments[idx];
+                let variadic = if metadata_argument.variadic { "VARIADIC " } else { "" };
+                let type_schema_prefix = context.schema_prefix_for(&graph_index);
+                match metadata_argument.argument_sql {
+                    Ok(SqlMapping::As(ref argument_sql)) => {
+                        signature.push(format!("{variadic}{type_schema_prefix}{argument_sql}"));
+                        let buf = format!("\
                                                 \t\"{pattern}\" {variadic}{schema_prefix}{sql_type}{default}{maybe_comma}/* {type_name} */\
                                             ",
                                                 pattern = arg.pattern,
-                                                schema_prefix = context.schema_prefix_for(&graph_index),
+                                                schema_prefix = type_schema_prefix,
                                                 // First try to match on [`TypeId`] since it's most reliable.
                                                 sql_type = argument_sql,
                                                 default = if let Some(def) = arg.used_ty.default { format!(" DEFAULT {}", def) } else { String::from("") },
-                                                variadic = if metadata_argument.variadic { "VARIADIC " } else { "" },
+                                                variadic = variadic,
                                                 maybe_comma = if needs_comma { ", " } else { " " },
                                                 type_name = metadata_argument.type_name,
                                         );
-                            args.push(buf);
-                        }
-                        Ok(SqlMapping::Composite { array_brackets }) => {
-                            let sql =
-                                self.fn_args[idx]
-                                    .used_ty
-                                    .composite_type
-                                    .map(|v| {
-                                        if array_brackets {
-                                            format!("{v}[]")
-                                        } else {
-                                            format!("{v}")
-                                        }
-                                    })
-                                    .ok_or_else(|| {
-                                        eyre!(
+                        args.push(buf);
+                    }
+                    Ok(SqlMapping::Composite { array_brackets }) => {
+                        // the composite type's name carries its own schema
+                        let sql = self.fn_args[idx]
+                            .used_ty
+                            .composite_type
+                            .map(|v| context.composite_type_sql(v, &schema_prefix))
+                            .map(|v| if array_brackets { format!("{v}[]") } else { v })
+                            .ok_or_else(|| {
+                                eyre!(
                                     "Macro expansion time suggested a composite_type!() in return"
                                 )
-                                    })?;
-                            let buf = format!("\
-                                \t\"{pattern}\" {variadic}{schema_prefix}{sql_type}{default}{maybe_comma}/* {type_name} */\
+                            })?;
+                        signature.push(format!("{variadic}{sql}"));
+                        let buf = format!("\
+                                \t\"{pattern}\" {variadic}{sql_type}{default}{maybe_comma}/* {type_name} */\
                             ",
                                 pattern = arg.pattern,
-                                schema_prefix = context.schema_prefix_for(&graph_index),
                                 // First try to match on [`TypeId`] since it's most reliable.
                                 sql_type = sql,
                                 default = if let Some(def) = arg.used_ty.default { format!(" DEFAULT {}", def) } else { String::from("") },
-                                variadic = if metadata_argument.variadic { "VARIADIC " } else { "" },
+                                variadic = variadic,
                                 maybe_comma = if needs_comma { ", " } else { " " },
                                 type_name = metadata_argument.type_name,
                         );
-                            args.push(buf);
-                        }
-                        Ok(SqlMapping::Source { array_brackets }) => {
-                            let sql =
-                                context
-                                    .source_only_to_sql_type(arg.used_ty.ty_source)
-                                    .map(|v| {
-                                        if array_brackets {
-                                            format!("{v}[]")
-                                        } else {
-                                            format!("{v}")
-                                        }
-                                    })
-                                    .ok_or_else(|| {
-                                        eyre!(
+                        args.push(buf);
+                    }
+                    Ok(SqlMapping::Source { array_brackets }) => {
+                        let sql = context
+                            .source_only_to_sql_type(arg.used_ty.ty_source)
+                            .map(|v| if array_brackets { format!("{v}[]") } else { format!("{v}") })
+                            .ok_or_else(|| {
+                                eyre!(
                                     "Macro expansion time suggested a source only mapping in return"
                                 )
-                                    })?;
-                            let buf = format!("\
+                            })?;
+                        signature.push(format!("{variadic}{type_schema_prefix}{sql}"));
+                        let buf = format!("\
                                 \t\"{pattern}\" {variadic}{schema_prefix}{sql_type}{default}{maybe_comma}/* {type_name} */\
                             ",
                                 pattern = arg.pattern,
-                                schema_prefix = context.schema_prefix_for(&graph_index),
+                                schema_prefix = type_schema_prefix,
                                 // First try to match on [`TypeId`] since it's most reliable.
                                 sql_type = sql,
                                 default = if let Some(def) = arg.used_ty.default { format!(" DEFAULT {}", def) } else { String::from("") },
-                                variadic = if metadata_argument.variadic { "VARIADIC " } else { "" },
+                                variadic = variadic,
                                 maybe_comma = if needs_comma { ", " } else { " " },
                                 type_name = metadata_argument.type_name,
                         );
-                            args.push(buf);
-                        }
-                        Ok(SqlMapping::Skip) => (),
-                        Err(err) => {
-                            match context.source_only_to_sql_type(arg.used_ty.ty_source) {
-                                Some(source_only_mapping) => {
-                                    let buf = format!("\
+                        args.push(buf);
+                    }
+                    Ok(SqlMapping::Skip) => (),
+                    Err(err) => {
+                        match context.source_only_to_sql_type(arg.used_ty.ty_source) {
+                            Some(source_only_mapping) => {
+                                signature.push(format!(
+                                    "{variadic}{type_schema_prefix}{source_only_mapping}"
+                                ));
+                                let buf = format!("\
                                             \t\"{pattern}\" {variadic}{schema_prefix}{sql_type}{default}{maybe_comma}/* {type_name} */\
                                         ",
                                             pattern = arg.pattern,
-                                            schema_prefix = context.schema_prefix_for(&graph_index),
+                                            schema_prefix = type_schema_prefix,
                                             // First try to match on [`TypeId`] since it's most reliable.
                                             sql_type = source_only_mapping,
                                             default = if let Some(def) = arg.used_ty.default { format!(" DEFAULT {}", def) } else { String::from("") },
-                                            variadic = if metadata_argument.variadic { "VARIADIC " } else { "" },
+                                            variadic = variadic,
                                             maybe_comma = if needs_comma { ", " } else { " " },
                                             type_name = metadata_argument.type_name,
                                     );
-                                    args.push(buf);
-                                }
-                                None => return Err(err).wrap_err("While mapping argument"),
+                                args.push(buf);
                             }
+                            None => return Err(err).wrap_err("While mapping argument"),
                         }
                     }
                 }
-                String::from("\n") + &args.join("\n") + "\n"
-            } else {
-                Default::default()
-            },
-            returns = match &self.fn_return {
-                PgExternReturnEntity::None => String::from("RETURNS void"),
-                PgExternReturnEntity::Type { ty } => {
-                    let graph_index = context
-                        .graph
-                        .neighbors_undirected(self_index)
-                        .find(|neighbor| match &context.graph[*neighbor] {
-                            SqlGraphEntity::Type(neighbor_ty) => neighbor_ty.id_matches(&ty.ty_id),
-                            SqlGraphEntity::Enum(neighbor_en) => neighbor_en.id_matches(&ty.ty_id),
-                            SqlGraphEntity::BuiltinType(defined) => &*defined == ty.full_path,
-                            _ => false,
-                        })
-                        .ok_or_else(|| eyre!("Could not find return type in graph."))?;
-                    let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
-                    let metadata_retval_sql = match metadata_retval.return_sql {
+            }
+            args.extend(out_args.iter().cloned());
+            String::from("\n") + &args.join("\n") + "\n"
+        } else {
+            Default::default()
+        };
+        let returns = match &self.fn_return {
+            PgExternReturnEntity::None => String::from("RETURNS void"),
+            PgExternReturnEntity::Type { ty } => {
+                let graph_index = context
+                    .type_index_of(&ty.ty_id, ty.full_path)
+                    .ok_or_else(|| eyre!("Could not find return type in graph."))?;
+                let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
+                let mut type_schema_prefix = context.schema_prefix_for(&graph_index);
+                let metadata_retval_sql = match metadata_retval.return_sql {
                         Ok(Returns::One(SqlMapping::As(ref sql))) => sql.clone(),
-                        Ok(Returns::One(SqlMapping::Composite { array_brackets })) => ty.composite_type.unwrap().to_string()
-                        + if array_brackets {
-                            "[]"
-                        } else {
-                            ""
+                        Ok(Returns::One(SqlMapping::Composite { array_brackets })) => {
+                            type_schema_prefix = String::new();
+                            context.composite_type_sql(ty.composite_type.unwrap(), &schema_prefix)
+                            + if array_brackets {
+                                "[]"
+                            } else {
+                                ""
+                            }
                         },
                         Ok(Returns::SetOf(SqlMapping::Source { array_brackets })) =>
                             context.source_only_to_sql_type(ty.ty_source).unwrap().to_string() + if array_brackets {
@@ -282,33 +598,29 @@ impl ToSql for PgExternEntity {
                             }
                         },
                     };
-                    format!(
-                        "RETURNS {schema_prefix}{sql_type} /* {full_path} */",
-                        sql_type = metadata_retval_sql,
-                        schema_prefix = context.schema_prefix_for(&graph_index),
-                        full_path = ty.full_path
-                    )
-                }
-                PgExternReturnEntity::SetOf { ty, optional: _, result: _ } => {
-                    let graph_index = context
-                        .graph
-                        .neighbors_undirected(self_index)
-                        .find(|neighbor| match &context.graph[*neighbor] {
-                            SqlGraphEntity::Type(neighbor_ty) => neighbor_ty.id_matches(&ty.ty_id),
-                            SqlGraphEntity::Enum(neighbor_en) => neighbor_en.id_matches(&ty.ty_id),
-                            SqlGraphEntity::BuiltinType(defined) => defined == ty.full_path,
-                            _ => false,
-                        })
-                        .ok_or_else(|| eyre!("Could not find return type in graph."))?;
-                    let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
-                    let metadata_retval_sql = match metadata_retval.return_sql {
+                format!(
+                    "RETURNS {schema_prefix}{sql_type} /* {full_path} */",
+                    sql_type = metadata_retval_sql,
+                    schema_prefix = type_schema_prefix,
+                    full_path = ty.full_path
+                )
+            }
+            PgExternReturnEntity::SetOf { ty, optional: _, result: _ } => {
+                let graph_index = context
+                    .type_index_of(&ty.ty_id, ty.full_path)
+                    .ok_or_else(|| eyre!("Could not find return type in graph."))?;
+                let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
+                let mut type_schema_prefix = context.schema_prefix_for(&graph_index);
+                let metadata_retval_sql = match metadata_retval.return_sql {
                             Ok(Returns::SetOf(SqlMapping::As(ref sql))) => sql.clone(),
-                            Ok(Returns::SetOf(SqlMapping::Composite { array_brackets })) =>
-                                ty.composite_type.unwrap().to_string() + if array_brackets {
+                            Ok(Returns::SetOf(SqlMapping::Composite { array_brackets })) => {
+                                type_schema_prefix = String::new();
+                                context.composite_type_sql(ty.composite_type.unwrap(), &schema_prefix) + if array_brackets {
                                     "[]"
                                 } else {
                                     ""
-                                },
+                                }
+                            },
                             Ok(Returns::SetOf(SqlMapping::Source { array_brackets })) =>
                                 context.source_only_to_sql_type(ty.ty_source).unwrap().to_string() + if array_brackets {
                                     "[]"
@@ -318,24 +630,24 @@ impl ToSql for PgExternEntity {
                             Ok(_other) => return Err(eyre!("Got non-setof mapped/composite return variant SQL in what macro-expansion thought was a setof")),
                             Err(err) => return Err(err).wrap_err("Error mapping return SQL"),
                         };
-                    format!(
-                        "RETURNS SETOF {schema_prefix}{sql_type} /* {full_path} */",
-                        sql_type = metadata_retval_sql,
-                        schema_prefix = context.schema_prefix_for(&graph_index),
-                        full_path = ty.full_path
-                    )
-                }
-                PgExternReturnEntity::Iterated { tys: table_items, optional: _, result: _ } => {
-                    let mut items = String::new();
-                    let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
-                    let metadata_retval_sqls = match metadata_retval.return_sql {
+                format!(
+                    "RETURNS SETOF {schema_prefix}{sql_type} /* {full_path} */",
+                    sql_type = metadata_retval_sql,
+                    schema_prefix = type_schema_prefix,
+                    full_path = ty.full_path
+                )
+            }
+            PgExternReturnEntity::Iterated { tys: table_items, optional: _, result: _ } => {
+                let mut items = String::new();
+                let metadata_retval = self.metadata.retval.clone().ok_or_else(|| eyre!("Macro expansion time and SQL resolution time had differing opinions about the return value existing"))?;
+                let metadata_retval_sqls = match metadata_retval.return_sql {
                             Ok(Returns::Table(variants)) => {
                                 let mut retval_sqls = vec![];
                                 for (idx, variant) in variants.iter().enumerate() {
                                     let sql = match variant {
                                         SqlMapping::As(sql) => sql.clone(),
                                         SqlMapping::Composite { array_brackets } => {
-                                            let composite = table_items[idx].ty.composite_type.unwrap().to_string();
+                                            let composite = context.composite_type_sql(table_items[idx].ty.composite_type.unwrap(), &schema_prefix);
                                             composite  + if *array_brackets {
                                                 "[]"
                                             } else {
@@ -358,73 +670,132 @@ impl ToSql for PgExternEntity {
                             Err(err) => return Err(err).wrap_err("Error mapping return SQL"),
                         };
 
-                    for (idx, returning::PgExternReturnEntityIteratedItem { ty, name: col_name }) in
-                        table_items.iter().enumerate()
-                    {
-                        let graph_index =
-                            context.graph.neighbors_undirected(self_index).find(|neighbor| {
-                                match &context.graph[*neighbor] {
-                                    SqlGraphEntity::Type(neightbor_ty) => {
-                                        neightbor_ty.id_matches(&ty.ty_id)
-                                    }
-                                    SqlGraphEntity::Enum(neightbor_en) => {
-                                        neightbor_en.id_matches(&ty.ty_id)
-                                    }
-                                    SqlGraphEntity::BuiltinType(defined) => defined == ty.ty_source,
-                                    _ => false,
-                                }
-                            });
-
-                        let needs_comma = idx < (table_items.len() - 1);
-                        let item = format!(
-                                "\n\t{col_name} {schema_prefix}{ty_resolved}{needs_comma} /* {ty_name} */",
-                                col_name = col_name.expect("An iterator of tuples should have `named!()` macro declarations."),
-                                schema_prefix = if let Some(graph_index) = graph_index {
-                                    context.schema_prefix_for(&graph_index)
-                                } else { "".into() },
-                                ty_resolved = metadata_retval_sqls[idx],
-                                needs_comma = if needs_comma { ", " } else { " " },
-                                ty_name = ty.full_path
-                        );
-                        items.push_str(&item);
-                    }
-                    format!("RETURNS TABLE ({}\n)", items)
+                for (idx, returning::PgExternReturnEntityIteratedItem { ty, name: col_name }) in
+                    table_items.iter().enumerate()
+                {
+                    // a composite type's name carries its own schema
+                    let graph_index = if ty.composite_type.is_some() {
+                        None
+                    } else {
+                        context.type_index_of(&ty.ty_id, ty.ty_source)
+                    };
+
+                    let needs_comma = idx < (table_items.len() - 1);
+                    let item = format!(
+                        "\n\t{col_name} {schema_prefix}{ty_resolved}{needs_comma} /* {ty_name} */",
+                        col_name = col_name.expect(
+                            "An iterator of tuples should have `named!()` macro declarations."
+                        ),
+                        schema_prefix = if let Some(graph_index) = graph_index {
+                            context.schema_prefix_for(&graph_index)
+                        } else {
+                            "".into()
+                        },
+                        ty_resolved = metadata_retval_sqls[idx],
+                        needs_comma = if needs_comma { ", " } else { " " },
+                        ty_name = ty.full_path
+                    );
+                    items.push_str(&item);
                 }
-                PgExternReturnEntity::Trigger => String::from("RETURNS trigger"),
-            },
-            search_path = if let Some(search_path) = &self.search_path {
-                let retval = format!("SET search_path TO {}", search_path.join(", "));
-                retval + "\n"
-            } else {
-                Default::default()
-            },
-            extern_attrs = if extern_attrs.is_empty() {
+                format!("RETURNS TABLE ({}\n)", items)
+            }
+            PgExternReturnEntity::Record { .. } => String::from("RETURNS record"),
+            PgExternReturnEntity::Trigger => String::from("RETURNS trigger"),
+        };
+        let settings = self
+            .settings
+            .iter()
+            .map(|(name, value)| format!("SET {} TO {}\n", name, value))
+            .collect::<String>();
+        let extern_attrs_sql = if extern_attrs.is_empty() {
+            String::default()
+        } else {
+            let mut retval = extern_attrs
+                .iter()
+                .filter(|attr| **attr != ExternArgs::CreateOrReplace)
+                .map(|attr| format!("{}", attr).to_uppercase())
+                .collect::<Vec<_>>()
+                .join(" ");
+            retval.push('\n');
+            retval
+        };
+        let fn_sql = format!(
+            "\
+                CREATE {or_replace} FUNCTION {schema}\"{name}\"({arguments}) {returns}\n\
+                {extern_attrs}\
+                {transform}\
+                {settings}\
+                LANGUAGE c /* Rust */\n\
+                AS '{module_pathname}', '{wrapper_symbol}';\
+            ",
+            or_replace =
+                if extern_attrs.contains(&ExternArgs::CreateOrReplace) { "OR REPLACE" } else { "" },
+            schema = schema_prefix,
+            name = self.name,
+            module_pathname = module_pathname,
+            transform = if transform_types.is_empty() {
                 String::default()
             } else {
-                let mut retval = extern_attrs
+                let types = transform_types
                     .iter()
-                    .filter(|attr| **attr != ExternArgs::CreateOrReplace)
-                    .map(|attr| format!("{}", attr).to_uppercase())
+                    .map(|ty| format!("FOR TYPE {}", ty))
                     .collect::<Vec<_>>()
-                    .join(" ");
-                retval.push('\n');
-                retval
+                    .join(", ");
+                format!("TRANSFORM {}\n", types)
             },
-            unaliased_name = self.unaliased_name,
+            extern_attrs = extern_attrs_sql,
+            wrapper_symbol = self.wrapper_symbol(),
+        );
+        let privileges_sql = self.privileges_sql(
+            context,
+            &format!("{}\"{}\"({})", schema_prefix, self.name, signature.join(", ")),
         );
 
+        // the companion `LANGUAGE sql` function is created right after the one it calls, with the
+        // same arguments, result, and options
+        let sql_wrapper_sql = match self.sql_wrapper() {
+            Some((wrapper_name, body)) => {
+                let wrapper_privileges_sql = self.privileges_sql(
+                    context,
+                    &format!("{}\"{}\"({})", schema_prefix, wrapper_name, signature.join(", ")),
+                );
+                format!(
+                    "\n\n\
+                        -- {module_path}::{name} (sql_wrapper)\n\
+                        CREATE {or_replace} FUNCTION {schema_prefix}\"{wrapper_name}\"({arguments}) {returns}\n\
+                        {extern_attrs_sql}\
+                        {settings}\
+                        LANGUAGE sql\n\
+                        AS $pgx_sql_wrapper$\n{body}\n$pgx_sql_wrapper$;\
+                        {wrapper_privileges_sql}\
+                    ",
+                    module_path = self.module_path,
+                    name = self.name,
+                    or_replace = if extern_attrs.contains(&ExternArgs::CreateOrReplace) {
+                        "OR REPLACE"
+                    } else {
+                        ""
+                    },
+                )
+            }
+            None => String::new(),
+        };
+
         let ext_sql = format!(
             "\n\
                                 -- {file}:{line}\n\
                                 -- {module_path}::{name}\n\
                                 {requires}\
                                 {fn_sql}\
+                                {privileges_sql}\
+                                {sql_wrapper_sql}\
                             ",
             name = self.name,
             module_path = self.module_path,
             file = self.file,
             line = self.line,
             fn_sql = fn_sql,
+            privileges_sql = privileges_sql,
             requires = {
                 let requires_attrs = self
                     .extern_attrs
@@ -453,12 +824,21 @@ impl ToSql for PgExternEntity {
 
         let rendered = if let Some(op) = &self.operator {
             let mut optionals = vec![];
-            if let Some(it) = op.commutator {
-                optionals.push(format!("\tCOMMUTATOR = {}", it));
-            };
-            if let Some(it) = op.negator {
-                optionals.push(format!("\tNEGATOR = {}", it));
-            };
+            for link in OperatorLink::ALL {
+                let other = match self.linked_operator(link, context.externs.keys())? {
+                    Some(other) if !self.leaves_out_linked_operator(link, other) => other,
+                    _ => continue,
+                };
+                // qualified, so it's the one the extension made, wherever that is
+                let other_schema_prefix = context.schema_prefix_for(&context.externs[other]);
+                let other_opname = other.operator.as_ref().and_then(|op| op.opname).unwrap();
+                let other_sql = if other_schema_prefix.is_empty() {
+                    other_opname.to_string()
+                } else {
+                    format!("OPERATOR({}{})", other_schema_prefix, other_opname)
+                };
+                optionals.push(format!("\t{} = {}", link.keyword(), other_sql));
+            }
             if let Some(it) = op.restrict {
                 optionals.push(format!("\tRESTRICT = {}", it));
             };
@@ -480,18 +860,10 @@ impl ToSql for PgExternEntity {
                 .fn_args
                 .get(0)
                 .ok_or_else(|| eyre!("Did not find `left_arg` for operator `{}`.", self.name))?;
-            let left_arg_graph_index = context
-                .graph
-                .neighbors_undirected(self_index)
-                .find(|neighbor| match &context.graph[*neighbor] {
-                    SqlGraphEntity::Type(ty) => ty.id_matches(&left_fn_arg.used_ty.ty_id),
-                    SqlGraphEntity::Enum(en) => en.id_matches(&left_fn_arg.used_ty.ty_id),
-                    SqlGraphEntity::BuiltinType(defined) => defined == &left_arg.type_name,
-                    _ => false,
-                })
-                .ok_or_else(|| {
-                    eyre!("Could not find left arg type in graph. Got: {:?}", left_arg)
-                })?;
+            let left_arg_graph_index =
+                context.type_index_of(&left_fn_arg.used_ty.ty_id, left_arg.type_name).ok_or_else(
+                    || eyre!("Could not find left arg type in graph. Got: {:?}", left_arg),
+                )?;
             let left_arg_sql = match left_arg.argument_sql {
                 Ok(SqlMapping::As(ref sql)) => sql.clone(),
                 Ok(SqlMapping::Composite { array_brackets }) => {
@@ -534,14 +906,7 @@ impl ToSql for PgExternEntity {
                 .get(1)
                 .ok_or_else(|| eyre!("Did not find `left_arg` for operator `{}`.", self.name))?;
             let right_arg_graph_index = context
-                .graph
-                .neighbors_undirected(self_index)
-                .find(|neighbor| match &context.graph[*neighbor] {
-                    SqlGraphEntity::Type(ty) => ty.id_matches(&right_fn_arg.used_ty.ty_id),
-                    SqlGraphEntity::Enum(en) => en.id_matches(&right_fn_arg.used_ty.ty_id),
-                    SqlGraphEntity::BuiltinType(defined) => defined == &right_arg.type_name,
-                    _ => false,
-                })
+                .type_index_of(&right_fn_arg.used_ty.ty_id, right_arg.type_name)
                 .ok_or_else(|| {
                     eyre!("Could not find right arg type in graph. Got: {:?}", right_arg)
                 })?;
@@ -581,13 +946,14 @@ impl ToSql for PgExternEntity {
             let operator_sql = format!("\n\n\
                                                     -- {file}:{line}\n\
                                                     -- {module_path}::{name}\n\
-                                                    CREATE OPERATOR {opname} (\n\
-                                                        \tPROCEDURE=\"{name}\",\n\
+                                                    CREATE OPERATOR {schema}{opname} (\n\
+                                                        \tPROCEDURE={schema}\"{name}\",\n\
                                                         \tLEFTARG={schema_prefix_left}{left_arg}, /* {left_name} */\n\
                                                         \tRIGHTARG={schema_prefix_right}{right_arg}{maybe_comma} /* {right_name} */\n\
                                                         {optionals}\
                                                     );\
                                                     ",
+                                                    schema = schema_prefix,
                                                     opname = op.opname.unwrap(),
                                                     file = self.file,
                                                     line = self.line,
@@ -610,3 +976,54 @@ impl ToSql for PgExternEntity {
         Ok(rendered)
     }
 }
+
+/// The polymorphic pseudo-types that resolve each other's types
+const POLYMORPHIC_FAMILIES: &[&[&str]] = &[
+    &["anyelement", "anyarray", "anynonarray", "anyenum", "anyrange", "anymultirange"],
+    &[
+        "anycompatible",
+        "anycompatiblearray",
+        "anycompatiblenonarray",
+        "anycompatiblerange",
+        "anycompatiblemultirange",
+    ],
+];
+
+/// The family of polymorphic pseudo-types that `sql` is one of
+fn polymorphic_family(sql: &str) -> Option<&'static [&'static str]> {
+    POLYMORPHIC_FAMILIES.iter().copied().find(|family| family.contains(&sql))
+}
+
+/// The SQL names of the types the extension creates or its functions use, in lower case, which
+/// Postgres folds unquoted names to
+fn known_sql_types(context: &PgxSql) -> std::collections::HashSet<String> {
+    let mut known = std::collections::HashSet::new();
+    known.extend(context.types.keys().map(|ty| ty.name.to_lowercase()));
+    known.extend(context.enums.keys().map(|ty| ty.name.to_lowercase()));
+    known.extend(context.domains.keys().map(|ty| ty.name.to_lowercase()));
+    for extension_sql in context.extension_sqls.keys() {
+        known.extend(extension_sql.creates.iter().filter_map(|created| match created {
+            SqlDeclaredEntity::Type(_) | SqlDeclaredEntity::Enum(_) => {
+                Some(created.sql().to_lowercase())
+            }
+            SqlDeclaredEntity::Function(_) => None,
+        }));
+    }
+    for function in context.externs.keys() {
+        let arguments = function.metadata.arguments.iter().map(|arg| &arg.argument_sql);
+        let arguments = arguments.filter_map(|sql| match sql {
+            Ok(SqlMapping::As(sql)) => Some(sql),
+            _ => None,
+        });
+        let returned = match function.metadata.retval.as_ref().map(|retval| &retval.return_sql) {
+            Some(Ok(Returns::One(SqlMapping::As(sql))))
+            | Some(Ok(Returns::SetOf(SqlMapping::As(sql)))) => Some(sql),
+            _ => None,
+        };
+        for sql in arguments.chain(returned) {
+            // a transform for a type is used for arrays of it too
+            known.insert(sql.trim_end_matches("[]").to_lowercase());
+        }
+    }
+    known
+}
@@ -26,3 +26,33 @@ pub struct PgOperatorEntity {
     pub hashes: bool,
     pub merges: bool,
 }
+
+/// The clauses of `CREATE OPERATOR` which name another operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatorLink {
+    /// `COMMUTATOR`, which takes the same arguments, swapped
+    Commutator,
+    /// `NEGATOR`, which takes the same arguments
+    Negator,
+}
+
+impl OperatorLink {
+    pub const ALL: [OperatorLink; 2] = [OperatorLink::Commutator, OperatorLink::Negator];
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            OperatorLink::Commutator => "COMMUTATOR",
+            OperatorLink::Negator => "NEGATOR",
+        }
+    }
+}
+
+impl PgOperatorEntity {
+    /// The name of the operator this one's `link` names, if it names one
+    pub fn linked(&self, link: OperatorLink) -> Option<&'static str> {
+        match link {
+            OperatorLink::Commutator => self.commutator,
+            OperatorLink::Negator => self.negator,
+        }
+    }
+}
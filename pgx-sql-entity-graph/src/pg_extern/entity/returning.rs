@@ -32,6 +32,11 @@ pub enum PgExternReturnEntity {
         optional: bool, /* Eg `Option<TableIterator<T>>` */
         result: bool,   /* Eg `Result<TableIterator<T>, E>` */
     },
+    Record {
+        tys: Vec<PgExternReturnEntityIteratedItem>,
+        optional: bool, /* Eg `Option<(A, B)>` */
+        result: bool,   /* Eg `Result<(A, B), E>` */
+    },
     Trigger,
 }
 
@@ -0,0 +1,223 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_extern]` body facts for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use proc_macro2::{Delimiter, Spacing, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use std::collections::BTreeSet;
+
+/// Something a `#[pg_extern]` function's body was seen to do, used by [`PgxSql::lint`](crate::PgxSql::lint).
+///
+/// These are found by looking for the names of known APIs in the body's tokens, where they're
+/// called or are part of a path, so neither string literals and comments, which aren't identifier
+/// tokens, nor variables that happen to share a name are mistaken for them.  Only direct uses are
+/// seen:  a function that calls a helper which uses SPI doesn't "use SPI" itself.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FunctionFact {
+    /// Runs queries with `Spi`
+    UsesSpi,
+    /// Reads a GUC's current value
+    ReadsGuc,
+    /// Generates random numbers
+    UsesRandom,
+    /// Reads the current time, which changes between calls
+    ReadsClock,
+    /// Reads the time the transaction or statement started, which doesn't change within a statement
+    ReadsTransactionTime,
+    /// Takes a `PgLwLock` exclusively, to modify shared state
+    LocksExclusive,
+}
+
+impl FunctionFact {
+    /// Scan a function body for the facts it exhibits
+    pub fn scan(block: &syn::Block) -> Vec<FunctionFact> {
+        let mut facts = BTreeSet::new();
+        scan_tokens(block.to_token_stream(), &mut facts);
+        facts.into_iter().collect()
+    }
+
+    fn from_ident(ident: &str, is_method: bool) -> Option<FunctionFact> {
+        if is_method {
+            // `PgLwLock::exclusive()` is the only API known by a method's name
+            return (ident == "exclusive").then_some(FunctionFact::LocksExclusive);
+        }
+        match ident {
+            "Spi" | "SpiClient" | "SPI_connect" | "SPI_execute" | "SPI_execute_plan"
+            | "SPI_cursor_open" => Some(FunctionFact::UsesSpi),
+            "GetConfigOption" | "GetConfigOptionByName" | "GetConfigOptionFlags" => {
+                Some(FunctionFact::ReadsGuc)
+            }
+            "rand" | "random" | "thread_rng" | "OsRng" | "pg_strong_random" | "pg_random"
+            | "set_seed" | "drandom" => Some(FunctionFact::UsesRandom),
+            "SystemTime" | "Instant" | "clock_timestamp" | "GetCurrentTimestamp" => {
+                Some(FunctionFact::ReadsClock)
+            }
+            "transaction_timestamp"
+            | "statement_timestamp"
+            | "GetCurrentTransactionStartTimestamp"
+            | "GetCurrentStatementStartTimestamp" => Some(FunctionFact::ReadsTransactionTime),
+            _ => None,
+        }
+    }
+}
+
+fn scan_tokens(tokens: TokenStream2, facts: &mut BTreeSet<FunctionFact>) {
+    let trees = tokens.into_iter().collect::<Vec<_>>();
+    for (index, tree) in trees.iter().enumerate() {
+        match tree {
+            TokenTree::Group(group) => scan_tokens(group.stream(), facts),
+            TokenTree::Ident(ident) => {
+                let is_method = index > 0 && is_punct(&trees[index - 1], '.');
+                let in_path = is_path_separator(&trees[index + 1..])
+                    || (index >= 2 && is_path_separator(&trees[index - 2..index]));
+                let called = matches!(
+                    trees.get(index + 1),
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis
+                );
+                if in_path || called {
+                    facts.extend(FunctionFact::from_ident(&ident.to_string(), is_method))
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn is_punct(tree: &TokenTree, ch: char) -> bool {
+    matches!(tree, TokenTree::Punct(punct) if punct.as_char() == ch)
+}
+
+/// Do `trees` start with a `::`?
+fn is_path_separator(trees: &[TokenTree]) -> bool {
+    match trees {
+        [TokenTree::Punct(first), second, ..] => {
+            first.as_char() == ':' && first.spacing() == Spacing::Joint && is_punct(second, ':')
+        }
+        _ => false,
+    }
+}
+
+impl ToTokens for FunctionFact {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let variant = match self {
+            FunctionFact::UsesSpi => quote! { UsesSpi },
+            FunctionFact::ReadsGuc => quote! { ReadsGuc },
+            FunctionFact::UsesRandom => quote! { UsesRandom },
+            FunctionFact::ReadsClock => quote! { ReadsClock },
+            FunctionFact::ReadsTransactionTime => quote! { ReadsTransactionTime },
+            FunctionFact::LocksExclusive => quote! { LocksExclusive },
+        };
+        tokens.extend(quote! { ::pgx::pgx_sql_entity_graph::FunctionFact::#variant });
+    }
+}
+
+impl core::fmt::Display for FunctionFact {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FunctionFact::UsesSpi => write!(f, "uses SPI"),
+            FunctionFact::ReadsGuc => write!(f, "reads a GUC"),
+            FunctionFact::UsesRandom => write!(f, "generates random numbers"),
+            FunctionFact::ReadsClock => write!(f, "reads the current time"),
+            FunctionFact::ReadsTransactionTime => {
+                write!(f, "reads the transaction or statement start time")
+            }
+            FunctionFact::LocksExclusive => write!(f, "takes an exclusive `PgLwLock`"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FunctionFact;
+    use syn::parse_quote;
+
+    #[test]
+    fn scan_finds_known_apis() {
+        let block: syn::Block = parse_quote! {{
+            let count = Spi::get_one::<i64>("SELECT count(*) FROM t");
+            let mut shared = STATE.exclusive();
+            *shared += rand::random::<i64>();
+            count
+        }};
+        assert_eq!(
+            FunctionFact::scan(&block),
+            vec![FunctionFact::UsesSpi, FunctionFact::UsesRandom, FunctionFact::LocksExclusive]
+        );
+    }
+
+    #[test]
+    fn scan_ignores_lookalikes() {
+        let block: syn::Block = parse_quote! {{
+            let exclusive = true;
+            let spi = "Spi";
+            exclusive
+        }};
+        assert_eq!(FunctionFact::scan(&block), vec![]);
+    }
+
+    #[test]
+    fn scan_ignores_strings_and_comments() {
+        let block: syn::Block = parse_quote! {{
+            // Spi::connect() isn't called here
+            /// nor is rand::random()
+            let query = "SELECT Spi::connect(), clock_timestamp()";
+            info!("{} at SystemTime::now()", query);
+            query
+        }};
+        assert_eq!(FunctionFact::scan(&block), vec![]);
+    }
+
+    #[test]
+    fn scan_ignores_variables_named_like_apis() {
+        let block: syn::Block = parse_quote! {{
+            let random = 4;
+            let (rand, Instant) = (random + 1, random * 2);
+            let spi = Point { random, rand };
+            spi.random + spi.rand + spi.random() + Instant
+        }};
+        assert_eq!(FunctionFact::scan(&block), vec![]);
+    }
+
+    #[test]
+    fn scan_finds_apis_in_macros_and_by_their_paths() {
+        let block: syn::Block = parse_quote! {{
+            use std::time::Instant;
+            let started = Instant::now();
+            info!("{:?}", pg_sys::pg_strong_random(buf.as_mut_ptr(), buf.len()));
+            started
+        }};
+        assert_eq!(
+            FunctionFact::scan(&block),
+            vec![FunctionFact::UsesRandom, FunctionFact::ReadsClock]
+        );
+        let block: syn::Block = parse_quote! {{ GetConfigOption(name.as_ptr(), false, false) }};
+        assert_eq!(FunctionFact::scan(&block), vec![FunctionFact::ReadsGuc]);
+    }
+
+    #[test]
+    fn scan_tells_clocks_apart() {
+        let block: syn::Block = parse_quote! {{
+            let started = pgx::clock::statement_timestamp();
+            let now = std::time::SystemTime::now();
+            (started, now)
+        }};
+        assert_eq!(
+            FunctionFact::scan(&block),
+            vec![FunctionFact::ReadsClock, FunctionFact::ReadsTransactionTime]
+        );
+        let block: syn::Block = parse_quote! {{ pgx::clock::transaction_timestamp() }};
+        assert_eq!(FunctionFact::scan(&block), vec![FunctionFact::ReadsTransactionTime]);
+    }
+}
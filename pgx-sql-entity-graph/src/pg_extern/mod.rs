@@ -18,18 +18,21 @@ to the `pgx` framework and very subject to change between versions. While you ma
 mod argument;
 mod attribute;
 pub mod entity;
+mod fact;
 mod operator;
 mod returning;
 mod search_path;
+mod setting;
 
 pub use argument::PgExternArgument;
+pub use fact::FunctionFact;
 pub use operator::PgOperator;
-pub use returning::NameMacro;
 
 use crate::ToSqlConfig;
 use attribute::Attribute;
 use operator::{PgxOperatorAttributeWithIdent, PgxOperatorOpName};
 use search_path::SearchPathList;
+use setting::Setting;
 
 use crate::enrich::CodeEnrichment;
 use crate::enrich::ToEntityGraphTokens;
@@ -42,9 +45,10 @@ use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{Meta, Token};
 
-use self::returning::Returning;
+pub(crate) use self::returning::Returning;
 
-use super::UsedType;
+use crate::metadata::FUNC_MAX_ARGS;
+use crate::NamedType;
 
 /// A parsed `#[pg_extern]` item.
 ///
@@ -74,16 +78,34 @@ pub struct PgExtern {
     func: syn::ItemFn,
     to_sql_config: ToSqlConfig,
     operator: Option<PgOperator>,
-    search_path: Option<SearchPathList>,
+    settings: Vec<Setting>,
+    /// Check the extension's installed SQL matches its library before every call, from
+    /// `check_version`
+    check_version: bool,
+    /// The most results a `memoize` function caches per statement, from `memoize` or
+    /// `memoize = max_entries`
+    memoize: Option<TokenStream2>,
+    /// Give each row of a set-returning function its own arena, from `arena`
+    arena: bool,
+    /// Also export the wrapper and its `Pg_finfo_record` by their old, unnamespaced symbols,
+    /// from `legacy_symbol`
+    legacy_symbol: bool,
     inputs: Vec<PgExternArgument>,
     input_types: Vec<syn::Type>,
     returns: Returning,
+    facts: Vec<FunctionFact>,
 }
 
 impl PgExtern {
     pub fn new(attr: TokenStream2, item: TokenStream2) -> Result<CodeEnrichment<Self>, syn::Error> {
         let mut attrs = Vec::new();
         let mut to_sql_config: Option<ToSqlConfig> = None;
+        let mut pg_version = None;
+        let mut settings = Vec::<Setting>::new();
+        let mut check_version = false;
+        let mut memoize = Vec::new();
+        let mut arena = false;
+        let mut legacy_symbol = false;
 
         let parser = Punctuated::<Attribute, Token![,]>::parse_terminated;
         let punctuated_attrs = parser.parse2(attr)?;
@@ -92,21 +114,86 @@ impl PgExtern {
                 Attribute::Sql(config) => {
                     to_sql_config.get_or_insert(config);
                 }
+                Attribute::PgVersion(range) => {
+                    pg_version.get_or_insert(range);
+                }
+                Attribute::Set(literal) => {
+                    settings.push(Setting::parse(&literal)?);
+                }
+                Attribute::CheckVersion => {
+                    check_version = true;
+                }
+                Attribute::Memoize(max_entries) => {
+                    memoize.push(max_entries);
+                }
+                Attribute::Arena => {
+                    arena = true;
+                }
+                Attribute::LegacySymbol => {
+                    legacy_symbol = true;
+                }
                 attr => {
                     attrs.push(attr);
                 }
             }
         }
 
+        if attrs.contains(&Attribute::Strict) && attrs.contains(&Attribute::CalledOnNullInput) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "a function can't be both `strict` and `called_on_null_input`",
+            ));
+        }
+        if attrs.contains(&Attribute::Internal) && attrs.contains(&Attribute::Public) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "a function can't be both `internal` and `public`",
+            ));
+        }
+        if let Some(idx) = attrs.iter().position(|attr| attr == &Attribute::Barrier) {
+            let conflicting = [
+                Attribute::Immutable,
+                Attribute::Stable,
+                Attribute::ParallelSafe,
+                Attribute::ParallelRestricted,
+            ];
+            if let Some(conflict) = conflicting.iter().find(|attr| attrs.contains(attr)) {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    format!(
+                        "`barrier` makes a function `volatile` and `parallel_unsafe`, so it can't also be `{}`",
+                        conflict.to_token_stream()
+                    ),
+                ));
+            }
+            attrs.splice(idx..=idx, [Attribute::Volatile, Attribute::ParallelUnsafe]);
+        }
+        let sql_wrappers =
+            attrs.iter().filter(|attr| matches!(attr, Attribute::SqlWrapper(_))).count();
+        let sql_wrapper_names =
+            attrs.iter().filter(|attr| matches!(attr, Attribute::SqlWrapperName(_))).count();
+        if sql_wrappers > 1 || sql_wrapper_names > 1 {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`sql_wrapper` and `sql_wrapper_name` may only be given once",
+            ));
+        }
+        if sql_wrapper_names > sql_wrappers {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`sql_wrapper_name` names the function created by `sql_wrapper`, which wasn't given",
+            ));
+        }
+
         let mut to_sql_config = to_sql_config.unwrap_or_default();
+        to_sql_config.pg_version = pg_version;
 
         let func = syn::parse2::<syn::ItemFn>(item)?;
 
         if let Some(ref mut content) = to_sql_config.content {
             let value = content.value();
-            let updated_value = value
-                .replace("@FUNCTION_NAME@", &*(func.sig.ident.to_string() + "_wrapper"))
-                + "\n";
+            // `@FUNCTION_NAME@` is replaced by the `ToSqlConfigEntity`, which knows the module path
+            let updated_value = value + "\n";
             *content = syn::LitStr::new(&updated_value, Span::call_site());
         }
 
@@ -114,22 +201,105 @@ impl PgExtern {
             crate::ident_is_acceptable_to_postgres(&func.sig.ident)?;
         }
         let operator = Self::operator(&func)?;
-        let search_path = Self::search_path(&func)?;
+        // `#[search_path(..)]` is sugar for `set = "search_path = .."`
+        if let Some(search_path) = Self::search_path(&func)? {
+            settings.insert(0, search_path.to_setting());
+        }
+        for (idx, setting) in settings.iter().enumerate() {
+            if settings[..idx].iter().any(|earlier| earlier.same_parameter(setting)) {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    format!("`{}` is set more than once", setting.name()),
+                ));
+            }
+        }
         let inputs = Self::inputs(&func)?;
         let input_types = Self::input_types(&func)?;
         let returns = Returning::try_from(&func.sig.output)?;
+        Self::validate_memoize(&memoize, &attrs, &func, &returns)?;
+        Self::validate_arena(arena, &returns)?;
+        let memoize = memoize.pop().map(|max_entries| match max_entries {
+            Some(max_entries) => max_entries.to_token_stream(),
+            None => quote! { ::pgx::memoize::DEFAULT_MAX_ENTRIES },
+        });
+        let facts = FunctionFact::scan(&func.block);
         Ok(CodeEnrichment(Self {
             attrs,
             func,
             to_sql_config,
             operator,
-            search_path,
+            settings,
+            check_version,
+            memoize,
+            arena,
+            legacy_symbol,
             inputs,
             input_types,
             returns,
+            facts,
         }))
     }
 
+    /// Check a `memoize` function's results can be cached for the rest of the statement
+    fn validate_memoize(
+        memoize: &[Option<syn::LitInt>],
+        attrs: &[Attribute],
+        func: &syn::ItemFn,
+        returns: &Returning,
+    ) -> syn::Result<()> {
+        if memoize.is_empty() {
+            return Ok(());
+        }
+        let error = |message: &str| Err(syn::Error::new(Span::call_site(), message));
+        if memoize.len() > 1 {
+            return error("`memoize` may only be given once");
+        }
+        if !attrs.contains(&Attribute::Stable) && !attrs.contains(&Attribute::Immutable) {
+            return error(
+                "`memoize` caches a function's results for the rest of the statement, so it has to be `stable` or `immutable`",
+            );
+        }
+        if attrs.contains(&Attribute::Raw) {
+            return error("a `raw` function's arguments can't be what its results are cached by");
+        }
+        match returns {
+            Returning::Type(ty)
+                if ty.resolved_ty != syn::parse_quote!(())
+                    && ty.resolved_ty != syn::parse_quote!(pg_sys::Datum)
+                    && ty.resolved_ty != syn::parse_quote!(pgx::pg_sys::Datum)
+                    && ty.resolved_ty != syn::parse_quote!(::pgx::pg_sys::Datum) => {}
+            _ => {
+                return error(
+                    "`memoize` can only cache a function's one result, which can't be a `Datum`",
+                )
+            }
+        }
+        // its arguments are cached by as a tuple, which is only `Hash` up to 12 elements
+        if func.sig.inputs.len() > 12 {
+            return Err(syn::Error::new(
+                func.sig.inputs.span(),
+                "a `memoize` function can have at most 12 arguments",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check an `arena` function makes rows, which are what its arena is reset between
+    fn validate_arena(arena: bool, returns: &Returning) -> syn::Result<()> {
+        if !arena {
+            return Ok(());
+        }
+        match returns {
+            Returning::SetOf { .. }
+            | Returning::Iterated { .. }
+            | Returning::IteratedRow { .. } => Ok(()),
+            _ => Err(syn::Error::new(
+                Span::call_site(),
+                "`arena` gives each row of a set-returning function its own arena, so it has to return a `SetOfIterator` or `TableIterator`; use `pgx::arena::with_temp_context()` in other functions",
+            )),
+        }
+    }
+
     fn input_types(func: &syn::ItemFn) -> syn::Result<Vec<syn::Type>> {
         func.sig
             .inputs
@@ -138,9 +308,8 @@ impl PgExtern {
                 match v {
                     syn::FnArg::Receiver(_) => None,
                     syn::FnArg::Typed(pat_ty) => {
-                        let static_ty = pat_ty.ty.clone();
-                        let mut static_ty = match UsedType::new(*static_ty) {
-                            Ok(v) => v.resolved_ty,
+                        let mut static_ty = match NamedType::new(&pat_ty.ty) {
+                            Ok(v) => v.used_ty.resolved_ty,
                             Err(e) => return Some(Err(e)),
                         };
                         staticize_lifetimes(&mut static_ty);
@@ -192,10 +361,7 @@ impl PgExtern {
                             in_commented_sql_block = false;
                         } else if in_commented_sql_block {
                             let sql = retval.get_or_insert_with(String::default);
-                            let line = inner.value().trim_start().replace(
-                                "@FUNCTION_NAME@",
-                                &*(self.func.sig.ident.to_string() + "_wrapper"),
-                            ) + "\n";
+                            let line = inner.value().trim_start().to_string() + "\n";
                             sql.push_str(&*line);
                         }
                     }
@@ -257,6 +423,33 @@ impl PgExtern {
     }
 
     fn inputs(func: &syn::ItemFn) -> syn::Result<Vec<PgExternArgument>> {
+        if let Some(extra) = func.sig.inputs.iter().nth(FUNC_MAX_ARGS) {
+            return Err(syn::Error::new(
+                extra.span(),
+                format!(
+                    "`{}` has {} arguments, but Postgres functions can have at most {}",
+                    func.sig.ident,
+                    func.sig.inputs.len(),
+                    FUNC_MAX_ARGS
+                ),
+            ));
+        }
+        // report every unsupported argument at once
+        let mut unsupported: Option<syn::Error> = None;
+        for input in &func.sig.inputs {
+            if let syn::FnArg::Typed(pat_ty) = input {
+                if let Err(e) = argument::validate_argument_type(&pat_ty.ty, &func.sig.generics) {
+                    match unsupported.as_mut() {
+                        Some(errors) => errors.combine(e),
+                        None => unsupported = Some(e),
+                    }
+                }
+            }
+        }
+        if let Some(errors) = unsupported {
+            return Err(errors);
+        }
+
         let mut args = Vec::default();
         for input in &func.sig.inputs {
             let arg = PgExternArgument::build(input.clone())?;
@@ -276,13 +469,14 @@ impl PgExtern {
             .iter()
             .map(|attr| attr.to_sql_entity_graph_tokens())
             .collect::<Punctuated<_, Token![,]>>();
-        let search_path = self.search_path.clone().into_iter();
+        let settings = &self.settings;
         let inputs = &self.inputs;
         let inputs_iter = inputs.iter().map(|v| v.entity_tokens());
 
         let input_types = self.input_types.iter().cloned();
 
         let returns = &self.returns;
+        let facts = &self.facts;
 
         let return_type = match &self.func.sig.output {
             syn::ReturnType::Default => None,
@@ -305,8 +499,9 @@ impl PgExtern {
 
         let sql_graph_entity_fn_name =
             syn::Ident::new(&format!("__pgx_internals_fn_{}", ident), Span::call_site());
+        let sql_graph_entity_fn_symbol = crate::entity_symbol_tokens("fn", ident);
         quote_spanned! { self.func.sig.span() =>
-            #[no_mangle]
+            #[export_name = #sql_graph_entity_fn_symbol]
             #[doc(hidden)]
             pub extern "Rust" fn  #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
                 extern crate alloc;
@@ -327,11 +522,11 @@ impl PgExtern {
                     file: file!(),
                     line: line!(),
                     extern_attrs: vec![#extern_attrs],
-                    #[allow(clippy::or_fun_call)]
-                    search_path: None #( .unwrap_or_else(|| Some(vec![#search_path])) )*,
+                    settings: vec![#(#settings),*],
                     #[allow(clippy::or_fun_call)]
                     operator: None #( .unwrap_or_else(|| Some(#operator)) )*,
                     to_sql_config: #to_sql_config,
+                    facts: vec![#(#facts),*],
                 };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Function(submission)
             }
@@ -343,13 +538,64 @@ impl PgExtern {
             &format!("pg_finfo_{}_wrapper", self.func.sig.ident),
             Span::call_site(),
         );
+        // `FUNC_MAX_ARGS` arguments are checked for when parsing, but Postgres may have been built
+        // with fewer
+        let nargs = self.func.sig.inputs.len();
+        let max_args_check = (nargs > 0).then(|| {
+            let message = format!(
+                "`{}` has {} arguments, more than this Postgres' `FUNC_MAX_ARGS`",
+                self.func.sig.ident, nargs
+            );
+            quote_spanned! { self.func.sig.inputs.span() =>
+                const _: () = if #nargs > ::pgx::pg_sys::FUNC_MAX_ARGS as usize {
+                    panic!(#message)
+                };
+            }
+        });
+        let finfo_symbol = crate::wrapper_symbol_tokens("pg_finfo_", &self.func.sig.ident);
         quote_spanned! { self.func.sig.span() =>
-            #[no_mangle]
+            #[export_name = #finfo_symbol]
             #[doc(hidden)]
             pub extern "C" fn #finfo_name() -> &'static ::pgx::pg_sys::Pg_finfo_record {
                 const V1_API: ::pgx::pg_sys::Pg_finfo_record = ::pgx::pg_sys::Pg_finfo_record { api_version: 1 };
                 &V1_API
             }
+            #max_args_check
+        }
+    }
+
+    /// With `legacy_symbol`, export the wrapper and its `Pg_finfo_record` as `{name}_wrapper` and
+    /// `pg_finfo_{name}_wrapper` too, so functions created by an earlier version of the
+    /// extension, whose `AS` clause still names those, keep working until it's upgraded
+    fn legacy_symbol_tokens(&self) -> TokenStream2 {
+        if !self.legacy_symbol {
+            return quote! {};
+        }
+        let func_name = &self.func.sig.ident;
+        let func_name_wrapper = Ident::new(&format!("{func_name}_wrapper"), func_name.span());
+        let legacy_wrapper =
+            Ident::new(&format!("__pgx_legacy_{func_name}_wrapper"), func_name.span());
+        let legacy_finfo =
+            Ident::new(&format!("__pgx_legacy_pg_finfo_{func_name}_wrapper"), func_name.span());
+        let wrapper_symbol = format!("{func_name}_wrapper");
+        let finfo_symbol = format!("pg_finfo_{func_name}_wrapper");
+        let finfo_name = Ident::new(&finfo_symbol, func_name.span());
+        let returns = match &self.returns {
+            Returning::None => quote! {},
+            _ => quote! { -> ::pgx::pg_sys::Datum },
+        };
+        quote_spanned! { self.func.sig.span() =>
+            #[export_name = #wrapper_symbol]
+            #[doc(hidden)]
+            pub unsafe extern "C" fn #legacy_wrapper(fcinfo: ::pgx::pg_sys::FunctionCallInfo) #returns {
+                #func_name_wrapper(fcinfo)
+            }
+
+            #[export_name = #finfo_symbol]
+            #[doc(hidden)]
+            pub extern "C" fn #legacy_finfo() -> &'static ::pgx::pg_sys::Pg_finfo_record {
+                #finfo_name()
+            }
         }
     }
 
@@ -359,11 +605,38 @@ impl PgExtern {
             &format!("{}_wrapper", &self.func.sig.ident.to_string()),
             self.func.sig.ident.span(),
         );
+        let wrapper_symbol = crate::wrapper_symbol_tokens("", &self.func.sig.ident);
         let func_generics = &self.func.sig.generics;
         let is_raw = self.extern_attrs().contains(&Attribute::Raw);
         // We use a `_` prefix to make functions with no args more satisfied during linting.
         let fcinfo_ident = syn::Ident::new("_fcinfo", self.func.sig.ident.span());
 
+        // In debug builds, remember which `parallel_safe` function is running so pgx can name it
+        // if it does something that isn't allowed in a parallel worker
+        let parallel_safe_function = if self.extern_attrs().contains(&Attribute::ParallelSafe) {
+            quote! {
+                #[cfg(debug_assertions)]
+                let _parallel_safe_function = ::pgx::parallel::ParallelSafeFunction::enter(stringify!(#func_name));
+            }
+        } else {
+            quote! {}
+        };
+
+        let version_check = if self.check_version {
+            quote! {
+                ::pgx::version_check::__check_version();
+            }
+        } else {
+            quote! {}
+        };
+
+        // counts the call until the wrapper returns or unwinds, with pgx's `function-stats`
+        // feature, and is nothing without it
+        let sql_name = self.name();
+        let function_stats = quote! {
+            ::pgx::__pgx_function_stats!(#sql_name, concat!(module_path!(), "::", stringify!(#func_name)));
+        };
+
         let args = &self.inputs;
         let arg_pats = args
             .iter()
@@ -371,6 +644,7 @@ impl PgExtern {
             .collect::<Vec<_>>();
         let arg_fetches = args.iter().enumerate().map(|(idx, arg)| {
             let pat = &arg_pats[idx];
+            let arg_name = arg.name.clone().unwrap_or_else(|| arg.pat.to_string());
             let resolved_ty = &arg.used_ty.resolved_ty;
             if arg.used_ty.resolved_ty.to_token_stream().to_string() == quote!(pgx::pg_sys::FunctionCallInfo).to_token_stream().to_string()
                 || arg.used_ty.resolved_ty.to_token_stream().to_string() == quote!(pg_sys::FunctionCallInfo).to_token_stream().to_string()
@@ -390,21 +664,33 @@ impl PgExtern {
                         let #pat = unsafe { ::pgx::fcinfo::pg_getarg_datum_raw(#fcinfo_ident, #idx) as #resolved_ty };
                     },
                     (false, None) => quote_spanned! { pat.span() =>
-                        let #pat = unsafe { ::pgx::fcinfo::pg_getarg::<#resolved_ty>(#fcinfo_ident, #idx).unwrap_or_else(|| panic!("{} is null", stringify!{#pat})) };
+                        let #pat = unsafe { ::pgx::fcinfo::pg_getarg_named::<#resolved_ty>(#fcinfo_ident, #idx, #arg_name).unwrap_or_else(|| panic!("{} is null", stringify!{#pat})) };
                     },
                     (false, Some(inner)) => quote_spanned! { pat.span() =>
-                        let #pat = unsafe { ::pgx::fcinfo::pg_getarg::<#inner>(#fcinfo_ident, #idx) };
+                        let #pat = unsafe { ::pgx::fcinfo::pg_getarg_named::<#inner>(#fcinfo_ident, #idx, #arg_name) };
                     },
                 }
             }
         });
 
+        // each row of an `arena` function is made with its own arena
+        let in_row_scope = |next_row: TokenStream2| {
+            if self.arena {
+                quote! { ::pgx::arena::__row_scope(|| #next_row) }
+            } else {
+                next_row
+            }
+        };
+
         match &self.returns {
             Returning::None => quote_spanned! { self.func.sig.span() =>
-                  #[no_mangle]
+                  #[export_name = #wrapper_symbol]
                   #[doc(hidden)]
                   #[::pgx::pgx_macros::pg_guard]
                   pub unsafe extern "C" fn #func_name_wrapper #func_generics(#fcinfo_ident: ::pgx::pg_sys::FunctionCallInfo) {
+                      #parallel_safe_function
+                      #version_check
+                      #function_stats
                       #(
                           #arg_fetches
                       )*
@@ -422,18 +708,20 @@ impl PgExtern {
                 } else if retval_ty.result {
                     if retval_ty.optional.is_some() {
                         // returning `Result<Option<T>>`
+                        let result = report_result(quote! { #result_ident });
                         quote_spanned! {
                             self.func.sig.output.span() =>
-                                match ::pgx::datum::IntoDatum::into_datum(#result_ident) {
+                                match ::pgx::datum::IntoDatum::into_datum(#result) {
                                     Some(datum) => datum,
                                     None => unsafe { ::pgx::fcinfo::pg_return_null(#fcinfo_ident) },
                                 }
                         }
                     } else {
                         // returning Result<T>
+                        let result = report_result(quote! { #result_ident });
                         quote_spanned! {
                             self.func.sig.output.span() =>
-                                ::pgx::datum::IntoDatum::into_datum(#result_ident).unwrap_or_else(|| panic!("returned Datum was NULL"))
+                                ::pgx::datum::IntoDatum::into_datum(#result).unwrap_or_else(|| panic!("returned Datum was NULL"))
                         }
                     }
                 } else if retval_ty.resolved_ty == syn::parse_quote!(pg_sys::Datum)
@@ -458,11 +746,84 @@ impl PgExtern {
                     }
                 };
 
+                // a `memoize` function is only called for arguments it hasn't been in this
+                // statement, which are cached by a tuple of their `MemoizeKey`s, and it has the
+                // extension track statements for that
+                let call = match &self.memoize {
+                    Some(max_entries) => quote_spanned! { self.func.sig.span() =>
+                        {
+                            ::pgx::__pgx_register_on_load!(::pgx::statement::track());
+                            ::pgx::memoize::StatementCache::new(concat!(module_path!(), "::", stringify!(#func_name)))
+                                .max_entries(#max_entries)
+                                .get_or_insert_with(
+                                    (#( ::pgx::memoize::MemoizeKey::memoize_key(&#arg_pats), )*),
+                                    move || unsafe { #func_name(#(#arg_pats),*) },
+                                )
+                        }
+                    },
+                    None => quote_spanned! { self.func.sig.span() =>
+                        unsafe { #func_name(#(#arg_pats),*) }
+                    },
+                };
+
+                // the type of a polymorphic value is only known at runtime, so check it's the one
+                // the call resolved the return type to
+                let check_polymorphic = if is_polymorphic(&retval_ty.original_ty) {
+                    quote_spanned! { self.func.sig.output.span() =>
+                        unsafe { ::pgx::datum::__check_polymorphic_return(stringify!(#func_name), &#result_ident, #fcinfo_ident) };
+                    }
+                } else {
+                    quote! {}
+                };
+
+                quote_spanned! { self.func.sig.span() =>
+                    #[export_name = #wrapper_symbol]
+                    #[doc(hidden)]
+                    #[::pgx::pgx_macros::pg_guard]
+                    pub unsafe extern "C" fn #func_name_wrapper #func_generics(#fcinfo_ident: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pg_sys::Datum {
+                        #parallel_safe_function
+                        #version_check
+                        #function_stats
+                        #(
+                            #arg_fetches
+                        )*
+
+                        #[allow(unused_unsafe)] // unwrapped fn might be unsafe
+                        let #result_ident = #call;
+                        #check_polymorphic
+
+                        #retval_transform
+                    }
+                }
+            }
+            Returning::Record { tys: _retval_tys, optional, result } => {
+                let result_ident = syn::Ident::new("result", self.func.sig.span());
+                let record = if *result {
+                    report_result(quote! { #result_ident })
+                } else {
+                    quote! { #result_ident }
+                };
+                let retval_transform = if *optional {
+                    quote_spanned! { self.func.sig.output.span() =>
+                        match #record {
+                            Some(record) => unsafe { ::pgx::htup::tuple_into_record(#fcinfo_ident, record) },
+                            None => unsafe { ::pgx::fcinfo::pg_return_null(#fcinfo_ident) },
+                        }
+                    }
+                } else {
+                    quote_spanned! { self.func.sig.output.span() =>
+                        unsafe { ::pgx::htup::tuple_into_record(#fcinfo_ident, #record) }
+                    }
+                };
+
                 quote_spanned! { self.func.sig.span() =>
-                    #[no_mangle]
+                    #[export_name = #wrapper_symbol]
                     #[doc(hidden)]
                     #[::pgx::pgx_macros::pg_guard]
                     pub unsafe extern "C" fn #func_name_wrapper #func_generics(#fcinfo_ident: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pg_sys::Datum {
+                        #parallel_safe_function
+                        #version_check
+                        #function_stats
                         #(
                             #arg_fetches
                         )*
@@ -481,15 +842,16 @@ impl PgExtern {
                         #func_name(#(#arg_pats),*)
                     }
                 } else if *result {
+                    let result = report_result(quote! { #func_name(#(#arg_pats),*) });
                     if *optional {
                         quote_spanned! { self.func.sig.span() =>
                             use ::pgx::pg_sys::panic::ErrorReportable;
-                            #func_name(#(#arg_pats),*).report()
+                            #result.report()
                         }
                     } else {
                         quote_spanned! { self.func.sig.span() =>
                             use ::pgx::pg_sys::panic::ErrorReportable;
-                            Some(#func_name(#(#arg_pats),*).report())
+                            Some(#result.report())
                         }
                     }
                 } else {
@@ -498,35 +860,44 @@ impl PgExtern {
                     }
                 };
 
+                let next_row = in_row_scope(quote_spanned! { self.func.sig.span() =>
+                    ::pgx::iter::SetOfIterator::srf_next(#fcinfo_ident, || {
+                        #( #arg_fetches )*
+                        #result_handler
+                    })
+                });
+
                 quote_spanned! { self.func.sig.span() =>
-                    #[no_mangle]
+                    #[export_name = #wrapper_symbol]
                     #[doc(hidden)]
                     #[::pgx::pgx_macros::pg_guard]
                     pub unsafe extern "C" fn #func_name_wrapper #func_generics(#fcinfo_ident: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pg_sys::Datum {
+                        #parallel_safe_function
+                        #version_check
+                        #function_stats
                         #[allow(unused_unsafe)]
                         unsafe {
                             // SAFETY: the caller has asserted that `fcinfo` is a valid FunctionCallInfo pointer, allocated by Postgres
                             // with all its fields properly setup.  Unless the user is calling this wrapper function directly, this
                             // will always be the case
-                            ::pgx::iter::SetOfIterator::srf_next(#fcinfo_ident, || {
-                                #( #arg_fetches )*
-                                #result_handler
-                            })
+                            #next_row
                         }
                     }
                 }
             }
-            Returning::Iterated { tys: _retval_tys, optional, result } => {
+            Returning::Iterated { optional, result, .. }
+            | Returning::IteratedRow { optional, result, .. } => {
                 let result_handler = if *optional {
                     // don't need unsafe annotations because of the larger unsafe block coming up
                     quote_spanned! { self.func.sig.span() =>
                         #func_name(#(#arg_pats),*)
                     }
                 } else if *result {
+                    let result = report_result(quote! { #func_name(#(#arg_pats),*) });
                     quote_spanned! { self.func.sig.span() =>
                         {
                             use ::pgx::pg_sys::panic::ErrorReportable;
-                            Some(#func_name(#(#arg_pats),*).report())
+                            Some(#result.report())
                         }
                     }
                 } else {
@@ -535,20 +906,27 @@ impl PgExtern {
                     }
                 };
 
+                let next_row = in_row_scope(quote_spanned! { self.func.sig.span() =>
+                    ::pgx::iter::TableIterator::srf_next(#fcinfo_ident, || {
+                        #( #arg_fetches )*
+                        #result_handler
+                    })
+                });
+
                 quote_spanned! { self.func.sig.span() =>
-                    #[no_mangle]
+                    #[export_name = #wrapper_symbol]
                     #[doc(hidden)]
                     #[::pgx::pgx_macros::pg_guard]
                     pub unsafe extern "C" fn #func_name_wrapper #func_generics(#fcinfo_ident: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pg_sys::Datum {
+                        #parallel_safe_function
+                        #version_check
+                        #function_stats
                         #[allow(unused_unsafe)]
                         unsafe {
                             // SAFETY: the caller has asserted that `fcinfo` is a valid FunctionCallInfo pointer, allocated by Postgres
                             // with all its fields properly setup.  Unless the user is calling this wrapper function directly, this
                             // will always be the case
-                            ::pgx::iter::TableIterator::srf_next(#fcinfo_ident, || {
-                                #( #arg_fetches )*
-                                #result_handler
-                            })
+                            #next_row
                         }
                     }
                 }
@@ -557,6 +935,51 @@ impl PgExtern {
     }
 }
 
+/// Wraps an expression evaluating to a `Result<T, E>` so that its error is converted into a
+/// `pgx::pg_sys::panic::ErrorReport` when `E` implements `pgx::ErrorReportable`, and left as-is
+/// otherwise
+fn report_result(result: TokenStream2) -> TokenStream2 {
+    quote! {
+        ({
+            #[allow(unused_imports)]
+            use ::pgx::error_report::__private::{ViaDisplay as _, ViaErrorReportable as _};
+            (&::pgx::error_report::__private::ResultWrapper::new(#result)).into_report_result()
+        })
+    }
+}
+
+/// The polymorphic pseudo-types a function can return, whose type is resolved by each call
+const POLYMORPHIC_TYPES: &[&str] =
+    &["AnyElement", "AnyArray", "AnyCompatible", "AnyCompatibleArray"];
+
+/// Is `ty` one of the [`POLYMORPHIC_TYPES`], or an `Option` or `Result` of one?
+///
+/// Like the rest of the macro, this goes by the types' names, as it can't know what they are.
+fn is_polymorphic(ty: &syn::Type) -> bool {
+    let last = match ty {
+        syn::Type::Path(path) => match path.path.segments.last() {
+            Some(last) => last,
+            None => return false,
+        },
+        syn::Type::Group(group) => return is_polymorphic(&group.elem),
+        syn::Type::Paren(paren) => return is_polymorphic(&paren.elem),
+        _ => return false,
+    };
+    if POLYMORPHIC_TYPES.iter().any(|polymorphic| last.ident == polymorphic) {
+        return true;
+    }
+    if last.ident != "Option" && last.ident != "Result" {
+        return false;
+    }
+    match &last.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(inner)) => is_polymorphic(inner),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 impl ToEntityGraphTokens for PgExtern {
     fn to_entity_graph_tokens(&self) -> TokenStream2 {
         self.entity_tokens()
@@ -565,14 +988,26 @@ impl ToEntityGraphTokens for PgExtern {
 
 impl ToRustCodeTokens for PgExtern {
     fn to_rust_code_tokens(&self) -> TokenStream2 {
+        if crate::enrich::SQL_GENERATION_ONLY {
+            return crate::enrich::sql_generation_stub(&self.func).into_token_stream();
+        }
         let original_func = &self.func;
         let wrapper_func = self.wrapper_func();
         let finfo_tokens = self.finfo_tokens();
+        let legacy_symbol_tokens = self.legacy_symbol_tokens();
+        // the extension only defines its `check_version` GUC if a function uses it
+        let check_version_opt_in = if self.check_version {
+            quote! { ::pgx::__pgx_register_on_load!(::pgx::version_check::__opt_in()); }
+        } else {
+            quote! {}
+        };
 
         quote_spanned! { self.func.sig.span() =>
             #original_func
             #wrapper_func
             #finfo_tokens
+            #legacy_symbol_tokens
+            #check_version_opt_in
         }
     }
 }
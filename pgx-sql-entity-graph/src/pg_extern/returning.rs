@@ -14,13 +14,14 @@ Use of this source code is governed by the MIT license that can be found in the
 to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
 
 */
+use crate::lifetimes::staticize_lifetimes;
+use crate::name_macro::{is_composite_type, NamedType};
 use crate::UsedType;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens, TokenStreamExt};
 use std::convert::TryFrom;
-use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
-use syn::{GenericArgument, PathArguments, Token, Type};
+use syn::{GenericArgument, PathArguments, Type};
 
 #[derive(Debug, Clone)]
 pub struct ReturningIteratedItem {
@@ -28,30 +29,88 @@ pub struct ReturningIteratedItem {
     pub name: Option<String>,
 }
 
+impl ReturningIteratedItem {
+    /// A column of a `TableIterator` or a tuple, which is named if it's a `name!()`
+    fn new(elem: &syn::Type, error: &str) -> Result<Self, syn::Error> {
+        match elem {
+            syn::Type::Path(_) | syn::Type::Reference(_) | syn::Type::Macro(_) => {
+                let NamedType { name, used_ty } = NamedType::new(elem)?;
+                Ok(ReturningIteratedItem { used_ty, name })
+            }
+            ty => Err(syn::Error::new(ty.span(), error)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Returning {
     None,
     Type(UsedType),
     SetOf { ty: UsedType, optional: bool, result: bool },
     Iterated { tys: Vec<ReturningIteratedItem>, optional: bool, result: bool },
+    // a `TableIterator` of a `#[derive(PgTableRow)]` struct, whose fields are the columns
+    IteratedRow { ty: syn::Type, optional: bool, result: bool },
+    Record { tys: Vec<ReturningIteratedItem>, optional: bool, result: bool },
     // /// Technically we don't ever create this, single triggers have their own macro.
     // Trigger,
 }
 
 impl Returning {
     fn parse_type_macro(type_macro: &mut syn::TypeMacro) -> Result<Returning, syn::Error> {
-        let mac = &type_macro.mac;
-        let opt_archetype = mac.path.segments.last().map(|archetype| archetype.ident.to_string());
-        match opt_archetype.as_deref() {
-            Some("composite_type") => {
-                Ok(Returning::Type(UsedType::new(syn::Type::Macro(type_macro.clone()))?))
-            }
-            _ => Err(syn::Error::new(
+        let ty = syn::Type::Macro(type_macro.clone());
+        if is_composite_type(&ty) {
+            Ok(Returning::Type(UsedType::new(ty)?))
+        } else {
+            Err(syn::Error::new(
                 type_macro.span(),
                 "type macros other than `composite_type!` are not yet implemented",
-            )),
+            ))
+        }
+    }
+
+    /// The tuple in a `(A, B)`, `Option<(A, B)>`, `Result<(A, B), E>` or
+    /// `Result<Option<(A, B)>, E>` return type, and whether it's in an `Option` and a `Result`
+    fn record_tuple(ty: &syn::Type) -> Option<(&syn::TypeTuple, bool, bool)> {
+        match ty {
+            syn::Type::Tuple(tuple) if !tuple.elems.is_empty() => Some((tuple, false, false)),
+            syn::Type::Paren(paren) => Self::record_tuple(&paren.elem),
+            syn::Type::Path(typepath) => {
+                let segment = typepath.path.segments.last()?;
+                let inner = match &segment.arguments {
+                    PathArguments::AngleBracketed(args) => match args.args.first()? {
+                        GenericArgument::Type(inner) => inner,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                match (segment.ident.to_string().as_str(), Self::record_tuple(inner)?) {
+                    ("Option", (tuple, false, false)) => Some((tuple, true, false)),
+                    ("Result", (tuple, optional, false)) => Some((tuple, optional, true)),
+                    _ => None,
+                }
+            }
+            _ => None,
         }
     }
+
+    fn parse_record(
+        tuple: &syn::TypeTuple,
+        optional: bool,
+        result: bool,
+    ) -> Result<Returning, syn::Error> {
+        if tuple.elems.len() < 2 {
+            return Err(syn::Error::new(
+                tuple.span(),
+                "a tuple returned as a record must have at least two values",
+            ));
+        }
+        let tys = tuple
+            .elems
+            .iter()
+            .map(|elem| ReturningIteratedItem::new(elem, "Unsupported type in a returned tuple"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Returning::Record { tys, optional, result })
+    }
 }
 
 impl TryFrom<&syn::ReturnType> for Returning {
@@ -61,6 +120,9 @@ impl TryFrom<&syn::ReturnType> for Returning {
         match &value {
             syn::ReturnType::Default => Ok(Returning::None),
             syn::ReturnType::Type(_, ty) => {
+                if let Some((tuple, optional, result)) = Self::record_tuple(ty) {
+                    return Self::parse_record(tuple, optional, result);
+                }
                 let mut ty = *ty.clone();
 
                 match ty {
@@ -230,76 +292,25 @@ impl TryFrom<&syn::ReturnType> for Returning {
                                 match &mut last_path_segment.arguments {
                                     syn::PathArguments::AngleBracketed(args) => {
                                         match args.args.last_mut().unwrap() {
+                                            syn::GenericArgument::Type(
+                                                row @ syn::Type::Path(_),
+                                            ) => {
+                                                return Ok(Returning::IteratedRow {
+                                                    ty: row.clone(),
+                                                    optional: saw_option_ident,
+                                                    result: saw_result_ident,
+                                                });
+                                            }
                                             syn::GenericArgument::Type(syn::Type::Tuple(
                                                 type_tuple,
                                             )) => {
                                                 for elem in &type_tuple.elems {
-                                                    match &elem {
-                                                        syn::Type::Path(path) => {
-                                                            let iterated_item =
-                                                                ReturningIteratedItem {
-                                                                    name: None,
-                                                                    used_ty: UsedType::new(
-                                                                        syn::Type::Path(
-                                                                            path.clone(),
-                                                                        ),
-                                                                    )?,
-                                                                };
-                                                            iterated_items.push(iterated_item);
-                                                        }
-                                                        syn::Type::Macro(type_macro) => {
-                                                            let mac = &type_macro.mac;
-                                                            let archetype =
-                                                                mac.path.segments.last().unwrap();
-                                                            match archetype
-                                                                .ident
-                                                                .to_string()
-                                                                .as_str()
-                                                            {
-                                                                "name" => {
-                                                                    let out: NameMacro =
-                                                                        mac.parse_body()?;
-                                                                    let iterated_item =
-                                                                        ReturningIteratedItem {
-                                                                            name: Some(out.ident),
-                                                                            used_ty: out.used_ty,
-                                                                        };
-                                                                    iterated_items
-                                                                        .push(iterated_item)
-                                                                }
-                                                                _ => {
-                                                                    let iterated_item =
-                                                                        ReturningIteratedItem {
-                                                                            name: None,
-                                                                            used_ty: UsedType::new(
-                                                                                syn::Type::Macro(
-                                                                                    type_macro
-                                                                                        .clone(),
-                                                                                ),
-                                                                            )?,
-                                                                        };
-                                                                    iterated_items
-                                                                        .push(iterated_item);
-                                                                }
-                                                            }
-                                                        }
-                                                        reference @ syn::Type::Reference(_) => {
-                                                            let iterated_item =
-                                                                ReturningIteratedItem {
-                                                                    name: None,
-                                                                    used_ty: UsedType::new(
-                                                                        (*reference).clone(),
-                                                                    )?,
-                                                                };
-                                                            iterated_items.push(iterated_item);
-                                                        }
-                                                        ty => {
-                                                            return Err(syn::Error::new(
-                                                                ty.span(),
-                                                                "Table Iterator must have an item",
-                                                            ));
-                                                        }
-                                                    };
+                                                    iterated_items.push(
+                                                        ReturningIteratedItem::new(
+                                                            elem,
+                                                            "Table Iterator must have an item",
+                                                        )?,
+                                                    );
                                                 }
                                             }
                                             syn::GenericArgument::Lifetime(_) => (),
@@ -385,21 +396,32 @@ impl ToTokens for Returning {
                 }
             }
             Returning::Iterated { tys: items, optional, result } => {
-                let quoted_items = items
-                    .iter()
-                    .map(|ReturningIteratedItem { used_ty, name }| {
-                        let name_iter = name.iter();
-                        let used_ty_entity_tokens = used_ty.entity_tokens();
-                        quote! {
-                            ::pgx::pgx_sql_entity_graph::PgExternReturnEntityIteratedItem {
-                                ty: #used_ty_entity_tokens,
-                                name: None #( .unwrap_or(Some(stringify!(#name_iter))) )*,
-                            }
-                        }
-                    })
-                    .collect::<Vec<_>>();
+                let quoted_items = iterated_items_tokens(items);
+                quote! {
+                    ::pgx::pgx_sql_entity_graph::PgExternReturnEntity::Iterated {
+                        tys: vec![
+                            #(#quoted_items),*
+                        ],
+                        optional: #optional,
+                        result: #result
+                    }
+                }
+            }
+            Returning::IteratedRow { ty, optional, result } => {
+                let mut ty = ty.clone();
+                staticize_lifetimes(&mut ty);
                 quote! {
                     ::pgx::pgx_sql_entity_graph::PgExternReturnEntity::Iterated {
+                        tys: <#ty as ::pgx::iter::PgTableRow>::table_row_columns(),
+                        optional: #optional,
+                        result: #result
+                    }
+                }
+            }
+            Returning::Record { tys: items, optional, result } => {
+                let quoted_items = iterated_items_tokens(items);
+                quote! {
+                    ::pgx::pgx_sql_entity_graph::PgExternReturnEntity::Record {
                         tys: vec![
                             #(#quoted_items),*
                         ],
@@ -413,29 +435,18 @@ impl ToTokens for Returning {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct NameMacro {
-    pub ident: String,
-    pub used_ty: UsedType,
-}
-
-impl Parse for NameMacro {
-    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
-        let ident = input
-            .parse::<syn::Ident>()
-            .map(|v| v.to_string())
-            // Avoid making folks unable to use rust keywords.
-            .or_else(|_| input.parse::<syn::Token![type]>().map(|_| String::from("type")))
-            .or_else(|_| input.parse::<syn::Token![mod]>().map(|_| String::from("mod")))
-            .or_else(|_| input.parse::<syn::Token![extern]>().map(|_| String::from("extern")))
-            .or_else(|_| input.parse::<syn::Token![async]>().map(|_| String::from("async")))
-            .or_else(|_| input.parse::<syn::Token![crate]>().map(|_| String::from("crate")))
-            .or_else(|_| input.parse::<syn::Token![use]>().map(|_| String::from("use")))?;
-        let _comma: Token![,] = input.parse()?;
-        let ty: syn::Type = input.parse()?;
-
-        let used_ty = UsedType::new(ty)?;
-
-        Ok(Self { ident, used_ty })
-    }
+fn iterated_items_tokens(items: &[ReturningIteratedItem]) -> Vec<TokenStream2> {
+    items
+        .iter()
+        .map(|ReturningIteratedItem { used_ty, name }| {
+            let name_iter = name.iter();
+            let used_ty_entity_tokens = used_ty.entity_tokens();
+            quote! {
+                ::pgx::pgx_sql_entity_graph::PgExternReturnEntityIteratedItem {
+                    ty: #used_ty_entity_tokens,
+                    name: None #( .unwrap_or(Some(stringify!(#name_iter))) )*,
+                }
+            }
+        })
+        .collect()
 }
@@ -14,8 +14,7 @@ Use of this source code is governed by the MIT license that can be found in the
 to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
 
 */
-use proc_macro2::TokenStream as TokenStream2;
-use quote::{quote, ToTokens};
+use super::setting::Setting;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::Token;
@@ -39,18 +38,19 @@ impl Parse for SearchPath {
     }
 }
 
-impl ToTokens for SearchPath {
-    fn to_tokens(&self, tokens: &mut TokenStream2) {
-        let at_start = self.at_start;
-        let dollar = self.dollar;
-        let path = &self.path;
-        let at_end = self.at_end;
-
-        let quoted = quote! {
-            concat!(stringify!(#at_start), stringify!(#dollar), stringify!(#path), stringify!(#at_end))
-        };
-
-        quoted.to_tokens(tokens);
+impl core::fmt::Display for SearchPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.at_start.is_some() {
+            write!(f, "@")?;
+        }
+        if self.dollar.is_some() {
+            write!(f, "$")?;
+        }
+        write!(f, "{}", self.path)?;
+        if self.at_end.is_some() {
+            write!(f, "@")?;
+        }
+        Ok(())
     }
 }
 
@@ -67,8 +67,9 @@ impl Parse for SearchPathList {
     }
 }
 
-impl ToTokens for SearchPathList {
-    fn to_tokens(&self, tokens: &mut TokenStream2) {
-        self.fields.to_tokens(tokens)
+impl SearchPathList {
+    /// The `SET search_path TO ..` clause these paths are sugar for
+    pub fn to_setting(&self) -> Setting {
+        Setting::search_path(self.fields.iter().map(|path| path.to_string()))
     }
 }
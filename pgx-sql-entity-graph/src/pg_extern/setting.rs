@@ -0,0 +1,182 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_extern(set = "..")]` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+
+/// A configuration parameter the function sets while it runs, from `set = "name = value"` or
+/// `#[search_path(..)]`, as a `SET name TO value` clause of its `CREATE FUNCTION`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    name: String,
+    value: String,
+}
+
+impl Setting {
+    /// Parses a `set = "name = value"` option.  The value is single-quoted unless it already is,
+    /// or is a plain word or number, such as `off` or `4`.  Each item of a comma-separated list,
+    /// such as `pg_catalog, public`, is single-quoted on its own, unless it already is.
+    pub fn parse(literal: &syn::LitStr) -> syn::Result<Self> {
+        let error = |message: String| syn::Error::new(literal.span(), message);
+        let setting = literal.value();
+        let (name, value) = setting
+            .split_once('=')
+            .ok_or_else(|| error(format!("expected `name = value`, not `{}`", setting)))?;
+        let (name, value) = (name.trim(), value.trim());
+
+        if !is_parameter_name(name) {
+            return Err(error(format!("`{}` isn't a configuration parameter's name", name)));
+        }
+        if value.is_empty() {
+            return Err(error(format!("`{}` isn't set to anything", name)));
+        }
+        let items = split_list(value);
+        let mut quoted = Vec::with_capacity(items.len());
+        for item in &items {
+            let item = item.trim();
+            let item = if item.is_empty() {
+                return Err(error(format!("`{}` has an empty item in `{}`", name, value)));
+            } else if item.starts_with('\'') {
+                if !is_quoted_literal(item) {
+                    return Err(error(format!("`{}` isn't a valid quoted value", item)));
+                }
+                item.to_string()
+            } else if items.len() == 1 && (is_plain_word(item) || is_number(item)) {
+                item.to_string()
+            } else {
+                format!("'{}'", item.replace('\'', "''"))
+            };
+            quoted.push(item);
+        }
+        Ok(Self { name: name.to_string(), value: quoted.join(", ") })
+    }
+
+    /// `SET search_path TO ..` for `#[search_path(..)]`, whose paths are used as they are
+    pub fn search_path(paths: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            name: String::from("search_path"),
+            value: paths.into_iter().collect::<Vec<_>>().join(", "),
+        }
+    }
+
+    /// Whether this sets the same configuration parameter as `other`, whose names aren't case
+    /// sensitive
+    pub fn same_parameter(&self, other: &Setting) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl ToTokens for Setting {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let name = &self.name;
+        let value = &self.value;
+        quote! { (#name, #value) }.to_tokens(tokens)
+    }
+}
+
+/// A parameter's name is an identifier, or dot-separated identifiers for an extension's own
+fn is_parameter_name(name: &str) -> bool {
+    name.split('.').all(|part| {
+        let mut chars = part.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    })
+}
+
+fn is_plain_word(value: &str) -> bool {
+    let mut chars = value.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_number(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let mut parts = digits.splitn(2, '.');
+    parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Split a value at the commas which aren't in single quotes
+fn split_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (idx, c) in value.char_indices() {
+        match c {
+            // a doubled quote in a literal closes and reopens it, which comes to the same
+            '\'' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+/// A single-quoted literal, in which any quotes are doubled
+fn is_quoted_literal(value: &str) -> bool {
+    value.len() >= 2
+        && value.ends_with('\'')
+        && !value[1..value.len() - 1].replace("''", "").contains('\'')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Setting;
+
+    fn parse(setting: &str) -> syn::Result<Setting> {
+        Setting::parse(&syn::LitStr::new(setting, proc_macro2::Span::call_site()))
+    }
+
+    fn value(setting: &str) -> String {
+        parse(setting).unwrap().value
+    }
+
+    #[test]
+    fn values_are_quoted_when_needed() {
+        assert_eq!(value("jit = off"), "off");
+        assert_eq!(value("max_parallel_workers_per_gather=4"), "4");
+        assert_eq!(value("seq_page_cost = 1.5"), "1.5");
+        assert_eq!(value("work_mem = 256MB"), "'256MB'");
+        assert_eq!(value("work_mem = '256MB'"), "'256MB'");
+        assert_eq!(value("search_path = pg_catalog, public"), "'pg_catalog', 'public'");
+        assert_eq!(value("search_path = '$user',public"), "'$user', 'public'");
+        assert_eq!(value("search_path = \"$user\", public"), "'\"$user\"', 'public'");
+        assert_eq!(value("myext.list = 'a, b', c"), "'a, b', 'c'");
+        assert_eq!(value("myext.list = 'it''s, fine'"), "'it''s, fine'");
+        assert_eq!(value("application_name = it's"), "'it''s'");
+        assert_eq!(parse("myext.Setting = on").unwrap().name(), "myext.Setting");
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert!(parse("work_mem").is_err());
+        assert!(parse("work_mem = ").is_err());
+        assert!(parse("work mem = 1").is_err());
+        assert!(parse("1work_mem = 1").is_err());
+        assert!(parse("myext. = 1").is_err());
+        assert!(parse("work_mem = '256MB").is_err());
+        assert!(parse("work_mem = 'a'b'").is_err());
+        assert!(parse("search_path = pg_catalog,").is_err());
+        assert!(parse("search_path = , public").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_gin_opclass]` related entities for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::pgx_sql::{find_extern_by_path, PgxSql};
+use crate::to_sql::ToSql;
+use crate::{
+    PgExternEntity, PgExternReturnEntity, SqlGraphEntity, SqlGraphIdentifier, UsedTypeEntity,
+};
+
+use eyre::eyre;
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+/// The output of a [`PgGinOpclass`](crate::PgGinOpclass) from `quote::ToTokens::to_tokens`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgGinOpclassEntity {
+    /// The name of the indexed type in Rust, which the class and its support functions are named
+    /// after
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub full_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    /// The indexed type
+    pub ty: UsedTypeEntity,
+    /// The type of the keys the index stores
+    pub key: UsedTypeEntity,
+    /// The operators the index answers, by their strategy number, as the paths of their
+    /// `#[pg_operator]` functions
+    pub operators: Vec<(u16, &'static str)>,
+    /// Whether the class has a `comparePartial` support function
+    pub partial_match: bool,
+}
+
+impl PgGinOpclassEntity {
+    /// The name of the support function `support`, such as `extract_value`, which is also the
+    /// name of its wrapper in Rust
+    pub fn support_fn_name(&self, support: &str) -> String {
+        format!("{}_gin_{}", self.name.to_lowercase(), support)
+    }
+
+    /// Find the `#[pg_operator]` function of each strategy, and ensure it's a binary operator
+    /// on the indexed type which returns a value
+    pub fn validate_operators<'a>(
+        &self,
+        externs: &'a HashMap<PgExternEntity, NodeIndex>,
+    ) -> eyre::Result<Vec<(u16, &'a PgExternEntity, NodeIndex)>> {
+        let mut operators = Vec::with_capacity(self.operators.len());
+        for &(strategy, path) in &self.operators {
+            let (function, index) = find_extern_by_path(path, externs)?
+                .filter(|(function, _)| function.operator.is_some())
+                .ok_or_else(|| {
+                    eyre!(
+                        "Strategy {} of the GIN operator class of `{}` ({}:{}) is `{}`, which isn't a `#[pg_operator]` function",
+                        strategy,
+                        self.name,
+                        self.file,
+                        self.line,
+                        path,
+                    )
+                })?;
+            if function.fn_args.len() != 2
+                || function.fn_args[0].used_ty.ty_id != self.ty.ty_id
+                || !matches!(function.fn_return, PgExternReturnEntity::Type { .. })
+            {
+                return Err(eyre!(
+                    "The operator of strategy {} of the GIN operator class of `{}` ({}:{}) must take a `{}` on its left and a query on its right: {}",
+                    strategy,
+                    self.name,
+                    self.file,
+                    self.line,
+                    self.ty.full_path,
+                    function.full_path,
+                ));
+            }
+            operators.push((strategy, function, index));
+        }
+        Ok(operators)
+    }
+}
+
+impl From<PgGinOpclassEntity> for SqlGraphEntity {
+    fn from(val: PgGinOpclassEntity) -> Self {
+        SqlGraphEntity::GinOperatorClass(val)
+    }
+}
+
+impl SqlGraphIdentifier for PgGinOpclassEntity {
+    fn dot_identifier(&self) -> String {
+        format!("gin opclass {}", self.full_path)
+    }
+    fn rust_identifier(&self) -> String {
+        self.full_path.to_string()
+    }
+
+    fn file(&self) -> Option<&'static str> {
+        Some(self.file)
+    }
+
+    fn line(&self) -> Option<u32> {
+        Some(self.line)
+    }
+}
+
+impl ToSql for PgGinOpclassEntity {
+    #[tracing::instrument(level = "debug", skip(self, context), fields(identifier = self.full_path))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        let self_index = context.gin_opclasses[self];
+        let schema = context.schema_prefix_for(&self_index);
+        let user = format!("the GIN operator class of `{}`", self.name);
+        let ty = context.used_type_sql(&self.ty, &user)?;
+        let key = context.used_type_sql(&self.key, &user)?;
+        let module_pathname = context.get_module_pathname();
+
+        let mut support = vec![
+            (1, "compare", format!("{key}, {key}"), "integer"),
+            (2, "extract_value", format!("{ty}, internal, internal"), "internal"),
+            (
+                3,
+                "extract_query",
+                format!("{ty}, internal, smallint, internal, internal, internal, internal"),
+                "internal",
+            ),
+            (
+                4,
+                "consistent",
+                format!(
+                    "internal, smallint, {ty}, integer, internal, internal, internal, internal"
+                ),
+                "boolean",
+            ),
+        ];
+        if self.partial_match {
+            support.push((
+                5,
+                "compare_partial",
+                format!("{key}, {key}, smallint, internal"),
+                "integer",
+            ));
+        }
+
+        let mut functions = String::new();
+        let mut items = Vec::new();
+        for (strategy, operator, index) in self.validate_operators(&context.externs)? {
+            let opname =
+                operator.operator.as_ref().and_then(|op| op.opname).ok_or_else(|| {
+                    eyre!("The operator `{}` has no `#[opname]`", operator.full_path)
+                })?;
+            let operator_schema = operator
+                .schema
+                .map(|schema| format!("{}.", schema))
+                .unwrap_or_else(|| context.schema_prefix_for(&index));
+            let user = format!("operator `{}`", operator.name);
+            let left = context.used_type_sql(&operator.fn_args[0].used_ty, &user)?;
+            let right = context.used_type_sql(&operator.fn_args[1].used_ty, &user)?;
+            items.push(format!("\tOPERATOR {strategy} {operator_schema}{opname}({left}, {right})"));
+        }
+        for (number, name, args, returns) in support {
+            let function_name = self.support_fn_name(name);
+            functions.push_str(&format!(
+                "CREATE FUNCTION {schema}\"{function_name}\"({args})\n\
+                    \tRETURNS {returns}\n\
+                    \tIMMUTABLE STRICT PARALLEL SAFE\n\
+                    \tLANGUAGE c\n\
+                    \tAS '{module_pathname}', '{symbol}';\n",
+                symbol = crate::wrapper_symbol(self.module_path, &function_name),
+            ));
+            items.push(format!("\tFUNCTION {number} {schema}\"{function_name}\"({args})"));
+        }
+        items.push(format!("\tSTORAGE {key}"));
+
+        let sql = format!(
+            "\n\
+            -- {file}:{line}\n\
+            -- {full_path}\n\
+            {functions}\
+            CREATE OPERATOR CLASS {schema}{name}_gin_ops DEFAULT FOR TYPE {ty} USING gin AS\n\
+            {items};\n\
+            ",
+            file = self.file,
+            line = self.line,
+            full_path = self.full_path,
+            name = self.name.to_lowercase(),
+            items = items.join(",\n"),
+        );
+        tracing::trace!(%sql);
+        Ok(sql)
+    }
+}
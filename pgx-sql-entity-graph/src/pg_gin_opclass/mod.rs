@@ -0,0 +1,268 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[pg_gin_opclass]` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+pub mod entity;
+
+use crate::enrich::{CodeEnrichment, ToEntityGraphTokens, ToRustCodeTokens};
+use crate::UsedType;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Ident, Token};
+
+/// The GIN support functions every class has, by the name of their generic implementation in
+/// `pgx::gin`
+const SUPPORT_FUNCTIONS: [&str; 4] = ["compare", "extract_value", "extract_query", "consistent"];
+
+/// The support function of a class with `partial_match`, which needs a `pgx::gin::GinPartialMatch`
+const PARTIAL_MATCH_SUPPORT_FUNCTION: &str = "compare_partial";
+
+/// A parsed `#[pg_gin_opclass]` item.
+///
+/// It should be used with [`PgGinOpclass::new`].
+///
+/// Using [`quote::ToTokens`] will output the declaration for a
+/// [`PgGinOpclassEntity`][crate::PgGinOpclassEntity], followed by the `impl` and the `extern "C"`
+/// wrappers of its support functions.
+///
+/// ```rust
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::PgGinOpclass;
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed = PgGinOpclass::new(
+///     quote! { 1 = tags_contain },
+///     quote! {
+///         impl GinIndexable for Tags {
+///             type Key = String;
+///             type Query = String;
+///         }
+///     },
+/// )?;
+/// let sql_graph_entity_tokens = parsed.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgGinOpclass {
+    item: syn::ItemImpl,
+    ident: Ident,
+    ty: UsedType,
+    key: UsedType,
+    operators: Vec<GinOperator>,
+    partial_match: bool,
+}
+
+impl PgGinOpclass {
+    pub fn new(attr: TokenStream2, item: TokenStream2) -> Result<CodeEnrichment<Self>, syn::Error> {
+        let args = Punctuated::<GinOpclassArg, Token![,]>::parse_terminated.parse2(attr)?;
+        let mut operators = Vec::new();
+        let mut partial_match = false;
+        for arg in args {
+            match arg {
+                GinOpclassArg::Operator(operator) => operators.push(operator),
+                GinOpclassArg::PartialMatch => partial_match = true,
+            }
+        }
+        if operators.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "a GIN operator class answers at least one operator, such as `#[pg_gin_opclass(1 = my_contains)]`",
+            ));
+        }
+        let mut strategies = Vec::new();
+        for operator in &operators {
+            if strategies.contains(&operator.strategy) {
+                return Err(syn::Error::new(
+                    operator.span,
+                    format!("strategy {} is given more than once", operator.strategy),
+                ));
+            }
+            strategies.push(operator.strategy);
+        }
+
+        let item = syn::parse2::<syn::ItemImpl>(item)?;
+        let is_gin_indexable = item.trait_.as_ref().map_or(false, |(_, path, _)| {
+            path.segments.last().map_or(false, |last| last.ident == "GinIndexable")
+        });
+        if !is_gin_indexable {
+            return Err(syn::Error::new(
+                item.impl_token.span,
+                "`#[pg_gin_opclass]` goes on an `impl GinIndexable for ...`",
+            ));
+        }
+        let ident = match &*item.self_ty {
+            syn::Type::Path(path) => path.path.segments.last().map(|last| last.ident.clone()),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            syn::Error::new(item.self_ty.span(), "a GIN operator class is for a named type")
+        })?;
+        let key = item
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::ImplItem::Type(ty) if ty.ident == "Key" => Some(ty.ty.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                syn::Error::new(
+                    item.impl_token.span,
+                    "a GIN operator class needs the `type Key` it stores in the index",
+                )
+            })?;
+
+        let ty = UsedType::new(*item.self_ty.clone())?;
+        let key = UsedType::new(key)?;
+        Ok(CodeEnrichment(Self { item, ident, ty, key, operators, partial_match }))
+    }
+
+    /// The `extern "C"` wrapper of each support function, and its `Pg_finfo_record`
+    fn wrapper_tokens(&self) -> TokenStream2 {
+        let self_ty = &self.item.self_ty;
+        let mut tokens = TokenStream2::new();
+        let partial_match = self.partial_match.then_some(PARTIAL_MATCH_SUPPORT_FUNCTION);
+        for support in SUPPORT_FUNCTIONS.into_iter().chain(partial_match) {
+            let name = Ident::new(
+                &format!("{}_gin_{}", self.ident.to_string().to_lowercase(), support),
+                self.ident.span(),
+            );
+            let wrapper = Ident::new(&format!("{}_wrapper", name), self.ident.span());
+            let finfo = Ident::new(&format!("pg_finfo_{}_wrapper", name), Span::call_site());
+            let wrapper_symbol = crate::wrapper_symbol_tokens("", &name);
+            let finfo_symbol = crate::wrapper_symbol_tokens("pg_finfo_", &name);
+            let generic = Ident::new(&format!("__gin_{}", support), Span::call_site());
+            tokens.extend(quote! {
+                #[export_name = #wrapper_symbol]
+                #[doc(hidden)]
+                #[::pgx::pgx_macros::pg_guard]
+                unsafe extern "C" fn #wrapper(fcinfo: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pg_sys::Datum {
+                    ::pgx::gin::#generic::<#self_ty>(fcinfo)
+                }
+
+                #[export_name = #finfo_symbol]
+                #[doc(hidden)]
+                pub extern "C" fn #finfo() -> &'static ::pgx::pg_sys::Pg_finfo_record {
+                    const V1_API: ::pgx::pg_sys::Pg_finfo_record = ::pgx::pg_sys::Pg_finfo_record { api_version: 1 };
+                    &V1_API
+                }
+            });
+        }
+        tokens
+    }
+}
+
+impl ToEntityGraphTokens for PgGinOpclass {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        let ty = self.ty.entity_tokens();
+        let key = self.key.entity_tokens();
+        let partial_match = self.partial_match;
+        let operators = self.operators.iter().map(|GinOperator { strategy, function, .. }| {
+            quote! { (#strategy, concat!(core::module_path!(), "::", stringify!(#function))) }
+        });
+
+        let sql_graph_entity_fn_name =
+            syn::Ident::new(&format!("__pgx_internals_gin_opclass_{}", ident), Span::call_site());
+        let sql_graph_entity_fn_symbol = crate::entity_symbol_tokens("gin_opclass", ident);
+        quote! {
+            #[export_name = #sql_graph_entity_fn_symbol]
+            #[doc(hidden)]
+            pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
+                extern crate alloc;
+                #[allow(unused_imports)]
+                use alloc::{vec, vec::Vec};
+                let submission = ::pgx::pgx_sql_entity_graph::PgGinOpclassEntity {
+                    name: stringify!(#ident),
+                    module_path: core::module_path!(),
+                    full_path: concat!(core::module_path!(), "::", stringify!(#ident)),
+                    file: file!(),
+                    line: line!(),
+                    ty: #ty,
+                    key: #key,
+                    operators: vec![#(#operators),*],
+                    partial_match: #partial_match,
+                };
+                ::pgx::pgx_sql_entity_graph::SqlGraphEntity::GinOperatorClass(submission)
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PgGinOpclass {
+    fn to_rust_code_tokens(&self) -> TokenStream2 {
+        let item = &self.item;
+        if crate::enrich::SQL_GENERATION_ONLY {
+            return item.to_token_stream();
+        }
+        let wrappers = self.wrapper_tokens();
+        quote! {
+            #item
+            #wrappers
+        }
+    }
+}
+
+/// An argument of `#[pg_gin_opclass]`
+#[derive(Debug, Clone)]
+enum GinOpclassArg {
+    Operator(GinOperator),
+    /// `partial_match`, for a type which is also `pgx::gin::GinPartialMatch`
+    PartialMatch,
+}
+
+impl Parse for GinOpclassArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::LitInt) {
+            return Ok(GinOpclassArg::Operator(input.parse()?));
+        }
+        let ident: Ident = input.parse()?;
+        if ident == "partial_match" {
+            Ok(GinOpclassArg::PartialMatch)
+        } else {
+            Err(syn::Error::new(
+                ident.span(),
+                "expected `strategy = operator_function` or `partial_match`",
+            ))
+        }
+    }
+}
+
+/// One `strategy = operator_function` of `#[pg_gin_opclass]`
+#[derive(Debug, Clone)]
+struct GinOperator {
+    strategy: u16,
+    function: Ident,
+    span: Span,
+}
+
+impl Parse for GinOperator {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let strategy: syn::LitInt = input.parse()?;
+        let _eq: Token![=] = input.parse()?;
+        let function: Ident = input.parse()?;
+        let span = strategy.span();
+        match strategy.base10_parse::<u16>() {
+            Ok(strategy) if strategy > 0 => Ok(Self { strategy, function, span }),
+            _ => Err(syn::Error::new(
+                span,
+                "a strategy number is a positive integer, as the support functions are given it",
+            )),
+        }
+    }
+}
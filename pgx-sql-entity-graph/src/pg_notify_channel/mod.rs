@@ -0,0 +1,230 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pgx::pg_notify_channel!()` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::enrich::{ToEntityGraphTokens, ToRustCodeTokens};
+use crate::CodeEnrichment;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Ident, LitStr, Token, Type, Visibility};
+
+/// The longest name a channel can have, in bytes, which is `NAMEDATALEN - 1`
+const MAX_CHANNEL_LEN: usize = 63;
+
+/// A parsed `pg_notify_channel!()` item.
+///
+/// It should be used with [`syn::parse::Parse`] functions.
+///
+/// Using [`quote::ToTokens`] will output the `static` [`Channel`] and the declaration of the
+/// [`ExtensionSqlEntity`][crate::ExtensionSqlEntity] that documents it.
+///
+/// [`Channel`]: https://docs.rs/pgx/latest/pgx/notify/struct.Channel.html
+///
+/// ```rust
+/// use syn::{Macro, parse::Parse, parse_quote, parse};
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::{CodeEnrichment, PgNotifyChannel};
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed: Macro = parse_quote! {
+///     pg_notify_channel!(
+///         /// New and updated orders
+///         pub static ORDERS: Channel<Order> = "orders";
+///     )
+/// };
+/// let inner_tokens = parsed.tokens;
+/// let inner: CodeEnrichment<PgNotifyChannel> = parse_quote! {
+///     #inner_tokens
+/// };
+/// let sql_graph_entity_tokens = inner.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgNotifyChannel {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    pub ty: Type,
+    pub name: LitStr,
+}
+
+impl Parse for CodeEnrichment<PgNotifyChannel> {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let _static: Token![static] = input.parse()?;
+        let ident = input.parse()?;
+        let _colon: Token![:] = input.parse()?;
+        let ty = input.parse()?;
+        let _eq: Token![=] = input.parse()?;
+        let name: LitStr = input.parse()?;
+        let _semi: Option<Token![;]> = input.parse()?;
+
+        let value = name.value();
+        if value.is_empty() || value.len() > MAX_CHANNEL_LEN || value.contains('\0') {
+            return Err(syn::Error::new(
+                name.span(),
+                "a channel's name must be 1 to 63 bytes, without any nul bytes",
+            ));
+        }
+        Ok(CodeEnrichment(PgNotifyChannel { attrs, vis, ident, ty, name }))
+    }
+}
+
+impl PgNotifyChannel {
+    /// The SQL comment describing the channel
+    pub fn sql(&self) -> String {
+        let mut sql = format!(
+            "-- NOTIFY channel \"{}\"\n--   payload: {} as JSON\n",
+            self.name.value(),
+            type_name(payload_type(&self.ty))
+        );
+        for line in self.doc_lines() {
+            if line.is_empty() {
+                sql.push_str("--\n");
+            } else {
+                sql.push_str(&format!("--   {}\n", line));
+            }
+        }
+        sql
+    }
+
+    fn doc_lines(&self) -> Vec<String> {
+        self.attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("doc"))
+            .filter_map(|attr| match attr.parse_meta() {
+                Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                    lit: syn::Lit::Str(doc), ..
+                })) => Some(doc.value().trim().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl ToEntityGraphTokens for PgNotifyChannel {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        let sql = self.sql();
+        let name = format!(
+            "notify_channel_{}",
+            self.name
+                .value()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+        );
+        let sql_graph_entity_fn_name =
+            Ident::new(&format!("__pgx_internals_sql_{}", name), Span::call_site());
+        quote! {
+            #[no_mangle]
+            #[doc(hidden)]
+            pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
+                extern crate alloc;
+                use alloc::vec::Vec;
+                use alloc::vec;
+                let submission = ::pgx::pgx_sql_entity_graph::ExtensionSqlEntity {
+                    sql: #sql,
+                    module_path: module_path!(),
+                    full_path: concat!(module_path!(), "::", stringify!(#ident)),
+                    file: file!(),
+                    line: line!(),
+                    name: #name,
+                    bootstrap: false,
+                    finalize: false,
+                    requires: vec![],
+                    infer_requires: false,
+                    creates: vec![],
+                    declares: vec![],
+                    config_dump: vec![],
+                    pg_version: None,
+                };
+                ::pgx::pgx_sql_entity_graph::SqlGraphEntity::CustomSql(submission)
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PgNotifyChannel {
+    fn to_rust_code_tokens(&self) -> TokenStream2 {
+        let PgNotifyChannel { attrs, vis, ident, ty, name } = self;
+        quote! {
+            #(#attrs)*
+            #vis static #ident: #ty = ::pgx::notify::Channel::new(#name);
+        }
+    }
+}
+
+/// `T`, when the channel's type is written as `Channel<T>`
+fn payload_type(ty: &Type) -> &Type {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                if let (true, Some(syn::GenericArgument::Type(payload)), 1) =
+                    (segment.ident == "Channel", args.args.first(), args.args.len())
+                {
+                    return payload;
+                }
+            }
+        }
+    }
+    ty
+}
+
+/// The type as it'd be written, rather than with a space between each token
+fn type_name(ty: &Type) -> String {
+    ty.to_token_stream().to_string().replace(' ', "").replace(',', ", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PgNotifyChannel;
+    use crate::CodeEnrichment;
+
+    fn parse(tokens: proc_macro2::TokenStream) -> syn::Result<PgNotifyChannel> {
+        syn::parse2::<CodeEnrichment<PgNotifyChannel>>(tokens).map(|channel| channel.0)
+    }
+
+    #[test]
+    fn documents_the_channel() {
+        let channel = parse(quote::quote! {
+            /// New and updated orders,
+            ///
+            /// one per row
+            pub static ORDERS: pgx::notify::Channel<Vec<(i64, String)>> = "orders";
+        })
+        .unwrap();
+        assert_eq!(
+            channel.sql(),
+            "-- NOTIFY channel \"orders\"\n\
+             --   payload: Vec<(i64, String)> as JSON\n\
+             --   New and updated orders,\n\
+             --\n\
+             --   one per row\n"
+        );
+    }
+
+    #[test]
+    fn channel_names_are_checked() {
+        assert!(parse(quote::quote! { static EMPTY: Channel<i32> = ""; }).is_err());
+        let long = "c".repeat(64);
+        assert!(parse(quote::quote! { static LONG: Channel<i32> = #long; }).is_err());
+        let longest = "c".repeat(63);
+        assert!(parse(quote::quote! { static LONGEST: Channel<i32> = #longest }).is_ok());
+    }
+}
@@ -0,0 +1,198 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pgx::pg_policy!()` related entities for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::metadata::{Returns, SqlMapping};
+use crate::pgx_sql::{find_extern_by_path, PgxSql};
+use crate::positioning_ref::PositioningRef;
+use crate::to_sql::ToSql;
+use crate::{PgExternEntity, PgExternReturnEntity, SqlGraphEntity, SqlGraphIdentifier};
+
+use eyre::eyre;
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+use super::PolicyCommand;
+
+/// The output of a [`PgPolicy`](crate::PgPolicy) from `quote::ToTokens::to_tokens`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgPolicyEntity {
+    pub name: &'static str,
+    pub table: &'static str,
+    pub module_path: &'static str,
+    pub full_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub command: PolicyCommand,
+    pub restrictive: bool,
+    pub roles: Vec<&'static str>,
+    pub using: Option<PolicyPredicateEntity>,
+    pub with_check: Option<PolicyPredicateEntity>,
+    pub requires: Vec<PositioningRef>,
+}
+
+/// A call to a `#[pg_extern]` function used as a policy's `USING` or `WITH CHECK` expression
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PolicyPredicateEntity {
+    pub function: PositioningRef,
+    pub args: Vec<&'static str>,
+}
+
+impl PolicyPredicateEntity {
+    /// Find the `#[pg_extern]` function this predicate calls
+    pub fn resolve<'a>(
+        &self,
+        externs: &'a HashMap<PgExternEntity, NodeIndex>,
+    ) -> eyre::Result<Option<(&'a PgExternEntity, NodeIndex)>> {
+        match &self.function {
+            PositioningRef::FullPath(path) => find_extern_by_path(path, externs),
+            PositioningRef::Name(_) => Ok(None),
+        }
+    }
+
+    /// Ensure this predicate calls a function that exists, accepts as many arguments as it's
+    /// given, and returns `bool`
+    pub fn validate<'a>(
+        &self,
+        policy: &PgPolicyEntity,
+        clause: &str,
+        externs: &'a HashMap<PgExternEntity, NodeIndex>,
+    ) -> eyre::Result<(&'a PgExternEntity, NodeIndex)> {
+        let (function, index) = self.resolve(externs)?.ok_or_else(|| {
+            eyre!(
+                "Could not find the `{clause}` function of policy `{}` ({}:{}): {}",
+                policy.name,
+                policy.file,
+                policy.line,
+                self.function,
+            )
+        })?;
+
+        let returns_bool = match &function.fn_return {
+            PgExternReturnEntity::Type { ty } => {
+                ty.metadata.return_sql == Ok(Returns::One(SqlMapping::literal("bool")))
+            }
+            _ => false,
+        };
+        if !returns_bool {
+            return Err(eyre!(
+                "The `{clause}` function of policy `{}` ({}:{}) must return `bool`: {}",
+                policy.name,
+                policy.file,
+                policy.line,
+                function.full_path,
+            ));
+        }
+
+        if function.fn_args.len() != self.args.len() {
+            return Err(eyre!(
+                "The `{clause}` function of policy `{}` ({}:{}) takes {} arguments, but is given {}: {}",
+                policy.name,
+                policy.file,
+                policy.line,
+                function.fn_args.len(),
+                self.args.len(),
+                function.full_path,
+            ));
+        }
+        Ok((function, index))
+    }
+
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        let (function, index) = self
+            .resolve(&context.externs)?
+            .ok_or_else(|| eyre!("Could not find policy function: {}", self.function))?;
+        let schema = function
+            .schema
+            .map(|schema| format!("{}.", schema))
+            .unwrap_or_else(|| context.schema_prefix_for(&index));
+        Ok(
+            format!(
+                "{schema}\"{name}\"({args})",
+                name = function.name,
+                args = self.args.join(", "),
+            ),
+        )
+    }
+}
+
+impl From<PgPolicyEntity> for SqlGraphEntity {
+    fn from(val: PgPolicyEntity) -> Self {
+        SqlGraphEntity::Policy(val)
+    }
+}
+
+impl SqlGraphIdentifier for PgPolicyEntity {
+    fn dot_identifier(&self) -> String {
+        format!("policy {}", self.name)
+    }
+    fn rust_identifier(&self) -> String {
+        self.full_path.to_string()
+    }
+
+    fn file(&self) -> Option<&'static str> {
+        Some(self.file)
+    }
+
+    fn line(&self) -> Option<u32> {
+        Some(self.line)
+    }
+}
+
+impl ToSql for PgPolicyEntity {
+    #[tracing::instrument(level = "debug", skip(self, context), fields(identifier = self.full_path))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        let mut clauses = vec![];
+        if self.restrictive {
+            clauses.push("AS RESTRICTIVE".to_string());
+        }
+        clauses.push(format!(
+            "FOR {}",
+            match self.command {
+                PolicyCommand::All => "ALL",
+                PolicyCommand::Select => "SELECT",
+                PolicyCommand::Insert => "INSERT",
+                PolicyCommand::Update => "UPDATE",
+                PolicyCommand::Delete => "DELETE",
+            }
+        ));
+        if !self.roles.is_empty() {
+            clauses.push(format!("TO {}", self.roles.join(", ")));
+        }
+        if let Some(using) = &self.using {
+            clauses.push(format!("USING ({})", using.to_sql(context)?));
+        }
+        if let Some(with_check) = &self.with_check {
+            clauses.push(format!("WITH CHECK ({})", with_check.to_sql(context)?));
+        }
+
+        let sql = format!(
+            "\n\
+            -- {file}:{line}\n\
+            -- {full_path}\n\
+            CREATE POLICY \"{name}\" ON {table}\n\
+                \t{clauses};\n\
+            ",
+            file = self.file,
+            line = self.line,
+            full_path = self.full_path,
+            name = self.name,
+            table = self.table,
+            clauses = clauses.join("\n\t"),
+        );
+        tracing::trace!(%sql);
+        Ok(sql)
+    }
+}
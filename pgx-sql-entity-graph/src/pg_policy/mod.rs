@@ -0,0 +1,338 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pgx::pg_policy!()` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+pub mod entity;
+
+use crate::enrich::{CodeEnrichment, ToEntityGraphTokens, ToRustCodeTokens};
+use crate::positioning_ref::PositioningRef;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens, TokenStreamExt};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Ident, LitStr, Token};
+
+/// A parsed `pg_policy!()` item.
+///
+/// It should be used with [`syn::parse::Parse`] functions.
+///
+/// Using [`quote::ToTokens`] will output the declaration for a [`PgPolicyEntity`][crate::PgPolicyEntity].
+///
+/// ```rust
+/// use syn::{Macro, parse::Parse, parse_quote, parse};
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::{CodeEnrichment, PgPolicy};
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed: Macro = parse_quote! {
+///     pg_policy!(
+///         name = "tenant_isolation",
+///         table = "tenant_data",
+///         command = select,
+///         using = tenant_visible(tenant_id),
+///     )
+/// };
+/// let inner_tokens = parsed.tokens;
+/// let inner: CodeEnrichment<PgPolicy> = parse_quote! {
+///     #inner_tokens
+/// };
+/// let sql_graph_entity_tokens = inner.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgPolicy {
+    pub name: LitStr,
+    pub table: LitStr,
+    pub command: PolicyCommand,
+    pub restrictive: bool,
+    pub roles: Vec<LitStr>,
+    pub using: Option<PolicyPredicate>,
+    pub with_check: Option<PolicyPredicate>,
+    pub requires: Vec<PositioningRef>,
+}
+
+impl Parse for CodeEnrichment<PgPolicy> {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let attrs = input.parse_terminated::<_, Token![,]>(PgPolicyAttribute::parse)?;
+
+        let mut name = None;
+        let mut table = None;
+        let mut command = None;
+        let mut restrictive = false;
+        let mut roles = vec![];
+        let mut using = None;
+        let mut with_check = None;
+        let mut requires = vec![];
+        for attr in attrs {
+            match attr {
+                PgPolicyAttribute::Name(value) => name = Some(value),
+                PgPolicyAttribute::Table(value) => table = Some(value),
+                PgPolicyAttribute::Command(value) => command = Some(value),
+                PgPolicyAttribute::Restrictive => restrictive = true,
+                PgPolicyAttribute::To(value) => roles.extend(value),
+                PgPolicyAttribute::Using(value) => using = Some(value),
+                PgPolicyAttribute::WithCheck(value) => with_check = Some(value),
+                PgPolicyAttribute::Requires(value) => requires.extend(value),
+            }
+        }
+
+        let name =
+            name.ok_or_else(|| syn::Error::new(input.span(), "expected `name` to be set"))?;
+        let table =
+            table.ok_or_else(|| syn::Error::new(input.span(), "expected `table` to be set"))?;
+        let command = command.unwrap_or(PolicyCommand::All);
+
+        // Postgres rejects these combinations too, but we'd rather not wait until
+        // `CREATE EXTENSION` to find out
+        if using.is_none() && with_check.is_none() {
+            return Err(syn::Error::new(
+                name.span(),
+                "a policy requires a `using` or `with_check` predicate, or both",
+            ));
+        }
+        if command == PolicyCommand::Insert && using.is_some() {
+            return Err(syn::Error::new(
+                name.span(),
+                "`command = insert` policies only accept a `with_check` predicate",
+            ));
+        }
+        if matches!(command, PolicyCommand::Select | PolicyCommand::Delete) && with_check.is_some()
+        {
+            return Err(syn::Error::new(
+                name.span(),
+                "`command = select` and `command = delete` policies only accept a `using` predicate",
+            ));
+        }
+
+        Ok(CodeEnrichment(PgPolicy {
+            name,
+            table,
+            command,
+            restrictive,
+            roles,
+            using,
+            with_check,
+            requires,
+        }))
+    }
+}
+
+impl ToEntityGraphTokens for PgPolicy {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let name = &self.name;
+        let table = &self.table;
+        let command = &self.command;
+        let restrictive = self.restrictive;
+        let roles = self.roles.iter();
+        let using = option_tokens(&self.using);
+        let with_check = option_tokens(&self.with_check);
+        let requires = self.requires.iter();
+
+        // policies are named per table, and either name can be any SQL identifier, even one that
+        // starts with a digit
+        let ident = format!("_{}_{}", table.value(), name.value())
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        let ident = Ident::new(&ident, Span::call_site());
+        let sql_graph_entity_fn_name =
+            Ident::new(&format!("__pgx_internals_policy{}", ident), Span::call_site());
+        let sql_graph_entity_fn_symbol = crate::entity_symbol_tokens("policy", &ident);
+        quote! {
+            #[export_name = #sql_graph_entity_fn_symbol]
+            #[doc(hidden)]
+            pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
+                extern crate alloc;
+                use alloc::vec::Vec;
+                use alloc::vec;
+                let submission = ::pgx::pgx_sql_entity_graph::PgPolicyEntity {
+                    name: #name,
+                    table: #table,
+                    module_path: module_path!(),
+                    full_path: concat!(module_path!(), "::", #name),
+                    file: file!(),
+                    line: line!(),
+                    command: #command,
+                    restrictive: #restrictive,
+                    roles: vec![#(#roles),*],
+                    using: #using,
+                    with_check: #with_check,
+                    requires: vec![#(#requires),*],
+                };
+                ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Policy(submission)
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PgPolicy {}
+
+fn option_tokens<T: ToTokens>(value: &Option<T>) -> TokenStream2 {
+    match value {
+        Some(value) => quote! { Some(#value) },
+        None => quote! { None },
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PgPolicyAttribute {
+    Name(LitStr),
+    Table(LitStr),
+    Command(PolicyCommand),
+    Restrictive,
+    To(Punctuated<LitStr, Token![,]>),
+    Using(PolicyPredicate),
+    WithCheck(PolicyPredicate),
+    Requires(Punctuated<PositioningRef, Token![,]>),
+}
+
+impl Parse for PgPolicyAttribute {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let ident: Ident = input.parse()?;
+        if ident == "restrictive" {
+            return Ok(Self::Restrictive);
+        }
+
+        let _eq: Token![=] = input.parse()?;
+        let found = match ident.to_string().as_str() {
+            "name" => Self::Name(input.parse()?),
+            "table" => Self::Table(input.parse()?),
+            "command" => Self::Command(input.parse()?),
+            "to" => {
+                let content;
+                let _bracket = syn::bracketed!(content in input);
+                Self::To(content.parse_terminated(<LitStr as Parse>::parse)?)
+            }
+            "using" => Self::Using(input.parse()?),
+            "with_check" => Self::WithCheck(input.parse()?),
+            "requires" => {
+                let content;
+                let _bracket = syn::bracketed!(content in input);
+                Self::Requires(content.parse_terminated(PositioningRef::parse)?)
+            }
+            other => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    &format!("Unknown pg_policy attribute: {}", other),
+                ))
+            }
+        };
+        Ok(found)
+    }
+}
+
+/// The command a policy applies to, as in `CREATE POLICY ... FOR command`
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PolicyCommand {
+    All,
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Parse for PolicyCommand {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let ident: Ident = input.parse()?;
+        let found = match ident.to_string().to_lowercase().as_str() {
+            "all" => Self::All,
+            "select" => Self::Select,
+            "insert" => Self::Insert,
+            "update" => Self::Update,
+            "delete" => Self::Delete,
+            _ => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected one of `all`, `select`, `insert`, `update`, or `delete`",
+                ))
+            }
+        };
+        Ok(found)
+    }
+}
+
+impl ToTokens for PolicyCommand {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let variant = Ident::new(&format!("{:?}", self), Span::call_site());
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::PolicyCommand::#variant
+        });
+    }
+}
+
+/// A parsed `using = function(args)` or `with_check = function(args)` predicate.
+///
+/// `function` is the path to a `#[pg_extern]` function, and each of the `args` is either an
+/// identifier, such as a column name, or a string literal of SQL, which is used verbatim.
+#[derive(Debug, Clone)]
+pub struct PolicyPredicate {
+    pub function: PositioningRef,
+    pub args: Vec<String>,
+}
+
+impl Parse for PolicyPredicate {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let path: syn::Path = input.parse()?;
+        let content;
+        let _paren = syn::parenthesized!(content in input);
+        let args = content.parse_terminated::<_, Token![,]>(PolicyPredicateArg::parse)?;
+
+        let mut segments =
+            path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<_>>();
+        if matches!(segments.first().map(String::as_str), Some("crate") | Some("self")) {
+            segments.remove(0);
+        }
+        Ok(PolicyPredicate {
+            function: PositioningRef::FullPath(segments.join("::")),
+            args: args.into_iter().map(|PolicyPredicateArg(arg)| arg).collect(),
+        })
+    }
+}
+
+impl ToTokens for PolicyPredicate {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let function = match &self.function {
+            // a bare function name is resolved relative to the module `pg_policy!()` is used in
+            PositioningRef::FullPath(path) if !path.contains("::") => quote! {
+                ::pgx::pgx_sql_entity_graph::PositioningRef::FullPath(
+                    String::from(concat!(module_path!(), "::", #path))
+                )
+            },
+            function => function.to_token_stream(),
+        };
+        let args = self.args.iter();
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::PolicyPredicateEntity {
+                function: #function,
+                args: vec![#(#args),*],
+            }
+        });
+    }
+}
+
+struct PolicyPredicateArg(String);
+
+impl Parse for PolicyPredicateArg {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        if input.peek(LitStr) {
+            let sql: LitStr = input.parse()?;
+            Ok(Self(sql.value()))
+        } else {
+            Ok(Self(Ident::parse_any(input)?.to_string()))
+        }
+    }
+}
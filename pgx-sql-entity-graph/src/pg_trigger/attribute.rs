@@ -14,12 +14,15 @@ use syn::Token;
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum PgTriggerAttribute {
     Sql(ToSqlConfig),
+    /// Give each call its own `pgx::arena::Arena`
+    Arena,
 }
 
 impl Parse for PgTriggerAttribute {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
         let ident: syn::Ident = input.parse()?;
         let found = match ident.to_string().as_str() {
+            "arena" => Self::Arena,
             "sql" => {
                 use crate::pgx_attribute::ArgValue;
                 use syn::Lit;
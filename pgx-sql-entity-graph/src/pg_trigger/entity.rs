@@ -19,8 +19,9 @@ pub struct PgTriggerEntity {
 }
 
 impl PgTriggerEntity {
-    fn wrapper_function_name(&self) -> String {
-        self.function_name.to_string() + "_wrapper"
+    /// The symbol of the trigger's `extern "C"` wrapper, which is namespaced by its module
+    pub fn wrapper_symbol(&self) -> String {
+        crate::wrapper_symbol(self.module_path, self.function_name)
     }
 }
 
@@ -53,7 +54,7 @@ impl ToSql for PgTriggerEntity {
             line = self.line,
             full_path = self.full_path,
             function_name = self.function_name,
-            wrapper_function_name = self.wrapper_function_name(),
+            wrapper_function_name = self.wrapper_symbol(),
         );
         Ok(sql)
     }
@@ -13,13 +13,15 @@ use crate::enrich::{ToEntityGraphTokens, ToRustCodeTokens};
 use crate::{CodeEnrichment, ToSqlConfig};
 use attribute::PgTriggerAttribute;
 use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::quote;
+use quote::{quote, ToTokens};
 use syn::{ItemFn, Token};
 
 #[derive(Debug, Clone)]
 pub struct PgTrigger {
     func: syn::ItemFn,
     to_sql_config: ToSqlConfig,
+    /// Is each call made with its own arena?
+    arena: bool,
 }
 
 impl PgTrigger {
@@ -27,21 +29,28 @@ impl PgTrigger {
         func: ItemFn,
         attributes: syn::punctuated::Punctuated<PgTriggerAttribute, Token![,]>,
     ) -> Result<CodeEnrichment<Self>, syn::Error> {
-        if attributes.len() > 1 {
+        let mut to_sql_configs = Vec::new();
+        let mut arena = false;
+        for attribute in attributes {
+            match attribute {
+                PgTriggerAttribute::Sql(config) => to_sql_configs.push(config),
+                PgTriggerAttribute::Arena => arena = true,
+            }
+        }
+        if to_sql_configs.len() > 1 {
             return Err(syn::Error::new(
                 Span::call_site(),
                 "Multiple `sql` arguments found, it must be unique",
             ));
         };
-        let to_sql_config = attributes
-            .first()
-            .cloned()
-            .map(|PgTriggerAttribute::Sql(mut config)| {
+        let to_sql_config = to_sql_configs
+            .pop()
+            .map(|mut config| {
                 if let Some(ref mut content) = config.content {
+                    // `@FUNCTION_NAME@` is replaced by the `ToSqlConfigEntity`, which knows the
+                    // module path
                     let value = content.value();
-                    let updated_value = value
-                        .replace("@FUNCTION_NAME@", &*(func.sig.ident.to_string() + "_wrapper"))
-                        + "\n";
+                    let updated_value = value + "\n";
                     *content = syn::LitStr::new(&updated_value, Span::call_site());
                 };
                 config
@@ -52,7 +61,7 @@ impl PgTrigger {
             crate::ident_is_acceptable_to_postgres(&func.sig.ident)?;
         }
 
-        Ok(CodeEnrichment(PgTrigger { func, to_sql_config }))
+        Ok(CodeEnrichment(PgTrigger { func, to_sql_config, arena }))
     }
 
     pub fn wrapper_tokens(&self) -> Result<ItemFn, syn::Error> {
@@ -61,19 +70,23 @@ impl PgTrigger {
             &format!("{}_wrapper", self.func.sig.ident.to_string()),
             self.func.sig.ident.span(),
         );
+        let wrapper_symbol = crate::wrapper_symbol_tokens("", &self.func.sig.ident);
+        let call = quote! { #function_ident(&pg_trigger) };
+        let call = if self.arena {
+            quote! { ::pgx::arena::__row_scope(|| #call) }
+        } else {
+            call
+        };
         let tokens = quote! {
-            #[no_mangle]
+            #[export_name = #wrapper_symbol]
             #[::pgx::pgx_macros::pg_guard]
             unsafe extern "C" fn #extern_func_ident(fcinfo: ::pgx::pg_sys::FunctionCallInfo) -> ::pgx::pg_sys::Datum {
                 let maybe_pg_trigger = unsafe { ::pgx::trigger_support::PgTrigger::from_fcinfo(fcinfo) };
                 let pg_trigger = maybe_pg_trigger.expect("PgTrigger::from_fcinfo failed");
-                let trigger_fn_result: Result<
-                    ::pgx::heap_tuple::PgHeapTuple<'_, _>,
-                    _,
-                > = #function_ident(&pg_trigger);
+                let trigger_fn_result: Result<_, _> = #call;
 
                 let trigger_retval = trigger_fn_result.expect("Trigger function panic");
-                match trigger_retval.into_trigger_datum() {
+                match ::pgx::trigger_support::IntoTriggerResult::into_trigger_datum(trigger_retval) {
                     None => unsafe { ::pgx::fcinfo::pg_return_null(fcinfo) },
                     Some(datum) => datum,
                 }
@@ -88,8 +101,9 @@ impl PgTrigger {
             &format!("pg_finfo_{}_wrapper", self.func.sig.ident),
             proc_macro2::Span::call_site(),
         );
+        let finfo_symbol = crate::wrapper_symbol_tokens("pg_finfo_", &self.func.sig.ident);
         let tokens = quote! {
-            #[no_mangle]
+            #[export_name = #finfo_symbol]
             #[doc(hidden)]
             pub extern "C" fn #finfo_name() -> &'static ::pgx::pg_sys::Pg_finfo_record {
                 const V1_API: ::pgx::pg_sys::Pg_finfo_record = ::pgx::pg_sys::Pg_finfo_record { api_version: 1 };
@@ -108,10 +122,11 @@ impl ToEntityGraphTokens for PgTrigger {
         );
         let func_sig_ident = &self.func.sig.ident;
         let function_name = func_sig_ident.to_string();
+        let sql_graph_entity_fn_symbol = crate::entity_symbol_tokens("trigger", func_sig_ident);
         let to_sql_config = &self.to_sql_config;
 
         quote! {
-            #[no_mangle]
+            #[export_name = #sql_graph_entity_fn_symbol]
             #[doc(hidden)]
             pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
                 use core::any::TypeId;
@@ -134,6 +149,9 @@ impl ToEntityGraphTokens for PgTrigger {
 
 impl ToRustCodeTokens for PgTrigger {
     fn to_rust_code_tokens(&self) -> TokenStream2 {
+        if crate::enrich::SQL_GENERATION_ONLY {
+            return crate::enrich::sql_generation_stub(&self.func).into_token_stream();
+        }
         let wrapper_func = self.wrapper_tokens().expect("Generating wrappper function for trigger");
         let finfo_func = self.finfo_tokens().expect("Generating finfo function for trigger");
         let func = &self.func;
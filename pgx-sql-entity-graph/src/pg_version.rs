@@ -0,0 +1,138 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pg_version = ".."` ranges for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens, TokenStreamExt};
+
+/// The Postgres major versions an entity's SQL is generated for, from a `pg_version = "..."`
+/// option.
+///
+/// Written like a Rust range of major versions:  `"14.."`, `"..15"`, `"13..=14"`, or just `"15"`.
+/// Both bounds are stored inclusively.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgVersionRange {
+    pub min: Option<u16>,
+    pub max: Option<u16>,
+}
+
+impl PgVersionRange {
+    /// Parse a range such as `"14.."`
+    pub fn parse(range: &str) -> Result<Self, String> {
+        let bound = |version: &str| {
+            version.trim().parse::<u16>().map_err(|_| {
+                format!(
+                    "`{}` is not a Postgres major version, in `pg_version = \"{}\"`",
+                    version, range
+                )
+            })
+        };
+        let optional_bound = |version: &str| {
+            if version.trim().is_empty() {
+                Ok(None)
+            } else {
+                bound(version).map(Some)
+            }
+        };
+
+        let parsed = if let Some((min, max)) = range.split_once("..=") {
+            Self { min: optional_bound(min)?, max: Some(bound(max)?) }
+        } else if let Some((min, max)) = range.split_once("..") {
+            let max = match optional_bound(max)? {
+                Some(0) => return Err(format!("`pg_version = \"{}\"` is empty", range)),
+                Some(max) => Some(max - 1),
+                None => None,
+            };
+            Self { min: optional_bound(min)?, max }
+        } else {
+            let version = bound(range)?;
+            Self { min: Some(version), max: Some(version) }
+        };
+
+        match parsed {
+            Self { min: Some(min), max: Some(max) } if min > max => {
+                Err(format!("`pg_version = \"{}\"` is empty", range))
+            }
+            parsed => Ok(parsed),
+        }
+    }
+
+    /// Is `version`, a Postgres major version like `15`, in this range?
+    pub fn contains(&self, version: u16) -> bool {
+        self.min.map_or(true, |min| min <= version) && self.max.map_or(true, |max| version <= max)
+    }
+}
+
+impl core::fmt::Display for PgVersionRange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min == max => write!(f, "{}", min),
+            (Some(min), Some(max)) => write!(f, "{}..={}", min, max),
+            (Some(min), None) => write!(f, "{}..", min),
+            (None, Some(max)) => write!(f, "..={}", max),
+            (None, None) => write!(f, ".."),
+        }
+    }
+}
+
+impl ToTokens for PgVersionRange {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let min = match self.min {
+            Some(min) => quote! { Some(#min) },
+            None => quote! { None },
+        };
+        let max = match self.max {
+            Some(max) => quote! { Some(#max) },
+            None => quote! { None },
+        };
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::PgVersionRange { min: #min, max: #max }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PgVersionRange;
+
+    #[test]
+    fn parses_ranges() {
+        let range = |min, max| PgVersionRange { min, max };
+        assert_eq!(PgVersionRange::parse("14.."), Ok(range(Some(14), None)));
+        assert_eq!(PgVersionRange::parse("..15"), Ok(range(None, Some(14))));
+        assert_eq!(PgVersionRange::parse("..=15"), Ok(range(None, Some(15))));
+        assert_eq!(PgVersionRange::parse("13..=14"), Ok(range(Some(13), Some(14))));
+        assert_eq!(PgVersionRange::parse("13..15"), Ok(range(Some(13), Some(14))));
+        assert_eq!(PgVersionRange::parse("15"), Ok(range(Some(15), Some(15))));
+    }
+
+    #[test]
+    fn rejects_bad_ranges() {
+        assert!(PgVersionRange::parse("fourteen..").is_err());
+        assert!(PgVersionRange::parse("15..14").is_err());
+        assert!(PgVersionRange::parse("14..14").is_err());
+        assert!(PgVersionRange::parse("..0").is_err());
+    }
+
+    #[test]
+    fn contains_versions() {
+        let range = PgVersionRange::parse("13..15").unwrap();
+        assert!(!range.contains(12));
+        assert!(range.contains(13));
+        assert!(range.contains(14));
+        assert!(!range.contains(15));
+        assert_eq!(range.to_string(), "13..=14");
+    }
+}
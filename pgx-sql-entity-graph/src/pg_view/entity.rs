@@ -0,0 +1,87 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pgx::pg_view!()` related entities for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::pgx_sql::PgxSql;
+use crate::positioning_ref::PositioningRef;
+use crate::to_sql::ToSql;
+use crate::{SqlGraphEntity, SqlGraphIdentifier};
+
+/// The output of a [`PgView`](crate::PgView) from `quote::ToTokens::to_tokens`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PgViewEntity {
+    pub name: &'static str,
+    pub module_path: &'static str,
+    pub full_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    /// The `SELECT` the view is of
+    pub query: &'static str,
+    /// What the view needs besides what its query is found to use, from its `requires = [..]`
+    pub requires: Vec<PositioningRef>,
+}
+
+impl PgViewEntity {
+    /// The query, without the trailing `;` that `CREATE VIEW` adds
+    pub fn trimmed_query(&self) -> &'static str {
+        self.query.trim().trim_end_matches(';').trim_end()
+    }
+}
+
+impl From<PgViewEntity> for SqlGraphEntity {
+    fn from(val: PgViewEntity) -> Self {
+        SqlGraphEntity::View(val)
+    }
+}
+
+impl SqlGraphIdentifier for PgViewEntity {
+    fn dot_identifier(&self) -> String {
+        format!("view {}", self.name)
+    }
+    fn rust_identifier(&self) -> String {
+        self.full_path.to_string()
+    }
+
+    fn file(&self) -> Option<&'static str> {
+        Some(self.file)
+    }
+
+    fn line(&self) -> Option<u32> {
+        Some(self.line)
+    }
+}
+
+impl ToSql for PgViewEntity {
+    #[tracing::instrument(level = "debug", skip(self, context), fields(identifier = self.full_path))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        let self_index = context.views[self];
+        let sql = format!(
+            "\n\
+            -- {file}:{line}\n\
+            -- {full_path}\n\
+            CREATE VIEW {schema}\"{name}\" AS\n\
+            {query};\n\
+            ",
+            file = self.file,
+            line = self.line,
+            full_path = self.full_path,
+            schema = context.schema_prefix_for(&self_index),
+            name = self.name,
+            query = self.trimmed_query(),
+        );
+        tracing::trace!(%sql);
+        Ok(sql)
+    }
+}
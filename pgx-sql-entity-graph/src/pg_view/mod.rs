@@ -0,0 +1,159 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`pgx::pg_view!()` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+pub mod entity;
+
+use crate::enrich::{CodeEnrichment, ToEntityGraphTokens, ToRustCodeTokens};
+use crate::positioning_ref::PositioningRef;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Ident, LitStr, Token};
+
+/// A parsed `pg_view!()` item.
+///
+/// It should be used with [`syn::parse::Parse`] functions.
+///
+/// Using [`quote::ToTokens`] will output the declaration for a [`PgViewEntity`][crate::PgViewEntity].
+///
+/// ```rust
+/// use syn::{Macro, parse::Parse, parse_quote, parse};
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::{CodeEnrichment, PgView};
+///
+/// # fn main() -> eyre::Result<()> {
+/// let parsed: Macro = parse_quote! {
+///     pg_view!("active_dogs", "SELECT * FROM dogs() WHERE active")
+/// };
+/// let inner_tokens = parsed.tokens;
+/// let inner: CodeEnrichment<PgView> = parse_quote! {
+///     #inner_tokens
+/// };
+/// let sql_graph_entity_tokens = inner.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgView {
+    pub name: LitStr,
+    pub query: LitStr,
+    pub requires: Vec<PositioningRef>,
+}
+
+impl Parse for CodeEnrichment<PgView> {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let name: LitStr = input.parse()?;
+        let _comma: Token![,] = input.parse()?;
+        let query: LitStr = input.parse()?;
+        let mut requires = Vec::new();
+        while input.peek(Token![,]) {
+            let _comma: Token![,] = input.parse()?;
+            if input.is_empty() {
+                break;
+            }
+            let option: Ident = input.parse()?;
+            if option != "requires" {
+                return Err(syn::Error::new(
+                    option.span(),
+                    format!("Unknown pg_view option: {}", option),
+                ));
+            }
+            let _eq: Token![=] = input.parse()?;
+            let content;
+            let _bracket = syn::bracketed!(content in input);
+            requires.extend(
+                Punctuated::<PositioningRef, Token![,]>::parse_terminated(&content)?.into_iter(),
+            );
+        }
+
+        let value = name.value();
+        let mut chars = value.chars();
+        let is_identifier = chars.next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(syn::Error::new(
+                name.span(),
+                "a view's name must be an identifier, of letters, digits and underscores",
+            ));
+        }
+        if query.value().trim().trim_end_matches(';').trim().is_empty() {
+            return Err(syn::Error::new(query.span(), "a view's query can't be empty"));
+        }
+        Ok(CodeEnrichment(PgView { name, query, requires }))
+    }
+}
+
+impl ToEntityGraphTokens for PgView {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let name = &self.name;
+        let query = &self.query;
+        let requires = &self.requires;
+        let ident = Ident::new(&name.value(), name.span());
+        let sql_graph_entity_fn_name =
+            Ident::new(&format!("__pgx_internals_view_{}", ident), Span::call_site());
+        let sql_graph_entity_fn_symbol = crate::entity_symbol_tokens("view", &ident);
+        quote! {
+            #[export_name = #sql_graph_entity_fn_symbol]
+            #[doc(hidden)]
+            pub extern "Rust" fn #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
+                extern crate alloc;
+                #[allow(unused_imports)]
+                use alloc::{vec, vec::Vec};
+                let submission = ::pgx::pgx_sql_entity_graph::PgViewEntity {
+                    name: #name,
+                    module_path: core::module_path!(),
+                    full_path: concat!(core::module_path!(), "::", #name),
+                    file: file!(),
+                    line: line!(),
+                    query: #query,
+                    requires: vec![#(#requires),*],
+                };
+                ::pgx::pgx_sql_entity_graph::SqlGraphEntity::View(submission)
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PgView {}
+
+#[cfg(test)]
+mod tests {
+    use super::PgView;
+    use crate::CodeEnrichment;
+
+    fn parse(tokens: proc_macro2::TokenStream) -> syn::Result<PgView> {
+        syn::parse2::<CodeEnrichment<PgView>>(tokens).map(|view| view.0)
+    }
+
+    #[test]
+    fn parses_views() {
+        let view = parse(quote::quote!("active_dogs", "SELECT * FROM dogs()")).unwrap();
+        assert_eq!(view.name.value(), "active_dogs");
+        assert!(view.requires.is_empty());
+
+        let view =
+            parse(quote::quote!("dogs", "SELECT 1", requires = ["dog_table", walk],)).unwrap();
+        assert_eq!(view.requires.len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_views() {
+        assert!(parse(quote::quote!("active dogs", "SELECT 1")).is_err());
+        assert!(parse(quote::quote!("dogs", " ; ")).is_err());
+        assert!(parse(quote::quote!("dogs", "SELECT 1", schema = "animals")).is_err());
+    }
+}
@@ -19,26 +19,37 @@ use eyre::eyre;
 use petgraph::dot::Dot;
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::StableGraph;
+use petgraph::Direction;
+use rayon::prelude::*;
 use std::any::TypeId;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::Debug;
 use std::path::Path;
 use tracing::instrument;
 
 use crate::aggregate::entity::PgAggregateEntity;
+use crate::composite_type::{same_identifier, CompositeTypeName};
 use crate::control_file::ControlFile;
 use crate::extension_sql::entity::{ExtensionSqlEntity, SqlDeclaredEntity};
-use crate::extension_sql::SqlDeclared;
-use crate::pg_extern::entity::PgExternEntity;
+use crate::extension_sql::scan::SqlReference;
+use crate::extension_sql::{SqlDeclared, SqlObject};
+use crate::lint::{lint_extern, Lint};
+use crate::metadata::SqlMapping;
+use crate::pg_cast::entity::PgCastEntity;
+use crate::pg_extern::entity::{OperatorLink, PgExternEntity};
+use crate::pg_gin_opclass::entity::PgGinOpclassEntity;
+use crate::pg_policy::entity::PgPolicyEntity;
 use crate::pg_trigger::entity::PgTriggerEntity;
+use crate::pg_view::entity::PgViewEntity;
 use crate::positioning_ref::PositioningRef;
+use crate::postgres_domain::entity::PostgresDomainEntity;
 use crate::postgres_enum::entity::PostgresEnumEntity;
 use crate::postgres_hash::entity::PostgresHashEntity;
 use crate::postgres_ord::entity::PostgresOrdEntity;
 use crate::postgres_type::entity::PostgresTypeEntity;
 use crate::schema::entity::SchemaEntity;
 use crate::to_sql::ToSql;
-use crate::{SqlGraphEntity, SqlGraphIdentifier};
+use crate::{SqlGraphEntity, SqlGraphIdentifier, UsedTypeEntity};
 
 use super::{PgExternReturnEntity, PgExternReturnEntityIteratedItem};
 
@@ -73,24 +84,78 @@ pub struct PgxSql {
     pub types: HashMap<PostgresTypeEntity, NodeIndex>,
     pub builtin_types: HashMap<String, NodeIndex>,
     pub enums: HashMap<PostgresEnumEntity, NodeIndex>,
+    pub domains: HashMap<PostgresDomainEntity, NodeIndex>,
     pub ords: HashMap<PostgresOrdEntity, NodeIndex>,
     pub hashes: HashMap<PostgresHashEntity, NodeIndex>,
     pub aggregates: HashMap<PgAggregateEntity, NodeIndex>,
     pub triggers: HashMap<PgTriggerEntity, NodeIndex>,
+    pub policies: HashMap<PgPolicyEntity, NodeIndex>,
+    pub casts: HashMap<PgCastEntity, NodeIndex>,
+    pub gin_opclasses: HashMap<PgGinOpclassEntity, NodeIndex>,
+    pub views: HashMap<PgViewEntity, NodeIndex>,
+    /// The types, enums and domains, by each of the [`TypeId`]s they map
+    pub type_ids: HashMap<TypeId, NodeIndex>,
     pub extension_name: String,
     pub versioned_so: bool,
+    /// The Postgres major version the SQL is generated for
+    pub pg_version: u16,
+    /// What [`PgxSql::schema_prefix_for`] returns for each node, which is worked out once, as it
+    /// means walking all the neighbors of types used by many functions
+    schema_prefixes: HashMap<NodeIndex, String>,
 }
 
+/// What [`PgxSql::to_split_sql`] puts each entity's SQL in a file of its own for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// The Postgres schema it's created in
+    Schema,
+    /// The top-level Rust module it's declared in
+    Module,
+}
+
+impl std::fmt::Display for SplitBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitBy::Schema => write!(f, "schema"),
+            SplitBy::Module => write!(f, "module"),
+        }
+    }
+}
+
+/// The file of [`PgxSql::to_split_sql`] which includes the others
+pub const SPLIT_INDEX_FILE: &str = "index.sql";
+
+/// How the [`SPLIT_INDEX_FILE`] starts, which marks a directory [`PgxSql::to_split_dir`] wrote to
+const SPLIT_INDEX_MARKER: &str =
+    "-- Written by `cargo pgx schema --split-by`, which replaces the files it includes.\n";
+
 impl PgxSql {
+    /// Build the graph of `entities` for the Postgres major version `pg_version`, such as `15`.
+    ///
+    /// Entities with a `pg_version = ".."` range which doesn't include `pg_version` are left out, as
+    /// if they'd been `#[cfg]`'d away, so anything which requires them fails to build.
     #[instrument(level = "error", skip(entities,))]
     pub fn build(
         entities: impl Iterator<Item = SqlGraphEntity>,
         extension_name: String,
         versioned_so: bool,
+        pg_version: u16,
     ) -> eyre::Result<Self> {
         let mut graph = StableGraph::new();
 
-        let mut entities = entities.collect::<Vec<_>>();
+        let mut entities = entities
+            .filter(|entity| match entity.pg_version() {
+                Some(range) if !range.contains(pg_version) => {
+                    tracing::debug!(
+                        identifier = %entity.rust_identifier(),
+                        %range,
+                        "Omitting entity for another Postgres version"
+                    );
+                    false
+                }
+                _ => true,
+            })
+            .collect::<Vec<_>>();
         entities.sort();
         // Split up things into their specific types:
         let mut control: Option<ControlFile> = None;
@@ -99,10 +164,15 @@ impl PgxSql {
         let mut externs: Vec<PgExternEntity> = Vec::default();
         let mut types: Vec<PostgresTypeEntity> = Vec::default();
         let mut enums: Vec<PostgresEnumEntity> = Vec::default();
+        let mut domains: Vec<PostgresDomainEntity> = Vec::default();
         let mut ords: Vec<PostgresOrdEntity> = Vec::default();
         let mut hashes: Vec<PostgresHashEntity> = Vec::default();
         let mut aggregates: Vec<PgAggregateEntity> = Vec::default();
         let mut triggers: Vec<PgTriggerEntity> = Vec::default();
+        let mut policies: Vec<PgPolicyEntity> = Vec::default();
+        let mut casts: Vec<PgCastEntity> = Vec::default();
+        let mut gin_opclasses: Vec<PgGinOpclassEntity> = Vec::default();
+        let mut views: Vec<PgViewEntity> = Vec::default();
         for entity in entities {
             match entity {
                 SqlGraphEntity::ExtensionRoot(input_control) => {
@@ -124,6 +194,9 @@ impl PgxSql {
                 SqlGraphEntity::Enum(input_enum) => {
                     enums.push(input_enum);
                 }
+                SqlGraphEntity::Domain(input_domain) => {
+                    domains.push(input_domain);
+                }
                 SqlGraphEntity::Ord(input_ord) => {
                     ords.push(input_ord);
                 }
@@ -136,6 +209,18 @@ impl PgxSql {
                 SqlGraphEntity::Trigger(input_trigger) => {
                     triggers.push(input_trigger);
                 }
+                SqlGraphEntity::Policy(input_policy) => {
+                    policies.push(input_policy);
+                }
+                SqlGraphEntity::Cast(input_cast) => {
+                    casts.push(input_cast);
+                }
+                SqlGraphEntity::GinOperatorClass(input_gin_opclass) => {
+                    gin_opclasses.push(input_gin_opclass);
+                }
+                SqlGraphEntity::View(input_view) => {
+                    views.push(input_view);
+                }
             }
         }
 
@@ -155,6 +240,7 @@ impl PgxSql {
         let mapped_schemas = initialize_schemas(&mut graph, bootstrap, finalize, schemas)?;
         let mapped_enums = initialize_enums(&mut graph, root, bootstrap, finalize, enums)?;
         let mapped_types = initialize_types(&mut graph, root, bootstrap, finalize, types)?;
+        let mapped_domains = initialize_domains(&mut graph, root, bootstrap, finalize, domains)?;
         let (mapped_externs, mut mapped_builtin_types) = initialize_externs(
             &mut graph,
             root,
@@ -163,6 +249,7 @@ impl PgxSql {
             externs,
             &mapped_types,
             &mapped_enums,
+            &mapped_domains,
         )?;
         let mapped_ords = initialize_ords(&mut graph, root, bootstrap, finalize, ords)?;
         let mapped_hashes = initialize_hashes(&mut graph, root, bootstrap, finalize, hashes)?;
@@ -177,6 +264,30 @@ impl PgxSql {
             &mapped_types,
         )?;
         let mapped_triggers = initialize_triggers(&mut graph, root, bootstrap, finalize, triggers)?;
+        let mapped_policies = initialize_policies(&mut graph, root, bootstrap, finalize, policies)?;
+        let mapped_casts = initialize_casts(
+            &mut graph,
+            root,
+            bootstrap,
+            finalize,
+            casts,
+            &mut mapped_builtin_types,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_domains,
+        )?;
+        let mapped_gin_opclasses = initialize_gin_opclasses(
+            &mut graph,
+            root,
+            bootstrap,
+            finalize,
+            gin_opclasses,
+            &mut mapped_builtin_types,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_domains,
+        )?;
+        let mapped_views = initialize_views(&mut graph, root, bootstrap, finalize, views)?;
 
         // Now we can circle back and build up the edge sets.
         connect_schemas(&mut graph, &mapped_schemas, root);
@@ -186,11 +297,20 @@ impl PgxSql {
             &mapped_schemas,
             &mapped_types,
             &mapped_enums,
+            &mapped_domains,
             &mapped_externs,
             &mapped_triggers,
         )?;
         connect_enums(&mut graph, &mapped_enums, &mapped_schemas);
         connect_types(&mut graph, &mapped_types, &mapped_schemas);
+        connect_domains(
+            &mut graph,
+            &mapped_domains,
+            &mapped_schemas,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_externs,
+        )?;
         connect_externs(
             &mut graph,
             &mapped_externs,
@@ -198,10 +318,12 @@ impl PgxSql {
             &mapped_schemas,
             &mapped_types,
             &mapped_enums,
+            &mapped_domains,
             &mapped_builtin_types,
             &mapped_extension_sqls,
             &mapped_triggers,
         )?;
+        connect_operators(&mut graph, &mapped_externs)?;
         connect_ords(
             &mut graph,
             &mapped_ords,
@@ -228,8 +350,64 @@ impl PgxSql {
             &mapped_externs,
         )?;
         connect_triggers(&mut graph, &mapped_triggers, &mapped_schemas);
+        connect_policies(
+            &mut graph,
+            &mapped_policies,
+            &mapped_schemas,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_domains,
+            &mapped_externs,
+            &mapped_extension_sqls,
+            &mapped_triggers,
+        )?;
+        connect_casts(
+            &mut graph,
+            &mapped_casts,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_domains,
+            &mapped_builtin_types,
+            &mapped_externs,
+        )?;
+        connect_gin_opclasses(
+            &mut graph,
+            &mapped_gin_opclasses,
+            &mapped_schemas,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_domains,
+            &mapped_builtin_types,
+            &mapped_externs,
+        )?;
+        connect_views(
+            &mut graph,
+            &mapped_views,
+            &mapped_schemas,
+            &mapped_types,
+            &mapped_enums,
+            &mapped_domains,
+            &mapped_externs,
+            &mapped_extension_sqls,
+            &mapped_triggers,
+        )?;
+
+        let mut type_ids = HashMap::new();
+        let type_mappings = mapped_types
+            .iter()
+            .flat_map(|(ty, &index)| ty.mappings.iter().map(move |mapping| (mapping.id, index)))
+            .chain(mapped_enums.iter().flat_map(|(en, &index)| {
+                en.mappings.iter().map(move |mapping| (mapping.id, index))
+            }))
+            .chain(mapped_domains.iter().flat_map(|(domain, &index)| {
+                domain.mappings.iter().map(move |mapping| (mapping.id, index))
+            }));
+        for (id, index) in type_mappings {
+            // like the edges, a type takes precedence over an enum, and an enum over a domain
+            type_ids.entry(id).or_insert(index);
+        }
 
-        let this = Self {
+        let mut this = Self {
             control: control,
             schemas: mapped_schemas,
             extension_sqls: mapped_extension_sqls,
@@ -237,20 +415,213 @@ impl PgxSql {
             types: mapped_types,
             builtin_types: mapped_builtin_types,
             enums: mapped_enums,
+            domains: mapped_domains,
             ords: mapped_ords,
             hashes: mapped_hashes,
             aggregates: mapped_aggregates,
             triggers: mapped_triggers,
+            policies: mapped_policies,
+            casts: mapped_casts,
+            gin_opclasses: mapped_gin_opclasses,
+            views: mapped_views,
+            type_ids,
             graph: graph,
             graph_root: root,
             graph_bootstrap: bootstrap,
             graph_finalize: finalize,
             extension_name: extension_name,
             versioned_so,
+            pg_version,
+            schema_prefixes: HashMap::new(),
         };
+        this.schema_prefixes = this
+            .graph
+            .node_indices()
+            .map(|index| (index, this.find_schema_prefix(&index)))
+            .collect();
+        // this needs the schemas entities are in, but doesn't change them
+        this.connect_inferred_requires();
         Ok(this)
     }
 
+    /// Add the edges `extension_sql!(.., infer_requires)` blocks and `pg_view!()`s need, from the
+    /// entities their SQL uses to them, as if they'd been listed in `requires = [..]`.
+    ///
+    /// The SQL is only scanned for names, so it warns about a call which looks like it's to one of
+    /// the extension's functions but isn't to anything in the graph, rather than failing.
+    fn connect_inferred_requires(&mut self) {
+        let blocks = self
+            .extension_sqls
+            .iter()
+            .filter(|(item, _)| item.infer_requires)
+            .map(|(item, &index)| (index, item.name, item.sql));
+        let views = self.views.iter().map(|(item, &index)| (index, item.name, item.query));
+        let inferring = blocks.chain(views).collect::<Vec<_>>();
+
+        let mut edges = Vec::new();
+        for (index, name, sql) in inferring {
+            let item = &self.graph[index];
+            for reference in crate::extension_sql::scan::references(sql) {
+                if reference.created {
+                    continue;
+                }
+                let targets = self.referenced_indexes(&reference, index);
+                if targets.is_empty() {
+                    self.warn_unresolved_reference(item, &reference);
+                }
+                for target in targets {
+                    if self.graph.find_edge(target, index).is_some()
+                        || edges.contains(&(target, index))
+                    {
+                        continue;
+                    }
+                    if petgraph::algo::has_path_connecting(&self.graph, index, target, None) {
+                        tracing::warn!(
+                            from = %self.graph[target].rust_identifier(),
+                            to = %item.rust_identifier(),
+                            "Not inferring that `{}` requires `{}`, which already comes after it",
+                            name,
+                            reference.name,
+                        );
+                        continue;
+                    }
+                    tracing::debug!(from = %self.graph[target].rust_identifier(), to = %item.rust_identifier(), "Adding {} after inferred requirement", item.dot_identifier());
+                    edges.push((target, index));
+                }
+            }
+        }
+        for (target, index) in edges {
+            self.graph.add_edge(target, index, SqlGraphRelationship::RequiredBy);
+        }
+    }
+
+    /// The functions, types, enums, domains, views, and `declares = [..]` objects of other blocks
+    /// that `reference`, in the SQL of the block or view at `block`, names
+    fn referenced_indexes(&self, reference: &SqlReference, block: NodeIndex) -> Vec<NodeIndex> {
+        let mut found = Vec::new();
+        if reference.called {
+            for (item, &index) in &self.externs {
+                let quoted = format!("\"{}\"", item.name.replace('"', "\"\""));
+                if same_identifier(&reference.name, &quoted)
+                    && self.reference_in_schema(reference, &self.schema_prefix_for(&index))
+                {
+                    found.push(index);
+                }
+            }
+        }
+        let types = self.types.iter().map(|(item, index)| (item.name, index));
+        let enums = self.enums.iter().map(|(item, index)| (item.name, index));
+        let domains = self.domains.iter().map(|(item, index)| (item.name, index));
+        for (name, &index) in types.chain(enums).chain(domains) {
+            if same_identifier(&reference.name, name)
+                && self.reference_in_schema(reference, &self.schema_prefix_for(&index))
+            {
+                found.push(index);
+            }
+        }
+        if !reference.called {
+            for (item, &index) in &self.views {
+                let quoted = format!("\"{}\"", item.name);
+                if index != block
+                    && same_identifier(&reference.name, &quoted)
+                    && self.reference_in_schema(reference, &self.schema_prefix_for(&index))
+                {
+                    found.push(index);
+                }
+            }
+        }
+        for (item, &index) in &self.extension_sqls {
+            if index == block {
+                continue;
+            }
+            for declared in &item.declares {
+                if matches!(declared, SqlObject::Function(_)) && !reference.called {
+                    continue;
+                }
+                let declared = CompositeTypeName::parse(declared.name());
+                let schema = match &declared.schema {
+                    Some(schema) => schema.clone(),
+                    None => self.schema_prefix_for(&index),
+                };
+                if same_identifier(&reference.name, &declared.name)
+                    && self.reference_in_schema(reference, &schema)
+                {
+                    found.push(index);
+                }
+            }
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// Could `reference` be to an entity in the schema with the prefix `entity_schema`?  One that
+    /// isn't qualified could be to anything on the `search_path`.
+    fn reference_in_schema(&self, reference: &SqlReference, entity_schema: &str) -> bool {
+        let entity_schema = entity_schema.trim_end_matches('.');
+        match &reference.schema {
+            None => true,
+            Some(schema) if same_identifier(schema, entity_schema) => true,
+            Some(schema) => {
+                self.is_extension_schema(schema)
+                    && (entity_schema.is_empty() || self.is_extension_schema(entity_schema))
+            }
+        }
+    }
+
+    /// Is `schema` how the SQL names the schema the extension is created in?
+    fn is_extension_schema(&self, schema: &str) -> bool {
+        schema == "@extschema@"
+            || extension_schema_alias(&self.control)
+                .map_or(false, |alias| same_identifier(schema, &alias))
+    }
+
+    fn warn_unresolved_reference(&self, item: &SqlGraphEntity, reference: &SqlReference) {
+        if !reference.called {
+            return;
+        }
+        match &reference.schema {
+            Some(schema)
+                if self.is_extension_schema(schema)
+                    || self.schemas.keys().any(|entity| same_identifier(schema, entity.name)) =>
+            {
+                tracing::warn!(
+                    sql = %item.rust_identifier(),
+                    "`{}.{}()` looks like one of the extension's functions, but there isn't a \
+                    `#[pg_extern]` or `declares = [Function(..)]` of that name in `{}`, so it \
+                    can't be required",
+                    schema,
+                    reference.name,
+                    schema,
+                );
+            }
+            Some(_) => (),
+            None => {
+                let renamed = self
+                    .externs
+                    .keys()
+                    .find(|function| same_identifier(&reference.name, function.unaliased_name));
+                if let Some(function) = renamed {
+                    tracing::warn!(
+                        sql = %item.rust_identifier(),
+                        "`{}()` is the Rust name of the `#[pg_extern]` named `{}` in SQL, so it \
+                        can't be required",
+                        reference.name,
+                        function.name,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Find contradictions between how the `#[pg_extern]` functions are declared and what their
+    /// bodies do, such as an `IMMUTABLE` function that uses SPI.
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = self.externs.keys().flat_map(lint_extern).collect::<Vec<_>>();
+        lints.sort();
+        lints
+    }
+
     #[instrument(level = "error", skip(self))]
     pub fn to_file(&self, file: impl AsRef<Path> + Debug) -> eyre::Result<()> {
         use std::fs::{create_dir_all, File};
@@ -361,6 +732,10 @@ impl PgxSql {
                         "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#C9A7C8\", weight = 5, shape = \"oval\"",
                         node.dot_identifier()
                     ),
+                    SqlGraphEntity::Domain(_item) => format!(
+                        "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#C9A7C8\", weight = 5, shape = \"oval\"",
+                        node.dot_identifier()
+                    ),
                     SqlGraphEntity::Ord(_item) => format!(
                         "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#FFCFD3\", weight = 5, shape = \"diamond\"",
                         node.dot_identifier()
@@ -377,6 +752,22 @@ impl PgxSql {
                         "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#FFE4E0\", weight = 5, shape = \"diamond\"",
                         node.dot_identifier()
                     ),
+                    SqlGraphEntity::Policy(_item) => format!(
+                        "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#C7DFC5\", weight = 3, shape = \"octagon\"",
+                        node.dot_identifier()
+                    ),
+                    SqlGraphEntity::Cast(_item) => format!(
+                        "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#E0D5C6\", weight = 3, shape = \"cds\"",
+                        node.dot_identifier()
+                    ),
+                    SqlGraphEntity::GinOperatorClass(_item) => format!(
+                        "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#D5C6E0\", weight = 3, shape = \"component\"",
+                        node.dot_identifier()
+                    ),
+                    SqlGraphEntity::View(_item) => format!(
+                        "label = \"{}\", penwidth = 0, style = \"filled\", fillcolor = \"#C6E0D5\", weight = 3, shape = \"tab\"",
+                        node.dot_identifier()
+                    ),
                     SqlGraphEntity::CustomSql(_item) => format!(
                         "label = \"{}\", weight = 3, shape = \"signature\"",
                         node.dot_identifier()
@@ -400,38 +791,157 @@ impl PgxSql {
     }
 
     pub fn schema_alias_of(&self, item_index: &NodeIndex) -> Option<String> {
-        self.graph
-            .neighbors_undirected(*item_index)
-            .flat_map(|neighbor_index| match &self.graph[neighbor_index] {
-                SqlGraphEntity::Schema(s) => Some(String::from(s.name)),
-                SqlGraphEntity::ExtensionRoot(control) => {
-                    if !control.relocatable {
-                        control.schema.clone()
-                    } else {
-                        Some(String::from("@extname@"))
-                    }
-                }
-                _ => None,
-            })
-            .next()
+        if !self.control.safe_search_path {
+            return self
+                .graph
+                .neighbors_undirected(*item_index)
+                .flat_map(|neighbor_index| match &self.graph[neighbor_index] {
+                    SqlGraphEntity::Schema(s) => Some(String::from(s.name)),
+                    SqlGraphEntity::ExtensionRoot(control) => extension_schema_alias(control),
+                    _ => None,
+                })
+                .next();
+        }
+        // everything is connected to the extension root, so with a safe `search_path` it's only
+        // the schema when there's no `#[pg_schema]` one
+        let mut root = None;
+        for neighbor_index in self.graph.neighbors_undirected(*item_index) {
+            match &self.graph[neighbor_index] {
+                SqlGraphEntity::Schema(s) => return Some(String::from(s.name)),
+                SqlGraphEntity::ExtensionRoot(control) => root = Some(control),
+                _ => (),
+            }
+        }
+        root.and_then(extension_schema_alias)
     }
 
     pub fn schema_prefix_for(&self, target: &NodeIndex) -> String {
+        match self.schema_prefixes.get(target) {
+            Some(prefix) => prefix.clone(),
+            None => self.find_schema_prefix(target),
+        }
+    }
+
+    fn find_schema_prefix(&self, target: &NodeIndex) -> String {
+        if let SqlGraphEntity::Function(function) = &self.graph[*target] {
+            if function.in_internal_schema(&self.control) {
+                return format!("{}.", crate::INTERNAL_SCHEMA);
+            }
+        }
         self.schema_alias_of(target)
             .map(|v| (v + ".").to_string())
             .unwrap_or_else(|| "".to_string())
     }
 
+    /// The SQL type of the `composite_type!(composite_type)` that an entity in the schema with the
+    /// prefix `entity_schema_prefix` uses, qualified by exactly one schema if it's known
+    ///
+    /// A qualified name is kept as it is.  A bare one is qualified by the schema of the
+    /// `extension_sql!()` which declares it, or `creates = [Type(..)]` it:  one in the entity's
+    /// own schema if there is one, or else one in the extension's schema.  Types nothing declares,
+    /// like `record`, are left bare.
+    pub fn composite_type_sql(&self, composite_type: &str, entity_schema_prefix: &str) -> String {
+        let used = CompositeTypeName::parse(composite_type);
+        if used.is_qualified() || used.is_record() {
+            return used.to_string();
+        }
+
+        let mut declared_schemas = Vec::new();
+        for (ext_item, ext_index) in &self.extension_sqls {
+            let ext_schema = || self.schema_prefix_for(ext_index).trim_end_matches('.').to_string();
+            for declared in ext_item.declares.iter() {
+                if declared.is_composite_type(composite_type) {
+                    let schema =
+                        CompositeTypeName::parse(declared.name()).schema.unwrap_or_else(ext_schema);
+                    declared_schemas.push(schema);
+                }
+            }
+            for created in ext_item.creates.iter() {
+                if let SqlDeclaredEntity::Type(_) = created {
+                    if same_identifier(&created.sql(), &used.name) {
+                        declared_schemas.push(ext_schema());
+                    }
+                }
+            }
+        }
+        declared_schemas.sort();
+
+        let entity_schema = entity_schema_prefix.trim_end_matches('.');
+        let extension_schema = extension_schema_alias(&self.control).unwrap_or_default();
+        let schema = declared_schemas
+            .iter()
+            .find(|schema| same_identifier(schema, entity_schema))
+            .or_else(|| {
+                declared_schemas.iter().find(|schema| same_identifier(schema, &extension_schema))
+            })
+            .or_else(|| declared_schemas.first());
+        used.qualified_by(schema.map(String::as_str)).to_string()
+    }
+
+    /// The node of the type, enum or domain which maps `ty_id`, or else of the builtin type
+    /// named `builtin`
+    pub fn type_index_of(&self, ty_id: &TypeId, builtin: &str) -> Option<NodeIndex> {
+        self.type_ids.get(ty_id).or_else(|| self.builtin_types.get(builtin)).copied()
+    }
+
+    /// The SQL of the type `ty`, which `user` names, qualified by its schema
+    pub fn used_type_sql(&self, ty: &UsedTypeEntity, user: &str) -> eyre::Result<String> {
+        let index = self.type_index_of(&ty.ty_id, ty.full_path).ok_or_else(|| {
+            eyre!("Could not find the type `{}` of {} in graph", ty.full_path, user)
+        })?;
+        match &ty.metadata.argument_sql {
+            Ok(SqlMapping::As(sql)) => Ok(format!("{}{}", self.schema_prefix_for(&index), sql)),
+            Ok(SqlMapping::Composite { array_brackets }) => {
+                let composite_type = ty.composite_type.ok_or_else(|| {
+                    eyre!("Found a composite type but macro expansion time did not reveal a name, use `pgx::composite_type!()`")
+                })?;
+                let sql = self.composite_type_sql(composite_type, "");
+                Ok(if *array_brackets { format!("{sql}[]") } else { sql })
+            }
+            Ok(SqlMapping::Source { .. }) | Ok(SqlMapping::Skip) => {
+                Err(eyre!("The type `{}` of {} has no SQL type", ty.full_path, user))
+            }
+            Err(err) => Err((*err).into()),
+        }
+    }
+
+    /// Render the SQL of each of `steps`.
+    ///
+    /// Each entity's SQL only depends on the graph, so they're rendered in parallel, except for
+    /// those with a `#[pgx(sql = callback)]`, which is the extension's own code and may not
+    /// expect to run on another thread, so they're rendered on the calling one.
+    fn render(&self, steps: &[NodeIndex]) -> eyre::Result<Vec<String>> {
+        let has_callback = |step_id: NodeIndex| {
+            self.graph[step_id].to_sql_config().map_or(false, |config| config.callback.is_some())
+        };
+        let mut rendered = steps
+            .par_iter()
+            .map(|&step_id| {
+                if has_callback(step_id) {
+                    Ok(None)
+                } else {
+                    self.graph[step_id].to_sql(self).map(Some)
+                }
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        for (&step_id, sql) in steps.iter().zip(rendered.iter_mut()) {
+            if sql.is_none() {
+                *sql = Some(self.graph[step_id].to_sql(self)?);
+            }
+        }
+        Ok(rendered.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
+    /// Render the SQL of every entity, in the order they depend on each other.
     #[instrument(level = "error", skip(self))]
     pub fn to_sql(&self) -> eyre::Result<String> {
-        let mut full_sql = String::new();
-        for step_id in petgraph::algo::toposort(&self.graph, None).map_err(|e| {
+        let steps = petgraph::algo::toposort(&self.graph, None).map_err(|e| {
             eyre!("Failed to toposort SQL entities, node with cycle: {:?}", self.graph[e.node_id()])
-        })? {
-            let step = &self.graph[step_id];
-
-            let sql = step.to_sql(self)?;
+        })?;
+        let rendered = self.render(&steps)?;
 
+        let mut full_sql = String::with_capacity(rendered.iter().map(|sql| sql.len() + 1).sum());
+        for sql in rendered {
             if !sql.is_empty() {
                 full_sql.push_str(&sql);
                 full_sql.push('\n');
@@ -440,6 +950,197 @@ impl PgxSql {
         Ok(full_sql)
     }
 
+    /// Render the SQL of every entity, divided into a file for each schema or Rust module, for
+    /// reviewing changes to it.
+    ///
+    /// Returns each file's name and SQL, starting with `index.sql`, which `\ir`-includes the
+    /// others in the order they run.  The entities are put in an order where each part's run
+    /// together as much as they can, so a part is only divided, into `name.sql`, `name.2.sql` and
+    /// so on, when another part's entities depend on some of its and are depended on by others.
+    ///
+    /// An extension's script can't include others, so `CREATE EXTENSION` still runs the single
+    /// file from [`PgxSql::to_sql`].
+    #[instrument(level = "error", skip(self))]
+    pub fn to_split_sql(&self, split_by: SplitBy) -> eyre::Result<Vec<(String, String)>> {
+        let steps = petgraph::algo::toposort(&self.graph, None).map_err(|e| {
+            eyre!("Failed to toposort SQL entities, node with cycle: {:?}", self.graph[e.node_id()])
+        })?;
+        let rendered = steps.iter().copied().zip(self.render(&steps)?).collect::<HashMap<_, _>>();
+        // entities without SQL are in no part, so they're run as soon as they can be
+        let part_of = |index: NodeIndex| {
+            if rendered[&index].is_empty() {
+                String::new()
+            } else {
+                self.split_part(index, split_by)
+            }
+        };
+
+        // Kahn's algorithm, which runs the current part's entities while any are ready, and then
+        // moves on to the first part, by name, with one that is
+        let mut incoming = steps
+            .iter()
+            .map(|&index| {
+                (index, self.graph.neighbors_directed(index, Direction::Incoming).count())
+            })
+            .collect::<HashMap<_, _>>();
+        let mut ready = steps
+            .iter()
+            .filter(|index| incoming[index] == 0)
+            .map(|&index| (part_of(index), index))
+            .collect::<BTreeSet<_>>();
+        let mut chunks: Vec<(String, String)> = Vec::new();
+        loop {
+            let current = chunks.last().map(|(part, _)| part.clone()).unwrap_or_default();
+            let next = match ready.iter().next() {
+                Some(first) if first.0.is_empty() => Some(first.clone()),
+                first => ready
+                    .range((current.clone(), NodeIndex::new(0))..)
+                    .next()
+                    .filter(|(part, _)| *part == current)
+                    .or(first)
+                    .cloned(),
+            };
+            let (part, index) = match next {
+                Some(next) => {
+                    ready.remove(&next);
+                    next
+                }
+                None => break,
+            };
+
+            let sql = &rendered[&index];
+            match chunks.last_mut() {
+                _ if sql.is_empty() => (),
+                Some((last, chunk)) if *last == part => {
+                    chunk.push_str(sql);
+                    chunk.push('\n');
+                }
+                _ => chunks.push((part, format!("{sql}\n"))),
+            }
+            for neighbor in self.graph.neighbors_directed(index, Direction::Outgoing) {
+                let count = incoming.get_mut(&neighbor).expect("a neighbor wasn't toposorted");
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert((part_of(neighbor), neighbor));
+                }
+            }
+        }
+
+        let mut index_sql = format!(
+            "\
+            {SPLIT_INDEX_MARKER}\
+            -- The SQL of the `{extension_name}` extension, split by {split_by} for reviewing.\n\
+            -- `CREATE EXTENSION` runs it as the single file `cargo pgx schema` generates.\n\
+            ",
+            extension_name = self.extension_name,
+        );
+        let mut files = Vec::with_capacity(chunks.len() + 1);
+        let mut chunks_of = HashMap::<String, usize>::new();
+        for (part, sql) in chunks {
+            let seen = chunks_of.entry(part.clone()).or_default();
+            *seen += 1;
+            let file = match *seen {
+                1 => format!("{}.sql", part),
+                n => format!("{}.{}.sql", part, n),
+            };
+            index_sql.push_str(&format!("\\ir {}\n", file));
+            files.push((file, sql));
+        }
+        files.insert(0, (String::from(SPLIT_INDEX_FILE), index_sql));
+        Ok(files)
+    }
+
+    /// Write the files from [`PgxSql::to_split_sql`] to the directory `dir`, first removing the
+    /// ones its `index.sql` says were written last time, so it's only got the current ones.
+    ///
+    /// Nothing else in `dir` is removed, and it's an error for `dir` to have anything in it but
+    /// such an `index.sql`, which might be overwritten.
+    #[instrument(level = "error", skip(self))]
+    pub fn to_split_dir(
+        &self,
+        dir: impl AsRef<Path> + Debug,
+        split_by: SplitBy,
+    ) -> eyre::Result<usize> {
+        let files = self.to_split_sql(split_by)?;
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let index = dir.join(SPLIT_INDEX_FILE);
+        match std::fs::read_to_string(&index) {
+            Ok(index_sql) if index_sql.starts_with(SPLIT_INDEX_MARKER) => {
+                for file in index_sql.lines().filter_map(|line| line.strip_prefix("\\ir ")) {
+                    // only ever a file of the directory, whatever's been done to the index
+                    if file.contains(std::path::is_separator) || !file.ends_with(".sql") {
+                        continue;
+                    }
+                    match std::fs::remove_file(dir.join(file)) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => (),
+                    }
+                }
+            }
+            Ok(_) => {
+                return Err(eyre!(
+                    "`{}` wasn't written by `--split-by`, so `{}` isn't written to",
+                    index.display(),
+                    dir.display(),
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if std::fs::read_dir(dir)?.next().is_some() {
+                    return Err(eyre!(
+                        "`{}` isn't empty, and has no `{}` written by `--split-by`, so it isn't written to",
+                        dir.display(),
+                        SPLIT_INDEX_FILE,
+                    ));
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+        for (file, sql) in &files {
+            std::fs::write(dir.join(file), sql)?;
+        }
+        Ok(files.len())
+    }
+
+    /// The name of the file [`PgxSql::to_split_sql`] puts the entity at `index` in, without its
+    /// `.sql`
+    fn split_part(&self, index: NodeIndex, split_by: SplitBy) -> String {
+        let entity = &self.graph[index];
+        let part = match (split_by, entity) {
+            // it's connected to every schema, but is the extension's preamble
+            (_, SqlGraphEntity::ExtensionRoot(_)) => String::new(),
+            (SplitBy::Schema, SqlGraphEntity::Schema(schema)) => schema.name.to_string(),
+            (
+                SplitBy::Schema,
+                SqlGraphEntity::Function(PgExternEntity { schema: Some(schema), .. }),
+            ) => schema.to_string(),
+            (SplitBy::Schema, _) => {
+                self.schema_prefix_for(&index).trim_end_matches('.').to_string()
+            }
+            (SplitBy::Module, entity) => entity
+                .module_path()
+                .and_then(|path| path.split("::").nth(1))
+                .unwrap_or_default()
+                .to_string(),
+        };
+        // the extension's own schema, or its root module, is named after it
+        let part = part
+            .replace("@extschema@", &self.extension_name)
+            .replace("@extname@", &self.extension_name);
+        let part = if part.is_empty() { self.extension_name.clone() } else { part };
+        let part = part
+            .trim_matches('"')
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+            .collect::<String>();
+        // the index has its file to itself
+        if format!("{}.sql", part) == SPLIT_INDEX_FILE {
+            part + "_"
+        } else {
+            part
+        }
+    }
+
     pub fn has_sql_declared_entity(&self, identifier: &SqlDeclared) -> Option<&SqlDeclaredEntity> {
         self.extension_sqls.iter().find_map(|(item, _index)| {
             let retval = item.creates.iter().find_map(|create_entity| {
@@ -471,6 +1172,19 @@ impl PgxSql {
     }
 }
 
+/// The schema the extension's own objects are created in, as it's written in the SQL
+fn extension_schema_alias(control: &ControlFile) -> Option<String> {
+    if !control.relocatable {
+        // Postgres only replaces `@extschema@` in the scripts of extensions which aren't relocatable
+        control
+            .schema
+            .clone()
+            .or_else(|| control.safe_search_path.then(|| String::from("@extschema@")))
+    } else {
+        Some(String::from("@extname@"))
+    }
+}
+
 #[tracing::instrument(level = "error", skip_all)]
 fn build_base_edges(
     graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
@@ -547,6 +1261,7 @@ pub fn find_positioning_ref_target<'a>(
     positioning_ref: &'a PositioningRef,
     types: &'a HashMap<PostgresTypeEntity, NodeIndex>,
     enums: &'a HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &'a HashMap<PostgresDomainEntity, NodeIndex>,
     externs: &'a HashMap<PgExternEntity, NodeIndex>,
     schemas: &'a HashMap<SchemaEntity, NodeIndex>,
     extension_sqls: &'a HashMap<ExtensionSqlEntity, NodeIndex>,
@@ -570,6 +1285,11 @@ pub fn find_positioning_ref_target<'a>(
                     return Some(&other_index);
                 }
             }
+            for (other, other_index) in domains {
+                if last_segment == &other.name && other.module_path.ends_with(&module_path) {
+                    return Some(&other_index);
+                }
+            }
             for (other, other_index) in externs {
                 if *last_segment == other.unaliased_name
                     && other.module_path.ends_with(&module_path)
@@ -601,6 +1321,37 @@ pub fn find_positioning_ref_target<'a>(
     None
 }
 
+/// Find the `#[pg_extern]` function at `path`, which may be relative to any module, but has to be
+/// relative to only one
+pub fn find_extern_by_path<'a>(
+    path: &str,
+    externs: &'a HashMap<PgExternEntity, NodeIndex>,
+) -> eyre::Result<Option<(&'a PgExternEntity, NodeIndex)>> {
+    let (module_path, function_name) = match path.rsplit_once("::") {
+        Some((module_path, function_name)) => (module_path, function_name),
+        None => ("", path),
+    };
+    let mut found = externs.iter().filter(|(other, _)| {
+        other.unaliased_name == function_name
+            && (module_path.is_empty()
+                || other.module_path == module_path
+                || other.module_path.ends_with(&format!("::{}", module_path)))
+    });
+    match (found.next(), found.next()) {
+        (Some((first, _)), Some((second, _))) => Err(eyre!(
+            "`{}` could be either `{}` ({}:{}) or `{}` ({}:{}), so it needs more of its module path",
+            path,
+            first.full_path,
+            first.file,
+            first.line,
+            second.full_path,
+            second.file,
+            second.line,
+        )),
+        (found, _) => Ok(found.map(|(other, &index)| (other, index))),
+    }
+}
+
 #[tracing::instrument(level = "error", skip_all)]
 fn connect_extension_sqls(
     graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
@@ -608,6 +1359,7 @@ fn connect_extension_sqls(
     schemas: &HashMap<SchemaEntity, NodeIndex>,
     types: &HashMap<PostgresTypeEntity, NodeIndex>,
     enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
     externs: &HashMap<PgExternEntity, NodeIndex>,
     triggers: &HashMap<PgTriggerEntity, NodeIndex>,
 ) -> eyre::Result<()> {
@@ -626,6 +1378,7 @@ fn connect_extension_sqls(
                 requires,
                 types,
                 enums,
+                domains,
                 externs,
                 schemas,
                 extension_sqls,
@@ -758,6 +1511,59 @@ fn connect_types(
     }
 }
 
+#[tracing::instrument(level = "error", skip_all)]
+fn initialize_domains(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    root: NodeIndex,
+    bootstrap: Option<NodeIndex>,
+    finalize: Option<NodeIndex>,
+    domains: Vec<PostgresDomainEntity>,
+) -> eyre::Result<HashMap<PostgresDomainEntity, NodeIndex>> {
+    let mut mapped_domains = HashMap::default();
+    for item in domains {
+        let entity: SqlGraphEntity = item.clone().into();
+        let index = graph.add_node(entity);
+        mapped_domains.insert(item, index);
+        build_base_edges(graph, index, root, bootstrap, finalize);
+    }
+    Ok(mapped_domains)
+}
+
+#[tracing::instrument(level = "error", skip_all)]
+fn connect_domains(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+    schemas: &HashMap<SchemaEntity, NodeIndex>,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    externs: &HashMap<PgExternEntity, NodeIndex>,
+) -> eyre::Result<()> {
+    for (item, &index) in domains {
+        make_schema_connection(
+            graph,
+            "Domain",
+            index,
+            &item.rust_identifier(),
+            item.module_path,
+            schemas,
+        );
+        make_type_or_enum_connection(
+            graph,
+            "Domain",
+            index,
+            &item.rust_identifier(),
+            &item.base_ty_id,
+            types,
+            enums,
+        );
+        if let Some((function, function_index)) = item.resolve_check(externs)? {
+            tracing::debug!(from = %item.rust_identifier(), to = function.full_path, "Adding Domain after Extern edge.");
+            graph.add_edge(function_index, index, SqlGraphRelationship::RequiredBy);
+        }
+    }
+    Ok(())
+}
+
 #[tracing::instrument(level = "error", skip_all)]
 fn initialize_externs(
     graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
@@ -767,6 +1573,7 @@ fn initialize_externs(
     externs: Vec<PgExternEntity>,
     mapped_types: &HashMap<PostgresTypeEntity, NodeIndex>,
     mapped_enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    mapped_domains: &HashMap<PostgresDomainEntity, NodeIndex>,
 ) -> eyre::Result<(HashMap<PgExternEntity, NodeIndex>, HashMap<String, NodeIndex>)> {
     let mut mapped_externs = HashMap::default();
     let mut mapped_builtin_types = HashMap::default();
@@ -790,6 +1597,12 @@ fn initialize_externs(
                     break;
                 }
             }
+            for (ty_item, &_ty_index) in mapped_domains {
+                if ty_item.id_matches(&arg.used_ty.ty_id) {
+                    found = true;
+                    break;
+                }
+            }
             if !found {
                 mapped_builtin_types.entry(arg.used_ty.full_path.to_string()).or_insert_with(
                     || {
@@ -817,13 +1630,20 @@ fn initialize_externs(
                         break;
                     }
                 }
+                for (ty_item, &_ty_index) in mapped_domains {
+                    if ty_item.id_matches(&ty.ty_id) {
+                        found = true;
+                        break;
+                    }
+                }
                 if !found {
                     mapped_builtin_types.entry(ty.full_path.to_string()).or_insert_with(|| {
                         graph.add_node(SqlGraphEntity::BuiltinType(ty.full_path.to_string()))
                     });
                 }
             }
-            PgExternReturnEntity::Iterated { tys: iterated_returns, optional: _, result: _ } => {
+            PgExternReturnEntity::Iterated { tys: iterated_returns, .. }
+            | PgExternReturnEntity::Record { tys: iterated_returns, .. } => {
                 for PgExternReturnEntityIteratedItem { ty: return_ty_entity, .. } in
                     iterated_returns
                 {
@@ -840,6 +1660,12 @@ fn initialize_externs(
                             break;
                         }
                     }
+                    for (ty_item, &_ty_index) in mapped_domains {
+                        if ty_item.id_matches(&return_ty_entity.ty_id) {
+                            found = true;
+                            break;
+                        }
+                    }
                     if !found {
                         mapped_builtin_types
                             .entry(return_ty_entity.ty_source.to_string())
@@ -864,6 +1690,7 @@ fn connect_externs(
     schemas: &HashMap<SchemaEntity, NodeIndex>,
     types: &HashMap<PostgresTypeEntity, NodeIndex>,
     enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
     builtin_types: &HashMap<String, NodeIndex>,
     extension_sqls: &HashMap<ExtensionSqlEntity, NodeIndex>,
     triggers: &HashMap<PgTriggerEntity, NodeIndex>,
@@ -878,6 +1705,7 @@ fn connect_externs(
                             requires,
                             types,
                             enums,
+                            domains,
                             externs,
                             schemas,
                             extension_sqls,
@@ -930,6 +1758,16 @@ fn connect_externs(
         for arg in &item.fn_args {
             let mut found = false;
 
+            if let Some(composite_type) = arg.used_ty.composite_type {
+                make_composite_type_connection(
+                    graph,
+                    index,
+                    &item.rust_identifier(),
+                    composite_type,
+                    extension_sqls,
+                    SqlGraphRelationship::RequiredByArg,
+                )?;
+            }
             for (ty_item, &ty_index) in types {
                 if ty_item.id_matches(&arg.used_ty.ty_id) {
                     tracing::debug!(from = %item.rust_identifier(), to = %ty_item.rust_identifier(), "Adding Extern after Type (due to argument) edge");
@@ -948,6 +1786,16 @@ fn connect_externs(
                     }
                 }
             }
+            if !found {
+                for (domain_item, &domain_index) in domains {
+                    if domain_item.id_matches(&arg.used_ty.ty_id) {
+                        tracing::debug!(from = %item.rust_identifier(), to = %domain_item.rust_identifier(), "Adding Extern after Domain (due to argument) edge");
+                        graph.add_edge(domain_index, index, SqlGraphRelationship::RequiredByArg);
+                        found = true;
+                        break;
+                    }
+                }
+            }
             if !found {
                 let builtin_index = builtin_types
                     .get(arg.used_ty.full_path)
@@ -975,6 +1823,16 @@ fn connect_externs(
             PgExternReturnEntity::None | PgExternReturnEntity::Trigger => (),
             PgExternReturnEntity::Type { ty, .. } | PgExternReturnEntity::SetOf { ty, .. } => {
                 let mut found = false;
+                if let Some(composite_type) = ty.composite_type {
+                    make_composite_type_connection(
+                        graph,
+                        index,
+                        &item.rust_identifier(),
+                        composite_type,
+                        extension_sqls,
+                        SqlGraphRelationship::RequiredByReturn,
+                    )?;
+                }
                 for (ty_item, &ty_index) in types {
                     if ty_item.id_matches(&ty.ty_id) {
                         tracing::debug!(from = %item.rust_identifier(), to = %ty_item.rust_identifier(), "Adding Extern after Type (due to return) edge");
@@ -993,6 +1851,16 @@ fn connect_externs(
                         }
                     }
                 }
+                if !found {
+                    for (ty_item, &ty_index) in domains {
+                        if ty_item.id_matches(&ty.ty_id) {
+                            tracing::debug!(from = %item.rust_identifier(), to = %ty_item.rust_identifier(), "Adding Extern after Domain (due to return) edge");
+                            graph.add_edge(ty_index, index, SqlGraphRelationship::RequiredByReturn);
+                            found = true;
+                            break;
+                        }
+                    }
+                }
                 if !found {
                     let builtin_index = builtin_types
                         .get(&ty.full_path.to_string())
@@ -1016,9 +1884,20 @@ fn connect_externs(
                     }
                 }
             }
-            PgExternReturnEntity::Iterated { tys: iterated_returns, optional: _, result: _ } => {
+            PgExternReturnEntity::Iterated { tys: iterated_returns, .. }
+            | PgExternReturnEntity::Record { tys: iterated_returns, .. } => {
                 for PgExternReturnEntityIteratedItem { ty: type_entity, .. } in iterated_returns {
                     let mut found = false;
+                    if let Some(composite_type) = type_entity.composite_type {
+                        make_composite_type_connection(
+                            graph,
+                            index,
+                            &item.rust_identifier(),
+                            composite_type,
+                            extension_sqls,
+                            SqlGraphRelationship::RequiredByReturn,
+                        )?;
+                    }
                     for (ty_item, &ty_index) in types {
                         if ty_item.id_matches(&type_entity.ty_id) {
                             tracing::debug!(from = %item.rust_identifier(), to = %ty_item.rust_identifier(), "Adding Extern after Type (due to return) edge");
@@ -1041,6 +1920,20 @@ fn connect_externs(
                             }
                         }
                     }
+                    if !found {
+                        for (ty_item, &ty_index) in domains {
+                            if ty_item.id_matches(&type_entity.ty_id) {
+                                tracing::debug!(from = %item.rust_identifier(), to = %ty_item.rust_identifier(), "Adding Extern after Domain (due to return) edge");
+                                graph.add_edge(
+                                    ty_index,
+                                    index,
+                                    SqlGraphRelationship::RequiredByReturn,
+                                );
+                                found = true;
+                                break;
+                            }
+                        }
+                    }
                     if !found {
                         let builtin_index =
                             builtin_types.get(&type_entity.ty_source.to_string()).expect(&format!(
@@ -1161,6 +2054,29 @@ fn connect_ords(
     }
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+/// An operator is created after the operator its `COMMUTATOR` or `NEGATOR` names, so that one
+/// exists already.  When two operators name each other, only the second names the first, as
+/// [`PgExternEntity::leaves_out_linked_operator`] says.
+fn connect_operators(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    externs: &HashMap<PgExternEntity, NodeIndex>,
+) -> eyre::Result<()> {
+    for (item, &index) in externs {
+        for link in OperatorLink::ALL {
+            let other = match item.linked_operator(link, externs.keys())? {
+                Some(other) if other != item && !item.leaves_out_linked_operator(link, other) => {
+                    other
+                }
+                _ => continue,
+            };
+            tracing::debug!(from = %item.rust_identifier(), to = %other.rust_identifier(), link = link.keyword(), "Adding Operator after linked Operator edge");
+            graph.add_edge(externs[other], index, SqlGraphRelationship::RequiredBy);
+        }
+    }
+    Ok(())
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 fn initialize_hashes(
     graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
@@ -1501,6 +2417,334 @@ fn connect_triggers(
     }
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+fn initialize_policies(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    root: NodeIndex,
+    bootstrap: Option<NodeIndex>,
+    finalize: Option<NodeIndex>,
+    policies: Vec<PgPolicyEntity>,
+) -> eyre::Result<HashMap<PgPolicyEntity, NodeIndex>> {
+    let mut mapped_policies = HashMap::default();
+    for item in policies {
+        let entity: SqlGraphEntity = item.clone().into();
+        let index = graph.add_node(entity);
+
+        mapped_policies.insert(item, index);
+        build_base_edges(graph, index, root, bootstrap, finalize);
+    }
+    Ok(mapped_policies)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+fn connect_policies(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    policies: &HashMap<PgPolicyEntity, NodeIndex>,
+    schemas: &HashMap<SchemaEntity, NodeIndex>,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+    externs: &HashMap<PgExternEntity, NodeIndex>,
+    extension_sqls: &HashMap<ExtensionSqlEntity, NodeIndex>,
+    triggers: &HashMap<PgTriggerEntity, NodeIndex>,
+) -> eyre::Result<()> {
+    for (item, &index) in policies {
+        make_schema_connection(
+            graph,
+            "Policy",
+            index,
+            &item.rust_identifier(),
+            item.module_path,
+            schemas,
+        );
+
+        let predicates = [("using", &item.using), ("with_check", &item.with_check)];
+        for (clause, predicate) in predicates {
+            if let Some(predicate) = predicate {
+                let (function, function_index) = predicate.validate(item, clause, externs)?;
+                tracing::debug!(from = %item.rust_identifier(), to = function.full_path, "Adding Policy after Extern edge.");
+                graph.add_edge(function_index, index, SqlGraphRelationship::RequiredBy);
+            }
+        }
+
+        for requires in &item.requires {
+            if let Some(target) = find_positioning_ref_target(
+                requires,
+                types,
+                enums,
+                domains,
+                externs,
+                schemas,
+                extension_sqls,
+                triggers,
+            ) {
+                tracing::debug!(from = %item.rust_identifier(), to = ?graph[*target].rust_identifier(), "Adding Policy after positioning ref target");
+                graph.add_edge(*target, index, SqlGraphRelationship::RequiredBy);
+            } else {
+                return Err(eyre!(
+                    "Could not find `requires` target of policy `{}` ({}:{}): {}",
+                    item.name,
+                    item.file,
+                    item.line,
+                    requires,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+fn initialize_casts(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    root: NodeIndex,
+    bootstrap: Option<NodeIndex>,
+    finalize: Option<NodeIndex>,
+    casts: Vec<PgCastEntity>,
+    mapped_builtin_types: &mut HashMap<String, NodeIndex>,
+    mapped_types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    mapped_enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    mapped_domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+) -> eyre::Result<HashMap<PgCastEntity, NodeIndex>> {
+    let mut mapped_casts = HashMap::default();
+    for item in casts {
+        let entity: SqlGraphEntity = item.clone().into();
+        let index = graph.add_node(entity);
+
+        // a binary-coercible cast may be the only user of a builtin type
+        for ty in [&item.source, &item.target] {
+            add_builtin_type(
+                graph,
+                ty,
+                mapped_builtin_types,
+                mapped_types,
+                mapped_enums,
+                mapped_domains,
+            );
+        }
+
+        mapped_casts.insert(item, index);
+        build_base_edges(graph, index, root, bootstrap, finalize);
+    }
+    Ok(mapped_casts)
+}
+
+/// Connect each cast to the types it casts between, and to the function it calls, which is
+/// checked to take one argument and return one value
+#[tracing::instrument(level = "info", skip_all)]
+fn connect_casts(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    casts: &HashMap<PgCastEntity, NodeIndex>,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+    builtin_types: &HashMap<String, NodeIndex>,
+    externs: &HashMap<PgExternEntity, NodeIndex>,
+) -> eyre::Result<()> {
+    for (item, &index) in casts {
+        for ty in [&item.source, &item.target] {
+            if let Some(ty_index) = used_type_node(ty, types, enums, domains, builtin_types) {
+                tracing::debug!(from = %item.rust_identifier(), to = %graph[ty_index].rust_identifier(), "Adding Cast after Type edge.");
+                graph.add_edge(ty_index, index, SqlGraphRelationship::RequiredBy);
+            }
+        }
+
+        if let Some((function, function_index)) = item.validate_function(externs)? {
+            tracing::debug!(from = %item.rust_identifier(), to = function.full_path, "Adding Cast after Extern edge.");
+            graph.add_edge(function_index, index, SqlGraphRelationship::RequiredBy);
+        }
+    }
+    Ok(())
+}
+
+/// Add a node for `ty`, unless it's one of the extension's types, enums or domains, or already has
+/// one, for entities which may be the only users of a builtin type
+fn add_builtin_type(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    ty: &UsedTypeEntity,
+    builtin_types: &mut HashMap<String, NodeIndex>,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+) {
+    let found = types.keys().any(|ty_item| ty_item.id_matches(&ty.ty_id))
+        || enums.keys().any(|ty_item| ty_item.id_matches(&ty.ty_id))
+        || domains.keys().any(|ty_item| ty_item.id_matches(&ty.ty_id));
+    if !found {
+        builtin_types.entry(ty.full_path.to_string()).or_insert_with(|| {
+            graph.add_node(SqlGraphEntity::BuiltinType(ty.full_path.to_string()))
+        });
+    }
+}
+
+/// The node of the type, enum, domain or builtin type `ty`
+fn used_type_node(
+    ty: &UsedTypeEntity,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+    builtin_types: &HashMap<String, NodeIndex>,
+) -> Option<NodeIndex> {
+    types
+        .iter()
+        .find(|(ty_item, _)| ty_item.id_matches(&ty.ty_id))
+        .map(|(_, &ty_index)| ty_index)
+        .or_else(|| {
+            enums
+                .iter()
+                .find(|(ty_item, _)| ty_item.id_matches(&ty.ty_id))
+                .map(|(_, &ty_index)| ty_index)
+        })
+        .or_else(|| {
+            domains
+                .iter()
+                .find(|(ty_item, _)| ty_item.id_matches(&ty.ty_id))
+                .map(|(_, &ty_index)| ty_index)
+        })
+        .or_else(|| builtin_types.get(ty.full_path).copied())
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+fn initialize_gin_opclasses(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    root: NodeIndex,
+    bootstrap: Option<NodeIndex>,
+    finalize: Option<NodeIndex>,
+    gin_opclasses: Vec<PgGinOpclassEntity>,
+    mapped_builtin_types: &mut HashMap<String, NodeIndex>,
+    mapped_types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    mapped_enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    mapped_domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+) -> eyre::Result<HashMap<PgGinOpclassEntity, NodeIndex>> {
+    let mut mapped_gin_opclasses = HashMap::default();
+    for item in gin_opclasses {
+        let entity: SqlGraphEntity = item.clone().into();
+        let index = graph.add_node(entity);
+
+        // the key type, such as `text`, may not be used by any function
+        add_builtin_type(
+            graph,
+            &item.key,
+            mapped_builtin_types,
+            mapped_types,
+            mapped_enums,
+            mapped_domains,
+        );
+
+        mapped_gin_opclasses.insert(item, index);
+        build_base_edges(graph, index, root, bootstrap, finalize);
+    }
+    Ok(mapped_gin_opclasses)
+}
+
+/// Connect each GIN operator class to its schema, the types it indexes and stores, and the
+/// operators it names, which are checked to be operators on its type
+#[tracing::instrument(level = "info", skip_all)]
+fn connect_gin_opclasses(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    gin_opclasses: &HashMap<PgGinOpclassEntity, NodeIndex>,
+    schemas: &HashMap<SchemaEntity, NodeIndex>,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+    builtin_types: &HashMap<String, NodeIndex>,
+    externs: &HashMap<PgExternEntity, NodeIndex>,
+) -> eyre::Result<()> {
+    for (item, &index) in gin_opclasses {
+        make_schema_connection(
+            graph,
+            "GinOperatorClass",
+            index,
+            &item.rust_identifier(),
+            item.module_path,
+            schemas,
+        );
+
+        for ty in [&item.ty, &item.key] {
+            if let Some(ty_index) = used_type_node(ty, types, enums, domains, builtin_types) {
+                tracing::debug!(from = %item.rust_identifier(), to = %graph[ty_index].rust_identifier(), "Adding GinOperatorClass after Type edge.");
+                graph.add_edge(ty_index, index, SqlGraphRelationship::RequiredBy);
+            }
+        }
+
+        for (_, operator, operator_index) in item.validate_operators(externs)? {
+            tracing::debug!(from = %item.rust_identifier(), to = operator.full_path, "Adding GinOperatorClass after Operator edge.");
+            graph.add_edge(operator_index, index, SqlGraphRelationship::RequiredBy);
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+fn initialize_views(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    root: NodeIndex,
+    bootstrap: Option<NodeIndex>,
+    finalize: Option<NodeIndex>,
+    views: Vec<PgViewEntity>,
+) -> eyre::Result<HashMap<PgViewEntity, NodeIndex>> {
+    let mut mapped_views = HashMap::default();
+    for item in views {
+        let entity: SqlGraphEntity = item.clone().into();
+        let index = graph.add_node(entity);
+
+        mapped_views.insert(item, index);
+        build_base_edges(graph, index, root, bootstrap, finalize);
+    }
+    Ok(mapped_views)
+}
+
+/// Connect each view to its schema and its `requires = [..]`.  What its query uses is found once
+/// the schemas of everything are known, by [`PgxSql::connect_inferred_requires`].
+#[tracing::instrument(level = "info", skip_all)]
+fn connect_views(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    views: &HashMap<PgViewEntity, NodeIndex>,
+    schemas: &HashMap<SchemaEntity, NodeIndex>,
+    types: &HashMap<PostgresTypeEntity, NodeIndex>,
+    enums: &HashMap<PostgresEnumEntity, NodeIndex>,
+    domains: &HashMap<PostgresDomainEntity, NodeIndex>,
+    externs: &HashMap<PgExternEntity, NodeIndex>,
+    extension_sqls: &HashMap<ExtensionSqlEntity, NodeIndex>,
+    triggers: &HashMap<PgTriggerEntity, NodeIndex>,
+) -> eyre::Result<()> {
+    for (item, &index) in views {
+        make_schema_connection(
+            graph,
+            "View",
+            index,
+            &item.rust_identifier(),
+            item.module_path,
+            schemas,
+        );
+
+        for requires in &item.requires {
+            if let Some(target) = find_positioning_ref_target(
+                requires,
+                types,
+                enums,
+                domains,
+                externs,
+                schemas,
+                extension_sqls,
+                triggers,
+            ) {
+                tracing::debug!(from = %item.rust_identifier(), to = ?graph[*target].rust_identifier(), "Adding View after positioning ref target");
+                graph.add_edge(*target, index, SqlGraphRelationship::RequiredBy);
+            } else {
+                return Err(eyre!(
+                    "Could not find `requires` target of view `{}` ({}:{}): {}",
+                    item.name,
+                    item.file,
+                    item.line,
+                    requires,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[tracing::instrument(level = "info", skip_all, fields(rust_identifier))]
 fn make_schema_connection(
     graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
@@ -1522,6 +2766,37 @@ fn make_schema_connection(
     found
 }
 
+/// Order the extern at `index` after the `extension_sql!()` declaring the `composite_type!()` it
+/// uses.  Every composite type other than `record` has to have been declared by one.
+#[tracing::instrument(level = "error", skip_all, fields(%rust_identifier))]
+fn make_composite_type_connection(
+    graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
+    index: NodeIndex,
+    rust_identifier: &str,
+    composite_type: &str,
+    extension_sqls: &HashMap<ExtensionSqlEntity, NodeIndex>,
+    relationship: SqlGraphRelationship,
+) -> eyre::Result<()> {
+    if composite_type.trim_end_matches("[]").eq_ignore_ascii_case("record") {
+        return Ok(());
+    }
+    let mut found = false;
+    for (ext_item, &ext_index) in extension_sqls {
+        if ext_item.declares_composite_type(composite_type) {
+            tracing::debug!(from = %rust_identifier, to = %ext_item.rust_identifier(), "Adding Extern after Extension SQL (due to composite type) edge");
+            graph.add_edge(ext_index, index, relationship);
+            found = true;
+        }
+    }
+    if !found {
+        return Err(eyre!(
+            "`{rust_identifier}` uses `composite_type!(\"{composite_type}\")`, but no `extension_sql!()` \
+             declares it.  Add `declares = [Type({composite_type})]` to the block that creates it."
+        ));
+    }
+    Ok(())
+}
+
 #[tracing::instrument(level = "error", skip_all, fields(%rust_identifier))]
 fn make_extern_connection(
     graph: &mut StableGraph<SqlGraphEntity, SqlGraphRelationship>,
@@ -1580,3 +2855,61 @@ fn make_type_or_enum_connection(
 
     found
 }
+
+#[cfg(test)]
+mod tests {
+    use super::find_extern_by_path;
+    use crate::metadata::FunctionMetadataEntity;
+    use crate::{PgExternEntity, PgExternReturnEntity, ToSqlConfigEntity};
+    use petgraph::stable_graph::NodeIndex;
+    use std::collections::HashMap;
+
+    fn function(module_path: &'static str, name: &'static str) -> PgExternEntity {
+        PgExternEntity {
+            name,
+            unaliased_name: name,
+            module_path,
+            full_path: name,
+            metadata: FunctionMetadataEntity { arguments: vec![], retval: None, path: name },
+            fn_args: vec![],
+            fn_return: PgExternReturnEntity::None,
+            schema: None,
+            file: "lib.rs",
+            line: 1,
+            extern_attrs: vec![],
+            settings: vec![],
+            operator: None,
+            to_sql_config: ToSqlConfigEntity {
+                enabled: true,
+                callback: None,
+                content: None,
+                pg_version: None,
+            },
+            facts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn finds_externs_by_whole_module_names() {
+        let externs = HashMap::from([
+            (function("ext::hotdogs", "bark"), NodeIndex::new(0)),
+            (function("ext::a::dogs", "bark"), NodeIndex::new(1)),
+        ]);
+        let found = |path| find_extern_by_path(path, &externs).unwrap().map(|(_, index)| index);
+        assert_eq!(found("dogs::bark"), Some(NodeIndex::new(1)));
+        assert_eq!(found("a::dogs::bark"), Some(NodeIndex::new(1)));
+        assert_eq!(found("ext::hotdogs::bark"), Some(NodeIndex::new(0)));
+        assert_eq!(found("cats::bark"), None);
+        assert_eq!(found("gs::bark"), None);
+    }
+
+    #[test]
+    fn ambiguous_paths_are_an_error() {
+        let externs = HashMap::from([
+            (function("ext::hotdogs", "bark"), NodeIndex::new(0)),
+            (function("ext::a::dogs", "bark"), NodeIndex::new(1)),
+        ]);
+        let error = find_extern_by_path("bark", &externs).unwrap_err();
+        assert!(error.to_string().contains("so it needs more of its module path"), "{error}");
+    }
+}
@@ -0,0 +1,176 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[derive(PostgresDomain)]` related entities for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use crate::mapping::RustSqlMapping;
+use crate::metadata::{ArgumentError, Returns, SqlMapping};
+use crate::pgx_sql::{find_extern_by_path, PgxSql};
+use crate::positioning_ref::PositioningRef;
+use crate::to_sql::entity::ToSqlConfigEntity;
+use crate::to_sql::ToSql;
+use crate::{PgExternEntity, PgExternReturnEntity, SqlGraphEntity, SqlGraphIdentifier};
+
+use eyre::eyre;
+use petgraph::graph::NodeIndex;
+use std::any::TypeId;
+use std::collections::{BTreeSet, HashMap};
+
+/// The output of a [`PostgresDomain`](crate::postgres_domain::PostgresDomain) from `quote::ToTokens::to_tokens`.
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub struct PostgresDomainEntity {
+    pub name: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub full_path: &'static str,
+    pub module_path: &'static str,
+    pub mappings: BTreeSet<RustSqlMapping>,
+    pub base_ty_id: TypeId,
+    pub base_full_path: &'static str,
+    pub base_sql: Result<SqlMapping, ArgumentError>,
+    pub default: Option<&'static str>,
+    pub not_null: bool,
+    pub check: Option<PositioningRef>,
+    pub to_sql_config: ToSqlConfigEntity,
+}
+
+impl PostgresDomainEntity {
+    pub fn id_matches(&self, candidate: &core::any::TypeId) -> bool {
+        self.mappings.iter().any(|tester| *candidate == tester.id)
+    }
+
+    /// Find the `#[pg_extern]` function used as this domain's `CHECK`, and ensure it accepts one
+    /// argument and returns `bool`
+    pub fn resolve_check<'a>(
+        &self,
+        externs: &'a HashMap<PgExternEntity, NodeIndex>,
+    ) -> eyre::Result<Option<(&'a PgExternEntity, NodeIndex)>> {
+        let path = match &self.check {
+            Some(PositioningRef::FullPath(path)) => path,
+            Some(PositioningRef::Name(_)) | None => return Ok(None),
+        };
+        let (function, index) = find_extern_by_path(path, externs)?.ok_or_else(|| {
+            eyre!(
+                "Could not find the `check` function of domain `{}` ({}:{}): {}",
+                self.name,
+                self.file,
+                self.line,
+                path,
+            )
+        })?;
+
+        let returns_bool = match &function.fn_return {
+            PgExternReturnEntity::Type { ty } => {
+                ty.metadata.return_sql == Ok(Returns::One(SqlMapping::literal("bool")))
+            }
+            _ => false,
+        };
+        if !returns_bool || function.fn_args.len() != 1 {
+            return Err(eyre!(
+                "The `check` function of domain `{}` ({}:{}) must take one argument and return `bool`: {}",
+                self.name,
+                self.file,
+                self.line,
+                function.full_path,
+            ));
+        }
+        Ok(Some((function, index)))
+    }
+}
+
+impl From<PostgresDomainEntity> for SqlGraphEntity {
+    fn from(val: PostgresDomainEntity) -> Self {
+        SqlGraphEntity::Domain(val)
+    }
+}
+
+impl SqlGraphIdentifier for PostgresDomainEntity {
+    fn dot_identifier(&self) -> String {
+        format!("domain {}", self.full_path)
+    }
+    fn rust_identifier(&self) -> String {
+        self.full_path.to_string()
+    }
+
+    fn file(&self) -> Option<&'static str> {
+        Some(self.file)
+    }
+
+    fn line(&self) -> Option<u32> {
+        Some(self.line)
+    }
+}
+
+impl ToSql for PostgresDomainEntity {
+    #[tracing::instrument(level = "debug", err, skip(self, context), fields(identifier = %self.rust_identifier()))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        let self_index = context.domains[self];
+
+        let base_sql = match &self.base_sql {
+            Ok(SqlMapping::As(sql)) => sql,
+            _ => {
+                return Err(eyre!(
+                    "`{}` can't be used as the base type of domain `{}`",
+                    self.base_full_path,
+                    self.name
+                ))
+            }
+        };
+        // the base type may be one of ours, in which case it needs its schema
+        let base_schema = context
+            .graph
+            .neighbors_undirected(self_index)
+            .find(|neighbor| match &context.graph[*neighbor] {
+                SqlGraphEntity::Type(ty) => ty.id_matches(&self.base_ty_id),
+                SqlGraphEntity::Enum(en) => en.id_matches(&self.base_ty_id),
+                _ => false,
+            })
+            .map(|index| context.schema_prefix_for(&index))
+            .unwrap_or_default();
+
+        let mut constraints = vec![];
+        if let Some(default) = self.default {
+            constraints.push(format!("DEFAULT {}", default));
+        }
+        if self.not_null {
+            constraints.push("NOT NULL".to_string());
+        }
+        if let Some((function, index)) = self.resolve_check(&context.externs)? {
+            let schema = function
+                .schema
+                .map(|schema| format!("{}.", schema))
+                .unwrap_or_else(|| context.schema_prefix_for(&index));
+            constraints.push(format!("CHECK ({schema}\"{name}\"(VALUE))", name = function.name));
+        }
+
+        let sql = format!(
+            "\n\
+                -- {file}:{line}\n\
+                -- {full_path}\n\
+                CREATE DOMAIN {schema}{name} AS {base_schema}{base_sql}{constraints};\
+            ",
+            schema = context.schema_prefix_for(&self_index),
+            full_path = self.full_path,
+            file = self.file,
+            line = self.line,
+            name = self.name,
+            constraints = constraints
+                .iter()
+                .map(|constraint| format!("\n\t{}", constraint))
+                .collect::<String>(),
+        );
+        tracing::trace!(%sql);
+        Ok(sql)
+    }
+}
@@ -0,0 +1,243 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`#[derive(PostgresDomain)]` related macro expansion for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+pub mod entity;
+
+use crate::enrich::{ToEntityGraphTokens, ToRustCodeTokens};
+use crate::positioning_ref::PositioningRef;
+use crate::{CodeEnrichment, ToSqlConfig};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{DeriveInput, Ident, ItemStruct, LitStr, Token};
+
+/// A parsed `#[derive(PostgresDomain)]` item.
+///
+/// It should be used with [`syn::parse::Parse`] functions.
+///
+/// Using [`quote::ToTokens`] will output the declaration for a `pgx::datum::pgx_sql_entity_graph::PostgresDomainEntity`.
+///
+/// ```rust
+/// use syn::{Macro, parse::Parse, parse_quote, parse};
+/// use quote::{quote, ToTokens};
+/// use pgx_sql_entity_graph::PostgresDomain;
+///
+/// # fn main() -> eyre::Result<()> {
+/// use pgx_sql_entity_graph::CodeEnrichment;
+/// let parsed: CodeEnrichment<PostgresDomain> = parse_quote! {
+///     #[derive(PostgresDomain)]
+///     #[pg_domain(check = is_email, not_null)]
+///     struct Email(String);
+/// };
+/// let sql_graph_entity_tokens = parsed.to_token_stream();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PostgresDomain {
+    name: Ident,
+    base: syn::Type,
+    default: Option<LitStr>,
+    not_null: bool,
+    check: Option<PositioningRef>,
+    to_sql_config: ToSqlConfig,
+}
+
+impl PostgresDomain {
+    pub fn new(
+        name: Ident,
+        generics: &syn::Generics,
+        fields: &syn::Fields,
+        attrs: &[syn::Attribute],
+    ) -> Result<CodeEnrichment<Self>, syn::Error> {
+        let to_sql_config = ToSqlConfig::from_attributes(attrs)?.unwrap_or_default();
+        if !to_sql_config.overrides_default() {
+            crate::ident_is_acceptable_to_postgres(&name)?;
+        }
+        if !generics.params.is_empty() {
+            return Err(syn::Error::new(
+                name.span(),
+                "#[derive(PostgresDomain)] can't be applied to generic types",
+            ));
+        }
+        let base = match fields {
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                fields.unnamed[0].ty.clone()
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "#[derive(PostgresDomain)] can only be applied to structs with exactly one unnamed field, like `struct Email(String)`",
+                ))
+            }
+        };
+
+        let mut default = None;
+        let mut not_null = false;
+        let mut check = None;
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("pg_domain")) {
+            let parsed = attr.parse_args_with(
+                Punctuated::<PostgresDomainAttribute, Token![,]>::parse_terminated,
+            )?;
+            for arg in parsed {
+                match arg {
+                    PostgresDomainAttribute::Default(value) => default = Some(value),
+                    PostgresDomainAttribute::NotNull => not_null = true,
+                    PostgresDomainAttribute::Check(value) => check = Some(value),
+                }
+            }
+        }
+
+        Ok(CodeEnrichment(Self { name, base, default, not_null, check, to_sql_config }))
+    }
+
+    pub fn from_derive_input(
+        derive_input: DeriveInput,
+    ) -> Result<CodeEnrichment<Self>, syn::Error> {
+        let data_struct = match derive_input.data {
+            syn::Data::Struct(data_struct) => data_struct,
+            syn::Data::Union(_) | syn::Data::Enum(_) => {
+                return Err(syn::Error::new(derive_input.ident.span(), "expected struct"))
+            }
+        };
+        Self::new(
+            derive_input.ident,
+            &derive_input.generics,
+            &data_struct.fields,
+            derive_input.attrs.as_slice(),
+        )
+    }
+}
+
+impl ToEntityGraphTokens for PostgresDomain {
+    fn to_entity_graph_tokens(&self) -> TokenStream2 {
+        let name = &self.name;
+        let base = &self.base;
+        let default = match &self.default {
+            Some(default) => quote! { Some(#default) },
+            None => quote! { None },
+        };
+        let not_null = self.not_null;
+        let check = match &self.check {
+            // a bare function name is resolved relative to the module the domain is declared in
+            Some(PositioningRef::FullPath(path)) if !path.contains("::") => quote! {
+                Some(::pgx::pgx_sql_entity_graph::PositioningRef::FullPath(
+                    String::from(concat!(module_path!(), "::", #path))
+                ))
+            },
+            Some(check) => quote! { Some(#check) },
+            None => quote! { None },
+        };
+        let sql_graph_entity_fn_name =
+            syn::Ident::new(&format!("__pgx_internals_domain_{}", name), Span::call_site());
+
+        let to_sql_config = &self.to_sql_config;
+
+        quote! {
+            unsafe impl ::pgx::pgx_sql_entity_graph::metadata::SqlTranslatable for #name {
+                fn argument_sql() -> core::result::Result<::pgx::pgx_sql_entity_graph::metadata::SqlMapping, ::pgx::pgx_sql_entity_graph::metadata::ArgumentError> {
+                    Ok(::pgx::pgx_sql_entity_graph::metadata::SqlMapping::As(String::from(stringify!(#name))))
+                }
+
+                fn return_sql() -> core::result::Result<::pgx::pgx_sql_entity_graph::metadata::Returns, ::pgx::pgx_sql_entity_graph::metadata::ReturnsError> {
+                    Ok(::pgx::pgx_sql_entity_graph::metadata::Returns::One(::pgx::pgx_sql_entity_graph::metadata::SqlMapping::As(String::from(stringify!(#name)))))
+                }
+            }
+
+            #[no_mangle]
+            #[doc(hidden)]
+            pub extern "Rust" fn  #sql_graph_entity_fn_name() -> ::pgx::pgx_sql_entity_graph::SqlGraphEntity {
+                extern crate alloc;
+                use alloc::vec::Vec;
+                use alloc::vec;
+                use ::pgx::datum::WithTypeIds;
+
+                let mut mappings = Default::default();
+                <#name as ::pgx::datum::WithTypeIds>::register_with_refs(&mut mappings, stringify!(#name).to_string());
+                ::pgx::datum::WithSizedTypeIds::<#name>::register_sized_with_refs(&mut mappings, stringify!(#name).to_string());
+                ::pgx::datum::WithArrayTypeIds::<#name>::register_array_with_refs(&mut mappings, stringify!(#name).to_string());
+                ::pgx::datum::WithVarlenaTypeIds::<#name>::register_varlena_with_refs(&mut mappings, stringify!(#name).to_string());
+
+                let submission = ::pgx::pgx_sql_entity_graph::PostgresDomainEntity {
+                    name: stringify!(#name),
+                    file: file!(),
+                    line: line!(),
+                    module_path: module_path!(),
+                    full_path: core::any::type_name::<#name>(),
+                    mappings: mappings.into_iter().collect(),
+                    base_ty_id: core::any::TypeId::of::<#base>(),
+                    base_full_path: core::any::type_name::<#base>(),
+                    base_sql: <#base as ::pgx::pgx_sql_entity_graph::metadata::SqlTranslatable>::argument_sql(),
+                    default: #default,
+                    not_null: #not_null,
+                    check: #check,
+                    to_sql_config: #to_sql_config,
+                };
+                ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Domain(submission)
+            }
+        }
+    }
+}
+
+impl ToRustCodeTokens for PostgresDomain {}
+
+impl Parse for CodeEnrichment<PostgresDomain> {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let parsed: ItemStruct = input.parse()?;
+        PostgresDomain::new(parsed.ident, &parsed.generics, &parsed.fields, &parsed.attrs)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PostgresDomainAttribute {
+    Default(LitStr),
+    NotNull,
+    Check(PositioningRef),
+}
+
+impl Parse for PostgresDomainAttribute {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let ident: Ident = input.parse()?;
+        if ident == "not_null" {
+            return Ok(Self::NotNull);
+        }
+
+        let _eq: Token![=] = input.parse()?;
+        let found = match ident.to_string().as_str() {
+            "default" => Self::Default(input.parse()?),
+            "check" => {
+                let path: syn::Path = input.parse()?;
+                let mut segments = path
+                    .segments
+                    .iter()
+                    .map(|segment| segment.ident.to_string())
+                    .collect::<Vec<_>>();
+                if matches!(segments.first().map(String::as_str), Some("crate") | Some("self")) {
+                    segments.remove(0);
+                }
+                Self::Check(PositioningRef::FullPath(segments.join("::")))
+            }
+            other => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    &format!("Unknown pg_domain attribute: {}", other),
+                ))
+            }
+        };
+        Ok(found)
+    }
+}
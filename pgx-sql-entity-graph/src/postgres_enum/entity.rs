@@ -18,7 +18,7 @@ use crate::mapping::RustSqlMapping;
 use crate::pgx_sql::PgxSql;
 use crate::to_sql::entity::ToSqlConfigEntity;
 use crate::to_sql::ToSql;
-use crate::{SqlGraphEntity, SqlGraphIdentifier};
+use crate::{Privileges, SqlGraphEntity, SqlGraphIdentifier};
 use std::collections::BTreeSet;
 
 /// The output of a [`PostgresEnum`](crate::postgres_enum::PostgresEnum) from `quote::ToTokens::to_tokens`.
@@ -32,6 +32,8 @@ pub struct PostgresEnumEntity {
     pub mappings: BTreeSet<RustSqlMapping>,
     pub variants: Vec<&'static str>,
     pub to_sql_config: ToSqlConfigEntity,
+    /// From the `owner` and `grant` options of `#[pgx(..)]`
+    pub privileges: Privileges,
 }
 
 impl PostgresEnumEntity {
@@ -74,6 +76,7 @@ impl ToSql for PostgresEnumEntity {
                 CREATE TYPE {schema}{name} AS ENUM (\n\
                     {variants}\
                 );\
+                {privileges}\
             ",
             schema = context.schema_prefix_for(&self_index),
             full_path = self.full_path,
@@ -87,6 +90,11 @@ impl ToSql for PostgresEnumEntity {
                 .collect::<Vec<_>>()
                 .join(",\n")
                 + "\n",
+            privileges = self.privileges.or(&context.control.privileges).to_sql(
+                "TYPE",
+                "USAGE",
+                &format!("{}{}", context.schema_prefix_for(&self_index), self.name)
+            ),
         );
         tracing::trace!(%sql);
         Ok(sql)
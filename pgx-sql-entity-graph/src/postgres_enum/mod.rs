@@ -17,7 +17,7 @@ to the `pgx` framework and very subject to change between versions. While you ma
 pub mod entity;
 
 use crate::enrich::{ToEntityGraphTokens, ToRustCodeTokens};
-use crate::{CodeEnrichment, ToSqlConfig};
+use crate::{CodeEnrichment, Privileges, ToSqlConfig};
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
 use syn::parse::{Parse, ParseStream};
@@ -53,6 +53,7 @@ pub struct PostgresEnum {
     generics: Generics,
     variants: Punctuated<syn::Variant, Token![,]>,
     to_sql_config: ToSqlConfig,
+    privileges: Privileges,
 }
 
 impl PostgresEnum {
@@ -61,12 +62,13 @@ impl PostgresEnum {
         generics: Generics,
         variants: Punctuated<syn::Variant, Token![,]>,
         to_sql_config: ToSqlConfig,
+        privileges: Privileges,
     ) -> Result<CodeEnrichment<Self>, syn::Error> {
         if !to_sql_config.overrides_default() {
             crate::ident_is_acceptable_to_postgres(&name)?;
         }
 
-        Ok(CodeEnrichment(Self { name, generics, variants, to_sql_config }))
+        Ok(CodeEnrichment(Self { name, generics, variants, to_sql_config, privileges }))
     }
 
     pub fn from_derive_input(
@@ -74,13 +76,20 @@ impl PostgresEnum {
     ) -> Result<CodeEnrichment<Self>, syn::Error> {
         let to_sql_config =
             ToSqlConfig::from_attributes(derive_input.attrs.as_slice())?.unwrap_or_default();
+        let privileges = Privileges::from_attributes(derive_input.attrs.as_slice())?;
         let data_enum = match derive_input.data {
             syn::Data::Enum(data_enum) => data_enum,
             syn::Data::Union(_) | syn::Data::Struct(_) => {
                 return Err(syn::Error::new(derive_input.ident.span(), "expected enum"))
             }
         };
-        Self::new(derive_input.ident, derive_input.generics, data_enum.variants, to_sql_config)
+        Self::new(
+            derive_input.ident,
+            derive_input.generics,
+            data_enum.variants,
+            to_sql_config,
+            privileges,
+        )
     }
 }
 
@@ -125,6 +134,7 @@ impl ToEntityGraphTokens for PostgresEnum {
             syn::Ident::new(&format!("__pgx_internals_enum_{}", name), Span::call_site());
 
         let to_sql_config = &self.to_sql_config;
+        let privileges = &self.privileges;
 
         quote! {
             unsafe impl #staticless_impl_generics ::pgx::pgx_sql_entity_graph::metadata::SqlTranslatable for #name #static_ty_generics #static_where_clauses {
@@ -160,6 +170,7 @@ impl ToEntityGraphTokens for PostgresEnum {
                     mappings: mappings.into_iter().collect(),
                     variants: vec![ #(  stringify!(#variants)  ),* ],
                     to_sql_config: #to_sql_config,
+                    privileges: #privileges,
                 };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Enum(submission)
             }
@@ -174,6 +185,7 @@ impl Parse for CodeEnrichment<PostgresEnum> {
         let parsed: ItemEnum = input.parse()?;
         let to_sql_config =
             ToSqlConfig::from_attributes(parsed.attrs.as_slice())?.unwrap_or_default();
-        PostgresEnum::new(parsed.ident, parsed.generics, parsed.variants, to_sql_config)
+        let privileges = Privileges::from_attributes(parsed.attrs.as_slice())?;
+        PostgresEnum::new(parsed.ident, parsed.generics, parsed.variants, to_sql_config, privileges)
     }
 }
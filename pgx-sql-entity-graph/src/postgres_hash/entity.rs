@@ -61,16 +61,24 @@ impl SqlGraphIdentifier for PostgresHashEntity {
 }
 
 impl ToSql for PostgresHashEntity {
-    #[tracing::instrument(level = "debug", err, skip(self, _context), fields(identifier = %self.rust_identifier()))]
-    fn to_sql(&self, _context: &PgxSql) -> eyre::Result<String> {
+    #[tracing::instrument(level = "debug", err, skip(self, context), fields(identifier = %self.rust_identifier()))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        // the type, its operator and its hash function are all made in the same module, which is
+        // only named when the `search_path` can't be relied on to find it
+        let schema = if context.control.safe_search_path {
+            context.schema_prefix_for(&context.hashes[self])
+        } else {
+            String::new()
+        };
         let sql = format!("\n\
                             -- {file}:{line}\n\
                             -- {full_path}\n\
-                            CREATE OPERATOR FAMILY {name}_hash_ops USING hash;\n\
-                            CREATE OPERATOR CLASS {name}_hash_ops DEFAULT FOR TYPE {name} USING hash FAMILY {name}_hash_ops AS\n\
-                                \tOPERATOR    1   =  ({name}, {name}),\n\
-                                \tFUNCTION    1   {fn_name}({name});\
+                            CREATE OPERATOR FAMILY {schema}{name}_hash_ops USING hash;\n\
+                            CREATE OPERATOR CLASS {schema}{name}_hash_ops DEFAULT FOR TYPE {schema}{name} USING hash FAMILY {schema}{name}_hash_ops AS\n\
+                                \tOPERATOR    1   {schema}=  ({schema}{name}, {schema}{name}),\n\
+                                \tFUNCTION    1   {schema}{fn_name}({schema}{name});\
                             ",
+                          schema = schema,
                           name = self.name,
                           full_path = self.full_path,
                           file = self.file,
@@ -81,20 +81,28 @@ impl SqlGraphIdentifier for PostgresOrdEntity {
 }
 
 impl ToSql for PostgresOrdEntity {
-    #[tracing::instrument(level = "debug", err, skip(self, _context), fields(identifier = %self.rust_identifier()))]
-    fn to_sql(&self, _context: &PgxSql) -> eyre::Result<String> {
+    #[tracing::instrument(level = "debug", err, skip(self, context), fields(identifier = %self.rust_identifier()))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
+        // the type, its operators and its comparison function are all made in the same module, which is
+        // only named when the `search_path` can't be relied on to find it
+        let schema = if context.control.safe_search_path {
+            context.schema_prefix_for(&context.ords[self])
+        } else {
+            String::new()
+        };
         let sql = format!("\n\
                             -- {file}:{line}\n\
                             -- {full_path}\n\
-                            CREATE OPERATOR FAMILY {name}_btree_ops USING btree;\n\
-                            CREATE OPERATOR CLASS {name}_btree_ops DEFAULT FOR TYPE {name} USING btree FAMILY {name}_btree_ops AS\n\
-                                  \tOPERATOR 1 <,\n\
-                                  \tOPERATOR 2 <=,\n\
-                                  \tOPERATOR 3 =,\n\
-                                  \tOPERATOR 4 >=,\n\
-                                  \tOPERATOR 5 >,\n\
-                                  \tFUNCTION 1 {cmp_fn_name}({name}, {name});\
+                            CREATE OPERATOR FAMILY {schema}{name}_btree_ops USING btree;\n\
+                            CREATE OPERATOR CLASS {schema}{name}_btree_ops DEFAULT FOR TYPE {schema}{name} USING btree FAMILY {schema}{name}_btree_ops AS\n\
+                                  \tOPERATOR 1 {schema}<,\n\
+                                  \tOPERATOR 2 {schema}<=,\n\
+                                  \tOPERATOR 3 {schema}=,\n\
+                                  \tOPERATOR 4 {schema}>=,\n\
+                                  \tOPERATOR 5 {schema}>,\n\
+                                  \tFUNCTION 1 {schema}{cmp_fn_name}({schema}{name}, {schema}{name});\
                             ",
+                          schema = schema,
                           name = self.name,
                           full_path = self.full_path,
                           file = self.file,
@@ -18,7 +18,7 @@ use crate::mapping::RustSqlMapping;
 use crate::pgx_sql::PgxSql;
 use crate::to_sql::entity::ToSqlConfigEntity;
 use crate::to_sql::ToSql;
-use crate::{SqlGraphEntity, SqlGraphIdentifier};
+use crate::{Privileges, SqlGraphEntity, SqlGraphIdentifier};
 use std::collections::BTreeSet;
 
 use eyre::eyre;
@@ -35,13 +35,36 @@ pub struct PostgresTypeEntity {
     pub in_fn_module_path: String,
     pub out_fn: &'static str,
     pub out_fn_module_path: String,
+    /// The binary send function, which is in the type's module, if it has one
+    pub send_fn: Option<&'static str>,
+    /// The binary receive function, which is in the type's module, if it has one
+    pub recv_fn: Option<&'static str>,
     pub to_sql_config: ToSqlConfigEntity,
+    /// From the `owner` and `grant` options of `#[pgx(..)]`
+    pub privileges: Privileges,
+    /// The `STORAGE` of `CREATE TYPE`, from the `storage` option of `#[pgx(..)]`
+    pub storage: Option<&'static str>,
+    /// The `ALIGNMENT` of `CREATE TYPE`, from the `alignment` option of `#[pgx(..)]`
+    pub alignment: Option<&'static str>,
 }
 
 impl PostgresTypeEntity {
     pub fn id_matches(&self, candidate: &core::any::TypeId) -> bool {
         self.mappings.iter().any(|tester| *candidate == tester.id)
     }
+
+    /// Is the function at `full_path` one of the type's support functions, whose SQL is generated
+    /// along with the type's?
+    pub fn is_support_fn(&self, full_path: &str) -> bool {
+        let is_in_fn =
+            full_path.starts_with(&self.in_fn_module_path) && full_path.ends_with(self.in_fn);
+        let is_out_fn =
+            full_path.starts_with(&self.out_fn_module_path) && full_path.ends_with(self.out_fn);
+        let is_local_fn = |name: Option<&str>| {
+            name.map_or(false, |name| full_path == format!("{}::{}", self.module_path, name))
+        };
+        is_in_fn || is_out_fn || is_local_fn(self.send_fn) || is_local_fn(self.recv_fn)
+    }
 }
 
 impl From<PostgresTypeEntity> for SqlGraphEntity {
@@ -81,6 +104,7 @@ impl ToSql for PostgresTypeEntity {
         // - CREATE TYPE;
         // - CREATE FUNCTION _in;
         // - CREATE FUNCTION _out;
+        // - CREATE FUNCTION _recv and _send, if it has them;
         // - CREATE TYPE (...);
 
         let in_fn_module_path = if !item.in_fn_module_path.is_empty() {
@@ -143,6 +167,37 @@ impl ToSql for PostgresTypeEntity {
         let out_fn_sql = out_fn.to_sql(context)?;
         tracing::trace!(%out_fn_sql);
 
+        // the `_send`/`_recv` functions `#[derive(PostgresType)]` makes are next to the type
+        let mut send_recv_fn_sql = String::new();
+        let mut send_recv = String::new();
+        for (name, option) in [(item.recv_fn, "RECEIVE"), (item.send_fn, "SEND")] {
+            let name = match name {
+                Some(name) => name,
+                None => continue,
+            };
+            let fn_path = format!("{}::{}", item.module_path, name);
+            let (fn_graph_index, func) = context
+                .graph
+                .neighbors_undirected(self_index)
+                .find_map(|neighbor| match &context.graph[neighbor] {
+                    SqlGraphEntity::Function(func) if func.full_path == fn_path => {
+                        Some((neighbor, func))
+                    }
+                    _ => None,
+                })
+                .ok_or_else(|| eyre!("Could not find `{}` graph entity.", fn_path))?;
+            tracing::trace!(?fn_path, "Found matching `{}` function", option);
+            send_recv_fn_sql.push('\n');
+            send_recv_fn_sql.push_str(&func.to_sql(context)?);
+            send_recv.push_str(&format!(
+                "\t{option} = {schema_prefix}{name}, /* {fn_path} */\n",
+                option = option,
+                schema_prefix = context.schema_prefix_for(&fn_graph_index),
+                name = name,
+                fn_path = fn_path,
+            ));
+        }
+
         let shell_type = format!(
             "\n\
                 -- {file}:{line}\n\
@@ -165,7 +220,9 @@ impl ToSql for PostgresTypeEntity {
                     \tINTERNALLENGTH = variable,\n\
                     \tINPUT = {schema_prefix_in_fn}{in_fn}, /* {in_fn_path} */\n\
                     \tOUTPUT = {schema_prefix_out_fn}{out_fn}, /* {out_fn_path} */\n\
-                    \tSTORAGE = extended\n\
+                    {send_recv}\
+                    {alignment}\
+                    \tSTORAGE = {storage}\n\
                 );\
             ",
             full_path = item.full_path,
@@ -179,9 +236,29 @@ impl ToSql for PostgresTypeEntity {
             schema_prefix_out_fn = context.schema_prefix_for(&out_fn_graph_index),
             out_fn = item.out_fn,
             out_fn_path = out_fn_path,
+            send_recv = send_recv,
+            alignment = item
+                .alignment
+                .map(|alignment| format!("\tALIGNMENT = {},\n", alignment))
+                .unwrap_or_default(),
+            storage = item.storage.unwrap_or("extended"),
         };
         tracing::trace!(sql = %materialized_type);
 
-        Ok(shell_type + "\n" + &in_fn_sql + "\n" + &out_fn_sql + "\n" + &materialized_type)
+        let privileges = item.privileges.or(&context.control.privileges).to_sql(
+            "TYPE",
+            "USAGE",
+            &format!("{}{}", context.schema_prefix_for(&self_index), item.name),
+        );
+
+        Ok(shell_type
+            + "\n"
+            + &in_fn_sql
+            + "\n"
+            + &out_fn_sql
+            + &send_recv_fn_sql
+            + "\n"
+            + &materialized_type
+            + &privileges)
     }
 }
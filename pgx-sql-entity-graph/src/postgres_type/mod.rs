@@ -20,9 +20,11 @@ use crate::enrich::{ToEntityGraphTokens, ToRustCodeTokens};
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
 use quote::quote;
 use syn::parse::{Parse, ParseStream};
-use syn::{DeriveInput, Generics, ItemStruct};
+use syn::spanned::Spanned;
+use syn::{Attribute, DeriveInput, Generics, ItemStruct, Lit};
 
-use crate::{CodeEnrichment, ToSqlConfig};
+use crate::pgx_attribute::{ArgValue, PgxArg, PgxAttribute};
+use crate::{CodeEnrichment, Privileges, ToSqlConfig};
 
 /// A parsed `#[derive(PostgresType)]` item.
 ///
@@ -53,21 +55,37 @@ pub struct PostgresType {
     generics: Generics,
     in_fn: Ident,
     out_fn: Ident,
+    send_recv_fns: Option<(Ident, Ident)>,
     to_sql_config: ToSqlConfig,
+    privileges: Privileges,
+    storage: TypeStorage,
 }
 
 impl PostgresType {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: Ident,
         generics: Generics,
         in_fn: Ident,
         out_fn: Ident,
+        send_recv_fns: Option<(Ident, Ident)>,
         to_sql_config: ToSqlConfig,
+        privileges: Privileges,
+        storage: TypeStorage,
     ) -> Result<CodeEnrichment<Self>, syn::Error> {
         if !to_sql_config.overrides_default() {
             crate::ident_is_acceptable_to_postgres(&name)?;
         }
-        Ok(CodeEnrichment(Self { generics, name, in_fn, out_fn, to_sql_config }))
+        Ok(CodeEnrichment(Self {
+            generics,
+            name,
+            in_fn,
+            out_fn,
+            send_recv_fns,
+            to_sql_config,
+            privileges,
+            storage,
+        }))
     }
 
     pub fn from_derive_input(
@@ -81,6 +99,8 @@ impl PostgresType {
         };
         let to_sql_config =
             ToSqlConfig::from_attributes(derive_input.attrs.as_slice())?.unwrap_or_default();
+        let privileges = Privileges::from_attributes(derive_input.attrs.as_slice())?;
+        let storage = TypeStorage::from_attributes(derive_input.attrs.as_slice())?;
         let funcname_in = Ident::new(
             &format!("{}_in", derive_input.ident).to_lowercase(),
             derive_input.ident.span(),
@@ -89,16 +109,89 @@ impl PostgresType {
             &format!("{}_out", derive_input.ident).to_lowercase(),
             derive_input.ident.span(),
         );
+        let send_recv_fns = send_recv_fns(&derive_input.ident, &derive_input.attrs);
         Self::new(
             derive_input.ident,
             derive_input.generics,
             funcname_in,
             funcname_out,
+            send_recv_fns,
             to_sql_config,
+            privileges,
+            storage,
         )
     }
 }
 
+const STORAGES: [&str; 4] = ["plain", "external", "extended", "main"];
+const ALIGNMENTS: [&str; 4] = ["char", "int2", "int4", "double"];
+
+/// The `storage` and `alignment` options of `#[pgx(..)]` on a `#[derive(PostgresType)]`, which are
+/// given to its `CREATE TYPE`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeStorage {
+    /// One of `plain`, `external`, `extended`, or `main`, `extended` if not given
+    pub storage: Option<String>,
+    /// One of `char`, `int2`, `int4`, or `double`, left to Postgres if not given
+    pub alignment: Option<String>,
+}
+
+impl TypeStorage {
+    pub fn from_attributes(attrs: &[Attribute]) -> Result<Self, syn::Error> {
+        let mut options = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("pgx")) {
+            for arg in attr.parse_args::<PgxAttribute>()?.args.iter() {
+                let nv = match arg {
+                    PgxArg::NameValue(nv) => nv,
+                    _ => continue,
+                };
+                let (name, option, allowed) = if nv.path.is_ident("storage") {
+                    ("storage", &mut options.storage, &STORAGES)
+                } else if nv.path.is_ident("alignment") {
+                    ("alignment", &mut options.alignment, &ALIGNMENTS)
+                } else {
+                    continue;
+                };
+                let expected = || {
+                    let allowed = allowed.iter().map(|value| format!("\"{}\"", value));
+                    format!("expected one of {}", allowed.collect::<Vec<_>>().join(", "))
+                };
+                let value = match nv.value {
+                    ArgValue::Lit(Lit::Str(ref value)) => {
+                        let lowercase = value.value().to_lowercase();
+                        if !allowed.contains(&lowercase.as_str()) {
+                            return Err(syn::Error::new(value.span(), expected()));
+                        }
+                        lowercase
+                    }
+                    ArgValue::Lit(ref other) => {
+                        return Err(syn::Error::new(other.span(), expected()))
+                    }
+                    ArgValue::Path(ref other) => {
+                        return Err(syn::Error::new(other.span(), expected()))
+                    }
+                };
+                if option.replace(value).is_some() {
+                    let message = format!("`{}` may only be given once", name);
+                    return Err(syn::Error::new(nv.path.span(), message));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The `_send` and `_recv` functions `#[derive(PostgresType)]` makes for a type with the
+/// `#[sendrecvfuncs]` attribute
+fn send_recv_fns(name: &Ident, attrs: &[syn::Attribute]) -> Option<(Ident, Ident)> {
+    if !attrs.iter().any(|attr| attr.path.is_ident("sendrecvfuncs")) {
+        return None;
+    }
+    let funcname_send = Ident::new(&format!("{}_send", name).to_lowercase(), name.span());
+    let funcname_recv = Ident::new(&format!("{}_recv", name).to_lowercase(), name.span());
+    Some((funcname_send, funcname_recv))
+}
+
 impl ToEntityGraphTokens for PostgresType {
     fn to_entity_graph_tokens(&self) -> TokenStream2 {
         let name = &self.name;
@@ -136,11 +229,26 @@ impl ToEntityGraphTokens for PostgresType {
 
         let in_fn = &self.in_fn;
         let out_fn = &self.out_fn;
+        let (send_fn, recv_fn) = match &self.send_recv_fns {
+            Some((send_fn, recv_fn)) => {
+                (quote! { Some(stringify!(#send_fn)) }, quote! { Some(stringify!(#recv_fn)) })
+            }
+            None => (quote! { None }, quote! { None }),
+        };
 
         let sql_graph_entity_fn_name =
             syn::Ident::new(&format!("__pgx_internals_type_{}", self.name), Span::call_site());
 
         let to_sql_config = &self.to_sql_config;
+        let privileges = &self.privileges;
+        let storage = match &self.storage.storage {
+            Some(storage) => quote! { Some(#storage) },
+            None => quote! { None },
+        };
+        let alignment = match &self.storage.alignment {
+            Some(alignment) => quote! { Some(#alignment) },
+            None => quote! { None },
+        };
 
         quote! {
             unsafe impl #staticless_impl_generics ::pgx::pgx_sql_entity_graph::metadata::SqlTranslatable for #name #static_ty_generics #static_where_clauses {
@@ -201,7 +309,12 @@ impl ToEntityGraphTokens for PostgresType {
                         let _ = path_items.pop(); // Drop the one we don't want.
                         path_items.join("::")
                     },
+                    send_fn: #send_fn,
+                    recv_fn: #recv_fn,
                     to_sql_config: #to_sql_config,
+                    privileges: #privileges,
+                    storage: #storage,
+                    alignment: #alignment,
                 };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Type(submission)
             }
@@ -216,10 +329,53 @@ impl Parse for CodeEnrichment<PostgresType> {
         let parsed: ItemStruct = input.parse()?;
         let to_sql_config =
             ToSqlConfig::from_attributes(parsed.attrs.as_slice())?.unwrap_or_default();
+        let privileges = Privileges::from_attributes(parsed.attrs.as_slice())?;
+        let storage = TypeStorage::from_attributes(parsed.attrs.as_slice())?;
         let funcname_in =
             Ident::new(&format!("{}_in", parsed.ident).to_lowercase(), parsed.ident.span());
         let funcname_out =
             Ident::new(&format!("{}_out", parsed.ident).to_lowercase(), parsed.ident.span());
-        PostgresType::new(parsed.ident, parsed.generics, funcname_in, funcname_out, to_sql_config)
+        let send_recv_fns = send_recv_fns(&parsed.ident, &parsed.attrs);
+        PostgresType::new(
+            parsed.ident,
+            parsed.generics,
+            funcname_in,
+            funcname_out,
+            send_recv_fns,
+            to_sql_config,
+            privileges,
+            storage,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypeStorage;
+    use syn::{parse_quote, DeriveInput};
+
+    #[test]
+    fn parses_storage_and_alignment() {
+        let input: DeriveInput = parse_quote! {
+            #[pgx(storage = "EXTERNAL", alignment = "double", owner = "ext_owner")]
+            struct Blob;
+        };
+        assert_eq!(
+            TypeStorage::from_attributes(&input.attrs).unwrap(),
+            TypeStorage { storage: Some("external".into()), alignment: Some("double".into()) }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_storage() {
+        let input: DeriveInput = parse_quote! {
+            #[pgx(storage = "compressed")]
+            struct Blob;
+        };
+        let error = TypeStorage::from_attributes(&input.attrs).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected one of \"plain\", \"external\", \"extended\", \"main\""
+        );
     }
 }
@@ -0,0 +1,208 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+/*!
+
+`owner = ".."` and `grant = ".."` options for Rust to SQL translation
+
+> Like all of the [`sql_entity_graph`][crate::pgx_sql_entity_graph] APIs, this is considered **internal**
+to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
+
+*/
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens, TokenStreamExt};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Attribute, Lit};
+
+use crate::pgx_attribute::{ArgValue, NameValueArg, PgxArg, PgxAttribute};
+
+const INVALID_ROLE: &str = "expected `owner = \"role\"` or `grant = \"role\"`";
+
+/// The role which should own an entity, and the roles it should be usable by, from the
+/// `owner = "role"` and `grant = "role"` options.
+///
+/// `grant` may be given more than once.  When an entity has neither, the defaults given to
+/// `pg_module_magic!()` apply.
+///
+/// Roles are quoted as identifiers, except for `PUBLIC`.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Privileges {
+    pub owner: Option<String>,
+    pub grants: Vec<String>,
+}
+
+impl Privileges {
+    pub fn new(owner: Option<&str>, grants: &[&str]) -> Self {
+        Self {
+            owner: owner.map(String::from),
+            grants: grants.iter().map(|grant| String::from(*grant)).collect(),
+        }
+    }
+
+    /// Parse an `owner` or `grant` option, returning `false` if `arg` is some other option
+    pub fn parse_arg(&mut self, arg: &NameValueArg) -> Result<bool, syn::Error> {
+        let is_owner = arg.path.is_ident("owner");
+        if !is_owner && !arg.path.is_ident("grant") {
+            return Ok(false);
+        }
+        let role = match arg.value {
+            ArgValue::Lit(Lit::Str(ref role)) if !role.value().is_empty() => role.value(),
+            ArgValue::Lit(ref other) => return Err(syn::Error::new(other.span(), INVALID_ROLE)),
+            ArgValue::Path(ref other) => return Err(syn::Error::new(other.span(), INVALID_ROLE)),
+        };
+        if is_owner {
+            if self.owner.is_some() {
+                return Err(syn::Error::new(arg.path.span(), "`owner` may only be given once"));
+            }
+            self.owner = Some(role);
+        } else {
+            self.grants.push(role);
+        }
+        Ok(true)
+    }
+
+    /// Used to parse the `owner` and `grant` options of any `#[pgx(..)]` item attributes
+    pub fn from_attributes(attrs: &[Attribute]) -> Result<Self, syn::Error> {
+        let mut privileges = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("pgx")) {
+            for arg in attr.parse_args::<PgxAttribute>()?.args.iter() {
+                if let PgxArg::NameValue(ref nv) = arg {
+                    privileges.parse_arg(nv)?;
+                }
+            }
+        }
+        Ok(privileges)
+    }
+
+    /// These privileges, with the owner and grants of `defaults` filling in for either which
+    /// wasn't given
+    pub fn or(&self, defaults: &Self) -> Self {
+        Self {
+            owner: self.owner.clone().or_else(|| defaults.owner.clone()),
+            grants: if self.grants.is_empty() {
+                defaults.grants.clone()
+            } else {
+                self.grants.clone()
+            },
+        }
+    }
+
+    /// The `ALTER .. OWNER TO` and `GRANT` statements for `signature`, which is a `kind` of
+    /// object, like `FUNCTION`, whose usage is granted as `privilege`, like `EXECUTE`
+    pub fn to_sql(&self, kind: &str, privilege: &str, signature: &str) -> String {
+        self.owner_sql(kind, signature) + &self.grants_sql(kind, privilege, signature)
+    }
+
+    /// The `ALTER .. OWNER TO` statement for `signature`, which is a `kind` of object
+    pub fn owner_sql(&self, kind: &str, signature: &str) -> String {
+        match &self.owner {
+            Some(owner) => {
+                format!("\nALTER {} {} OWNER TO {};", kind, signature, quote_role(owner))
+            }
+            None => String::new(),
+        }
+    }
+
+    /// The `GRANT` statements for `signature`, which is a `kind` of object.
+    ///
+    /// The grants replace the privileges `PUBLIC` has on the object by default, so it is only
+    /// usable by the roles named.
+    pub fn grants_sql(&self, kind: &str, privilege: &str, signature: &str) -> String {
+        if self.grants.is_empty() {
+            return String::new();
+        }
+        let mut sql = format!("\nREVOKE ALL ON {} {} FROM PUBLIC;", kind, signature);
+        for grant in &self.grants {
+            sql.push_str(&format!(
+                "\nGRANT {} ON {} {} TO {};",
+                privilege,
+                kind,
+                signature,
+                quote_role(grant)
+            ));
+        }
+        sql
+    }
+}
+
+fn quote_role(role: &str) -> String {
+    if role.eq_ignore_ascii_case("public") {
+        String::from("PUBLIC")
+    } else {
+        format!("\"{}\"", role.replace('"', "\"\""))
+    }
+}
+
+/// Parses the options of an attribute which takes nothing but `owner` and `grant`, like
+/// `#[pg_schema(owner = "ext_owner")]`
+impl Parse for Privileges {
+    fn parse(input: ParseStream) -> Result<Self, syn::Error> {
+        let mut privileges = Self::default();
+        for arg in input.parse::<PgxAttribute>()?.args.iter() {
+            let path = match arg {
+                PgxArg::NameValue(ref nv) if privileges.parse_arg(nv)? => continue,
+                PgxArg::NameValue(ref nv) => &nv.path,
+                PgxArg::Path(ref path) => path,
+                PgxArg::List(ref list) => &list.path,
+            };
+            return Err(syn::Error::new(path.span(), INVALID_ROLE));
+        }
+        Ok(privileges)
+    }
+}
+
+impl ToTokens for Privileges {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let owner = match &self.owner {
+            Some(owner) => quote! { Some(#owner) },
+            None => quote! { None },
+        };
+        let grants = &self.grants;
+        tokens.append_all(quote! {
+            ::pgx::pgx_sql_entity_graph::Privileges::new(#owner, &[#(#grants),*])
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Privileges;
+
+    #[test]
+    fn renders_owner_and_grants() {
+        let privileges = Privileges::new(Some("ext_owner"), &["app_rw", "public"]);
+        assert_eq!(
+            privileges.to_sql("FUNCTION", "EXECUTE", "\"f\"(integer)"),
+            "\nALTER FUNCTION \"f\"(integer) OWNER TO \"ext_owner\";\
+             \nREVOKE ALL ON FUNCTION \"f\"(integer) FROM PUBLIC;\
+             \nGRANT EXECUTE ON FUNCTION \"f\"(integer) TO \"app_rw\";\
+             \nGRANT EXECUTE ON FUNCTION \"f\"(integer) TO PUBLIC;"
+        );
+        assert_eq!(Privileges::default().to_sql("TYPE", "USAGE", "t"), "");
+    }
+
+    #[test]
+    fn quotes_roles() {
+        let privileges = Privileges::new(Some("odd\"role"), &[]);
+        assert_eq!(
+            privileges.to_sql("SCHEMA", "USAGE", "s"),
+            "\nALTER SCHEMA s OWNER TO \"odd\"\"role\";"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_defaults() {
+        let defaults = Privileges::new(Some("ext_owner"), &["app_ro"]);
+        assert_eq!(Privileges::default().or(&defaults), defaults);
+        assert_eq!(
+            Privileges::new(None, &["app_rw"]).or(&defaults),
+            Privileges::new(Some("ext_owner"), &["app_rw"])
+        );
+    }
+}
@@ -15,6 +15,7 @@ to the `pgx` framework and very subject to change between versions. While you ma
 
 */
 use crate::pgx_sql::PgxSql;
+use crate::privileges::Privileges;
 use crate::to_sql::ToSql;
 use crate::{SqlGraphEntity, SqlGraphIdentifier};
 
@@ -25,6 +26,7 @@ pub struct SchemaEntity {
     pub name: &'static str,
     pub file: &'static str,
     pub line: u32,
+    pub privileges: Privileges,
 }
 
 impl From<SchemaEntity> for SqlGraphEntity {
@@ -51,17 +53,22 @@ impl SqlGraphIdentifier for SchemaEntity {
 }
 
 impl ToSql for SchemaEntity {
-    #[tracing::instrument(level = "debug", err, skip(self, _context), fields(identifier = %self.rust_identifier()))]
-    fn to_sql(&self, _context: &PgxSql) -> eyre::Result<String> {
+    #[tracing::instrument(level = "debug", err, skip(self, context), fields(identifier = %self.rust_identifier()))]
+    fn to_sql(&self, context: &PgxSql) -> eyre::Result<String> {
         let sql = format!(
             "\n\
                 -- {file}:{line}\n\
                 CREATE SCHEMA IF NOT EXISTS {name}; /* {module_path} */\
+                {privileges}\
             ",
             name = self.name,
             file = self.file,
             line = self.line,
             module_path = self.module_path,
+            privileges = self
+                .privileges
+                .or(&context.control.privileges)
+                .to_sql("SCHEMA", "USAGE", self.name),
         );
         tracing::trace!(%sql);
         Ok(sql)
@@ -21,6 +21,8 @@ use quote::{quote, ToTokens, TokenStreamExt};
 use syn::parse::{Parse, ParseStream};
 use syn::ItemMod;
 
+use crate::privileges::Privileges;
+
 /// A parsed `#[pg_schema] mod example {}` item.
 ///
 /// It should be used with [`syn::parse::Parse`] functions.
@@ -43,6 +45,8 @@ use syn::ItemMod;
 #[derive(Debug, Clone)]
 pub struct Schema {
     pub module: ItemMod,
+    /// From the `owner` and `grant` options of `#[pg_schema(..)]`
+    pub privileges: Privileges,
 }
 
 impl Schema {
@@ -63,6 +67,7 @@ impl Schema {
     #[cfg(not(feature = "no-schema-generation"))]
     fn entity_tokens(&self) -> TokenStream2 {
         let ident = &self.module.ident;
+        let privileges = &self.privileges;
         let postfix = {
             use std::hash::{Hash, Hasher};
 
@@ -92,6 +97,7 @@ impl Schema {
                         name: stringify!(#ident),
                         file: file!(),
                         line: line!(),
+                        privileges: #privileges,
                     };
                 ::pgx::pgx_sql_entity_graph::SqlGraphEntity::Schema(submission)
             }
@@ -127,6 +133,6 @@ impl Parse for Schema {
     fn parse(input: ParseStream) -> Result<Self, syn::Error> {
         let module: ItemMod = input.parse()?;
         crate::ident_is_acceptable_to_postgres(&module.ident)?;
-        Ok(Self { module })
+        Ok(Self { module, privileges: Privileges::default() })
     }
 }
@@ -14,6 +14,7 @@ Use of this source code is governed by the MIT license that can be found in the
 to the `pgx` framework and very subject to change between versions. While you may use this, please do it with caution.
 
 */
+use crate::pg_version::PgVersionRange;
 use crate::pgx_sql::PgxSql;
 use crate::to_sql::ToSqlFn;
 use crate::SqlGraphEntity;
@@ -30,20 +31,35 @@ use crate::SqlGraphEntity;
 ///
 /// When `enabled` is false, no SQL is generated for the item being configured.
 ///
+/// When `pg_version` has a value, the item is left out of the graph entirely, as if it didn't
+/// exist, when generating SQL for a Postgres version outside of that range.
+///
 /// When `callback` has a value, the corresponding `ToSql` implementation should invoke the
-/// callback instead of performing their default behavior.
+/// callback instead of performing their default behavior.  The callback may still produce the
+/// default SQL through [`SqlGraphEntity::to_default_sql`].
+///
+/// ## Stability of callback arguments
+///
+/// Callbacks are handed the [`SqlGraphEntity`] being rendered and the whole [`PgxSql`] context.
+/// The variants of `SqlGraphEntity`, the set of entity types they wrap, and
+/// [`SqlGraphEntity::to_default_sql`] are expected to remain stable within a `pgx` minor release.
+/// The fields of the individual entity types (such as `PgExternEntity`) and the contents of
+/// `PgxSql` are generator internals, and may change in any release.  Callbacks which only read
+/// identifying fields, like an entity's `name` and `schema`, and otherwise build upon the default
+/// SQL are the least likely to need changes when upgrading.
 #[derive(Default, Clone)]
 pub struct ToSqlConfigEntity {
     pub enabled: bool,
     pub callback: Option<ToSqlFn>,
     pub content: Option<&'static str>,
+    pub pg_version: Option<PgVersionRange>,
 }
 impl ToSqlConfigEntity {
     /// Helper used to implement traits (`Eq`, `Ord`, etc) despite `ToSqlFn` not
     /// having an implementation for them.
     #[inline]
-    fn fields(&self) -> (bool, Option<&str>, Option<usize>) {
-        (self.enabled, self.content, self.callback.map(|f| f as usize))
+    fn fields(&self) -> (bool, Option<&str>, Option<usize>, Option<PgVersionRange>) {
+        (self.enabled, self.content, self.callback.map(|f| f as usize), self.pg_version)
     }
     /// Given a SqlGraphEntity, this function converts it to SQL based on the current configuration.
     ///
@@ -74,6 +90,7 @@ impl ToSqlConfigEntity {
             let module_pathname = context.get_module_pathname();
 
             let content = content.replace("@MODULE_PATHNAME@", &module_pathname);
+            let content = replace_function_name(content, entity);
 
             return Some(Ok(format!(
                 "\n\
@@ -94,6 +111,7 @@ impl ToSqlConfigEntity {
                     let module_pathname = &context.get_module_pathname();
 
                     let content = content.replace("@MODULE_PATHNAME@", &module_pathname);
+                    let content = replace_function_name(content, entity);
 
                     Some(Ok(format!(
                         "\n\
@@ -112,6 +130,19 @@ impl ToSqlConfigEntity {
     }
 }
 
+/// Replace `@FUNCTION_NAME@` with the symbol of the function's wrapper, for a function or trigger
+fn replace_function_name(content: String, entity: &SqlGraphEntity) -> String {
+    match entity {
+        SqlGraphEntity::Function(func) => {
+            content.replace("@FUNCTION_NAME@", &func.wrapper_symbol())
+        }
+        SqlGraphEntity::Trigger(trigger) => {
+            content.replace("@FUNCTION_NAME@", &trigger.wrapper_symbol())
+        }
+        _ => content,
+    }
+}
+
 impl std::cmp::PartialOrd for ToSqlConfigEntity {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(&other))
@@ -135,11 +166,12 @@ impl std::hash::Hash for ToSqlConfigEntity {
 }
 impl std::fmt::Debug for ToSqlConfigEntity {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let (enabled, content, callback) = self.fields();
+        let (enabled, content, callback, pg_version) = self.fields();
         f.debug_struct("ToSqlConfigEntity")
             .field("enabled", &enabled)
             .field("callback", &callback)
             .field("content", &content)
+            .field("pg_version", &pg_version)
             .finish()
     }
 }
@@ -23,6 +23,7 @@ use quote::{quote, ToTokens, TokenStreamExt};
 use syn::spanned::Spanned;
 use syn::{AttrStyle, Attribute, Lit};
 
+use crate::pg_version::PgVersionRange;
 use crate::pgx_attribute::{ArgValue, PgxArg, PgxAttribute};
 use crate::pgx_sql::PgxSql;
 use crate::SqlGraphEntity;
@@ -39,48 +40,58 @@ pub trait ToSql {
 /// The signature of a function that can transform a SqlGraphEntity to a SQL string
 ///
 /// This is used to provide a facility for overriding the default SQL generator behavior using
-/// the `#[to_sql(path::to::function)]` attribute in circumstances where the default behavior is
-/// not desirable.
+/// the `#[pgx(sql = path::to::function)]` attribute (or `sql = path::to::function` in
+/// `#[pg_extern]` and `#[pg_trigger]`) in circumstances where the default behavior is not
+/// desirable.
 ///
-/// Implementations can invoke `ToSql::to_sql(entity, context)` on the unwrapped SqlGraphEntity
-/// type should they wish to delegate to the default behavior for any reason.
+/// The function named in the attribute doesn't need to match this signature exactly: it may
+/// return any `Result<String, E>` where `E` converts into the boxed error, which includes
+/// `eyre::Result<String>`.
+///
+/// Implementations can invoke [`SqlGraphEntity::to_default_sql`] should they wish to decorate,
+/// rather than replace, the SQL that would otherwise have been generated.
 pub type ToSqlFn =
     fn(
         &SqlGraphEntity,
         &PgxSql,
     ) -> std::result::Result<String, Box<dyn std::error::Error + Send + Sync + 'static>>;
 
-/// A parsed `sql` option from a `pgx` related procedural macro.
+/// A parsed `sql` option from a `pgx` related procedural macro, along with the `pg_version`
+/// option limiting which Postgres versions the SQL is generated for.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ToSqlConfig {
     pub enabled: bool,
     pub callback: Option<syn::Path>,
     pub content: Option<syn::LitStr>,
+    pub pg_version: Option<PgVersionRange>,
 }
 impl From<bool> for ToSqlConfig {
     fn from(enabled: bool) -> Self {
-        Self { enabled, callback: None, content: None }
+        Self { enabled, callback: None, content: None, pg_version: None }
     }
 }
 impl From<syn::Path> for ToSqlConfig {
     fn from(path: syn::Path) -> Self {
-        Self { enabled: true, callback: Some(path), content: None }
+        Self { enabled: true, callback: Some(path), content: None, pg_version: None }
     }
 }
 impl From<syn::LitStr> for ToSqlConfig {
     fn from(content: syn::LitStr) -> Self {
-        Self { enabled: true, callback: None, content: Some(content) }
+        Self { enabled: true, callback: None, content: Some(content), pg_version: None }
     }
 }
 impl Default for ToSqlConfig {
     fn default() -> Self {
-        Self { enabled: true, callback: None, content: None }
+        Self { enabled: true, callback: None, content: None, pg_version: None }
     }
 }
 
 const INVALID_ATTR_CONTENT: &str =
     "expected `#[pgx(sql = content)]`, where `content` is a boolean, string, or path to a function";
 
+const INVALID_PG_VERSION: &str =
+    "expected `pg_version = \"range\"`, where `range` is like \"14..\", \"..15\", or \"13..=14\"";
+
 impl ToSqlConfig {
     /// Used for general purpose parsing from an attribute
     pub fn from_attribute(attr: &Attribute) -> Result<Option<Self>, syn::Error> {
@@ -92,29 +103,37 @@ impl ToSqlConfig {
         }
 
         let attr = attr.parse_args::<PgxAttribute>()?;
+        let mut found: Option<Self> = None;
         for arg in attr.args.iter() {
             if let PgxArg::NameValue(ref nv) = arg {
+                if nv.path.is_ident("pg_version") {
+                    let range = match nv.value {
+                        ArgValue::Lit(Lit::Str(ref s)) => PgVersionRange::parse(&s.value())
+                            .map_err(|e| syn::Error::new(s.span(), e))?,
+                        ArgValue::Lit(ref other) => {
+                            return Err(syn::Error::new(other.span(), INVALID_PG_VERSION));
+                        }
+                        ArgValue::Path(ref other) => {
+                            return Err(syn::Error::new(other.span(), INVALID_PG_VERSION));
+                        }
+                    };
+                    found.get_or_insert_with(Self::default).pg_version = Some(range);
+                    continue;
+                }
                 if !nv.path.is_ident("sql") {
                     continue;
                 }
 
+                let config = found.get_or_insert_with(Self::default);
                 match nv.value {
                     ArgValue::Path(ref callback_path) => {
-                        return Ok(Some(Self {
-                            enabled: true,
-                            callback: Some(callback_path.clone()),
-                            content: None,
-                        }));
+                        config.callback = Some(callback_path.clone());
                     }
                     ArgValue::Lit(Lit::Bool(ref b)) => {
-                        return Ok(Some(Self { enabled: b.value, callback: None, content: None }));
+                        config.enabled = b.value;
                     }
                     ArgValue::Lit(Lit::Str(ref s)) => {
-                        return Ok(Some(Self {
-                            enabled: true,
-                            callback: None,
-                            content: Some(s.clone()),
-                        }));
+                        config.content = Some(s.clone());
                     }
                     ArgValue::Lit(ref other) => {
                         return Err(syn::Error::new(other.span(), INVALID_ATTR_CONTENT));
@@ -123,7 +142,7 @@ impl ToSqlConfig {
             }
         }
 
-        Ok(None)
+        Ok(found)
     }
 
     /// Used to parse a generator config from a set of item attributes
@@ -145,12 +164,21 @@ impl ToTokens for ToSqlConfig {
         let enabled = self.enabled;
         let callback = &self.callback;
         let content = &self.content;
+        let pg_version = match &self.pg_version {
+            Some(range) => quote! { Some(#range) },
+            None => quote! { None },
+        };
         if let Some(callback_path) = callback {
             tokens.append_all(quote! {
                 ::pgx::pgx_sql_entity_graph::ToSqlConfigEntity {
                     enabled: #enabled,
-                    callback: Some(#callback_path),
+                    callback: Some(|entity: &::pgx::pgx_sql_entity_graph::SqlGraphEntity,
+                                    context: &::pgx::pgx_sql_entity_graph::PgxSql| {
+                        // accept any error type that can be boxed, such as `eyre::Report`
+                        #callback_path(entity, context).map_err(::core::convert::Into::into)
+                    }),
                     content: None,
+                    pg_version: #pg_version,
                 }
             });
             return;
@@ -161,6 +189,7 @@ impl ToTokens for ToSqlConfig {
                     enabled: #enabled,
                     callback: None,
                     content: Some(#sql),
+                    pg_version: #pg_version,
                 }
             });
             return;
@@ -170,6 +199,7 @@ impl ToTokens for ToSqlConfig {
                 enabled: #enabled,
                 callback: None,
                 content: None,
+                pg_version: #pg_version,
             }
         });
     }
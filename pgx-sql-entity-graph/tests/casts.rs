@@ -0,0 +1,172 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for `#[pg_cast]`s, which are created after the function they call and the
+//! types they cast between.
+mod common;
+
+use common::{control, returning, schema, ty, with_args};
+use pgx_sql_entity_graph::{CastContext, PgCastEntity, SqlGraphEntity, UsedTypeEntity};
+
+struct Celsius;
+struct JsonB;
+struct Label;
+
+fn celsius() -> UsedTypeEntity {
+    ty::<Celsius>("Celsius", "celsius")
+}
+
+fn jsonb() -> UsedTypeEntity {
+    ty::<JsonB>("JsonB", "jsonb")
+}
+
+fn function(
+    module_path: &'static str,
+    name: &'static str,
+    args: Vec<UsedTypeEntity>,
+    returns: UsedTypeEntity,
+) -> SqlGraphEntity {
+    let args = args.into_iter().map(|arg| ("value", arg)).collect();
+    SqlGraphEntity::Function(returning(
+        with_args(common::function(module_path, name), args),
+        returns,
+    ))
+}
+
+fn cast(
+    module_path: &'static str,
+    name: &'static str,
+    (source, target): (UsedTypeEntity, UsedTypeEntity),
+    binary: bool,
+    context: CastContext,
+) -> SqlGraphEntity {
+    SqlGraphEntity::Cast(PgCastEntity {
+        name,
+        module_path,
+        full_path: name,
+        file: "lib.rs",
+        line: 2,
+        source,
+        target,
+        function: if binary { None } else { Some(name) },
+        context,
+    })
+}
+
+/// A cast calling the function `name`, which it's declared with
+fn cast_with_function(name: &'static str, context: CastContext) -> Vec<SqlGraphEntity> {
+    vec![
+        function("ext", name, vec![celsius()], jsonb()),
+        cast("ext", name, (celsius(), jsonb()), false, context),
+    ]
+}
+
+fn generate(entities: Vec<SqlGraphEntity>) -> eyre::Result<String> {
+    let mut all = vec![schema("ext::temperature", "temperature")];
+    all.extend(entities);
+    common::generate(control(), all)
+}
+
+#[test]
+fn implicit_casts_are_created_after_their_function() {
+    let sql = generate(cast_with_function("celsius_to_jsonb", CastContext::Implicit)).unwrap();
+    let function_at = sql.find("FUNCTION \"celsius_to_jsonb\"(\n").unwrap();
+    let cast_at = sql
+        .find(
+            "CREATE CAST (celsius AS jsonb)\n\
+            \tWITH FUNCTION \"celsius_to_jsonb\"(celsius)\n\
+            \tAS IMPLICIT;",
+        )
+        .unwrap();
+    assert!(function_at < cast_at, "{sql}");
+}
+
+#[test]
+fn assignment_casts_say_so() {
+    let sql = generate(cast_with_function("celsius_to_jsonb", CastContext::Assignment)).unwrap();
+    assert!(
+        sql.contains("\tWITH FUNCTION \"celsius_to_jsonb\"(celsius)\n\tAS ASSIGNMENT;"),
+        "{sql}"
+    );
+}
+
+#[test]
+fn explicit_casts_have_no_context() {
+    let sql = generate(cast_with_function("celsius_to_jsonb", CastContext::Explicit)).unwrap();
+    assert!(sql.contains("\tWITH FUNCTION \"celsius_to_jsonb\"(celsius);"), "{sql}");
+    assert!(!sql.contains("AS IMPLICIT") && !sql.contains("AS ASSIGNMENT"), "{sql}");
+}
+
+#[test]
+fn binary_casts_need_no_function() {
+    let label = ty::<Label>("Label", "label");
+    let text = ty::<String>("alloc::string::String", "TEXT");
+    let sql =
+        generate(vec![cast("ext", "label_as_text", (label, text), true, CastContext::Assignment)])
+            .unwrap();
+    assert!(
+        sql.contains("CREATE CAST (label AS TEXT)\n\tWITHOUT FUNCTION\n\tAS ASSIGNMENT;"),
+        "{sql}"
+    );
+}
+
+#[test]
+fn functions_in_schemas_are_named_with_their_schema() {
+    let sql = generate(vec![
+        function("ext::temperature", "celsius_to_jsonb", vec![celsius()], jsonb()),
+        cast(
+            "ext::temperature",
+            "celsius_to_jsonb",
+            (celsius(), jsonb()),
+            false,
+            CastContext::Implicit,
+        ),
+    ])
+    .unwrap();
+    assert!(sql.contains("\tWITH FUNCTION temperature.\"celsius_to_jsonb\"(celsius)"), "{sql}");
+}
+
+#[test]
+fn casting_with_a_missing_function_is_an_error() {
+    let error = generate(vec![cast(
+        "ext",
+        "celsius_to_jsonb",
+        (celsius(), jsonb()),
+        false,
+        CastContext::Implicit,
+    )])
+    .unwrap_err();
+    assert!(error.to_string().contains("which isn't a `#[pg_extern]` function"), "{error:?}");
+}
+
+#[test]
+fn casting_with_a_function_of_two_arguments_is_an_error() {
+    let error = generate(vec![
+        function("ext", "celsius_to_jsonb", vec![celsius(), celsius()], jsonb()),
+        cast("ext", "celsius_to_jsonb", (celsius(), jsonb()), false, CastContext::Implicit),
+    ])
+    .unwrap_err();
+    assert!(error.to_string().contains("must take exactly one argument"), "{error:?}");
+}
+
+#[test]
+fn functions_are_found_by_whole_module_names() {
+    let error = generate(vec![
+        function("ext::temperature", "celsius_to_jsonb", vec![celsius()], jsonb()),
+        cast(
+            "ext",
+            "perature::celsius_to_jsonb",
+            (celsius(), jsonb()),
+            false,
+            CastContext::Implicit,
+        ),
+    ])
+    .unwrap_err();
+    assert!(error.to_string().contains("which isn't a `#[pg_extern]` function"), "{error:?}");
+}
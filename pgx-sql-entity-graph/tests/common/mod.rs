@@ -0,0 +1,173 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Builders for the entities the `pgx` macros make, for the tests which generate SQL from them.
+//!
+//! Each builder fills in what most tests don't care about, and tests change the rest with struct
+//! update syntax, e.g. `PgExternEntity { extern_attrs, ..function("ext", "helper") }`.
+#![allow(dead_code)]
+
+use pgx_sql_entity_graph::metadata::{
+    FunctionMetadataEntity, FunctionMetadataTypeEntity, Returns, SqlMapping,
+};
+use pgx_sql_entity_graph::{
+    ControlFile, ExtensionSqlEntity, PgExternArgumentEntity, PgExternEntity, PgExternReturnEntity,
+    PgOperatorEntity, PgxSql, Privileges, SchemaEntity, SqlGraphEntity, ToSqlConfigEntity,
+    UsedTypeEntity,
+};
+use std::any::TypeId;
+
+/// The `.control` file of an extension named `ext`, which isn't relocatable and has no schema
+pub fn control() -> ControlFile {
+    ControlFile {
+        comment: String::from("tests"),
+        default_version: String::from("1.0"),
+        module_pathname: None,
+        relocatable: false,
+        superuser: true,
+        schema: None,
+        privileges: Privileges::default(),
+        internal_functions: false,
+        safe_search_path: false,
+    }
+}
+
+/// The `#[pg_schema]` module `module_path`, named `name`
+pub fn schema(module_path: &'static str, name: &'static str) -> SqlGraphEntity {
+    SqlGraphEntity::Schema(SchemaEntity {
+        module_path,
+        name,
+        file: "lib.rs",
+        line: 1,
+        privileges: Privileges::default(),
+    })
+}
+
+/// The Rust type `T`, spelled `rust`, which is `sql` in SQL
+pub fn ty<T: 'static>(rust: &'static str, sql: &str) -> UsedTypeEntity {
+    UsedTypeEntity {
+        ty_source: rust,
+        ty_id: TypeId::of::<T>(),
+        full_path: rust,
+        module_path: String::new(),
+        composite_type: None,
+        variadic: false,
+        default: None,
+        optional: false,
+        metadata: FunctionMetadataTypeEntity {
+            type_name: rust,
+            argument_sql: Ok(SqlMapping::As(sql.to_string())),
+            return_sql: Ok(Returns::One(SqlMapping::As(sql.to_string()))),
+            variadic: false,
+            optional: false,
+        },
+    }
+}
+
+pub fn int() -> UsedTypeEntity {
+    ty::<i32>("i32", "integer")
+}
+
+pub fn text() -> UsedTypeEntity {
+    ty::<String>("alloc::string::String", "TEXT")
+}
+
+pub fn to_sql_config() -> ToSqlConfigEntity {
+    ToSqlConfigEntity { enabled: true, callback: None, content: None, pg_version: None }
+}
+
+/// The `#[pg_extern]` function `name` in `module_path`, which takes and returns nothing
+pub fn function(module_path: &'static str, name: &'static str) -> PgExternEntity {
+    PgExternEntity {
+        name,
+        unaliased_name: name,
+        module_path,
+        full_path: name,
+        metadata: FunctionMetadataEntity { arguments: vec![], retval: None, path: name },
+        fn_args: vec![],
+        fn_return: PgExternReturnEntity::None,
+        schema: None,
+        file: "lib.rs",
+        line: 1,
+        extern_attrs: vec![],
+        settings: vec![],
+        operator: None,
+        to_sql_config: to_sql_config(),
+        facts: Vec::new(),
+    }
+}
+
+/// `function`, taking the arguments `args`, each named by its pattern
+pub fn with_args(
+    mut function: PgExternEntity,
+    args: Vec<(&'static str, UsedTypeEntity)>,
+) -> PgExternEntity {
+    function.metadata.arguments = args.iter().map(|(_, arg)| arg.metadata.clone()).collect();
+    function.fn_args = args
+        .into_iter()
+        .map(|(pattern, used_ty)| PgExternArgumentEntity { pattern, used_ty })
+        .collect();
+    function
+}
+
+/// `function`, returning a `ty`
+pub fn returning(mut function: PgExternEntity, ty: UsedTypeEntity) -> PgExternEntity {
+    function.metadata.retval = Some(ty.metadata.clone());
+    function.fn_return = PgExternReturnEntity::Type { ty };
+    function
+}
+
+/// The `#[pg_operator]` `opname`, which has no commutator, negator or estimators
+pub fn operator(opname: &'static str) -> PgOperatorEntity {
+    PgOperatorEntity {
+        opname: Some(opname),
+        commutator: None,
+        negator: None,
+        restrict: None,
+        join: None,
+        hashes: false,
+        merges: false,
+    }
+}
+
+/// The `extension_sql!()` block `name` in `module_path`, which requires and declares nothing
+pub fn block(
+    module_path: &'static str,
+    name: &'static str,
+    sql: &'static str,
+) -> ExtensionSqlEntity {
+    ExtensionSqlEntity {
+        module_path,
+        full_path: name,
+        sql,
+        file: "lib.rs",
+        line: 1,
+        name,
+        bootstrap: false,
+        finalize: false,
+        requires: vec![],
+        infer_requires: false,
+        creates: vec![],
+        declares: vec![],
+        config_dump: vec![],
+        pg_version: None,
+    }
+}
+
+/// The graph of the extension `ext`, described by `control`, made of `entities`
+pub fn build(control: ControlFile, entities: Vec<SqlGraphEntity>) -> eyre::Result<PgxSql> {
+    let mut all = vec![SqlGraphEntity::ExtensionRoot(control)];
+    all.extend(entities);
+    PgxSql::build(all.into_iter(), String::from("ext"), false, 15)
+}
+
+/// The SQL of the extension `ext`, described by `control`, made of `entities`
+pub fn generate(control: ControlFile, entities: Vec<SqlGraphEntity>) -> eyre::Result<String> {
+    build(control, entities)?.to_sql()
+}
@@ -0,0 +1,142 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for `composite_type!()`s declared in a schema, which should be qualified by
+//! that schema exactly once wherever they're used.
+mod common;
+
+use common::{block, control, schema, ty, with_args};
+use pgx_sql_entity_graph::metadata::{FunctionMetadataTypeEntity, Returns, SqlMapping};
+use pgx_sql_entity_graph::{
+    ControlFile, ExtensionSqlEntity, PgExternReturnEntity, PgExternReturnEntityIteratedItem,
+    SqlDeclaredEntity, SqlGraphEntity, SqlObject, UsedTypeEntity,
+};
+
+struct Dog;
+
+fn dog(composite_type: &'static str, returns: Returns, array_brackets: bool) -> UsedTypeEntity {
+    let mut dog = ty::<Dog>("pgx::heap_tuple::PgHeapTuple<'static, AllocatedByRust>", "");
+    dog.composite_type = Some(composite_type);
+    dog.metadata.argument_sql = Ok(SqlMapping::Composite { array_brackets });
+    dog.metadata.return_sql = Ok(returns);
+    dog
+}
+
+fn function(
+    module_path: &'static str,
+    name: &'static str,
+    args: Vec<UsedTypeEntity>,
+    retval: Option<FunctionMetadataTypeEntity>,
+    fn_return: PgExternReturnEntity,
+) -> SqlGraphEntity {
+    let args = args.into_iter().map(|arg| ("dog", arg)).collect();
+    let mut function = with_args(common::function(module_path, name), args);
+    function.metadata.retval = retval;
+    function.fn_return = fn_return;
+    SqlGraphEntity::Function(function)
+}
+
+fn generate(functions: Vec<SqlGraphEntity>) -> String {
+    let mut entities = vec![
+        schema("ext::animals", "animals"),
+        SqlGraphEntity::CustomSql(ExtensionSqlEntity {
+            declares: vec![SqlObject::Type(String::from("dog"))],
+            ..block("ext::animals", "dog", "CREATE TYPE animals.dog AS (name TEXT);")
+        }),
+    ];
+    entities.extend(functions);
+    common::generate(ControlFile { schema: Some(String::from("ext")), ..control() }, entities)
+        .unwrap()
+}
+
+#[test]
+fn qualified_composite_types_are_qualified_once() {
+    let arg =
+        dog("animals.dog", Returns::One(SqlMapping::Composite { array_brackets: false }), false);
+    let one = arg.clone();
+    let set_of =
+        dog("animals.dog", Returns::SetOf(SqlMapping::Composite { array_brackets: false }), false);
+    let column =
+        dog("animals.dog", Returns::One(SqlMapping::Composite { array_brackets: true }), true);
+    let table = FunctionMetadataTypeEntity {
+        return_sql: Ok(Returns::Table(vec![SqlMapping::Composite { array_brackets: true }])),
+        ..column.metadata.clone()
+    };
+
+    let sql = generate(vec![
+        function(
+            "ext",
+            "walk",
+            vec![arg],
+            Some(one.metadata.clone()),
+            PgExternReturnEntity::Type { ty: one },
+        ),
+        function(
+            "ext",
+            "pack",
+            vec![],
+            Some(set_of.metadata.clone()),
+            PgExternReturnEntity::SetOf { ty: set_of, optional: false, result: false },
+        ),
+        function(
+            "ext",
+            "litters",
+            vec![],
+            Some(table),
+            PgExternReturnEntity::Iterated {
+                tys: vec![PgExternReturnEntityIteratedItem { ty: column, name: Some("pups") }],
+                optional: false,
+                result: false,
+            },
+        ),
+    ]);
+
+    assert!(sql.contains("\"dog\" animals.dog /*"), "{sql}");
+    assert!(sql.contains("RETURNS animals.dog /*"), "{sql}");
+    assert!(sql.contains("RETURNS SETOF animals.dog /*"), "{sql}");
+    assert!(sql.contains("pups animals.dog[] /*"), "{sql}");
+    assert!(!sql.contains("animals.animals"), "{sql}");
+    assert!(!sql.contains("ext.animals"), "{sql}");
+}
+
+#[test]
+fn bare_composite_types_resolve_to_their_schema() {
+    let in_schema =
+        dog("dog", Returns::One(SqlMapping::Composite { array_brackets: false }), false);
+    let in_root = dog("dog", Returns::One(SqlMapping::Composite { array_brackets: true }), true);
+
+    let sql = generate(vec![
+        function("ext::animals", "bark", vec![in_schema], None, PgExternReturnEntity::None),
+        function("ext", "bark_all", vec![in_root], None, PgExternReturnEntity::None),
+    ]);
+
+    assert!(sql.contains("\"dog\" animals.dog /*"), "{sql}");
+    assert!(sql.contains("\"dog\" animals.dog[] /*"), "{sql}");
+    assert!(!sql.contains("animals.animals"), "{sql}");
+}
+
+#[test]
+fn bare_composite_types_resolve_to_the_schema_that_creates_them() {
+    let puppy = dog("puppy", Returns::One(SqlMapping::Composite { array_brackets: false }), false);
+    let stray = dog("stray", Returns::One(SqlMapping::Composite { array_brackets: false }), false);
+
+    let sql = generate(vec![
+        schema("ext::kennel", "kennel"),
+        SqlGraphEntity::CustomSql(ExtensionSqlEntity {
+            creates: vec![SqlDeclaredEntity::build("Type", "Puppy").unwrap()],
+            ..block("ext::kennel", "puppy", "CREATE TYPE kennel.Puppy AS (name TEXT);")
+        }),
+        function("ext", "adopt", vec![puppy], None, PgExternReturnEntity::None),
+        function("ext", "wander", vec![stray], None, PgExternReturnEntity::None),
+    ]);
+
+    assert!(sql.contains("\"dog\" kennel.puppy /*"), "{sql}");
+    // nothing declares it, so it's left for the `search_path` to find
+    assert!(sql.contains("\"dog\" stray /*"), "{sql}");
+}
@@ -0,0 +1,123 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for `#[pg_gin_opclass]`es, which are created after the operators they name
+//! and the types they index and store.
+mod common;
+
+use common::{control, returning, text, ty, with_args};
+use pgx_sql_entity_graph::{PgExternEntity, PgGinOpclassEntity, SqlGraphEntity, UsedTypeEntity};
+
+struct Document;
+
+fn document() -> UsedTypeEntity {
+    ty::<Document>("Document", "Document")
+}
+
+fn operator(name: &'static str, opname: &'static str, left: UsedTypeEntity) -> SqlGraphEntity {
+    let function =
+        with_args(common::function("ext", name), vec![("value", left), ("value", text())]);
+    SqlGraphEntity::Function(PgExternEntity {
+        operator: Some(common::operator(opname)),
+        ..returning(function, ty::<bool>("bool", "bool"))
+    })
+}
+
+fn opclass(operators: Vec<(u16, &'static str)>, partial_match: bool) -> SqlGraphEntity {
+    SqlGraphEntity::GinOperatorClass(PgGinOpclassEntity {
+        name: "Document",
+        module_path: "ext",
+        full_path: "ext::Document",
+        file: "lib.rs",
+        line: 2,
+        ty: document(),
+        key: text(),
+        operators,
+        partial_match,
+    })
+}
+
+fn generate(entities: Vec<SqlGraphEntity>) -> eyre::Result<String> {
+    common::generate(control(), entities)
+}
+
+#[test]
+fn opclasses_are_created_after_their_operators() {
+    let sql = generate(vec![
+        operator("document_contains", "@>", document()),
+        opclass(vec![(1, "document_contains")], false),
+    ])
+    .unwrap();
+    let operator_at = sql.find("CREATE OPERATOR @> (").unwrap();
+    let opclass_at = sql
+        .find(
+            "CREATE OPERATOR CLASS document_gin_ops DEFAULT FOR TYPE Document USING gin AS\n\
+            \tOPERATOR 1 @>(Document, TEXT),\n\
+            \tFUNCTION 1 \"document_gin_compare\"(TEXT, TEXT),\n\
+            \tFUNCTION 2 \"document_gin_extract_value\"(Document, internal, internal),\n",
+        )
+        .unwrap();
+    assert!(operator_at < opclass_at, "{sql}");
+    assert!(sql.contains("\tSTORAGE TEXT;"), "{sql}");
+    assert!(!sql.contains("FUNCTION 5"), "{sql}");
+}
+
+#[test]
+fn support_functions_call_their_wrappers() {
+    let sql = generate(vec![
+        operator("document_contains", "@>", document()),
+        opclass(vec![(1, "document_contains")], false),
+    ])
+    .unwrap();
+    assert!(
+        sql.contains(
+            "CREATE FUNCTION \"document_gin_consistent\"(internal, smallint, Document, integer, internal, internal, internal, internal)\n\
+            \tRETURNS boolean\n\
+            \tIMMUTABLE STRICT PARALLEL SAFE\n\
+            \tLANGUAGE c\n\
+            \tAS 'MODULE_PATHNAME', 'ext::document_gin_consistent_wrapper';"
+        ),
+        "{sql}"
+    );
+}
+
+#[test]
+fn partial_matches_add_compare_partial() {
+    let sql = generate(vec![
+        operator("document_contains", "@>", document()),
+        opclass(vec![(1, "document_contains")], true),
+    ])
+    .unwrap();
+    assert!(
+        sql.contains(
+            "\tFUNCTION 5 \"document_gin_compare_partial\"(TEXT, TEXT, smallint, internal),"
+        ),
+        "{sql}"
+    );
+}
+
+#[test]
+fn naming_a_missing_operator_is_an_error() {
+    let error = generate(vec![
+        operator("document_contains", "@>", document()),
+        opclass(vec![(1, "document_contains"), (2, "document_overlaps")], false),
+    ])
+    .unwrap_err();
+    assert!(error.to_string().contains("which isn't a `#[pg_operator]` function"), "{error:?}");
+}
+
+#[test]
+fn naming_an_operator_on_another_type_is_an_error() {
+    let error = generate(vec![
+        operator("text_contains", "@>", text()),
+        opclass(vec![(1, "text_contains")], false),
+    ])
+    .unwrap_err();
+    assert!(error.to_string().contains("must take a `Document` on its left"), "{error:?}");
+}
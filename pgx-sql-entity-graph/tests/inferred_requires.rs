@@ -0,0 +1,137 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The requirements `extension_sql!(.., infer_requires)` blocks get from the functions and types
+//! their SQL uses, such as a function called in a column's `DEFAULT`.
+mod common;
+
+use common::control;
+use pgx_sql_entity_graph::{
+    ExtensionSqlEntity, PgExternEntity, PgxSql, SqlGraphEntity, SqlGraphIdentifier, SqlObject,
+};
+
+fn function(name: &'static str, unaliased_name: &'static str) -> SqlGraphEntity {
+    SqlGraphEntity::Function(PgExternEntity { unaliased_name, ..common::function("ext", name) })
+}
+
+fn block(
+    name: &'static str,
+    sql: &'static str,
+    infer_requires: bool,
+    declares: Vec<SqlObject>,
+) -> SqlGraphEntity {
+    SqlGraphEntity::CustomSql(ExtensionSqlEntity {
+        infer_requires,
+        declares,
+        ..common::block("ext", name, sql)
+    })
+}
+
+fn build(entities: Vec<SqlGraphEntity>) -> PgxSql {
+    common::build(control(), entities).unwrap()
+}
+
+/// Does the entity with the Rust identifier `from` come before the block named `to`?
+fn requires(pgx_sql: &PgxSql, from: &str, to: &str) -> bool {
+    let from = pgx_sql
+        .graph
+        .node_indices()
+        .find(|&index| pgx_sql.graph[index].rust_identifier() == from)
+        .unwrap();
+    let to = pgx_sql.extension_sqls.iter().find(|(item, _)| item.name == to).unwrap().1;
+    pgx_sql.graph.find_edge(from, *to).is_some()
+}
+
+#[test]
+fn defaults_and_generated_columns_are_required() {
+    let pgx_sql = build(vec![
+        function("next_id", "next_id"),
+        function("with_tax", "with_tax"),
+        block(
+            "orders",
+            "CREATE TABLE orders (
+                id bigint DEFAULT next_id(),
+                total numeric GENERATED ALWAYS AS (@extschema@.with_tax(subtotal)) STORED
+            );",
+            true,
+            vec![],
+        ),
+    ]);
+    assert!(requires(&pgx_sql, "next_id", "orders"));
+    assert!(requires(&pgx_sql, "with_tax", "orders"));
+
+    let sql = pgx_sql.to_sql().unwrap();
+    let table = sql.find("CREATE TABLE orders").unwrap();
+    assert!(sql.find("FUNCTION \"next_id\"()").unwrap() < table, "{sql}");
+    assert!(sql.find("FUNCTION \"with_tax\"()").unwrap() < table, "{sql}");
+}
+
+#[test]
+fn comments_and_strings_are_not_required() {
+    let pgx_sql = build(vec![
+        function("next_id", "next_id"),
+        block(
+            "orders",
+            "-- next_id() isn't used
+            CREATE TABLE orders (
+                id text DEFAULT 'next_id()' /* next_id() */,
+                body text DEFAULT $$ SELECT next_id() $$
+            );",
+            true,
+            vec![],
+        ),
+    ]);
+    assert!(!requires(&pgx_sql, "next_id", "orders"));
+}
+
+#[test]
+fn only_blocks_asking_are_inferred() {
+    let pgx_sql = build(vec![
+        function("next_id", "next_id"),
+        block("orders", "CREATE TABLE orders (id bigint DEFAULT next_id());", false, vec![]),
+    ]);
+    assert!(!requires(&pgx_sql, "next_id", "orders"));
+}
+
+#[test]
+fn declared_objects_are_required() {
+    let pgx_sql = build(vec![
+        block("dog", "CREATE TYPE dog AS (name text);", false, vec![SqlObject::Type("dog".into())]),
+        block("kennel", "CREATE TABLE kennel (resident Dog);", true, vec![]),
+        // a function's name is only a requirement where it's called
+        block(
+            "walk",
+            "CREATE FUNCTION walk() RETURNS void LANGUAGE sql AS '';",
+            false,
+            vec![SqlObject::Function("walk".into())],
+        ),
+        block("walks", "CREATE TABLE walks (walk text);", true, vec![]),
+    ]);
+    assert!(requires(&pgx_sql, "dog", "kennel"));
+    assert!(!requires(&pgx_sql, "walk", "walks"));
+}
+
+#[test]
+fn names_are_compared_like_postgres() {
+    let pgx_sql = build(vec![
+        function("Next_Id", "next_id_fn"),
+        block("folded", "CREATE TABLE folded (id bigint DEFAULT Next_Id());", true, vec![]),
+        block("quoted", "CREATE TABLE quoted (id bigint DEFAULT \"Next_Id\"());", true, vec![]),
+        block(
+            "other_schema",
+            "CREATE TABLE other (id bigint DEFAULT public.\"Next_Id\"());",
+            true,
+            vec![],
+        ),
+    ]);
+    // `Next_Id` folds to `next_id`, but the function is created as `"Next_Id"`
+    assert!(!requires(&pgx_sql, "Next_Id", "folded"));
+    assert!(requires(&pgx_sql, "Next_Id", "quoted"));
+    assert!(!requires(&pgx_sql, "Next_Id", "other_schema"));
+}
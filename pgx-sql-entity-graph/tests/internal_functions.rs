@@ -0,0 +1,87 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for `#[pg_extern(internal)]` functions, which are kept in the extension's
+//! `@extschema@_internal` schema and can't be executed by `PUBLIC`.
+mod common;
+
+use common::generate;
+use pgx_sql_entity_graph::{ControlFile, ExternArgs, PgExternEntity, SqlGraphEntity};
+
+fn function(name: &'static str, extern_attrs: Vec<ExternArgs>) -> SqlGraphEntity {
+    SqlGraphEntity::Function(PgExternEntity { extern_attrs, ..common::function("ext", name) })
+}
+
+fn control(relocatable: bool, internal_functions: bool) -> ControlFile {
+    ControlFile { relocatable, internal_functions, ..common::control() }
+}
+
+#[test]
+fn internal_functions_are_hidden() {
+    let sql = generate(
+        control(false, false),
+        vec![function("helper", vec![ExternArgs::Internal]), function("api", vec![])],
+    )
+    .unwrap();
+
+    assert!(sql.contains("CREATE SCHEMA IF NOT EXISTS @extschema@_internal;"), "{sql}");
+    assert!(sql.contains("FUNCTION @extschema@_internal.\"helper\"()"), "{sql}");
+    assert!(
+        sql.contains("REVOKE ALL ON FUNCTION @extschema@_internal.\"helper\"() FROM PUBLIC;"),
+        "{sql}"
+    );
+    assert!(sql.contains("FUNCTION \"api\"()"), "{sql}");
+    assert!(!sql.contains("\"api\"() FROM PUBLIC"), "{sql}");
+}
+
+#[test]
+fn public_functions_need_no_internal_schema() {
+    let sql = generate(control(false, false), vec![function("api", vec![])]).unwrap();
+    assert!(!sql.contains("_internal"), "{sql}");
+    assert!(!sql.contains("REVOKE"), "{sql}");
+}
+
+#[test]
+fn functions_can_be_internal_by_default() {
+    let sql = generate(
+        control(false, true),
+        vec![function("helper", vec![]), function("api", vec![ExternArgs::Public])],
+    )
+    .unwrap();
+
+    assert!(
+        sql.contains("REVOKE ALL ON FUNCTION @extschema@_internal.\"helper\"() FROM PUBLIC;"),
+        "{sql}"
+    );
+    assert!(sql.contains("FUNCTION \"api\"()"), "{sql}");
+    assert!(!sql.contains("\"api\"() FROM PUBLIC"), "{sql}");
+}
+
+#[test]
+fn grants_still_apply_to_internal_functions() {
+    let sql = generate(
+        control(false, false),
+        vec![function("helper", vec![ExternArgs::Internal, ExternArgs::Grant("app".into())])],
+    )
+    .unwrap();
+
+    assert!(
+        sql.contains("GRANT EXECUTE ON FUNCTION @extschema@_internal.\"helper\"() TO \"app\";"),
+        "{sql}"
+    );
+    assert_eq!(sql.matches("FROM PUBLIC").count(), 1, "{sql}");
+}
+
+#[test]
+fn relocatable_extensions_cant_have_internal_functions() {
+    let error =
+        generate(control(true, false), vec![function("helper", vec![ExternArgs::Internal])])
+            .unwrap_err();
+    assert!(error.to_string().contains("relocatable"), "{error:?}");
+}
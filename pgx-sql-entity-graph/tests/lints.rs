@@ -0,0 +1,86 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The lints `cargo pgx schema --lint` reports, for functions whose attributes contradict what
+//! their bodies do, and the functions they leave alone.
+mod common;
+
+use common::{control, int};
+use pgx_sql_entity_graph::{
+    ExternArgs, FunctionFact, Lint, LintLevel, PgExternEntity, SqlGraphEntity, UsedTypeEntity,
+};
+
+fn lint(extern_attrs: Vec<ExternArgs>, facts: Vec<FunctionFact>) -> Vec<Lint> {
+    lint_function(PgExternEntity { extern_attrs, facts, ..common::function("ext", "checked") })
+}
+
+fn lint_function(function: PgExternEntity) -> Vec<Lint> {
+    common::build(control(), vec![SqlGraphEntity::Function(function)]).unwrap().lint()
+}
+
+#[test]
+fn immutable_functions_using_spi_are_errors() {
+    let lints = lint(vec![ExternArgs::Immutable], vec![FunctionFact::UsesSpi]);
+    assert_eq!(lints.len(), 1, "{lints:?}");
+    assert_eq!(lints[0].level, LintLevel::Error);
+    assert_eq!(lints[0].full_path, "checked");
+    assert!(lints[0].message.contains("is IMMUTABLE but uses SPI"), "{}", lints[0]);
+    assert!(lints[0].message.contains("declare it STABLE or VOLATILE"), "{}", lints[0]);
+}
+
+#[test]
+fn immutable_functions_reading_the_transaction_time_should_be_stable() {
+    let lints = lint(vec![ExternArgs::Immutable], vec![FunctionFact::ReadsTransactionTime]);
+    assert_eq!(lints.len(), 1, "{lints:?}");
+    assert_eq!(lints[0].level, LintLevel::Error);
+    assert!(lints[0].message.ends_with("declare it STABLE"), "{}", lints[0]);
+}
+
+#[test]
+fn stable_functions_reading_the_clock_should_be_volatile() {
+    let lints = lint(vec![ExternArgs::Stable], vec![FunctionFact::ReadsClock]);
+    assert_eq!(lints.len(), 1, "{lints:?}");
+    assert_eq!(lints[0].level, LintLevel::Warning);
+    assert!(lints[0].message.contains("is STABLE but reads the current time"), "{}", lints[0]);
+    assert!(lints[0].message.ends_with("declare it VOLATILE"), "{}", lints[0]);
+}
+
+#[test]
+fn parallel_safe_functions_taking_exclusive_locks_are_warnings() {
+    let lints = lint(vec![ExternArgs::ParallelSafe], vec![FunctionFact::LocksExclusive]);
+    assert_eq!(lints.len(), 1, "{lints:?}");
+    assert_eq!(lints[0].level, LintLevel::Warning);
+    assert!(lints[0].message.contains("PARALLEL RESTRICTED"), "{}", lints[0]);
+}
+
+#[test]
+fn strict_functions_taking_options_are_warnings() {
+    let optional = UsedTypeEntity { optional: true, ..int() };
+    let function = common::with_args(
+        PgExternEntity { extern_attrs: vec![ExternArgs::Strict], ..common::function("ext", "f") },
+        vec![("required", int()), ("maybe", optional)],
+    );
+    let lints = lint_function(function);
+    assert_eq!(lints.len(), 1, "{lints:?}");
+    assert!(lints[0].message.contains("takes `maybe` as an `Option`"), "{}", lints[0]);
+}
+
+#[test]
+fn consistent_functions_have_no_lints() {
+    for (extern_attrs, facts) in [
+        (vec![ExternArgs::Volatile], vec![FunctionFact::UsesSpi, FunctionFact::ReadsClock]),
+        (vec![ExternArgs::Stable], vec![FunctionFact::UsesSpi, FunctionFact::ReadsGuc]),
+        (vec![ExternArgs::Stable], vec![FunctionFact::ReadsTransactionTime]),
+        (vec![ExternArgs::Immutable, ExternArgs::ParallelSafe], vec![]),
+        (vec![], vec![FunctionFact::LocksExclusive]),
+    ] {
+        let lints = lint(extern_attrs.clone(), facts.clone());
+        assert_eq!(lints, vec![], "{extern_attrs:?} with {facts:?}");
+    }
+}
@@ -0,0 +1,147 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for operators whose `COMMUTATOR` or `NEGATOR` is another operator of the
+//! extension, which must exist when it's named, or Postgres makes a shell operator of it.
+mod common;
+
+use common::{control, int, schema, ty, with_args};
+use pgx_sql_entity_graph::{PgExternEntity, PgOperatorEntity, SqlGraphEntity, UsedTypeEntity};
+
+struct Point;
+
+fn point() -> UsedTypeEntity {
+    ty::<Point>("Point", "point_t")
+}
+
+fn operator(
+    module_path: &'static str,
+    name: &'static str,
+    opname: &'static str,
+    commutator: Option<&'static str>,
+    negator: Option<&'static str>,
+    (left, right): (UsedTypeEntity, UsedTypeEntity),
+) -> SqlGraphEntity {
+    SqlGraphEntity::Function(PgExternEntity {
+        operator: Some(PgOperatorEntity { commutator, negator, ..common::operator(opname) }),
+        ..with_args(common::function(module_path, name), vec![("left", left), ("right", right)])
+    })
+}
+
+fn generate(operators: Vec<SqlGraphEntity>) -> eyre::Result<String> {
+    let mut entities = vec![schema("ext::geo", "geo")];
+    entities.extend(operators);
+    common::generate(control(), entities)
+}
+
+/// The `CREATE OPERATOR` of the operator made by the function `name`, and where it is in `sql`
+fn create_operator<'a>(sql: &'a str, name: &str) -> (usize, &'a str) {
+    let start = sql.find(&format!("\"{}\",\n\tLEFTARG", name)).unwrap();
+    let start = sql[..start].rfind("CREATE OPERATOR").unwrap();
+    let end = start + sql[start..].find(");").unwrap();
+    (start, &sql[start..end])
+}
+
+#[test]
+fn commutators_naming_each_other_are_created_in_turn() {
+    let sql = generate(vec![
+        operator("ext", "point_lt", "<", Some(">"), None, (point(), point())),
+        operator("ext", "point_gt", ">", Some("<"), None, (point(), point())),
+    ])
+    .unwrap();
+
+    // only the second names the first, which Postgres then links to it
+    let (gt_at, gt) = create_operator(&sql, "point_gt");
+    let (lt_at, lt) = create_operator(&sql, "point_lt");
+    assert!(gt_at < lt_at, "{sql}");
+    assert!(!gt.contains("COMMUTATOR"), "{sql}");
+    assert!(lt.contains("COMMUTATOR = >"), "{sql}");
+}
+
+#[test]
+fn operators_are_created_after_the_operator_they_name() {
+    let sql = generate(vec![
+        operator("ext", "point_a_lt", "<", Some(">"), Some(">="), (point(), point())),
+        operator("ext", "point_b_gt", ">", None, None, (point(), point())),
+        operator("ext", "point_c_ge", ">=", None, None, (point(), point())),
+    ])
+    .unwrap();
+
+    let (lt_at, lt) = create_operator(&sql, "point_a_lt");
+    assert!(create_operator(&sql, "point_b_gt").0 < lt_at, "{sql}");
+    assert!(create_operator(&sql, "point_c_ge").0 < lt_at, "{sql}");
+    assert!(lt.contains("COMMUTATOR = >"), "{sql}");
+    assert!(lt.contains("NEGATOR = >="), "{sql}");
+}
+
+#[test]
+fn operators_can_be_their_own_commutator() {
+    let sql = generate(vec![operator("ext", "point_eq", "=", Some("="), None, (point(), point()))])
+        .unwrap();
+    assert!(create_operator(&sql, "point_eq").1.contains("COMMUTATOR = ="), "{sql}");
+}
+
+#[test]
+fn commutators_of_other_types_take_swapped_arguments() {
+    let sql = generate(vec![
+        operator("ext", "point_contains", "@>", Some("<@"), None, (point(), int())),
+        operator("ext", "int_in_point", "<@", Some("@>"), None, (int(), point())),
+        // not a commutator of either, as its arguments are the other way around
+        operator("ext", "point_in_int", "<@", None, None, (point(), int())),
+    ])
+    .unwrap();
+
+    let (contains_at, contains) = create_operator(&sql, "point_contains");
+    let (in_point_at, in_point) = create_operator(&sql, "int_in_point");
+    assert!(in_point_at < contains_at, "{sql}");
+    assert!(!in_point.contains("COMMUTATOR"), "{sql}");
+    assert!(contains.contains("COMMUTATOR = <@"), "{sql}");
+}
+
+#[test]
+fn commutators_are_matched_by_their_rust_types() {
+    // the same type, however it's spelled
+    let reexported = || ty::<Point>("crate::geo::Point", "point_t");
+    let sql = generate(vec![
+        operator("ext", "point_contains", "@>", Some("<@"), None, (point(), int())),
+        operator("ext", "int_in_point", "<@", Some("@>"), None, (int(), reexported())),
+    ])
+    .unwrap();
+    assert!(create_operator(&sql, "point_contains").1.contains("COMMUTATOR = <@"), "{sql}");
+}
+
+#[test]
+fn operators_in_schemas_are_named_with_their_schema() {
+    let sql = generate(vec![
+        operator("ext::geo", "point_lt", "<", Some(">"), None, (point(), point())),
+        operator("ext::geo", "point_gt", ">", Some("<"), None, (point(), point())),
+    ])
+    .unwrap();
+    assert!(create_operator(&sql, "point_lt").1.contains("COMMUTATOR = OPERATOR(geo.>)"), "{sql}");
+}
+
+#[test]
+fn naming_a_missing_operator_is_an_error() {
+    let error =
+        generate(vec![operator("ext", "point_lt", "<", Some(">"), None, (point(), point()))])
+            .unwrap_err();
+    assert!(error.to_string().contains("doesn't create a `>` operator"), "{error:?}");
+}
+
+#[test]
+fn naming_an_operator_on_other_types_is_an_error() {
+    let error = generate(vec![
+        operator("ext", "point_contains", "@>", Some("<@"), None, (point(), int())),
+        operator("ext", "point_in_int", "<@", None, None, (point(), int())),
+    ])
+    .unwrap_err();
+    let error = error.to_string();
+    assert!(error.contains("must be on (integer, point_t)"), "{error}");
+    assert!(error.contains("are on (point_t, integer)"), "{error}");
+}
@@ -0,0 +1,53 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for `#[pg_extern(rows = ..)]` functions, which tell the planner how many rows
+//! they return.
+mod common;
+
+use common::{control, int, returning};
+use pgx_sql_entity_graph::metadata::{Returns, SqlMapping};
+use pgx_sql_entity_graph::{ExternArgs, PgExternEntity, PgExternReturnEntity, SqlGraphEntity};
+
+fn function(name: &'static str, set: bool, extern_attrs: Vec<ExternArgs>) -> SqlGraphEntity {
+    let mut function = returning(common::function("ext", name), int());
+    if set {
+        function.fn_return =
+            PgExternReturnEntity::SetOf { ty: int(), optional: false, result: false };
+        if let Some(retval) = function.metadata.retval.as_mut() {
+            retval.return_sql = Ok(Returns::SetOf(SqlMapping::As(String::from("integer"))));
+        }
+    }
+    SqlGraphEntity::Function(PgExternEntity { extern_attrs, ..function })
+}
+
+fn generate(function: SqlGraphEntity) -> eyre::Result<String> {
+    common::generate(control(), vec![function])
+}
+
+#[test]
+fn set_returning_functions_estimate_their_rows() {
+    let sql = generate(function(
+        "few",
+        true,
+        vec![ExternArgs::Immutable, ExternArgs::Rows(String::from("3"))],
+    ))
+    .unwrap();
+    assert!(sql.contains("RETURNS SETOF integer /* i32 */\nIMMUTABLE ROWS 3\n"), "{sql}");
+}
+
+#[test]
+fn other_functions_cant_estimate_rows() {
+    let error =
+        generate(function("one", false, vec![ExternArgs::Rows(String::from("3"))])).unwrap_err();
+    assert!(
+        error.to_string().contains("`one` has `rows = 3`, but only a function returning a"),
+        "{error}"
+    );
+}
@@ -0,0 +1,176 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for extensions with `pg_module_magic!(search_path = "safe")`, which mustn't
+//! depend on the `search_path` it's run with to find what the extension made.
+mod common;
+
+use common::{int, returning, schema, to_sql_config, with_args};
+use pgx_sql_entity_graph::{
+    CastContext, ControlFile, PgCastEntity, PgExternEntity, PgOperatorEntity, PostgresHashEntity,
+    PostgresOrdEntity, SqlGraphEntity, SAFE_SEARCH_PATH,
+};
+use std::any::TypeId;
+
+struct Dog;
+
+fn function(
+    module_path: &'static str,
+    name: &'static str,
+    args: usize,
+    operator: Option<&'static str>,
+) -> SqlGraphEntity {
+    let args = (0..args).map(|_| ("value", int())).collect();
+    SqlGraphEntity::Function(PgExternEntity {
+        operator: operator.map(|opname| PgOperatorEntity {
+            commutator: Some(opname),
+            restrict: Some("eqsel"),
+            ..common::operator(opname)
+        }),
+        ..returning(with_args(common::function(module_path, name), args), int())
+    })
+}
+
+/// One of each kind of entity which names another of the extension's in its SQL
+fn entities(module_path: &'static str) -> Vec<SqlGraphEntity> {
+    vec![
+        function(module_path, "dog_id", 0, None),
+        function(module_path, "dog_cmp", 2, None),
+        function(module_path, "dog_hash", 1, None),
+        function(module_path, "dog_eq", 2, Some("==")),
+        SqlGraphEntity::Cast(PgCastEntity {
+            name: "dog_hash",
+            module_path,
+            full_path: "dog_hash",
+            file: "lib.rs",
+            line: 1,
+            source: int(),
+            target: int(),
+            function: Some("dog_hash"),
+            context: CastContext::Explicit,
+        }),
+        SqlGraphEntity::Ord(PostgresOrdEntity {
+            name: "Dog",
+            file: "lib.rs",
+            line: 1,
+            full_path: "Dog",
+            module_path,
+            id: TypeId::of::<Dog>(),
+            to_sql_config: to_sql_config(),
+        }),
+        SqlGraphEntity::Hash(PostgresHashEntity {
+            name: "Dog",
+            file: "lib.rs",
+            line: 1,
+            full_path: "Dog",
+            module_path,
+            id: TypeId::of::<Dog>(),
+            to_sql_config: to_sql_config(),
+        }),
+    ]
+}
+
+fn generate(
+    relocatable: bool,
+    safe_search_path: bool,
+    entities: Vec<SqlGraphEntity>,
+) -> eyre::Result<String> {
+    let mut all = vec![schema("ext::kennel", "kennel")];
+    all.extend(entities);
+    common::generate(ControlFile { relocatable, safe_search_path, ..common::control() }, all)
+}
+
+/// Every place `sql` names something the extension made, which aren't qualified by `schema`
+fn unqualified<'a>(sql: &'a str, schema: &str) -> Vec<&'a str> {
+    let names = [
+        "\"dog_id\"",
+        "\"dog_cmp\"",
+        "\"dog_hash\"",
+        "\"dog_eq\"",
+        "dog_cmp(",
+        "dog_hash(",
+        "==",
+        "<",
+        "Dog ",
+        "Dog,",
+        "Dog)",
+    ];
+    let mut found = Vec::new();
+    for line in sql.lines().filter(|line| !line.trim_start().starts_with("--")) {
+        // what's in comments is only for people
+        let line = line.split("/*").next().unwrap();
+        let line = line.trim_end();
+        for name in names {
+            for (at, _) in line.match_indices(name) {
+                if !line[..at].ends_with(schema) {
+                    found.push(line);
+                }
+            }
+        }
+    }
+    found
+}
+
+#[test]
+fn the_search_path_is_made_safe_first() {
+    let sql = generate(false, true, entities("ext")).unwrap();
+    let preamble_at = sql.find(SAFE_SEARCH_PATH).unwrap();
+    assert!(preamble_at < sql.find("CREATE").unwrap(), "{sql}");
+    assert!(SAFE_SEARCH_PATH.contains("'pg_catalog', pg_catalog.current_setting('search_path')"));
+}
+
+#[test]
+fn the_search_path_is_inherited_by_default() {
+    let sql = generate(false, false, entities("ext")).unwrap();
+    assert!(!sql.contains("search_path"), "{sql}");
+    assert!(!sql.contains("@extschema@"), "{sql}");
+    assert!(sql.contains("FUNCTION 1 dog_cmp(Dog, Dog);"), "{sql}");
+}
+
+#[test]
+fn opclasses_are_unqualified_by_default() {
+    let sql = generate(false, false, entities("ext::kennel")).unwrap();
+    assert!(sql.contains("CREATE OPERATOR CLASS Dog_btree_ops DEFAULT FOR TYPE Dog"), "{sql}");
+    assert!(sql.contains("FUNCTION 1 dog_cmp(Dog, Dog);"), "{sql}");
+    assert!(sql.contains("FUNCTION    1   dog_hash(Dog);"), "{sql}");
+}
+
+#[test]
+fn everything_in_the_extensions_schema_is_qualified() {
+    let sql = generate(false, true, entities("ext")).unwrap();
+    assert_eq!(unqualified(&sql, "@extschema@."), Vec::<&str>::new(), "{sql}");
+
+    assert!(sql.contains("FUNCTION @extschema@.\"dog_id\"()"), "{sql}");
+    assert!(sql.contains("CREATE OPERATOR @extschema@.== ("), "{sql}");
+    assert!(sql.contains("\tPROCEDURE=@extschema@.\"dog_eq\",\n"), "{sql}");
+    assert!(sql.contains("\tCOMMUTATOR = OPERATOR(@extschema@.==)"), "{sql}");
+    assert!(sql.contains("WITH FUNCTION @extschema@.\"dog_hash\"(integer)"), "{sql}");
+    assert!(
+        sql.contains("FUNCTION 1 @extschema@.dog_cmp(@extschema@.Dog, @extschema@.Dog);"),
+        "{sql}"
+    );
+    assert!(
+        sql.contains("\tOPERATOR    1   @extschema@.=  (@extschema@.Dog, @extschema@.Dog),"),
+        "{sql}"
+    );
+}
+
+#[test]
+fn everything_in_other_schemas_is_qualified() {
+    let sql = generate(false, true, entities("ext::kennel")).unwrap();
+    assert_eq!(unqualified(&sql, "kennel."), Vec::<&str>::new(), "{sql}");
+    assert!(!sql.contains("@extschema@"), "{sql}");
+}
+
+#[test]
+fn relocatable_extensions_only_get_the_safe_search_path() {
+    let sql = generate(true, true, entities("ext::kennel")).unwrap();
+    assert!(sql.contains(SAFE_SEARCH_PATH), "{sql}");
+    assert!(!sql.contains("@extschema@"), "{sql}");
+}
@@ -0,0 +1,155 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The files `cargo pgx schema --split-by` writes an extension's SQL to, for reviewing it.
+mod common;
+
+use common::{control, schema};
+use pgx_sql_entity_graph::{
+    ExtensionSqlEntity, PgxSql, PositioningRef, SplitBy, SqlGraphEntity, SPLIT_INDEX_FILE,
+};
+
+fn block(
+    module_path: &'static str,
+    name: &'static str,
+    sql: &'static str,
+    requires: &[&str],
+) -> SqlGraphEntity {
+    SqlGraphEntity::CustomSql(ExtensionSqlEntity {
+        requires: requires.iter().map(|name| PositioningRef::Name(name.to_string())).collect(),
+        ..common::block(module_path, name, sql)
+    })
+}
+
+fn build(entities: Vec<SqlGraphEntity>) -> PgxSql {
+    let mut all = vec![schema("ext::kennel", "kennel")];
+    all.extend(entities);
+    common::build(control(), all).unwrap()
+}
+
+fn names(files: &[(String, String)]) -> Vec<&str> {
+    files.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+/// Every line of `sql` which isn't blank, sorted
+fn lines(sql: &str) -> Vec<&str> {
+    let mut lines = sql.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn split_by_schema() {
+    let pgx_sql = build(vec![
+        block("ext::kennel", "dogs", "CREATE TABLE kennel.dogs (name text);", &[]),
+        block("ext", "cats", "CREATE TABLE cats (name text);", &[]),
+    ]);
+    let files = pgx_sql.to_split_sql(SplitBy::Schema).unwrap();
+    assert_eq!(names(&files), vec![SPLIT_INDEX_FILE, "ext.sql", "kennel.sql"]);
+    assert!(files[0].1.ends_with("\\ir ext.sql\n\\ir kennel.sql\n"), "{}", files[0].1);
+    assert!(files[1].1.contains("CREATE TABLE cats"), "{}", files[1].1);
+    assert!(files[2].1.contains("CREATE SCHEMA IF NOT EXISTS kennel;"), "{}", files[2].1);
+    assert!(files[2].1.contains("CREATE TABLE kennel.dogs"), "{}", files[2].1);
+
+    // it's all the same SQL as the single file, just divided up
+    let split = files[1..].iter().map(|(_, sql)| sql.as_str()).collect::<String>();
+    assert_eq!(lines(&split), lines(&pgx_sql.to_sql().unwrap()));
+}
+
+#[test]
+fn split_by_module() {
+    let pgx_sql = build(vec![
+        block("ext::kennel::dogs", "dogs", "CREATE TABLE dogs (name text);", &[]),
+        block("ext::kennel::cats", "cats", "CREATE TABLE cats (name text);", &[]),
+        block("ext::barn", "horses", "CREATE TABLE horses (name text);", &[]),
+    ]);
+    let files = pgx_sql.to_split_sql(SplitBy::Module).unwrap();
+    assert_eq!(names(&files), vec![SPLIT_INDEX_FILE, "ext.sql", "barn.sql", "kennel.sql"]);
+    assert!(files[3].1.contains("CREATE TABLE dogs"), "{}", files[3].1);
+    assert!(files[3].1.contains("CREATE TABLE cats"), "{}", files[3].1);
+}
+
+#[test]
+fn parts_are_only_divided_when_they_have_to_be() {
+    let pgx_sql = build(vec![
+        block("ext", "first", "CREATE TABLE first (id int);", &[]),
+        block("ext::kennel", "second", "CREATE TABLE kennel.second (id int);", &["first"]),
+        block("ext", "third", "CREATE TABLE third (id int);", &["second"]),
+        block("ext", "unrelated", "CREATE TABLE unrelated (id int);", &[]),
+    ]);
+    let files = pgx_sql.to_split_sql(SplitBy::Schema).unwrap();
+    assert_eq!(names(&files), vec![SPLIT_INDEX_FILE, "ext.sql", "kennel.sql", "ext.2.sql"]);
+    assert!(files[1].1.contains("CREATE TABLE unrelated"), "{}", files[1].1);
+    assert!(files[3].1.contains("CREATE TABLE third"), "{}", files[3].1);
+}
+
+#[test]
+fn changing_one_part_leaves_the_others_alone() {
+    let before = build(vec![
+        block("ext::kennel", "dogs", "CREATE TABLE kennel.dogs (name text);", &[]),
+        block("ext", "cats", "CREATE TABLE cats (name text);", &[]),
+    ])
+    .to_split_sql(SplitBy::Schema)
+    .unwrap();
+    let after = build(vec![
+        block("ext::kennel", "dogs", "CREATE TABLE kennel.dogs (name text);", &[]),
+        block("ext::kennel", "puppies", "CREATE TABLE kennel.puppies (name text);", &[]),
+        block("ext", "cats", "CREATE TABLE cats (name text);", &[]),
+    ])
+    .to_split_sql(SplitBy::Schema)
+    .unwrap();
+    assert_eq!(names(&before), names(&after));
+    assert_eq!(before[0], after[0]);
+    assert_eq!(before[1], after[1]);
+    assert_ne!(before[2], after[2]);
+}
+
+/// An empty directory of its own for the test `name`
+fn empty_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("pgx-split-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn split_dirs_only_lose_the_files_written_to_them() {
+    let dir = empty_dir("rewritten");
+    build(vec![
+        block("ext::kennel", "dogs", "CREATE TABLE kennel.dogs (name text);", &[]),
+        block("ext", "cats", "CREATE TABLE cats (name text);", &[]),
+    ])
+    .to_split_dir(&dir, SplitBy::Schema)
+    .unwrap();
+    std::fs::write(dir.join("ext--1.0--1.1.sql"), "-- by hand").unwrap();
+    assert!(dir.join("kennel.sql").exists());
+
+    build(vec![block("ext", "cats", "CREATE TABLE cats (name text);", &[])])
+        .to_split_dir(&dir, SplitBy::Schema)
+        .unwrap();
+    assert!(!dir.join("kennel.sql").exists());
+    assert!(dir.join("ext.sql").exists());
+    assert_eq!(std::fs::read_to_string(dir.join("ext--1.0--1.1.sql")).unwrap(), "-- by hand");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_dirs_with_other_files_are_left_alone() {
+    let dir = empty_dir("other_files");
+    std::fs::write(dir.join("ext--1.0--1.1.sql"), "-- by hand").unwrap();
+    let pgx_sql = build(vec![block("ext", "cats", "CREATE TABLE cats (name text);", &[])]);
+    let error = pgx_sql.to_split_dir(&dir, SplitBy::Schema).unwrap_err();
+    assert!(error.to_string().contains("isn't empty"), "{error}");
+
+    std::fs::write(dir.join(SPLIT_INDEX_FILE), "\\ir ext--1.0--1.1.sql\n").unwrap();
+    let error = pgx_sql.to_split_dir(&dir, SplitBy::Schema).unwrap_err();
+    assert!(error.to_string().contains("wasn't written by `--split-by`"), "{error}");
+    assert!(dir.join("ext--1.0--1.1.sql").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,62 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Entities are rendered in parallel, except for `#[pgx(sql = callback)]` ones, which are the
+//! extension's own code and are called on the thread generating the SQL.
+mod common;
+
+use common::control;
+use pgx_sql_entity_graph::{PgExternEntity, PgxSql, SqlGraphEntity, ToSqlConfigEntity};
+use std::cell::Cell;
+
+thread_local! {
+    static GENERATING: Cell<bool> = Cell::new(false);
+}
+
+fn callback(
+    entity: &SqlGraphEntity,
+    _context: &PgxSql,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    if !GENERATING.with(Cell::get) {
+        return Err("called on another thread".into());
+    }
+    match entity {
+        SqlGraphEntity::Function(function) => Ok(format!("-- callback for {}", function.name)),
+        _ => Err("not a function".into()),
+    }
+}
+
+fn names(prefix: &str) -> Vec<&'static str> {
+    (0..50).map(|idx| &*Box::leak(format!("{prefix}_{idx}").into_boxed_str())).collect()
+}
+
+#[test]
+fn callbacks_are_called_on_the_calling_thread() {
+    let mut entities = names("rendered")
+        .into_iter()
+        .map(|name| SqlGraphEntity::Function(common::function("ext", name)))
+        .collect::<Vec<_>>();
+    let with_callback = names("callback");
+    entities.extend(with_callback.iter().map(|&name| {
+        SqlGraphEntity::Function(PgExternEntity {
+            to_sql_config: ToSqlConfigEntity {
+                callback: Some(callback),
+                ..common::to_sql_config()
+            },
+            ..common::function("ext", name)
+        })
+    }));
+
+    GENERATING.with(|generating| generating.set(true));
+    let sql = common::generate(control(), entities).unwrap();
+    for name in with_callback {
+        assert!(sql.contains(&format!("-- callback for {name}\n")), "{sql}");
+    }
+    assert!(sql.contains("\"rendered_0\""), "{sql}");
+}
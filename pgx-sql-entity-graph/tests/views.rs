@@ -0,0 +1,113 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The SQL generated for `pg_view!()`s, which are created in their module's schema after what their
+//! queries use.
+mod common;
+
+use common::{control, schema};
+use pgx_sql_entity_graph::{PgViewEntity, PositioningRef, SqlGraphEntity};
+
+fn function(module_path: &'static str, name: &'static str) -> SqlGraphEntity {
+    SqlGraphEntity::Function(common::function(module_path, name))
+}
+
+fn block(name: &'static str, sql: &'static str) -> SqlGraphEntity {
+    SqlGraphEntity::CustomSql(common::block("ext", name, sql))
+}
+
+fn view(
+    module_path: &'static str,
+    name: &'static str,
+    query: &'static str,
+    requires: Vec<PositioningRef>,
+) -> SqlGraphEntity {
+    SqlGraphEntity::View(PgViewEntity {
+        name,
+        module_path,
+        full_path: name,
+        file: "lib.rs",
+        line: 2,
+        query,
+        requires,
+    })
+}
+
+fn generate(entities: Vec<SqlGraphEntity>) -> eyre::Result<String> {
+    let mut all = vec![schema("ext::animals", "animals")];
+    all.extend(entities);
+    common::generate(control(), all)
+}
+
+#[test]
+fn views_are_created_after_the_functions_they_call() {
+    let sql = generate(vec![
+        view("ext", "good_dogs", "SELECT * FROM dogs() WHERE good;", vec![]),
+        function("ext", "dogs"),
+    ])
+    .unwrap();
+    let function_at = sql.find("FUNCTION \"dogs\"()").unwrap();
+    let view_at =
+        sql.find("CREATE VIEW \"good_dogs\" AS\nSELECT * FROM dogs() WHERE good;\n").unwrap();
+    assert!(function_at < view_at, "{sql}");
+}
+
+#[test]
+fn views_are_created_after_the_views_they_select_from() {
+    let sql = generate(vec![
+        view("ext", "best_dog", "SELECT * FROM good_dogs LIMIT 1", vec![]),
+        view("ext", "good_dogs", "SELECT * FROM dogs() WHERE good", vec![]),
+        function("ext", "dogs"),
+    ])
+    .unwrap();
+    let good_at = sql.find("CREATE VIEW \"good_dogs\"").unwrap();
+    let best_at = sql.find("CREATE VIEW \"best_dog\"").unwrap();
+    assert!(good_at < best_at, "{sql}");
+}
+
+#[test]
+fn views_in_schemas_are_named_with_their_schema() {
+    let sql = generate(vec![
+        function("ext::animals", "dogs"),
+        view("ext::animals", "good_dogs", "SELECT * FROM animals.dogs()", vec![]),
+    ])
+    .unwrap();
+    let function_at = sql.find("FUNCTION animals.\"dogs\"()").unwrap();
+    let view_at = sql.find("CREATE VIEW animals.\"good_dogs\" AS").unwrap();
+    assert!(function_at < view_at, "{sql}");
+}
+
+#[test]
+fn views_are_created_after_what_they_require() {
+    let sql = generate(vec![
+        view(
+            "ext",
+            "dog_names",
+            "SELECT name FROM dog_table",
+            vec![PositioningRef::Name(String::from("dog_table"))],
+        ),
+        block("dog_table", "CREATE TABLE dog_table (name text);"),
+    ])
+    .unwrap();
+    let table_at = sql.find("CREATE TABLE dog_table").unwrap();
+    let view_at = sql.find("CREATE VIEW \"dog_names\"").unwrap();
+    assert!(table_at < view_at, "{sql}");
+}
+
+#[test]
+fn views_requiring_nothing_fail() {
+    let error = generate(vec![view(
+        "ext",
+        "dog_names",
+        "SELECT name FROM dog_table",
+        vec![PositioningRef::Name(String::from("dog_table"))],
+    )])
+    .unwrap_err();
+    assert!(error.to_string().contains("Could not find `requires` target of view `dog_names`"));
+}
@@ -0,0 +1,49 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The wrapper symbols which the generated SQL names, which are namespaced by module path so
+//! functions of the same name in different modules don't collide.
+mod common;
+
+use common::control;
+use pgx_sql_entity_graph::{PgExternEntity, SqlGraphEntity};
+
+fn function(
+    name: &'static str,
+    module_path: &'static str,
+    schema: &'static str,
+    file: &'static str,
+) -> SqlGraphEntity {
+    SqlGraphEntity::Function(PgExternEntity {
+        schema: Some(schema),
+        file,
+        ..common::function(module_path, name)
+    })
+}
+
+fn generate(functions: Vec<SqlGraphEntity>) -> eyre::Result<String> {
+    common::generate(control(), functions)
+}
+
+#[test]
+fn functions_name_their_namespaced_wrappers() {
+    let sql = generate(vec![function("create", "ext::admin", "admin", "src/admin.rs")]).unwrap();
+    assert!(sql.contains("AS 'MODULE_PATHNAME', 'ext::admin::create_wrapper';"), "{sql}");
+}
+
+#[test]
+fn same_name_in_different_modules_names_different_wrappers() {
+    let sql = generate(vec![
+        function("create", "ext::admin", "admin", "src/admin.rs"),
+        function("create", "ext::tests", "tests", "src/tests.rs"),
+    ])
+    .unwrap();
+    assert!(sql.contains("AS 'MODULE_PATHNAME', 'ext::admin::create_wrapper';"), "{sql}");
+    assert!(sql.contains("AS 'MODULE_PATHNAME', 'ext::tests::create_wrapper';"), "{sql}");
+}
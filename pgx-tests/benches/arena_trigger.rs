@@ -0,0 +1,58 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! How many rows a second a `BEFORE INSERT` trigger which allocates for every word of every row
+//! can insert, with a `String` for each word, and with `#[pg_trigger(arena)]`'s arena.
+//!
+//! Run with `cargo bench -p pgx-tests --features pg15 --bench arena_trigger`, and `ROWS=n` to
+//! change the number of rows inserted, 200000 by default.  The triggers are in
+//! `src/tests/arena_tests.rs`.
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 5;
+
+fn main() -> eyre::Result<()> {
+    let rows: i32 =
+        std::env::var("ROWS").ok().and_then(|rows| rows.parse().ok()).unwrap_or(200_000);
+    let mut client = pgx_tests::session(vec!["shared_preload_libraries='pgx_tests'"])?;
+
+    for (name, trigger) in [("heap", "arena_tests_slug_heap"), ("arena", "arena_tests_slug_arena")]
+    {
+        client.batch_execute(&format!(
+            "DROP TABLE IF EXISTS arena_trigger_bench;
+            CREATE TABLE arena_trigger_bench (title text, slug text);
+            CREATE TRIGGER arena_trigger_bench BEFORE INSERT ON arena_trigger_bench
+                FOR EACH ROW EXECUTE FUNCTION {}()",
+            trigger
+        ))?;
+
+        let mut total = Duration::ZERO;
+        for _ in 0..ITERATIONS {
+            client.batch_execute("TRUNCATE arena_trigger_bench")?;
+            let start = Instant::now();
+            client.execute(
+                "INSERT INTO arena_trigger_bench (title)
+                    SELECT 'The Quick Brown Fox Number ' || i || ' Jumps Over The Lazy Dog'
+                    FROM generate_series(1, $1) i",
+                &[&rows],
+            )?;
+            total += start.elapsed();
+        }
+        let each = total / ITERATIONS;
+        println!(
+            "{:>5}: {} rows in {:?}, {:.0} rows/s",
+            name,
+            rows,
+            each,
+            f64::from(rows) / each.as_secs_f64()
+        );
+    }
+    client.batch_execute("DROP TABLE arena_trigger_bench")?;
+    Ok(())
+}
@@ -0,0 +1,46 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! How long returning an array of squares takes, collected into a `Vec` first, and streamed into
+//! the array by an `ArrayFromIterator`.
+//!
+//! Run with `cargo bench -p pgx-tests --features pg15 --bench array_from_iterator`, and
+//! `ELEMENTS=n` to change the number of elements in each array, 1000000 by default.  The functions
+//! are in `src/tests/array_tests.rs`.
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10;
+
+fn main() -> eyre::Result<()> {
+    let elements: i32 = std::env::var("ELEMENTS")
+        .ok()
+        .and_then(|elements| elements.parse().ok())
+        .unwrap_or(1_000_000);
+    let mut client = pgx_tests::session(vec![])?;
+
+    for (name, function) in [("vec", "vec_squares"), ("iterator", "array_from_iterator_squares")] {
+        // `cardinality()` keeps the array in the backend, so only building it is timed
+        let query = format!("SELECT cardinality({}($1))", function);
+        let mut total = Duration::ZERO;
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            client.query_one(&query, &[&elements])?;
+            total += start.elapsed();
+        }
+        let each = total / ITERATIONS;
+        println!(
+            "{:>8}: {} elements in {:?}, {:.0} elements/s",
+            name,
+            elements,
+            each,
+            f64::from(elements) / each.as_secs_f64()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! How long returning a 10MB `text` takes, built in a `String`, and built in a `StringInfo`.
+//!
+//! Run with `cargo bench -p pgx-tests --features pg15 --bench stringinfo_return`, and `BYTES=n` to
+//! change the length of the text, 10485760 by default.  The functions are in
+//! `src/tests/stringinfo_tests.rs`.
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10;
+
+fn main() -> eyre::Result<()> {
+    let bytes: i32 =
+        std::env::var("BYTES").ok().and_then(|bytes| bytes.parse().ok()).unwrap_or(10_485_760);
+    let mut client = pgx_tests::session(vec![])?;
+
+    for (name, function) in [("String", "string_repeat"), ("StringInfo", "stringinfo_repeat")] {
+        // `octet_length()` keeps the text in the backend, so only building it is timed
+        let query = format!("SELECT octet_length({}($1))", function);
+        let mut total = Duration::ZERO;
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            client.query_one(&query, &[&bytes])?;
+            total += start.elapsed();
+        }
+        let each = total / ITERATIONS;
+        println!(
+            "{:>10}: {} bytes in {:?}, {:.1} MB/s",
+            name,
+            bytes,
+            each,
+            f64::from(bytes) / each.as_secs_f64() / 1_048_576.0
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Compiles `c/export_abi_consumer.c`, the C extension code `export_abi_tests` calls the exported
+//! functions from, against the headers of the Postgres being tested.
+//!
+//! That's only needed by the extension `cargo pgx install --test` builds from pgx-tests itself,
+//! with the `pg_test` feature, so extensions which just depend on pgx-tests skip it.
+use pgx_pg_config::{PgConfig, Pgx, SUPPORTED_MAJOR_VERSIONS};
+
+fn main() -> eyre::Result<()> {
+    if std::env::var("DOCS_RS").unwrap_or("false".into()) == "1" {
+        return Ok(());
+    }
+    if std::env::var("CARGO_FEATURE_PG_TEST").is_err() {
+        return Ok(());
+    }
+    println!("cargo:rerun-if-changed=c");
+    println!("cargo:rerun-if-env-changed=PGX_PG_CONFIG_PATH");
+    println!("cargo:rerun-if-env-changed=PGX_PG_CONFIG_AS_ENV");
+
+    let pg_config = match PgConfig::from_env() {
+        Ok(pg_config) => pg_config,
+        Err(_) => {
+            let feature = SUPPORTED_MAJOR_VERSIONS
+                .iter()
+                .map(|version| format!("pg{}", version))
+                .find(|feature| {
+                    std::env::var(format!("CARGO_FEATURE_{}", feature.to_uppercase())).is_ok()
+                });
+            match feature {
+                Some(feature) => Pgx::from_config()?.get(&feature)?,
+                // without a `pg$VERSION` feature, `pgx-pg-sys` reports the error
+                None => return Ok(()),
+            }
+        }
+    };
+
+    cc::Build::new()
+        .file("c/export_abi_consumer.c")
+        .include("c")
+        .include(pg_config.includedir_server()?)
+        .warnings(false)
+        .compile("pgx_tests_export_abi_consumer");
+    Ok(())
+}
@@ -0,0 +1,88 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+/*
+ * Calls the `#[pg_export_abi]` functions of `src/tests/export_abi_tests.rs` the way another
+ * extension would, through the header `cargo pgx schema --emit-header` generates.
+ */
+#include "postgres.h"
+#include "utils/builtins.h"
+
+#include "pgx_tests_abi.h"
+
+static PgxAbiDatum
+value(Datum datum)
+{
+	PgxAbiDatum arg;
+
+	arg.value = datum;
+	arg.isnull = false;
+	return arg;
+}
+
+static PgxAbiDatum
+null(void)
+{
+	PgxAbiDatum arg;
+
+	arg.value = (Datum) 0;
+	arg.isnull = true;
+	return arg;
+}
+
+/*
+ * Returns NULL if every call behaved, or else a description of the first which didn't.
+ */
+const char *
+export_abi_consumer_run(void)
+{
+	PgxAbiDatum args[2];
+	PgxAbiDatum result;
+	PgxAbiError error;
+	int32		status;
+
+	args[0] = value(Int32GetDatum(40));
+	args[1] = value(Int32GetDatum(2));
+	status = pgx_tests_export_abi_add(args, PGX_TESTS_EXPORT_ABI_ADD_NARGS, &result, &error);
+	if (status != PGX_ABI_OK || result.isnull || DatumGetInt32(result.value) != 42)
+		return "export_abi_add(40, 2) didn't return 42";
+
+	args[1] = null();
+	status = pgx_tests_export_abi_add(args, PGX_TESTS_EXPORT_ABI_ADD_NARGS, &result, &error);
+	if (status != PGX_ABI_OK || result.isnull || DatumGetInt32(result.value) != 40)
+		return "export_abi_add(40, NULL) didn't return 40";
+
+	args[0] = null();
+	status = pgx_tests_export_abi_add(args, PGX_TESTS_EXPORT_ABI_ADD_NARGS, &result, &error);
+	if (status != PGX_ABI_ERROR || error.sqlerrcode != ERRCODE_NULL_VALUE_NOT_ALLOWED)
+		return "export_abi_add(NULL, NULL) didn't fail with null_value_not_allowed";
+
+	status = pgx_tests_export_abi_add(args, 1, &result, &error);
+	if (status != PGX_ABI_BAD_CALL || error.message == NULL)
+		return "export_abi_add() with one argument wasn't a bad call";
+
+	args[0] = value(CStringGetTextDatum("Brandy"));
+	status = pgx_tests_export_abi_greet(args, PGX_TESTS_EXPORT_ABI_GREET_NARGS, &result, &error);
+	if (status != PGX_ABI_OK || result.isnull ||
+		strcmp(TextDatumGetCString(result.value), "hello, Brandy") != 0)
+		return "export_abi_greet('Brandy') didn't return 'hello, Brandy'";
+
+	args[0] = value(Int32GetDatum(1));
+	args[1] = value(Int32GetDatum(0));
+	status = pgx_tests_export_abi_divide(args, PGX_TESTS_EXPORT_ABI_DIVIDE_NARGS, &result, &error);
+	if (status != PGX_ABI_ERROR || error.sqlerrcode != ERRCODE_DIVISION_BY_ZERO ||
+		strcmp(error.message, "division by zero") != 0)
+		return "export_abi_divide(1, 0) didn't fail with division_by_zero";
+
+	status = pgx_tests_export_abi_panic(NULL, PGX_TESTS_EXPORT_ABI_PANIC_NARGS, &result, &error);
+	if (status != PGX_ABI_PANIC || strstr(error.message, "export_abi_panic panicked") == NULL)
+		return "export_abi_panic() didn't report its panic";
+
+	return NULL;
+}
@@ -0,0 +1,64 @@
+/*
+ * The functions `pgx_tests` exports for other extensions to call directly.
+ *
+ * Generated by `cargo pgx schema --emit-header`.  Do not edit.
+ *
+ * Each function takes its arguments as an array of `nargs` datums, and stores its result in
+ * `*result`.  It returns PGX_ABI_OK, or on failure another PGX_ABI_* status, with the failure's
+ * SQLSTATE and message, allocated in the caller's memory context, in `*error`.  A caught ERROR
+ * (PGX_ABI_ERROR) leaves the transaction in the same state PG_CATCH() does, so unless the call
+ * was made in a subtransaction, the caller must raise an ERROR of its own.
+ *
+ * The library is found with load_external_function(), for instance:
+ *
+ *     pgx_abi_fn f = (pgx_abi_fn) load_external_function("$libdir/pgx_tests", "symbol", true, NULL);
+ */
+#ifndef PGX_TESTS_PGX_ABI_H
+#define PGX_TESTS_PGX_ABI_H
+
+#include "postgres.h"
+
+#ifndef PGX_ABI_VERSION
+#define PGX_ABI_VERSION 1
+
+#define PGX_ABI_OK 0
+#define PGX_ABI_ERROR 1
+#define PGX_ABI_PANIC 2
+#define PGX_ABI_BAD_CALL 3
+
+typedef struct PgxAbiDatum
+{
+	Datum		value;
+	bool		isnull;
+} PgxAbiDatum;
+
+typedef struct PgxAbiError
+{
+	int			sqlerrcode;
+	char	   *message;
+} PgxAbiError;
+
+typedef int32 (*pgx_abi_fn) (const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+#endif
+
+#if PGX_ABI_VERSION != 1
+#error "pgx_tests's functions need version 1 of the pgx ABI"
+#endif
+
+/* pgx_tests::tests::export_abi_tests::export_abi_add(a: i32 NOT NULL, b: Option<i32>) -> i32 */
+#define PGX_TESTS_EXPORT_ABI_ADD_NARGS 2
+extern int32 pgx_tests_export_abi_add(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+
+/* pgx_tests::tests::export_abi_tests::export_abi_divide(a: i32 NOT NULL, b: i32 NOT NULL) -> i32 */
+#define PGX_TESTS_EXPORT_ABI_DIVIDE_NARGS 2
+extern int32 pgx_tests_export_abi_divide(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+
+/* pgx_tests::tests::export_abi_tests::export_abi_greet(name: &str NOT NULL) -> String */
+#define PGX_TESTS_EXPORT_ABI_GREET_NARGS 1
+extern int32 pgx_tests_export_abi_greet(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+
+/* pgx_tests::tests::export_abi_tests::export_abi_panic() -> i32 */
+#define PGX_TESTS_EXPORT_ABI_PANIC_NARGS 0
+extern int32 pgx_tests_export_abi_panic(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+
+#endif							/* PGX_TESTS_PGX_ABI_H */
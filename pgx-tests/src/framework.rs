@@ -11,20 +11,28 @@ use std::collections::HashSet;
 use std::process::{Command, Stdio};
 
 use eyre::{eyre, WrapErr};
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use owo_colors::OwoColorize;
 use pgx::prelude::*;
-use pgx_pg_config::{createdb, get_c_locale_flags, get_target_dir, PgConfig, Pgx};
+use pgx_pg_config::{createdb, get_c_locale_flags, get_target_dir, rotate_log_file, PgConfig, Pgx};
 use postgres::error::DbError;
 use std::collections::HashMap;
 use std::fmt::Write as _;
+use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use sysinfo::{Pid, ProcessExt, System, SystemExt};
 
+mod schema_snapshot;
 mod shutdown;
+mod sql_regress;
+pub use schema_snapshot::{
+    assert_schema_snapshot, generate_abi_header, generate_schema, PgxMarker, SchemaSnapshotOptions,
+};
 pub use shutdown::add_shutdown_hook;
+pub use sql_regress::run_sql_regress;
 
 type LogLines = Arc<Mutex<HashMap<String, Vec<String>>>>;
 
@@ -42,6 +50,14 @@ static TEST_MUTEX: Lazy<Mutex<SetupState>> = Lazy::new(|| {
     })
 });
 
+/// How many lines from the end of the Postgres log a failure which Postgres couldn't report to
+/// the test, such as a crashed backend, includes
+const CRASH_LOG_LINES: usize = 50;
+
+/// The port the test framework's Postgres instance was started on, which isn't known until it
+/// has started, as it may have had to try more than one
+static TEST_PORT: OnceCell<u16> = OnceCell::new();
+
 // The goal of this closure is to allow "wrapping" of anything that might issue
 // an SQL simple_query or query using either a postgres::Client or
 // postgres::Transaction and capture the output. The use of this wrapper is
@@ -110,7 +126,7 @@ pub fn run_test(
 ) -> eyre::Result<()> {
     let (loglines, system_session_id) = initialize_test_framework(postgresql_conf)?;
 
-    let (mut client, session_id) = client()?;
+    let (mut client, session_id) = client().map_err(|e| e.wrap_err(crash_log_tail()))?;
 
     let schema = "tests"; // get_extension_schema();
     let result = match client.transaction() {
@@ -126,7 +142,7 @@ pub fn run_test(
             result
         }
 
-        Err(e) => panic!("attempt to run test tx failed:\n{e}"),
+        Err(e) => panic!("attempt to run test tx failed:\n{e}\n{}", crash_log_tail()),
     };
 
     if let Err(e) = result {
@@ -172,10 +188,15 @@ pub fn run_test(
                     );
                 }
             } else {
-                panic!("Failed downcast to DbError:\n{e}")
+                panic!("Failed downcast to DbError:\n{e}\n{}", crash_log_tail())
             }
         } else {
-            panic!("Error without deeper source cause:\n{e}\n", e = error_as_string.bold().red())
+            // the connection was lost, most likely because the backend crashed
+            panic!(
+                "Error without deeper source cause:\n{e}\n{log}",
+                e = error_as_string.bold().red(),
+                log = crash_log_tail()
+            )
         }
     } else if let Some(message) = expected_error {
         // we expected an ERROR, but didn't get one
@@ -196,6 +217,45 @@ fn format_loglines(session_id: &str, loglines: &LogLines) -> String {
     result
 }
 
+/// The last [`CRASH_LOG_LINES`] lines of the test instance's log, from every session, after
+/// waiting a second for Postgres to finish writing about whatever went wrong
+fn crash_log_tail() -> String {
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let path = match get_pglog_path() {
+        Ok(path) => path,
+        Err(e) => return format!("couldn't find the Postgres log: {e}"),
+    };
+    match std::fs::read(&path) {
+        Ok(log) => {
+            let log = String::from_utf8_lossy(&log);
+            let lines = log.lines().collect::<Vec<_>>();
+            let tail = &lines[lines.len().saturating_sub(CRASH_LOG_LINES)..];
+            format!(
+                "\n{} `{}`:\n{}\n",
+                "The end of the Postgres log in".bold(),
+                path.display(),
+                tail.join("\n").dimmed().white()
+            )
+        }
+        Err(e) => format!("couldn't read the Postgres log in `{}`: {e}", path.display()),
+    }
+}
+
+/// Open the test instance's log to append to, starting it over unless `cargo pgx test` already
+/// did so for this run
+fn open_pglog() -> eyre::Result<File> {
+    let path = get_pglog_path()?;
+    if std::env::var("PGX_TEST_LOG_ROTATED").as_deref() != Ok("true") {
+        rotate_log_file(&path)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| format!("couldn't open the Postgres log in `{}`", path.display()))
+}
+
 fn initialize_test_framework(
     postgresql_conf: Vec<&'static str>,
 ) -> eyre::Result<(LogLines, String)> {
@@ -211,9 +271,10 @@ fn initialize_test_framework(
     if !state.installed {
         shutdown::register_shutdown_hook();
         install_extension()?;
-        initdb(postgresql_conf)?;
+        let mut pglog = open_pglog()?;
+        initdb(postgresql_conf, &mut pglog)?;
 
-        let system_session_id = start_pg(state.loglines.clone())?;
+        let system_session_id = start_pg(state.loglines.clone(), pglog)?;
         let pg_config = get_pg_config()?;
         dropdb()?;
         createdb(&pg_config, get_pg_dbname(), true, false)?;
@@ -230,7 +291,7 @@ fn get_pg_config() -> eyre::Result<PgConfig> {
 
     let pg_version = pg_sys::get_pg_major_version_num();
 
-    let pg_config = pgx
+    let mut pg_config = pgx
         .get(&format!("pg{}", pg_version))
         .wrap_err_with(|| {
             format!("Error getting pg_config: {} is not a valid postgres version", pg_version)
@@ -238,16 +299,38 @@ fn get_pg_config() -> eyre::Result<PgConfig> {
         .unwrap()
         .clone();
 
+    if let Some(port) = TEST_PORT.get() {
+        pg_config.set_test_port(*port);
+    }
+
     Ok(pg_config)
 }
 
-pub fn client() -> eyre::Result<(postgres::Client, String)> {
+/// A libpq connection string for the test database.
+///
+/// In socket-only mode (see [`PgConfig::socket_only`]) its `host` is the directory of the
+/// instance's Unix socket, which libpq and the `postgres` crate both understand.
+pub fn connection_string() -> eyre::Result<String> {
+    connection_string_to(get_pg_dbname())
+}
+
+/// A libpq connection string for another database of the test framework's Postgres instance
+fn connection_string_to(dbname: &str) -> eyre::Result<String> {
     let pg_config = get_pg_config()?;
-    let mut client = postgres::Config::new()
-        .host(pg_config.host())
-        .port(pg_config.test_port().expect("unable to determine test port"))
-        .user(&get_pg_user())
-        .dbname(&get_pg_dbname())
+    let quote = |value: &str| format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"));
+    Ok(format!(
+        "host={} port={} user={} dbname={}",
+        quote(&pg_config.host()?),
+        pg_config.test_port()?,
+        quote(&get_pg_user()),
+        quote(dbname)
+    ))
+}
+
+pub fn client() -> eyre::Result<(postgres::Client, String)> {
+    let mut client = connection_string()?
+        .parse::<postgres::Config>()
+        .wrap_err("Unable to parse the test database's connection string")?
         .connect(postgres::NoTls)
         .unwrap();
 
@@ -281,6 +364,118 @@ pub fn client() -> eyre::Result<(postgres::Client, String)> {
     Ok((client, session_id))
 }
 
+/// Connect a session to the test database for a plain `#[test]` which needs more than one at a time,
+/// such as to see what one session makes of another's changes.  Postgres is started with
+/// `postgresql_conf`, and the extension created, if that hasn't been done yet.
+///
+/// Unlike a `#[pg_test]`, nothing the session does is rolled back.
+pub fn session(postgresql_conf: Vec<&'static str>) -> eyre::Result<postgres::Client> {
+    initialize_test_framework(postgresql_conf)?;
+    Ok(client()?.0)
+}
+
+/// Dump the test database with `pg_dump`, restore the dump into a new database named `dbname`,
+/// replacing any database of that name, and connect a session to it, for a plain `#[test]` checking
+/// what survives a dump and restore.  Postgres is started with `postgresql_conf`, and the extension
+/// created, if that hasn't been done yet.
+///
+/// Only what's been committed is dumped, so the rows to check are best written with [`session()`].
+/// The database is dropped when the returned [`RestoredDatabase`] is, even if the test panics.
+pub fn dump_and_restore(
+    postgresql_conf: Vec<&'static str>,
+    dbname: &str,
+) -> eyre::Result<RestoredDatabase> {
+    let mut client = session(postgresql_conf.clone())?;
+    let pg_config = get_pg_config()?;
+    let quoted = quote_database_name(dbname);
+    client.batch_execute(&format!("DROP DATABASE IF EXISTS {}", quoted))?;
+    client.batch_execute(&format!("CREATE DATABASE {}", quoted))?;
+    // from here on, whatever goes wrong, the database is dropped
+    let mut restored =
+        RestoredDatabase { client: None, dbname: dbname.to_string(), postgresql_conf };
+
+    let dump = Command::new(pg_config.pg_dump_path()?)
+        .arg("--dbname")
+        .arg(connection_string()?)
+        .output()
+        .wrap_err("unable to run pg_dump")?;
+    if !dump.status.success() {
+        return Err(eyre!("pg_dump failed:\n{}", String::from_utf8_lossy(dump.stderr.as_slice())));
+    }
+
+    let mut psql = Command::new(pg_config.psql_path()?)
+        .arg("-Xq")
+        .arg("-v")
+        .arg("ON_ERROR_STOP=1")
+        .arg("--dbname")
+        .arg(connection_string_to(dbname)?)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err("unable to run psql")?;
+    psql.stdin.take().unwrap().write_all(&dump.stdout)?;
+    let restore = psql.wait_with_output()?;
+    if !restore.status.success() {
+        return Err(eyre!(
+            "restoring the dump into `{}` failed:\n{}",
+            dbname,
+            String::from_utf8_lossy(restore.stderr.as_slice())
+        ));
+    }
+
+    restored.client = Some(
+        connection_string_to(dbname)?
+            .parse::<postgres::Config>()
+            .wrap_err("Unable to parse the restored database's connection string")?
+            .connect(postgres::NoTls)
+            .wrap_err_with(|| format!("Unable to connect to the restored database `{}`", dbname))?,
+    );
+    Ok(restored)
+}
+
+/// A session connected to the database [`dump_and_restore()`] restored, which drops the database
+/// when it's dropped
+pub struct RestoredDatabase {
+    client: Option<postgres::Client>,
+    dbname: String,
+    postgresql_conf: Vec<&'static str>,
+}
+
+impl std::ops::Deref for RestoredDatabase {
+    type Target = postgres::Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client.as_ref().expect("the restored database isn't connected")
+    }
+}
+
+impl std::ops::DerefMut for RestoredDatabase {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client.as_mut().expect("the restored database isn't connected")
+    }
+}
+
+impl Drop for RestoredDatabase {
+    fn drop(&mut self) {
+        // `DROP DATABASE` waits a few seconds for the closed session's backend to exit
+        if let Some(client) = self.client.take() {
+            let _ = client.close();
+        }
+        let dropped = session(self.postgresql_conf.clone()).and_then(|mut client| {
+            let sql = format!("DROP DATABASE IF EXISTS {}", quote_database_name(&self.dbname));
+            client.batch_execute(&sql).wrap_err("unable to drop the restored database")
+        });
+        if let Err(e) = dropped {
+            eprintln!("the restored database `{}` was left behind: {:?}", self.dbname, e);
+        }
+    }
+}
+
+fn quote_database_name(dbname: &str) -> String {
+    format!("\"{}\"", dbname.replace('"', "\"\""))
+}
+
 fn install_extension() -> eyre::Result<()> {
     eprintln!("installing extension");
     let profile = std::env::var("PGX_BUILD_PROFILE").unwrap_or("debug".into());
@@ -381,7 +576,7 @@ fn install_extension() -> eyre::Result<()> {
     Ok(())
 }
 
-fn initdb(postgresql_conf: Vec<&'static str>) -> eyre::Result<()> {
+fn initdb(postgresql_conf: Vec<&'static str>, pglog: &mut File) -> eyre::Result<()> {
     let pgdata = get_pgdata_path()?;
 
     if !pgdata.is_dir() {
@@ -393,8 +588,8 @@ fn initdb(postgresql_conf: Vec<&'static str>) -> eyre::Result<()> {
             .args(get_c_locale_flags())
             .arg("-D")
             .arg(pgdata.to_str().unwrap())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         let command_str = format!("{:?}", command);
 
@@ -412,12 +607,17 @@ fn initdb(postgresql_conf: Vec<&'static str>) -> eyre::Result<()> {
             )
         })?;
 
+        pglog
+            .write_all(&output.stdout)
+            .and_then(|_| pglog.write_all(&output.stderr))
+            .wrap_err("couldn't write initdb's output to the Postgres log")?;
+
         if !output.status.success() {
             return Err(eyre!(
                 "Failed to initialize database using command: {}\n\n{}{}",
                 command_str,
-                String::from_utf8(output.stdout).unwrap(),
-                String::from_utf8(output.stderr).unwrap()
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
             ));
         }
     }
@@ -449,42 +649,94 @@ fn modify_postgresql_conf(pgdata: PathBuf, postgresql_conf: Vec<&'static str>) -
     Ok(())
 }
 
-fn start_pg(loglines: LogLines) -> eyre::Result<String> {
+fn start_pg(loglines: LogLines, pglog: File) -> eyre::Result<String> {
     let pg_config = get_pg_config()?;
-    let mut command =
-        Command::new(pg_config.postmaster_path().wrap_err("unable to determine postmaster path")?);
-    command
-        .arg("-D")
-        .arg(get_pgdata_path()?.to_str().unwrap())
-        .arg("-h")
-        .arg(pg_config.host())
-        .arg("-p")
-        .arg(pg_config.test_port().expect("unable to determine test port").to_string())
-        // Redirecting logs to files can hang the test framework, override it
-        .args(["-c", "log_destination=stderr", "-c", "logging_collector=off"])
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::piped());
+    let ports = pg_config.test_ports()?;
+    for port in ports.iter().copied() {
+        if !pg_config.socket_only() && std::net::TcpListener::bind(("localhost", port)).is_err() {
+            eprintln!("{}", format!("port {port} is in use, trying another").yellow());
+            continue;
+        }
 
-    let command_str = format!("{command:?}");
+        let mut command = Command::new(
+            pg_config.postmaster_path().wrap_err("unable to determine postmaster path")?,
+        );
+        command
+            .arg("-D")
+            .arg(get_pgdata_path()?.to_str().unwrap())
+            .arg("-h")
+            .arg(pg_config.listen_addresses())
+            .arg("-p")
+            .arg(port.to_string())
+            // Redirecting logs to files can hang the test framework, override it
+            .args(["-c", "log_destination=stderr", "-c", "logging_collector=off"])
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::piped());
+
+        let command_str = format!("{command:?}");
 
-    // start Postgres and monitor its stderr in the background
-    // also notify the main thread when it's ready to accept connections
-    let session_id = monitor_pg(command, command_str, loglines);
+        // start Postgres and monitor its stderr in the background
+        // also notify the main thread when it's ready to accept connections
+        let pglog = pglog.try_clone().wrap_err("couldn't share the Postgres log")?;
+        match monitor_pg(command, command_str, loglines.clone(), pglog) {
+            PostmasterStartup::Ready(session_id) => {
+                TEST_PORT.set(port).expect("Postgres was already started");
+                return Ok(session_id);
+            }
+            // another process took the port between our check and Postgres binding it
+            PostmasterStartup::PortInUse => {
+                eprintln!("{}", format!("port {port} is in use, trying another").yellow());
+            }
+            PostmasterStartup::Failed => {
+                return Err(eyre!("Postgres failed to start\n{}", crash_log_tail()))
+            }
+        }
+    }
+
+    Err(eyre!(
+        "Postgres failed to start: every port it may use is in use ({}).  Set a different range with `testing_port_range` in `{}` or `PGX_TEST_PORT_RANGE`",
+        ports.iter().map(|port| port.to_string()).collect::<Vec<_>>().join(", "),
+        Pgx::config_toml()?.display(),
+    ))
+}
+
+enum PostmasterStartup {
+    /// Postgres is ready to accept connections, and this is its session id
+    Ready(String),
+    /// Postgres exited because its port, or Unix socket, was already in use
+    PortInUse,
+    /// Postgres exited for some other reason, which it logged
+    Failed,
+}
 
-    Ok(session_id)
+/// Is `line` Postgres complaining that its TCP port or Unix socket is already in use?
+fn is_port_in_use(line: &str) -> bool {
+    line.contains("Address already in use")
+        || (line.contains(".s.PGSQL.") && line.contains("already exists"))
 }
 
-fn monitor_pg(mut command: Command, cmd_string: String, loglines: LogLines) -> String {
+fn monitor_pg(
+    mut command: Command,
+    cmd_string: String,
+    loglines: LogLines,
+    mut pglog: File,
+) -> PostmasterStartup {
     let (sender, receiver) = std::sync::mpsc::channel();
 
     std::thread::spawn(move || {
         let mut child = command.spawn().expect("postmaster didn't spawn");
 
         let pid = child.id();
+        let running = Arc::new(AtomicBool::new(true));
         // Add a shutdown hook so we can terminate it when the test framework
         // exits. TODO: Consider finding a way to handle cases where we fail to
         // clean up due to a SIGNAL?
+        let still_running = running.clone();
         add_shutdown_hook(move || unsafe {
+            if !still_running.load(Ordering::SeqCst) {
+                // it failed to start, and its pid may already belong to something else
+                return;
+            }
             libc::kill(pid as libc::pid_t, libc::SIGTERM);
             let message_string = std::ffi::CString::new(
                 format!("stopping postgres (pid={pid})\n").bold().blue().to_string(),
@@ -502,6 +754,7 @@ fn monitor_pg(mut command: Command, cmd_string: String, loglines: LogLines) -> S
 
         let regex = regex::Regex::new(r#"\[.*?\] \[.*?\] \[(?P<session_id>.*?)\]"#).unwrap();
         let mut is_started_yet = false;
+        let mut port_in_use = false;
         let mut lines = reader.lines();
         while let Some(Ok(line)) = lines.next() {
             let session_id = match get_named_capture(&regex, "session_id", &line) {
@@ -511,10 +764,14 @@ fn monitor_pg(mut command: Command, cmd_string: String, loglines: LogLines) -> S
 
             if line.contains("database system is ready to accept connections") {
                 // Postgres says it's ready to go
-                sender.send(session_id.clone()).unwrap();
+                sender.send(PostmasterStartup::Ready(session_id.clone())).unwrap();
                 is_started_yet = true;
             }
 
+            if !is_started_yet && is_port_in_use(&line) {
+                port_in_use = true;
+            }
+
             if !is_started_yet || line.contains("TMSG: ") {
                 eprintln!("{}", line.cyan());
             }
@@ -533,11 +790,28 @@ fn monitor_pg(mut command: Command, cmd_string: String, loglines: LogLines) -> S
             //     eprintln!("{}", line.bold().purple());
             // }
 
+            // it's only for reading after a failure, which shouldn't turn into another one
+            let _ = writeln!(pglog, "{line}");
+
             let mut loglines = loglines.lock().unwrap();
             let session_lines = loglines.entry(session_id).or_insert_with(Vec::new);
             session_lines.push(line);
         }
 
+        if !is_started_yet {
+            // Postgres exited before it was ready, so reap it before saying why
+            let _ = child.wait();
+            running.store(false, Ordering::SeqCst);
+            sender
+                .send(if port_in_use {
+                    PostmasterStartup::PortInUse
+                } else {
+                    PostmasterStartup::Failed
+                })
+                .unwrap();
+            return;
+        }
+
         // wait for Postgres to really finish
         match child.try_wait() {
             Ok(status) => {
@@ -551,7 +825,7 @@ fn monitor_pg(mut command: Command, cmd_string: String, loglines: LogLines) -> S
 
     // wait for Postgres to indicate it's ready to accept connection
     // and return its pid when it is
-    receiver.recv().expect("Postgres failed to start")
+    receiver.recv().unwrap_or(PostmasterStartup::Failed)
 }
 
 fn dropdb() -> eyre::Result<()> {
@@ -563,7 +837,7 @@ fn dropdb() -> eyre::Result<()> {
         .env_remove("PGUSER")
         .arg("--if-exists")
         .arg("-h")
-        .arg(pg_config.host())
+        .arg(pg_config.host()?)
         .arg("-p")
         .arg(pg_config.test_port().expect("unable to determine test port").to_string())
         .arg(get_pg_dbname())
@@ -608,6 +882,10 @@ fn get_extension_name() -> String {
         .replace("-", "_")
 }
 
+fn get_pglog_path() -> eyre::Result<PathBuf> {
+    get_pg_config()?.test_log_file()
+}
+
 fn get_pgdata_path() -> eyre::Result<PathBuf> {
     let mut target_dir = get_target_dir()?;
     target_dir.push(&format!("pgx-test-data-{}", pg_sys::get_pg_major_version_num()));
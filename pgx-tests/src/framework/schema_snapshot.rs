@@ -0,0 +1,327 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use eyre::{eyre, WrapErr};
+use object::{Object, ObjectSymbol};
+use owo_colors::OwoColorize;
+use pgx::pgx_sql_entity_graph::{ControlFile, PgExportAbiEntity, PgxSql, SqlGraphEntity};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// The type of the `__pgx_marker` function created by [`pgx::pg_sql_graph_magic!()`]
+pub type PgxMarker = extern "Rust" fn(()) -> ControlFile;
+
+/// Generates the SQL schema of the extension whose `__pgx_marker` is `marker`, from within the
+/// current process.
+///
+/// This runs the same entity-graph-to-SQL pipeline as `cargo pgx schema`, but rather than building
+/// the extension's shared library and loading it, it calls the `__pgx_internals_*` functions
+/// linked into the running executable.  As such it must be called from a test binary of the
+/// extension crate itself, such as its `#[cfg(test)]` unit tests, and doesn't need Postgres to be
+/// running.
+///
+/// The extension's name is usually `env!("CARGO_PKG_NAME")`.  Most users want the
+/// [`assert_schema_snapshot!()`](macro@crate::assert_schema_snapshot) macro instead.
+pub fn generate_schema(marker: PgxMarker, extension_name: &str) -> eyre::Result<String> {
+    let mut entities = vec![SqlGraphEntity::ExtensionRoot(marker(()))];
+    for address in linked_functions(marker, "__pgx_internals")? {
+        // SAFETY: every `__pgx_internals_*` function has this signature
+        let entity = unsafe {
+            let func: extern "Rust" fn() -> SqlGraphEntity = std::mem::transmute(address);
+            func()
+        };
+        entities.push(entity);
+    }
+    if entities.len() == 1 {
+        return Err(eyre!("no SQL entities were found in the current executable"));
+    }
+
+    let versioned_so = match &entities[0] {
+        SqlGraphEntity::ExtensionRoot(control) => control.module_pathname.is_none(),
+        _ => unreachable!(),
+    };
+    // the schema is generated for the Postgres version this test binary was built for
+    let pg_version = (pgx::pg_sys::PG_VERSION_NUM / 10000) as u16;
+    PgxSql::build(entities.into_iter(), extension_name.to_string(), versioned_so, pg_version)
+        .wrap_err("SQL generation error")?
+        .to_sql()
+}
+
+/// Generates the C header of the `#[pg_export_abi]` functions of the extension whose
+/// `__pgx_marker` is `marker`, as `cargo pgx schema --emit-header` does, from within the current
+/// process.
+///
+/// Like [`generate_schema()`], it must be called from a test binary of the extension crate itself.
+pub fn generate_abi_header(marker: PgxMarker, extension_name: &str) -> eyre::Result<String> {
+    let mut exports = Vec::new();
+    for address in linked_functions(marker, "__pgx_abi_export_")? {
+        // SAFETY: every `__pgx_abi_export_*` function has this signature
+        let export = unsafe {
+            let func: extern "Rust" fn() -> PgExportAbiEntity = std::mem::transmute(address);
+            func()
+        };
+        exports.push(export);
+    }
+    Ok(PgExportAbiEntity::c_header(extension_name, &exports))
+}
+
+/// The addresses of the functions linked into the running executable whose names start with
+/// `prefix`, in the order of their names
+fn linked_functions(marker: PgxMarker, prefix: &str) -> eyre::Result<Vec<usize>> {
+    let exe = std::env::current_exe().wrap_err("couldn't find the current executable")?;
+    let exe_data = std::fs::read(&exe)
+        .wrap_err_with(|| format!("couldn't read the current executable `{}`", exe.display()))?;
+    let exe_obj = object::File::parse(&*exe_data)
+        .wrap_err_with(|| format!("couldn't parse the current executable `{}`", exe.display()))?;
+
+    let mut marker_address = None;
+    let mut functions = BTreeSet::new();
+    for symbol in exe_obj.symbols().filter(|symbol| symbol.is_definition()) {
+        let name = match symbol.name() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        // Mac will prefix symbols with `_`
+        #[cfg(target_os = "macos")]
+        let name = name.strip_prefix('_').unwrap_or(name);
+
+        if name == "__pgx_marker" {
+            marker_address = Some(symbol.address());
+        } else if name.starts_with(prefix) {
+            functions.insert((name.to_string(), symbol.address()));
+        }
+    }
+
+    // The executable may have been loaded anywhere, so find out where by comparing the address
+    // `__pgx_marker` actually has to the one in the symbol table
+    let marker_address = marker_address.ok_or_else(|| {
+        eyre!(
+            "`__pgx_marker` isn't in the symbol table of `{}`, has it been stripped?",
+            exe.display()
+        )
+    })?;
+    let load_bias = (marker as usize).wrapping_sub(marker_address as usize);
+    Ok(functions
+        .into_iter()
+        .map(|(_name, address)| address.wrapping_add(load_bias as u64) as usize)
+        .collect())
+}
+
+/// How [`assert_schema_snapshot!()`](macro@crate::assert_schema_snapshot) compares the generated schema to the snapshot
+#[derive(Debug, Clone, Default)]
+pub struct SchemaSnapshotOptions {
+    /// Keep the `-- src/file.rs:line` comments that precede each entity.  They're removed by
+    /// default so that moving code around doesn't change the snapshot.
+    pub source_locations: bool,
+}
+
+/// The function behind [`assert_schema_snapshot!()`](macro@crate::assert_schema_snapshot)
+///
+/// # Panics
+///
+/// If the schema can't be generated, or if it differs from the snapshot at `snapshot_path` and the
+/// `UPDATE_SNAPSHOT` environment variable isn't set to `1`.
+#[track_caller]
+pub fn assert_schema_snapshot(
+    marker: PgxMarker,
+    extension_name: &str,
+    snapshot_path: impl AsRef<Path>,
+    options: SchemaSnapshotOptions,
+) {
+    let snapshot_path = snapshot_path.as_ref();
+    let actual = match generate_schema(marker, extension_name) {
+        Ok(sql) => normalize(&sql, &options),
+        Err(e) => panic!("couldn't generate the schema of `{}`: {:?}", extension_name, e),
+    };
+
+    if std::env::var("UPDATE_SNAPSHOT").as_deref() == Ok("1") {
+        if let Some(parent) = snapshot_path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|e| {
+                panic!("couldn't create directory `{}`: {}", parent.display(), e)
+            });
+        }
+        std::fs::write(snapshot_path, &actual).unwrap_or_else(|e| {
+            panic!("couldn't write schema snapshot `{}`: {}", snapshot_path.display(), e)
+        });
+        return;
+    }
+
+    let expected = match std::fs::read_to_string(snapshot_path) {
+        Ok(expected) => normalize(&expected, &options),
+        Err(e) => panic!(
+            "couldn't read schema snapshot `{}`: {}\nRun with `UPDATE_SNAPSHOT=1` to create it",
+            snapshot_path.display(),
+            e
+        ),
+    };
+
+    if expected != actual {
+        panic!(
+            "the generated schema doesn't match the snapshot `{}`:\n\n{}\nRun with `UPDATE_SNAPSHOT=1` to update it",
+            snapshot_path.display(),
+            diff(&expected, &actual)
+        );
+    }
+}
+
+/// Asserts that the SQL schema of the current extension matches the snapshot at a path relative
+/// to the crate's `Cargo.toml`.
+///
+/// Use it from a plain `#[test]` in the extension crate, which doesn't need Postgres to be running.
+/// If the schema differs from the snapshot, the test fails with a diff between the two.  Running
+/// the test with the `UPDATE_SNAPSHOT=1` environment variable writes the current schema to the
+/// snapshot instead.
+///
+/// The `-- src/file.rs:line` comments that precede each entity aren't part of the snapshot, unless
+/// `source_locations = true` is given.
+///
+/// ```rust,ignore
+/// #[cfg(test)]
+/// mod schema_tests {
+///     #[test]
+///     fn schema_is_unchanged() {
+///         pgx_tests::assert_schema_snapshot!("sql/expected.sql");
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_schema_snapshot {
+    ($path:expr) => {
+        $crate::assert_schema_snapshot!($path, source_locations = false)
+    };
+    ($path:expr, source_locations = $source_locations:expr) => {
+        $crate::assert_schema_snapshot(
+            crate::__pgx_marker,
+            env!("CARGO_PKG_NAME"),
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path),
+            $crate::SchemaSnapshotOptions { source_locations: $source_locations },
+        )
+    };
+}
+
+fn normalize(sql: &str, options: &SchemaSnapshotOptions) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    for line in sql.lines() {
+        if !options.source_locations && is_source_location(line) {
+            continue;
+        }
+        normalized.push_str(line.trim_end());
+        normalized.push('\n');
+    }
+    normalized
+}
+
+/// Is `line` a `-- src/file.rs:123` comment?
+fn is_source_location(line: &str) -> bool {
+    match line.trim().strip_prefix("-- ").and_then(|location| location.rsplit_once(':')) {
+        Some((file, line)) => {
+            file.ends_with(".rs") && !line.is_empty() && line.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// A line-by-line diff of `expected` and `actual`, with three lines of context around each change
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // only the part between the common prefix and suffix needs a real diff
+    let prefix = expected.iter().zip(&actual).take_while(|(e, a)| e == a).count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let (old, new) =
+        (&expected[prefix..expected.len() - suffix], &actual[prefix..actual.len() - suffix]);
+
+    // longest common subsequence lengths of every pair of suffixes of `old` and `new`
+    let mut lcs = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = expected[..prefix].iter().map(|line| (' ', *line)).collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            changes.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            changes.push(('+', new[j]));
+            j += 1;
+        } else {
+            changes.push(('-', old[i]));
+            i += 1;
+        }
+    }
+    changes.extend(expected[expected.len() - suffix..].iter().map(|line| (' ', *line)));
+
+    let mut output = String::new();
+    let mut last_shown = None;
+    for (index, (kind, line)) in changes.iter().enumerate() {
+        let near_change = changes
+            [index.saturating_sub(CONTEXT)..(index + CONTEXT + 1).min(changes.len())]
+            .iter()
+            .any(|(kind, _)| *kind != ' ');
+        if !near_change {
+            continue;
+        }
+        if last_shown.map_or(index > 0, |last| last + 1 != index) {
+            writeln!(output, "{}", "...".dimmed()).unwrap();
+        }
+        last_shown = Some(index);
+        match kind {
+            '-' => writeln!(output, "{}", format!("-{}", line).red()).unwrap(),
+            '+' => writeln!(output, "{}", format!("+{}", line).green()).unwrap(),
+            _ => writeln!(output, " {}", line).unwrap(),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_locations_are_removed() {
+        let sql = "-- src/lib.rs:12\n-- my_ext::hello\nCREATE FUNCTION hello() ...;  \n";
+        let options = SchemaSnapshotOptions::default();
+        assert_eq!(normalize(sql, &options), "-- my_ext::hello\nCREATE FUNCTION hello() ...;\n");
+
+        let options = SchemaSnapshotOptions { source_locations: true };
+        assert_eq!(
+            normalize(sql, &options),
+            "-- src/lib.rs:12\n-- my_ext::hello\nCREATE FUNCTION hello() ...;\n"
+        );
+    }
+
+    #[test]
+    fn diff_shows_changes_with_context() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let actual = "a\nb\nc\nd\nE\nf\ng\nh\n";
+        let diff = diff(expected, actual);
+        assert!(diff.contains("-e"));
+        assert!(diff.contains("+E"));
+        assert!(diff.contains(" b"));
+        assert!(!diff.contains(" a"));
+    }
+}
@@ -0,0 +1,298 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Regression tests written as SQL scripts with expected output, like Postgres' own
+use eyre::{eyre, WrapErr};
+use owo_colors::OwoColorize;
+use pgx::pg_sys;
+use pgx_pg_config::get_target_dir;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::schema_snapshot::diff;
+use super::{
+    client, connection_string_to, get_extension_name, get_pg_config, initialize_test_framework,
+    query_wrapper,
+};
+
+/// The database the scripts are run in, recreated for each one
+const REGRESS_DBNAME: &str = "pgx_regress";
+
+/// A line in a script which makes the rows of the next query's result compare in any order
+const UNORDERED: &str = "-- pgx: unordered";
+
+/// Run each `sql/*.sql` script in `dir` through `psql`, and compare its output to the matching
+/// `expected/*.out` file, as Postgres' `pg_regress` does.
+///
+/// Each script runs in a new database, in which the extension has been created.  Its statements
+/// are echoed into the output, which is compared after removing trailing whitespace.  The rows of
+/// a query's result are compared in any order when the query is preceded by a `-- pgx: unordered`
+/// comment.  The time zone is `UTC` and the date style is `ISO, MDY`.
+///
+/// Every script is run, and then the differences between all of those whose output didn't match
+/// are returned as the error.  When the `UPDATE_SNAPSHOT` environment variable is set to `1`, the
+/// output is written to the expected files instead, which is what `cargo pgx test --bless` does.
+///
+/// This is usually called through [`pg_sql_regress!()`](macro@crate::pg_sql_regress).
+pub fn run_sql_regress(
+    dir: impl AsRef<Path>,
+    postgresql_conf: Vec<&'static str>,
+) -> eyre::Result<()> {
+    let dir = dir.as_ref();
+    let scripts = find_scripts(&dir.join("sql"))?;
+    if scripts.is_empty() {
+        return Ok(());
+    }
+
+    initialize_test_framework(postgresql_conf)?;
+    let bless = std::env::var("UPDATE_SNAPSHOT").as_deref() == Ok("1");
+    let results_dir = get_target_dir()?
+        .join(format!("pgx-regress-results-{}", pg_sys::get_pg_major_version_num()));
+    std::fs::create_dir_all(&results_dir)
+        .wrap_err_with(|| format!("couldn't create `{}`", results_dir.display()))?;
+
+    let mut failures = String::new();
+    for script in scripts {
+        let name = script.file_stem().unwrap().to_string_lossy().into_owned();
+        let expected_path = dir.join("expected").join(format!("{}.out", name));
+        let results_path = results_dir.join(format!("{}.out", name));
+        let actual = run_script(&script, &results_path)?;
+
+        if bless {
+            if let Some(parent) = expected_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .wrap_err_with(|| format!("couldn't create `{}`", parent.display()))?;
+            }
+            std::fs::write(&expected_path, &actual)
+                .wrap_err_with(|| format!("couldn't write `{}`", expected_path.display()))?;
+            eprintln!("{} {}", "blessed".bold().green(), expected_path.display());
+            continue;
+        }
+
+        match std::fs::read_to_string(&expected_path) {
+            Ok(expected) if normalize(&expected) == normalize(&actual) => {
+                eprintln!("{} {}", "ok".bold().green(), script.display());
+            }
+            Ok(expected) => {
+                eprintln!("{} {}", "FAILED".bold().red(), script.display());
+                failures.push_str(&format!(
+                    "`{}` doesn't match `{}`:\n\n{}\n",
+                    script.display(),
+                    expected_path.display(),
+                    diff(&normalize(&expected), &normalize(&actual))
+                ));
+            }
+            Err(e) => {
+                eprintln!("{} {}", "FAILED".bold().red(), script.display());
+                failures.push_str(&format!("couldn't read `{}`: {}\n", expected_path.display(), e));
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "{}The output of each script is in `{}`.  Run `cargo pgx test --bless` to accept it",
+            failures,
+            results_dir.display()
+        ))
+    }
+}
+
+/// Runs the SQL regression tests in the crate's `tests/` directory, from a `#[test]` function
+/// named `pg_sql_regress`.
+///
+/// Like `#[pg_test]`, it calls `crate::pg_test::setup()`, and starts Postgres with the settings
+/// from `crate::pg_test::postgresql_conf_options()`.  See [`run_sql_regress()`] for how the
+/// scripts in `tests/sql/` are run and compared to `tests/expected/`.
+///
+/// ```rust,ignore
+/// #[cfg(test)]
+/// pub mod pg_test {
+///     pub fn setup(_options: Vec<&str>) {}
+///
+///     pub fn postgresql_conf_options() -> Vec<&'static str> {
+///         vec![]
+///     }
+///
+///     pgx_tests::pg_sql_regress!();
+/// }
+/// ```
+#[macro_export]
+macro_rules! pg_sql_regress {
+    () => {
+        #[test]
+        fn pg_sql_regress() {
+            crate::pg_test::setup(Vec::new());
+            if let Err(e) = $crate::run_sql_regress(
+                ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"),
+                crate::pg_test::postgresql_conf_options(),
+            ) {
+                panic!("{:?}", e)
+            }
+        }
+    };
+}
+
+fn find_scripts(sql_dir: &Path) -> eyre::Result<Vec<PathBuf>> {
+    if !sql_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut scripts = Vec::new();
+    for entry in std::fs::read_dir(sql_dir)
+        .wrap_err_with(|| format!("couldn't read `{}`", sql_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().map_or(false, |extension| extension == "sql") {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+    Ok(scripts)
+}
+
+/// Run `script` through `psql` in a new database, returning its output, which is also written to
+/// `results_path`
+fn run_script(script: &Path, results_path: &Path) -> eyre::Result<String> {
+    let (mut client, _) = client()?;
+    for query in [
+        format!("DROP DATABASE IF EXISTS {};", REGRESS_DBNAME),
+        format!("CREATE DATABASE {};", REGRESS_DBNAME),
+    ] {
+        query_wrapper(Some(query), None, |query, _| client.simple_query(query.unwrap().as_str()))
+            .wrap_err("couldn't create the SQL regression test database")?;
+    }
+    drop(client);
+
+    let connection_string = connection_string_to(REGRESS_DBNAME)?;
+    let mut regress_client = connection_string
+        .parse::<postgres::Config>()
+        .wrap_err("Unable to parse the regression database's connection string")?
+        .connect(postgres::NoTls)
+        .wrap_err("couldn't connect to the SQL regression test database")?;
+    let extension_name = get_extension_name();
+    query_wrapper(
+        Some(format!("CREATE EXTENSION {} CASCADE;", extension_name)),
+        None,
+        |query, _| regress_client.simple_query(query.unwrap().as_str()),
+    )
+    .wrap_err_with(|| format!("couldn't create the extension `{}`", extension_name))?;
+    drop(regress_client);
+
+    // like pg_regress, the script is read from stdin so that errors aren't prefixed by its path,
+    // and stdout and stderr share a file so that errors are interleaved with the statements
+    let input =
+        File::open(script).wrap_err_with(|| format!("couldn't open `{}`", script.display()))?;
+    let output = File::create(results_path)
+        .wrap_err_with(|| format!("couldn't create `{}`", results_path.display()))?;
+    let status = Command::new(get_pg_config()?.psql_path()?)
+        .env_remove("PGDATABASE")
+        .env_remove("PGHOST")
+        .env_remove("PGPORT")
+        .env_remove("PGUSER")
+        .env("PGTZ", "UTC")
+        .env("PGDATESTYLE", "ISO, MDY")
+        .arg("-X")
+        .arg("-a")
+        .arg("-q")
+        .arg("-d")
+        .arg(&connection_string)
+        .stdin(Stdio::from(input))
+        .stdout(Stdio::from(output.try_clone()?))
+        .stderr(Stdio::from(output))
+        .status()
+        .wrap_err("couldn't run psql")?;
+    if !status.success() {
+        return Err(eyre!("psql failed to run `{}`: {}", script.display(), status));
+    }
+
+    std::fs::read_to_string(results_path)
+        .wrap_err_with(|| format!("couldn't read `{}`", results_path.display()))
+}
+
+/// Remove trailing whitespace, and sort the rows of the results which follow an `UNORDERED` line
+fn normalize(output: &str) -> String {
+    let lines = output.lines().map(str::trim_end).collect::<Vec<_>>();
+    let mut normalized = Vec::with_capacity(lines.len());
+    let mut unordered = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        normalized.push(line.to_string());
+        i += 1;
+
+        if line == UNORDERED {
+            unordered = true;
+        } else if unordered && is_header_separator(line) {
+            // the rows run up to the `(N rows)` footer
+            let rows = lines[i..].iter().take_while(|line| !is_row_count(line)).count();
+            let mut sorted = lines[i..i + rows].to_vec();
+            sorted.sort_unstable();
+            normalized.extend(sorted.into_iter().map(String::from));
+            i += rows;
+            unordered = false;
+        }
+    }
+    while normalized.last().map_or(false, |line| line.is_empty()) {
+        normalized.pop();
+    }
+
+    let mut normalized = normalized.join("\n");
+    normalized.push('\n');
+    normalized
+}
+
+/// Is `line` the `----+----` line under a result's column names?
+fn is_header_separator(line: &str) -> bool {
+    line.starts_with('-')
+        && line.chars().all(|c| c == '-' || c == '+')
+        && line.split('+').all(|dashes| dashes.len() >= 3)
+}
+
+/// Is `line` a result's `(N rows)` footer?
+fn is_row_count(line: &str) -> bool {
+    match line.strip_prefix('(').and_then(|line| line.strip_suffix(')')) {
+        Some(count) => match count.split_once(' ') {
+            Some((n, "row" | "rows")) => n.chars().all(|c| c.is_ascii_digit()),
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_whitespace_is_ignored() {
+        assert_eq!(normalize("SELECT 1;\n ?column? \n\n\n"), normalize("SELECT 1;\n ?column?\n"));
+    }
+
+    #[test]
+    fn unordered_rows_are_sorted() {
+        let output = "-- pgx: unordered\nSELECT x FROM t;\n x\n---\n 2\n 1\n(2 rows)\n\nSELECT x FROM t;\n x\n---\n 2\n 1\n(2 rows)\n";
+        assert_eq!(
+            normalize(output),
+            "-- pgx: unordered\nSELECT x FROM t;\n x\n---\n 1\n 2\n(2 rows)\n\nSELECT x FROM t;\n x\n---\n 2\n 1\n(2 rows)\n"
+        );
+    }
+
+    #[test]
+    fn separators_and_footers() {
+        assert!(is_header_separator("---+-----"));
+        assert!(!is_header_separator("-- a comment"));
+        assert!(!is_header_separator("--"));
+        assert!(is_row_count("(1 row)"));
+        assert!(is_row_count("(12 rows)"));
+        assert!(!is_row_count("(a row)"));
+    }
+}
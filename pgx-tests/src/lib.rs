@@ -25,4 +25,6 @@ pub mod pg_test {
     pub fn postgresql_conf_options() -> Vec<&'static str> {
         vec!["shared_preload_libraries='pgx_tests'"]
     }
+
+    crate::pg_sql_regress!();
 }
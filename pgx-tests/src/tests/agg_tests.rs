@@ -0,0 +1,252 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::agg::{Covariance, KahanSum, Welford};
+use pgx::prelude::*;
+use pgx::{Aggregate, AggregateInternalState, ParallelOption};
+
+pub struct AggTestsStddevSamp;
+
+#[pg_aggregate]
+impl Aggregate for AggTestsStddevSamp {
+    const NAME: &'static str = "agg_tests_stddev_samp";
+    const PARALLEL: Option<ParallelOption> = Some(ParallelOption::Safe);
+    type Args = f64;
+    type State = AggregateInternalState<Welford>;
+    type Finalize = Option<f64>;
+
+    fn state(
+        mut current: Self::State,
+        arg: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.get_or_insert_default(fcinfo).push(arg);
+        current
+    }
+
+    fn combine(
+        current: Self::State,
+        other: Self::State,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.combine(other, fcinfo, |current, other| current.merge(&other))
+    }
+
+    fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+        current.serialize(fcinfo)
+    }
+
+    fn deserial(
+        _current: Self::State,
+        buf: Vec<u8>,
+        mut internal: PgBox<Self::State>,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> PgBox<Self::State> {
+        *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+        internal
+    }
+
+    fn finalize(
+        current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        current.get(fcinfo).and_then(Welford::stddev_samp)
+    }
+}
+
+pub struct AggTestsCorr;
+
+#[pg_aggregate]
+impl Aggregate for AggTestsCorr {
+    const NAME: &'static str = "agg_tests_corr";
+    const PARALLEL: Option<ParallelOption> = Some(ParallelOption::Safe);
+    type Args = (f64, f64);
+    type State = AggregateInternalState<Covariance>;
+    type Finalize = Option<f64>;
+
+    fn state(
+        mut current: Self::State,
+        (y, x): Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.get_or_insert_default(fcinfo).push(y, x);
+        current
+    }
+
+    fn combine(
+        current: Self::State,
+        other: Self::State,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.combine(other, fcinfo, |current, other| current.merge(&other))
+    }
+
+    fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+        current.serialize(fcinfo)
+    }
+
+    fn deserial(
+        _current: Self::State,
+        buf: Vec<u8>,
+        mut internal: PgBox<Self::State>,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> PgBox<Self::State> {
+        *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+        internal
+    }
+
+    fn finalize(
+        current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        current.get(fcinfo).and_then(Covariance::corr)
+    }
+}
+
+pub struct AggTestsSum;
+
+#[pg_aggregate]
+impl Aggregate for AggTestsSum {
+    const NAME: &'static str = "agg_tests_sum";
+    const PARALLEL: Option<ParallelOption> = Some(ParallelOption::Safe);
+    type Args = f64;
+    type State = AggregateInternalState<KahanSum>;
+    type Finalize = Option<f64>;
+
+    fn state(
+        mut current: Self::State,
+        arg: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.get_or_insert_default(fcinfo).push(arg);
+        current
+    }
+
+    fn combine(
+        current: Self::State,
+        other: Self::State,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.combine(other, fcinfo, |current, other| current.merge(&other))
+    }
+
+    fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+        current.serialize(fcinfo)
+    }
+
+    fn deserial(
+        _current: Self::State,
+        buf: Vec<u8>,
+        mut internal: PgBox<Self::State>,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> PgBox<Self::State> {
+        *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+        internal
+    }
+
+    fn finalize(
+        current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        current.get(fcinfo).map(KahanSum::sum)
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    /// Asserts that our aggregate's result is within a relative epsilon of the builtin's
+    fn assert_close(ours: Option<f64>, builtin: Option<f64>) {
+        match (ours, builtin) {
+            (Some(ours), Some(builtin)) => assert!(
+                (ours - builtin).abs() <= 1e-9 * builtin.abs().max(1.0),
+                "{} isn't close to the builtin's {}",
+                ours,
+                builtin
+            ),
+            _ => assert_eq!(ours, builtin),
+        }
+    }
+
+    /// Creates `agg_tests_values`, of values far from zero and close together, which is where
+    /// summing squares loses the most precision
+    fn create_values(rows: i32) -> Result<(), pgx::spi::Error> {
+        Spi::run(&format!(
+            "CREATE TABLE agg_tests_values AS
+            SELECT 1e9 + (i * 7919 % 1000) / 7.0::float8 AS y, (i * 104729 % 997)::float8 AS x
+            FROM generate_series(1, {}) AS i",
+            rows
+        ))?;
+        Spi::run("ANALYZE agg_tests_values")
+    }
+
+    fn compare_with_builtins() -> Result<(), pgx::spi::Error> {
+        let (ours, builtin) = Spi::get_two::<f64, f64>(
+            "SELECT agg_tests_stddev_samp(y), stddev_samp(y) FROM agg_tests_values",
+        )?;
+        assert_close(ours, builtin);
+
+        let (ours, builtin) = Spi::get_two::<f64, f64>(
+            "SELECT agg_tests_corr(y, x), corr(y, x) FROM agg_tests_values",
+        )?;
+        assert_close(ours, builtin);
+
+        // `sum(float8)` adds up naively, so compare with the exact sum instead
+        let (ours, exact) = Spi::get_two::<f64, f64>(
+            "SELECT agg_tests_sum(y), sum(y::numeric)::float8 FROM agg_tests_values",
+        )?;
+        assert_close(ours, exact);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_aggregates_match_the_builtins() -> Result<(), pgx::spi::Error> {
+        create_values(10000)?;
+        compare_with_builtins()
+    }
+
+    #[pg_test]
+    fn test_parallel_aggregates_match_the_builtins() -> Result<(), pgx::spi::Error> {
+        create_values(100000)?;
+        Spi::run("SET LOCAL max_parallel_workers_per_gather = 4")?;
+        Spi::run("SET LOCAL parallel_setup_cost = 0")?;
+        Spi::run("SET LOCAL parallel_tuple_cost = 0")?;
+        Spi::run("SET LOCAL min_parallel_table_scan_size = 0")?;
+        Spi::run("SET LOCAL parallel_leader_participation = off")?;
+
+        let plan = Spi::get_one::<pgx::Json>(
+            "EXPLAIN (FORMAT JSON) SELECT agg_tests_stddev_samp(y) FROM agg_tests_values",
+        )?
+        .expect("EXPLAIN returned nothing")
+        .0;
+        assert!(plan.to_string().contains(r#""Partial Mode":"Partial""#), "{}", plan);
+        compare_with_builtins()
+    }
+
+    #[pg_test]
+    fn test_too_few_values_are_null() -> Result<(), pgx::spi::Error> {
+        create_values(1)?;
+        assert_eq!(
+            Spi::get_two::<f64, f64>(
+                "SELECT agg_tests_stddev_samp(y), agg_tests_corr(y, x) FROM agg_tests_values"
+            )?,
+            (None, None)
+        );
+        Spi::run("TRUNCATE agg_tests_values")?;
+        assert_eq!(Spi::get_one::<f64>("SELECT agg_tests_sum(y) FROM agg_tests_values")?, None);
+        Ok(())
+    }
+}
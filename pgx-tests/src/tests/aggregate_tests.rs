@@ -7,7 +7,7 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 use pgx::prelude::*;
-use pgx::{Aggregate, Internal, ParallelOption};
+use pgx::{Aggregate, AggregateInternalState, Internal, ParallelOption};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -142,6 +142,64 @@ impl Aggregate for DemoPercentileDisc {
     }
 }
 
+#[derive(Copy, Clone, Default, Debug)]
+pub struct DemoSortedUnique;
+
+#[pg_aggregate]
+impl Aggregate for DemoSortedUnique {
+    const PARALLEL: Option<ParallelOption> = Some(pgx::aggregate::ParallelOption::Safe);
+    type Args = i32;
+    type State = AggregateInternalState<Vec<i32>>;
+    type Finalize = Vec<i32>;
+
+    fn state(
+        mut current: Self::State,
+        arg: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        current.get_or_insert_default(fcinfo).push(arg);
+        current
+    }
+
+    fn combine(
+        first: Self::State,
+        second: Self::State,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        first.combine(second, fcinfo, |first, second| first.extend(second))
+    }
+
+    fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+        current.serialize(fcinfo)
+    }
+
+    fn deserial(
+        _current: Self::State,
+        buf: Vec<u8>,
+        mut internal: PgBox<Self::State>,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> PgBox<Self::State> {
+        *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+        internal
+    }
+
+    fn finalize(
+        mut current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        let mut values = current.take(fcinfo).unwrap_or_default();
+        values.sort();
+        values.dedup();
+        values
+    }
+}
+
+#[pg_extern]
+fn aggregate_tests_internal_state_outside_aggregate(fcinfo: pg_sys::FunctionCallInfo) -> i32 {
+    AggregateInternalState::new(42, fcinfo).get(fcinfo).copied().unwrap_or_default()
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgx::pg_schema]
 mod tests {
@@ -189,4 +247,86 @@ mod tests {
         );
         assert_eq!(retval, Ok(Some(5)));
     }
+
+    #[pg_test]
+    fn aggregate_demo_sorted_unique() -> Result<(), pgx::spi::Error> {
+        let retval = Spi::get_one::<Vec<i32>>(
+            "SELECT DemoSortedUnique(value) FROM UNNEST(ARRAY [3, 1, 2, 1, 3]) as value;",
+        )?;
+        assert_eq!(retval, Some(vec![1, 2, 3]));
+
+        // no rows, no state
+        let retval = Spi::get_one::<Vec<i32>>(
+            "SELECT DemoSortedUnique(value) FROM UNNEST(ARRAY []::int[]) as value;",
+        )?;
+        assert_eq!(retval, Some(vec![]));
+
+        // the states of the groups are independent
+        let retval = Spi::get_one::<String>(
+            "SELECT string_agg(sorted::text, ';' ORDER BY g) FROM (
+                SELECT value % 2 AS g, DemoSortedUnique(value) AS sorted
+                FROM generate_series(1, 6) AS value GROUP BY value % 2
+            ) AS groups;",
+        )?;
+        assert_eq!(retval.as_deref(), Some("{2,4,6};{1,3,5}"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn aggregate_demo_sorted_unique_parallel() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TABLE demo_parallel AS SELECT value % 1000 AS value FROM generate_series(1, 100000) AS value")?;
+        Spi::run("ANALYZE demo_parallel")?;
+        Spi::run("SET LOCAL max_parallel_workers_per_gather = 4")?;
+        Spi::run("SET LOCAL parallel_setup_cost = 0")?;
+        Spi::run("SET LOCAL parallel_tuple_cost = 0")?;
+        Spi::run("SET LOCAL min_parallel_table_scan_size = 0")?;
+        Spi::run("SET LOCAL parallel_leader_participation = off")?;
+
+        let query = "SELECT DemoSortedUnique(value) FROM demo_parallel";
+        let plan = Spi::get_one::<pgx::Json>(&format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", query))?
+            .expect("EXPLAIN returned nothing")
+            .0;
+        assert!(
+            find_in_plan(&plan, "Partial Mode").contains(&&serde_json::json!("Partial")),
+            "the aggregate wasn't partial: {}",
+            plan
+        );
+        let launched = find_in_plan(&plan, "Workers Launched");
+        assert!(
+            launched.iter().any(|workers| workers.as_i64().unwrap_or_default() > 1),
+            "fewer than two workers were launched: {}",
+            plan
+        );
+
+        let retval = Spi::get_one::<Vec<i32>>(query)?;
+        assert_eq!(retval, Some((0..1000).collect::<Vec<_>>()));
+        Ok(())
+    }
+
+    #[pg_test(
+        error = "an AggregateInternalState can only be used by the functions of an aggregate"
+    )]
+    fn aggregate_internal_state_outside_aggregate() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<i32>("SELECT aggregate_tests_internal_state_outside_aggregate()").map(|_| ())
+    }
+
+    /// The values of every `key` in an `EXPLAIN (FORMAT JSON)` plan
+    fn find_in_plan<'a>(plan: &'a serde_json::Value, key: &str) -> Vec<&'a serde_json::Value> {
+        match plan {
+            serde_json::Value::Object(object) => object
+                .iter()
+                .flat_map(|(k, v)| {
+                    let mut found = find_in_plan(v, key);
+                    if k == key {
+                        found.push(v);
+                    }
+                    found
+                })
+                .collect(),
+            serde_json::Value::Array(array) => {
+                array.iter().flat_map(|v| find_in_plan(v, key)).collect()
+            }
+            _ => vec![],
+        }
+    }
 }
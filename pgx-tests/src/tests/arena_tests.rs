@@ -0,0 +1,191 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::arena;
+use pgx::prelude::*;
+use pgx::AllocatedByRust;
+use std::cell::RefCell;
+
+thread_local! {
+    /// The arenas' contexts that [`arena_tests_remember()`] was given
+    static CONTEXTS: RefCell<Vec<pg_sys::MemoryContext>> = RefCell::new(Vec::new());
+}
+
+fn arena_tests_remember(arena: &arena::Arena) {
+    CONTEXTS.with(|contexts| contexts.borrow_mut().push(arena.memory_context().value()));
+}
+
+fn arena_tests_take_contexts() -> Vec<pg_sys::MemoryContext> {
+    CONTEXTS.with(|contexts| contexts.take())
+}
+
+/// `title`, lowercased, with its words joined by `-`s, using a `String` for each word
+fn arena_tests_slug_on_heap(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for word in title.split_whitespace() {
+        let word = word.to_ascii_lowercase();
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    slug
+}
+
+/// The same, using the arena of the row for each word
+fn arena_tests_slug_in_arena(title: &str) -> String {
+    arena::with_row_arena(|arena| {
+        arena_tests_remember(arena);
+        let mut slug = String::with_capacity(title.len());
+        for word in title.split_whitespace() {
+            let word = arena.alloc_str(word);
+            word.make_ascii_lowercase();
+            if !slug.is_empty() {
+                slug.push('-');
+            }
+            slug.push_str(word);
+        }
+        slug
+    })
+}
+
+// the triggers `benches/arena_trigger.rs` compares, for a table of `(title text, slug text)`
+
+#[pg_trigger]
+fn arena_tests_slug_heap(
+    trigger: &PgTrigger,
+) -> Result<PgHeapTuple<'_, AllocatedByRust>, PgTriggerError> {
+    let mut row = trigger.new().expect("a row-level INSERT or UPDATE trigger").into_owned();
+    let title = row.get_by_name::<String>("title").unwrap().unwrap_or_default();
+    row.set_by_name("slug", arena_tests_slug_on_heap(&title)).unwrap();
+    Ok(row)
+}
+
+#[pg_trigger(arena)]
+fn arena_tests_slug_arena(
+    trigger: &PgTrigger,
+) -> Result<PgHeapTuple<'_, AllocatedByRust>, PgTriggerError> {
+    let mut row = trigger.new().expect("a row-level INSERT or UPDATE trigger").into_owned();
+    let title = row.get_by_name::<String>("title").unwrap().unwrap_or_default();
+    row.set_by_name("slug", arena_tests_slug_in_arena(&title)).unwrap();
+    Ok(row)
+}
+
+#[pg_extern(arena)]
+fn arena_tests_slugs(titles: Vec<String>) -> SetOfIterator<'static, String> {
+    SetOfIterator::new(titles.into_iter().map(|title| arena_tests_slug_in_arena(&title)))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::arena_tests_take_contexts;
+    use pgx::arena;
+    use pgx::prelude::*;
+
+    #[repr(align(64))]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Aligned(u8);
+
+    #[pg_test]
+    fn test_alloc() {
+        arena::with_temp_context(|arena| {
+            let number = arena.alloc(42_i64);
+            *number += 1;
+            assert_eq!(*number, 43);
+
+            let s = arena.alloc_str("Hello");
+            s.make_ascii_uppercase();
+            assert_eq!(s, "HELLO");
+
+            assert_eq!(arena.alloc_slice(&[1, 2, 3]), &[1, 2, 3]);
+            assert_eq!(arena.alloc_slice::<i32>(&[]), &[] as &[i32]);
+
+            let aligned = arena.alloc(Aligned(7));
+            assert_eq!(aligned as *const Aligned as usize % 64, 0);
+            assert_eq!(arena.alloc_slice(&[Aligned(1), Aligned(2)]), &[Aligned(1), Aligned(2)]);
+            let _: &mut () = arena.alloc(());
+        })
+    }
+
+    #[pg_test]
+    fn test_reset_and_reused() {
+        let first = arena::with_temp_context(|arena| {
+            arena.alloc_str(&"x".repeat(100_000));
+            arena.memory_context().value()
+        });
+        unsafe {
+            assert!((*first).isReset);
+        }
+
+        // nested ones have their own
+        let (second, nested) = arena::with_temp_context(|arena| {
+            let nested = arena::with_temp_context(|nested| nested.memory_context().value());
+            (arena.memory_context().value(), nested)
+        });
+        assert_eq!(second, first);
+        assert_ne!(nested, first);
+    }
+
+    #[pg_test]
+    fn test_reset_when_unwinding() {
+        let mut context = std::ptr::null_mut();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena::with_temp_context(|arena| {
+                arena.alloc_str("lost");
+                context = arena.memory_context().value();
+                panic!("unwinding");
+            })
+        }));
+        assert!(result.is_err());
+        unsafe {
+            assert!((*context).isReset);
+        }
+    }
+
+    #[pg_test]
+    fn test_trigger_row_arena() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TABLE tests.arena_tests_posts (title text, slug text);
+            CREATE TRIGGER arena_tests_posts_slug BEFORE INSERT ON tests.arena_tests_posts
+                FOR EACH ROW EXECUTE FUNCTION arena_tests_slug_arena()",
+        )?;
+        arena_tests_take_contexts();
+        Spi::run(
+            "INSERT INTO tests.arena_tests_posts (title)
+                VALUES ('Hello  World'), ('Arenas Are Fast')",
+        )?;
+        let slugs = Spi::get_one::<String>(
+            "SELECT string_agg(slug, ',' ORDER BY slug) FROM tests.arena_tests_posts",
+        )?;
+        assert_eq!(slugs, Some("arenas-are-fast,hello-world".into()));
+
+        // each row had the same pooled context, which was reset between them
+        let contexts = arena_tests_take_contexts();
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts[0], contexts[1]);
+        unsafe {
+            assert!((*contexts[0]).isReset);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_srf_row_arena() -> Result<(), pgx::spi::Error> {
+        arena_tests_take_contexts();
+        let slugs = Spi::get_one::<String>(
+            "SELECT string_agg(slug, ',') FROM arena_tests_slugs(ARRAY['A B', 'C D E', 'F']) AS slug",
+        )?;
+        assert_eq!(slugs, Some("a-b,c-d-e,f".into()));
+        assert_eq!(arena_tests_take_contexts().len(), 3);
+        Ok(())
+    }
+}
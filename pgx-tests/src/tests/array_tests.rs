@@ -165,6 +165,24 @@ fn arr_sort_uniq(arr: Array<i32>) -> Vec<i32> {
     v
 }
 
+#[pg_extern]
+fn array_from_iterator_squares(n: i32) -> ArrayFromIterator<'static, i64> {
+    ArrayFromIterator::from_values((1..=n as i64).map(|i| i * i))
+}
+
+#[pg_extern]
+fn vec_squares(n: i32) -> Vec<i64> {
+    (1..=n as i64).map(|i| i * i).collect()
+}
+
+// `filter` hides how many elements there'll be
+#[pg_extern]
+fn array_from_iterator_nonempty_words<'a>(input: &'a str) -> ArrayFromIterator<'a, &'a str> {
+    ArrayFromIterator::new(
+        input.split(',').filter(|_| true).map(|word| Some(word).filter(|word| !word.is_empty())),
+    )
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgx::pg_schema]
 mod tests {
@@ -356,6 +374,39 @@ mod tests {
         assert_eq!(result, Ok(Some(vec![1, 2, 3])));
     }
 
+    #[pg_test]
+    fn test_array_iterator() -> Result<(), pgx::spi::Error> {
+        let squares = Spi::get_one::<Vec<i64>>("SELECT array_from_iterator_squares(5)")?;
+        assert_eq!(squares, Some(vec![1, 4, 9, 16, 25]));
+
+        let empty = Spi::get_one::<Vec<i64>>("SELECT array_from_iterator_squares(0)")?;
+        assert_eq!(empty, Some(vec![]));
+
+        let words = Spi::get_one::<Vec<Option<String>>>(
+            "SELECT array_from_iterator_nonempty_words('a,,b,')",
+        )?;
+        assert_eq!(words, Some(vec![Some("a".into()), None, Some("b".into()), None]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_array_from_iterator_return_type() -> Result<(), pgx::spi::Error> {
+        let return_type = Spi::get_one::<String>(
+            "SELECT prorettype::regtype::text FROM pg_proc WHERE proname = 'array_from_iterator_squares'",
+        )?;
+        assert_eq!(return_type.as_deref(), Some("bigint[]"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_array_from_iterator_matches_vec() -> Result<(), pgx::spi::Error> {
+        let matches = Spi::get_one::<bool>(
+            "SELECT array_from_iterator_squares(1000000) = vec_squares(1000000)",
+        )?;
+        assert_eq!(matches, Some(true));
+        Ok(())
+    }
+
     #[pg_test]
     #[should_panic]
     fn test_arr_sort_uniq_with_null() -> Result<(), pgx::spi::Error> {
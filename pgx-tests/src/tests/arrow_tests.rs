@@ -0,0 +1,192 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::arrow::RecordBatch;
+use pgx::prelude::*;
+
+extension_sql!(
+    r#"
+    CREATE TABLE arrow_tests_scalars (
+        b bool, i2 int2, i4 int4, i8 int8, f4 real, f8 double precision, t text, vc varchar,
+        bytes bytea, d date, ts timestamp, tstz timestamptz, n numeric
+    );
+    INSERT INTO arrow_tests_scalars VALUES
+        (true, 1, 2, 3, 4.5, 6.25, 'seven', 'eight', '\x09', '2023-01-10', '2023-01-10 11:12:13.141516',
+         '2023-01-10 11:12:13.141516+00', 17.5),
+        (false, -1, -2, -3, -4.5, -6.25, '', '', '\x', '1970-01-01', '1999-12-31 23:59:59.999999',
+         '1969-07-20 20:17:40+00', -0.001),
+        (NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL);
+
+    CREATE TABLE arrow_tests_arrays (
+        b bool[], i2 int2[], i4 int4[], i8 int8[], f4 real[], f8 double precision[], t text[],
+        bytes bytea[], d date[], ts timestamp[], tstz timestamptz[], n numeric[]
+    );
+    INSERT INTO arrow_tests_arrays VALUES
+        ('{true,NULL,false}', '{1,NULL}', '{2}', '{3,4}', '{4.5}', '{6.25,NULL}', '{seven,NULL,""}',
+         '{"\\x09",NULL}', '{2023-01-10}', '{"2023-01-10 11:12:13"}', '{"2023-01-10 11:12:13+00"}',
+         '{17.5,NULL,-0.001}'),
+        ('{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}'),
+        (NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL);
+    "#,
+    name = "arrow_tests_tables",
+);
+
+fn arrow_tests_batch(query: &str) -> Result<RecordBatch, pgx::arrow::Error> {
+    Spi::connect(|client| client.select(query, None, None)?.to_record_batch())
+}
+
+#[pg_extern]
+fn arrow_tests_scalars_round_trip() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(b, Option<bool>),
+            name!(i2, Option<i16>),
+            name!(i4, Option<i32>),
+            name!(i8, Option<i64>),
+            name!(f4, Option<f32>),
+            name!(f8, Option<f64>),
+            name!(t, Option<String>),
+            name!(vc, Option<String>),
+            name!(bytes, Option<Vec<u8>>),
+            name!(d, Option<Date>),
+            name!(ts, Option<Timestamp>),
+            name!(tstz, Option<TimestampWithTimeZone>),
+            name!(n, Option<AnyNumeric>),
+        ),
+    >,
+    pgx::arrow::Error,
+> {
+    TableIterator::from_record_batch(&arrow_tests_batch("SELECT * FROM arrow_tests_scalars")?)
+}
+
+#[pg_extern]
+fn arrow_tests_arrays_round_trip() -> Result<
+    TableIterator<
+        'static,
+        (
+            name!(b, Option<Vec<Option<bool>>>),
+            name!(i2, Option<Vec<Option<i16>>>),
+            name!(i4, Option<Vec<Option<i32>>>),
+            name!(i8, Option<Vec<Option<i64>>>),
+            name!(f4, Option<Vec<Option<f32>>>),
+            name!(f8, Option<Vec<Option<f64>>>),
+            name!(t, Option<Vec<Option<String>>>),
+            name!(bytes, Option<Vec<Option<Vec<u8>>>>),
+            name!(d, Option<Vec<Option<Date>>>),
+            name!(ts, Option<Vec<Option<Timestamp>>>),
+            name!(tstz, Option<Vec<Option<TimestampWithTimeZone>>>),
+            name!(n, Option<Vec<Option<AnyNumeric>>>),
+        ),
+    >,
+    pgx::arrow::Error,
+> {
+    TableIterator::from_record_batch(&arrow_tests_batch("SELECT * FROM arrow_tests_arrays")?)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::arrow_tests_batch;
+    use pgx::arrow::array::{Array, Decimal128Array, Int32Array, ListArray, StringArray};
+    use pgx::arrow::datatypes::{DataType, TimeUnit};
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_scalars_to_record_batch() -> Result<(), pgx::arrow::Error> {
+        let batch = arrow_tests_batch("SELECT * FROM arrow_tests_scalars")?;
+        assert_eq!(batch.num_rows(), 3);
+
+        let types =
+            batch.schema().fields().iter().map(|f| f.data_type().clone()).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                DataType::Boolean,
+                DataType::Int16,
+                DataType::Int32,
+                DataType::Int64,
+                DataType::Float32,
+                DataType::Float64,
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Binary,
+                DataType::Date32,
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".to_string())),
+                DataType::Decimal128(38, 3),
+            ]
+        );
+        for column in batch.columns() {
+            assert_eq!(column.null_count(), 1);
+            assert!(column.is_null(2));
+        }
+
+        let i4 = batch.column(2).as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(i4.value(0), 2);
+        let t = batch.column(6).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(t.value(0), "seven");
+        let n = batch.column(12).as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(n.value(0), 17_500);
+        assert_eq!(n.value(1), -1);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_arrays_to_record_batch() -> Result<(), pgx::arrow::Error> {
+        let batch = arrow_tests_batch("SELECT * FROM arrow_tests_arrays")?;
+        assert_eq!(batch.num_rows(), 3);
+
+        let i2 = batch.column(1).as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(i2.value_type(), DataType::Int16);
+        assert_eq!(i2.value_length(0), 2);
+        assert!(i2.value(0).is_null(1));
+        assert_eq!(i2.value_length(1), 0);
+        assert!(i2.is_null(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_scalars_round_trip() -> Result<(), pgx::spi::Error> {
+        let differences = Spi::get_one::<i64>(
+            "SELECT count(*) FROM (
+                (SELECT * FROM arrow_tests_scalars EXCEPT ALL SELECT * FROM arrow_tests_scalars_round_trip())
+                UNION ALL
+                (SELECT * FROM arrow_tests_scalars_round_trip() EXCEPT ALL SELECT * FROM arrow_tests_scalars)
+            ) differences",
+        )?;
+        assert_eq!(differences, Some(0));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_arrays_round_trip() -> Result<(), pgx::spi::Error> {
+        let differences = Spi::get_one::<i64>(
+            "SELECT count(*) FROM (
+                (SELECT * FROM arrow_tests_arrays EXCEPT ALL SELECT * FROM arrow_tests_arrays_round_trip())
+                UNION ALL
+                (SELECT * FROM arrow_tests_arrays_round_trip() EXCEPT ALL SELECT * FROM arrow_tests_arrays)
+            ) differences",
+        )?;
+        assert_eq!(differences, Some(0));
+        Ok(())
+    }
+
+    #[pg_test(error = "column `p` has type OID 600, which can't be converted to Arrow")]
+    fn test_unsupported_type() -> Result<(), pgx::arrow::Error> {
+        arrow_tests_batch("SELECT point(1, 2) AS p").map(|_| ())
+    }
+
+    #[pg_test(error = "column `n` contains `NaN`, which is out of range")]
+    fn test_numeric_nan() -> Result<(), pgx::arrow::Error> {
+        arrow_tests_batch("SELECT 'NaN'::numeric AS n").map(|_| ())
+    }
+}
@@ -0,0 +1,141 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::PgBackendLocal;
+
+static DISCARDED: PgBackendLocal<i32> = PgBackendLocal::new(|| 0).reset_on_discard_all();
+static SETTINGS: PgBackendLocal<i32> = PgBackendLocal::new(|| 0).reset_on_reset_all();
+static ABORTED: PgBackendLocal<i32> = PgBackendLocal::new(|| 0).reset_on_abort();
+static KEPT: PgBackendLocal<i32> = PgBackendLocal::new(|| 0);
+
+fn next(local: &'static PgBackendLocal<i32>) -> i32 {
+    local.with_mut(|value| {
+        *value += 1;
+        *value
+    })
+}
+
+/// The next value of each of the statics, which the SQL regression tests also call
+#[pg_extern]
+fn backend_local_tests_next(
+) -> TableIterator<'static, (name!(discarded, i32), name!(aborted, i32), name!(kept, i32))> {
+    TableIterator::once((next(&DISCARDED), next(&ABORTED), next(&KEPT)))
+}
+
+static EVENTS: PgBackendLocal<Vec<String>> = PgBackendLocal::new(Vec::new);
+
+#[pg_init]
+fn backend_local_tests_init() {
+    pgx::on_discard(|discard| EVENTS.with_mut(|events| events.push(format!("{:?}", discard))));
+    pgx::on_reset_all(|| EVENTS.with_mut(|events| events.push("ResetAll".to_string())));
+}
+
+/// The `DISCARD`s and `RESET ALL`s run since this was last called, which the SQL regression tests
+/// also call
+#[pg_extern]
+fn backend_local_tests_events() -> Vec<String> {
+    EVENTS.with_mut(std::mem::take)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::{next, ABORTED, DISCARDED, KEPT, SETTINGS};
+    use pgx::expr::PgExpression;
+    use pgx::prelude::*;
+    use pgx::PgBackendLocal;
+
+    static LAZY: PgBackendLocal<Vec<i32>> = PgBackendLocal::new(|| vec![1, 2, 3]);
+
+    #[pg_test]
+    fn test_initialized_when_first_used() {
+        LAZY.reset();
+        assert!(!LAZY.is_initialized());
+        assert_eq!(LAZY.with(|values| values.len()), 3);
+        assert!(LAZY.is_initialized());
+
+        LAZY.with_mut(|values| values.push(4));
+        assert_eq!(LAZY.with(|values| values.clone()), vec![1, 2, 3, 4]);
+
+        LAZY.reset();
+        assert_eq!(LAZY.with(|values| values.clone()), vec![1, 2, 3]);
+    }
+
+    #[pg_test]
+    fn test_shared_by_the_backend() -> Result<(), pgx::spi::Error> {
+        let before = next(&KEPT);
+        let (_, _, kept) =
+            Spi::get_three::<i32, i32, i32>("SELECT * FROM backend_local_tests_next()")?;
+        assert_eq!(kept, Some(before + 1));
+        assert_eq!(next(&KEPT), before + 2);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_not_reset_when_a_subtransaction_aborts() -> Result<(), pgx::spi::Error> {
+        // only the whole transaction aborting resets it, and the test's transaction doesn't
+        let before = next(&ABORTED);
+        Spi::run(
+            "DO $$
+            BEGIN
+                PERFORM 1 / 0;
+            EXCEPTION WHEN division_by_zero THEN
+                NULL;
+            END
+            $$",
+        )?;
+        assert_eq!(next(&ABORTED), before + 1);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_discard_and_reset_all_callbacks() -> Result<(), pgx::spi::Error> {
+        // `DISCARD ALL` can't run in a transaction, so the SQL regression tests cover that
+        super::backend_local_tests_events();
+        Spi::run("DISCARD PLANS")?;
+        Spi::run("DISCARD SEQUENCES")?;
+        Spi::run("DISCARD TEMP")?;
+        Spi::run("RESET ALL")?;
+        // resetting a single setting isn't reported
+        Spi::run("RESET work_mem")?;
+        assert_eq!(
+            Spi::get_one::<Vec<String>>("SELECT backend_local_tests_events()")?,
+            Some(vec!["Plans".into(), "Sequences".into(), "Temp".into(), "ResetAll".into()])
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_reset_on_reset_all() -> Result<(), pgx::spi::Error> {
+        next(&SETTINGS);
+        let discarded = next(&DISCARDED);
+        Spi::run("RESET ALL")?;
+        assert_eq!(next(&SETTINGS), 1);
+        assert_eq!(next(&DISCARDED), discarded + 1);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_expression_after_discard_plans() -> Result<(), Box<dyn std::error::Error>> {
+        let expr = PgExpression::parse("$1 + 1", &[PgBuiltInOids::INT4OID.oid()])?;
+        assert_eq!(expr.evaluate_as::<i32>(&[41.into_datum()])?, Some(42));
+        Spi::run("DISCARD PLANS")?;
+        assert_eq!(expr.evaluate_as::<i32>(&[1.into_datum()])?, Some(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_reset_while_in_use() {
+        DISCARDED.with(|_| DISCARDED.reset());
+    }
+}
@@ -0,0 +1,71 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::backend::{self, BackendInfo, BackendState, SignalError};
+    use pgx::prelude::*;
+
+    fn me() -> BackendInfo {
+        backend::iterate()
+            .find(|info| info.pid == backend::my_pid())
+            .expect("the current backend isn't in the snapshot")
+    }
+
+    #[pg_test]
+    fn test_current_backend_is_listed() -> Result<(), pgx::spi::Error> {
+        let me = me();
+        let database = Spi::get_one::<String>("SELECT current_database()::text")?;
+        let user = Spi::get_one::<String>("SELECT session_user::text")?;
+        assert_eq!(me.database_name, database);
+        assert_eq!(me.user_name, user);
+        assert_eq!(me.backend_id, Some(backend::my_backend_id()));
+        assert_eq!(me.backend_type, "client backend");
+        assert_eq!(me.state, Some(BackendState::Active));
+        assert_eq!(me.state.and_then(|state| state.as_str()), Some("active"));
+        assert!(me.query.is_some());
+        assert!(me.backend_start.is_some());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_matches_pg_stat_activity() -> Result<(), pgx::spi::Error> {
+        let pids = Spi::get_one::<Vec<i32>>(
+            "SELECT array_agg(pid ORDER BY pid) FROM pg_catalog.pg_stat_activity",
+        )?
+        .unwrap();
+        let mut ours = backend::iterate().map(|info| info.pid).collect::<Vec<_>>();
+        ours.sort();
+        assert_eq!(ours, pids);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_signal_not_a_backend() {
+        let nobody = BackendInfo { pid: i32::MAX, ..me() };
+        assert_eq!(nobody.cancel(), Err(SignalError::NotABackend(i32::MAX)));
+        assert_eq!(nobody.terminate(), Err(SignalError::NotABackend(i32::MAX)));
+    }
+
+    #[pg_test]
+    fn test_unprivileged_user() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE ROLE backend_tests_nobody; SET LOCAL ROLE backend_tests_nobody")?;
+        let me = me();
+        // the session's superuser role owns the backend, not the current user
+        assert_eq!(me.query, None);
+        assert_eq!(me.state, None);
+        assert!(me.database_name.is_some());
+        assert_eq!(me.cancel(), Err(SignalError::PermissionDenied(me.pid)));
+        Ok(())
+    }
+}
@@ -82,6 +82,100 @@ pub extern "C" fn bgworker_return_value(arg: pg_sys::Datum) {
     .expect("bgworker transaction failed");
 }
 
+#[pg_guard]
+#[no_mangle]
+/// Here we test that a failing scheduled job doesn't stop the others from running
+pub extern "C" fn bgworker_scheduler(_arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    use std::time::Duration;
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(
+        Some(crate::framework::get_pg_dbname()),
+        Some(crate::framework::get_pg_user().as_str()),
+    );
+
+    BackgroundWorker::transaction(|| {
+        Spi::run("CREATE TABLE tests.bgworker_scheduler_test (job TEXT);")
+    })
+    .expect("bgworker transaction failed");
+
+    fn failing_job() {
+        Spi::run("INSERT INTO tests.bgworker_scheduler_test VALUES ('failing')")
+            .expect("insert failed");
+        panic!("scheduled job failed on purpose");
+    }
+
+    fn counting_job() {
+        Spi::run("INSERT INTO tests.bgworker_scheduler_test VALUES ('counting')")
+            .expect("insert failed");
+    }
+
+    run_scheduled(vec![
+        (Schedule::every(Duration::from_millis(10)), failing_job),
+        (Schedule::every(Duration::from_millis(10)), counting_job),
+    ]);
+}
+
+#[pg_guard]
+#[no_mangle]
+/// Here we test that a worker without a database connection can find our database and start a
+/// child worker in it
+pub extern "C" fn bgworker_launcher(_arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(None, None);
+
+    let databases = BackgroundWorker::list_databases();
+    let template0 = databases.iter().find(|db| db.name == "template0").expect("no template0");
+    assert!(template0.is_template && !template0.allow_connections);
+
+    let ours = databases
+        .iter()
+        .find(|db| db.name == crate::framework::get_pg_dbname())
+        .expect("our database is missing");
+    let child = BackgroundWorkerBuilder::new("dynamic_bgworker_database_child")
+        .set_library("pgx_tests")
+        .set_function("bgworker_database_child")
+        .set_argument(ours.oid.into_datum())
+        .enable_spi_access()
+        .set_notify_pid(unsafe { pg_sys::MyProcPid })
+        .load_dynamic();
+    child.wait_for_shutdown().expect("child worker was not tracked");
+}
+
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn bgworker_database_child(arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    let dboid = unsafe { pg_sys::Oid::from_datum(arg, false) }.expect("invalid arg");
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi_by_oid(dboid, None);
+
+    BackgroundWorker::transaction(|| {
+        Spi::run(
+            "CREATE TABLE tests.bgworker_launcher_test AS SELECT current_database()::text AS dbname;",
+        )
+    })
+    .expect("bgworker transaction failed");
+}
+
+#[pg_guard]
+#[no_mangle]
+/// Here we test that a panicking worker writes a crash report, to the directory it's given as its
+/// `extra`, with no more than `arg` a minute
+pub extern "C" fn bgworker_crash(arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    let limit = unsafe { i32::from_datum(arg, false) }.expect("invalid arg");
+    BackgroundWorker::set_crash_report_dir(BackgroundWorker::get_extra());
+    BackgroundWorker::set_crash_report_limit(limit as usize);
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+
+    for step in 1..=40 {
+        pgx::breadcrumb!("step {}", step);
+    }
+    panic!("crashed on purpose");
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgx::pg_schema]
 mod tests {
@@ -91,6 +185,7 @@ mod tests {
     use pgx::bgworkers::*;
     use pgx::prelude::*;
     use pgx::{pg_sys, IntoDatum};
+    use std::path::PathBuf;
 
     #[pg_test]
     fn test_dynamic_bgworker() {
@@ -155,4 +250,157 @@ mod tests {
 
         assert_eq!(Ok(Some(123)), Spi::get_one::<i32>("SELECT v FROM tests.bgworker_test_return;"));
     }
+
+    #[pg_test]
+    fn test_bgworker_scheduler_isolates_failures() -> Result<(), pgx::spi::Error> {
+        let worker = BackgroundWorkerBuilder::new("dynamic_bgworker_scheduler")
+            .set_library("pgx_tests")
+            .set_function("bgworker_scheduler")
+            .enable_spi_access()
+            .set_notify_pid(unsafe { pg_sys::MyProcPid })
+            .load_dynamic();
+        worker.wait_for_startup().expect("no PID from the worker");
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        worker.terminate().wait_for_shutdown().expect("aborted shutdown");
+
+        let counted = Spi::get_one::<i64>(
+            "SELECT count(*) FROM tests.bgworker_scheduler_test WHERE job = 'counting'",
+        )?;
+        assert!(counted.unwrap_or_default() > 1);
+
+        // the failing job's insert was rolled back along with its subtransaction
+        let failed = Spi::get_one::<i64>(
+            "SELECT count(*) FROM tests.bgworker_scheduler_test WHERE job = 'failing'",
+        )?;
+        assert_eq!(failed, Some(0));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_bgworker_launcher_starts_database_child() -> Result<(), pgx::spi::Error> {
+        let launcher = BackgroundWorkerBuilder::new("dynamic_bgworker_launcher")
+            .set_library("pgx_tests")
+            .set_function("bgworker_launcher")
+            .enable_spi_access()
+            .set_notify_pid(unsafe { pg_sys::MyProcPid })
+            .load_dynamic();
+        // the launcher only exits once its child has
+        launcher.wait_for_shutdown().expect("aborted shutdown");
+
+        let dbname = Spi::get_one::<String>("SELECT dbname FROM tests.bgworker_launcher_test")?;
+        assert_eq!(dbname.as_deref(), Some(crate::framework::get_pg_dbname()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_schedule_cron_next_after() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // 2023-01-01 00:00:00 UTC, a Sunday
+        let start = UNIX_EPOCH + Duration::from_secs(1_672_531_200);
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+
+        let every_five = Schedule::cron("*/5 * * * *").unwrap();
+        assert_eq!(every_five.next_after(start), Some(minutes(5)));
+        assert_eq!(every_five.next_after(minutes(7)), Some(minutes(10)));
+
+        let daily = Schedule::cron("@daily").unwrap();
+        assert_eq!(daily.next_after(start), Some(minutes(24 * 60)));
+
+        // the following Monday at 09:30
+        let weekdays = Schedule::cron("30 9 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(start), Some(minutes(24 * 60 + 9 * 60 + 30)));
+
+        // day-of-month and day-of-week are OR'd together when both are restricted:  the 15th,
+        // or any Sunday, whichever comes first
+        let either = Schedule::cron("0 0 15 * 7").unwrap();
+        assert_eq!(either.next_after(start), Some(minutes(7 * 24 * 60)));
+
+        // 2024-02-29 00:00:00 UTC
+        let leap_day = Schedule::cron("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(start),
+            Some(UNIX_EPOCH + Duration::from_secs(1_709_164_800))
+        );
+
+        assert_eq!(Schedule::cron("0 0 31 2 *").unwrap().next_after(start), None);
+        assert_eq!(
+            Schedule::every(Duration::from_secs(10)).next_after(start),
+            Some(start + Duration::from_secs(10))
+        );
+    }
+
+    #[pg_test]
+    fn test_schedule_cron_parse_errors() {
+        assert!(matches!(Schedule::cron("* * * *"), Err(ScheduleError::WrongFieldCount(_))));
+        assert!(matches!(Schedule::cron("60 * * * *"), Err(ScheduleError::InvalidField { .. })));
+        assert!(matches!(Schedule::cron("*/0 * * * *"), Err(ScheduleError::InvalidField { .. })));
+        assert!(matches!(Schedule::cron("5-1 * * * *"), Err(ScheduleError::InvalidField { .. })));
+        assert!(matches!(Schedule::cron("* * 0 * *"), Err(ScheduleError::InvalidField { .. })));
+        assert_eq!(
+            Schedule::cron("0,30 */2 1-15/7 * 0").unwrap().to_string(),
+            "0,30 */2 1-15/7 * 0"
+        );
+    }
+
+    /// Run a worker that panics, with its crash reports in `dir`, and return them all
+    fn crash(dir: &str, limit: i32, times: usize) -> Vec<(String, serde_json::Value)> {
+        for _ in 0..times {
+            BackgroundWorkerBuilder::new("crashing_bgworker")
+                .set_library("pgx_tests")
+                .set_function("bgworker_crash")
+                .set_argument(limit.into_datum())
+                .set_extra(dir)
+                .set_notify_pid(unsafe { pg_sys::MyProcPid })
+                .load_dynamic()
+                .wait_for_shutdown()
+                .expect("aborted shutdown");
+        }
+
+        let mut reports = std::fs::read_dir(pgx::paths::data_dir().join(dir))
+            .expect("no crash report directory")
+            .map(|entry| {
+                let path: PathBuf = entry.unwrap().path();
+                let report = std::fs::read(&path).unwrap();
+                let name = path.file_name().unwrap().to_str().unwrap().to_string();
+                (name, serde_json::from_slice(&report).expect("invalid crash report"))
+            })
+            .collect::<Vec<_>>();
+        reports.sort_by(|a, b| a.0.cmp(&b.0));
+        reports
+    }
+
+    #[pg_test]
+    fn test_bgworker_crash_report() {
+        let dir = "pgx_tests_crash_report";
+        std::fs::remove_dir_all(pgx::paths::data_dir().join(dir)).ok();
+
+        let reports = crash(dir, 5, 1);
+        assert_eq!(reports.len(), 1);
+        let (name, report) = &reports[0];
+        assert!(name.starts_with("crashing_bgworker-") && name.ends_with(".json"), "{}", name);
+        assert_eq!(report["worker"], "crashing_bgworker");
+        assert_eq!(report["message"], "crashed on purpose");
+        assert!(report["pid"].as_i64().unwrap() > 0);
+        assert!(report["time"].as_str().unwrap().ends_with('Z'));
+        assert!(report["location"].as_str().unwrap().contains("bgworker_tests.rs"));
+        assert!(report["backtrace"].as_str().unwrap().contains("bgworker_crash"));
+
+        // only the latest breadcrumbs are kept
+        let breadcrumbs = report["breadcrumbs"].as_array().unwrap();
+        assert_eq!(breadcrumbs.len(), MAX_BREADCRUMBS);
+        assert_eq!(breadcrumbs[0]["message"], format!("step {}", 41 - MAX_BREADCRUMBS));
+        assert_eq!(breadcrumbs[MAX_BREADCRUMBS - 1]["message"], "step 40");
+    }
+
+    #[pg_test]
+    fn test_bgworker_crash_report_limit() {
+        let dir = "pgx_tests_crash_report_limit";
+        std::fs::remove_dir_all(pgx::paths::data_dir().join(dir)).ok();
+
+        // the third crash in a minute isn't reported
+        let reports = crash(dir, 2, 3);
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|(_, report)| report["message"] == "crashed on purpose"));
+    }
 }
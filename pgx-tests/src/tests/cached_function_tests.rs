@@ -0,0 +1,217 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use pgx::prelude::*;
+
+#[pg_extern(immutable, parallel_safe)]
+fn cached_function_tests_add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+thread_local! {
+    static REENTERED: pgx::cached_function::CachedFunction =
+        pgx::cached_function::CachedFunction::by_name(
+            "tests.cached_function_reentered(int) returns int",
+        )
+        .unwrap();
+}
+
+/// Call `tests.cached_function_reentered(depth)`, which calls back into this
+#[pg_extern]
+fn cached_function_tests_reenter(depth: i32) -> i32 {
+    REENTERED.with(|f| f.call_as::<i32>(&[depth.into_datum()]).unwrap().unwrap())
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::cached_function::{CachedFunction, Error};
+    use pgx::prelude::*;
+
+    fn create_score(returns: &str, body: &str) -> Result<(), pgx::spi::Error> {
+        Spi::run(&format!(
+            "CREATE FUNCTION tests.cached_function_score(text) RETURNS {returns} \
+             STRICT LANGUAGE sql AS $$ {body} $$"
+        ))
+    }
+
+    #[pg_test]
+    fn test_call_builtin_function() -> Result<(), Error> {
+        let lower = CachedFunction::by_name("pg_catalog.lower(text) returns text")?;
+        assert_eq!(lower.arg_types(), vec![pg_sys::TEXTOID]);
+        assert_eq!(lower.return_type(), pg_sys::TEXTOID);
+        assert_eq!(
+            lower.call_as::<String>(&["A Good Dog".into_datum()])?.as_deref(),
+            Some("a good dog")
+        );
+        assert_eq!(lower.call_as::<String>(&["BARK".into_datum()])?.as_deref(), Some("bark"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_call_extension_function() -> Result<(), Error> {
+        let add = CachedFunction::by_name("cached_function_tests_add(int, int) RETURNS integer")?;
+        assert_eq!(add.call_as::<i32>(&[3.into_datum(), 4.into_datum()])?, Some(7));
+        // it's STRICT, so it isn't called with a NULL
+        assert_eq!(add.call_as::<i32>(&[3.into_datum(), None])?, None);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_call_sql_function() -> Result<(), Box<dyn std::error::Error>> {
+        create_score("int", "SELECT length($1)")?;
+        let score = CachedFunction::by_name("tests.cached_function_score(text) returns int")?;
+        assert_eq!(score.call_as::<i32>(&["dog".into_datum()])?, Some(3));
+        assert_eq!(score.call_as::<i32>(&["puppy".into_datum()])?, Some(5));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_dropped_and_recreated() -> Result<(), Box<dyn std::error::Error>> {
+        create_score("int", "SELECT length($1)")?;
+        let score = CachedFunction::by_name("tests.cached_function_score(text) returns int")?;
+        let oid = score.oid();
+        assert_eq!(score.call_as::<i32>(&["dog".into_datum()])?, Some(3));
+
+        Spi::run("DROP FUNCTION tests.cached_function_score(text)")?;
+        create_score("int", "SELECT length($1) * 10")?;
+        assert_eq!(score.call_as::<i32>(&["dog".into_datum()])?, Some(30));
+        assert_ne!(score.oid(), oid);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_replaced() -> Result<(), Box<dyn std::error::Error>> {
+        create_score("int", "SELECT length($1)")?;
+        let score = CachedFunction::by_name("tests.cached_function_score(text) returns int")?;
+        assert_eq!(score.call_as::<i32>(&["dog".into_datum()])?, Some(3));
+
+        Spi::run(
+            "CREATE OR REPLACE FUNCTION tests.cached_function_score(text) RETURNS int \
+             STRICT LANGUAGE sql AS $$ SELECT -length($1) $$",
+        )?;
+        assert_eq!(score.call_as::<i32>(&["dog".into_datum()])?, Some(-3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_reentered_after_ddl() -> Result<(), Box<dyn std::error::Error>> {
+        // each call changes a function, so the call back into the extension looks this one up
+        // again while the outer call is still running it
+        Spi::run(
+            "CREATE FUNCTION tests.cached_function_reentered(depth int) RETURNS int \
+             LANGUAGE plpgsql AS $$ BEGIN \
+                 IF depth = 0 THEN RETURN 0; END IF; \
+                 CREATE OR REPLACE FUNCTION tests.cached_function_touched() RETURNS int \
+                     LANGUAGE sql AS 'SELECT 1'; \
+                 RETURN cached_function_tests_reenter(depth - 1) + 1; \
+             END $$",
+        )?;
+        assert_eq!(Spi::get_one::<i32>("SELECT cached_function_tests_reenter(3)")?, Some(3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_dropped() -> Result<(), Box<dyn std::error::Error>> {
+        create_score("int", "SELECT length($1)")?;
+        let score = CachedFunction::by_name("tests.cached_function_score(text) returns int")?;
+        Spi::run("DROP FUNCTION tests.cached_function_score(text)")?;
+
+        match score.call(&["dog".into_datum()]) {
+            Err(Error::PostgresError(e)) => assert_eq!(
+                e.message(),
+                "function \"tests.cached_function_score(text)\" does not exist"
+            ),
+            other => panic!("called a dropped function: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_recreated_incompatibly() -> Result<(), Box<dyn std::error::Error>> {
+        create_score("int", "SELECT length($1)")?;
+        let score = CachedFunction::by_name("tests.cached_function_score(text) returns int")?;
+        Spi::run("DROP FUNCTION tests.cached_function_score(text)")?;
+        create_score("text", "SELECT upper($1)")?;
+
+        let error = score.call(&["dog".into_datum()]).unwrap_err();
+        assert!(matches!(error, Error::SignatureMismatch { .. }));
+        assert_eq!(
+            error.to_string(),
+            "`tests.cached_function_score(text) returns int` doesn't match the function, which returns text"
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_binary_coercible_return_type() -> Result<(), Error> {
+        let to_regproc = CachedFunction::by_name("pg_catalog.to_regproc(text) returns oid")?;
+        assert_eq!(to_regproc.return_type(), pg_sys::REGPROCOID);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_invalid_signature() {
+        for signature in ["pg_catalog.lower(text)", "pg_catalog.lower returns text", "returns text"]
+        {
+            assert!(
+                matches!(CachedFunction::by_name(signature), Err(Error::InvalidSignature(_))),
+                "{}",
+                signature
+            );
+        }
+    }
+
+    #[pg_test]
+    fn test_not_a_plain_function() {
+        for (signature, reason) in [
+            ("pg_catalog.sum(int) returns bigint", "is an aggregate"),
+            ("pg_catalog.row_number() returns bigint", "is a window function"),
+            ("pg_catalog.generate_series(int, int) returns int", "returns a set"),
+            ("pg_catalog.lower(text) returns int", "returns text"),
+        ] {
+            match CachedFunction::by_name(signature) {
+                Err(Error::SignatureMismatch { reason: found, .. }) => assert_eq!(found, reason),
+                other => panic!("{} was {:?}", signature, other.map(|f| f.oid())),
+            }
+        }
+    }
+
+    #[pg_test]
+    fn test_no_such_function() {
+        assert!(matches!(
+            CachedFunction::by_name("pg_catalog.lower(int) returns text"),
+            Err(Error::PostgresError(_))
+        ));
+        assert!(matches!(
+            CachedFunction::by_name("pg_catalog.lower(text) returns no_such_type"),
+            Err(Error::PostgresError(_))
+        ));
+    }
+
+    #[pg_test]
+    fn test_argument_count_mismatch() -> Result<(), Error> {
+        let lower = CachedFunction::by_name("pg_catalog.lower(text) returns text")?;
+        assert!(matches!(
+            lower.call(&[]),
+            Err(Error::ArgumentCountMismatch { expected: 1, got: 0 })
+        ));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_call_as_the_wrong_type() -> Result<(), Error> {
+        let lower = CachedFunction::by_name("pg_catalog.lower(text) returns text")?;
+        assert!(matches!(lower.call_as::<i32>(&["A".into_datum()]), Err(Error::DatumError(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern(volatile)]
+fn clock_tests_stamp() -> String {
+    format!(
+        "{} {}",
+        i64::from(pgx::clock::statement_timestamp()),
+        i64::from(pgx::clock::clock_timestamp())
+    )
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::clock::{clock_timestamp, statement_timestamp, transaction_timestamp};
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_clocks_match_sql() -> Result<(), pgx::spi::Error> {
+        assert_eq!(
+            Spi::get_one::<TimestampWithTimeZone>("SELECT now()")?,
+            Some(transaction_timestamp())
+        );
+        assert_eq!(
+            Spi::get_one::<TimestampWithTimeZone>("SELECT transaction_timestamp()")?,
+            Some(transaction_timestamp())
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_clocks_order() {
+        let transaction = i64::from(transaction_timestamp());
+        let statement = i64::from(statement_timestamp());
+        let before = i64::from(clock_timestamp());
+        let after = i64::from(clock_timestamp());
+        assert!(transaction <= statement);
+        assert!(statement <= before);
+        assert!(before <= after);
+        assert_eq!(transaction, i64::from(transaction_timestamp()));
+    }
+
+    /// 2023-01-01T00:00:00Z, in microseconds since Postgres' epoch of 2000-01-01
+    const FROZEN: i64 = 725_846_400_000_000;
+
+    #[pg_test(freeze_time = "2023-01-01T00:00:00Z")]
+    fn test_freeze_time_repeats() -> Result<(), pgx::spi::Error> {
+        let first = Spi::get_one::<String>("SELECT clock_tests_stamp()")?;
+        let second = Spi::get_one::<String>("SELECT clock_tests_stamp()")?;
+        assert_eq!(first, Some(format!("{} {}", FROZEN, FROZEN)));
+        assert_eq!(first, second);
+        assert_eq!(i64::from(transaction_timestamp()), FROZEN);
+
+        // SQL's own clocks aren't frozen without `shadow_now`
+        assert_ne!(
+            Spi::get_one::<TimestampWithTimeZone>("SELECT now()")?.map(i64::from),
+            Some(FROZEN)
+        );
+        Ok(())
+    }
+
+    #[pg_test(freeze_time = "2023-01-01T00:00:00Z", shadow_now)]
+    fn test_freeze_time_shadows_now() -> Result<(), pgx::spi::Error> {
+        for function in ["now", "transaction_timestamp", "statement_timestamp", "clock_timestamp"] {
+            let frozen = Spi::get_one::<TimestampWithTimeZone>(&format!("SELECT {}()", function))?;
+            assert_eq!(frozen.map(i64::from), Some(FROZEN), "{}() isn't frozen", function);
+        }
+        let real = Spi::get_one::<TimestampWithTimeZone>("SELECT pg_catalog.now()")?;
+        assert_ne!(real.map(i64::from), Some(FROZEN));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_freeze_time_guard_thaws() {
+        let real = i64::from(transaction_timestamp());
+        {
+            let _frozen = pgx::testing::freeze_time("2023-01-01T00:00:00Z");
+            assert_eq!(i64::from(transaction_timestamp()), FROZEN);
+        }
+        assert_eq!(i64::from(transaction_timestamp()), real);
+    }
+}
@@ -0,0 +1,138 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::compat;
+    use pgx::prelude::*;
+
+    unsafe fn count_rows(scan: compat::TableScanDesc, rel: pg_sys::Relation) -> i64 {
+        let slot = compat::table_slot_create(rel);
+        let mut count = 0;
+        while compat::table_scan_getnextslot(scan, pg_sys::ScanDirection_ForwardScanDirection, slot)
+        {
+            assert!(!compat::exec_fetch_slot_heap_tuple(slot).is_null());
+            count += 1;
+        }
+        pg_sys::ExecDropSingleTupleTableSlot(slot);
+        count
+    }
+
+    #[pg_test]
+    fn test_catalog_scan() -> Result<(), pgx::spi::Error> {
+        let count = unsafe {
+            let rel = compat::table_open(pg_sys::DatabaseRelationId, pg_sys::AccessShareLock as _);
+            let scan = compat::table_beginscan_catalog(rel, 0, std::ptr::null_mut());
+            let count = count_rows(scan, rel);
+            compat::table_endscan(scan);
+            compat::table_close(rel, pg_sys::AccessShareLock as _);
+            count
+        };
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM pg_database")?, Some(count));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_table_scan() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TABLE compat_scan AS SELECT x FROM generate_series(1, 42) x")?;
+        let relid = Spi::get_one::<pg_sys::Oid>("SELECT 'compat_scan'::regclass::oid")?.unwrap();
+        let count = unsafe {
+            // the rows were inserted by a later command than our statement's snapshot can see
+            let snapshot = pg_sys::RegisterSnapshot(pg_sys::GetLatestSnapshot());
+            let rel = compat::table_open(relid, pg_sys::AccessShareLock as _);
+            let scan = compat::table_beginscan(rel, snapshot, 0, std::ptr::null_mut());
+            let count = count_rows(scan, rel);
+            compat::table_endscan(scan);
+            compat::table_close(rel, pg_sys::AccessShareLock as _);
+            pg_sys::UnregisterSnapshot(snapshot);
+            count
+        };
+        assert_eq!(count, 42);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_heap_tuple_slot() {
+        unsafe {
+            let rel = compat::table_open(pg_sys::DatabaseRelationId, pg_sys::AccessShareLock as _);
+            let scan = compat::table_beginscan_catalog(rel, 0, std::ptr::null_mut());
+            let scan_slot = compat::table_slot_create(rel);
+            assert!(compat::table_scan_getnextslot(
+                scan,
+                pg_sys::ScanDirection_ForwardScanDirection,
+                scan_slot
+            ));
+            let tuple = pg_sys::heap_copytuple(compat::exec_fetch_slot_heap_tuple(scan_slot));
+
+            let slot = compat::make_heap_tuple_slot((*rel).rd_att);
+            compat::exec_store_heap_tuple(tuple, slot, true);
+            assert_eq!(compat::exec_fetch_slot_heap_tuple(slot), tuple);
+
+            pg_sys::ExecDropSingleTupleTableSlot(slot);
+            pg_sys::ExecDropSingleTupleTableSlot(scan_slot);
+            compat::table_endscan(scan);
+            compat::table_close(rel, pg_sys::AccessShareLock as _);
+        }
+    }
+
+    #[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+    #[pg_test]
+    fn test_virtual_tuple_slot() {
+        unsafe {
+            let rel = compat::table_open(pg_sys::DatabaseRelationId, pg_sys::AccessShareLock as _);
+            let slot = compat::make_virtual_tuple_slot((*rel).rd_att);
+            assert_eq!((*slot).tts_ops, &pg_sys::TTSOpsVirtual as *const _);
+            pg_sys::ExecDropSingleTupleTableSlot(slot);
+            compat::table_close(rel, pg_sys::AccessShareLock as _);
+        }
+    }
+
+    #[pg_test]
+    fn test_lists() {
+        unsafe {
+            assert_eq!(compat::list_length(std::ptr::null()), 0);
+
+            let mut ints = std::ptr::null_mut();
+            let mut oids = std::ptr::null_mut();
+            let mut ptrs = std::ptr::null_mut();
+            let mut values = [0u8; 3];
+            for i in 0..3 {
+                ints = pg_sys::lappend_int(ints, i as i32 * 10);
+                oids = pg_sys::lappend_oid(oids, pg_sys::Oid::from_u32_unchecked(i as u32 + 100));
+                ptrs = pg_sys::lappend(ptrs, values.as_mut_ptr().add(i).cast());
+            }
+
+            assert_eq!(compat::list_length(ints), 3);
+            assert_eq!(compat::list_nth_int(ints, 2), 20);
+            assert_eq!(compat::list_nth_oid(oids, 1), pg_sys::Oid::from_u32_unchecked(101));
+            assert_eq!(compat::list_nth(ptrs, 2), values.as_mut_ptr().add(2).cast());
+        }
+    }
+
+    #[pg_test]
+    fn test_timestamps() {
+        let start = compat::get_current_transaction_start_timestamp();
+        let now = compat::get_current_timestamp();
+        assert!(start <= now);
+        assert_eq!(compat::timestamp_difference_milliseconds(now, start), 0);
+        assert!(compat::timestamp_difference_exceeds(start, now + 1_000_000, 999));
+        assert!(!compat::timestamp_difference_exceeds(start, start, 1));
+    }
+
+    #[pg_test]
+    fn test_mark_guc_prefix_reserved() -> Result<(), pgx::spi::Error> {
+        compat::mark_guc_prefix_reserved("pgx_tests_compat");
+        assert_eq!(Spi::get_one::<bool>("SELECT true")?, Some(true));
+        Ok(())
+    }
+}
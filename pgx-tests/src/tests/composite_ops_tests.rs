@@ -0,0 +1,139 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+extension_sql!(
+    r#"
+CREATE TYPE composite_ops_point3d AS (
+    x int,
+    y int,
+    label text
+);
+"#,
+    name = "create_composite_ops_point3d",
+    declares = [Type(composite_ops_point3d)]
+);
+
+pg_composite_ops!("composite_ops_point3d", requires = ["create_composite_ops_point3d"]);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use std::cmp::Ordering;
+
+    /// Every pair of these, including the `NULL` attributes, must compare the same way
+    /// Postgres' own record comparison does
+    const POINTS: &str = "
+        SELECT * FROM (VALUES
+            (1, ROW(1, 2, 'a')::composite_ops_point3d),
+            (2, ROW(1, 2, 'a')::composite_ops_point3d),
+            (3, ROW(1, 2, 'b')::composite_ops_point3d),
+            (4, ROW(2, 1, 'a')::composite_ops_point3d),
+            (5, ROW(1, NULL, 'a')::composite_ops_point3d),
+            (6, ROW(1, NULL, NULL)::composite_ops_point3d),
+            (7, ROW(NULL, 2, 'a')::composite_ops_point3d),
+            (8, ROW(NULL, NULL, NULL)::composite_ops_point3d)
+        ) points(id, p)";
+
+    fn mismatches(ours: &str, postgres: &str) -> Result<Option<i64>, pgx::spi::Error> {
+        Spi::get_one(&format!(
+            "WITH points AS ({POINTS})
+             SELECT count(*) FROM points a, points b
+              WHERE ({ours}) IS DISTINCT FROM ({postgres})"
+        ))
+    }
+
+    #[pg_test]
+    fn test_composite_ops_match_record_comparison() -> Result<(), pgx::spi::Error> {
+        let comparisons = [
+            ("composite_ops_point3d_cmp(a.p, b.p)", "btrecordcmp(a.p, b.p)"),
+            ("a.p = b.p", "a.p::record = b.p::record"),
+            ("a.p <> b.p", "a.p::record <> b.p::record"),
+            ("a.p < b.p", "a.p::record < b.p::record"),
+            ("a.p <= b.p", "a.p::record <= b.p::record"),
+            ("a.p > b.p", "a.p::record > b.p::record"),
+            ("a.p >= b.p", "a.p::record >= b.p::record"),
+        ];
+        for (ours, postgres) in comparisons {
+            assert_eq!(mismatches(ours, postgres)?, Some(0), "{} vs {}", ours, postgres);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_composite_ops_null_ordering() -> Result<(), pgx::spi::Error> {
+        // a NULL attribute sorts after any non-NULL one, and is equal to another NULL
+        assert_eq!(
+            Spi::get_one::<bool>(
+                "SELECT ROW(1, 2, 'a')::composite_ops_point3d < ROW(1, NULL, 'a')::composite_ops_point3d"
+            )?,
+            Some(true)
+        );
+        assert_eq!(
+            Spi::get_one::<bool>(
+                "SELECT ROW(1, NULL, 'a')::composite_ops_point3d = ROW(1, NULL, 'a')::composite_ops_point3d"
+            )?,
+            Some(true)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_composite_ops_order_by() -> Result<(), pgx::spi::Error> {
+        let ours = Spi::get_one::<Vec<i32>>(&format!(
+            "WITH points AS ({POINTS}) SELECT array_agg(id ORDER BY p, id) FROM points"
+        ))?;
+        let postgres = Spi::get_one::<Vec<i32>>(&format!(
+            "WITH points AS ({POINTS}) SELECT array_agg(id ORDER BY p::record, id) FROM points"
+        ))?;
+        assert_eq!(ours, postgres);
+        assert_eq!(ours, Some(vec![1, 2, 3, 5, 6, 4, 7, 8]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_composite_ops_btree_index() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TEMPORARY TABLE composite_ops_points AS SELECT id, p FROM (VALUES (1, ROW(1, 2, 'a')::composite_ops_point3d), (2, ROW(2, 1, 'a')::composite_ops_point3d)) points(id, p)")?;
+        Spi::run("CREATE INDEX composite_ops_points_p ON composite_ops_points (p)")?;
+        let opclass = Spi::get_one::<String>(
+            "SELECT opcname::text FROM pg_index JOIN pg_opclass ON pg_opclass.oid = pg_index.indclass[0]
+              WHERE indexrelid = 'composite_ops_points_p'::regclass",
+        )?;
+        assert_eq!(opclass.as_deref(), Some("composite_ops_point3d_btree_ops"));
+
+        Spi::run("SET LOCAL enable_seqscan = off")?;
+        let id = Spi::get_one::<i32>(
+            "SELECT id FROM composite_ops_points WHERE p = ROW(2, 1, 'a')::composite_ops_point3d",
+        )?;
+        assert_eq!(id, Some(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_record_cmp() -> Result<(), Box<dyn std::error::Error>> {
+        let mut left = PgHeapTuple::new_composite_type("composite_ops_point3d")?;
+        left.set_by_name("x", 1)?;
+        let mut right = PgHeapTuple::new_composite_type("composite_ops_point3d")?;
+        right.set_by_name("x", 1)?;
+
+        // every other attribute is NULL in both
+        assert!(left.record_eq(&right));
+        assert_eq!(left.record_cmp(&right), Ordering::Equal);
+
+        right.set_by_name("y", 2)?;
+        assert!(!left.record_eq(&right));
+        assert_eq!(left.record_cmp(&right), Ordering::Greater);
+        assert_eq!(right.record_cmp(&left), Ordering::Less);
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+// the builtin rule is created again by `CREATE EXTENSION` when the dump is restored, so dumping it
+// too would fail on its primary key.  `scratch` isn't dumped at all
+extension_sql!(
+    r#"
+    CREATE TABLE config_dump_tests_rules (pattern text PRIMARY KEY, builtin bool NOT NULL DEFAULT false);
+    INSERT INTO config_dump_tests_rules VALUES ('%.tmp', true);
+    CREATE TABLE config_dump_tests_settings (name text PRIMARY KEY, value text NOT NULL);
+    CREATE TABLE config_dump_tests_scratch (value text);
+    "#,
+    name = "config_dump_tests_tables",
+    declares = [
+        Table(config_dump_tests_rules),
+        Table(config_dump_tests_settings),
+        Table(config_dump_tests_scratch)
+    ],
+    config_dump = [config_dump_tests_rules = "WHERE NOT builtin", config_dump_tests_settings],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    /// The filter the table `name` is dumped with, or `None` if it isn't
+    fn dump_filter(name: &str) -> Result<Option<String>, pgx::spi::Error> {
+        Spi::get_one_with_args(
+            "SELECT extcondition[array_position(extconfig, $1::regclass)]
+            FROM pg_extension WHERE extname = 'pgx_tests'",
+            vec![(PgBuiltInOids::TEXTOID.oid(), name.into_datum())],
+        )
+    }
+
+    #[pg_test]
+    fn test_tables_are_marked() -> Result<(), pgx::spi::Error> {
+        assert_eq!(dump_filter("config_dump_tests_rules")?.as_deref(), Some("WHERE NOT builtin"));
+        assert_eq!(dump_filter("config_dump_tests_settings")?.as_deref(), Some(""));
+        assert_eq!(dump_filter("config_dump_tests_scratch")?, None);
+        Ok(())
+    }
+
+    // a plain #[test], as only what's committed is dumped
+    #[test]
+    fn test_rows_survive_dump_and_restore() -> eyre::Result<()> {
+        let options = crate::pg_test::postgresql_conf_options();
+        let mut session = pgx_tests::session(options.clone())?;
+        session.batch_execute(
+            "INSERT INTO config_dump_tests_rules VALUES ('%.bak') ON CONFLICT DO NOTHING;
+            INSERT INTO config_dump_tests_settings VALUES ('retention', '7 days')
+                ON CONFLICT (name) DO UPDATE SET value = excluded.value;
+            INSERT INTO config_dump_tests_scratch VALUES ('lost');",
+        )?;
+
+        let mut restored = pgx_tests::dump_and_restore(options, "pgx_tests_config_dump")?;
+        let rules = restored
+            .query("SELECT pattern, builtin FROM config_dump_tests_rules ORDER BY pattern", &[])?
+            .into_iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, bool>(1)))
+            .collect::<Vec<_>>();
+        assert_eq!(rules, vec![("%.bak".into(), false), ("%.tmp".into(), true)]);
+
+        let setting: String = restored
+            .query_one(
+                "SELECT value FROM config_dump_tests_settings WHERE name = 'retention'",
+                &[],
+            )?
+            .get(0);
+        assert_eq!(setting, "7 days");
+
+        let scratch: i64 =
+            restored.query_one("SELECT count(*) FROM config_dump_tests_scratch", &[])?.get(0);
+        assert_eq!(scratch, 0);
+
+        // and the restored database isn't left behind
+        drop(restored);
+        let left: i64 = session
+            .query_one(
+                "SELECT count(*) FROM pg_database WHERE datname = 'pgx_tests_config_dump'",
+                &[],
+            )?
+            .get(0);
+        assert_eq!(left, 0);
+        Ok(())
+    }
+}
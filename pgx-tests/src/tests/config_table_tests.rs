@@ -0,0 +1,196 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::config_table::{self, ConfigTable, ConfigTableError};
+use pgx::prelude::*;
+use std::collections::HashMap;
+
+pgx::pg_config_table_trigger!(config_table_tests_changed);
+
+// `sessions` is only changed by the test which uses more than one session, as what it changes is
+// committed
+extension_sql!(
+    r#"
+    CREATE TABLE config_table_tests_config (name text PRIMARY KEY, value text NOT NULL);
+    INSERT INTO config_table_tests_config VALUES ('greeting', 'hello');
+    CREATE TRIGGER config_table_tests_config_changed
+        AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON config_table_tests_config
+        FOR EACH STATEMENT EXECUTE FUNCTION config_table_tests_changed();
+
+    CREATE TABLE config_table_tests_sessions (name text PRIMARY KEY, value text NOT NULL);
+    INSERT INTO config_table_tests_sessions VALUES ('greeting', 'hello');
+    CREATE TRIGGER config_table_tests_sessions_changed
+        AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON config_table_tests_sessions
+        FOR EACH STATEMENT EXECUTE FUNCTION config_table_tests_changed();
+    "#,
+    name = "config_table_tests_tables",
+    requires = [config_table_tests_changed],
+);
+
+fn config_table_tests_load(
+    rows: pgx::spi::SpiTupleTable,
+) -> Result<HashMap<String, String>, pgx::spi::Error> {
+    rows.map(|row| {
+        let name = row.get_by_name::<String, _>("name")?.unwrap_or_default();
+        Ok((name, row.get_by_name::<String, _>("value")?.unwrap_or_default()))
+    })
+    .collect()
+}
+
+thread_local! {
+    static CONFIG: ConfigTable<HashMap<String, String>> =
+        config_table::watch("config_table_tests_config", config_table_tests_load);
+    static SESSIONS: ConfigTable<HashMap<String, String>> =
+        config_table::watch("config_table_tests_sessions", config_table_tests_load);
+}
+
+#[pg_extern]
+fn config_table_tests_setting(name: &str) -> Result<Option<String>, ConfigTableError> {
+    Ok(CONFIG.with(|config| config.get())?.get(name).cloned())
+}
+
+#[pg_extern]
+fn config_table_tests_loads() -> i64 {
+    CONFIG.with(|config| config.loads() as i64)
+}
+
+#[pg_extern]
+fn config_table_tests_session_setting(name: &str) -> Result<Option<String>, ConfigTableError> {
+    Ok(SESSIONS.with(|sessions| sessions.get())?.get(name).cloned())
+}
+
+#[pg_extern]
+fn config_table_tests_session_loads() -> i64 {
+    SESSIONS.with(|sessions| sessions.loads() as i64)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::config_table_tests_load;
+    use pgx::config_table::{self, ConfigTableError};
+    use pgx::prelude::*;
+
+    fn setting() -> Result<Option<String>, pgx::spi::Error> {
+        Spi::get_one("SELECT config_table_tests_setting('greeting')")
+    }
+
+    fn loads() -> Result<Option<i64>, pgx::spi::Error> {
+        Spi::get_one("SELECT config_table_tests_loads()")
+    }
+
+    #[pg_test]
+    fn test_read_once_until_changed() -> Result<(), pgx::spi::Error> {
+        assert_eq!(setting()?, Some("hello".into()));
+        let before = loads()?;
+        assert_eq!(setting()?, Some("hello".into()));
+        assert_eq!(loads()?, before);
+
+        // the transaction making the change sees it straight away
+        Spi::run("UPDATE config_table_tests_config SET value = 'goodbye'")?;
+        assert_eq!(setting()?, Some("goodbye".into()));
+        assert_eq!(setting()?, Some("goodbye".into()));
+        assert_eq!(loads()?, before.map(|before| before + 1));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_other_tables_dont_matter() -> Result<(), pgx::spi::Error> {
+        assert_eq!(setting()?, Some("hello".into()));
+        let before = loads()?;
+        Spi::run(
+            "CREATE TABLE tests.config_table_tests_other (id int);
+            INSERT INTO tests.config_table_tests_other VALUES (1)",
+        )?;
+        assert_eq!(setting()?, Some("hello".into()));
+        assert_eq!(loads()?, before);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_missing_table() -> Result<(), pgx::spi::Error> {
+        let missing =
+            config_table::watch("tests.config_table_tests_missing", |rows| Ok(rows.len()));
+        assert_eq!(
+            missing.get().map(|rows| *rows),
+            Err(ConfigTableError::Missing("tests.config_table_tests_missing"))
+        );
+        assert_eq!(missing.loads(), 0);
+
+        // it's looked for again, such as once `CREATE EXTENSION` has made it
+        Spi::run(
+            "CREATE TABLE tests.config_table_tests_missing (name text, value text);
+            INSERT INTO tests.config_table_tests_missing VALUES ('a', '1'), ('b', '2')",
+        )?;
+        assert_eq!(missing.get().map(|rows| *rows), Ok(2));
+        assert_eq!(missing.loads(), 1);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_permission_denied() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE ROLE config_table_tests_nobody;
+            SET LOCAL ROLE config_table_tests_nobody",
+        )?;
+        let config = config_table::watch("config_table_tests_config", config_table_tests_load);
+        assert_eq!(
+            config.get().map(|config| config.len()),
+            Err(ConfigTableError::PermissionDenied("config_table_tests_config"))
+        );
+
+        Spi::run(
+            "RESET ROLE;
+            GRANT SELECT ON config_table_tests_config TO config_table_tests_nobody;
+            SET LOCAL ROLE config_table_tests_nobody",
+        )?;
+        assert_eq!(config.get().map(|config| config.len()), Ok(1));
+        Ok(())
+    }
+
+    fn session_setting(client: &mut postgres::Client) -> eyre::Result<Option<String>> {
+        Ok(client.query_one("SELECT config_table_tests_session_setting('greeting')", &[])?.get(0))
+    }
+
+    fn session_loads(client: &mut postgres::Client) -> eyre::Result<i64> {
+        Ok(client.query_one("SELECT config_table_tests_session_loads()", &[])?.get(0))
+    }
+
+    // a plain #[test], which changes the table from a second session and commits what it changes
+    #[test]
+    fn test_changes_from_another_session() -> eyre::Result<()> {
+        let options = crate::pg_test::postgresql_conf_options();
+        let mut reader = pgx_tests::session(options.clone())?;
+        let mut writer = pgx_tests::session(options)?;
+        writer.batch_execute("UPDATE config_table_tests_sessions SET value = 'one'")?;
+        assert_eq!(session_setting(&mut reader)?, Some("one".into()));
+
+        writer.batch_execute("UPDATE config_table_tests_sessions SET value = 'two'")?;
+        assert_eq!(session_setting(&mut reader)?, Some("two".into()));
+        let read = session_loads(&mut reader)?;
+
+        // nothing is seen until it's committed, and nothing is read again if it's rolled back
+        writer.batch_execute("BEGIN; UPDATE config_table_tests_sessions SET value = 'three'")?;
+        assert_eq!(session_setting(&mut reader)?, Some("two".into()));
+        writer.batch_execute("ROLLBACK")?;
+        assert_eq!(session_setting(&mut reader)?, Some("two".into()));
+        assert_eq!(session_loads(&mut reader)?, read);
+
+        // a transaction whose snapshot is older than the change keeps seeing what it saw
+        reader.batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ")?;
+        assert_eq!(session_setting(&mut reader)?, Some("two".into()));
+        writer.batch_execute("UPDATE config_table_tests_sessions SET value = 'four'")?;
+        assert_eq!(session_setting(&mut reader)?, Some("two".into()));
+        reader.batch_execute("COMMIT")?;
+        assert_eq!(session_setting(&mut reader)?, Some("four".into()));
+        Ok(())
+    }
+}
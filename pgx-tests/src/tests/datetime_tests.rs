@@ -39,6 +39,16 @@ fn convert_timetz_to_time(t: TimeWithTimeZone) -> Time {
     t.to_utc().into()
 }
 
+#[pg_extern]
+fn accept_interval(i: Interval) -> Interval {
+    i
+}
+
+#[pg_extern]
+fn interval_parts(i: Interval) -> Vec<i64> {
+    vec![i.months() as i64, i.days() as i64, i.micros()]
+}
+
 #[pg_extern]
 fn accept_timestamp(t: Timestamp) -> Timestamp {
     t
@@ -442,4 +452,232 @@ mod tests {
         assert!(ts.is_neg_infinity());
         Ok(())
     }
+
+    #[pg_test]
+    fn test_accept_interval_round_trip() -> Result<(), pgx::spi::Error> {
+        let result = Spi::get_one::<bool>(
+            "SELECT accept_interval('1 year 2 mons 3 days 04:05:06.789') = '1 year 2 mons 3 days 04:05:06.789'::interval",
+        )?;
+        assert_eq!(result, Some(true));
+
+        let parts = Spi::get_one::<Vec<i64>>("SELECT interval_parts('1 mon -2 days 00:00:01')")?;
+        assert_eq!(parts, Some(vec![1, -2, 1_000_000]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_interval_from_duration() {
+        let interval = Interval::from(Duration::from_secs(3 * 86_400 + 4 * 3_600));
+        assert_eq!(interval, Interval::new(0, 0, (3 * 86_400 + 4 * 3_600) * 1_000_000));
+        assert_eq!(interval.justify(), Interval::new(0, 3, 4 * 3_600 * 1_000_000));
+
+        // Durations have nanosecond precision, but intervals only keep microseconds
+        assert_eq!(Interval::from(Duration::from_nanos(1_999)).micros(), 1);
+    }
+
+    #[pg_test(error = "interval out of range")]
+    fn test_interval_from_huge_duration() {
+        let _ = Interval::from(Duration::from_secs(u64::MAX));
+    }
+
+    #[pg_test]
+    fn test_interval_display_matches_postgres() -> Result<(), pgx::spi::Error> {
+        for value in [
+            "0",
+            "1 year 2 mons 3 days 04:05:06.789",
+            "-1 days +02:03:00",
+            "1 day -00:00:01",
+            "-1 year -2 mons +3 days -04:05:06",
+            "1 mon",
+            "00:00:00.000001",
+            "-00:00:00.5",
+            "178000000 years",
+            "-2147483648 mons 2147483647 days",
+        ] {
+            let interval = Spi::get_one::<Interval>(&format!("SELECT '{value}'::interval"))?
+                .expect("datum was null");
+            let expected = Spi::get_one::<String>(&format!("SELECT '{value}'::interval::text"))?
+                .expect("datum was null");
+            assert_eq!(interval.to_string(), expected);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_interval_justify_matches_postgres() -> Result<(), pgx::spi::Error> {
+        for value in ["1 mon -1 hour", "-1 mon 1 hour", "35 days 27:00:00", "-35 days -27:00:00"] {
+            let interval = Spi::get_one::<Interval>(&format!("SELECT '{value}'::interval"))?
+                .expect("datum was null");
+
+            let justified =
+                Spi::get_one::<Interval>(&format!("SELECT justify_interval('{value}')"))?;
+            assert_eq!(Some(interval.justify()), justified);
+            let justified = Spi::get_one::<Interval>(&format!("SELECT justify_hours('{value}')"))?;
+            assert_eq!(Some(interval.justify_hours()), justified);
+            let justified = Spi::get_one::<Interval>(&format!("SELECT justify_days('{value}')"))?;
+            assert_eq!(Some(interval.justify_days()), justified);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_interval_arithmetic_matches_postgres() -> Result<(), pgx::spi::Error> {
+        let a = Interval::new(1, 2, 3_000_000);
+        let b = Interval::new(-4, 5, -6_000_000);
+        assert_eq!(a + b, Interval::new(-3, 7, -3_000_000));
+        assert_eq!(-a, Interval::new(-1, -2, -3_000_000));
+
+        for factor in [0.5, 1.5, -2.25, 1.0 / 3.0, 1e-7] {
+            let expected = Spi::get_one::<Interval>(&format!(
+                "SELECT '1 year 2 mons 3 days 04:05:06.789'::interval * {factor}::float8"
+            ))?;
+            assert_eq!(Some(Interval::new(14, 3, 14_706_789_000) * factor), expected);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_interval_overflow() {
+        let max = Interval::new(i32::MAX, i32::MAX, i64::MAX);
+        assert_eq!(max.checked_add(Interval::new(0, 0, 1)), None);
+        assert_eq!(Interval::new(0, 0, i64::MIN).checked_neg(), None);
+        assert_eq!(max.checked_mul(2.0), None);
+        assert_eq!(Interval::new(0, 0, 1).checked_mul(f64::NAN), None);
+        assert_eq!(Interval::new(0, 1, 0).checked_mul(1.0), Some(Interval::new(0, 1, 0)));
+    }
+
+    #[pg_test(error = "interval out of range")]
+    fn test_interval_add_overflow() {
+        let _ = Interval::new(0, 0, i64::MAX) + Interval::new(0, 0, 1);
+    }
+
+    #[pg_test(error = "interval out of range")]
+    fn test_interval_mul_overflow() {
+        let _ = Interval::new(i32::MAX, 0, 0) * 2.0;
+    }
+
+    /// A xorshift generator, so the round trip tests below cover lots of values but always the
+    /// same ones
+    fn random_in(state: &mut u64, min: i64, max: i64) -> i64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        let span = (max as i128 - min as i128 + 1) as u128;
+        (min as i128 + (*state as u128 % span) as i128) as i64
+    }
+
+    #[pg_test]
+    fn test_unix_epoch_micros_round_trip() {
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        let min = Timestamp::MIN.to_unix_epoch_micros().unwrap();
+        assert_eq!(min, -210_866_803_200_000_000);
+        for _ in 0..10_000 {
+            let micros = random_in(&mut state, min, i64::MAX);
+            let ts = Timestamp::from_unix_epoch_micros(micros).unwrap();
+            assert_eq!(ts.to_unix_epoch_micros(), Ok(micros));
+            let tstz = TimestampWithTimeZone::from_unix_epoch_micros(micros).unwrap();
+            assert_eq!(tstz.to_unix_epoch_micros(), Ok(micros));
+        }
+        for _ in 0..10_000 {
+            let micros = random_in(&mut state, i64::MIN, min - 1);
+            assert_eq!(
+                Timestamp::from_unix_epoch_micros(micros),
+                Err(pgx::FromTimeError::MicrosOutOfBounds)
+            );
+        }
+    }
+
+    #[pg_test]
+    fn test_unix_epoch_millis_and_seconds_round_trip() {
+        let mut state = 0x9e37_79b9_7f4a_7c15;
+        let min_millis = Timestamp::MIN.to_unix_epoch_millis().unwrap();
+        let min_seconds = Timestamp::MIN.to_unix_epoch_seconds().unwrap();
+        for _ in 0..10_000 {
+            let millis = random_in(&mut state, min_millis, i64::MAX / 1_000);
+            let ts = Timestamp::from_unix_epoch_millis(millis).unwrap();
+            assert_eq!(ts.to_unix_epoch_millis(), Ok(millis));
+
+            let seconds = random_in(&mut state, min_seconds, i64::MAX / 1_000_000);
+            let tstz = TimestampWithTimeZone::from_unix_epoch_seconds(seconds).unwrap();
+            assert_eq!(tstz.to_unix_epoch_seconds(), Ok(seconds));
+        }
+        for _ in 0..10_000 {
+            let millis = random_in(&mut state, i64::MAX / 1_000 + 1, i64::MAX);
+            assert!(Timestamp::from_unix_epoch_millis(millis).is_err());
+            let seconds = random_in(&mut state, i64::MIN, min_seconds - 1);
+            assert!(TimestampWithTimeZone::from_unix_epoch_seconds(seconds).is_err());
+        }
+
+        // sub-second timestamps before the epoch round down
+        let ts = Timestamp::from_unix_epoch_micros(-1).unwrap();
+        assert_eq!(ts.to_unix_epoch_millis(), Ok(-1));
+        assert_eq!(ts.to_unix_epoch_seconds(), Ok(-1));
+    }
+
+    #[pg_test]
+    fn test_unix_epoch_limits() {
+        assert_eq!(Timestamp::INFINITY.to_unix_epoch_micros(), Err(pgx::FromTimeError::Infinity));
+        assert_eq!(
+            TimestampWithTimeZone::NEG_INFINITY.to_unix_epoch_seconds(),
+            Err(pgx::FromTimeError::NegInfinity)
+        );
+        // the latest timestamps are too far from 1970 for their microseconds to fit an i64
+        assert_eq!(
+            Timestamp::MAX.to_unix_epoch_micros(),
+            Err(pgx::FromTimeError::MicrosOutOfBounds)
+        );
+        assert_eq!(Date::INFINITY.to_unix_epoch_days(), Err(pgx::FromTimeError::Infinity));
+    }
+
+    #[pg_test]
+    fn test_unix_epoch_matches_postgres() -> Result<(), pgx::spi::Error> {
+        for seconds in [0_i64, 1, -1, 951_782_400, -2_208_988_800, 253_402_300_799] {
+            let expected = Spi::get_one_with_args::<TimestampWithTimeZone>(
+                "SELECT to_timestamp($1::float8)",
+                vec![(PgBuiltInOids::INT8OID.oid(), seconds.into_datum())],
+            )?;
+            assert_eq!(
+                Some(TimestampWithTimeZone::from_unix_epoch_seconds(seconds).unwrap()),
+                expected
+            );
+        }
+        for days in [0_i32, 1, -1, 10_957, -719_528] {
+            let expected = Spi::get_one_with_args::<Date>(
+                "SELECT '1970-01-01'::date + $1",
+                vec![(PgBuiltInOids::INT4OID.oid(), days.into_datum())],
+            )?;
+            assert_eq!(Some(Date::from_unix_epoch_days(days).unwrap()), expected);
+        }
+
+        assert_eq!(
+            Spi::get_one::<Timestamp>("SELECT '4714-11-24 00:00:00 BC'::timestamp")?,
+            Some(Timestamp::MIN)
+        );
+        assert_eq!(
+            Spi::get_one::<Timestamp>("SELECT '294276-12-31 23:59:59.999999'::timestamp")?,
+            Some(Timestamp::MAX)
+        );
+        assert_eq!(Spi::get_one::<Date>("SELECT '4714-11-24 BC'::date")?, Some(Date::MIN));
+        assert_eq!(Spi::get_one::<Date>("SELECT '5874897-12-31'::date")?, Some(Date::MAX));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_unix_epoch_days_round_trip() {
+        let mut state = 0xdead_beef_cafe_f00d;
+        let min = Date::MIN.to_unix_epoch_days().unwrap() as i64;
+        let max = Date::MAX.to_unix_epoch_days().unwrap() as i64;
+        for _ in 0..10_000 {
+            let days = random_in(&mut state, min, max) as i32;
+            let date = Date::from_unix_epoch_days(days).unwrap();
+            assert_eq!(date.to_unix_epoch_days(), Ok(days));
+            assert_eq!(Date::from_julian_days(date.to_julian_days()), Ok(date));
+        }
+        for _ in 0..10_000 {
+            let days = random_in(&mut state, max + 1, i32::MAX as i64) as i32;
+            assert_eq!(Date::from_unix_epoch_days(days), Err(pgx::FromTimeError::DaysOutOfBounds));
+            let days = random_in(&mut state, i32::MIN as i64, min - 1) as i32;
+            assert_eq!(Date::from_unix_epoch_days(days), Err(pgx::FromTimeError::DaysOutOfBounds));
+        }
+    }
 }
@@ -0,0 +1,116 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::{AnyElement, JsonB};
+
+#[pg_extern]
+fn debug_array(array: Array<i32>) -> String {
+    format!("{:?}", array)
+}
+
+#[pg_extern]
+fn debug_range(range: Range<i32>) -> String {
+    format!("{:?}", range)
+}
+
+#[pg_extern]
+fn debug_jsonb(jsonb: JsonB) -> String {
+    format!("{:?}", jsonb)
+}
+
+#[pg_extern]
+fn debug_anyelement(element: AnyElement) -> String {
+    format!("{:?}", element)
+}
+
+#[pg_extern]
+fn debug_dog(dog: pgx::composite_type!("Dog")) -> String {
+    format!("{:?}", dog)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::datum::debug_datum;
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_debug_datum() {
+        unsafe {
+            assert_eq!(debug_datum(pg_sys::INT4OID, 42.into_datum()), "'42'::integer");
+            assert_eq!(debug_datum(pg_sys::TEXTOID, "it's".into_datum()), "'it''s'::text");
+            assert_eq!(debug_datum(pg_sys::INT4OID, None), "NULL");
+        }
+    }
+
+    #[pg_test]
+    fn test_debug_datum_catches_errors() -> Result<(), pgx::spi::Error> {
+        // there's no type with oid 0, so looking up its output function raises an ERROR
+        let rendered = unsafe { debug_datum(pg_sys::InvalidOid, 42.into_datum()) };
+        assert_eq!(rendered, "<error rendering>");
+
+        // and the transaction carries on
+        assert_eq!(Spi::get_one::<i32>("SELECT 1")?, Some(1));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_debug_array() -> Result<(), pgx::spi::Error> {
+        let rendered = Spi::get_one::<String>("SELECT debug_array(ARRAY[1, NULL, 3])")?;
+        assert_eq!(rendered.as_deref(), Some(r#"["1", NULL, "3"]"#));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_debug_range() -> Result<(), pgx::spi::Error> {
+        let rendered = Spi::get_one::<String>("SELECT debug_range('[1, 5)'::int4range)")?;
+        assert_eq!(rendered.as_deref(), Some(r#"Range("[1,5)")"#));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_debug_jsonb() -> Result<(), pgx::spi::Error> {
+        let rendered = Spi::get_one::<String>(r#"SELECT debug_jsonb('{"a": [1, null]}')"#)?;
+        assert_eq!(rendered.as_deref(), Some(r#"JsonB({"a":[1,null]})"#));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_debug_anyelement() -> Result<(), pgx::spi::Error> {
+        let rendered = Spi::get_one::<String>("SELECT debug_anyelement('2022-01-01'::date)")?;
+        assert_eq!(rendered.as_deref(), Some("AnyElement('2022-01-01'::date)"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_debug_heap_tuple() -> Result<(), pgx::spi::Error> {
+        let rendered = Spi::get_one::<String>("SELECT debug_dog(ROW('Nami', NULL)::Dog)")?;
+        assert_eq!(
+            rendered.as_deref(),
+            Some("PgHeapTuple { name: 'Nami'::text, scritches: NULL }")
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_debug_spi_heap_tuple() -> Result<(), pgx::spi::Error> {
+        let rendered = Spi::connect(|client| {
+            let row = client
+                .select("SELECT 1 AS a, NULL::text AS b, 'NULL' AS c", None, None)?
+                .first()
+                .get_heap_tuple()?;
+            Ok::<_, pgx::spi::Error>(format!("{:?}", row.unwrap()))
+        })?;
+        assert_eq!(rendered, "SpiHeapTupleData { a: '1'::integer, b: NULL, c: 'NULL'::text }");
+        Ok(())
+    }
+}
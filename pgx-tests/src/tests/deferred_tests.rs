@@ -0,0 +1,144 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use std::cell::{Cell, RefCell};
+
+thread_local! {
+    static INSERTED: Cell<i64> = Cell::new(0);
+    pub(crate) static RAN: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Counts the rows each statement inserts into `tests.deferred_items`, into `tests.deferred_counts`
+#[pg_trigger]
+fn count_deferred_inserts(
+    trigger: &pgx::PgTrigger,
+) -> Result<PgHeapTuple<'_, AllocatedByPostgres>, pgx::PgTriggerError> {
+    INSERTED.with(|inserted| inserted.set(inserted.get() + 1));
+    pgx::after_statement_once("count_deferred_inserts", || {
+        let inserted = INSERTED.with(|inserted| inserted.replace(0));
+        Spi::run_with_args(
+            "INSERT INTO tests.deferred_counts (inserted) VALUES ($1)",
+            Some(vec![(PgBuiltInOids::INT8OID.oid(), inserted.into_datum())]),
+        )
+        .unwrap();
+    });
+    Ok(trigger.new().expect("not an AFTER INSERT ... FOR EACH ROW trigger"))
+}
+
+#[pg_extern]
+fn deferred_push(label: String, key: Option<String>) {
+    let f = move || {
+        if label == "panic" {
+            panic!("deferred closure panicked");
+        }
+        RAN.with(|ran| ran.borrow_mut().push(label));
+    };
+    match key {
+        Some(key) => pgx::after_statement_once(key, f),
+        None => pgx::after_statement(f),
+    }
+}
+
+#[pg_extern]
+fn deferred_push_then_fail(label: String) {
+    pgx::after_statement(move || RAN.with(|ran| ran.borrow_mut().push(label)));
+    error!("failing after queueing");
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::RAN;
+    use pgx::prelude::*;
+
+    fn take_ran() -> Vec<String> {
+        RAN.with(|ran| std::mem::take(&mut *ran.borrow_mut()))
+    }
+
+    #[pg_test]
+    fn test_after_statement_trigger() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TABLE tests.deferred_items (id int)")?;
+        Spi::run("CREATE TABLE tests.deferred_counts (id serial, inserted bigint)")?;
+        Spi::run(
+            "CREATE TRIGGER count_inserts AFTER INSERT ON tests.deferred_items
+                FOR EACH ROW EXECUTE PROCEDURE tests.count_deferred_inserts()",
+        )?;
+
+        Spi::run("INSERT INTO tests.deferred_items SELECT generate_series(1, 10)")?;
+        Spi::run("INSERT INTO tests.deferred_items VALUES (11), (12)")?;
+        Spi::run("INSERT INTO tests.deferred_items VALUES (13)")?;
+
+        assert_eq!(
+            Spi::get_one::<Vec<i64>>(
+                "SELECT array_agg(inserted ORDER BY id) FROM tests.deferred_counts"
+            )?,
+            Some(vec![10, 2, 1])
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_after_statement_order_and_keys() -> Result<(), pgx::spi::Error> {
+        take_ran();
+        Spi::run(
+            "SELECT tests.deferred_push('a', 'k'), tests.deferred_push('b', NULL),
+                    tests.deferred_push('c', 'k'), tests.deferred_push('d', 'l')",
+        )?;
+        assert_eq!(take_ran(), vec!["a", "b", "d"]);
+
+        // the key is free again once its closure has run
+        Spi::run("SELECT tests.deferred_push('e', 'k')")?;
+        assert_eq!(take_ran(), vec!["e"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_after_statement_panic_is_isolated() -> Result<(), pgx::spi::Error> {
+        take_ran();
+        Spi::run(
+            "SELECT tests.deferred_push('before', NULL), tests.deferred_push('panic', NULL),
+                    tests.deferred_push('after', NULL)",
+        )?;
+        assert_eq!(take_ran(), vec!["before", "after"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_after_statement_forgotten_on_rollback() -> Result<(), pgx::spi::Error> {
+        take_ran();
+        Spi::run(
+            "DO $$
+            BEGIN
+                BEGIN
+                    PERFORM tests.deferred_push_then_fail('forgotten');
+                EXCEPTION WHEN others THEN
+                    NULL;
+                END;
+                PERFORM tests.deferred_push('kept', NULL);
+            END;
+            $$",
+        )?;
+        assert_eq!(take_ran(), vec!["kept"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_before_commit_waits() -> Result<(), pgx::spi::Error> {
+        take_ran();
+        Spi::run("SELECT 1")?;
+        pgx::before_commit(|| RAN.with(|ran| ran.borrow_mut().push("committing".into())));
+        Spi::run("SELECT 1")?;
+        // tests are rolled back, so it never runs
+        assert_eq!(take_ran(), Vec::<String>::new());
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern(immutable)]
+fn domain_tests_is_email(value: &str) -> bool {
+    value.contains('@')
+}
+
+#[derive(PostgresDomain, PartialEq, Debug)]
+#[pg_domain(check = domain_tests_is_email, not_null)]
+pub struct DomainTestsEmail(String);
+
+#[derive(PostgresDomain, PartialEq, Debug)]
+#[pg_domain(default = "1")]
+pub struct DomainTestsQuantity(i32);
+
+extension_sql!(
+    r#"
+CREATE TYPE domain_tests_contact AS (
+    name TEXT,
+    email DomainTestsEmail
+);
+"#,
+    name = "create_domain_tests_contact",
+    requires = [DomainTestsEmail],
+    declares = [Type(domain_tests_contact)]
+);
+
+#[pg_extern]
+fn domain_tests_host(email: DomainTestsEmail) -> String {
+    email.0.split('@').nth(1).unwrap_or_default().to_string()
+}
+
+#[pg_extern]
+fn domain_tests_make_email(user: &str, host: &str) -> DomainTestsEmail {
+    DomainTestsEmail(format!("{}@{}", user, host))
+}
+
+#[pg_extern]
+fn domain_tests_hosts(emails: Vec<DomainTestsEmail>) -> Vec<String> {
+    emails.into_iter().map(domain_tests_host).collect()
+}
+
+#[pg_extern]
+fn domain_tests_double(quantity: DomainTestsQuantity) -> DomainTestsQuantity {
+    DomainTestsQuantity(quantity.0 * 2)
+}
+
+#[pg_extern]
+fn domain_tests_contact_email(
+    contact: pgx::composite_type!("domain_tests_contact"),
+) -> Option<DomainTestsEmail> {
+    contact.get_by_name("email").unwrap()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::{DomainTestsEmail, DomainTestsQuantity};
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_domain_is_created() -> Result<(), pgx::spi::Error> {
+        let typtype = Spi::get_one::<String>(
+            "SELECT typtype::text FROM pg_type WHERE oid = 'DomainTestsEmail'::regtype",
+        )?;
+        assert_eq!(typtype.as_deref(), Some("d"));
+
+        let base = Spi::get_one::<String>(
+            "SELECT typbasetype::regtype::text FROM pg_type WHERE oid = 'DomainTestsEmail'::regtype",
+        )?;
+        assert_eq!(base.as_deref(), Some("text"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_domain_argument() -> Result<(), pgx::spi::Error> {
+        let host = Spi::get_one::<String>("SELECT domain_tests_host('nami@example.com')")?;
+        assert_eq!(host.as_deref(), Some("example.com"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_domain_return() -> Result<(), pgx::spi::Error> {
+        let email = Spi::get_one::<DomainTestsEmail>(
+            "SELECT domain_tests_make_email('nami', 'example.com')",
+        )?;
+        assert_eq!(email, Some(DomainTestsEmail("nami@example.com".into())));
+
+        let returns = Spi::get_one::<String>(
+            "SELECT prorettype::regtype::text FROM pg_proc WHERE proname = 'domain_tests_make_email'",
+        )?;
+        assert_eq!(returns.as_deref(), Some("domaintestsemail"));
+        Ok(())
+    }
+
+    #[pg_test(
+        error = "value for domain domaintestsemail violates check constraint \"domaintestsemail_check\""
+    )]
+    fn test_domain_check() -> Result<Option<String>, pgx::spi::Error> {
+        Spi::get_one("SELECT domain_tests_host('not an email')")
+    }
+
+    #[pg_test(error = "domain domaintestsemail does not allow null values")]
+    fn test_domain_not_null() -> Result<Option<String>, pgx::spi::Error> {
+        Spi::get_one("SELECT domain_tests_host(NULL::DomainTestsEmail)")
+    }
+
+    #[pg_test]
+    fn test_domain_array() -> Result<(), pgx::spi::Error> {
+        let hosts = Spi::get_one::<Vec<String>>(
+            "SELECT domain_tests_hosts(ARRAY['nami@example.com', 'brandy@example.org']::DomainTestsEmail[])",
+        )?;
+        assert_eq!(hosts, Some(vec!["example.com".to_string(), "example.org".to_string()]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_domain_default() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TEMPORARY TABLE domain_tests_orders (id int, quantity DomainTestsQuantity)",
+        )?;
+        Spi::run("INSERT INTO domain_tests_orders (id) VALUES (1)")?;
+        let quantity = Spi::get_one::<DomainTestsQuantity>(
+            "SELECT domain_tests_double(quantity) FROM domain_tests_orders",
+        )?;
+        assert_eq!(quantity, Some(DomainTestsQuantity(2)));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_domain_in_composite() -> Result<(), pgx::spi::Error> {
+        let email = Spi::get_one::<DomainTestsEmail>(
+            "SELECT domain_tests_contact_email(ROW('Nami', 'nami@example.com')::domain_tests_contact)",
+        )?;
+        assert_eq!(email, Some(DomainTestsEmail("nami@example.com".into())));
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::{direct_function_call, direct_pg_extern_function_call_as_datum, IntoDatum};
+use std::cell::RefCell;
+
+thread_local! {
+    /// The names of the [`DropTracker`]s dropped since the last [`take_dropped()`]
+    static DROPPED: RefCell<Vec<&'static str>> = RefCell::new(Vec::new());
+}
+
+/// Records its name in [`DROPPED`] when it's dropped
+pub struct DropTracker(&'static str);
+
+impl Drop for DropTracker {
+    fn drop(&mut self) {
+        DROPPED.with(|dropped| dropped.borrow_mut().push(self.0));
+    }
+}
+
+fn take_dropped() -> Vec<&'static str> {
+    DROPPED.with(|dropped| dropped.take())
+}
+
+#[pg_extern]
+fn drop_order_tests_spi_error() {
+    let _outer = DropTracker("outer");
+    Spi::connect(|client| {
+        let _inner = DropTracker("inner");
+        client.select("SELECT 1 / 0", None, None).map(|_| ())
+    })
+    .expect("SPI failed");
+}
+
+#[pg_extern]
+fn drop_order_tests_cursor_error() {
+    let _outer = DropTracker("outer");
+    Spi::connect(|client| {
+        let mut cursor =
+            client.open_cursor("SELECT 1 / (3 - x) FROM generate_series(1, 5) x", None);
+        let _inner = DropTracker("inner");
+        cursor.fetch(5).map(|_| ())
+    })
+    .expect("SPI failed");
+}
+
+#[pg_extern]
+fn drop_order_tests_direct_call_error() {
+    let _outer = DropTracker("outer");
+    let _inner = DropTracker("inner");
+    unsafe {
+        direct_function_call::<i32>(pg_sys::int4div, vec![1.into_datum(), 0.into_datum()]);
+    }
+}
+
+#[pg_extern]
+fn drop_order_tests_raise() {
+    let _raising = DropTracker("raising");
+    error!("raised by drop_order_tests_raise()");
+}
+
+#[pg_extern]
+fn drop_order_tests_pg_extern_error() {
+    let _outer = DropTracker("outer");
+    let _inner = DropTracker("inner");
+    unsafe {
+        direct_pg_extern_function_call_as_datum(drop_order_tests_raise_wrapper, vec![]);
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::{take_dropped, DropTracker};
+    use pgx::prelude::*;
+    use pgx::{direct_function_call, IntoDatum, PgMemoryContexts};
+
+    /// Calls `function` from a PL/pgSQL block that catches the `condition` it raises, and returns
+    /// the names of the [`DropTracker`]s dropped on the way
+    fn dropped_by(function: &str, condition: &str) -> Result<Vec<&'static str>, pgx::spi::Error> {
+        take_dropped();
+        Spi::run(&format!(
+            "DO $$
+            BEGIN
+                PERFORM {}();
+                RAISE EXCEPTION 'no error was raised';
+            EXCEPTION WHEN {} THEN
+                NULL;
+            END
+            $$",
+            function, condition
+        ))?;
+        Ok(take_dropped())
+    }
+
+    #[pg_test]
+    fn test_spi_error_drops_locals() -> Result<(), pgx::spi::Error> {
+        let dropped = dropped_by("drop_order_tests_spi_error", "division_by_zero")?;
+        assert_eq!(dropped, vec!["inner", "outer"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_cursor_error_drops_locals() -> Result<(), pgx::spi::Error> {
+        // the cursor is dropped while its portal is still active, which it leaves for Postgres
+        let dropped = dropped_by("drop_order_tests_cursor_error", "division_by_zero")?;
+        assert_eq!(dropped, vec!["inner", "outer"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_direct_call_error_drops_locals() -> Result<(), pgx::spi::Error> {
+        let dropped = dropped_by("drop_order_tests_direct_call_error", "division_by_zero")?;
+        assert_eq!(dropped, vec!["inner", "outer"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_pg_extern_error_drops_locals() -> Result<(), pgx::spi::Error> {
+        // the ERROR is raised by the called function's own guard, after it's unwound its frame
+        let dropped = dropped_by("drop_order_tests_pg_extern_error", "internal_error")?;
+        assert_eq!(dropped, vec!["raising", "inner", "outer"]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_error_restores_and_deletes_memory_context() {
+        let before = unsafe { pg_sys::CurrentMemoryContext };
+        take_dropped();
+
+        let caught = PgTryBuilder::new(|| unsafe {
+            PgMemoryContexts::Transient {
+                parent: PgMemoryContexts::CurrentMemoryContext.value(),
+                name: "drop_order_tests",
+                min_context_size: 4096,
+                initial_block_size: 4096,
+                max_block_size: 4096,
+            }
+            .switch_to(|context| {
+                context.leak_and_drop_on_delete(DropTracker("context"));
+                let _local = DropTracker("local");
+                direct_function_call::<i32>(pg_sys::int4div, vec![1.into_datum(), 0.into_datum()]);
+                false
+            })
+        })
+        .catch_when(PgSqlErrorCode::ERRCODE_DIVISION_BY_ZERO, |_| true)
+        .execute();
+
+        assert!(caught);
+        assert_eq!(unsafe { pg_sys::CurrentMemoryContext }, before);
+        // the local is dropped first, then the context is deleted, once it's been switched out of
+        assert_eq!(take_dropped(), vec!["local", "context"]);
+    }
+}
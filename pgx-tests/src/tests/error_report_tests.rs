@@ -0,0 +1,240 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::{direct_function_call, ErrorReportable, PgxError};
+
+#[derive(thiserror::Error, ErrorReportable, Debug)]
+#[error_report(code = ERRCODE_INVALID_PARAMETER_VALUE)]
+pub enum AccountError {
+    #[error("account {0} does not exist")]
+    #[error_report(code = ERRCODE_NO_DATA_FOUND, hint = "open account {0} first")]
+    NoSuchAccount(i64),
+
+    #[error("insufficient funds")]
+    #[error_report(
+        code = ERRCODE_CHECK_VIOLATION,
+        detail = "the balance is {balance}, but {amount} was requested"
+    )]
+    InsufficientFunds { balance: i64, amount: i64 },
+
+    #[error("the ledger is locked")]
+    Locked,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("only displayable")]
+pub struct DisplayOnlyError;
+
+#[pg_extern]
+fn error_report_tests_account(variant: &str) -> Result<i64, AccountError> {
+    match variant {
+        "missing" => Err(AccountError::NoSuchAccount(42)),
+        "insufficient" => Err(AccountError::InsufficientFunds { balance: 10, amount: 20 }),
+        "locked" => Err(AccountError::Locked),
+        _ => Ok(0),
+    }
+}
+
+#[pg_extern]
+fn error_report_tests_account_setof(
+    variant: &str,
+) -> Result<SetOfIterator<'static, i64>, AccountError> {
+    error_report_tests_account(variant).map(|value| SetOfIterator::new(std::iter::once(value)))
+}
+
+#[pg_extern]
+fn error_report_tests_display_only() -> Result<i64, DisplayOnlyError> {
+    Err(DisplayOnlyError)
+}
+
+#[pg_extern]
+fn error_report_tests_missing_cursor() -> Result<bool, pgx::spi::Error> {
+    Spi::connect(|client| client.find_cursor("error_report_tests_no_such_cursor").map(|_| true))
+}
+
+#[pg_extern]
+fn error_report_tests_pgx_numeric(value: AnyNumeric) -> Result<i32, PgxError> {
+    Ok(i32::try_from(value)?)
+}
+
+#[pg_extern]
+fn error_report_tests_pgx_spi() -> Result<bool, PgxError> {
+    Ok(error_report_tests_missing_cursor()?)
+}
+
+#[pg_extern]
+fn error_report_tests_pgx_caught() -> Result<Option<i32>, PgxError> {
+    PgTryBuilder::new(|| {
+        Ok(unsafe {
+            direct_function_call::<i32>(pg_sys::int4div, vec![1.into_datum(), 0.into_datum()])
+        })
+    })
+    .catch_others(|e| Err(e.into()))
+    .execute()
+}
+
+#[pg_extern]
+fn error_report_tests_pgx_own() -> Result<i64, PgxError> {
+    Err(PgxError::report(AccountError::NoSuchAccount(7)))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    /// Runs `statement` in a `DO` block that fails unless it raises `sqlstate`, and that then
+    /// runs `check`, which can use the `detail` and `hint` of the caught error
+    fn expect_sqlstate(
+        statement: &str,
+        sqlstate: &str,
+        check: &str,
+    ) -> Result<(), pgx::spi::Error> {
+        Spi::run(&format!(
+            "DO $$
+            DECLARE
+                detail text;
+                hint text;
+            BEGIN
+                {statement};
+                RAISE EXCEPTION 'no error was raised';
+            EXCEPTION WHEN SQLSTATE '{sqlstate}' THEN
+                GET STACKED DIAGNOSTICS detail = PG_EXCEPTION_DETAIL, hint = PG_EXCEPTION_HINT;
+                {check};
+            END
+            $$"
+        ))
+    }
+
+    #[pg_test]
+    fn test_variant_code_and_hint() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate(
+            "PERFORM error_report_tests_account('missing')",
+            "P0002",
+            "IF hint <> 'open account 42 first' THEN RAISE EXCEPTION 'wrong hint: %', hint; END IF",
+        )
+    }
+
+    #[pg_test]
+    fn test_variant_code_and_detail() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate(
+            "PERFORM error_report_tests_account('insufficient')",
+            "23514",
+            "IF detail <> 'the balance is 10, but 20 was requested' THEN
+                RAISE EXCEPTION 'wrong detail: %', detail;
+            END IF",
+        )
+    }
+
+    #[pg_test]
+    fn test_enum_default_code() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_account('locked')", "22023", "NULL")
+    }
+
+    #[pg_test]
+    fn test_setof_code() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_account_setof('missing')", "P0002", "NULL")
+    }
+
+    #[pg_test]
+    fn test_display_only_falls_back_to_data_exception() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_display_only()", "22000", "NULL")
+    }
+
+    #[pg_test]
+    fn test_spi_error_code() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_missing_cursor()", "34000", "NULL")
+    }
+
+    #[pg_test]
+    fn test_pgx_error_numeric_code() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_pgx_numeric(1e20)", "22003", "NULL")
+    }
+
+    #[pg_test]
+    fn test_pgx_error_spi_code() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_pgx_spi()", "34000", "NULL")
+    }
+
+    #[pg_test]
+    fn test_pgx_error_caught_code() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate("PERFORM error_report_tests_pgx_caught()", "22012", "NULL")
+    }
+
+    #[pg_test]
+    fn test_pgx_error_own_code_and_hint() -> Result<(), pgx::spi::Error> {
+        expect_sqlstate(
+            "PERFORM error_report_tests_pgx_own()",
+            "P0002",
+            "IF hint <> 'open account 7 first' THEN RAISE EXCEPTION 'wrong hint: %', hint; END IF",
+        )
+    }
+
+    #[pg_test]
+    fn test_pgx_error_conversions() {
+        use pgx::{ErrorReportable, PgHeapTupleError, PgTriggerError, PgxError};
+
+        let code = |e: PgxError| e.sql_error_code();
+        assert_eq!(
+            code(pgx::spi::Error::InvalidPosition.into()),
+            PgSqlErrorCode::ERRCODE_INVALID_CURSOR_STATE
+        );
+        assert_eq!(
+            code(pgx::TryFromDatumError::NoSuchAttributeName("x".into()).into()),
+            PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN
+        );
+        assert_eq!(
+            code(pgx::RangeConversionError::NullDatum.into()),
+            PgSqlErrorCode::ERRCODE_NULL_VALUE_NOT_ALLOWED
+        );
+        assert_eq!(
+            code(pgx::FromTimeError::HoursOutOfBounds.into()),
+            PgSqlErrorCode::ERRCODE_DATETIME_FIELD_OVERFLOW
+        );
+        assert_eq!(
+            code(PgHeapTupleError::NoSuchType("t".into()).into()),
+            PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT
+        );
+        assert_eq!(
+            code(PgTriggerError::NotTrigger.into()),
+            PgSqlErrorCode::ERRCODE_E_R_I_E_TRIGGER_PROTOCOL_VIOLATED
+        );
+        let numeric = i8::try_from(AnyNumeric::from(1000)).unwrap_err();
+        assert_eq!(code(numeric.into()), PgSqlErrorCode::ERRCODE_NUMERIC_VALUE_OUT_OF_RANGE);
+
+        // the message, detail, and hint are those of the error it holds
+        let own =
+            PgxError::report(super::AccountError::InsufficientFunds { balance: 1, amount: 2 });
+        assert_eq!(own.sql_error_code(), PgSqlErrorCode::ERRCODE_CHECK_VIOLATION);
+        assert_eq!(own.message(), "insufficient funds");
+        assert_eq!(own.detail().as_deref(), Some("the balance is 1, but 2 was requested"));
+        assert_eq!(own.to_string(), "insufficient funds");
+    }
+
+    #[pg_test]
+    fn test_pgx_error_from_caught_error() {
+        use pgx::{ErrorReportable, PgxError};
+
+        let caught = PgTryBuilder::new(|| -> PgxError {
+            ereport!(ERROR, PgSqlErrorCode::ERRCODE_LOCK_NOT_AVAILABLE, "the ledger is locked");
+        })
+        .catch_others(PgxError::from)
+        .execute();
+        assert_eq!(caught.sql_error_code(), PgSqlErrorCode::ERRCODE_LOCK_NOT_AVAILABLE);
+        assert_eq!(caught.message(), "the ledger is locked");
+    }
+
+    #[pg_test(error = "account 42 does not exist")]
+    fn test_message() -> Result<Option<i64>, pgx::spi::Error> {
+        Spi::get_one("SELECT error_report_tests_account('missing')")
+    }
+}
@@ -0,0 +1,78 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_export_abi]
+fn export_abi_add(a: i32, b: Option<i32>) -> i32 {
+    a + b.unwrap_or(0)
+}
+
+#[pg_export_abi]
+fn export_abi_greet(name: &str) -> String {
+    format!("hello, {}", name)
+}
+
+#[pg_export_abi]
+fn export_abi_divide(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        ereport!(ERROR, PgSqlErrorCode::ERRCODE_DIVISION_BY_ZERO, "division by zero");
+    }
+    a / b
+}
+
+#[pg_export_abi]
+fn export_abi_panic() -> i32 {
+    panic!("export_abi_panic panicked")
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    #[cfg(feature = "pg_test")]
+    use std::ffi::CStr;
+    #[cfg(feature = "pg_test")]
+    use std::os::raw::c_char;
+
+    // `build.rs` only compiles the C code with `pg_test`, which the extension `cargo pgx install
+    // --test` builds has, while the test binary, which doesn't run the body, doesn't
+    #[cfg(feature = "pg_test")]
+    extern "C" {
+        /// From `c/export_abi_consumer.c`, which calls the functions above through the header
+        fn export_abi_consumer_run() -> *const c_char;
+    }
+
+    #[pg_test]
+    fn test_c_calls_exported_functions() {
+        #[cfg(feature = "pg_test")]
+        {
+            let failure = unsafe { export_abi_consumer_run() };
+            if !failure.is_null() {
+                panic!("{}", unsafe { CStr::from_ptr(failure) }.to_string_lossy());
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_is_up_to_date() {
+        let header = pgx_tests::generate_abi_header(crate::__pgx_marker, "pgx_tests").unwrap();
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("c/pgx_tests_abi.h");
+        if std::env::var("UPDATE_SNAPSHOT").as_deref() == Ok("1") {
+            std::fs::write(&path, &header).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            header,
+            "run `cargo pgx schema --emit-header c/pgx_tests_abi.h`, or this test with `UPDATE_SNAPSHOT=1`"
+        );
+    }
+}
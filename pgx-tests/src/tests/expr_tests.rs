@@ -0,0 +1,108 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use pgx::prelude::*;
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(<~>)]
+fn expr_tests_distance(a: i32, b: i32) -> i32 {
+    (a - b).abs()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::expr::{Error, PgExpression};
+    use pgx::prelude::*;
+
+    fn int4_args(n: usize) -> Vec<PgOid> {
+        vec![PgBuiltInOids::INT4OID.oid(); n]
+    }
+
+    #[pg_test]
+    fn test_evaluate_extension_operator() -> Result<(), Error> {
+        let expr = PgExpression::parse("$1 <~> $2", &int4_args(2))?;
+        assert_eq!(expr.result_type(), pg_sys::INT4OID);
+        assert_eq!(expr.evaluate_as::<i32>(&[3.into_datum(), 10.into_datum()])?, Some(7));
+        assert_eq!(expr.evaluate_as::<i32>(&[10.into_datum(), 3.into_datum()])?, Some(7));
+        assert_eq!(expr.evaluate_as::<i32>(&[None, 3.into_datum()])?, None);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_evaluate_qual() -> Result<(), Error> {
+        let expr = PgExpression::parse("$1 <~> $2 < 5", &int4_args(2))?;
+        assert!(expr.evaluate_qual(&[1.into_datum(), 4.into_datum()])?);
+        assert!(!expr.evaluate_qual(&[1.into_datum(), 40.into_datum()])?);
+        assert!(!expr.evaluate_qual(&[1.into_datum(), None])?);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_evaluate_text() -> Result<(), Error> {
+        let expr = PgExpression::parse("upper($1) || '!'", &[PgBuiltInOids::TEXTOID.oid()])?;
+        assert_eq!(expr.evaluate_as::<String>(&["hi".into_datum()])?, Some("HI!".to_string()));
+        assert_eq!(expr.evaluate_as::<String>(&["bye".into_datum()])?, Some("BYE!".to_string()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_argument_count_mismatch() -> Result<(), Error> {
+        let expr = PgExpression::parse("$1 <~> $2", &int4_args(2))?;
+        assert!(matches!(
+            expr.evaluate(&[1.into_datum()]),
+            Err(Error::ArgumentCountMismatch { expected: 2, got: 1 })
+        ));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_syntax_error() {
+        assert!(matches!(
+            PgExpression::parse("$1 <~>", &int4_args(1)),
+            Err(Error::PostgresError(_))
+        ));
+    }
+
+    #[pg_test]
+    fn test_not_an_expression() {
+        assert!(matches!(
+            PgExpression::parse("1 FROM pg_class", &[]),
+            Err(Error::NotAnExpression(_))
+        ));
+        assert!(matches!(PgExpression::parse("count(*)", &[]), Err(Error::NotAnExpression(_))));
+    }
+
+    #[pg_test]
+    fn test_wrong_result_type() -> Result<(), Error> {
+        let expr = PgExpression::parse("$1 <~> $2", &int4_args(2))?;
+        assert!(matches!(
+            expr.evaluate_qual(&[1.into_datum(), 2.into_datum()]),
+            Err(Error::DatumError(_))
+        ));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_dropped_function() -> Result<(), Box<dyn std::error::Error>> {
+        Spi::run("CREATE FUNCTION tests.expr_tests_twice(int4) RETURNS int4 LANGUAGE sql AS 'SELECT $1 * 2'")?;
+        let expr = PgExpression::parse("tests.expr_tests_twice($1)", &int4_args(1))?;
+        assert_eq!(expr.evaluate_as::<i32>(&[21.into_datum()])?, Some(42));
+
+        Spi::run("DROP FUNCTION tests.expr_tests_twice(int4)")?;
+        assert!(matches!(expr.evaluate(&[21.into_datum()]), Err(Error::PostgresError(_))));
+
+        Spi::run("CREATE FUNCTION tests.expr_tests_twice(int4) RETURNS int4 LANGUAGE sql AS 'SELECT $1 + $1'")?;
+        assert_eq!(expr.evaluate_as::<i32>(&[4.into_datum()])?, Some(8));
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern(immutable)]
+fn extension_sql_tests_default_quantity() -> i32 {
+    3
+}
+
+#[pg_extern(immutable)]
+fn extension_sql_tests_doubled(value: i32) -> i32 {
+    value * 2
+}
+
+// nothing is listed in `requires`, and the comment and string literals name functions that don't
+// exist, which would fail the build if they were required
+extension_sql!(
+    r#"
+-- uses extension_sql_tests_missing(), which doesn't exist
+CREATE TABLE extension_sql_tests_orders (
+    note text DEFAULT 'extension_sql_tests_missing()',
+    quantity int DEFAULT extension_sql_tests_default_quantity(),
+    doubled int GENERATED ALWAYS AS (extension_sql_tests_doubled(quantity)) STORED,
+    body text DEFAULT $$ SELECT extension_sql_tests_missing() $$
+);
+"#,
+    name = "extension_sql_tests_orders",
+    infer_requires
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_inferred_requires() -> Result<(), pgx::spi::Error> {
+        let doubled = Spi::get_one::<i32>(
+            "INSERT INTO extension_sql_tests_orders DEFAULT VALUES RETURNING doubled",
+        )?;
+        assert_eq!(doubled, Some(6));
+        Ok(())
+    }
+}
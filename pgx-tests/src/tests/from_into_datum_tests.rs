@@ -27,4 +27,37 @@ mod tests {
         assert!(result.is_err());
         assert_eq!("Postgres type boolean oid={#16, builtin: BOOLOID} is not compatible with the Rust type alloc::string::String oid={#25, builtin: TEXTOID}", result.unwrap_err().to_string());
     }
+
+    #[pg_test]
+    fn test_datum_constructors_match_postgres() {
+        use pgx::DatumExt;
+
+        // int2um() negates a smallint, which it reads with DatumGetInt16()
+        let negated = unsafe {
+            pgx::direct_function_call_as_datum(
+                pg_sys::int2um,
+                vec![Some(pg_sys::Datum::from_i16(7))],
+            )
+        };
+        assert_eq!(negated.unwrap().as_i16(), -7);
+
+        let negated = unsafe {
+            pgx::direct_function_call_as_datum(
+                pg_sys::int2um,
+                vec![Some(pg_sys::Datum::from_i16(-7))],
+            )
+        };
+        assert_eq!(negated.unwrap().as_i16(), 7);
+
+        let not = unsafe {
+            pgx::direct_function_call_as_datum(
+                pg_sys::boolne,
+                vec![Some(pg_sys::Datum::from_bool(true)), Some(pg_sys::Datum::from_bool(false))],
+            )
+        };
+        assert!(not.unwrap().as_bool());
+
+        assert_eq!(pg_sys::Datum::from_char(-3).as_char(), -3);
+        assert_eq!(pg_sys::Datum::from(0usize).as_ptr::<pg_sys::varlena>(), None);
+    }
 }
@@ -0,0 +1,133 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern(immutable, parallel_safe)]
+fn function_stats_tests_add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[pg_extern]
+fn function_stats_tests_fail() -> i32 {
+    panic!("function_stats_tests_fail always fails")
+}
+
+#[pg_extern]
+fn function_stats_tests_disabled() {}
+
+#[pg_extern]
+fn function_stats_tests_reset() {}
+
+/// The process that made the call
+#[pg_extern(parallel_safe)]
+fn function_stats_tests_pid() -> i32 {
+    unsafe { pg_sys::MyProcPid }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::function_stats::function_stats;
+    use pgx::prelude::*;
+
+    /// The `calls` and `errors` of the function named `name`, as SQL's `function_stats()` has them
+    fn stats_of(name: &str) -> Result<(i64, i64), pgx::spi::Error> {
+        let (calls, errors) = Spi::get_two_with_args::<i64, i64>(
+            "SELECT calls, errors FROM function_stats() WHERE name = $1",
+            vec![(PgBuiltInOids::TEXTOID.oid(), name.into_datum())],
+        )?;
+        Ok((calls.unwrap(), errors.unwrap()))
+    }
+
+    #[pg_test]
+    fn test_every_function_is_counted() {
+        let stats = function_stats();
+        let add = stats.iter().find(|stats| stats.name == "function_stats_tests_add").unwrap();
+        assert_eq!(add.path, "pgx_tests::tests::function_stats_tests::function_stats_tests_add");
+        assert!(stats.iter().any(|stats| stats.name == "function_stats"));
+        assert!(stats.windows(2).all(|pair| pair[0].name <= pair[1].name));
+    }
+
+    #[pg_test]
+    fn test_counts_calls() -> Result<(), pgx::spi::Error> {
+        let before = stats_of("function_stats_tests_add")?;
+        Spi::run("SELECT function_stats_tests_add(i, 1) FROM generate_series(1, 10) AS i")?;
+        assert_eq!(stats_of("function_stats_tests_add")?, (before.0 + 10, before.1));
+
+        let add = function_stats()
+            .into_iter()
+            .find(|stats| stats.name == "function_stats_tests_add")
+            .unwrap();
+        assert!(add.max_time <= add.total_time);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_counts_errors() -> Result<(), pgx::spi::Error> {
+        let before = stats_of("function_stats_tests_fail")?;
+        Spi::connect(|mut client| {
+            for _ in 0..3 {
+                let failed = client.with_savepoint("function_stats_tests", |client| {
+                    client.select("SELECT function_stats_tests_fail()", None, None).map(|_| ())
+                });
+                assert!(failed.is_err());
+            }
+        });
+        assert_eq!(stats_of("function_stats_tests_fail")?, (before.0 + 3, before.1 + 3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_counts_every_backends_calls() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TABLE function_stats_tests_rows AS SELECT generate_series(1, 100000) AS value",
+        )?;
+        Spi::run("ANALYZE function_stats_tests_rows")?;
+        Spi::run("SET LOCAL max_parallel_workers_per_gather = 4")?;
+        Spi::run("SET LOCAL parallel_setup_cost = 0")?;
+        Spi::run("SET LOCAL parallel_tuple_cost = 0")?;
+        Spi::run("SET LOCAL min_parallel_table_scan_size = 0")?;
+        Spi::run("SET LOCAL parallel_leader_participation = off")?;
+
+        let before = stats_of("function_stats_tests_pid")?;
+        // the parallel workers add their counts to shared memory as they finish
+        let (calls, backends) = Spi::get_two::<i64, i64>(
+            "SELECT count(*), count(DISTINCT pid) \
+             FROM (SELECT function_stats_tests_pid() AS pid FROM function_stats_tests_rows) AS calls",
+        )?;
+        let after = stats_of("function_stats_tests_pid")?;
+
+        assert_eq!(calls, Some(100000));
+        assert!(backends.unwrap_or_default() > 1, "the calls were made by {:?} backends", backends);
+        assert_eq!(after, (before.0 + 100000, before.1));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_disabled_collection_counts_nothing() -> Result<(), pgx::spi::Error> {
+        let before = stats_of("function_stats_tests_disabled")?;
+        Spi::run("SET LOCAL pgx_tests.function_stats = off")?;
+        Spi::run("SELECT function_stats_tests_disabled() FROM generate_series(1, 5)")?;
+        Spi::run("SET LOCAL pgx_tests.function_stats = on")?;
+        assert_eq!(stats_of("function_stats_tests_disabled")?, before);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_reset() -> Result<(), pgx::spi::Error> {
+        Spi::run("SELECT function_stats_tests_reset() FROM generate_series(1, 2)")?;
+        assert!(stats_of("function_stats_tests_reset")?.0 >= 2);
+        Spi::run("SELECT function_stats_reset()")?;
+        assert_eq!(stats_of("function_stats_tests_reset")?, (0, 0));
+        Ok(())
+    }
+}
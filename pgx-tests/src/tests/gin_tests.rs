@@ -0,0 +1,239 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::gin::{GinConsistent, GinIndexable, GinPartialMatch, GinQuery};
+use pgx::prelude::*;
+use pgx::StringInfo;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ffi::CStr;
+
+/// A text which can be searched for substrings with a GIN index of its trigrams
+#[derive(Serialize, Deserialize, PostgresType)]
+#[inoutfuncs]
+pub struct GinTestsDocument {
+    text: String,
+}
+
+impl InOutFuncs for GinTestsDocument {
+    fn input(input: &CStr) -> Self {
+        GinTestsDocument { text: input.to_str().unwrap().to_string() }
+    }
+
+    fn output(&self, buffer: &mut StringInfo) {
+        buffer.push_str(&self.text)
+    }
+}
+
+/// The three-character substrings of `text`
+fn trigrams(text: &str) -> BTreeSet<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    chars.windows(3).map(|trigram| trigram.iter().collect()).collect()
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(@>)]
+fn gin_tests_document_contains(document: GinTestsDocument, substring: &str) -> bool {
+    document.text.contains(substring)
+}
+
+#[pg_gin_opclass(1 = gin_tests_document_contains)]
+impl GinIndexable for GinTestsDocument {
+    type Key = String;
+    type Query = String;
+
+    fn extract_value(self) -> Vec<String> {
+        trigrams(&self.text).into_iter().collect()
+    }
+
+    fn extract_query(substring: String, _strategy: u16) -> GinQuery<String> {
+        if substring.chars().count() < 3 {
+            GinQuery::all()
+        } else {
+            GinQuery::new(trigrams(&substring).into_iter().collect())
+        }
+    }
+
+    fn consistent(_strategy: u16, check: &[bool]) -> GinConsistent {
+        // a document with every trigram may still not have them in the right order
+        if check.iter().all(|&has| has) {
+            GinConsistent::Maybe
+        } else {
+            GinConsistent::False
+        }
+    }
+}
+
+/// Space-separated words, which can be searched for a word starting with a prefix
+#[derive(Serialize, Deserialize, PostgresType)]
+#[inoutfuncs]
+pub struct GinTestsWords {
+    words: Vec<String>,
+}
+
+impl InOutFuncs for GinTestsWords {
+    fn input(input: &CStr) -> Self {
+        GinTestsWords {
+            words: input.to_str().unwrap().split_whitespace().map(String::from).collect(),
+        }
+    }
+
+    fn output(&self, buffer: &mut StringInfo) {
+        buffer.push_str(&self.words.join(" "))
+    }
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(^@)]
+fn gin_tests_words_prefixed(words: GinTestsWords, prefix: &str) -> bool {
+    words.words.iter().any(|word| word.starts_with(prefix))
+}
+
+#[pg_gin_opclass(1 = gin_tests_words_prefixed, partial_match)]
+impl GinIndexable for GinTestsWords {
+    type Key = String;
+    type Query = String;
+
+    fn extract_value(self) -> Vec<String> {
+        self.words
+    }
+
+    fn extract_query(prefix: String, _strategy: u16) -> GinQuery<String> {
+        GinQuery::new(vec![prefix]).partial()
+    }
+
+    fn consistent(_strategy: u16, check: &[bool]) -> GinConsistent {
+        check[0].into()
+    }
+}
+
+impl GinPartialMatch for GinTestsWords {
+    fn compare_partial(prefix: &String, word: &String, _strategy: u16) -> Ordering {
+        // the words after the prefix which start with it all come before those which don't
+        if word.starts_with(prefix.as_str()) {
+            Ordering::Equal
+        } else {
+            Ordering::Greater
+        }
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    fn create_documents() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TABLE gin_tests_documents AS
+            SELECT i AS id, format('row %s %s', i, CASE WHEN i % 100 = 0 THEN 'needle' ELSE 'hay' END)::text::GinTestsDocument AS doc
+            FROM generate_series(1, 1000) AS i",
+        )?;
+        Spi::run("INSERT INTO gin_tests_documents VALUES (1001, 'abcxbcd'), (1002, 'ab')")?;
+        Spi::run("CREATE INDEX gin_tests_documents_doc ON gin_tests_documents USING gin (doc)")?;
+        Spi::run("ANALYZE gin_tests_documents")?;
+        Spi::run("SET LOCAL enable_seqscan = off")
+    }
+
+    /// The ids of the documents which contain `substring`
+    fn containing(substring: &str) -> Result<Vec<i32>, pgx::spi::Error> {
+        Spi::get_one_with_args::<Vec<i32>>(
+            "SELECT coalesce(array_agg(id ORDER BY id), '{}') FROM gin_tests_documents WHERE doc @> $1",
+            vec![(PgBuiltInOids::TEXTOID.oid(), substring.into_datum())],
+        )
+        .map(Option::unwrap_or_default)
+    }
+
+    #[pg_test]
+    fn test_opclass_is_the_default() -> Result<(), pgx::spi::Error> {
+        let storage = Spi::get_one::<String>(
+            "SELECT opckeytype::regtype::text FROM pg_opclass
+            WHERE opcname = 'gintestsdocument_gin_ops' AND opcdefault",
+        )?;
+        assert_eq!(storage.as_deref(), Some("text"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_only_partial_match_classes_compare_partially() -> Result<(), pgx::spi::Error> {
+        let support = |opclass: &str| {
+            Spi::get_one::<i64>(&format!(
+                "SELECT count(*) FROM pg_amproc JOIN pg_opclass ON amprocfamily = opcfamily
+                WHERE opcname = '{opclass}' AND amprocnum = 5"
+            ))
+        };
+        assert_eq!(support("gintestsdocument_gin_ops")?, Some(0));
+        assert_eq!(support("gintestswords_gin_ops")?, Some(1));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_index_scans_match_prefixes() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TABLE gin_tests_words AS
+            SELECT i AS id, format('word%s other', i)::GinTestsWords AS words
+            FROM generate_series(1, 1000) AS i",
+        )?;
+        Spi::run("CREATE INDEX gin_tests_words_words ON gin_tests_words USING gin (words)")?;
+        Spi::run("ANALYZE gin_tests_words")?;
+        Spi::run("SET LOCAL enable_seqscan = off")?;
+
+        let prefixed = |prefix: &str| {
+            Spi::get_one_with_args::<Vec<i32>>(
+                "SELECT coalesce(array_agg(id ORDER BY id), '{}') FROM gin_tests_words WHERE words ^@ $1",
+                vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())],
+            )
+            .map(Option::unwrap_or_default)
+        };
+        assert_eq!(prefixed("word99")?, vec![99, 990, 991, 992, 993, 994, 995, 996, 997, 998, 999]);
+        assert_eq!(prefixed("word1000")?, vec![1000]);
+        assert_eq!(prefixed("oth")?.len(), 1000);
+        assert_eq!(prefixed("nothing")?, Vec::<i32>::new());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_index_scans_find_substrings() -> Result<(), pgx::spi::Error> {
+        create_documents()?;
+        let plan = Spi::get_one::<pgx::Json>(
+            "EXPLAIN (FORMAT JSON) SELECT id FROM gin_tests_documents WHERE doc @> 'needle'",
+        )?
+        .expect("EXPLAIN returned nothing")
+        .0
+        .to_string();
+        assert!(plan.contains(r#""Node Type":"Bitmap Index Scan""#), "{}", plan);
+        assert!(plan.contains(r#""Index Name":"gin_tests_documents_doc""#), "{}", plan);
+
+        assert_eq!(containing("needle")?, (1..=10).map(|i| i * 100).collect::<Vec<_>>());
+        assert_eq!(containing("row 99 ")?, vec![99]);
+        assert_eq!(containing("nothing")?, Vec::<i32>::new());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_index_scans_recheck_the_order_of_trigrams() -> Result<(), pgx::spi::Error> {
+        create_documents()?;
+        // 'abcxbcd' has both of the trigrams of 'abcd'
+        assert_eq!(containing("abcd")?, Vec::<i32>::new());
+        assert_eq!(containing("xbcd")?, vec![1001]);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_short_substrings_check_every_document() -> Result<(), pgx::spi::Error> {
+        create_documents()?;
+        // 'ab' has no trigrams at all
+        assert_eq!(containing("ab")?, vec![1001, 1002]);
+        assert_eq!(containing("")?.len(), 1002);
+        Ok(())
+    }
+}
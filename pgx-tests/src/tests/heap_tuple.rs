@@ -28,6 +28,7 @@ CREATE TYPE AnimalFriendshipEdge AS (
 );
 "#,
     name = "create_composites",
+    declares = [Type(Dog), Type(Cat), Type(Fish), Type(AnimalFriendshipEdge)],
     bootstrap
 );
 
@@ -408,6 +409,59 @@ mod returning {
     // Returning VariadicArray/Array isn't supported, use a Vec.
 }
 
+// Modifying in place
+mod modifying {
+    use super::*;
+
+    #[pg_extern]
+    fn rename_and_scritch(
+        dog: pgx::composite_type!("Dog"),
+        name: &str,
+        scritches: i32,
+    ) -> pgx::composite_type!("Dog") {
+        let mut dog: PgHeapTuple<AllocatedByRust> = dog;
+        dog.modify(|mutator| {
+            mutator.set("name", name)?;
+            mutator.set("scritches", scritches)
+        })
+        .unwrap();
+        dog
+    }
+
+    #[pg_extern]
+    fn maybe_scritch(
+        dog: pgx::composite_type!("Dog"),
+        scritch: bool,
+    ) -> pgx::composite_type!("Dog") {
+        let mut dog: PgHeapTuple<AllocatedByRust> = dog;
+        if scritch {
+            let scritches = dog.get_by_name::<i32>("scritches").unwrap().unwrap_or_default();
+            dog.set_by_name("scritches", scritches + 1).unwrap();
+        }
+        dog
+    }
+}
+
+// `name!()` in argument position, which gives the argument its SQL name
+mod named_arguments {
+    use super::*;
+
+    #[pg_extern]
+    fn count_boops(
+        dog: name!(owner, pgx::composite_type!("Dog")),
+        cats: name!(pets, Option<Vec<Option<pgx::composite_type!("Cat")>>>),
+        r#type: name!(type, default!(i32, 1)),
+    ) -> (name!(owner_name, Option<String>), name!(boops, i32)) {
+        let boops = cats
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .map(|cat| cat.get_by_name::<i32>("boops").unwrap().unwrap_or_default())
+            .sum::<i32>();
+        (dog.get_by_name("name").unwrap(), boops * r#type)
+    }
+}
+
 // Just a compile test...
 // We don't run these, but we ensure we can build SQL for them
 mod sql_generator_tests {
@@ -640,7 +694,7 @@ mod tests {
     use pgx::datum::TryFromDatumError;
     use pgx::heap_tuple::PgHeapTupleError;
     use pgx::prelude::*;
-    use pgx::AllocatedByRust;
+    use pgx::{AllocatedByRust, PgMemoryContexts};
     use std::num::NonZeroUsize;
 
     #[pg_test]
@@ -653,6 +707,36 @@ mod tests {
         assert_eq!(retval, Ok(Some("Nami")));
     }
 
+    #[pg_test]
+    fn test_named_composite_arguments() -> Result<(), pgx::spi::Error> {
+        let names = Spi::get_one::<Vec<String>>(
+            "SELECT proargnames::text[] FROM pg_proc WHERE proname = 'count_boops'",
+        )?;
+        assert_eq!(
+            names,
+            Some(vec![
+                "owner".into(),
+                "pets".into(),
+                "type".into(),
+                "owner_name".into(),
+                "boops".into()
+            ])
+        );
+
+        let (owner, boops) = Spi::get_two::<String, i32>(
+            "SELECT * FROM count_boops(
+                pets => ARRAY[ROW('Sally', 2)::Cat, NULL, ROW('Anchovy', 3)::Cat],
+                owner => ROW('Nami', 0)::Dog)",
+        )?;
+        assert_eq!(owner.as_deref(), Some("Nami"));
+        assert_eq!(boops, Some(5));
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT boops FROM count_boops(ROW('Nami', 0)::Dog, NULL, 2)")?,
+            Some(0)
+        );
+        Ok(())
+    }
+
     #[pg_test]
     fn test_gets_name_field_default() {
         let retval = Spi::get_one::<&str>(
@@ -950,4 +1034,127 @@ mod tests {
         let table = result.expect("unable to select table result");
         assert_eq!(table.len(), 10_000);
     }
+
+    #[pg_test]
+    fn test_modify() -> Result<(), pgx::spi::Error> {
+        let dog = Spi::get_one::<PgHeapTuple<'_, AllocatedByRust>>(
+            "SELECT rename_and_scritch(ROW('Nami', 0)::Dog, 'Brandy', 42)",
+        )?
+        .expect("datum was null");
+        assert_eq!(dog.get_by_name("name").unwrap(), Some("Brandy"));
+        assert_eq!(dog.get_by_name("scritches").unwrap(), Some(42));
+
+        let dogs = Spi::get_one::<bool>(
+            "SELECT maybe_scritch(ROW('Nami', 1)::Dog, false) = ROW('Nami', 1)::Dog
+                AND maybe_scritch(ROW('Nami', 1)::Dog, true) = ROW('Nami', 2)::Dog",
+        )?;
+        assert_eq!(dogs, Some(true));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_modify_error_changes_nothing() {
+        Spi::run("CREATE TYPE DogWithAge AS (name text, age int);").expect("SPI failed");
+        let mut heap_tuple = PgHeapTuple::new_composite_type("DogWithAge").unwrap();
+        heap_tuple.set_by_name("name", "Nami").unwrap();
+
+        let result = heap_tuple.modify(|mutator| {
+            mutator.set("name", "Brandy")?;
+            mutator.set("age", "four")
+        });
+        assert!(matches!(result, Err(TryFromDatumError::IncompatibleTypes { .. })));
+        assert_eq!(heap_tuple.get_by_name("name").unwrap(), Some("Nami"));
+        assert_eq!(heap_tuple.get_by_name::<i32>("age").unwrap(), None);
+    }
+
+    /// A composite value of 100 `int` columns, with `c{n}` set to `n`
+    fn wide_composite_datum() -> pg_sys::Datum {
+        let columns = (1..=100).map(|n| format!("c{} int", n)).collect::<Vec<_>>();
+        Spi::run(&format!("CREATE TYPE wide AS ({})", columns.join(", "))).expect("SPI failed");
+        let values = (1..=100).map(|n| n.to_string()).collect::<Vec<_>>();
+        Spi::get_one::<PgHeapTuple<'_, AllocatedByRust>>(&format!(
+            "SELECT ROW({})::wide",
+            values.join(", ")
+        ))
+        .expect("SPI failed")
+        .expect("datum was null")
+        .into_composite_datum()
+        .unwrap()
+    }
+
+    #[pg_test]
+    fn test_modify_wide_tuple() {
+        let datum = wide_composite_datum();
+        let mut by_modify = unsafe { PgHeapTuple::from_composite_datum(datum) };
+        let mut by_set = unsafe { PgHeapTuple::from_composite_datum(datum) };
+
+        by_modify
+            .modify(|mutator| {
+                for n in 1..=100 {
+                    mutator.set(&format!("c{}", n), -n)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        for n in 1..=100 {
+            by_set.set_by_name(&format!("c{}", n), -n).unwrap();
+        }
+
+        assert!(by_modify.record_eq(&by_set));
+        assert_eq!(by_modify.get_by_name("c100").unwrap(), Some(-100));
+    }
+
+    #[pg_test]
+    fn test_unmodified_tuple_isnt_copied() {
+        let datum = wide_composite_datum();
+
+        // the same allocation comes back, until the tuple is modified
+        let mut tuple = unsafe { PgHeapTuple::from_composite_datum(datum) };
+        tuple.modify(|_| Ok(())).unwrap();
+        assert_eq!(tuple.into_composite_datum(), Some(datum));
+
+        let mut tuple = unsafe { PgHeapTuple::from_composite_datum(datum) };
+        tuple.set_by_name("c1", 0).unwrap();
+        assert_ne!(tuple.into_composite_datum(), Some(datum));
+    }
+
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    #[pg_test]
+    fn test_unmodified_tuple_allocates_nothing() {
+        let datum = wide_composite_datum();
+
+        // with blocks this small, anything as big as the tuple is allocated a block of its own
+        let context = unsafe {
+            pg_sys::AllocSetContextCreateExtended(
+                pg_sys::CurrentMemoryContext,
+                "test_unmodified_tuple_allocates_nothing\0".as_ptr().cast(),
+                1024,
+                1024,
+                1024,
+            )
+        };
+        let allocated = || unsafe { pg_sys::MemoryContextMemAllocated(context, true) };
+        let tuple_size = 100 * std::mem::size_of::<i32>();
+        let before = allocated();
+
+        let returned = unsafe {
+            PgMemoryContexts::For(context).switch_to(|_| {
+                let mut tuple = PgHeapTuple::from_composite_datum(datum);
+                tuple.modify(|_| Ok(())).unwrap();
+                tuple.into_composite_datum()
+            })
+        };
+        assert_eq!(returned, Some(datum));
+        assert_eq!(allocated(), before);
+
+        let copied = unsafe {
+            PgMemoryContexts::For(context).switch_to(|_| {
+                let mut tuple = PgHeapTuple::from_composite_datum(datum);
+                tuple.set_by_name("c1", 0).unwrap();
+                tuple.into_composite_datum()
+            })
+        };
+        assert_ne!(copied, Some(datum));
+        assert!(allocated() - before >= tuple_size, "{}", allocated() - before);
+    }
 }
@@ -128,4 +128,132 @@ mod tests {
         // TODO:  it'd be nice to also test that .commit() and .abort() also get called
         //    but I don't see how to do that since we're running *inside* a transaction here
     }
+
+    #[pg_test]
+    unsafe fn test_plan_tree_instrumentation() -> Result<(), pgx::spi::Error> {
+        use pgx::explain::InstrumentFlags;
+
+        struct ExplainHook {
+            plans: Vec<(Vec<(String, Option<String>, f64, f64)>, serde_json::Value)>,
+        }
+        impl PgHooks for ExplainHook {
+            fn executor_start(
+                &mut self,
+                mut query_desc: PgBox<pg_sys::QueryDesc>,
+                eflags: i32,
+                prev_hook: fn(PgBox<pg_sys::QueryDesc>, i32) -> HookResult<()>,
+            ) -> HookResult<()> {
+                query_desc.enable_instrumentation(InstrumentFlags::TIMER | InstrumentFlags::ROWS);
+                prev_hook(query_desc, eflags)
+            }
+
+            fn executor_end(
+                &mut self,
+                query_desc: PgBox<pg_sys::QueryDesc>,
+                prev_hook: fn(PgBox<pg_sys::QueryDesc>) -> HookResult<()>,
+            ) -> HookResult<()> {
+                let nodes = query_desc
+                    .plan_tree()
+                    .map(|node| {
+                        (
+                            node.node_name().to_string(),
+                            node.relation_name(),
+                            node.actual_rows(),
+                            node.loops(),
+                        )
+                    })
+                    .collect();
+                self.plans.push((nodes, query_desc.to_explain_json()));
+                prev_hook(query_desc)
+            }
+        }
+
+        Spi::run("CREATE TABLE hooks_tests_explain AS SELECT x FROM generate_series(1, 100) x")?;
+        static mut HOOK: ExplainHook = ExplainHook { plans: Vec::new() };
+        pgx::hooks::register_hook(&mut HOOK);
+        Spi::get_one::<i64>("SELECT count(*) FROM hooks_tests_explain WHERE x > 50")?;
+
+        let (nodes, json) = HOOK
+            .plans
+            .iter()
+            .find(|(nodes, _)| {
+                nodes
+                    .iter()
+                    .any(|(_, relation, _, _)| relation.as_deref() == Some("hooks_tests_explain"))
+            })
+            .expect("the query's plan wasn't seen");
+        assert_eq!(
+            nodes.iter().map(|(name, ..)| name.as_str()).collect::<Vec<_>>(),
+            vec!["Aggregate", "Seq Scan"]
+        );
+        let (_, _, rows, loops) = &nodes[1];
+        assert_eq!((*rows, *loops), (50.0, 1.0));
+
+        let plan = &json[0]["Plan"];
+        assert_eq!(plan["Node Type"], "Aggregate");
+        assert_eq!(plan["Actual Rows"], 1.0);
+        assert_eq!(plan["Plans"][0]["Node Type"], "Seq Scan");
+        assert_eq!(plan["Plans"][0]["Parent Relationship"], "Outer");
+        assert_eq!(plan["Plans"][0]["Relation Name"], "hooks_tests_explain");
+        assert_eq!(plan["Plans"][0]["Actual Rows"], 50.0);
+        Ok(())
+    }
+
+    #[pg_test]
+    unsafe fn test_planner_hook_adds_qual() -> Result<(), pgx::spi::Error> {
+        use pgx::make_const;
+
+        struct FalseQualHook {
+            target: pg_sys::Oid,
+        }
+        impl PgHooks for FalseQualHook {
+            fn planner(
+                &mut self,
+                parse: PgBox<pg_sys::Query>,
+                query_string: *const std::os::raw::c_char,
+                cursor_options: i32,
+                bound_params: PgBox<pg_sys::ParamListInfoData>,
+                prev_hook: fn(
+                    PgBox<pg_sys::Query>,
+                    query_string: *const std::os::raw::c_char,
+                    i32,
+                    PgBox<pg_sys::ParamListInfoData>,
+                ) -> HookResult<*mut pg_sys::PlannedStmt>,
+            ) -> HookResult<*mut pg_sys::PlannedStmt> {
+                let rtable = unsafe { PgList::<pg_sys::RangeTblEntry>::from_pg(parse.rtable) };
+                let scans_target = rtable.iter_ptr().any(|rte| unsafe {
+                    (*rte).rtekind == pg_sys::RTEKind_RTE_RELATION && (*rte).relid == self.target
+                });
+                if scans_target && parse.commandType == pg_sys::CmdType_CMD_SELECT {
+                    // WHERE <quals> AND false
+                    let jointree = unsafe { parse.jointree.as_mut() }.unwrap();
+                    let mut args = PgList::<pg_sys::Node>::new();
+                    if !jointree.quals.is_null() {
+                        args.push(jointree.quals);
+                    }
+                    args.push(make_const(false).cast());
+                    jointree.quals = unsafe {
+                        pg_sys::makeBoolExpr(pg_sys::BoolExprType_AND_EXPR, args.into_pg(), -1)
+                    }
+                    .cast();
+                }
+                prev_hook(parse, query_string, cursor_options, bound_params)
+            }
+        }
+
+        Spi::run("CREATE TABLE hooks_tests_hidden AS SELECT x FROM generate_series(1, 10) x")?;
+        Spi::run("CREATE TABLE hooks_tests_visible AS SELECT x FROM generate_series(1, 10) x")?;
+        let target = Spi::get_one::<pg_sys::Oid>("SELECT 'hooks_tests_hidden'::regclass::oid")?;
+        static mut HOOK: FalseQualHook = FalseQualHook { target: pg_sys::InvalidOid };
+        HOOK.target = target.unwrap();
+        pgx::hooks::register_hook(&mut HOOK);
+
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM hooks_tests_hidden")?, Some(0));
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM hooks_tests_hidden WHERE x > 5")?,
+            Some(0)
+        );
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM hooks_tests_visible")?, Some(10));
+        Ok(())
+    }
 }
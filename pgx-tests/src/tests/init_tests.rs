@@ -0,0 +1,61 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use std::sync::Mutex;
+
+static CALLED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Also called from the hand-written `_PG_init()`, in `shmem_tests`
+pub(crate) fn record(name: &'static str) {
+    CALLED.lock().unwrap().push(name);
+}
+
+#[pg_init(order = 1)]
+fn init_last() {
+    record("init_last");
+}
+
+#[pg_init]
+fn init_b() {
+    record("init_b");
+}
+
+#[pg_init]
+fn init_a() {
+    record("init_a");
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::CALLED;
+    use pgx::prelude::*;
+    use pgx::{PgLwLock, PgSharedMemoryInitialization};
+
+    #[pg_test]
+    fn test_pg_init_order() {
+        assert_eq!(*CALLED.lock().unwrap(), vec!["_PG_init", "init_a", "init_b", "init_last"]);
+    }
+
+    #[pg_test]
+    fn test_is_preloaded() {
+        assert!(pgx::is_preloaded());
+    }
+
+    #[pg_test(
+        error = "`pg_shmem_init!()` can only be used in `_PG_init()` while the extension is loaded through `shared_preload_libraries`"
+    )]
+    fn test_shmem_init_requires_preload() {
+        static LATE: PgLwLock<i32> = PgLwLock::new();
+        pgx::pg_shmem_init!(LATE);
+    }
+}
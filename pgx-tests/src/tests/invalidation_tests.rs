@@ -0,0 +1,133 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::invalidation::{on_relcache_change, InvalidatedCache};
+    use pgx::prelude::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn column_count(relid: pg_sys::Oid) -> i64 {
+        Spi::get_one::<i64>(&format!(
+            "SELECT count(*) FROM pg_attribute WHERE attrelid = {relid} AND attnum > 0 AND NOT attisdropped"
+        ))
+        .expect("SPI failed")
+        .expect("count was NULL")
+    }
+
+    fn create_table(name: &str) -> Result<pg_sys::Oid, pgx::spi::Error> {
+        Spi::run(&format!("CREATE TABLE tests.{name} (a int)"))?;
+        Ok(Spi::get_one::<pg_sys::Oid>(&format!("SELECT 'tests.{name}'::regclass::oid"))?
+            .expect("oid was NULL"))
+    }
+
+    #[pg_test]
+    fn test_invalidated_cache_rebuilds_after_alter_table() -> Result<(), pgx::spi::Error> {
+        let relid = create_table("invalidated_cache_test")?;
+        let other = create_table("invalidated_cache_other")?;
+
+        let cache = InvalidatedCache::<pg_sys::Oid, i64>::new();
+        let rebuilds = Cell::new(0);
+        let columns = |relid| {
+            cache.get_or_insert_with(relid, || {
+                rebuilds.set(rebuilds.get() + 1);
+                column_count(relid)
+            })
+        };
+
+        assert_eq!(columns(relid), 1);
+        assert_eq!(columns(other), 1);
+        assert_eq!(columns(relid), 1);
+        assert_eq!(rebuilds.get(), 2);
+
+        Spi::run("ALTER TABLE tests.invalidated_cache_test ADD COLUMN b int")?;
+        assert_eq!(cache.get(&relid), None);
+        assert_eq!(cache.get(&other), Some(1));
+
+        assert_eq!(columns(relid), 2);
+        assert_eq!(rebuilds.get(), 3);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_invalidated_cache_retries_after_alter_in_f() -> Result<(), pgx::spi::Error> {
+        let relid = create_table("invalidated_cache_alter_in_f")?;
+
+        let cache = InvalidatedCache::<pg_sys::Oid, i64>::new();
+        let rebuilds = Cell::new(0);
+        let columns = cache.get_or_insert_with(relid, || {
+            rebuilds.set(rebuilds.get() + 1);
+            let columns = column_count(relid);
+            if rebuilds.get() == 1 {
+                // the count we just took is stale once this is processed
+                Spi::run("ALTER TABLE tests.invalidated_cache_alter_in_f ADD COLUMN b int")
+                    .expect("SPI failed");
+            }
+            columns
+        });
+
+        assert_eq!(columns, 2);
+        assert_eq!(rebuilds.get(), 2);
+        assert_eq!(cache.get(&relid), Some(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_invalidated_cache_composite_keys() -> Result<(), pgx::spi::Error> {
+        let relid = create_table("invalidated_cache_composite")?;
+
+        let cache = InvalidatedCache::<(pg_sys::Oid, &'static str), i32>::new();
+        cache.insert((relid, "a"), 1);
+        cache.insert((relid, "b"), 2);
+        cache.insert((pg_sys::Oid::INVALID, "c"), 3);
+        assert_eq!(cache.len(), 3);
+
+        Spi::run("ALTER TABLE tests.invalidated_cache_composite RENAME COLUMN a TO z")?;
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&(pg_sys::Oid::INVALID, "c")), Some(3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_on_relcache_change() -> Result<(), pgx::spi::Error> {
+        let relid = create_table("on_relcache_change_test")?;
+
+        let seen = Rc::new(Cell::new(false));
+        let seen_by_callback = seen.clone();
+        on_relcache_change(move |changed| {
+            if changed == Some(relid) {
+                seen_by_callback.set(true);
+            }
+        });
+
+        Spi::run("ALTER TABLE tests.on_relcache_change_test ADD COLUMN b int")?;
+        assert!(seen.get());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_panicking_callback_is_contained() -> Result<(), pgx::spi::Error> {
+        let relid = create_table("panicking_relcache_callback")?;
+        on_relcache_change(move |changed| {
+            if changed == Some(relid) {
+                panic!("this callback always fails");
+            }
+        });
+
+        // the panic is reported as a WARNING, and doesn't abort our transaction
+        Spi::run("ALTER TABLE tests.panicking_relcache_callback ADD COLUMN b int")?;
+        assert_eq!(column_count(relid), 2);
+        Ok(())
+    }
+}
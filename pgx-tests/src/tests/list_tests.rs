@@ -0,0 +1,109 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{make_const, make_func_expr, make_var, node_to_string, PgList, PgMemoryContexts};
+
+    fn consts(values: &[i32]) -> Vec<*mut pg_sys::Const> {
+        values.iter().map(|&value| make_const(value)).collect()
+    }
+
+    fn values(list: &PgList<pg_sys::Const>) -> Vec<i32> {
+        list.iter_ptr()
+            .map(|c| unsafe { i32::from_datum((*c).constvalue, (*c).constisnull).unwrap() })
+            .collect()
+    }
+
+    #[pg_test]
+    fn test_collect_and_convert() {
+        let list = consts(&[1, 2, 3]).into_iter().collect::<PgList<_>>();
+        assert_eq!(values(&list), vec![1, 2, 3]);
+
+        let vec: Vec<*mut pg_sys::Const> = list.into();
+        assert_eq!(vec.len(), 3);
+        let list = PgList::from(vec);
+        assert_eq!(values(&list), vec![1, 2, 3]);
+
+        assert!(PgList::<pg_sys::Const>::from(Vec::new()).as_ptr().is_null());
+    }
+
+    #[pg_test]
+    fn test_insert_replace_concat() {
+        let mut list = PgList::from(consts(&[2, 4]));
+        let mut extra = consts(&[1, 3, 5, 6]).into_iter();
+        list.insert(0, extra.next().unwrap());
+        list.insert(2, extra.next().unwrap());
+        list.insert(4, extra.next().unwrap());
+        assert_eq!(values(&list), vec![1, 2, 3, 4, 5]);
+
+        let old = list.replace(4, extra.next().unwrap());
+        assert_eq!(unsafe { (*old).constvalue.value() }, 5);
+        assert_eq!(values(&list), vec![1, 2, 3, 4, 6]);
+
+        list.concat(PgList::from(consts(&[7, 8])));
+        list.concat(PgList::new());
+        assert_eq!(values(&list), vec![1, 2, 3, 4, 6, 7, 8]);
+
+        let mut empty = PgList::new();
+        empty.concat(list);
+        assert_eq!(values(&empty), vec![1, 2, 3, 4, 6, 7, 8]);
+    }
+
+    #[pg_test(error = "insertion index 2 is out of bounds for PgList of 1")]
+    fn test_insert_out_of_bounds() {
+        let mut list = PgList::from(consts(&[1]));
+        list.insert(2, make_const(2));
+    }
+
+    #[pg_test]
+    fn test_new_in_memory_context() {
+        let mut list = PgList::<pg_sys::Const>::new_in(PgMemoryContexts::TopTransactionContext);
+        let values_to_push = consts(&[1, 2, 3]);
+        let mut transient = PgMemoryContexts::Transient {
+            parent: PgMemoryContexts::CurrentMemoryContext.value(),
+            name: "list_tests",
+            min_context_size: 0,
+            initial_block_size: 1024,
+            max_block_size: 1024,
+        };
+        // the transient context is deleted afterwards, but the list's cells weren't allocated in it
+        unsafe {
+            transient.switch_to(|_| values_to_push.into_iter().for_each(|value| list.push(value)))
+        };
+        assert_eq!(values(&list), vec![1, 2, 3]);
+    }
+
+    #[pg_test]
+    fn test_make_nodes() -> Result<(), pgx::spi::Error> {
+        let null = make_const(None::<String>);
+        unsafe {
+            assert_eq!((*null).consttype, pg_sys::TEXTOID);
+            assert_ne!((*null).constcollid, pg_sys::InvalidOid);
+            assert!((*null).constisnull);
+            assert!(!(*null).constbyval);
+        }
+
+        let int4pl = Spi::get_one::<pg_sys::Oid>("SELECT 'int4pl'::regproc::oid")?.unwrap();
+        let var = make_var(1, 2, pg_sys::INT4OID, -1);
+        let args =
+            [var.cast(), make_const(40).cast()].into_iter().collect::<PgList<pg_sys::Node>>();
+        let expr = make_func_expr(int4pl, pg_sys::INT4OID, args);
+        let string = unsafe { node_to_string(expr.cast()) }.unwrap();
+        assert!(string.starts_with(&format!("{{FUNCEXPR :funcid {} :funcresulttype 23", int4pl)));
+        assert!(string.contains("{VAR :varno 1 :varattno 2 :vartype 23"));
+        assert!(string.contains("{CONST :consttype 23"));
+        Ok(())
+    }
+}
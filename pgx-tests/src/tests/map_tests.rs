@@ -0,0 +1,124 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use serde_json::{json, Value};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[pg_extern]
+    fn jsonb_map_keys(map: HashMap<String, Value>) -> Vec<String> {
+        let mut keys = map.into_keys().collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    #[pg_extern]
+    fn jsonb_map_of(key: &str, value: i64) -> BTreeMap<String, Value> {
+        BTreeMap::from([(key.to_string(), json!(value)), ("const".to_string(), json!([true]))])
+    }
+
+    /// Creates hstore, or returns `false` if it isn't available to be created
+    fn hstore_installed() -> Result<bool, pgx::spi::Error> {
+        let available = Spi::get_one::<bool>(
+            "SELECT EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = 'hstore')",
+        )?;
+        if available == Some(true) {
+            Spi::run("CREATE EXTENSION IF NOT EXISTS hstore")?;
+        }
+        Ok(available == Some(true))
+    }
+
+    #[pg_test]
+    fn test_jsonb_map_args_and_returns() -> Result<(), pgx::spi::Error> {
+        let keys = Spi::get_one::<Vec<String>>(
+            r#"SELECT tests.jsonb_map_keys('{"b": 1, "a": [null], "c": {}}'::jsonb)"#,
+        )?;
+        assert_eq!(keys, Some(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+
+        let map = Spi::get_one::<HashMap<String, Value>>("SELECT tests.jsonb_map_of('x', 42)")?;
+        assert_eq!(
+            map,
+            Some(HashMap::from([
+                ("x".to_string(), json!(42)),
+                ("const".to_string(), json!([true]))
+            ]))
+        );
+        let text = Spi::get_one::<String>("SELECT tests.jsonb_map_of('x', 42)::text")?;
+        assert_eq!(text.as_deref(), Some(r#"{"x": 42, "const": [true]}"#));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_hstore_from_datum() -> Result<(), pgx::spi::Error> {
+        if !hstore_installed()? {
+            return Ok(());
+        }
+
+        let map = Spi::get_one::<BTreeMap<String, Option<String>>>(
+            r#"SELECT 'b=>2, a=>1, "long key"=>NULL, empty=>""'::hstore"#,
+        )?;
+        assert_eq!(
+            map,
+            Some(BTreeMap::from([
+                ("a".to_string(), Some("1".to_string())),
+                ("b".to_string(), Some("2".to_string())),
+                ("empty".to_string(), Some(String::new())),
+                ("long key".to_string(), None),
+            ]))
+        );
+        let empty = Spi::get_one::<HashMap<String, Option<String>>>("SELECT ''::hstore")?;
+        assert_eq!(empty, Some(HashMap::new()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_hstore_into_datum() -> Result<(), pgx::spi::Error> {
+        if !hstore_installed()? {
+            return Ok(());
+        }
+
+        let map = HashMap::from([
+            ("zzz".to_string(), Some("last".to_string())),
+            ("a".to_string(), None),
+            ("mm".to_string(), Some("ünïcödé".to_string())),
+        ]);
+        let args = vec![(PgOid::from(pgx::hstore_oid()), map.into_datum())];
+        // hstore's own operators only find keys in hstores with the pairs in the right order
+        let found = Spi::get_one_with_args::<bool>(
+            "SELECT $1 ? 'zzz' AND $1 ? 'a' AND $1 -> 'mm' = 'ünïcödé' AND ($1 -> 'a') IS NULL",
+            args.clone(),
+        )?;
+        assert_eq!(found, Some(true));
+        let text = Spi::get_one_with_args::<String>("SELECT $1::text", args)?;
+        assert_eq!(text.as_deref(), Some(r#""a"=>NULL, "mm"=>"ünïcödé", "zzz"=>"last""#));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_hstore_round_trip() -> Result<(), pgx::spi::Error> {
+        if !hstore_installed()? {
+            return Ok(());
+        }
+
+        let map = (0..100)
+            .map(|i| (format!("key{}", i), if i % 7 == 0 { None } else { Some(i.to_string()) }))
+            .collect::<BTreeMap<_, _>>();
+        let args = vec![(PgOid::from(pgx::hstore_oid()), map.clone().into_datum())];
+        let round_tripped =
+            Spi::get_one_with_args::<BTreeMap<String, Option<String>>>("SELECT $1", args)?;
+        assert_eq!(round_tripped, Some(map));
+        Ok(())
+    }
+}
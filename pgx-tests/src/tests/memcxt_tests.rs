@@ -49,6 +49,30 @@ mod tests {
         assert!(did_drop.load(Ordering::SeqCst))
     }
 
+    struct PanicOnDrop;
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            panic!("PanicOnDrop was dropped");
+        }
+    }
+
+    #[pg_test(error = "PanicOnDrop was dropped")]
+    fn test_leak_and_drop_panic() {
+        unsafe {
+            PgMemoryContexts::Transient {
+                parent: PgMemoryContexts::CurrentMemoryContext.value(),
+                name: "test",
+                min_context_size: 4096,
+                initial_block_size: 4096,
+                max_block_size: 4096,
+            }
+            .switch_to(|context| {
+                context.leak_and_drop_on_delete(PanicOnDrop);
+            });
+        }
+    }
+
     #[pg_test]
     fn parent() {
         unsafe {
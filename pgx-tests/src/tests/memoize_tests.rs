@@ -0,0 +1,142 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use std::cell::Cell;
+
+thread_local! {
+    /// How many times the functions below have run since the last [`take_calls()`]
+    static CALLS: Cell<u32> = Cell::new(0);
+}
+
+fn called() {
+    CALLS.with(|calls| calls.set(calls.get() + 1));
+}
+
+fn take_calls() -> u32 {
+    CALLS.with(|calls| calls.replace(0))
+}
+
+#[pg_extern(stable, memoize)]
+fn memoize_tests_lookup(key: i32) -> Option<i64> {
+    called();
+    Spi::get_one(&format!("SELECT {}::bigint * 10", key)).expect("SPI failed")
+}
+
+#[pg_extern(stable, memoize = 2)]
+fn memoize_tests_limited(key: i32) -> i32 {
+    called();
+    key
+}
+
+#[pg_extern(stable, memoize)]
+fn memoize_tests_rate(region: &str) -> Option<f64> {
+    called();
+    Spi::get_one_with_args(
+        "SELECT rate FROM tests.memoize_tests_rates WHERE region = $1",
+        vec![(PgBuiltInOids::TEXTOID.oid(), region.into_datum())],
+    )
+    .expect("SPI failed")
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::take_calls;
+    use pgx::memoize::{self, CacheStats, StatementCache};
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_once_per_distinct_arguments() -> Result<(), pgx::spi::Error> {
+        let query =
+            "SELECT sum(memoize_tests_lookup(x % 3))::bigint FROM generate_series(1, 100) x";
+        take_calls();
+        assert_eq!(Spi::get_one::<i64>(query)?, Some(34 * 10 + 33 * 20));
+        assert_eq!(take_calls(), 3);
+
+        // the next statement runs the function again
+        assert_eq!(Spi::get_one::<i64>(query)?, Some(34 * 10 + 33 * 20));
+        assert_eq!(take_calls(), 3);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_max_entries() -> Result<(), pgx::spi::Error> {
+        let name = "pgx_tests::tests::memoize_tests::memoize_tests_limited";
+        let before = memoize::stats(name);
+        take_calls();
+        Spi::run("SELECT memoize_tests_limited(x % 5) FROM generate_series(1, 100) x")?;
+
+        // only 1 and 2, the first two keys, are cached
+        assert_eq!(take_calls(), 2 + 60);
+        let after = memoize::stats(name);
+        assert_eq!(after.hits - before.hits, 38);
+        assert_eq!(after.misses - before.misses, 62);
+        assert_eq!(after.uncached - before.uncached, 60);
+        assert_eq!(after.entries, 0);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_changes_are_seen() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TABLE tests.memoize_tests_rates (region text, rate float8);
+            INSERT INTO tests.memoize_tests_rates VALUES ('north', 1), ('south', 2)",
+        )?;
+        take_calls();
+        // the `IF`s are evaluated without running a statement, but after the `UPDATE`
+        Spi::run(
+            "DO $$
+            BEGIN
+                IF memoize_tests_rate('north') <> 1 OR memoize_tests_rate('north') <> 1 THEN
+                    RAISE EXCEPTION 'wrong rate before the update';
+                END IF;
+                UPDATE tests.memoize_tests_rates SET rate = rate * 10;
+                IF memoize_tests_rate('north') <> 10 THEN
+                    RAISE EXCEPTION 'the rate before the update was cached';
+                END IF;
+            END
+            $$",
+        )?;
+        assert_eq!(take_calls(), 2);
+
+        let total = Spi::get_one::<f64>(
+            "SELECT sum(memoize_tests_rate(region)) FROM tests.memoize_tests_rates, generate_series(1, 10)",
+        )?;
+        assert_eq!(total, Some(10.0 * 10.0 + 20.0 * 10.0));
+        assert_eq!(take_calls(), 2);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_statement_cache() {
+        static SQUARES: StatementCache<i64, i64> =
+            StatementCache::new("memoize_tests_squares").max_entries(10);
+
+        // the test itself is a statement
+        assert_eq!(SQUARES.get_or_insert_with(3, || 9), 9);
+        assert_eq!(SQUARES.get_or_insert_with(3, || unreachable!()), 9);
+        assert_eq!(SQUARES.stats(), CacheStats { hits: 1, misses: 1, uncached: 0, entries: 1 });
+
+        SQUARES.clear();
+        assert_eq!(SQUARES.get_or_insert_with(3, || -9), -9);
+        assert_eq!(SQUARES.stats().entries, 1);
+    }
+
+    #[pg_test(
+        error = "the statement cache `memoize_tests_mixed` is used with more than one key or value type"
+    )]
+    fn test_statement_cache_types() {
+        StatementCache::<i64, i64>::new("memoize_tests_mixed").get_or_insert_with(1, || 1);
+        StatementCache::<String, i64>::new("memoize_tests_mixed")
+            .get_or_insert_with(String::from("1"), || 1);
+    }
+}
@@ -0,0 +1,129 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::JsonB;
+
+#[pg_extern]
+fn mock_tests_total(values: Array<i32>) -> i64 {
+    values.iter().flatten().map(i64::from).sum()
+}
+
+#[pg_extern]
+fn mock_tests_total_deny_null(values: Array<i32>) -> i64 {
+    values.iter_deny_null().map(i64::from).sum()
+}
+
+#[pg_extern]
+fn mock_tests_join(separator: &str, values: VariadicArray<String>) -> String {
+    values.iter().flatten().collect::<Vec<_>>().join(separator)
+}
+
+#[pg_extern]
+fn mock_tests_seconds_since(date: Date, timestamp: Timestamp) -> i64 {
+    let days = date.to_unix_epoch_days().expect("infinite date");
+    timestamp.to_unix_epoch_seconds().expect("infinite timestamp") - i64::from(days) * 86_400
+}
+
+#[pg_extern]
+fn mock_tests_keys(value: JsonB) -> Vec<String> {
+    value.0.as_object().map(|object| object.keys().cloned().collect()).unwrap_or_default()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::JsonB;
+
+    // plain #[test]s, which call the functions with values made by pgx's `mock` feature
+
+    #[test]
+    fn test_mock_array() {
+        let values = Array::from_nullable_vec_for_test(vec![Some(1), None, Some(2)]);
+        assert_eq!(values.len(), 3);
+        assert_eq!(values.get(1), Some(None));
+        assert_eq!(values.get(3), None);
+        assert_eq!(format!("{:?}", values), "[1, NULL, 2]");
+        assert_eq!(super::mock_tests_total(values), 3);
+        assert_eq!(super::mock_tests_total_deny_null(Array::from_vec_for_test(vec![4, 5])), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "array contains NULL")]
+    fn test_mock_array_deny_null() {
+        super::mock_tests_total_deny_null(Array::from_nullable_vec_for_test(vec![Some(1), None]));
+    }
+
+    #[test]
+    #[should_panic(expected = "an array made for a test can't be converted")]
+    fn test_mock_array_into_array_type() {
+        Array::from_vec_for_test(vec![1]).into_array_type();
+    }
+
+    #[test]
+    fn test_mock_variadic_array() {
+        let values = VariadicArray::from_nullable_vec_for_test(vec![
+            Some(String::from("a")),
+            None,
+            Some(String::from("b")),
+        ]);
+        assert_eq!(format!("{:?}", values), r#"["a", NULL, "b"]"#);
+        assert_eq!(super::mock_tests_join(", ", values), "a, b");
+    }
+
+    #[test]
+    fn test_mock_date_and_timestamp() {
+        let date = Date::from_parts_for_test(2000, 1, 1);
+        let timestamp = Timestamp::from_parts_for_test(2000, 1, 2, 0, 0, 1.5);
+        assert_eq!(super::mock_tests_seconds_since(date, timestamp), 86_401);
+
+        let epoch = Timestamp::from_parts_for_test(1970, 1, 1, 0, 0, 0.0);
+        assert_eq!(epoch.to_unix_epoch_micros(), Ok(0));
+        let leap_day = Date::from_parts_for_test(2024, 2, 29);
+        assert_eq!(leap_day.to_unix_epoch_days(), Ok(19_782));
+        // there's no year 0, so 1 BC is followed by 1 AD
+        let bc = Date::from_parts_for_test(-1, 12, 31);
+        let ad = Date::from_parts_for_test(1, 1, 1);
+        assert_eq!(ad.to_pg_epoch_days() - bc.to_pg_epoch_days(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "day 29 is out of range for month 2")]
+    fn test_mock_date_out_of_range() {
+        Date::from_parts_for_test(2023, 2, 29);
+    }
+
+    #[test]
+    fn test_mock_jsonb() {
+        let value = JsonB::from_value(serde_json::json!({ "a": 1, "b": [true] }));
+        assert_eq!(super::mock_tests_keys(value), vec!["a", "b"]);
+    }
+
+    // and the same functions, as Postgres calls them
+
+    #[pg_test]
+    fn test_functions_from_sql() -> Result<(), pgx::spi::Error> {
+        let total = Spi::get_one::<i64>("SELECT mock_tests_total(ARRAY[1, NULL, 2])")?;
+        assert_eq!(total, Some(3));
+        let joined = Spi::get_one::<String>("SELECT mock_tests_join(', ', 'a', NULL, 'b')")?;
+        assert_eq!(joined, Some(String::from("a, b")));
+        let seconds = Spi::get_one::<i64>(
+            "SELECT mock_tests_seconds_since('2000-01-01', make_timestamp(2000, 1, 2, 0, 0, 1.5))",
+        )?;
+        assert_eq!(seconds, Some(86_401));
+        let keys = Spi::get_one::<Vec<String>>(
+            r#"SELECT mock_tests_keys('{"a": 1, "b": [true]}'::jsonb)"#,
+        )?;
+        assert_eq!(keys, Some(vec![String::from("a"), String::from("b")]));
+        Ok(())
+    }
+}
@@ -7,47 +7,110 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 
+mod agg_tests;
 mod aggregate_tests;
 mod anyarray_tests;
+mod arena_tests;
 mod array_tests;
+mod arrow_tests;
 mod attributes_tests;
+mod backend_local_tests;
+mod backend_tests;
 mod bgworker_tests;
 mod bytea_tests;
+mod cached_function_tests;
 mod cfg_tests;
+mod clock_tests;
+mod compat_tests;
+mod composite_ops_tests;
+mod config_dump_tests;
+mod config_table_tests;
 mod datetime_tests;
+mod datum_debug_tests;
 mod default_arg_value_tests;
+mod deferred_tests;
 mod derive_pgtype_lifetimes;
+mod domain_tests;
+mod drop_order_tests;
 mod enum_type_tests;
+mod error_report_tests;
+mod export_abi_tests;
+#[cfg(feature = "cshim")]
+mod expr_tests;
+mod extension_sql_tests;
 mod fcinfo_tests;
 mod from_into_datum_tests;
+mod function_stats_tests;
+mod gin_tests;
 mod guc_tests;
 mod heap_tuple;
 #[cfg(feature = "cshim")]
 mod hooks_tests;
 mod inet_tests;
+mod init_tests;
 mod internal_tests;
+mod invalidation_tests;
 mod json_tests;
 mod lifetime_tests;
+mod list_tests;
 mod log_tests;
+mod map_tests;
 mod memcxt_tests;
+mod memoize_tests;
+mod mock_tests;
+mod money_tests;
 mod name_tests;
+mod non_zero_tests;
+mod notify_tests;
 mod numeric_tests;
+mod oidvector_tests;
+mod operator_tests;
+mod parallel_tests;
+mod partition_tests;
+mod paths_tests;
+mod pg_cast_tests;
+mod pg_constant_tests;
 mod pg_extern_tests;
 mod pg_guard_tests;
+mod pg_policy_tests;
 mod pg_try_tests;
+mod pg_view_tests;
 mod pgbox_tests;
 mod pgx_module_qualification;
+mod polymorphic_tests;
 mod postgres_type_tests;
+mod privileges_tests;
+mod quote_tests;
+mod random_tests;
 mod range_tests;
+mod record_tests;
 mod result_tests;
 mod schema_tests;
+mod send_recv_tests;
+mod shm_mq_tests;
 mod shmem_tests;
+mod slot_tests;
+mod spi_csv_tests;
+mod spi_query_tests;
 mod spi_tests;
 mod srf_tests;
+mod statement_tests;
+mod statistic_tests;
+mod stats_tests;
+mod stringinfo_tests;
 mod struct_type_tests;
+mod table_row_tests;
+mod temp_file_tests;
+mod temp_relation_tests;
+mod test_fixture_tests;
+mod toast_tests;
 mod trigger_tests;
+mod tsearch_tests;
+mod tupdesc_tests;
 mod uuid_tests;
+mod varbit_tests;
 mod variadic_tests;
+mod version_check_tests;
 mod xact_callback_tests;
 mod xid64_tests;
 mod zero_datum_edge_cases;
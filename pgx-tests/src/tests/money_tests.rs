@@ -0,0 +1,75 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::Money;
+
+#[pg_extern]
+fn money_tests_add_tax(price: Money, percent: i64) -> Money {
+    price + Money::from_cents(price.cents() * percent / 100)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{AnyNumeric, Money};
+
+    #[pg_test]
+    fn test_money_round_trip() -> Result<(), pgx::spi::Error> {
+        Spi::run("SET LOCAL lc_monetary = 'C'")?;
+        let money = Spi::get_one::<Money>("SELECT '12.34'::money")?;
+        assert_eq!(money, Some(Money::from_cents(1234)));
+
+        let text = Spi::get_one_with_args::<String>(
+            "SELECT $1::numeric::text",
+            vec![(PgBuiltInOids::CASHOID.oid(), Money::from_cents(-505).into_datum())],
+        )?;
+        assert_eq!(text.as_deref(), Some("-5.05"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_money_function() -> Result<(), pgx::spi::Error> {
+        Spi::run("SET LOCAL lc_monetary = 'C'")?;
+        let money = Spi::get_one::<Money>("SELECT money_tests_add_tax('10.00', 8)")?;
+        assert_eq!(money, Some(Money::from_cents(1080)));
+
+        let signature = Spi::get_one::<String>(
+            "SELECT pg_get_function_identity_arguments('money_tests_add_tax'::regproc)",
+        )?;
+        assert_eq!(signature.as_deref(), Some("price money, percent bigint"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_money_arithmetic() {
+        let a = Money::from_cents(150);
+        let b = Money::from_cents(275);
+        assert_eq!(a + b, Money::from_cents(425));
+        assert_eq!(a - b, Money::from_cents(-125));
+        assert_eq!(-a, Money::from_cents(-150));
+        assert_eq!(Money::MAX.checked_add(a), None);
+        assert_eq!(b.checked_div(2), Some(Money::from_cents(137)));
+        assert_eq!(b.checked_div(0), None);
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+    }
+
+    #[pg_test]
+    fn test_money_numeric() {
+        assert_eq!(AnyNumeric::from(Money::from_cents(-1234)).to_string(), "-12.34");
+        assert_eq!(
+            Money::try_from(AnyNumeric::try_from("1.005").unwrap()),
+            Ok(Money::from_cents(101))
+        );
+        assert!(Money::try_from(AnyNumeric::try_from("1e30").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use std::num::{NonZeroI16, NonZeroI32, NonZeroI64};
+
+    #[pg_extern]
+    fn non_zero_tests_next_id(id: NonZeroI32) -> NonZeroI64 {
+        NonZeroI64::new(i64::from(id.get()) + 1).unwrap()
+    }
+
+    #[pg_extern]
+    fn non_zero_tests_maybe(id: Option<NonZeroI16>) -> Option<NonZeroI16> {
+        id
+    }
+
+    #[pg_test]
+    fn test_non_zero_round_trip() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<i64>("SELECT tests.non_zero_tests_next_id(41)")?, Some(42));
+        assert_eq!(Spi::get_one::<NonZeroI32>("SELECT -7")?, Some(NonZeroI32::new(-7).unwrap()));
+        assert_eq!(
+            Spi::get_one::<NonZeroI16>("SELECT tests.non_zero_tests_maybe(3::smallint)")?,
+            NonZeroI16::new(3)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_null_is_none() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<NonZeroI16>("SELECT tests.non_zero_tests_maybe(NULL)")?, None);
+        assert_eq!(Spi::get_one::<NonZeroI64>("SELECT NULL::bigint")?, None);
+        Ok(())
+    }
+
+    #[pg_test(error = "argument `id` can't be zero, because it's a NonZeroI32")]
+    fn test_zero_argument() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<i64>("SELECT tests.non_zero_tests_next_id(0)").map(|_| ())
+    }
+
+    #[pg_test(error = "argument `id` can't be zero, because it's a NonZeroI16")]
+    fn test_zero_optional_argument() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<i16>("SELECT tests.non_zero_tests_maybe(0::smallint)").map(|_| ())
+    }
+
+    #[pg_test(error = "a NonZeroI64 can't be zero")]
+    fn test_zero_value() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<NonZeroI64>("SELECT 0::bigint").map(|_| ())
+    }
+}
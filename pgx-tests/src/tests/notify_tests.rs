@@ -0,0 +1,166 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::notify::Channel;
+use pgx::prelude::*;
+use pgx::{FromDatum, IntoDatum, PgOid};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Tick {
+    seq: i32,
+}
+
+pg_notify_channel!(
+    /// Numbered ticks, sent between the background workers of `notify_tests`
+    pub static TICKS: Channel<Tick> = "notify_tests_ticks";
+);
+
+#[pg_guard]
+#[no_mangle]
+/// Subscribes to `TICKS`, starts `notify_tests_sender`, and records what it sends in
+/// `tests.notify_tests_received`
+pub extern "C" fn notify_tests_listener(arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    use std::time::{Duration, Instant};
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(
+        Some(crate::framework::get_pg_dbname()),
+        Some(crate::framework::get_pg_user().as_str()),
+    );
+    let count = unsafe { i32::from_datum(arg, false) }.expect("invalid arg");
+
+    let mut ticks = TICKS.subscribe();
+    BackgroundWorkerBuilder::new("notify_tests_sender")
+        .set_library("pgx_tests")
+        .set_function("notify_tests_sender")
+        .set_argument(count.into_datum())
+        .enable_spi_access()
+        .load_dynamic();
+
+    let mut seqs = Vec::new();
+    let mut malformed = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while seqs.len() < count as usize || malformed.is_empty() {
+        if Instant::now() > deadline
+            || !BackgroundWorker::wait_latch(Some(Duration::from_millis(100)))
+        {
+            break;
+        }
+        for tick in ticks.pending() {
+            match tick {
+                Ok(tick) => seqs.push(tick.seq),
+                Err(e) => malformed.push(e.payload),
+            }
+        }
+    }
+
+    BackgroundWorker::transaction(|| {
+        Spi::run_with_args(
+            "CREATE TABLE tests.notify_tests_received AS SELECT $1 AS seqs, $2 AS malformed",
+            Some(vec![
+                (PgOid::BuiltIn(PgBuiltInOids::INT4ARRAYOID), seqs.into_datum()),
+                (PgOid::BuiltIn(PgBuiltInOids::TEXTARRAYOID), malformed.into_datum()),
+            ]),
+        )
+    })
+    .expect("bgworker transaction failed");
+}
+
+#[pg_guard]
+#[no_mangle]
+/// Sends `arg` ticks in one transaction, with a payload that isn't a tick after the first
+pub extern "C" fn notify_tests_sender(arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(
+        Some(crate::framework::get_pg_dbname()),
+        Some(crate::framework::get_pg_user().as_str()),
+    );
+    let count = unsafe { i32::from_datum(arg, false) }.expect("invalid arg");
+
+    BackgroundWorker::transaction(|| {
+        for seq in 0..count {
+            TICKS.notify(&Tick { seq }).expect("couldn't send a tick");
+            if seq == 0 {
+                pgx::notify::notify(TICKS.name(), "not a tick").expect("couldn't send a non-tick");
+            }
+        }
+    });
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::bgworkers::*;
+    use pgx::notify::{self, Channel, NotifyError, MAX_CHANNEL_LEN, MAX_PAYLOAD_LEN};
+    use pgx::prelude::*;
+    use pgx::IntoDatum;
+
+    #[pg_test]
+    fn test_delivery_order() -> Result<(), pgx::spi::Error> {
+        let listener = BackgroundWorkerBuilder::new("notify_tests_listener")
+            .set_library("pgx_tests")
+            .set_function("notify_tests_listener")
+            .set_argument(100i32.into_datum())
+            .enable_spi_access()
+            .set_notify_pid(unsafe { pg_sys::MyProcPid })
+            .load_dynamic();
+        listener.wait_for_shutdown().expect("aborted shutdown");
+
+        // in "mutable" mode, so it sees what the listener committed after this test started
+        let (seqs, malformed) = Spi::connect(|mut client| {
+            client
+                .update("SELECT seqs, malformed FROM tests.notify_tests_received", None, None)?
+                .first()
+                .get_two::<Vec<i32>, Vec<String>>()
+        })?;
+        assert_eq!(seqs, Some((0..100).collect::<Vec<_>>()));
+        assert_eq!(malformed, Some(vec![String::from("not a tick")]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_payload_size_limit() {
+        assert_eq!(MAX_PAYLOAD_LEN, 7999);
+        let channel = Channel::<String>::new("notify_tests_sizes");
+
+        // with its quotes, the JSON string is as long as a payload can be
+        let longest = "x".repeat(MAX_PAYLOAD_LEN - 2);
+        channel.notify(&longest).expect("the longest payload wasn't sent");
+        notify::notify(channel.name(), &"x".repeat(MAX_PAYLOAD_LEN))
+            .expect("the longest raw payload wasn't sent");
+
+        let too_long = "x".repeat(MAX_PAYLOAD_LEN - 1);
+        match channel.notify(&too_long) {
+            Err(NotifyError::PayloadTooLarge { channel, len }) => {
+                assert_eq!(channel, "notify_tests_sizes");
+                assert_eq!(len, MAX_PAYLOAD_LEN + 1);
+            }
+            other => panic!("expected PayloadTooLarge, not {:?}", other),
+        }
+    }
+
+    #[pg_test]
+    fn test_invalid_channels_and_payloads() {
+        let longest = "c".repeat(MAX_CHANNEL_LEN);
+        assert!(notify::notify(&longest, "").is_ok());
+        let too_long = "c".repeat(MAX_CHANNEL_LEN + 1);
+        assert!(matches!(notify::notify(&too_long, ""), Err(NotifyError::InvalidChannel(_))));
+        assert!(matches!(notify::notify("", ""), Err(NotifyError::InvalidChannel(_))));
+        assert!(matches!(notify::notify("nul", "a\0b"), Err(NotifyError::NulInPayload)));
+    }
+
+    #[pg_test(error = "only background workers connected to a database can subscribe to a channel")]
+    fn test_subscribe_outside_a_background_worker() {
+        let _ = super::TICKS.subscribe();
+    }
+}
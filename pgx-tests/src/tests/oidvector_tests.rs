@@ -0,0 +1,69 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::{Int2Vector, OidVector};
+
+#[pg_extern]
+fn oidvector_tests_len(types: OidVector) -> i32 {
+    types.len() as i32
+}
+
+#[pg_extern]
+fn oidvector_tests_reverse(columns: Int2Vector) -> Int2Vector {
+    columns.iter().rev().copied().collect()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{Int2Vector, OidVector};
+
+    #[pg_test]
+    fn test_proargtypes() -> Result<(), pgx::spi::Error> {
+        let types = Spi::get_one::<OidVector>(
+            "SELECT proargtypes FROM pg_proc WHERE oid = 'int4pl'::regproc",
+        )?
+        .expect("proargtypes was null");
+        assert_eq!(types.as_slice(), &[pg_sys::INT4OID, pg_sys::INT4OID]);
+        assert_eq!(types.iter().count(), 2);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_empty_oidvector() -> Result<(), pgx::spi::Error> {
+        let types = Spi::get_one::<OidVector>(
+            "SELECT proargtypes FROM pg_proc WHERE oid = 'now'::regproc",
+        )?;
+        assert_eq!(types, Some(OidVector::default()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_oidvector_argument() -> Result<(), pgx::spi::Error> {
+        let len = Spi::get_one::<i32>("SELECT oidvector_tests_len('23 25 16'::oidvector)")?;
+        assert_eq!(len, Some(3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_int2vector() -> Result<(), pgx::spi::Error> {
+        let reversed =
+            Spi::get_one::<Int2Vector>("SELECT oidvector_tests_reverse('1 2 3'::int2vector)")?;
+        assert_eq!(reversed.as_deref(), Some(&[3_i16, 2, 1][..]));
+
+        let text =
+            Spi::get_one::<String>("SELECT oidvector_tests_reverse('4 5'::int2vector)::text")?;
+        assert_eq!(text.as_deref(), Some("5 4"));
+        Ok(())
+    }
+}
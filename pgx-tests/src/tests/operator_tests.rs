@@ -0,0 +1,153 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(#+#)]
+fn operator_tests_coalesce_add(left: Option<i32>, right: Option<i32>) -> Option<i32> {
+    Some(left.unwrap_or(0) + right.unwrap_or(0))
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(#-#)]
+fn operator_tests_sub(left: i32, right: i32) -> i32 {
+    left - right
+}
+
+#[derive(Serialize, Deserialize, PostgresType)]
+pub struct OperatorTestsLength {
+    millimeters: i64,
+}
+
+// `<` names `>` before it's defined, which is still created first
+#[pg_operator(immutable, parallel_safe)]
+#[opname(<)]
+#[commutator(>)]
+fn operator_tests_length_lt(left: OperatorTestsLength, right: OperatorTestsLength) -> bool {
+    left.millimeters < right.millimeters
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(>)]
+#[commutator(<)]
+fn operator_tests_length_gt(left: OperatorTestsLength, right: OperatorTestsLength) -> bool {
+    left.millimeters > right.millimeters
+}
+
+#[pg_schema]
+mod operator_tests_schema {
+    use pgx::prelude::*;
+
+    #[pg_operator(immutable, parallel_safe)]
+    #[opname(#*#)]
+    fn operator_tests_coalesce_mul(left: Option<i32>, right: Option<i32>) -> Option<i32> {
+        Some(left.unwrap_or(1) * right.unwrap_or(1))
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    const OPERANDS: [(Option<i32>, Option<i32>); 4] =
+        [(None, None), (Some(2), None), (None, Some(3)), (Some(2), Some(3))];
+
+    fn evaluate(
+        sql: &str,
+        (left, right): (Option<i32>, Option<i32>),
+    ) -> Result<Option<i32>, pgx::spi::Error> {
+        Spi::get_one_with_args::<i32>(
+            sql,
+            vec![
+                (PgBuiltInOids::INT4OID.oid(), left.into_datum()),
+                (PgBuiltInOids::INT4OID.oid(), right.into_datum()),
+            ],
+        )
+    }
+
+    fn is_strict(function: &str) -> Result<Option<bool>, pgx::spi::Error> {
+        Spi::get_one_with_args::<bool>(
+            "SELECT proisstrict FROM pg_proc WHERE proname = $1",
+            vec![(PgBuiltInOids::TEXTOID.oid(), function.into_datum())],
+        )
+    }
+
+    #[pg_test]
+    fn test_optional_operator_is_not_strict() -> Result<(), pgx::spi::Error> {
+        assert_eq!(is_strict("operator_tests_coalesce_add")?, Some(false));
+        for operands in OPERANDS {
+            let by_operator = evaluate("SELECT $1 #+# $2", operands)?;
+            let by_function = evaluate("SELECT operator_tests_coalesce_add($1, $2)", operands)?;
+            assert_eq!(by_operator, by_function, "{:?}", operands);
+        }
+        assert_eq!(evaluate("SELECT $1 #+# $2", (None, Some(3)))?, Some(3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_operator_is_strict() -> Result<(), pgx::spi::Error> {
+        assert_eq!(is_strict("operator_tests_sub")?, Some(true));
+        for operands in OPERANDS {
+            let by_operator = evaluate("SELECT $1 #-# $2", operands)?;
+            let by_function = evaluate("SELECT operator_tests_sub($1, $2)", operands)?;
+            assert_eq!(by_operator, by_function, "{:?}", operands);
+        }
+        assert_eq!(evaluate("SELECT $1 #-# $2", (Some(2), None))?, None);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_operator_in_schema() -> Result<(), pgx::spi::Error> {
+        assert_eq!(is_strict("operator_tests_coalesce_mul")?, Some(false));
+        for operands in OPERANDS {
+            let by_operator =
+                evaluate("SELECT $1 OPERATOR(operator_tests_schema.#*#) $2", operands)?;
+            let by_function = evaluate(
+                "SELECT operator_tests_schema.operator_tests_coalesce_mul($1, $2)",
+                operands,
+            )?;
+            assert_eq!(by_operator, by_function, "{:?}", operands);
+        }
+        assert_eq!(
+            evaluate("SELECT $1 OPERATOR(operator_tests_schema.#*#) $2", (None, None))?,
+            Some(1)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_commutators_are_linked() -> Result<(), pgx::spi::Error> {
+        let linked = Spi::get_one::<bool>(
+            "SELECT lt.oprcom = gt.oid AND gt.oprcom = lt.oid
+            FROM pg_operator lt, pg_operator gt
+            WHERE lt.oprcode = 'operator_tests_length_lt'::regproc
+            AND gt.oprcode = 'operator_tests_length_gt'::regproc",
+        )?;
+        assert_eq!(linked, Some(true));
+        // no shell operator was left behind for either
+        let shells = Spi::get_one::<i64>(
+            "SELECT count(*) FROM pg_operator
+            WHERE oprcode = 0 AND oprleft = 'operatortestslength'::regtype",
+        )?;
+        assert_eq!(shells, Some(0));
+        assert_eq!(
+            Spi::get_one::<bool>(
+                r#"SELECT '{"millimeters": 1}'::OperatorTestsLength
+                    < '{"millimeters": 2}'::OperatorTestsLength"#
+            )?,
+            Some(true)
+        );
+        Ok(())
+    }
+}
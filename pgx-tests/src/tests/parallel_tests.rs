@@ -0,0 +1,100 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+extension_sql!(
+    r#"
+CREATE TABLE parallel_tests_log (id int);
+CREATE SEQUENCE parallel_tests_seq;
+"#,
+    name = "create_parallel_tests_log"
+);
+
+#[pg_extern(parallel_safe)]
+fn parallel_tests_safe() -> i32 {
+    1
+}
+
+#[pg_extern(parallel_restricted)]
+fn parallel_tests_restricted() -> i32 {
+    1
+}
+
+#[pg_extern(parallel_unsafe)]
+fn parallel_tests_unsafe() -> i32 {
+    1
+}
+
+/// Wrongly declared `parallel_safe`, as it writes to a table
+#[pg_extern(parallel_safe, requires = ["create_parallel_tests_log"])]
+fn parallel_tests_insert() -> i32 {
+    Spi::run("INSERT INTO parallel_tests_log VALUES (1)").unwrap();
+    1
+}
+
+/// Wrongly declared `parallel_safe`, as it advances a sequence
+#[pg_extern(parallel_safe, requires = ["create_parallel_tests_log"])]
+fn parallel_tests_nextval() -> i64 {
+    Spi::get_one("SELECT nextval('parallel_tests_seq')").unwrap().unwrap()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    fn force_parallel_mode() -> Result<(), pgx::spi::Error> {
+        Spi::run("SET LOCAL force_parallel_mode = on")?;
+        Spi::run("SET LOCAL parallel_setup_cost = 0")?;
+        Spi::run("SET LOCAL parallel_tuple_cost = 0")
+    }
+
+    #[pg_test]
+    fn test_parallel_levels() -> Result<(), pgx::spi::Error> {
+        for (function, proparallel) in [
+            ("parallel_tests_safe", "s"),
+            ("parallel_tests_restricted", "r"),
+            ("parallel_tests_unsafe", "u"),
+        ] {
+            let actual = Spi::get_one::<String>(&format!(
+                "SELECT proparallel::text FROM pg_proc WHERE proname = '{}'",
+                function
+            ))?;
+            assert_eq!(actual.as_deref(), Some(proparallel), "{}", function);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_parallel_safe_in_worker() -> Result<(), pgx::spi::Error> {
+        force_parallel_mode()?;
+        let value = Spi::get_one::<i32>("SELECT parallel_tests_safe()")?;
+        assert_eq!(value, Some(1));
+        Ok(())
+    }
+
+    #[pg_test(
+        error = "function `parallel_tests_insert` is declared `parallel_safe`, but tried to run the SPI query `INSERT INTO parallel_tests_log VALUES (1)` in a parallel worker"
+    )]
+    fn test_parallel_safe_spi_write() -> Result<Option<i32>, pgx::spi::Error> {
+        force_parallel_mode()?;
+        Spi::get_one("SELECT parallel_tests_insert()")
+    }
+
+    #[pg_test(
+        error = "function `parallel_tests_nextval` is declared `parallel_safe`, but tried to run the SPI query `SELECT nextval('parallel_tests_seq')` in a parallel worker"
+    )]
+    fn test_parallel_safe_nextval() -> Result<Option<i64>, pgx::spi::Error> {
+        force_parallel_mode()?;
+        Spi::get_one("SELECT parallel_tests_nextval()")
+    }
+}
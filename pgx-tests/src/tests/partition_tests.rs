@@ -0,0 +1,198 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use std::cell::RefCell;
+
+thread_local! {
+    static FIRED_ON: RefCell<Vec<(String, String)>> = RefCell::new(Vec::new());
+}
+
+#[pg_trigger]
+fn record_partition_root(
+    trigger: &pgx::PgTrigger,
+) -> Result<PgHeapTuple<'_, AllocatedByPostgres>, pgx::spi::Error> {
+    let (relation, root) = unsafe { (trigger.relation().unwrap(), trigger.root_relation()?) };
+    FIRED_ON.with(|fired_on| {
+        fired_on.borrow_mut().push((relation.name().to_string(), root.name().to_string()))
+    });
+    Ok(trigger.new().expect("not an INSERT trigger"))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{PartitionKey, PartitionStrategy, RelKind};
+
+    fn create_measurements() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "CREATE TABLE tests.measurements (id int, region text, value int) PARTITION BY LIST (region);
+             CREATE TABLE tests.measurements_east PARTITION OF tests.measurements FOR VALUES IN ('east');
+             CREATE TABLE tests.measurements_west PARTITION OF tests.measurements FOR VALUES IN ('west')
+                 PARTITION BY RANGE (id);
+             CREATE TABLE tests.measurements_west_low PARTITION OF tests.measurements_west
+                 FOR VALUES FROM (MINVALUE) TO (10);
+             CREATE TABLE tests.measurements_west_high PARTITION OF tests.measurements_west
+                 FOR VALUES FROM (10) TO (MAXVALUE);
+             CREATE TABLE tests.measurements_other PARTITION OF tests.measurements DEFAULT;",
+        )
+    }
+
+    fn names(relations: Vec<PgRelation>) -> Vec<String> {
+        let mut names = relations.iter().map(|rel| rel.name().to_string()).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    fn find_partition_for(table: &PgRelation, row: &str) -> Result<String, pgx::spi::Error> {
+        let tuple = Spi::get_one::<PgHeapTuple<'_, AllocatedByRust>>(&format!(
+            "SELECT {}::tests.measurements",
+            row
+        ))?
+        .unwrap();
+        Ok(table.find_partition_for(&tuple).name().to_string())
+    }
+
+    #[pg_test]
+    fn test_relkind() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        Spi::run("CREATE INDEX measurements_id ON tests.measurements (id)")?;
+        Spi::run("CREATE VIEW tests.measurements_view AS SELECT * FROM tests.measurements")?;
+
+        let parent = PgRelation::open_with_name_and_share_lock("tests.measurements").unwrap();
+        assert_eq!(parent.relkind(), RelKind::PartitionedTable);
+        assert!(parent.is_partitioned());
+        assert!(!parent.is_partition());
+        assert!(!parent.is_table());
+
+        let east = PgRelation::open_with_name_and_share_lock("tests.measurements_east").unwrap();
+        assert_eq!(east.relkind(), RelKind::Table);
+        assert!(!east.is_partitioned());
+        assert!(east.is_partition());
+
+        let west = PgRelation::open_with_name_and_share_lock("tests.measurements_west").unwrap();
+        assert!(west.is_partitioned());
+        assert!(west.is_partition());
+
+        let index = PgRelation::open_with_name_and_share_lock("tests.measurements_id").unwrap();
+        assert_eq!(index.relkind(), RelKind::PartitionedIndex);
+        assert!(!index.is_partitioned());
+        let view = PgRelation::open_with_name_and_share_lock("tests.measurements_view").unwrap();
+        assert_eq!(view.relkind(), RelKind::View);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_partitions() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        let parent = PgRelation::open_with_name_and_share_lock("tests.measurements").unwrap();
+        assert_eq!(
+            names(parent.partitions()?),
+            vec!["measurements_east", "measurements_other", "measurements_west"]
+        );
+        let west = PgRelation::open_with_name_and_share_lock("tests.measurements_west").unwrap();
+        assert_eq!(
+            names(west.partitions()?),
+            vec!["measurements_west_high", "measurements_west_low"]
+        );
+        let east = PgRelation::open_with_name_and_share_lock("tests.measurements_east").unwrap();
+        assert!(east.partitions()?.is_empty());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_partition_key() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        Spi::run(
+            "CREATE TABLE tests.hashed (a int, b text, c int) PARTITION BY HASH (c, (a * 2), b)",
+        )?;
+
+        let parent = PgRelation::open_with_name_and_share_lock("tests.measurements").unwrap();
+        assert_eq!(
+            parent.partition_key()?,
+            Some(PartitionKey {
+                strategy: PartitionStrategy::List,
+                columns: vec![Some("region".into())]
+            })
+        );
+        let west = PgRelation::open_with_name_and_share_lock("tests.measurements_west").unwrap();
+        assert_eq!(west.partition_key()?.map(|key| key.strategy), Some(PartitionStrategy::Range));
+        let hashed = PgRelation::open_with_name_and_share_lock("tests.hashed").unwrap();
+        assert_eq!(
+            hashed.partition_key()?,
+            Some(PartitionKey {
+                strategy: PartitionStrategy::Hash,
+                columns: vec![Some("c".into()), None, Some("b".into())]
+            })
+        );
+        let east = PgRelation::open_with_name_and_share_lock("tests.measurements_east").unwrap();
+        assert_eq!(east.partition_key()?, None);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_partition_root() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        let low = PgRelation::open_with_name_and_share_lock("tests.measurements_west_low").unwrap();
+        assert_eq!(
+            low.partition_root()?.map(|root| root.name().to_string()).as_deref(),
+            Some("measurements")
+        );
+        let parent = PgRelation::open_with_name_and_share_lock("tests.measurements").unwrap();
+        assert!(parent.partition_root()?.is_none());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_find_partition_for() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        let parent = PgRelation::open_with_name_and_share_lock("tests.measurements").unwrap();
+        assert_eq!(find_partition_for(&parent, "(1, 'east', 0)")?, "measurements_east");
+        assert_eq!(find_partition_for(&parent, "(5, 'west', 0)")?, "measurements_west_low");
+        assert_eq!(find_partition_for(&parent, "(50, 'west', 0)")?, "measurements_west_high");
+        assert_eq!(find_partition_for(&parent, "(1, 'north', 0)")?, "measurements_other");
+        assert_eq!(find_partition_for(&parent, "(1, NULL, 0)")?, "measurements_other");
+
+        // a sub-partitioned table routes rows of its own
+        let west = PgRelation::open_with_name_and_share_lock("tests.measurements_west").unwrap();
+        assert_eq!(find_partition_for(&west, "(5, 'west', 0)")?, "measurements_west_low");
+        Ok(())
+    }
+
+    #[pg_test(error = "no partition of relation \"measurements_west\" found for row")]
+    fn test_find_partition_for_nowhere() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        // without a default partition, a row can have nowhere to go
+        let west = PgRelation::open_with_name_and_share_lock("tests.measurements_west").unwrap();
+        find_partition_for(&west, "(NULL, 'west', 0)")?;
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_trigger_root_relation() -> Result<(), pgx::spi::Error> {
+        create_measurements()?;
+        Spi::run(
+            "CREATE TRIGGER record_root AFTER INSERT ON tests.measurements
+                FOR EACH ROW EXECUTE PROCEDURE tests.record_partition_root()",
+        )?;
+        Spi::run("INSERT INTO tests.measurements VALUES (1, 'east', 0), (20, 'west', 0)")?;
+        let fired_on = super::FIRED_ON.with(|fired_on| fired_on.take());
+        assert_eq!(
+            fired_on,
+            vec![
+                ("measurements_east".to_string(), "measurements".to_string()),
+                ("measurements_west_high".to_string(), "measurements".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
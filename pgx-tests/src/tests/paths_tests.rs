@@ -0,0 +1,81 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::paths::{self, FileAccessError};
+    use pgx::prelude::*;
+    use pgx::{ErrorReportable, PgSqlErrorCode};
+    use std::path::{Path, PathBuf};
+
+    fn setting(name: &str) -> Result<PathBuf, pgx::spi::Error> {
+        let value = Spi::get_one::<String>(&format!("SELECT current_setting('{name}')"))?;
+        Ok(PathBuf::from(value.expect("a setting")))
+    }
+
+    #[pg_test]
+    fn test_dirs() -> Result<(), pgx::spi::Error> {
+        assert_eq!(paths::data_dir(), setting("data_directory")?);
+        assert_eq!(paths::log_dir(), paths::data_dir().join(setting("log_directory")?));
+        assert_eq!(paths::tmp_dir(), paths::data_dir().join("base/pgsql_tmp"));
+        assert!(paths::extension_share_dir().join("pgx_tests.control").exists());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_write_and_read() -> Result<(), FileAccessError> {
+        let path = Path::new("pgx_paths_tests.txt");
+        paths::write_file_durable(path, b"first")?;
+        paths::write_file_durable(path, b"second")?;
+        assert_eq!(paths::read_file(path)?, b"second");
+        assert_eq!(paths::read_file(paths::data_dir().join(path))?, b"second");
+
+        // nothing's left beside it
+        let leftovers = std::fs::read_dir(paths::data_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("pgx_paths_tests.txt."))
+            .count();
+        assert_eq!(leftovers, 0);
+        std::fs::remove_file(paths::data_dir().join(path)).unwrap();
+
+        let missing = paths::read_file(path).unwrap_err();
+        assert_eq!(missing.sql_error_code(), PgSqlErrorCode::ERRCODE_UNDEFINED_FILE);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_checks() -> Result<(), pgx::spi::Error> {
+        // the test user is a superuser, who can use any path
+        assert_eq!(paths::check_read_path("/etc/./hosts").unwrap(), Path::new("/etc/hosts"));
+
+        Spi::run("CREATE ROLE paths_tests_nobody; SET ROLE paths_tests_nobody")?;
+        assert!(matches!(
+            paths::check_read_path("/etc/hosts"),
+            Err(FileAccessError::AbsolutePath(_))
+        ));
+        assert!(matches!(
+            paths::check_write_path("../outside.txt"),
+            Err(FileAccessError::OutsideDataDir(_))
+        ));
+        let inside = paths::data_dir().join("base/../global");
+        assert_eq!(paths::check_read_path(&inside).unwrap(), paths::data_dir().join("global"));
+        assert_eq!(paths::check_write_path("a/./b").unwrap(), Path::new("a/b"));
+
+        let error = paths::write_file_durable("/etc/hosts", b"").unwrap_err();
+        assert_eq!(error.sql_error_code(), PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE);
+        assert_eq!(error.to_string(), "absolute path not allowed");
+        Spi::run("RESET ROLE")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::JsonB;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PostgresType)]
+pub struct CastTestsCelsius {
+    degrees: f64,
+}
+
+#[pg_extern(immutable, parallel_safe)]
+#[pg_cast(implicit)]
+fn cast_tests_celsius_to_jsonb(value: CastTestsCelsius) -> JsonB {
+    JsonB(serde_json::json!({ "celsius": value.degrees }))
+}
+
+#[pg_extern(immutable, parallel_safe)]
+#[pg_cast(assignment)]
+fn cast_tests_celsius_to_float8(value: CastTestsCelsius) -> f64 {
+    value.degrees
+}
+
+// the attributes work in either order
+#[pg_cast]
+#[pg_extern(immutable, parallel_safe)]
+fn cast_tests_celsius_to_int8(value: CastTestsCelsius) -> i64 {
+    value.degrees.round() as i64
+}
+
+// only names the types, as the cast reinterprets the value's bytes
+#[pg_cast(binary)]
+#[allow(dead_code)]
+fn cast_tests_celsius_as_bytea(_value: CastTestsCelsius) -> Vec<u8> {
+    unreachable!("a binary-coercible cast doesn't call a function")
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    /// The `castcontext` and `castmethod` of the cast from `CastTestsCelsius` to `target`
+    fn cast_of(target: &str) -> Result<(Option<String>, Option<String>), pgx::spi::Error> {
+        Spi::get_two(&format!(
+            "SELECT castcontext::text, castmethod::text FROM pg_cast
+            WHERE castsource = 'CastTestsCelsius'::regtype AND casttarget = '{}'::regtype",
+            target
+        ))
+    }
+
+    #[pg_test]
+    fn test_cast_contexts() -> Result<(), pgx::spi::Error> {
+        let function = Some(String::from("f"));
+        assert_eq!(cast_of("jsonb")?, (Some(String::from("i")), function.clone()));
+        assert_eq!(cast_of("float8")?, (Some(String::from("a")), function.clone()));
+        assert_eq!(cast_of("int8")?, (Some(String::from("e")), function));
+        assert_eq!(cast_of("bytea")?, (Some(String::from("e")), Some(String::from("b"))));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_implicit_cast() -> Result<(), pgx::spi::Error> {
+        // `jsonb_typeof()` only takes `jsonb`
+        let found = Spi::get_one::<String>(
+            r#"SELECT jsonb_typeof('{"degrees": 21.5}'::CastTestsCelsius)"#,
+        )?;
+        assert_eq!(found.as_deref(), Some("object"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_assignment_cast() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TEMPORARY TABLE cast_tests_readings (reading float8)")?;
+        Spi::run(
+            r#"INSERT INTO cast_tests_readings VALUES ('{"degrees": 21.5}'::CastTestsCelsius)"#,
+        )?;
+        assert_eq!(Spi::get_one::<f64>("SELECT reading FROM cast_tests_readings")?, Some(21.5));
+        Ok(())
+    }
+
+    #[pg_test(error = "operator does not exist: casttestscelsius + bigint")]
+    fn test_assignment_cast_isnt_implicit() -> Result<(), pgx::spi::Error> {
+        Spi::run(r#"SELECT '{"degrees": 21.5}'::CastTestsCelsius + 1::int8"#)
+    }
+
+    #[pg_test]
+    fn test_explicit_cast() -> Result<(), pgx::spi::Error> {
+        let found = Spi::get_one::<i64>(r#"SELECT '{"degrees": 21.5}'::CastTestsCelsius::int8"#)?;
+        assert_eq!(found, Some(22));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_binary_cast() -> Result<(), pgx::spi::Error> {
+        let bytes = Spi::get_one::<i32>(
+            r#"SELECT octet_length('{"degrees": 21.5}'::CastTestsCelsius::bytea)"#,
+        )?;
+        assert!(bytes.unwrap() > 0);
+        Ok(())
+    }
+}
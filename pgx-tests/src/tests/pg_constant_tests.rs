@@ -0,0 +1,48 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+pg_constant!(
+    /// How deep the constant tests' trees may be
+    pub CONSTANT_TESTS_MAX_DEPTH: i32 = 16;
+);
+
+pg_constant!(CONSTANT_TESTS_GREETING: &str = "hello");
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::{CONSTANT_TESTS_GREETING, CONSTANT_TESTS_MAX_DEPTH};
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_sql_reads_the_rust_constant() -> Result<(), pgx::spi::Error> {
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT constant_tests_max_depth()")?,
+            Some(CONSTANT_TESTS_MAX_DEPTH)
+        );
+        assert_eq!(
+            Spi::get_one::<String>("SELECT constant_tests_greeting()")?.as_deref(),
+            Some(CONSTANT_TESTS_GREETING)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_constants_are_immutable() -> Result<(), pgx::spi::Error> {
+        let volatility = Spi::get_one::<String>(
+            "SELECT provolatile::text FROM pg_proc WHERE proname = 'constant_tests_max_depth' AND pronargs = 0",
+        )?;
+        assert_eq!(volatility.as_deref(), Some("i"));
+        Ok(())
+    }
+}
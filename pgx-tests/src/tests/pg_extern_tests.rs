@@ -21,6 +21,99 @@ fn fdw_handler_return() -> pgx::PgBox<pgx::pg_sys::FdwRoutine> {
     unimplemented!("Not a functional test, just a signature test for SQL generation. Feel free to make a functional test!")
 }
 
+// Postgres allows up to `FUNC_MAX_ARGS` (usually 100) arguments
+#[pg_extern]
+#[rustfmt::skip]
+fn sum_90_args(a1: i32, a2: i32, a3: i32, a4: i32, a5: i32, a6: i32, a7: i32, a8: i32, a9: i32, a10: i32, a11: i32, a12: i32, a13: i32, a14: i32, a15: i32, a16: i32, a17: i32, a18: i32, a19: i32, a20: i32, a21: i32, a22: i32, a23: i32, a24: i32, a25: i32, a26: i32, a27: i32, a28: i32, a29: i32, a30: i32, a31: i32, a32: i32, a33: i32, a34: i32, a35: i32, a36: i32, a37: i32, a38: i32, a39: i32, a40: i32, a41: i32, a42: i32, a43: i32, a44: i32, a45: i32, a46: i32, a47: i32, a48: i32, a49: i32, a50: i32, a51: i32, a52: i32, a53: i32, a54: i32, a55: i32, a56: i32, a57: i32, a58: i32, a59: i32, a60: i32, a61: i32, a62: i32, a63: i32, a64: i32, a65: i32, a66: i32, a67: i32, a68: i32, a69: i32, a70: i32, a71: i32, a72: i32, a73: i32, a74: i32, a75: i32, a76: i32, a77: i32, a78: i32, a79: i32, a80: i32, a81: i32, a82: i32, a83: i32, a84: i32, a85: i32, a86: i32, a87: i32, a88: i32, a89: i32, a90: i32) -> i64 {
+    [a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15, a16, a17, a18, a19, a20, a21, a22, a23, a24, a25, a26, a27, a28, a29, a30, a31, a32, a33, a34, a35, a36, a37, a38, a39, a40, a41, a42, a43, a44, a45, a46, a47, a48, a49, a50, a51, a52, a53, a54, a55, a56, a57, a58, a59, a60, a61, a62, a63, a64, a65, a66, a67, a68, a69, a70, a71, a72, a73, a74, a75, a76, a77, a78, a79, a80, a81, a82, a83, a84, a85, a86, a87, a88, a89, a90]
+        .iter()
+        .map(|&a| a as i64)
+        .sum()
+}
+
+#[pg_extern(called_on_null_input)]
+fn pg_extern_tests_called_on_null_input(value: i32) -> i32 {
+    value
+}
+
+#[derive(PostgresEnum)]
+pub enum PgExternTestsTransformed {
+    A,
+    B,
+}
+
+// Postgres never calls the transform of a `LANGUAGE c` function, but it has to exist for
+// `TRANSFORM FOR TYPE` to name it
+#[pg_extern]
+fn pg_extern_tests_transform_from_sql(value: pgx::Internal) -> pgx::Internal {
+    value
+}
+
+#[pg_extern]
+fn pg_extern_tests_transform_to_sql(_value: pgx::Internal) -> PgExternTestsTransformed {
+    unimplemented!("transforms aren't called for `LANGUAGE c` functions")
+}
+
+extension_sql!(
+    r#"
+CREATE TRANSFORM FOR PgExternTestsTransformed LANGUAGE c (
+    FROM SQL WITH FUNCTION pg_extern_tests_transform_from_sql(internal),
+    TO SQL WITH FUNCTION pg_extern_tests_transform_to_sql(internal)
+);
+"#,
+    name = "pg_extern_tests_transform",
+    requires = [
+        PgExternTestsTransformed,
+        pg_extern_tests_transform_from_sql,
+        pg_extern_tests_transform_to_sql
+    ]
+);
+
+#[pg_extern(transform = "PgExternTestsTransformed", requires = ["pg_extern_tests_transform"])]
+fn pg_extern_tests_uses_transform(value: PgExternTestsTransformed) -> PgExternTestsTransformed {
+    value
+}
+
+// functions with the same name in different modules are exported as different symbols
+#[pgx::pg_schema]
+mod pg_extern_tests_first {
+    use pgx::prelude::*;
+
+    #[pg_extern]
+    fn same_name() -> &'static str {
+        "first"
+    }
+}
+
+#[pgx::pg_schema]
+mod pg_extern_tests_second {
+    use pgx::prelude::*;
+
+    #[pg_extern]
+    fn same_name() -> &'static str {
+        "second"
+    }
+}
+
+#[pg_extern(legacy_symbol)]
+fn pg_extern_tests_legacy_symbol() -> i32 {
+    42
+}
+
+#[pg_extern(barrier, sql_wrapper = "SELECT pg_extern_tests_barrier($1)")]
+fn pg_extern_tests_barrier(value: i32) -> i32 {
+    value * 2
+}
+
+#[pg_extern(
+    stable,
+    sql_wrapper = "SELECT * FROM pg_extern_tests_numbers($1)",
+    sql_wrapper_name = "pg_extern_tests_numbers_checked"
+)]
+fn pg_extern_tests_numbers(count: i32) -> SetOfIterator<'static, i32> {
+    SetOfIterator::new(1..=count)
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgx::pg_schema]
 mod tests {
@@ -124,4 +217,151 @@ mod tests {
         let result = Spi::get_one::<bool>(r#"SELECT tests."custom_name"()"#);
         assert_eq!(result, Ok(Some(true)));
     }
+
+    #[pg_test]
+    fn test_sum_90_args() -> Result<(), pgx::spi::Error> {
+        let args = (1..=90).map(|i| i.to_string()).collect::<Vec<_>>().join(", ");
+        let sum = Spi::get_one::<i64>(&format!("SELECT sum_90_args({})", args))?;
+        assert_eq!(sum, Some((1..=90).sum()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_called_on_null_input() -> Result<(), pgx::spi::Error> {
+        let strict = Spi::get_one::<bool>(
+            "SELECT proisstrict FROM pg_proc WHERE proname = 'pg_extern_tests_called_on_null_input'",
+        )?;
+        assert_eq!(strict, Some(false));
+        assert_eq!(Spi::get_one::<i32>("SELECT pg_extern_tests_called_on_null_input(7)")?, Some(7));
+        Ok(())
+    }
+
+    #[pg_test(error = "value is null")]
+    fn test_called_on_null_input_with_null() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<i32>("SELECT pg_extern_tests_called_on_null_input(NULL)").map(|_| ())
+    }
+
+    #[pg_test]
+    fn test_transform() -> Result<(), pgx::spi::Error> {
+        let transforms = Spi::get_one::<bool>(
+            "SELECT protrftypes = ARRAY['PgExternTestsTransformed'::regtype::oid]
+             FROM pg_proc WHERE proname = 'pg_extern_tests_uses_transform'",
+        )?;
+        assert_eq!(transforms, Some(true));
+
+        let value = Spi::get_one::<String>("SELECT pg_extern_tests_uses_transform('B')::text")?;
+        assert_eq!(value.as_deref(), Some("B"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_same_name_in_different_modules() -> Result<(), pgx::spi::Error> {
+        for schema in ["pg_extern_tests_first", "pg_extern_tests_second"] {
+            let symbol = Spi::get_one::<String>(&format!(
+                "SELECT prosrc FROM pg_proc WHERE oid = '{}.same_name'::regproc",
+                schema
+            ))?
+            .expect("prosrc was null");
+            assert!(symbol.ends_with(&format!("::{}::same_name_wrapper", schema)), "{}", symbol);
+        }
+        assert_eq!(
+            Spi::get_one::<String>("SELECT pg_extern_tests_first.same_name()")?.as_deref(),
+            Some("first")
+        );
+        assert_eq!(
+            Spi::get_one::<String>("SELECT pg_extern_tests_second.same_name()")?.as_deref(),
+            Some("second")
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_legacy_symbol() -> Result<(), pgx::spi::Error> {
+        // what an extension installed before wrappers were namespaced still names
+        let library = Spi::get_one::<String>(
+            "SELECT probin FROM pg_proc WHERE oid = 'pg_extern_tests_legacy_symbol'::regproc",
+        )?
+        .expect("probin was null");
+        Spi::run(&format!(
+            "CREATE FUNCTION pg_extern_tests_legacy_installed() RETURNS integer
+             LANGUAGE c AS '{}', 'pg_extern_tests_legacy_symbol_wrapper'",
+            library
+        ))?;
+        assert_eq!(Spi::get_one::<i32>("SELECT pg_extern_tests_legacy_installed()")?, Some(42));
+        assert_eq!(Spi::get_one::<i32>("SELECT pg_extern_tests_legacy_symbol()")?, Some(42));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_barrier() -> Result<(), pgx::spi::Error> {
+        let options = |name: &str| {
+            Spi::get_three::<String, String, String>(&format!(
+                "SELECT provolatile::text, proparallel::text, l.lanname::text
+                   FROM pg_proc p JOIN pg_language l ON l.oid = p.prolang
+                  WHERE p.oid = '{}(integer)'::regprocedure",
+                name
+            ))
+        };
+        let expected = |language: &str| {
+            (Some("v".to_string()), Some("u".to_string()), Some(language.to_string()))
+        };
+        assert_eq!(options("pg_extern_tests_barrier")?, expected("c"));
+        assert_eq!(options("pg_extern_tests_barrier_sql")?, expected("sql"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_sql_wrapper() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<i32>("SELECT pg_extern_tests_barrier_sql(21)")?, Some(42));
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT sum(x) FROM pg_extern_tests_numbers_checked(4) x")?,
+            Some(10)
+        );
+        let volatility = Spi::get_one::<String>(
+            "SELECT provolatile::text FROM pg_proc
+              WHERE oid = 'pg_extern_tests_numbers_checked(integer)'::regprocedure",
+        )?;
+        assert_eq!(volatility.as_deref(), Some("s"));
+        Ok(())
+    }
+
+    #[pg_extern(set = "work_mem = 256MB", set = "jit = off")]
+    #[search_path(pg_catalog, public)]
+    fn pg_extern_tests_settings() -> String {
+        ["work_mem", "jit", "search_path"]
+            .iter()
+            .map(|name| {
+                Spi::get_one::<String>(&format!("SELECT current_setting('{}')", name))
+                    .unwrap()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    #[pg_test]
+    fn test_settings() -> Result<(), pgx::spi::Error> {
+        let config = Spi::get_one::<Vec<String>>(
+            "SELECT proconfig::text[] FROM pg_proc WHERE proname = 'pg_extern_tests_settings'",
+        )?;
+        assert_eq!(
+            config,
+            Some(vec![
+                "search_path=pg_catalog, public".to_string(),
+                "work_mem=256MB".to_string(),
+                "jit=off".to_string(),
+            ])
+        );
+
+        Spi::run("SET LOCAL work_mem = '4MB'")?;
+        assert_eq!(
+            Spi::get_one::<String>("SELECT tests.pg_extern_tests_settings()")?.as_deref(),
+            Some("256MB; off; pg_catalog, public")
+        );
+        assert_eq!(
+            Spi::get_one::<String>("SELECT current_setting('work_mem')")?.as_deref(),
+            Some("4MB")
+        );
+        Ok(())
+    }
 }
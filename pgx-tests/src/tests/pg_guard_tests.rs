@@ -8,6 +8,11 @@ Use of this source code is governed by the MIT license that can be found in the
 */
 
 use pgx::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::os::raw::{c_int, c_void};
 
 #[pg_extern]
 fn extern_func() -> bool {
@@ -38,9 +43,184 @@ extern "C" fn extern_func_impl_2<'a>() -> bool {
     true
 }
 
+/// A type whose comparison and hashing panic for negative values, to prove the functions
+/// generated by `#[derive(PostgresEq, PostgresOrd, PostgresHash)]` are guarded
+#[derive(PostgresType, PostgresEq, PostgresOrd, PostgresHash, Serialize, Deserialize, Debug)]
+pub struct GuardTestsPoint {
+    x: i32,
+}
+
+impl GuardTestsPoint {
+    fn checked(&self) -> i32 {
+        assert!(self.x >= 0, "negative GuardTestsPoint");
+        self.x
+    }
+}
+
+impl PartialEq for GuardTestsPoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.checked() == other.checked()
+    }
+}
+
+impl Eq for GuardTestsPoint {}
+
+impl PartialOrd for GuardTestsPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GuardTestsPoint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.checked().cmp(&other.checked())
+    }
+}
+
+impl Hash for GuardTestsPoint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.checked().hash(state)
+    }
+}
+
+/// Sorts `values` with `pg_qsort()` and a comparator that panics when it sees a negative value
+#[pg_extern]
+fn guard_tests_qsort(mut values: Vec<i32>) -> Vec<i32> {
+    let cmp = pgx::pg_guarded_closure!(|a: *const c_void, b: *const c_void| -> c_int {
+        let (a, b) = unsafe { (*(a as *const i32), *(b as *const i32)) };
+        assert!(a >= 0 && b >= 0, "negative value {}", a.min(b));
+        a.cmp(&b) as c_int
+    });
+
+    unsafe {
+        pg_sys::pg_qsort(values.as_mut_ptr().cast(), values.len(), size_of::<i32>(), Some(cmp));
+    }
+    values
+}
+
+/// Sorts `values` with `qsort_arg()` and a comparator that returns an error when it sees a value
+/// below `min`
+#[pg_extern]
+fn guard_tests_qsort_arg(mut values: Vec<i32>, min: i32) -> Vec<i32> {
+    let cmp = pgx::pg_guarded_closure!(|a: *const c_void,
+                                        b: *const c_void,
+                                        arg: *mut c_void|
+     -> Result<c_int, pgx::spi::Error> {
+        let (a, b, min) = unsafe { (*(a as *const i32), *(b as *const i32), *(arg as *const i32)) };
+        if a < min || b < min {
+            return Err(pgx::spi::Error::CursorNotFound(a.min(b).to_string()));
+        }
+        Ok(a.cmp(&b) as c_int)
+    });
+
+    let mut min = min;
+    unsafe {
+        pg_sys::qsort_arg(
+            values.as_mut_ptr().cast(),
+            values.len(),
+            size_of::<i32>(),
+            Some(cmp),
+            &mut min as *mut i32 as *mut c_void,
+        );
+    }
+    values
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pg_schema]
 mod tests {
     #[allow(unused_imports)]
     use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_derived_operators_are_parallel_unsafe_unless_declared() -> Result<(), pgx::spi::Error> {
+        // `GuardTestsPoint` doesn't declare `#[pgx(parallel_safe)]`
+        for function in ["guardtestspoint_eq", "guardtestspoint_cmp", "guardtestspoint_hash"] {
+            let (volatility, parallel) = Spi::get_two::<String, String>(&format!(
+                "SELECT provolatile::text, proparallel::text FROM pg_proc WHERE proname = '{}'",
+                function
+            ))?;
+            assert_eq!(volatility.as_deref(), Some("i"), "{}", function);
+            assert_eq!(parallel.as_deref(), Some("u"), "{}", function);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_guarded_closure() -> Result<(), pgx::spi::Error> {
+        let sorted = Spi::get_one::<Vec<i32>>("SELECT guard_tests_qsort(ARRAY[3, 1, 2])")?;
+        assert_eq!(sorted, Some(vec![1, 2, 3]));
+        Ok(())
+    }
+
+    #[pg_test(error = "negative value -2")]
+    fn test_guarded_closure_panic() -> Result<Option<Vec<i32>>, pgx::spi::Error> {
+        Spi::get_one("SELECT guard_tests_qsort(ARRAY[3, -2, 1])")
+    }
+
+    #[pg_test]
+    fn test_guarded_closure_result() -> Result<(), pgx::spi::Error> {
+        let sorted = Spi::get_one::<Vec<i32>>("SELECT guard_tests_qsort_arg(ARRAY[3, 1, 2], 0)")?;
+        assert_eq!(sorted, Some(vec![1, 2, 3]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_guarded_closure_error() -> Result<(), pgx::spi::Error> {
+        // the error keeps the SQLSTATE its `ErrorReportable` implementation gives it
+        Spi::run(
+            "DO $$
+            BEGIN
+                PERFORM guard_tests_qsort_arg(ARRAY[3, -1, 2], 0);
+                RAISE EXCEPTION 'no error was raised';
+            EXCEPTION WHEN invalid_cursor_name THEN
+                NULL;
+            END
+            $$",
+        )
+    }
+
+    #[pg_test]
+    fn test_backend_survives_guarded_panic() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "DO $$
+            BEGIN
+                PERFORM guard_tests_qsort(ARRAY[3, -1, 2]);
+            EXCEPTION WHEN internal_error THEN
+                NULL;
+            END
+            $$",
+        )?;
+        let sorted = Spi::get_one::<Vec<i32>>("SELECT guard_tests_qsort(ARRAY[2, 1])")?;
+        assert_eq!(sorted, Some(vec![1, 2]));
+        Ok(())
+    }
+
+    #[pg_test(error = "negative GuardTestsPoint")]
+    fn test_derived_eq_panic() -> Result<Option<bool>, pgx::spi::Error> {
+        Spi::get_one(r#"SELECT '{"x": -1}'::GuardTestsPoint = '{"x": 1}'::GuardTestsPoint"#)
+    }
+
+    #[pg_test(error = "negative GuardTestsPoint")]
+    fn test_derived_cmp_panic() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            r#"SELECT v FROM (VALUES ('{"x": 2}'::GuardTestsPoint), ('{"x": -1}'::GuardTestsPoint)) t(v) ORDER BY v"#,
+        )
+    }
+
+    #[pg_test(error = "negative GuardTestsPoint")]
+    fn test_derived_hash_panic() -> Result<Option<i32>, pgx::spi::Error> {
+        Spi::get_one(r#"SELECT guardtestspoint_hash('{"x": -1}'::GuardTestsPoint)"#)
+    }
+
+    #[pg_test]
+    fn test_derived_operators() -> Result<(), pgx::spi::Error> {
+        let lt = Spi::get_one::<bool>(
+            r#"SELECT '{"x": 1}'::GuardTestsPoint < '{"x": 2}'::GuardTestsPoint"#,
+        )?;
+        assert_eq!(lt, Some(true));
+        Ok(())
+    }
 }
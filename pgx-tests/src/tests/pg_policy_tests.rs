@@ -0,0 +1,93 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+extension_sql!(
+    r#"
+    CREATE TABLE policy_tests_data (tenant_id int, payload text);
+    INSERT INTO policy_tests_data VALUES (1, 'mine'), (2, 'theirs');
+    ALTER TABLE policy_tests_data ENABLE ROW LEVEL SECURITY;
+    "#,
+    name = "policy_tests_data",
+);
+
+#[pg_extern(stable)]
+fn policy_tests_tenant_visible(tenant_id: i32) -> bool {
+    tenant_id == 1
+}
+
+pg_policy!(
+    name = "policy_tests_tenant_select",
+    table = "policy_tests_data",
+    command = select,
+    to = ["PUBLIC"],
+    using = policy_tests_tenant_visible(tenant_id),
+    requires = ["policy_tests_data"],
+);
+
+pg_policy!(
+    name = "policy_tests_tenant_insert",
+    table = "policy_tests_data",
+    command = insert,
+    with_check = policy_tests_tenant_visible(tenant_id),
+    requires = ["policy_tests_data"],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    fn become_unprivileged_user() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "DO $$ BEGIN
+                IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'policy_tests_user') THEN
+                    CREATE ROLE policy_tests_user;
+                END IF;
+            END $$;",
+        )?;
+        Spi::run("GRANT SELECT, INSERT ON policy_tests_data TO policy_tests_user")?;
+        Spi::run("SET LOCAL ROLE policy_tests_user")
+    }
+
+    #[pg_test]
+    fn test_policies_are_created() -> Result<(), pgx::spi::Error> {
+        let select = Spi::get_two::<String, String>(
+            "SELECT cmd::text, qual FROM pg_policies WHERE policyname = 'policy_tests_tenant_select'",
+        )?;
+        assert_eq!(select.0.as_deref(), Some("SELECT"));
+        assert!(select.1.unwrap().contains("policy_tests_tenant_visible(tenant_id)"));
+
+        let insert = Spi::get_two::<String, String>(
+            "SELECT cmd::text, with_check FROM pg_policies WHERE policyname = 'policy_tests_tenant_insert'",
+        )?;
+        assert_eq!(insert.0.as_deref(), Some("INSERT"));
+        assert!(insert.1.unwrap().contains("policy_tests_tenant_visible(tenant_id)"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_select_policy_filters_rows() -> Result<(), pgx::spi::Error> {
+        become_unprivileged_user()?;
+        let visible =
+            Spi::get_one::<String>("SELECT string_agg(payload, ',') FROM policy_tests_data")?;
+        assert_eq!(visible.as_deref(), Some("mine"));
+        Ok(())
+    }
+
+    #[pg_test(error = "new row violates row-level security policy for table \"policy_tests_data\"")]
+    fn test_insert_policy_rejects_rows() -> Result<(), pgx::spi::Error> {
+        become_unprivileged_user()?;
+        Spi::run("INSERT INTO policy_tests_data VALUES (1, 'allowed')")?;
+        Spi::run("INSERT INTO policy_tests_data VALUES (2, 'rejected')")
+    }
+}
@@ -0,0 +1,66 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern]
+fn view_tests_dogs() -> TableIterator<'static, (name!(name, String), name!(good, bool))> {
+    TableIterator::new(
+        vec![("Brandy".to_string(), true), ("Nami".to_string(), false), ("Ruby".to_string(), true)]
+            .into_iter(),
+    )
+}
+
+// declared before the view it selects from, which must still be created first
+pg_view!("view_tests_best_dog", "SELECT name FROM view_tests_good_dogs ORDER BY name LIMIT 1");
+
+pg_view!("view_tests_good_dogs", "SELECT name FROM view_tests_dogs() WHERE good;");
+
+#[pg_schema]
+mod view_tests_kennel {
+    use pgx::prelude::*;
+
+    pg_view!("dog_count", "SELECT count(*) AS dogs FROM view_tests_dogs()");
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_views_select_from_functions() -> Result<(), pgx::spi::Error> {
+        let names = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(name ORDER BY name) FROM view_tests_good_dogs",
+        )?;
+        assert_eq!(names, Some(vec!["Brandy".to_string(), "Ruby".to_string()]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_views_select_from_views() -> Result<(), pgx::spi::Error> {
+        assert_eq!(
+            Spi::get_one::<String>("SELECT name FROM view_tests_best_dog")?.as_deref(),
+            Some("Brandy")
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_views_are_created_in_their_schema() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<i64>("SELECT dogs FROM view_tests_kennel.dog_count")?, Some(3));
+        let in_schema = Spi::get_one::<bool>(
+            "SELECT EXISTS (SELECT 1 FROM pg_views WHERE schemaname = 'view_tests_kennel' AND viewname = 'dog_count')",
+        )?;
+        assert_eq!(in_schema, Some(true));
+        Ok(())
+    }
+}
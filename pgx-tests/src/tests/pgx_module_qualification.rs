@@ -24,7 +24,11 @@ mod pgx_modqual_tests {
         PostgresEq, PostgresHash, PostgresOrd, PostgresType,
     };
 
-    ::pgx::extension_sql!("SELECT 1;", name = "pgx_module_qualification_test");
+    ::pgx::extension_sql!(
+        "CREATE TYPE Foo AS (value int);",
+        name = "pgx_module_qualification_test",
+        declares = [Type(Foo)]
+    );
 
     #[derive(
         Eq,
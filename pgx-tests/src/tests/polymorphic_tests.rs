@@ -0,0 +1,156 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::{AnyArray, AnyElement};
+
+/// Is `value` an empty `text` or array?
+fn is_empty(value: &AnyElement) -> bool {
+    unsafe {
+        if value.oid() == pg_sys::TEXTOID {
+            value.into::<&str>().map_or(true, str::is_empty)
+        } else if pg_sys::get_element_type(value.oid()) != pg_sys::InvalidOid {
+            let array = pg_sys::pg_detoast_datum(value.datum().cast_mut_ptr());
+            (*array.cast::<pg_sys::ArrayType>()).ndim == 0
+        } else {
+            false
+        }
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn polymorphic_tests_coalesce_not_empty(
+    a: Option<AnyElement>,
+    b: Option<AnyElement>,
+) -> Option<AnyElement> {
+    a.filter(|a| !is_empty(a)).or(b)
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn polymorphic_tests_zero(value: AnyElement) -> AnyElement {
+    match value.oid() {
+        pg_sys::INT4OID => AnyElement::from_value(0i32),
+        pg_sys::INT8OID => AnyElement::from_value(0i64),
+        pg_sys::TEXTOID => AnyElement::from_value(String::new()),
+        _ => None,
+    }
+    .unwrap_or(value)
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn polymorphic_tests_first(values: AnyArray) -> Option<AnyElement> {
+    let values = unsafe { values.into::<Vec<Option<AnyElement>>>() }?;
+    values.into_iter().next().flatten()
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn polymorphic_tests_wrong_type(_value: AnyElement) -> AnyElement {
+    AnyElement::from_value(42i32).unwrap()
+}
+
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+#[pg_extern(immutable, parallel_safe)]
+fn polymorphic_tests_first_non_null(
+    a: Option<pgx::AnyCompatible>,
+    b: Option<pgx::AnyCompatible>,
+) -> Option<pgx::AnyCompatible> {
+    a.or(b)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_coalesce_not_empty_int() -> Result<(), pgx::spi::Error> {
+        let retval =
+            Spi::get_one::<i32>("SELECT polymorphic_tests_coalesce_not_empty(NULL::int, 2)")?;
+        assert_eq!(retval, Some(2));
+        let retval = Spi::get_one::<i32>("SELECT polymorphic_tests_coalesce_not_empty(1, 2)")?;
+        assert_eq!(retval, Some(1));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_coalesce_not_empty_text() -> Result<(), pgx::spi::Error> {
+        let retval = Spi::get_one::<String>(
+            "SELECT polymorphic_tests_coalesce_not_empty(''::text, 'b'::text)",
+        )?;
+        assert_eq!(retval.as_deref(), Some("b"));
+        let retval = Spi::get_one::<String>(
+            "SELECT polymorphic_tests_coalesce_not_empty('a'::text, 'b'::text)",
+        )?;
+        assert_eq!(retval.as_deref(), Some("a"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_coalesce_not_empty_array() -> Result<(), pgx::spi::Error> {
+        let retval = Spi::get_one::<Vec<i32>>(
+            "SELECT polymorphic_tests_coalesce_not_empty('{}'::int[], ARRAY[1, 2])",
+        )?;
+        assert_eq!(retval, Some(vec![1, 2]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_returns_the_arguments_type() -> Result<(), pgx::spi::Error> {
+        let (int, text) = Spi::get_two::<String, String>(
+            "SELECT pg_typeof(polymorphic_tests_coalesce_not_empty(1, 2))::text, \
+                    pg_typeof(polymorphic_tests_coalesce_not_empty('a'::text, 'b'))::text",
+        )?;
+        assert_eq!(int.as_deref(), Some("integer"));
+        assert_eq!(text.as_deref(), Some("text"));
+
+        let retval = Spi::get_one::<String>("SELECT polymorphic_tests_first(ARRAY['a', 'b'])")?;
+        assert_eq!(retval.as_deref(), Some("a"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_returns_a_new_value() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<i32>("SELECT polymorphic_tests_zero(42)")?, Some(0));
+        assert_eq!(Spi::get_one::<i64>("SELECT polymorphic_tests_zero(42::bigint)")?, Some(0));
+        assert_eq!(
+            Spi::get_one::<String>("SELECT polymorphic_tests_zero('a'::text)")?.as_deref(),
+            Some("")
+        );
+        Ok(())
+    }
+
+    #[pg_test(
+        error = "function polymorphic_tests_coalesce_not_empty(integer, text) does not exist"
+    )]
+    fn test_arguments_must_be_of_the_same_type() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<i32>("SELECT polymorphic_tests_coalesce_not_empty(1, 'a'::text)").map(|_| ())
+    }
+
+    #[pg_test(
+        error = "`polymorphic_tests_wrong_type` returned a value of type integer, but was called to return text"
+    )]
+    fn test_returning_the_wrong_type() -> Result<(), pgx::spi::Error> {
+        Spi::get_one::<String>("SELECT polymorphic_tests_wrong_type('a'::text)").map(|_| ())
+    }
+
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    #[pg_test]
+    fn test_any_compatible() -> Result<(), pgx::spi::Error> {
+        // the arguments are converted to their common type, which is what's returned
+        let (value, type_name) = Spi::get_two::<String, String>(
+            "SELECT polymorphic_tests_first_non_null(NULL::int, 2.5)::text, \
+                    pg_typeof(polymorphic_tests_first_non_null(1, 2.5))::text",
+        )?;
+        assert_eq!(value.as_deref(), Some("2.5"));
+        assert_eq!(type_name.as_deref(), Some("numeric"));
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::Aggregate;
+
+// these use predefined roles, which exist before the extension is created, and
+// `pg_signal_backend` is a member of neither of them
+
+#[pg_extern(owner = "pg_monitor", grant = "pg_read_all_settings")]
+fn privileges_tests_function(value: i32) -> i32 {
+    value
+}
+
+#[derive(PostgresEnum, PartialEq, Debug)]
+#[pgx(owner = "pg_monitor", grant = "pg_read_all_settings")]
+pub enum PrivilegesTestsColor {
+    Red,
+    Green,
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct PrivilegesTestsSum;
+
+#[pg_aggregate]
+#[pgx(owner = "pg_monitor", grant = "pg_read_all_settings")]
+impl Aggregate for PrivilegesTestsSum {
+    const INITIAL_CONDITION: Option<&'static str> = Some("0");
+
+    type Args = i32;
+    type State = i32;
+
+    fn state(current: Self::State, arg: Self::Args, _fcinfo: pg_sys::FunctionCallInfo) -> i32 {
+        current + arg
+    }
+}
+
+#[pgx::pg_schema(owner = "pg_monitor", grant = "pg_read_all_settings")]
+mod privileges_tests_schema {
+    use pgx::prelude::*;
+
+    #[pg_extern]
+    fn privileges_tests_in_schema() -> i32 {
+        42
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_function_privileges() -> Result<(), pgx::spi::Error> {
+        let owner = Spi::get_one::<String>(
+            "SELECT pg_get_userbyid(proowner)::text FROM pg_proc
+              WHERE oid = 'privileges_tests_function(integer)'::regprocedure",
+        )?;
+        assert_eq!(owner.as_deref(), Some("pg_monitor"));
+
+        let (granted, public) = Spi::get_two::<bool, bool>(
+            "SELECT has_function_privilege('pg_read_all_settings', 'privileges_tests_function(integer)', 'EXECUTE'),
+                    has_function_privilege('pg_signal_backend', 'privileges_tests_function(integer)', 'EXECUTE')",
+        )?;
+        assert_eq!(granted, Some(true));
+        assert_eq!(public, Some(false));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_type_privileges() -> Result<(), pgx::spi::Error> {
+        let (owner, granted, public) = Spi::get_three::<String, bool, bool>(
+            "SELECT pg_get_userbyid(typowner)::text,
+                    has_type_privilege('pg_read_all_settings', oid, 'USAGE'),
+                    has_type_privilege('pg_signal_backend', oid, 'USAGE')
+               FROM pg_type WHERE oid = 'privilegestestscolor'::regtype",
+        )?;
+        assert_eq!(owner.as_deref(), Some("pg_monitor"));
+        assert_eq!(granted, Some(true));
+        assert_eq!(public, Some(false));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_aggregate_privileges() -> Result<(), pgx::spi::Error> {
+        let (owner, granted, public) = Spi::get_three::<String, bool, bool>(
+            "SELECT pg_get_userbyid(proowner)::text,
+                    has_function_privilege('pg_read_all_settings', oid, 'EXECUTE'),
+                    has_function_privilege('pg_signal_backend', oid, 'EXECUTE')
+               FROM pg_proc WHERE oid = 'privileges_tests_sum(integer)'::regprocedure",
+        )?;
+        assert_eq!(owner.as_deref(), Some("pg_monitor"));
+        assert_eq!(granted, Some(true));
+        assert_eq!(public, Some(false));
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT privileges_tests_sum(x) FROM generate_series(1, 4) x")?,
+            Some(10)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_schema_privileges() -> Result<(), pgx::spi::Error> {
+        let (owner, granted, public) = Spi::get_three::<String, bool, bool>(
+            "SELECT pg_get_userbyid(nspowner)::text,
+                    has_schema_privilege('pg_read_all_settings', oid, 'USAGE'),
+                    has_schema_privilege('pg_signal_backend', oid, 'USAGE')
+               FROM pg_namespace WHERE nspname = 'privileges_tests_schema'",
+        )?;
+        assert_eq!(owner.as_deref(), Some("pg_monitor"));
+        assert_eq!(granted, Some(true));
+        assert_eq!(public, Some(false));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_crate_defaults_leave_others_alone() -> Result<(), pgx::spi::Error> {
+        // pgx_tests gives `pg_module_magic!()` no options, so other functions are executable by all
+        let public = Spi::get_one::<bool>(
+            "SELECT has_function_privilege('pg_signal_backend', 'privileges_tests_schema.privileges_tests_in_schema()', 'EXECUTE')",
+        )?;
+        assert_eq!(public, Some(true));
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{format_sql, quote_identifier, quote_literal, quote_qualified_identifier};
+
+    const HOSTILE: &[&str] = &[
+        "",
+        "plain",
+        "MixedCase",
+        "select",
+        "with space",
+        r#"double"quote"#,
+        "single'quote",
+        r"back\slash",
+        "Robert'); DROP TABLE students;--",
+        r#"x"; DROP TABLE students;--"#,
+        "naïve 日本語 🦀",
+        "$$ dollar $tag$",
+    ];
+
+    #[pg_test]
+    fn test_quote_identifier_matches_sql() -> Result<(), pgx::spi::Error> {
+        for ident in HOSTILE {
+            let expected = Spi::get_one_with_args::<String>(
+                "SELECT quote_ident($1)",
+                vec![(PgBuiltInOids::TEXTOID.oid(), (*ident).into_datum())],
+            )?;
+            assert_eq!(Some(quote_identifier(ident)), expected);
+        }
+        assert_eq!(quote_identifier("plain"), "plain");
+        assert_eq!(quote_identifier("select"), r#""select""#);
+        assert_eq!(quote_identifier(r#"double"quote"#), r#""double""quote""#);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_quote_literal_matches_sql() -> Result<(), pgx::spi::Error> {
+        for literal in HOSTILE {
+            let expected = Spi::get_one_with_args::<String>(
+                "SELECT quote_literal($1)",
+                vec![(PgBuiltInOids::TEXTOID.oid(), (*literal).into_datum())],
+            )?;
+            assert_eq!(Some(quote_literal(literal)), expected);
+        }
+        assert_eq!(quote_literal("single'quote"), "'single''quote'");
+        assert_eq!(quote_literal(r"back\slash"), r"E'back\\slash'");
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_quote_qualified_identifier() {
+        assert_eq!(quote_qualified_identifier("public", "users"), "public.users");
+        assert_eq!(quote_qualified_identifier("My Schema", "user"), r#""My Schema"."user""#);
+    }
+
+    #[pg_test]
+    fn test_format_sql_round_trips_hostile_input() -> Result<(), pgx::spi::Error> {
+        for (i, name) in HOSTILE.iter().enumerate().filter(|(_, name)| !name.is_empty()) {
+            Spi::run(&format_sql!("CREATE TABLE tests.{ident} ({ident} text)", name, name))?;
+            Spi::run(&format_sql!("INSERT INTO tests.{ident} VALUES ({literal})", name, name))?;
+            let sql =
+                format_sql!("SELECT {ident}, {literal}::int FROM tests.{ident}", name, i, name);
+            let (value, index) = Spi::get_two::<String, i32>(&sql)?;
+            assert_eq!(value.as_deref(), Some(*name));
+            assert_eq!(index, Some(i as i32));
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_format_sql_braces() {
+        assert_eq!(
+            format_sql!("SELECT '{{}}'::json, {literal}", "{ident}"),
+            "SELECT '{}'::json, '{ident}'"
+        );
+    }
+
+    #[pg_test(error = "format_sql!() has too few arguments: SELECT {ident}, {ident}")]
+    fn test_format_sql_too_few_arguments() {
+        format_sql!("SELECT {ident}, {ident}", "a");
+    }
+
+    #[pg_test(error = "format_sql!() has too many arguments: SELECT {ident}")]
+    fn test_format_sql_too_many_arguments() {
+        format_sql!("SELECT {ident}", "a", "b");
+    }
+
+    #[pg_test(
+        error = "format_sql!() only supports `{ident}` and `{literal}` placeholders: SELECT {}"
+    )]
+    fn test_format_sql_unquoted_placeholder() {
+        format_sql!("SELECT {}", "1; DROP TABLE students");
+    }
+}
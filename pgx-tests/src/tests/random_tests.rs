@@ -0,0 +1,46 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::random::{pg_random, set_seed};
+
+    #[pg_test]
+    fn test_pg_random_honors_setseed() -> Result<(), pgx::spi::Error> {
+        Spi::run("SELECT setseed(0.5)")?;
+        let from_sql = Spi::get_one::<f64>("SELECT random()")?;
+        set_seed(0.5);
+        assert_eq!(Some(pg_random()), from_sql);
+
+        let x = pg_random();
+        assert!((0.0..1.0).contains(&x));
+        Ok(())
+    }
+
+    #[pg_test(error = "setseed parameter 2 is out of allowed range [-1,1]")]
+    fn test_set_seed_out_of_range() {
+        set_seed(2.0);
+    }
+
+    #[pg_test(seed = 42)]
+    fn test_seed_repeats() -> Result<(), pgx::spi::Error> {
+        let first = (pg_random(), Spi::get_one::<f64>("SELECT random()")?);
+        pgx::testing::seed_random(42);
+        assert_eq!((pg_random(), Spi::get_one::<f64>("SELECT random()")?), first);
+
+        pgx::testing::seed_random(43);
+        assert_ne!(pg_random(), first.0);
+        Ok(())
+    }
+}
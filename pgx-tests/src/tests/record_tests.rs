@@ -0,0 +1,88 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use pgx::prelude::*;
+
+#[pg_extern]
+fn record_minmax(values: Array<i32>) -> (i32, i32) {
+    values.iter().flatten().fold((i32::MAX, i32::MIN), |(min, max), v| (min.min(v), max.max(v)))
+}
+
+#[pg_extern]
+fn record_named(word: &str) -> (name!(word, String), name!(len, i64), Option<bool>) {
+    (word.to_uppercase(), word.len() as i64, None)
+}
+
+#[pg_extern]
+fn record_maybe(x: i32) -> Option<(i32, &'static str)> {
+    if x > 0 {
+        Some((x, "positive"))
+    } else {
+        None
+    }
+}
+
+#[pg_extern]
+fn record_result(x: i32) -> Result<(i32, i32), String> {
+    x.checked_mul(2).map(|doubled| (x, doubled)).ok_or_else(|| format!("{} is too big", x))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_record_bare() -> Result<(), pgx::spi::Error> {
+        let text = Spi::get_one::<String>("SELECT record_minmax(ARRAY[3, 1, NULL, 2])::text")?;
+        assert_eq!(text.as_deref(), Some("(1,3)"));
+        let max = Spi::get_one::<i32>("SELECT (record_minmax(ARRAY[3, 1, 2])).column2")?;
+        assert_eq!(max, Some(3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_record_in_from() -> Result<(), pgx::spi::Error> {
+        let (min, max) =
+            Spi::get_two::<i32, i32>("SELECT column1, column2 FROM record_minmax(ARRAY[5, -4])")?;
+        assert_eq!((min, max), (Some(-4), Some(5)));
+
+        Spi::connect(|client| {
+            let row = client.select("SELECT * FROM record_named('pgx')", None, None)?.first();
+            assert_eq!(row.get_by_name::<String, _>("word")?.as_deref(), Some("PGX"));
+            assert_eq!(row.get_by_name::<i64, _>("len")?, Some(3));
+            assert_eq!(row.get_by_name::<bool, _>("column3")?, None);
+            Ok(())
+        })
+    }
+
+    #[pg_test]
+    fn test_record_optional() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<bool>("SELECT record_maybe(0) IS NULL")?, Some(true));
+        let (x, sign) =
+            Spi::get_two::<i32, String>("SELECT column1, column2 FROM record_maybe(7)")?;
+        assert_eq!((x, sign.as_deref()), (Some(7), Some("positive")));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_record_result() -> Result<(), pgx::spi::Error> {
+        let doubled = Spi::get_one::<i32>("SELECT column2 FROM record_result(21)")?;
+        assert_eq!(doubled, Some(42));
+        Ok(())
+    }
+
+    #[pg_test(error = "2147483647 is too big")]
+    fn test_record_result_error() -> Result<(), pgx::spi::Error> {
+        Spi::run("SELECT record_result(2147483647)")
+    }
+}
@@ -23,6 +23,11 @@ mod test_schema {
     #[pg_extern(sql = generate_function)]
     fn func_generated_with_custom_sql() {}
 
+    #[pg_extern(sql = grant_execute)]
+    fn func_generated_with_grant() -> i32 {
+        42
+    }
+
     #[derive(Debug, PostgresType, Serialize, Deserialize)]
     pub struct TestType(pub u64);
 
@@ -47,9 +52,9 @@ mod test_schema {
                 "\
                 CREATE FUNCTION test_schema.\"func_generated_with_custom_name\"() RETURNS void\n\
                 LANGUAGE c /* Rust */\n\
-                AS 'MODULE_PATHNAME', '{unaliased_name}_wrapper';\
+                AS 'MODULE_PATHNAME', '{wrapper_symbol}';\
                 ",
-                unaliased_name = func.name,
+                wrapper_symbol = func.wrapper_symbol(),
             ))
         } else {
             panic!("expected extern function entity, got {:?}", entity);
@@ -71,6 +76,24 @@ mod test_schema {
             panic!("expected type entity, got {:?}", entity);
         }
     }
+
+    fn grant_execute(entity: &SqlGraphEntity, context: &PgxSql) -> eyre::Result<String> {
+        let default_sql = entity.to_default_sql(context)?;
+        if let SqlGraphEntity::Function(ref func) = entity {
+            let schema = func
+                .schema
+                .map(|schema| format!("{}.", schema))
+                .unwrap_or_else(|| context.schema_prefix_for(&context.externs[func]));
+            Ok(format!(
+                "{default_sql}\n\
+                GRANT EXECUTE ON FUNCTION {schema}\"{name}\"() TO PUBLIC;\n\
+                ",
+                name = func.name,
+            ))
+        } else {
+            eyre::bail!("expected extern function entity, got {:?}", entity)
+        }
+    }
 }
 
 #[pg_extern(schema = "test_schema")]
@@ -143,6 +166,19 @@ mod tests {
         Spi::run("SELECT test_schema.func_generated_with_custom_name();").expect("SPI failed");
     }
 
+    #[pg_test]
+    fn custom_to_sql_decorates_default() {
+        // The default SQL was still used to create the function...
+        let result = Spi::get_one::<i32>("SELECT test_schema.func_generated_with_grant();");
+        assert_eq!(result, Ok(Some(42)));
+
+        // ...and the explicit GRANT gave it a non-default ACL
+        let result = Spi::get_one::<bool>(
+            "SELECT proacl IS NOT NULL FROM pg_proc WHERE oid = 'test_schema.func_generated_with_grant()'::regprocedure;",
+        );
+        assert_eq!(result, Ok(Some(true)));
+    }
+
     #[pg_test]
     fn custom_to_sql_type() {
         // Validate that the type we generated has the expected modifications
@@ -0,0 +1,77 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, PostgresType)]
+#[sendrecvfuncs]
+pub struct SendRecvPoint {
+    x: i32,
+    y: i32,
+}
+
+impl SendRecvFuncs for SendRecvPoint {
+    fn send(&self) -> Vec<u8> {
+        [self.x.to_be_bytes(), self.y.to_be_bytes()].concat()
+    }
+
+    fn recv(bytes: &[u8]) -> Self {
+        if bytes.len() != 8 {
+            error!("a SendRecvPoint is 8 bytes, not {}", bytes.len());
+        }
+        let (x, y) = bytes.split_at(4);
+        SendRecvPoint {
+            x: i32::from_be_bytes(x.try_into().unwrap()),
+            y: i32::from_be_bytes(y.try_into().unwrap()),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::SendRecvPoint;
+    use pgx::prelude::*;
+    use pgx::testing::assert_binary_roundtrip;
+
+    #[pg_test]
+    fn test_builtin_binary_roundtrips() {
+        assert_binary_roundtrip(true);
+        assert_binary_roundtrip(i16::MIN);
+        assert_binary_roundtrip(-42i32);
+        assert_binary_roundtrip(i64::MAX);
+        assert_binary_roundtrip(1.5f64);
+        assert_binary_roundtrip(String::from("hello, world"));
+        assert_binary_roundtrip(vec![Some(1), None, Some(3)]);
+        assert_binary_roundtrip(pgx::Uuid::from_bytes([7; 16]));
+    }
+
+    #[pg_test]
+    fn test_custom_binary_roundtrip() {
+        assert_binary_roundtrip(SendRecvPoint { x: 1, y: -2 });
+    }
+
+    #[pg_test]
+    fn test_custom_type_has_send_recv() -> Result<(), pgx::spi::Error> {
+        let (send, recv) = Spi::get_two::<String, String>(
+            "SELECT typsend::regproc::text, typreceive::regproc::text FROM pg_type WHERE oid = 'SendRecvPoint'::regtype",
+        )?;
+        assert_eq!(send.as_deref(), Some("sendrecvpoint_send"));
+        assert_eq!(recv.as_deref(), Some("sendrecvpoint_recv"));
+
+        let bytes = Spi::get_one::<Vec<u8>>(
+            r#"SELECT sendrecvpoint_send('{"x": 1, "y": 258}'::SendRecvPoint)"#,
+        )?;
+        assert_eq!(bytes, Some(vec![0, 0, 0, 1, 0, 0, 1, 2]));
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::bgworkers::{BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use pgx::prelude::*;
+use pgx::shm_mq::{DsmSegment, ShmMq};
+use pgx::DatumExt;
+
+const QUEUE_SIZE: usize = 16 * 1024;
+
+#[pg_extern]
+fn shm_mq_tests_stream() -> SetOfIterator<'static, i64> {
+    let mq = ShmMq::create(DsmSegment::create(QUEUE_SIZE), QUEUE_SIZE);
+    let worker = BackgroundWorkerBuilder::new("shm_mq_tests_worker")
+        .set_library("pgx_tests")
+        .set_function("shm_mq_tests_worker")
+        .set_argument(Some(pg_sys::Datum::from(mq.dsm_handle())))
+        .set_notify_pid(unsafe { pg_sys::MyProcPid })
+        .load_dynamic();
+    SetOfIterator::new(mq.into_receiver(Some(&worker)).into_messages::<i64>())
+}
+
+// holds the sending half of a queue nobody receives from while it returns rows
+#[pg_extern]
+fn shm_mq_tests_hold_sender() -> SetOfIterator<'static, i64> {
+    let sender = ShmMq::create(DsmSegment::create(QUEUE_SIZE), QUEUE_SIZE).into_sender(None);
+    SetOfIterator::new((1..=10).map(move |i| {
+        let _ = &sender;
+        i
+    }))
+}
+
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn shm_mq_tests_worker(arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    let segment = DsmSegment::attach(arg.as_u32()).expect("the segment is gone");
+    let sender = ShmMq::attach(segment).into_sender(None);
+    for i in 0..10_000i64 {
+        if sender.send_msg(&i).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::QUEUE_SIZE;
+    use pgx::prelude::*;
+    use pgx::shm_mq::{DsmSegment, ShmMq, ShmMqError};
+    use std::time::Duration;
+
+    #[pg_test]
+    fn test_stream_from_worker() -> Result<(), pgx::spi::Error> {
+        let (count, sum) = Spi::get_two::<i64, i64>(
+            "SELECT count(*), sum(v)::bigint FROM shm_mq_tests_stream() v",
+        )?;
+        assert_eq!(count, Some(10_000));
+        assert_eq!(sum, Some(49_995_000));
+        Ok(())
+    }
+
+    // the receiver is dropped with the function's memory context, after the `ERROR` has
+    // detached from its segment
+    #[pg_test(error = "division by zero")]
+    fn test_error_while_receiving() -> Result<(), pgx::spi::Error> {
+        Spi::run("SELECT 1 / (v - 5) FROM shm_mq_tests_stream() v")
+    }
+
+    #[pg_test(error = "division by zero")]
+    fn test_error_while_sending() -> Result<(), pgx::spi::Error> {
+        Spi::run("SELECT 1 / (v - 5) FROM shm_mq_tests_hold_sender() v")
+    }
+
+    #[pg_test(error = "the queue is still attached")]
+    fn test_error_while_held() {
+        let _receiver =
+            ShmMq::create(DsmSegment::create(QUEUE_SIZE), QUEUE_SIZE).into_receiver(None);
+        error!("the queue is still attached");
+    }
+
+    #[pg_test]
+    fn test_recv_times_out_without_sender() {
+        let receiver =
+            ShmMq::create(DsmSegment::create(QUEUE_SIZE), QUEUE_SIZE).into_receiver(None);
+        assert_eq!(receiver.recv(Some(Duration::from_millis(50))), Err(ShmMqError::Timeout));
+    }
+}
@@ -7,26 +7,76 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 use pgx::prelude::*;
-use pgx::{pg_shmem_init, PgAtomic, PgLwLock, PgSharedMemoryInitialization};
+use pgx::{
+    pg_shmem_init, FromDatum, PgAtomic, PgAtomicU64, PgLwLock, PgSharedMemoryInitialization,
+};
 use std::sync::atomic::AtomicBool;
 
 static ATOMIC: PgAtomic<AtomicBool> = PgAtomic::new();
+static COUNTER: PgAtomic<PgAtomicU64> = PgAtomic::new();
 static LWLOCK: PgLwLock<bool> = PgLwLock::new();
 
 #[pg_guard]
 pub extern "C" fn _PG_init() {
+    crate::tests::init_tests::record("_PG_init");
+
     // This ensures that this functionality works across PostgreSQL versions
     pg_shmem_init!(ATOMIC);
+    pg_shmem_init!(COUNTER);
     pg_shmem_init!(LWLOCK);
+
+    crate::tests::stats_tests::define_counters();
 }
+
+#[pg_guard]
+#[no_mangle]
+/// Here we add to `COUNTER` as many times as the worker's argument says, from a backend of its own
+pub extern "C" fn shmem_tests_count_bgworker(arg: pg_sys::Datum) {
+    use pgx::bgworkers::*;
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    let times = unsafe { i32::from_datum(arg, false) }.expect("invalid arg");
+    for _ in 0..times {
+        COUNTER.get().fetch_add(1);
+    }
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgx::pg_schema]
 mod tests {
     #[allow(unused_imports)]
     use crate as pgx_tests;
 
-    use crate::tests::shmem_tests::LWLOCK;
+    use crate::tests::shmem_tests::{COUNTER, LWLOCK};
+    use pgx::bgworkers::BackgroundWorkerBuilder;
     use pgx::prelude::*;
+    use pgx::{IntoDatum, PgAtomicU32, ShmemVersion, ShmemVersionMismatch, ShmemVersioned};
+
+    /// The header `pg_shmem_init!()` wrote ahead of `LWLOCK`'s value, in shared memory
+    fn lwlock_header() -> *mut ShmemVersion {
+        let name = std::ffi::CString::new(LWLOCK.get_name()).unwrap();
+        let mut found = false;
+        unsafe {
+            let shmem = pg_sys::ShmemInitStruct(
+                name.as_ptr(),
+                std::mem::size_of::<ShmemVersioned<bool>>(),
+                &mut found,
+            ) as *mut ShmemVersioned<bool>;
+            assert!(found, "LWLOCK's shared memory wasn't found by its name");
+            std::ptr::addr_of_mut!((*shmem).version)
+        }
+    }
+
+    /// Check `LWLOCK`'s header after overwriting it with `forged`, then put it back
+    fn check_forged(forged: ShmemVersion) -> Result<(), ShmemVersionMismatch> {
+        let header = lwlock_header();
+        let _lock = LWLOCK.exclusive();
+        unsafe {
+            let real = header.replace(forged);
+            let checked = LWLOCK.shmem_version().check(&*header);
+            header.write(real);
+            checked
+        }
+    }
 
     #[pg_test]
     #[should_panic(expected = "cache lookup failed for type 0")]
@@ -52,4 +102,95 @@ mod tests {
         });
         let _lock = LWLOCK.exclusive();
     }
+
+    #[pg_test]
+    fn test_shmem_is_named_after_its_static() {
+        assert_eq!(LWLOCK.get_name(), "pgx_tests::tests::shmem_tests::LWLOCK");
+    }
+
+    #[pg_test]
+    fn test_shmem_starts_with_its_version() {
+        assert_eq!(LWLOCK.shmem_version(), ShmemVersion::current::<bool>());
+        assert_eq!(unsafe { *lwlock_header() }, ShmemVersion::current::<bool>());
+        assert_eq!(check_forged(ShmemVersion::current::<bool>()), Ok(()));
+    }
+
+    #[pg_test]
+    fn test_current_version_follows_the_type() {
+        assert_eq!(ShmemVersion::current::<u64>(), ShmemVersion::current::<u64>());
+        assert_ne!(ShmemVersion::current::<u64>(), ShmemVersion::current::<i64>());
+        assert_ne!(ShmemVersion::current::<u64>(), ShmemVersion::current::<[u64; 2]>());
+        assert_ne!(ShmemVersion::current::<u64>(), ShmemVersion::new(1));
+        assert_eq!(ShmemVersion::from(1), ShmemVersion::new(1));
+    }
+
+    #[pg_test]
+    fn test_forged_layout_mismatches() {
+        let forged = ShmemVersion::new(42);
+        let mismatch = check_forged(forged).unwrap_err();
+        assert_eq!(
+            mismatch,
+            ShmemVersionMismatch::Layout {
+                expected: ShmemVersion::current::<bool>().layout(),
+                found: 42
+            }
+        );
+        assert_eq!(
+            mismatch.to_string(),
+            format!(
+                "it's laid out as version 000000000000002a, but the library lays it out as {:016x}",
+                ShmemVersion::current::<bool>().layout()
+            )
+        );
+
+        // and it was put back
+        assert_eq!(unsafe { *lwlock_header() }, ShmemVersion::current::<bool>());
+    }
+
+    #[pg_test]
+    fn test_forged_unversioned_memory_mismatches() {
+        // such as a `bool` that was laid out by a pgx that didn't version shared memory
+        let forged: ShmemVersion = unsafe { std::mem::transmute([1u8; 16]) };
+        assert_eq!(check_forged(forged), Err(ShmemVersionMismatch::NotVersioned));
+    }
+
+    #[pg_test]
+    fn test_atomic_operations() {
+        let atomic = PgAtomicU32::new(5);
+        assert_eq!(atomic.fetch_add(3), 5);
+        assert_eq!(atomic.add_fetch(2), 10);
+        assert_eq!(atomic.sub_fetch(4), 6);
+        assert_eq!(atomic.fetch_sub(-1), 6);
+        assert_eq!(atomic.exchange(0b1100), 7);
+        assert_eq!(atomic.fetch_and(0b0110), 0b1100);
+        assert_eq!(atomic.fetch_or(0b0001), 0b0100);
+        assert_eq!(atomic.compare_exchange(0, 1), Err(0b0101));
+        assert_eq!(atomic.compare_exchange(0b0101, 1), Ok(0b0101));
+        atomic.write(u32::MAX);
+        assert_eq!(atomic.add_fetch(1), 0);
+        assert_eq!(unsafe { (*atomic.as_ptr()).value }, 0);
+    }
+
+    #[pg_test]
+    fn test_atomic_counts_every_backends_increments() {
+        let before = COUNTER.get().read();
+        let workers = (0..4)
+            .map(|_| {
+                BackgroundWorkerBuilder::new("shmem_tests_counter")
+                    .set_library("pgx_tests")
+                    .set_function("shmem_tests_count_bgworker")
+                    .set_argument(100_000i32.into_datum())
+                    .set_notify_pid(unsafe { pg_sys::MyProcPid })
+                    .load_dynamic()
+            })
+            .collect::<Vec<_>>();
+        // and this backend counts alongside them
+        for _ in 0..100_000 {
+            COUNTER.get().fetch_add(1);
+        }
+        for worker in workers {
+            worker.wait_for_shutdown().expect("aborted shutdown");
+        }
+        assert_eq!(COUNTER.get().read() - before, 500_000);
+    }
 }
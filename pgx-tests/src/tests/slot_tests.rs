@@ -0,0 +1,239 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::slot::Slot;
+    use pgx::{PgTupleDesc, TryFromDatumError};
+    use std::num::NonZeroUsize;
+
+    fn attno(attno: usize) -> NonZeroUsize {
+        NonZeroUsize::new(attno).unwrap()
+    }
+
+    /// A `DestReceiver` that reads each row with a [`Slot`] before passing it on to `inner`
+    #[repr(C)]
+    struct ReadingReceiver {
+        receiver: pg_sys::DestReceiver,
+        inner: *mut pg_sys::DestReceiver,
+    }
+
+    static mut ROWS: Vec<(Option<i32>, Option<String>)> = Vec::new();
+
+    #[pg_guard]
+    unsafe extern "C" fn receive_slot(
+        slot: *mut pg_sys::TupleTableSlot,
+        receiver: *mut pg_sys::DestReceiver,
+    ) -> bool {
+        let slot = Slot::from_pg(slot);
+        ROWS.push((slot.get_typed(attno(1)).unwrap(), slot.get_typed(attno(2)).unwrap()));
+
+        let inner = (*(receiver as *mut ReadingReceiver)).inner;
+        (*inner).receiveSlot.unwrap()(slot.as_ptr(), inner)
+    }
+
+    #[pg_guard]
+    unsafe extern "C" fn startup(
+        receiver: *mut pg_sys::DestReceiver,
+        operation: i32,
+        typeinfo: pg_sys::TupleDesc,
+    ) {
+        let inner = (*(receiver as *mut ReadingReceiver)).inner;
+        (*inner).rStartup.unwrap()(inner, operation, typeinfo)
+    }
+
+    #[pg_guard]
+    unsafe extern "C" fn shutdown(receiver: *mut pg_sys::DestReceiver) {
+        let inner = (*(receiver as *mut ReadingReceiver)).inner;
+        (*inner).rShutdown.unwrap()(inner)
+    }
+
+    #[pg_guard]
+    unsafe extern "C" fn destroy(_receiver: *mut pg_sys::DestReceiver) {}
+
+    static mut PREV_EXECUTOR_RUN: pg_sys::ExecutorRun_hook_type = None;
+
+    /// An `ExecutorRun_hook` that sends the query's rows through a [`ReadingReceiver`]
+    #[pg_guard]
+    unsafe extern "C" fn reading_executor_run(
+        query_desc: *mut pg_sys::QueryDesc,
+        direction: pg_sys::ScanDirection,
+        count: u64,
+        execute_once: bool,
+    ) {
+        let inner = (*query_desc).dest;
+        let mut reader = ReadingReceiver {
+            receiver: pg_sys::DestReceiver {
+                receiveSlot: Some(receive_slot),
+                rStartup: Some(startup),
+                rShutdown: Some(shutdown),
+                rDestroy: Some(destroy),
+                mydest: (*inner).mydest,
+            },
+            inner,
+        };
+        (*query_desc).dest = &mut reader.receiver;
+        match PREV_EXECUTOR_RUN {
+            Some(prev) => prev(query_desc, direction, count, execute_once),
+            None => pg_sys::standard_ExecutorRun(query_desc, direction, count, execute_once),
+        }
+        (*query_desc).dest = inner;
+    }
+
+    /// [`reading_executor_run`], installed as the `ExecutorRun_hook` until this is dropped
+    struct ReadingHook;
+
+    impl ReadingHook {
+        unsafe fn install() -> ReadingHook {
+            PREV_EXECUTOR_RUN = pg_sys::ExecutorRun_hook.replace(reading_executor_run);
+            ReadingHook
+        }
+    }
+
+    impl Drop for ReadingHook {
+        fn drop(&mut self) {
+            unsafe { pg_sys::ExecutorRun_hook = PREV_EXECUTOR_RUN.take() }
+        }
+    }
+
+    fn create_slot_tests_row() -> Result<PgTupleDesc<'static>, pgx::spi::Error> {
+        Spi::run("CREATE TYPE slot_tests_row AS (id int, name text)")?;
+        Ok(PgTupleDesc::for_composite_type("slot_tests_row").unwrap())
+    }
+
+    #[pg_test]
+    unsafe fn test_executor_run_hook_reads_slots() -> Result<(), pgx::spi::Error> {
+        let hook = ReadingHook::install();
+        Spi::run("SELECT * FROM (VALUES (1, 'one'), (NULL, 'two'), (3, NULL)) AS t")?;
+        drop(hook);
+        assert_eq!(
+            ROWS,
+            vec![(Some(1), Some("one".into())), (None, Some("two".into())), (Some(3), None)]
+        );
+
+        // the hook was removed
+        Spi::run("SELECT * FROM (VALUES (4, 'four')) AS t")?;
+        assert_eq!(ROWS.len(), 3);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_store_values_and_materialize() -> Result<(), pgx::spi::Error> {
+        let tupdesc = create_slot_tests_row()?;
+        let mut slot = Slot::new_heap(&tupdesc);
+        assert!(slot.is_empty());
+        assert_eq!(slot.len(), 2);
+
+        let name = "Brandy".to_string().into_datum();
+        slot.store_values(&[42.into_datum(), name]).unwrap();
+        slot.materialize();
+        assert!(!slot.is_empty());
+        assert_eq!(slot.get_typed::<i32>(attno(1)), Ok(Some(42)));
+        assert_eq!(slot.get_typed::<String>(attno(2)), Ok(Some("Brandy".into())));
+
+        let tuple = slot.to_heap_tuple().unwrap();
+        assert_eq!(tuple.get_by_name::<String>("name"), Ok(Some("Brandy".into())));
+
+        slot.store_values(&[None, None]).unwrap();
+        assert_eq!(slot.get(attno(1)), None);
+        assert_eq!(tuple.get_by_name::<i32>("id"), Ok(Some(42)));
+
+        slot.clear();
+        assert!(slot.is_empty());
+        assert!(slot.to_heap_tuple().is_none());
+        assert!(slot.store_values(&[None]).is_err());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_store_heap_tuple() -> Result<(), pgx::spi::Error> {
+        let tupdesc = create_slot_tests_row()?;
+        let tuple = PgHeapTuple::from_datums(
+            tupdesc.clone(),
+            [7.into_datum(), "Ruby".to_string().into_datum()],
+        )
+        .unwrap();
+
+        let mut slot = Slot::new_heap(&tupdesc);
+        slot.store_heap_tuple(&tuple);
+        drop(tuple);
+        assert_eq!(slot.get_typed::<i32>(attno(1)), Ok(Some(7)));
+        assert_eq!(slot.get_typed::<String>(attno(2)), Ok(Some("Ruby".into())));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "pg11"))]
+    #[pg_test]
+    fn test_virtual_slots() -> Result<(), pgx::spi::Error> {
+        let tupdesc = create_slot_tests_row()?;
+        let tuple = PgHeapTuple::from_datums(tupdesc.clone(), [7.into_datum(), None]).unwrap();
+
+        // the tuple is deformed into the virtual slot, and formed again when copied out
+        let mut slot = Slot::new_virtual(&tupdesc);
+        slot.store_heap_tuple(&tuple);
+        assert_eq!(slot.get_typed::<i32>(attno(1)), Ok(Some(7)));
+        assert_eq!(slot.get_typed::<String>(attno(2)), Ok(None));
+        assert_eq!(slot.to_heap_tuple().unwrap().get_by_name::<i32>("id"), Ok(Some(7)));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_minimal_slots() -> Result<(), pgx::spi::Error> {
+        let tupdesc = create_slot_tests_row()?;
+        let tuple = PgHeapTuple::from_datums(
+            tupdesc.clone(),
+            [7.into_datum(), "Ruby".to_string().into_datum()],
+        )
+        .unwrap();
+
+        let mut slot = Slot::new_minimal(&tupdesc);
+        slot.store_heap_tuple(&tuple);
+        drop(tuple);
+        assert_eq!(slot.get_typed::<i32>(attno(1)), Ok(Some(7)));
+        assert_eq!(slot.get_typed::<String>(attno(2)), Ok(Some("Ruby".into())));
+
+        // values stored separately are formed into a minimal tuple when it's materialized
+        let name = "Brandy".to_string().into_datum();
+        slot.store_values(&[42.into_datum(), name]).unwrap();
+        slot.materialize();
+        let copy = slot.to_heap_tuple().unwrap();
+        assert_eq!(copy.get_by_name::<i32>("id"), Ok(Some(42)));
+        assert_eq!(copy.get_by_name::<String>("name"), Ok(Some("Brandy".into())));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_get_typed_errors() -> Result<(), pgx::spi::Error> {
+        let tupdesc = create_slot_tests_row()?;
+        let mut slot = Slot::new_heap(&tupdesc);
+        slot.store_values(&[1.into_datum(), None]).unwrap();
+
+        assert!(matches!(
+            slot.get_typed::<String>(attno(1)),
+            Err(TryFromDatumError::IncompatibleTypes { .. })
+        ));
+        assert_eq!(
+            slot.get_typed::<i32>(attno(3)),
+            Err(TryFromDatumError::NoSuchAttributeNumber(attno(3)))
+        );
+        Ok(())
+    }
+
+    #[pg_test(error = "slot is empty")]
+    fn test_get_from_empty_slot() {
+        let tupdesc = create_slot_tests_row().unwrap();
+        let slot = Slot::new_heap(&tupdesc);
+        slot.get(attno(1));
+    }
+}
@@ -0,0 +1,181 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::spi_csv::{CsvError, CsvWriter};
+    use std::io::{self, Write};
+
+    fn export(mut csv: CsvWriter<Vec<u8>>, query: &str) -> Result<(u64, String), CsvError> {
+        let rows = Spi::connect(|client| csv.export(&client, query, None, None))?;
+        Ok((rows, String::from_utf8(csv.into_inner()).unwrap()))
+    }
+
+    #[pg_test]
+    fn test_quoting() -> Result<(), CsvError> {
+        let (rows, csv) = export(
+            CsvWriter::new(Vec::new()).header(true),
+            r#"SELECT 1 AS id, NULL::text AS a, '' AS b, 'x,y' AS "c,d", 'say "hi"' AS e,
+                E'two\nlines' AS f, ARRAY[1, 2] AS g
+            UNION ALL
+            SELECT 2, 'plain', ' ', '\.', 'é', E'cr\r', NULL"#,
+        )?;
+        assert_eq!(rows, 2);
+        assert_eq!(
+            csv,
+            "id,a,b,\"c,d\",e,f,g\n\
+            1,,\"\",\"x,y\",\"say \"\"hi\"\"\",\"two\nlines\",\"{1,2}\"\n\
+            2,plain, ,\\.,é,\"cr\r\",\n"
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_single_column() -> Result<(), CsvError> {
+        let (_, csv) =
+            export(CsvWriter::new(Vec::new()), r"SELECT unnest(ARRAY['a', '\.', NULL])")?;
+        assert_eq!(csv, "a\n\"\\.\"\n\n");
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_header_without_rows() -> Result<(), CsvError> {
+        let (rows, csv) =
+            export(CsvWriter::new(Vec::new()).header(true), "SELECT 1 AS a, 2 AS b WHERE false")?;
+        assert_eq!(rows, 0);
+        assert_eq!(csv, "a,b\n");
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_options() -> Result<(), CsvError> {
+        let (_, csv) = export(
+            CsvWriter::new(Vec::new()).delimiter(b';').null(r"\N"),
+            r"SELECT 'a;b' AS x, NULL::int AS y, '\N' AS z, 'c,d' AS w",
+        )?;
+        assert_eq!(csv, "\"a;b\";\\N;\"\\N\";c,d\n");
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_write_row() -> Result<(), CsvError> {
+        let mut csv = CsvWriter::new(Vec::new()).header(true);
+        Spi::connect(|client| {
+            client.select_streaming(
+                "SELECT i, i * i AS square FROM generate_series(1, 5) i",
+                Some(2),
+                None,
+                |row| match row.get::<i32>(1)? {
+                    Some(i) if i % 2 == 1 => csv.write_row(row),
+                    _ => Ok(()),
+                },
+            )
+        })?;
+        assert_eq!(String::from_utf8(csv.into_inner()).unwrap(), "i,square\n1,1\n3,9\n5,25\n");
+        Ok(())
+    }
+
+    /// Fails to write once it's been given `limit` bytes
+    struct FailingWriter {
+        written: usize,
+        limit: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written + buf.len() > self.limit {
+                return Err(io::Error::new(io::ErrorKind::Other, "the disk is full"));
+            }
+            self.written += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[pg_test]
+    fn test_write_error_closes_cursor() -> spi::Result<()> {
+        let cursors = Spi::get_one::<i64>("SELECT count(*) FROM pg_cursors")?;
+        let mut csv = CsvWriter::new(FailingWriter { written: 0, limit: 1000 });
+        let result = Spi::connect(|client| {
+            csv.export(&client, "SELECT i FROM generate_series(1, 100000) i", Some(100), None)
+        });
+        assert!(matches!(result, Err(CsvError::Io(_))));
+        assert!(csv.into_inner().written <= 1000);
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM pg_cursors")?, cursors);
+        Ok(())
+    }
+
+    /// Counts the lines it's given, and how much memory the backend has at most allocated while
+    /// they're written
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    struct CountingWriter {
+        lines: u64,
+        max_allocated: usize,
+    }
+
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    fn allocated() -> usize {
+        unsafe { pg_sys::MemoryContextMemAllocated(pg_sys::TopMemoryContext, true) }
+    }
+
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let lines = buf.iter().filter(|&&b| b == b'\n').count() as u64;
+            if self.lines / 100_000 != (self.lines + lines) / 100_000 {
+                self.max_allocated = self.max_allocated.max(allocated());
+            }
+            self.lines += lines;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    #[pg_test]
+    fn test_export_in_bounded_memory() -> Result<(), CsvError> {
+        const ROWS: u64 = 5_000_000;
+        // the rows take up hundreds of megabytes, and no more than a batch of them should be in
+        // memory at once
+        const MEMORY_CAP: usize = 32 * 1024 * 1024;
+
+        let before = allocated();
+        let mut csv =
+            CsvWriter::new(CountingWriter { lines: 0, max_allocated: before }).header(true);
+        let rows = Spi::connect(|client| {
+            csv.export(
+                &client,
+                "SELECT i, 'row number ' || i AS label, now() AS at
+                FROM generate_series(1, $1) i",
+                Some(10_000),
+                Some(vec![(PgOid::BuiltIn(PgBuiltInOids::INT8OID), (ROWS as i64).into_datum())]),
+            )
+        })?;
+        let counted = csv.into_inner();
+        assert_eq!(rows, ROWS);
+        assert_eq!(counted.lines, ROWS + 1);
+        assert!(
+            counted.max_allocated - before < MEMORY_CAP,
+            "{} bytes were allocated to export the rows",
+            counted.max_allocated - before
+        );
+        Ok(())
+    }
+}
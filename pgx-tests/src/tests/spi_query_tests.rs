@@ -0,0 +1,161 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::format_sql;
+    use pgx::prelude::*;
+    use pgx::spi_query::SpiQuery;
+
+    const HOSTILE: &[&str] = &[
+        "plain",
+        "MixedCase",
+        "select",
+        "with space",
+        r#"double"quote"#,
+        "single'quote",
+        r"back\slash",
+        "Robert'); DROP TABLE students;--",
+        r#"x"; DROP TABLE students;--"#,
+        "naïve 日本語 🦀",
+        "$1 $$ dollar $tag$",
+    ];
+
+    #[pg_test]
+    fn test_select_renders_quoted_and_bound() {
+        let (sql, args) = SpiQuery::select()
+            .columns(["a", "Mixed Case"])
+            .from(("schema", "table"))
+            .where_eq("id", 5i64)
+            .where_null("deleted")
+            .order_by_desc("a")
+            .limit(10)
+            .offset(20)
+            .build();
+        assert_eq!(
+            sql,
+            r#"SELECT a, "Mixed Case" FROM schema."table" WHERE id = $1 AND deleted IS NULL ORDER BY a DESC LIMIT $2 OFFSET $3"#
+        );
+        let args = args.unwrap();
+        assert_eq!(args.len(), 3);
+        assert_eq!(args[0].0, PgOid::from(PgBuiltInOids::INT8OID));
+
+        let (sql, args) = SpiQuery::select().build();
+        assert_eq!(sql, "SELECT *");
+        assert!(args.is_none());
+    }
+
+    #[pg_test]
+    fn test_modifications_render_quoted_and_bound() {
+        let (sql, _) =
+            SpiQuery::insert_into("t").value("a", 1).value("b", "x").returning(["id"]).build();
+        assert_eq!(sql, "INSERT INTO t (a, b) VALUES ($1, $2) RETURNING id");
+
+        let (sql, args) = SpiQuery::insert_into("t").build();
+        assert_eq!(sql, "INSERT INTO t DEFAULT VALUES");
+        assert!(args.is_none());
+
+        let (sql, _) = SpiQuery::update(("s", "t")).set("a", 1).where_eq("id", 2).build();
+        assert_eq!(sql, "UPDATE s.t SET a = $1 WHERE id = $2");
+
+        let (sql, _) = SpiQuery::delete_from("t")
+            .where_not_null("a")
+            .returning_fragment(SpiQuery::unsafe_fragment("*"))
+            .build();
+        assert_eq!(sql, "DELETE FROM t WHERE a IS NOT NULL RETURNING *");
+    }
+
+    #[pg_test]
+    fn test_values_are_never_inlined() {
+        for value in HOSTILE {
+            let (sql, args) = SpiQuery::select().from("t").where_eq("a", *value).build();
+            assert_eq!(sql, "SELECT * FROM t WHERE a = $1");
+            assert_eq!(args.unwrap().len(), 1);
+        }
+    }
+
+    #[pg_test]
+    fn test_hostile_identifiers_and_values_round_trip() -> Result<(), pgx::spi::Error> {
+        for name in HOSTILE {
+            Spi::run(&format_sql!(
+                "CREATE TABLE tests.{ident} (id bigserial, {ident} text)",
+                name,
+                name
+            ))?;
+
+            let (sql, args) = SpiQuery::insert_into(("tests", *name))
+                .value(name, *name)
+                .returning(["id"])
+                .build();
+            let id = Spi::connect(|mut client| {
+                client.update(&sql, None, args)?.first().get_one::<i64>()
+            })?;
+            assert_eq!(id, Some(1));
+
+            let (sql, args) = SpiQuery::select()
+                .columns([*name])
+                .from(("tests", *name))
+                .where_eq(name, *name)
+                .where_eq("id", 1i64)
+                .build();
+            let value = Spi::connect(|client| {
+                client.select(&sql, None, args)?.first().get_one::<String>()
+            })?;
+            assert_eq!(value.as_deref(), Some(*name));
+
+            let (sql, args) = SpiQuery::update(("tests", *name))
+                .set(name, format!("{}{}", name, name))
+                .where_eq(name, *name)
+                .returning([*name])
+                .build();
+            let value = Spi::connect(|mut client| {
+                client.update(&sql, None, args)?.first().get_one::<String>()
+            })?;
+            assert_eq!(value, Some(format!("{}{}", name, name)));
+
+            let (sql, args) = SpiQuery::delete_from(("tests", *name))
+                .where_eq(name, format!("{}{}", name, name))
+                .returning(["id"])
+                .build();
+            let deleted = Spi::connect(|mut client| {
+                client.update(&sql, None, args)?.first().get_one::<i64>()
+            })?;
+            assert_eq!(deleted, Some(1));
+
+            let count =
+                Spi::get_one::<i64>(&format_sql!("SELECT count(*) FROM tests.{ident}", name))?;
+            assert_eq!(count, Some(0));
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_unsafe_fragment() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TABLE tests.fragments (a int); INSERT INTO tests.fragments VALUES (1), (2), (3)")?;
+        let (sql, args) = SpiQuery::select()
+            .column_fragment(SpiQuery::unsafe_fragment("count(*)"))
+            .from(("tests", "fragments"))
+            .where_fragment(SpiQuery::unsafe_fragment("a > 1"))
+            .build();
+        assert_eq!(sql, "SELECT count(*) FROM tests.fragments WHERE (a > 1)");
+        let count =
+            Spi::connect(|client| client.select(&sql, None, args)?.first().get_one::<i64>())?;
+        assert_eq!(count, Some(2));
+        Ok(())
+    }
+
+    #[pg_test(error = "an UPDATE of t doesn't set any columns")]
+    fn test_update_without_set() {
+        SpiQuery::update("t").where_eq("id", 1).build();
+    }
+}
@@ -123,6 +123,105 @@ mod tests {
         assert!(Spi::get_one::<i32>("SELECT 1 LIMIT 0").is_err());
     }
 
+    #[pg_test]
+    fn test_spi_get_one_unexpected_shape() {
+        use spi::Error::UnexpectedShape;
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*), max(x) FROM (VALUES (1), (2)) t(x)"),
+            Err(UnexpectedShape { rows: 1, cols: 2 })
+        );
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT * FROM generate_series(1, 5)"),
+            Err(UnexpectedShape { rows: 2, cols: 1 })
+        );
+        assert_eq!(
+            Spi::get_one_with_args::<i32>(
+                "SELECT * FROM generate_series(1, $1)",
+                vec![(PgBuiltInOids::INT4OID.oid(), 2.into_datum())]
+            ),
+            Err(UnexpectedShape { rows: 2, cols: 1 })
+        );
+    }
+
+    #[pg_test]
+    fn test_spi_get_two_three_unexpected_shape() {
+        use spi::Error::UnexpectedShape;
+        assert_eq!(
+            Spi::get_two::<i32, i32>("SELECT 1, 2, 3"),
+            Err(UnexpectedShape { rows: 1, cols: 3 })
+        );
+        assert_eq!(
+            Spi::get_two::<i32, i32>("VALUES (1, 2), (3, 4)"),
+            Err(UnexpectedShape { rows: 2, cols: 2 })
+        );
+        assert_eq!(
+            Spi::get_three::<i32, i32, i32>("SELECT 1, 2"),
+            Err(UnexpectedShape { rows: 1, cols: 2 })
+        );
+        assert_eq!(
+            Spi::get_three_with_args::<i32, i32, i32>(
+                "SELECT $1, 2, 3, 4",
+                vec![(PgBuiltInOids::INT4OID.oid(), 1.into_datum())]
+            ),
+            Err(UnexpectedShape { rows: 1, cols: 4 })
+        );
+    }
+
+    #[pg_test]
+    fn test_spi_get_shape_matches() -> Result<(), spi::Error> {
+        assert_eq!(Spi::get_one::<i32>("SELECT 1")?, Some(1));
+        assert_eq!(Spi::get_two::<i32, i32>("SELECT 1, 2")?, (Some(1), Some(2)));
+        assert_eq!(Spi::get_three::<i32, i32, i32>("SELECT 1, 2, 3")?, (Some(1), Some(2), Some(3)));
+        // no rows is the right shape, and the same error as before there was one
+        assert_eq!(Spi::get_one::<i32>("SELECT 1 LIMIT 0"), Err(spi::Error::InvalidPosition));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_spi_tuple_table_get_one_unexpected_shape() -> Result<(), spi::Error> {
+        Spi::connect(|client| {
+            let table = client.select("SELECT 1, 2", None, None)?.first();
+            assert_eq!(
+                table.get_one::<i32>(),
+                Err(spi::Error::UnexpectedShape { rows: 1, cols: 2 })
+            );
+            // any column can still be had by its ordinal
+            assert_eq!(table.get::<i32>(2)?, Some(2));
+            Ok(())
+        })
+    }
+
+    #[pg_test]
+    fn test_spi_get_first() -> Result<(), spi::Error> {
+        assert_eq!(Spi::get_first::<i32>("VALUES (1, 2), (3, 4)")?, Some(1));
+        assert_eq!(
+            Spi::get_first_with_args::<i32>(
+                "SELECT * FROM generate_series($1, 10)",
+                vec![(PgBuiltInOids::INT4OID.oid(), 5.into_datum())]
+            )?,
+            Some(5)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_spi_only_row_after_dml() -> Result<(), spi::Error> {
+        Spi::connect(|mut client| {
+            client.update("CREATE TABLE tests.only_row_dml (x int)", None, None)?;
+            client.update("INSERT INTO tests.only_row_dml VALUES (1), (2), (3)", None, None)?;
+            let updated = client.update(
+                "UPDATE tests.only_row_dml SET x = x * 10 RETURNING x",
+                Some(2),
+                None,
+            )?;
+            assert_eq!(updated.len(), 2);
+            Ok::<_, spi::Error>(())
+        })?;
+        // the limit only bounded the rows returned: the UPDATE still changed all three
+        assert_eq!(Spi::get_one::<i64>("SELECT sum(x) FROM tests.only_row_dml")?, Some(60));
+        Ok(())
+    }
+
     #[pg_test]
     fn test_spi_run() {
         assert!(Spi::run("SELECT 1").is_ok());
@@ -459,4 +558,117 @@ mod tests {
         assert_eq!(with_select, with_get_one);
         Ok(())
     }
+
+    #[pg_test]
+    fn test_with_savepoint_skips_bad_rows() -> spi::Result<()> {
+        Spi::run("CREATE TABLE ingested (id int PRIMARY KEY, value int CHECK (value % 3 <> 0))")?;
+        let skipped = Spi::connect(|mut client| {
+            let mut skipped = Vec::new();
+            for id in 1..=30 {
+                let inserted = client.with_savepoint("ingest_row", |client| {
+                    client.update(
+                        "INSERT INTO ingested (id, value) VALUES ($1, $1)",
+                        None,
+                        Some(vec![(PgOid::BuiltIn(PgBuiltInOids::INT4OID), id.into_datum())]),
+                    )
+                });
+                match inserted {
+                    Ok(result) => {
+                        result?;
+                    }
+                    Err(_) => skipped.push(id),
+                }
+            }
+            Ok::<_, spi::Error>(skipped)
+        })?;
+
+        assert_eq!(skipped, (1..=10).map(|i| i * 3).collect::<Vec<_>>());
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM ingested")?, Some(20));
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM ingested WHERE value % 3 = 0")?,
+            Some(0)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_with_savepoint_keeps_tuple_table() -> spi::Result<()> {
+        let value = Spi::connect(|mut client| {
+            let table = client
+                .with_savepoint("select", |client| client.select("SELECT 42", None, None))
+                .expect("savepoint was rolled back")?;
+            table.first().get_one::<i32>()
+        })?;
+        assert_eq!(value, Some(42));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_with_savepoint_catches_panic() -> spi::Result<()> {
+        Spi::run("CREATE TABLE savepoint_panic (id int)")?;
+        let result = Spi::connect(|mut client| {
+            client.with_savepoint("panic", |client| {
+                client.update("INSERT INTO savepoint_panic VALUES (1)", None, None).unwrap();
+                panic!("oops");
+            })
+        });
+        assert!(matches!(result, Err(pgx::pg_sys::panic::CaughtError::RustPanic { .. })));
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM savepoint_panic")?, Some(0));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_select_streaming() -> spi::Result<()> {
+        let (streamed, seen) = Spi::connect(|client| {
+            let mut seen = Vec::new();
+            let streamed = client.select_streaming(
+                "SELECT i FROM generate_series(1, 10) i",
+                Some(3),
+                None,
+                |row| {
+                    seen.push(row.get::<i32>(1)?.unwrap());
+                    Ok::<_, spi::Error>(())
+                },
+            )?;
+            Ok::<_, spi::Error>((streamed, seen))
+        })?;
+        assert_eq!(streamed, 10);
+        assert_eq!(seen, (1..=10).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_select_streaming_no_rows() -> spi::Result<()> {
+        let streamed = Spi::connect(|client| {
+            client.select_streaming("SELECT 1 WHERE false", None, None, |_| -> spi::Result<()> {
+                panic!("there are no rows")
+            })
+        })?;
+        assert_eq!(streamed, 0);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_select_streaming_error_closes_cursor() -> spi::Result<()> {
+        let cursors = Spi::get_one::<i64>("SELECT count(*) FROM pg_cursors")?;
+        let mut seen = 0;
+        let result = Spi::connect(|client| {
+            client.select_streaming(
+                "SELECT i FROM generate_series(1, 100) i",
+                Some(10),
+                None,
+                |row| {
+                    seen += 1;
+                    match row.get::<i32>(1)? {
+                        Some(15) => Err(spi::Error::InvalidPosition),
+                        _ => Ok(()),
+                    }
+                },
+            )
+        });
+        assert_eq!(result, Err(spi::Error::InvalidPosition));
+        assert_eq!(seen, 15);
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM pg_cursors")?, cursors);
+        Ok(())
+    }
 }
@@ -93,6 +93,16 @@ fn split_table_with_borrow<'a>(
     TableIterator::new(input.split_terminator(pattern).enumerate().map(|(i, s)| (i as i32, s)))
 }
 
+#[pg_extern(rows = 3)]
+fn three_row_series() -> SetOfIterator<'static, i32> {
+    SetOfIterator::new(vec![1, 2, 3])
+}
+
+#[pg_extern(rows = 2)]
+fn two_row_table() -> TableIterator<'static, (name!(idx, i32), name!(value, &'static str))> {
+    TableIterator::new(vec![(1, "a"), (2, "b")])
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgx::pg_schema]
 mod tests {
@@ -228,6 +238,24 @@ mod tests {
         assert_eq!(cnt, Ok(1000000))
     }
 
+    /// How many rows the planner estimates `query` returns
+    fn planned_rows(query: &str) -> Result<Option<f64>, spi::Error> {
+        let plan = Spi::get_one::<pgx::Json>(&format!("EXPLAIN (FORMAT JSON) {query}"))?;
+        Ok(plan.and_then(|plan| plan.0[0]["Plan"]["Plan Rows"].as_f64()))
+    }
+
+    #[pg_test]
+    fn test_rows_estimate() -> Result<(), spi::Error> {
+        assert_eq!(planned_rows("SELECT * FROM three_row_series()")?, Some(3.0));
+        assert_eq!(planned_rows("SELECT * FROM two_row_table()")?, Some(2.0));
+        assert_eq!(planned_rows("SELECT * FROM example_generate_series(1, 3)")?, Some(1000.0));
+        assert_eq!(
+            Spi::get_one::<f32>("SELECT prorows FROM pg_proc WHERE proname = 'three_row_series'")?,
+            Some(3.0)
+        );
+        Ok(())
+    }
+
     #[pg_test(error = "column \"cause_an_error\" does not exist")]
     pub fn spi_in_iterator(
     ) -> TableIterator<'static, (name!(id, i32), name!(relname, Result<Option<String>, spi::Error>))>
@@ -0,0 +1,87 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_init]
+fn statement_tests_init() {
+    pgx::statement::track();
+}
+
+#[pg_extern]
+fn statement_tests_nesting_level() -> i32 {
+    pgx::nesting_level() as i32
+}
+
+#[pg_extern]
+fn statement_tests_nested_nesting_level() -> i32 {
+    Spi::get_one::<i32>("SELECT statement_tests_nesting_level()")
+        .expect("SPI failed")
+        .expect("statement_tests_nesting_level() was null")
+}
+
+#[pg_extern]
+fn statement_tests_query_string() -> Option<String> {
+    pgx::current_query_string()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_nesting_level() -> Result<(), pgx::spi::Error> {
+        // the test is called by the top-level `SELECT`
+        assert_eq!(pgx::nesting_level(), 1);
+        assert_eq!(Spi::get_one::<i32>("SELECT statement_tests_nesting_level()")?, Some(2));
+        assert_eq!(Spi::get_one::<i32>("SELECT statement_tests_nested_nesting_level()")?, Some(3));
+        assert_eq!(pgx::nesting_level(), 1);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_nesting_level_after_error() -> Result<(), pgx::spi::Error> {
+        Spi::run(
+            "DO $$
+            BEGIN
+                PERFORM 1 / x FROM generate_series(0, 0) x;
+            EXCEPTION WHEN division_by_zero THEN
+                NULL;
+            END
+            $$",
+        )?;
+        assert_eq!(pgx::nesting_level(), 1);
+        assert_eq!(Spi::get_one::<i32>("SELECT statement_tests_nesting_level()")?, Some(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_current_query_string() -> Result<(), pgx::spi::Error> {
+        let query = pgx::current_query_string().expect("no query is running");
+        assert!(query.contains("test_current_query_string"), "{}", query);
+        assert_eq!(Spi::get_one::<String>("SELECT current_query()")?.as_ref(), Some(&query));
+
+        // nested statements see the top-level query
+        assert_eq!(Spi::get_one::<String>("SELECT statement_tests_query_string()")?, Some(query));
+        Ok(())
+    }
+
+    #[cfg(any(feature = "pg14", feature = "pg15"))]
+    #[pg_test]
+    fn test_current_query_id() -> Result<(), pgx::spi::Error> {
+        let expected = Spi::get_one::<i64>(
+            "SELECT query_id FROM pg_stat_activity WHERE pid = pg_backend_pid()",
+        )?;
+        assert_eq!(pgx::current_query_id().map(|id| id as i64), expected);
+        Ok(())
+    }
+}
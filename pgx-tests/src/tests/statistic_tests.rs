@@ -0,0 +1,112 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::statistic::PgStatistics;
+
+    /// 500 ones, 300 twos, the numbers 3 to 202 once each, and 100 `NULL`s, analyzed
+    fn analyzed_table() -> Result<pg_sys::Oid, pgx::spi::Error> {
+        Spi::run("CREATE TABLE statistic_tests_t (x int)")?;
+        Spi::run(
+            "INSERT INTO statistic_tests_t
+             SELECT 1 FROM generate_series(1, 500)
+             UNION ALL SELECT 2 FROM generate_series(1, 300)
+             UNION ALL SELECT generate_series(3, 202)
+             UNION ALL SELECT NULL FROM generate_series(1, 100)",
+        )?;
+        Spi::run("ANALYZE statistic_tests_t")?;
+        Ok(Spi::get_one::<pg_sys::Oid>("SELECT 'statistic_tests_t'::regclass::oid")?.unwrap())
+    }
+
+    #[pg_test]
+    fn test_statistics_match_pg_stats() -> Result<(), pgx::spi::Error> {
+        let table = analyzed_table()?;
+        let stats = PgStatistics::for_column(table, 1).expect("the table was analyzed");
+
+        let (null_frac, n_distinct) = Spi::get_two::<f32, f32>(
+            "SELECT null_frac, n_distinct FROM pg_stats WHERE tablename = 'statistic_tests_t'",
+        )?;
+        assert_eq!(Some(stats.null_frac()), null_frac);
+        assert_eq!(Some(stats.n_distinct()), n_distinct);
+        let distinct = stats.estimated_distinct().unwrap();
+        assert!((distinct - 202.0).abs() <= 1.0, "estimated {} distinct values", distinct);
+
+        let mcv = stats.most_common_values().expect("the table has common values");
+        assert_eq!(mcv.element_type(), pg_sys::INT4OID);
+        assert_eq!(
+            Some(mcv.values_as::<i32>().unwrap()),
+            Spi::get_one::<Vec<Option<i32>>>(
+                "SELECT most_common_vals::text::int[] FROM pg_stats WHERE tablename = 'statistic_tests_t'"
+            )?
+        );
+        assert_eq!(mcv.values().len(), mcv.numbers().len());
+        assert_eq!(
+            Some(mcv.numbers().iter().copied().map(Some).collect::<Vec<_>>()),
+            Spi::get_one::<Vec<Option<f32>>>(
+                "SELECT most_common_freqs FROM pg_stats WHERE tablename = 'statistic_tests_t'"
+            )?
+        );
+        assert!((mcv.numbers()[0] - 500.0 / 1100.0).abs() < 0.001);
+
+        let histogram = stats.histogram_bounds().expect("the table has a histogram");
+        assert_eq!(
+            Some(histogram.values_as::<i32>().unwrap()),
+            Spi::get_one::<Vec<Option<i32>>>(
+                "SELECT histogram_bounds::text::int[] FROM pg_stats WHERE tablename = 'statistic_tests_t'"
+            )?
+        );
+        assert!(histogram.numbers().is_empty());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_values_as_the_wrong_type() -> Result<(), pgx::spi::Error> {
+        let table = analyzed_table()?;
+        let stats = PgStatistics::for_column(table, 1).unwrap();
+        let mcv = stats.most_common_values().unwrap();
+        assert!(matches!(
+            mcv.values_as::<String>(),
+            Err(pgx::TryFromDatumError::IncompatibleTypes { .. })
+        ));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_eq_selectivity() -> Result<(), pgx::spi::Error> {
+        let table = analyzed_table()?;
+        let stats = PgStatistics::for_column(table, 1).unwrap();
+        let selectivity = |constant| unsafe { stats.eq_selectivity(constant) };
+
+        // a most common value is as common as it was counted to be
+        let common = selectivity(pgx::make_const(1i32));
+        assert!((common - 500.0 / 1100.0).abs() < 0.001, "selectivity of 1 is {}", common);
+
+        // the other 200 rows are spread over the other 200 values
+        let rare = selectivity(pgx::make_const(50i32));
+        assert!((rare * 1100.0 - 1.0).abs() < 0.1, "selectivity of 50 is {}", rare);
+
+        assert_eq!(selectivity(pgx::make_const(None::<i32>)), 0.0);
+        assert_eq!(selectivity(pgx::make_const(1i64)), pg_sys::DEFAULT_EQ_SEL);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_unanalyzed_column() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TABLE statistic_tests_empty (x int)")?;
+        let table = Spi::get_one::<pg_sys::Oid>("SELECT 'statistic_tests_empty'::regclass::oid")?;
+        assert!(PgStatistics::for_column(table.unwrap(), 1).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::once_cell::sync::OnceCell;
+use pgx::stats::{define_counter, Counter};
+
+static HITS: OnceCell<Counter> = OnceCell::new();
+static MISSES: OnceCell<Counter> = OnceCell::new();
+
+/// Called from `_PG_init()`, in `shmem_tests`
+pub(crate) fn define_counters() {
+    HITS.set(define_counter("stats_tests_hits")).unwrap();
+    MISSES.set(define_counter("stats_tests_misses")).unwrap();
+}
+
+pgx::pg_stats_counters!(stats_tests_counters);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use super::{HITS, MISSES};
+    use pgx::prelude::*;
+    use pgx::stats;
+
+    fn counter_value(name: &str) -> Result<Option<i64>, pgx::spi::Error> {
+        Spi::get_one_with_args(
+            "SELECT value FROM stats_tests_counters() WHERE name = $1",
+            vec![(PgBuiltInOids::TEXTOID.oid(), name.into_datum())],
+        )
+    }
+
+    #[pg_test]
+    fn test_counters_are_defined() {
+        let hits = HITS.get().unwrap();
+        assert_eq!(hits.name(), "stats_tests_hits");
+        assert_eq!(stats::counter("stats_tests_hits"), Some(*hits));
+        assert_eq!(stats::counter("stats_tests_unknown"), None);
+
+        let names = stats::counters().into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["stats_tests_hits", "stats_tests_misses"]);
+    }
+
+    #[pg_test]
+    fn test_incr_is_visible_to_this_backend() -> Result<(), pgx::spi::Error> {
+        let misses = MISSES.get().unwrap();
+        let before = misses.get();
+        misses.incr(3);
+        assert_eq!(misses.get(), before + 3);
+        assert_eq!(counter_value("stats_tests_misses")?, Some(before as i64 + 3));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_flush_keeps_values() -> Result<(), pgx::spi::Error> {
+        let hits = HITS.get().unwrap();
+        hits.incr(2);
+        let before = hits.get();
+        stats::flush_counters();
+        assert_eq!(hits.get(), before);
+        assert_eq!(counter_value("stats_tests_hits")?, Some(before as i64));
+        Ok(())
+    }
+}
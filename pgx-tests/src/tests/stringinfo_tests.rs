@@ -0,0 +1,87 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::StringInfo;
+use std::fmt::Write;
+
+#[pg_extern]
+fn stringinfo_counting(n: i32) -> StringInfo {
+    let mut si = StringInfo::new();
+    for i in 0..n {
+        if i > 0 {
+            si.push(',');
+        }
+        write!(si, "{}", i).unwrap();
+    }
+    si
+}
+
+#[pg_extern]
+fn stringinfo_repeat(len: i32) -> StringInfo {
+    let mut si = StringInfo::new();
+    si.reserve(len as usize);
+    for i in 0..len {
+        si.push_bytes(&[b'a' + (i % 26) as u8]);
+    }
+    si
+}
+
+#[pg_extern]
+fn string_repeat(len: i32) -> String {
+    let mut s = String::new();
+    s.reserve(len as usize);
+    for i in 0..len {
+        s.push((b'a' + (i % 26) as u8) as char);
+    }
+    s
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{PgMemoryContexts, StringInfo};
+
+    #[pg_test]
+    fn test_stringinfo_return() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<&str>("SELECT stringinfo_counting(5)")?, Some("0,1,2,3,4"));
+        assert_eq!(Spi::get_one::<&str>("SELECT stringinfo_counting(0)")?, Some(""));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_stringinfo_return_matches_string() -> Result<(), pgx::spi::Error> {
+        // 10MB, well past the StringInfo's initial capacity
+        let same = Spi::get_one::<bool>(
+            "SELECT stringinfo_repeat(10485760) = string_repeat(10485760)
+                AND length(stringinfo_repeat(10485760)) = 10485760",
+        )?;
+        assert_eq!(same, Some(true));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_stringinfo_new_in() {
+        let mut si = unsafe { StringInfo::new_in(PgMemoryContexts::TopTransactionContext) };
+        si.push_str("hello");
+        assert_eq!(si.as_str(), Ok("hello"));
+    }
+
+    #[pg_test]
+    fn test_stringinfo_argument() {
+        let mut si = StringInfo::new();
+        si.push_str("from postgres");
+        let datum = pg_sys::Datum::from(si.into_pg());
+        let arg = unsafe { <&StringInfo>::from_datum(datum, false) }.unwrap();
+        assert_eq!(arg.as_str(), Ok("from postgres"));
+    }
+}
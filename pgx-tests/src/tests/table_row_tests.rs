@@ -0,0 +1,121 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::AllocatedByRust;
+
+#[derive(PgTableRow)]
+struct TableRowTestsPair<'a> {
+    idx: i32,
+    #[pgx(name = "value")]
+    text: &'a str,
+}
+
+#[derive(PgTableRow)]
+struct TableRowTestsPup {
+    name: String,
+    nickname: Option<String>,
+    // the `Dog` of `heap_tuple.rs`
+    #[pgx(composite_type = "Dog")]
+    dog: PgHeapTuple<'static, AllocatedByRust>,
+    #[pgx(composite_type = "Dog")]
+    litter: Vec<PgHeapTuple<'static, AllocatedByRust>>,
+}
+
+#[pg_extern]
+fn table_row_tests_pairs<'a>(input: &'a str) -> TableIterator<'a, TableRowTestsPair<'a>> {
+    TableIterator::new(
+        input
+            .split_whitespace()
+            .enumerate()
+            .map(|(idx, text)| TableRowTestsPair { idx: idx as i32 + 1, text }),
+    )
+}
+
+#[pg_extern]
+fn table_row_tests_pairs_tuple<'a>(
+    input: &'a str,
+) -> TableIterator<'a, (name!(idx, i32), name!(value, &'a str))> {
+    TableIterator::new(
+        input.split_whitespace().enumerate().map(|(idx, text)| (idx as i32 + 1, text)),
+    )
+}
+
+#[pg_extern]
+fn table_row_tests_maybe_pairs<'a>(
+    input: Option<&'a str>,
+) -> Option<TableIterator<'a, TableRowTestsPair<'a>>> {
+    Some(table_row_tests_pairs(input?))
+}
+
+#[pg_extern]
+fn table_row_tests_pups(names: Vec<String>) -> TableIterator<'static, TableRowTestsPup> {
+    TableIterator::new(names.into_iter().map(|name| {
+        let mut dog = PgHeapTuple::new_composite_type("Dog").unwrap();
+        dog.set_by_name("name", name.as_str()).unwrap();
+        dog.set_by_name("scritches", name.len() as i32).unwrap();
+        let nickname = name.strip_suffix("ie").map(|short| short.to_string());
+        TableRowTestsPup { name, nickname, dog, litter: vec![] }
+    }))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+
+    #[pg_test]
+    fn test_rows() -> Result<(), pgx::spi::Error> {
+        let rows = Spi::get_one::<String>(
+            "SELECT string_agg(idx || ':' || value, ',' ORDER BY idx)
+                FROM table_row_tests_pairs('a b c')",
+        )?;
+        assert_eq!(rows, Some("1:a,2:b,3:c".into()));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_same_sql_as_tuple() -> Result<(), pgx::spi::Error> {
+        let result = |function: &str| {
+            Spi::get_one::<String>(&format!("SELECT pg_get_function_result('{function}'::regproc)"))
+        };
+        assert_eq!(result("table_row_tests_pairs")?, Some("TABLE(idx integer, value text)".into()));
+        assert_eq!(result("table_row_tests_pairs")?, result("table_row_tests_pairs_tuple")?);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_optional() -> Result<(), pgx::spi::Error> {
+        let count = Spi::get_one::<i64>("SELECT count(*) FROM table_row_tests_maybe_pairs(NULL)")?;
+        assert_eq!(count, Some(0));
+        let count = Spi::get_one::<i64>("SELECT count(*) FROM table_row_tests_maybe_pairs('a b')")?;
+        assert_eq!(count, Some(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_nullable_and_composite_columns() -> Result<(), pgx::spi::Error> {
+        let result = Spi::get_one::<String>(
+            "SELECT pg_get_function_result('table_row_tests_pups'::regproc)",
+        )?;
+        assert_eq!(result, Some("TABLE(name text, nickname text, dog dog, litter dog[])".into()));
+
+        let rows = Spi::get_one::<String>(
+            "SELECT string_agg(
+                    name || ':' || coalesce(nickname, 'NULL') || ':' || (dog).scritches,
+                    ',' ORDER BY name
+                )
+                FROM table_row_tests_pups(ARRAY['Brandy', 'Nami', 'Nemmie'])",
+        )?;
+        assert_eq!(rows, Some("Brandy:NULL:6,Nami:NULL:4,Nemmie:Nemm:6".into()));
+        Ok(())
+    }
+}
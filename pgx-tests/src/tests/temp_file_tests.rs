@@ -0,0 +1,188 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::temp_file::PgTempFile;
+    use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+    /// How many temp files this backend has in the default tablespace
+    fn own_temp_files() -> usize {
+        let (data_dir, pid) = unsafe {
+            (std::ffi::CStr::from_ptr(pg_sys::DataDir).to_str().unwrap(), pg_sys::MyProcPid)
+        };
+        let prefix = format!("pgsql_tmp{}.", pid);
+        match std::fs::read_dir(format!("{}/base/pgsql_tmp", data_dir)) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+                .count(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Writes more than `temp_file_limit` allows to a new temp file
+    fn spill_too_much() {
+        Spi::run("SET LOCAL temp_file_limit = '1MB'").unwrap();
+        let mut file = unsafe { PgTempFile::new() };
+        for _ in 0..512 {
+            file.write_all(&[42; 8192]).unwrap();
+        }
+    }
+
+    #[pg_test]
+    fn test_read_write_seek() -> std::io::Result<()> {
+        let mut file = unsafe { PgTempFile::new() };
+        assert!(file.is_empty());
+        file.write_all(b"hello, world")?;
+        assert_eq!(file.len(), 12);
+
+        file.rewind()?;
+        let mut hello = [0; 5];
+        file.read_exact(&mut hello)?;
+        assert_eq!(&hello, b"hello");
+
+        file.seek(SeekFrom::End(-5))?;
+        file.write_all(b"there")?;
+        assert_eq!(file.len(), 12);
+        assert_eq!(file.stream_position()?, 12);
+
+        file.seek(SeekFrom::Current(-12))?;
+        let mut everything = String::new();
+        file.read_to_string(&mut everything)?;
+        assert_eq!(everything, "hello, there");
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_seek_out_of_bounds() {
+        let mut file = unsafe { PgTempFile::new() };
+        file.write_all(b"short").unwrap();
+        assert_eq!(file.seek(SeekFrom::Start(6)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(file.seek(SeekFrom::End(-6)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(file.stream_position().unwrap(), 5);
+    }
+
+    #[pg_test]
+    fn test_spill_many_blocks() -> std::io::Result<()> {
+        let mut file = unsafe { PgTempFile::new() };
+        for i in 0..10_000u32 {
+            file.write_all(&i.to_ne_bytes())?;
+        }
+        file.seek(SeekFrom::Start(4 * 9_000))?;
+        let mut i = [0; 4];
+        file.read_exact(&mut i)?;
+        assert_eq!(u32::from_ne_bytes(i), 9_000);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_values() -> std::io::Result<()> {
+        let mut file = unsafe { PgTempFile::new() };
+        file.write_value(42i32)?;
+        file.write_value("forty-two")?;
+        file.write_value(Option::<i64>::None)?;
+        file.write_value(vec![4, 2])?;
+        file.rewind()?;
+
+        assert_eq!(file.read_value::<i32>()?, Some(Some(42)));
+        assert_eq!(file.read_value::<String>()?, Some(Some(String::from("forty-two"))));
+        assert_eq!(file.read_value::<i64>()?, Some(None));
+        assert_eq!(file.read_value::<Vec<i32>>()?, Some(Some(vec![4, 2])));
+        assert_eq!(file.read_value::<i32>()?, None);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_datums() -> std::io::Result<()> {
+        let mut file = unsafe { PgTempFile::new() };
+        let text = "a good dog".into_datum();
+        unsafe {
+            file.write_datum(pg_sys::TEXTOID, text)?;
+            file.write_datum(pg_sys::BOOLOID, None)?;
+        }
+        file.rewind()?;
+
+        let (typoid, datum) = file.read_datum()?.unwrap();
+        assert_eq!(typoid, pg_sys::TEXTOID);
+        assert_eq!(
+            unsafe { String::from_datum(datum.unwrap(), false) }.as_deref(),
+            Some("a good dog")
+        );
+        assert_eq!(file.read_datum()?, Some((pg_sys::BOOLOID, None)));
+        assert_eq!(file.read_datum()?, None);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_read_value_of_the_wrong_type() {
+        let mut file = unsafe { PgTempFile::new() };
+        file.write_value(42i32).unwrap();
+        file.rewind().unwrap();
+        assert_eq!(file.read_value::<String>().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[pg_test]
+    fn test_truncated_datum() {
+        let mut file = unsafe { PgTempFile::new() };
+        file.write_value("forty-two").unwrap();
+        file.seek(SeekFrom::End(-1)).unwrap();
+        let len = file.stream_position().unwrap();
+        file.rewind().unwrap();
+
+        let mut truncated = unsafe { PgTempFile::new() };
+        let mut bytes = vec![0; len as usize];
+        file.read_exact(&mut bytes).unwrap();
+        truncated.write_all(&bytes).unwrap();
+        truncated.rewind().unwrap();
+        assert_eq!(truncated.read_datum().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[pg_test]
+    fn test_drop_deletes_the_file() {
+        let before = own_temp_files();
+        let mut file = unsafe { PgTempFile::new() };
+        file.write_all(&[42; 3 * 8192]).unwrap();
+        assert_eq!(own_temp_files(), before + 1);
+        drop(file);
+        assert_eq!(own_temp_files(), before);
+    }
+
+    #[pg_test(error = "temporary file size exceeds temp_file_limit (1024kB)")]
+    fn test_temp_file_limit() {
+        spill_too_much()
+    }
+
+    #[pg_test]
+    fn test_error_mid_write_deletes_the_file() -> Result<(), pgx::spi::Error> {
+        let before = own_temp_files();
+        let spilled =
+            Spi::connect(|mut client| client.with_savepoint("spill", |_| spill_too_much()));
+        match spilled {
+            Err(e) => match e {
+                pg_sys::panic::CaughtError::PostgresError(ereport) => assert_eq!(
+                    ereport.message(),
+                    "temporary file size exceeds temp_file_limit (1024kB)"
+                ),
+                e => panic!("spilling failed with something other than an ERROR: {:?}", e),
+            },
+            Ok(()) => panic!("spilling more than temp_file_limit succeeded"),
+        }
+        assert_eq!(own_temp_files(), before);
+
+        // and the limit was rolled back with the savepoint
+        assert_eq!(Spi::get_one::<String>("SHOW temp_file_limit")?.as_deref(), Some("-1"));
+        Ok(())
+    }
+}
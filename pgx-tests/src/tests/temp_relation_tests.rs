@@ -0,0 +1,108 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::rel::OnCommit;
+
+    fn scratch(name: &str, on_commit: OnCommit) -> Result<PgRelation, pgx::spi::Error> {
+        let scratch = PgRelation::create_temp(
+            name,
+            &[
+                ("id", PgOid::from(pg_sys::INT8OID), -1),
+                ("Label", PgOid::from(pg_sys::VARCHAROID), 8),
+            ],
+            on_commit,
+        )?;
+        scratch.insert_rows(vec![
+            vec![1i64.into_datum(), "one".into_datum()],
+            vec![2i64.into_datum(), None],
+        ])?;
+        Ok(scratch)
+    }
+
+    /// Runs the `ON COMMIT` actions, as committing would.  The test's transaction is aborted, so
+    /// this is the only way to see them happen
+    fn run_on_commit_actions() {
+        unsafe { pg_sys::PreCommit_on_commit_actions() }
+    }
+
+    #[pg_test]
+    fn test_create_temp() -> Result<(), pgx::spi::Error> {
+        let scratch = scratch("scratch", OnCommit::PreserveRows)?;
+        assert_eq!(scratch.name(), "scratch");
+        assert!(scratch.is_table());
+        assert_eq!(scratch.tuple_desc().len(), 2);
+        assert_eq!(
+            Spi::get_one::<&str>(
+                r#"SELECT format_type(atttypid, atttypmod) FROM pg_attribute WHERE attrelid = 'pg_temp.scratch'::regclass AND attname = 'Label'"#
+            )?,
+            Some("character varying(8)")
+        );
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM pg_temp.scratch WHERE \"Label\" IS NULL")?,
+            Some(1)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_create_temp_on_commit_drop() -> Result<(), pgx::spi::Error> {
+        drop(scratch("scratch_drop", OnCommit::Drop)?);
+        assert_eq!(
+            Spi::get_one::<bool>("SELECT to_regclass('pg_temp.scratch_drop') IS NOT NULL")?,
+            Some(true)
+        );
+        run_on_commit_actions();
+        assert_eq!(
+            Spi::get_one::<bool>("SELECT to_regclass('pg_temp.scratch_drop') IS NULL")?,
+            Some(true)
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_create_temp_on_commit_delete_rows() -> Result<(), pgx::spi::Error> {
+        drop(scratch("scratch_delete", OnCommit::DeleteRows)?);
+        run_on_commit_actions();
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM pg_temp.scratch_delete")?, Some(0));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_create_temp_on_commit_preserve_rows() -> Result<(), pgx::spi::Error> {
+        drop(scratch("scratch_preserve", OnCommit::PreserveRows)?);
+        run_on_commit_actions();
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM pg_temp.scratch_preserve")?, Some(2));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_create_index() -> Result<(), pgx::spi::Error> {
+        let scratch = scratch("scratch_indexed", OnCommit::Drop)?;
+        let index = scratch.create_index("scratch_indexed_id_idx", &["id"], true)?;
+        assert!(index.is_index());
+        assert_eq!(index.heap_relation().map(|heap| heap.oid()), Some(scratch.oid()));
+        assert_eq!(index.namespace_oid(), scratch.namespace_oid());
+        Ok(())
+    }
+
+    #[pg_test(error = "duplicate key value violates unique constraint \"scratch_unique_id_idx\"")]
+    fn test_create_unique_index() -> Result<(), pgx::spi::Error> {
+        let scratch = scratch("scratch_unique", OnCommit::Drop)?;
+        scratch.create_index("scratch_unique_id_idx", &["id"], true)?;
+        scratch.insert_rows(vec![vec![1i64.into_datum(), None]])?;
+        Ok(())
+    }
+}
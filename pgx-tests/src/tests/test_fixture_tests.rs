@@ -0,0 +1,85 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+    use pgx::prelude::*;
+    use pgx::testing::{TestDb, TestResult};
+
+    #[derive(Debug, thiserror::Error)]
+    enum CountError {
+        #[error("expected {expected} rows, found {found}")]
+        WrongCount { expected: i64, found: i64 },
+    }
+
+    fn check_count(db: &TestDb, expected: i64) -> TestResult {
+        let found = db.query_one::<i64>("SELECT count(*) FROM t")?.unwrap_or_default();
+        if found != expected {
+            Err(CountError::WrongCount { expected, found })?;
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_fixture_schema(db: &TestDb) -> TestResult {
+        assert_eq!(db.schema(), "pgx_test_test_fixture_schema");
+        assert_eq!(
+            db.query_one::<String>("SELECT current_database()::text")?.as_deref(),
+            Some(db.database())
+        );
+        assert_eq!(
+            db.query_one::<String>("SELECT current_schema()::text")?.as_deref(),
+            Some(db.schema())
+        );
+
+        db.execute("CREATE TABLE t AS SELECT generate_series(1, 3) AS x")?;
+        let schema = db.query_one::<String>(
+            "SELECT relnamespace::regnamespace::text FROM pg_class WHERE oid = 't'::regclass",
+        )?;
+        assert_eq!(schema.as_deref(), Some(db.schema()));
+        assert_eq!(db.query::<i32>("SELECT x FROM t ORDER BY x")?, vec![Some(1), Some(2), Some(3)]);
+        check_count(db, 3)
+    }
+
+    #[pg_test]
+    fn test_fixture_without_result(db: &TestDb) {
+        db.execute("CREATE TABLE t (x int)").unwrap();
+        assert_eq!(db.query::<i32>("SELECT x FROM t").unwrap(), vec![]);
+    }
+
+    #[pg_test]
+    fn test_fixture_schema_is_dropped() -> TestResult {
+        TestDb::with("dropped", |db| db.execute("CREATE TABLE t (x int)"))?;
+        let exists = Spi::get_one::<bool>(
+            "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = 'pgx_test_dropped')",
+        )?;
+        assert_eq!(exists, Some(false));
+        Ok(())
+    }
+
+    #[pg_test(error = "expected 2 rows, found 1")]
+    fn test_fixture_user_error(db: &TestDb) -> TestResult {
+        db.execute("CREATE TABLE t AS SELECT 1 AS x")?;
+        check_count(db, 2)
+    }
+
+    #[pg_test(error = "SpiTupleTable positioned before the start or after the end")]
+    fn test_spi_error_with_question_mark() -> TestResult {
+        Spi::get_one::<i32>("SELECT 1 WHERE false")?;
+        Ok(())
+    }
+
+    #[pg_test(error = "a custom error")]
+    fn test_display_error() -> Result<(), String> {
+        Err("a custom error".to_string())
+    }
+}
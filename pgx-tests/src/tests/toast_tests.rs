@@ -0,0 +1,111 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PostgresType)]
+#[pgx(storage = "external", alignment = "double")]
+pub struct ToastTestsExternal {
+    data: String,
+}
+
+#[derive(Serialize, Deserialize, PostgresType)]
+pub struct ToastTestsExtended {
+    data: String,
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::toast::{self, Compression};
+
+    const MEGABYTE: i32 = 1024 * 1024;
+
+    #[pg_test]
+    fn test_create_type_options() -> Result<(), pgx::spi::Error> {
+        let storage = |name: &str| {
+            Spi::get_two::<String, String>(&format!(
+                "SELECT typstorage::text, typalign::text FROM pg_type WHERE oid = '{}'::regtype",
+                name
+            ))
+        };
+        assert_eq!(storage("toasttestsexternal")?, (Some("e".into()), Some("d".into())));
+        assert_eq!(storage("toasttestsextended")?, (Some("x".into()), Some("i".into())));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_external_storage_is_uncompressed() -> Result<(), pgx::spi::Error> {
+        Spi::run("CREATE TABLE toast_tests_types (e ToastTestsExternal, x ToastTestsExtended)")?;
+        Spi::run(
+            "INSERT INTO toast_tests_types
+             SELECT value::ToastTestsExternal, value::ToastTestsExtended
+               FROM (SELECT '{\"data\":\"' || repeat('x', 1024 * 1024) || '\"}' AS value) v",
+        )?;
+        let (external, extended) = Spi::get_two::<i32, i32>(
+            "SELECT pg_column_size(e), pg_column_size(x) FROM toast_tests_types",
+        )?;
+        assert!(external.unwrap() > MEGABYTE);
+        assert!(extended.unwrap() < MEGABYTE);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_compress_varlena() -> Result<(), pgx::spi::Error> {
+        // an `external` column isn't compressed when it's stored, unless it already was
+        Spi::run("CREATE TABLE toast_tests_text (id int, t text)")?;
+        Spi::run("ALTER TABLE toast_tests_text ALTER COLUMN t SET STORAGE EXTERNAL")?;
+
+        let text = pgx::rust_str_to_text_p(&"x".repeat(MEGABYTE as usize));
+        let compressed = unsafe {
+            assert!(!toast::is_compressed(text.as_ptr()));
+            let compressed = toast::compress_varlena(text.as_ptr(), Compression::Pglz).unwrap();
+            assert!(toast::is_compressed(compressed));
+            assert!(pgx::varsize_any(compressed) < MEGABYTE as usize);
+
+            // recompressing starts from the decompressed value
+            let recompressed = toast::compress_varlena(compressed, Compression::Pglz).unwrap();
+            assert_eq!(pgx::varsize_any(recompressed), pgx::varsize_any(compressed));
+            compressed
+        };
+
+        for (id, varlena) in [(1, text.as_ptr()), (2, compressed)] {
+            Spi::run_with_args(
+                "INSERT INTO toast_tests_text VALUES ($1, $2)",
+                Some(vec![
+                    (PgBuiltInOids::INT4OID.oid(), id.into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), Some(pg_sys::Datum::from(varlena))),
+                ]),
+            )?;
+        }
+        let size = |id: i32| {
+            Spi::get_two::<i32, i32>(&format!(
+                "SELECT pg_column_size(t), length(t) FROM toast_tests_text WHERE id = {}",
+                id
+            ))
+        };
+        let (uncompressed_size, uncompressed_length) = size(1)?;
+        let (compressed_size, compressed_length) = size(2)?;
+        assert!(uncompressed_size.unwrap() >= MEGABYTE);
+        assert!(compressed_size.unwrap() < MEGABYTE);
+        assert_eq!(uncompressed_length, Some(MEGABYTE));
+        assert_eq!(compressed_length, Some(MEGABYTE));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_too_small_to_compress() {
+        let text = pgx::rust_str_to_text_p("tiny");
+        assert_eq!(unsafe { toast::compress_varlena(text.as_ptr(), Compression::Default) }, None);
+    }
+}
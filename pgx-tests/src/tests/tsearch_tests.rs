@@ -0,0 +1,162 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+use pgx::{Position, TsQuery, TsVector};
+
+#[pg_extern]
+fn tsearch_tests_words(vector: TsVector) -> Vec<String> {
+    vector.iter().map(|lexeme| lexeme.word().to_string()).collect()
+}
+
+#[pg_extern]
+fn tsearch_tests_every_other(vector: TsVector) -> TsVector {
+    let lexemes = vector
+        .iter()
+        .map(|lexeme| (lexeme.word(), lexeme.positions().iter().step_by(2).copied().collect()))
+        .collect::<Vec<(&str, Vec<Position>)>>();
+    let lexemes =
+        lexemes.iter().map(|(word, positions)| (*word, positions.as_slice())).collect::<Vec<_>>();
+    TsVector::from_lexemes(&lexemes)
+}
+
+#[pg_extern]
+fn tsearch_tests_matches(vector: TsVector, query: TsQuery) -> bool {
+    query.matches(&vector)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{Position, TsQuery, TsVector, Weight};
+
+    const DOCUMENT: &str = "The fat cats ate the fat rats, and then the cats slept";
+
+    #[pg_test]
+    fn test_to_tsvector() -> Result<(), pgx::spi::Error> {
+        let vector =
+            Spi::get_one::<TsVector>(&format!("SELECT to_tsvector('english', '{}')", DOCUMENT))?
+                .expect("to_tsvector was null");
+
+        let lexemes = vector
+            .iter()
+            .map(|lexeme| {
+                let positions = lexeme.positions().iter().map(|p| p.pos()).collect::<Vec<_>>();
+                (lexeme.word(), positions)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lexemes,
+            vec![
+                ("ate", vec![4]),
+                ("cat", vec![3, 11]),
+                ("fat", vec![2, 6]),
+                ("rat", vec![7]),
+                ("slept", vec![12]),
+            ]
+        );
+        assert!(vector
+            .iter()
+            .flat_map(|lexeme| lexeme.positions())
+            .all(|p| p.weight() == Weight::D));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_from_lexemes_matches_to_tsvector() -> Result<(), pgx::spi::Error> {
+        let vector = TsVector::from_lexemes(&[
+            ("rat", &[Position::new(7)]),
+            ("cat", &[Position::new(11), Position::new(3)]),
+            ("fat", &[Position::new(2)]),
+            ("fat", &[Position::new(6)]),
+            ("ate", &[Position::new(4)]),
+            ("slept", &[Position::new(12)]),
+        ]);
+        let equal = Spi::get_one_with_args::<bool>(
+            &format!("SELECT $1 = to_tsvector('english', '{}')", DOCUMENT),
+            vec![(PgBuiltInOids::TSVECTOROID.oid(), vector.into_datum())],
+        )?;
+        assert_eq!(equal, Some(true));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_weights_and_text() -> Result<(), pgx::spi::Error> {
+        let vector = TsVector::from_lexemes(&[
+            ("fat", &[Position::with_weight(2, Weight::B), Position::new(2)]),
+            ("cat", &[Position::with_weight(3, Weight::A)]),
+            ("rat", &[]),
+        ]);
+        assert_eq!(vector.to_string(), "'cat':3A 'fat':2B 'rat'");
+
+        let text = Spi::get_one_with_args::<String>(
+            "SELECT $1::text",
+            vec![(PgBuiltInOids::TSVECTOROID.oid(), vector.clone().into_datum())],
+        )?;
+        assert_eq!(text.as_deref(), Some("'cat':3A 'fat':2B 'rat'"));
+
+        let parsed = "'fat':2B 'cat':3A rat".parse::<TsVector>().unwrap();
+        assert_eq!(parsed, vector);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_tsvector_roundtrip() -> Result<(), pgx::spi::Error> {
+        let vector = Spi::get_one::<TsVector>(&format!(
+            "SELECT tsearch_tests_every_other(to_tsvector('english', '{}'))",
+            DOCUMENT
+        ))?
+        .expect("tsearch_tests_every_other was null");
+        assert_eq!(vector.to_string(), "'ate':4 'cat':3 'fat':2 'rat':7 'slept':12");
+
+        let words = Spi::get_one::<Vec<String>>("SELECT tsearch_tests_words('b:1 a c:2,3')")?;
+        assert_eq!(words, Some(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_matches_like_sql() -> Result<(), pgx::spi::Error> {
+        let vector =
+            Spi::get_one::<TsVector>(&format!("SELECT to_tsvector('english', '{}')", DOCUMENT))?
+                .expect("to_tsvector was null");
+
+        for query in ["fat & rat", "fat & dog", "cat <-> slept", "fat <-> rat", "!dog", "sleep:*"] {
+            let parsed = query.parse::<TsQuery>().unwrap();
+            let expected = Spi::get_one::<bool>(&format!(
+                "SELECT to_tsvector('english', '{}') @@ '{}'::tsquery",
+                DOCUMENT, query
+            ))?;
+            assert_eq!(Some(parsed.matches(&vector)), expected, "{}", query);
+
+            let from_sql = Spi::get_one::<bool>(&format!(
+                "SELECT tsearch_tests_matches(to_tsvector('english', '{}'), to_tsquery('english', '{}'))",
+                DOCUMENT, query
+            ))?;
+            assert_eq!(from_sql, expected, "{}", query);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_tsquery_text() -> Result<(), pgx::spi::Error> {
+        let query = Spi::get_one::<TsQuery>("SELECT to_tsquery('english', 'Fat & (Rats | Cats)')")?
+            .expect("to_tsquery was null");
+        assert_eq!(query.to_string(), "'fat' & ( 'rat' | 'cat' )");
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_syntax_errors() {
+        assert!("fat &".parse::<TsQuery>().is_err());
+        assert!("'fat".parse::<TsVector>().is_err());
+    }
+}
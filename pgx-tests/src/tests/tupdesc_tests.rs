@@ -0,0 +1,119 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use pgx::prelude::*;
+use pgx::TupleDescBuilder;
+
+fn id_and_name() -> pgx::PgTupleDesc<'static> {
+    TupleDescBuilder::new()
+        .add("id", PgBuiltInOids::INT4OID)
+        .add_with_typmod("name", PgBuiltInOids::VARCHAROID, 10 + 4)
+        .bless()
+        .unwrap()
+}
+
+#[pg_extern(sql = r#"
+    CREATE FUNCTION "tupdesc_builder_record"() RETURNS record
+    STRICT
+    LANGUAGE c /* Rust */
+    AS '@MODULE_PATHNAME@', '@FUNCTION_NAME@';
+"#)]
+fn tupdesc_builder_record() -> pgx::composite_type!("record") {
+    PgHeapTuple::from_datums(id_and_name(), [42.into_datum(), "Nami".into_datum()]).unwrap()
+}
+
+#[pg_extern(sql = r#"
+    CREATE FUNCTION "tupdesc_builder_records"() RETURNS SETOF record
+    STRICT
+    LANGUAGE c /* Rust */
+    AS '@MODULE_PATHNAME@', '@FUNCTION_NAME@';
+"#)]
+fn tupdesc_builder_records() -> SetOfIterator<'static, pgx::composite_type!("record")> {
+    SetOfIterator::new(
+        (1..=3).map(|id| PgHeapTuple::from_datums(id_and_name(), [id.into_datum(), None]).unwrap()),
+    )
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::{TupleDescBuilder, TupleDescBuilderError};
+
+    #[pg_test]
+    fn test_bless() {
+        let tupdesc = super::id_and_name();
+        assert_eq!(tupdesc.oid(), pg_sys::RECORDOID);
+        assert!(tupdesc.typmod() >= 0);
+        assert_eq!(tupdesc.len(), 2);
+        assert_eq!(tupdesc.get(0).unwrap().atttypid, pg_sys::INT4OID);
+        assert_eq!(tupdesc.get(1).unwrap().atttypmod, 14);
+
+        // the same shape is always given the same typmod
+        assert_eq!(super::id_and_name().typmod(), tupdesc.typmod());
+    }
+
+    #[pg_test]
+    fn test_returns_record() -> Result<(), pgx::spi::Error> {
+        let (id, name) = Spi::get_two::<i32, String>(
+            "SELECT * FROM tupdesc_builder_record() AS (id int, name varchar(10))",
+        )?;
+        assert_eq!(id, Some(42));
+        assert_eq!(name.as_deref(), Some("Nami"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_returns_setof_record() -> Result<(), pgx::spi::Error> {
+        let (sum, names) = Spi::get_two::<i64, i64>(
+            "SELECT sum(id), count(name) FROM tupdesc_builder_records() AS (id int, name varchar(10))",
+        )?;
+        assert_eq!(sum, Some(6));
+        assert_eq!(names, Some(0));
+        Ok(())
+    }
+
+    #[pg_test(error = "function return row and query-specified return row do not match")]
+    fn test_returns_record_mismatch() -> Result<(), pgx::spi::Error> {
+        Spi::run("SELECT * FROM tupdesc_builder_record() AS (id int, name int)")
+    }
+
+    #[pg_test]
+    fn test_duplicate_column_name() {
+        let result = TupleDescBuilder::new()
+            .add("a", PgBuiltInOids::INT4OID)
+            .add("a", PgBuiltInOids::TEXTOID)
+            .bless();
+        assert_eq!(result.err(), Some(TupleDescBuilderError::DuplicateColumnName("a".into())));
+    }
+
+    #[pg_test]
+    fn test_invalid_column_names() {
+        for name in ["", "nul\0byte"] {
+            let result = TupleDescBuilder::new().add(name, PgBuiltInOids::INT4OID).bless();
+            assert_eq!(result.err(), Some(TupleDescBuilderError::InvalidColumnName(name.into())));
+        }
+
+        let long = "x".repeat(64);
+        let result = TupleDescBuilder::new().add(&long, PgBuiltInOids::INT4OID).bless();
+        assert_eq!(result.err(), Some(TupleDescBuilderError::ColumnNameTooLong(long)));
+    }
+
+    #[pg_test]
+    fn test_unknown_type() {
+        let result = TupleDescBuilder::new().add("a", pg_sys::InvalidOid).bless();
+        assert_eq!(
+            result.err(),
+            Some(TupleDescBuilderError::UnknownType("a".into(), pg_sys::InvalidOid))
+        );
+    }
+}
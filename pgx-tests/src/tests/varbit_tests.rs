@@ -0,0 +1,95 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::prelude::*;
+    use pgx::VarBit;
+
+    #[pg_extern]
+    fn varbit_tests_flip(bits: VarBit) -> VarBit {
+        bits.iter().map(|bit| !bit).collect()
+    }
+
+    #[pg_test]
+    fn test_from_sql_literals() -> Result<(), pgx::spi::Error> {
+        let bits = Spi::get_one::<VarBit>("SELECT B'1011001110'::varbit")?.unwrap();
+        assert_eq!(bits.len(), 10);
+        assert_eq!(bits.to_string(), "1011001110");
+        assert_eq!(bits.to_u128(), Some(0b1011001110));
+        assert_eq!(bits.get(2), Some(true));
+        assert_eq!(bits.get(10), None);
+
+        let fixed = Spi::get_one::<VarBit>("SELECT 5::bit(3)")?.unwrap();
+        assert_eq!(fixed.to_vec(), vec![true, false, true]);
+
+        let empty = Spi::get_one::<VarBit>("SELECT B''::varbit")?.unwrap();
+        assert!(empty.is_empty());
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_round_trip() -> Result<(), pgx::spi::Error> {
+        for literal in ["", "0", "1", "10110011", "101100111", &"10".repeat(100)] {
+            let flipped = Spi::get_one::<String>(&format!(
+                "SELECT tests.varbit_tests_flip(B'{}')::text",
+                literal
+            ))?;
+            let expected =
+                literal.chars().map(|c| if c == '0' { '1' } else { '0' }).collect::<String>();
+            assert_eq!(flipped, Some(expected));
+
+            let bits = literal.parse::<VarBit>().unwrap();
+            let equal = Spi::get_one_with_args::<bool>(
+                &format!("SELECT $1 = B'{}'", literal),
+                vec![(PgBuiltInOids::VARBITOID.oid(), bits.clone().into_datum())],
+            )?;
+            assert_eq!(equal, Some(true), "{}", literal);
+            assert_eq!(VarBit::from(bits.to_vec()), bits);
+        }
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_u128() -> Result<(), pgx::spi::Error> {
+        let bits = VarBit::from_u128(u128::MAX - 1, 128);
+        assert_eq!(bits.to_u128(), Some(u128::MAX - 1));
+        assert_eq!(VarBit::from_u128(1, 3).to_string(), "001");
+        assert_eq!(VarBit::zeros(129).to_u128(), None);
+
+        let from_sql = Spi::get_one::<VarBit>("SELECT 1234::bit(16)")?.unwrap();
+        assert_eq!(from_sql.to_u128(), Some(1234));
+        assert_eq!(from_sql, VarBit::from_u128(1234, 16));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_set_and_push() {
+        let mut bits = VarBit::zeros(9);
+        bits.set(8, true);
+        bits.set(0, true);
+        bits.set(0, false);
+        bits.push(true);
+        assert_eq!(bits.to_string(), "0000000011");
+        assert!("10201".parse::<VarBit>().is_err());
+    }
+
+    #[pg_test]
+    fn test_signature() -> Result<(), pgx::spi::Error> {
+        let signature = Spi::get_one::<String>(
+            "SELECT 'tests.varbit_tests_flip'::regproc::oid::regprocedure::text",
+        )?;
+        assert_eq!(signature.as_deref(), Some("tests.varbit_tests_flip(bit varying)"));
+        Ok(())
+    }
+}
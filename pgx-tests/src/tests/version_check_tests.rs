@@ -0,0 +1,128 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern(check_version)]
+fn version_check_tests_checked() -> bool {
+    true
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgx::pg_schema]
+mod tests {
+    #[allow(unused_imports)]
+    use crate as pgx_tests;
+
+    use pgx::pg_sys::panic::CaughtError;
+    use pgx::prelude::*;
+    use pgx::version_check::{self, VersionMismatch};
+
+    const LIBRARY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+    fn install_version(version: &str) -> Result<(), pgx::spi::Error> {
+        Spi::run(&format!(
+            "UPDATE pg_catalog.pg_extension SET extversion = '{}' WHERE extname = 'pgx_tests'",
+            version
+        ))
+    }
+
+    /// The message and hint of the `ERROR` the check raises
+    fn check_error() -> (String, Option<String>) {
+        PgTryBuilder::new(|| {
+            version_check::check_extension_version();
+            panic!("the versions matched");
+        })
+        .catch_others(|e| match e {
+            CaughtError::ErrorReport(report) => {
+                (report.message().to_string(), report.hint().map(String::from))
+            }
+            e => e.rethrow(),
+        })
+        .execute()
+    }
+
+    #[pg_test]
+    fn test_versions_match() -> Result<(), pgx::spi::Error> {
+        assert_eq!(version_check::extension_name(), Some("pgx_tests"));
+        assert_eq!(version_check::library_version(), Some(LIBRARY_VERSION));
+        assert_eq!(version_check::installed_version().as_deref(), Some(LIBRARY_VERSION));
+        assert_eq!(version_check::compare_versions(), Ok(()));
+        assert_eq!(Spi::get_one::<bool>("SELECT version_check_tests_checked()")?, Some(true));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_sql_older_than_library() -> Result<(), pgx::spi::Error> {
+        install_version("0.0.1")?;
+        assert_eq!(
+            version_check::compare_versions(),
+            Err(VersionMismatch {
+                extension: "pgx_tests".into(),
+                installed: "0.0.1".into(),
+                library: LIBRARY_VERSION.into(),
+            })
+        );
+        let (message, hint) = check_error();
+        assert_eq!(
+            message,
+            format!("extension pgx_tests SQL is 0.0.1 but library is {}", LIBRARY_VERSION)
+        );
+        assert_eq!(hint.as_deref(), Some("run `ALTER EXTENSION pgx_tests UPDATE`"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_library_older_than_sql() -> Result<(), pgx::spi::Error> {
+        install_version("999.0.0")?;
+        let (message, hint) = check_error();
+        assert_eq!(
+            message,
+            format!("extension pgx_tests SQL is 999.0.0 but library is {}", LIBRARY_VERSION)
+        );
+        assert_eq!(
+            hint.as_deref(),
+            Some("the library is older than the installed SQL: install version 999.0.0 of the library, and reconnect")
+        );
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_check_version_function_raises() -> Result<(), pgx::spi::Error> {
+        install_version("0.0.1")?;
+        let raised = PgTryBuilder::new(|| {
+            let _ = Spi::get_one::<bool>("SELECT version_check_tests_checked()");
+            false
+        })
+        .catch_others(|_| true)
+        .execute();
+        assert!(raised);
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_check_version_guc() -> Result<(), pgx::spi::Error> {
+        assert_eq!(Spi::get_one::<String>("SHOW pgx_tests.check_version")?.as_deref(), Some("on"));
+        install_version("0.0.1")?;
+        Spi::run("SET LOCAL pgx_tests.check_version = off")?;
+        assert_eq!(Spi::get_one::<bool>("SELECT version_check_tests_checked()")?, Some(true));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn test_unchecked_functions_dont_check() -> Result<(), pgx::spi::Error> {
+        install_version("0.0.1")?;
+        assert_eq!(Spi::get_one::<i32>("SELECT tests.version_check_tests_add_one(1)")?, Some(2));
+        Ok(())
+    }
+
+    #[pg_extern]
+    fn version_check_tests_add_one(i: i32) -> i32 {
+        i + 1
+    }
+}
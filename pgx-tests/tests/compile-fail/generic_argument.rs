@@ -0,0 +1,16 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern]
+fn generic_argument<T: Into<i64>>(value: T) -> i64 {
+    value.into()
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern]
+fn nested_name_argument(owner: Option<name!(owner, i32)>) -> i32 {
+    owner.unwrap_or_default()
+}
+
+#[pg_extern]
+fn nested_name_return() -> TableIterator<'static, (name!(id, Option<name!(value, i32)>),)> {
+    TableIterator::new(std::iter::empty())
+}
+
+fn main() {}
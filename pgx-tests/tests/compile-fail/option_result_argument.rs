@@ -0,0 +1,16 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use pgx::prelude::*;
+
+#[pg_extern]
+fn option_result_argument(value: Option<Result<i32, String>>) -> i32 {
+    value.and_then(Result::ok).unwrap_or_default()
+}
+
+fn main() {}
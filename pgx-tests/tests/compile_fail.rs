@@ -0,0 +1,14 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+/// Each file in `tests/compile-fail/` mustn't compile, with the errors in its `.stderr` file
+#[test]
+fn compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/compile-fail/*.rs");
+}
@@ -0,0 +1,25 @@
+-- each column counts the calls since its `PgBackendLocal` was last reset
+SELECT * FROM backend_local_tests_next();
+SELECT * FROM backend_local_tests_next();
+-- only `DISCARD ALL` resets the one that asked for it
+DISCARD PLANS;
+SELECT * FROM backend_local_tests_next();
+DISCARD ALL;
+SELECT * FROM backend_local_tests_next();
+-- an aborted transaction resets the one that asked for it
+BEGIN;
+SELECT * FROM backend_local_tests_next();
+SELECT 1 / 0;
+ROLLBACK;
+SELECT * FROM backend_local_tests_next();
+-- a committed one doesn't
+BEGIN;
+SELECT * FROM backend_local_tests_next();
+COMMIT;
+SELECT * FROM backend_local_tests_next();
+-- `DISCARD ALL` is also a `RESET ALL`, and both are reported to the callbacks
+SELECT backend_local_tests_events();
+DISCARD ALL;
+SELECT backend_local_tests_events();
+RESET ALL;
+SELECT backend_local_tests_events();
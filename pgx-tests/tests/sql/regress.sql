@@ -0,0 +1,5 @@
+-- the SQL regression tests are run in a database of their own
+SELECT current_database();
+-- pgx: unordered
+SELECT x FROM (VALUES (2), (1), (3)) AS t(x);
+SELECT 1 / 0;
@@ -0,0 +1,377 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Numerically stable accumulators for writing [aggregates](crate::aggregate) over `float8`s.
+//!
+//! Adding up many floating point numbers one by one loses precision, and computing a variance from
+//! the sum of squares loses most of it when the values are large and close together.  These keep
+//! their error small, the way Postgres' own `float8` aggregates do:
+//!
+//! * [`KahanSum`] sums values with compensated (Kahan-Babuška-Neumaier) summation
+//! * [`Welford`] keeps the count, mean and variance of values, as `avg()`, `var_samp()`,
+//!   `stddev_pop()` and the like need
+//! * [`Covariance`] does the same for pairs of values, as `covar_pop()`, `corr()` and `regr_slope()`
+//!   need
+//!
+//! Each has a `push()` for an aggregate's `state` function and a `merge()` for its `combine`
+//! function, which merges what two parallel workers accumulated.  They implement `serde`'s
+//! `Serialize` and `Deserialize`, so they can be an aggregate's state as they are, or be kept in an
+//! [`AggregateInternalState`](crate::AggregateInternalState) and sent between workers with its
+//! `serialize()` and `deserialize()`.
+//!
+//! As with Postgres' aggregates, a `NaN` makes every result `NaN`, and an infinite value makes the
+//! results which depend on it infinite or `NaN`.
+//!
+//! ## Example
+//!
+//! A parallel-safe `stddev_samp()`:
+//!
+//! ```rust
+//! use pgx::agg::Welford;
+//! use pgx::prelude::*;
+//! use pgx::{Aggregate, AggregateInternalState, ParallelOption};
+//!
+//! pub struct StddevSamp;
+//!
+//! #[pg_aggregate]
+//! impl Aggregate for StddevSamp {
+//!     const PARALLEL: Option<ParallelOption> = Some(ParallelOption::Safe);
+//!     type Args = f64;
+//!     type State = AggregateInternalState<Welford>;
+//!     type Finalize = Option<f64>;
+//!
+//!     fn state(
+//!         mut current: Self::State,
+//!         arg: Self::Args,
+//!         fcinfo: pg_sys::FunctionCallInfo,
+//!     ) -> Self::State {
+//!         current.get_or_insert_default(fcinfo).push(arg);
+//!         current
+//!     }
+//!
+//!     fn combine(
+//!         current: Self::State,
+//!         other: Self::State,
+//!         fcinfo: pg_sys::FunctionCallInfo,
+//!     ) -> Self::State {
+//!         current.combine(other, fcinfo, |current, other| current.merge(&other))
+//!     }
+//!
+//!     fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+//!         current.serialize(fcinfo)
+//!     }
+//!
+//!     fn deserial(
+//!         _current: Self::State,
+//!         buf: Vec<u8>,
+//!         mut internal: PgBox<Self::State>,
+//!         fcinfo: pg_sys::FunctionCallInfo,
+//!     ) -> PgBox<Self::State> {
+//!         *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+//!         internal
+//!     }
+//!
+//!     fn finalize(
+//!         current: Self::State,
+//!         _direct_args: Self::OrderedSetArgs,
+//!         fcinfo: pg_sys::FunctionCallInfo,
+//!     ) -> Self::Finalize {
+//!         current.get(fcinfo).and_then(Welford::stddev_samp)
+//!     }
+//! }
+//! ```
+use serde::{Deserialize, Serialize};
+
+/// A sum of `f64`s, with the rounding error of each addition carried into the next
+///
+/// ```rust
+/// use pgx::agg::KahanSum;
+///
+/// let values = [1.0, 1e100, 1.0, -1e100];
+/// assert_eq!(values.iter().sum::<f64>(), 0.0);
+/// assert_eq!(values.into_iter().collect::<KahanSum>().sum(), 2.0);
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KahanSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl KahanSum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `x` to the sum
+    pub fn push(&mut self, x: f64) {
+        let sum = self.sum + x;
+        // whichever of the two is larger keeps its low-order bits, and the other's are lost
+        self.compensation +=
+            if self.sum.abs() >= x.abs() { (self.sum - sum) + x } else { (x - sum) + self.sum };
+        self.sum = sum;
+    }
+
+    /// Adds what `other` summed to this sum
+    pub fn merge(&mut self, other: &KahanSum) {
+        self.push(other.sum);
+        self.compensation += other.compensation;
+    }
+
+    /// The sum, which is `0.0` if nothing was added
+    pub fn sum(&self) -> f64 {
+        let sum = self.sum + self.compensation;
+        // the compensation of an infinite sum is `NaN`
+        if sum.is_nan() && !self.sum.is_nan() {
+            self.sum
+        } else {
+            sum
+        }
+    }
+}
+
+impl Extend<f64> for KahanSum {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl FromIterator<f64> for KahanSum {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        let mut sum = KahanSum::new();
+        sum.extend(iter);
+        sum
+    }
+}
+
+/// The count, mean, and sum of squared differences from the mean of `f64`s, kept up to date with
+/// Welford's algorithm, from which their variance and standard deviation follow
+///
+/// The methods named after a SQL aggregate return what it would, including `None` where it would
+/// return `NULL`.
+///
+/// ```rust
+/// use pgx::agg::Welford;
+///
+/// let mut first = [1e9 + 4.0, 1e9 + 7.0].into_iter().collect::<Welford>();
+/// let second = [1e9 + 13.0, 1e9 + 16.0].into_iter().collect::<Welford>();
+/// first.merge(&second);
+/// assert_eq!(first.count(), 4);
+/// assert_eq!(first.mean(), Some(1e9 + 10.0));
+/// assert_eq!(first.var_samp(), Some(30.0));
+/// assert_eq!(first.var_pop(), Some(22.5));
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Welford {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accumulates `x`
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Accumulates what `other` accumulated, as if its values were pushed to this one
+    pub fn merge(&mut self, other: &Welford) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let (n1, n2, n) = (self.count as f64, other.count as f64, count as f64);
+        self.mean += delta * n2 / n;
+        self.m2 += other.m2 + delta * delta * n1 * n2 / n;
+        self.count = count;
+    }
+
+    /// How many values were accumulated, as SQL's `count()`
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// SQL's `avg()`
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// The sum of the squared differences of the values from their mean, as SQL's `regr_sxx()`
+    pub fn sum_of_squares(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.m2)
+    }
+
+    /// SQL's `var_pop()`
+    pub fn var_pop(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.m2 / self.count as f64)
+    }
+
+    /// SQL's `var_samp()`, or `variance()`
+    pub fn var_samp(&self) -> Option<f64> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f64)
+    }
+
+    /// SQL's `stddev_pop()`
+    pub fn stddev_pop(&self) -> Option<f64> {
+        self.var_pop().map(f64::sqrt)
+    }
+
+    /// SQL's `stddev_samp()`, or `stddev()`
+    pub fn stddev_samp(&self) -> Option<f64> {
+        self.var_samp().map(f64::sqrt)
+    }
+}
+
+impl Extend<f64> for Welford {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl FromIterator<f64> for Welford {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        let mut welford = Welford::new();
+        welford.extend(iter);
+        welford
+    }
+}
+
+/// [`Welford`]'s accumulator for pairs of `f64`s, as `(y, x)`, which is the order SQL's two-argument
+/// aggregates take them in, from which their covariance, correlation, and linear regression follow
+///
+/// ```rust
+/// use pgx::agg::Covariance;
+///
+/// let covariance = [(3.0, 1.0), (5.0, 2.0), (7.0, 3.0)].into_iter().collect::<Covariance>();
+/// assert_eq!(covariance.covar_samp(), Some(2.0));
+/// assert_eq!(covariance.corr(), Some(1.0));
+/// assert_eq!(covariance.regr_slope(), Some(2.0));
+/// assert_eq!(covariance.regr_intercept(), Some(1.0));
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Covariance {
+    y: Welford,
+    x: Welford,
+    /// The sum of the products of the differences of each `y` and `x` from their means
+    c: f64,
+}
+
+impl Covariance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accumulates the pair `(y, x)`
+    pub fn push(&mut self, y: f64, x: f64) {
+        let delta_x = x - self.x.mean;
+        self.y.push(y);
+        self.x.push(x);
+        self.c += delta_x * (y - self.y.mean);
+    }
+
+    /// Accumulates what `other` accumulated, as if its pairs were pushed to this one
+    pub fn merge(&mut self, other: &Covariance) {
+        if other.count() == 0 {
+            return;
+        }
+        if self.count() == 0 {
+            *self = *other;
+            return;
+        }
+        let (n1, n2) = (self.count() as f64, other.count() as f64);
+        let n = n1 + n2;
+        self.c +=
+            other.c + (other.x.mean - self.x.mean) * (other.y.mean - self.y.mean) * n1 * n2 / n;
+        self.y.merge(&other.y);
+        self.x.merge(&other.x);
+    }
+
+    /// How many pairs were accumulated, as SQL's `regr_count()`
+    pub fn count(&self) -> u64 {
+        self.x.count
+    }
+
+    /// The accumulated `y`s, the first of each pair, as in `regr_avgy()` and `regr_syy()`
+    pub fn y(&self) -> &Welford {
+        &self.y
+    }
+
+    /// The accumulated `x`s, the second of each pair, as in `regr_avgx()` and `regr_sxx()`
+    pub fn x(&self) -> &Welford {
+        &self.x
+    }
+
+    /// The sum of the products of the differences of the `y`s and `x`s from their means, as SQL's
+    /// `regr_sxy()`
+    pub fn sum_of_products(&self) -> Option<f64> {
+        (self.count() > 0).then_some(self.c)
+    }
+
+    /// SQL's `covar_pop()`
+    pub fn covar_pop(&self) -> Option<f64> {
+        (self.count() > 0).then(|| self.c / self.count() as f64)
+    }
+
+    /// SQL's `covar_samp()`
+    pub fn covar_samp(&self) -> Option<f64> {
+        (self.count() > 1).then(|| self.c / (self.count() - 1) as f64)
+    }
+
+    /// SQL's `corr()`, which is `None` when either the `y`s or the `x`s are all the same
+    pub fn corr(&self) -> Option<f64> {
+        if self.count() == 0 || self.x.m2 == 0.0 || self.y.m2 == 0.0 {
+            return None;
+        }
+        Some(self.c / (self.x.m2 * self.y.m2).sqrt())
+    }
+
+    /// SQL's `regr_slope()`, which is `None` when the `x`s are all the same
+    pub fn regr_slope(&self) -> Option<f64> {
+        if self.count() == 0 || self.x.m2 == 0.0 {
+            return None;
+        }
+        Some(self.c / self.x.m2)
+    }
+
+    /// SQL's `regr_intercept()`, which is `None` when the `x`s are all the same
+    pub fn regr_intercept(&self) -> Option<f64> {
+        self.regr_slope().map(|slope| self.y.mean - slope * self.x.mean)
+    }
+}
+
+impl Extend<(f64, f64)> for Covariance {
+    fn extend<I: IntoIterator<Item = (f64, f64)>>(&mut self, iter: I) {
+        for (y, x) in iter {
+            self.push(y, x);
+        }
+    }
+}
+
+impl FromIterator<(f64, f64)> for Covariance {
+    fn from_iter<I: IntoIterator<Item = (f64, f64)>>(iter: I) -> Self {
+        let mut covariance = Covariance::new();
+        covariance.extend(iter);
+        covariance
+    }
+}
@@ -208,7 +208,7 @@ CREATE FUNCTION "demo_sum_state"(
 ) RETURNS DemoSum /* aggregate::DemoSum */
 PARALLEL SAFE IMMUTABLE STRICT
 LANGUAGE c /* Rust */
-AS 'MODULE_PATHNAME', 'demo_sum_state_wrapper';
+AS 'MODULE_PATHNAME', 'aggregate::demo_sum_state_wrapper';
 ```
 
 ## Non-`Self` State
@@ -252,7 +252,7 @@ CREATE FUNCTION "demo_sum_state"(
 ) RETURNS DemoSumState /* aggregate::DemoSumState */
 STRICT
 LANGUAGE c /* Rust */
-AS 'MODULE_PATHNAME', 'demo_sum_state_wrapper';
+AS 'MODULE_PATHNAME', 'aggregate::demo_sum_state_wrapper';
 
 -- src/lib.rs:13
 -- aggregate::DemoSum
@@ -266,12 +266,83 @@ CREATE AGGREGATE DemoSum (
 );
 ```
 
+## `internal` State and Parallel Aggregation
+
+A state which isn't a Postgres type, such as a `Vec`, can be kept in an
+[`AggregateInternalState`], which Postgres knows as `internal`.  It's allocated in the aggregate's
+memory context, and raises an `ERROR` rather than being used outside of one.  Postgres only runs an
+aggregate with an `internal` state in parallel when it has `combine`, `serial` and `deserial`
+functions:
+
+```rust
+# use pgx::prelude::*;
+#
+pub struct DemoSortedArray;
+
+#[pg_aggregate]
+impl Aggregate for DemoSortedArray {
+    const PARALLEL: Option<ParallelOption> = Some(ParallelOption::Safe);
+    type Args = i32;
+    type State = AggregateInternalState<Vec<i32>>;
+    type Finalize = Vec<i32>;
+
+    fn state(
+        mut current: Self::State,
+        arg: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo
+    ) -> Self::State {
+        current.get_or_insert_default(fcinfo).push(arg);
+        current
+    }
+
+    fn combine(
+        current: Self::State,
+        other: Self::State,
+        fcinfo: pg_sys::FunctionCallInfo
+    ) -> Self::State {
+        current.combine(other, fcinfo, |current, other| current.extend(other))
+    }
+
+    fn serial(current: Self::State, fcinfo: pg_sys::FunctionCallInfo) -> Vec<u8> {
+        current.serialize(fcinfo)
+    }
+
+    fn deserial(
+        _current: Self::State,
+        buf: Vec<u8>,
+        mut internal: PgBox<Self::State>,
+        fcinfo: pg_sys::FunctionCallInfo
+    ) -> PgBox<Self::State> {
+        *internal = AggregateInternalState::deserialize(&buf, fcinfo);
+        internal
+    }
+
+    fn finalize(
+        mut current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        fcinfo: pg_sys::FunctionCallInfo
+    ) -> Self::Finalize {
+        let mut values = current.take(fcinfo).unwrap_or_default();
+        values.sort();
+        values
+    }
+}
+```
+
 */
 
 use crate::error;
 use crate::memcxt::PgMemoryContexts;
-use crate::pg_sys::{AggCheckCallContext, CurrentMemoryContext, FunctionCallInfo, MemoryContext};
+use crate::pg_sys::{
+    self, AggCheckCallContext, CurrentMemoryContext, FunctionCallInfo, MemoryContext,
+};
 use crate::pgbox::PgBox;
+use crate::{FromDatum, IntoDatum};
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 pub use pgx_sql_entity_graph::{FinalizeModify, ParallelOption};
 
@@ -450,3 +521,231 @@ where
         }
     }
 }
+
+/// Where the value of an [`AggregateInternalState`] lives
+struct InternalStateSlot<T> {
+    /// The aggregate memory context whose deletion drops `value`
+    context: MemoryContext,
+    /// `None` once the value has been moved out
+    value: Option<T>,
+}
+
+/// An aggregate's `internal` state, holding a `T` for as long as the aggregate memory context it was
+/// allocated in
+///
+/// Unlike [`Internal`](crate::Internal), it knows which type it holds and which memory context it
+/// was allocated in.  Each of its methods takes the calling function's `fcinfo`, which it uses to
+/// find the aggregate memory context with `AggCheckCallContext()`, and raises an `ERROR` if the
+/// function isn't being called as part of an aggregate.
+///
+/// A value found in another aggregate memory context than the current one, such as a partial state
+/// which a parallel worker sent and the `deserial` function rebuilt, is moved into the current one
+/// before it's modified, and `#[pg_aggregate]` does the same with what `combine` returns.  That
+/// keeps every state Postgres holds on to alive for as long as it does.
+///
+/// `#[pg_aggregate]` creates `deserial` with the signature Postgres expects when the `State` is an
+/// `AggregateInternalState`, and [`serialize()`](Self::serialize) and
+/// [`deserialize()`](Self::deserialize) implement `serial` and `deserial` for any `T` which serde
+/// supports, which is all a parallel aggregate needs.  See the
+/// [`aggregate` example](https://github.com/tcdi/pgx/tree/master/pgx-examples/aggregate).
+pub struct AggregateInternalState<T> {
+    slot: *mut InternalStateSlot<T>,
+}
+
+impl<T> Default for AggregateInternalState<T> {
+    /// An empty state, which is what Postgres passes to `state` and `combine` the first time
+    fn default() -> Self {
+        Self { slot: std::ptr::null_mut() }
+    }
+}
+
+impl<T> AggregateInternalState<T> {
+    /// A state holding `value`, allocated in the aggregate memory context
+    pub fn new(value: T, fcinfo: FunctionCallInfo) -> Self {
+        let mut state = Self::default();
+        state.insert(value, fcinfo);
+        state
+    }
+
+    /// Is there no value?
+    pub fn is_empty(&self) -> bool {
+        self.slot.is_null()
+    }
+
+    /// The value, unless there is none
+    pub fn get(&self, fcinfo: FunctionCallInfo) -> Option<&T> {
+        aggregate_memory_context(fcinfo);
+        // SAFETY:  a non-null slot was allocated by `allocate_slot()` in an aggregate memory
+        // context, which Postgres hasn't deleted as it's still calling the aggregate's functions
+        unsafe { self.slot.as_ref()?.value.as_ref() }
+    }
+
+    /// The value, unless there is none, after moving it into the current aggregate memory context
+    pub fn get_mut(&mut self, fcinfo: FunctionCallInfo) -> Option<&mut T> {
+        self.adopt(aggregate_memory_context(fcinfo));
+        // SAFETY:  `adopt()` made sure the slot is in the current aggregate memory context
+        unsafe { self.slot.as_mut()?.value.as_mut() }
+    }
+
+    /// Replace the value with `value`, returning a mutable reference to it
+    ///
+    /// The old value is dropped along with the memory context it was allocated in.
+    pub fn insert(&mut self, value: T, fcinfo: FunctionCallInfo) -> &mut T {
+        self.slot = allocate_slot(aggregate_memory_context(fcinfo), value);
+        // SAFETY:  `allocate_slot()` returns a valid pointer to a slot holding a value
+        unsafe { (*self.slot).value.as_mut().unwrap() }
+    }
+
+    /// The value, after inserting the result of `f` if there is none
+    pub fn get_or_insert_with(
+        &mut self,
+        fcinfo: FunctionCallInfo,
+        f: impl FnOnce() -> T,
+    ) -> &mut T {
+        if self.is_empty() {
+            self.insert(f(), fcinfo)
+        } else {
+            self.get_mut(fcinfo).expect("a non-empty aggregate state has no value")
+        }
+    }
+
+    /// The value, after inserting a default one if there is none
+    pub fn get_or_insert_default(&mut self, fcinfo: FunctionCallInfo) -> &mut T
+    where
+        T: Default,
+    {
+        self.get_or_insert_with(fcinfo, T::default)
+    }
+
+    /// Move the value out, leaving the state empty
+    pub fn take(&mut self, fcinfo: FunctionCallInfo) -> Option<T> {
+        aggregate_memory_context(fcinfo);
+        let slot = std::mem::replace(&mut self.slot, std::ptr::null_mut());
+        // SAFETY:  see `get()`
+        unsafe { slot.as_mut()?.value.take() }
+    }
+
+    /// This state, with its value moved into the current aggregate memory context if it was
+    /// allocated in another one
+    pub fn into_aggregate_context(mut self, fcinfo: FunctionCallInfo) -> Self {
+        self.adopt(aggregate_memory_context(fcinfo));
+        self
+    }
+
+    /// Combine `other` into this state with `f`, for implementing [`Aggregate::combine`]
+    ///
+    /// When either state is empty, the other one is the result, moved into the current aggregate
+    /// memory context if need be.
+    pub fn combine(
+        mut self,
+        mut other: Self,
+        fcinfo: FunctionCallInfo,
+        f: impl FnOnce(&mut T, T),
+    ) -> Self {
+        match other.take(fcinfo) {
+            None => self.into_aggregate_context(fcinfo),
+            Some(value) => {
+                match self.get_mut(fcinfo) {
+                    Some(current) => f(current, value),
+                    None => {
+                        self.insert(value, fcinfo);
+                    }
+                }
+                self
+            }
+        }
+    }
+
+    /// The value, serialized for sending between parallel workers, for implementing
+    /// [`Aggregate::serial`]
+    pub fn serialize(&self, fcinfo: FunctionCallInfo) -> Vec<u8>
+    where
+        T: Serialize,
+    {
+        serde_cbor::to_vec(&self.get(fcinfo))
+            .unwrap_or_else(|e| error!("failed to serialize the aggregate state: {}", e))
+    }
+
+    /// A state holding what [`serialize()`](Self::serialize) serialized, for implementing
+    /// [`Aggregate::deserial`]
+    pub fn deserialize(bytes: &[u8], fcinfo: FunctionCallInfo) -> Self
+    where
+        T: DeserializeOwned,
+    {
+        let value: Option<T> = serde_cbor::from_slice(bytes)
+            .unwrap_or_else(|e| error!("failed to deserialize the aggregate state: {}", e));
+        match value {
+            Some(value) => Self::new(value, fcinfo),
+            None => Self::default(),
+        }
+    }
+
+    /// Move the value into `context` if it was allocated in another one
+    fn adopt(&mut self, context: MemoryContext) {
+        // SAFETY:  see `get()`
+        if let Some(slot) = unsafe { self.slot.as_mut() } {
+            if slot.context != context {
+                self.slot = match slot.value.take() {
+                    Some(value) => allocate_slot(context, value),
+                    None => std::ptr::null_mut(),
+                };
+            }
+        }
+    }
+}
+
+/// The memory context of the aggregate which is calling the function, raising an `ERROR` when it
+/// isn't being called as part of an aggregate
+fn aggregate_memory_context(fcinfo: FunctionCallInfo) -> MemoryContext {
+    let mut memory_context = std::ptr::null_mut();
+    // SAFETY:  `AggCheckCallContext()` only looks at what a non-null `fcinfo` points to
+    if fcinfo.is_null() || unsafe { AggCheckCallContext(fcinfo, &mut memory_context) } == 0 {
+        error!("an AggregateInternalState can only be used by the functions of an aggregate")
+    }
+    memory_context
+}
+
+fn allocate_slot<T>(context: MemoryContext, value: T) -> *mut InternalStateSlot<T> {
+    PgMemoryContexts::For(context)
+        .leak_and_drop_on_delete(InternalStateSlot { context, value: Some(value) })
+}
+
+impl<T> FromDatum for AggregateInternalState<T> {
+    #[inline]
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _: pg_sys::Oid,
+    ) -> Option<Self> {
+        Some(Self { slot: if is_null { std::ptr::null_mut() } else { datum.cast_mut_ptr() } })
+    }
+}
+
+impl<T> IntoDatum for AggregateInternalState<T> {
+    #[inline]
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        if self.slot.is_null() {
+            None
+        } else {
+            Some(pg_sys::Datum::from(self.slot))
+        }
+    }
+
+    #[inline]
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::INTERNALOID
+    }
+}
+
+unsafe impl<T> SqlTranslatable for AggregateInternalState<T> {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("internal"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("internal")))
+    }
+    // Postgres passes an empty state to the first `state` call, so the function mustn't be strict
+    fn optional() -> bool {
+        true
+    }
+}
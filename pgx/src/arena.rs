@@ -0,0 +1,207 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Temporary allocations which are freed all at once, instead of one at a time
+//!
+//! Work done for every row, such as in a trigger, often makes `String`s and `Vec`s which are
+//! dropped before the next row.  An [`Arena`] hands out memory from a Postgres memory context
+//! instead, which is reset once the work is done, as Postgres does with its own per-row contexts:
+//!
+//! ```rust,no_run
+//! use pgx::arena;
+//!
+//! fn distinct_ignoring_case(words: &[&str]) -> usize {
+//!     arena::with_temp_context(|arena| {
+//!         let mut lowered = words
+//!             .iter()
+//!             .map(|word| {
+//!                 let word = arena.alloc_str(word);
+//!                 word.make_ascii_lowercase();
+//!                 &*word
+//!             })
+//!             .collect::<Vec<_>>();
+//!         lowered.sort_unstable();
+//!         lowered.dedup();
+//!         lowered.len()
+//!     })
+//! }
+//! ```
+//!
+//! What's allocated can't outlive the closure, so it's never used after the context is reset.
+//!
+//! A trigger or set-returning function can have an arena for each row it's called for, with
+//! [`#[pg_trigger(arena)]`](crate::pg_trigger) or [`#[pg_extern(arena)]`](crate::pg_extern), which
+//! [`with_row_arena()`] uses:
+//!
+//! ```rust,no_run
+//! use pgx::arena;
+//! use pgx::prelude::*;
+//!
+//! #[pg_extern(arena)]
+//! fn shouted(names: Vec<String>) -> SetOfIterator<'static, String> {
+//!     SetOfIterator::new(names.into_iter().map(|name| {
+//!         arena::with_row_arena(|arena| {
+//!             let name = arena.alloc_str(&name);
+//!             name.make_ascii_uppercase();
+//!             format!("{}!", name)
+//!         })
+//!     }))
+//! }
+//! ```
+use crate::pg_sys;
+use crate::PgMemoryContexts;
+use std::alloc::Layout;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// How many reset contexts are kept for the next [`with_temp_context()`], which is as deeply as
+/// they're usually nested
+const POOLED_CONTEXTS: usize = 4;
+
+thread_local! {
+    /// Contexts which have been reset, and can be used again
+    static POOL: RefCell<Vec<pg_sys::MemoryContext>> = RefCell::new(Vec::new());
+    /// The context of the row a `#[pg_trigger(arena)]` or `#[pg_extern(arena)]` function is
+    /// making, if it's making one
+    static ROW: Cell<Option<pg_sys::MemoryContext>> = Cell::new(None);
+}
+
+/// Memory from a Postgres memory context, which is freed all at once
+///
+/// Nothing allocated in it is dropped, only freed, so what's allocated shouldn't own memory
+/// elsewhere, such as a `String` does.
+pub struct Arena {
+    context: pg_sys::MemoryContext,
+    // it's only for the backend's thread
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl Arena {
+    /// Move `value` into the arena
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+        unsafe {
+            // SAFETY:  `ptr` is aligned and big enough for a `T`, and nothing else has it
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copy `s` into the arena
+    pub fn alloc_str(&self, s: &str) -> &mut str {
+        let bytes = self.alloc_slice(s.as_bytes());
+        unsafe {
+            // SAFETY:  they're a copy of a `str`'s bytes
+            std::str::from_utf8_unchecked_mut(bytes)
+        }
+    }
+
+    /// Copy `slice` into the arena
+    pub fn alloc_slice<T: Copy>(&self, slice: &[T]) -> &mut [T] {
+        let layout = Layout::array::<T>(slice.len()).expect("the slice is too big for an arena");
+        let ptr = self.alloc_layout(layout).cast::<T>();
+        unsafe {
+            // SAFETY:  `ptr` is aligned and big enough for the slice, and doesn't overlap it
+            std::ptr::copy_nonoverlapping(slice.as_ptr(), ptr.as_ptr(), slice.len());
+            std::slice::from_raw_parts_mut(ptr.as_ptr(), slice.len())
+        }
+    }
+
+    /// The arena's memory context, such as to have Postgres allocate in it
+    pub fn memory_context(&self) -> PgMemoryContexts {
+        PgMemoryContexts::For(self.context)
+    }
+
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            // SAFETY:  an alignment is never 0
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+        unsafe {
+            // SAFETY:  `MemoryContextAlloc()` raises an ERROR rather than return NULL, and its
+            // memory is `MAXIMUM_ALIGNOF`-aligned, so one with a larger alignment is padded
+            if layout.align() <= pg_sys::MAXIMUM_ALIGNOF as usize {
+                let ptr = pg_sys::MemoryContextAlloc(self.context, layout.size());
+                NonNull::new_unchecked(ptr.cast())
+            } else {
+                let size = layout.size() + layout.align() - 1;
+                let ptr = pg_sys::MemoryContextAlloc(self.context, size).cast::<u8>();
+                NonNull::new_unchecked(ptr.add(ptr.align_offset(layout.align())))
+            }
+        }
+    }
+}
+
+/// Run `f` with an [`Arena`], which is reset once `f` returns, or unwinds
+///
+/// Its context is a child of `TopMemoryContext` which is kept once it's reset, for the next call,
+/// so a reset one keeps its first block of memory, and it isn't made again for every row.
+pub fn with_temp_context<R>(f: impl FnOnce(&Arena) -> R) -> R {
+    let context = POOL.with(|pool| pool.borrow_mut().pop()).unwrap_or_else(|| unsafe {
+        // SAFETY:  `TopMemoryContext` lasts as long as the backend, and the name is static
+        pg_sys::AllocSetContextCreateExtended(
+            pg_sys::TopMemoryContext,
+            b"pgx arena\0".as_ptr().cast(),
+            pg_sys::ALLOCSET_DEFAULT_MINSIZE as usize,
+            pg_sys::ALLOCSET_DEFAULT_INITSIZE as usize,
+            pg_sys::ALLOCSET_DEFAULT_MAXSIZE as usize,
+        )
+    });
+    let _release = ReleaseOnDrop(context);
+    f(&Arena { context, _not_send: PhantomData })
+}
+
+/// Run `f` with the arena of the row a `#[pg_trigger(arena)]` or `#[pg_extern(arena)]` function is
+/// making, which is reset once the row's made, or with a temporary one, as
+/// [`with_temp_context()`] makes, anywhere else
+pub fn with_row_arena<R>(f: impl FnOnce(&Arena) -> R) -> R {
+    match ROW.with(|row| row.get()) {
+        Some(context) => f(&Arena { context, _not_send: PhantomData }),
+        None => with_temp_context(f),
+    }
+}
+
+/// Make a row with its own arena, for [`with_row_arena()`]
+#[doc(hidden)]
+pub fn __row_scope<R>(f: impl FnOnce() -> R) -> R {
+    with_temp_context(|arena| {
+        let outer = ROW.with(|row| row.replace(Some(arena.context)));
+        let _restore = RestoreRowOnDrop(outer);
+        f()
+    })
+}
+
+/// Resets the context it holds when dropped, and keeps it for the next [`with_temp_context()`]
+struct ReleaseOnDrop(pg_sys::MemoryContext);
+
+impl Drop for ReleaseOnDrop {
+    fn drop(&mut self) {
+        let keep = POOL.with(|pool| pool.borrow().len() < POOLED_CONTEXTS);
+        unsafe {
+            if keep {
+                pg_sys::MemoryContextReset(self.0);
+            } else {
+                pg_sys::MemoryContextDelete(self.0);
+            }
+        }
+        if keep {
+            POOL.with(|pool| pool.borrow_mut().push(self.0));
+        }
+    }
+}
+
+/// Restores the arena of the row an outer function was making when dropped
+struct RestoreRowOnDrop(Option<pg_sys::MemoryContext>);
+
+impl Drop for RestoreRowOnDrop {
+    fn drop(&mut self) {
+        ROW.with(|row| row.set(self.0));
+    }
+}
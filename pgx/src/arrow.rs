@@ -0,0 +1,645 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Convert SPI results to and from [Apache Arrow](https://arrow.apache.org) record batches.
+//!
+//! Requires the `arrow` feature.
+//!
+//! [`SpiTupleTable::to_record_batch`] turns the result of a query into a [`RecordBatch`], which
+//! can then be handed to any Arrow-based library without copying it row-by-row again.  Going the
+//! other way, [`TableIterator::from_record_batch`] returns the rows of a [`RecordBatch`] from a
+//! `#[pg_extern]` function.
+//!
+//! Postgres types map to Arrow types like so:
+//!
+//! | Postgres             | Arrow                               |
+//! |----------------------|-------------------------------------|
+//! | `bool`               | `Boolean`                           |
+//! | `smallint`           | `Int16`                             |
+//! | `integer`            | `Int32`                             |
+//! | `bigint`             | `Int64`                             |
+//! | `real`               | `Float32`                           |
+//! | `double precision`   | `Float64`                           |
+//! | `text`, `varchar`    | `Utf8`                              |
+//! | `bytea`              | `Binary`                            |
+//! | `date`               | `Date32`                            |
+//! | `timestamp`          | `Timestamp(Microsecond, None)`      |
+//! | `timestamptz`        | `Timestamp(Microsecond, "UTC")`     |
+//! | `numeric`            | `Decimal128(38, s)`                 |
+//! | arrays of the above  | `List` of the above                 |
+//!
+//! `NULL`s are preserved in both directions.  The scale `s` of a `numeric` column is the largest
+//! scale of its values, and a value with more than 38 digits, `NaN`, or infinity is an error.  Any
+//! other type is an [`Error::UnsupportedType`] naming the column and its type OID.  Cast such
+//! columns in the query, for example to `text`, to convert them anyway.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::arrow::RecordBatch;
+//! use pgx::prelude::*;
+//!
+//! #[pg_extern]
+//! fn round_trip() -> Result<TableIterator<'static, (name!(id, Option<i32>), name!(label, Option<String>))>, pgx::arrow::Error> {
+//!     let batch: RecordBatch =
+//!         Spi::connect(|client| client.select("SELECT id, label FROM things", None, None)?.to_record_batch())?;
+//!     TableIterator::from_record_batch(&batch)
+//! }
+//! ```
+use crate::datum::{AnyNumeric, Date, FromDatum, IntoDatum, Timestamp, TimestampWithTimeZone};
+use crate::iter::TableIterator;
+use crate::spi::{self, SpiHeapTupleData, SpiTupleTable};
+use crate::{pg_sys, IntoHeapTuple, PgSqlErrorCode};
+use ::arrow::array::{
+    Array, ArrayBuilder, ArrayRef, BinaryArray, BinaryBuilder, BooleanArray, BooleanBuilder,
+    Date32Array, Date32Builder, Decimal128Array, Decimal128Builder, Float32Array, Float32Builder,
+    Float64Array, Float64Builder, Int16Array, Int16Builder, Int32Array, Int32Builder, Int64Array,
+    Int64Builder, ListArray, ListBuilder, StringArray, StringBuilder, TimestampMicrosecondArray,
+    TimestampMicrosecondBuilder,
+};
+use ::arrow::datatypes::{DataType, Field, Schema, TimeUnit, DECIMAL128_MAX_PRECISION};
+use std::any::type_name;
+use std::sync::Arc;
+
+pub use ::arrow::error::ArrowError;
+pub use ::arrow::record_batch::RecordBatch;
+pub use ::arrow::{array, datatypes};
+
+/// Microseconds between the Unix epoch, which Arrow uses, and the Postgres epoch of 2000-01-01
+const POSTGRES_EPOCH_MICROS: i64 = POSTGRES_EPOCH_DAYS as i64 * 86_400 * 1_000_000;
+
+/// Days between the Unix epoch and the Postgres epoch
+const POSTGRES_EPOCH_DAYS: i32 =
+    crate::datum::POSTGRES_EPOCH_JDATE - crate::datum::UNIX_EPOCH_JDATE;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// A column's Postgres type has no Arrow equivalent
+    #[error("column `{column}` has type OID {oid}, which can't be converted to Arrow")]
+    UnsupportedType { column: String, oid: pg_sys::Oid },
+
+    /// A column's Arrow type can't be read as the requested Rust type
+    #[error("column `{column}` has Arrow type {data_type}, which can't be read as `{rust_type}`")]
+    UnsupportedArrowType { column: String, data_type: DataType, rust_type: &'static str },
+
+    /// A `NULL` was found where the requested Rust type can't represent it
+    #[error("column `{column}` contains a NULL, but `{rust_type}` is not an `Option`")]
+    UnexpectedNull { column: String, rust_type: &'static str },
+
+    /// A value doesn't fit its Postgres or Arrow counterpart
+    #[error("column `{column}` contains `{value}`, which is out of range")]
+    OutOfRange { column: String, value: String },
+
+    /// A record batch has a different number of columns than the requested rows
+    #[error("the record batch has {found} columns, but {expected} were expected")]
+    ColumnCountMismatch { expected: usize, found: usize },
+
+    #[error(transparent)]
+    Spi(#[from] spi::Error),
+
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+}
+
+impl crate::ErrorReportable for Error {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            Error::UnsupportedType { .. } => PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            Error::UnsupportedArrowType { .. } | Error::ColumnCountMismatch { .. } => {
+                PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH
+            }
+            Error::UnexpectedNull { .. } => PgSqlErrorCode::ERRCODE_NULL_VALUE_NOT_ALLOWED,
+            Error::OutOfRange { .. } => PgSqlErrorCode::ERRCODE_NUMERIC_VALUE_OUT_OF_RANGE,
+            Error::Spi(e) => crate::ErrorReportable::sql_error_code(e),
+            Error::Arrow(_) => PgSqlErrorCode::ERRCODE_EXTERNAL_ROUTINE_EXCEPTION,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl SpiTupleTable {
+    /// Converts every row of this table, no matter the current position, into a [`RecordBatch`]
+    /// with one column for each of the table's columns.
+    ///
+    /// See the [module documentation](crate::arrow) for how Postgres types are converted.
+    pub fn to_record_batch(self) -> Result<RecordBatch> {
+        let columns = (1..=self.columns()?)
+            .map(|ordinal| Ok((self.column_name(ordinal)?, self.column_type_oid(ordinal)?.value())))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = self.rewind().collect::<Vec<_>>();
+
+        let mut fields = Vec::with_capacity(columns.len());
+        let mut arrays = Vec::with_capacity(columns.len());
+        for (index, (name, oid)) in columns.into_iter().enumerate() {
+            let array = column_to_array(&rows, index + 1, &name, oid)?;
+            fields.push(Field::new(&name, array.data_type().clone(), true));
+            arrays.push(array);
+        }
+        Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+    }
+}
+
+fn column_to_array(
+    rows: &[SpiHeapTupleData],
+    ordinal: usize,
+    name: &str,
+    oid: pg_sys::Oid,
+) -> Result<ArrayRef> {
+    match oid {
+        pg_sys::BOOLOID => build_column(rows, ordinal, BooleanBuilder::new(), append_bool),
+        pg_sys::INT2OID => build_column(rows, ordinal, Int16Builder::new(), append_i16),
+        pg_sys::INT4OID => build_column(rows, ordinal, Int32Builder::new(), append_i32),
+        pg_sys::INT8OID => build_column(rows, ordinal, Int64Builder::new(), append_i64),
+        pg_sys::FLOAT4OID => build_column(rows, ordinal, Float32Builder::new(), append_f32),
+        pg_sys::FLOAT8OID => build_column(rows, ordinal, Float64Builder::new(), append_f64),
+        pg_sys::TEXTOID | pg_sys::VARCHAROID => {
+            build_column(rows, ordinal, StringBuilder::new(), append_string)
+        }
+        pg_sys::BYTEAOID => build_column(rows, ordinal, BinaryBuilder::new(), append_bytes),
+        pg_sys::DATEOID => build_column(rows, ordinal, Date32Builder::new(), append_date),
+        pg_sys::TIMESTAMPOID => {
+            build_column(rows, ordinal, timestamp_builder(None), append_timestamp)
+        }
+        pg_sys::TIMESTAMPTZOID => {
+            build_column(rows, ordinal, timestamp_builder(Some("UTC")), append_timestamptz)
+        }
+        pg_sys::NUMERICOID => {
+            let values = rows
+                .iter()
+                .map(|row| Ok(row.get_datum_by_ordinal(ordinal)?.value::<AnyNumeric>()?))
+                .collect::<Result<Vec<_>>>()?;
+            let (mut builder, scale) = decimal_builder(name, values.iter().flatten())?;
+            for value in values {
+                append_numeric(&mut builder, scale, value, name)?;
+            }
+            Ok(ArrayBuilder::finish(&mut builder))
+        }
+
+        pg_sys::BOOLARRAYOID => {
+            build_list_column(rows, ordinal, BooleanBuilder::new(), append_bool)
+        }
+        pg_sys::INT2ARRAYOID => build_list_column(rows, ordinal, Int16Builder::new(), append_i16),
+        pg_sys::INT4ARRAYOID => build_list_column(rows, ordinal, Int32Builder::new(), append_i32),
+        pg_sys::INT8ARRAYOID => build_list_column(rows, ordinal, Int64Builder::new(), append_i64),
+        pg_sys::FLOAT4ARRAYOID => {
+            build_list_column(rows, ordinal, Float32Builder::new(), append_f32)
+        }
+        pg_sys::FLOAT8ARRAYOID => {
+            build_list_column(rows, ordinal, Float64Builder::new(), append_f64)
+        }
+        pg_sys::TEXTARRAYOID | pg_sys::VARCHARARRAYOID => {
+            build_list_column(rows, ordinal, StringBuilder::new(), append_string)
+        }
+        pg_sys::BYTEAARRAYOID => {
+            build_list_column(rows, ordinal, BinaryBuilder::new(), append_bytes)
+        }
+        pg_sys::DATEARRAYOID => build_list_column(rows, ordinal, Date32Builder::new(), append_date),
+        pg_sys::TIMESTAMPARRAYOID => {
+            build_list_column(rows, ordinal, timestamp_builder(None), append_timestamp)
+        }
+        pg_sys::TIMESTAMPTZARRAYOID => {
+            build_list_column(rows, ordinal, timestamp_builder(Some("UTC")), append_timestamptz)
+        }
+        pg_sys::NUMERICARRAYOID => {
+            let values = rows
+                .iter()
+                .map(|row| {
+                    Ok(row.get_datum_by_ordinal(ordinal)?.value::<Vec<Option<AnyNumeric>>>()?)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let (builder, scale) = decimal_builder(
+                name,
+                values.iter().flatten().flat_map(|array| array.iter().flatten()),
+            )?;
+            let mut builder = ListBuilder::new(builder);
+            for value in values {
+                match value {
+                    Some(array) => {
+                        for element in array {
+                            append_numeric(builder.values(), scale, element, name)?;
+                        }
+                        builder.append(true);
+                    }
+                    None => builder.append(false),
+                }
+            }
+            Ok(ArrayBuilder::finish(&mut builder))
+        }
+
+        oid => Err(Error::UnsupportedType { column: name.to_string(), oid }),
+    }
+}
+
+/// Appends a value that's already been read from a Postgres datum to an Arrow builder
+type Append<B, T> = fn(&mut B, Option<T>);
+
+fn build_column<T, B>(
+    rows: &[SpiHeapTupleData],
+    ordinal: usize,
+    mut builder: B,
+    append: Append<B, T>,
+) -> Result<ArrayRef>
+where
+    T: FromDatum + IntoDatum,
+    B: ArrayBuilder,
+{
+    for row in rows {
+        append(&mut builder, row.get_datum_by_ordinal(ordinal)?.value::<T>()?);
+    }
+    Ok(builder.finish())
+}
+
+fn build_list_column<T, B>(
+    rows: &[SpiHeapTupleData],
+    ordinal: usize,
+    values: B,
+    append: Append<B, T>,
+) -> Result<ArrayRef>
+where
+    T: FromDatum + IntoDatum,
+    B: ArrayBuilder,
+{
+    let mut builder = ListBuilder::new(values);
+    for row in rows {
+        match row.get_datum_by_ordinal(ordinal)?.value::<Vec<Option<T>>>()? {
+            Some(array) => {
+                for element in array {
+                    append(builder.values(), element);
+                }
+                builder.append(true);
+            }
+            None => builder.append(false),
+        }
+    }
+    Ok(ArrayBuilder::finish(&mut builder))
+}
+
+fn append_bool(builder: &mut BooleanBuilder, value: Option<bool>) {
+    builder.append_option(value)
+}
+
+fn append_i16(builder: &mut Int16Builder, value: Option<i16>) {
+    builder.append_option(value)
+}
+
+fn append_i32(builder: &mut Int32Builder, value: Option<i32>) {
+    builder.append_option(value)
+}
+
+fn append_i64(builder: &mut Int64Builder, value: Option<i64>) {
+    builder.append_option(value)
+}
+
+fn append_f32(builder: &mut Float32Builder, value: Option<f32>) {
+    builder.append_option(value)
+}
+
+fn append_f64(builder: &mut Float64Builder, value: Option<f64>) {
+    builder.append_option(value)
+}
+
+fn append_string(builder: &mut StringBuilder, value: Option<String>) {
+    builder.append_option(value)
+}
+
+fn append_bytes(builder: &mut BinaryBuilder, value: Option<Vec<u8>>) {
+    builder.append_option(value)
+}
+
+fn append_date(builder: &mut Date32Builder, value: Option<Date>) {
+    builder.append_option(value.map(|date| date.to_pg_epoch_days() + POSTGRES_EPOCH_DAYS))
+}
+
+fn append_timestamp(builder: &mut TimestampMicrosecondBuilder, value: Option<Timestamp>) {
+    builder.append_option(value.map(|ts| i64::from(ts).saturating_add(POSTGRES_EPOCH_MICROS)))
+}
+
+fn append_timestamptz(
+    builder: &mut TimestampMicrosecondBuilder,
+    value: Option<TimestampWithTimeZone>,
+) {
+    builder.append_option(value.map(|ts| i64::from(ts).saturating_add(POSTGRES_EPOCH_MICROS)))
+}
+
+fn timestamp_builder(timezone: Option<&str>) -> TimestampMicrosecondBuilder {
+    TimestampMicrosecondBuilder::new()
+        .with_data_type(DataType::Timestamp(TimeUnit::Microsecond, timezone.map(str::to_string)))
+}
+
+/// Creates a builder whose scale, which is also returned, fits every one of the `values`
+fn decimal_builder<'a>(
+    column: &str,
+    values: impl Iterator<Item = &'a AnyNumeric>,
+) -> Result<(Decimal128Builder, i8)> {
+    let scale = values.map(|value| numeric_scale(&value.to_string())).max().unwrap_or(0);
+    let scale = i8::try_from(scale)
+        .ok()
+        .filter(|&scale| scale as u8 <= DECIMAL128_MAX_PRECISION)
+        .ok_or_else(|| Error::OutOfRange {
+        column: column.to_string(),
+        value: format!("a numeric scale of {scale}"),
+    })?;
+    Ok((Decimal128Builder::new().with_precision_and_scale(DECIMAL128_MAX_PRECISION, scale)?, scale))
+}
+
+fn append_numeric(
+    builder: &mut Decimal128Builder,
+    scale: i8,
+    value: Option<AnyNumeric>,
+    column: &str,
+) -> Result<()> {
+    match value {
+        Some(value) => {
+            let value = value.to_string();
+            let unscaled = numeric_to_i128(&value, scale as usize)
+                .ok_or_else(|| Error::OutOfRange { column: column.to_string(), value })?;
+            builder.append_value(unscaled);
+        }
+        None => builder.append_null(),
+    }
+    Ok(())
+}
+
+/// The number of digits after the decimal point of a `numeric`'s text representation
+fn numeric_scale(value: &str) -> usize {
+    value.split_once('.').map(|(_, fraction)| fraction.len()).unwrap_or(0)
+}
+
+/// Parses a `numeric`'s text representation as a [`Decimal128Array`] value of the given scale.
+///
+/// Returns `None` for `NaN`, infinities, and values with too many digits
+fn numeric_to_i128(value: &str, scale: usize) -> Option<i128> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if fraction.len() > scale
+        || !(integer.bytes().chain(fraction.bytes())).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let mut unscaled = String::with_capacity(integer.len() + scale);
+    unscaled.push_str(integer);
+    unscaled.push_str(fraction);
+    unscaled.extend(std::iter::repeat('0').take(scale - fraction.len()));
+    if unscaled.trim_start_matches('0').len() > DECIMAL128_MAX_PRECISION as usize {
+        return None;
+    }
+    let unscaled = unscaled.parse::<i128>().ok()?;
+    Some(if negative { -unscaled } else { unscaled })
+}
+
+/// Formats a [`Decimal128Array`] value as text that [`AnyNumeric`] can parse
+fn i128_to_numeric(unscaled: i128, scale: i8) -> String {
+    let digits = unscaled.unsigned_abs().to_string();
+    let sign = if unscaled < 0 { "-" } else { "" };
+    if scale <= 0 {
+        let zeros = "0".repeat(-(scale as i32) as usize);
+        return format!("{sign}{digits}{zeros}");
+    }
+    let scale = scale as usize;
+    let digits = format!("{digits:0>width$}", width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    format!("{sign}{integer}.{fraction}")
+}
+
+/// A Rust type that can be read from a non-null element of an Arrow array.
+///
+/// pgx implements this for the Rust types of the Postgres types listed in the
+/// [module documentation](crate::arrow), and for `Vec<Option<T>>` of those, which reads a `List`.
+pub trait FromArrowValue: Sized {
+    /// Reads the element at `index` of `array`, which is known not to be null.  `column` is only
+    /// used to describe errors.
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self>;
+}
+
+/// A Rust type that can be read from an element, null or not, of an Arrow array.
+///
+/// This is implemented for every [`FromArrowValue`] type, which errors on nulls, and for `Option`s
+/// of them, which don't.
+pub trait FromArrowColumn: Sized {
+    fn from_arrow_column(column: &str, array: &dyn Array, index: usize) -> Result<Self>;
+}
+
+/// A row of Rust values that can be read from the columns of a [`RecordBatch`].
+///
+/// This is implemented for tuples of up to 16 [`FromArrowColumn`] types, which are read from the
+/// batch's columns by position.
+pub trait FromArrowRow: Sized {
+    /// The number of columns a row is read from
+    const COLUMNS: usize;
+
+    fn from_arrow_row(batch: &RecordBatch, index: usize) -> Result<Self>;
+}
+
+impl<'a, T> TableIterator<'a, T>
+where
+    T: IntoHeapTuple + FromArrowRow + 'a,
+{
+    /// Reads every row of a [`RecordBatch`], whose columns must match the types of `T` by
+    /// position.
+    ///
+    /// The rows are all read up front, so that any conversion error is returned here rather than
+    /// part-way through returning rows to Postgres.
+    pub fn from_record_batch(batch: &RecordBatch) -> Result<Self> {
+        if batch.num_columns() != T::COLUMNS {
+            return Err(Error::ColumnCountMismatch {
+                expected: T::COLUMNS,
+                found: batch.num_columns(),
+            });
+        }
+        let rows = (0..batch.num_rows())
+            .map(|index| T::from_arrow_row(batch, index))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(TableIterator::new(rows))
+    }
+}
+
+fn unsupported<T>(column: &str, array: &dyn Array) -> Error {
+    Error::UnsupportedArrowType {
+        column: column.to_string(),
+        data_type: array.data_type().clone(),
+        rust_type: type_name::<T>(),
+    }
+}
+
+fn downcast<'a, A: 'static, T>(column: &str, array: &'a dyn Array) -> Result<&'a A> {
+    array.as_any().downcast_ref::<A>().ok_or_else(|| unsupported::<T>(column, array))
+}
+
+macro_rules! from_arrow_primitive {
+    ($($ty:ty => $array:ty),* $(,)?) => {
+        $(
+            impl FromArrowValue for $ty {
+                fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+                    Ok(downcast::<$array, Self>(column, array)?.value(index))
+                }
+            }
+        )*
+    };
+}
+
+from_arrow_primitive! {
+    bool => BooleanArray,
+    i16 => Int16Array,
+    i32 => Int32Array,
+    i64 => Int64Array,
+    f32 => Float32Array,
+    f64 => Float64Array,
+}
+
+impl FromArrowValue for String {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        Ok(downcast::<StringArray, Self>(column, array)?.value(index).to_string())
+    }
+}
+
+impl FromArrowValue for Vec<u8> {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        Ok(downcast::<BinaryArray, Self>(column, array)?.value(index).to_vec())
+    }
+}
+
+impl FromArrowValue for Date {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        let days = downcast::<Date32Array, Self>(column, array)?.value(index);
+        days.checked_sub(POSTGRES_EPOCH_DAYS).map(Date::from_pg_epoch_days).ok_or_else(|| {
+            Error::OutOfRange { column: column.to_string(), value: days.to_string() }
+        })
+    }
+}
+
+impl FromArrowValue for Timestamp {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        let micros = arrow_timestamp_micros::<Self>(column, array, index)?;
+        micros
+            .checked_sub(POSTGRES_EPOCH_MICROS)
+            .and_then(|micros| Timestamp::try_from(micros).ok())
+            .ok_or_else(|| Error::OutOfRange {
+                column: column.to_string(),
+                value: micros.to_string(),
+            })
+    }
+}
+
+impl FromArrowValue for TimestampWithTimeZone {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        let micros = arrow_timestamp_micros::<Self>(column, array, index)?;
+        micros
+            .checked_sub(POSTGRES_EPOCH_MICROS)
+            .and_then(|micros| TimestampWithTimeZone::try_from(micros).ok())
+            .ok_or_else(|| Error::OutOfRange {
+                column: column.to_string(),
+                value: micros.to_string(),
+            })
+    }
+}
+
+/// Arrow timestamps with and without a time zone are both microseconds since the Unix epoch, so
+/// either can be read as a `timestamp` or a `timestamptz`
+fn arrow_timestamp_micros<T>(column: &str, array: &dyn Array, index: usize) -> Result<i64> {
+    Ok(downcast::<TimestampMicrosecondArray, T>(column, array)?.value(index))
+}
+
+impl FromArrowValue for AnyNumeric {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        let array = downcast::<Decimal128Array, Self>(column, array)?;
+        let value = i128_to_numeric(array.value(index), array.scale());
+        AnyNumeric::try_from(value.as_str())
+            .map_err(|_| Error::OutOfRange { column: column.to_string(), value })
+    }
+}
+
+impl<T: FromArrowValue> FromArrowValue for Vec<Option<T>> {
+    fn from_arrow_value(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+        let elements = downcast::<ListArray, Self>(column, array)?.value(index);
+        (0..elements.len())
+            .map(|element| {
+                if elements.is_null(element) {
+                    Ok(None)
+                } else {
+                    T::from_arrow_value(column, elements.as_ref(), element).map(Some)
+                }
+            })
+            .collect()
+    }
+}
+
+macro_rules! from_arrow_column {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl FromArrowColumn for $ty {
+                fn from_arrow_column(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+                    if array.is_null(index) {
+                        return Err(Error::UnexpectedNull {
+                            column: column.to_string(),
+                            rust_type: type_name::<Self>(),
+                        });
+                    }
+                    Self::from_arrow_value(column, array, index)
+                }
+            }
+
+            impl FromArrowColumn for Option<$ty> {
+                fn from_arrow_column(column: &str, array: &dyn Array, index: usize) -> Result<Self> {
+                    if array.is_null(index) {
+                        return Ok(None);
+                    }
+                    <$ty>::from_arrow_value(column, array, index).map(Some)
+                }
+            }
+        )*
+    };
+}
+
+from_arrow_column! {
+    bool, i16, i32, i64, f32, f64, String, Vec<u8>, Date, Timestamp, TimestampWithTimeZone, AnyNumeric,
+    Vec<Option<bool>>, Vec<Option<i16>>, Vec<Option<i32>>, Vec<Option<i64>>, Vec<Option<f32>>,
+    Vec<Option<f64>>, Vec<Option<String>>, Vec<Option<Vec<u8>>>, Vec<Option<Date>>,
+    Vec<Option<Timestamp>>, Vec<Option<TimestampWithTimeZone>>, Vec<Option<AnyNumeric>>,
+}
+
+macro_rules! from_arrow_row {
+    ($count:literal => $($ty:ident $index:tt),+) => {
+        impl<$($ty: FromArrowColumn),+> FromArrowRow for ($($ty,)+) {
+            const COLUMNS: usize = $count;
+
+            fn from_arrow_row(batch: &RecordBatch, index: usize) -> Result<Self> {
+                let schema = batch.schema();
+                Ok(($(
+                    $ty::from_arrow_column(
+                        schema.field($index).name(),
+                        batch.column($index).as_ref(),
+                        index,
+                    )?,
+                )+))
+            }
+        }
+    };
+}
+
+from_arrow_row!(1 => A 0);
+from_arrow_row!(2 => A 0, B 1);
+from_arrow_row!(3 => A 0, B 1, C 2);
+from_arrow_row!(4 => A 0, B 1, C 2, D 3);
+from_arrow_row!(5 => A 0, B 1, C 2, D 3, E 4);
+from_arrow_row!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+from_arrow_row!(7 => A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+from_arrow_row!(8 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+from_arrow_row!(9 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+from_arrow_row!(10 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+from_arrow_row!(11 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+from_arrow_row!(12 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+from_arrow_row!(13 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+from_arrow_row!(14 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+from_arrow_row!(15 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+from_arrow_row!(16 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
@@ -6,32 +6,213 @@ All rights reserved.
 
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
+
+//! Atomics in shared memory, which every backend can update without taking a [`PgLwLock`](crate::PgLwLock)
+use crate::{pg_sys, ShmemVersion};
 use once_cell::sync::OnceCell;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
+/// An atomic that lives in shared memory:  a [`PgAtomicU32`] or [`PgAtomicU64`], which are
+/// Postgres' own `pg_atomic_uint32` and `pg_atomic_uint64`, or a Rust atomic, such as an
+/// [`AtomicBool`](std::sync::atomic::AtomicBool)
+///
+/// It's for a counter or flag that every backend updates, without the cost of a lock.  Rust's
+/// atomics are lock-free on every platform Postgres supports, so they're just as safe to share
+/// between processes.
+///
+/// It's attached to shared memory by [`pg_shmem_init!()`](crate::pg_shmem_init), so the extension
+/// has to be loaded through `shared_preload_libraries`.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::{pg_shmem_init, PgAtomic, PgAtomicU64, PgSharedMemoryInitialization};
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// static CALLS: PgAtomic<PgAtomicU64> = PgAtomic::new();
+/// static ENABLED: PgAtomic<AtomicBool> = PgAtomic::new();
+///
+/// #[pg_init]
+/// fn init_shmem() {
+///     pg_shmem_init!(CALLS);
+///     pg_shmem_init!(ENABLED);
+/// }
+///
+/// #[pg_extern(parallel_safe)]
+/// fn count_call() -> Option<i64> {
+///     if ENABLED.get().load(Ordering::Relaxed) {
+///         Some(CALLS.get().add_fetch(1) as i64)
+///     } else {
+///         None
+///     }
+/// }
+/// ```
 pub struct PgAtomic<T> {
     inner: OnceCell<*mut T>,
+    name: OnceCell<&'static str>,
+    version: OnceCell<ShmemVersion>,
 }
 
 impl<T> PgAtomic<T> {
     pub const fn new() -> Self {
-        Self { inner: OnceCell::new() }
+        Self { inner: OnceCell::new(), name: OnceCell::new(), version: OnceCell::new() }
+    }
+
+    /// The name its shared memory is found by, if `pg_shmem_init!()` gave it one
+    pub fn name(&self) -> Option<&'static str> {
+        self.name.get().copied()
+    }
+
+    /// The version its shared memory is laid out as, which is [`ShmemVersion::current()`] unless
+    /// `pg_shmem_init!()` was given another
+    pub fn shmem_version(&self) -> ShmemVersion {
+        self.version.get().copied().unwrap_or_else(ShmemVersion::current::<T>)
+    }
+
+    /// Name it, and the version of its shared memory, before it's first used
+    pub(crate) fn identify(&self, name: &'static str, version: Option<ShmemVersion>) {
+        let _ = self.name.set(name);
+        if let Some(version) = version {
+            let _ = self.version.set(version);
+        }
     }
 }
 
 impl<T> PgAtomic<T>
 where
-    T: atomic_traits::Atomic + Default,
+    T: PgAtomicValue,
 {
+    /// Point it at its value in shared memory, which `pg_shmem_init!()` does
     pub fn attach(&self, value: *mut T) {
         self.inner.set(value).expect("This PgAtomic is not empty, can't re-attach");
     }
 
+    /// The atomic in shared memory, which every backend shares
+    ///
+    /// # Panics
+    ///
+    /// If it hasn't been attached to shared memory by `pg_shmem_init!()`.
     pub fn get(&self) -> &T {
         unsafe {
-            self.inner.get().expect("This PgAtomic as not been initialized").as_ref().unwrap()
+            self.inner.get().expect("This PgAtomic has not been initialized").as_ref().unwrap()
         }
     }
 }
 
-unsafe impl<T> Send for PgAtomic<T> where T: atomic_traits::Atomic + Default {}
-unsafe impl<T> Sync for PgAtomic<T> where T: atomic_traits::Atomic + Default {}
+unsafe impl<T> Send for PgAtomic<T> where T: PgAtomicValue {}
+unsafe impl<T> Sync for PgAtomic<T> where T: PgAtomicValue {}
+
+/// What a [`PgAtomic`] can hold:  a value every backend can update at once, without a lock
+///
+/// # Safety
+///
+/// Every way to change it through a `&` reference must be atomic across processes, and its
+/// `Default` must be its initial value in shared memory.
+pub unsafe trait PgAtomicValue: Default {}
+
+unsafe impl<T> PgAtomicValue for T where T: atomic_traits::Atomic + Default {}
+
+macro_rules! pg_atomic {
+    ($name:ident, $pg_type:ident, $atomic:ident, $unsigned:ty, $signed:ty, $suffix:literal) => {
+        #[doc = concat!("Postgres' `", stringify!($pg_type), "`, for a [`PgAtomic`]")]
+        ///
+        /// Its operations are those of the `pg_atomic_*` functions, which are inline in Postgres'
+        /// headers, so they're made here from a Rust atomic laid out the same way.  As in
+        /// Postgres, reads and writes are unordered, and every other operation is a full barrier.
+        /// C code that's given it by [`Self::as_ptr()`] can use the `pg_atomic_*` functions on
+        /// the same value.
+        #[repr(transparent)]
+        #[derive(Default)]
+        pub struct $name(UnsafeCell<pg_sys::$pg_type>);
+
+        // the Rust atomic can stand in for Postgres' only if it's laid out the same way
+        const _: () = assert!(
+            std::mem::size_of::<pg_sys::$pg_type>() == std::mem::size_of::<$atomic>()
+                && std::mem::align_of::<pg_sys::$pg_type>() >= std::mem::align_of::<$atomic>()
+        );
+
+        // SAFETY:  it's only ever changed atomically
+        unsafe impl Sync for $name {}
+        unsafe impl PgAtomicValue for $name {}
+
+        impl $name {
+            pub const fn new(value: $unsigned) -> Self {
+                Self(UnsafeCell::new(pg_sys::$pg_type { value }))
+            }
+
+            fn atomic(&self) -> &$atomic {
+                // SAFETY:  they're laid out the same way, and it's only ever changed atomically
+                unsafe { &*(self.0.get() as *const $atomic) }
+            }
+
+            /// Postgres' `pg_atomic_uint*`, for C code that uses the `pg_atomic_*` functions
+            pub fn as_ptr(&self) -> *mut pg_sys::$pg_type {
+                self.0.get()
+            }
+
+            #[doc = concat!("Its value, as `pg_atomic_read_", $suffix, "()` reads it")]
+            pub fn read(&self) -> $unsigned {
+                self.atomic().load(Ordering::Relaxed)
+            }
+
+            #[doc = concat!("Set its value, as `pg_atomic_write_", $suffix, "()` does")]
+            pub fn write(&self, value: $unsigned) {
+                self.atomic().store(value, Ordering::Relaxed)
+            }
+
+            #[doc = concat!("Set its value, returning the old one, as `pg_atomic_exchange_", $suffix, "()` does")]
+            pub fn exchange(&self, value: $unsigned) -> $unsigned {
+                self.atomic().swap(value, Ordering::SeqCst)
+            }
+
+            /// Set its value to `new` if it's `expected`, returning `Ok` with the old value if it
+            #[doc = concat!("was, or `Err` with its value if it wasn't, as `pg_atomic_compare_exchange_", $suffix, "()` does")]
+            pub fn compare_exchange(
+                &self,
+                expected: $unsigned,
+                new: $unsigned,
+            ) -> Result<$unsigned, $unsigned> {
+                self.atomic().compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            #[doc = concat!("Add to it, returning the old value, as `pg_atomic_fetch_add_", $suffix, "()` does")]
+            pub fn fetch_add(&self, add: $signed) -> $unsigned {
+                self.atomic().fetch_add(add as $unsigned, Ordering::SeqCst)
+            }
+
+            #[doc = concat!("Subtract from it, returning the old value, as `pg_atomic_fetch_sub_", $suffix, "()` does")]
+            pub fn fetch_sub(&self, sub: $signed) -> $unsigned {
+                self.atomic().fetch_sub(sub as $unsigned, Ordering::SeqCst)
+            }
+
+            #[doc = concat!("`&` it with `and`, returning the old value, as `pg_atomic_fetch_and_", $suffix, "()` does")]
+            pub fn fetch_and(&self, and: $unsigned) -> $unsigned {
+                self.atomic().fetch_and(and, Ordering::SeqCst)
+            }
+
+            #[doc = concat!("`|` it with `or`, returning the old value, as `pg_atomic_fetch_or_", $suffix, "()` does")]
+            pub fn fetch_or(&self, or: $unsigned) -> $unsigned {
+                self.atomic().fetch_or(or, Ordering::SeqCst)
+            }
+
+            #[doc = concat!("Add to it, returning the new value, as `pg_atomic_add_fetch_", $suffix, "()` does")]
+            pub fn add_fetch(&self, add: $signed) -> $unsigned {
+                self.fetch_add(add).wrapping_add(add as $unsigned)
+            }
+
+            #[doc = concat!("Subtract from it, returning the new value, as `pg_atomic_sub_fetch_", $suffix, "()` does")]
+            pub fn sub_fetch(&self, sub: $signed) -> $unsigned {
+                self.fetch_sub(sub).wrapping_sub(sub as $unsigned)
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($name)).field(&self.read()).finish()
+            }
+        }
+    };
+}
+
+pg_atomic!(PgAtomicU32, pg_atomic_uint32, AtomicU32, u32, i32, "u32");
+pg_atomic!(PgAtomicU64, pg_atomic_uint64, AtomicU64, u64, i64, "u64");
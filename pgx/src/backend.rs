@@ -0,0 +1,329 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! What every backend is doing, as `pg_stat_activity` shows, without querying it through SPI
+//!
+//! [`iterate()`] reads the same shared backend status array that `pg_stat_activity` does, and the
+//! wait events from each backend's `PGPROC`.  Like `pg_stat_activity`, it's a snapshot taken the
+//! first time it's read in a transaction, and the same snapshot is returned for the rest of it,
+//! until `pg_sys::pgstat_clear_snapshot()`.
+//!
+//! ```rust,no_run
+//! use pgx::backend;
+//!
+//! for info in backend::iterate() {
+//!     if info.pid != backend::my_pid() && info.application_name == "runaway" {
+//!         info.cancel().ok();
+//!     }
+//! }
+//! ```
+use crate::{pg_sys, TimestampWithTimeZone};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+/// The process ID of the current backend
+pub fn my_pid() -> i32 {
+    unsafe { pg_sys::MyProcPid }
+}
+
+/// The current backend's ID, its slot in shared memory's per-backend arrays, which is
+/// `pg_sys::InvalidBackendId` in a process that isn't a backend
+pub fn my_backend_id() -> i32 {
+    unsafe { pg_sys::MyBackendId }
+}
+
+/// What a backend is doing, as `pg_stat_activity.state` shows it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendState {
+    Active,
+    Idle,
+    IdleInTransaction,
+    IdleInTransactionAborted,
+    FastpathFunctionCall,
+    /// `track_activities` is off for this backend
+    Disabled,
+    /// Not a client backend, or just starting
+    Undefined,
+}
+
+impl BackendState {
+    fn from_pg(state: pg_sys::BackendState) -> Self {
+        match state {
+            pg_sys::BackendState_STATE_RUNNING => BackendState::Active,
+            pg_sys::BackendState_STATE_IDLE => BackendState::Idle,
+            pg_sys::BackendState_STATE_IDLEINTRANSACTION => BackendState::IdleInTransaction,
+            pg_sys::BackendState_STATE_IDLEINTRANSACTION_ABORTED => {
+                BackendState::IdleInTransactionAborted
+            }
+            pg_sys::BackendState_STATE_FASTPATH => BackendState::FastpathFunctionCall,
+            pg_sys::BackendState_STATE_DISABLED => BackendState::Disabled,
+            _ => BackendState::Undefined,
+        }
+    }
+
+    /// The state's name in `pg_stat_activity`, or `None` if it shows `NULL`
+    pub fn as_str(&self) -> Option<&'static str> {
+        match self {
+            BackendState::Active => Some("active"),
+            BackendState::Idle => Some("idle"),
+            BackendState::IdleInTransaction => Some("idle in transaction"),
+            BackendState::IdleInTransactionAborted => Some("idle in transaction (aborted)"),
+            BackendState::FastpathFunctionCall => Some("fastpath function call"),
+            BackendState::Disabled => Some("disabled"),
+            BackendState::Undefined => None,
+        }
+    }
+}
+
+/// One backend, or other server process, as a row of `pg_stat_activity` shows it
+///
+/// The fields that `pg_stat_activity` hides from a user who isn't a member of the backend's role
+/// or of `pg_read_all_stats` are `None` here too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendInfo {
+    pub pid: i32,
+    /// `None` for processes that aren't backends, like the checkpointer
+    pub backend_id: Option<i32>,
+    /// Like `client backend` or `autovacuum worker`
+    pub backend_type: String,
+    pub database_oid: Option<pg_sys::Oid>,
+    pub database_name: Option<String>,
+    pub user_oid: Option<pg_sys::Oid>,
+    pub user_name: Option<String>,
+    pub application_name: String,
+    pub client_hostname: Option<String>,
+    pub state: Option<BackendState>,
+    pub wait_event_type: Option<String>,
+    pub wait_event: Option<String>,
+    /// The current or last query, truncated to `track_activity_query_size`
+    pub query: Option<String>,
+    /// Always `None` before Postgres 14
+    pub query_id: Option<i64>,
+    pub backend_start: Option<TimestampWithTimeZone>,
+    pub xact_start: Option<TimestampWithTimeZone>,
+    pub query_start: Option<TimestampWithTimeZone>,
+    pub state_change: Option<TimestampWithTimeZone>,
+    pub backend_xid: Option<pg_sys::TransactionId>,
+    pub backend_xmin: Option<pg_sys::TransactionId>,
+    pub ssl: bool,
+    /// Always `None` before Postgres 12
+    pub gss: Option<bool>,
+}
+
+/// Why a backend couldn't be signalled
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum SignalError {
+    #[error("PID {0} is not a PostgreSQL backend process")]
+    NotABackend(i32),
+    #[error("permission denied to signal backend process {0}")]
+    PermissionDenied(i32),
+    #[error("could not send signal to process {pid}: {error}")]
+    Failed { pid: i32, error: String },
+}
+
+impl BackendInfo {
+    /// Ask the backend to exit, as `pg_terminate_backend()` does
+    ///
+    /// The current user must be a member of the backend's role or of `pg_signal_backend`, and
+    /// only a superuser can signal a superuser's backend, or a process without a role.
+    pub fn terminate(&self) -> Result<(), SignalError> {
+        signal_backend(self.pid, libc::SIGTERM)
+    }
+
+    /// Cancel the backend's current query, as `pg_cancel_backend()` does.  It needs the same
+    /// permissions as [`terminate()`](Self::terminate).
+    pub fn cancel(&self) -> Result<(), SignalError> {
+        signal_backend(self.pid, libc::SIGINT)
+    }
+}
+
+/// A snapshot of every backend, and other server process, `pg_stat_activity` would show
+///
+/// Names are looked up in the catalogs, so this must be called in a transaction.
+pub fn iterate() -> impl Iterator<Item = BackendInfo> {
+    let count = unsafe { pg_sys::pgstat_fetch_stat_numbackends() };
+    (1..=count)
+        .filter_map(|beid| unsafe {
+            // SAFETY:  this is a copy local to this backend, kept until the transaction ends
+            let local = pg_sys::pgstat_fetch_stat_local_beentry(beid as c_int);
+            local.as_ref().map(|local| backend_info(local))
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+unsafe fn backend_info(local: &pg_sys::LocalPgBackendStatus) -> BackendInfo {
+    let status = &local.backendStatus;
+    let pid = status.st_procpid;
+
+    let mut proc_ = pg_sys::BackendPidGetProc(pid);
+    if proc_.is_null() && status.st_backendType != pg_sys::BackendType_B_BACKEND {
+        proc_ = pg_sys::AuxiliaryPidGetProc(pid);
+    }
+    let backend_id = proc_
+        .as_ref()
+        .map(|proc_| proc_.backendId)
+        .filter(|backend_id| *backend_id != pg_sys::InvalidBackendId);
+
+    let database_oid = Some(status.st_databaseid).filter(|oid| *oid != pg_sys::InvalidOid);
+    let user_oid = Some(status.st_userid).filter(|oid| *oid != pg_sys::InvalidOid);
+    let permitted = has_stats_permission(status.st_userid);
+
+    let (wait_event_type, wait_event) = match proc_.as_ref() {
+        Some(proc_) if permitted => {
+            // the backend updates it without a lock, as pg_stat_activity reads it
+            let wait_event_info = std::ptr::read_volatile(&proc_.wait_event_info);
+            (
+                copy_cstr(pg_sys::pgstat_get_wait_event_type(wait_event_info)),
+                copy_cstr(pg_sys::pgstat_get_wait_event(wait_event_info)),
+            )
+        }
+        _ => (None, None),
+    };
+
+    let query = if permitted && !status.st_activity_raw.is_null() {
+        let clipped = pg_sys::pgstat_clip_activity(status.st_activity_raw);
+        let query = copy_cstr(clipped);
+        pg_sys::pfree(clipped.cast());
+        query
+    } else {
+        None
+    };
+
+    BackendInfo {
+        pid,
+        backend_id,
+        backend_type: copy_cstr(backend_type_desc(status.st_backendType)).unwrap_or_default(),
+        database_oid,
+        database_name: database_oid.and_then(|oid| palloced_cstr(pg_sys::get_database_name(oid))),
+        user_oid,
+        user_name: user_oid.and_then(|oid| palloced_cstr(pg_sys::GetUserNameFromId(oid, true))),
+        application_name: copy_cstr(status.st_appname).unwrap_or_default(),
+        client_hostname: if permitted {
+            copy_cstr(status.st_clienthostname).filter(|hostname| !hostname.is_empty())
+        } else {
+            None
+        },
+        state: permitted.then(|| BackendState::from_pg(status.st_state)),
+        wait_event_type,
+        wait_event,
+        query,
+        query_id: if permitted { query_id(status) } else { None },
+        backend_start: timestamp(status.st_proc_start_timestamp),
+        xact_start: if permitted { timestamp(status.st_xact_start_timestamp) } else { None },
+        query_start: if permitted { timestamp(status.st_activity_start_timestamp) } else { None },
+        state_change: if permitted { timestamp(status.st_state_start_timestamp) } else { None },
+        backend_xid: Some(local.backend_xid).filter(|xid| *xid != pg_sys::InvalidTransactionId),
+        backend_xmin: Some(local.backend_xmin).filter(|xid| *xid != pg_sys::InvalidTransactionId),
+        ssl: status.st_ssl,
+        gss: gss(status),
+    }
+}
+
+/// Can the current user see everything about a backend of `role`?
+unsafe fn has_stats_permission(role: pg_sys::Oid) -> bool {
+    #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+    let read_all_stats = pg_sys::Oid::from_u32_unchecked(pg_sys::DEFAULT_ROLE_READ_ALL_STATS);
+    #[cfg(any(feature = "pg14", feature = "pg15"))]
+    let read_all_stats = pg_sys::Oid::from_u32_unchecked(pg_sys::ROLE_PG_READ_ALL_STATS);
+
+    let user = pg_sys::GetUserId();
+    pg_sys::has_privs_of_role(user, read_all_stats) || pg_sys::has_privs_of_role(user, role)
+}
+
+fn signal_backend(pid: i32, signal: c_int) -> Result<(), SignalError> {
+    #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+    let signal_backend =
+        unsafe { pg_sys::Oid::from_u32_unchecked(pg_sys::DEFAULT_ROLE_SIGNAL_BACKENDID) };
+    #[cfg(any(feature = "pg14", feature = "pg15"))]
+    let signal_backend = unsafe { pg_sys::Oid::from_u32_unchecked(pg_sys::ROLE_PG_SIGNAL_BACKEND) };
+
+    unsafe {
+        let proc_ = pg_sys::BackendPidGetProc(pid);
+        let role = match proc_.as_ref() {
+            Some(proc_) => proc_.roleId,
+            None => return Err(SignalError::NotABackend(pid)),
+        };
+
+        // a process without a role might be as important as a superuser's
+        if (role == pg_sys::InvalidOid || pg_sys::superuser_arg(role)) && !pg_sys::superuser() {
+            return Err(SignalError::PermissionDenied(pid));
+        }
+        let user = pg_sys::GetUserId();
+        if !pg_sys::has_privs_of_role(user, role)
+            && !pg_sys::has_privs_of_role(user, signal_backend)
+        {
+            return Err(SignalError::PermissionDenied(pid));
+        }
+
+        // backends lead their own process group, so this signals any children too, as
+        // pg_signal_backend() does
+        if libc::kill(-pid, signal) != 0 {
+            return Err(SignalError::Failed {
+                pid,
+                error: std::io::Error::last_os_error().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "pg11", feature = "pg12"))]
+unsafe fn backend_type_desc(backend_type: pg_sys::BackendType) -> *const c_char {
+    pg_sys::pgstat_get_backend_desc(backend_type)
+}
+
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+unsafe fn backend_type_desc(backend_type: pg_sys::BackendType) -> *const c_char {
+    pg_sys::GetBackendTypeDesc(backend_type)
+}
+
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+fn query_id(_status: &pg_sys::PgBackendStatus) -> Option<i64> {
+    None
+}
+
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+fn query_id(status: &pg_sys::PgBackendStatus) -> Option<i64> {
+    Some(status.st_query_id as i64).filter(|query_id| *query_id != 0)
+}
+
+#[cfg(feature = "pg11")]
+fn gss(_status: &pg_sys::PgBackendStatus) -> Option<bool> {
+    None
+}
+
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+fn gss(status: &pg_sys::PgBackendStatus) -> Option<bool> {
+    Some(status.st_gss)
+}
+
+fn timestamp(timestamp: pg_sys::TimestampTz) -> Option<TimestampWithTimeZone> {
+    if timestamp == 0 {
+        None
+    } else {
+        TimestampWithTimeZone::try_from(timestamp).ok()
+    }
+}
+
+unsafe fn copy_cstr(cstr: *const c_char) -> Option<String> {
+    if cstr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(cstr).to_string_lossy().into_owned())
+    }
+}
+
+unsafe fn palloced_cstr(cstr: *mut c_char) -> Option<String> {
+    let copy = copy_cstr(cstr);
+    if !cstr.is_null() {
+        pg_sys::pfree(cstr.cast());
+    }
+    copy
+}
@@ -0,0 +1,381 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Per-backend statics, initialized when they're first used, which can start over when the session
+//! is reset
+//!
+//! A `static` cache lives as long as the backend does.  A connection pooler hands the same backend
+//! to one client after another, and resets the session in between with `DISCARD ALL`, which leaves
+//! an ordinary static as the last client left it.  A [`PgBackendLocal`] can be reset then, and when
+//! a transaction aborts, after which it's initialized again when it's next used.
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::PgBackendLocal;
+//! use std::collections::HashMap;
+//!
+//! static LOOKUPS: PgBackendLocal<HashMap<String, i64>> =
+//!     PgBackendLocal::new(HashMap::new).reset_on_discard_all();
+//!
+//! #[pg_extern]
+//! fn cached_length(key: &str) -> i64 {
+//!     LOOKUPS.with_mut(|lookups| *lookups.entry(key.to_string()).or_insert(key.len() as i64))
+//! }
+//! ```
+//!
+//! Anything else the session holds on to, like prepared plans, can be dropped with a closure
+//! registered by [`on_discard()`], and state derived from settings can be recomputed after
+//! `RESET ALL` with one registered by [`on_reset_all()`]:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::Discard;
+//!
+//! #[pg_init]
+//! fn init() {
+//!     pgx::on_discard(|discard| {
+//!         if discard.includes(Discard::Plans) {
+//!             // forget any prepared plans
+//!         }
+//!     });
+//! }
+//! ```
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::{is_a, pg_guard, pg_sys};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+static mut REGISTERED: Vec<&'static dyn Resettable> = Vec::new();
+static mut INSTALLED: bool = false;
+
+static mut PREV_PROCESS_UTILITY: pg_sys::ProcessUtility_hook_type = None;
+
+thread_local! {
+    static DISCARD_CALLBACKS: RefCell<Vec<Rc<dyn Fn(Discard)>>> = RefCell::new(Vec::new());
+    static RESET_ALL_CALLBACKS: RefCell<Vec<Rc<dyn Fn()>>> = RefCell::new(Vec::new());
+}
+
+/// What a `DISCARD` statement discarded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Discard {
+    /// `DISCARD ALL`, which discards everything the others do, and is also a `RESET ALL`
+    All,
+    /// `DISCARD PLANS`
+    Plans,
+    /// `DISCARD SEQUENCES`
+    Sequences,
+    /// `DISCARD TEMP`
+    Temp,
+}
+
+impl Discard {
+    /// Did this discard what `other` does?  `DISCARD ALL` includes every other kind
+    pub fn includes(self, other: Discard) -> bool {
+        self == Discard::All || self == other
+    }
+}
+
+/// Call `f` after each `DISCARD` statement the backend runs, with what it discarded
+///
+/// Connection poolers run `DISCARD ALL` between clients, after which nothing the last client left
+/// in the session should be used.  `f` is called once the statement has succeeded, and shouldn't
+/// do more than forget what was discarded.
+pub fn on_discard<F: Fn(Discard) + 'static>(f: F) {
+    DISCARD_CALLBACKS.with(|callbacks| callbacks.borrow_mut().push(Rc::new(f)));
+    // SAFETY:  a backend only has the one thread
+    unsafe { ensure_installed() }
+}
+
+/// Call `f` after each `RESET ALL` the backend runs, including the one `DISCARD ALL` does, so
+/// that state derived from the session's settings can be recomputed
+///
+/// `f` isn't called again should the transaction the `RESET ALL` ran in roll back, and with it the
+/// settings, so it should only forget what it has cached.
+pub fn on_reset_all<F: Fn() + 'static>(f: F) {
+    RESET_ALL_CALLBACKS.with(|callbacks| callbacks.borrow_mut().push(Rc::new(f)));
+    // SAFETY:  a backend only has the one thread
+    unsafe { ensure_installed() }
+}
+
+/// A value that's private to the backend, made by `init` when it's first used
+///
+/// Postgres runs each backend in a single thread, and that's the only thread it may be used from.
+pub struct PgBackendLocal<T> {
+    value: RefCell<Option<T>>,
+    init: fn() -> T,
+    reset_on_discard_all: bool,
+    reset_on_reset_all: bool,
+    reset_on_abort: bool,
+    registered: Cell<bool>,
+}
+
+// SAFETY:  a backend only has the one thread, which Postgres requires it be used from
+unsafe impl<T> Sync for PgBackendLocal<T> {}
+
+impl<T: 'static> PgBackendLocal<T> {
+    /// A value that `init` makes when it's first used
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            value: RefCell::new(None),
+            init,
+            reset_on_discard_all: false,
+            reset_on_reset_all: false,
+            reset_on_abort: false,
+            registered: Cell::new(false),
+        }
+    }
+
+    /// Reset it when the session is reset with `DISCARD ALL`, as connection poolers do between
+    /// clients
+    pub const fn reset_on_discard_all(mut self) -> Self {
+        self.reset_on_discard_all = true;
+        self
+    }
+
+    /// Reset it after `RESET ALL`, and `DISCARD ALL`, for a value that's derived from the
+    /// session's settings
+    pub const fn reset_on_reset_all(mut self) -> Self {
+        self.reset_on_reset_all = true;
+        self
+    }
+
+    /// Reset it when a transaction aborts, so nothing that transaction put in it outlives it
+    pub const fn reset_on_abort(mut self) -> Self {
+        self.reset_on_abort = true;
+        self
+    }
+
+    /// Call `f` with the value, which is initialized first if it has to be
+    ///
+    /// # Panics
+    ///
+    /// If it's already being changed by [`with_mut()`](Self::with_mut), from further up the stack.
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        self.initialize();
+        f(self.value.borrow().as_ref().expect("a PgBackendLocal was reset while it was in use"))
+    }
+
+    /// Call `f` with the value, mutably, which is initialized first if it has to be
+    ///
+    /// # Panics
+    ///
+    /// If it's already in use, from further up the stack.
+    pub fn with_mut<R>(&'static self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.initialize();
+        f(self.value.borrow_mut().as_mut().expect("a PgBackendLocal was reset while it was in use"))
+    }
+
+    /// Has it been initialized since it was last reset?
+    pub fn is_initialized(&self) -> bool {
+        self.value.borrow().is_some()
+    }
+
+    /// Drop the value, so that it's initialized again when it's next used
+    ///
+    /// # Panics
+    ///
+    /// If it's in use, from further up the stack.
+    pub fn reset(&self) {
+        // the value is dropped after the borrow ends, in case its `Drop` uses it
+        let value = self.value.borrow_mut().take();
+        drop(value);
+    }
+
+    fn initialize(&'static self) {
+        if self.value.borrow().is_some() {
+            return;
+        }
+        let value = (self.init)();
+        *self.value.borrow_mut() = Some(value);
+
+        if !self.registered.replace(true)
+            && (self.reset_on_discard_all || self.reset_on_reset_all || self.reset_on_abort)
+        {
+            // SAFETY:  a backend only has the one thread
+            unsafe {
+                REGISTERED.push(self);
+                ensure_installed();
+            }
+        }
+    }
+}
+
+/// Why the [`PgBackendLocal`]s that asked to be are reset
+#[derive(Copy, Clone)]
+enum ResetEvent {
+    DiscardAll,
+    ResetAll,
+    Abort,
+}
+
+trait Resettable {
+    fn reset_for(&self, event: ResetEvent);
+}
+
+impl<T: 'static> Resettable for PgBackendLocal<T> {
+    fn reset_for(&self, event: ResetEvent) {
+        let reset = match event {
+            ResetEvent::DiscardAll => self.reset_on_discard_all,
+            ResetEvent::ResetAll => self.reset_on_reset_all,
+            ResetEvent::Abort => self.reset_on_abort,
+        };
+        // a value that's still in use is left alone, rather than panicking in a callback
+        if reset && self.value.try_borrow_mut().is_ok() {
+            self.reset();
+        }
+    }
+}
+
+fn reset_locals(event: ResetEvent) {
+    // SAFETY:  a backend only has the one thread, and a value's `Drop` can't register another
+    // value, as registering happens when a value is first initialized
+    for local in unsafe { REGISTERED.iter() } {
+        local.reset_for(event);
+    }
+}
+
+unsafe fn ensure_installed() {
+    if !INSTALLED {
+        PREV_PROCESS_UTILITY = pg_sys::ProcessUtility_hook.replace(process_utility);
+        pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut());
+        INSTALLED = true;
+    }
+}
+
+/// A utility statement that resets part of the session
+#[derive(Copy, Clone)]
+enum SessionReset {
+    Discard(Discard),
+    ResetAll,
+}
+
+unsafe fn session_reset(pstmt: *mut pg_sys::PlannedStmt) -> Option<SessionReset> {
+    let stmt = (*pstmt).utilityStmt;
+    if stmt.is_null() {
+        None
+    } else if is_a(stmt, pg_sys::NodeTag_T_DiscardStmt) {
+        match (*(stmt as *mut pg_sys::DiscardStmt)).target {
+            pg_sys::DiscardMode_DISCARD_ALL => Some(SessionReset::Discard(Discard::All)),
+            pg_sys::DiscardMode_DISCARD_PLANS => Some(SessionReset::Discard(Discard::Plans)),
+            pg_sys::DiscardMode_DISCARD_SEQUENCES => {
+                Some(SessionReset::Discard(Discard::Sequences))
+            }
+            pg_sys::DiscardMode_DISCARD_TEMP => Some(SessionReset::Discard(Discard::Temp)),
+            _ => None,
+        }
+    } else if is_a(stmt, pg_sys::NodeTag_T_VariableSetStmt)
+        && (*(stmt as *mut pg_sys::VariableSetStmt)).kind == pg_sys::VariableSetKind_VAR_RESET_ALL
+    {
+        Some(SessionReset::ResetAll)
+    } else {
+        None
+    }
+}
+
+/// Tell everything that asked about the statement that just succeeded
+fn after_session_reset(reset: SessionReset) {
+    if let SessionReset::Discard(discard) = reset {
+        if discard == Discard::All {
+            reset_locals(ResetEvent::DiscardAll);
+        }
+        // the list is copied so the callbacks can register more
+        for callback in DISCARD_CALLBACKS.with(|callbacks| callbacks.borrow().clone()) {
+            callback(discard);
+        }
+    }
+    if matches!(reset, SessionReset::ResetAll | SessionReset::Discard(Discard::All)) {
+        reset_locals(ResetEvent::ResetAll);
+        for callback in RESET_ALL_CALLBACKS.with(|callbacks| callbacks.borrow().clone()) {
+            callback();
+        }
+    }
+}
+
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+#[pg_guard]
+unsafe extern "C" fn process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const ::std::os::raw::c_char,
+    context: pg_sys::ProcessUtilityContext,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    completion_tag: *mut pg_sys::QueryCompletion,
+) {
+    let reset = session_reset(pstmt);
+    match PREV_PROCESS_UTILITY {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| {
+            prev(pstmt, query_string, context, params, query_env, dest, completion_tag)
+        }),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            context,
+            params,
+            query_env,
+            dest,
+            completion_tag,
+        ),
+    }
+    if let Some(reset) = reset {
+        after_session_reset(reset);
+    }
+}
+
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+#[pg_guard]
+unsafe extern "C" fn process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const ::std::os::raw::c_char,
+    read_only_tree: bool,
+    context: pg_sys::ProcessUtilityContext,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    completion_tag: *mut pg_sys::QueryCompletion,
+) {
+    let reset = session_reset(pstmt);
+    match PREV_PROCESS_UTILITY {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| {
+            prev(
+                pstmt,
+                query_string,
+                read_only_tree,
+                context,
+                params,
+                query_env,
+                dest,
+                completion_tag,
+            )
+        }),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            read_only_tree,
+            context,
+            params,
+            query_env,
+            dest,
+            completion_tag,
+        ),
+    }
+    if let Some(reset) = reset {
+        after_session_reset(reset);
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn xact_callback(event: pg_sys::XactEvent, _arg: *mut std::os::raw::c_void) {
+    if matches!(
+        event,
+        pg_sys::XactEvent_XACT_EVENT_ABORT | pg_sys::XactEvent_XACT_EVENT_PARALLEL_ABORT
+    ) {
+        reset_locals(ResetEvent::Abort);
+    }
+}
@@ -0,0 +1,302 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Crash reports for [`BackgroundWorker`]s that panic
+//!
+//! A panicking worker otherwise leaves only the panic's message in the server log before it
+//! exits, and perhaps restarts.  Once [`BackgroundWorker::set_crash_report_dir()`] has been
+//! called, a panic also writes a JSON report to that directory with the worker's name, the
+//! message, where it panicked, a backtrace, and the last [`MAX_BREADCRUMBS`] messages left with
+//! [`breadcrumb!()`](crate::breadcrumb):
+//!
+//! ```rust,no_run
+//! use pgx::bgworkers::*;
+//! use pgx::prelude::*;
+//!
+//! #[pg_guard]
+//! #[no_mangle]
+//! pub extern "C" fn importer_main(_arg: pg_sys::Datum) {
+//!     // in the data directory, unless it's an absolute path
+//!     BackgroundWorker::set_crash_report_dir("pg_log/importer_crashes");
+//!     BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+//!     BackgroundWorker::connect_worker_to_spi(Some("postgres"), None);
+//!
+//!     for batch in 1.. {
+//!         pgx::breadcrumb!("importing batch {}", batch);
+//!         // ...
+//!         # break;
+//!     }
+//! }
+//! ```
+//!
+//! The report is named `<worker name>-<milliseconds since 1970>-<pid>.json`, and its path is
+//! logged at `LOG`.  If it can't be written, the report itself is logged instead.  A worker that
+//! keeps crashing as it restarts writes no more than
+//! [`set_crash_report_limit()`](BackgroundWorker::set_crash_report_limit) reports a minute, so
+//! that it doesn't fill the disk:  the ones after that are only mentioned in the log.
+//!
+//! The panic hook can't know whether a panic is going to be caught, so panics the worker
+//! recovers from, such as those of [`run_scheduled()`](crate::bgworkers::run_scheduled)'s jobs,
+//! are reported too.
+use crate::bgworkers::BackgroundWorker;
+use crate::pg_sys;
+use crate::pg_sys::log;
+use pgx_pg_sys::panic::{CaughtError, ErrorReport, ErrorReportWithLevel};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::panic::PanicInfo;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How many of the latest [`breadcrumb!()`](crate::breadcrumb)s a crash report has
+pub const MAX_BREADCRUMBS: usize = 32;
+
+/// How many crash reports a minute a worker writes unless
+/// [`BackgroundWorker::set_crash_report_limit()`] says otherwise
+pub const DEFAULT_CRASH_REPORTS_PER_MINUTE: usize = 5;
+
+/// Leave a breadcrumb, a message saying what the worker is doing, for its crash report
+///
+/// Only the last [`MAX_BREADCRUMBS`] are kept, and they cost no more than formatting the message
+/// whether or not the worker writes crash reports.
+///
+/// ```rust,no_run
+/// # let table = "orders";
+/// pgx::breadcrumb!("vacuuming {}", table);
+/// ```
+#[macro_export]
+macro_rules! breadcrumb {
+    ($($arg:tt)*) => {
+        $crate::bgworkers::leave_breadcrumb(format!($($arg)*))
+    };
+}
+
+struct Breadcrumb {
+    at: SystemTime,
+    message: String,
+}
+
+thread_local! {
+    static BREADCRUMBS: RefCell<VecDeque<Breadcrumb>> =
+        RefCell::new(VecDeque::with_capacity(MAX_BREADCRUMBS));
+}
+
+#[derive(Clone)]
+struct CrashReportConfig {
+    dir: PathBuf,
+    per_minute: usize,
+}
+
+static CONFIG: Mutex<Option<CrashReportConfig>> = Mutex::new(None);
+
+/// What [`breadcrumb!()`](crate::breadcrumb) does with its message
+pub fn leave_breadcrumb(message: String) {
+    BREADCRUMBS.with(|breadcrumbs| {
+        let mut breadcrumbs = breadcrumbs.borrow_mut();
+        if breadcrumbs.len() == MAX_BREADCRUMBS {
+            breadcrumbs.pop_front();
+        }
+        breadcrumbs.push_back(Breadcrumb { at: SystemTime::now(), message });
+    })
+}
+
+impl BackgroundWorker {
+    /// Write a JSON crash report to `dir` when this worker panics, with its name, the panic's
+    /// message and location, a backtrace, and the last [`MAX_BREADCRUMBS`]
+    /// [`breadcrumb!()`](crate::breadcrumb)s
+    ///
+    /// A relative `dir` is in the data directory.  It's created when the first report is
+    /// written, if it doesn't exist.  Reports are named
+    /// `<worker name>-<milliseconds since 1970>-<pid>.json`, and each one's path is logged.  If
+    /// one can't be written, the report itself is logged instead.
+    ///
+    /// Panics the worker recovers from, such as those of
+    /// [`run_scheduled()`](crate::bgworkers::run_scheduled)'s jobs, are reported too, as there's
+    /// no telling when they happen whether they'll be caught.
+    pub fn set_crash_report_dir(dir: impl AsRef<Path>) {
+        unsafe {
+            assert!(!pg_sys::MyBgworkerEntry.is_null(), "BackgroundWorker associated functions can only be called from a registered background worker");
+        }
+        let dir = crate::paths::data_dir().join(dir);
+        let mut config = CONFIG.lock().unwrap();
+        match config.as_mut() {
+            Some(config) => config.dir = dir,
+            None => {
+                *config =
+                    Some(CrashReportConfig { dir, per_minute: DEFAULT_CRASH_REPORTS_PER_MINUTE })
+            }
+        }
+        drop(config);
+
+        static INSTALL_HOOK: Once = Once::new();
+        INSTALL_HOOK.call_once(|| {
+            // after pgx's own hook, which remembers where the panic was for its `ERROR`
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                previous(info);
+                report_crash(info);
+            }));
+        });
+    }
+
+    /// Write no more than `per_minute` crash reports a minute, counting those written by this
+    /// worker's earlier runs, rather than [`DEFAULT_CRASH_REPORTS_PER_MINUTE`]
+    ///
+    /// This has no effect until [`BackgroundWorker::set_crash_report_dir()`] has been called.
+    pub fn set_crash_report_limit(per_minute: usize) {
+        if let Some(config) = CONFIG.lock().unwrap().as_mut() {
+            config.per_minute = per_minute;
+        }
+    }
+}
+
+fn report_crash(info: &PanicInfo) {
+    // a panic while the lock is held has nothing to report to
+    let config = match CONFIG.try_lock().ok().and_then(|config| config.clone()) {
+        Some(config) => config,
+        None => return,
+    };
+    let now = SystemTime::now();
+    let worker = BackgroundWorker::get_name();
+    let message = panic_message(info);
+    let report = crash_report(worker, &message, info, now);
+
+    match write_crash_report(&config, worker, &report, now) {
+        Ok(Some(path)) => log!(
+            "background worker \"{}\" panicked: {}; crash report written to \"{}\"",
+            worker,
+            message,
+            path.display()
+        ),
+        Ok(None) => log!(
+            "background worker \"{}\" panicked: {}; no crash report was written, as {} have \
+            been written in the last minute",
+            worker,
+            message,
+            config.per_minute
+        ),
+        Err(e) => log!(
+            "background worker \"{}\" panicked; could not write its crash report to \"{}\": {}\n{}",
+            worker,
+            config.dir.display(),
+            e,
+            report
+        ),
+    }
+}
+
+fn panic_message(info: &PanicInfo) -> String {
+    let payload = info.payload();
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(report) = payload.downcast_ref::<ErrorReportWithLevel>() {
+        report.message().to_string()
+    } else if let Some(report) = payload.downcast_ref::<ErrorReport>() {
+        report.message().to_string()
+    } else if let Some(caught) = payload.downcast_ref::<CaughtError>() {
+        match caught {
+            CaughtError::PostgresError(report)
+            | CaughtError::ErrorReport(report)
+            | CaughtError::RustPanic { ereport: report, .. } => report.message().to_string(),
+        }
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+fn crash_report(worker: &str, message: &str, info: &PanicInfo, now: SystemTime) -> String {
+    let breadcrumbs = BREADCRUMBS
+        .try_with(|breadcrumbs| {
+            match breadcrumbs.try_borrow() {
+            Ok(breadcrumbs) => breadcrumbs
+                .iter()
+                .map(|crumb| {
+                    serde_json::json!({ "time": format_time(crumb.at), "message": crumb.message })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+        })
+        .unwrap_or_default();
+    let report = serde_json::json!({
+        "time": format_time(now),
+        "worker": worker,
+        "pid": unsafe { pg_sys::MyProcPid },
+        "message": message,
+        "location": info.location().map(|location| location.to_string()),
+        "breadcrumbs": breadcrumbs,
+        "backtrace": std::backtrace::Backtrace::force_capture().to_string(),
+    });
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
+
+/// Write `report` to the next file in the crash report directory, unless `worker` has written
+/// as many as it may in the last minute, returning where it was written
+fn write_crash_report(
+    config: &CrashReportConfig,
+    worker: &str,
+    report: &str,
+    now: SystemTime,
+) -> std::io::Result<Option<PathBuf>> {
+    std::fs::create_dir_all(&config.dir)?;
+
+    let prefix = format!("{}-", file_name_safe(worker));
+    let now_millis = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let minute_ago = now_millis.saturating_sub(Duration::from_secs(60).as_millis());
+    let mut recent = 0;
+    for entry in std::fs::read_dir(&config.dir)? {
+        let name = entry?.file_name();
+        let written = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix)?.strip_suffix(".json"))
+            .and_then(|rest| rest.split('-').next()?.parse::<u128>().ok());
+        if matches!(written, Some(millis) if millis > minute_ago) {
+            recent += 1;
+        }
+    }
+    if recent >= config.per_minute {
+        return Ok(None);
+    }
+
+    // written elsewhere first, so the report is either all there or not there at all
+    let pid = unsafe { pg_sys::MyProcPid };
+    let path = config.dir.join(format!("{}{}-{}.json", prefix, now_millis, pid));
+    let tmp = config.dir.join(format!(".{}{}-{}.json.tmp", prefix, now_millis, pid));
+    std::fs::write(&tmp, report)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(Some(path))
+}
+
+/// `name`, with anything other than ASCII letters, digits, `_` and `.` replaced with `_`
+fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+/// `time` as an RFC 3339 timestamp in UTC, to the millisecond
+fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = super::scheduler::civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
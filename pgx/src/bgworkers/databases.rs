@@ -0,0 +1,176 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Discover the databases of a cluster from a background worker, so that a "launcher" worker
+//! can start one dynamic worker per database, much like the autovacuum launcher does.
+//!
+//! A background worker can only ever connect to one database, so visiting every database takes
+//! two kinds of workers:
+//!
+//! - a launcher, which calls [`BackgroundWorker::connect_worker_to_spi`] with no database at
+//!   all, giving it access to shared catalogs such as `pg_database`, and then uses
+//!   [`BackgroundWorker::list_databases`] to decide which databases to visit
+//! - a child for each database, started with [`BackgroundWorkerBuilder::load_dynamic`] and given
+//!   the database's OID as its argument, which connects with
+//!   [`BackgroundWorker::connect_worker_to_spi_by_oid`]
+//!
+//! When the launcher sets itself as the child's notify PID, it can wait for each child to exit
+//! with [`DynamicBackgroundWorker::wait_for_shutdown`].  Postgres doesn't tell the launcher a
+//! child's exit code, so children that need to report an outcome must do so themselves, for
+//! example through shared memory or a table.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::bgworkers::*;
+//! use pgx::prelude::*;
+//!
+//! #[pg_guard]
+//! #[no_mangle]
+//! pub extern "C" fn launcher_main(_arg: pg_sys::Datum) {
+//!     BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+//!     BackgroundWorker::connect_worker_to_spi(None, None);
+//!
+//!     for database in BackgroundWorker::list_databases() {
+//!         if !database.allow_connections {
+//!             continue;
+//!         }
+//!         let child = BackgroundWorkerBuilder::new(&format!("child for {}", database.name))
+//!             .set_library("example")
+//!             .set_function("child_main")
+//!             .set_argument(database.oid.into_datum())
+//!             .enable_spi_access()
+//!             .set_notify_pid(unsafe { pg_sys::MyProcPid })
+//!             .load_dynamic();
+//!         child.wait_for_shutdown().expect("child worker was not tracked");
+//!     }
+//! }
+//!
+//! #[pg_guard]
+//! #[no_mangle]
+//! pub extern "C" fn child_main(arg: pg_sys::Datum) {
+//!     let dboid = unsafe { pg_sys::Oid::from_datum(arg, false) }.expect("no database OID");
+//!     BackgroundWorker::connect_worker_to_spi_by_oid(dboid, None);
+//!     // do per-database work here
+//! }
+//! ```
+use crate::bgworkers::BackgroundWorker;
+use crate::compat;
+use crate::pg_sys;
+use std::ffi::CStr;
+
+/// A database of the cluster, as described by its `pg_database` row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseEntry {
+    pub oid: pg_sys::Oid,
+    pub name: String,
+    /// Is this a template database, such as `template1`?
+    pub is_template: bool,
+    /// Does this database accept connections?  Background workers can't connect to it otherwise
+    pub allow_connections: bool,
+}
+
+impl BackgroundWorker {
+    /// Returns every database of the cluster.
+    ///
+    /// This only needs shared catalogs, so it works for a worker that called
+    /// [`BackgroundWorker::connect_worker_to_spi`] without naming a database, as well as for one
+    /// connected to a specific database.  It runs in its own transaction unless one is already
+    /// in progress.
+    pub fn list_databases() -> Vec<DatabaseEntry> {
+        unsafe {
+            assert!(!pg_sys::MyBgworkerEntry.is_null(), "BackgroundWorker associated functions can only be called from a registered background worker");
+        }
+
+        let in_transaction = unsafe { pg_sys::IsTransactionState() };
+        unsafe {
+            if !in_transaction {
+                pg_sys::StartTransactionCommand();
+            }
+        }
+
+        let mut databases = Vec::new();
+        unsafe {
+            // SAFETY:  we're in a transaction, and `pg_database` is a shared catalog that's
+            // readable no matter which database, if any, we're connected to
+            let rel =
+                pg_sys::relation_open(pg_sys::DatabaseRelationId, pg_sys::AccessShareLock as _);
+            let scan = compat::table_beginscan_catalog(rel, 0, std::ptr::null_mut());
+            loop {
+                let tup = pg_sys::heap_getnext(scan, pg_sys::ScanDirection_ForwardScanDirection);
+                if tup.is_null() {
+                    break;
+                }
+                databases.push(database_entry(tup));
+            }
+            compat::table_endscan(scan);
+            pg_sys::relation_close(rel, pg_sys::AccessShareLock as _);
+        }
+
+        unsafe {
+            if !in_transaction {
+                pg_sys::CommitTransactionCommand();
+            }
+        }
+        databases
+    }
+
+    /// Like [`BackgroundWorker::connect_worker_to_spi`], but identifies the database, and
+    /// optionally the user, by OID.
+    ///
+    /// This is the natural way for a worker started by a launcher to connect, as OIDs fit in a
+    /// worker's `Datum` argument and, unlike names, don't change when a database is renamed.
+    pub fn connect_worker_to_spi_by_oid(dboid: pg_sys::Oid, useroid: Option<pg_sys::Oid>) {
+        unsafe {
+            assert!(!pg_sys::MyBgworkerEntry.is_null(), "BackgroundWorker associated functions can only be called from a registered background worker");
+            pg_sys::BackgroundWorkerInitializeConnectionByOid(
+                dboid,
+                useroid.unwrap_or(pg_sys::InvalidOid),
+                0,
+            );
+        }
+    }
+}
+
+unsafe fn database_entry(tup: pg_sys::HeapTuple) -> DatabaseEntry {
+    // SAFETY:  the caller has assured us that `tup` is a valid `pg_database` tuple
+    let form = pg_sys::GETSTRUCT(tup) as pg_sys::Form_pg_database;
+    let form = form.as_ref().unwrap();
+    DatabaseEntry {
+        oid: database_oid(tup, form),
+        name: CStr::from_ptr(form.datname.data.as_ptr()).to_string_lossy().into_owned(),
+        is_template: form.datistemplate,
+        allow_connections: form.datallowconn,
+    }
+}
+
+#[cfg(feature = "pg11")]
+unsafe fn database_oid(
+    tup: pg_sys::HeapTuple,
+    _form: &pg_sys::FormData_pg_database,
+) -> pg_sys::Oid {
+    // a port of Postgres' `HeapTupleHeaderGetOid` macro, as Postgres 11 stores catalog OIDs
+    // in the tuple header rather than in a column
+    let t_data = (*tup).t_data;
+    if ((*t_data).t_infomask & pg_sys::HEAP_HASOID as u16) != 0 {
+        *(t_data.cast::<std::os::raw::c_char>().add((*t_data).t_hoff as _))
+            .sub(std::mem::size_of::<pg_sys::Oid>())
+            .cast::<pg_sys::Oid>()
+    } else {
+        pg_sys::InvalidOid
+    }
+}
+
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+unsafe fn database_oid(
+    _tup: pg_sys::HeapTuple,
+    form: &pg_sys::FormData_pg_database,
+) -> pg_sys::Oid {
+    form.oid
+}
@@ -10,6 +10,14 @@ Use of this source code is governed by the MIT license that can be found in the
 //! Safely create Postgres Background Workers, including with full SPI support
 //!
 //! See: [https://www.postgresql.org/docs/current/bgworker.html](https://www.postgresql.org/docs/current/bgworker.html)
+mod crash_report;
+mod databases;
+mod scheduler;
+
+pub use crash_report::*;
+pub use databases::*;
+pub use scheduler::*;
+
 use crate::pg_sys;
 use pgx_pg_sys::PgTryBuilder;
 use std::convert::TryInto;
@@ -193,6 +201,9 @@ impl BackgroundWorker {
 
     /// Once connected to SPI via `connect_worker_to_spi()`, begin a transaction to
     /// use the `pgx::Spi` interface. Returns the return value of the `F` function.
+    ///
+    /// Any notifications sent in it, with [`pgx::notify`](crate::notify), are delivered when it
+    /// commits.
     pub fn transaction<F: FnOnce() -> R + std::panic::UnwindSafe + std::panic::RefUnwindSafe, R>(
         transaction_body: F,
     ) -> R {
@@ -206,6 +217,10 @@ impl BackgroundWorker {
             let result = PgTryBuilder::new(transaction_body).execute();
             pg_sys::PopActiveSnapshot();
             pg_sys::CommitTransactionCommand();
+            // before 15, the backends listening for the transaction's notifications are only
+            // signaled after it commits, which a client backend's main loop does
+            #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13", feature = "pg14"))]
+            pg_sys::ProcessCompletedNotifies();
             result
         }
     }
@@ -260,6 +275,11 @@ impl From<pg_sys::BgwHandleStatus> for BackgroundWorkerStatus {
 }
 
 impl DynamicBackgroundWorker {
+    /// The worker's handle, for Postgres APIs that watch a worker, like `shm_mq_attach()`
+    pub(crate) fn handle_ptr(&self) -> *mut pg_sys::BackgroundWorkerHandle {
+        self.handle
+    }
+
     /// Return dynamic background worker's PID if the worker is successfully registered,
     /// otherwise it return worker's status as an error.
     pub fn pid(&self) -> Result<Pid, BackgroundWorkerStatus> {
@@ -343,9 +363,9 @@ impl TerminatingDynamicBackgroundWorker {
 
 /// A builder-style interface for creating a new Background Worker
 ///
-/// For a static background worker, this must be used from within your extension's `_PG_init()` function,
-/// finishing with the `.load()` function. Dynamic background workers are loaded with `.load_dynamic()` and
-/// have no restriction as to where they can be loaded.
+/// For a static background worker, this must be used from within a [`#[pg_init]`](crate::pg_init)
+/// function or your extension's `_PG_init()`, finishing with the `.load()` function. Dynamic background
+/// workers are loaded with `.load_dynamic()` and have no restriction as to where they can be loaded.
 ///
 /// ## Example
 ///
@@ -353,8 +373,8 @@ impl TerminatingDynamicBackgroundWorker {
 /// use pgx::prelude::*;
 /// use pgx::bgworkers::BackgroundWorkerBuilder;
 ///
-/// #[pg_guard]
-/// pub extern "C" fn _PG_init() {
+/// #[pg_init]
+/// fn init_bgworker() {
 ///     BackgroundWorkerBuilder::new("My Example BGWorker")
 ///         .set_function("background_worker_main")
 ///         .set_library("example")
@@ -532,7 +552,11 @@ impl BackgroundWorkerBuilder {
 
     /// Once properly configured, call `load()` to get the BackgroundWorker registered and
     /// started at the proper time by Postgres.
+    ///
+    /// Raises an `ERROR` unless called while the extension is loaded through
+    /// `shared_preload_libraries`.  Use `load_dynamic()` otherwise.
     pub fn load(self: Self) {
+        crate::init::require_preload("`BackgroundWorkerBuilder::load()`");
         let mut bgw: pg_sys::BackgroundWorker = (&self).into();
 
         unsafe {
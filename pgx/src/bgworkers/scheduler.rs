@@ -0,0 +1,526 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! A small `pg_cron`-style job scheduler for running Rust functions from a [`BackgroundWorker`]
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::bgworkers::*;
+//! use pgx::prelude::*;
+//! use std::time::Duration;
+//!
+//! #[pg_guard]
+//! #[no_mangle]
+//! pub extern "C" fn scheduler_main(_arg: pg_sys::Datum) {
+//!     BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+//!     BackgroundWorker::connect_worker_to_spi(Some("postgres"), None);
+//!
+//!     // runs until the worker receives a SIGTERM
+//!     run_scheduled(vec![
+//!         (Schedule::every(Duration::from_secs(30)), refresh_stats),
+//!         (Schedule::cron("0 3 * * *").expect("invalid cron expression"), purge_old_rows),
+//!     ]);
+//! }
+//!
+//! fn refresh_stats() {
+//!     Spi::run("REFRESH MATERIALIZED VIEW my_stats").expect("failed to refresh my_stats");
+//! }
+//!
+//! fn purge_old_rows() {
+//!     Spi::run("DELETE FROM my_log WHERE ts < now() - '30 days'::interval")
+//!         .expect("failed to purge my_log");
+//! }
+//! ```
+use crate::bgworkers::BackgroundWorker;
+use crate::datum::{POSTGRES_EPOCH_JDATE, UNIX_EPOCH_JDATE};
+use crate::prelude::*;
+use crate::{PGXSharedMemory, PgLwLock};
+use pgx_pg_sys::panic::CaughtError;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The longest the scheduler sleeps before looking at the wall clock again, so that it notices
+/// the system clock being changed
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// How far into the future we look for the next time a cron expression matches
+const MAX_CRON_SEARCH_DAYS: i64 = 366 * 10;
+
+/// The maximum number of jobs a [`SchedulerStatus`] can report on
+pub const MAX_SCHEDULED_JOBS: usize = 32;
+
+/// Errors from parsing a [`Schedule`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("cron expression `{0}` must have exactly five fields")]
+    WrongFieldCount(String),
+
+    #[error("invalid field `{field}` in cron expression `{expression}`")]
+    InvalidField { expression: String, field: String },
+}
+
+impl crate::ErrorReportable for ScheduleError {
+    fn sql_error_code(&self) -> crate::PgSqlErrorCode {
+        crate::PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+    }
+}
+
+/// When a scheduled job should run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Repeatedly, with this much time between the end of one run and the start of the next
+    Every(Duration),
+
+    /// Whenever the (UTC) wall clock matches a cron expression
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    /// Run a job repeatedly, waiting `interval` between runs.  The first run happens `interval`
+    /// after the scheduler starts.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `interval` is zero
+    pub fn every(interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "a scheduled interval must be greater than zero");
+        Schedule::Every(interval)
+    }
+
+    /// Run a job whenever the current UTC time matches the standard five-field cron `expression`
+    /// (`minute hour day-of-month month day-of-week`).
+    ///
+    /// Each field may be `*`, a number, a range such as `1-5`, any of those followed by a `/step`,
+    /// or a comma-separated list of them.  The `@yearly`, `@monthly`, `@weekly`, `@daily`, and
+    /// `@hourly` shorthands are also understood.
+    pub fn cron(expression: &str) -> Result<Self, ScheduleError> {
+        CronSchedule::parse(expression).map(Schedule::Cron)
+    }
+
+    /// The next time, strictly after `after`, that a job on this schedule should run.
+    ///
+    /// Returns `None` if there is no such time, such as for the cron expression `0 0 31 2 *`.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        match self {
+            Schedule::Every(interval) => after.checked_add(*interval),
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Every(interval) => write!(f, "every {:?}", interval),
+            Schedule::Cron(cron) => f.write_str(&cron.expression),
+        }
+    }
+}
+
+/// A parsed cron expression.  See [`Schedule::cron()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    fn parse(expression: &str) -> Result<Self, ScheduleError> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(ScheduleError::WrongFieldCount(expression.to_string()));
+        }
+
+        let parse = |field: &str, min: u32, max: u32| {
+            parse_cron_field(field, min, max).ok_or_else(|| ScheduleError::InvalidField {
+                expression: expression.to_string(),
+                field: field.to_string(),
+            })
+        };
+
+        // both 0 and 7 mean Sunday
+        let mut days_of_week = parse(fields[4], 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(CronSchedule {
+            expression: expression.to_string(),
+            minutes: parse(fields[0], 0, 59)?,
+            hours: parse(fields[1], 0, 23)?,
+            days_of_month: parse(fields[2], 1, 31)?,
+            months: parse(fields[3], 1, 12)?,
+            days_of_week,
+            // like Vixie cron, a day field that starts with `*` doesn't restrict the other one
+            any_day_of_month: fields[2].starts_with('*'),
+            any_day_of_week: fields[4].starts_with('*'),
+        })
+    }
+
+    fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let seconds = match after.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs_f64().ceil() as i64),
+        };
+
+        // minutes since the Unix epoch, starting with the first whole minute after `after`
+        let mut minute = seconds.div_euclid(60) + 1;
+        let limit = minute + MAX_CRON_SEARCH_DAYS * 24 * 60;
+        while minute < limit {
+            let day = minute.div_euclid(24 * 60);
+            let (year, month, day_of_month) = civil_from_days(day);
+
+            if !has_bit(self.months, month as i64) {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                minute = days_from_civil(year, month, 1) * 24 * 60;
+            } else if !self.day_matches(day, day_of_month) {
+                minute = (day + 1) * 24 * 60;
+            } else if !has_bit(self.hours, minute.rem_euclid(24 * 60) / 60) {
+                minute = (minute.div_euclid(60) + 1) * 60;
+            } else if !has_bit(self.minutes, minute.rem_euclid(60)) {
+                minute += 1;
+            } else {
+                let since_epoch = minute * 60;
+                return if since_epoch >= 0 {
+                    UNIX_EPOCH.checked_add(Duration::from_secs(since_epoch as u64))
+                } else {
+                    UNIX_EPOCH.checked_sub(Duration::from_secs(since_epoch.unsigned_abs()))
+                };
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, day: i64, day_of_month: u32) -> bool {
+        // 1970-01-01 was a Thursday
+        let day_of_week = (day + 4).rem_euclid(7);
+        let dom = has_bit(self.days_of_month, day_of_month as i64);
+        let dow = has_bit(self.days_of_week, day_of_week);
+        if self.any_day_of_month || self.any_day_of_week {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+}
+
+fn has_bit(bits: u64, n: i64) -> bool {
+    bits & (1 << n) != 0
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse().ok()?, end.parse().ok()?)
+        } else {
+            // `5/15` means "starting at 5, every 15"
+            let start = range.parse().ok()?;
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Some(bits)
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian (year, month, day)
+pub(super) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+/// A proleptic Gregorian (year, month, day) to days since 1970-01-01
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The state of one job run by [`run_scheduled_with_status()`]
+#[derive(Debug, Clone, Default)]
+pub struct ScheduledJobStatus {
+    schedule: heapless::String<64>,
+    last_run: Option<pg_sys::TimestampTz>,
+    next_run: Option<pg_sys::TimestampTz>,
+    runs: u64,
+    failures: u64,
+}
+
+/// The state of every job run by [`run_scheduled_with_status()`], for storing in shared memory
+/// so that it can be inspected from regular backends.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// use pgx::bgworkers::*;
+/// use pgx::prelude::*;
+/// use pgx::{pg_shmem_init, PgLwLock, PgSharedMemoryInitialization};
+///
+/// static SCHEDULER_STATUS: PgLwLock<SchedulerStatus> = PgLwLock::new();
+///
+/// #[pg_guard]
+/// pub extern "C" fn _PG_init() {
+///     pg_shmem_init!(SCHEDULER_STATUS);
+///     // ... register the background worker that calls `run_scheduled_with_status()`
+/// }
+///
+/// #[pg_extern]
+/// fn scheduled_jobs() -> TableIterator<
+///     'static,
+///     (
+///         name!(job, i32),
+///         name!(schedule, String),
+///         name!(last_run, Option<TimestampWithTimeZone>),
+///         name!(next_run, Option<TimestampWithTimeZone>),
+///         name!(runs, i64),
+///         name!(failures, i64),
+///     ),
+/// > {
+///     SchedulerStatus::table(&SCHEDULER_STATUS)
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerStatus {
+    jobs: heapless::Vec<ScheduledJobStatus, MAX_SCHEDULED_JOBS>,
+}
+
+unsafe impl PGXSharedMemory for SchedulerStatus {}
+
+impl SchedulerStatus {
+    /// The current status of each job, in the order they were given to the scheduler, suitable
+    /// for returning from a `#[pg_extern]` function
+    pub fn table(
+        status: &PgLwLock<SchedulerStatus>,
+    ) -> TableIterator<
+        'static,
+        (
+            name!(job, i32),
+            name!(schedule, String),
+            name!(last_run, Option<TimestampWithTimeZone>),
+            name!(next_run, Option<TimestampWithTimeZone>),
+            name!(runs, i64),
+            name!(failures, i64),
+        ),
+    > {
+        let rows = status
+            .share()
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(i, job)| {
+                (
+                    i as i32 + 1,
+                    job.schedule.to_string(),
+                    job.last_run.and_then(|ts| TimestampWithTimeZone::try_from(ts).ok()),
+                    job.next_run.and_then(|ts| TimestampWithTimeZone::try_from(ts).ok()),
+                    job.runs as i64,
+                    job.failures as i64,
+                )
+            })
+            .collect::<Vec<_>>();
+        TableIterator::new(rows.into_iter())
+    }
+}
+
+struct ScheduledJob {
+    schedule: Schedule,
+    job: fn(),
+    next_run: Option<SystemTime>,
+}
+
+/// Run `jobs` on their schedules until this background worker receives a SIGTERM.
+///
+/// The calling background worker should have already attached its signal handlers with
+/// [`BackgroundWorker::attach_signal_handlers()`], including SIGTERM so that it can be stopped,
+/// and SIGHUP so that configuration changes are picked up between jobs.
+///
+/// If the worker is connected to a database, each job runs in its own transaction, so it can
+/// freely use [`Spi`].  Jobs are isolated from each other:  if one raises an ERROR or panics,
+/// its work is rolled back and the failure is logged, but the scheduler and every other job carry
+/// on.
+///
+/// Jobs that are due at the same time run one after another, in the order given.  If a job is
+/// still running when it next becomes due, or the system clock jumps forward, the missed runs are
+/// skipped rather than run back-to-back.  If the system clock jumps backwards, every job is
+/// rescheduled from the new time.
+pub fn run_scheduled(jobs: Vec<(Schedule, fn())>) {
+    run_scheduled_with_status(jobs, None)
+}
+
+/// Like [`run_scheduled()`], but also records each job's last and next run times in `status`.
+///
+/// Only the first [`MAX_SCHEDULED_JOBS`] jobs are recorded, but all of them are run.
+pub fn run_scheduled_with_status(
+    jobs: Vec<(Schedule, fn())>,
+    status: Option<&PgLwLock<SchedulerStatus>>,
+) {
+    let mut now = SystemTime::now();
+    let mut jobs = jobs
+        .into_iter()
+        .map(|(schedule, job)| ScheduledJob { next_run: schedule.next_after(now), schedule, job })
+        .collect::<Vec<_>>();
+
+    if let Some(status) = status {
+        let mut status = status.exclusive();
+        status.jobs.clear();
+        for job in jobs.iter().take(MAX_SCHEDULED_JOBS) {
+            let mut schedule = heapless::String::new();
+            for c in job.schedule.to_string().chars() {
+                if schedule.push(c).is_err() {
+                    break;
+                }
+            }
+            let job_status = ScheduledJobStatus {
+                schedule,
+                next_run: job.next_run.map(to_timestamptz),
+                ..Default::default()
+            };
+            status.jobs.push(job_status).ok();
+        }
+    }
+
+    loop {
+        for (i, job) in jobs.iter_mut().enumerate() {
+            if !matches!(job.next_run, Some(next_run) if next_run <= now) {
+                continue;
+            }
+
+            let started = SystemTime::now();
+            let result = run_job(job.job);
+            if let Err(message) = &result {
+                log!("scheduled job #{} ({}) failed: {}", i + 1, job.schedule, message);
+            }
+
+            now = SystemTime::now();
+            job.next_run = job.schedule.next_after(now);
+            if let Some(status) = status {
+                if let Some(job_status) = status.exclusive().jobs.get_mut(i) {
+                    job_status.last_run = Some(to_timestamptz(started));
+                    job_status.next_run = job.next_run.map(to_timestamptz);
+                    job_status.runs += 1;
+                    job_status.failures += result.is_err() as u64;
+                }
+            }
+        }
+
+        let sleep = jobs
+            .iter()
+            .filter_map(|job| job.next_run)
+            .min()
+            .map(|next_run| next_run.duration_since(now).unwrap_or_default())
+            .unwrap_or(MAX_SLEEP)
+            .min(MAX_SLEEP);
+        if !BackgroundWorker::wait_latch(Some(sleep)) {
+            break;
+        }
+        if BackgroundWorker::sighup_received() {
+            // the SIGHUP handler has already reloaded the configuration file
+            debug1!("scheduler reloaded its configuration");
+        }
+
+        let then = now;
+        now = SystemTime::now();
+        if now < then {
+            debug1!("system clock moved backwards, rescheduling all jobs");
+            for job in jobs.iter_mut() {
+                job.next_run = job.schedule.next_after(now);
+            }
+        }
+    }
+}
+
+/// Run a single job, in a (sub)transaction if we're connected to a database, converting any
+/// ERROR or panic it raises into an `Err` with its message
+fn run_job(job: fn()) -> Result<(), String> {
+    let connected = unsafe { pg_sys::MyDatabaseId != pg_sys::InvalidOid };
+    let result = if connected {
+        BackgroundWorker::transaction(|| unsafe {
+            // SAFETY:  we're inside a transaction, so we can start a subtransaction, and we
+            // restore the memory context and resource owner however it ends
+            let memcxt = pg_sys::CurrentMemoryContext;
+            let owner = pg_sys::CurrentResourceOwner;
+            pg_sys::BeginInternalSubTransaction(std::ptr::null());
+
+            let result = PgTryBuilder::new(|| {
+                job();
+                Ok(())
+            })
+            .catch_others(Err)
+            .execute();
+            if result.is_ok() {
+                pg_sys::ReleaseCurrentSubTransaction();
+            } else {
+                pg_sys::RollbackAndReleaseCurrentSubTransaction();
+            }
+            pg_sys::MemoryContextSwitchTo(memcxt);
+            pg_sys::CurrentResourceOwner = owner;
+            result
+        })
+    } else {
+        PgTryBuilder::new(|| {
+            job();
+            Ok(())
+        })
+        .catch_others(Err)
+        .execute()
+    };
+
+    result.map_err(|e| match e {
+        CaughtError::PostgresError(ereport)
+        | CaughtError::ErrorReport(ereport)
+        | CaughtError::RustPanic { ereport, .. } => ereport.message().to_string(),
+    })
+}
+
+fn to_timestamptz(time: SystemTime) -> pg_sys::TimestampTz {
+    const USECS_PER_DAY: i64 = 86_400_000_000;
+    let since_unix_epoch = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_micros() as i64,
+        Err(before) => -(before.duration().as_micros() as i64),
+    };
+    since_unix_epoch - (POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE) as i64 * USECS_PER_DAY
+}
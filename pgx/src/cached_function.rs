@@ -0,0 +1,383 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Call a SQL function by its signature, such as one the user named in a GUC, without planning a
+//! query to call it each time.
+//!
+//! A [`CachedFunction`] looks the function up once, checks it's the function its signature
+//! describes, and calls it the way the executor calls a function in a query.  If the function is
+//! changed, dropped, or dropped and created again, it's looked up again before it's next called.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use pgx::cached_function::CachedFunction;
+//! use pgx::prelude::*;
+//!
+//! # fn foo() -> Result<(), pgx::cached_function::Error> {
+//! let score = CachedFunction::by_name("scoring.score(text, int) returns float8")?;
+//!
+//! assert_eq!(score.call_as::<f64>(&["a good dog".into_datum(), 11.into_datum()])?, Some(14.0));
+//! # Ok(())
+//! # }
+//! ```
+use crate::backend_local::{on_discard, on_reset_all, Discard};
+use crate::fcinfo::{direct_function_call, fmgr_function_call_as_datum};
+use crate::invalidation::on_syscache_change;
+use crate::prelude::*;
+use crate::{PgMemoryContexts, TryFromDatumError};
+use pgx_pg_sys::panic::{CaughtError, ErrorReportWithLevel};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Set of possible errors `pgx` might return while looking up or calling a [`CachedFunction`]
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The signature isn't a function's name and argument types, then `returns` and its type
+    #[error("`{0}` isn't a function signature, such as `schema.name(text) returns int`")]
+    InvalidSignature(String),
+
+    /// The function the signature names can't be called as the signature says, such as because
+    /// it was created again with another return type
+    #[error("`{signature}` doesn't match the function, which {reason}")]
+    SignatureMismatch { signature: String, reason: String },
+
+    /// The number of values supplied to [`CachedFunction::call()`] doesn't match the number of
+    /// arguments the function takes
+    #[error("Argument count mismatch (expected {expected}, got {got})")]
+    ArgumentCountMismatch { expected: usize, got: usize },
+
+    /// The function's result can't be converted into the requested Rust type
+    #[error("Datum error: {0}")]
+    DatumError(#[from] TryFromDatumError),
+
+    /// Postgres raised an ERROR while looking up the function or its return type, such as because
+    /// it doesn't exist (anymore)
+    #[error("{}", .0.message())]
+    PostgresError(ErrorReportWithLevel),
+}
+
+impl crate::ErrorReportable for Error {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            Error::InvalidSignature(_) => PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            Error::SignatureMismatch { .. } => PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH,
+            Error::ArgumentCountMismatch { .. } => PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            Error::DatumError(e) => crate::ErrorReportable::sql_error_code(e),
+            Error::PostgresError(report) => report.sql_error_code(),
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Error::PostgresError(report) => report.detail().map(str::to_string),
+            _ => None,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Error::SignatureMismatch { .. } => Some(String::from(
+                "the function was changed since the signature was written: change one to match the other",
+            )),
+            Error::PostgresError(report) => report.hint().map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+/// Bumped every time the catalog entries a function's signature depends on are invalidated, and
+/// when the session is reset
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A SQL function, looked up by its signature, that can be called without planning a query.
+///
+/// The signature is the function's name and argument types, as `regprocedure` takes them, then
+/// `returns` and its return type, such as `public.score(text, int) returns float8`.  An
+/// unqualified name is looked up in the `search_path`, when the `CachedFunction` is made and
+/// whenever it's looked up again, so a function named by the user should be schema-qualified.
+///
+/// The function is looked up again before the next call whenever any function, type or namespace
+/// is changed, or the session is reset by `DISCARD PLANS`, `DISCARD ALL`, or `RESET ALL`, so a
+/// function that's dropped and created again is the one that's called.  Looking it up fails (with
+/// [`Error::PostgresError`]) once it no longer exists, and (with [`Error::SignatureMismatch`]) if
+/// it was created again with another return type.
+///
+/// Its looked-up state lives in its own memory context, under `TopMemoryContext`, so it can be
+/// kept across transactions.  A call that's still running keeps the state it was called with, so
+/// the function can call back into the same `CachedFunction`, even if that looks it up again.
+pub struct CachedFunction {
+    signature: String,
+    resolved: RefCell<Rc<Resolved>>,
+}
+
+struct Resolved {
+    generation: u64,
+    oid: pg_sys::Oid,
+    arg_types: Vec<pg_sys::Oid>,
+    return_type: pg_sys::Oid,
+    strict: bool,
+    collation: pg_sys::Oid,
+    flinfo: *mut pg_sys::FmgrInfo,
+    /// What `flinfo`, and whatever the function caches in it, is allocated in
+    _context: PgMemoryContexts,
+}
+
+impl CachedFunction {
+    /// Look up the function `signature` describes, and check it is that function.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSignature`] if `signature` isn't a signature,
+    /// [`Error::PostgresError`] if Postgres can't find the function or its return type, and
+    /// [`Error::SignatureMismatch`] if the function returns another type, returns a set, or is an
+    /// aggregate, window function or procedure.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `signature` contains a null byte.
+    pub fn by_name(signature: &str) -> Result<Self> {
+        register_invalidation_callbacks();
+
+        let signature = signature.to_string();
+        let resolved = Resolved::new(&signature)?;
+        Ok(CachedFunction { signature, resolved: RefCell::new(Rc::new(resolved)) })
+    }
+
+    /// The signature this function was looked up by
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// The OID of the function, as of the last time it was looked up
+    pub fn oid(&self) -> pg_sys::Oid {
+        self.resolved.borrow().oid
+    }
+
+    /// The types of the function's arguments, in order
+    pub fn arg_types(&self) -> Vec<pg_sys::Oid> {
+        self.resolved.borrow().arg_types.clone()
+    }
+
+    /// The type the function returns
+    pub fn return_type(&self) -> pg_sys::Oid {
+        self.resolved.borrow().return_type
+    }
+
+    /// Call the function with the specified argument values, where `None` represents NULL.
+    ///
+    /// A `STRICT` function isn't called when any of them is NULL, as it returns NULL.  A function
+    /// that takes or returns a collatable type is called with its type's default collation.  A
+    /// pass-by-reference result is allocated in the current memory context.
+    ///
+    /// Any Postgres ERROR raised by the function itself is raised as usual and is not converted
+    /// into an [`Error`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ArgumentCountMismatch`] if the wrong number of values are provided, or
+    /// the same errors as [`CachedFunction::by_name()`] if the function had to be looked up again
+    /// and that failed.
+    pub fn call(&self, args: &[Option<pg_sys::Datum>]) -> Result<Option<pg_sys::Datum>> {
+        self.resolve()?.call(args)
+    }
+
+    /// Call the function and convert its result into a Rust type.
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors described by [`CachedFunction::call()`], returns
+    /// [`Error::DatumError`] if the function's return type isn't compatible with `T`.
+    pub fn call_as<T: FromDatum + IntoDatum>(
+        &self,
+        args: &[Option<pg_sys::Datum>],
+    ) -> Result<Option<T>> {
+        let resolved = self.resolve()?;
+        let datum = resolved.call(args)?;
+        unsafe {
+            // SAFETY:  the datum was just returned by the function, which returns `return_type`
+            Ok(T::try_from_datum(
+                datum.unwrap_or(pg_sys::Datum::from(0usize)),
+                datum.is_none(),
+                resolved.return_type,
+            )?)
+        }
+    }
+
+    /// The function as it was last looked up, looking it up again first if it's changed since.
+    ///
+    /// The caller gets its own reference, rather than a borrow, because the function it calls may
+    /// call back into this `CachedFunction` and replace the lookup, whose `flinfo` the caller is
+    /// still using.
+    fn resolve(&self) -> Result<Rc<Resolved>> {
+        unsafe {
+            // SAFETY:  this is always safe to call from within a transaction, and will deliver
+            // pending invalidations to our callbacks
+            pg_sys::AcceptInvalidationMessages();
+        }
+        if self.resolved.borrow().generation != GENERATION.load(Ordering::Relaxed) {
+            // the previous lookup is freed once no call is using it.  If looking it up again fails
+            // we'll simply try again on the next call
+            let fresh = Resolved::new(&self.signature)?;
+            self.resolved.replace(Rc::new(fresh));
+        }
+        Ok(Rc::clone(&self.resolved.borrow()))
+    }
+}
+
+impl Resolved {
+    fn call(&self, args: &[Option<pg_sys::Datum>]) -> Result<Option<pg_sys::Datum>> {
+        if args.len() != self.arg_types.len() {
+            return Err(Error::ArgumentCountMismatch {
+                expected: self.arg_types.len(),
+                got: args.len(),
+            });
+        }
+        if self.strict && args.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+        unsafe {
+            // SAFETY:  `flinfo` was looked up for a function that isn't set-returning, and takes
+            // as many arguments as we were given
+            Ok(fmgr_function_call_as_datum(self.flinfo, self.collation, args.to_vec()))
+        }
+    }
+
+    fn new(signature: &str) -> Result<Self> {
+        let (function, returns) = split_signature(signature)
+            .ok_or_else(|| Error::InvalidSignature(signature.to_string()))?;
+        let function = CString::new(function).expect("signature contained a null byte");
+        let returns = CString::new(returns).expect("signature contained a null byte");
+
+        // a function or type that doesn't exist, or a signature Postgres can't parse, is reported
+        // back to the caller as an `Err`
+        let (oid, expected) = PgTryBuilder::new(|| unsafe {
+            // SAFETY:  both are given a valid cstring, and raise an ERROR rather than return NULL
+            let oid = direct_function_call::<pg_sys::Oid>(
+                pg_sys::regprocedurein,
+                vec![function.as_c_str().into_datum()],
+            );
+            let expected = direct_function_call::<pg_sys::Oid>(
+                pg_sys::regtypein,
+                vec![returns.as_c_str().into_datum()],
+            );
+            Ok((oid.unwrap(), expected.unwrap()))
+        })
+        .catch_others(|e| match e {
+            CaughtError::PostgresError(ereport) | CaughtError::ErrorReport(ereport) => {
+                Err(Error::PostgresError(ereport))
+            }
+            CaughtError::RustPanic { .. } => e.rethrow(),
+        })
+        .execute()?;
+
+        let mismatch =
+            |reason: String| Error::SignatureMismatch { signature: signature.to_string(), reason };
+        unsafe {
+            // SAFETY:  `regprocedurein()` found the function, so these lookups can't fail
+            let mut arg_types = std::ptr::null_mut();
+            let mut nargs = 0;
+            let return_type = pg_sys::get_func_signature(oid, &mut arg_types, &mut nargs);
+            let arg_types = std::slice::from_raw_parts(arg_types, nargs as usize).to_vec();
+
+            match pg_sys::get_func_prokind(oid) as u8 {
+                pg_sys::PROKIND_FUNCTION => {}
+                pg_sys::PROKIND_AGGREGATE => return Err(mismatch(String::from("is an aggregate"))),
+                pg_sys::PROKIND_WINDOW => {
+                    return Err(mismatch(String::from("is a window function")))
+                }
+                _ => return Err(mismatch(String::from("is a procedure"))),
+            }
+            if pg_sys::get_func_retset(oid) {
+                return Err(mismatch(String::from("returns a set")));
+            }
+            if !pg_sys::IsBinaryCoercible(return_type, expected) {
+                return Err(mismatch(format!("returns {}", type_name(return_type))));
+            }
+
+            // the type's default collation is what a query would call it with, given a value of
+            // that type that isn't a column
+            let collation = arg_types
+                .iter()
+                .chain(Some(&return_type))
+                .map(|typoid| pg_sys::get_typcollation(*typoid))
+                .find(|collation| *collation != pg_sys::InvalidOid)
+                .unwrap_or(pg_sys::InvalidOid);
+
+            let context = PgMemoryContexts::TopMemoryContext
+                .switch_to(|_| PgMemoryContexts::new("CachedFunction"));
+            let flinfo =
+                PgMemoryContexts::For(context.value()).palloc0_struct::<pg_sys::FmgrInfo>();
+            pg_sys::fmgr_info_cxt(oid, flinfo, context.value());
+
+            Ok(Resolved {
+                generation: GENERATION.load(Ordering::Relaxed),
+                oid,
+                arg_types,
+                return_type,
+                strict: pg_sys::func_strict(oid),
+                collation,
+                flinfo,
+                _context: context,
+            })
+        }
+    }
+}
+
+/// Split `signature` into the function, with its argument types, and the type it returns
+fn split_signature(signature: &str) -> Option<(&str, &str)> {
+    const RETURNS: &str = "returns";
+    let at = signature.to_ascii_lowercase().rfind(RETURNS)?;
+    let (function, returns) = (&signature[..at], &signature[at + RETURNS.len()..]);
+    let keyword = function.ends_with(|c: char| c.is_whitespace() || c == ')')
+        && returns.starts_with(char::is_whitespace);
+    let (function, returns) = (function.trim(), returns.trim());
+    if keyword && function.ends_with(')') && !returns.is_empty() {
+        Some((function, returns))
+    } else {
+        None
+    }
+}
+
+fn type_name(typoid: pg_sys::Oid) -> String {
+    unsafe { CStr::from_ptr(pg_sys::format_type_be(typoid)).to_string_lossy().into_owned() }
+}
+
+/// Register, once per backend, the catalog invalidation and session reset callbacks that tell us
+/// functions need to be looked up again
+fn register_invalidation_callbacks() {
+    static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    if !REGISTERED.swap(true, Ordering::Relaxed) {
+        for cacheid in [
+            pg_sys::SysCacheIdentifier_PROCOID,
+            pg_sys::SysCacheIdentifier_TYPEOID,
+            pg_sys::SysCacheIdentifier_NAMESPACEOID,
+        ] {
+            on_syscache_change(cacheid, |_| {
+                GENERATION.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+
+        // `DISCARD PLANS` should discard these lookups too, and what an unqualified name finds
+        // can change with the `search_path` that `RESET ALL` resets
+        on_discard(|discard| {
+            if discard.includes(Discard::Plans) {
+                GENERATION.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        on_reset_all(|| {
+            GENERATION.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+}
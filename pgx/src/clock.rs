@@ -0,0 +1,58 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Postgres' clocks, which say what volatility the functions reading them need
+//!
+//! Unlike [`std::time::SystemTime`], [`transaction_timestamp()`] and [`statement_timestamp()`]
+//! don't change within a statement, so a function that reads them can be declared `STABLE`.  Only
+//! one that reads [`clock_timestamp()`] needs to be `VOLATILE`.  `cargo pgx schema --lint` checks
+//! which of them a `#[pg_extern]` function uses.
+//!
+//! All three return the same, fixed time while a `#[pg_test(freeze_time = "..")]` runs, or while
+//! the guard returned by [`pgx::testing::freeze_time()`](crate::testing::freeze_time) is alive.
+use crate::{pg_sys, TimestampWithTimeZone};
+use std::cell::Cell;
+
+thread_local! {
+    static FROZEN: Cell<Option<pg_sys::TimestampTz>> = Cell::new(None);
+}
+
+/// Make the clocks return `at`, or the real time again if it's `None`, returning what they were
+/// frozen at before
+pub(crate) fn freeze(at: Option<pg_sys::TimestampTz>) -> Option<pg_sys::TimestampTz> {
+    FROZEN.with(|frozen| frozen.replace(at))
+}
+
+/// The time the current transaction started, like SQL's `transaction_timestamp()` and `now()`.
+///
+/// It's the same for every call within a transaction.
+pub fn transaction_timestamp() -> TimestampWithTimeZone {
+    to_timestamp(unsafe { pg_sys::GetCurrentTransactionStartTimestamp() })
+}
+
+/// The time the current statement started, like SQL's `statement_timestamp()`.
+///
+/// It's the same for every call within a statement, and the same as [`transaction_timestamp()`]
+/// for the first statement of a transaction.
+pub fn statement_timestamp() -> TimestampWithTimeZone {
+    to_timestamp(unsafe { pg_sys::GetCurrentStatementStartTimestamp() })
+}
+
+/// The current time, like SQL's `clock_timestamp()`.
+///
+/// It changes between calls, so a function that uses it must be declared `VOLATILE`.
+pub fn clock_timestamp() -> TimestampWithTimeZone {
+    to_timestamp(unsafe { pg_sys::GetCurrentTimestamp() })
+}
+
+#[inline]
+fn to_timestamp(ts: pg_sys::TimestampTz) -> TimestampWithTimeZone {
+    let ts = FROZEN.with(|frozen| frozen.get()).unwrap_or(ts);
+    ts.try_into().expect("the clock is outside the range of timestamp with time zone")
+}
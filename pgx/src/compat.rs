@@ -0,0 +1,738 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! One spelling for Postgres APIs that were renamed or reshaped between major versions.
+//!
+//! Postgres 12 introduced table access methods, which renamed most of the `heap_*` functions an
+//! extension uses to read a relation and changed how tuple slots are created, filled and read.
+//! The raw bindings in [`pg_sys`] follow whichever version pgx was compiled against, so code that
+//! uses them directly needs a `#[cfg(feature = "pgXX")]` arm for every version it supports.
+//!
+//! The functions here are named after their Postgres 12+ counterparts and do the right thing on
+//! every supported version.  Several of them, such as [`table_beginscan`], are `static inline`
+//! functions in Postgres' headers and so have no binding in [`pg_sys`] at all.
+//!
+//! Operations that an older version can't provide are simply not defined when compiling for
+//! that version, so using them is a compile error rather than a runtime surprise.  For example,
+//! [`make_virtual_tuple_slot`] doesn't exist with the `pg11` feature.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::compat;
+//! use pgx::pg_sys;
+//!
+//! unsafe fn count_rows(relid: pg_sys::Oid) -> usize {
+//!     let rel = compat::table_open(relid, pg_sys::AccessShareLock as _);
+//!     let scan = compat::table_beginscan(rel, pg_sys::GetActiveSnapshot(), 0, std::ptr::null_mut());
+//!     let slot = compat::table_slot_create(rel);
+//!
+//!     let mut count = 0;
+//!     while compat::table_scan_getnextslot(scan, pg_sys::ScanDirection_ForwardScanDirection, slot) {
+//!         count += 1;
+//!     }
+//!
+//!     pg_sys::ExecDropSingleTupleTableSlot(slot);
+//!     compat::table_endscan(scan);
+//!     compat::table_close(rel, pg_sys::AccessShareLock as _);
+//!     count
+//! }
+//! ```
+use crate::pg_sys;
+use std::ffi::CString;
+use std::os::raw::c_void;
+
+/// A scan over a relation, as returned by [`table_beginscan`] and [`table_beginscan_catalog`]
+#[cfg(feature = "pg11")]
+pub type TableScanDesc = pg_sys::HeapScanDesc;
+
+/// A scan over a relation, as returned by [`table_beginscan`] and [`table_beginscan_catalog`]
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub type TableScanDesc = pg_sys::TableScanDesc;
+
+//
+// opening and closing relations
+//
+
+/// Open the table with the given OID, taking `lockmode`.
+///
+/// This is `heap_open` on Postgres 11.
+///
+/// # Safety
+///
+/// Must be called inside a transaction.  `relid` must identify a table, and the returned relation
+/// must be closed with [`table_close`].
+#[cfg(feature = "pg11")]
+pub unsafe fn table_open(relid: pg_sys::Oid, lockmode: pg_sys::LOCKMODE) -> pg_sys::Relation {
+    pg_sys::heap_open(relid, lockmode)
+}
+
+/// Open the table with the given OID, taking `lockmode`.
+///
+/// This is `heap_open` on Postgres 11.
+///
+/// # Safety
+///
+/// Must be called inside a transaction.  `relid` must identify a table, and the returned relation
+/// must be closed with [`table_close`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_open(relid: pg_sys::Oid, lockmode: pg_sys::LOCKMODE) -> pg_sys::Relation {
+    pg_sys::table_open(relid, lockmode)
+}
+
+/// Close a relation opened with [`table_open`], releasing `lockmode` unless it's `NoLock`.
+///
+/// # Safety
+///
+/// `rel` must have been opened with [`table_open`] and must not be used afterwards.
+#[cfg(feature = "pg11")]
+pub unsafe fn table_close(rel: pg_sys::Relation, lockmode: pg_sys::LOCKMODE) {
+    // `heap_close` is a macro for `relation_close` on Postgres 11
+    pg_sys::relation_close(rel, lockmode)
+}
+
+/// Close a relation opened with [`table_open`], releasing `lockmode` unless it's `NoLock`.
+///
+/// # Safety
+///
+/// `rel` must have been opened with [`table_open`] and must not be used afterwards.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_close(rel: pg_sys::Relation, lockmode: pg_sys::LOCKMODE) {
+    pg_sys::table_close(rel, lockmode)
+}
+
+//
+// sequential scans
+//
+
+/// Start a sequential scan of `rel` using `snapshot`, filtered by `nkeys` scan keys.
+///
+/// # Safety
+///
+/// `rel` must be an open relation, `snapshot` must stay registered or active for the duration of
+/// the scan, and `key` must point to `nkeys` scan keys (or be null when `nkeys` is zero).  The scan
+/// must be ended with [`table_endscan`].
+#[cfg(feature = "pg11")]
+pub unsafe fn table_beginscan(
+    rel: pg_sys::Relation,
+    snapshot: pg_sys::Snapshot,
+    nkeys: i32,
+    key: pg_sys::ScanKey,
+) -> TableScanDesc {
+    pg_sys::heap_beginscan(rel, snapshot, nkeys, key)
+}
+
+/// Start a sequential scan of `rel` using `snapshot`, filtered by `nkeys` scan keys.
+///
+/// # Safety
+///
+/// `rel` must be an open relation, `snapshot` must stay registered or active for the duration of
+/// the scan, and `key` must point to `nkeys` scan keys (or be null when `nkeys` is zero).  The scan
+/// must be ended with [`table_endscan`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_beginscan(
+    rel: pg_sys::Relation,
+    snapshot: pg_sys::Snapshot,
+    nkeys: i32,
+    key: pg_sys::ScanKey,
+) -> TableScanDesc {
+    // a port of the `static inline` `table_beginscan` from Postgres' `tableam.h`
+    let flags = pg_sys::ScanOptions_SO_TYPE_SEQSCAN
+        | pg_sys::ScanOptions_SO_ALLOW_STRAT
+        | pg_sys::ScanOptions_SO_ALLOW_SYNC
+        | pg_sys::ScanOptions_SO_ALLOW_PAGEMODE;
+    let scan_begin = (*(*rel).rd_tableam).scan_begin.expect("table AM has no scan_begin");
+    scan_begin(rel, snapshot, nkeys, key, std::ptr::null_mut(), flags)
+}
+
+/// Start a sequential scan of the catalog relation `rel`, using a fresh catalog snapshot that's
+/// released by [`table_endscan`].
+///
+/// # Safety
+///
+/// `rel` must be an open catalog relation and `key` must point to `nkeys` scan keys (or be null
+/// when `nkeys` is zero).  The scan must be ended with [`table_endscan`].
+#[cfg(feature = "pg11")]
+pub unsafe fn table_beginscan_catalog(
+    rel: pg_sys::Relation,
+    nkeys: i32,
+    key: pg_sys::ScanKey,
+) -> TableScanDesc {
+    pg_sys::heap_beginscan_catalog(rel, nkeys, key)
+}
+
+/// Start a sequential scan of the catalog relation `rel`, using a fresh catalog snapshot that's
+/// released by [`table_endscan`].
+///
+/// # Safety
+///
+/// `rel` must be an open catalog relation and `key` must point to `nkeys` scan keys (or be null
+/// when `nkeys` is zero).  The scan must be ended with [`table_endscan`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_beginscan_catalog(
+    rel: pg_sys::Relation,
+    nkeys: i32,
+    key: pg_sys::ScanKey,
+) -> TableScanDesc {
+    pg_sys::table_beginscan_catalog(rel, nkeys, key)
+}
+
+/// Store the next tuple of `scan` in `slot`, returning `false`, with `slot` cleared, once the scan
+/// is exhausted.
+///
+/// # Safety
+///
+/// `scan` must be a scan started by [`table_beginscan`] or [`table_beginscan_catalog`], and `slot`
+/// must be compatible with the scanned relation, such as one made by [`table_slot_create`].
+#[cfg(feature = "pg11")]
+pub unsafe fn table_scan_getnextslot(
+    scan: TableScanDesc,
+    direction: pg_sys::ScanDirection,
+    slot: *mut pg_sys::TupleTableSlot,
+) -> bool {
+    let tuple = pg_sys::heap_getnext(scan, direction);
+    if tuple.is_null() {
+        pg_sys::ExecClearTuple(slot);
+        false
+    } else {
+        // the tuple lives in the scan's current buffer, which the slot pins for as long as it
+        // holds the tuple
+        pg_sys::ExecStoreTuple(tuple, slot, (*scan).rs_cbuf, false);
+        true
+    }
+}
+
+/// Store the next tuple of `scan` in `slot`, returning `false`, with `slot` cleared, once the scan
+/// is exhausted.
+///
+/// # Safety
+///
+/// `scan` must be a scan started by [`table_beginscan`] or [`table_beginscan_catalog`], and `slot`
+/// must be compatible with the scanned relation, such as one made by [`table_slot_create`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_scan_getnextslot(
+    scan: TableScanDesc,
+    direction: pg_sys::ScanDirection,
+    slot: *mut pg_sys::TupleTableSlot,
+) -> bool {
+    // a port of the `static inline` `table_scan_getnextslot` from Postgres' `tableam.h`
+    let rel = (*scan).rs_rd;
+    (*slot).tts_tableOid = (*rel).rd_id;
+    let getnextslot =
+        (*(*rel).rd_tableam).scan_getnextslot.expect("table AM has no scan_getnextslot");
+    getnextslot(scan, direction, slot)
+}
+
+/// End a scan started by [`table_beginscan`] or [`table_beginscan_catalog`].
+///
+/// # Safety
+///
+/// `scan` must not be used afterwards.
+#[cfg(feature = "pg11")]
+pub unsafe fn table_endscan(scan: TableScanDesc) {
+    pg_sys::heap_endscan(scan)
+}
+
+/// End a scan started by [`table_beginscan`] or [`table_beginscan_catalog`].
+///
+/// # Safety
+///
+/// `scan` must not be used afterwards.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_endscan(scan: TableScanDesc) {
+    // a port of the `static inline` `table_endscan` from Postgres' `tableam.h`
+    let scan_end = (*(*(*scan).rs_rd).rd_tableam).scan_end.expect("table AM has no scan_end");
+    scan_end(scan)
+}
+
+//
+// tuple slots
+//
+
+/// Make a standalone slot for tuples of `rel`, as returned by [`table_scan_getnextslot`].
+///
+/// Postgres 11 only has heap tables, so this is a heap tuple slot there.
+///
+/// # Safety
+///
+/// `rel` must be an open relation.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(feature = "pg11")]
+pub unsafe fn table_slot_create(rel: pg_sys::Relation) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::MakeSingleTupleTableSlot((*rel).rd_att)
+}
+
+/// Make a standalone slot for tuples of `rel`, as returned by [`table_scan_getnextslot`].
+///
+/// Postgres 11 only has heap tables, so this is a heap tuple slot there.
+///
+/// # Safety
+///
+/// `rel` must be an open relation.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn table_slot_create(rel: pg_sys::Relation) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::table_slot_create(rel, std::ptr::null_mut())
+}
+
+/// Make a standalone slot that holds heap tuples described by `tupdesc`.
+///
+/// # Safety
+///
+/// `tupdesc` must outlive the slot.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(feature = "pg11")]
+pub unsafe fn make_heap_tuple_slot(tupdesc: pg_sys::TupleDesc) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::MakeSingleTupleTableSlot(tupdesc)
+}
+
+/// Make a standalone slot that holds heap tuples described by `tupdesc`.
+///
+/// # Safety
+///
+/// `tupdesc` must outlive the slot.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn make_heap_tuple_slot(tupdesc: pg_sys::TupleDesc) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::MakeSingleTupleTableSlot(tupdesc, &pg_sys::TTSOpsHeapTuple)
+}
+
+/// Make a standalone slot that holds minimal tuples described by `tupdesc`, as a tuplestore or
+/// a hash table does.
+///
+/// # Safety
+///
+/// `tupdesc` must outlive the slot.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(feature = "pg11")]
+pub unsafe fn make_minimal_tuple_slot(tupdesc: pg_sys::TupleDesc) -> *mut pg_sys::TupleTableSlot {
+    // every Postgres 11 slot can hold a minimal tuple
+    pg_sys::MakeSingleTupleTableSlot(tupdesc)
+}
+
+/// Make a standalone slot that holds minimal tuples described by `tupdesc`, as a tuplestore or
+/// a hash table does.
+///
+/// # Safety
+///
+/// `tupdesc` must outlive the slot.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn make_minimal_tuple_slot(tupdesc: pg_sys::TupleDesc) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::MakeSingleTupleTableSlot(tupdesc, &pg_sys::TTSOpsMinimalTuple)
+}
+
+/// Make a standalone "virtual" slot, which holds a tuple as separate `Datum`s rather than as a
+/// formed heap tuple.
+///
+/// Postgres 11 has no virtual slots, so this doesn't exist with the `pg11` feature.
+///
+/// # Safety
+///
+/// `tupdesc` must outlive the slot.  The slot must be released with
+/// [`pg_sys::ExecDropSingleTupleTableSlot`].
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn make_virtual_tuple_slot(tupdesc: pg_sys::TupleDesc) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::MakeSingleTupleTableSlot(tupdesc, &pg_sys::TTSOpsVirtual)
+}
+
+/// Store the palloc'd heap tuple `tuple` in `slot`, which frees it when cleared if `should_free`
+/// is true.
+///
+/// # Safety
+///
+/// `slot` must be a heap tuple slot, such as one made by [`make_heap_tuple_slot`], and `tuple`
+/// must match its tuple descriptor and outlive the slot's use of it.
+#[cfg(feature = "pg11")]
+pub unsafe fn exec_store_heap_tuple(
+    tuple: pg_sys::HeapTuple,
+    slot: *mut pg_sys::TupleTableSlot,
+    should_free: bool,
+) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::ExecStoreTuple(tuple, slot, pg_sys::InvalidBuffer as pg_sys::Buffer, should_free)
+}
+
+/// Store the palloc'd heap tuple `tuple` in `slot`, which frees it when cleared if `should_free`
+/// is true.
+///
+/// # Safety
+///
+/// `slot` must be a heap tuple slot, such as one made by [`make_heap_tuple_slot`], and `tuple`
+/// must match its tuple descriptor and outlive the slot's use of it.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn exec_store_heap_tuple(
+    tuple: pg_sys::HeapTuple,
+    slot: *mut pg_sys::TupleTableSlot,
+    should_free: bool,
+) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::ExecStoreHeapTuple(tuple, slot, should_free)
+}
+
+/// Return the tuple held by `slot` as a heap tuple owned by the slot, forming one if necessary.
+///
+/// # Safety
+///
+/// `slot` must not be empty, and the returned tuple is only valid until the slot is cleared.
+#[cfg(feature = "pg11")]
+pub unsafe fn exec_fetch_slot_heap_tuple(slot: *mut pg_sys::TupleTableSlot) -> pg_sys::HeapTuple {
+    pg_sys::ExecFetchSlotTuple(slot)
+}
+
+/// Return the tuple held by `slot` as a heap tuple owned by the slot, forming one if necessary.
+///
+/// # Safety
+///
+/// `slot` must not be empty, and the returned tuple is only valid until the slot is cleared.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn exec_fetch_slot_heap_tuple(slot: *mut pg_sys::TupleTableSlot) -> pg_sys::HeapTuple {
+    // materializing makes the slot own the tuple, which is what Postgres 11 always did
+    pg_sys::ExecFetchSlotHeapTuple(slot, true, std::ptr::null_mut())
+}
+
+/// Is `slot` empty, holding no tuple at all?
+///
+/// # Safety
+///
+/// `slot` must be a valid slot.
+#[cfg(feature = "pg11")]
+pub unsafe fn tts_empty(slot: *const pg_sys::TupleTableSlot) -> bool {
+    (*slot).tts_isempty
+}
+
+/// Is `slot` empty, holding no tuple at all?
+///
+/// # Safety
+///
+/// `slot` must be a valid slot.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn tts_empty(slot: *const pg_sys::TupleTableSlot) -> bool {
+    (*slot).tts_flags as u32 & pg_sys::TTS_FLAG_EMPTY != 0
+}
+
+/// Empty `slot`, releasing whatever it owned.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot, and nothing may still use the `Datum`s that were read from it.
+#[cfg(feature = "pg11")]
+pub unsafe fn exec_clear_tuple(slot: *mut pg_sys::TupleTableSlot) -> *mut pg_sys::TupleTableSlot {
+    pg_sys::ExecClearTuple(slot)
+}
+
+/// Empty `slot`, releasing whatever it owned.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot, and nothing may still use the `Datum`s that were read from it.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn exec_clear_tuple(slot: *mut pg_sys::TupleTableSlot) -> *mut pg_sys::TupleTableSlot {
+    // a port of the `static inline` `ExecClearTuple` from Postgres' `tuptable.h`
+    let clear = (*(*slot).tts_ops).clear.expect("slot has no clear callback");
+    clear(slot);
+    slot
+}
+
+/// Copy everything `slot` refers to into memory it owns, so it no longer depends on a buffer, on
+/// another slot, or on whoever stored its `Datum`s.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot that isn't empty.
+#[cfg(feature = "pg11")]
+pub unsafe fn exec_materialize_slot(slot: *mut pg_sys::TupleTableSlot) {
+    pg_sys::ExecMaterializeSlot(slot);
+}
+
+/// Copy everything `slot` refers to into memory it owns, so it no longer depends on a buffer, on
+/// another slot, or on whoever stored its `Datum`s.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot that isn't empty.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn exec_materialize_slot(slot: *mut pg_sys::TupleTableSlot) {
+    // a port of the `static inline` `ExecMaterializeSlot` from Postgres' `tuptable.h`
+    let materialize = (*(*slot).tts_ops).materialize.expect("slot has no materialize callback");
+    materialize(slot)
+}
+
+/// Read attribute `attnum` of the tuple in `slot`, deforming the tuple only as far as needed and
+/// caching what was deformed in the slot.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot that isn't empty, and `attnum` must be between 1 and the number
+/// of attributes of its tuple descriptor.  A pass-by-reference `Datum` is only valid until the
+/// slot is cleared.
+#[cfg(feature = "pg11")]
+pub unsafe fn slot_getattr(
+    slot: *mut pg_sys::TupleTableSlot,
+    attnum: i32,
+    isnull: *mut bool,
+) -> pg_sys::Datum {
+    pg_sys::slot_getattr(slot, attnum, isnull)
+}
+
+/// Read attribute `attnum` of the tuple in `slot`, deforming the tuple only as far as needed and
+/// caching what was deformed in the slot.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot that isn't empty, and `attnum` must be between 1 and the number
+/// of attributes of its tuple descriptor.  A pass-by-reference `Datum` is only valid until the
+/// slot is cleared.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn slot_getattr(
+    slot: *mut pg_sys::TupleTableSlot,
+    attnum: i32,
+    isnull: *mut bool,
+) -> pg_sys::Datum {
+    // a port of the `static inline` `slot_getattr` from Postgres' `tuptable.h`, for user attributes
+    if attnum > (*slot).tts_nvalid as i32 {
+        pg_sys::slot_getsomeattrs_int(slot, attnum);
+    }
+    let index = attnum as usize - 1;
+    *isnull = *(*slot).tts_isnull.add(index);
+    *(*slot).tts_values.add(index)
+}
+
+/// Return a palloc'd copy of the tuple in `slot`, as a heap tuple, in the current memory context.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot that isn't empty.
+#[cfg(feature = "pg11")]
+pub unsafe fn exec_copy_slot_heap_tuple(slot: *mut pg_sys::TupleTableSlot) -> pg_sys::HeapTuple {
+    pg_sys::ExecCopySlotTuple(slot)
+}
+
+/// Return a palloc'd copy of the tuple in `slot`, as a heap tuple, in the current memory context.
+///
+/// # Safety
+///
+/// `slot` must be a valid slot that isn't empty.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn exec_copy_slot_heap_tuple(slot: *mut pg_sys::TupleTableSlot) -> pg_sys::HeapTuple {
+    // a port of the `static inline` `ExecCopySlotHeapTuple` from Postgres' `tuptable.h`
+    let copy_heap_tuple =
+        (*(*slot).tts_ops).copy_heap_tuple.expect("slot has no copy_heap_tuple callback");
+    copy_heap_tuple(slot)
+}
+
+/// Store the palloc'd heap tuple `tuple` in `slot`, whatever kind of slot it is, which frees it
+/// when it's no longer needed if `should_free` is true.
+///
+/// Unlike [`exec_store_heap_tuple`], the slot doesn't need to be a heap tuple slot: virtual and
+/// minimal tuple slots deform the tuple instead.
+///
+/// # Safety
+///
+/// `tuple` must match the tuple descriptor of `slot`, and outlive the slot's use of it.
+#[cfg(feature = "pg11")]
+pub unsafe fn exec_force_store_heap_tuple(
+    tuple: pg_sys::HeapTuple,
+    slot: *mut pg_sys::TupleTableSlot,
+    should_free: bool,
+) {
+    // every Postgres 11 slot can hold a heap tuple
+    pg_sys::ExecStoreTuple(tuple, slot, pg_sys::InvalidBuffer as pg_sys::Buffer, should_free);
+}
+
+/// Store the palloc'd heap tuple `tuple` in `slot`, whatever kind of slot it is, which frees it
+/// when it's no longer needed if `should_free` is true.
+///
+/// Unlike [`exec_store_heap_tuple`], the slot doesn't need to be a heap tuple slot: virtual and
+/// minimal tuple slots deform the tuple instead.
+///
+/// # Safety
+///
+/// `tuple` must match the tuple descriptor of `slot`, and outlive the slot's use of it.
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn exec_force_store_heap_tuple(
+    tuple: pg_sys::HeapTuple,
+    slot: *mut pg_sys::TupleTableSlot,
+    should_free: bool,
+) {
+    pg_sys::ExecForceStoreHeapTuple(tuple, slot, should_free)
+}
+
+//
+// timestamps
+//
+
+/// The current time, as a `timestamp with time zone`.
+///
+/// Unlike `now()`, this isn't fixed for the duration of the transaction.
+pub fn get_current_timestamp() -> pg_sys::TimestampTz {
+    unsafe { pg_sys::GetCurrentTimestamp() }
+}
+
+/// The start time of the current transaction, which is what SQL's `now()` returns.
+pub fn get_current_transaction_start_timestamp() -> pg_sys::TimestampTz {
+    unsafe { pg_sys::GetCurrentTransactionStartTimestamp() }
+}
+
+/// The number of milliseconds from `start` to `stop`, rounded up and clamped to zero when `stop`
+/// is before `start`.
+pub fn timestamp_difference_milliseconds(
+    start: pg_sys::TimestampTz,
+    stop: pg_sys::TimestampTz,
+) -> i64 {
+    unsafe { pg_sys::TimestampDifferenceMilliseconds(start, stop) as i64 }
+}
+
+/// Has at least `msec` milliseconds passed between `start` and `stop`?
+pub fn timestamp_difference_exceeds(
+    start: pg_sys::TimestampTz,
+    stop: pg_sys::TimestampTz,
+    msec: i32,
+) -> bool {
+    unsafe { pg_sys::TimestampDifferenceExceeds(start, stop, msec) }
+}
+
+//
+// lists
+//
+
+/// The number of elements in `list`, which may be `NIL` (a null pointer).
+///
+/// # Safety
+///
+/// `list` must be null or point to a valid `List`.
+pub unsafe fn list_length(list: *const pg_sys::List) -> usize {
+    if list.is_null() {
+        0
+    } else {
+        (*list).length as usize
+    }
+}
+
+/// The `n`th pointer of `list`, which is a linked list before Postgres 13 and an array after.
+///
+/// # Safety
+///
+/// `list` must be a valid pointer `List` and `n` must be less than its [`list_length`].
+#[cfg(any(feature = "pg11", feature = "pg12"))]
+pub unsafe fn list_nth(list: *const pg_sys::List, n: usize) -> *mut c_void {
+    pg_sys::list_nth(list, n as _)
+}
+
+/// The `n`th pointer of `list`, which is a linked list before Postgres 13 and an array after.
+///
+/// # Safety
+///
+/// `list` must be a valid pointer `List` and `n` must be less than its [`list_length`].
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn list_nth(list: *const pg_sys::List, n: usize) -> *mut c_void {
+    assert!(n < list_length(list), "list index {} out of bounds", n);
+    (*(*list).elements.add(n)).ptr_value
+}
+
+/// The `n`th integer of `list`.
+///
+/// # Safety
+///
+/// `list` must be a valid integer `List` and `n` must be less than its [`list_length`].
+#[cfg(any(feature = "pg11", feature = "pg12"))]
+pub unsafe fn list_nth_int(list: *const pg_sys::List, n: usize) -> i32 {
+    pg_sys::list_nth_int(list, n as _)
+}
+
+/// The `n`th integer of `list`.
+///
+/// # Safety
+///
+/// `list` must be a valid integer `List` and `n` must be less than its [`list_length`].
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn list_nth_int(list: *const pg_sys::List, n: usize) -> i32 {
+    assert!(n < list_length(list), "list index {} out of bounds", n);
+    (*(*list).elements.add(n)).int_value
+}
+
+/// The `n`th OID of `list`.
+///
+/// # Safety
+///
+/// `list` must be a valid OID `List` and `n` must be less than its [`list_length`].
+#[cfg(any(feature = "pg11", feature = "pg12"))]
+pub unsafe fn list_nth_oid(list: *const pg_sys::List, n: usize) -> pg_sys::Oid {
+    pg_sys::list_nth_oid(list, n as _)
+}
+
+/// The `n`th OID of `list`.
+///
+/// # Safety
+///
+/// `list` must be a valid OID `List` and `n` must be less than its [`list_length`].
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub unsafe fn list_nth_oid(list: *const pg_sys::List, n: usize) -> pg_sys::Oid {
+    assert!(n < list_length(list), "list index {} out of bounds", n);
+    (*(*list).elements.add(n)).oid_value
+}
+
+//
+// GUCs
+//
+
+/// Claim every GUC named `prefix.*` for this extension, so that Postgres warns about, and on
+/// Postgres 15 removes, placeholder settings under `prefix` that the extension didn't define.
+///
+/// Call this from `_PG_init()` after defining the extension's GUCs.  It's
+/// `EmitWarningsOnPlaceholders` before Postgres 15.
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13", feature = "pg14"))]
+pub fn mark_guc_prefix_reserved(prefix: &str) {
+    let prefix = CString::new(prefix).expect("GUC prefix contains a null byte");
+    unsafe {
+        pg_sys::EmitWarningsOnPlaceholders(prefix.as_ptr());
+    }
+}
+
+/// Claim every GUC named `prefix.*` for this extension, so that Postgres warns about, and on
+/// Postgres 15 removes, placeholder settings under `prefix` that the extension didn't define.
+///
+/// Call this from `_PG_init()` after defining the extension's GUCs.  It's
+/// `EmitWarningsOnPlaceholders` before Postgres 15.
+#[cfg(feature = "pg15")]
+pub fn mark_guc_prefix_reserved(prefix: &str) {
+    let prefix = CString::new(prefix).expect("GUC prefix contains a null byte");
+    unsafe {
+        pg_sys::MarkGUCPrefixReserved(prefix.as_ptr());
+    }
+}
+
+/// Write `size` bytes at `ptr` to `file`, returning how many were written, which is fewer on an
+/// error.
+///
+/// # Safety
+///
+/// `file` must be a valid, open `BufFile` and `ptr` must point to at least `size` bytes.
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+pub unsafe fn buf_file_write(
+    file: *mut pg_sys::BufFile,
+    ptr: *const std::os::raw::c_void,
+    size: usize,
+) -> usize {
+    pg_sys::BufFileWrite(file, ptr as *mut _, size)
+}
+
+/// Write `size` bytes at `ptr` to `file`, returning how many were written.  Postgres 14 raises an
+/// `ERROR` rather than writing fewer.
+///
+/// # Safety
+///
+/// `file` must be a valid, open `BufFile` and `ptr` must point to at least `size` bytes.
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+pub unsafe fn buf_file_write(
+    file: *mut pg_sys::BufFile,
+    ptr: *const std::os::raw::c_void,
+    size: usize,
+) -> usize {
+    pg_sys::BufFileWrite(file, ptr as *mut _, size);
+    size
+}
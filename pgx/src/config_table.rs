@@ -0,0 +1,341 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Keep a backend-local copy of an extension's configuration table, which is read again after
+//! the table changes, without restarting any backends.
+//!
+//! The table needs a trigger, which [`pg_config_table_trigger!`](crate::pg_config_table_trigger)
+//! defines, to tell every backend when it's changed.  The trigger has Postgres invalidate the
+//! table's relcache entry, and the backends which have read the table notice that and read it again
+//! on their next [`ConfigTable::get()`].
+//!
+//! ```rust,no_run
+//! use pgx::config_table::{self, ConfigTable};
+//! use pgx::prelude::*;
+//! use std::collections::HashMap;
+//!
+//! pgx::pg_config_table_trigger!(myext_config_changed);
+//!
+//! extension_sql!(
+//!     r#"
+//!     CREATE TABLE myext_config (name text PRIMARY KEY, value text NOT NULL);
+//!     CREATE TRIGGER myext_config_changed
+//!         AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON myext_config
+//!         FOR EACH STATEMENT EXECUTE FUNCTION myext_config_changed();
+//!     "#,
+//!     name = "myext_config",
+//!     requires = [myext_config_changed],
+//! );
+//!
+//! thread_local! {
+//!     // Postgres backends are single-threaded, so a `thread_local!` is a per-backend copy
+//!     static CONFIG: ConfigTable<HashMap<String, String>> =
+//!         config_table::watch("myext_config", |rows| {
+//!             rows.map(|row| {
+//!                 let name = row.get_by_name("name")?.unwrap();
+//!                 Ok((name, row.get_by_name("value")?.unwrap()))
+//!             })
+//!             .collect()
+//!         });
+//! }
+//!
+//! #[pg_extern]
+//! fn myext_setting(name: &str) -> Result<Option<String>, config_table::ConfigTableError> {
+//!     Ok(CONFIG.with(|config| config.get())?.get(name).cloned())
+//! }
+//! ```
+//!
+//! ## Transactions
+//!
+//! Postgres only tells other backends about the change once the transaction making it commits, so
+//! they never see configuration which might yet be rolled back.  The transaction making the change
+//! sees it as soon as it's made, as it would with any query, and if it rolls back, the table is
+//! read again.
+//!
+//! A backend which hears about a change in the middle of a transaction may read the table with a
+//! snapshot taken before that change, such as in a `REPEATABLE READ` transaction.  What it reads is
+//! used until the end of the transaction, and then read again.
+//!
+//! ## Before the table exists, and who can read it
+//!
+//! [`ConfigTable::get()`] returns [`ConfigTableError::Missing`] while there's no such table, such as
+//! while `CREATE EXTENSION` hasn't created it yet, and looks for it again next time.
+//!
+//! The table is read as the current user, who needs `SELECT` on it, or else
+//! [`ConfigTableError::PermissionDenied`] is returned.  Once it's read, every role in the backend
+//! shares the copy.
+use crate::heap_tuple::PgHeapTuple;
+use crate::invalidation::register_relcache_callback;
+use crate::spi::{self, SpiTupleTable};
+use crate::trigger_support::{PgTrigger, PgTriggerError};
+use crate::{
+    pg_sys, quote_literal, quote_qualified_identifier, AllocatedByPostgres, PgSqlErrorCode, Spi,
+};
+use std::cell::{Cell, RefCell};
+use std::ffi::CStr;
+use std::rc::Rc;
+
+/// Why a [`ConfigTable`] couldn't be read
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ConfigTableError {
+    #[error("the configuration table \"{0}\" doesn't exist")]
+    Missing(&'static str),
+    #[error("permission denied to read the configuration table \"{0}\"")]
+    PermissionDenied(&'static str),
+    #[error("the configuration table \"{0}\" can only be read in a transaction")]
+    NoTransaction(&'static str),
+    #[error(transparent)]
+    Spi(#[from] spi::Error),
+}
+
+impl crate::ErrorReportable for ConfigTableError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            ConfigTableError::Missing(_) => PgSqlErrorCode::ERRCODE_UNDEFINED_TABLE,
+            ConfigTableError::PermissionDenied(_) => PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE,
+            ConfigTableError::NoTransaction(_) => PgSqlErrorCode::ERRCODE_INVALID_TRANSACTION_STATE,
+            ConfigTableError::Spi(e) => crate::ErrorReportable::sql_error_code(e),
+        }
+    }
+}
+
+/// How far the copy of the table can be trusted
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Freshness {
+    /// The table has changed since it was read, or it hasn't been read
+    Stale,
+    /// Until the table changes
+    Fresh,
+    /// Until the end of the transaction with this local transaction id, as the table changed during
+    /// it, so it may have been read with an older snapshot
+    UntilEndOf(pg_sys::LocalTransactionId),
+}
+
+struct State {
+    freshness: Cell<Freshness>,
+    /// The table, once it's been found
+    relid: Cell<Option<pg_sys::Oid>>,
+    /// The last transaction during which the table may have changed
+    changed_in: Cell<Option<pg_sys::LocalTransactionId>>,
+}
+
+/// A backend-local copy of a configuration table, made by [`watch()`]
+///
+/// Clones share the copy.
+pub struct ConfigTable<T> {
+    table: &'static str,
+    load: Rc<dyn Fn(SpiTupleTable) -> Result<T, spi::Error>>,
+    state: Rc<State>,
+    value: Rc<RefCell<Option<Rc<T>>>>,
+    loads: Rc<Cell<u64>>,
+}
+
+/// Keep a copy of the table named `table`, as `load` makes it from all of the table's rows, which is
+/// made again once the table changes.
+///
+/// `table` is looked up on the `search_path` unless it's qualified by a schema.  The table should
+/// have the trigger [`pg_config_table_trigger!`](crate::pg_config_table_trigger) defines, or else
+/// nothing tells the backend it's changed.  Nothing is read until [`ConfigTable::get()`] is called.
+pub fn watch<T: 'static, F>(table: &'static str, load: F) -> ConfigTable<T>
+where
+    F: Fn(SpiTupleTable) -> Result<T, spi::Error> + 'static,
+{
+    let state = Rc::new(State {
+        freshness: Cell::new(Freshness::Stale),
+        relid: Cell::new(None),
+        changed_in: Cell::new(None),
+    });
+    let weak = Rc::downgrade(&state);
+    register_relcache_callback(Rc::new(move |relid| match weak.upgrade() {
+        Some(state) => {
+            // until the table's found, any relation might be it
+            let watched = state.relid.get();
+            if relid.is_none() || watched.is_none() || relid == watched {
+                state.freshness.set(Freshness::Stale);
+                state.changed_in.set(Some(current_lxid()));
+            }
+            true
+        }
+        None => false,
+    }));
+    ConfigTable {
+        table,
+        load: Rc::new(load),
+        state,
+        value: Rc::new(RefCell::new(None)),
+        loads: Rc::new(Cell::new(0)),
+    }
+}
+
+impl<T> ConfigTable<T> {
+    /// The name of the table, as it was given to [`watch()`]
+    pub fn table(&self) -> &'static str {
+        self.table
+    }
+
+    /// How many times the table has been read
+    pub fn loads(&self) -> u64 {
+        self.loads.get()
+    }
+
+    /// The copy of the table, which is read first if it's changed since it was last read, or
+    /// hasn't been read yet
+    ///
+    /// If reading it fails, the error is returned and it's read again next time.
+    pub fn get(&self) -> Result<Rc<T>, ConfigTableError> {
+        if !unsafe { pg_sys::IsTransactionState() } {
+            return Err(ConfigTableError::NoTransaction(self.table));
+        }
+        // hear about changes other backends have committed since the start of the transaction
+        unsafe { pg_sys::AcceptInvalidationMessages() };
+
+        let lxid = current_lxid();
+        let fresh = match self.state.freshness.get() {
+            Freshness::Stale => false,
+            Freshness::Fresh => true,
+            Freshness::UntilEndOf(until) => until == lxid,
+        };
+        if fresh {
+            if let Some(value) = self.value.borrow().as_ref() {
+                return Ok(value.clone());
+            }
+        }
+
+        // set first, so a change while it's read makes it stale again
+        self.state.freshness.set(if self.state.changed_in.get() == Some(lxid) {
+            Freshness::UntilEndOf(lxid)
+        } else {
+            Freshness::Fresh
+        });
+        match self.read() {
+            Ok(value) => {
+                let value = Rc::new(value);
+                self.loads.set(self.loads.get() + 1);
+                *self.value.borrow_mut() = Some(value.clone());
+                Ok(value)
+            }
+            Err(e) => {
+                self.state.freshness.set(Freshness::Stale);
+                Err(e)
+            }
+        }
+    }
+
+    /// Read the table again on the next [`ConfigTable::get()`]
+    pub fn invalidate(&self) {
+        self.state.freshness.set(Freshness::Stale);
+    }
+
+    fn read(&self) -> Result<T, ConfigTableError> {
+        let relid = Spi::get_one::<pg_sys::Oid>(&format!(
+            "SELECT to_regclass({})::oid",
+            quote_literal(self.table)
+        ))?
+        .ok_or(ConfigTableError::Missing(self.table))?;
+        self.state.relid.set(Some(relid));
+
+        let acl = unsafe {
+            pg_sys::pg_class_aclcheck(relid, pg_sys::GetUserId(), pg_sys::ACL_SELECT as _)
+        };
+        if acl != pg_sys::AclResult_ACLCHECK_OK {
+            return Err(ConfigTableError::PermissionDenied(self.table));
+        }
+
+        // the table that was found, even if the `search_path` changes
+        let query = format!("SELECT * FROM {}", qualified_name(relid, self.table)?);
+        let load = &self.load;
+        Ok(Spi::connect(|client| load(client.select(&query, None, None)?))?)
+    }
+}
+
+impl<T> Clone for ConfigTable<T> {
+    fn clone(&self) -> Self {
+        ConfigTable {
+            table: self.table,
+            load: self.load.clone(),
+            state: self.state.clone(),
+            value: self.value.clone(),
+            loads: self.loads.clone(),
+        }
+    }
+}
+
+/// What the trigger [`pg_config_table_trigger!`](crate::pg_config_table_trigger) defines does: tell
+/// every backend that the trigger's table has changed, once the transaction commits.
+///
+/// It returns the row the trigger was fired for, if any, so it can be used as any kind of trigger,
+/// although an `AFTER .. FOR EACH STATEMENT` one does the least work.
+pub fn changed(
+    trigger: &PgTrigger,
+) -> Result<Option<PgHeapTuple<'_, AllocatedByPostgres>>, PgTriggerError> {
+    let relid = trigger.relid()?;
+    unsafe {
+        // SAFETY:  we're in a transaction, as the trigger is running
+        pg_sys::CacheInvalidateRelcacheByRelid(relid);
+    }
+    Ok(trigger.new().or_else(|| trigger.current()))
+}
+
+/// The name of the relation `relid`, the configuration table `table`, qualified by its schema and
+/// quoted, if there's still such a relation
+fn qualified_name(relid: pg_sys::Oid, table: &'static str) -> Result<String, ConfigTableError> {
+    unsafe {
+        let name = pg_sys::get_rel_name(relid);
+        let schema = pg_sys::get_namespace_name(pg_sys::get_rel_namespace(relid));
+        if name.is_null() || schema.is_null() {
+            return Err(ConfigTableError::Missing(table));
+        }
+        Ok(quote_qualified_identifier(
+            &CStr::from_ptr(schema).to_string_lossy(),
+            &CStr::from_ptr(name).to_string_lossy(),
+        ))
+    }
+}
+
+fn current_lxid() -> pg_sys::LocalTransactionId {
+    unsafe {
+        // SAFETY:  every backend has a `PGPROC`
+        (*pg_sys::MyProc).lxid
+    }
+}
+
+/// Defines a trigger function named `$fn_name` for the table of a
+/// [`ConfigTable`](crate::config_table::ConfigTable), which tells every backend when the table has
+/// changed.
+///
+/// The trigger itself is created with the table, in an [`extension_sql!`](crate::extension_sql)
+/// which `requires` the function:
+///
+/// ```sql
+/// CREATE TRIGGER myext_config_changed
+///     AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON myext_config
+///     FOR EACH STATEMENT EXECUTE FUNCTION myext_config_changed();
+/// ```
+///
+/// One function can be used for any number of tables.
+///
+/// ```rust,no_run
+/// pgx::pg_config_table_trigger!(myext_config_changed);
+/// ```
+#[macro_export]
+macro_rules! pg_config_table_trigger {
+    ($fn_name:ident) => {
+        #[$crate::pg_trigger]
+        fn $fn_name(
+            trigger: &$crate::trigger_support::PgTrigger,
+        ) -> ::core::result::Result<
+            ::core::option::Option<
+                $crate::heap_tuple::PgHeapTuple<'_, $crate::AllocatedByPostgres>,
+            >,
+            $crate::trigger_support::PgTriggerError,
+        > {
+            $crate::config_table::changed(trigger)
+        }
+    };
+}
@@ -34,6 +34,13 @@ impl AnyArray {
         self.typoid
     }
 
+    /// A `value` of type `T`, to return from a function which returns an `anyarray`.  It has to
+    /// be of the type the call resolves it to, such as an array of its `anyelement` arguments.
+    pub fn from_value<T: IntoDatum>(value: T) -> Option<AnyArray> {
+        let typoid = T::type_oid();
+        value.into_datum().map(|datum| AnyArray { datum, typoid })
+    }
+
     #[inline]
     pub fn into<T: FromDatum>(&self) -> Option<T> {
         unsafe { T::from_polymorphic_datum(self.datum(), false, self.oid()) }
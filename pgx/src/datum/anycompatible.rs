@@ -0,0 +1,129 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The [`anycompatible` family of polymorphic pseudo-types][anycompatible], which Postgres 13 added
+//!
+//! Unlike `anyelement`, the arguments of this family don't all have to be of the same type:  they're
+//! converted to a common type, as the arguments of `CASE` or `COALESCE` are.
+//!
+//! [anycompatible]: https://www.postgresql.org/docs/current/extend-type-system.html#EXTEND-TYPES-POLYMORPHIC
+use crate::datum::DebugDatum;
+use crate::{pg_sys, FromDatum, IntoDatum};
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+
+/// The `anycompatible` polymorphic pseudo-type.
+///
+// rustdoc doesn't directly support a warning block: https://github.com/rust-lang/rust/issues/73935
+/// **Warning**: Calling [`FromDatum::from_datum`] with this type will unconditonally panic. Call
+/// [`FromDatum::from_polymorphic_datum`] with a type ID instead.
+#[derive(Clone, Copy)]
+pub struct AnyCompatible {
+    datum: pg_sys::Datum,
+    typoid: pg_sys::Oid,
+}
+
+/// The `anycompatiblearray` polymorphic pseudo-type.
+///
+/// **Warning**: Calling [`FromDatum::from_datum`] with this type will unconditonally panic. Call
+/// [`FromDatum::from_polymorphic_datum`] with a type ID instead.
+#[derive(Clone, Copy)]
+pub struct AnyCompatibleArray {
+    datum: pg_sys::Datum,
+    typoid: pg_sys::Oid,
+}
+
+macro_rules! impl_any_compatible {
+    ($ty:ident, $name:literal, $oid:ident) => {
+        impl std::fmt::Debug for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($ty))
+                    .field(&DebugDatum(self.typoid, Some(self.datum)))
+                    .finish()
+            }
+        }
+
+        impl $ty {
+            pub fn datum(&self) -> pg_sys::Datum {
+                self.datum
+            }
+
+            pub fn oid(&self) -> pg_sys::Oid {
+                self.typoid
+            }
+
+            /// A `value` of type `T`, to return from a function which returns this type.  It has
+            /// to be of the type the call resolves it to.
+            pub fn from_value<T: IntoDatum>(value: T) -> Option<$ty> {
+                let typoid = T::type_oid();
+                value.into_datum().map(|datum| $ty { datum, typoid })
+            }
+
+            /// Convert this value into a specific type.
+            ///
+            /// # Safety
+            ///
+            /// This function is unsafe as it cannot guarantee that the underlying datum can be
+            /// represented as `T`.  This is your responsibility
+            #[inline]
+            pub unsafe fn into<T: FromDatum>(&self) -> Option<T> {
+                T::from_polymorphic_datum(self.datum(), false, self.oid())
+            }
+        }
+
+        impl FromDatum for $ty {
+            const GET_TYPOID: bool = true;
+
+            /// You should **never** call this function to make this type; it will unconditionally
+            /// panic.  For polymorphic types such as this one, you must use
+            /// [`FromDatum::from_polymorphic_datum`] and pass a type ID.
+            #[inline]
+            unsafe fn from_datum(_datum: pg_sys::Datum, _is_null: bool) -> Option<$ty> {
+                panic!("Can't create a polymorphic type using from_datum, call FromDatum::from_polymorphic_datum instead")
+            }
+
+            #[inline]
+            unsafe fn from_polymorphic_datum(
+                datum: pg_sys::Datum,
+                is_null: bool,
+                typoid: pg_sys::Oid,
+            ) -> Option<$ty> {
+                if is_null {
+                    None
+                } else {
+                    Some($ty { datum, typoid })
+                }
+            }
+        }
+
+        impl IntoDatum for $ty {
+            #[inline]
+            fn into_datum(self) -> Option<pg_sys::Datum> {
+                Some(self.datum)
+            }
+
+            fn type_oid() -> pg_sys::Oid {
+                pg_sys::$oid
+            }
+        }
+
+        unsafe impl SqlTranslatable for $ty {
+            fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+                Ok(SqlMapping::literal($name))
+            }
+            fn return_sql() -> Result<Returns, ReturnsError> {
+                Ok(Returns::One(SqlMapping::literal($name)))
+            }
+        }
+    };
+}
+
+impl_any_compatible!(AnyCompatible, "anycompatible", ANYCOMPATIBLEOID);
+impl_any_compatible!(AnyCompatibleArray, "anycompatiblearray", ANYCOMPATIBLEARRAYOID);
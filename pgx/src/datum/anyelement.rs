@@ -7,6 +7,7 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 
+use crate::datum::DebugDatum;
 use crate::{pg_sys, FromDatum, IntoDatum};
 use pgx_sql_entity_graph::metadata::{
     ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
@@ -19,12 +20,18 @@ use pgx_sql_entity_graph::metadata::{
 /// [`FromDatum::from_polymorphic_datum`] with a type ID instead.
 ///
 /// [anyelement]: https://www.postgresql.org/docs/current/extend-type-system.html#EXTEND-TYPES-POLYMORPHIC
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy)]
 pub struct AnyElement {
     datum: pg_sys::Datum,
     typoid: pg_sys::Oid,
 }
 
+impl std::fmt::Debug for AnyElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AnyElement").field(&DebugDatum(self.typoid, Some(self.datum))).finish()
+    }
+}
+
 impl AnyElement {
     pub fn datum(&self) -> pg_sys::Datum {
         self.datum
@@ -34,6 +41,13 @@ impl AnyElement {
         self.typoid
     }
 
+    /// A `value` of type `T`, to return from a function which returns an `anyelement`.  It has to
+    /// be of the type the call resolves it to, which is the type of its `anyelement` arguments.
+    pub fn from_value<T: IntoDatum>(value: T) -> Option<AnyElement> {
+        let typoid = T::type_oid();
+        value.into_datum().map(|datum| AnyElement { datum, typoid })
+    }
+
     /// Convert this element into a specific type.
     ///
     /// # Safety
@@ -8,6 +8,7 @@ Use of this source code is governed by the MIT license that can be found in the
 */
 
 use crate::array::RawArray;
+use crate::datum::DebugText;
 use crate::layout::*;
 use crate::slice::PallocSlice;
 use crate::{pg_sys, FromDatum, IntoDatum, PgMemoryContexts};
@@ -62,6 +63,9 @@ pub struct Array<'a, T: FromDatum> {
     null_slice: NullKind<'a>,
     elem_layout: Layout,
     _marker: PhantomData<T>,
+    /// The elements of an array made by [`Array::from_vec_for_test()`], which has no `raw` array
+    #[cfg(feature = "mock")]
+    mock: Option<crate::mock::MockElements<'a, T>>,
 }
 
 // FIXME: When Array::over gets removed, this enum can probably be dropped
@@ -97,6 +101,29 @@ impl<'a, T: FromDatum + serde::Serialize> serde::Serialize for Array<'a, T> {
     }
 }
 
+impl<'a, T: FromDatum> std::fmt::Debug for Array<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        #[cfg(feature = "mock")]
+        if let Some(mock) = &self.mock {
+            return mock.fmt(f);
+        }
+
+        let oid = self.raw.as_ref().map(|r| r.oid()).unwrap_or_default();
+        f.debug_list()
+            .entries((0..self.nelems).map(|i| {
+                let datum = match self.null_slice.get(i) {
+                    Some(false) => unsafe {
+                        // SAFETY:  i is within nelems
+                        self.datum_slice.as_ref().and_then(|slice| slice.get(i)).copied()
+                    },
+                    _ => None,
+                };
+                DebugText(oid, datum)
+            }))
+            .finish()
+    }
+}
+
 impl<'a, T: FromDatum> Drop for Array<'a, T> {
     fn drop(&mut self) {
         // First drop the slice that references the RawArray
@@ -191,10 +218,33 @@ impl<'a, T: FromDatum> Array<'a, T> {
             null_slice,
             elem_layout,
             _marker: PhantomData,
+            #[cfg(feature = "mock")]
+            mock: None,
+        }
+    }
+
+    /// An array that Postgres has never seen, whose elements come from `mock`
+    #[cfg(feature = "mock")]
+    pub(crate) fn from_mock(mock: crate::mock::MockElements<'a, T>) -> Array<'a, T> {
+        let nelems = mock.len();
+        Array {
+            needs_pfree: false,
+            raw: None,
+            nelems,
+            datum_slice: None,
+            null_slice: NullKind::Strict(nelems),
+            // there's no `raw` array for it to describe
+            elem_layout: Layout { align: Align::Double, size: Size::Varlena, pass: PassBy::Ref },
+            _marker: PhantomData,
+            mock: Some(mock),
         }
     }
 
     pub fn into_array_type(mut self) -> *const pg_sys::ArrayType {
+        #[cfg(feature = "mock")]
+        if self.mock.is_some() {
+            panic!("an array made for a test can't be converted to an `ArrayType`");
+        }
         let ptr = mem::take(&mut self.raw).map(|raw| raw.into_ptr().as_ptr() as _);
         mem::forget(self);
         ptr.unwrap_or(ptr::null())
@@ -234,6 +284,14 @@ impl<'a, T: FromDatum> Array<'a, T> {
     ///
     /// This function will panic when called if the array contains any SQL NULL values.
     pub fn iter_deny_null(&self) -> ArrayTypedIterator<'_, T> {
+        #[cfg(feature = "mock")]
+        if let Some(mock) = &self.mock {
+            if mock.any_nulls() {
+                panic!("array contains NULL");
+            }
+            return ArrayTypedIterator { array: self, curr: 0 };
+        }
+
         if let Some(at) = &self.raw {
             // SAFETY: if Some, then the ArrayType is from Postgres
             if unsafe { at.any_nulls() } {
@@ -259,6 +317,11 @@ impl<'a, T: FromDatum> Array<'a, T> {
     #[allow(clippy::option_option)]
     #[inline]
     pub fn get(&self, i: usize) -> Option<Option<T>> {
+        #[cfg(feature = "mock")]
+        if let Some(mock) = &self.mock {
+            return mock.get(i);
+        }
+
         if i >= self.nelems {
             None
         } else {
@@ -284,7 +347,18 @@ impl<'a, T: FromDatum + serde::Serialize> serde::Serialize for VariadicArray<'a,
     }
 }
 
+impl<'a, T: FromDatum> std::fmt::Debug for VariadicArray<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl<'a, T: FromDatum> VariadicArray<'a, T> {
+    #[cfg(feature = "mock")]
+    pub(crate) fn from_mock(mock: crate::mock::MockElements<'a, T>) -> VariadicArray<'a, T> {
+        VariadicArray(Array::from_mock(mock))
+    }
+
     pub fn into_array_type(self) -> *const pg_sys::ArrayType {
         self.0.into_array_type()
     }
@@ -630,6 +704,7 @@ where
             Returns::One(SqlMapping::Skip) => Err(ReturnsError::SkipInArray),
             Returns::SetOf(_) => Err(ReturnsError::SetOfInArray),
             Returns::Table(_) => Err(ReturnsError::TableInArray),
+            Returns::Record(_) => Err(ReturnsError::RecordInArray),
         }
     }
 }
@@ -661,6 +736,7 @@ where
             Returns::One(SqlMapping::Skip) => Err(ReturnsError::SkipInArray),
             Returns::SetOf(_) => Err(ReturnsError::SetOfInArray),
             Returns::Table(_) => Err(ReturnsError::TableInArray),
+            Returns::Record(_) => Err(ReturnsError::RecordInArray),
         }
     }
 
@@ -7,7 +7,7 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 
-use crate::{pg_sys, FromDatum, IntoDatum};
+use crate::{pg_sys, FromDatum, FromTimeError, IntoDatum};
 use core::ffi::CStr;
 use core::num::TryFromIntError;
 use pgx_sql_entity_graph::metadata::{
@@ -17,7 +17,11 @@ use pgx_sql_entity_graph::metadata::{
 pub const POSTGRES_EPOCH_JDATE: i32 = pg_sys::POSTGRES_EPOCH_JDATE as i32;
 pub const UNIX_EPOCH_JDATE: i32 = pg_sys::UNIX_EPOCH_JDATE as i32;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+// taken from /include/datatype/timestamp.h
+const DATETIME_MIN_JULIAN: i32 = pg_sys::DATETIME_MIN_JULIAN as i32;
+const DATE_END_JULIAN: i32 = pg_sys::DATE_END_JULIAN as i32;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 #[repr(transparent)]
 pub struct Date(i32);
 
@@ -57,12 +61,38 @@ impl FromDatum for Date {
 impl Date {
     pub const NEG_INFINITY: Self = Date(i32::MIN);
     pub const INFINITY: Self = Date(i32::MAX);
+    /// The earliest finite date, `4714-11-24 BC`
+    pub const MIN: Self = Date(DATETIME_MIN_JULIAN - POSTGRES_EPOCH_JDATE);
+    /// The latest finite date, `5874897-12-31`
+    pub const MAX: Self = Date(DATE_END_JULIAN - 1 - POSTGRES_EPOCH_JDATE);
 
     #[inline]
     pub fn from_pg_epoch_days(pg_epoch_days: i32) -> Date {
         Date(pg_epoch_days)
     }
 
+    /// The date `days` days after the Unix epoch, `1970-01-01`.
+    ///
+    /// Returns [`FromTimeError::DaysOutOfBounds`] if that's before [`Self::MIN`] or after
+    /// [`Self::MAX`].
+    pub fn from_unix_epoch_days(days: i32) -> Result<Date, FromTimeError> {
+        days.checked_add(UNIX_EPOCH_JDATE)
+            .ok_or(FromTimeError::DaysOutOfBounds)
+            .and_then(Self::from_julian_days)
+    }
+
+    /// The date of the Julian day number `julian_days`.
+    ///
+    /// Returns [`FromTimeError::DaysOutOfBounds`] if that's before [`Self::MIN`] or after
+    /// [`Self::MAX`].
+    pub fn from_julian_days(julian_days: i32) -> Result<Date, FromTimeError> {
+        if (DATETIME_MIN_JULIAN..DATE_END_JULIAN).contains(&julian_days) {
+            Ok(Date(julian_days - POSTGRES_EPOCH_JDATE))
+        } else {
+            Err(FromTimeError::DaysOutOfBounds)
+        }
+    }
+
     #[inline]
     pub fn is_infinity(&self) -> bool {
         self == &Self::INFINITY
@@ -83,16 +113,28 @@ impl Date {
         self.0
     }
 
-    #[inline]
-    fn to_unix_epoch_days(&self) -> i32 {
-        self.0 + POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE
+    /// The number of days since the Unix epoch, `1970-01-01`.
+    ///
+    /// Returns [`FromTimeError::Infinity`] or [`FromTimeError::NegInfinity`] for the infinite
+    /// dates, and [`FromTimeError::DaysOutOfBounds`] for a date made by
+    /// [`Self::from_pg_epoch_days()`] that's too far after [`Self::MAX`].
+    pub fn to_unix_epoch_days(&self) -> Result<i32, FromTimeError> {
+        match *self {
+            Self::NEG_INFINITY => Err(FromTimeError::NegInfinity),
+            Self::INFINITY => Err(FromTimeError::Infinity),
+            _ => self
+                .0
+                .checked_add(POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE)
+                .ok_or(FromTimeError::DaysOutOfBounds),
+        }
     }
 
     #[inline]
     pub fn to_posix_time(&self) -> libc::time_t {
         let secs_per_day: libc::time_t =
             pg_sys::SECS_PER_DAY.try_into().expect("couldn't fit time into time_t");
-        libc::time_t::from(self.to_unix_epoch_days()) * secs_per_day
+        let unix_epoch_days = self.0 + POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE;
+        libc::time_t::from(unix_epoch_days) * secs_per_day
     }
 }
 
@@ -0,0 +1,94 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Rendering datums for [`Debug`] output, through their types' output functions
+use crate::{pg_sys, PgTryBuilder};
+use std::ffi::CStr;
+use std::fmt::{Debug, Formatter};
+use std::panic::AssertUnwindSafe;
+
+/// What's rendered instead of a datum whose type's output function (or anything else) failed
+const ERROR_RENDERING: &str = "<error rendering>";
+
+/// Render a datum of any type for debugging, as a SQL literal like `'42'::integer`, or as `NULL`.
+///
+/// The value is rendered with the type's output function.  If that, or looking up the type, raises
+/// an `ERROR` or panics, the error is caught and `"<error rendering>"` is returned instead, so
+/// this is safe to use while handling another error.
+///
+/// ## Safety
+///
+/// `datum` must really be of the type `type_oid`.  If it isn't, the output function might read
+/// invalid memory, which no amount of error handling can catch.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::datum::debug_datum;
+///
+/// unsafe {
+///     assert_eq!(debug_datum(pg_sys::INT4OID, 42.into_datum()), "'42'::integer");
+///     assert_eq!(debug_datum(pg_sys::INT4OID, None), "NULL");
+/// }
+/// ```
+pub unsafe fn debug_datum(type_oid: pg_sys::Oid, datum: Option<pg_sys::Datum>) -> String {
+    match datum {
+        None => "NULL".to_string(),
+        Some(datum) => guarded(|| {
+            let value = output_text(type_oid, datum);
+            let type_name = cstr_to_string(pg_sys::format_type_be(type_oid));
+            format!("'{}'::{}", value.replace('\'', "''"), type_name)
+        })
+        .unwrap_or_else(|| ERROR_RENDERING.to_string()),
+    }
+}
+
+/// [`Debug`]s as [`debug_datum()`] renders the datum, which must be of the type
+pub(crate) struct DebugDatum(pub(crate) pg_sys::Oid, pub(crate) Option<pg_sys::Datum>);
+
+impl Debug for DebugDatum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(unsafe { &debug_datum(self.0, self.1) })
+    }
+}
+
+/// [`Debug`]s as the output function's text, quoted like a Rust string, or as `NULL`.  For values
+/// whose type is already known from the Rust type they're in.  The datum must be of the type
+pub(crate) struct DebugText(pub(crate) pg_sys::Oid, pub(crate) Option<pg_sys::Datum>);
+
+impl Debug for DebugText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.1 {
+            None => f.write_str("NULL"),
+            Some(datum) => match guarded(|| unsafe { output_text(self.0, datum) }) {
+                Some(text) => Debug::fmt(&text, f),
+                None => f.write_str(ERROR_RENDERING),
+            },
+        }
+    }
+}
+
+/// Runs `f`, returning `None` if it raises an `ERROR` or panics
+fn guarded<F: FnOnce() -> String>(f: F) -> Option<String> {
+    PgTryBuilder::new(AssertUnwindSafe(|| Some(f()))).catch_others(|_| None).execute()
+}
+
+unsafe fn output_text(type_oid: pg_sys::Oid, datum: pg_sys::Datum) -> String {
+    let mut output_func = pg_sys::InvalidOid;
+    let mut is_varlena = false;
+    pg_sys::getTypeOutputInfo(type_oid, &mut output_func, &mut is_varlena);
+    cstr_to_string(pg_sys::OidOutputFunctionCall(output_func, datum))
+}
+
+unsafe fn cstr_to_string(cstr: *mut std::os::raw::c_char) -> String {
+    let string = CStr::from_ptr(cstr).to_string_lossy().into_owned();
+    pg_sys::pfree(cstr.cast());
+    string
+}
@@ -0,0 +1,117 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Reading pass-by-value datums, and checking a type can be one
+use crate::pg_sys;
+use std::ptr::NonNull;
+
+/// Read a [`pg_sys::Datum`] as the value it holds, the way Postgres' `DatumGetXxx()` macros do
+///
+/// Each method keeps only the low bits its type needs, so it gets back exactly what the matching
+/// `Datum::from_xxx()` constructor, or Postgres, put in.  None of them can tell if the datum
+/// actually holds that type: that's up to the caller.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::DatumExt;
+///
+/// let datum = pg_sys::Datum::from_i16(-2);
+/// assert_eq!(datum.as_i16(), -2);
+/// ```
+pub trait DatumExt {
+    /// `DatumGetBool()`
+    fn as_bool(self) -> bool;
+    /// `DatumGetChar()`, for a `"char"`
+    fn as_char(self) -> i8;
+    /// `DatumGetInt16()`
+    fn as_i16(self) -> i16;
+    /// `DatumGetInt32()`
+    fn as_i32(self) -> i32;
+    /// `DatumGetUInt32()`
+    fn as_u32(self) -> u32;
+    /// `DatumGetInt64()`
+    fn as_i64(self) -> i64;
+    /// `DatumGetFloat4()`
+    fn as_f32(self) -> f32;
+    /// `DatumGetFloat8()`
+    fn as_f64(self) -> f64;
+    /// `DatumGetPointer()`, or `None` if the datum is a null pointer
+    fn as_ptr<T>(self) -> Option<NonNull<T>>;
+}
+
+impl DatumExt for pg_sys::Datum {
+    #[inline]
+    fn as_bool(self) -> bool {
+        self.value() != 0
+    }
+
+    #[inline]
+    fn as_char(self) -> i8 {
+        self.value() as i8
+    }
+
+    #[inline]
+    fn as_i16(self) -> i16 {
+        self.value() as i16
+    }
+
+    #[inline]
+    fn as_i32(self) -> i32 {
+        self.value() as i32
+    }
+
+    #[inline]
+    fn as_u32(self) -> u32 {
+        self.value() as u32
+    }
+
+    #[inline]
+    fn as_i64(self) -> i64 {
+        self.value() as i64
+    }
+
+    #[inline]
+    fn as_f32(self) -> f32 {
+        f32::from_bits(self.as_u32())
+    }
+
+    #[inline]
+    fn as_f64(self) -> f64 {
+        f64::from_bits(self.value() as u64)
+    }
+
+    #[inline]
+    fn as_ptr<T>(self) -> Option<NonNull<T>> {
+        NonNull::new(self.cast_mut_ptr())
+    }
+}
+
+/// Fail to compile unless a `T` fits in a [`pg_sys::Datum`], so it can be passed by value
+///
+/// Use it in a `const` next to a [`FromDatum`](crate::FromDatum) or
+/// [`IntoDatum`](crate::IntoDatum) implementation that copies a `T` in or out of a datum's bits,
+/// rather than pointing to it:
+///
+/// ```rust
+/// #[derive(Copy, Clone)]
+/// #[repr(transparent)]
+/// struct Millis(i64);
+///
+/// const _: () = pgx::assert_pass_by_value::<Millis>();
+/// ```
+///
+/// ```compile_fail
+/// const _: () = pgx::assert_pass_by_value::<[u64; 2]>();
+/// ```
+pub const fn assert_pass_by_value<T>() {
+    assert!(
+        std::mem::size_of::<T>() <= std::mem::size_of::<pg_sys::Datum>(),
+        "the type is larger than a Datum, so it can't be passed by value"
+    );
+}
@@ -10,11 +10,11 @@ Use of this source code is governed by the MIT license that can be found in the
 //! for converting a pg_sys::Datum and a corresponding "is_null" bool into a typed Option
 
 use crate::{
-    pg_sys, text_to_rust_str_unchecked, varlena_to_byte_slice, AllocatedByPostgres, IntoDatum,
-    PgBox, PgMemoryContexts,
+    assert_pass_by_value, pg_sys, text_to_rust_str_unchecked, varlena_to_byte_slice,
+    AllocatedByPostgres, DatumExt, IntoDatum, PgBox, PgMemoryContexts, PgSqlErrorCode,
 };
 use core::ffi::CStr;
-use std::num::NonZeroUsize;
+use std::num::{NonZeroI16, NonZeroI32, NonZeroI64, NonZeroUsize};
 
 /// If converting a Datum to a Rust type fails, this is the set of possible reasons why.
 #[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
@@ -34,6 +34,18 @@ pub enum TryFromDatumError {
     NoSuchAttributeName(String),
 }
 
+impl crate::ErrorReportable for TryFromDatumError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            TryFromDatumError::IncompatibleTypes { .. } => {
+                PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH
+            }
+            TryFromDatumError::NoSuchAttributeNumber(_)
+            | TryFromDatumError::NoSuchAttributeName(_) => PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN,
+        }
+    }
+}
+
 /// Convert a `(pg_sys::Datum, is_null:bool` pair into a Rust type
 ///
 /// Default implementations are provided for the common Rust types.
@@ -76,6 +88,30 @@ pub trait FromDatum {
     where
         Self: Sized;
 
+    /// Like `from_polymorphic_datum`, for the argument of a `#[pg_extern]` function named
+    /// `argument`.  Types which can't represent every value of their SQL type override it to name
+    /// the argument in the `ERROR` they raise for one they can't.
+    ///
+    /// ## Safety
+    ///
+    /// Same caveats as `FromDatum::from_datum(...)`
+    unsafe fn from_argument(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        typoid: pg_sys::Oid,
+        argument: &str,
+    ) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let _ = argument;
+        if Self::GET_TYPOID {
+            FromDatum::from_polymorphic_datum(datum, is_null, typoid)
+        } else {
+            FromDatum::from_datum(datum, is_null)
+        }
+    }
+
     /// Default implementation switched to the specified memory context and then simply calls
     /// `FromDatum::from_datum(...)` from within that context.
     ///
@@ -201,6 +237,16 @@ impl FromDatum for pg_sys::Oid {
     }
 }
 
+// the types below are copied out of a datum's bits
+const _: () = assert_pass_by_value::<bool>();
+const _: () = assert_pass_by_value::<i8>();
+const _: () = assert_pass_by_value::<i16>();
+const _: () = assert_pass_by_value::<i32>();
+const _: () = assert_pass_by_value::<u32>();
+const _: () = assert_pass_by_value::<i64>();
+const _: () = assert_pass_by_value::<f32>();
+const _: () = assert_pass_by_value::<f64>();
+
 /// for bool
 impl FromDatum for bool {
     #[inline]
@@ -212,7 +258,7 @@ impl FromDatum for bool {
         if is_null {
             None
         } else {
-            Some(datum.value() != 0)
+            Some(datum.as_bool())
         }
     }
 }
@@ -228,7 +274,7 @@ impl FromDatum for i8 {
         if is_null {
             None
         } else {
-            Some(datum.value() as _)
+            Some(datum.as_char())
         }
     }
 }
@@ -244,7 +290,7 @@ impl FromDatum for i16 {
         if is_null {
             None
         } else {
-            Some(datum.value() as _)
+            Some(datum.as_i16())
         }
     }
 }
@@ -260,11 +306,69 @@ impl FromDatum for i32 {
         if is_null {
             None
         } else {
-            Some(datum.value() as _)
+            Some(datum.as_i32())
         }
     }
 }
 
+macro_rules! non_zero_from_datum {
+    ($($non_zero:ident => $as_int:ident),* $(,)?) => {$(
+        /// Raises an `ERROR` for zero
+        impl FromDatum for $non_zero {
+            #[inline]
+            unsafe fn from_polymorphic_datum(
+                datum: pg_sys::Datum,
+                is_null: bool,
+                _: pg_sys::Oid,
+            ) -> Option<$non_zero> {
+                if is_null {
+                    None
+                } else {
+                    Some($non_zero::new(datum.$as_int()).unwrap_or_else(|| {
+                        non_zero_is_zero(format!("a {} can't be zero", stringify!($non_zero)))
+                    }))
+                }
+            }
+
+            #[inline]
+            unsafe fn from_argument(
+                datum: pg_sys::Datum,
+                is_null: bool,
+                _: pg_sys::Oid,
+                argument: &str,
+            ) -> Option<$non_zero> {
+                if is_null {
+                    None
+                } else {
+                    Some($non_zero::new(datum.$as_int()).unwrap_or_else(|| {
+                        non_zero_is_zero(format!(
+                            "argument `{}` can't be zero, because it's a {}",
+                            argument,
+                            stringify!($non_zero)
+                        ))
+                    }))
+                }
+            }
+        }
+    )*};
+}
+
+non_zero_from_datum! {
+    NonZeroI16 => as_i16,
+    NonZeroI32 => as_i32,
+    NonZeroI64 => as_i64,
+}
+
+fn non_zero_is_zero(message: String) -> ! {
+    pg_sys::panic::ErrorReport::new(
+        PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+        message,
+        crate::function_name!(),
+    )
+    .report(crate::PgLogLevel::ERROR);
+    unreachable!()
+}
+
 /// for oid
 impl FromDatum for u32 {
     #[inline]
@@ -276,7 +380,7 @@ impl FromDatum for u32 {
         if is_null {
             None
         } else {
-            Some(datum.value() as _)
+            Some(datum.as_u32())
         }
     }
 }
@@ -292,7 +396,7 @@ impl FromDatum for i64 {
         if is_null {
             None
         } else {
-            Some(datum.value() as _)
+            Some(datum.as_i64())
         }
     }
 }
@@ -308,7 +412,7 @@ impl FromDatum for f32 {
         if is_null {
             None
         } else {
-            Some(f32::from_bits(datum.value() as _))
+            Some(datum.as_f32())
         }
     }
 }
@@ -324,7 +428,7 @@ impl FromDatum for f64 {
         if is_null {
             None
         } else {
-            Some(f64::from_bits(datum.value() as _))
+            Some(datum.as_f64())
         }
     }
 }
@@ -33,7 +33,7 @@ impl IntoDatum for pg_sys::BOX {
         unsafe {
             direct_function_call_as_datum(
                 pg_sys::box_out,
-                vec![Some(pg_sys::Datum::from(the_box as *mut pg_sys::BOX))],
+                vec![Some(pg_sys::Datum::from_pointer(the_box as *mut pg_sys::BOX))],
             )
         }
     }
@@ -67,7 +67,7 @@ impl IntoDatum for pg_sys::Point {
         unsafe {
             direct_function_call_as_datum(
                 pg_sys::point_out,
-                vec![Some(pg_sys::Datum::from(point as *mut _))],
+                vec![Some(pg_sys::Datum::from_pointer(point as *mut pg_sys::Point))],
             )
         }
     }
@@ -0,0 +1,139 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use crate::{pg_sys, FromDatum, IntoDatum};
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use std::ops::Deref;
+
+macro_rules! int_vector {
+    (
+        $(#[$meta:meta])*
+        $name:ident, $element:ty, $pg_struct:ident, $build:ident, $oid:ident, $sql:literal
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+        pub struct $name(Vec<$element>);
+
+        impl $name {
+            pub fn as_slice(&self) -> &[$element] {
+                &self.0
+            }
+
+            pub fn into_vec(self) -> Vec<$element> {
+                self.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = [$element];
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl From<Vec<$element>> for $name {
+            fn from(values: Vec<$element>) -> Self {
+                $name(values)
+            }
+        }
+
+        impl FromIterator<$element> for $name {
+            fn from_iter<I: IntoIterator<Item = $element>>(iter: I) -> Self {
+                $name(iter.into_iter().collect())
+            }
+        }
+
+        impl IntoIterator for $name {
+            type Item = $element;
+            type IntoIter = std::vec::IntoIter<$element>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.into_iter()
+            }
+        }
+
+        impl<'a> IntoIterator for &'a $name {
+            type Item = &'a $element;
+            type IntoIter = std::slice::Iter<'a, $element>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.iter()
+            }
+        }
+
+        impl FromDatum for $name {
+            unsafe fn from_polymorphic_datum(
+                datum: pg_sys::Datum,
+                is_null: bool,
+                _typoid: pg_sys::Oid,
+            ) -> Option<$name> {
+                if is_null {
+                    None
+                } else {
+                    // these are never toasted, as they're stored in-line in the catalogs, but a
+                    // value built by something else could still have a short header
+                    let varlena = datum.cast_mut_ptr::<pg_sys::varlena>();
+                    let detoasted = pg_sys::pg_detoast_datum(varlena);
+                    let vector = detoasted as *mut pg_sys::$pg_struct;
+                    let values = (*vector).values.as_slice((*vector).dim1 as usize).to_vec();
+                    if detoasted != varlena {
+                        pg_sys::pfree(detoasted.cast());
+                    }
+                    Some($name(values))
+                }
+            }
+        }
+
+        impl IntoDatum for $name {
+            fn into_datum(self) -> Option<pg_sys::Datum> {
+                let len = self.0.len().try_into().expect(concat!("too many values for an ", $sql));
+                Some(unsafe { pg_sys::$build(self.0.as_ptr(), len) }.into())
+            }
+
+            fn type_oid() -> pg_sys::Oid {
+                pg_sys::$oid
+            }
+        }
+
+        unsafe impl SqlTranslatable for $name {
+            fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+                Ok(SqlMapping::literal($sql))
+            }
+            fn return_sql() -> Result<Returns, ReturnsError> {
+                Ok(Returns::One(SqlMapping::literal($sql)))
+            }
+        }
+    };
+}
+
+int_vector!(
+    /// An `oidvector` from PostgreSQL, such as `pg_proc.proargtypes`
+    ///
+    /// Its values are copied out of the Datum, and can be accessed as a slice through [`Deref`].
+    OidVector,
+    pg_sys::Oid,
+    oidvector,
+    buildoidvector,
+    OIDVECTOROID,
+    "oidvector"
+);
+
+int_vector!(
+    /// An `int2vector` from PostgreSQL, such as `pg_index.indkey`
+    ///
+    /// Its values are copied out of the Datum, and can be accessed as a slice through [`Deref`].
+    Int2Vector,
+    i16,
+    int2vector,
+    buildint2vector,
+    INT2VECTOROID,
+    "int2vector"
+);
@@ -39,7 +39,7 @@ impl Internal {
     /// The value will be dropped when the [PgMemoryContexts::CurrentMemoryContext] is deleted.
     #[inline(always)]
     pub fn new<T>(t: T) -> Self {
-        Self(Some(pg_sys::Datum::from(
+        Self(Some(pg_sys::Datum::from_pointer(
             PgMemoryContexts::CurrentMemoryContext.leak_and_drop_on_delete(t),
         )))
     }
@@ -74,7 +74,7 @@ impl Internal {
     /// your responsibility.
     #[inline(always)]
     pub unsafe fn insert<T>(&mut self, value: T) -> &mut T {
-        let datum = pg_sys::Datum::from(
+        let datum = pg_sys::Datum::from_pointer(
             PgMemoryContexts::CurrentMemoryContext.leak_and_drop_on_delete(value),
         );
         let ptr = self.0.insert(datum);
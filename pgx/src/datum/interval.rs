@@ -0,0 +1,409 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+use super::time::{USECS_PER_DAY, USECS_PER_HOUR, USECS_PER_MINUTE, USECS_PER_SEC};
+use crate::{pg_sys, FromDatum, IntoDatum, PgMemoryContexts, PgSqlErrorCode};
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use std::fmt::{Display, Formatter, Write};
+use std::ops::{Add, Mul, Neg};
+
+const DAYS_PER_MONTH: i32 = 30;
+const MONTHS_PER_YEAR: i32 = 12;
+const SECS_PER_DAY: f64 = 86_400.0;
+
+/// A Postgres `interval`, which is made up of three independent parts:  a number of months, a
+/// number of days, and a number of microseconds.
+///
+/// Like Postgres, the parts are never normalized unless you ask for it with one of the
+/// `justify` functions, so `Interval::new(0, 1, 0)` and `Interval::new(0, 0, 86_400_000_000)` are
+/// not equal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Interval {
+    months: i32,
+    days: i32,
+    micros: i64,
+}
+
+impl Interval {
+    pub const ZERO: Self = Interval { months: 0, days: 0, micros: 0 };
+
+    pub const fn new(months: i32, days: i32, micros: i64) -> Self {
+        Interval { months, days, micros }
+    }
+
+    /// The months part of this interval
+    pub const fn months(&self) -> i32 {
+        self.months
+    }
+
+    /// The days part of this interval
+    pub const fn days(&self) -> i32 {
+        self.days
+    }
+
+    /// The time part of this interval, in microseconds
+    pub const fn micros(&self) -> i64 {
+        self.micros
+    }
+
+    /// Add two intervals, part by part, returning `None` if any part overflows
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(Interval {
+            months: self.months.checked_add(rhs.months)?,
+            days: self.days.checked_add(rhs.days)?,
+            micros: self.micros.checked_add(rhs.micros)?,
+        })
+    }
+
+    /// Negate this interval, returning `None` if any part is at its minimum value
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Interval {
+            months: self.months.checked_neg()?,
+            days: self.days.checked_neg()?,
+            micros: self.micros.checked_neg()?,
+        })
+    }
+
+    /// Multiply this interval by `factor` the same way Postgres' `interval * float8` operator
+    /// does, including cascading fractional months into days and fractional days into time.
+    ///
+    /// Returns `None` if the result is out of range.
+    pub fn checked_mul(self, factor: f64) -> Option<Self> {
+        let months = factor_i32(self.months as f64 * factor)?;
+        let mut days = factor_i32(self.days as f64 * factor)?;
+
+        // cascade the fractional parts down into the smaller units
+        let month_remainder_days =
+            ts_round((self.months as f64 * factor - months as f64) * DAYS_PER_MONTH as f64);
+        let mut sec_remainder = ts_round(
+            (self.days as f64 * factor - days as f64 + month_remainder_days
+                - month_remainder_days.trunc())
+                * SECS_PER_DAY,
+        );
+
+        // rounding might give us 24:00:00, or the cascade might give us more than a day
+        if sec_remainder.abs() >= SECS_PER_DAY {
+            let whole_days = (sec_remainder / SECS_PER_DAY) as i32;
+            days = days.checked_add(whole_days)?;
+            sec_remainder -= whole_days as f64 * SECS_PER_DAY;
+        }
+
+        let days = days.checked_add(month_remainder_days as i32)?;
+        let micros = rint(self.micros as f64 * factor + sec_remainder * USECS_PER_SEC as f64);
+        if micros.is_nan() || micros < i64::MIN as f64 || micros >= -(i64::MIN as f64) {
+            return None;
+        }
+
+        Some(Interval { months, days, micros: micros as i64 })
+    }
+
+    /// Roll 24-hour time periods into days, like Postgres' `justify_hours()`.
+    ///
+    /// ```rust
+    /// use pgx::Interval;
+    ///
+    /// let i = Interval::new(0, 1, -60 * 60 * 1_000_000).justify_hours();
+    /// assert_eq!(i, Interval::new(0, 0, 23 * 60 * 60 * 1_000_000));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Raises a Postgres ERROR if the result is out of range.
+    pub fn justify_hours(self) -> Self {
+        let mut result = self;
+        let whole_days = result.micros / USECS_PER_DAY as i64;
+        result.micros -= whole_days * USECS_PER_DAY as i64;
+        result.days = result.days.checked_add(whole_days as i32).unwrap_or_else(|| out_of_range());
+
+        if result.days > 0 && result.micros < 0 {
+            result.micros += USECS_PER_DAY as i64;
+            result.days -= 1;
+        } else if result.days < 0 && result.micros > 0 {
+            result.micros -= USECS_PER_DAY as i64;
+            result.days += 1;
+        }
+        result
+    }
+
+    /// Roll 30-day time periods into months, like Postgres' `justify_days()`.
+    ///
+    /// # Panics
+    ///
+    /// Raises a Postgres ERROR if the result is out of range.
+    pub fn justify_days(self) -> Self {
+        let mut result = self;
+        let whole_months = result.days / DAYS_PER_MONTH;
+        result.days -= whole_months * DAYS_PER_MONTH;
+        result.months = result.months.checked_add(whole_months).unwrap_or_else(|| out_of_range());
+
+        if result.months > 0 && result.days < 0 {
+            result.days += DAYS_PER_MONTH;
+            result.months -= 1;
+        } else if result.months < 0 && result.days > 0 {
+            result.days -= DAYS_PER_MONTH;
+            result.months += 1;
+        }
+        result
+    }
+
+    /// Adjust this interval using both [`Interval::justify_days()`] and
+    /// [`Interval::justify_hours()`], with additional sign adjustments, like Postgres'
+    /// `justify_interval()`.
+    ///
+    /// ```rust
+    /// use pgx::Interval;
+    ///
+    /// // '1 mon -1 hour' becomes '29 days 23:00:00'
+    /// let i = Interval::new(1, 0, -60 * 60 * 1_000_000).justify();
+    /// assert_eq!(i, Interval::new(0, 29, 23 * 60 * 60 * 1_000_000));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Raises a Postgres ERROR if the result is out of range.
+    pub fn justify(self) -> Self {
+        let mut result = self;
+
+        // pre-justify days if it might prevent overflow
+        if (result.days > 0 && result.micros > 0) || (result.days < 0 && result.micros < 0) {
+            let whole_months = result.days / DAYS_PER_MONTH;
+            result.days -= whole_months * DAYS_PER_MONTH;
+            result.months =
+                result.months.checked_add(whole_months).unwrap_or_else(|| out_of_range());
+        }
+
+        let whole_days = result.micros / USECS_PER_DAY as i64;
+        result.micros -= whole_days * USECS_PER_DAY as i64;
+        result.days = result.days.checked_add(whole_days as i32).unwrap_or_else(|| out_of_range());
+
+        let whole_months = result.days / DAYS_PER_MONTH;
+        result.days -= whole_months * DAYS_PER_MONTH;
+        result.months = result.months.checked_add(whole_months).unwrap_or_else(|| out_of_range());
+
+        if result.months > 0 && (result.days < 0 || (result.days == 0 && result.micros < 0)) {
+            result.days += DAYS_PER_MONTH;
+            result.months -= 1;
+        } else if result.months < 0 && (result.days > 0 || (result.days == 0 && result.micros > 0))
+        {
+            result.days -= DAYS_PER_MONTH;
+            result.months += 1;
+        }
+
+        if result.days > 0 && result.micros < 0 {
+            result.micros += USECS_PER_DAY as i64;
+            result.days -= 1;
+        } else if result.days < 0 && result.micros > 0 {
+            result.micros -= USECS_PER_DAY as i64;
+            result.days += 1;
+        }
+        result
+    }
+}
+
+fn out_of_range() -> ! {
+    pg_sys::ereport!(
+        ERROR,
+        PgSqlErrorCode::ERRCODE_DATETIME_FIELD_OVERFLOW,
+        "interval out of range"
+    );
+}
+
+/// Postgres' `TSROUND()`:  round to microsecond precision
+fn ts_round(value: f64) -> f64 {
+    rint(value * USECS_PER_SEC as f64) / USECS_PER_SEC as f64
+}
+
+/// C's `rint()` under the default rounding mode, which rounds halfway cases to even
+fn rint(value: f64) -> f64 {
+    if (value - value.trunc()).abs() == 0.5 {
+        2.0 * (value / 2.0).round()
+    } else {
+        value.round()
+    }
+}
+
+fn factor_i32(value: f64) -> Option<i32> {
+    if value.is_nan() || value > i32::MAX as f64 || value < i32::MIN as f64 {
+        None
+    } else {
+        Some(value as i32)
+    }
+}
+
+/// Converts a [`std::time::Duration`] into an [`Interval`] made up entirely of microseconds.
+///
+/// The conversion is lossy:  Postgres intervals only have microsecond precision, and since a
+/// `Duration` has no notion of calendar months or days, both are always zero.  Use
+/// [`Interval::justify()`] to spread the time out over days and months.
+///
+/// # Panics
+///
+/// Raises a Postgres ERROR if the duration doesn't fit in an `i64` number of microseconds
+/// (roughly 292,000 years).
+impl From<std::time::Duration> for Interval {
+    fn from(duration: std::time::Duration) -> Self {
+        let micros = i64::try_from(duration.as_micros()).unwrap_or_else(|_| out_of_range());
+        Interval { months: 0, days: 0, micros }
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+
+    /// Postgres' `interval + interval` operator, which raises an ERROR on overflow
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs).unwrap_or_else(|| out_of_range())
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+
+    /// Postgres' unary `-interval` operator, which raises an ERROR on overflow
+    fn neg(self) -> Self::Output {
+        self.checked_neg().unwrap_or_else(|| out_of_range())
+    }
+}
+
+impl Mul<f64> for Interval {
+    type Output = Interval;
+
+    /// Postgres' `interval * float8` operator, which raises an ERROR on overflow
+    fn mul(self, rhs: f64) -> Self::Output {
+        self.checked_mul(rhs).unwrap_or_else(|| out_of_range())
+    }
+}
+
+/// Formats the interval the same way Postgres does with its default `IntervalStyle` of
+/// `postgres`, such as `1 year 2 mons 3 days 04:05:06.789`
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn int_part(
+            f: &mut Formatter<'_>,
+            value: i32,
+            units: &str,
+            is_zero: &mut bool,
+            is_before: &mut bool,
+        ) -> std::fmt::Result {
+            if value == 0 {
+                return Ok(());
+            }
+            write!(
+                f,
+                "{}{}{} {}{}",
+                if !*is_zero { " " } else { "" },
+                if *is_before && value > 0 { "+" } else { "" },
+                value,
+                units,
+                if value != 1 { "s" } else { "" }
+            )?;
+            *is_before = value < 0;
+            *is_zero = false;
+            Ok(())
+        }
+
+        let mut is_zero = true;
+        let mut is_before = false;
+        int_part(f, self.months / MONTHS_PER_YEAR, "year", &mut is_zero, &mut is_before)?;
+        int_part(f, self.months % MONTHS_PER_YEAR, "mon", &mut is_zero, &mut is_before)?;
+        int_part(f, self.days, "day", &mut is_zero, &mut is_before)?;
+
+        let mut time = self.micros;
+        let hours = time / USECS_PER_HOUR as i64;
+        time -= hours * USECS_PER_HOUR as i64;
+        let minutes = time / USECS_PER_MINUTE as i64;
+        time -= minutes * USECS_PER_MINUTE as i64;
+        let seconds = time / USECS_PER_SEC as i64;
+        let fsec = time - seconds * USECS_PER_SEC as i64;
+
+        if is_zero || self.micros != 0 {
+            let minus = hours < 0 || minutes < 0 || seconds < 0 || fsec < 0;
+            write!(
+                f,
+                "{}{}{:02}:{:02}:{:02}",
+                if is_zero { "" } else { " " },
+                if minus {
+                    "-"
+                } else if is_before {
+                    "+"
+                } else {
+                    ""
+                },
+                hours.unsigned_abs(),
+                minutes.unsigned_abs(),
+                seconds.unsigned_abs()
+            )?;
+            if fsec != 0 {
+                let mut fraction = String::with_capacity(7);
+                write!(fraction, ".{:06}", fsec.unsigned_abs())?;
+                f.write_str(fraction.trim_end_matches('0'))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromDatum for Interval {
+    #[inline]
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Interval> {
+        if is_null {
+            None
+        } else {
+            let interval = datum.cast_mut_ptr::<pg_sys::Interval>().read();
+            Some(Interval { months: interval.month, days: interval.day, micros: interval.time })
+        }
+    }
+}
+
+impl IntoDatum for Interval {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        unsafe {
+            // SAFETY:  we just allocated `interval` and it's the right size
+            let interval =
+                PgMemoryContexts::CurrentMemoryContext.palloc_struct::<pg_sys::Interval>();
+            interval.write(pg_sys::Interval {
+                time: self.micros,
+                day: self.days,
+                month: self.months,
+            });
+            Some(pg_sys::Datum::from_pointer(interval))
+        }
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::INTERVALOID
+    }
+}
+
+impl serde::Serialize for Interval {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<<S as serde::Serializer>::Ok, <S as serde::Serializer>::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+unsafe impl SqlTranslatable for Interval {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("interval"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("interval")))
+    }
+}
@@ -106,6 +106,10 @@ where
     /// If we detect that the `Err()` variant contains `[pg_sys::panic::ErrorReport]`, then we
     /// directly raise that as the error.  This enables users to set a specific "sql error code"
     /// for a returned error, along with providing the HINT and DETAIL lines of the error.
+    ///
+    /// `#[pg_extern]` functions returning a `Result` whose error type implements
+    /// [`ErrorReportable`](crate::ErrorReportable) have that error converted to an `ErrorReport`
+    /// first, so it's raised with the SQLSTATE, DETAIL, and HINT it describes.
     #[inline]
     fn into_datum(self) -> Option<Datum> {
         self.report().into_datum()
@@ -121,7 +125,7 @@ where
 impl IntoDatum for bool {
     #[inline]
     fn into_datum(self) -> Option<pg_sys::Datum> {
-        Some(pg_sys::Datum::from(if self { 1 } else { 0 }))
+        Some(pg_sys::Datum::from_bool(self))
     }
 
     fn type_oid() -> pg_sys::Oid {
@@ -132,7 +136,7 @@ impl IntoDatum for bool {
 /// for "char"
 impl IntoDatum for i8 {
     fn into_datum(self) -> Option<pg_sys::Datum> {
-        Some(pg_sys::Datum::from(self))
+        Some(pg_sys::Datum::from_char(self))
     }
 
     fn type_oid() -> pg_sys::Oid {
@@ -144,7 +148,7 @@ impl IntoDatum for i8 {
 impl IntoDatum for i16 {
     #[inline]
     fn into_datum(self) -> Option<pg_sys::Datum> {
-        Some(pg_sys::Datum::from(self))
+        Some(pg_sys::Datum::from_i16(self))
     }
 
     fn type_oid() -> pg_sys::Oid {
@@ -211,6 +215,32 @@ impl IntoDatum for i64 {
     }
 }
 
+/// Like the integers they wrap, as `smallint`, `integer` and `bigint`
+macro_rules! non_zero_into_datum {
+    ($($non_zero:ty => $int:ty),* $(,)?) => {$(
+        impl IntoDatum for $non_zero {
+            #[inline]
+            fn into_datum(self) -> Option<pg_sys::Datum> {
+                self.get().into_datum()
+            }
+
+            fn type_oid() -> pg_sys::Oid {
+                <$int>::type_oid()
+            }
+
+            fn is_compatible_with(other: pg_sys::Oid) -> bool {
+                <$int>::is_compatible_with(other)
+            }
+        }
+    )*};
+}
+
+non_zero_into_datum! {
+    std::num::NonZeroI16 => i16,
+    std::num::NonZeroI32 => i32,
+    std::num::NonZeroI64 => i64,
+}
+
 /// for real
 impl IntoDatum for f32 {
     #[inline]
@@ -394,7 +424,7 @@ impl<'a> IntoDatum for &'a [u8] {
                 self.len(),
             );
 
-            Some(Datum::from(varlena))
+            Some(Datum::from_pointer(varlena))
         }
     }
 
@@ -18,17 +18,27 @@ use serde::{Serialize, Serializer};
 use serde_json::Value;
 
 /// A `json` type from PostgreSQL
-#[derive(Debug)]
 pub struct Json(pub Value);
 
 /// A `jsonb` type from PostgreSQL
-#[derive(Debug)]
 pub struct JsonB(pub Value);
 
 /// A wholly Rust-[`String`][std::string::String] owned copy of a `json` type from PostgreSQL
 #[derive(Debug)]
 pub struct JsonString(pub String);
 
+impl std::fmt::Debug for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Json").field(&format_args!("{}", self.0)).finish()
+    }
+}
+
+impl std::fmt::Debug for JsonB {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("JsonB").field(&format_args!("{}", self.0)).finish()
+    }
+}
+
 /// for json
 impl FromDatum for Json {
     #[inline]
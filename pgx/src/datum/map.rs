@@ -0,0 +1,349 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Maps with `String` keys, as `jsonb` objects or, with the `hstore` feature, as `hstore`s
+//!
+//! A `HashMap<String, serde_json::Value>` or `BTreeMap<String, serde_json::Value>` is a `jsonb`
+//! object, and a `HashMap<String, Option<String>>` or `BTreeMap<String, Option<String>>` is an
+//! `hstore`.  The `hstore` type comes from the extension of the same name, which must be installed
+//! in the database for them to be used.  Its OID is looked up the first time it's needed.
+use crate::{
+    direct_function_call, direct_function_call_as_datum, pg_sys, void_mut_ptr, FromDatum, IntoDatum,
+};
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+
+/// Serialize `map` straight to `jsonb`, without building a [`serde_json::Value`] of it first
+fn map_into_jsonb<M: Serialize>(map: &M) -> pg_sys::Datum {
+    let string = serde_json::to_string(map).expect("failed to serialize map as jsonb");
+    let cstring =
+        alloc::ffi::CString::new(string).expect("string version of jsonb is not valid UTF8");
+    unsafe {
+        direct_function_call_as_datum(pg_sys::jsonb_in, vec![Some(cstring.as_ptr().into())])
+            .expect("jsonb_in returned NULL")
+    }
+}
+
+/// Deserialize a `jsonb` object straight into a map
+unsafe fn map_from_jsonb<M: DeserializeOwned>(datum: pg_sys::Datum) -> M {
+    let varlena = datum.cast_mut_ptr();
+    let detoasted = pg_sys::pg_detoast_datum_packed(varlena);
+
+    let cstr =
+        direct_function_call::<&core::ffi::CStr>(pg_sys::jsonb_out, vec![Some(detoasted.into())])
+            .expect("failed to convert jsonb to a cstring");
+    let map = serde_json::from_str(cstr.to_str().expect("text version of jsonb is not valid UTF8"))
+        .expect("jsonb value is not an object");
+
+    pg_sys::pfree(cstr.as_ptr() as void_mut_ptr);
+    if detoasted != varlena {
+        pg_sys::pfree(detoasted as void_mut_ptr);
+    }
+    map
+}
+
+impl<S: BuildHasher> IntoDatum for HashMap<String, Value, S> {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        Some(map_into_jsonb(&self))
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::JSONBOID
+    }
+}
+
+impl<S: BuildHasher + Default> FromDatum for HashMap<String, Value, S> {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null {
+            None
+        } else {
+            Some(map_from_jsonb(datum))
+        }
+    }
+}
+
+unsafe impl<S> SqlTranslatable for HashMap<String, Value, S> {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("jsonb"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("jsonb")))
+    }
+}
+
+impl IntoDatum for BTreeMap<String, Value> {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        Some(map_into_jsonb(&self))
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::JSONBOID
+    }
+}
+
+impl FromDatum for BTreeMap<String, Value> {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null {
+            None
+        } else {
+            Some(map_from_jsonb(datum))
+        }
+    }
+}
+
+unsafe impl SqlTranslatable for BTreeMap<String, Value> {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("jsonb"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("jsonb")))
+    }
+}
+
+#[cfg(feature = "hstore")]
+pub use hstore::hstore_oid;
+
+#[cfg(feature = "hstore")]
+mod hstore {
+    use super::*;
+    use crate::{set_varsize, vardata_any, varsize_any_exhdr};
+    use std::cell::Cell;
+
+    /// `HS_FLAG_NEWVERSION`, set in the count of every `hstore` written since Postgres 9.0
+    const HS_FLAG_NEWVERSION: u32 = 0x80000000;
+    /// `HS_COUNT()`'s mask
+    const HS_COUNT_MASK: u32 = 0x0FFFFFFF;
+    const HENTRY_ISFIRST: u32 = 0x80000000;
+    const HENTRY_ISNULL: u32 = 0x40000000;
+    const HENTRY_POSMASK: u32 = 0x3FFFFFFF;
+
+    thread_local! {
+        static HSTORE_OID: Cell<Option<pg_sys::Oid>> = Cell::new(None);
+        static WATCHING_TYPES: Cell<bool> = Cell::new(false);
+    }
+
+    /// The OID of the `hstore` type, from the schema the `hstore` extension is installed in.
+    ///
+    /// It's looked up the first time it's needed, and again after any type is dropped or altered,
+    /// in case the extension was dropped and created again.
+    ///
+    /// ## Panics
+    ///
+    /// Raises an `ERROR` if the `hstore` extension isn't installed in the current database.
+    pub fn hstore_oid() -> pg_sys::Oid {
+        if let Some(oid) = HSTORE_OID.with(Cell::get) {
+            return oid;
+        }
+
+        let oid = match crate::Spi::get_one::<pg_sys::Oid>(
+            "SELECT t.oid FROM pg_catalog.pg_type t \
+                JOIN pg_catalog.pg_extension e ON t.typnamespace = e.extnamespace \
+                WHERE e.extname = 'hstore' AND t.typname = 'hstore'",
+        ) {
+            Ok(Some(oid)) => oid,
+            Ok(None) | Err(crate::spi::Error::InvalidPosition) => no_hstore(),
+            Err(e) => panic!("couldn't look up the hstore type: {}", e),
+        };
+
+        if !WATCHING_TYPES.with(|watching| watching.replace(true)) {
+            crate::invalidation::on_syscache_change(pg_sys::SysCacheIdentifier_TYPEOID, |_| {
+                HSTORE_OID.with(|cached| cached.set(None))
+            });
+        }
+        HSTORE_OID.with(|cached| cached.set(Some(oid)));
+        oid
+    }
+
+    fn no_hstore() -> ! {
+        pg_sys::panic::ErrorReport::new(
+            crate::PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT,
+            "type \"hstore\" does not exist",
+            crate::function_name!(),
+        )
+        .set_hint("Maps with `Option<String>` values are hstores, which need the hstore extension.  Run `CREATE EXTENSION hstore`.")
+        .report(crate::PgLogLevel::ERROR);
+        unreachable!()
+    }
+
+    /// Build an `hstore` just like hstore's own `hstorePairs()` does.  Its keys are sorted by
+    /// length, and then bytewise, which is the order hstore looks them up in.
+    unsafe fn pairs_into_hstore<'a>(
+        pairs: impl Iterator<Item = (&'a String, &'a Option<String>)>,
+    ) -> pg_sys::Datum {
+        let mut pairs = pairs.collect::<Vec<_>>();
+        pairs.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+        let count = pairs.len();
+        let data_len = pairs
+            .iter()
+            .map(|(key, value)| key.len() + value.as_ref().map_or(0, |value| value.len()))
+            .sum::<usize>();
+        if count > HS_COUNT_MASK as usize || data_len > HENTRY_POSMASK as usize {
+            pg_sys::ereport!(
+                ERROR,
+                crate::PgSqlErrorCode::ERRCODE_PROGRAM_LIMIT_EXCEEDED,
+                "map is too large to be an hstore"
+            );
+        }
+
+        let header_len = pg_sys::VARHDRSZ + std::mem::size_of::<u32>();
+        let entries_len = count * 2 * std::mem::size_of::<u32>();
+        let total_len = header_len + entries_len + data_len;
+        let hstore = pg_sys::palloc0(total_len) as *mut u8;
+        set_varsize(hstore.cast(), total_len as i32);
+        hstore
+            .add(pg_sys::VARHDRSZ)
+            .cast::<u32>()
+            .write_unaligned(count as u32 | HS_FLAG_NEWVERSION);
+
+        let entries = hstore.add(header_len).cast::<u32>();
+        let strings = hstore.add(header_len + entries_len);
+        let mut end = 0;
+        for (i, (key, value)) in pairs.into_iter().enumerate() {
+            std::ptr::copy_nonoverlapping(key.as_ptr(), strings.add(end), key.len());
+            end += key.len();
+            let first = if i == 0 { HENTRY_ISFIRST } else { 0 };
+            entries.add(2 * i).write_unaligned(end as u32 | first);
+            match value {
+                Some(value) => {
+                    std::ptr::copy_nonoverlapping(value.as_ptr(), strings.add(end), value.len());
+                    end += value.len();
+                    entries.add(2 * i + 1).write_unaligned(end as u32);
+                }
+                None => entries.add(2 * i + 1).write_unaligned(end as u32 | HENTRY_ISNULL),
+            }
+        }
+        pg_sys::Datum::from_pointer(hstore)
+    }
+
+    /// Read the pairs of an `hstore`, whose keys and values are copied into Rust `String`s
+    unsafe fn hstore_into_pairs<M: FromIterator<(String, Option<String>)>>(
+        datum: pg_sys::Datum,
+    ) -> M {
+        let varlena = datum.cast_mut_ptr();
+        let detoasted = pg_sys::pg_detoast_datum(varlena);
+        let len = varsize_any_exhdr(detoasted);
+        let data = vardata_any(detoasted) as *const u8;
+
+        let size = data.cast::<u32>().read_unaligned();
+        if size & HS_FLAG_NEWVERSION == 0 && size != 0 {
+            panic!("hstores in the format from before Postgres 9.0 aren't supported");
+        }
+        let count = (size & HS_COUNT_MASK) as usize;
+        let entries = data.add(std::mem::size_of::<u32>()).cast::<u32>();
+        let strings = entries.add(2 * count).cast::<u8>();
+        let strings_len = len - std::mem::size_of::<u32>() - 2 * count * std::mem::size_of::<u32>();
+        let strings = std::slice::from_raw_parts(strings, strings_len);
+
+        let text = |from: usize, to: usize| {
+            String::from_utf8(strings[from..to].to_vec()).expect("hstore is not valid UTF8")
+        };
+        let mut start = 0;
+        let pairs = (0..count)
+            .map(|i| {
+                let key_end = (entries.add(2 * i).read_unaligned() & HENTRY_POSMASK) as usize;
+                let value_entry = entries.add(2 * i + 1).read_unaligned();
+                let value_end = (value_entry & HENTRY_POSMASK) as usize;
+                let key = text(start, key_end);
+                let value = if value_entry & HENTRY_ISNULL != 0 {
+                    None
+                } else {
+                    Some(text(key_end, value_end))
+                };
+                start = value_end;
+                (key, value)
+            })
+            .collect();
+
+        if detoasted != varlena {
+            pg_sys::pfree(detoasted.cast());
+        }
+        pairs
+    }
+
+    impl<S: BuildHasher> IntoDatum for HashMap<String, Option<String>, S> {
+        fn into_datum(self) -> Option<pg_sys::Datum> {
+            unsafe { Some(pairs_into_hstore(self.iter())) }
+        }
+
+        fn type_oid() -> pg_sys::Oid {
+            hstore_oid()
+        }
+    }
+
+    impl<S: BuildHasher + Default> FromDatum for HashMap<String, Option<String>, S> {
+        unsafe fn from_polymorphic_datum(
+            datum: pg_sys::Datum,
+            is_null: bool,
+            _: pg_sys::Oid,
+        ) -> Option<Self> {
+            if is_null {
+                None
+            } else {
+                Some(hstore_into_pairs(datum))
+            }
+        }
+    }
+
+    unsafe impl<S> SqlTranslatable for HashMap<String, Option<String>, S> {
+        fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+            Ok(SqlMapping::literal("hstore"))
+        }
+        fn return_sql() -> Result<Returns, ReturnsError> {
+            Ok(Returns::One(SqlMapping::literal("hstore")))
+        }
+    }
+
+    impl IntoDatum for BTreeMap<String, Option<String>> {
+        fn into_datum(self) -> Option<pg_sys::Datum> {
+            unsafe { Some(pairs_into_hstore(self.iter())) }
+        }
+
+        fn type_oid() -> pg_sys::Oid {
+            hstore_oid()
+        }
+    }
+
+    impl FromDatum for BTreeMap<String, Option<String>> {
+        unsafe fn from_polymorphic_datum(
+            datum: pg_sys::Datum,
+            is_null: bool,
+            _: pg_sys::Oid,
+        ) -> Option<Self> {
+            if is_null {
+                None
+            } else {
+                Some(hstore_into_pairs(datum))
+            }
+        }
+    }
+
+    unsafe impl SqlTranslatable for BTreeMap<String, Option<String>> {
+        fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+            Ok(SqlMapping::literal("hstore"))
+        }
+        fn return_sql() -> Result<Returns, ReturnsError> {
+            Ok(Returns::One(SqlMapping::literal("hstore")))
+        }
+    }
+}
@@ -10,49 +10,72 @@ Use of this source code is governed by the MIT license that can be found in the
 //! Handing for easily converting Postgres Datum types into their corresponding Rust types
 //! and converting Rust types into their corresponding Postgres types
 mod anyarray;
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+mod anycompatible;
 mod anyelement;
 mod array;
 mod date;
+mod debug;
+mod ext;
 mod from;
 mod geo;
 mod inet;
+mod int_vectors;
 mod internal;
+mod interval;
 mod into;
 mod item_pointer_data;
 mod json;
+mod map;
+mod money;
 pub mod numeric;
 pub mod numeric_support;
+mod polymorphic;
 #[deny(unsafe_op_in_unsafe_fn)]
 mod range;
 mod time;
 mod time_stamp;
 mod time_stamp_with_timezone;
 mod time_with_timezone;
+mod tsearch;
 mod tuples;
 mod uuid;
+mod varbit;
 mod varlena;
 
 pub use self::time::*;
 pub use self::uuid::*;
 pub use anyarray::*;
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+pub use anycompatible::*;
 pub use anyelement::*;
 pub use array::*;
 pub use date::*;
+pub use debug::debug_datum;
+pub(crate) use debug::{DebugDatum, DebugText};
+pub use ext::*;
 pub use from::*;
 pub use geo::*;
 pub use inet::*;
+pub use int_vectors::*;
 pub use internal::*;
+pub use interval::*;
 pub use into::*;
 pub use item_pointer_data::*;
 pub use json::*;
+pub use map::*;
+pub use money::*;
 pub use numeric::{AnyNumeric, Numeric};
 use once_cell::sync::Lazy;
+pub use polymorphic::*;
 pub use range::*;
 use std::any::TypeId;
 pub use time_stamp::*;
 pub use time_stamp_with_timezone::*;
 pub use time_with_timezone::*;
+pub use tsearch::*;
 pub use tuples::*;
+pub use varbit::*;
 pub use varlena::*;
 
 use crate::PgBox;
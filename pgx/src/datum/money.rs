@@ -0,0 +1,183 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+use crate::numeric::Error;
+use crate::{pg_sys, AnyNumeric, FromDatum, IntoDatum};
+use core::str::FromStr;
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// The number of fractional digits [`Money`] assumes when converting to and from other types
+const FRACTIONAL_DIGITS: u32 = 2;
+const SCALE: i64 = 10_i64.pow(FRACTIONAL_DIGITS);
+
+/// A `money` value from PostgreSQL, which is a fixed-point amount stored as a whole number of the
+/// currency's smallest unit
+///
+/// How Postgres itself formats and parses `money` depends on the `lc_monetary` setting, but the
+/// stored value is always an integer.  [`Money`] is that integer, and interprets it as cents:  its
+/// arithmetic is integer arithmetic, and its [`Display`] and conversions to and from
+/// [`AnyNumeric`] assume two fractional digits, whatever the locale.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[repr(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const MIN: Money = Money(i64::MIN);
+    pub const MAX: Money = Money(i64::MAX);
+
+    /// A `money` value of `cents` hundredths of the currency unit
+    pub const fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    /// The stored value, which are hundredths of the currency unit
+    pub const fn cents(&self) -> i64 {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: Money) -> Option<Money> {
+        self.0.checked_add(rhs.0).map(Money)
+    }
+
+    pub fn checked_sub(self, rhs: Money) -> Option<Money> {
+        self.0.checked_sub(rhs.0).map(Money)
+    }
+
+    pub fn checked_mul(self, rhs: i64) -> Option<Money> {
+        self.0.checked_mul(rhs).map(Money)
+    }
+
+    /// Divides by `rhs`, truncating towards zero like Postgres' `money / int8` does.  Returns
+    /// `None` if `rhs` is zero or the result overflows.
+    pub fn checked_div(self, rhs: i64) -> Option<Money> {
+        self.0.checked_div(rhs).map(Money)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    /// # Panics
+    ///
+    /// If the result overflows, like `money + money` does in Postgres 15 and later
+    fn add(self, rhs: Money) -> Money {
+        self.checked_add(rhs).expect("money out of range")
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    /// # Panics
+    ///
+    /// If the result overflows, like `money - money` does in Postgres 15 and later
+    fn sub(self, rhs: Money) -> Money {
+        self.checked_sub(rhs).expect("money out of range")
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(self.0.checked_neg().expect("money out of range"))
+    }
+}
+
+impl Display for Money {
+    /// Formats as a plain decimal with two fractional digits, such as `-1234.50`, which is what
+    /// `money::numeric` returns in the `C` locale
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let scale = SCALE as u64;
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            abs / scale,
+            abs % scale,
+            width = FRACTIONAL_DIGITS as usize
+        )
+    }
+}
+
+impl From<i64> for Money {
+    fn from(cents: i64) -> Self {
+        Money(cents)
+    }
+}
+
+impl From<Money> for i64 {
+    fn from(money: Money) -> Self {
+        money.0
+    }
+}
+
+impl From<Money> for AnyNumeric {
+    fn from(money: Money) -> Self {
+        AnyNumeric::from_str(&money.to_string())
+            .expect("a formatted Money should be a valid numeric")
+    }
+}
+
+impl TryFrom<AnyNumeric> for Money {
+    type Error = Error;
+
+    /// Rounds `value` to the nearest cent, with ties away from zero
+    fn try_from(value: AnyNumeric) -> Result<Self, Self::Error> {
+        i64::try_from(value * SCALE).map(Money)
+    }
+}
+
+impl FromDatum for Money {
+    #[inline]
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Money> {
+        i64::from_polymorphic_datum(datum, is_null, pg_sys::INT8OID).map(Money)
+    }
+}
+
+impl IntoDatum for Money {
+    #[inline]
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        self.0.into_datum()
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::CASHOID
+    }
+}
+
+unsafe impl SqlTranslatable for Money {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("money"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("money")))
+    }
+}
@@ -1,3 +1,4 @@
+use crate::PgSqlErrorCode;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
@@ -27,3 +28,13 @@ impl Display for Error {
 }
 
 impl std::error::Error for Error {}
+
+impl crate::ErrorReportable for Error {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            Error::OutOfRange(_) => PgSqlErrorCode::ERRCODE_NUMERIC_VALUE_OUT_OF_RANGE,
+            Error::Invalid(_) => PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION,
+            Error::ConversionNotSupported(_) => PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+        }
+    }
+}
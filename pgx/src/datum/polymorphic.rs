@@ -0,0 +1,95 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! What the [polymorphic pseudo-types][polymorphic], such as [`AnyElement`], have in common
+//!
+//! A function can return one when one of its arguments is of the same family, and Postgres
+//! resolves the type it returns from the types of the arguments it's called with.  The value it
+//! returns has to be of that type, which the function's wrapper checks.
+//!
+//! [polymorphic]: https://www.postgresql.org/docs/current/extend-type-system.html#EXTEND-TYPES-POLYMORPHIC
+use crate::datum::AnyArray;
+use crate::datum::AnyElement;
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+use crate::datum::{AnyCompatible, AnyCompatibleArray};
+use crate::{pg_sys, PgSqlErrorCode};
+use core::ffi::CStr;
+
+/// A polymorphic value a function can return, or what it can return one in.  Not public API.
+#[doc(hidden)]
+pub trait PolymorphicReturn {
+    fn returned_type_oid(&self) -> Option<pg_sys::Oid>;
+}
+
+macro_rules! impl_polymorphic {
+    ($($ty:ty),*) => {
+        $(
+            impl PolymorphicReturn for $ty {
+                fn returned_type_oid(&self) -> Option<pg_sys::Oid> {
+                    Some(self.oid())
+                }
+            }
+        )*
+    };
+}
+
+impl_polymorphic!(AnyElement, AnyArray);
+#[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+impl_polymorphic!(AnyCompatible, AnyCompatibleArray);
+
+impl<T: PolymorphicReturn> PolymorphicReturn for Option<T> {
+    fn returned_type_oid(&self) -> Option<pg_sys::Oid> {
+        self.as_ref().and_then(PolymorphicReturn::returned_type_oid)
+    }
+}
+
+impl<T: PolymorphicReturn, E> PolymorphicReturn for Result<T, E> {
+    fn returned_type_oid(&self) -> Option<pg_sys::Oid> {
+        self.as_ref().ok().and_then(PolymorphicReturn::returned_type_oid)
+    }
+}
+
+/// Raise an `ERROR` if `value`, which `function` returned, isn't of the type the call resolved its
+/// polymorphic return type to.  Not public API.
+///
+/// Domains are compared by their base types, as that's what Postgres resolves them to.
+///
+/// # Safety
+///
+/// `fcinfo` must be the function's valid [`pg_sys::FunctionCallInfo`].
+#[doc(hidden)]
+pub unsafe fn __check_polymorphic_return<T: PolymorphicReturn>(
+    function: &str,
+    value: &T,
+    fcinfo: pg_sys::FunctionCallInfo,
+) {
+    let returned = match value.returned_type_oid() {
+        Some(returned) => returned,
+        None => return,
+    };
+    let resolved = crate::fcinfo::pg_return_type(fcinfo);
+    if resolved == pg_sys::InvalidOid
+        || pg_sys::getBaseType(returned) == pg_sys::getBaseType(resolved)
+    {
+        return;
+    }
+
+    let type_name =
+        |oid| CStr::from_ptr(pg_sys::format_type_be(oid)).to_string_lossy().into_owned();
+    pg_sys::ereport!(
+        ERROR,
+        PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH,
+        format!(
+            "`{}` returned a value of type {}, but was called to return {}",
+            function,
+            type_name(returned),
+            type_name(resolved)
+        )
+    );
+}
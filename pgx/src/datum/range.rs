@@ -8,9 +8,10 @@ Use of this source code is governed by the MIT license that can be found in the
 */
 
 //! Utility functions for working with `pg_sys::RangeType` structs
+use crate::datum::DebugText;
 use crate::{
-    pg_sys, void_mut_ptr, AnyNumeric, Date, FromDatum, IntoDatum, Numeric, Timestamp,
-    TimestampWithTimeZone,
+    pg_sys, void_mut_ptr, AnyNumeric, Date, FromDatum, IntoDatum, Numeric, PgSqlErrorCode,
+    Timestamp, TimestampWithTimeZone,
 };
 use pgx_pg_sys::{Oid, RangeBound};
 use pgx_sql_entity_graph::metadata::{
@@ -101,6 +102,17 @@ where
     }
 }
 
+impl<T> std::fmt::Debug for Range<T>
+where
+    T: FromDatum + IntoDatum + RangeSubType,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Range")
+            .field(&DebugText(T::range_type_oid(), Some(self.range_type.into())))
+            .finish()
+    }
+}
+
 impl<T> Drop for Range<T>
 where
     T: FromDatum + IntoDatum + RangeSubType,
@@ -335,6 +347,14 @@ pub enum RangeConversionError {
     NullDatum,
 }
 
+impl crate::ErrorReportable for RangeConversionError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            RangeConversionError::NullDatum => PgSqlErrorCode::ERRCODE_NULL_VALUE_NOT_ALLOWED,
+        }
+    }
+}
+
 unsafe impl SqlTranslatable for Range<i32> {
     fn argument_sql() -> Result<SqlMapping, ArgumentError> {
         Ok(SqlMapping::literal("int4range"))
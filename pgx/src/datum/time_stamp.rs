@@ -7,6 +7,7 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 
+use crate::datum::time_stamp_with_timezone::{END_TIMESTAMP_USEC, MIN_TIMESTAMP_USEC};
 use crate::{pg_sys, FromDatum, FromTimeError, IntoDatum, TimestampWithTimeZone};
 use core::ffi::CStr;
 use pgx_sql_entity_graph::metadata::{
@@ -14,13 +15,17 @@ use pgx_sql_entity_graph::metadata::{
 };
 use serde::Deserialize;
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 #[repr(transparent)]
 pub struct Timestamp(pg_sys::Timestamp);
 
 impl Timestamp {
     pub const NEG_INFINITY: Self = Timestamp(i64::MIN);
     pub const INFINITY: Self = Timestamp(i64::MAX);
+    /// The earliest finite timestamp, `4714-11-24 00:00:00 BC`
+    pub const MIN: Self = Timestamp(MIN_TIMESTAMP_USEC);
+    /// The latest finite timestamp, the last microsecond before `294277-01-01 00:00:00`
+    pub const MAX: Self = Timestamp(END_TIMESTAMP_USEC);
 
     #[inline]
     pub fn is_infinity(&self) -> bool {
@@ -31,6 +36,38 @@ impl Timestamp {
     pub fn is_neg_infinity(&self) -> bool {
         self == &Self::NEG_INFINITY
     }
+
+    /// The timestamp `micros` microseconds after `1970-01-01 00:00:00`.  See
+    /// [`TimestampWithTimeZone::from_unix_epoch_micros()`].
+    pub fn from_unix_epoch_micros(micros: i64) -> Result<Self, FromTimeError> {
+        TimestampWithTimeZone::from_unix_epoch_micros(micros).map(Timestamp::from)
+    }
+
+    /// See [`TimestampWithTimeZone::from_unix_epoch_millis()`]
+    pub fn from_unix_epoch_millis(millis: i64) -> Result<Self, FromTimeError> {
+        TimestampWithTimeZone::from_unix_epoch_millis(millis).map(Timestamp::from)
+    }
+
+    /// See [`TimestampWithTimeZone::from_unix_epoch_seconds()`]
+    pub fn from_unix_epoch_seconds(seconds: i64) -> Result<Self, FromTimeError> {
+        TimestampWithTimeZone::from_unix_epoch_seconds(seconds).map(Timestamp::from)
+    }
+
+    /// The number of microseconds since `1970-01-01 00:00:00`.  See
+    /// [`TimestampWithTimeZone::to_unix_epoch_micros()`].
+    pub fn to_unix_epoch_micros(&self) -> Result<i64, FromTimeError> {
+        TimestampWithTimeZone::from(self.clone()).to_unix_epoch_micros()
+    }
+
+    /// See [`TimestampWithTimeZone::to_unix_epoch_millis()`]
+    pub fn to_unix_epoch_millis(&self) -> Result<i64, FromTimeError> {
+        TimestampWithTimeZone::from(self.clone()).to_unix_epoch_millis()
+    }
+
+    /// See [`TimestampWithTimeZone::to_unix_epoch_seconds()`]
+    pub fn to_unix_epoch_seconds(&self) -> Result<i64, FromTimeError> {
+        TimestampWithTimeZone::from(self.clone()).to_unix_epoch_seconds()
+    }
 }
 
 impl From<TimestampWithTimeZone> for Timestamp {
@@ -18,6 +18,12 @@ use std::convert::TryFrom;
 #[allow(dead_code)] // such is cfg life
 pub(crate) const USECS_PER_SEC: i64 = 1_000_000;
 
+/// Microseconds from the Unix epoch, 1970-01-01, to the Postgres epoch, 2000-01-01
+pub(crate) const UNIX_TO_PG_EPOCH_USECS: i64 = (pg_sys::POSTGRES_EPOCH_JDATE
+    - pg_sys::UNIX_EPOCH_JDATE) as i64
+    * pg_sys::SECS_PER_DAY as i64
+    * USECS_PER_SEC;
+
 #[cfg(feature = "time-crate")]
 mod with_time_crate {
     use super::*;
@@ -80,16 +86,20 @@ impl TryFrom<pg_sys::Datum> for TimestampWithTimeZone {
 }
 
 // taken from /include/datatype/timestamp.h
-const MIN_TIMESTAMP_USEC: i64 = -211_813_488_000_000_000;
-const END_TIMESTAMP_USEC: i64 = 9_223_371_331_200_000_000 - 1; // dec by 1 to accommodate exclusive range match pattern
+pub(crate) const MIN_TIMESTAMP_USEC: i64 = -211_813_488_000_000_000;
+pub(crate) const END_TIMESTAMP_USEC: i64 = 9_223_371_331_200_000_000 - 1; // dec by 1 to accommodate exclusive range match pattern
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 #[repr(transparent)]
 pub struct TimestampWithTimeZone(pg_sys::TimestampTz);
 
 impl TimestampWithTimeZone {
     pub const NEG_INFINITY: Self = TimestampWithTimeZone(i64::MIN);
     pub const INFINITY: Self = TimestampWithTimeZone(i64::MAX);
+    /// The earliest finite timestamp, `4714-11-24 00:00:00+00 BC`
+    pub const MIN: Self = TimestampWithTimeZone(MIN_TIMESTAMP_USEC);
+    /// The latest finite timestamp, the last microsecond before `294277-01-01 00:00:00+00`
+    pub const MAX: Self = TimestampWithTimeZone(END_TIMESTAMP_USEC);
 
     #[inline]
     pub fn is_infinity(&self) -> bool {
@@ -100,6 +110,53 @@ impl TimestampWithTimeZone {
     pub fn is_neg_infinity(&self) -> bool {
         self == &Self::NEG_INFINITY
     }
+
+    /// The timestamp `micros` microseconds after the Unix epoch, `1970-01-01 00:00:00+00`.
+    ///
+    /// Returns [`FromTimeError::MicrosOutOfBounds`] if that's before [`Self::MIN`] or after
+    /// [`Self::MAX`].
+    pub fn from_unix_epoch_micros(micros: i64) -> Result<Self, FromTimeError> {
+        micros
+            .checked_sub(UNIX_TO_PG_EPOCH_USECS)
+            .filter(|usecs| (MIN_TIMESTAMP_USEC..=END_TIMESTAMP_USEC).contains(usecs))
+            .map(TimestampWithTimeZone)
+            .ok_or(FromTimeError::MicrosOutOfBounds)
+    }
+
+    /// Like [`Self::from_unix_epoch_micros()`], for milliseconds after the Unix epoch
+    pub fn from_unix_epoch_millis(millis: i64) -> Result<Self, FromTimeError> {
+        let micros = millis.checked_mul(1_000).ok_or(FromTimeError::MicrosOutOfBounds)?;
+        Self::from_unix_epoch_micros(micros)
+    }
+
+    /// Like [`Self::from_unix_epoch_micros()`], for seconds after the Unix epoch
+    pub fn from_unix_epoch_seconds(seconds: i64) -> Result<Self, FromTimeError> {
+        let micros = seconds.checked_mul(USECS_PER_SEC).ok_or(FromTimeError::MicrosOutOfBounds)?;
+        Self::from_unix_epoch_micros(micros)
+    }
+
+    /// The number of microseconds since the Unix epoch, `1970-01-01 00:00:00+00`.
+    ///
+    /// Returns [`FromTimeError::Infinity`] or [`FromTimeError::NegInfinity`] for the infinite
+    /// timestamps, and [`FromTimeError::MicrosOutOfBounds`] for the last 29 years or so before
+    /// [`Self::MAX`], whose microseconds since the Unix epoch don't fit in an `i64`.
+    pub fn to_unix_epoch_micros(&self) -> Result<i64, FromTimeError> {
+        match *self {
+            Self::NEG_INFINITY => Err(FromTimeError::NegInfinity),
+            Self::INFINITY => Err(FromTimeError::Infinity),
+            _ => self.0.checked_add(UNIX_TO_PG_EPOCH_USECS).ok_or(FromTimeError::MicrosOutOfBounds),
+        }
+    }
+
+    /// Like [`Self::to_unix_epoch_micros()`], in whole milliseconds, rounded down
+    pub fn to_unix_epoch_millis(&self) -> Result<i64, FromTimeError> {
+        Ok(self.to_unix_epoch_micros()?.div_euclid(1_000))
+    }
+
+    /// Like [`Self::to_unix_epoch_micros()`], in whole seconds, rounded down
+    pub fn to_unix_epoch_seconds(&self) -> Result<i64, FromTimeError> {
+        Ok(self.to_unix_epoch_micros()?.div_euclid(USECS_PER_SEC))
+    }
 }
 
 impl From<TimestampWithTimeZone> for i64 {
@@ -148,7 +205,7 @@ impl FromDatum for TimestampWithTimeZone {
     }
 }
 
-#[derive(thiserror::Error, Debug, Clone, Copy)]
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FromTimeError {
     #[error("timestamp value is negative infinity and shouldn't map to time::PrimitiveDateTime")]
     NegInfinity,
@@ -164,6 +221,14 @@ pub enum FromTimeError {
     MinutesOutOfBounds,
     #[error("seconds outside of target range")]
     SecondsOutOfBounds,
+    #[error("days outside of target range")]
+    DaysOutOfBounds,
+}
+
+impl crate::ErrorReportable for FromTimeError {
+    fn sql_error_code(&self) -> crate::PgSqlErrorCode {
+        crate::PgSqlErrorCode::ERRCODE_DATETIME_FIELD_OVERFLOW
+    }
 }
 
 impl serde::Serialize for TimestampWithTimeZone {
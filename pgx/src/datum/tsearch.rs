@@ -0,0 +1,485 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Full text search's `tsvector` and `tsquery`
+use crate::{
+    direct_function_call, direct_function_call_as_datum, pg_sys, varsize_any, FromDatum, IntoDatum,
+};
+use core::ffi::CStr;
+use pg_sys::AsPgCStr;
+use pgx_pg_sys::errcodes::PgSqlErrorCode;
+use pgx_pg_sys::panic::CaughtError;
+use pgx_pg_sys::PgTryBuilder;
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use std::fmt::{self, Display, Formatter};
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// The longest lexeme a `tsvector` can hold, in bytes
+const MAX_LEXEME_LEN: usize = (1 << 11) - 1;
+
+/// The most positions a lexeme in a `tsvector` can have.  Postgres keeps the first ones
+const MAX_POSITIONS: usize = 256;
+
+/// The most bytes a `tsvector`'s lexemes and positions can take
+const MAX_STRINGS_LEN: usize = (1 << 20) - 1;
+
+/// The weight of a lexeme at a [`Position`], from `A`, the highest, to `D`, the default
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Weight {
+    D = 0,
+    C = 1,
+    B = 2,
+    A = 3,
+}
+
+/// Where a lexeme appears in a document, counted in words from 1, and the [`Weight`] it has there
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Position(pg_sys::WordEntryPos);
+
+impl Position {
+    /// The highest position.  Positions past it are stored as it, as Postgres does
+    pub const MAX: u16 = (1 << 14) - 1;
+
+    /// The position `pos`, with the default weight, `D`
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is `0`
+    pub fn new(pos: u16) -> Self {
+        Position::with_weight(pos, Weight::D)
+    }
+
+    /// The position `pos`, with `weight`
+    ///
+    /// # Panics
+    ///
+    /// If `pos` is `0`
+    pub fn with_weight(pos: u16, weight: Weight) -> Self {
+        assert!(pos > 0, "tsvector positions start at 1");
+        Position((weight as u16) << 14 | pos.min(Position::MAX))
+    }
+
+    pub fn pos(&self) -> u16 {
+        self.0 & Position::MAX
+    }
+
+    pub fn weight(&self) -> Weight {
+        match self.0 >> 14 {
+            3 => Weight::A,
+            2 => Weight::B,
+            1 => Weight::C,
+            _ => Weight::D,
+        }
+    }
+}
+
+/// A normalized word in a [`TsVector`], and the [`Position`]s it has in the document, which are
+/// sorted and unique
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Lexeme {
+    word: String,
+    positions: Vec<Position>,
+}
+
+impl Lexeme {
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+
+    /// Empty when the `tsvector` was made without positions, such as by `strip()`
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+}
+
+/// A `tsvector` from PostgreSQL:  a document as the sorted, unique lexemes in it
+///
+/// Its lexemes are copied out of the Datum, and can be accessed as a slice through [`Deref`].  It's
+/// parsed from text as a `tsvector` literal through [`FromStr`], or built from lexemes with
+/// [`TsVector::from_lexemes()`].  A document is turned into one with SQL's `to_tsvector()`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct TsVector(Vec<Lexeme>);
+
+impl TsVector {
+    /// The `tsvector` with these lexemes at these positions
+    ///
+    /// Like the `tsvector` input function, the lexemes don't need to be in order, and any which
+    /// appear more than once are merged.  A position which appears more than once for the same
+    /// lexeme keeps its highest weight.
+    ///
+    /// ```rust,no_run
+    /// use pgx::{Position, TsVector, Weight};
+    ///
+    /// let vector = TsVector::from_lexemes(&[
+    ///     ("fat", &[Position::new(2)]),
+    ///     ("cat", &[Position::with_weight(3, Weight::A)]),
+    ///     ("rat", &[]),
+    /// ]);
+    /// assert_eq!(vector.to_string(), "'cat':3A 'fat':2 'rat'");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If a lexeme is longer than 2047 bytes, or they take more than a megabyte altogether
+    pub fn from_lexemes(lexemes: &[(&str, &[Position])]) -> Self {
+        let mut sorted = lexemes.to_vec();
+        sorted.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+
+        let mut merged: Vec<Lexeme> = Vec::with_capacity(sorted.len());
+        for (word, positions) in sorted {
+            assert!(
+                word.len() <= MAX_LEXEME_LEN,
+                "a tsvector's lexemes can't be longer than {} bytes",
+                MAX_LEXEME_LEN
+            );
+            match merged.last_mut() {
+                Some(last) if last.word == word => last.positions.extend_from_slice(positions),
+                _ => merged.push(Lexeme { word: word.to_string(), positions: positions.to_vec() }),
+            }
+        }
+
+        for lexeme in &mut merged {
+            let positions = &mut lexeme.positions;
+            // the highest weight of each position sorts last, and is the one kept
+            positions.sort_by_key(|position| (position.pos(), position.weight()));
+            positions.reverse();
+            positions.dedup_by_key(|position| position.pos());
+            positions.reverse();
+            positions.truncate(MAX_POSITIONS);
+        }
+
+        TsVector(merged)
+    }
+
+    pub fn as_slice(&self) -> &[Lexeme] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<Lexeme> {
+        self.0
+    }
+
+    /// Build the `tsvector` in the `CurrentMemoryContext`
+    fn to_datum(&self) -> pg_sys::Datum {
+        let positions_len = |lexeme: &Lexeme| {
+            std::mem::size_of::<u16>()
+                + lexeme.positions.len() * std::mem::size_of::<pg_sys::WordEntryPos>()
+        };
+
+        let mut strings_len = 0;
+        for lexeme in &self.0 {
+            strings_len += lexeme.word.len();
+            if !lexeme.positions.is_empty() {
+                strings_len = short_align(strings_len) + positions_len(lexeme);
+            }
+        }
+        assert!(
+            strings_len <= MAX_STRINGS_LEN,
+            "a tsvector's lexemes can't take more than {} bytes",
+            MAX_STRINGS_LEN
+        );
+
+        let header_len = std::mem::size_of::<pg_sys::TSVectorData>()
+            + self.0.len() * std::mem::size_of::<pg_sys::WordEntry>();
+        unsafe {
+            let vector = pg_sys::palloc0(header_len + strings_len) as *mut pg_sys::TSVectorData;
+            crate::set_varsize(vector.cast(), (header_len + strings_len) as i32);
+            (*vector).size = self.0.len() as i32;
+
+            let entries = (*vector).entries.as_mut_ptr();
+            let strings = entries.add(self.0.len()) as *mut u8;
+            let mut offset = 0;
+            for (i, lexeme) in self.0.iter().enumerate() {
+                let mut entry = pg_sys::WordEntry::default();
+                entry.set_haspos(!lexeme.positions.is_empty() as u32);
+                entry.set_len(lexeme.word.len() as u32);
+                entry.set_pos(offset as u32);
+                entries.add(i).write(entry);
+
+                std::ptr::copy_nonoverlapping(
+                    lexeme.word.as_ptr(),
+                    strings.add(offset),
+                    lexeme.word.len(),
+                );
+                offset += lexeme.word.len();
+
+                if !lexeme.positions.is_empty() {
+                    offset = short_align(offset);
+                    let positions = strings.add(offset) as *mut pg_sys::WordEntryPosVector;
+                    (*positions).npos = lexeme.positions.len() as u16;
+                    let dest = (*positions).pos.as_mut_ptr();
+                    for (j, position) in lexeme.positions.iter().enumerate() {
+                        dest.add(j).write(position.0);
+                    }
+                    offset += positions_len(lexeme);
+                }
+            }
+
+            pg_sys::Datum::from_pointer(vector)
+        }
+    }
+}
+
+impl Deref for TsVector {
+    type Target = [Lexeme];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl IntoIterator for TsVector {
+    type Item = Lexeme;
+    type IntoIter = std::vec::IntoIter<Lexeme>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TsVector {
+    type Item = &'a Lexeme;
+    type IntoIter = std::slice::Iter<'a, Lexeme>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl FromStr for TsVector {
+    type Err = TsParseError;
+
+    /// Parse a `tsvector` literal, such as `'fat':2 'cat':3A`, with Postgres' input function
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        unsafe {
+            let datum = parse(s, pg_sys::tsvectorin)?;
+            let vector = TsVector::from_datum(datum, false).unwrap();
+            pg_sys::pfree(datum.cast_mut_ptr());
+            Ok(vector)
+        }
+    }
+}
+
+impl Display for TsVector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        unsafe {
+            let datum = self.to_datum();
+            let text = direct_function_call::<&CStr>(pg_sys::tsvectorout, vec![Some(datum)]);
+            let result = f.write_str(&text.unwrap().to_string_lossy());
+            pg_sys::pfree(datum.cast_mut_ptr());
+            result
+        }
+    }
+}
+
+impl FromDatum for TsVector {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<TsVector> {
+        if is_null {
+            return None;
+        }
+
+        let varlena = datum.cast_mut_ptr::<pg_sys::varlena>();
+        let detoasted = pg_sys::pg_detoast_datum(varlena);
+        let vector = detoasted as *const pg_sys::TSVectorData;
+        let size = (*vector).size as usize;
+        let entries = (*vector).entries.as_slice(size);
+        let strings = entries.as_ptr().add(size) as *const u8;
+
+        let lexemes = entries
+            .iter()
+            .map(|entry| {
+                let word = std::slice::from_raw_parts(
+                    strings.add(entry.pos() as usize),
+                    entry.len() as usize,
+                );
+                let positions = if entry.haspos() != 0 {
+                    let offset = short_align((entry.pos() + entry.len()) as usize);
+                    let positions = strings.add(offset) as *const pg_sys::WordEntryPosVector;
+                    (*positions)
+                        .pos
+                        .as_slice((*positions).npos as usize)
+                        .iter()
+                        .map(|&position| Position(position))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                Lexeme { word: String::from_utf8_lossy(word).into_owned(), positions }
+            })
+            .collect();
+
+        if detoasted != varlena {
+            pg_sys::pfree(detoasted.cast());
+        }
+        Some(TsVector(lexemes))
+    }
+}
+
+impl IntoDatum for TsVector {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        Some(self.to_datum())
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::TSVECTOROID
+    }
+}
+
+unsafe impl SqlTranslatable for TsVector {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("tsvector"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("tsvector")))
+    }
+}
+
+/// A `tsquery` from PostgreSQL:  lexemes combined with `&`, `|`, `!` and `<->`, which a
+/// [`TsVector`] [`matches`](TsQuery::matches) or doesn't
+///
+/// It's copied out of the Datum, and is parsed from text as a `tsquery` literal through
+/// [`FromStr`].  A query is turned into one with SQL's `to_tsquery()` and its relatives.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct TsQuery(Vec<u8>);
+
+impl TsQuery {
+    /// Does `vector` match this query, as `vector @@ query` would in SQL?
+    pub fn matches(&self, vector: &TsVector) -> bool {
+        unsafe {
+            let vector = vector.to_datum();
+            let query = self.to_datum();
+            let matches =
+                direct_function_call::<bool>(pg_sys::ts_match_vq, vec![Some(vector), Some(query)]);
+            pg_sys::pfree(vector.cast_mut_ptr());
+            pg_sys::pfree(query.cast_mut_ptr());
+            matches.unwrap()
+        }
+    }
+
+    /// Copy the `tsquery` into the `CurrentMemoryContext`
+    fn to_datum(&self) -> pg_sys::Datum {
+        unsafe {
+            let query = pg_sys::palloc(self.0.len()) as *mut u8;
+            std::ptr::copy_nonoverlapping(self.0.as_ptr(), query, self.0.len());
+            pg_sys::Datum::from_pointer(query)
+        }
+    }
+}
+
+impl FromStr for TsQuery {
+    type Err = TsParseError;
+
+    /// Parse a `tsquery` literal, such as `fat & (rat | cat)`, with Postgres' input function
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        unsafe {
+            let datum = parse(s, pg_sys::tsqueryin)?;
+            let query = TsQuery::from_datum(datum, false).unwrap();
+            pg_sys::pfree(datum.cast_mut_ptr());
+            Ok(query)
+        }
+    }
+}
+
+impl Display for TsQuery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        unsafe {
+            let datum = self.to_datum();
+            let text = direct_function_call::<&CStr>(pg_sys::tsqueryout, vec![Some(datum)]);
+            let result = f.write_str(&text.unwrap().to_string_lossy());
+            pg_sys::pfree(datum.cast_mut_ptr());
+            result
+        }
+    }
+}
+
+impl fmt::Debug for TsQuery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TsQuery").field(&self.to_string()).finish()
+    }
+}
+
+impl FromDatum for TsQuery {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<TsQuery> {
+        if is_null {
+            return None;
+        }
+
+        // detoasting always leaves it with a 4-byte header, which is what `to_datum()` needs
+        let varlena = datum.cast_mut_ptr::<pg_sys::varlena>();
+        let detoasted = pg_sys::pg_detoast_datum(varlena);
+        let bytes =
+            std::slice::from_raw_parts(detoasted as *const u8, varsize_any(detoasted)).to_vec();
+        if detoasted != varlena {
+            pg_sys::pfree(detoasted.cast());
+        }
+        Some(TsQuery(bytes))
+    }
+}
+
+impl IntoDatum for TsQuery {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        Some(self.to_datum())
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::TSQUERYOID
+    }
+}
+
+unsafe impl SqlTranslatable for TsQuery {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("tsquery"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("tsquery")))
+    }
+}
+
+/// The syntax error Postgres reported for a `tsvector` or `tsquery` literal
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("{0}")]
+pub struct TsParseError(pub String);
+
+/// Parse `s` with the input function `func`, catching the `ERROR` it raises for bad syntax
+unsafe fn parse(
+    s: &str,
+    func: unsafe fn(pg_sys::FunctionCallInfo) -> pg_sys::Datum,
+) -> Result<pg_sys::Datum, TsParseError> {
+    let ptr = s.as_pg_cstr();
+    let cstr = CStr::from_ptr(ptr);
+    let result = PgTryBuilder::new(|| {
+        Ok(direct_function_call_as_datum(func, vec![cstr.into_datum()]).unwrap())
+    })
+    .catch_when(PgSqlErrorCode::ERRCODE_SYNTAX_ERROR, |e| {
+        if let CaughtError::PostgresError(ref ereport) = e {
+            Err(TsParseError(ereport.message().to_string()))
+        } else {
+            e.rethrow()
+        }
+    })
+    .execute();
+    pg_sys::pfree(ptr.cast());
+    result
+}
+
+/// Round `len` up to a multiple of 2, like Postgres' `SHORTALIGN()`
+fn short_align(len: usize) -> usize {
+    (len + 1) & !1
+}
@@ -0,0 +1,251 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Bit strings, `bit(n)` and `bit varying`
+use crate::{pg_sys, FromDatum, IntoDatum};
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// The header of a bit string after its varlena header:  its length in bits
+const BIT_LEN_SIZE: usize = std::mem::size_of::<i32>();
+
+/// A `bit varying`, or `bit(n)`, from PostgreSQL:  a string of bits, the first of which is the
+/// leftmost in its SQL literal, such as `B'1011'`
+///
+/// Its bits are copied out of the Datum.  It's parsed from text such as `"1011"` through
+/// [`FromStr`], and converts to and from a `Vec<bool>`, and a `u128` for strings of up to 128
+/// bits.
+///
+/// ```rust,no_run
+/// use pgx::VarBit;
+///
+/// let mut bits = VarBit::from_u128(0b1011, 4);
+/// bits.set(1, true);
+/// assert_eq!(bits.to_string(), "1111");
+/// assert_eq!(bits.to_u128(), Some(0b1111));
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct VarBit {
+    len: usize,
+    /// Most significant bit first, like Postgres, with any bits past `len` zero
+    bytes: Vec<u8>,
+}
+
+impl VarBit {
+    /// The empty bit string, `B''`
+    pub fn new() -> Self {
+        VarBit::default()
+    }
+
+    /// `len` zero bits
+    pub fn zeros(len: usize) -> Self {
+        VarBit { len, bytes: vec![0; bytes_for(len)] }
+    }
+
+    /// The low `len` bits of `value`, the most significant of them first, like SQL's
+    /// `value::bit(len)` for an integer
+    ///
+    /// # Panics
+    ///
+    /// If `len` is more than 128
+    pub fn from_u128(value: u128, len: usize) -> Self {
+        assert!(len <= 128, "a u128 has 128 bits, not {}", len);
+        let mut bits = VarBit::zeros(len);
+        for idx in 0..len {
+            bits.set(idx, value >> (len - 1 - idx) & 1 == 1);
+        }
+        bits
+    }
+
+    /// The bits as an integer, the first of them the most significant, or `None` if there are
+    /// more than 128 of them
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.len > 128 {
+            return None;
+        }
+        Some(self.iter().fold(0, |value, bit| value << 1 | bit as u128))
+    }
+
+    /// The number of bits
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bit at `idx`, counting from the left, or `None` if it's past the end
+    pub fn get(&self, idx: usize) -> Option<bool> {
+        (idx < self.len).then(|| self.bytes[idx / 8] & mask(idx) != 0)
+    }
+
+    /// Set the bit at `idx`, counting from the left
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is past the end
+    pub fn set(&mut self, idx: usize, bit: bool) {
+        assert!(idx < self.len, "bit {} is past the end of a {}-bit string", idx, self.len);
+        if bit {
+            self.bytes[idx / 8] |= mask(idx);
+        } else {
+            self.bytes[idx / 8] &= !mask(idx);
+        }
+    }
+
+    /// Add a bit to the end
+    pub fn push(&mut self, bit: bool) {
+        if self.len % 8 == 0 {
+            self.bytes.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, bit);
+    }
+
+    /// The bits from left to right
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|idx| self.bytes[idx / 8] & mask(idx) != 0)
+    }
+
+    pub fn to_vec(&self) -> Vec<bool> {
+        self.iter().collect()
+    }
+
+    /// Build the bit string in the `CurrentMemoryContext`
+    fn to_datum(&self) -> pg_sys::Datum {
+        let header_len = pg_sys::VARHDRSZ + BIT_LEN_SIZE;
+        let total_len = header_len + self.bytes.len();
+        unsafe {
+            let varbit = pg_sys::palloc0(total_len) as *mut u8;
+            crate::set_varsize(varbit.cast(), total_len as i32);
+            (varbit.add(pg_sys::VARHDRSZ) as *mut i32).write_unaligned(self.len as i32);
+            std::ptr::copy_nonoverlapping(
+                self.bytes.as_ptr(),
+                varbit.add(header_len),
+                self.bytes.len(),
+            );
+            pg_sys::Datum::from(varbit)
+        }
+    }
+}
+
+/// The bytes needed for `len` bits
+fn bytes_for(len: usize) -> usize {
+    (len + 7) / 8
+}
+
+/// The bit at `idx` in its byte
+fn mask(idx: usize) -> u8 {
+    0x80 >> (idx % 8)
+}
+
+impl From<&[bool]> for VarBit {
+    fn from(bits: &[bool]) -> Self {
+        bits.iter().copied().collect()
+    }
+}
+
+impl From<Vec<bool>> for VarBit {
+    fn from(bits: Vec<bool>) -> Self {
+        bits.into_iter().collect()
+    }
+}
+
+impl From<VarBit> for Vec<bool> {
+    fn from(bits: VarBit) -> Self {
+        bits.to_vec()
+    }
+}
+
+impl FromIterator<bool> for VarBit {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bits = VarBit::new();
+        iter.into_iter().for_each(|bit| bits.push(bit));
+        bits
+    }
+}
+
+/// Anything but `0`s and `1`s in a bit string
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("\"{0}\" is not a valid binary digit")]
+pub struct VarBitParseError(pub char);
+
+impl FromStr for VarBit {
+    type Err = VarBitParseError;
+
+    /// Parse bits written like SQL's `B'1011'` literals, without the `B''`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars()
+            .map(|c| match c {
+                '0' => Ok(false),
+                '1' => Ok(true),
+                other => Err(VarBitParseError(other)),
+            })
+            .collect()
+    }
+}
+
+impl Display for VarBit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.iter().try_for_each(|bit| f.write_str(if bit { "1" } else { "0" }))
+    }
+}
+
+impl FromDatum for VarBit {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<VarBit> {
+        if is_null {
+            return None;
+        }
+
+        // detoasting always leaves it with a 4-byte header
+        let varlena = datum.cast_mut_ptr::<pg_sys::varlena>();
+        let detoasted = pg_sys::pg_detoast_datum(varlena);
+        let ptr = detoasted as *const u8;
+        let len = (ptr.add(pg_sys::VARHDRSZ) as *const i32).read_unaligned() as usize;
+        let bytes =
+            std::slice::from_raw_parts(ptr.add(pg_sys::VARHDRSZ + BIT_LEN_SIZE), bytes_for(len))
+                .to_vec();
+        if detoasted != varlena {
+            pg_sys::pfree(detoasted.cast());
+        }
+        Some(VarBit { len, bytes })
+    }
+}
+
+impl IntoDatum for VarBit {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        Some(self.to_datum())
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::VARBITOID
+    }
+
+    /// `bit(n)` is stored just like `bit varying`
+    fn is_compatible_with(other: pg_sys::Oid) -> bool {
+        other == pg_sys::VARBITOID || other == pg_sys::BITOID
+    }
+}
+
+unsafe impl SqlTranslatable for VarBit {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("bit varying"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("bit varying")))
+    }
+}
@@ -0,0 +1,317 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Queue closures to run once the current statement ends, or just before the current transaction
+//! commits.
+//!
+//! This is mostly useful from triggers, to coalesce per-row work into a single piece of work per
+//! statement or per transaction.  Queued closures run in the order they were queued, and a closure
+//! queued with a key is only queued if no closure with the same key is still waiting to run.
+//!
+//! Both queues are cleared, without running anything, when the transaction aborts.  Closures
+//! queued in a subtransaction that's rolled back (such as a PL/pgSQL `EXCEPTION` block) are
+//! forgotten too.
+//!
+//! ## Examples
+//!
+//! Count how many rows each `INSERT` statement inserted:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//!
+//! #[pg_trigger]
+//! fn count_inserts(
+//!     trigger: &pgx::PgTrigger,
+//! ) -> Result<PgHeapTuple<'_, AllocatedByPostgres>, pgx::PgTriggerError> {
+//!     thread_local! { static INSERTED: std::cell::Cell<i64> = std::cell::Cell::new(0) }
+//!
+//!     INSERTED.with(|inserted| inserted.set(inserted.get() + 1));
+//!     pgx::after_statement_once("count_inserts", || {
+//!         let inserted = INSERTED.with(|inserted| inserted.replace(0));
+//!         Spi::run_with_args(
+//!             "INSERT INTO insert_counts VALUES ($1)",
+//!             Some(vec![(PgBuiltInOids::INT8OID.oid(), inserted.into_datum())]),
+//!         )
+//!         .unwrap();
+//!     });
+//!     Ok(trigger.new().expect("not an AFTER INSERT ... FOR EACH ROW trigger"))
+//! }
+//! ```
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::prelude::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+
+/// A queued closure
+struct Deferred {
+    /// Orders closures across both queues, so a rolled back subtransaction can forget its own
+    seq: u64,
+    /// The executor nesting depth of the statement an `after_statement` closure waits for
+    depth: usize,
+    key: Option<String>,
+    f: Box<dyn FnOnce()>,
+}
+
+/// Where things stood when a subtransaction started
+struct SubXactMark {
+    subid: pg_sys::SubTransactionId,
+    depth: usize,
+    seq: u64,
+}
+
+#[derive(Default)]
+struct Queues {
+    installed: bool,
+    flushing: bool,
+    /// How many executors are running
+    depth: usize,
+    next_seq: u64,
+    after_statement: VecDeque<Deferred>,
+    before_commit: VecDeque<Deferred>,
+    subxacts: Vec<SubXactMark>,
+}
+
+thread_local! {
+    static QUEUES: RefCell<Queues> = RefCell::new(Queues::default());
+}
+
+static mut PREV_EXECUTOR_START: pg_sys::ExecutorStart_hook_type = None;
+static mut PREV_EXECUTOR_END: pg_sys::ExecutorEnd_hook_type = None;
+
+/// Run `f` when the statement currently being executed ends.
+///
+/// If an `ERROR` is raised or a panic happens while `f` runs, whatever `f` did is rolled back and
+/// the error is reported as a `WARNING`, so it doesn't affect the statement, which has already
+/// finished, or the other queued closures.
+///
+/// The statement is the innermost one running when this is called:  for a trigger, that's the
+/// statement which fired it, and not a query the trigger itself runs through `Spi`.  Closures
+/// still queued when the transaction commits, such as those queued during a `COPY`, run just
+/// before it does.
+pub fn after_statement<F: FnOnce() + 'static>(f: F) {
+    enqueue(Queue::AfterStatement, None, Box::new(f))
+}
+
+/// Like [`after_statement()`], but only queues `f` if no closure queued with the same `key` is
+/// still waiting to run
+pub fn after_statement_once<K: Into<String>, F: FnOnce() + 'static>(key: K, f: F) {
+    enqueue(Queue::AfterStatement, Some(key.into()), Box::new(f))
+}
+
+/// Run `f` just before the current transaction commits.
+///
+/// This is the last point at which the transaction can be aborted, and that's what an `ERROR` or
+/// panic raised by `f` does.  The closures queued after it won't run.
+pub fn before_commit<F: FnOnce() + 'static>(f: F) {
+    enqueue(Queue::BeforeCommit, None, Box::new(f))
+}
+
+/// Like [`before_commit()`], but only queues `f` if no closure queued with the same `key` is
+/// still waiting to run
+pub fn before_commit_once<K: Into<String>, F: FnOnce() + 'static>(key: K, f: F) {
+    enqueue(Queue::BeforeCommit, Some(key.into()), Box::new(f))
+}
+
+#[derive(Copy, Clone)]
+enum Queue {
+    AfterStatement,
+    BeforeCommit,
+}
+
+fn enqueue(queue: Queue, key: Option<String>, f: Box<dyn FnOnce()>) {
+    QUEUES.with(|queues| {
+        let mut queues = queues.borrow_mut();
+        if !queues.installed {
+            // SAFETY:  we're only ever installed once per backend, and our hooks and callbacks
+            // live forever
+            unsafe { install() };
+            queues.installed = true;
+            // we can't know how deeply nested in executors we are, but as we're being called,
+            // we're presumably inside of at least one
+            queues.depth = 1;
+        }
+
+        let Queues { depth, next_seq, after_statement, before_commit, .. } = &mut *queues;
+        let queue = match queue {
+            Queue::AfterStatement => after_statement,
+            Queue::BeforeCommit => before_commit,
+        };
+        if key.is_some() && queue.iter().any(|deferred| deferred.key == key) {
+            return;
+        }
+        queue.push_back(Deferred { seq: *next_seq, depth: *depth, key, f });
+        *next_seq += 1;
+    })
+}
+
+unsafe fn install() {
+    PREV_EXECUTOR_START = pg_sys::ExecutorStart_hook.replace(executor_start);
+    PREV_EXECUTOR_END = pg_sys::ExecutorEnd_hook.replace(executor_end);
+    pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut());
+    pg_sys::RegisterSubXactCallback(Some(subxact_callback), std::ptr::null_mut());
+}
+
+#[pg_guard]
+unsafe extern "C" fn executor_start(query_desc: *mut pg_sys::QueryDesc, eflags: i32) {
+    match PREV_EXECUTOR_START {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| prev(query_desc, eflags)),
+        None => pg_sys::standard_ExecutorStart(query_desc, eflags),
+    }
+    QUEUES.with(|queues| queues.borrow_mut().depth += 1);
+}
+
+#[pg_guard]
+unsafe extern "C" fn executor_end(query_desc: *mut pg_sys::QueryDesc) {
+    match PREV_EXECUTOR_END {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| prev(query_desc)),
+        None => pg_sys::standard_ExecutorEnd(query_desc),
+    }
+    let depth = QUEUES.with(|queues| {
+        let mut queues = queues.borrow_mut();
+        // an executor that started before we were installed might end at depth zero
+        queues.depth = queues.depth.saturating_sub(1);
+        queues.depth
+    });
+    run_after_statement(depth + 1);
+}
+
+/// Run the `after_statement` closures waiting for statements at `depth` or deeper
+fn run_after_statement(depth: usize) {
+    let was_flushing =
+        QUEUES.with(|queues| std::mem::replace(&mut queues.borrow_mut().flushing, true));
+    if was_flushing {
+        // the statements our closures run end here too.  Anything they queue is run by the loop
+        // below, which is already running
+        return;
+    }
+
+    while let Some(deferred) = QUEUES.with(|queues| {
+        let mut queues = queues.borrow_mut();
+        let next = queues.after_statement.iter().position(|deferred| deferred.depth >= depth)?;
+        queues.after_statement.remove(next)
+    }) {
+        run_isolated(deferred);
+    }
+
+    QUEUES.with(|queues| queues.borrow_mut().flushing = false);
+}
+
+/// Run an `after_statement` closure in a subtransaction, reporting any `ERROR` or panic as a
+/// `WARNING`
+fn run_isolated(deferred: Deferred) {
+    let Deferred { key, f, .. } = deferred;
+    unsafe {
+        // SAFETY:  we're inside a transaction, so we can start a subtransaction, and we
+        // restore the memory context and resource owner however it ends
+        let memcxt = pg_sys::CurrentMemoryContext;
+        let owner = pg_sys::CurrentResourceOwner;
+        pg_sys::BeginInternalSubTransaction(std::ptr::null());
+
+        let result = PgTryBuilder::new(AssertUnwindSafe(|| {
+            f();
+            Ok(())
+        }))
+        .catch_others(Err)
+        .execute();
+        match result {
+            Ok(()) => pg_sys::ReleaseCurrentSubTransaction(),
+            Err(e) => {
+                pg_sys::RollbackAndReleaseCurrentSubTransaction();
+                let message = match &e {
+                    pg_sys::panic::CaughtError::PostgresError(ereport)
+                    | pg_sys::panic::CaughtError::ErrorReport(ereport)
+                    | pg_sys::panic::CaughtError::RustPanic { ereport, .. } => ereport.message(),
+                };
+                match key {
+                    Some(key) => warning!("after_statement closure `{}` failed: {}", key, message),
+                    None => warning!("after_statement closure failed: {}", message),
+                }
+            }
+        }
+        pg_sys::MemoryContextSwitchTo(memcxt);
+        pg_sys::CurrentResourceOwner = owner;
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn xact_callback(event: pg_sys::XactEvent, _arg: *mut std::os::raw::c_void) {
+    match event {
+        pg_sys::XactEvent_XACT_EVENT_PRE_COMMIT | pg_sys::XactEvent_XACT_EVENT_PRE_PREPARE => {
+            // no statement is running anymore, so whatever is still waiting for one to end runs
+            // now, including what the `before_commit` closures queue.  An ERROR or panic from a
+            // `before_commit` closure aborts the transaction, which clears the queues
+            QUEUES.with(|queues| queues.borrow_mut().depth = 0);
+            loop {
+                run_after_statement(0);
+                match QUEUES.with(|queues| queues.borrow_mut().before_commit.pop_front()) {
+                    Some(deferred) => (deferred.f)(),
+                    None => break,
+                }
+            }
+        }
+        pg_sys::XactEvent_XACT_EVENT_COMMIT
+        | pg_sys::XactEvent_XACT_EVENT_ABORT
+        | pg_sys::XactEvent_XACT_EVENT_PREPARE => {
+            // drop the queued closures outside of our borrow, in case they own anything that
+            // wants to queue something else when dropped
+            let (after_statement, before_commit) = QUEUES.with(|queues| {
+                let mut queues = queues.borrow_mut();
+                queues.depth = 0;
+                queues.flushing = false;
+                queues.subxacts.clear();
+                (
+                    std::mem::take(&mut queues.after_statement),
+                    std::mem::take(&mut queues.before_commit),
+                )
+            });
+            drop((after_statement, before_commit));
+        }
+        _ => {}
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn subxact_callback(
+    event: pg_sys::SubXactEvent,
+    my_subid: pg_sys::SubTransactionId,
+    _parent_subid: pg_sys::SubTransactionId,
+    _arg: *mut std::os::raw::c_void,
+) {
+    let forgotten = QUEUES.with(|queues| {
+        let queues = &mut *queues.borrow_mut();
+        match event {
+            pg_sys::SubXactEvent_SUBXACT_EVENT_START_SUB => {
+                let mark =
+                    SubXactMark { subid: my_subid, depth: queues.depth, seq: queues.next_seq };
+                queues.subxacts.push(mark);
+                None
+            }
+            pg_sys::SubXactEvent_SUBXACT_EVENT_COMMIT_SUB => {
+                queues.subxacts.retain(|mark| mark.subid != my_subid);
+                None
+            }
+            pg_sys::SubXactEvent_SUBXACT_EVENT_ABORT_SUB => {
+                let at = queues.subxacts.iter().position(|mark| mark.subid == my_subid)?;
+                let mark = queues.subxacts.drain(at..).next()?;
+                // the executors that were running when the ERROR was raised never ended
+                queues.depth = mark.depth;
+                let mut forgotten = Vec::new();
+                for queue in [&mut queues.after_statement, &mut queues.before_commit] {
+                    while queue.back().map_or(false, |deferred| deferred.seq >= mark.seq) {
+                        forgotten.extend(queue.pop_back());
+                    }
+                }
+                Some(forgotten)
+            }
+            _ => None,
+        }
+    });
+    drop(forgotten);
+}
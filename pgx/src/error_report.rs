@@ -0,0 +1,284 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Raise the errors returned by `#[pg_extern]` functions with a specific SQLSTATE.
+//!
+//! When a `#[pg_extern]` function returns `Err(e)`, pgx raises `e` as a Postgres `ERROR`.  If the
+//! type of `e` implements [`ErrorReportable`], the error's SQLSTATE, message, detail, and hint
+//! all come from it.  Otherwise the error is raised with its [`Display`] representation as the
+//! message and `ERRCODE_DATA_EXCEPTION` (`22000`) as the SQLSTATE.
+//!
+//! A function that can fail in several of the ways pgx's APIs do can return a [`PgxError`],
+//! which any of their errors convert into, and which is raised as the error it holds would be.
+//!
+//! [`ErrorReportable`] can be derived for enums and structs.  Each variant, or the struct, may
+//! be annotated with `#[error_report(...)]`, taking:
+//!
+//! - `code = ERRCODE_...`, the name of a [`PgSqlErrorCode`] variant.  Variants without one use
+//!   the `code` given on the enum itself, or `ERRCODE_DATA_EXCEPTION` if there's none
+//! - `message = "..."`, which defaults to the error's [`Display`] representation
+//! - `detail = "..."` and `hint = "..."`, which are omitted by default
+//!
+//! The strings are format strings in which named fields can be used by name, and unnamed ones
+//! as `_0`, `_1`, and so on.
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::ErrorReportable;
+//!
+//! #[derive(thiserror::Error, ErrorReportable, Debug)]
+//! #[error_report(code = ERRCODE_DATA_EXCEPTION)]
+//! enum AccountError {
+//!     #[error("account {0} does not exist")]
+//!     #[error_report(code = ERRCODE_NO_DATA_FOUND, hint = "open the account first")]
+//!     NoSuchAccount(i64),
+//!
+//!     #[error("insufficient funds")]
+//!     #[error_report(
+//!         code = ERRCODE_CHECK_VIOLATION,
+//!         detail = "the balance is {balance}, but {amount} was requested"
+//!     )]
+//!     InsufficientFunds { balance: i64, amount: i64 },
+//!
+//!     #[error("the ledger is locked")]
+//!     Locked,
+//! }
+//!
+//! #[pg_extern]
+//! fn withdraw(account: i64, amount: i64) -> Result<i64, AccountError> {
+//!     Err(AccountError::InsufficientFunds { balance: 0, amount })
+//! }
+//! ```
+use crate::pg_sys::errcodes::PgSqlErrorCode;
+use crate::pg_sys::panic::{CaughtError, ErrorReport};
+use std::fmt::Display;
+
+/// An error type that knows how to be raised as a Postgres `ERROR`.
+///
+/// Not to be confused with [`pg_sys::panic::ErrorReportable`](crate::pg_sys::panic::ErrorReportable),
+/// which raises the error of a `Result`, and which uses this trait's [`ErrorReport`] when the
+/// generated `#[pg_extern]` wrapper is given an error type that implements it.
+///
+/// This is usually derived.  See the [module documentation](crate::error_report) for details.
+pub trait ErrorReportable: Display {
+    /// The SQLSTATE to raise this error with
+    fn sql_error_code(&self) -> PgSqlErrorCode;
+
+    /// The primary error message, which is this error's [`Display`] representation by default
+    fn message(&self) -> String {
+        self.to_string()
+    }
+
+    /// The optional `DETAIL` line
+    fn detail(&self) -> Option<String> {
+        None
+    }
+
+    /// The optional `HINT` line
+    fn hint(&self) -> Option<String> {
+        None
+    }
+
+    /// Builds the [`ErrorReport`] this error is raised as
+    #[track_caller]
+    fn to_error_report(&self) -> ErrorReport {
+        let mut report =
+            ErrorReport::new(self.sql_error_code(), self.message(), std::any::type_name::<Self>());
+        if let Some(detail) = self.detail() {
+            report = report.set_detail(detail);
+        }
+        if let Some(hint) = self.hint() {
+            report = report.set_hint(hint);
+        }
+        report
+    }
+}
+
+/// Any of the errors pgx's APIs return, or a caught `ERROR`, for functions that deal in several
+/// kinds of them
+///
+/// Each converts into it with `?`, and a `#[pg_extern]` function returning it raises it as the
+/// error it holds would be raised, with that error's SQLSTATE, detail, and hint.  A caught
+/// `ERROR` is raised again as it was, from where it was first raised.  An extension's own
+/// [`ErrorReportable`] errors become one with [`PgxError::report()`].
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::PgxError;
+///
+/// #[pg_extern]
+/// fn order_total(id: i64) -> Result<Option<i64>, PgxError> {
+///     let total = Spi::get_one_with_args::<AnyNumeric>(
+///         "SELECT sum(amount) FROM order_lines WHERE order_id = $1",
+///         vec![(PgBuiltInOids::INT8OID.oid(), id.into_datum())],
+///     )?;
+///     // a total too big for an `i64` is raised as `ERRCODE_NUMERIC_VALUE_OUT_OF_RANGE`
+///     Ok(total.map(i64::try_from).transpose()?)
+/// }
+/// ```
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum PgxError {
+    #[error(transparent)]
+    Spi(#[from] crate::spi::Error),
+
+    #[error(transparent)]
+    Datum(#[from] crate::TryFromDatumError),
+
+    #[error(transparent)]
+    Range(#[from] crate::RangeConversionError),
+
+    #[error(transparent)]
+    Time(#[from] crate::FromTimeError),
+
+    #[error(transparent)]
+    Numeric(#[from] crate::datum::numeric_support::error::Error),
+
+    #[error(transparent)]
+    HeapTuple(#[from] crate::heap_tuple::PgHeapTupleError),
+
+    #[error(transparent)]
+    Trigger(#[from] crate::PgTriggerError),
+
+    /// An `ERROR` caught with [`PgTryBuilder`](crate::PgTryBuilder), whether Postgres or Rust
+    /// raised it, or any other [`ErrorReport`]
+    #[error("{}", .0.message())]
+    Report(ErrorReport),
+}
+
+impl PgxError {
+    /// `error`, to be raised as it would be itself
+    #[track_caller]
+    pub fn report<E: ErrorReportable>(error: E) -> Self {
+        PgxError::Report(error.to_error_report())
+    }
+
+    fn reportable(&self) -> Result<&dyn ErrorReportable, &ErrorReport> {
+        match self {
+            PgxError::Spi(e) => Ok(e),
+            PgxError::Datum(e) => Ok(e),
+            PgxError::Range(e) => Ok(e),
+            PgxError::Time(e) => Ok(e),
+            PgxError::Numeric(e) => Ok(e),
+            PgxError::HeapTuple(e) => Ok(e),
+            PgxError::Trigger(e) => Ok(e),
+            PgxError::Report(report) => Err(report),
+        }
+    }
+}
+
+impl From<ErrorReport> for PgxError {
+    fn from(report: ErrorReport) -> Self {
+        PgxError::Report(report)
+    }
+}
+
+impl From<CaughtError> for PgxError {
+    fn from(caught: CaughtError) -> Self {
+        match caught {
+            CaughtError::PostgresError(report)
+            | CaughtError::ErrorReport(report)
+            | CaughtError::RustPanic { ereport: report, .. } => {
+                PgxError::Report(report.into_error_report())
+            }
+        }
+    }
+}
+
+impl ErrorReportable for PgxError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self.reportable() {
+            Ok(e) => e.sql_error_code(),
+            Err(report) => report.sql_error_code(),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self.reportable() {
+            Ok(e) => e.message(),
+            Err(report) => report.message().to_string(),
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self.reportable() {
+            Ok(e) => e.detail(),
+            Err(report) => report.detail().map(str::to_string),
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self.reportable() {
+            Ok(e) => e.hint(),
+            Err(report) => report.hint().map(str::to_string),
+        }
+    }
+
+    #[track_caller]
+    fn to_error_report(&self) -> ErrorReport {
+        match self.reportable() {
+            Ok(e) => e.to_error_report(),
+            // with the location it was raised from, rather than ours
+            Err(report) => report.clone(),
+        }
+    }
+}
+
+/// Used by the code `#[pg_extern]` generates to pick [`ErrorReportable`] for the returned error
+/// type when it's implemented, and [`Display`] otherwise.  Not public API.
+#[doc(hidden)]
+pub mod __private {
+    use super::ErrorReportable;
+    use crate::pg_sys::panic::ErrorReport;
+    use std::cell::Cell;
+
+    pub struct ResultWrapper<T, E>(Cell<Option<Result<T, E>>>);
+
+    impl<T, E> ResultWrapper<T, E> {
+        pub fn new(result: Result<T, E>) -> Self {
+            Self(Cell::new(Some(result)))
+        }
+
+        fn take(&self) -> Result<T, E> {
+            self.0.take().expect("ResultWrapper was already taken")
+        }
+    }
+
+    // `(&ResultWrapper::new(result)).into_report_result()` resolves to this impl's method, which
+    // takes `&ResultWrapper` as `self`, whenever `E: ErrorReportable`...
+    pub trait ViaErrorReportable {
+        type Ok;
+        fn into_report_result(&self) -> Result<Self::Ok, ErrorReport>;
+    }
+
+    impl<T, E: ErrorReportable> ViaErrorReportable for ResultWrapper<T, E> {
+        type Ok = T;
+
+        #[track_caller]
+        fn into_report_result(&self) -> Result<T, ErrorReport> {
+            self.take().map_err(|e| e.to_error_report())
+        }
+    }
+
+    // ...and otherwise to this one, which requires an extra auto-ref to take `&&ResultWrapper`
+    pub trait ViaDisplay {
+        type Ok;
+        type Err;
+        fn into_report_result(&self) -> Result<Self::Ok, Self::Err>;
+    }
+
+    impl<T, E> ViaDisplay for &ResultWrapper<T, E> {
+        type Ok = T;
+        type Err = E;
+
+        fn into_report_result(&self) -> Result<T, E> {
+            self.take()
+        }
+    }
+}
@@ -0,0 +1,451 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! The per-node timings `EXPLAIN ANALYZE` reports, for the executor hooks of an extension such as
+//! `auto_explain`
+//!
+//! The executor only collects them when it's asked to before it starts, so an
+//! [`executor_start`](crate::hooks::PgHooks::executor_start) hook asks for them with
+//! [`PgBox<QueryDesc>::enable_instrumentation()`](PgBox::enable_instrumentation), and an
+//! [`executor_end`](crate::hooks::PgHooks::executor_end) hook reads them, before the executor
+//! frees them, with [`PgBox<QueryDesc>::plan_tree()`](PgBox::plan_tree):
+//!
+//! ```rust,no_run
+//! use pgx::explain::InstrumentFlags;
+//! use pgx::hooks::{HookResult, PgHooks};
+//! use pgx::prelude::*;
+//!
+//! struct SlowScans;
+//!
+//! impl PgHooks for SlowScans {
+//!     fn executor_start(
+//!         &mut self,
+//!         mut query_desc: PgBox<pg_sys::QueryDesc>,
+//!         eflags: i32,
+//!         prev_hook: fn(PgBox<pg_sys::QueryDesc>, i32) -> HookResult<()>,
+//!     ) -> HookResult<()> {
+//!         query_desc.enable_instrumentation(InstrumentFlags::TIMER | InstrumentFlags::ROWS);
+//!         prev_hook(query_desc, eflags)
+//!     }
+//!
+//!     fn executor_end(
+//!         &mut self,
+//!         query_desc: PgBox<pg_sys::QueryDesc>,
+//!         prev_hook: fn(PgBox<pg_sys::QueryDesc>) -> HookResult<()>,
+//!     ) -> HookResult<()> {
+//!         for node in query_desc.plan_tree() {
+//!             if node.total_time() * node.loops() > 1000.0 {
+//!                 warning!("{} took {}ms", node.node_name(), node.total_time() * node.loops());
+//!             }
+//!         }
+//!         prev_hook(query_desc)
+//!     }
+//! }
+//! ```
+//!
+//! The nodes borrow the `QueryDesc`, so they can't outlive the hook, and what they report is
+//! copied into Rust memory, so nothing is left in the memory context the hook was called in.
+use crate::{pg_sys, PgBox, PgList, WhoAllocated};
+use bitflags::bitflags;
+use core::ffi::CStr;
+use serde_json::{json, Map, Value};
+
+bitflags! {
+    /// What the executor measures about each node of the plan
+    pub struct InstrumentFlags: i32 {
+        /// How long each node took, which also counts the rows
+        const TIMER = pg_sys::InstrumentOption_INSTRUMENT_TIMER as i32;
+        /// The buffers each node used
+        const BUFFERS = pg_sys::InstrumentOption_INSTRUMENT_BUFFERS as i32;
+        /// The rows each node returned
+        const ROWS = pg_sys::InstrumentOption_INSTRUMENT_ROWS as i32;
+        /// The WAL each node wrote
+        #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+        const WAL = pg_sys::InstrumentOption_INSTRUMENT_WAL as i32;
+        /// Everything
+        const ALL = pg_sys::InstrumentOption_INSTRUMENT_ALL as i32;
+    }
+}
+
+impl<AllocatedBy: WhoAllocated> PgBox<pg_sys::QueryDesc, AllocatedBy> {
+    /// Ask the executor to measure what `flags` say about each node of the plan
+    ///
+    /// It only does when it's asked before it starts, so this has to be called by an
+    /// `executor_start` hook before it calls the previous hook.
+    pub fn enable_instrumentation(&mut self, flags: InstrumentFlags) {
+        self.instrument_options |= flags.bits();
+    }
+
+    /// Measure how long the whole query takes, which [`to_explain_json()`](Self::to_explain_json)
+    /// reports as its `"Execution Time"`
+    ///
+    /// The measurement belongs to the executor's state, so this has to be called by an
+    /// `executor_start` hook after it has called the previous hook.
+    pub fn enable_total_time(&mut self) {
+        if !self.totaltime.is_null() || self.estate.is_null() {
+            return;
+        }
+        unsafe {
+            // SAFETY:  the executor's state lives until the query ends, as must the measurement
+            let estate = self.estate;
+            let totaltime = crate::PgMemoryContexts::For((*estate).es_query_cxt).switch_to(|_| {
+                #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+                {
+                    pg_sys::InstrAlloc(1, pg_sys::InstrumentOption_INSTRUMENT_ALL as i32)
+                }
+                #[cfg(any(feature = "pg14", feature = "pg15"))]
+                {
+                    pg_sys::InstrAlloc(1, pg_sys::InstrumentOption_INSTRUMENT_ALL as i32, false)
+                }
+            });
+            self.totaltime = totaltime;
+        }
+    }
+
+    /// Every node of the plan, depth-first, starting with its root
+    ///
+    /// It's empty until the executor has started.  What the nodes measured is only complete once
+    /// it has finished, in an `executor_end` hook.
+    pub fn plan_tree(&self) -> PlanNodeIter<'_> {
+        let root = if self.planstate.is_null() {
+            None
+        } else {
+            Some(PlanNode {
+                planstate: self.planstate,
+                relationship: None,
+                _marker: Default::default(),
+            })
+        };
+        PlanNodeIter { stack: root.into_iter().collect() }
+    }
+
+    /// What the plan measured, in the structure that `EXPLAIN (ANALYZE, FORMAT JSON)` prints
+    ///
+    /// It has each node's type, relation, estimates and measurements, but leaves out the details
+    /// only `EXPLAIN` can work out, such as the expressions the nodes evaluate.
+    pub fn to_explain_json(&self) -> Value {
+        let mut explain = Map::new();
+        if let Some(root) = self.plan_tree().next() {
+            explain.insert("Plan".into(), root.to_explain_json());
+        }
+        if !self.totaltime.is_null() {
+            unsafe {
+                // SAFETY:  we checked it's not null, and the executor is done with it
+                pg_sys::InstrEndLoop(self.totaltime);
+                explain.insert("Execution Time".into(), json!((*self.totaltime).total * 1000.0));
+            }
+        }
+        Value::Array(vec![Value::Object(explain)])
+    }
+}
+
+/// A node of an executing plan, and what it measured
+///
+/// The times are in milliseconds, and like the rows, they're averaged over the node's loops, as
+/// `EXPLAIN ANALYZE` reports them.  They're all zero unless the measurements were asked for with
+/// [`PgBox<QueryDesc>::enable_instrumentation()`](PgBox::enable_instrumentation).
+#[derive(Clone, Copy)]
+pub struct PlanNode<'a> {
+    planstate: *mut pg_sys::PlanState,
+    relationship: Option<&'static str>,
+    _marker: core::marker::PhantomData<&'a pg_sys::QueryDesc>,
+}
+
+impl<'a> PlanNode<'a> {
+    /// The node's [`NodeTag`](pg_sys::NodeTag), such as `T_SeqScan`, which is that of its plan
+    pub fn node_type(&self) -> pg_sys::NodeTag {
+        unsafe { (*self.plan()).type_ }
+    }
+
+    /// The node's name as `EXPLAIN` prints it, such as `"Seq Scan"`
+    pub fn node_name(&self) -> &'static str {
+        node_name(self.node_type())
+    }
+
+    /// The name of the table the node scans, if it scans one
+    pub fn relation_name(&self) -> Option<String> {
+        if !is_relation_scan(self.node_type()) {
+            return None;
+        }
+        unsafe {
+            // SAFETY:  scans' plans are all `Scan`s, and the range table lives as long as the
+            // executor's state
+            let scanrelid = (*self.plan().cast::<pg_sys::Scan>()).scanrelid as usize;
+            let estate = (*self.planstate).state;
+            if scanrelid == 0 || estate.is_null() {
+                return None;
+            }
+            let range_table = PgList::<pg_sys::RangeTblEntry>::from_pg((*estate).es_range_table);
+            let rte = range_table.get_ptr(scanrelid - 1)?;
+            if (*rte).rtekind != pg_sys::RTEKind_RTE_RELATION {
+                return None;
+            }
+            let name = pg_sys::get_rel_name((*rte).relid);
+            if name.is_null() {
+                return None;
+            }
+            let owned = CStr::from_ptr(name).to_string_lossy().into_owned();
+            pg_sys::pfree(name.cast());
+            Some(owned)
+        }
+    }
+
+    /// How the node relates to its parent, such as `"Outer"` or `"InitPlan"`, as `EXPLAIN` prints
+    /// it.  `None` for the root.
+    pub fn parent_relationship(&self) -> Option<&'static str> {
+        self.relationship
+    }
+
+    /// How many rows the planner estimated the node would return
+    pub fn plan_rows(&self) -> f64 {
+        unsafe { (*self.plan()).plan_rows }
+    }
+
+    /// What the planner estimated it would cost to return all of the node's rows
+    pub fn total_cost(&self) -> f64 {
+        unsafe { (*self.plan()).total_cost }
+    }
+
+    /// How many times the node was run, such as for each row of the outer side of a nested loop
+    pub fn loops(&self) -> f64 {
+        self.instrumentation().map_or(0.0, |instrument| instrument.nloops)
+    }
+
+    /// How many rows the node returned, on average, each time it was run
+    pub fn actual_rows(&self) -> f64 {
+        self.per_loop(|instrument| instrument.ntuples)
+    }
+
+    /// How long the node took to return its first row, on average, each time it was run
+    pub fn startup_time(&self) -> f64 {
+        self.per_loop(|instrument| instrument.startup * 1000.0)
+    }
+
+    /// How long the node took to return all of its rows, on average, each time it was run
+    pub fn total_time(&self) -> f64 {
+        self.per_loop(|instrument| instrument.total * 1000.0)
+    }
+
+    /// The node's children, in the order `EXPLAIN` prints them
+    pub fn children(&self) -> impl Iterator<Item = PlanNode<'a>> {
+        let mut children = Vec::new();
+        unsafe {
+            // SAFETY:  the executor's tree of nodes lives until the query ends, and each kind of
+            // node is checked before it's cast
+            let planstate = self.planstate;
+            let mut push = |child: *mut pg_sys::PlanState, relationship: &'static str| {
+                if !child.is_null() {
+                    children.push(PlanNode {
+                        planstate: child,
+                        relationship: Some(relationship),
+                        _marker: Default::default(),
+                    });
+                }
+            };
+
+            for subplan in PgList::<pg_sys::SubPlanState>::from_pg((*planstate).initPlan).iter_ptr()
+            {
+                push((*subplan).planstate, "InitPlan");
+            }
+            push((*planstate).lefttree, "Outer");
+            push((*planstate).righttree, "Inner");
+
+            let members = match (*planstate).type_ {
+                pg_sys::NodeTag_T_AppendState => {
+                    let append = planstate.cast::<pg_sys::AppendState>();
+                    Some(((*append).appendplans, (*append).as_nplans))
+                }
+                pg_sys::NodeTag_T_MergeAppendState => {
+                    let merge_append = planstate.cast::<pg_sys::MergeAppendState>();
+                    Some(((*merge_append).mergeplans, (*merge_append).ms_nplans))
+                }
+                pg_sys::NodeTag_T_BitmapAndState => {
+                    let bitmap_and = planstate.cast::<pg_sys::BitmapAndState>();
+                    Some(((*bitmap_and).bitmapplans, (*bitmap_and).nplans))
+                }
+                pg_sys::NodeTag_T_BitmapOrState => {
+                    let bitmap_or = planstate.cast::<pg_sys::BitmapOrState>();
+                    Some(((*bitmap_or).bitmapplans, (*bitmap_or).nplans))
+                }
+                _ => None,
+            };
+            if let Some((members, count)) = members {
+                if !members.is_null() {
+                    for member in std::slice::from_raw_parts(members, count.max(0) as usize) {
+                        push(*member, "Member");
+                    }
+                }
+            }
+            match (*planstate).type_ {
+                pg_sys::NodeTag_T_SubqueryScanState => {
+                    push((*planstate.cast::<pg_sys::SubqueryScanState>()).subplan, "Subquery");
+                }
+                pg_sys::NodeTag_T_CustomScanState => {
+                    let custom = planstate.cast::<pg_sys::CustomScanState>();
+                    for child in
+                        PgList::<pg_sys::PlanState>::from_pg((*custom).custom_ps).iter_ptr()
+                    {
+                        push(child, "Member");
+                    }
+                }
+                _ => {}
+            }
+
+            for subplan in PgList::<pg_sys::SubPlanState>::from_pg((*planstate).subPlan).iter_ptr()
+            {
+                push((*subplan).planstate, "SubPlan");
+            }
+        }
+        children.into_iter()
+    }
+
+    /// The node and its children, in the structure of a node of `EXPLAIN (ANALYZE, FORMAT JSON)`
+    pub fn to_explain_json(&self) -> Value {
+        let mut node = Map::new();
+        node.insert("Node Type".into(), json!(self.node_name()));
+        if let Some(relationship) = self.parent_relationship() {
+            node.insert("Parent Relationship".into(), json!(relationship));
+        }
+        if let Some(relation_name) = self.relation_name() {
+            node.insert("Relation Name".into(), json!(relation_name));
+        }
+        unsafe {
+            let plan = self.plan();
+            node.insert("Startup Cost".into(), json!((*plan).startup_cost));
+            node.insert("Total Cost".into(), json!((*plan).total_cost));
+            node.insert("Plan Rows".into(), json!((*plan).plan_rows));
+            node.insert("Plan Width".into(), json!((*plan).plan_width));
+        }
+        if self.loops() > 0.0 {
+            node.insert("Actual Startup Time".into(), json!(self.startup_time()));
+            node.insert("Actual Total Time".into(), json!(self.total_time()));
+            node.insert("Actual Rows".into(), json!(self.actual_rows()));
+        }
+        node.insert("Actual Loops".into(), json!(self.loops()));
+
+        let children = self.children().map(|child| child.to_explain_json()).collect::<Vec<_>>();
+        if !children.is_empty() {
+            node.insert("Plans".into(), Value::Array(children));
+        }
+        Value::Object(node)
+    }
+
+    fn plan(&self) -> *mut pg_sys::Plan {
+        unsafe { (*self.planstate).plan }
+    }
+
+    /// What the node measured, once the executor has been told that its current loop is over, as
+    /// `EXPLAIN` does
+    fn instrumentation(&self) -> Option<pg_sys::Instrumentation> {
+        unsafe {
+            let instrument = (*self.planstate).instrument;
+            if instrument.is_null() {
+                return None;
+            }
+            pg_sys::InstrEndLoop(instrument);
+            Some(*instrument)
+        }
+    }
+
+    fn per_loop(&self, measurement: impl FnOnce(&pg_sys::Instrumentation) -> f64) -> f64 {
+        match self.instrumentation() {
+            Some(instrument) if instrument.nloops > 0.0 => {
+                measurement(&instrument) / instrument.nloops
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// An iterator over a [`PlanNode`] and all of its descendants, depth-first
+pub struct PlanNodeIter<'a> {
+    stack: Vec<PlanNode<'a>>,
+}
+
+impl<'a> Iterator for PlanNodeIter<'a> {
+    type Item = PlanNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let first = self.stack.len();
+        self.stack.extend(node.children());
+        // the first child is visited first
+        self.stack[first..].reverse();
+        Some(node)
+    }
+}
+
+/// Does a node with this tag scan a table through its `Scan::scanrelid`?
+fn is_relation_scan(tag: pg_sys::NodeTag) -> bool {
+    match tag {
+        pg_sys::NodeTag_T_SeqScan
+        | pg_sys::NodeTag_T_SampleScan
+        | pg_sys::NodeTag_T_IndexScan
+        | pg_sys::NodeTag_T_IndexOnlyScan
+        | pg_sys::NodeTag_T_BitmapHeapScan
+        | pg_sys::NodeTag_T_TidScan
+        | pg_sys::NodeTag_T_ForeignScan
+        | pg_sys::NodeTag_T_CustomScan => true,
+        #[cfg(any(feature = "pg14", feature = "pg15"))]
+        pg_sys::NodeTag_T_TidRangeScan => true,
+        _ => false,
+    }
+}
+
+/// The name `EXPLAIN` gives the plan node with this tag
+fn node_name(tag: pg_sys::NodeTag) -> &'static str {
+    match tag {
+        pg_sys::NodeTag_T_Result => "Result",
+        pg_sys::NodeTag_T_ProjectSet => "ProjectSet",
+        pg_sys::NodeTag_T_ModifyTable => "ModifyTable",
+        pg_sys::NodeTag_T_Append => "Append",
+        pg_sys::NodeTag_T_MergeAppend => "Merge Append",
+        pg_sys::NodeTag_T_RecursiveUnion => "Recursive Union",
+        pg_sys::NodeTag_T_BitmapAnd => "BitmapAnd",
+        pg_sys::NodeTag_T_BitmapOr => "BitmapOr",
+        pg_sys::NodeTag_T_NestLoop => "Nested Loop",
+        pg_sys::NodeTag_T_MergeJoin => "Merge Join",
+        pg_sys::NodeTag_T_HashJoin => "Hash Join",
+        pg_sys::NodeTag_T_SeqScan => "Seq Scan",
+        pg_sys::NodeTag_T_SampleScan => "Sample Scan",
+        pg_sys::NodeTag_T_Gather => "Gather",
+        pg_sys::NodeTag_T_GatherMerge => "Gather Merge",
+        pg_sys::NodeTag_T_IndexScan => "Index Scan",
+        pg_sys::NodeTag_T_IndexOnlyScan => "Index Only Scan",
+        pg_sys::NodeTag_T_BitmapIndexScan => "Bitmap Index Scan",
+        pg_sys::NodeTag_T_BitmapHeapScan => "Bitmap Heap Scan",
+        pg_sys::NodeTag_T_TidScan => "Tid Scan",
+        #[cfg(any(feature = "pg14", feature = "pg15"))]
+        pg_sys::NodeTag_T_TidRangeScan => "Tid Range Scan",
+        pg_sys::NodeTag_T_SubqueryScan => "Subquery Scan",
+        pg_sys::NodeTag_T_FunctionScan => "Function Scan",
+        pg_sys::NodeTag_T_TableFuncScan => "Table Function Scan",
+        pg_sys::NodeTag_T_ValuesScan => "Values Scan",
+        pg_sys::NodeTag_T_CteScan => "CTE Scan",
+        pg_sys::NodeTag_T_NamedTuplestoreScan => "Named Tuplestore Scan",
+        pg_sys::NodeTag_T_WorkTableScan => "WorkTable Scan",
+        pg_sys::NodeTag_T_ForeignScan => "Foreign Scan",
+        pg_sys::NodeTag_T_CustomScan => "Custom Scan",
+        pg_sys::NodeTag_T_Material => "Materialize",
+        #[cfg(any(feature = "pg14", feature = "pg15"))]
+        pg_sys::NodeTag_T_Memoize => "Memoize",
+        pg_sys::NodeTag_T_Sort => "Sort",
+        #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+        pg_sys::NodeTag_T_IncrementalSort => "Incremental Sort",
+        pg_sys::NodeTag_T_Group => "Group",
+        pg_sys::NodeTag_T_Agg => "Aggregate",
+        pg_sys::NodeTag_T_WindowAgg => "WindowAgg",
+        pg_sys::NodeTag_T_Unique => "Unique",
+        pg_sys::NodeTag_T_SetOp => "SetOp",
+        pg_sys::NodeTag_T_LockRows => "LockRows",
+        pg_sys::NodeTag_T_Limit => "Limit",
+        pg_sys::NodeTag_T_Hash => "Hash",
+        _ => "???",
+    }
+}
@@ -0,0 +1,145 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! A stable C ABI for other extensions to call an extension's functions directly, without SQL
+//!
+//! A function marked [`#[pg_export_abi]`](macro@crate::pg_export_abi) gets an `extern "C"` shim,
+//! exported from the extension's library, which every such function shares the signature of:
+//!
+//! ```c
+//! int32 myext_add(const PgxAbiDatum *args, int32 nargs, PgxAbiDatum *result, PgxAbiError *error);
+//! ```
+//!
+//! The arguments and result are datums, as the function's [`FromDatum`] and [`IntoDatum`] types
+//! convert them.  Rather than unwinding or `longjmp()`ing into the caller, an `ERROR` or a Rust
+//! panic is returned as a [`PGX_ABI_ERROR`] or [`PGX_ABI_PANIC`] status, with its SQLSTATE and
+//! message in the [`PgxAbiError`].
+//!
+//! `cargo pgx schema --emit-header myext_abi.h` writes the C header declaring the types,
+//! statuses and every exported function, for the other extension to include.
+use crate::pg_sys::panic::CaughtError;
+use crate::pg_sys::PgTryBuilder;
+use crate::{pg_sys, FromDatum, PgLogLevel, PgSqlErrorCode};
+use std::ffi::CString;
+use std::panic::AssertUnwindSafe;
+
+pub use pgx_sql_entity_graph::PGX_ABI_VERSION;
+
+/// The call succeeded, and its result is in `*result`
+pub const PGX_ABI_OK: i32 = 0;
+/// The function raised an `ERROR`, which was caught
+pub const PGX_ABI_ERROR: i32 = 1;
+/// The function panicked, and the panic was caught
+pub const PGX_ABI_PANIC: i32 = 2;
+/// The call itself was wrong, such as having the wrong number of arguments
+pub const PGX_ABI_BAD_CALL: i32 = 3;
+
+/// An argument or result of an exported function, laid out like Postgres' `NullableDatum`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PgxAbiDatum {
+    pub value: pg_sys::Datum,
+    pub isnull: bool,
+}
+
+/// Why a call to an exported function failed
+#[repr(C)]
+#[derive(Debug)]
+pub struct PgxAbiError {
+    /// The SQLSTATE, encoded as `MAKE_SQLSTATE()` does
+    pub sqlerrcode: i32,
+    /// The message, allocated in the memory context that was current when the function was
+    /// called, or null if the call succeeded
+    pub message: *mut std::os::raw::c_char,
+}
+
+/// Convert the argument `idx`.  Not public API.
+#[doc(hidden)]
+pub unsafe fn __arg<T: FromDatum>(args: &[PgxAbiDatum], idx: usize) -> Option<T> {
+    T::from_datum(args[idx].value, args[idx].isnull)
+}
+
+/// Raise the `ERROR` for a `NULL` argument that isn't an `Option`.  Not public API.
+#[doc(hidden)]
+pub fn __null_argument(function: &str, argument: &str) -> ! {
+    crate::pg_sys::panic::ErrorReport::new(
+        PgSqlErrorCode::ERRCODE_NULL_VALUE_NOT_ALLOWED,
+        format!("argument `{}` of `{}` can't be NULL", argument, function),
+        "__null_argument",
+    )
+    .report(PgLogLevel::ERROR);
+    unreachable!()
+}
+
+/// The body of every `#[pg_export_abi]` shim:  check the call, run `f` on the arguments, and
+/// catch anything it raises.  Not public API.
+#[doc(hidden)]
+pub unsafe fn __call(
+    symbol: &str,
+    expected_nargs: usize,
+    args: *const PgxAbiDatum,
+    nargs: i32,
+    result: *mut PgxAbiDatum,
+    error: *mut PgxAbiError,
+    f: impl FnOnce(&[PgxAbiDatum]) -> Option<pg_sys::Datum>,
+) -> i32 {
+    if result.is_null() || error.is_null() {
+        return PGX_ABI_BAD_CALL;
+    }
+    *error = PgxAbiError { sqlerrcode: 0, message: std::ptr::null_mut() };
+
+    let caller_context = pg_sys::CurrentMemoryContext;
+    if nargs < 0 || nargs as usize != expected_nargs || (args.is_null() && expected_nargs > 0) {
+        set_error(
+            error,
+            caller_context,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            &format!("`{}` takes {} arguments, not {}", symbol, expected_nargs, nargs),
+        );
+        return PGX_ABI_BAD_CALL;
+    }
+    let args =
+        if expected_nargs == 0 { &[] } else { std::slice::from_raw_parts(args, expected_nargs) };
+
+    let outcome = PgTryBuilder::new(AssertUnwindSafe(|| Ok(f(args))))
+        .catch_others(|caught| Err(caught))
+        .execute();
+    match outcome {
+        Ok(datum) => {
+            *result = match datum {
+                Some(value) => PgxAbiDatum { value, isnull: false },
+                None => PgxAbiDatum { value: pg_sys::Datum::from(0), isnull: true },
+            };
+            PGX_ABI_OK
+        }
+        Err(caught) => {
+            let (status, ereport) = match &caught {
+                CaughtError::PostgresError(ereport) | CaughtError::ErrorReport(ereport) => {
+                    (PGX_ABI_ERROR, ereport)
+                }
+                CaughtError::RustPanic { ereport, .. } => (PGX_ABI_PANIC, ereport),
+            };
+            set_error(error, caller_context, ereport.sql_error_code(), ereport.message());
+            status
+        }
+    }
+}
+
+unsafe fn set_error(
+    error: *mut PgxAbiError,
+    context: pg_sys::MemoryContext,
+    sqlerrcode: PgSqlErrorCode,
+    message: &str,
+) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    *error = PgxAbiError {
+        sqlerrcode: sqlerrcode as i32,
+        message: pg_sys::MemoryContextStrdup(context, message.as_ptr()),
+    };
+}
@@ -0,0 +1,397 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Evaluate arbitrary Postgres expressions, such as a `CHECK` constraint or a pushed-down qual,
+//! over values supplied from Rust.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use pgx::expr::PgExpression;
+//! use pgx::prelude::*;
+//! use pgx::{IntoDatum, PgBuiltInOids};
+//!
+//! # fn foo() -> Result<(), pgx::expr::Error> {
+//! let expr = PgExpression::parse("$1 + $2 > 10", &[
+//!     PgBuiltInOids::INT4OID.oid(),
+//!     PgBuiltInOids::INT4OID.oid(),
+//! ])?;
+//!
+//! assert!(expr.evaluate_qual(&[7.into_datum(), 5.into_datum()])?);
+//! assert!(!expr.evaluate_qual(&[7.into_datum(), None])?);
+//! # Ok(())
+//! # }
+//! ```
+use crate::backend_local::{on_discard, on_reset_all, Discard};
+use crate::invalidation::on_syscache_change;
+use crate::prelude::*;
+use crate::{PgList, PgMemoryContexts, TryFromDatumError};
+use pgx_pg_sys::panic::{CaughtError, ErrorReportWithLevel};
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Set of possible errors `pgx` might return while preparing or evaluating a [`PgExpression`]
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The number of values supplied to [`PgExpression::evaluate()`] doesn't match the number
+    /// of argument types given to [`PgExpression::parse()`]
+    #[error("Argument count mismatch (expected {expected}, got {got})")]
+    ArgumentCountMismatch { expected: usize, got: usize },
+
+    /// The source text was valid SQL, but not a standalone scalar expression
+    #[error("`{0}` is not a standalone expression")]
+    NotAnExpression(String),
+
+    /// The expression's result can't be converted into the requested Rust type
+    #[error("Datum error: {0}")]
+    DatumError(#[from] TryFromDatumError),
+
+    /// Postgres raised an ERROR while parsing (or re-parsing) the expression
+    #[error("{}", .0.message())]
+    PostgresError(ErrorReportWithLevel),
+}
+
+impl crate::ErrorReportable for Error {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            Error::ArgumentCountMismatch { .. } => PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            Error::NotAnExpression(_) => PgSqlErrorCode::ERRCODE_SYNTAX_ERROR,
+            Error::DatumError(e) => crate::ErrorReportable::sql_error_code(e),
+            Error::PostgresError(report) => report.sql_error_code(),
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Error::PostgresError(report) => report.detail().map(str::to_string),
+            _ => None,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Error::PostgresError(report) => report.hint().map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+/// Bumped every time the catalog entries an expression might depend on are invalidated, and when
+/// the session is reset
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A Postgres expression that has been parsed, analyzed, and prepared for execution.
+///
+/// Arguments are referenced from the expression text as `$1`, `$2`, etc, and their types are
+/// fixed when the expression is parsed.
+///
+/// A `PgExpression` owns its own executor state, which lives in `TopMemoryContext`, so it can be
+/// cached across transactions.  If any function, operator, type, or namespace is changed in the
+/// meantime, or the session is reset by `DISCARD PLANS`, `DISCARD ALL`, or `RESET ALL`, the
+/// expression is transparently re-parsed before its next evaluation.  Re-parsing
+/// fails (with [`Error::PostgresError`]) if the expression refers to an object that no longer
+/// exists.
+pub struct PgExpression {
+    source: String,
+    arg_types: Vec<pg_sys::Oid>,
+    prepared: RefCell<Prepared>,
+}
+
+struct Prepared {
+    generation: u64,
+    result_type: pg_sys::Oid,
+    estate: *mut pg_sys::EState,
+    state: *mut pg_sys::ExprState,
+    econtext: *mut pg_sys::ExprContext,
+    params: pg_sys::ParamListInfo,
+}
+
+impl Drop for Prepared {
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY:  we created `estate` and it owns everything else we point to
+            pg_sys::FreeExecutorState(self.estate);
+        }
+    }
+}
+
+impl PgExpression {
+    /// Parse `sql_expr` into an executable expression whose `$n` parameters have the types
+    /// specified by `arg_types`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::PostgresError`] if Postgres can't parse or analyze the expression, and
+    /// [`Error::NotAnExpression`] if it's something other than a single scalar expression (for
+    /// example, it contains a `FROM` clause, an aggregate, or a sub-select).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `sql_expr` contains a null byte.
+    pub fn parse(sql_expr: &str, arg_types: &[PgOid]) -> Result<Self> {
+        register_invalidation_callbacks();
+
+        let source = sql_expr.to_string();
+        let arg_types = arg_types.iter().map(|oid| oid.value()).collect::<Vec<_>>();
+        let prepared = Prepared::new(&source, &arg_types)?;
+        Ok(PgExpression { source, arg_types, prepared: RefCell::new(prepared) })
+    }
+
+    /// The original text of this expression
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The types of the `$n` arguments, in order
+    pub fn arg_types(&self) -> &[pg_sys::Oid] {
+        &self.arg_types
+    }
+
+    /// The type Postgres resolved as the result of this expression
+    pub fn result_type(&self) -> pg_sys::Oid {
+        self.prepared.borrow().result_type
+    }
+
+    /// Evaluate this expression with the specified argument values, where `None` represents NULL.
+    ///
+    /// Evaluation happens in a per-call memory context that is reset at the start of the next
+    /// call, so a pass-by-reference result is only valid until this expression is evaluated again
+    /// or dropped.  Use [`PgExpression::evaluate_as()`] to get an owned Rust value instead.
+    ///
+    /// Any Postgres ERROR raised while evaluating the expression itself (division by zero, for
+    /// example) is raised as usual and is not converted into an [`Error`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ArgumentCountMismatch`] if the wrong number of values are provided, or
+    /// [`Error::PostgresError`] if the expression had to be re-parsed and that failed.
+    pub fn evaluate(&self, values: &[Option<pg_sys::Datum>]) -> Result<Option<pg_sys::Datum>> {
+        if values.len() != self.arg_types.len() {
+            return Err(Error::ArgumentCountMismatch {
+                expected: self.arg_types.len(),
+                got: values.len(),
+            });
+        }
+
+        unsafe {
+            // SAFETY:  this is always safe to call from within a transaction, and will deliver
+            // pending invalidations to our callbacks
+            pg_sys::AcceptInvalidationMessages();
+        }
+        if self.prepared.borrow().generation != GENERATION.load(Ordering::Relaxed) {
+            // the previous executor state is freed when it's replaced.  If re-parsing fails we'll
+            // simply try again on the next call
+            let fresh = Prepared::new(&self.source, &self.arg_types)?;
+            self.prepared.replace(fresh);
+        }
+
+        let prepared = self.prepared.borrow();
+        unsafe {
+            // SAFETY:  `prepared` was fully initialized by `Prepared::new()`, and `params` was
+            // allocated with room for exactly `self.arg_types.len()` entries
+            let econtext = prepared.econtext;
+            pg_sys::MemoryContextReset((*econtext).ecxt_per_tuple_memory);
+
+            let params = (*prepared.params).params.as_mut_slice(values.len());
+            for (param, value) in params.iter_mut().zip(values) {
+                param.value = value.unwrap_or(pg_sys::Datum::from(0usize));
+                param.isnull = value.is_none();
+            }
+
+            let state = prepared.state;
+            let evalfunc = (*state).evalfunc.expect("ExprState has no evalfunc");
+            let mut is_null = false;
+            let datum = PgMemoryContexts::For((*econtext).ecxt_per_tuple_memory)
+                .switch_to(|_| evalfunc(state, econtext, &mut is_null));
+
+            Ok(if is_null { None } else { Some(datum) })
+        }
+    }
+
+    /// Evaluate this expression and convert its result into a Rust type.
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors described by [`PgExpression::evaluate()`], returns
+    /// [`Error::DatumError`] if the expression's result type isn't compatible with `T`.
+    pub fn evaluate_as<T: FromDatum + IntoDatum>(
+        &self,
+        values: &[Option<pg_sys::Datum>],
+    ) -> Result<Option<T>> {
+        let datum = self.evaluate(values)?;
+        unsafe {
+            // SAFETY:  the datum was just produced by Postgres for our expression's result type
+            Ok(T::try_from_datum(
+                datum.unwrap_or(pg_sys::Datum::from(0usize)),
+                datum.is_none(),
+                self.result_type(),
+            )?)
+        }
+    }
+
+    /// Evaluate this expression as a qualifier, the way a `WHERE` clause or `CHECK` constraint
+    /// would.  A NULL result is treated as `false`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DatumError`] if the expression doesn't return `bool`, otherwise the
+    /// same errors as [`PgExpression::evaluate()`].
+    pub fn evaluate_qual(&self, values: &[Option<pg_sys::Datum>]) -> Result<bool> {
+        Ok(self.evaluate_as::<bool>(values)?.unwrap_or(false))
+    }
+}
+
+impl Prepared {
+    fn new(source: &str, arg_types: &[pg_sys::Oid]) -> Result<Self> {
+        let query_string =
+            CString::new(format!("SELECT {}", source)).expect("expression contained a null byte");
+        let mut arg_types = arg_types.to_vec();
+        let nargs = arg_types.len();
+        let arg_types_ptr = arg_types.as_mut_ptr();
+
+        // anything that goes wrong while Postgres parses the expression is reported back to
+        // the caller as an `Err`.  Nothing has been allocated outside the current memory context
+        // at this point, so there's nothing for us to clean up
+        let analyzed = PgTryBuilder::new(|| unsafe {
+            // SAFETY:  `query_string` and `arg_types` outlive these calls, and Postgres doesn't
+            // modify the argument types array as they're all known
+            let raw_stmts =
+                PgList::<pg_sys::RawStmt>::from_pg(pg_sys::pg_parse_query(query_string.as_ptr()));
+            if raw_stmts.len() != 1 {
+                return Ok(None);
+            }
+
+            #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13", feature = "pg14"))]
+            let query = pg_sys::parse_analyze(
+                raw_stmts.head().unwrap(),
+                query_string.as_ptr(),
+                arg_types_ptr,
+                nargs as _,
+                std::ptr::null_mut(),
+            );
+            #[cfg(feature = "pg15")]
+            let query = pg_sys::parse_analyze_fixedparams(
+                raw_stmts.head().unwrap(),
+                query_string.as_ptr(),
+                arg_types_ptr,
+                nargs as _,
+                std::ptr::null_mut(),
+            );
+
+            Ok(single_expression(query))
+        })
+        .catch_others(|e| match e {
+            CaughtError::PostgresError(ereport) | CaughtError::ErrorReport(ereport) => {
+                Err(Error::PostgresError(ereport))
+            }
+            CaughtError::RustPanic { .. } => e.rethrow(),
+        })
+        .execute()?;
+
+        let expr = analyzed.ok_or_else(|| Error::NotAnExpression(source.to_string()))?;
+
+        unsafe {
+            // SAFETY:  `expr` is a valid, analyzed expression tree.  `ExecPrepareExpr()` plans
+            // and copies it into the EState's own memory context, so the parse tree it came from
+            // can be discarded with the current memory context
+            let result_type = pg_sys::exprType(expr.cast());
+            let estate =
+                PgMemoryContexts::TopMemoryContext.switch_to(|_| pg_sys::CreateExecutorState());
+            let state = pg_sys::ExecPrepareExpr(expr, estate);
+
+            let nparams = nargs;
+            let params = PgMemoryContexts::For((*estate).es_query_cxt).palloc0(
+                std::mem::size_of::<pg_sys::ParamListInfoData>()
+                    + nparams * std::mem::size_of::<pg_sys::ParamExternData>(),
+            ) as pg_sys::ParamListInfo;
+            (*params).numParams = nparams as _;
+            for (param, typoid) in (*params).params.as_mut_slice(nparams).iter_mut().zip(arg_types)
+            {
+                param.ptype = typoid;
+                param.pflags = pg_sys::PARAM_FLAG_CONST as _;
+                param.isnull = true;
+            }
+
+            // `CreateExprContext()` picks up the EState's param list for us
+            (*estate).es_param_list_info = params;
+            let econtext = pg_sys::CreateExprContext(estate);
+
+            Ok(Prepared {
+                generation: GENERATION.load(Ordering::Relaxed),
+                result_type,
+                estate,
+                state,
+                econtext,
+                params,
+            })
+        }
+    }
+}
+
+/// Extract the sole target expression from an analyzed `SELECT <expr>`, provided that's all the
+/// query does
+unsafe fn single_expression(query: *mut pg_sys::Query) -> Option<*mut pg_sys::Expr> {
+    let query = query.as_ref()?;
+    let target_list = PgList::<pg_sys::TargetEntry>::from_pg(query.targetList);
+    let has_quals = !query.jointree.is_null() && !(*query.jointree).quals.is_null();
+
+    if query.commandType != pg_sys::CmdType_CMD_SELECT
+        || target_list.len() != 1
+        || !query.rtable.is_null()
+        || has_quals
+        || query.hasAggs
+        || query.hasWindowFuncs
+        || query.hasTargetSRFs
+        || query.hasSubLinks
+        || !query.groupClause.is_null()
+        || !query.havingQual.is_null()
+        || !query.distinctClause.is_null()
+        || !query.sortClause.is_null()
+        || !query.limitOffset.is_null()
+        || !query.limitCount.is_null()
+        || !query.setOperations.is_null()
+    {
+        return None;
+    }
+
+    target_list.head().and_then(|tle| tle.as_ref()).map(|tle| tle.expr)
+}
+
+/// Register, once per backend, the catalog invalidation and session reset callbacks that tell us
+/// prepared expressions need to be re-parsed
+fn register_invalidation_callbacks() {
+    static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    if !REGISTERED.swap(true, Ordering::Relaxed) {
+        for cacheid in [
+            pg_sys::SysCacheIdentifier_PROCOID,
+            pg_sys::SysCacheIdentifier_OPEROID,
+            pg_sys::SysCacheIdentifier_TYPEOID,
+            pg_sys::SysCacheIdentifier_NAMESPACEOID,
+        ] {
+            on_syscache_change(cacheid, |_| {
+                GENERATION.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+
+        // `DISCARD PLANS` should discard these plans too, and what an expression resolves to can
+        // change with the `search_path` that `RESET ALL` resets
+        on_discard(|discard| {
+            if discard.includes(Discard::Plans) {
+                GENERATION.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        on_reset_all(|| {
+            GENERATION.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+}
@@ -72,6 +72,19 @@ pub struct NULL;
 ///     TableIterator::new(vec![1, 2, 3].into_iter().zip(vec!["A", "B", "C"].into_iter()))
 /// }
 /// ```
+///
+/// It also names an argument in SQL, differently from the Rust one, and can wrap any argument
+/// type, including a `composite_type!()` and the `Option`s and `Vec`s of one.  It has to wrap the
+/// whole type:  `Option<name!(..)>` isn't allowed.
+///
+/// ```rust
+/// use pgx::prelude::*;
+///
+/// #[pg_extern]
+/// fn walk(dog: name!(owner, Option<pgx::composite_type!("Dog")>)) -> bool {
+///     dog.is_some()
+/// }
+/// ```
 #[macro_export]
 macro_rules! name {
     ($name:tt, $ty:ty) => {
@@ -324,6 +337,34 @@ pub use pg_11::*;
 #[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
 pub use pg_12_13_14_15::*;
 
+/// Get a numbered argument for a `PG_FUNCTION_INFO_V1` function as the specified Rust type, like
+/// [`pg_getarg()`], naming it `argument` in the `ERROR` raised for a value `T` can't represent.
+///
+/// This is what the `#[pg_extern]` macro uses.
+///
+/// # Safety
+///
+/// This function is unsafe as we cannot ensure the `fcinfo` argument is a valid
+/// [`pg_sys::FunctionCallInfo`] pointer.  This is your responsibility.
+///
+/// We also cannot ensure that the specified Rust type `T` is compatible with whatever the
+/// underlying datum is at the argument `num` position.  This too, is your responsibility
+#[inline]
+pub unsafe fn pg_getarg_named<T: FromDatum>(
+    fcinfo: pg_sys::FunctionCallInfo,
+    num: usize,
+    argument: &str,
+) -> Option<T> {
+    unsafe {
+        // SAFETY:  The user has asserted that `fcinfo` is valid, and that `T` is compatible with
+        // the argument's Datum
+        let datum = pg_getarg_datum_raw(fcinfo, num);
+        let isnull = pg_arg_is_null(fcinfo, num);
+        let typoid = if T::GET_TYPOID { pg_getarg_type(fcinfo, num) } else { pg_sys::InvalidOid };
+        T::from_argument(datum, isnull, typoid, argument)
+    }
+}
+
 /// Get a numbered argument for a `PG_FUNCTION_INFO_V1` function as raw pointer to a Rust type `T`.
 ///
 /// If the specified argument Datum is NULL, returns [`Option::None`].
@@ -355,6 +396,20 @@ pub unsafe fn pg_getarg_type(fcinfo: pg_sys::FunctionCallInfo, num: usize) -> pg
     pg_sys::get_fn_expr_argtype(fcinfo.as_ref().unwrap().flinfo, num as std::os::raw::c_int)
 }
 
+/// The type a call resolved the function's polymorphic return type, such as `anyelement`, to
+///
+/// It's `InvalidOid` when the function wasn't called from an expression, such as by
+/// [`direct_function_call()`], as there's nothing to resolve it from.
+///
+/// # Safety
+///
+/// The provided `fcinfo` must be valid otherwise this function results in undefined behavior due
+/// to an out of bounds read.
+#[inline]
+pub unsafe fn pg_return_type(fcinfo: pg_sys::FunctionCallInfo) -> pg_sys::Oid {
+    pg_sys::get_fn_expr_rettype(fcinfo.as_ref().unwrap().flinfo)
+}
+
 /// This is intended for Postgres functions that take an actual `cstring` argument, not for getting
 /// a varlena argument type as a CStr.
 ///
@@ -493,22 +548,29 @@ pub unsafe fn direct_function_call_as_datum(
     func: unsafe fn(pg_sys::FunctionCallInfo) -> pg_sys::Datum,
     args: Vec<Option<pg_sys::Datum>>,
 ) -> Option<pg_sys::Datum> {
-    direct_function_call_as_datum_internal(|fcinfo| func(fcinfo), args)
+    direct_function_call_as_datum_internal(
+        |fcinfo| func(fcinfo),
+        std::ptr::null_mut(),
+        pg_sys::InvalidOid,
+        args,
+    )
 }
 
 #[cfg(feature = "pg11")]
 unsafe fn direct_function_call_as_datum_internal(
     func: impl FnOnce(pg_sys::FunctionCallInfo) -> pg_sys::Datum,
+    flinfo: *mut pg_sys::FmgrInfo,
+    collation: pg_sys::Oid,
     args: Vec<Option<pg_sys::Datum>>,
 ) -> Option<pg_sys::Datum> {
     let fcinfo_ptr = pg_sys::palloc(std::mem::size_of::<pg_sys::FunctionCallInfoData>())
         .cast::<pg_sys::FunctionCallInfoData>();
 
     let fcinfo = fcinfo_ptr.as_mut().unwrap_unchecked();
-    fcinfo.flinfo = std::ptr::null_mut();
+    fcinfo.flinfo = flinfo;
     fcinfo.context = std::ptr::null_mut();
     fcinfo.resultinfo = std::ptr::null_mut();
-    fcinfo.fncollation = pg_sys::InvalidOid;
+    fcinfo.fncollation = collation;
     fcinfo.isnull = false;
     fcinfo.nargs = args.len() as _;
 
@@ -527,6 +589,8 @@ unsafe fn direct_function_call_as_datum_internal(
 #[cfg(not(feature = "pg11"))]
 unsafe fn direct_function_call_as_datum_internal(
     func: impl FnOnce(pg_sys::FunctionCallInfo) -> pg_sys::Datum,
+    flinfo: *mut pg_sys::FmgrInfo,
+    collation: pg_sys::Oid,
     args: Vec<Option<pg_sys::Datum>>,
 ) -> Option<pg_sys::Datum> {
     let nargs = args.len();
@@ -537,10 +601,10 @@ unsafe fn direct_function_call_as_datum_internal(
     .cast::<pg_sys::FunctionCallInfoBaseData>();
 
     let fcinfo = fcinfo_ptr.as_mut().unwrap_unchecked();
-    fcinfo.flinfo = std::ptr::null_mut();
+    fcinfo.flinfo = flinfo;
     fcinfo.context = std::ptr::null_mut();
     fcinfo.resultinfo = std::ptr::null_mut();
-    fcinfo.fncollation = pg_sys::InvalidOid;
+    fcinfo.fncollation = collation;
     fcinfo.isnull = false;
     fcinfo.nargs = nargs as _;
 
@@ -563,11 +627,39 @@ unsafe fn direct_function_call_as_datum_internal(
 /// ## Safety
 ///
 /// This function is unsafe as the function you're calling is also unsafe
+///
+/// An `ERROR` raised by `func` is caught, like one raised by any `pg_sys` function, so the
+/// caller's stack is unwound, running its destructors, before the `ERROR` is rethrown.
 pub unsafe fn direct_pg_extern_function_call_as_datum(
     func: unsafe extern "C" fn(pg_sys::FunctionCallInfo) -> pg_sys::Datum,
     args: Vec<Option<pg_sys::Datum>>,
 ) -> Option<pg_sys::Datum> {
-    direct_function_call_as_datum_internal(|fcinfo| func(fcinfo), args)
+    direct_function_call_as_datum_internal(
+        |fcinfo| pg_sys::ffi::pg_guard_ffi_boundary(|| func(fcinfo)),
+        std::ptr::null_mut(),
+        pg_sys::InvalidOid,
+        args,
+    )
+}
+
+/// Call the function `flinfo` was looked up for by `fmgr_info()`, with `collation` and `args`, the
+/// way the executor calls a function in a query
+///
+/// ## Safety
+///
+/// `flinfo` must be valid, for a function that isn't set-returning, which takes `args`
+pub(crate) unsafe fn fmgr_function_call_as_datum(
+    flinfo: *mut pg_sys::FmgrInfo,
+    collation: pg_sys::Oid,
+    args: Vec<Option<pg_sys::Datum>>,
+) -> Option<pg_sys::Datum> {
+    let func = (*flinfo).fn_addr.expect("FmgrInfo has no function");
+    direct_function_call_as_datum_internal(
+        |fcinfo| pg_sys::ffi::pg_guard_ffi_boundary(|| func(fcinfo)),
+        flinfo,
+        collation,
+        args,
+    )
 }
 
 #[inline]
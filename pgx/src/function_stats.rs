@@ -0,0 +1,371 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! How often, for how long, and how unsuccessfully each `#[pg_extern]` function has been called.
+//!
+//! With the `function-stats` crate feature, the wrapper Postgres calls each `#[pg_extern]`
+//! function through counts its calls, how long they took in total and at most, and how many of
+//! them raised an `ERROR`.  [`pg_module_magic!()`](crate::pg_module_magic) then also creates two
+//! SQL functions in the extension's schema:
+//!
+//! ```sql
+//! SELECT * FROM my_extension.function_stats();
+//! --       name       |           path            | calls | errors | total_time | max_time
+//! -- -----------------+---------------------------+-------+--------+------------+----------
+//! --  lookup          | my_extension::lookup      |    42 |      1 |   3.218211 | 0.402633
+//!
+//! SELECT my_extension.function_stats_reset();
+//! ```
+//!
+//! Times are in milliseconds.  A set-returning function's wrapper is called once for every row it
+//! returns, and once more to find it has none left, and each of those counts as a call.
+//!
+//! Like [`pgx::stats`](crate::stats), a call only updates backend-local counts, and a backend adds
+//! all of its counts to shared memory at once when its transaction commits or aborts, or right
+//! away outside of a transaction.  The shared counts are sized by the number of `#[pg_extern]`
+//! functions in the library, and are only available when the extension is loaded through
+//! `shared_preload_libraries`.  Otherwise `function_stats()` only shows the calls made by the
+//! backend it's called from, during its current transaction.
+//!
+//! Collection can be turned off with the `<extension>.function_stats` GUC, which is `on` by
+//! default and which only superusers can change.  While it's `off`, each call costs a single
+//! branch.  Without the feature, the wrappers don't count anything at all.
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::guc::{GucContext, GucRegistry, GucSetting};
+use crate::{pg_guard, pg_shmem_init, pg_sys, PgSharedMemoryInitialization, PgSqlErrorCode};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: GucSetting<bool> = GucSetting::new(true);
+static SHARED_STATS: SharedFunctionStats = SharedFunctionStats;
+
+// these are all backend-local, and only ever accessed from the backend's main thread
+static mut FUNCTIONS: Vec<&'static FunctionSlot> = Vec::new();
+static mut PENDING: Vec<Counts> = Vec::new();
+static mut SHARED: Option<(*mut pg_sys::LWLock, *mut Counts)> = None;
+
+const SHMEM_NAME: &str = "pgx function stats";
+
+/// A `#[pg_extern]` function whose calls are counted.  Not public API.
+#[doc(hidden)]
+pub struct FunctionSlot {
+    name: &'static str,
+    path: &'static str,
+    index: AtomicUsize,
+}
+
+impl FunctionSlot {
+    pub const fn new(name: &'static str, path: &'static str) -> Self {
+        Self { name, path, index: AtomicUsize::new(usize::MAX) }
+    }
+}
+
+/// What's been counted of one function's calls
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[repr(C)]
+struct Counts {
+    calls: u64,
+    errors: u64,
+    total_nanos: u64,
+    max_nanos: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.calls = self.calls.wrapping_add(other.calls);
+        self.errors = self.errors.wrapping_add(other.errors);
+        self.total_nanos = self.total_nanos.wrapping_add(other.total_nanos);
+        self.max_nanos = self.max_nanos.max(other.max_nanos);
+    }
+}
+
+/// The statistics of one `#[pg_extern]` function, as [`function_stats`] returns them
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionStats {
+    /// The function's name in SQL
+    pub name: &'static str,
+    /// The function's full Rust path
+    pub path: &'static str,
+    pub calls: u64,
+    /// How many calls raised an `ERROR`
+    pub errors: u64,
+    pub total_time: Duration,
+    /// The longest any one call took
+    pub max_time: Duration,
+}
+
+/// Registers a `#[pg_extern]` function's wrapper.  Not public API.
+#[doc(hidden)]
+pub fn __register_function(slot: &'static FunctionSlot) {
+    // SAFETY:  called by the dynamic loader, one at a time, before `_PG_init()`
+    unsafe { FUNCTIONS.push(slot) }
+}
+
+/// Counts one call of a `#[pg_extern]` function, when it's dropped.  Not public API.
+#[doc(hidden)]
+pub struct __FunctionCall {
+    slot: &'static FunctionSlot,
+    started: Instant,
+}
+
+impl __FunctionCall {
+    #[inline]
+    pub fn start(slot: &'static FunctionSlot) -> Option<Self> {
+        if !ENABLED.get() {
+            return None;
+        }
+        Some(Self { slot, started: Instant::now() })
+    }
+}
+
+impl Drop for __FunctionCall {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        let panicking = std::thread::panicking();
+        unsafe {
+            // SAFETY:  only ever accessed from the backend's main thread
+            let counts = match PENDING.get_mut(self.slot.index.load(Ordering::Relaxed)) {
+                Some(counts) => counts,
+                None => return,
+            };
+            counts.add(&Counts {
+                calls: 1,
+                errors: panicking as u64,
+                total_nanos: elapsed,
+                max_nanos: elapsed,
+            });
+
+            // inside a transaction, the counts are flushed when it ends.  Taking the lock while
+            // unwinding from an `ERROR` risks another, so those wait for the next flush
+            if !panicking && !pg_sys::IsTransactionState() {
+                flush_function_stats();
+            }
+        }
+    }
+}
+
+/// Numbers the registered functions, defines the `<extension>.function_stats` GUC and, when
+/// preloaded, asks for the shared memory to count their calls in.  Called from `_PG_init()`.
+pub(crate) unsafe fn install() {
+    FUNCTIONS.sort_by(|a, b| (a.name, a.path).cmp(&(b.name, b.path)));
+    for (index, slot) in FUNCTIONS.iter().enumerate() {
+        slot.index.store(index, Ordering::Relaxed);
+    }
+    PENDING = vec![Counts::default(); FUNCTIONS.len()];
+    pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut());
+
+    if let Some(extension) = crate::version_check::extension_name() {
+        GucRegistry::define_bool_guc(
+            &format!("{}.function_stats", extension),
+            "Count the calls of the extension's functions",
+            "Keep the number of calls, errors, and the total and longest time spent in each of the extension's functions, for its `function_stats()`.",
+            &ENABLED,
+            GucContext::Suset,
+        );
+    }
+
+    if pg_sys::process_shared_preload_libraries_in_progress && !FUNCTIONS.is_empty() {
+        pg_shmem_init!(SHARED_STATS);
+    }
+}
+
+/// The shared counts of every function, behind their own `LWLock`
+struct SharedFunctionStats;
+
+impl PgSharedMemoryInitialization for SharedFunctionStats {
+    fn pg_init(&'static self) {
+        unsafe {
+            let name = CString::new(SHMEM_NAME).unwrap();
+            pg_sys::RequestAddinShmemSpace(std::mem::size_of::<Counts>() * FUNCTIONS.len());
+            pg_sys::RequestNamedLWLockTranche(name.as_ptr(), 1);
+        }
+    }
+
+    fn shmem_init(&'static self) {
+        unsafe {
+            let name = CString::new(SHMEM_NAME).unwrap();
+            let addin_shmem_init_lock: *mut pg_sys::LWLock =
+                &mut (*pg_sys::MainLWLockArray.add(21)).lock;
+            pg_sys::LWLockAcquire(addin_shmem_init_lock, pg_sys::LWLockMode_LW_EXCLUSIVE);
+
+            let mut found = false;
+            let counts = pg_sys::ShmemInitStruct(
+                name.as_ptr(),
+                std::mem::size_of::<Counts>() * FUNCTIONS.len(),
+                &mut found,
+            ) as *mut Counts;
+            if !found {
+                for index in 0..FUNCTIONS.len() {
+                    std::ptr::write(counts.add(index), Counts::default());
+                }
+            }
+            let lock = &mut (*pg_sys::GetNamedLWLockTranche(name.as_ptr())).lock;
+            SHARED = Some((lock, counts));
+
+            pg_sys::LWLockRelease(addin_shmem_init_lock);
+        }
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn xact_callback(event: pg_sys::XactEvent, _arg: *mut std::os::raw::c_void) {
+    match event {
+        pg_sys::XactEvent_XACT_EVENT_COMMIT
+        | pg_sys::XactEvent_XACT_EVENT_ABORT
+        | pg_sys::XactEvent_XACT_EVENT_PARALLEL_COMMIT
+        | pg_sys::XactEvent_XACT_EVENT_PARALLEL_ABORT
+        | pg_sys::XactEvent_XACT_EVENT_PREPARE => flush_function_stats(),
+        _ => {}
+    }
+}
+
+/// Adds this backend's counts to shared memory now, rather than at the end of the current
+/// transaction
+///
+/// Without shared memory, this backend's counts are kept until the transaction ends, and then
+/// forgotten.
+pub fn flush_function_stats() {
+    unsafe {
+        // SAFETY:  only ever accessed from the backend's main thread, and `SHARED` points to
+        // `PENDING.len()` counts
+        if PENDING.iter().all(|pending| pending.calls == 0) {
+            return;
+        }
+        let (lock, counts) = match SHARED {
+            Some(shared) => shared,
+            None => {
+                if !pg_sys::IsTransactionState() {
+                    PENDING.fill(Counts::default());
+                }
+                return;
+            }
+        };
+
+        pg_sys::LWLockAcquire(lock, pg_sys::LWLockMode_LW_EXCLUSIVE);
+        for (index, pending) in PENDING.iter_mut().enumerate() {
+            (*counts.add(index)).add(&std::mem::take(pending));
+        }
+        pg_sys::LWLockRelease(lock);
+    }
+}
+
+/// Returns the statistics of every `#[pg_extern]` function, ordered by their SQL name.
+///
+/// Like [`stats::counters`](crate::stats::counters), they include the counts still pending in
+/// this backend.
+pub fn function_stats() -> Vec<FunctionStats> {
+    unsafe {
+        // SAFETY:  only ever accessed from the backend's main thread, and `SHARED` points to
+        // `FUNCTIONS.len()` counts
+        let mut all = PENDING.clone();
+        if let Some((lock, counts)) = SHARED {
+            pg_sys::LWLockAcquire(lock, pg_sys::LWLockMode_LW_SHARED);
+            for (index, total) in all.iter_mut().enumerate() {
+                total.add(&*counts.add(index));
+            }
+            pg_sys::LWLockRelease(lock);
+        }
+
+        FUNCTIONS
+            .iter()
+            .zip(all)
+            .map(|(slot, counts)| FunctionStats {
+                name: slot.name,
+                path: slot.path,
+                calls: counts.calls,
+                errors: counts.errors,
+                total_time: Duration::from_nanos(counts.total_nanos),
+                max_time: Duration::from_nanos(counts.max_nanos),
+            })
+            .collect()
+    }
+}
+
+/// Sets every function's counts, in shared memory and in this backend, back to zero.
+///
+/// Other backends' pending counts are still added when their transactions end.  Raises an `ERROR`
+/// unless the current user is a superuser.
+pub fn reset_function_stats() {
+    unsafe {
+        if !pg_sys::superuser() {
+            pg_sys::ereport!(
+                ERROR,
+                PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE,
+                "only superusers can reset the function statistics"
+            );
+        }
+
+        PENDING.fill(Counts::default());
+        if let Some((lock, counts)) = SHARED {
+            pg_sys::LWLockAcquire(lock, pg_sys::LWLockMode_LW_EXCLUSIVE);
+            for index in 0..FUNCTIONS.len() {
+                std::ptr::write(counts.add(index), Counts::default());
+            }
+            pg_sys::LWLockRelease(lock);
+        }
+    }
+}
+
+/// Counts a call of the `#[pg_extern]` function named `$name` until the end of the enclosing
+/// block, with the `function-stats` feature.  Not public API.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_function_stats {
+    ($name:expr, $path:expr) => {
+        static __PGX_FUNCTION_SLOT: $crate::function_stats::FunctionSlot =
+            $crate::function_stats::FunctionSlot::new($name, $path);
+        $crate::__pgx_register_on_load!($crate::function_stats::__register_function(
+            &__PGX_FUNCTION_SLOT
+        ));
+        let _function_call = $crate::function_stats::__FunctionCall::start(&__PGX_FUNCTION_SLOT);
+    };
+}
+
+/// Creates the `function_stats()` and `function_stats_reset()` SQL functions, with the
+/// `function-stats` feature.  Not public API.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_function_stats_functions {
+    () => {
+        /// The number of calls, errors, and the total and longest time in milliseconds spent in
+        /// each of this extension's functions
+        #[$crate::pg_extern(volatile, parallel_safe)]
+        fn function_stats() -> $crate::iter::TableIterator<
+            'static,
+            (
+                $crate::name!(name, String),
+                $crate::name!(path, String),
+                $crate::name!(calls, i64),
+                $crate::name!(errors, i64),
+                $crate::name!(total_time, f64),
+                $crate::name!(max_time, f64),
+            ),
+        > {
+            $crate::iter::TableIterator::new(
+                $crate::function_stats::function_stats().into_iter().map(|stats| {
+                    (
+                        stats.name.to_string(),
+                        stats.path.to_string(),
+                        stats.calls as i64,
+                        stats.errors as i64,
+                        stats.total_time.as_secs_f64() * 1000.0,
+                        stats.max_time.as_secs_f64() * 1000.0,
+                    )
+                }),
+            )
+        }
+
+        /// Sets the counts `function_stats()` returns back to zero
+        #[$crate::pg_extern(volatile)]
+        fn function_stats_reset() {
+            $crate::function_stats::reset_function_stats()
+        }
+    };
+}
@@ -0,0 +1,278 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Support for indexing a type with [GIN](https://www.postgresql.org/docs/current/gin.html).
+//!
+//! A GIN index stores the *keys* of each value, such as the words of a document or the elements
+//! of an array, and finds the values which have the keys a query needs.  Implement
+//! [`GinIndexable`] for a type to say what its keys are, and annotate the `impl` with
+//! [`#[pg_gin_opclass]`](crate::pg_gin_opclass), naming the operators the index can answer by
+//! their strategy number.  It creates the support functions, which Postgres calls with `internal`
+//! arguments that pgx takes care of, and a default `CREATE OPERATOR CLASS ... USING gin` for the
+//! type.  A class which also matches keys partially, such as by their prefix, implements
+//! [`GinPartialMatch`] too, and says so with `#[pg_gin_opclass(.., partial_match)]`.
+//!
+//! ```rust,ignore
+//! use pgx::gin::{GinConsistent, GinIndexable, GinQuery};
+//! use pgx::prelude::*;
+//!
+//! #[pg_operator(immutable, parallel_safe)]
+//! #[opname(@>)]
+//! fn tags_contain(tags: Tags, tag: &str) -> bool {
+//!     tags.0.iter().any(|t| t == tag)
+//! }
+//!
+//! #[pg_gin_opclass(1 = tags_contain)]
+//! impl GinIndexable for Tags {
+//!     type Key = String;
+//!     type Query = String;
+//!
+//!     fn extract_value(self) -> Vec<String> {
+//!         self.0
+//!     }
+//!
+//!     fn extract_query(tag: String, _strategy: u16) -> GinQuery<String> {
+//!         GinQuery::new(vec![tag])
+//!     }
+//!
+//!     fn consistent(_strategy: u16, check: &[bool]) -> GinConsistent {
+//!         check[0].into()
+//!     }
+//! }
+//! ```
+//!
+//! Then `CREATE INDEX ON posts USING gin (tags)` indexes `WHERE tags @> 'rust'`.
+use crate::{pg_getarg, pg_getarg_pointer, pg_sys, FromDatum, IntoDatum};
+use std::cmp::Ordering;
+
+/// A type whose values can be indexed by the keys they contain, with a GIN index.
+///
+/// Use it with [`#[pg_gin_opclass]`](crate::pg_gin_opclass).  The [module docs](crate::gin)
+/// have an example.
+pub trait GinIndexable: FromDatum {
+    /// The type of the keys the index stores, which is its `STORAGE` type
+    ///
+    /// Keys are kept in the index in their [`Ord`] order, which has to agree with their
+    /// [`GinPartialMatch::compare_partial`], if there is one.  A variable-length key, such as `String` or `Vec<u8>`,
+    /// is stored as the Postgres value it converts to.
+    type Key: IntoDatum + FromDatum + Ord;
+
+    /// The type of the right-hand side of the class' operators, which may differ from `Self`
+    type Query: FromDatum;
+
+    /// The keys of a value, in any order and with any duplicates.  A value with no keys is only
+    /// found by queries which ask for [`GinSearchMode::IncludeEmpty`] or more
+    fn extract_value(self) -> Vec<Self::Key>;
+
+    /// The keys a query on the operator with the number `strategy` looks for, and how
+    fn extract_query(query: Self::Query, strategy: u16) -> GinQuery<Self::Key>;
+
+    /// Whether a value might satisfy a query on the operator with the number `strategy`, from
+    /// which of the keys the query looked for it has
+    ///
+    /// `check` has an element for each key, in the order
+    /// [`GinIndexable::extract_query`] returned them.
+    fn consistent(strategy: u16, check: &[bool]) -> GinConsistent;
+}
+
+/// A [`GinIndexable`] type whose queries can match keys partially, with [`GinQuery::partial`]
+///
+/// Its operator class needs the `comparePartial` support function, which
+/// `#[pg_gin_opclass(.., partial_match)]` creates from this.
+pub trait GinPartialMatch: GinIndexable {
+    /// How an indexed `key` compares to a `partial` key of a query, on the operator with the
+    /// number `strategy`
+    ///
+    /// The index scans the keys from `partial` onward, in their [`Ord`] order: `Equal` means
+    /// `key` matches, `Less` means it doesn't but a later key might, and `Greater` ends the scan.
+    fn compare_partial(partial: &Self::Key, key: &Self::Key, strategy: u16) -> Ordering;
+}
+
+/// What [`GinIndexable::extract_query`] looks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GinQuery<K> {
+    pub keys: Vec<K>,
+    /// Whether every key is to be matched partially, through
+    /// [`GinPartialMatch::compare_partial`], rather than exactly.  Postgres raises an ERROR for
+    /// a partial query of a class without `partial_match`
+    pub partial: bool,
+    pub mode: GinSearchMode,
+}
+
+impl<K> GinQuery<K> {
+    /// A query for values with the `keys`, in the [`GinSearchMode::Default`] mode
+    pub fn new(keys: Vec<K>) -> Self {
+        Self { keys, partial: false, mode: GinSearchMode::Default }
+    }
+
+    /// A query the index can't narrow down, which checks every value that isn't `NULL`, as
+    /// for a query with too few keys to look for
+    pub fn all() -> Self {
+        Self { keys: Vec::new(), partial: false, mode: GinSearchMode::All }
+    }
+
+    /// The same query, with its keys matched partially
+    pub fn partial(self) -> Self {
+        Self { partial: true, ..self }
+    }
+
+    /// The same query, in the `mode`
+    pub fn mode(self, mode: GinSearchMode) -> Self {
+        Self { mode, ..self }
+    }
+}
+
+/// Which values a GIN index scan passes to [`GinIndexable::consistent`], besides those with one of
+/// the query's keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GinSearchMode {
+    /// Only values with at least one of the keys.  A query without keys finds nothing
+    Default,
+    /// Also values with no keys at all
+    IncludeEmpty,
+    /// Every value which isn't `NULL`
+    All,
+    /// Every value, including `NULL`s
+    Everything,
+}
+
+impl GinSearchMode {
+    fn to_pg(self) -> i32 {
+        (match self {
+            GinSearchMode::Default => pg_sys::GIN_SEARCH_MODE_DEFAULT,
+            GinSearchMode::IncludeEmpty => pg_sys::GIN_SEARCH_MODE_INCLUDE_EMPTY,
+            GinSearchMode::All => pg_sys::GIN_SEARCH_MODE_ALL,
+            GinSearchMode::Everything => pg_sys::GIN_SEARCH_MODE_EVERYTHING,
+        }) as i32
+    }
+}
+
+/// Whether a value satisfies a query, as [`GinIndexable::consistent`] can tell from its keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GinConsistent {
+    /// It does
+    True,
+    /// It might, and the operator is called on the value to find out
+    Maybe,
+    /// It doesn't
+    False,
+}
+
+impl From<bool> for GinConsistent {
+    fn from(value: bool) -> Self {
+        if value {
+            GinConsistent::True
+        } else {
+            GinConsistent::False
+        }
+    }
+}
+
+/// The keys, as a `palloc`ed array of datums, with their count in `nkeys` and, when there are
+/// `NULL` keys, which ones are in `null_flags`
+unsafe fn keys_into_datums<K: IntoDatum>(
+    keys: Vec<K>,
+    nkeys: *mut i32,
+    null_flags: *mut *mut bool,
+) -> pg_sys::Datum {
+    let datums = pg_sys::palloc0(std::mem::size_of::<pg_sys::Datum>() * keys.len().max(1))
+        as *mut pg_sys::Datum;
+    let mut nulls: *mut bool = std::ptr::null_mut();
+    *nkeys = keys.len() as i32;
+    for (i, key) in keys.into_iter().enumerate() {
+        match key.into_datum() {
+            Some(datum) => *datums.add(i) = datum,
+            None => {
+                if nulls.is_null() {
+                    nulls = pg_sys::palloc0(std::mem::size_of::<bool>() * (*nkeys as usize))
+                        as *mut bool;
+                }
+                *nulls.add(i) = true;
+            }
+        }
+    }
+    if !null_flags.is_null() {
+        *null_flags = nulls;
+    }
+    pg_sys::Datum::from(datums)
+}
+
+/// GIN support function 1, `compare(key, key) returns int4`.  Not public API.
+#[doc(hidden)]
+pub unsafe fn __gin_compare<T: GinIndexable>(fcinfo: pg_sys::FunctionCallInfo) -> pg_sys::Datum {
+    let a = pg_getarg::<T::Key>(fcinfo, 0).expect("GIN keys can't be NULL");
+    let b = pg_getarg::<T::Key>(fcinfo, 1).expect("GIN keys can't be NULL");
+    pg_sys::Datum::from(a.cmp(&b) as i32)
+}
+
+/// GIN support function 2, `extractValue(value, nkeys internal, nullFlags internal) returns
+/// internal`.  Not public API.
+#[doc(hidden)]
+pub unsafe fn __gin_extract_value<T: GinIndexable>(
+    fcinfo: pg_sys::FunctionCallInfo,
+) -> pg_sys::Datum {
+    let value = pg_getarg::<T>(fcinfo, 0).expect("GIN doesn't extract the keys of NULL");
+    let nkeys = pg_getarg_pointer::<i32>(fcinfo, 1).unwrap();
+    let null_flags = pg_getarg_pointer::<*mut bool>(fcinfo, 2).unwrap_or(std::ptr::null_mut());
+    keys_into_datums(value.extract_value(), nkeys, null_flags)
+}
+
+/// GIN support function 3, `extractQuery(query, nkeys internal, strategy int2, pmatch internal,
+/// extra_data internal, nullFlags internal, searchMode internal) returns internal`.  Not public
+/// API.
+#[doc(hidden)]
+pub unsafe fn __gin_extract_query<T: GinIndexable>(
+    fcinfo: pg_sys::FunctionCallInfo,
+) -> pg_sys::Datum {
+    let query = pg_getarg::<T::Query>(fcinfo, 0).expect("GIN doesn't search for NULL");
+    let nkeys = pg_getarg_pointer::<i32>(fcinfo, 1).unwrap();
+    let strategy = pg_getarg::<i16>(fcinfo, 2).unwrap() as u16;
+    let partial_match = pg_getarg_pointer::<*mut bool>(fcinfo, 3).unwrap_or(std::ptr::null_mut());
+    let null_flags = pg_getarg_pointer::<*mut bool>(fcinfo, 5).unwrap_or(std::ptr::null_mut());
+    let search_mode = pg_getarg_pointer::<i32>(fcinfo, 6).unwrap_or(std::ptr::null_mut());
+
+    let GinQuery { keys, partial, mode } = T::extract_query(query, strategy);
+    if partial && !keys.is_empty() && !partial_match.is_null() {
+        let flags = pg_sys::palloc(std::mem::size_of::<bool>() * keys.len()) as *mut bool;
+        std::ptr::write_bytes(flags, true as u8, keys.len());
+        *partial_match = flags;
+    }
+    if !search_mode.is_null() {
+        *search_mode = mode.to_pg();
+    }
+    keys_into_datums(keys, nkeys, null_flags)
+}
+
+/// GIN support function 4, `consistent(check internal, strategy int2, query, nkeys int4,
+/// extra_data internal, recheck internal, queryKeys internal, nullFlags internal) returns bool`.
+/// Not public API.
+#[doc(hidden)]
+pub unsafe fn __gin_consistent<T: GinIndexable>(fcinfo: pg_sys::FunctionCallInfo) -> pg_sys::Datum {
+    let check = pg_getarg_pointer::<bool>(fcinfo, 0).unwrap();
+    let strategy = pg_getarg::<i16>(fcinfo, 1).unwrap() as u16;
+    let nkeys = pg_getarg::<i32>(fcinfo, 3).unwrap() as usize;
+    let recheck = pg_getarg_pointer::<bool>(fcinfo, 5).unwrap();
+
+    let check = if nkeys == 0 { &[][..] } else { std::slice::from_raw_parts(check, nkeys) };
+    let consistent = T::consistent(strategy, check);
+    *recheck = consistent == GinConsistent::Maybe;
+    pg_sys::Datum::from(consistent != GinConsistent::False)
+}
+
+/// GIN support function 5, `comparePartial(partial_key, key, strategy int2, extra_data
+/// internal) returns int4`.  Not public API.
+#[doc(hidden)]
+pub unsafe fn __gin_compare_partial<T: GinPartialMatch>(
+    fcinfo: pg_sys::FunctionCallInfo,
+) -> pg_sys::Datum {
+    let partial = pg_getarg::<T::Key>(fcinfo, 0).expect("GIN keys can't be NULL");
+    let key = pg_getarg::<T::Key>(fcinfo, 1).expect("GIN keys can't be NULL");
+    let strategy = pg_getarg::<i16>(fcinfo, 2).unwrap() as u16;
+    pg_sys::Datum::from(T::compare_partial(&partial, &key, strategy) as i32)
+}
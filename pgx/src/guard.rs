@@ -0,0 +1,113 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Guard Rust callbacks that are handed to Postgres C code, such as comparators given to
+//! `qsort_arg()` or the `func` of a `MemoryContextCallback`.
+//!
+//! A Rust panic must never unwind through C stack frames.  Functions declared with `#[pg_guard]`
+//! or `#[pg_extern]` already convert panics into Postgres `ERROR`s, but a callback that's only
+//! needed in one place is often written inline, where an attribute can't be used.
+//! [`pg_guarded_closure!`](crate::pg_guarded_closure) turns closure syntax into a guarded
+//! `unsafe extern "C" fn` for those cases.
+//!
+//! # Postgres `ERROR`s and Rust destructors
+//!
+//! Postgres raises an `ERROR` by `siglongjmp`ing to wherever it last called `sigsetjmp`, which
+//! would skip the destructors of every Rust frame in between.  pgx keeps that from happening:
+//!
+//! - Every `pg_sys` function is called through
+//!   [`pg_guard_ffi_boundary()`](crate::pg_sys::ffi::pg_guard_ffi_boundary), which catches the
+//!   `ERROR` and turns it into a panic.  The panic unwinds the stack, dropping each frame's locals
+//!   in the usual order, until it reaches the guarded function Postgres called, which rethrows the
+//!   `ERROR`, or a [`PgTryBuilder`](crate::PgTryBuilder) that catches it.
+//! - A function pointer isn't guarded just because it's handed to Rust.  pgx calls the previous
+//!   hooks it chains to, and the `#[pg_extern]` wrappers given to
+//!   [`direct_pg_extern_function_call()`](crate::direct_pg_extern_function_call), through
+//!   `pg_guard_ffi_boundary()`, and anything else calling a Postgres function pointer should too.
+//! - Destructors run before Postgres aborts the (sub)transaction, so the memory contexts and SPI
+//!   connection they might clean up are still there.  `pfree()` and `MemoryContextDelete()` are
+//!   fine, and [`PgMemoryContexts::switch_to()`](crate::PgMemoryContexts::switch_to) switches back
+//!   to the previous context on its way out.  A `std::sync::Mutex` locked across the `ERROR` is
+//!   poisoned, as it would be by any panic.
+//! - A destructor mustn't raise another `ERROR` while unwinding, as a panic during a panic aborts
+//!   the backend, which takes the whole cluster through crash recovery.  Whatever might, such as
+//!   closing a portal that's still running, should be skipped when [`std::thread::panicking()`],
+//!   and left for Postgres' own cleanup.
+//! - A `FATAL` or `PANIC`, or `proc_exit()`, ends the backend without unwinding, so destructors
+//!   don't run, and shouldn't be relied on for anything that outlives the backend.
+
+/// Turns a non-capturing closure into an `unsafe extern "C" fn` that's safe to hand to Postgres.
+///
+/// The resulting function behaves like one declared with `#[pg_guard]`: a Rust panic within the
+/// closure is raised as a Postgres `ERROR`, and a Postgres `ERROR` raised by something the closure
+/// calls is rethrown, once the closure's stack frame is unwound, instead of `longjmp`ing over it.
+///
+/// Every argument, and the return type if there is one, must be written out, and the closure can't
+/// capture anything from its environment.  State is passed the way the C API expects it, usually
+/// through a `void *arg`.  As with `#[pg_guard]`, the return type must be `Copy`, which is true of
+/// anything a C function can return.
+///
+/// If the closure's return type is written as `Result<T, E>`, the function returns `T`, and an
+/// `Err` is raised as an `ERROR`, using the SQLSTATE of `E` if it implements
+/// [`ErrorReportable`](crate::ErrorReportable), just like the `Err` of a `#[pg_extern]` function.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use std::os::raw::{c_int, c_void};
+///
+/// let mut values = vec![3_i32, 1, 2];
+/// let cmp = pgx::pg_guarded_closure!(|a: *const c_void, b: *const c_void| -> c_int {
+///     let (a, b) = unsafe { (*(a as *const i32), *(b as *const i32)) };
+///     a.cmp(&b) as c_int
+/// });
+/// unsafe {
+///     pg_sys::pg_qsort(
+///         values.as_mut_ptr().cast(),
+///         values.len(),
+///         std::mem::size_of::<i32>(),
+///         Some(cmp),
+///     );
+/// }
+/// ```
+#[macro_export]
+macro_rules! pg_guarded_closure {
+    (|$($arg:ident : $ty:ty),* $(,)?| -> Result<$ok:ty, $err:ty> $body:block) => {{
+        unsafe extern "C" fn __pgx_guarded_closure($($arg: $ty),*) -> $ok {
+            fn __pgx_guarded_closure_inner($($arg: $ty),*) -> ::core::result::Result<$ok, $err> $body
+
+            $crate::pg_sys::panic::pgx_extern_c_guard(move || {
+                #[allow(unused_imports)]
+                use $crate::error_report::__private::{ViaDisplay as _, ViaErrorReportable as _};
+                use $crate::pg_sys::panic::ErrorReportable as _;
+
+                let result = __pgx_guarded_closure_inner($($arg),*);
+                (&$crate::error_report::__private::ResultWrapper::new(result))
+                    .into_report_result()
+                    .report()
+            })
+        }
+        __pgx_guarded_closure as unsafe extern "C" fn($($ty),*) -> $ok
+    }};
+    (|$($arg:ident : $ty:ty),* $(,)?| -> $ret:ty $body:block) => {{
+        unsafe extern "C" fn __pgx_guarded_closure($($arg: $ty),*) -> $ret {
+            fn __pgx_guarded_closure_inner($($arg: $ty),*) -> $ret $body
+
+            $crate::pg_sys::panic::pgx_extern_c_guard(move || __pgx_guarded_closure_inner($($arg),*))
+        }
+        __pgx_guarded_closure as unsafe extern "C" fn($($ty),*) -> $ret
+    }};
+    (|$($arg:ident : $ty:ty),* $(,)?| $body:block) => {{
+        unsafe extern "C" fn __pgx_guarded_closure($($arg: $ty),*) {
+            fn __pgx_guarded_closure_inner($($arg: $ty),*) $body
+
+            $crate::pg_sys::panic::pgx_extern_c_guard(move || __pgx_guarded_closure_inner($($arg),*))
+        }
+        __pgx_guarded_closure as unsafe extern "C" fn($($ty),*)
+    }};
+}
@@ -1,15 +1,16 @@
 //! Provides a safe interface to Postgres `HeapTuple` objects.
 //!
 //! [`PgHeapTuple`]s also describe composite types as defined by [`pgx::composite_type!()`][crate::composite_type].
-use crate::datum::lookup_type_name;
+use crate::datum::{lookup_type_name, DebugDatum};
 use crate::pg_sys::{Datum, Oid};
 use crate::{
     heap_getattr_raw, pg_sys, AllocatedByPostgres, AllocatedByRust, FromDatum, IntoDatum, PgBox,
-    PgMemoryContexts, PgTupleDesc, TriggerTuple, TryFromDatumError, WhoAllocated,
+    PgMemoryContexts, PgSqlErrorCode, PgTupleDesc, TriggerTuple, TryFromDatumError, WhoAllocated,
 };
 use pgx_sql_entity_graph::metadata::{
     ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
 };
+use std::cmp::Ordering;
 use std::num::NonZeroUsize;
 
 /// Describes errors that can occur when trying to create a new [PgHeapTuple].
@@ -22,6 +23,17 @@ pub enum PgHeapTupleError {
     NoSuchType(String),
 }
 
+impl crate::ErrorReportable for PgHeapTupleError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            PgHeapTupleError::IncorrectAttributeCount(..) => {
+                PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH
+            }
+            PgHeapTupleError::NoSuchType(_) => PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT,
+        }
+    }
+}
+
 /// A [`PgHeapTuple`] is a lightweight wrapper around Postgres' [`pg_sys::HeapTuple`] object and a [`PgTupleDesc`].
 ///
 /// In order to access the attributes within a [`pg_sys::HeapTuple`], the [`PgTupleDesc`] is required
@@ -37,6 +49,9 @@ pub enum PgHeapTupleError {
 pub struct PgHeapTuple<'a, AllocatedBy: WhoAllocated> {
     tuple: PgBox<pg_sys::HeapTupleData, AllocatedBy>,
     tupdesc: PgTupleDesc<'a>,
+    /// The composite Datum the tuple was read from, until it's modified, which is returned as-is
+    /// rather than copied
+    composite: Option<pg_sys::Datum>,
 }
 
 impl<'a> FromDatum for PgHeapTuple<'a, AllocatedByRust> {
@@ -67,8 +82,8 @@ impl<'a> FromDatum for PgHeapTuple<'a, AllocatedByRust> {
             memory_context.switch_to(|_| {
                 // we're copying the composite datum into this memory context
                 let tuple = PgHeapTuple::from_composite_datum(composite);
-                let datum = tuple.into_composite_datum();
-                Some(PgHeapTuple::from_composite_datum(datum.unwrap()))
+                let (_, datum) = tuple.as_composite_datum();
+                Some(PgHeapTuple::from_composite_datum(datum))
             })
         }
     }
@@ -85,7 +100,7 @@ impl<'a> PgHeapTuple<'a, AllocatedByPostgres> {
     /// nor can we guaratee that the provided [PgTupleDesc] properly describes the structure of
     /// the heap tuple.
     pub unsafe fn from_heap_tuple(tupdesc: PgTupleDesc<'a>, heap_tuple: pg_sys::HeapTuple) -> Self {
-        Self { tuple: PgBox::from_pg(heap_tuple), tupdesc }
+        Self { tuple: PgBox::from_pg(heap_tuple), tupdesc, composite: None }
     }
 
     /// Creates a new [PgHeapTuple] from one of the two (`Current` or `New`) trigger tuples.  The returned
@@ -122,6 +137,7 @@ impl<'a> PgHeapTuple<'a, AllocatedByPostgres> {
         PgHeapTuple {
             tuple: unsafe { PgBox::<pg_sys::HeapTupleData, AllocatedByRust>::from_rust(copy) },
             tupdesc: self.tupdesc,
+            composite: None,
         }
     }
 }
@@ -167,6 +183,7 @@ impl<'a> PgHeapTuple<'a, AllocatedByRust> {
             Ok(PgHeapTuple {
                 tuple: PgBox::<pg_sys::HeapTupleData, AllocatedByRust>::from_rust(heap_tuple),
                 tupdesc: tuple_desc,
+                composite: None,
             })
         }
     }
@@ -198,10 +215,23 @@ impl<'a> PgHeapTuple<'a, AllocatedByRust> {
             Ok(Self {
                 tuple: PgBox::<pg_sys::HeapTupleData, AllocatedByRust>::from_rust(formed_tuple),
                 tupdesc,
+                composite: None,
             })
         }
     }
 
+    /// Wraps `heap_tuple`, a copy that nothing else refers to, so it's ours to modify
+    pub(crate) unsafe fn from_copied_heap_tuple(
+        tupdesc: PgTupleDesc<'a>,
+        heap_tuple: pg_sys::HeapTuple,
+    ) -> Self {
+        Self {
+            tuple: PgBox::<pg_sys::HeapTupleData, AllocatedByRust>::from_rust(heap_tuple),
+            tupdesc,
+            composite: None,
+        }
+    }
+
     /// Creates a new [PgHeapTuple] from an opaque Datum that should be a "composite" type.
     ///
     /// The Datum should be a pointer to a [pg_sys::HeapTupleHeader].  Typically, this will be used
@@ -211,6 +241,10 @@ impl<'a> PgHeapTuple<'a, AllocatedByRust> {
     /// CREATE TYPE my_composite AS (name text, age i32);
     /// ```
     ///
+    /// Until it's modified, [`PgHeapTuple::into_composite_datum`] returns the Datum it was made from,
+    /// rather than a copy, so a `#[pg_extern]` function that takes a composite argument by value
+    /// can return it without copying it.
+    ///
     /// ## Safety
     ///
     /// This function is unsafe as we cannot guarantee that the provided Datum is a valid [pg_sys::HeapTupleHeader]
@@ -227,7 +261,11 @@ impl<'a> PgHeapTuple<'a, AllocatedByRust> {
         data.t_len = crate::heap_tuple_header_get_datum_length(htup_header) as u32;
         data.t_data = htup_header;
 
-        Self { tuple: data, tupdesc: PgTupleDesc::from_pg(tupdesc) }
+        Self {
+            tuple: data,
+            tupdesc: PgTupleDesc::from_pg(tupdesc),
+            composite: Some(pg_sys::Datum::from(htup_header)),
+        }
     }
 
     /// Given the name for an attribute in this [PgHeapTuple], change its value.
@@ -263,50 +301,130 @@ impl<'a> PgHeapTuple<'a, AllocatedByRust> {
         attno: NonZeroUsize,
         value: T,
     ) -> Result<(), TryFromDatumError> {
-        unsafe {
-            match self.get_attribute_by_index(attno) {
-                None => return Err(TryFromDatumError::NoSuchAttributeNumber(attno)),
-                Some(att) => {
-                    let type_oid = T::type_oid();
-                    let composite_type_oid = value.composite_type_oid();
-                    let is_compatible_composite_types =
-                        type_oid == pg_sys::RECORDOID && composite_type_oid == Some(att.atttypid);
-                    if !is_compatible_composite_types && !T::is_compatible_with(att.atttypid) {
-                        return Err(TryFromDatumError::IncompatibleTypes {
-                            rust_type: std::any::type_name::<T>(),
-                            rust_oid: att.atttypid,
-                            datum_type: lookup_type_name(type_oid),
-                            datum_oid: type_oid,
-                        });
-                    }
-                }
-            }
+        self.modify(|mutator| mutator.set_by_index(attno, value))
+    }
 
-            let mut datums =
-                (0..self.tupdesc.len()).map(|i| pg_sys::Datum::from(i)).collect::<Vec<_>>();
-            let mut nulls = (0..self.tupdesc.len()).map(|_| false).collect::<Vec<_>>();
-            let mut do_replace = (0..self.tupdesc.len()).map(|_| false).collect::<Vec<_>>();
+    /// Change any number of this [PgHeapTuple]'s attributes, with the [PgHeapTupleMutator] given to
+    /// `f`, in a single copy of the tuple.
+    ///
+    /// Each [`PgHeapTuple::set_by_name`] copies the whole tuple, so changing several attributes of a
+    /// wide tuple is better done all at once.  Nothing is changed if `f` returns an error, and the
+    /// tuple isn't copied at all if `f` doesn't change anything.
+    ///
+    /// ```rust,no_run
+    /// use pgx::prelude::*;
+    ///
+    /// Spi::run("CREATE TYPE dog AS (name text, age int, scritches int);");
+    /// let mut dog = PgHeapTuple::new_composite_type("dog").unwrap();
+    ///
+    /// dog.modify(|mutator| {
+    ///     mutator.set("name", "Brandy")?;
+    ///     mutator.set("age", 4)?;
+    ///     mutator.set("scritches", 42)
+    /// })
+    /// .unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// Whatever `f` returns, such as the errors of [`PgHeapTupleMutator::set`].
+    pub fn modify<R>(
+        &mut self,
+        f: impl FnOnce(&mut PgHeapTupleMutator<'_, 'a>) -> Result<R, TryFromDatumError>,
+    ) -> Result<R, TryFromDatumError> {
+        let natts = self.tupdesc.len();
+        let mut mutator = PgHeapTupleMutator {
+            tuple: self,
+            datums: vec![pg_sys::Datum::from(0); natts],
+            nulls: vec![false; natts],
+            do_replace: vec![false; natts],
+        };
+        let result = f(&mut mutator)?;
 
-            let datum = value.into_datum();
-            let attno = attno.get() - 1;
+        let PgHeapTupleMutator { mut datums, mut nulls, mut do_replace, .. } = mutator;
+        if do_replace.contains(&true) {
+            unsafe {
+                let new_tuple = PgBox::<pg_sys::HeapTupleData, AllocatedByRust>::from_rust(
+                    pg_sys::heap_modify_tuple(
+                        self.tuple.as_ptr(),
+                        self.tupdesc.as_ptr(),
+                        datums.as_mut_ptr(),
+                        nulls.as_mut_ptr(),
+                        do_replace.as_mut_ptr(),
+                    ),
+                );
+                let old_tuple = std::mem::replace(&mut self.tuple, new_tuple);
+                drop(old_tuple);
+            }
+            self.composite = None;
+        }
+        Ok(result)
+    }
+}
 
-            nulls[attno] = datum.is_none();
-            datums[attno] = datum.unwrap_or(0.into());
-            do_replace[attno] = true;
+/// The changes to a [`PgHeapTuple`]'s attributes that [`PgHeapTuple::modify`] makes, all at once
+pub struct PgHeapTupleMutator<'t, 'a> {
+    tuple: &'t PgHeapTuple<'a, AllocatedByRust>,
+    datums: Vec<pg_sys::Datum>,
+    nulls: Vec<bool>,
+    do_replace: Vec<bool>,
+}
 
-            let new_tuple = PgBox::<pg_sys::HeapTupleData, AllocatedByRust>::from_rust(
-                pg_sys::heap_modify_tuple(
-                    self.tuple.as_ptr(),
-                    self.tupdesc.as_ptr(),
-                    datums.as_mut_ptr(),
-                    nulls.as_mut_ptr(),
-                    do_replace.as_mut_ptr(),
-                ),
-            );
-            let old_tuple = std::mem::replace(&mut self.tuple, new_tuple);
-            drop(old_tuple);
-            Ok(())
+impl<'t, 'a> PgHeapTupleMutator<'t, 'a> {
+    /// Given the name for an attribute, change its value.
+    ///
+    /// Attribute names are case sensitive.
+    ///
+    /// ## Errors
+    ///
+    /// - return [TryFromDatumError::NoSuchAttributeName] if the attribute does not exist
+    /// - return [TryFromDatumError::IncompatibleTypes] if the Rust type of the `value` is not
+    /// compatible with the attribute's Postgres type
+    pub fn set<T: IntoDatum>(&mut self, attname: &str, value: T) -> Result<(), TryFromDatumError> {
+        match self.tuple.get_attribute_by_name(attname) {
+            None => Err(TryFromDatumError::NoSuchAttributeName(attname.to_string())),
+            Some((attnum, _)) => self.set_by_index(attnum, value),
+        }
+    }
+
+    /// Given the index for an attribute, change its value.
+    ///
+    /// Attribute numbers start at 1, not 0.
+    ///
+    /// ## Errors
+    /// - return [TryFromDatumError::NoSuchAttributeNumber] if the attribute does not exist
+    /// - return [TryFromDatumError::IncompatibleTypes] if the Rust type of the `value` is not
+    /// compatible with the attribute's Postgres type
+    pub fn set_by_index<T: IntoDatum>(
+        &mut self,
+        attno: NonZeroUsize,
+        value: T,
+    ) -> Result<(), TryFromDatumError> {
+        match self.tuple.get_attribute_by_index(attno) {
+            None => return Err(TryFromDatumError::NoSuchAttributeNumber(attno)),
+            Some(att) => {
+                let type_oid = T::type_oid();
+                let composite_type_oid = value.composite_type_oid();
+                let is_compatible_composite_types =
+                    type_oid == pg_sys::RECORDOID && composite_type_oid == Some(att.atttypid);
+                if !is_compatible_composite_types && !T::is_compatible_with(att.atttypid) {
+                    return Err(TryFromDatumError::IncompatibleTypes {
+                        rust_type: std::any::type_name::<T>(),
+                        rust_oid: att.atttypid,
+                        datum_type: lookup_type_name(type_oid),
+                        datum_oid: type_oid,
+                    });
+                }
+            }
         }
+
+        let datum = value.into_datum();
+        let attno = attno.get() - 1;
+
+        self.nulls[attno] = datum.is_none();
+        self.datums[attno] = datum.unwrap_or(0.into());
+        self.do_replace[attno] = true;
+        Ok(())
     }
 }
 
@@ -339,12 +457,47 @@ impl<'a, AllocatedBy: WhoAllocated> IntoDatum for PgHeapTuple<'a, AllocatedBy> {
 impl<'a, AllocatedBy: WhoAllocated> PgHeapTuple<'a, AllocatedBy> {
     /// Consume this [`PgHeapTuple`] and return a composite Datum representation, containing the tuple
     /// data and the corresponding tuple descriptor information.
+    ///
+    /// A tuple which was made from a composite Datum, and hasn't been modified since, returns that
+    /// same Datum.  Any other is copied into the current `MemoryContext`.
     pub fn into_composite_datum(self) -> Option<pg_sys::Datum> {
+        if let Some(composite) = self.composite {
+            return Some(composite);
+        }
         unsafe {
             Some(pg_sys::heap_copy_tuple_as_datum(self.tuple.as_ptr(), self.tupdesc.as_ptr()))
         }
     }
 
+    /// The underlying [`pg_sys::HeapTupleData`], which is still owned by this [`PgHeapTuple`]
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *mut pg_sys::HeapTupleData {
+        self.tuple.as_ptr()
+    }
+
+    /// The value of the attribute named `attname`, or `None` if it's NULL or there's no such
+    /// attribute
+    pub(crate) fn get_datum_by_name(&self, attname: &str) -> Option<pg_sys::Datum> {
+        let att = self.tupdesc.iter().find(|att| att.name() == attname)?;
+        // SAFETY:  the tuple is described by our descriptor, whose attribute this is
+        unsafe {
+            heap_getattr_raw(
+                self.tuple.as_ptr(),
+                NonZeroUsize::new(att.attnum as usize)?,
+                self.tupdesc.as_ptr(),
+            )
+        }
+    }
+
+    /// A composite Datum copy of this tuple, in the current memory context, and its type
+    pub(crate) fn as_composite_datum(&self) -> (pg_sys::Oid, pg_sys::Datum) {
+        unsafe {
+            let datum =
+                pg_sys::heap_copy_tuple_as_datum(self.tuple.as_ptr(), self.tupdesc.as_ptr());
+            (self.tupdesc.tdtypeid, datum)
+        }
+    }
+
     /// Consume this [`PgHeapTuple`] and return a Datum representation appropriate for returning from
     /// a trigger function
     pub fn into_trigger_datum(self) -> Option<pg_sys::Datum> {
@@ -359,6 +512,49 @@ impl<'a, AllocatedBy: WhoAllocated> PgHeapTuple<'a, AllocatedBy> {
         self.tuple.into_pg()
     }
 
+    /// Compares this composite value with `other` the way Postgres compares two values of a
+    /// composite type, with `btrecordcmp()`.
+    ///
+    /// Attributes are compared in order, each with the default btree operator class of its type.
+    /// Two `NULL` attributes are equal, and a `NULL` attribute is greater than a non-`NULL` one.
+    ///
+    /// Raises an `ERROR` if the two don't have the same number and types of attributes, or if an
+    /// attribute's type can't be ordered.
+    pub fn record_cmp<Other: WhoAllocated>(&self, other: &PgHeapTuple<'_, Other>) -> Ordering {
+        unsafe {
+            // the typcache entry for `record` itself, never that of the specific composite type, as
+            // that might have an operator class (such as from `pg_composite_ops!()`) that calls us
+            let entry =
+                pg_sys::lookup_type_cache(pg_sys::RECORDOID, pg_sys::TYPECACHE_CMP_PROC_FINFO as _);
+            let result = pg_sys::FunctionCall2Coll(
+                &mut (*entry).cmp_proc_finfo,
+                pg_sys::InvalidOid,
+                self.as_composite_datum().1,
+                other.as_composite_datum().1,
+            );
+            (result.value() as i32).cmp(&0)
+        }
+    }
+
+    /// Is this composite value equal to `other`, the way Postgres' `record_eq()` decides?
+    ///
+    /// Attributes are compared in order, each with the default equality operator of its type, and
+    /// two `NULL` attributes are equal.  Raises an `ERROR` under the same conditions as
+    /// [`PgHeapTuple::record_cmp`].
+    pub fn record_eq<Other: WhoAllocated>(&self, other: &PgHeapTuple<'_, Other>) -> bool {
+        unsafe {
+            let entry =
+                pg_sys::lookup_type_cache(pg_sys::RECORDOID, pg_sys::TYPECACHE_EQ_OPR_FINFO as _);
+            let result = pg_sys::FunctionCall2Coll(
+                &mut (*entry).eq_opr_finfo,
+                pg_sys::InvalidOid,
+                self.as_composite_datum().1,
+                other.as_composite_datum().1,
+            );
+            bool::from_datum(result, false).unwrap_or(false)
+        }
+    }
+
     /// Returns the number of attributes in this [`PgHeapTuple`].
     #[inline]
     pub fn len(&self) -> usize {
@@ -463,6 +659,27 @@ impl<'a, AllocatedBy: WhoAllocated> PgHeapTuple<'a, AllocatedBy> {
     }
 }
 
+impl<'a, AllocatedBy: WhoAllocated> std::fmt::Debug for PgHeapTuple<'a, AllocatedBy> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("PgHeapTuple");
+        for (i, att) in self.tupdesc.iter().enumerate() {
+            if att.attisdropped {
+                continue;
+            }
+            let datum = unsafe {
+                // SAFETY:  we own the tuple and its descriptor, and i is within its attributes
+                heap_getattr_raw(
+                    self.tuple.as_ptr(),
+                    NonZeroUsize::new(i + 1).unwrap(),
+                    self.tupdesc.as_ptr(),
+                )
+            };
+            debug.field(att.name(), &DebugDatum(att.type_oid().value(), datum));
+        }
+        debug.finish()
+    }
+}
+
 /** Composite type support
 
 Support for working with types defined by SQL statements like:
@@ -534,7 +751,7 @@ CREATE FUNCTION "scritch"(
         "maybe_dog" Dog /* core::option::Option<pgx::heap_tuple::PgHeapTuple<pgx::pgbox::AllocatedByRust>> */
 ) RETURNS Dog /* core::option::Option<pgx::heap_tuple::PgHeapTuple<pgx::pgbox::AllocatedByRust>> */
 LANGUAGE c /* Rust */
-AS 'MODULE_PATHNAME', 'scritch_wrapper';
+AS 'MODULE_PATHNAME', 'a_bunch_of_dog_functions::scritch_wrapper';
 ```
 
 It's possibly to use `composite_type!()` inside a `default!()` macro:
@@ -247,10 +247,9 @@ pub unsafe fn register_hook(hook: &'static mut (dyn PgHooks)) {
 unsafe extern "C" fn pgx_executor_start(query_desc: *mut pg_sys::QueryDesc, eflags: i32) {
     fn prev(query_desc: PgBox<pg_sys::QueryDesc>, eflags: i32) -> HookResult<()> {
         unsafe {
-            (HOOKS.as_mut().unwrap().prev_executor_start_hook.as_ref().unwrap())(
-                query_desc.into_pg(),
-                eflags,
-            )
+            let prev_hook = HOOKS.as_mut().unwrap().prev_executor_start_hook.unwrap();
+            let query_desc = query_desc.into_pg();
+            pg_sys::ffi::pg_guard_ffi_boundary(|| prev_hook(query_desc, eflags))
         }
         HookResult::new(())
     }
@@ -272,12 +271,11 @@ unsafe extern "C" fn pgx_executor_run(
         execute_once: bool,
     ) -> HookResult<()> {
         unsafe {
-            (HOOKS.as_mut().unwrap().prev_executor_run_hook.as_ref().unwrap())(
-                query_desc.into_pg(),
-                direction,
-                count,
-                execute_once,
-            )
+            let prev_hook = HOOKS.as_mut().unwrap().prev_executor_run_hook.unwrap();
+            let query_desc = query_desc.into_pg();
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                prev_hook(query_desc, direction, count, execute_once)
+            })
         }
         HookResult::new(())
     }
@@ -289,9 +287,9 @@ unsafe extern "C" fn pgx_executor_run(
 unsafe extern "C" fn pgx_executor_finish(query_desc: *mut pg_sys::QueryDesc) {
     fn prev(query_desc: PgBox<pg_sys::QueryDesc>) -> HookResult<()> {
         unsafe {
-            (HOOKS.as_mut().unwrap().prev_executor_finish_hook.as_ref().unwrap())(
-                query_desc.into_pg(),
-            )
+            let prev_hook = HOOKS.as_mut().unwrap().prev_executor_finish_hook.unwrap();
+            let query_desc = query_desc.into_pg();
+            pg_sys::ffi::pg_guard_ffi_boundary(|| prev_hook(query_desc))
         }
         HookResult::new(())
     }
@@ -303,7 +301,9 @@ unsafe extern "C" fn pgx_executor_finish(query_desc: *mut pg_sys::QueryDesc) {
 unsafe extern "C" fn pgx_executor_end(query_desc: *mut pg_sys::QueryDesc) {
     fn prev(query_desc: PgBox<pg_sys::QueryDesc>) -> HookResult<()> {
         unsafe {
-            (HOOKS.as_mut().unwrap().prev_executor_end_hook.as_ref().unwrap())(query_desc.into_pg())
+            let prev_hook = HOOKS.as_mut().unwrap().prev_executor_end_hook.unwrap();
+            let query_desc = query_desc.into_pg();
+            pg_sys::ffi::pg_guard_ffi_boundary(|| prev_hook(query_desc))
         }
         HookResult::new(())
     }
@@ -321,10 +321,9 @@ unsafe extern "C" fn pgx_executor_check_perms(
         ereport_on_violation: bool,
     ) -> HookResult<bool> {
         HookResult::new(unsafe {
-            (HOOKS.as_mut().unwrap().prev_executor_check_perms_hook.as_ref().unwrap())(
-                range_table.into_pg(),
-                ereport_on_violation,
-            )
+            let prev_hook = HOOKS.as_mut().unwrap().prev_executor_check_perms_hook.unwrap();
+            let range_table = range_table.into_pg();
+            pg_sys::ffi::pg_guard_ffi_boundary(|| prev_hook(range_table, ereport_on_violation))
         })
     }
     let hook = &mut HOOKS.as_mut().unwrap().current_hook;
@@ -353,15 +352,20 @@ unsafe extern "C" fn pgx_process_utility(
         completion_tag: *mut pg_sys::QueryCompletion,
     ) -> HookResult<()> {
         HookResult::new(unsafe {
-            (HOOKS.as_mut().unwrap().prev_process_utility_hook.as_ref().unwrap())(
-                pstmt.into_pg(),
-                query_string.as_ptr(),
-                context,
-                params.into_pg(),
-                query_env.into_pg(),
-                dest.into_pg(),
-                completion_tag,
-            )
+            let prev_hook = HOOKS.as_mut().unwrap().prev_process_utility_hook.unwrap();
+            let (pstmt, params) = (pstmt.into_pg(), params.into_pg());
+            let (query_env, dest) = (query_env.into_pg(), dest.into_pg());
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                prev_hook(
+                    pstmt,
+                    query_string.as_ptr(),
+                    context,
+                    params,
+                    query_env,
+                    dest,
+                    completion_tag,
+                )
+            })
         })
     }
 
@@ -402,16 +406,22 @@ unsafe extern "C" fn pgx_process_utility(
         completion_tag: *mut pg_sys::QueryCompletion,
     ) -> HookResult<()> {
         HookResult::new(unsafe {
-            (HOOKS.as_mut().unwrap().prev_process_utility_hook.as_ref().unwrap())(
-                pstmt.into_pg(),
-                query_string.as_ptr(),
-                read_only_tree.unwrap(),
-                context,
-                params.into_pg(),
-                query_env.into_pg(),
-                dest.into_pg(),
-                completion_tag,
-            )
+            let prev_hook = HOOKS.as_mut().unwrap().prev_process_utility_hook.unwrap();
+            let (pstmt, params) = (pstmt.into_pg(), params.into_pg());
+            let (query_env, dest) = (query_env.into_pg(), dest.into_pg());
+            let read_only_tree = read_only_tree.unwrap();
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                prev_hook(
+                    pstmt,
+                    query_string.as_ptr(),
+                    read_only_tree,
+                    context,
+                    params,
+                    query_env,
+                    dest,
+                    completion_tag,
+                )
+            })
         })
     }
 
@@ -465,23 +475,21 @@ unsafe extern "C" fn pgx_planner_impl(
         bound_params: PgBox<pg_sys::ParamListInfoData>,
     ) -> HookResult<*mut pg_sys::PlannedStmt> {
         HookResult::new(unsafe {
+            let prev_hook = HOOKS.as_mut().unwrap().prev_planner_hook.unwrap();
+            let (parse, bound_params) = (parse.into_pg(), bound_params.into_pg());
+
             #[cfg(any(feature = "pg11", feature = "pg12"))]
             {
-                (HOOKS.as_mut().unwrap().prev_planner_hook.as_ref().unwrap())(
-                    parse.into_pg(),
-                    cursor_options,
-                    bound_params.into_pg(),
-                )
+                pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                    prev_hook(parse, cursor_options, bound_params)
+                })
             }
 
             #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
             {
-                (HOOKS.as_mut().unwrap().prev_planner_hook.as_ref().unwrap())(
-                    parse.into_pg(),
-                    query_string,
-                    cursor_options,
-                    bound_params.into_pg(),
-                )
+                pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                    prev_hook(parse, query_string, cursor_options, bound_params)
+                })
             }
         })
     }
@@ -510,7 +518,10 @@ unsafe extern "C" fn pgx_post_parse_analyze(
         HookResult::new(unsafe {
             match HOOKS.as_mut().unwrap().prev_post_parse_analyze_hook.as_ref() {
                 None => (),
-                Some(f) => (f)(parse_state.as_ptr(), query.as_ptr()),
+                Some(f) => {
+                    let (parse_state, query) = (parse_state.as_ptr(), query.as_ptr());
+                    pg_sys::ffi::pg_guard_ffi_boundary(|| f(parse_state, query))
+                }
             }
         })
     }
@@ -535,7 +546,9 @@ unsafe extern "C" fn pgx_post_parse_analyze(
             match HOOKS.as_mut().unwrap().prev_post_parse_analyze_hook.as_ref() {
                 None => (),
                 Some(f) => {
-                    (f)(parse_state.as_ptr(), query.as_ptr(), jumble_state.unwrap().as_ptr())
+                    let (parse_state, query) = (parse_state.as_ptr(), query.as_ptr());
+                    let jumble_state = jumble_state.unwrap().as_ptr();
+                    pg_sys::ffi::pg_guard_ffi_boundary(|| f(parse_state, query, jumble_state))
                 }
             }
         })
@@ -557,7 +570,10 @@ unsafe extern "C" fn pgx_emit_log(error_data: *mut pg_sys::ErrorData) {
         HookResult::new(unsafe {
             match HOOKS.as_mut().unwrap().prev_emit_log_hook.as_ref() {
                 None => (),
-                Some(f) => (f)(error_data.as_ptr()),
+                Some(f) => {
+                    let error_data = error_data.as_ptr();
+                    pg_sys::ffi::pg_guard_ffi_boundary(|| f(error_data))
+                }
             }
         })
     }
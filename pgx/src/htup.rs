@@ -175,6 +175,29 @@ pub fn heap_getattr_datum_ex(
     DatumWithTypeInfo { datum, is_null, typoid, typlen, typbyval }
 }
 
+/// Convert a Rust tuple returned by a `#[pg_extern]` function into the `record` Postgres expects,
+/// whose columns are the function's `OUT` parameters.
+///
+/// # Safety
+///
+/// `fcinfo` must be the [`pg_sys::FunctionCallInfo`] of the function returning `tuple`.
+#[doc(hidden)]
+pub unsafe fn tuple_into_record<T: IntoHeapTuple>(
+    fcinfo: pg_sys::FunctionCallInfo,
+    tuple: T,
+) -> pg_sys::Datum {
+    let mut tupdesc = std::ptr::null_mut();
+    if pg_sys::get_call_result_type(fcinfo, std::ptr::null_mut(), &mut tupdesc)
+        != pg_sys::TypeFuncClass_TYPEFUNC_COMPOSITE
+    {
+        pg_sys::error!("return type must be a row type");
+    }
+    pg_sys::BlessTupleDesc(tupdesc);
+
+    let heap_tuple = tuple.into_heap_tuple(tupdesc);
+    pg_sys::HeapTupleHeaderGetDatum((*heap_tuple).t_data)
+}
+
 /// Implemented for Rust tuples that can be represented as a Postgres [`pg_sys::HeapTupleData`].
 pub trait IntoHeapTuple {
     /// Convert `Self` into a `pg_sys::HeapTupleData`, returning a pointer to it.
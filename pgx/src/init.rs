@@ -0,0 +1,149 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Support for [`#[pg_init]`](crate::pg_init) and [`#[pg_fini]`](crate::pg_fini) functions.
+//!
+//! Postgres calls a library's `_PG_init()` once, when it loads it.  [`pg_module_magic!()`](crate::pg_module_magic)
+//! defines `_PG_init()` and `_PG_fini()` for the extension, and they run every `#[pg_init]` and
+//! `#[pg_fini]` function, in any module, in a deterministic order:  by their `order`, which
+//! defaults to zero, and then by their full path.  `#[pg_fini]` functions run in the reverse of that
+//! order.
+//!
+//! A `_PG_init()` or `_PG_fini()` written by hand with `#[pg_guard]` also still works.  It's run
+//! before any `#[pg_init]` function, and after every `#[pg_fini]` function.
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::{pg_guard, pg_sys, PgLogLevel, PgSqlErrorCode};
+
+#[doc(hidden)]
+#[derive(Copy, Clone)]
+pub struct InitFunction {
+    order: i32,
+    name: &'static str,
+    func: fn(),
+}
+
+static mut INIT_FUNCTIONS: Vec<InitFunction> = Vec::new();
+static mut FINI_FUNCTIONS: Vec<InitFunction> = Vec::new();
+static mut INITIALIZED: bool = false;
+static mut LOADED: bool = false;
+static mut PRELOADED: bool = false;
+
+/// Was this extension loaded through `shared_preload_libraries`?
+///
+/// Things that can only be set up while Postgres is starting, like shared memory and static
+/// background workers, aren't available if it wasn't.  Extensions that can also be loaded by
+/// `CREATE EXTENSION` alone can check this to do without them.
+///
+/// This is `false` until the extension's `_PG_init()` has run.
+pub fn is_preloaded() -> bool {
+    unsafe { PRELOADED }
+}
+
+/// Has the extension's `_PG_init()` finished, including every `#[pg_init]` function?
+pub(crate) fn has_loaded() -> bool {
+    unsafe { LOADED }
+}
+
+/// Raises an `ERROR` unless the extension is being loaded through `shared_preload_libraries`, which
+/// `what`, such as "`pg_shmem_init!()`", requires.
+#[track_caller]
+pub fn require_preload(what: &str) {
+    if unsafe { !pg_sys::process_shared_preload_libraries_in_progress } {
+        crate::pg_sys::panic::ErrorReport::new(
+            PgSqlErrorCode::ERRCODE_OBJECT_NOT_IN_PREREQUISITE_STATE,
+            format!(
+                "{} can only be used in `_PG_init()` while the extension is loaded through `shared_preload_libraries`",
+                what
+            ),
+            "require_preload",
+        )
+        .set_hint("add the extension to `shared_preload_libraries`, or check `pgx::is_preloaded()` first")
+        .report(PgLogLevel::ERROR);
+    }
+}
+
+/// Registers a `#[pg_init]` function.  Not public API.
+#[doc(hidden)]
+pub fn __register_init(order: i32, name: &'static str, func: fn()) {
+    // SAFETY:  called by the dynamic loader, one at a time, before `_PG_init()`
+    unsafe { INIT_FUNCTIONS.push(InitFunction { order, name, func }) }
+}
+
+/// Registers a `#[pg_fini]` function.  Not public API.
+#[doc(hidden)]
+pub fn __register_fini(order: i32, name: &'static str, func: fn()) {
+    // SAFETY:  called by the dynamic loader, one at a time, before `_PG_init()`
+    unsafe { FINI_FUNCTIONS.push(InitFunction { order, name, func }) }
+}
+
+fn sorted(functions: &[InitFunction]) -> Vec<InitFunction> {
+    let mut functions = functions.to_vec();
+    functions.sort_by(|a, b| (a.order, a.name).cmp(&(b.order, b.name)));
+    functions
+}
+
+/// The body of the `_PG_init()` that `pg_module_magic!()` defines.  Not public API.
+#[doc(hidden)]
+#[pg_guard]
+pub extern "C" fn __pgx_pg_init() {
+    unsafe {
+        if INITIALIZED {
+            // Postgres only calls `_PG_init()` once, but something else might call it again
+            pg_sys::warning!("this extension has already been initialized in this process");
+            return;
+        }
+        INITIALIZED = true;
+        PRELOADED = pg_sys::process_shared_preload_libraries_in_progress;
+        #[cfg(feature = "function-stats")]
+        crate::function_stats::install();
+
+        for function in sorted(&INIT_FUNCTIONS) {
+            (function.func)();
+        }
+        // once they've had the chance to ask for it with `pgx::statement::track()`
+        crate::statement::install();
+        LOADED = true;
+    }
+}
+
+/// The body of the `_PG_fini()` that `pg_module_magic!()` defines.  Not public API.
+#[doc(hidden)]
+#[pg_guard]
+pub extern "C" fn __pgx_pg_fini() {
+    unsafe {
+        if !INITIALIZED {
+            return;
+        }
+        INITIALIZED = false;
+
+        for function in sorted(&FINI_FUNCTIONS).into_iter().rev() {
+            (function.func)();
+        }
+    }
+}
+
+/// Runs `$register` when the library is loaded by the dynamic loader, before Postgres calls
+/// `_PG_init()`.  Not public API.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_register_on_load {
+    ($register:expr) => {
+        const _: () = {
+            #[used]
+            #[cfg_attr(target_os = "macos", link_section = "__DATA,__mod_init_func")]
+            #[cfg_attr(not(target_os = "macos"), link_section = ".init_array")]
+            static __PGX_REGISTER_ON_LOAD: extern "C" fn() = {
+                extern "C" fn __pgx_register_on_load() {
+                    $register;
+                }
+                __pgx_register_on_load
+            };
+        };
+    };
+}
@@ -12,6 +12,9 @@ Use of this source code is governed by the MIT license that can be found in the
 //!
 //! The default implementations use `serde_json` to serialize a custom type to human-readable strings,
 //! and `serde_cbor` to serialize internally as a `varlena *` for storage on disk.
+//!
+//! Types may also have binary send/receive functions, used by binary `COPY` and clients that ask
+//! for results in binary, by implementing [`SendRecvFuncs`].
 
 use crate::*;
 
@@ -72,3 +75,45 @@ pub trait JsonInOutFuncs<'de>: serde::de::Deserialize<'de> + serde::ser::Seriali
     /// error message should be generated?
     const NULL_ERROR_MESSAGE: Option<&'static str> = None;
 }
+
+/// `#[derive(PostgresType)]` types with the `#[sendrecvfuncs]` attribute need to implement this
+/// trait to provide the binary send/receive functions for that type
+///
+/// Without them, Postgres refuses to send the type in binary, such as with
+/// `COPY ... (FORMAT BINARY)`.  [`pgx::testing::assert_binary_roundtrip()`] checks that the two
+/// agree.
+///
+/// [`pgx::testing::assert_binary_roundtrip()`]: crate::testing::assert_binary_roundtrip
+pub trait SendRecvFuncs {
+    /// Convert `Self` into the bytes of its binary representation, which should be portable
+    /// between machines, such as with integers in network byte order
+    fn send(&self) -> Vec<u8>;
+
+    /// Given the bytes [`send()`](Self::send) made, turn them back into `Self`
+    ///
+    /// It is expected that malformed input will raise an `error!()` or `panic!()`
+    fn recv(bytes: &[u8]) -> Self
+    where
+        Self: Sized;
+}
+
+/// Call `recv` with the unread bytes of the `StringInfo` a receive function is given, which are
+/// then all read, as Postgres checks they are
+#[doc(hidden)]
+pub fn __recv_from_internal<T>(input: Internal, recv: fn(&[u8]) -> T) -> T {
+    unsafe {
+        // SAFETY:  Postgres calls a type's receive function with the `StringInfo` to read from
+        let buffer = input
+            .get_mut::<pg_sys::StringInfoData>()
+            .expect("a receive function was called without a buffer");
+        let unread = (buffer.len - buffer.cursor).max(0) as usize;
+        let bytes = if unread == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(buffer.data.add(buffer.cursor as usize).cast::<u8>(), unread)
+        };
+        let value = recv(bytes);
+        buffer.cursor = buffer.len;
+        value
+    }
+}
@@ -0,0 +1,317 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Run Rust closures when Postgres invalidates its cached catalog entries, and keep backend-local
+//! caches of catalog-derived state from going stale.
+//!
+//! Whenever a catalog row changes -- say, because of an `ALTER TABLE` -- Postgres queues an
+//! invalidation message that every backend, including the one that made the change, processes
+//! at well-defined points such as the start of a transaction or the end of a command.  The
+//! closures registered here are called while those messages are processed.
+//!
+//! Invalidation callbacks must not raise an ERROR, so any panic from a closure is caught and
+//! reported as a WARNING instead.  For the same reason, closures should limit themselves to
+//! forgetting cached state and should not try to look up new catalog information.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use pgx::invalidation::InvalidatedCache;
+//! use pgx::prelude::*;
+//!
+//! thread_local! {
+//!     // Postgres backends are single-threaded, so a `thread_local!` is a per-backend cache
+//!     static COLUMN_NAMES: InvalidatedCache<pg_sys::Oid, Vec<String>> = InvalidatedCache::new();
+//! }
+//!
+//! fn column_names(relid: pg_sys::Oid) -> Vec<String> {
+//!     COLUMN_NAMES.with(|cache| {
+//!         // the expensive lookup is only repeated after the table is altered
+//!         cache.get_or_insert_with(relid, || lookup_column_names(relid))
+//!     })
+//! }
+//! # fn lookup_column_names(_relid: pg_sys::Oid) -> Vec<String> { vec![] }
+//! ```
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+/// A registered callback.  Returns `false` once it no longer needs to be called
+type Callback<A> = Rc<dyn Fn(A) -> bool>;
+
+thread_local! {
+    static RELCACHE_CALLBACKS: RefCell<Option<Vec<Callback<Option<pg_sys::Oid>>>>> =
+        RefCell::new(None);
+    static SYSCACHE_CALLBACKS: RefCell<HashMap<pg_sys::SysCacheIdentifier, Vec<Callback<Option<u32>>>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Call `f` whenever a relation's relcache entry is invalidated, such as after it's altered,
+/// vacuumed, or dropped.
+///
+/// `f` is given the OID of the relation, or `None` if every relation was invalidated at once
+/// (which happens, for example, when the invalidation queue overflows).
+///
+/// Postgres only allows a small number of relcache callbacks per backend, so no matter how many
+/// closures are registered, `pgx` only ever registers one with Postgres, the first time this is
+/// called.  It's therefore safe to call this from both `_PG_init()` and lazily on first use.
+pub fn on_relcache_change<F: Fn(Option<pg_sys::Oid>) + 'static>(f: F) {
+    register_relcache_callback(Rc::new(move |relid| {
+        f(relid);
+        true
+    }));
+}
+
+/// Call `f` whenever an entry in the syscache identified by `cache`, such as
+/// `pg_sys::SysCacheIdentifier_PROCOID`, is invalidated.
+///
+/// `f` is given the hash value of the invalidated entry's key, or `None` if every entry of the
+/// cache was invalidated at once.
+///
+/// As with [`on_relcache_change()`], `pgx` registers at most one callback with Postgres for each
+/// syscache, no matter how many closures are registered for it.
+pub fn on_syscache_change<F: Fn(Option<u32>) + 'static>(cache: pg_sys::SysCacheIdentifier, f: F) {
+    #[pg_guard]
+    unsafe extern "C" fn syscache_callback(
+        _arg: pg_sys::Datum,
+        cacheid: std::os::raw::c_int,
+        hashvalue: u32,
+    ) {
+        let hashvalue = if hashvalue == 0 { None } else { Some(hashvalue) };
+        let callbacks = SYSCACHE_CALLBACKS.with(|registry| {
+            registry.borrow().get(&(cacheid as pg_sys::SysCacheIdentifier)).cloned()
+        });
+        let finished = dispatch(callbacks.unwrap_or_default(), hashvalue);
+        if !finished.is_empty() {
+            SYSCACHE_CALLBACKS.with(|registry| {
+                if let Some(callbacks) =
+                    registry.borrow_mut().get_mut(&(cacheid as pg_sys::SysCacheIdentifier))
+                {
+                    callbacks.retain(|cb| !finished.iter().any(|done| Rc::ptr_eq(cb, done)));
+                }
+            });
+        }
+    }
+
+    let callback: Callback<Option<u32>> = Rc::new(move |hashvalue| {
+        f(hashvalue);
+        true
+    });
+    let needs_registration = SYSCACHE_CALLBACKS.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let needs_registration = !registry.contains_key(&cache);
+        registry.entry(cache).or_default().push(callback);
+        needs_registration
+    });
+
+    if needs_registration {
+        unsafe {
+            // SAFETY:  our callback has the right signature and lives forever
+            pg_sys::CacheRegisterSyscacheCallback(
+                cache as _,
+                Some(syscache_callback),
+                pg_sys::Datum::from(0usize),
+            );
+        }
+    }
+}
+
+pub(crate) fn register_relcache_callback(callback: Callback<Option<pg_sys::Oid>>) {
+    #[pg_guard]
+    unsafe extern "C" fn relcache_callback(_arg: pg_sys::Datum, relid: pg_sys::Oid) {
+        let relid = if relid == pg_sys::InvalidOid { None } else { Some(relid) };
+        let callbacks = RELCACHE_CALLBACKS.with(|registry| registry.borrow().clone());
+        let finished = dispatch(callbacks.unwrap_or_default(), relid);
+        if !finished.is_empty() {
+            RELCACHE_CALLBACKS.with(|registry| {
+                if let Some(callbacks) = registry.borrow_mut().as_mut() {
+                    callbacks.retain(|cb| !finished.iter().any(|done| Rc::ptr_eq(cb, done)));
+                }
+            });
+        }
+    }
+
+    let needs_registration = RELCACHE_CALLBACKS.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let needs_registration = registry.is_none();
+        registry.get_or_insert_with(Vec::new).push(callback);
+        needs_registration
+    });
+
+    if needs_registration {
+        unsafe {
+            // SAFETY:  our callback has the right signature and lives forever
+            pg_sys::CacheRegisterRelcacheCallback(
+                Some(relcache_callback),
+                pg_sys::Datum::from(0usize),
+            );
+        }
+    }
+}
+
+/// Call every callback with `arg`, returning the ones that asked to be unregistered.
+///
+/// We're working from a copy of the registered callbacks, so they're free to register new ones
+fn dispatch<A: Copy>(callbacks: Vec<Callback<A>>, arg: A) -> Vec<Callback<A>> {
+    callbacks
+        .into_iter()
+        .filter(|callback| {
+            let keep = PgTryBuilder::new(AssertUnwindSafe(|| callback(arg)))
+                .catch_others(|e| {
+                    let message = match &e {
+                        pg_sys::panic::CaughtError::PostgresError(ereport)
+                        | pg_sys::panic::CaughtError::ErrorReport(ereport)
+                        | pg_sys::panic::CaughtError::RustPanic { ereport, .. } => {
+                            ereport.message()
+                        }
+                    };
+                    warning!("cache invalidation callback failed: {}", message);
+                    true
+                })
+                .execute();
+            !keep
+        })
+        .collect()
+}
+
+/// A type that can be used as the key of an [`InvalidatedCache`], because it's associated with
+/// a specific relation
+pub trait RelationKey: Eq + Hash {
+    /// The relation whose invalidation should evict this key
+    fn relation(&self) -> pg_sys::Oid;
+}
+
+impl RelationKey for pg_sys::Oid {
+    fn relation(&self) -> pg_sys::Oid {
+        *self
+    }
+}
+
+impl<T: Eq + Hash> RelationKey for (pg_sys::Oid, T) {
+    fn relation(&self) -> pg_sys::Oid {
+        self.0
+    }
+}
+
+/// A backend-local map of cached, per-relation state that automatically evicts entries when
+/// their relation's relcache entry is invalidated.
+///
+/// Clones of an `InvalidatedCache` share the same entries.  The invalidation callback only holds
+/// a weak reference to them, so once every clone is dropped the callback is quietly
+/// unregistered.
+pub struct InvalidatedCache<K, V> {
+    entries: Rc<Entries<K, V>>,
+}
+
+struct Entries<K, V> {
+    map: RefCell<HashMap<K, V>>,
+    /// Bumped every time entries are evicted, so a value computed meanwhile isn't cached
+    evictions: Cell<u64>,
+}
+
+impl<K: RelationKey + 'static, V: 'static> InvalidatedCache<K, V> {
+    pub fn new() -> Self {
+        let entries =
+            Rc::new(Entries { map: RefCell::new(HashMap::new()), evictions: Cell::new(0) });
+        let weak = Rc::downgrade(&entries);
+        register_relcache_callback(Rc::new(move |relid| match weak.upgrade() {
+            Some(entries) => {
+                evict(&entries, relid);
+                true
+            }
+            None => false,
+        }));
+        InvalidatedCache { entries }
+    }
+
+    /// Returns a clone of the cached value for `key`, if there is one
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.entries.map.borrow().get(key).cloned()
+    }
+
+    /// Returns a clone of the cached value for `key`, first computing and caching it with `f` if
+    /// necessary.
+    ///
+    /// The cache isn't borrowed while `f` runs, so `f` is free to look up catalog information,
+    /// which may cause invalidations to be processed.  If any are, what `f` saw may already be
+    /// stale, so its value isn't cached, and `f` is called again.
+    pub fn get_or_insert_with<F: FnMut() -> V>(&self, key: K, mut f: F) -> V
+    where
+        V: Clone,
+    {
+        loop {
+            if let Some(value) = self.get(&key) {
+                return value;
+            }
+
+            let evictions = self.entries.evictions.get();
+            let value = f();
+            if self.entries.evictions.get() == evictions {
+                self.entries.map.borrow_mut().insert(key, value.clone());
+                return value;
+            }
+        }
+    }
+
+    /// Cache `value` for `key`, returning the previously cached value, if any
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.entries.map.borrow_mut().insert(key, value)
+    }
+
+    /// Remove the cached value for `key`, returning it if there was one
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.entries.map.borrow_mut().remove(key)
+    }
+
+    /// Remove every cached value associated with the relation `relid`
+    pub fn invalidate(&self, relid: pg_sys::Oid) {
+        evict(&self.entries, Some(relid))
+    }
+
+    /// Remove every cached value
+    pub fn clear(&self) {
+        evict(&self.entries, None)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.map.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.map.borrow().is_empty()
+    }
+}
+
+impl<K: RelationKey + 'static, V: 'static> Default for InvalidatedCache<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Clone for InvalidatedCache<K, V> {
+    fn clone(&self) -> Self {
+        InvalidatedCache { entries: self.entries.clone() }
+    }
+}
+
+fn evict<K: RelationKey, V>(entries: &Entries<K, V>, relid: Option<pg_sys::Oid>) {
+    entries.evictions.set(entries.evictions.get() + 1);
+    let mut map = entries.map.borrow_mut();
+    match relid {
+        Some(relid) => map.retain(|key, _| key.relation() != relid),
+        None => map.clear(),
+    }
+}
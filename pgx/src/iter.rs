@@ -1,9 +1,10 @@
 use std::iter::once;
 
-use crate::IntoHeapTuple;
+use crate::{pg_sys, IntoDatum, IntoHeapTuple, PgMemoryContexts};
 use pgx_sql_entity_graph::metadata::{
     ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
 };
+use pgx_sql_entity_graph::PgExternReturnEntityIteratedItem;
 
 /// Support for returning a `SETOF T` from an SQL function.
 ///
@@ -14,6 +15,16 @@ use pgx_sql_entity_graph::metadata::{
 /// iterator *can* borrow from its environment, following Rust's normal borrowing rules.  If no
 /// borrowing is necessary, the `'static` lifetime should be used.
 ///
+/// Postgres plans a query as if a set-returning function returns 1000 rows, since it can't know
+/// until it's run.  When it's usually many fewer, or many more, give the function a better estimate
+/// with `#[pg_extern(rows = ..)]`.
+///
+/// The iterator's own [`Iterator::size_hint`] isn't used for this, even when it's exact: the plan
+/// is made before the function is called, from the `ROWS` of its `CREATE FUNCTION`.  Nor does it
+/// size anything once it's running, as the function returns its rows one per call (Postgres'
+/// value-per-call mode), so there's no result to allocate up front, and a `Tuplestorestate` can't
+/// be pre-sized anyway.
+///
 /// # Examples
 ///
 /// This example simply returns a set of integers in the range `1..=5`.
@@ -69,22 +80,169 @@ where
             Ok(Returns::One(sql)) => Ok(Returns::SetOf(sql)),
             Ok(Returns::SetOf(_)) => Err(ReturnsError::NestedSetOf),
             Ok(Returns::Table(_)) => Err(ReturnsError::SetOfContainingTable),
+            Ok(Returns::Record(_)) => Err(ReturnsError::SetOfContainingRecord),
             err @ Err(_) => err,
         }
     }
 }
 
+/// Support for returning an array from an SQL function, without collecting it into a `Vec` first.
+///
+/// [`ArrayFromIterator`] is used as the return type of a `#[pg_extern]`-style function that returns
+/// a `T[]`.  Its elements are added to the array as the iterator you provide during construction
+/// yields them, and a `None` is a `NULL` element.  If the iterator's
+/// [`size_hint()`](Iterator::size_hint) says how many elements there'll be, at least, room is made
+/// for that many up front.
+///
+/// It's the counterpart of [`SetOfIterator`], for a single array rather than a set of rows.
+///
+/// # Examples
+///
+/// This example returns the squares of `1..=n`, which could be many more than fit in memory twice.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// #[pg_extern]
+/// fn squares(n: i32) -> ArrayFromIterator<'static, i64> {
+///     ArrayFromIterator::from_values((1..=n as i64).map(|i| i * i))
+/// }
+/// ```
+///
+/// And here we return the words of a `&str`, with a `NULL` for each empty one:
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// #[pg_extern]
+/// fn split_commas<'a>(input: &'a str) -> ArrayFromIterator<'a, &'a str> {
+///     ArrayFromIterator::new(input.split(',').map(|word| Some(word).filter(|word| !word.is_empty())))
+/// }
+/// ```
+pub struct ArrayFromIterator<'a, T> {
+    iter: Box<dyn Iterator<Item = Option<T>> + 'a>,
+}
+
+impl<'a, T> ArrayFromIterator<'a, T> {
+    /// An array of the elements `iter` yields, where `None` is a `NULL` element
+    pub fn new<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Option<T>> + 'a,
+    {
+        Self { iter: Box::new(iter.into_iter()) }
+    }
+
+    /// An array of the elements `iter` yields, none of which are `NULL`
+    pub fn from_values<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T> + 'a,
+        T: 'a,
+    {
+        Self::new(iter.into_iter().map(Some))
+    }
+}
+
+impl<'a, T> Iterator for ArrayFromIterator<'a, T> {
+    type Item = Option<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> IntoDatum for ArrayFromIterator<'a, T>
+where
+    T: IntoDatum,
+{
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        let element_type = T::type_oid();
+        let mcontext = PgMemoryContexts::CurrentMemoryContext.value();
+        let (estimated_len, _) = self.iter.size_hint();
+        unsafe {
+            // SAFETY:  the state is allocated in `mcontext`, as is the array made from it
+            let mut state = pg_sys::initArrayResult(element_type, mcontext, false);
+            reserve_array_result(state, estimated_len);
+            for element in self.iter {
+                let datum = element.and_then(IntoDatum::into_datum);
+                state = pg_sys::accumArrayResult(
+                    state,
+                    datum.unwrap_or(0.into()),
+                    datum.is_none(),
+                    element_type,
+                    mcontext,
+                );
+            }
+            Some(pg_sys::makeArrayResult(state, mcontext))
+        }
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        unsafe { pg_sys::get_array_type(T::type_oid()) }
+    }
+}
+
+/// Make room in `state` for `len` elements, rather than have `accumArrayResult()` double its room
+/// each time it runs out
+unsafe fn reserve_array_result(state: *mut pg_sys::ArrayBuildState, len: usize) {
+    // Postgres can't allocate more than `MaxAllocSize` at a time
+    const MAX_ALLOC_SIZE: usize = 0x3fff_ffff;
+    let len = len.min(MAX_ALLOC_SIZE / std::mem::size_of::<pg_sys::Datum>());
+    if len <= (*state).alen as usize {
+        return;
+    }
+    (*state).dvalues =
+        pg_sys::repalloc((*state).dvalues.cast(), len * std::mem::size_of::<pg_sys::Datum>())
+            .cast();
+    (*state).dnulls =
+        pg_sys::repalloc((*state).dnulls.cast(), len * std::mem::size_of::<bool>()).cast();
+    (*state).alen = len as i32;
+}
+
+unsafe impl<'a, T> SqlTranslatable for ArrayFromIterator<'a, T>
+where
+    T: SqlTranslatable,
+{
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Err(ArgumentError::NotValidAsArgument("ArrayFromIterator"))
+    }
+
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        match T::return_sql()? {
+            Returns::One(SqlMapping::As(sql)) => {
+                Ok(Returns::One(SqlMapping::As(format!("{sql}[]"))))
+            }
+            Returns::One(SqlMapping::Composite { array_brackets: _ }) => {
+                Ok(Returns::One(SqlMapping::Composite { array_brackets: true }))
+            }
+            Returns::One(SqlMapping::Source { array_brackets: _ }) => {
+                Ok(Returns::One(SqlMapping::Source { array_brackets: true }))
+            }
+            Returns::One(SqlMapping::Skip) => Err(ReturnsError::SkipInArray),
+            Returns::SetOf(_) => Err(ReturnsError::SetOfInArray),
+            Returns::Table(_) => Err(ReturnsError::TableInArray),
+            Returns::Record(_) => Err(ReturnsError::RecordInArray),
+        }
+    }
+}
+
 /// Support for a `TABLE (...)` from an SQL function.
 ///
 /// [`TableIterator`] is typically used as the return type of a `#[pg_extern]`-style function,
 /// indicating that the function returns a table of named columns.  [`TableIterator`] is
-/// generic over `T`, but that `T` must be a Rust tuple containing one or more elements.  They
-/// must also be "named" using pgx' [`name!`] macro.  See the examples below.
+/// generic over `T`, which is either a Rust tuple containing one or more elements, which must be
+/// "named" using pgx' [`name!`] macro, or a struct deriving [`PgTableRow`].  See the examples
+/// below.
 ///
 /// It is a lightweight wrapper around an iterator, which you provide during construction.  The
 /// iterator *can* borrow from its environment, following Rust's normal borrowing rules.  If no
 /// borrowing is necessary, the `'static` lifetime should be used.
 ///
+/// As with [`SetOfIterator`], `#[pg_extern(rows = ..)]` tells the planner how many rows to expect.
+///
 /// # Examples
 ///
 /// This example returns a table of employee information.
@@ -116,6 +274,26 @@ where
 ///     TableIterator::new(input.split_whitespace().enumerate().map(|(n, w)| (n as i32, w)))
 /// }
 /// ```
+///
+/// The same, with a struct for each row, whose fields are the columns.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+///
+/// #[derive(PgTableRow)]
+/// struct Word<'a> {
+///     num: i32,
+///     #[pgx(name = "word")]
+///     text: &'a str,
+/// }
+///
+/// #[pg_extern]
+/// fn split_words<'a>(input: &'a str) -> TableIterator<'a, Word<'a>> {
+///     TableIterator::new(
+///         input.split_whitespace().enumerate().map(|(n, text)| Word { num: n as i32, text }),
+///     )
+/// }
+/// ```
 pub struct TableIterator<'a, T> {
     iter: Box<dyn Iterator<Item = T> + 'a>,
 }
@@ -145,6 +323,44 @@ impl<'a, T> Iterator for TableIterator<'a, T> {
     }
 }
 
+/// A struct whose fields are the columns of a [`TableIterator`]'s rows, in order
+///
+/// Implement it with `#[derive(PgTableRow)]`, which names each column after its field, or
+/// `#[pgx(name = "...")]`.  An `Option` field is a column which can be `NULL`, and a
+/// `PgHeapTuple` field is given its composite type with `#[pgx(composite_type = "...")]`.
+pub trait PgTableRow: IntoHeapTuple {
+    /// The name and type of each column, for the function's `RETURNS TABLE (...)`
+    #[doc(hidden)]
+    fn table_row_columns() -> Vec<PgExternReturnEntityIteratedItem>;
+
+    /// The SQL of each column's type
+    #[doc(hidden)]
+    fn table_row_return_sql() -> Vec<Result<Returns, ReturnsError>>;
+}
+
+unsafe impl<'a, T> SqlTranslatable for TableIterator<'a, T>
+where
+    T: PgTableRow + 'a,
+{
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Err(ArgumentError::Table)
+    }
+
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        T::table_row_return_sql()
+            .into_iter()
+            .map(|column| match column {
+                Ok(Returns::One(sql)) => Ok(sql),
+                Ok(Returns::SetOf(_)) => Err(ReturnsError::TableContainingSetOf),
+                Ok(Returns::Table(_)) => Err(ReturnsError::NestedTable),
+                Ok(Returns::Record(_)) => Err(ReturnsError::TableContainingRecord),
+                Err(err) => Err(err),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Returns::Table)
+    }
+}
+
 seq_macro::seq!(I in 0..=32 {
     #(
         seq_macro::seq!(N in 0..=I {
@@ -164,6 +380,7 @@ seq_macro::seq!(I in 0..=32 {
                             Ok(Returns::One(sql)) => sql,
                             Ok(Returns::SetOf(_)) => return Err(ReturnsError::TableContainingSetOf),
                             Ok(Returns::Table(_)) => return Err(ReturnsError::NestedTable),
+                            Ok(Returns::Record(_)) => return Err(ReturnsError::TableContainingRecord),
                             Err(err) => return Err(err),
                         });
                     )*
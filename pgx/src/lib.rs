@@ -35,42 +35,83 @@ pub use pgx_macros::*;
 /// The PGX prelude includes necessary imports to make extensions work.
 pub mod prelude;
 
+pub mod agg;
 pub mod aggregate;
+pub mod arena;
 pub mod array;
+#[cfg(feature = "arrow")]
+pub mod arrow;
 pub mod atomics;
+pub mod backend;
+pub mod backend_local;
 pub mod bgworkers;
+pub mod cached_function;
 pub mod callbacks;
+pub mod clock;
+pub mod compat;
+pub mod config_table;
 pub mod datum;
+pub mod deferred;
 pub mod enum_helper;
+pub mod error_report;
+pub mod explain;
+pub mod export_abi;
+#[cfg(feature = "cshim")]
+pub mod expr;
 pub mod fcinfo;
 pub mod ffi;
+#[cfg(feature = "function-stats")]
+pub mod function_stats;
+pub mod gin;
+pub mod guard;
 pub mod guc;
 pub mod heap_tuple;
 #[cfg(feature = "cshim")]
 pub mod hooks;
 pub mod htup;
+pub mod init;
 pub mod inoutfuncs;
+pub mod invalidation;
 pub mod itemptr;
 pub mod iter;
 #[cfg(feature = "cshim")]
 pub mod list;
 pub mod lwlock;
 pub mod memcxt;
+pub mod memoize;
 pub mod misc;
+#[cfg(feature = "mock")]
+pub mod mock;
 #[cfg(feature = "cshim")]
 pub mod namespace;
 pub mod nodes;
+pub mod notify;
+pub mod parallel;
+pub mod paths;
 pub mod pgbox;
+pub mod quote;
+pub mod random;
 pub mod rel;
+pub mod shm_mq;
 pub mod shmem;
+pub mod slot;
 pub mod spi;
+pub mod spi_csv;
+pub mod spi_query;
 #[cfg(feature = "cshim")]
 pub mod spinlock;
 pub mod srf;
+pub mod statement;
+pub mod statistic;
+pub mod stats;
 pub mod stringinfo;
+pub mod temp_file;
+pub mod testing;
+pub mod toast;
 pub mod trigger_support;
 pub mod tupdesc;
 pub mod varlena;
+pub mod version_check;
 pub mod wrappers;
 pub mod xid;
 
@@ -83,14 +124,18 @@ mod slice;
 
 pub use aggregate::*;
 pub use atomics::*;
+pub use backend_local::{on_discard, on_reset_all, Discard, PgBackendLocal};
 pub use callbacks::*;
 pub use datum::*;
+pub use deferred::{after_statement, after_statement_once, before_commit, before_commit_once};
 pub use enum_helper::*;
+pub use error_report::{ErrorReportable, PgxError};
 pub use fcinfo::*;
 pub use guc::*;
 #[cfg(feature = "cshim")]
 pub use hooks::*;
 pub use htup::*;
+pub use init::is_preloaded;
 pub use inoutfuncs::*;
 pub use itemptr::*;
 #[cfg(feature = "cshim")]
@@ -101,9 +146,11 @@ pub use memcxt::*;
 pub use namespace::*;
 pub use nodes::*;
 pub use pgbox::*;
+pub use quote::{quote_identifier, quote_literal, quote_qualified_identifier};
 pub use rel::*;
 pub use shmem::*;
 pub use spi::Spi; // only Spi.  We don't want the top-level namespace polluted with spi::Result and spi::Error
+pub use statement::{current_query_id, current_query_string, nesting_level};
 pub use stringinfo::*;
 pub use trigger_support::*;
 pub use tupdesc::*;
@@ -138,14 +185,61 @@ pub use pgx_sql_entity_graph;
 /// </pre></div>
 ///
 /// This calls both [`pg_magic_func!()`](pg_magic_func) and [`pg_sql_graph_magic!()`](pg_sql_graph_magic).
+///
+/// The role which owns, and the roles which may use, the extension's functions, types, schemas,
+/// and aggregates can be given for all of them at once, as `owner = "role"` and any number of
+/// `grant = "role"` options.  These are the defaults for entities which don't give their own
+/// `owner` or `grant` options, and generate `ALTER .. OWNER TO` and `GRANT` statements after
+/// each entity is created:
+///
+/// ```rust,ignore
+/// pgx::pg_module_magic!(owner = "ext_owner", grant = "app_rw", grant = "app_ro");
+/// ```
+///
+/// Granting a role usage of an entity revokes the usage `PUBLIC` has of it by default.
+///
+/// An extension whose functions should only be called by its own SQL unless they're marked
+/// [`#[pg_extern(public)]`](macro@pg_extern) can make them all
+/// [`internal`](macro@pg_extern) by default:
+///
+/// ```rust,ignore
+/// pgx::pg_module_magic!(functions = "internal");
+/// ```
+///
+/// The generated SQL names what the extension makes in its own schema without qualifying it, so
+/// it's found through the `search_path` the script is run with, which searches `pg_temp` first for
+/// tables and types, and the extension's schema, where roles other than its owner may be able to
+/// create objects of the same names.  An extension can instead have its script search `pg_catalog`
+/// first and `pg_temp` last, and, when it isn't `relocatable`, name those objects
+/// `@extschema@.name`:
+///
+/// ```rust,ignore
+/// pgx::pg_module_magic!(search_path = "safe");
+/// ```
 #[macro_export]
 macro_rules! pg_module_magic {
-    () => {
+    ($($option:ident = $value:literal),* $(,)?) => {
         $crate::pg_magic_func!();
-        $crate::pg_sql_graph_magic!();
+        $crate::pg_sql_graph_magic!($($option = $value),*);
     };
 }
 
+// without the `function-stats` feature, `#[pg_extern]` wrappers count nothing and there are no
+// functions to show their counts
+#[cfg(not(feature = "function-stats"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_function_stats {
+    ($name:expr, $path:expr) => {};
+}
+
+#[cfg(not(feature = "function-stats"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_function_stats_functions {
+    () => {};
+}
+
 /// Create the `Pg_magic_func` required by PGX in extensions.
 ///
 /// <div class="example-wrap" style="display:inline-block">
@@ -170,6 +264,11 @@ macro_rules! pg_module_magic {
 /// > PG_MODULE_MAGIC;
 /// > ```
 ///
+/// It also defines the extension's `_PG_init()` and `_PG_fini()`, which run its
+/// [`#[pg_init]`](pg_init) and [`#[pg_fini]`](pg_fini) functions, as described in [`init`].
+/// `_PG_init()` first registers the extension's name, which its control file is named after, and
+/// its library's version, for the checks described in [`version_check`].
+///
 /// ## Acknowledgements
 ///
 /// This macro was initially inspired from the `pg_module` macro by [Daniel Fagnan]
@@ -181,67 +280,109 @@ macro_rules! pg_module_magic {
 #[macro_export]
 macro_rules! pg_magic_func {
     () => {
-        #[no_mangle]
-        #[allow(non_snake_case)]
-        #[allow(unused)]
-        #[link_name = "Pg_magic_func"]
-        #[doc(hidden)]
-        pub extern "C" fn Pg_magic_func() -> &'static pgx::pg_sys::Pg_magic_struct {
-            use core::mem::size_of;
-            use pgx;
+        $crate::__pgx_unless_sql_generation! {
+            #[no_mangle]
+            #[allow(non_snake_case)]
+            #[allow(unused)]
+            #[link_name = "Pg_magic_func"]
+            #[doc(hidden)]
+            pub extern "C" fn Pg_magic_func() -> &'static pgx::pg_sys::Pg_magic_struct {
+                use core::mem::size_of;
+                use pgx;
 
-            #[cfg(any(feature = "pg11", feature = "pg12"))]
-            const MY_MAGIC: pgx::pg_sys::Pg_magic_struct = pgx::pg_sys::Pg_magic_struct {
-                len: size_of::<pgx::pg_sys::Pg_magic_struct>() as i32,
-                version: pgx::pg_sys::PG_VERSION_NUM as i32 / 100,
-                funcmaxargs: pgx::pg_sys::FUNC_MAX_ARGS as i32,
-                indexmaxkeys: pgx::pg_sys::INDEX_MAX_KEYS as i32,
-                namedatalen: pgx::pg_sys::NAMEDATALEN as i32,
-                float4byval: pgx::pg_sys::USE_FLOAT4_BYVAL as i32,
-                float8byval: cfg!(target_pointer_width = "64") as i32,
-            };
+                #[cfg(any(feature = "pg11", feature = "pg12"))]
+                const MY_MAGIC: pgx::pg_sys::Pg_magic_struct = pgx::pg_sys::Pg_magic_struct {
+                    len: size_of::<pgx::pg_sys::Pg_magic_struct>() as i32,
+                    version: pgx::pg_sys::PG_VERSION_NUM as i32 / 100,
+                    funcmaxargs: pgx::pg_sys::FUNC_MAX_ARGS as i32,
+                    indexmaxkeys: pgx::pg_sys::INDEX_MAX_KEYS as i32,
+                    namedatalen: pgx::pg_sys::NAMEDATALEN as i32,
+                    float4byval: pgx::pg_sys::USE_FLOAT4_BYVAL as i32,
+                    float8byval: cfg!(target_pointer_width = "64") as i32,
+                };
 
-            #[cfg(any(feature = "pg13", feature = "pg14"))]
-            const MY_MAGIC: pgx::pg_sys::Pg_magic_struct = pgx::pg_sys::Pg_magic_struct {
-                len: size_of::<pgx::pg_sys::Pg_magic_struct>() as i32,
-                version: pgx::pg_sys::PG_VERSION_NUM as i32 / 100,
-                funcmaxargs: pgx::pg_sys::FUNC_MAX_ARGS as i32,
-                indexmaxkeys: pgx::pg_sys::INDEX_MAX_KEYS as i32,
-                namedatalen: pgx::pg_sys::NAMEDATALEN as i32,
-                float8byval: cfg!(target_pointer_width = "64") as i32,
-            };
+                #[cfg(any(feature = "pg13", feature = "pg14"))]
+                const MY_MAGIC: pgx::pg_sys::Pg_magic_struct = pgx::pg_sys::Pg_magic_struct {
+                    len: size_of::<pgx::pg_sys::Pg_magic_struct>() as i32,
+                    version: pgx::pg_sys::PG_VERSION_NUM as i32 / 100,
+                    funcmaxargs: pgx::pg_sys::FUNC_MAX_ARGS as i32,
+                    indexmaxkeys: pgx::pg_sys::INDEX_MAX_KEYS as i32,
+                    namedatalen: pgx::pg_sys::NAMEDATALEN as i32,
+                    float8byval: cfg!(target_pointer_width = "64") as i32,
+                };
 
-            #[cfg(any(feature = "pg15"))]
-            const MY_MAGIC: pgx::pg_sys::Pg_magic_struct = pgx::pg_sys::Pg_magic_struct {
-                len: size_of::<pgx::pg_sys::Pg_magic_struct>() as i32,
-                version: pgx::pg_sys::PG_VERSION_NUM as i32 / 100,
-                funcmaxargs: pgx::pg_sys::FUNC_MAX_ARGS as i32,
-                indexmaxkeys: pgx::pg_sys::INDEX_MAX_KEYS as i32,
-                namedatalen: pgx::pg_sys::NAMEDATALEN as i32,
-                float8byval: cfg!(target_pointer_width = "64") as i32,
-                abi_extra: {
-                    // array::from_fn isn't const yet, boohoo, so const-copy a bstr
-                    let magic = b"PostgreSQL";
-                    let mut abi = [0 as ::pgx::ffi::c_char; 32];
-                    let mut i = 0;
-                    while i < magic.len() {
-                        abi[i] = magic[i] as _;
-                        i += 1;
-                    }
-                    abi
-                },
-            };
+                #[cfg(any(feature = "pg15"))]
+                const MY_MAGIC: pgx::pg_sys::Pg_magic_struct = pgx::pg_sys::Pg_magic_struct {
+                    len: size_of::<pgx::pg_sys::Pg_magic_struct>() as i32,
+                    version: pgx::pg_sys::PG_VERSION_NUM as i32 / 100,
+                    funcmaxargs: pgx::pg_sys::FUNC_MAX_ARGS as i32,
+                    indexmaxkeys: pgx::pg_sys::INDEX_MAX_KEYS as i32,
+                    namedatalen: pgx::pg_sys::NAMEDATALEN as i32,
+                    float8byval: cfg!(target_pointer_width = "64") as i32,
+                    abi_extra: {
+                        // array::from_fn isn't const yet, boohoo, so const-copy a bstr
+                        let magic = b"PostgreSQL";
+                        let mut abi = [0 as ::pgx::ffi::c_char; 32];
+                        let mut i = 0;
+                        while i < magic.len() {
+                            abi[i] = magic[i] as _;
+                            i += 1;
+                        }
+                        abi
+                    },
+                };
+
+                // go ahead and register our panic handler since Postgres
+                // calls this function first
+                pgx::initialize();
+
+                // return the magic
+                &MY_MAGIC
+            }
 
-            // go ahead and register our panic handler since Postgres
-            // calls this function first
-            pgx::initialize();
+            // in an anonymous const so they don't collide with a `_PG_init()` written by hand, which
+            // `#[pg_guard]` doesn't export but registers to be called from this one
+            const _: () = {
+                #[no_mangle]
+                #[allow(non_snake_case)]
+                #[doc(hidden)]
+                pub extern "C" fn _PG_init() {
+                    $crate::version_check::__register(
+                        $crate::__pgx_extension_name!(),
+                        env!("CARGO_PKG_VERSION"),
+                    );
+                    $crate::init::__pgx_pg_init();
+                }
 
-            // return the magic
-            &MY_MAGIC
+                #[no_mangle]
+                #[allow(non_snake_case)]
+                #[doc(hidden)]
+                pub extern "C" fn _PG_fini() {
+                    $crate::init::__pgx_pg_fini();
+                }
+            };
         }
     };
 }
 
+// A library built with the `sql-generation` feature, only to generate its schema, has stubs for
+// functions, so it exports no magic block, and Postgres refuses to load it by mistake
+#[cfg(not(feature = "sql-generation"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_unless_sql_generation {
+    ($($item:item)*) => {
+        $($item)*
+    };
+}
+
+#[cfg(feature = "sql-generation")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_unless_sql_generation {
+    ($($item:item)*) => {};
+}
+
 /// Create necessary extension-local internal marker for use with SQL generation.
 ///
 /// <div class="example-wrap" style="display:inline-block">
@@ -251,9 +392,15 @@ macro_rules! pg_magic_func {
 /// This macro should only be directly called in advanced use cases.
 ///
 /// </pre></div>
+///
+/// It takes the same `owner`, `grant`, `functions` and `search_path` options as
+/// [`pg_module_magic!()`](pg_module_magic).
+///
+/// With the `function-stats` feature, it also creates the `function_stats()` and
+/// `function_stats_reset()` SQL functions described in `pgx::function_stats`.
 #[macro_export]
 macro_rules! pg_sql_graph_magic {
-    () => {
+    ($($option:ident = $value:literal),* $(,)?) => {
         // A marker which must exist in the root of the extension.
 #[no_mangle]
         #[doc(hidden)]
@@ -266,16 +413,54 @@ macro_rules! pg_sql_graph_magic {
             let context = include_str!(concat!(
                 env!("CARGO_MANIFEST_DIR"),
                 "/",
-                env!("CARGO_CRATE_NAME"),
+                $crate::__pgx_extension_name!(),
                 ".control"
             ))
             .replace("@CARGO_VERSION@", package_version);
 
-            let control_file =
+            #[allow(unused_mut)]
+            let mut control_file =
                 $crate::pgx_sql_entity_graph::ControlFile::try_from(context.as_str())
                     .expect("Could not parse control file, is it valid?");
+            $($crate::__pgx_magic_option!(control_file, $option = $value);)*
             control_file
         }
+
+        $crate::__pgx_function_stats_functions!();
+    };
+}
+
+/// The extension's name, which its control file is named after, as `CREATE EXTENSION` knows it
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_extension_name {
+    () => {
+        env!("CARGO_CRATE_NAME")
+    };
+}
+
+/// Applies one of the `owner`, `grant`, `functions` and `search_path` options of
+/// [`pg_sql_graph_magic!()`](pg_sql_graph_magic), and rejects any other
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pgx_magic_option {
+    ($control_file:expr, owner = $owner:literal) => {
+        $control_file.privileges.owner = Some(String::from($owner));
+    };
+    ($control_file:expr, grant = $grant:literal) => {
+        $control_file.privileges.grants.push(String::from($grant));
+    };
+    ($control_file:expr, functions = "internal") => {
+        $control_file.internal_functions = true;
+    };
+    ($control_file:expr, functions = "public") => {
+        $control_file.internal_functions = false;
+    };
+    ($control_file:expr, search_path = "safe") => {
+        $control_file.safe_search_path = true;
+    };
+    ($control_file:expr, search_path = "inherit") => {
+        $control_file.safe_search_path = false;
     };
 }
 
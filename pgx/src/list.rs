@@ -11,13 +11,30 @@ Use of this source code is governed by the MIT license that can be found in the
 //!
 //! It functions similarly to a Rust [`Vec`][std::vec::Vec], including iterator support, but provides separate
 //! understandings of [`List`][crate::pg_sys::List]s of [`Oid`][crate::pg_sys::Oid]s, Integers, and Pointers.
+//!
+//! Lists of pointers can also be built up, for instance to hand a new qual back to the planner:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::{make_const, PgList};
+//!
+//! let args = [make_const(true), make_const(false)]
+//!     .into_iter()
+//!     .map(|arg| arg.cast::<pg_sys::Node>())
+//!     .collect::<PgList<pg_sys::Node>>();
+//! let qual = unsafe { pg_sys::makeBoolExpr(pg_sys::BoolExprType_AND_EXPR, args.into_pg(), -1) };
+//! ```
+//!
+//! Postgres 13 changed a `List` from a linked list of cells to an array of them, which `PgList`
+//! hides: its methods behave the same on every version.
 
-use crate::{is_a, pg_sys, void_mut_ptr};
+use crate::{is_a, pg_sys, void_mut_ptr, PgMemoryContexts};
 use std::marker::PhantomData;
 
 pub struct PgList<T> {
     list: *mut pg_sys::List,
     allocated_by_pg: bool,
+    memcxt: Option<pg_sys::MemoryContext>,
     _marker: PhantomData<T>,
 }
 impl<T> Default for PgList<T> {
@@ -31,12 +48,30 @@ impl<T> PgList<T> {
         PgList {
             list: std::ptr::null_mut(), // an empty List is NIL
             allocated_by_pg: false,
+            memcxt: None,
             _marker: PhantomData,
         }
     }
 
+    /// Create an empty list whose cells will be allocated in `memcxt`, rather than in whichever
+    /// context is current when it grows
+    ///
+    /// Use [`PgList::into_pg()`] to keep the list for as long as `memcxt` lives.
+    pub fn new_in(memcxt: PgMemoryContexts) -> Self {
+        PgList { memcxt: Some(memcxt.value()), ..Self::new() }
+    }
+
     pub unsafe fn from_pg(list: *mut pg_sys::List) -> Self {
-        PgList { list, allocated_by_pg: true, _marker: PhantomData }
+        PgList { list, allocated_by_pg: true, memcxt: None, _marker: PhantomData }
+    }
+
+    /// Run `f`, which allocates list cells, in this list's memory context, if it has one
+    #[inline]
+    fn in_memcxt<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        match self.memcxt {
+            Some(memcxt) => unsafe { PgMemoryContexts::For(memcxt).switch_to(|_| f()) },
+            None => f(),
+        }
     }
 
     pub fn as_ptr(&self) -> *mut pg_sys::List {
@@ -197,7 +232,84 @@ impl<T> PgList<T> {
     /// we don't dereference it
     #[inline]
     pub fn push(&mut self, ptr: *mut T) {
-        self.list = unsafe { pg_sys::lappend(self.list, ptr as void_mut_ptr) };
+        let list = self.list;
+        self.list = self.in_memcxt(|| unsafe { pg_sys::lappend(list, ptr as void_mut_ptr) });
+    }
+
+    /// Insert a pointer value at position `i`, shifting the values after it along by one
+    ///
+    /// # Panics
+    ///
+    /// If `i` is greater than the list's length
+    #[cfg(any(feature = "pg11", feature = "pg12"))]
+    pub fn insert(&mut self, i: usize, ptr: *mut T) {
+        assert!(
+            i <= self.len(),
+            "insertion index {} is out of bounds for PgList of {}",
+            i,
+            self.len()
+        );
+        let list = self.list;
+        self.list = self.in_memcxt(|| unsafe {
+            if i == 0 {
+                pg_sys::lcons(ptr as void_mut_ptr, list)
+            } else {
+                let prev = pg_sys::pgx_list_nth_cell(list, (i - 1) as i32);
+                pg_sys::lappend_cell(list, prev, ptr as void_mut_ptr);
+                list
+            }
+        });
+    }
+
+    /// Insert a pointer value at position `i`, shifting the values after it along by one
+    ///
+    /// # Panics
+    ///
+    /// If `i` is greater than the list's length
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    pub fn insert(&mut self, i: usize, ptr: *mut T) {
+        assert!(
+            i <= self.len(),
+            "insertion index {} is out of bounds for PgList of {}",
+            i,
+            self.len()
+        );
+        let list = self.list;
+        self.list = self
+            .in_memcxt(|| unsafe { pg_sys::list_insert_nth(list, i as i32, ptr as void_mut_ptr) });
+    }
+
+    /// Replace the pointer value at position `i`, returning the one it replaced
+    ///
+    /// # Panics
+    ///
+    /// If `i` is out of bounds, or this isn't a list of pointers
+    pub fn replace(&mut self, i: usize, ptr: *mut T) -> *mut T {
+        let old = self
+            .get_ptr(i)
+            .unwrap_or_else(|| panic!("index {} is out of bounds for PgList of {}", i, self.len()));
+        unsafe { self.replace_ptr(i, ptr) };
+        old
+    }
+
+    /// Append all of `other`'s values to the end of this list
+    #[cfg(any(feature = "pg11", feature = "pg12"))]
+    pub fn concat(&mut self, other: PgList<T>) {
+        // Postgres links `other`'s cells into this list, so they're ours now
+        self.list = unsafe { pg_sys::list_concat(self.list, other.into_pg()) };
+    }
+
+    /// Append all of `other`'s values to the end of this list
+    #[cfg(any(feature = "pg13", feature = "pg14", feature = "pg15"))]
+    pub fn concat(&mut self, other: PgList<T>) {
+        // Postgres copies `other`'s cells, and `other` frees its own when it's dropped
+        let list = self.list;
+        self.list = self.in_memcxt(|| unsafe { pg_sys::list_concat(list, other.as_ptr()) });
+    }
+
+    /// Copy this list's pointer values into a `Vec`
+    pub fn to_vec(&self) -> Vec<*mut T> {
+        self.iter_ptr().collect()
     }
 
     #[inline]
@@ -212,6 +324,32 @@ impl<T> PgList<T> {
     }
 }
 
+impl<T> FromIterator<*mut T> for PgList<T> {
+    fn from_iter<I: IntoIterator<Item = *mut T>>(iter: I) -> Self {
+        let mut list = PgList::new();
+        iter.into_iter().for_each(|ptr| list.push(ptr));
+        list
+    }
+}
+
+impl<T> Extend<*mut T> for PgList<T> {
+    fn extend<I: IntoIterator<Item = *mut T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|ptr| self.push(ptr));
+    }
+}
+
+impl<T> From<Vec<*mut T>> for PgList<T> {
+    fn from(vec: Vec<*mut T>) -> Self {
+        vec.into_iter().collect()
+    }
+}
+
+impl<T> From<PgList<T>> for Vec<*mut T> {
+    fn from(list: PgList<T>) -> Self {
+        list.to_vec()
+    }
+}
+
 struct PgListIteratorPtr<'a, T> {
     list: &'a PgList<T>,
     pos: usize,
@@ -6,7 +6,7 @@ All rights reserved.
 
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
-use crate::pg_sys;
+use crate::{pg_sys, ShmemVersion};
 use core::ops::{Deref, DerefMut};
 use once_cell::sync::OnceCell;
 use std::fmt;
@@ -32,6 +32,7 @@ use uuid::Uuid;
 pub struct PgLwLock<T> {
     inner: OnceCell<PgLwLockInner<T>>,
     name: OnceCell<&'static str>,
+    version: OnceCell<ShmemVersion>,
 }
 
 unsafe impl<T: Send> Send for PgLwLock<T> {}
@@ -41,7 +42,7 @@ impl<T> PgLwLock<T> {
     /// Create an empty lock which can be created as a global with None as a
     /// sentinel value
     pub const fn new() -> Self {
-        PgLwLock { inner: OnceCell::new(), name: OnceCell::new() }
+        PgLwLock { inner: OnceCell::new(), name: OnceCell::new(), version: OnceCell::new() }
     }
 
     /// Create a new lock for T by attaching a LWLock, which is looked up by name
@@ -50,7 +51,7 @@ impl<T> PgLwLock<T> {
         let name = OnceCell::new();
         inner.set(PgLwLockInner::<T>::new(input_name, value)).unwrap();
         name.set(input_name).unwrap();
-        PgLwLock { inner, name }
+        PgLwLock { inner, name, version: OnceCell::new() }
     }
 
     /// Get the name of the PgLwLock
@@ -65,6 +66,20 @@ impl<T> PgLwLock<T> {
         }
     }
 
+    /// The version its shared memory is laid out as, which is [`ShmemVersion::current()`] unless
+    /// `pg_shmem_init!()` was given another
+    pub fn shmem_version(&self) -> ShmemVersion {
+        self.version.get().copied().unwrap_or_else(ShmemVersion::current::<T>)
+    }
+
+    /// Name it, and the version of its shared memory, before it's first used
+    pub(crate) fn identify(&self, name: &'static str, version: Option<ShmemVersion>) {
+        let _ = self.name.set(name);
+        if let Some(version) = version {
+            let _ = self.version.set(version);
+        }
+    }
+
     /// Obtain a shared lock (which comes with `&T` access)
     pub fn share(&self) -> PgLwLockShareGuard<T> {
         self.inner.get().expect("Can't give out share, lock is in an empty state").share()
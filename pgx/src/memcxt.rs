@@ -15,6 +15,8 @@ Use of this source code is governed by the MIT license that can be found in the
 //! An enum-based interface (`PgMemoryContexts`) around Postgres' various `MemoryContext`s provides
 //! simple accessibility to working with MemoryContexts in a compiler-checked manner
 //!
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::pg_guard;
 use crate::pg_sys;
 use crate::pg_sys::AsPgCStr;
 use core::ptr;
@@ -347,6 +349,9 @@ impl PgMemoryContexts {
     /// within that context, and then `CurrentMemoryContext` is restored to what it was before
     /// we started.
     ///
+    /// That happens even if the function panics, or raises an `ERROR` from a `pg_sys` call, and a
+    /// [`PgMemoryContexts::Transient`] context is deleted either way.
+    ///
     /// ## Examples
     ///
     /// ```rust,no_run
@@ -396,13 +401,9 @@ impl PgMemoryContexts {
                     )
                 };
 
-                let result = PgMemoryContexts::exec_in_context(context, f);
-
-                unsafe {
-                    pg_sys::MemoryContextDelete(context);
-                }
-
-                result
+                // deleted once we've switched back out of it, including when unwinding
+                let _delete = DeleteOnDrop(context);
+                PgMemoryContexts::exec_in_context(context, f)
             }
             _ => PgMemoryContexts::exec_in_context(self.value(), f),
         }
@@ -559,7 +560,13 @@ impl PgMemoryContexts {
     /// Consumes an instance of `T` and leaks it.  Whenever Postgres deletes
     /// this MemoryContext, the original instance of `T` will be resurrected and its `impl Drop`
     /// will be called.
+    ///
+    /// If that `impl Drop` panics, the panic is raised as a Postgres `ERROR` from within the
+    /// context's deletion.
     pub fn leak_and_drop_on_delete<T>(&mut self, v: T) -> *mut T {
+        // called from `MemoryContextCallResetCallbacks()`, so a panicking `impl Drop` must not
+        // unwind into it
+        #[pg_guard]
         unsafe extern "C" fn drop_on_delete<T>(ptr: void_mut_ptr) {
             let boxed = Box::from_raw(ptr as *mut T);
             drop(boxed);
@@ -602,20 +609,36 @@ impl PgMemoryContexts {
         context: pg_sys::MemoryContext,
         f: F,
     ) -> R {
-        let prev_context;
-
         // mimic what palloc.h does for switching memory contexts
-        unsafe {
-            prev_context = pg_sys::CurrentMemoryContext;
+        let _restore = unsafe {
+            let restore = RestoreOnDrop(pg_sys::CurrentMemoryContext);
             pg_sys::CurrentMemoryContext = context;
-        }
+            restore
+        };
+
+        f(&mut PgMemoryContexts::For(context))
+    }
+}
+
+/// Restores the `CurrentMemoryContext` it holds when dropped, so it's restored when a panic, or a
+/// Postgres `ERROR` turned into one, unwinds past [`PgMemoryContexts::exec_in_context`]
+struct RestoreOnDrop(pg_sys::MemoryContext);
 
-        let result = f(&mut PgMemoryContexts::For(context));
-        // restore our understanding of the current memory context
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
         unsafe {
-            pg_sys::CurrentMemoryContext = prev_context;
+            pg_sys::CurrentMemoryContext = self.0;
         }
+    }
+}
+
+/// Deletes the `MemoryContext` it holds when dropped
+struct DeleteOnDrop(pg_sys::MemoryContext);
 
-        result
+impl Drop for DeleteOnDrop {
+    fn drop(&mut self) {
+        unsafe {
+            pg_sys::MemoryContextDelete(self.0);
+        }
     }
 }
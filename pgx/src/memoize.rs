@@ -0,0 +1,403 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Caching a `STABLE` function's results for the rest of the statement that calls it
+//!
+//! Postgres may call a `STABLE` function once per row, even when most rows pass it the same
+//! arguments, but it promises the function sees the same database throughout a statement.  So a
+//! function which looks something up through [`Spi`](crate::Spi) can remember what it found until
+//! the statement ends, with `memoize`:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//!
+//! #[pg_extern(stable, memoize)]
+//! fn tax_rate(region: &str) -> Option<f64> {
+//!     Spi::get_one_with_args(
+//!         "SELECT rate FROM tax_rates WHERE region = $1",
+//!         vec![(PgBuiltInOids::TEXTOID.oid(), region.into_datum())],
+//!     )
+//!     .unwrap()
+//! }
+//! ```
+//!
+//! Then `SELECT price * tax_rate(region) FROM orders` runs one query per region, not per order.
+//! `memoize = 100` caches at most 100 results, instead of [`DEFAULT_MAX_ENTRIES`].
+//!
+//! The cache is a [`StatementCache`], which a function can also use itself, for something other
+//! than its whole result:
+//!
+//! ```rust,no_run
+//! use pgx::memoize::StatementCache;
+//! use pgx::prelude::*;
+//!
+//! static OWNERS: StatementCache<pg_sys::Oid, String> = StatementCache::new("owners");
+//!
+//! #[pg_extern(stable)]
+//! fn describe(table: pg_sys::Oid) -> String {
+//!     let owner = OWNERS.get_or_insert_with(table, || {
+//!         Spi::get_one_with_args(
+//!             "SELECT relowner::regrole::text FROM pg_class WHERE oid = $1",
+//!             vec![(PgBuiltInOids::OIDOID.oid(), table.into_datum())],
+//!         )
+//!         .unwrap()
+//!         .unwrap_or_default()
+//!     });
+//!     format!("owned by {}", owner)
+//! }
+//! ```
+//!
+//! What's cached is emptied when the statement that cached it ends, whether it finishes or raises
+//! an `ERROR`, so the next statement sees any changes.  A statement run by a function, through
+//! [`Spi`](crate::Spi) or PL/pgSQL, is a statement of its own, at a deeper
+//! [`nesting_level()`](crate::nesting_level), so it doesn't see what its caller cached either.
+//! Each `FETCH` from a cursor is a statement of its own too.  And as PL/pgSQL evaluates some
+//! expressions without running a statement, what's cached is also forgotten when the transaction
+//! changes the database, or ends, which is when Postgres lets those expressions see newer data.
+//! Nothing is cached while no statement is running, such as while the planner estimates a
+//! `STABLE` function's result.
+//!
+//! Only `STABLE` and `IMMUTABLE` functions' results can be cached.  A `VOLATILE` function may
+//! return something else for the same arguments within a statement, which Postgres allows.
+use crate::{pg_sys, AnyNumeric, Array, Date, FromDatum, Interval, Numeric, Timestamp};
+use crate::{TimestampWithTimeZone, Uuid};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// How many results a [`StatementCache`] keeps, unless it's given [`StatementCache::max_entries()`]
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+thread_local! {
+    /// Every [`StatementCache`] used by this backend, by name
+    static CACHES: RefCell<HashMap<&'static str, Slot>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct Slot {
+    /// What's cached for each statement that's running, with the innermost last
+    levels: Vec<Level>,
+    stats: CacheStats,
+}
+
+/// What a [`StatementCache`] cached for the statement at one nesting level
+struct Level {
+    nesting_level: u32,
+    /// The transaction's command id when it was cached, which changes once the transaction
+    /// changes the database
+    command_id: pg_sys::CommandId,
+    /// A `HashMap<K, V>`
+    entries: Box<dyn Any>,
+    len: usize,
+}
+
+impl Slot {
+    /// What's cached for the statement at `nesting_level`, which is the innermost one running,
+    /// unless the database has changed since
+    fn level(&mut self, nesting_level: u32, command_id: pg_sys::CommandId) -> Option<&mut Level> {
+        self.levels
+            .last_mut()
+            .filter(|level| level.nesting_level == nesting_level && level.command_id == command_id)
+    }
+}
+
+/// How well a [`StatementCache`] has worked, over every statement since the backend started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups which found a cached value
+    pub hits: u64,
+    /// Lookups which didn't, so the value was computed
+    pub misses: u64,
+    /// Computed values which weren't cached, because the cache was full
+    pub uncached: u64,
+    /// The values cached for the statements that are running
+    pub entries: usize,
+}
+
+/// A cache of values that lasts until the end of the current statement
+///
+/// It's identified by its name, which has to be unique in the extension, so it can be a `static`
+/// or made where it's used.  `K` is what a value is cached by, and the values are cloned out of
+/// it, so they're usually owned types.
+///
+/// It tells statements apart by [`nesting_level()`](crate::nesting_level), so unless the extension
+/// has a `#[pg_extern(memoize)]` function, it has to call [`pgx::statement::track()`](crate::statement::track)
+/// while loading for anything to be cached.
+pub struct StatementCache<K, V> {
+    name: &'static str,
+    max_entries: usize,
+    _types: PhantomData<fn(K) -> V>,
+}
+
+impl<K, V> StatementCache<K, V> {
+    pub const fn new(name: &'static str) -> Self {
+        StatementCache { name, max_entries: DEFAULT_MAX_ENTRIES, _types: PhantomData }
+    }
+
+    /// Cache at most `max_entries` values in a statement, after which new ones are computed
+    /// every time
+    pub const fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// How well the cache has worked
+    pub fn stats(&self) -> CacheStats {
+        stats(self.name)
+    }
+}
+
+impl<K: Hash + Eq + 'static, V: Clone + 'static> StatementCache<K, V> {
+    /// The value cached for `key` in the current statement, or the one `f` computes, which is
+    /// then cached
+    ///
+    /// `f` may use this cache too, such as by calling its own function for other arguments.
+    ///
+    /// # Panics
+    ///
+    /// If another cache with the same name has different types
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> V {
+        let nesting_level = crate::nesting_level();
+        if nesting_level == 0 {
+            return f();
+        }
+        let command_id = unsafe {
+            // SAFETY:  a statement is running, so a transaction is too
+            pg_sys::GetCurrentCommandId(false)
+        };
+
+        let cached = CACHES.with(|caches| {
+            let mut caches = caches.borrow_mut();
+            let slot = caches.entry(self.name).or_default();
+            let found = match slot.level(nesting_level, command_id) {
+                Some(level) => self.downcast(level).get(&key).cloned(),
+                None => None,
+            };
+            match found {
+                Some(_) => slot.stats.hits += 1,
+                None => slot.stats.misses += 1,
+            }
+            found
+        });
+        if let Some(value) = cached {
+            return value;
+        }
+
+        // the cache isn't borrowed while `f` runs, in case it uses it too
+        let value = f();
+        let stale = CACHES.with(|caches| {
+            let mut caches = caches.borrow_mut();
+            let slot = caches.entry(self.name).or_default();
+            let mut stale = None;
+            if slot.level(nesting_level, command_id).is_none() {
+                // what this statement cached before the database changed
+                if slot.levels.last().map_or(false, |level| level.nesting_level == nesting_level) {
+                    let level = slot.levels.pop().unwrap();
+                    slot.stats.entries -= level.len;
+                    stale = Some(level);
+                }
+                slot.levels.push(Level {
+                    nesting_level,
+                    command_id,
+                    entries: Box::new(HashMap::<K, V>::new()),
+                    len: 0,
+                });
+            }
+            let level = slot.levels.last_mut().unwrap();
+            if level.len >= self.max_entries {
+                slot.stats.uncached += 1;
+            } else if self.downcast(level).insert(key, value.clone()).is_none() {
+                level.len += 1;
+                slot.stats.entries += 1;
+            }
+            stale
+        });
+        // dropped once the caches aren't borrowed
+        drop(stale);
+        value
+    }
+
+    /// Forget every value cached by the statements that are running
+    pub fn clear(&self) {
+        let levels = CACHES.with(|caches| {
+            let mut caches = caches.borrow_mut();
+            caches.get_mut(self.name).map(|slot| {
+                slot.stats.entries = 0;
+                std::mem::take(&mut slot.levels)
+            })
+        });
+        // a value's `Drop` might use a cache, so they're dropped once the caches aren't borrowed
+        drop(levels);
+    }
+
+    fn downcast<'a>(&self, level: &'a mut Level) -> &'a mut HashMap<K, V> {
+        match level.entries.downcast_mut() {
+            Some(entries) => entries,
+            None => panic!(
+                "the statement cache `{}` is used with more than one key or value type",
+                self.name
+            ),
+        }
+    }
+}
+
+/// How well the [`StatementCache`] named `name` has worked
+///
+/// A `#[pg_extern(memoize)]` function's is named for its path, such as `my_extension::tax_rate`.
+pub fn stats(name: &str) -> CacheStats {
+    CACHES.with(|caches| caches.borrow().get(name).map(|slot| slot.stats)).unwrap_or_default()
+}
+
+/// Forget what every [`StatementCache`] cached for the statements deeper than `nesting_level`, as
+/// they've ended
+pub(crate) fn statements_ended(nesting_level: u32) {
+    let ended = CACHES.with(|caches| {
+        let mut ended = Vec::new();
+        for slot in caches.borrow_mut().values_mut() {
+            while slot.levels.last().map_or(false, |level| level.nesting_level > nesting_level) {
+                let level = slot.levels.pop().unwrap();
+                slot.stats.entries -= level.len;
+                ended.push(level);
+            }
+        }
+        ended
+    });
+    // dropped once the caches aren't borrowed
+    drop(ended);
+}
+
+/// An argument of a `#[pg_extern(memoize)]` function, as its results are cached by
+///
+/// It's implemented for owned and borrowed types alike, as the key has to outlive the call.
+pub trait MemoizeKey {
+    type Key: Hash + Eq + 'static;
+
+    fn memoize_key(&self) -> Self::Key;
+}
+
+macro_rules! memoize_key_by_clone {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl MemoizeKey for $ty {
+                type Key = $ty;
+
+                fn memoize_key(&self) -> Self::Key {
+                    self.clone()
+                }
+            }
+        )*
+    };
+}
+
+memoize_key_by_clone!(
+    (),
+    bool,
+    char,
+    i8,
+    u8,
+    i16,
+    i32,
+    i64,
+    String,
+    pg_sys::Oid,
+    AnyNumeric,
+    Date,
+    Interval,
+    Timestamp,
+    TimestampWithTimeZone,
+    Uuid,
+);
+
+/// Floats are cached by their bits, so `-0.0` and `0.0` are different keys, and so are `NaN`s
+impl MemoizeKey for f32 {
+    type Key = u32;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.to_bits()
+    }
+}
+
+impl MemoizeKey for f64 {
+    type Key = u64;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.to_bits()
+    }
+}
+
+impl<const P: u32, const S: u32> MemoizeKey for Numeric<P, S> {
+    type Key = Numeric<P, S>;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.clone()
+    }
+}
+
+impl MemoizeKey for str {
+    type Key = String;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.to_owned()
+    }
+}
+
+impl MemoizeKey for CStr {
+    type Key = CString;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.to_owned()
+    }
+}
+
+impl<T: MemoizeKey + ?Sized> MemoizeKey for &T {
+    type Key = T::Key;
+
+    fn memoize_key(&self) -> Self::Key {
+        (**self).memoize_key()
+    }
+}
+
+impl<T: MemoizeKey> MemoizeKey for Option<T> {
+    type Key = Option<T::Key>;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.as_ref().map(T::memoize_key)
+    }
+}
+
+impl<T: MemoizeKey> MemoizeKey for [T] {
+    type Key = Vec<T::Key>;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.iter().map(T::memoize_key).collect()
+    }
+}
+
+impl<T: MemoizeKey> MemoizeKey for Vec<T> {
+    type Key = Vec<T::Key>;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.as_slice().memoize_key()
+    }
+}
+
+impl<T: MemoizeKey + FromDatum> MemoizeKey for Array<'_, T> {
+    type Key = Vec<Option<T::Key>>;
+
+    fn memoize_key(&self) -> Self::Key {
+        self.iter().map(|element| element.as_ref().map(T::memoize_key)).collect()
+    }
+}
@@ -0,0 +1,224 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Constructors that let a plain `#[test]` call a `#[pg_extern]` function directly, without a
+//! running Postgres
+//!
+//! Most pgx types are views of a Postgres `Datum`, so they can only be made inside of a backend.
+//! With the `mock` feature, these can be made anywhere:
+//!
+//! - [`Array::from_vec_for_test()`] and [`Array::from_nullable_vec_for_test()`], and the same
+//!   for [`VariadicArray`]
+//! - [`Timestamp::from_parts_for_test()`] and [`Date::from_parts_for_test()`]
+//! - [`Json::from_value()`] and [`JsonB::from_value()`]
+//!
+//! An array made this way lives on the Rust heap and isn't a Postgres array at all, so converting
+//! it into one panics.  That keeps these tests to a function's logic, while `#[pg_test]`s still
+//! cover how it's called from SQL.
+//!
+//! The feature only belongs in tests, so enable it for the extension's dev-dependency on pgx,
+//! which leaves it out of the shared library `cargo pgx install` and `cargo pgx package` build:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! pgx = { version = "=0.7.1", features = ["mock"] }
+//! ```
+//!
+//! ```rust,ignore
+//! use pgx::prelude::*;
+//!
+//! #[pg_extern]
+//! fn total(values: Array<i32>) -> i64 {
+//!     values.iter().flatten().map(i64::from).sum()
+//! }
+//!
+//! #[cfg(test)]
+//! mod unit_tests {
+//!     use pgx::prelude::*;
+//!
+//!     #[test]
+//!     fn test_total_skips_nulls() {
+//!         let values = Array::from_nullable_vec_for_test(vec![Some(1), None, Some(2)]);
+//!         assert_eq!(super::total(values), 3);
+//!     }
+//! }
+//! ```
+use crate::{Array, Date, FromDatum, Json, JsonB, Timestamp, VariadicArray};
+use serde_json::Value;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+const USECS_PER_SEC: i64 = 1_000_000;
+const SECS_PER_DAY: i64 = 86_400;
+
+/// The elements of an [`Array`] made for a test, each of which is cloned when it's asked for
+pub(crate) struct MockElements<'a, T> {
+    elements: Box<dyn Fn(usize) -> Option<Option<T>> + 'a>,
+    /// Formats the elements as a list, as `Array`'s `Debug` does those of a Postgres array
+    debug: Box<dyn Fn(&mut Formatter<'_>) -> std::fmt::Result + 'a>,
+    len: usize,
+    any_nulls: bool,
+}
+
+impl<'a, T: Clone + Debug + 'a> MockElements<'a, T> {
+    fn new(elements: Vec<Option<T>>) -> Self {
+        let elements = Rc::new(elements);
+        let shown = Rc::clone(&elements);
+        MockElements {
+            len: elements.len(),
+            any_nulls: elements.iter().any(Option::is_none),
+            elements: Box::new(move |i| elements.get(i).cloned()),
+            debug: Box::new(move |f| {
+                f.debug_list().entries(shown.iter().map(MockElement)).finish()
+            }),
+        }
+    }
+}
+
+/// An element of a [`MockElements`], which is `NULL` if it's `None`
+struct MockElement<'e, T>(&'e Option<T>);
+
+impl<T: Debug> Debug for MockElement<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(element) => element.fmt(f),
+            None => f.write_str("NULL"),
+        }
+    }
+}
+
+impl<T> Debug for MockElements<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        (self.debug)(f)
+    }
+}
+
+impl<T> MockElements<'_, T> {
+    pub(crate) fn get(&self, i: usize) -> Option<Option<T>> {
+        (self.elements)(i)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn any_nulls(&self) -> bool {
+        self.any_nulls
+    }
+}
+
+impl<'a, T: FromDatum + Clone + Debug + 'a> Array<'a, T> {
+    /// An array of `elements`, none of which are `NULL`
+    pub fn from_vec_for_test(elements: Vec<T>) -> Self {
+        Self::from_nullable_vec_for_test(elements.into_iter().map(Some).collect())
+    }
+
+    /// An array of `elements`, where `None` is `NULL`
+    pub fn from_nullable_vec_for_test(elements: Vec<Option<T>>) -> Self {
+        Array::from_mock(MockElements::new(elements))
+    }
+}
+
+impl<'a, T: FromDatum + Clone + Debug + 'a> VariadicArray<'a, T> {
+    /// The arguments `elements`, none of which are `NULL`
+    pub fn from_vec_for_test(elements: Vec<T>) -> Self {
+        Self::from_nullable_vec_for_test(elements.into_iter().map(Some).collect())
+    }
+
+    /// The arguments `elements`, where `None` is `NULL`
+    pub fn from_nullable_vec_for_test(elements: Vec<Option<T>>) -> Self {
+        VariadicArray::from_mock(MockElements::new(elements))
+    }
+}
+
+impl Timestamp {
+    /// The timestamp `make_timestamp(year, month, day, hour, minute, second)` would return, where a
+    /// negative `year` is BC
+    ///
+    /// # Panics
+    ///
+    /// If any part is out of range, or the timestamp is outside of [`Timestamp::MIN`] and
+    /// [`Timestamp::MAX`]
+    pub fn from_parts_for_test(
+        year: i32,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: f64,
+    ) -> Self {
+        assert!(hour < 24, "hour {} is out of range", hour);
+        assert!(minute < 60, "minute {} is out of range", minute);
+        assert!((0.0..60.0).contains(&second), "second {} is out of range", second);
+
+        let days = unix_epoch_days(year, month, day);
+        let seconds = days * SECS_PER_DAY + i64::from(hour) * 3_600 + i64::from(minute) * 60;
+        let micros = seconds * USECS_PER_SEC + (second * USECS_PER_SEC as f64).round() as i64;
+        Timestamp::from_unix_epoch_micros(micros).unwrap_or_else(|e| {
+            panic!(
+                "{}-{}-{} {}:{}:{} isn't a valid timestamp: {}",
+                year, month, day, hour, minute, second, e
+            )
+        })
+    }
+}
+
+impl Date {
+    /// The date `make_date(year, month, day)` would return, where a negative `year` is BC
+    ///
+    /// # Panics
+    ///
+    /// If any part is out of range, or the date is outside of [`Date::MIN`] and [`Date::MAX`]
+    pub fn from_parts_for_test(year: i32, month: u8, day: u8) -> Self {
+        let days = unix_epoch_days(year, month, day);
+        i32::try_from(days)
+            .ok()
+            .and_then(|days| Date::from_unix_epoch_days(days).ok())
+            .unwrap_or_else(|| panic!("{}-{}-{} isn't a valid date", year, month, day))
+    }
+}
+
+impl Json {
+    pub fn from_value(value: Value) -> Self {
+        Json(value)
+    }
+}
+
+impl JsonB {
+    pub fn from_value(value: Value) -> Self {
+        JsonB(value)
+    }
+}
+
+/// The days from `1970-01-01` to a date in the proleptic Gregorian calendar, which Postgres uses
+/// for every date, even those before it was adopted
+fn unix_epoch_days(year: i32, month: u8, day: u8) -> i64 {
+    assert!(year != 0, "there's no year 0, as 1 BC is year -1");
+    assert!((1..=12).contains(&month), "month {} is out of range", month);
+    // there's a year 0 for the arithmetic, which 1 BC is
+    let year = if year < 0 { i64::from(year) + 1 } else { i64::from(year) };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    assert!(
+        (1..=month_days[month as usize - 1]).contains(&day),
+        "day {} is out of range for month {}",
+        day,
+        month
+    );
+
+    // counting from March, so a leap day is the last of its year
+    let (year, month) =
+        if month <= 2 { (year - 1, i64::from(month) + 9) } else { (year, i64::from(month) - 3) };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    // 0000-03-01 was 719,468 days before 1970-01-01
+    era * 146_097 + day_of_era - 719_468
+}
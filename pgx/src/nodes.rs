@@ -9,7 +9,7 @@ Use of this source code is governed by the MIT license that can be found in the
 
 //! Helper functions and such for Postgres' various query tree `Node`s
 
-use crate::pg_sys;
+use crate::{pg_sys, IntoDatum, PgList};
 
 /// #define IsA(nodeptr,_type_)            (nodeTag(nodeptr) == T_##_type_)
 #[inline]
@@ -38,3 +38,60 @@ pub unsafe fn node_to_string<'a>(nodeptr: *mut pg_sys::Node) -> Option<&'a str>
         }
     }
 }
+
+/// Make a [`pg_sys::Const`] holding `value`, of the type [`IntoDatum::type_oid()`] gives
+///
+/// A `None` makes a `NULL` constant.  The constant, and any pass-by-reference value it holds, are
+/// allocated in `CurrentMemoryContext`.
+pub fn make_const<T: IntoDatum>(value: T) -> *mut pg_sys::Const {
+    let typoid = T::type_oid();
+    let datum = value.into_datum();
+    unsafe {
+        let mut typlen = 0;
+        let mut typbyval = false;
+        pg_sys::get_typlenbyval(typoid, &mut typlen, &mut typbyval);
+        pg_sys::makeConst(
+            typoid,
+            -1,
+            pg_sys::get_typcollation(typoid),
+            typlen as i32,
+            datum.unwrap_or(pg_sys::Datum::from(0usize)),
+            datum.is_none(),
+            typbyval,
+        )
+    }
+}
+
+/// Make a [`pg_sys::Var`] referring to attribute `attno`, of type `typoid`, of the range table
+/// entry `varno` in the current query level
+pub fn make_var(
+    varno: pg_sys::Index,
+    attno: pg_sys::AttrNumber,
+    typoid: pg_sys::Oid,
+    typmod: i32,
+) -> *mut pg_sys::Var {
+    unsafe {
+        pg_sys::makeVar(varno as _, attno, typoid, typmod, pg_sys::get_typcollation(typoid), 0)
+    }
+}
+
+/// Make a [`pg_sys::FuncExpr`] calling the function `funcid`, which returns `rettype`, with `args`
+///
+/// Its collations are left unset, which suits functions whose arguments and result aren't of
+/// collatable types.
+pub fn make_func_expr(
+    funcid: pg_sys::Oid,
+    rettype: pg_sys::Oid,
+    args: PgList<pg_sys::Node>,
+) -> *mut pg_sys::FuncExpr {
+    unsafe {
+        pg_sys::makeFuncExpr(
+            funcid,
+            rettype,
+            args.into_pg(),
+            pg_sys::InvalidOid,
+            pg_sys::InvalidOid,
+            pg_sys::CoercionForm_COERCE_EXPLICIT_CALL,
+        )
+    }
+}
@@ -0,0 +1,312 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Sending notifications with `NOTIFY`, and receiving them in background workers
+//!
+//! A [`Channel<T>`] sends values of `T` as JSON payloads.  Postgres delivers a transaction's
+//! notifications when it commits, in the order they were sent, to every backend listening on
+//! their channel, and folds identical notifications sent in one transaction into one.
+//!
+//! ```rust,no_run
+//! use pgx::notify::Channel;
+//! use pgx::prelude::*;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Order {
+//!     id: i64,
+//!     total: f64,
+//! }
+//!
+//! static ORDERS: Channel<Order> = Channel::new("orders");
+//!
+//! #[pg_extern]
+//! fn place_order(id: i64, total: f64) {
+//!     ORDERS.notify(&Order { id, total }).expect("couldn't send the notification");
+//! }
+//! ```
+//!
+//! A client receives them as it would any notification, after `LISTEN orders`.  So can a
+//! background worker, with [`Channel::subscribe()`]:
+//!
+//! ```rust,no_run
+//! # use pgx::notify::Channel;
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct Order { id: i64, total: f64 }
+//! # static ORDERS: Channel<Order> = Channel::new("orders");
+//! use pgx::bgworkers::{BackgroundWorker, SignalWakeFlags};
+//! use pgx::prelude::*;
+//!
+//! #[pg_guard]
+//! #[no_mangle]
+//! pub extern "C" fn order_worker(_arg: pg_sys::Datum) {
+//!     BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+//!     BackgroundWorker::connect_worker_to_spi(Some("shop"), None);
+//!
+//!     let mut orders = ORDERS.subscribe();
+//!     while BackgroundWorker::wait_latch(None) {
+//!         for order in orders.pending() {
+//!             match order {
+//!                 Ok(order) => log!("order {} came to {}", order.id, order.total),
+//!                 Err(e) => warning!("{}", e),
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`pg_notify_channel!`](crate::pg_notify_channel) declares a channel and documents it in the
+//! extension's schema, so a DBA can find out what it sends.
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::{pg_guard, pg_sys};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_int};
+
+/// The longest payload a notification can have, in bytes, which is one less than Postgres'
+/// `NOTIFY_PAYLOAD_MAX_LENGTH`, as that counts the trailing nul
+pub const MAX_PAYLOAD_LEN: usize = (pg_sys::BLCKSZ - pg_sys::NAMEDATALEN - 128) as usize - 1;
+
+/// The longest name a channel can have, in bytes
+pub const MAX_CHANNEL_LEN: usize = pg_sys::NAMEDATALEN as usize - 1;
+
+/// Why a notification couldn't be sent
+#[derive(thiserror::Error, Debug)]
+pub enum NotifyError {
+    #[error(
+        "\"{0}\" isn't a valid channel name:  it must be 1 to 63 bytes, without any nul bytes"
+    )]
+    InvalidChannel(String),
+    #[error(
+        "the payload for channel \"{channel}\" is {len} bytes, but can't be more than {}",
+        MAX_PAYLOAD_LEN
+    )]
+    PayloadTooLarge { channel: String, len: usize },
+    #[error("a payload can't contain nul bytes")]
+    NulInPayload,
+    #[error("couldn't encode the payload as JSON: {0}")]
+    Encode(#[from] serde_json::Error),
+}
+
+/// A notification whose payload couldn't be decoded
+#[derive(thiserror::Error, Debug)]
+#[error("backend {sender_pid} sent a malformed payload on channel \"{channel}\": {error}")]
+pub struct MalformedPayload {
+    pub channel: String,
+    pub payload: String,
+    pub sender_pid: i32,
+    #[source]
+    pub error: serde_json::Error,
+}
+
+/// Send a notification, like `pg_notify(channel, payload)`, when the current transaction commits
+pub fn notify(channel: &str, payload: &str) -> Result<(), NotifyError> {
+    let channel_name = channel_name(channel)?;
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(NotifyError::PayloadTooLarge {
+            channel: channel.to_string(),
+            len: payload.len(),
+        });
+    }
+    let payload = CString::new(payload).map_err(|_| NotifyError::NulInPayload)?;
+    unsafe { pg_sys::Async_Notify(channel_name.as_ptr(), payload.as_ptr()) }
+    Ok(())
+}
+
+fn channel_name(channel: &str) -> Result<CString, NotifyError> {
+    match CString::new(channel) {
+        Ok(name) if !channel.is_empty() && channel.len() <= MAX_CHANNEL_LEN => Ok(name),
+        _ => Err(NotifyError::InvalidChannel(channel.to_string())),
+    }
+}
+
+/// A channel whose notifications carry a `T`, as JSON
+pub struct Channel<T> {
+    name: &'static str,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T> Channel<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Channel { name, _marker: PhantomData }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T: Serialize> Channel<T> {
+    /// Send `value` when the current transaction commits
+    pub fn notify(&self, value: &T) -> Result<(), NotifyError> {
+        notify(self.name, &serde_json::to_string(value)?)
+    }
+}
+
+impl<T: DeserializeOwned> Channel<T> {
+    /// Listen on the channel from this background worker, and receive its notifications from the
+    /// returned [`Subscription`]
+    ///
+    /// Outside of a transaction, this starts and commits one of its own, so the worker is
+    /// listening when it returns.  Inside of one, it's listening once that transaction commits.
+    ///
+    /// # Panics
+    ///
+    /// If this backend isn't a background worker connected to a database.  The worker mustn't send
+    /// anything else to its (non-existent) frontend, as it would after
+    /// `pq_redirect_to_shm_mq()`.
+    pub fn subscribe(&self) -> Subscription<T> {
+        unsafe {
+            assert!(
+                pg_sys::IsBackgroundWorker && pg_sys::MyDatabaseId != pg_sys::InvalidOid,
+                "only background workers connected to a database can subscribe to a channel"
+            );
+            receive_notifications();
+        }
+
+        let channel_name = channel_name(self.name).unwrap_or_else(|e| panic!("{}", e));
+        if unsafe { pg_sys::IsTransactionState() } {
+            unsafe { pg_sys::Async_Listen(channel_name.as_ptr()) }
+        } else {
+            crate::bgworkers::BackgroundWorker::transaction(|| unsafe {
+                pg_sys::Async_Listen(channel_name.as_ptr())
+            })
+        }
+        Subscription { channel: self.name, _marker: PhantomData }
+    }
+}
+
+/// The notifications a background worker receives on a [`Channel`]
+///
+/// They arrive while the worker waits on its latch, which they set.  Dropping the subscription
+/// doesn't stop the worker listening, which it does until it exits.  It should keep up, as
+/// Postgres can't remove notifications from its queue until every listener has read them.
+pub struct Subscription<T> {
+    channel: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Subscription<T> {
+    /// The notifications that have arrived, in the order they were sent, with those whose payload
+    /// doesn't decode as a `T` as errors
+    ///
+    /// Like reading any notification, this has to happen outside of a transaction, and is
+    /// otherwise left for the next call.
+    pub fn pending(&mut self) -> impl Iterator<Item = Result<T, MalformedPayload>> + '_ {
+        unsafe {
+            #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13", feature = "pg14"))]
+            pg_sys::ProcessNotifyInterrupt();
+            #[cfg(feature = "pg15")]
+            pg_sys::ProcessNotifyInterrupt(false);
+        }
+
+        let received = RECEIVED.with(|received| {
+            let mut received = received.borrow_mut();
+            let (ours, others) =
+                received.drain(..).partition::<VecDeque<_>, _>(|n| n.channel == self.channel);
+            *received = others;
+            ours
+        });
+        received.into_iter().map(|notification| {
+            serde_json::from_str(&notification.payload).map_err(|error| MalformedPayload {
+                channel: notification.channel,
+                payload: notification.payload,
+                sender_pid: notification.sender_pid,
+                error,
+            })
+        })
+    }
+
+    pub fn channel(&self) -> &'static str {
+        self.channel
+    }
+}
+
+struct Received {
+    sender_pid: i32,
+    channel: String,
+    payload: String,
+}
+
+thread_local! {
+    /// Notifications read from the queue, for whichever [`Subscription`] is for their channel
+    static RECEIVED: RefCell<VecDeque<Received>> = RefCell::new(VecDeque::new());
+}
+
+/// Postgres sends the notifications a backend reads to its frontend, if it has one, so a background
+/// worker pretends it does, whose messages are kept in [`RECEIVED`]
+static FRONTEND: pg_sys::PQcommMethods = pg_sys::PQcommMethods {
+    comm_reset: Some(frontend_noop),
+    flush: Some(frontend_flush),
+    flush_if_writable: Some(frontend_flush),
+    is_send_pending: Some(frontend_is_send_pending),
+    putmessage: Some(frontend_putmessage),
+    putmessage_noblock: Some(frontend_putmessage_noblock),
+    #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+    startcopyout: Some(frontend_noop),
+    #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+    endcopyout: Some(frontend_endcopyout),
+};
+
+unsafe fn receive_notifications() {
+    if !std::ptr::eq(pg_sys::PqCommMethods, &FRONTEND) {
+        pg_sys::PqCommMethods = &FRONTEND;
+        pg_sys::whereToSendOutput = pg_sys::CommandDest_DestRemote;
+        // before 14, the payload is left out for frontends older than protocol 2
+        #[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+        if pg_sys::FrontendProtocol == 0 {
+            pg_sys::FrontendProtocol = 3 << 16;
+        }
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn frontend_putmessage(msgtype: c_char, s: *const c_char, len: usize) -> c_int {
+    // a NotificationResponse is the sender's pid, then the channel and payload as C strings
+    if msgtype as u8 == b'A' && len > 4 {
+        let message = std::slice::from_raw_parts(s as *const u8, len);
+        let (pid, rest) = message.split_at(4);
+        let mut strings = rest.split_inclusive(|&b| b == 0).map(CStr::from_bytes_with_nul);
+        if let (Some(Ok(channel)), Some(Ok(payload))) = (strings.next(), strings.next()) {
+            let notification = Received {
+                sender_pid: i32::from_be_bytes(pid.try_into().unwrap()),
+                channel: channel.to_string_lossy().into_owned(),
+                payload: payload.to_string_lossy().into_owned(),
+            };
+            RECEIVED.with(|received| received.borrow_mut().push_back(notification));
+        }
+    }
+    0
+}
+
+#[pg_guard]
+unsafe extern "C" fn frontend_putmessage_noblock(msgtype: c_char, s: *const c_char, len: usize) {
+    frontend_putmessage(msgtype, s, len);
+}
+
+#[pg_guard]
+unsafe extern "C" fn frontend_flush() -> c_int {
+    0
+}
+
+#[pg_guard]
+unsafe extern "C" fn frontend_is_send_pending() -> bool {
+    false
+}
+
+#[pg_guard]
+unsafe extern "C" fn frontend_noop() {}
+
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+#[pg_guard]
+unsafe extern "C" fn frontend_endcopyout(_error_abort: bool) {}
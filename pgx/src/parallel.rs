@@ -0,0 +1,125 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Checks that functions declared `#[pg_extern(parallel_safe)]` really are.
+//!
+//! Postgres runs `PARALLEL SAFE` functions in parallel workers, where anything that writes to the
+//! database, advances a sequence, or sends a notification raises an `ERROR` that says what was
+//! attempted, but not which function is to blame.
+//!
+//! When an extension is built with debug assertions, the wrapper `#[pg_extern(parallel_safe)]`
+//! generates records which function is running, and pgx APIs that do something forbidden in a
+//! parallel worker then raise an `ERROR` that names it.  Release builds skip the bookkeeping, and
+//! get Postgres' own errors.
+use crate::pg_sys;
+use crate::pg_sys::errcodes::PgSqlErrorCode;
+use crate::pg_sys::panic::{CaughtError, ErrorReport};
+use crate::pg_sys::PgTryBuilder;
+use std::cell::Cell;
+use std::panic::AssertUnwindSafe;
+
+thread_local! {
+    static PARALLEL_SAFE_FUNCTION: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Is this backend a parallel worker?  This is Postgres' `IsParallelWorker()`.
+#[inline]
+pub fn is_parallel_worker() -> bool {
+    // SAFETY: a plain integer that's only ever written by this backend
+    unsafe { pg_sys::ParallelWorkerNumber >= 0 }
+}
+
+/// The name of the innermost `#[pg_extern(parallel_safe)]` function running in this backend, if
+/// the extension was built with debug assertions
+pub fn current_parallel_safe_function() -> Option<&'static str> {
+    PARALLEL_SAFE_FUNCTION.with(|function| function.get())
+}
+
+/// Records that the `#[pg_extern(parallel_safe)]` function `name` is running until it's dropped.
+/// Not public API.
+#[doc(hidden)]
+pub struct ParallelSafeFunction {
+    previous: Option<&'static str>,
+}
+
+impl ParallelSafeFunction {
+    pub fn enter(name: &'static str) -> Self {
+        Self { previous: PARALLEL_SAFE_FUNCTION.with(|function| function.replace(Some(name))) }
+    }
+}
+
+impl Drop for ParallelSafeFunction {
+    fn drop(&mut self) {
+        PARALLEL_SAFE_FUNCTION.with(|function| function.set(self.previous));
+    }
+}
+
+/// Raises an `ERROR` if a `#[pg_extern(parallel_safe)]` function running in a parallel worker
+/// is about to `operation`, such as "send a notification".
+///
+/// pgx APIs that can't work in a parallel worker call this before doing anything.  It does
+/// nothing outside of parallel workers, or when no `parallel_safe` function is known to be
+/// running.
+#[track_caller]
+pub fn check_parallel_operation(operation: &str) {
+    if let Some(function) = parallel_safe_function_in_worker() {
+        forbidden(function, operation, None).report(crate::PgLogLevel::ERROR);
+    }
+}
+
+/// Runs `f`, which performs the SPI `query`, and replaces the error Postgres raises if it's not
+/// allowed in a parallel worker with one naming the `parallel_safe` function that ran it
+pub(crate) fn guard_spi<R>(query: impl FnOnce() -> Option<String>, f: impl FnOnce() -> R) -> R {
+    let function = match parallel_safe_function_in_worker() {
+        Some(function) => function,
+        None => return f(),
+    };
+
+    let query = query();
+    PgTryBuilder::new(AssertUnwindSafe(f))
+        .catch_when(PgSqlErrorCode::ERRCODE_INVALID_TRANSACTION_STATE, |error| {
+            let message = match &error {
+                CaughtError::PostgresError(ereport)
+                | CaughtError::ErrorReport(ereport)
+                | CaughtError::RustPanic { ereport, .. } => ereport.message().to_string(),
+            };
+            let operation = match &query {
+                Some(query) => format!("run the SPI query `{}`", query),
+                None => "run an SPI query".to_string(),
+            };
+            forbidden(function, &operation, Some(message)).report(crate::PgLogLevel::ERROR);
+            unreachable!()
+        })
+        .execute()
+}
+
+fn parallel_safe_function_in_worker() -> Option<&'static str> {
+    if is_parallel_worker() {
+        current_parallel_safe_function()
+    } else {
+        None
+    }
+}
+
+#[track_caller]
+fn forbidden(function: &'static str, operation: &str, cause: Option<String>) -> ErrorReport {
+    let report = ErrorReport::new(
+        PgSqlErrorCode::ERRCODE_INVALID_TRANSACTION_STATE,
+        format!(
+            "function `{}` is declared `parallel_safe`, but tried to {} in a parallel worker",
+            function, operation
+        ),
+        function,
+    )
+    .set_hint("declare it `parallel_restricted` or `parallel_unsafe` instead");
+    match cause {
+        Some(cause) => report.set_detail(cause),
+        None => report,
+    }
+}
@@ -0,0 +1,334 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Where the server keeps its files, and reading and writing files as the server does
+//!
+//! The directories are resolved the way the server resolves them, so a relative `log_directory`
+//! is under the data directory, which is also what other relative paths are relative to, as it's
+//! the backend's working directory:
+//!
+//! ```rust,no_run
+//! use pgx::paths;
+//! use pgx::prelude::*;
+//!
+//! #[pg_extern]
+//! fn dump_diagnostics(report: &str) -> Result<String, paths::FileAccessError> {
+//!     let path = paths::log_dir().join("myext-diagnostics.txt");
+//!     paths::write_file_durable(&path, report.as_bytes())?;
+//!     Ok(path.display().to_string())
+//! }
+//! ```
+//!
+//! [`read_file()`] and [`write_file_durable()`] check paths as `pg_read_binary_file()` and
+//! adminpack's `pg_file_write()` do:  a role without the privileges of `pg_read_server_files` or
+//! `pg_write_server_files`, which a superuser has, can only use paths in the data directory or an
+//! absolute `log_directory`.  Their errors are [`ErrorReportable`](crate::ErrorReportable), with the
+//! `SQLSTATE`s the server uses.
+use crate::pg_sys::panic::ErrorReport;
+use crate::{pg_sys, PgLogLevel, PgSqlErrorCode};
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// The most [`pg_sys::FileRead()`] and [`pg_sys::FileWrite()`] are asked to do at once
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Why a file couldn't be read or written
+#[derive(thiserror::Error, Debug)]
+pub enum FileAccessError {
+    #[error("absolute path not allowed")]
+    AbsolutePath(PathBuf),
+    #[error("reference to parent directory (\"..\") not allowed")]
+    ParentReference(PathBuf),
+    #[error("path must be in or below the data directory")]
+    OutsideDataDir(PathBuf),
+    #[error("path \"{}\" contains a null byte", .0.display())]
+    NulByte(PathBuf),
+    #[error("could not open file \"{}\": {source}", path.display())]
+    Open { path: PathBuf, source: io::Error },
+    #[error("could not read file \"{}\": {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("could not write file \"{}\": {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+    #[error("could not fsync file \"{}\": {source}", path.display())]
+    Sync { path: PathBuf, source: io::Error },
+}
+
+impl crate::ErrorReportable for FileAccessError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            FileAccessError::AbsolutePath(_)
+            | FileAccessError::ParentReference(_)
+            | FileAccessError::OutsideDataDir(_) => PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE,
+            FileAccessError::NulByte(_) => PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            FileAccessError::Open { source, .. }
+            | FileAccessError::Read { source, .. }
+            | FileAccessError::Write { source, .. }
+            | FileAccessError::Sync { source, .. } => errcode_for_file_access(source),
+        }
+    }
+}
+
+/// The `SQLSTATE` of `error`, as the server's `errcode_for_file_access()` gives it
+fn errcode_for_file_access(error: &io::Error) -> PgSqlErrorCode {
+    match error.raw_os_error() {
+        Some(libc::EPERM | libc::EACCES | libc::EROFS) => {
+            PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE
+        }
+        Some(libc::ENOENT) => PgSqlErrorCode::ERRCODE_UNDEFINED_FILE,
+        Some(libc::EEXIST) => PgSqlErrorCode::ERRCODE_DUPLICATE_FILE,
+        Some(libc::ENOTDIR | libc::EISDIR | libc::ENOTEMPTY) => {
+            PgSqlErrorCode::ERRCODE_WRONG_OBJECT_TYPE
+        }
+        Some(libc::ENOSPC) => PgSqlErrorCode::ERRCODE_DISK_FULL,
+        Some(libc::ENFILE | libc::EMFILE) => PgSqlErrorCode::ERRCODE_INSUFFICIENT_RESOURCES,
+        Some(libc::EIO) => PgSqlErrorCode::ERRCODE_IO_ERROR,
+        Some(libc::ENAMETOOLONG) => PgSqlErrorCode::ERRCODE_NAME_TOO_LONG,
+        _ => PgSqlErrorCode::ERRCODE_INTERNAL_ERROR,
+    }
+}
+
+/// The data directory, `data_directory`
+pub fn data_dir() -> PathBuf {
+    unsafe {
+        // SAFETY:  the postmaster sets `DataDir` before any backend starts
+        path_from_cstr(CStr::from_ptr(pg_sys::DataDir))
+    }
+}
+
+/// The directory the server's log files are written to, `log_directory`, which is in the data
+/// directory unless it's an absolute path
+pub fn log_dir() -> PathBuf {
+    let log_directory = unsafe {
+        // SAFETY:  `log_directory` is a built-in setting, so it's never NULL
+        let value = pg_sys::GetConfigOption(b"log_directory\0".as_ptr().cast(), false, false);
+        path_from_cstr(CStr::from_ptr(value))
+    };
+    data_dir().join(log_directory)
+}
+
+/// The directory temporary files are made in when `temp_tablespaces` is empty, which is in the
+/// default tablespace
+pub fn tmp_dir() -> PathBuf {
+    let mut path = [0 as std::os::raw::c_char; pg_sys::MAXPGPATH as usize];
+    let tmp = unsafe {
+        // SAFETY:  `path` is the `MAXPGPATH` bytes `TempTablespacePath()` may write
+        pg_sys::TempTablespacePath(path.as_mut_ptr(), pg_sys::DEFAULTTABLESPACE_OID);
+        path_from_cstr(CStr::from_ptr(path.as_ptr()))
+    };
+    data_dir().join(tmp)
+}
+
+/// The directory extensions' control and SQL files are installed in, `$sharedir/extension`
+pub fn extension_share_dir() -> PathBuf {
+    let mut path = [0 as std::os::raw::c_char; pg_sys::MAXPGPATH as usize];
+    unsafe {
+        // SAFETY:  `my_exec_path` is set when the server starts, and `path` is the `MAXPGPATH`
+        // bytes `get_share_path()` may write
+        pg_sys::get_share_path(pg_sys::my_exec_path.as_ptr(), path.as_mut_ptr());
+        path_from_cstr(CStr::from_ptr(path.as_ptr())).join("extension")
+    }
+}
+
+/// Check the current user can read `path`, returning it canonicalized, as the server would
+/// before reading it for `pg_read_binary_file()`
+pub fn check_read_path(path: impl AsRef<Path>) -> Result<PathBuf, FileAccessError> {
+    check_path(path.as_ref(), READ_SERVER_FILES)
+}
+
+/// Check the current user can write `path`, returning it canonicalized, as adminpack would before
+/// writing it for `pg_file_write()`
+pub fn check_write_path(path: impl AsRef<Path>) -> Result<PathBuf, FileAccessError> {
+    check_path(path.as_ref(), WRITE_SERVER_FILES)
+}
+
+/// Read all of the file at `path`, if the current user can, per [`check_read_path()`]
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<u8>, FileAccessError> {
+    let path = check_read_path(path)?;
+    let file = Vfd::open(&path, pg_sys::O_RDONLY | pg_sys::PG_BINARY)
+        .map_err(|source| FileAccessError::Open { path: path.clone(), source })?;
+
+    let mut contents = Vec::new();
+    let mut chunk = vec![0_u8; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut chunk, contents.len());
+        if read < 0 {
+            let source = io::Error::last_os_error();
+            return Err(FileAccessError::Read { path, source });
+        }
+        if read == 0 {
+            return Ok(contents);
+        }
+        contents.extend_from_slice(&chunk[..read as usize]);
+    }
+}
+
+/// Replace the file at `path` with `contents`, if the current user can, per
+/// [`check_write_path()`], so that it's either the old file or all of `contents` after a crash
+///
+/// As the server does with its own files, `contents` is written to a temporary file beside `path`,
+/// which is fsync'd, and then renamed over `path` with `durable_rename()`, which also fsyncs the
+/// directory.  Like the server, a failed fsync is a `PANIC` unless `data_sync_retry` is on, and a
+/// failed rename is an `ERROR`.
+pub fn write_file_durable(path: impl AsRef<Path>, contents: &[u8]) -> Result<(), FileAccessError> {
+    let path = check_write_path(path)?;
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(format!(".{}.tmp", unsafe { pg_sys::MyProcPid }));
+    let tmp = PathBuf::from(tmp);
+
+    if let Err(e) = write_and_sync(&tmp, contents) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    let from = c_path(&tmp)?;
+    let to = c_path(&path)?;
+    unsafe {
+        // SAFETY:  both are NUL-terminated paths, and at `ERROR`, it doesn't return if it fails
+        pg_sys::durable_rename(from.as_ptr(), to.as_ptr(), pg_sys::ERROR as c_int);
+    }
+    Ok(())
+}
+
+fn write_and_sync(path: &Path, contents: &[u8]) -> Result<(), FileAccessError> {
+    let file =
+        Vfd::open(path, pg_sys::O_WRONLY | pg_sys::O_CREAT | pg_sys::O_TRUNC | pg_sys::PG_BINARY)
+            .map_err(|source| FileAccessError::Open { path: path.into(), source })?;
+
+    let mut offset = 0;
+    for chunk in contents.chunks(CHUNK_SIZE) {
+        let written = file.write(chunk, offset);
+        if written != chunk.len() as c_int {
+            // a short write without an error is the disk being full, as the server assumes
+            let source = match written {
+                -1 => io::Error::last_os_error(),
+                _ => io::Error::from_raw_os_error(libc::ENOSPC),
+            };
+            return Err(FileAccessError::Write { path: path.into(), source });
+        }
+        offset += chunk.len();
+    }
+
+    if unsafe { pg_sys::FileSync(file.0, 0) } != 0 {
+        let error = FileAccessError::Sync { path: path.into(), source: io::Error::last_os_error() };
+        // the kernel may have dropped the pages it couldn't write, so the server won't retry
+        let level = unsafe { pg_sys::data_sync_elevel(pg_sys::ERROR as c_int) };
+        if level != pg_sys::ERROR as c_int {
+            let code = crate::ErrorReportable::sql_error_code(&error);
+            ErrorReport::new(code, error.to_string(), "write_file_durable")
+                .report(PgLogLevel::from(level));
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Canonicalize `path` and check it, as `convert_and_check_filename()` does, for a role without
+/// the privileges of `role`
+fn check_path(path: &Path, role: pg_sys::Oid) -> Result<PathBuf, FileAccessError> {
+    let mut bytes = c_path(path)?.into_bytes_with_nul();
+    unsafe {
+        // SAFETY:  `bytes` is NUL-terminated, and `canonicalize_path()` only ever shortens it
+        pg_sys::canonicalize_path(bytes.as_mut_ptr().cast());
+    }
+    bytes.truncate(bytes.iter().position(|&b| b == 0).expect("a NUL-terminated path"));
+    let path = CString::new(bytes).expect("a path without NULs");
+    let canonical = path_from_cstr(&path);
+
+    unsafe {
+        // SAFETY:  the paths are NUL-terminated, and `DataDir` is set
+        if pg_sys::has_privs_of_role(pg_sys::GetUserId(), role) {
+            return Ok(canonical);
+        }
+        if canonical.is_absolute() {
+            if pg_sys::path_contains_parent_reference(path.as_ptr()) {
+                return Err(FileAccessError::ParentReference(canonical));
+            }
+            let log_dir = log_dir();
+            let in_log_dir = log_dir.is_absolute()
+                && pg_sys::path_is_prefix_of_path(c_path(&log_dir)?.as_ptr(), path.as_ptr());
+            if !pg_sys::path_is_prefix_of_path(pg_sys::DataDir, path.as_ptr()) && !in_log_dir {
+                return Err(FileAccessError::AbsolutePath(canonical));
+            }
+        } else if !pg_sys::path_is_relative_and_below_cwd(path.as_ptr()) {
+            return Err(FileAccessError::OutsideDataDir(canonical));
+        }
+    }
+    Ok(canonical)
+}
+
+// SAFETY:  they're the oids of the built-in `pg_read_server_files` and `pg_write_server_files` roles
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+const READ_SERVER_FILES: pg_sys::Oid =
+    unsafe { pg_sys::Oid::from_u32_unchecked(pg_sys::DEFAULT_ROLE_READ_SERVER_FILES) };
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+const WRITE_SERVER_FILES: pg_sys::Oid =
+    unsafe { pg_sys::Oid::from_u32_unchecked(pg_sys::DEFAULT_ROLE_WRITE_SERVER_FILES) };
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+const READ_SERVER_FILES: pg_sys::Oid =
+    unsafe { pg_sys::Oid::from_u32_unchecked(pg_sys::ROLE_PG_READ_SERVER_FILES) };
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+const WRITE_SERVER_FILES: pg_sys::Oid =
+    unsafe { pg_sys::Oid::from_u32_unchecked(pg_sys::ROLE_PG_WRITE_SERVER_FILES) };
+
+fn c_path(path: &Path) -> Result<CString, FileAccessError> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| FileAccessError::NulByte(path.to_path_buf()))
+}
+
+fn path_from_cstr(path: &CStr) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(path.to_bytes()))
+}
+
+/// A file opened with the server's virtual file descriptors, which is closed when dropped
+struct Vfd(pg_sys::File);
+
+impl Vfd {
+    fn open(path: &Path, flags: u32) -> io::Result<Self> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        match unsafe { pg_sys::PathNameOpenFile(path.as_ptr(), flags as c_int) } {
+            file if file < 0 => Err(io::Error::last_os_error()),
+            file => Ok(Vfd(file)),
+        }
+    }
+
+    #[cfg(feature = "pg11")]
+    fn read(&self, buf: &mut [u8], _offset: usize) -> c_int {
+        unsafe { pg_sys::FileRead(self.0, buf.as_mut_ptr().cast(), buf.len() as c_int, 0) }
+    }
+
+    #[cfg(not(feature = "pg11"))]
+    fn read(&self, buf: &mut [u8], offset: usize) -> c_int {
+        unsafe {
+            pg_sys::FileRead(self.0, buf.as_mut_ptr().cast(), buf.len() as c_int, offset as _, 0)
+        }
+    }
+
+    #[cfg(feature = "pg11")]
+    fn write(&self, buf: &[u8], _offset: usize) -> c_int {
+        // it only reads `buf`, despite its `char *`
+        unsafe { pg_sys::FileWrite(self.0, buf.as_ptr() as *mut _, buf.len() as c_int, 0) }
+    }
+
+    #[cfg(not(feature = "pg11"))]
+    fn write(&self, buf: &[u8], offset: usize) -> c_int {
+        // it only reads `buf`, despite its `char *`
+        unsafe {
+            pg_sys::FileWrite(self.0, buf.as_ptr() as *mut _, buf.len() as c_int, offset as _, 0)
+        }
+    }
+}
+
+impl Drop for Vfd {
+    fn drop(&mut self) {
+        unsafe { pg_sys::FileClose(self.0) }
+    }
+}
@@ -9,7 +9,7 @@ pub use crate::pg_module_magic;
 pub use crate::{default, name};
 
 // Needed for variant RETURNS
-pub use crate::iter::{SetOfIterator, TableIterator};
+pub use crate::iter::{ArrayFromIterator, SetOfIterator, TableIterator};
 
 // Needed for complex returns and Triggers
 pub use crate::heap_tuple::{PgHeapTuple, PgHeapTupleError};
@@ -18,11 +18,11 @@ pub use crate::pgbox::{AllocatedByPostgres, AllocatedByRust, PgBox, WhoAllocated
 // These could be factored into a temporal type module that could be easily imported for code which works with them.
 // However, reexporting them seems fine for now.
 pub use crate::datum::{
-    AnyNumeric, Array, Date, FromDatum, IntoDatum, Numeric, PgVarlena, PostgresType, Range,
-    RangeData, RangeSubType, Time, TimeWithTimeZone, Timestamp, TimestampWithTimeZone,
+    AnyNumeric, Array, Date, FromDatum, Interval, IntoDatum, Numeric, PgVarlena, PostgresType,
+    Range, RangeData, RangeSubType, Time, TimeWithTimeZone, Timestamp, TimestampWithTimeZone,
     VariadicArray,
 };
-pub use crate::inoutfuncs::{InOutFuncs, JsonInOutFuncs, PgVarlenaInOutFuncs};
+pub use crate::inoutfuncs::{InOutFuncs, JsonInOutFuncs, PgVarlenaInOutFuncs, SendRecvFuncs};
 
 // Trigger support
 pub use crate::trigger_support::{
@@ -30,7 +30,7 @@ pub use crate::trigger_support::{
 };
 
 // Aggregate support
-pub use crate::aggregate::{Aggregate, FinalizeModify, ParallelOption};
+pub use crate::aggregate::{Aggregate, AggregateInternalState, FinalizeModify, ParallelOption};
 
 pub use crate::pg_sys::oids::PgOid;
 pub use crate::pg_sys::pg_try::PgTryBuilder;
@@ -0,0 +1,156 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Quote identifiers and literals for building dynamic SQL, exactly as Postgres itself does
+//!
+//! These all call the server's own quoting functions, so keywords, embedded quotes, backslashes
+//! and non-ASCII characters are handled the same way `quote_ident()` and `quote_literal()` handle
+//! them from SQL.
+use crate::pg_sys;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+/// Quote `ident` for use as a SQL identifier, like the `quote_ident()` SQL function.  It's only
+/// put in double quotes if it needs to be.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use pgx::quote_identifier;
+///
+/// assert_eq!(quote_identifier("users"), "users");
+/// assert_eq!(quote_identifier("select"), r#""select""#);
+/// assert_eq!(quote_identifier(r#"My "Table""#), r#""My ""Table""""#);
+/// ```
+///
+/// ## Panics
+///
+/// If `ident` contains a NULL byte
+pub fn quote_identifier(ident: &str) -> String {
+    let ident = to_cstring(ident);
+    // SAFETY:  quote_identifier() returns either its argument or a palloc'd copy, so we can't pfree
+    // what it returns, and must copy it before `ident` is dropped
+    unsafe { from_cstr(pg_sys::quote_identifier(ident.as_ptr()), false) }
+}
+
+/// Quote `schema` and `name` as a schema-qualified identifier, `schema.name`, quoting each as
+/// [`quote_identifier()`] does.
+///
+/// ## Panics
+///
+/// If `schema` or `name` contain a NULL byte
+pub fn quote_qualified_identifier(schema: &str, name: &str) -> String {
+    let schema = to_cstring(schema);
+    let name = to_cstring(name);
+    // SAFETY:  quote_qualified_identifier() always returns a palloc'd string
+    unsafe { from_cstr(pg_sys::quote_qualified_identifier(schema.as_ptr(), name.as_ptr()), true) }
+}
+
+/// Quote `literal` as a SQL string literal, like the `quote_literal()` SQL function.  Strings with
+/// backslashes are written as `E'...'` literals, so they're read back the same whatever the value
+/// of `standard_conforming_strings`.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use pgx::quote_literal;
+///
+/// assert_eq!(quote_literal("it's"), "'it''s'");
+/// assert_eq!(quote_literal(r"C:\temp"), r"E'C:\\temp'");
+/// ```
+///
+/// ## Panics
+///
+/// If `literal` contains a NULL byte
+pub fn quote_literal(literal: &str) -> String {
+    let literal = to_cstring(literal);
+    // SAFETY:  quote_literal_cstr() always returns a palloc'd string
+    unsafe { from_cstr(pg_sys::quote_literal_cstr(literal.as_ptr()), true) }
+}
+
+/// Build a SQL string from a format string whose `{ident}` placeholders are replaced, in order,
+/// by the arguments quoted with [`quote_identifier()`], and whose `{literal}` placeholders by the
+/// arguments quoted with [`quote_literal()`].  Write `{{` and `}}` for literal braces.
+///
+/// The arguments can be anything that implements [`Display`](std::fmt::Display).  There's no
+/// placeholder that inserts an argument without quoting it.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use pgx::format_sql;
+///
+/// let table = "Robert'); DROP TABLE students;--";
+/// let sql = format_sql!("SELECT * FROM {ident}.{ident} WHERE name = {literal}", "public", table, "O'Brien");
+/// assert_eq!(
+///     sql,
+///     r#"SELECT * FROM public."Robert'); DROP TABLE students;--" WHERE name = 'O''Brien'"#
+/// );
+/// ```
+///
+/// ## Panics
+///
+/// If the format string has a placeholder other than `{ident}` or `{literal}`, an unmatched brace,
+/// or not exactly as many placeholders as there are arguments.  Also if an argument contains a
+/// NULL byte.
+#[macro_export]
+macro_rules! format_sql {
+    ($fmt:expr $(, $arg:expr)* $(,)?) => {
+        $crate::quote::__format_sql($fmt, &[$(::std::string::ToString::to_string(&$arg)),*])
+    };
+}
+
+#[doc(hidden)]
+pub fn __format_sql(fmt: &str, args: &[String]) -> String {
+    let mut sql = String::with_capacity(fmt.len());
+    let mut args = args.iter();
+    let mut rest = fmt;
+    while let Some(at) = rest.find(|c| c == '{' || c == '}') {
+        sql.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            sql.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let quote: fn(&str) -> String = if rest.starts_with("{ident}") {
+            quote_identifier
+        } else if rest.starts_with("{literal}") {
+            quote_literal
+        } else {
+            panic!(
+                "format_sql!() only supports `{{ident}}` and `{{literal}}` placeholders: {}",
+                fmt
+            )
+        };
+        let arg =
+            args.next().unwrap_or_else(|| panic!("format_sql!() has too few arguments: {}", fmt));
+        sql.push_str(&quote(arg));
+        rest = &rest[rest.find('}').unwrap() + 1..];
+    }
+    sql.push_str(rest);
+
+    if args.next().is_some() {
+        panic!("format_sql!() has too many arguments: {}", fmt);
+    }
+    sql
+}
+
+fn to_cstring(s: &str) -> CString {
+    CString::new(s).expect("string to quote contained a null byte")
+}
+
+unsafe fn from_cstr(cstr: *const c_char, pfree: bool) -> String {
+    let s = CStr::from_ptr(cstr).to_str().expect("quoted string is not valid UTF8").to_string();
+    if pfree {
+        pg_sys::pfree(cstr as *mut _);
+    }
+    s
+}
@@ -0,0 +1,32 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Postgres' own random numbers, as returned by SQL's `random()`
+//!
+//! These share their state with `random()`, so they're repeatable after `setseed()` is called,
+//! or [`set_seed()`] is.  A function that uses them must be declared `VOLATILE`, which
+//! `cargo pgx schema --lint` checks.
+use crate::{direct_function_call, direct_function_call_as_datum, pg_sys, IntoDatum};
+
+/// A random number in the range `0.0 <= x < 1.0`, like SQL's `random()`
+pub fn pg_random() -> f64 {
+    unsafe { direct_function_call::<f64>(pg_sys::drandom, vec![]) }.expect("random() returned NULL")
+}
+
+/// Seed the numbers returned by [`pg_random()`] and SQL's `random()` for the rest of the
+/// session, like SQL's `setseed()`.
+///
+/// ## Panics
+///
+/// Raises an `ERROR` if `seed` is outside of the range `-1.0 <= seed <= 1.0`.
+pub fn set_seed(seed: f64) {
+    unsafe {
+        direct_function_call_as_datum(pg_sys::setseed, vec![seed.into_datum()]);
+    }
+}
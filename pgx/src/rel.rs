@@ -9,14 +9,89 @@ Use of this source code is governed by the MIT license that can be found in the
 
 //! Provides a safe wrapper around Postgres' `pg_sys::RelationData` struct
 use crate::{
-    direct_function_call, name_data_to_str, pg_sys, FromDatum, IntoDatum, PgBox, PgTupleDesc,
+    compat, direct_function_call, name_data_to_str, pg_sys, quote_identifier, spi, FromDatum,
+    IntoDatum, PgBox, PgBuiltInOids, PgHeapTuple, PgOid, PgTupleDesc, Spi, WhoAllocated,
 };
 use pgx_sql_entity_graph::metadata::{
     ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
 };
+use std::ffi::CStr;
 use std::ops::Deref;
 use std::os::raw::c_char;
 
+/// What happens to a temporary table created with [`PgRelation::create_temp()`] at the end of
+/// the transaction that created it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OnCommit {
+    /// `ON COMMIT DROP`:  the table is dropped
+    Drop,
+    /// `ON COMMIT DELETE ROWS`:  the table is truncated at the end of every transaction
+    DeleteRows,
+    /// `ON COMMIT PRESERVE ROWS`:  the table lives until the end of the session
+    PreserveRows,
+}
+
+impl OnCommit {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            OnCommit::Drop => "ON COMMIT DROP",
+            OnCommit::DeleteRows => "ON COMMIT DELETE ROWS",
+            OnCommit::PreserveRows => "ON COMMIT PRESERVE ROWS",
+        }
+    }
+}
+
+/// The kind of a relation, from its `pg_class.relkind`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RelKind {
+    Table,
+    Index,
+    Sequence,
+    ToastValue,
+    View,
+    MatView,
+    CompositeType,
+    ForeignTable,
+    /// A partitioned table, which holds no rows itself
+    PartitionedTable,
+    /// An index on a partitioned table, made of an index on each partition
+    PartitionedIndex,
+}
+
+impl RelKind {
+    fn from_relkind(relkind: c_char) -> Self {
+        match relkind as u8 {
+            pg_sys::RELKIND_RELATION => RelKind::Table,
+            pg_sys::RELKIND_INDEX => RelKind::Index,
+            pg_sys::RELKIND_SEQUENCE => RelKind::Sequence,
+            pg_sys::RELKIND_TOASTVALUE => RelKind::ToastValue,
+            pg_sys::RELKIND_VIEW => RelKind::View,
+            pg_sys::RELKIND_MATVIEW => RelKind::MatView,
+            pg_sys::RELKIND_COMPOSITE_TYPE => RelKind::CompositeType,
+            pg_sys::RELKIND_FOREIGN_TABLE => RelKind::ForeignTable,
+            pg_sys::RELKIND_PARTITIONED_TABLE => RelKind::PartitionedTable,
+            pg_sys::RELKIND_PARTITIONED_INDEX => RelKind::PartitionedIndex,
+            unknown => panic!("unrecognized relkind: {}", unknown as char),
+        }
+    }
+}
+
+/// How a partitioned table's rows are divided among its partitions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PartitionStrategy {
+    Hash,
+    List,
+    Range,
+}
+
+/// A partitioned table's partition key, from `pg_partitioned_table`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionKey {
+    pub strategy: PartitionStrategy,
+    /// The name of each column in the key, or `None` where the key has an expression instead
+    pub columns: Vec<Option<String>>,
+}
+
 pub struct PgRelation {
     boxed: PgBox<pg_sys::RelationData>,
     need_close: bool,
@@ -281,6 +356,28 @@ impl PgRelation {
         rd_rel.relkind == pg_sys::RELKIND_PARTITIONED_TABLE as c_char
     }
 
+    /// What kind of relation this is
+    pub fn relkind(&self) -> RelKind {
+        let rd_rel: &pg_sys::FormData_pg_class =
+            unsafe { self.boxed.rd_rel.as_ref().expect("rd_rel is NULL") };
+        RelKind::from_relkind(rd_rel.relkind)
+    }
+
+    /// Is this a partitioned table, which has partitions rather than any storage of its own?
+    ///
+    /// A partitioned index is partitioned too, but its partitions are the indexes of the table's
+    /// partitions, which [`PgRelation::partitions()`] doesn't return.
+    pub fn is_partitioned(&self) -> bool {
+        self.relkind() == RelKind::PartitionedTable
+    }
+
+    /// Is this relation a partition of a partitioned table or index?
+    pub fn is_partition(&self) -> bool {
+        let rd_rel: &pg_sys::FormData_pg_class =
+            unsafe { self.boxed.rd_rel.as_ref().expect("rd_rel is NULL") };
+        rd_rel.relispartition
+    }
+
     pub fn is_toast_value(&self) -> bool {
         let rd_rel: &pg_sys::FormData_pg_class =
             unsafe { self.boxed.rd_rel.as_ref().expect("rd_rel is NULL") };
@@ -292,6 +389,327 @@ impl PgRelation {
         self.need_close = true;
         self
     }
+
+    /// Create a temporary table named `name`, with `columns` of `(name, type, typmod)`, and open it
+    /// with an `AccessExclusiveLock`.  Use a typmod of `-1` for columns whose type has none.
+    ///
+    /// The table is created with `CREATE TEMP TABLE` through SPI rather than with
+    /// `pg_sys::heap_create_with_catalog()`, whose signature changes between Postgres versions and
+    /// which skips what the command does besides, like recording dependencies on the column types
+    /// and firing event triggers.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust,no_run
+    /// use pgx::prelude::*;
+    /// use pgx::rel::OnCommit;
+    ///
+    /// # fn scratch() -> Result<(), pgx::spi::Error> {
+    /// let scratch = PgRelation::create_temp(
+    ///     "scratch",
+    ///     &[("id", PgOid::from(pg_sys::INT8OID), -1), ("label", PgOid::from(pg_sys::VARCHAROID), 20)],
+    ///     OnCommit::Drop,
+    /// )?;
+    /// scratch.insert_rows(vec![vec![1i64.into_datum(), "one".into_datum()]])?;
+    /// scratch.create_index("scratch_id_idx", &["id"], true)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Panics
+    ///
+    /// If `name` or a column name contains a NULL byte
+    pub fn create_temp(
+        name: &str,
+        columns: &[(&str, PgOid, i32)],
+        on_commit: OnCommit,
+    ) -> spi::Result<PgRelation> {
+        let columns = columns
+            .iter()
+            .map(|(column, type_oid, typmod)| {
+                // SAFETY:  format_type_with_typemod() always returns a palloc'd, NULL-terminated string
+                let type_name = unsafe {
+                    let cstr = pg_sys::format_type_with_typemod(type_oid.value(), *typmod);
+                    let type_name = CStr::from_ptr(cstr).to_string_lossy().into_owned();
+                    pg_sys::pfree(cstr.cast());
+                    type_name
+                };
+                format!("{} {}", quote_identifier(column), type_name)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let name = quote_identifier(name);
+        Spi::run(&format!("CREATE TEMP TABLE {} ({}) {}", name, columns, on_commit.as_sql()))?;
+
+        // SAFETY:  we just created the table, so it exists, and we already hold the lock we ask for
+        unsafe {
+            let oid = direct_function_call::<pg_sys::Oid>(
+                pg_sys::to_regclass,
+                vec![format!("pg_temp.{}", name).into_datum()],
+            )
+            .expect("newly created temp table not found");
+            Ok(PgRelation::with_lock(oid, pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE))
+        }
+    }
+
+    /// Create an index named `name` on this relation's `columns`, and open it with an
+    /// `AccessExclusiveLock`.  The index is created in this relation's namespace, using the
+    /// default access method, which is normally btree.
+    ///
+    /// ## Panics
+    ///
+    /// If `name` or a column name contains a NULL byte
+    pub fn create_index(
+        &self,
+        name: &str,
+        columns: &[&str],
+        unique: bool,
+    ) -> spi::Result<PgRelation> {
+        let namespace = quote_identifier(self.namespace());
+        let name = quote_identifier(name);
+        Spi::run(&format!(
+            "CREATE {}INDEX {} ON {}.{} ({})",
+            if unique { "UNIQUE " } else { "" },
+            name,
+            namespace,
+            quote_identifier(self.name()),
+            columns.iter().map(|column| quote_identifier(column)).collect::<Vec<_>>().join(", ")
+        ))?;
+
+        // SAFETY:  we just created the index, so it exists, and we already hold the lock we ask for
+        unsafe {
+            let oid = direct_function_call::<pg_sys::Oid>(
+                pg_sys::to_regclass,
+                vec![format!("{}.{}", namespace, name).into_datum()],
+            )
+            .expect("newly created index not found");
+            Ok(PgRelation::with_lock(oid, pg_sys::AccessExclusiveLock as pg_sys::LOCKMODE))
+        }
+    }
+
+    /// Insert `rows` into this relation, returning how many were inserted.
+    ///
+    /// Each row holds one datum for each of the relation's (non-dropped) columns, in order, which
+    /// must be of the column's type.  The `INSERT` is planned once and executed for every row.
+    pub fn insert_rows<I: IntoIterator<Item = Vec<Option<pg_sys::Datum>>>>(
+        &self,
+        rows: I,
+    ) -> spi::Result<u64> {
+        let tupdesc = self.tuple_desc();
+        let types = tupdesc
+            .iter()
+            .filter(|attribute| !attribute.is_dropped())
+            .map(|attribute| attribute.type_oid())
+            .collect::<Vec<_>>();
+        let query = format!(
+            "INSERT INTO {}.{} VALUES ({})",
+            quote_identifier(self.namespace()),
+            quote_identifier(self.name()),
+            (1..=types.len()).map(|i| format!("${}", i)).collect::<Vec<_>>().join(", ")
+        );
+
+        Spi::connect(|mut client| {
+            let statement = client.prepare(&query, Some(types))?.keep();
+            let mut inserted = 0;
+            for row in rows {
+                client.update(&statement, None, Some(row))?;
+                inserted += 1;
+            }
+            Ok(inserted)
+        })
+    }
+
+    // `pg_sys::PartitionDesc` and `pg_sys::PartitionKey` are opaque in pgx's bindings, so
+    // the partitioning functions below read the catalogs through SPI instead, except for routing
+    // rows, which the executor's own `ExecFindPartition()` does
+
+    /// This partitioned relation's partitions, each opened with an `AccessShareLock`.  Partitions
+    /// that are partitioned themselves are returned as they are, not as their own partitions.
+    pub fn partitions(&self) -> spi::Result<Vec<PgRelation>> {
+        Spi::connect(|client| {
+            client
+                .select(
+                    "SELECT inhrelid FROM pg_inherits JOIN pg_class ON pg_class.oid = inhrelid
+                      WHERE inhparent = $1 AND relispartition AND relkind IN ('r', 'p', 'f')
+                      ORDER BY inhrelid",
+                    None,
+                    Some(vec![(PgBuiltInOids::OIDOID.oid(), self.oid().into_datum())]),
+                )?
+                .map(|row| {
+                    let oid = row.get::<pg_sys::Oid>(1)?.expect("inhrelid is NULL");
+                    // SAFETY:  the partition exists, and we're locking it as we open it
+                    Ok(unsafe {
+                        PgRelation::with_lock(oid, pg_sys::AccessShareLock as pg_sys::LOCKMODE)
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// This partitioned table's partition key, or `None` if it isn't a partitioned table
+    pub fn partition_key(&self) -> spi::Result<Option<PartitionKey>> {
+        let key = Spi::get_two_with_args::<String, Vec<i16>>(
+            "SELECT partstrat::text, partattrs::int2[] FROM pg_partitioned_table WHERE partrelid = $1",
+            vec![(PgBuiltInOids::OIDOID.oid(), self.oid().into_datum())],
+        );
+        let (strategy, attnums) = match key {
+            Ok((Some(strategy), Some(attnums))) => (strategy, attnums),
+            Ok(_) | Err(spi::Error::InvalidPosition) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let strategy = match strategy.as_str() {
+            "h" => PartitionStrategy::Hash,
+            "l" => PartitionStrategy::List,
+            "r" => PartitionStrategy::Range,
+            unknown => panic!("unrecognized partition strategy: {}", unknown),
+        };
+        let tupdesc = self.tuple_desc();
+        let columns = attnums
+            .into_iter()
+            .map(|attnum| match attnum {
+                0 => None,
+                attnum => tupdesc.get(attnum as usize - 1).map(|att| att.name().to_string()),
+            })
+            .collect();
+        Ok(Some(PartitionKey { strategy, columns }))
+    }
+
+    /// The partitioned table at the top of the partition tree this relation is in, opened with an
+    /// `AccessShareLock`, or `None` if this relation isn't a partition.
+    ///
+    /// A trigger that fires on a partition can use this to find the table the row was inserted
+    /// into, as with [`PgTrigger::root_relation()`](crate::PgTrigger::root_relation).
+    pub fn partition_root(&self) -> spi::Result<Option<PgRelation>> {
+        if !self.is_partition() {
+            return Ok(None);
+        }
+        let root = Spi::get_one_with_args::<pg_sys::Oid>(
+            "WITH RECURSIVE ancestors(relid, depth) AS (
+                 SELECT inhparent, 1 FROM pg_inherits WHERE inhrelid = $1
+                 UNION ALL
+                 SELECT inhparent, depth + 1
+                   FROM ancestors
+                   JOIN pg_class ON pg_class.oid = relid
+                   JOIN pg_inherits ON inhrelid = relid
+                  WHERE relispartition
+             )
+             SELECT relid FROM ancestors ORDER BY depth DESC LIMIT 1",
+            vec![(PgBuiltInOids::OIDOID.oid(), self.oid().into_datum())],
+        )?;
+        // SAFETY:  the root exists, and we're locking it as we open it
+        Ok(root.map(|oid| unsafe {
+            PgRelation::with_lock(oid, pg_sys::AccessShareLock as pg_sys::LOCKMODE)
+        }))
+    }
+
+    /// Find the leaf partition of this partitioned table that `tuple` belongs in, routing it with
+    /// `ExecFindPartition()` as Postgres does when inserting it into this table.  The leaf is
+    /// opened with a `RowExclusiveLock`, ready to insert into.
+    ///
+    /// `tuple` may be a row of this table or of any table in its partition tree, as its columns
+    /// are matched by name.
+    ///
+    /// # Panics
+    ///
+    /// If this isn't a partitioned table.  Like inserting the tuple would, this also raises an
+    /// ERROR if no partition accepts it.
+    pub fn find_partition_for<A: WhoAllocated>(&self, tuple: &PgHeapTuple<'_, A>) -> PgRelation {
+        assert!(self.is_partitioned(), "`{}` isn't a partitioned table", self.name());
+
+        // `tuple`'s columns may be in another order, so it's formed anew as a row of this table
+        let (mut values, mut nulls): (Vec<_>, Vec<_>) = self
+            .tuple_desc()
+            .iter()
+            .map(|att| match tuple.get_datum_by_name(att.name()) {
+                Some(datum) if !att.is_dropped() => (datum, false),
+                _ => (pg_sys::Datum::from(0), true),
+            })
+            .unzip();
+
+        unsafe {
+            let rel = self.boxed.as_ptr();
+            let row =
+                pg_sys::heap_form_tuple(self.boxed.rd_att, values.as_mut_ptr(), nulls.as_mut_ptr());
+            let slot = compat::make_heap_tuple_slot(self.boxed.rd_att);
+            compat::exec_store_heap_tuple(row, slot, true);
+
+            // a range table of just this relation, which is where errors about the tuple look up
+            // its columns
+            let mut rte =
+                PgBox::<pg_sys::RangeTblEntry>::alloc_node(pg_sys::NodeTag_T_RangeTblEntry);
+            rte.rtekind = pg_sys::RTEKind_RTE_RELATION;
+            rte.relid = self.oid();
+            rte.relkind = self.boxed.rd_rel.as_ref().expect("rd_rel is NULL").relkind;
+            rte.requiredPerms = pg_sys::ACL_INSERT as pg_sys::AclMode;
+            #[cfg(not(feature = "pg11"))]
+            {
+                rte.rellockmode = pg_sys::RowExclusiveLock as _;
+            }
+            let range_table = pg_sys::lappend(std::ptr::null_mut(), rte.into_pg().cast());
+
+            let leaf = find_leaf_partition(rel, range_table, slot);
+            pg_sys::ExecDropSingleTupleTableSlot(slot);
+
+            // SAFETY:  routing locked the leaf, which exists
+            PgRelation::with_lock(leaf, pg_sys::RowExclusiveLock as pg_sys::LOCKMODE)
+        }
+    }
+}
+
+/// The leaf partition of the partitioned table `rel` that the tuple in `slot` belongs in, routed
+/// as `COPY FROM` does, with no `ModifyTableState`
+#[cfg(feature = "pg11")]
+unsafe fn find_leaf_partition(
+    rel: pg_sys::Relation,
+    range_table: *mut pg_sys::List,
+    slot: *mut pg_sys::TupleTableSlot,
+) -> pg_sys::Oid {
+    let estate = pg_sys::CreateExecutorState();
+    (*estate).es_range_table = range_table;
+    let root = PgBox::<pg_sys::ResultRelInfo>::alloc_node(pg_sys::NodeTag_T_ResultRelInfo);
+    pg_sys::InitResultRelInfo(root.as_ptr(), rel, 1, std::ptr::null_mut(), 0);
+
+    let proute = pg_sys::ExecSetupPartitionTupleRouting(std::ptr::null_mut(), rel);
+    let index =
+        pg_sys::ExecFindPartition(root.as_ptr(), (*proute).partition_dispatch_info, slot, estate);
+    let leaf = *(*proute).partition_oids.add(index as usize);
+
+    pg_sys::ExecCleanupTupleRouting(std::ptr::null_mut(), proute);
+    pg_sys::FreeExecutorState(estate);
+    leaf
+}
+
+/// The leaf partition of the partitioned table `rel` that the tuple in `slot` belongs in, routed
+/// as `COPY FROM` does, with a `ModifyTableState` of no plan
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+unsafe fn find_leaf_partition(
+    rel: pg_sys::Relation,
+    range_table: *mut pg_sys::List,
+    slot: *mut pg_sys::TupleTableSlot,
+) -> pg_sys::Oid {
+    let estate = pg_sys::CreateExecutorState();
+    pg_sys::ExecInitRangeTable(estate, range_table);
+    let root = PgBox::<pg_sys::ResultRelInfo>::alloc_node(pg_sys::NodeTag_T_ResultRelInfo);
+    pg_sys::InitResultRelInfo(root.as_ptr(), rel, 1, std::ptr::null_mut(), 0);
+
+    let mut mtstate =
+        PgBox::<pg_sys::ModifyTableState>::alloc_node(pg_sys::NodeTag_T_ModifyTableState);
+    mtstate.ps.plan = std::ptr::null_mut();
+    mtstate.ps.state = estate;
+    mtstate.operation = pg_sys::CmdType_CMD_INSERT;
+    mtstate.resultRelInfo = root.as_ptr();
+
+    #[cfg(any(feature = "pg12", feature = "pg13"))]
+    let proute = pg_sys::ExecSetupPartitionTupleRouting(estate, mtstate.as_ptr(), rel);
+    #[cfg(any(feature = "pg14", feature = "pg15"))]
+    let proute = pg_sys::ExecSetupPartitionTupleRouting(estate, rel);
+    let leaf = pg_sys::ExecFindPartition(mtstate.as_ptr(), root.as_ptr(), proute, slot, estate);
+    let leaf = (*(*leaf).ri_RelationDesc).rd_id;
+
+    pg_sys::ExecCleanupTupleRouting(mtstate.as_ptr(), proute);
+    pg_sys::FreeExecutorState(estate);
+    leaf
 }
 
 impl Clone for PgRelation {
@@ -0,0 +1,364 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Streaming messages between backends through a shared memory message queue, Postgres' `shm_mq`
+//!
+//! A queue lives in a dynamic shared memory segment, a [`DsmSegment`], which one backend creates
+//! and the other attaches to by its [`DsmSegment::handle()`].  Each queue has one sender and one
+//! receiver.  Sending blocks while the queue is full, and receiving while it's empty, in both cases
+//! waiting on the process latch, so they can be canceled like any other query.  When either side
+//! drops its half, or exits, the other finds the queue detached.
+//!
+//! A typical use is a `#[pg_extern]` function which launches a dynamic background worker to
+//! compute its results, and returns them as the worker sends them:
+//!
+//! ```rust,no_run
+//! use pgx::bgworkers::BackgroundWorkerBuilder;
+//! use pgx::prelude::*;
+//! use pgx::shm_mq::{DsmSegment, ShmMq};
+//! use pgx::DatumExt;
+//!
+//! #[pg_extern]
+//! fn squares() -> SetOfIterator<'static, i64> {
+//!     let mq = ShmMq::create(DsmSegment::create(64 * 1024), 64 * 1024);
+//!     let worker = BackgroundWorkerBuilder::new("squares")
+//!         .set_library("my_extension")
+//!         .set_function("squares_worker")
+//!         .set_argument(Some(pg_sys::Datum::from(mq.dsm_handle())))
+//!         .set_notify_pid(unsafe { pg_sys::MyProcPid })
+//!         .load_dynamic();
+//!     SetOfIterator::new(mq.into_receiver(Some(&worker)).into_messages::<i64>())
+//! }
+//!
+//! #[pg_guard]
+//! #[no_mangle]
+//! pub extern "C" fn squares_worker(arg: pg_sys::Datum) {
+//!     let segment = DsmSegment::attach(arg.as_u32()).expect("the segment is gone");
+//!     let sender = ShmMq::attach(segment).into_sender(None);
+//!     for i in 0..100i64 {
+//!         if sender.send_msg(&(i * i)).is_err() {
+//!             break; // nobody's listening anymore
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Typed messages are encoded with CBOR, as `#[derive(PostgresType)]` types are.
+use crate::bgworkers::DynamicBackgroundWorker;
+use crate::{pg_guard, pg_sys};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+/// Why a message couldn't be sent or received
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ShmMqError {
+    #[error("the other side of the shared memory queue has detached")]
+    Detached,
+    #[error("timed out waiting for a message from the shared memory queue")]
+    Timeout,
+    #[error("couldn't encode the message: {0}")]
+    Encode(String),
+    #[error("couldn't decode the message: {0}")]
+    Decode(String),
+}
+
+/// A dynamic shared memory segment, which is detached from when it's dropped
+///
+/// It's also detached from at the end of the transaction, or query, it was created or attached in,
+/// when the resource owner that tracks it is released, such as when it's aborted by an `ERROR`.
+/// Dropping it after that does nothing, but it mustn't be used anymore.  Postgres destroys it
+/// once every backend has detached.
+pub struct DsmSegment {
+    seg: *mut pg_sys::dsm_segment,
+    size: usize,
+    /// Set by [`mark_detached()`] when Postgres detaches from the segment before it's dropped,
+    /// after which `seg` is freed.  It's boxed so its address doesn't change when it's moved.
+    detached: Box<Cell<bool>>,
+}
+
+impl DsmSegment {
+    /// Create a new segment of `size` bytes
+    ///
+    /// # Panics
+    ///
+    /// Raises an `ERROR` if there are too many segments, or not enough memory, to create it.
+    pub fn create(size: usize) -> Self {
+        let seg = unsafe { pg_sys::dsm_create(size, 0) };
+        DsmSegment::track(seg, size)
+    }
+
+    /// Attach to the segment another backend created, by its [`DsmSegment::handle()`], or `None`
+    /// if it's been destroyed
+    pub fn attach(handle: pg_sys::dsm_handle) -> Option<Self> {
+        let seg = unsafe { pg_sys::dsm_attach(handle) };
+        if seg.is_null() {
+            None
+        } else {
+            Some(DsmSegment::track(seg, unsafe { pg_sys::dsm_segment_map_length(seg) }))
+        }
+    }
+
+    fn track(seg: *mut pg_sys::dsm_segment, size: usize) -> Self {
+        let segment = DsmSegment { seg, size, detached: Box::new(Cell::new(false)) };
+        unsafe { pg_sys::on_dsm_detach(seg, Some(mark_detached), segment.detached_arg()) };
+        segment
+    }
+
+    fn detached_arg(&self) -> pg_sys::Datum {
+        pg_sys::Datum::from(&*self.detached as *const Cell<bool>)
+    }
+
+    /// If Postgres has detached from the segment, which it does when the resource owner that
+    /// tracks it is released
+    pub fn is_detached(&self) -> bool {
+        self.detached.get()
+    }
+
+    /// The handle other backends attach to this segment by
+    pub fn handle(&self) -> pg_sys::dsm_handle {
+        unsafe { pg_sys::dsm_segment_handle(self.seg) }
+    }
+
+    /// The address the segment is mapped at in this backend
+    pub fn address(&self) -> *mut std::os::raw::c_void {
+        unsafe { pg_sys::dsm_segment_address(self.seg) }
+    }
+
+    /// The size of the segment, in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for DsmSegment {
+    fn drop(&mut self) {
+        if self.is_detached() {
+            // an `ERROR` released the resource owner, which detached it and freed `self.seg`,
+            // before what held it was dropped, such as the iterator of a set-returning function
+            return;
+        }
+        unsafe {
+            pg_sys::cancel_on_dsm_detach(self.seg, Some(mark_detached), self.detached_arg());
+            pg_sys::dsm_detach(self.seg)
+        }
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn mark_detached(_seg: *mut pg_sys::dsm_segment, detached: pg_sys::Datum) {
+    (*detached.cast_mut_ptr::<Cell<bool>>()).set(true);
+}
+
+/// A message queue at the start of a [`DsmSegment`], before it's been taken by its sender or its
+/// receiver
+pub struct ShmMq {
+    mq: *mut pg_sys::shm_mq,
+    segment: DsmSegment,
+}
+
+impl ShmMq {
+    /// Create a queue of `size` bytes at the start of `segment`
+    ///
+    /// # Panics
+    ///
+    /// If `segment` is smaller than `size`, or `size` is too small to hold a queue.
+    pub fn create(segment: DsmSegment, size: usize) -> Self {
+        assert!(size <= segment.size(), "a queue of {} bytes doesn't fit in the segment", size);
+        assert!(
+            size > unsafe { pg_sys::shm_mq_minimum_size },
+            "a queue needs more than {} bytes",
+            unsafe { pg_sys::shm_mq_minimum_size }
+        );
+        let mq = unsafe { pg_sys::shm_mq_create(segment.address(), size) };
+        ShmMq { mq, segment }
+    }
+
+    /// The queue another backend created at the start of `segment`
+    pub fn attach(segment: DsmSegment) -> Self {
+        ShmMq { mq: segment.address().cast(), segment }
+    }
+
+    /// The handle of the queue's segment, for the other backend to attach to it by
+    pub fn dsm_handle(&self) -> pg_sys::dsm_handle {
+        self.segment.handle()
+    }
+
+    /// Become the queue's sender.  If `receiver` is the background worker that receives, sending
+    /// stops waiting for it if it exits, or never starts.
+    pub fn into_sender(self, receiver: Option<&DynamicBackgroundWorker>) -> ShmMqSender {
+        unsafe { pg_sys::shm_mq_set_sender(self.mq, pg_sys::MyProc) };
+        ShmMqSender { handle: self.attach_handle(receiver) }
+    }
+
+    /// Become the queue's receiver.  If `sender` is the background worker that sends, receiving
+    /// stops waiting for it if it exits, or never starts.
+    pub fn into_receiver(self, sender: Option<&DynamicBackgroundWorker>) -> ShmMqReceiver {
+        unsafe { pg_sys::shm_mq_set_receiver(self.mq, pg_sys::MyProc) };
+        ShmMqReceiver { handle: self.attach_handle(sender) }
+    }
+
+    fn attach_handle(self, worker: Option<&DynamicBackgroundWorker>) -> QueueHandle {
+        let worker = worker.map_or(std::ptr::null_mut(), |worker| worker.handle_ptr());
+        let mqh = unsafe { pg_sys::shm_mq_attach(self.mq, self.segment.seg, worker) };
+        QueueHandle { mqh, segment: self.segment }
+    }
+}
+
+/// A queue attached to, which is detached from before its segment is
+struct QueueHandle {
+    mqh: *mut pg_sys::shm_mq_handle,
+    segment: DsmSegment,
+}
+
+impl Drop for QueueHandle {
+    fn drop(&mut self) {
+        // once the segment's detached, so is the queue, and the queue is unmapped
+        if !self.segment.is_detached() {
+            unsafe { pg_sys::shm_mq_detach(self.mqh) }
+        }
+    }
+}
+
+/// The sending half of a [`ShmMq`].  Dropping it detaches from the queue, after which the
+/// receiver gets the messages still in the queue, and then [`ShmMqError::Detached`].
+pub struct ShmMqSender {
+    handle: QueueHandle,
+}
+
+impl ShmMqSender {
+    /// Send `message`, waiting for room for it in the queue
+    ///
+    /// A message can be larger than the queue.  It's sent in parts as the receiver makes room.
+    pub fn send(&self, message: &[u8]) -> Result<(), ShmMqError> {
+        let result = unsafe { shm_mq_send(self.handle.mqh, message) };
+        match result {
+            pg_sys::shm_mq_result_SHM_MQ_SUCCESS => Ok(()),
+            _ => Err(ShmMqError::Detached),
+        }
+    }
+
+    /// Send `message`, encoded with CBOR, for [`ShmMqReceiver::recv_msg()`] to decode
+    pub fn send_msg<T: Serialize>(&self, message: &T) -> Result<(), ShmMqError> {
+        let encoded = serde_cbor::to_vec(message).map_err(|e| ShmMqError::Encode(e.to_string()))?;
+        self.send(&encoded)
+    }
+}
+
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13", feature = "pg14"))]
+unsafe fn shm_mq_send(mqh: *mut pg_sys::shm_mq_handle, message: &[u8]) -> pg_sys::shm_mq_result {
+    pg_sys::shm_mq_send(mqh, message.len(), message.as_ptr().cast(), false)
+}
+
+#[cfg(feature = "pg15")]
+unsafe fn shm_mq_send(mqh: *mut pg_sys::shm_mq_handle, message: &[u8]) -> pg_sys::shm_mq_result {
+    // flush each message, so the receiver sees it as soon as it's sent
+    pg_sys::shm_mq_send(mqh, message.len(), message.as_ptr().cast(), false, true)
+}
+
+/// The receiving half of a [`ShmMq`].  Dropping it detaches from the queue, after which the
+/// sender gets [`ShmMqError::Detached`].
+pub struct ShmMqReceiver {
+    handle: QueueHandle,
+}
+
+impl ShmMqReceiver {
+    /// Receive the next message, waiting for up to `timeout` for one, or as long as it takes if
+    /// it's `None`
+    ///
+    /// The wait ends early with an `ERROR` if the query is canceled, and the backend exits if
+    /// the postmaster dies.
+    pub fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, ShmMqError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let mut nbytes = 0;
+            let mut data = std::ptr::null_mut();
+            let result =
+                unsafe { pg_sys::shm_mq_receive(self.handle.mqh, &mut nbytes, &mut data, true) };
+            match result {
+                pg_sys::shm_mq_result_SHM_MQ_SUCCESS => {
+                    // SAFETY:  it's valid until the next receive, so it's copied out
+                    let message = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), nbytes) };
+                    return Ok(message.to_vec());
+                }
+                pg_sys::shm_mq_result_SHM_MQ_DETACHED => return Err(ShmMqError::Detached),
+                _ => {}
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(ShmMqError::Timeout),
+                },
+                None => None,
+            };
+            wait_for_latch(remaining);
+        }
+    }
+
+    /// Receive the next message and decode it from CBOR, as [`ShmMqSender::send_msg()`] encodes
+    /// it
+    pub fn recv_msg<T: DeserializeOwned>(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<T, ShmMqError> {
+        let message = self.recv(timeout)?;
+        serde_cbor::from_slice(&message).map_err(|e| ShmMqError::Decode(e.to_string()))
+    }
+
+    /// An iterator of every message until the sender detaches, decoded from CBOR, as for a
+    /// [`SetOfIterator`](crate::iter::SetOfIterator)
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if a message can't be decoded as a `T`.
+    pub fn into_messages<T: DeserializeOwned>(self) -> ShmMqMessages<T> {
+        ShmMqMessages { receiver: self, _message: PhantomData }
+    }
+}
+
+/// The messages a [`ShmMqReceiver`] receives, until its sender detaches
+pub struct ShmMqMessages<T> {
+    receiver: ShmMqReceiver,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Iterator for ShmMqMessages<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self.receiver.recv_msg(None) {
+            Ok(message) => Some(message),
+            Err(ShmMqError::Detached) => None,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Wait on this backend's latch, which the other side of a queue sets when it sends or receives
+fn wait_for_latch(timeout: Option<Duration>) {
+    let (events, timeout) = match timeout {
+        Some(timeout) => (
+            pg_sys::WL_LATCH_SET | pg_sys::WL_TIMEOUT | pg_sys::WL_POSTMASTER_DEATH,
+            timeout.as_millis().clamp(1, i32::MAX as u128) as _,
+        ),
+        None => (pg_sys::WL_LATCH_SET | pg_sys::WL_POSTMASTER_DEATH, -1),
+    };
+    unsafe {
+        let rc =
+            pg_sys::WaitLatch(pg_sys::MyLatch, events as i32, timeout, pg_sys::PG_WAIT_EXTENSION);
+        if rc & pg_sys::WL_POSTMASTER_DEATH as i32 != 0 {
+            pg_sys::proc_exit(1);
+        }
+        pg_sys::ResetLatch(pg_sys::MyLatch);
+        pg_sys::check_for_interrupts!();
+    }
+}
@@ -7,7 +7,7 @@ All rights reserved.
 Use of this source code is governed by the MIT license that can be found in the LICENSE file.
 */
 use crate::lwlock::*;
-use crate::{pg_sys, PgAtomic};
+use crate::{pg_sys, PgAtomic, PgAtomicValue, PgLogLevel, PgSqlErrorCode};
 use std::hash::Hash;
 use uuid::Uuid;
 
@@ -15,7 +15,7 @@ use uuid::Uuid;
 pub unsafe trait PGXSharedMemory {}
 
 /// In order to store a type in Postgres Shared Memory, it must be passed to
-/// `pg_shmem_init!()` during `_PG_init()`.
+/// `pg_shmem_init!()` from a [`#[pg_init]`](crate::pg_init) function, or `_PG_init()`.
 ///
 /// Additionally, the type must be a `static` global and also be `#[derive(Copy, Clone)]`.
 ///
@@ -27,7 +27,9 @@ pub unsafe trait PGXSharedMemory {}
 /// Custom types need to also implement the `PGXSharedMemory` trait.
 ///
 /// > Extensions that use shared memory **must** be loaded via `postgresql.conf`'s
-/// `shared_preload_libraries` configuration setting.  
+/// `shared_preload_libraries` configuration setting.  `pg_shmem_init!()` raises an `ERROR` otherwise,
+/// so extensions that also work without it should check [`pgx::is_preloaded()`](crate::is_preloaded)
+/// first.
 ///
 /// # Example
 ///
@@ -38,19 +40,51 @@ pub unsafe trait PGXSharedMemory {}
 /// // primitive types must be protected behind a `PgLwLock`
 /// static PRIMITIVE: PgLwLock<i32> = PgLwLock::new();
 ///
-/// // Rust atomics can be used without locks, wrapped in a `PgAtomic`
+/// // atomics can be used without locks, wrapped in a `PgAtomic`
 /// static ATOMIC: PgAtomic<std::sync::atomic::AtomicBool> = PgAtomic::new();
+/// static COUNTER: PgAtomic<pgx::PgAtomicU64> = PgAtomic::new();
 ///
-/// #[pg_guard]
-/// pub extern "C" fn _PG_init() {
+/// #[pg_init]
+/// fn init_shmem() {
 ///     pg_shmem_init!(PRIMITIVE);
 ///     pg_shmem_init!(ATOMIC);
+///     pg_shmem_init!(COUNTER);
+/// }
+/// ```
+///
+/// # Versions
+///
+/// Each `static`'s shared memory is named after its path, and starts with the [`ShmemVersion`]
+/// it's laid out as, which is [`ShmemVersion::current()`] for its type unless it's given as
+/// `version = ..`.  A backend whose library lays it out as a different version, such as one
+/// started after the library was upgraded but before Postgres was restarted, raises a `FATAL`
+/// rather than reading it as the wrong type.
+///
+/// A type whose layout changed compatibly, such as by being renamed, can keep its shared memory
+/// by giving the version it was laid out as before:
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::{pg_shmem_init, PgLwLock, PgSharedMemoryInitialization};
+///
+/// static STATS: PgLwLock<u64> = PgLwLock::new();
+///
+/// #[pg_init]
+/// fn init_shmem() {
+///     // it's always been laid out the same way, whatever the type's called
+///     pg_shmem_init!(STATS, version = 1);
 /// }
 /// ```
 #[cfg(not(feature = "pg15"))]
 #[macro_export]
 macro_rules! pg_shmem_init {
-    ($thing:expr) => {
+    (@identified $thing:expr, $version:expr) => {
+        $crate::init::require_preload("`pg_shmem_init!()`");
+        $crate::PgSharedMemoryInitialization::set_identity(
+            &$thing,
+            concat!(module_path!(), "::", stringify!($thing)),
+            $version,
+        );
         $thing.pg_init();
 
         unsafe {
@@ -62,19 +96,31 @@ macro_rules! pg_shmem_init {
             extern "C" fn __pgx_private_shmem_hook() {
                 unsafe {
                     if let Some(i) = PREV_SHMEM_STARTUP_HOOK {
-                        i();
+                        pg_sys::ffi::pg_guard_ffi_boundary(|| i());
                     }
                 }
                 $thing.shmem_init();
             }
         }
     };
+    ($thing:expr, version = $version:expr) => {
+        $crate::pg_shmem_init!(@identified $thing, Some($crate::ShmemVersion::from($version)));
+    };
+    ($thing:expr) => {
+        $crate::pg_shmem_init!(@identified $thing, None);
+    };
 }
 
 #[cfg(feature = "pg15")]
 #[macro_export]
 macro_rules! pg_shmem_init {
-    ($thing:expr) => {
+    (@identified $thing:expr, $version:expr) => {
+        $crate::init::require_preload("`pg_shmem_init!()`");
+        $crate::PgSharedMemoryInitialization::set_identity(
+            &$thing,
+            concat!(module_path!(), "::", stringify!($thing)),
+            $version,
+        );
         unsafe {
             static mut PREV_SHMEM_REQUEST_HOOK: Option<unsafe extern "C" fn()> = None;
             PREV_SHMEM_REQUEST_HOOK = pg_sys::shmem_request_hook;
@@ -84,7 +130,7 @@ macro_rules! pg_shmem_init {
             extern "C" fn __pgx_private_shmem_request_hook() {
                 unsafe {
                     if let Some(i) = PREV_SHMEM_REQUEST_HOOK {
-                        i();
+                        pg_sys::ffi::pg_guard_ffi_boundary(|| i());
                     }
                 }
                 $thing.pg_init();
@@ -100,13 +146,19 @@ macro_rules! pg_shmem_init {
             extern "C" fn __pgx_private_shmem_hook() {
                 unsafe {
                     if let Some(i) = PREV_SHMEM_STARTUP_HOOK {
-                        i();
+                        pg_sys::ffi::pg_guard_ffi_boundary(|| i());
                     }
                 }
                 $thing.shmem_init();
             }
         }
     };
+    ($thing:expr, version = $version:expr) => {
+        $crate::pg_shmem_init!(@identified $thing, Some($crate::ShmemVersion::from($version)));
+    };
+    ($thing:expr) => {
+        $crate::pg_shmem_init!(@identified $thing, None);
+    };
 }
 
 /// A trait that types can implement to provide their own Postgres Shared Memory initialization process
@@ -118,6 +170,11 @@ pub trait PgSharedMemoryInitialization {
     /// Automatically called by the `pg_shmem_init!()` macro, when Postgres is initializing its
     /// shared memory system
     fn shmem_init(&'static self);
+
+    /// Called by the `pg_shmem_init!()` macro before anything else, with the path of the `static`
+    /// it was given and the version its shared memory is laid out as, or `None` for
+    /// [`ShmemVersion::current()`].  Does nothing unless it's implemented.
+    fn set_identity(&'static self, _path: &'static str, _version: Option<ShmemVersion>) {}
 }
 
 impl<T> PgSharedMemoryInitialization for PgLwLock<T>
@@ -131,11 +188,15 @@ where
     fn shmem_init(&'static self) {
         PgSharedMem::shmem_init_locked(self);
     }
+
+    fn set_identity(&'static self, path: &'static str, version: Option<ShmemVersion>) {
+        self.identify(stable_name(path), version);
+    }
 }
 
 impl<T> PgSharedMemoryInitialization for PgAtomic<T>
 where
-    T: atomic_traits::Atomic + Default,
+    T: PgAtomicValue,
 {
     fn pg_init(&'static self) {
         PgSharedMem::pg_init_atomic(self);
@@ -144,6 +205,10 @@ where
     fn shmem_init(&'static self) {
         PgSharedMem::shmem_init_atomic(self);
     }
+
+    fn set_identity(&'static self, path: &'static str, version: Option<ShmemVersion>) {
+        self.identify(stable_name(path), version);
+    }
 }
 
 /// This struct contains methods to drive creation of types in shared memory
@@ -154,61 +219,192 @@ impl PgSharedMem {
     pub fn pg_init_locked<T: Default + PGXSharedMemory>(lock: &PgLwLock<T>) {
         unsafe {
             let lock = alloc::ffi::CString::new(lock.get_name()).expect("CString::new failed");
-            pg_sys::RequestAddinShmemSpace(std::mem::size_of::<T>());
+            pg_sys::RequestAddinShmemSpace(std::mem::size_of::<ShmemVersioned<T>>());
             pg_sys::RequestNamedLWLockTranche(lock.as_ptr(), 1);
         }
     }
 
     /// Must be run from _PG_init for atomics
-    pub fn pg_init_atomic<T: atomic_traits::Atomic + Default>(_atomic: &PgAtomic<T>) {
+    pub fn pg_init_atomic<T: PgAtomicValue>(_atomic: &PgAtomic<T>) {
         unsafe {
-            pg_sys::RequestAddinShmemSpace(std::mem::size_of::<T>());
+            pg_sys::RequestAddinShmemSpace(std::mem::size_of::<ShmemVersioned<T>>());
         }
     }
 
     /// Must be run from the shared memory init hook, use for types which are guarded by a `LWLock`
     pub fn shmem_init_locked<T: Default + PGXSharedMemory>(lock: &PgLwLock<T>) {
-        let mut found = false;
         unsafe {
-            let shm_name = alloc::ffi::CString::new(lock.get_name()).expect("CString::new failed");
             let addin_shmem_init_lock: *mut pg_sys::LWLock =
                 &mut (*pg_sys::MainLWLockArray.add(21)).lock;
             pg_sys::LWLockAcquire(addin_shmem_init_lock, pg_sys::LWLockMode_LW_EXCLUSIVE);
 
-            let fv_shmem =
-                pg_sys::ShmemInitStruct(shm_name.into_raw(), std::mem::size_of::<T>(), &mut found)
-                    as *mut T;
-
-            std::ptr::write(fv_shmem, <T>::default());
+            let (fv_shmem, found) = attach_versioned::<T>(lock.get_name(), lock.shmem_version());
+            if !found {
+                std::ptr::write(fv_shmem, <T>::default());
+            }
 
             lock.attach(fv_shmem);
             pg_sys::LWLockRelease(addin_shmem_init_lock);
         }
     }
 
-    /// Must be run from the shared memory init hook, use for rust atomics behind `PgAtomic`
-    pub fn shmem_init_atomic<T: atomic_traits::Atomic + Default>(atomic: &PgAtomic<T>) {
+    /// Must be run from the shared memory init hook, use for atomics behind `PgAtomic`
+    pub fn shmem_init_atomic<T: PgAtomicValue>(atomic: &PgAtomic<T>) {
         unsafe {
-            let shm_name = alloc::ffi::CString::new(Uuid::new_v4().to_string())
-                .expect("CString::new() failed");
+            let shm_name = match atomic.name() {
+                Some(name) => name.to_string(),
+                None => Uuid::new_v4().to_string(),
+            };
 
             let addin_shmem_init_lock: *mut pg_sys::LWLock =
                 &mut (*pg_sys::MainLWLockArray.add(21)).lock;
 
-            let mut found = false;
             pg_sys::LWLockAcquire(addin_shmem_init_lock, pg_sys::LWLockMode_LW_EXCLUSIVE);
-            let fv_shmem =
-                pg_sys::ShmemInitStruct(shm_name.into_raw(), std::mem::size_of::<T>(), &mut found)
-                    as *mut T;
+            let (fv_shmem, found) = attach_versioned::<T>(&shm_name, atomic.shmem_version());
 
             atomic.attach(fv_shmem);
-            let atomic = T::default();
-            std::ptr::copy(&atomic, fv_shmem, 1);
+            if !found {
+                let atomic = T::default();
+                std::ptr::copy(&atomic, fv_shmem, 1);
+            }
             pg_sys::LWLockRelease(addin_shmem_init_lock);
         }
     }
 }
 
+/// Find, or make, the shared memory `name` for a `T` laid out as `version`, returning the `T` and
+/// whether it was found.  Raises a `FATAL` if it was found laid out as another version.
+///
+/// SAFETY: the `AddinShmemInitLock` must be held
+unsafe fn attach_versioned<T>(name: &str, version: ShmemVersion) -> (*mut T, bool) {
+    let shm_name = alloc::ffi::CString::new(name).expect("CString::new failed");
+    let mut found = false;
+    let shmem = pg_sys::ShmemInitStruct(
+        shm_name.into_raw(),
+        std::mem::size_of::<ShmemVersioned<T>>(),
+        &mut found,
+    ) as *mut ShmemVersioned<T>;
+
+    if !found {
+        std::ptr::addr_of_mut!((*shmem).version).write(version);
+    } else if let Err(mismatch) = version.check(&(*shmem).version) {
+        crate::pg_sys::panic::ErrorReport::new(
+            PgSqlErrorCode::ERRCODE_OBJECT_NOT_IN_PREREQUISITE_STATE,
+            format!(
+                "shared memory \"{}\" was laid out by a different version of the extension's library",
+                name
+            ),
+            "attach_versioned",
+        )
+        .set_detail(mismatch.to_string())
+        .set_hint("restart Postgres, so the new library's shared memory is laid out afresh")
+        .report(PgLogLevel::FATAL);
+    }
+    (std::ptr::addr_of_mut!((*shmem).value), found)
+}
+
+/// A name for the shared memory of the `static` at `path` that's the same every time the library is
+/// loaded, and which Postgres can tell apart from any other, as it only compares the first
+/// `SHMEM_INDEX_KEYSIZE - 1` bytes of each
+fn stable_name(path: &str) -> &'static str {
+    const MAX_LEN: usize = pg_sys::SHMEM_INDEX_KEYSIZE as usize - 1;
+    let path = path.split_whitespace().collect::<String>();
+    let name = if path.len() <= MAX_LEN {
+        path
+    } else {
+        let hash = format!("#{:016x}", fnv1a(FNV_OFFSET_BASIS, path.as_bytes()));
+        let mut end = MAX_LEN - hash.len();
+        while !path.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}{}", &path[..end], hash)
+    };
+    Box::leak(name.into_boxed_str())
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, which unlike [`std::collections::hash_map::DefaultHasher`] is the same in every build
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// What every `pg_shmem_init!()` layout starts with, so it isn't mistaken for a version
+const SHMEM_MAGIC: u64 = u64::from_be_bytes(*b"pgxshmem");
+
+/// The version of the layout of a `static`'s shared memory, which
+/// [`pg_shmem_init!()`](crate::pg_shmem_init) writes ahead of it, and checks when it attaches to
+/// shared memory that's already there
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShmemVersion {
+    magic: u64,
+    layout: u64,
+}
+
+impl ShmemVersion {
+    /// A version numbered by the extension, rather than worked out from a type
+    pub const fn new(layout: u64) -> ShmemVersion {
+        ShmemVersion { magic: SHMEM_MAGIC, layout }
+    }
+
+    /// The version a `T` is laid out as, unless it's given another, which is a hash of its name,
+    /// size and alignment.
+    ///
+    /// It changes with the type, so it's only the same for two libraries if the type is the same,
+    /// but that's all it can tell:  a field that changed type without changing size needs a new
+    /// version given to `pg_shmem_init!()`.
+    pub fn current<T>() -> ShmemVersion {
+        let hash = fnv1a(FNV_OFFSET_BASIS, std::any::type_name::<T>().as_bytes());
+        let hash = fnv1a(hash, &(std::mem::size_of::<T>() as u64).to_le_bytes());
+        let hash = fnv1a(hash, &(std::mem::align_of::<T>() as u64).to_le_bytes());
+        ShmemVersion::new(hash)
+    }
+
+    /// The number of the layout
+    pub fn layout(&self) -> u64 {
+        self.layout
+    }
+
+    /// Check shared memory `found` laid out as this version, as `pg_shmem_init!()` does before
+    /// using shared memory it didn't make
+    pub fn check(&self, found: &ShmemVersion) -> Result<(), ShmemVersionMismatch> {
+        if found.magic != SHMEM_MAGIC {
+            Err(ShmemVersionMismatch::NotVersioned)
+        } else if found.layout != self.layout {
+            Err(ShmemVersionMismatch::Layout { expected: self.layout, found: found.layout })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl From<u64> for ShmemVersion {
+    fn from(layout: u64) -> Self {
+        ShmemVersion::new(layout)
+    }
+}
+
+/// Shared memory wasn't laid out as the version a library expected
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShmemVersionMismatch {
+    #[error(
+        "it's laid out as version {found:016x}, but the library lays it out as {expected:016x}"
+    )]
+    Layout { expected: u64, found: u64 },
+
+    #[error("it doesn't start with a version, so it wasn't laid out by `pg_shmem_init!()`")]
+    NotVersioned,
+}
+
+/// How [`pg_shmem_init!()`](crate::pg_shmem_init) lays out a `T` in shared memory, after the
+/// version it's laid out as
+#[repr(C)]
+pub struct ShmemVersioned<T> {
+    pub version: ShmemVersion,
+    pub value: T,
+}
+
 unsafe impl PGXSharedMemory for bool {}
 unsafe impl PGXSharedMemory for char {}
 unsafe impl PGXSharedMemory for str {}
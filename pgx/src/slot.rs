@@ -0,0 +1,267 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Safe access to Postgres' `TupleTableSlot`s, the executor's currency for tuples.
+//!
+//! Hooks, custom `DestReceiver`s and anything else that sits next to the executor are handed
+//! tuples in slots.  A [`Slot`] reads and writes one without caring which kind of slot it is
+//! (virtual, heap, minimal or buffer heap tuple) or which Postgres version pgx was compiled
+//! against, deferring to [`crate::compat`] for the parts that changed in Postgres 12.
+//!
+//! Clearing a slot, or storing another tuple in it, frees what it held, so those take `&mut self`
+//! and can't happen while anything still borrows the [`Slot`], such as a `Datum` read from it.
+//!
+//! ```rust,compile_fail
+//! # use pgx::prelude::*;
+//! # use pgx::slot::Slot;
+//! # use std::num::NonZeroUsize;
+//! # unsafe fn f(slot: *mut pg_sys::TupleTableSlot) {
+//! let mut slot = Slot::from_pg(slot);
+//! let datum = slot.get(NonZeroUsize::new(1).unwrap());
+//! slot.clear();
+//! println!("{:?}", datum); // the datum was freed with the tuple
+//! # }
+//! ```
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::slot::Slot;
+//! use std::num::NonZeroUsize;
+//!
+//! /// The first attribute of a tuple handed to a `DestReceiver`'s `receiveSlot`, as a `text`
+//! unsafe fn first_text(slot: *mut pg_sys::TupleTableSlot) -> Option<String> {
+//!     let slot = Slot::from_pg(slot);
+//!     slot.get_typed::<String>(NonZeroUsize::new(1).unwrap()).unwrap()
+//! }
+//! ```
+use crate::heap_tuple::PgHeapTupleError;
+use crate::{
+    compat, pg_sys, AllocatedByRust, FromDatum, IntoDatum, PgHeapTuple, PgMemoryContexts,
+    PgTupleDesc, TryFromDatumError, WhoAllocated,
+};
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::ptr::NonNull;
+
+/// A [`pg_sys::TupleTableSlot`], either borrowed from Postgres for `'a` or made, and dropped, by
+/// us for tuples of a [`PgTupleDesc`] that outlives it.
+pub struct Slot<'a> {
+    slot: NonNull<pg_sys::TupleTableSlot>,
+    /// Did we make the slot, and so have to drop it?
+    owned: bool,
+    _marker: PhantomData<&'a mut pg_sys::TupleTableSlot>,
+}
+
+impl<'a> Slot<'a> {
+    /// Wrap a slot owned by Postgres, such as one passed to a `DestReceiver` or a hook.
+    ///
+    /// ## Safety
+    ///
+    /// `slot` must be a valid, non-null slot, and nothing else may use it for `'a`.
+    pub unsafe fn from_pg(slot: *mut pg_sys::TupleTableSlot) -> Slot<'a> {
+        Slot { slot: NonNull::new(slot).expect("slot is null"), owned: false, _marker: PhantomData }
+    }
+
+    /// Make a new, empty, standalone slot that holds heap tuples of `tupdesc`.
+    pub fn new_heap(tupdesc: &'a PgTupleDesc<'_>) -> Slot<'a> {
+        unsafe { Slot::from_owned(compat::make_heap_tuple_slot(tupdesc.as_ptr())) }
+    }
+
+    /// Make a new, empty, standalone slot that holds minimal tuples of `tupdesc`, which are heap
+    /// tuples without the header fields only a table needs.
+    pub fn new_minimal(tupdesc: &'a PgTupleDesc<'_>) -> Slot<'a> {
+        unsafe { Slot::from_owned(compat::make_minimal_tuple_slot(tupdesc.as_ptr())) }
+    }
+
+    /// Make a new, empty, standalone slot that holds tuples of `tupdesc` as separate `Datum`s.
+    ///
+    /// Postgres 11 has no virtual slots, so this doesn't exist with the `pg11` feature.
+    #[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+    pub fn new_virtual(tupdesc: &'a PgTupleDesc<'_>) -> Slot<'a> {
+        unsafe { Slot::from_owned(compat::make_virtual_tuple_slot(tupdesc.as_ptr())) }
+    }
+
+    unsafe fn from_owned(slot: *mut pg_sys::TupleTableSlot) -> Slot<'a> {
+        Slot { slot: NonNull::new_unchecked(slot), owned: true, _marker: PhantomData }
+    }
+
+    /// The underlying [`pg_sys::TupleTableSlot`], which is still owned by this [`Slot`]
+    #[inline]
+    pub fn as_ptr(&self) -> *mut pg_sys::TupleTableSlot {
+        self.slot.as_ptr()
+    }
+
+    /// The tuple descriptor of the tuples this slot holds
+    pub fn tuple_desc(&self) -> PgTupleDesc<'a> {
+        unsafe { PgTupleDesc::from_pg_unchecked(self.slot.as_ref().tts_tupleDescriptor) }
+    }
+
+    /// The number of attributes of the tuples this slot holds
+    #[inline]
+    pub fn len(&self) -> usize {
+        unsafe { (*self.slot.as_ref().tts_tupleDescriptor).natts as usize }
+    }
+
+    /// Is this slot empty, holding no tuple at all?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        unsafe { compat::tts_empty(self.slot.as_ptr()) }
+    }
+
+    /// The value of attribute `attno` of the tuple in this slot, or `None` if it's `NULL`.
+    ///
+    /// The tuple is only deformed as far as `attno`, and what was deformed is cached in the slot,
+    /// so reading every attribute in turn deforms the tuple once.  The `Datum` is borrowed from
+    /// the slot, because a pass-by-reference one points into the tuple, which is freed when the
+    /// slot is cleared or another tuple is stored.
+    ///
+    /// ## Panics
+    ///
+    /// If the slot is empty, or `attno` is greater than [`Slot::len`].
+    pub fn get(&self, attno: NonZeroUsize) -> Option<&pg_sys::Datum> {
+        assert!(!self.is_empty(), "slot is empty");
+        assert!(
+            attno.get() <= self.len(),
+            "attribute number {} is out of bounds for a slot of {} attributes",
+            attno,
+            self.len()
+        );
+        unsafe {
+            // SAFETY:  `slot_getattr()` deforms the tuple as far as `attno` into the slot's own
+            // arrays, which are only changed by what takes `&mut self`
+            let slot = self.slot.as_ptr();
+            let mut is_null = false;
+            compat::slot_getattr(slot, attno.get() as i32, &mut is_null);
+            if is_null {
+                None
+            } else {
+                Some(&*(*slot).tts_values.add(attno.get() - 1))
+            }
+        }
+    }
+
+    /// The value of attribute `attno` of the tuple in this slot, as a `T`.
+    ///
+    /// ## Errors
+    /// - return [`TryFromDatumError::NoSuchAttributeNumber`] if the attribute does not exist
+    /// - return [`TryFromDatumError::IncompatibleTypes`] if the Rust type of the `value` is not
+    /// compatible with the attribute's Postgres type
+    ///
+    /// ## Panics
+    ///
+    /// If the slot is empty.
+    pub fn get_typed<T: FromDatum + IntoDatum + 'static>(
+        &self,
+        attno: NonZeroUsize,
+    ) -> Result<Option<T>, TryFromDatumError> {
+        let tupdesc = self.tuple_desc();
+        let att =
+            tupdesc.get(attno.get() - 1).ok_or(TryFromDatumError::NoSuchAttributeNumber(attno))?;
+        let typoid = match T::type_oid() {
+            record @ pg_sys::RECORDOID => record,
+            _ => att.type_oid().value(),
+        };
+        match self.get(attno) {
+            None => Ok(None),
+            Some(datum) => unsafe { T::try_from_datum(*datum, false, typoid) },
+        }
+    }
+
+    /// Replace the tuple in this slot with one made of `values`, `None` being `NULL`.
+    ///
+    /// Pass-by-reference values aren't copied, so they must outlive the slot's use of them unless
+    /// [`Slot::materialize`] is called afterwards.
+    ///
+    /// ## Errors
+    /// - [`PgHeapTupleError::IncorrectAttributeCount`] if there isn't one value for each of the
+    /// slot's attributes, in which case the slot is left as it was
+    pub fn store_values(
+        &mut self,
+        values: &[Option<pg_sys::Datum>],
+    ) -> Result<(), PgHeapTupleError> {
+        if values.len() != self.len() {
+            return Err(PgHeapTupleError::IncorrectAttributeCount(values.len(), self.len()));
+        }
+
+        unsafe {
+            let slot = self.slot.as_ptr();
+            compat::exec_clear_tuple(slot);
+            for (i, value) in values.iter().enumerate() {
+                *(*slot).tts_isnull.add(i) = value.is_none();
+                *(*slot).tts_values.add(i) = value.unwrap_or(pg_sys::Datum::from(0));
+            }
+            pg_sys::ExecStoreVirtualTuple(slot);
+        }
+        Ok(())
+    }
+
+    /// Replace the tuple in this slot with a copy of `tuple`, which must have the same attributes.
+    ///
+    /// The copy is made in the slot's own memory context and freed by the slot.  A heap tuple slot
+    /// stores it as-is, and any other kind of slot, such as a minimal tuple slot, deforms it.
+    pub fn store_heap_tuple<AllocatedBy: WhoAllocated>(
+        &mut self,
+        tuple: &PgHeapTuple<'_, AllocatedBy>,
+    ) {
+        unsafe {
+            let slot = self.slot.as_ptr();
+            let copy = PgMemoryContexts::For((*slot).tts_mcxt)
+                .switch_to(|_| pg_sys::heap_copytuple(tuple.as_ptr()));
+            compat::exec_force_store_heap_tuple(copy, slot, true);
+        }
+    }
+
+    /// A copy of the tuple in this slot, in the current memory context, or `None` if it's empty.
+    pub fn to_heap_tuple(&self) -> Option<PgHeapTuple<'a, AllocatedByRust>> {
+        if self.is_empty() {
+            return None;
+        }
+        unsafe {
+            let copy = compat::exec_copy_slot_heap_tuple(self.slot.as_ptr());
+            Some(PgHeapTuple::from_copied_heap_tuple(self.tuple_desc(), copy))
+        }
+    }
+
+    /// Copy everything the tuple in this slot refers to, such as the values given to
+    /// [`Slot::store_values`] or the buffer a scanned tuple is in, into memory the slot owns.
+    ///
+    /// Does nothing if the slot is empty.
+    pub fn materialize(&mut self) {
+        if !self.is_empty() {
+            unsafe { compat::exec_materialize_slot(self.slot.as_ptr()) }
+        }
+    }
+
+    /// Empty this slot, freeing whatever it owned.
+    pub fn clear(&mut self) {
+        unsafe {
+            compat::exec_clear_tuple(self.slot.as_ptr());
+        }
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { pg_sys::ExecDropSingleTupleTableSlot(self.slot.as_ptr()) }
+        }
+    }
+}
+
+impl std::fmt::Debug for Slot<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Slot")
+            .field("len", &self.len())
+            .field("is_empty", &self.is_empty())
+            .field("owned", &self.owned)
+            .finish()
+    }
+}
@@ -9,23 +9,28 @@ Use of this source code is governed by the MIT license that can be found in the
 
 //! Safe access to Postgres' *Server Programming Interface* (SPI).
 
+use crate::datum::DebugDatum;
 use crate::{
     pg_sys, register_xact_callback, FromDatum, IntoDatum, Json, PgMemoryContexts, PgOid,
-    PgXactCallbackEvent, TryFromDatumError,
+    PgSqlErrorCode, PgTryBuilder, PgTupleDesc, PgXactCallbackEvent, TryFromDatumError,
 };
 use core::fmt::Formatter;
-use pgx_pg_sys::panic::ErrorReportable;
+use pgx_pg_sys::panic::{CaughtError, ErrorReportable};
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::mem;
 use std::ops::{Deref, Index};
+use std::panic::AssertUnwindSafe;
 use std::ptr::NonNull;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// How many rows [`SpiClient::select_streaming()`] fetches at a time, unless it's told otherwise
+pub const DEFAULT_FETCH_SIZE: libc::c_long = 1000;
+
 /// These match the Postgres `#define`d constants prefixed `SPI_OK_*` that you can find in `pg_sys`.
 #[derive(Debug, PartialEq)]
 #[repr(i32)]
@@ -143,6 +148,46 @@ pub enum Error {
     /// The [`pg_sys::SPI_tuptable`] is null
     #[error("The active `SPI_tuptable` is NULL")]
     NoTupleTable,
+
+    /// A query whose result should have been no more than one row, of as many columns as were
+    /// asked for, returned `rows` rows of `cols` columns.  No more than two rows are fetched, so
+    /// `rows` is at most 2.
+    #[error("Unexpected query result shape ({rows} row(s) of {cols} column(s))")]
+    UnexpectedShape { rows: usize, cols: usize },
+}
+
+impl crate::ErrorReportable for Error {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            Error::SpiError(code) => match code {
+                SpiErrorCodes::Argument | SpiErrorCodes::Param => {
+                    PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+                }
+                SpiErrorCodes::Transaction => PgSqlErrorCode::ERRCODE_INVALID_TRANSACTION_STATE,
+                SpiErrorCodes::NoAttribute => PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN,
+                SpiErrorCodes::NoOutFunc => PgSqlErrorCode::ERRCODE_UNDEFINED_FUNCTION,
+                SpiErrorCodes::TypUnknown => PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT,
+                SpiErrorCodes::RelDuplicate => PgSqlErrorCode::ERRCODE_DUPLICATE_OBJECT,
+                SpiErrorCodes::RelNotFound => PgSqlErrorCode::ERRCODE_UNDEFINED_TABLE,
+                SpiErrorCodes::Connect
+                | SpiErrorCodes::Copy
+                | SpiErrorCodes::OpUnknown
+                | SpiErrorCodes::Unconnected
+                | SpiErrorCodes::Cursor => PgSqlErrorCode::ERRCODE_INTERNAL_ERROR,
+            },
+            Error::DatumError(e) => crate::ErrorReportable::sql_error_code(e),
+            Error::PreparedStatementArgumentMismatch { .. } => {
+                PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE
+            }
+            Error::InvalidPosition => PgSqlErrorCode::ERRCODE_INVALID_CURSOR_STATE,
+            Error::CursorNotFound(_) => PgSqlErrorCode::ERRCODE_INVALID_CURSOR_NAME,
+            Error::NoTupleTable => PgSqlErrorCode::ERRCODE_NO_DATA_FOUND,
+            Error::UnexpectedShape { rows, .. } if *rows > 1 => {
+                PgSqlErrorCode::ERRCODE_CARDINALITY_VIOLATION
+            }
+            Error::UnexpectedShape { .. } => PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH,
+        }
+    }
 }
 
 pub struct Spi;
@@ -292,32 +337,35 @@ impl<'a> Query for &'a str {
         }
 
         let src = CString::new(self).expect("query contained a null byte");
-        let status_code = match arguments {
-            Some(args) => {
-                let nargs = args.len();
-                let (types, data): (Vec<_>, Vec<_>) = args.into_iter().unzip();
-                let mut argtypes = types.into_iter().map(PgOid::value).collect::<Vec<_>>();
-                let (mut datums, nulls): (Vec<_>, Vec<_>) =
-                    data.into_iter().map(prepare_datum).unzip();
-
-                // SAFETY: arguments are prepared above
-                unsafe {
-                    pg_sys::SPI_execute_with_args(
-                        src.as_ptr(),
-                        nargs as i32,
-                        argtypes.as_mut_ptr(),
-                        datums.as_mut_ptr(),
-                        nulls.as_ptr(),
-                        Spi::is_read_only(),
-                        limit.unwrap_or(0),
-                    )
+        let status_code = crate::parallel::guard_spi(
+            || Some(self.to_string()),
+            || match arguments {
+                Some(args) => {
+                    let nargs = args.len();
+                    let (types, data): (Vec<_>, Vec<_>) = args.into_iter().unzip();
+                    let mut argtypes = types.into_iter().map(PgOid::value).collect::<Vec<_>>();
+                    let (mut datums, nulls): (Vec<_>, Vec<_>) =
+                        data.into_iter().map(prepare_datum).unzip();
+
+                    // SAFETY: arguments are prepared above
+                    unsafe {
+                        pg_sys::SPI_execute_with_args(
+                            src.as_ptr(),
+                            nargs as i32,
+                            argtypes.as_mut_ptr(),
+                            datums.as_mut_ptr(),
+                            nulls.as_ptr(),
+                            Spi::is_read_only(),
+                            limit.unwrap_or(0),
+                        )
+                    }
                 }
-            }
-            // SAFETY: arguments are prepared above
-            None => unsafe {
-                pg_sys::SPI_execute(src.as_ptr(), Spi::is_read_only(), limit.unwrap_or(0))
+                // SAFETY: arguments are prepared above
+                None => unsafe {
+                    pg_sys::SPI_execute(src.as_ptr(), Spi::is_read_only(), limit.unwrap_or(0))
+                },
             },
-        };
+        );
 
         Ok(SpiClient::prepare_tuple_table(status_code)?)
     }
@@ -375,14 +423,27 @@ pub struct SpiHeapTupleData {
 }
 
 impl Spi {
+    /// The value of the single column of the single row `query` returns
+    ///
+    /// # Errors
+    ///
+    /// If `query` returns more than one row, or a number of columns other than one, an
+    /// [`Error::UnexpectedShape`] is returned.  [`Spi::get_first()`] instead takes the first
+    /// column of the first row, whatever else there is.
+    ///
+    /// Only two rows are fetched to check that, but an `UPDATE ... RETURNING` still modifies
+    /// every row it matches, and is an error afterwards if that was more than one.
     pub fn get_one<A: FromDatum + IntoDatum>(query: &str) -> Result<Option<A>> {
-        Spi::connect(|mut client| client.update(query, Some(1), None)?.first().get_one())
+        Spi::connect(|mut client| client.update(query, Some(2), None)?.only_row(1)?.get_one())
     }
 
+    /// The values of the two columns of the single row `query` returns, like [`Spi::get_one()`]
     pub fn get_two<A: FromDatum + IntoDatum, B: FromDatum + IntoDatum>(
         query: &str,
     ) -> Result<(Option<A>, Option<B>)> {
-        Spi::connect(|mut client| client.update(query, Some(1), None)?.first().get_two::<A, B>())
+        Spi::connect(|mut client| {
+            client.update(query, Some(2), None)?.only_row(2)?.get_two::<A, B>()
+        })
     }
 
     pub fn get_three<
@@ -393,7 +454,7 @@ impl Spi {
         query: &str,
     ) -> Result<(Option<A>, Option<B>, Option<C>)> {
         Spi::connect(|mut client| {
-            client.update(query, Some(1), None)?.first().get_three::<A, B, C>()
+            client.update(query, Some(2), None)?.only_row(3)?.get_three::<A, B, C>()
         })
     }
 
@@ -401,7 +462,7 @@ impl Spi {
         query: &str,
         args: Vec<(PgOid, Option<pg_sys::Datum>)>,
     ) -> Result<Option<A>> {
-        Spi::connect(|mut client| client.update(query, Some(1), Some(args))?.first().get_one())
+        Spi::connect(|mut client| client.update(query, Some(2), Some(args))?.only_row(1)?.get_one())
     }
 
     pub fn get_two_with_args<A: FromDatum + IntoDatum, B: FromDatum + IntoDatum>(
@@ -409,7 +470,7 @@ impl Spi {
         args: Vec<(PgOid, Option<pg_sys::Datum>)>,
     ) -> Result<(Option<A>, Option<B>)> {
         Spi::connect(|mut client| {
-            client.update(query, Some(1), Some(args))?.first().get_two::<A, B>()
+            client.update(query, Some(2), Some(args))?.only_row(2)?.get_two::<A, B>()
         })
     }
 
@@ -422,10 +483,24 @@ impl Spi {
         args: Vec<(PgOid, Option<pg_sys::Datum>)>,
     ) -> Result<(Option<A>, Option<B>, Option<C>)> {
         Spi::connect(|mut client| {
-            client.update(query, Some(1), Some(args))?.first().get_three::<A, B, C>()
+            client.update(query, Some(2), Some(args))?.only_row(3)?.get_three::<A, B, C>()
         })
     }
 
+    /// The value of the first column of the first row `query` returns, if it returns any,
+    /// ignoring any other rows and columns
+    pub fn get_first<A: FromDatum + IntoDatum>(query: &str) -> Result<Option<A>> {
+        Spi::connect(|mut client| client.update(query, Some(1), None)?.first().get(1))
+    }
+
+    /// [`Spi::get_first()`], with arguments
+    pub fn get_first_with_args<A: FromDatum + IntoDatum>(
+        query: &str,
+        args: Vec<(PgOid, Option<pg_sys::Datum>)>,
+    ) -> Result<Option<A>> {
+        Spi::connect(|mut client| client.update(query, Some(1), Some(args))?.first().get(1))
+    }
+
     /// just run an arbitrary SQL statement.
     ///
     /// ## Safety
@@ -616,6 +691,191 @@ impl<'a> SpiClient<'a> {
             .ok_or(Error::CursorNotFound(name.to_string()))?;
         Ok(SpiCursor { ptr, __marker: PhantomData })
     }
+
+    /// Perform a SELECT statement through a cursor, calling `f` with each of its rows as they're
+    /// fetched, `fetch_size` of them at a time, or [`DEFAULT_FETCH_SIZE`].  Returns how many rows
+    /// `f` was called with.
+    ///
+    /// Unlike [`SpiClient::select()`], the result is never held all at once:  each batch's
+    /// [`SpiTupleTable`], and whatever Postgres allocated while `f` looked at its rows, is freed
+    /// before the next batch is fetched, so the memory used is bounded by `fetch_size` rather than
+    /// by the size of the result.  A row is only valid during the call to `f`, so anything kept
+    /// from it must be copied out, as [`SpiHeapTupleData::get()`] does for an owned type like
+    /// `String`.
+    ///
+    /// Interrupts, such as the query being cancelled, are checked before each batch.  If `f`
+    /// returns an error, no more rows are fetched, the cursor is closed and the error is
+    /// returned.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust,no_run
+    /// use pgx::prelude::*;
+    ///
+    /// # fn longest() -> Result<usize, pgx::spi::Error> {
+    /// Spi::connect(|client| {
+    ///     let mut longest = 0;
+    ///     client.select_streaming("SELECT body FROM documents", Some(500), None, |row| {
+    ///         let body = row.get::<String>(1)?.unwrap_or_default();
+    ///         longest = longest.max(body.len());
+    ///         Ok::<_, pgx::spi::Error>(())
+    ///     })?;
+    ///     Ok(longest)
+    /// })
+    /// # }
+    /// ```
+    ///
+    /// ## Panics
+    ///
+    /// If `fetch_size` isn't positive
+    pub fn select_streaming<Q, E, F>(
+        &self,
+        query: Q,
+        fetch_size: Option<libc::c_long>,
+        args: Q::Arguments,
+        mut f: F,
+    ) -> std::result::Result<u64, E>
+    where
+        Q: Query,
+        E: From<Error>,
+        F: FnMut(&SpiHeapTupleData) -> std::result::Result<(), E>,
+    {
+        self.stream(query, fetch_size, args, |event| match event {
+            StreamEvent::Columns(_) => Ok(()),
+            StreamEvent::Row(row) => f(row),
+        })
+    }
+
+    /// [`SpiClient::select_streaming()`], which also tells `f` about the first batch, before any
+    /// of its rows and even if it's empty, so the result's columns can be looked at
+    pub(crate) fn stream<Q, E, F>(
+        &self,
+        query: Q,
+        fetch_size: Option<libc::c_long>,
+        args: Q::Arguments,
+        mut f: F,
+    ) -> std::result::Result<u64, E>
+    where
+        Q: Query,
+        E: From<Error>,
+        F: FnMut(StreamEvent<'_>) -> std::result::Result<(), E>,
+    {
+        let fetch_size = fetch_size.unwrap_or(DEFAULT_FETCH_SIZE);
+        assert!(fetch_size > 0, "the fetch size must be positive, not {}", fetch_size);
+
+        // dropped, and so closed, however we return
+        let mut cursor = self.open_cursor(query, args);
+        let mut batch_context = PgMemoryContexts::new("pgx streaming batch");
+        let mut streamed = 0;
+        loop {
+            pg_sys::check_for_interrupts!();
+            let mut table = cursor.fetch(fetch_size)?;
+            let fetched = table.len();
+            let tuptable = table.table;
+
+            let result = if streamed == 0 { f(StreamEvent::Columns(&table)) } else { Ok(()) };
+            let result = result.and_then(|()| unsafe {
+                // SAFETY:  `batch_context` is a valid context we made, and nothing allocated in it
+                // outlives this batch:  the rows `f` is given can't escape it
+                batch_context.switch_to(|_| table.try_for_each(|row| f(StreamEvent::Row(&row))))
+            });
+            unsafe {
+                // SAFETY:  nothing refers to the batch's rows, or what was allocated for them,
+                // any more
+                if let Some(tuptable) = tuptable {
+                    pg_sys::SPI_freetuptable(tuptable);
+                }
+                batch_context.reset();
+            }
+            result?;
+
+            streamed += fetched as u64;
+            if fetched < fetch_size as usize {
+                return Ok(streamed);
+            }
+        }
+    }
+
+    /// Run `f` in a subtransaction named `name`, which is released if `f` returns and rolled back
+    /// if it raises an `ERROR` or panics, in which case that error is returned rather than raised.
+    ///
+    /// This is what a PL/pgSQL `BEGIN ... EXCEPTION` block does, and the usual way to skip the
+    /// rows of a batch that fail while keeping the rest.  The subtransaction is an "internal" one,
+    /// so it doesn't touch the catalogs and only gets a transaction ID of its own if `f` writes
+    /// something.  That makes it cheap enough to use once per row, and the same `name` can be
+    /// reused for every call.  Postgres only shows the name in errors about the subtransaction
+    /// itself.  SQL's `SAVEPOINT`, `RELEASE` and `ROLLBACK TO` can't be used through SPI.
+    ///
+    /// `f` is given this same client, so the statements it runs use the surrounding SPI
+    /// connection and `f` runs in the caller's memory context.  When the subtransaction is
+    /// released, whatever `f` got back, including [`SpiTupleTable`]s and open cursors, stays
+    /// usable.  When it's rolled back, everything `f` did is undone: rows it wrote are gone and
+    /// the tuple tables and cursors it created are freed.  The memory context and resource owner
+    /// in effect when `with_savepoint` was called are restored either way.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust,no_run
+    /// use pgx::prelude::*;
+    ///
+    /// # fn ingest(rows: Vec<(i32, String)>) -> Result<usize, pgx::spi::Error> {
+    /// Spi::connect(|mut client| {
+    ///     let mut ingested = 0;
+    ///     for (id, value) in rows {
+    ///         let inserted = client.with_savepoint("ingest_row", |client| {
+    ///             client.update(
+    ///                 "INSERT INTO ingested (id, value) VALUES ($1, $2)",
+    ///                 None,
+    ///                 Some(vec![
+    ///                     (PgOid::BuiltIn(PgBuiltInOids::INT4OID), id.into_datum()),
+    ///                     (PgOid::BuiltIn(PgBuiltInOids::TEXTOID), value.into_datum()),
+    ///                 ]),
+    ///             )
+    ///         });
+    ///         match inserted {
+    ///             Ok(result) => {
+    ///                 result?;
+    ///                 ingested += 1;
+    ///             }
+    ///             Err(e) => warning!("skipping row {}: {:?}", id, e),
+    ///         }
+    ///     }
+    ///     Ok(ingested)
+    /// })
+    /// # }
+    /// ```
+    pub fn with_savepoint<T, F>(&mut self, name: &str, f: F) -> std::result::Result<T, CaughtError>
+    where
+        F: FnOnce(&mut SpiClient<'a>) -> T,
+    {
+        let name = CString::new(name).expect("savepoint name contains a null byte");
+        unsafe {
+            // SAFETY:  we're connected to SPI, so we're inside a transaction and can start a
+            // subtransaction, and we restore the memory context and resource owner however it
+            // ends.  Postgres copies the name
+            let memcxt = pg_sys::CurrentMemoryContext;
+            let owner = pg_sys::CurrentResourceOwner;
+            pg_sys::BeginInternalSubTransaction(name.as_ptr());
+            pg_sys::MemoryContextSwitchTo(memcxt);
+
+            let result =
+                PgTryBuilder::new(AssertUnwindSafe(|| Ok(f(self)))).catch_others(Err).execute();
+            match result {
+                Ok(_) => pg_sys::ReleaseCurrentSubTransaction(),
+                Err(_) => pg_sys::RollbackAndReleaseCurrentSubTransaction(),
+            }
+            pg_sys::MemoryContextSwitchTo(memcxt);
+            pg_sys::CurrentResourceOwner = owner;
+            result
+        }
+    }
+}
+
+/// What [`SpiClient::stream()`] is telling its callback about
+pub(crate) enum StreamEvent<'a> {
+    /// The first batch of the result, before its rows
+    Columns(&'a SpiTupleTable),
+    Row(&'a SpiHeapTupleData),
 }
 
 type CursorName = String;
@@ -637,7 +897,8 @@ type CursorName = String;
 /// this is a Pgx limitation that might get lifted in the future.
 ///
 /// In the meantime, if you're using cursors to limit memory usage, make sure to use
-/// multiple separate Spi sessions, retrieving the cursor by name.
+/// multiple separate Spi sessions, retrieving the cursor by name, or use
+/// [`SpiClient::select_streaming()`], which frees each batch once it's been seen.
 ///
 /// # Examples
 /// ## Simple cursor
@@ -719,6 +980,13 @@ impl SpiCursor<'_> {
 
 impl Drop for SpiCursor<'_> {
     fn drop(&mut self) {
+        // an ERROR raised while fetching leaves the portal active, and closing an active portal
+        // raises another, which can't be turned into a second panic.  Postgres drops the portal
+        // itself when it aborts the transaction
+        if std::thread::panicking() {
+            return;
+        }
+
         // SAFETY: SPI functions to create/find cursors fail via elog, so self.ptr is valid if we successfully set it
         unsafe {
             pg_sys::SPI_cursor_close(self.ptr.as_mut());
@@ -836,15 +1104,18 @@ impl<'a: 'b, 'b> Query for &'b PreparedStatement<'a> {
         let (mut datums, mut nulls): (Vec<_>, Vec<_>) = args.into_iter().map(prepare_datum).unzip();
 
         // SAFETY: all arguments are prepared above
-        let status_code = unsafe {
-            pg_sys::SPI_execute_plan(
-                self.plan.as_ptr(),
-                datums.as_mut_ptr(),
-                nulls.as_mut_ptr(),
-                Spi::is_read_only(),
-                limit.unwrap_or(0),
-            )
-        };
+        let status_code = crate::parallel::guard_spi(
+            || None,
+            || unsafe {
+                pg_sys::SPI_execute_plan(
+                    self.plan.as_ptr(),
+                    datums.as_mut_ptr(),
+                    nulls.as_mut_ptr(),
+                    Spi::is_read_only(),
+                    limit.unwrap_or(0),
+                )
+            },
+        );
 
         Ok(SpiClient::prepare_tuple_table(status_code)?)
     }
@@ -955,13 +1226,22 @@ impl SpiTupleTable {
         self.len() == 0
     }
 
+    /// The value of the current row's only column
+    ///
+    /// # Errors
+    ///
+    /// If the table has a number of columns other than one, an [`Error::UnexpectedShape`] is
+    /// returned.  [`SpiTupleTable::get()`] gets a column of a row with any number of them.
     pub fn get_one<A: FromDatum + IntoDatum>(&self) -> Result<Option<A>> {
+        self.expect_columns(1)?;
         self.get(1)
     }
 
+    /// The values of the current row's two columns, like [`SpiTupleTable::get_one()`]
     pub fn get_two<A: FromDatum + IntoDatum, B: FromDatum + IntoDatum>(
         &self,
     ) -> Result<(Option<A>, Option<B>)> {
+        self.expect_columns(2)?;
         let a = self.get::<A>(1)?;
         let b = self.get::<B>(2)?;
         Ok((a, b))
@@ -974,14 +1254,35 @@ impl SpiTupleTable {
     >(
         &self,
     ) -> Result<(Option<A>, Option<B>, Option<C>)> {
+        self.expect_columns(3)?;
         let a = self.get::<A>(1)?;
         let b = self.get::<B>(2)?;
         let c = self.get::<C>(3)?;
         Ok((a, b, c))
     }
 
+    /// An [`Error::UnexpectedShape`] unless the table has `cols` columns
+    fn expect_columns(&self, cols: usize) -> Result<()> {
+        match self.columns()? {
+            columns if columns == cols => Ok(()),
+            columns => Err(Error::UnexpectedShape { rows: self.len(), cols: columns }),
+        }
+    }
+
+    /// The table positioned at its first row, or an [`Error::UnexpectedShape`] unless it has
+    /// no more than one row, of `cols` columns
+    fn only_row(self, cols: usize) -> Result<Self> {
+        if self.len() > 1 {
+            return Err(Error::UnexpectedShape { rows: self.len(), cols: self.columns()? });
+        }
+        self.expect_columns(cols)?;
+        Ok(self.first())
+    }
+
     #[inline(always)]
-    fn get_spi_tuptable(&self) -> Result<(*mut pg_sys::SPITupleTable, *mut pg_sys::TupleDescData)> {
+    pub(crate) fn get_spi_tuptable(
+        &self,
+    ) -> Result<(*mut pg_sys::SPITupleTable, *mut pg_sys::TupleDescData)> {
         let table = *self.table.as_ref().ok_or(Error::NoTupleTable)?;
         unsafe {
             // SAFETY:  we just assured that `table` is not null
@@ -1214,6 +1515,11 @@ impl SpiHeapTupleData {
         Ok(Some(data))
     }
 
+    /// The descriptor of the row's columns
+    pub(crate) fn tupdesc(&self) -> pg_sys::TupleDesc {
+        self.tupdesc.as_ptr()
+    }
+
     /// Get a typed value from this HeapTuple by its ordinal position.
     ///
     /// The ordinal position is 1-based
@@ -1340,6 +1646,29 @@ impl SpiHeapTupleData {
     }
 }
 
+impl Debug for SpiHeapTupleData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // SAFETY: we know self.tupdesc is a valid, non-null pointer, and we don't release it
+        let tupdesc = unsafe { PgTupleDesc::from_pg_unchecked(self.tupdesc.as_ptr()) };
+        let mut debug = f.debug_struct("SpiHeapTupleData");
+        for (i, att) in tupdesc.iter().enumerate() {
+            if att.attisdropped {
+                continue;
+            }
+            if let Some(entry) = self.entries.get(&(i + 1)) {
+                debug.field(att.name(), entry);
+            }
+        }
+        debug.finish()
+    }
+}
+
+impl Debug for SpiHeapTupleDataEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        DebugDatum(self.type_oid, self.datum).fmt(f)
+    }
+}
+
 impl SpiHeapTupleDataEntry {
     pub fn value<T: IntoDatum + FromDatum>(&self) -> Result<Option<T>> {
         match self.datum.as_ref() {
@@ -1350,6 +1679,10 @@ impl SpiHeapTupleDataEntry {
         }
     }
 
+    pub(crate) fn datum(&self) -> Option<pg_sys::Datum> {
+        self.datum
+    }
+
     pub fn oid(&self) -> pg_sys::Oid {
         self.type_oid
     }
@@ -0,0 +1,248 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Write the result of a query as CSV to any [`std::io::Write`], as it's fetched
+//!
+//! [`CsvWriter::export()`] runs the query with [`SpiClient::select_streaming()`], so no more than
+//! a batch of rows is in memory at once, however big the result is:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::spi_csv::{CsvError, CsvWriter};
+//!
+//! #[pg_extern]
+//! fn export_orders(path: &str) -> Result<i64, CsvError> {
+//!     let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+//!     let mut csv = CsvWriter::new(file).header(true);
+//!     let rows = Spi::connect(|client| {
+//!         csv.export(&client, "SELECT * FROM orders ORDER BY id", None, None)
+//!     })?;
+//!     Ok(rows as i64)
+//! }
+//! ```
+//!
+//! Values are written as `COPY ... TO ... (FORMAT csv)` writes them:  as their type's output
+//! function gives them, quoted only when they have to be, and with a `NULL` unquoted and empty.
+//! They're in the server's encoding, rather than being converted to the client's as `COPY` does.
+use crate::spi::{self, SpiClient, SpiHeapTupleData, StreamEvent};
+use crate::{pg_sys, PgMemoryContexts, PgSqlErrorCode};
+use std::ffi::CStr;
+use std::io::{self, Write};
+
+const QUOTE: u8 = b'"';
+
+/// Why rows couldn't be written
+#[derive(thiserror::Error, Debug)]
+pub enum CsvError {
+    #[error(transparent)]
+    Spi(#[from] spi::Error),
+    #[error("could not write CSV: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl crate::ErrorReportable for CsvError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            CsvError::Spi(e) => crate::ErrorReportable::sql_error_code(e),
+            CsvError::Io(_) => PgSqlErrorCode::ERRCODE_IO_ERROR,
+        }
+    }
+}
+
+/// Writes rows to `W` as CSV, a line for each
+pub struct CsvWriter<W: Write> {
+    out: W,
+    header: bool,
+    delimiter: u8,
+    null: Vec<u8>,
+    /// The output functions of the result's columns, once it's been seen
+    columns: Option<Vec<pg_sys::FmgrInfo>>,
+    /// Where the output functions keep what they cache, as long as they're used
+    context: PgMemoryContexts,
+    line: Vec<u8>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Write to `out`, with `,` between values, no header, and an empty `NULL`
+    pub fn new(out: W) -> Self {
+        CsvWriter {
+            out,
+            header: false,
+            delimiter: b',',
+            null: Vec::new(),
+            columns: None,
+            context: PgMemoryContexts::new("pgx CsvWriter"),
+            line: Vec::new(),
+        }
+    }
+
+    /// Whether the first line is the names of the columns, as with `COPY`'s `HEADER`
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Separate the values with `delimiter` rather than `,`
+    ///
+    /// ## Panics
+    ///
+    /// If `delimiter` is `"`, `\r` or `\n`, which `COPY` doesn't allow either
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        if matches!(delimiter, QUOTE | b'\r' | b'\n') {
+            panic!("{:?} can't be a CSV delimiter", delimiter as char);
+        }
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write a `NULL` as `null` rather than as nothing
+    ///
+    /// ## Panics
+    ///
+    /// If `null` has a `"`, `\r` or `\n` in it, which `COPY` doesn't allow either
+    pub fn null(mut self, null: &str) -> Self {
+        if null.bytes().any(|b| matches!(b, QUOTE | b'\r' | b'\n')) {
+            panic!("{:?} can't be a CSV NULL", null);
+        }
+        self.null = null.as_bytes().to_vec();
+        self
+    }
+
+    /// Run `query` with [`SpiClient::select_streaming()`] and write each of its rows, after the
+    /// header if there's to be one, which is written even if there are no rows.  Returns how many
+    /// rows were written.
+    ///
+    /// The writer is flushed once the last row has been written.
+    pub fn export<Q: spi::Query>(
+        &mut self,
+        client: &SpiClient,
+        query: Q,
+        fetch_size: Option<libc::c_long>,
+        args: Q::Arguments,
+    ) -> Result<u64, CsvError> {
+        let rows = client.stream(query, fetch_size, args, |event| match event {
+            StreamEvent::Columns(table) => {
+                let (_, tupdesc) = table.get_spi_tuptable()?;
+                // SAFETY:  the table's descriptor is valid while we have the table
+                unsafe { self.start(tupdesc) }
+            }
+            StreamEvent::Row(row) => self.write_row(row),
+        })?;
+        self.out.flush()?;
+        Ok(rows)
+    }
+
+    /// Write `row`, such as from [`SpiClient::select_streaming()`], after the header if this is
+    /// the first row and there's to be one
+    ///
+    /// Every row written must have the same columns as the first.
+    pub fn write_row(&mut self, row: &SpiHeapTupleData) -> Result<(), CsvError> {
+        if self.columns.is_none() {
+            // SAFETY:  the row's descriptor is valid while we have the row
+            unsafe { self.start(row.tupdesc())? }
+        }
+        let columns = self.columns.as_mut().expect("the columns have been looked up");
+        assert_eq!(columns.len(), row.columns(), "the row has different columns than the first");
+
+        self.line.clear();
+        let single = columns.len() == 1;
+        for (i, output) in columns.iter_mut().enumerate() {
+            if i > 0 {
+                self.line.push(self.delimiter);
+            }
+            match row.get_datum_by_ordinal(i + 1)?.datum() {
+                None => self.line.extend_from_slice(&self.null),
+                Some(datum) => unsafe {
+                    // SAFETY:  `output` is the output function of the column's type, and the
+                    // value it gives us is a palloc'd string we free once it's copied
+                    let value = pg_sys::OutputFunctionCall(output, datum);
+                    write_value(
+                        &mut self.line,
+                        CStr::from_ptr(value).to_bytes(),
+                        self.delimiter,
+                        &self.null,
+                        single,
+                    );
+                    pg_sys::pfree(value.cast());
+                },
+            }
+        }
+        self.line.push(b'\n');
+        self.out.write_all(&self.line)?;
+        Ok(())
+    }
+
+    /// The writer, which hasn't been flushed unless [`CsvWriter::export()`] has just been used
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Look up the output functions of the columns of `tupdesc`, and write the header if there's
+    /// to be one
+    ///
+    /// ## Safety
+    ///
+    /// `tupdesc` must be a valid tuple descriptor
+    unsafe fn start(&mut self, tupdesc: pg_sys::TupleDesc) -> Result<(), CsvError> {
+        let natts = (*tupdesc).natts;
+        let mut columns = Vec::with_capacity(natts as usize);
+        let mut names = Vec::with_capacity(natts as usize);
+        for attno in 1..=natts {
+            let type_oid = pg_sys::SPI_gettypeid(tupdesc, attno);
+            let mut output_oid = pg_sys::InvalidOid;
+            let mut is_varlena = false;
+            pg_sys::getTypeOutputInfo(type_oid, &mut output_oid, &mut is_varlena);
+            let mut output = pg_sys::FmgrInfo::default();
+            pg_sys::fmgr_info_cxt(output_oid, &mut output, self.context.value());
+            columns.push(output);
+
+            if self.header {
+                let name = pg_sys::SPI_fname(tupdesc, attno);
+                names.push(CStr::from_ptr(name).to_bytes().to_vec());
+                pg_sys::pfree(name.cast());
+            }
+        }
+        let single = columns.len() == 1;
+        self.columns = Some(columns);
+
+        if self.header {
+            self.line.clear();
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    self.line.push(self.delimiter);
+                }
+                write_value(&mut self.line, name, self.delimiter, &self.null, single);
+            }
+            self.line.push(b'\n');
+            self.out.write_all(&self.line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Append `value` to `line`, quoted if it has to be, as `COPY`'s `CopyAttributeOutCSV()` does
+fn write_value(line: &mut Vec<u8>, value: &[u8], delimiter: u8, null: &[u8], single: bool) {
+    // a value that looks like a `NULL`, or like the end of the data to a `COPY FROM` reading a
+    // single column, is quoted so it isn't read as one
+    let quoted = value == null
+        || (single && value == b"\\.")
+        || value.iter().any(|&b| b == delimiter || matches!(b, QUOTE | b'\r' | b'\n'));
+    if !quoted {
+        line.extend_from_slice(value);
+        return;
+    }
+    line.push(QUOTE);
+    for &b in value {
+        if b == QUOTE {
+            line.push(QUOTE);
+        }
+        line.push(b);
+    }
+    line.push(QUOTE);
+}
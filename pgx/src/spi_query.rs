@@ -0,0 +1,456 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Build `SELECT`, `INSERT`, `UPDATE` and `DELETE` statements for [`SpiClient`](crate::spi::SpiClient)
+//! without splicing strings together
+//!
+//! Every table and column name is quoted with [`quote_identifier()`], and every value is bound as
+//! a `$n` parameter rather than written into the SQL, so neither can change what the statement
+//! does, whatever they contain:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::spi_query::SpiQuery;
+//!
+//! let (sql, args) = SpiQuery::select()
+//!     .columns(["a", "b"])
+//!     .from(("schema", "table"))
+//!     .where_eq("id", 5i64)
+//!     .limit(10)
+//!     .build();
+//! assert_eq!(sql, r#"SELECT a, b FROM schema."table" WHERE id = $1 LIMIT $2"#);
+//!
+//! Spi::connect(|client| {
+//!     let rows = client.select(&sql, None, args)?;
+//!     Ok::<_, pgx::spi::Error>(rows.len())
+//! });
+//! ```
+//!
+//! The only way to put SQL of your own into a statement is [`SpiQuery::unsafe_fragment()`], which
+//! is written in exactly as given.  It's not `unsafe` in Rust's sense, but it's on you to make
+//! sure nothing in it came from a user.
+use crate::{pg_sys, quote_identifier, quote_qualified_identifier, IntoDatum, PgOid};
+
+/// The arguments of a built statement, in the form [`SpiClient::select()`](crate::spi::SpiClient::select)
+/// and [`SpiClient::update()`](crate::spi::SpiClient::update) take them
+pub type SpiQueryArgs = Option<Vec<(PgOid, Option<pg_sys::Datum>)>>;
+
+/// Where to start building a statement
+pub struct SpiQuery;
+
+impl SpiQuery {
+    /// Start a `SELECT`
+    pub fn select() -> Select {
+        Select::default()
+    }
+
+    /// Start an `INSERT` of one row into `table`
+    pub fn insert_into(table: impl Into<TableName>) -> Insert {
+        Insert {
+            table: table.into(),
+            columns: Vec::new(),
+            returning: Vec::new(),
+            params: Params::default(),
+        }
+    }
+
+    /// Start an `UPDATE` of `table`
+    pub fn update(table: impl Into<TableName>) -> Update {
+        Update {
+            table: table.into(),
+            assignments: Vec::new(),
+            filter: Vec::new(),
+            returning: Vec::new(),
+            params: Params::default(),
+        }
+    }
+
+    /// Start a `DELETE` from `table`
+    pub fn delete_from(table: impl Into<TableName>) -> Delete {
+        Delete {
+            table: table.into(),
+            filter: Vec::new(),
+            returning: Vec::new(),
+            params: Params::default(),
+        }
+    }
+
+    /// SQL to be written into a statement exactly as given, neither quoted nor bound
+    ///
+    /// Use it for what the builders can't express, like `count(*)` or `a < b`, and never with
+    /// anything that came from outside the extension's own code.
+    pub fn unsafe_fragment(sql: impl Into<String>) -> SqlFragment {
+        SqlFragment(sql.into())
+    }
+}
+
+/// A table's name, optionally qualified by its schema:  `"table"` or `("schema", "table")`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableName {
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+impl TableName {
+    fn quoted(&self) -> String {
+        match &self.schema {
+            Some(schema) => quote_qualified_identifier(schema, &self.name),
+            None => quote_identifier(&self.name),
+        }
+    }
+}
+
+impl From<&str> for TableName {
+    fn from(name: &str) -> Self {
+        TableName { schema: None, name: name.to_string() }
+    }
+}
+
+impl From<String> for TableName {
+    fn from(name: String) -> Self {
+        TableName { schema: None, name }
+    }
+}
+
+impl<S: Into<String>, N: Into<String>> From<(S, N)> for TableName {
+    fn from((schema, name): (S, N)) -> Self {
+        TableName { schema: Some(schema.into()), name: name.into() }
+    }
+}
+
+/// Raw SQL, made by [`SpiQuery::unsafe_fragment()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlFragment(String);
+
+/// A column name, or a fragment in its place
+#[derive(Debug, Clone)]
+enum Column {
+    Name(String),
+    Fragment(SqlFragment),
+}
+
+impl Column {
+    fn render(&self) -> String {
+        match self {
+            Column::Name(name) => quote_identifier(name),
+            Column::Fragment(SqlFragment(sql)) => sql.clone(),
+        }
+    }
+}
+
+fn render_columns(columns: &[Column]) -> String {
+    columns.iter().map(Column::render).collect::<Vec<_>>().join(", ")
+}
+
+/// A `WHERE` condition.  Any value it compares to is already bound, as `$param`.
+#[derive(Debug, Clone)]
+enum Condition {
+    Eq { column: String, param: usize },
+    IsNull { column: String },
+    IsNotNull { column: String },
+    Fragment(SqlFragment),
+}
+
+impl Condition {
+    fn render(&self) -> String {
+        match self {
+            Condition::Eq { column, param } => format!("{} = ${}", quote_identifier(column), param),
+            Condition::IsNull { column } => format!("{} IS NULL", quote_identifier(column)),
+            Condition::IsNotNull { column } => format!("{} IS NOT NULL", quote_identifier(column)),
+            Condition::Fragment(SqlFragment(sql)) => format!("({})", sql),
+        }
+    }
+}
+
+fn render_filter(sql: &mut String, filter: &[Condition]) {
+    if !filter.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&filter.iter().map(Condition::render).collect::<Vec<_>>().join(" AND "));
+    }
+}
+
+fn render_returning(sql: &mut String, returning: &[Column]) {
+    if !returning.is_empty() {
+        sql.push_str(" RETURNING ");
+        sql.push_str(&render_columns(returning));
+    }
+}
+
+/// The values bound so far, numbered from `$1` in the order they were given
+#[derive(Debug, Default)]
+struct Params(Vec<(PgOid, Option<pg_sys::Datum>)>);
+
+impl Params {
+    fn bind<T: IntoDatum>(&mut self, value: T) -> usize {
+        self.0.push((PgOid::from(T::type_oid()), value.into_datum()));
+        self.0.len()
+    }
+
+    fn into_args(self) -> SpiQueryArgs {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(self.0)
+        }
+    }
+}
+
+/// The `WHERE` methods shared by [`Select`], [`Update`] and [`Delete`]
+macro_rules! filter_methods {
+    () => {
+        /// Only the rows whose `column` equals `value`, which is bound as a parameter.  A `None`
+        /// is never equal to anything, so use [`where_null()`](Self::where_null) for `IS NULL`.
+        pub fn where_eq<T: IntoDatum>(mut self, column: &str, value: T) -> Self {
+            let param = self.params.bind(value);
+            self.filter.push(Condition::Eq { column: column.to_string(), param });
+            self
+        }
+
+        /// Only the rows whose `column` is `NULL`
+        pub fn where_null(mut self, column: &str) -> Self {
+            self.filter.push(Condition::IsNull { column: column.to_string() });
+            self
+        }
+
+        /// Only the rows whose `column` isn't `NULL`
+        pub fn where_not_null(mut self, column: &str) -> Self {
+            self.filter.push(Condition::IsNotNull { column: column.to_string() });
+            self
+        }
+
+        /// Only the rows for which the raw `condition` is true
+        pub fn where_fragment(mut self, condition: SqlFragment) -> Self {
+            self.filter.push(Condition::Fragment(condition));
+            self
+        }
+    };
+}
+
+/// The `RETURNING` methods shared by [`Insert`], [`Update`] and [`Delete`]
+macro_rules! returning_methods {
+    () => {
+        /// Return these columns of each row the statement changes
+        pub fn returning<I, S>(mut self, columns: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            self.returning.extend(
+                columns.into_iter().map(|column| Column::Name(column.as_ref().to_string())),
+            );
+            self
+        }
+
+        /// Return the raw `expression` for each row the statement changes, like `*`
+        pub fn returning_fragment(mut self, expression: SqlFragment) -> Self {
+            self.returning.push(Column::Fragment(expression));
+            self
+        }
+    };
+}
+
+/// A `SELECT` statement, started by [`SpiQuery::select()`]
+#[derive(Debug, Default)]
+pub struct Select {
+    columns: Vec<Column>,
+    from: Option<TableName>,
+    filter: Vec<Condition>,
+    order_by: Vec<(Column, bool)>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    params: Params,
+}
+
+impl Select {
+    /// Select these columns, after any already selected.  Without any, it's `SELECT *`.
+    pub fn columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.columns
+            .extend(columns.into_iter().map(|column| Column::Name(column.as_ref().to_string())));
+        self
+    }
+
+    /// Select the raw `expression`, like `count(*)`
+    pub fn column_fragment(mut self, expression: SqlFragment) -> Self {
+        self.columns.push(Column::Fragment(expression));
+        self
+    }
+
+    /// Select from `table`
+    pub fn from(mut self, table: impl Into<TableName>) -> Self {
+        self.from = Some(table.into());
+        self
+    }
+
+    filter_methods!();
+
+    /// Sort by `column`, ascending, after any sorting already given
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.order_by.push((Column::Name(column.to_string()), false));
+        self
+    }
+
+    /// Sort by `column`, descending, after any sorting already given
+    pub fn order_by_desc(mut self, column: &str) -> Self {
+        self.order_by.push((Column::Name(column.to_string()), true));
+        self
+    }
+
+    /// Return at most `count` rows
+    pub fn limit(mut self, count: i64) -> Self {
+        self.limit = Some(self.params.bind(count));
+        self
+    }
+
+    /// Skip the first `count` rows
+    pub fn offset(mut self, count: i64) -> Self {
+        self.offset = Some(self.params.bind(count));
+        self
+    }
+
+    /// The statement's SQL and the values bound to its parameters
+    pub fn build(self) -> (String, SpiQueryArgs) {
+        let mut sql = String::from("SELECT ");
+        if self.columns.is_empty() {
+            sql.push('*');
+        } else {
+            sql.push_str(&render_columns(&self.columns));
+        }
+        if let Some(table) = &self.from {
+            sql.push_str(" FROM ");
+            sql.push_str(&table.quoted());
+        }
+        render_filter(&mut sql, &self.filter);
+        if !self.order_by.is_empty() {
+            let order_by = self
+                .order_by
+                .iter()
+                .map(|(column, desc)| {
+                    format!("{}{}", column.render(), if *desc { " DESC" } else { "" })
+                })
+                .collect::<Vec<_>>();
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&order_by.join(", "));
+        }
+        if let Some(param) = self.limit {
+            sql.push_str(&format!(" LIMIT ${}", param));
+        }
+        if let Some(param) = self.offset {
+            sql.push_str(&format!(" OFFSET ${}", param));
+        }
+        (sql, self.params.into_args())
+    }
+}
+
+/// An `INSERT` of one row, started by [`SpiQuery::insert_into()`]
+#[derive(Debug)]
+pub struct Insert {
+    table: TableName,
+    columns: Vec<(String, usize)>,
+    returning: Vec<Column>,
+    params: Params,
+}
+
+impl Insert {
+    /// Set `column` to `value`, which is bound as a parameter.  Columns not given get their
+    /// defaults.
+    pub fn value<T: IntoDatum>(mut self, column: &str, value: T) -> Self {
+        let param = self.params.bind(value);
+        self.columns.push((column.to_string(), param));
+        self
+    }
+
+    returning_methods!();
+
+    /// The statement's SQL and the values bound to its parameters
+    pub fn build(self) -> (String, SpiQueryArgs) {
+        let mut sql = format!("INSERT INTO {}", self.table.quoted());
+        if self.columns.is_empty() {
+            sql.push_str(" DEFAULT VALUES");
+        } else {
+            let (columns, params): (Vec<_>, Vec<_>) = self
+                .columns
+                .iter()
+                .map(|(column, param)| (quote_identifier(column), format!("${}", param)))
+                .unzip();
+            sql.push_str(&format!(" ({}) VALUES ({})", columns.join(", "), params.join(", ")));
+        }
+        render_returning(&mut sql, &self.returning);
+        (sql, self.params.into_args())
+    }
+}
+
+/// An `UPDATE`, started by [`SpiQuery::update()`]
+#[derive(Debug)]
+pub struct Update {
+    table: TableName,
+    assignments: Vec<(String, usize)>,
+    filter: Vec<Condition>,
+    returning: Vec<Column>,
+    params: Params,
+}
+
+impl Update {
+    /// Set `column` to `value`, which is bound as a parameter
+    pub fn set<T: IntoDatum>(mut self, column: &str, value: T) -> Self {
+        let param = self.params.bind(value);
+        self.assignments.push((column.to_string(), param));
+        self
+    }
+
+    filter_methods!();
+
+    returning_methods!();
+
+    /// The statement's SQL and the values bound to its parameters
+    ///
+    /// ## Panics
+    ///
+    /// If no column was [`set()`](Self::set)
+    pub fn build(self) -> (String, SpiQueryArgs) {
+        if self.assignments.is_empty() {
+            panic!("an UPDATE of {} doesn't set any columns", self.table.quoted());
+        }
+        let assignments = self
+            .assignments
+            .iter()
+            .map(|(column, param)| format!("{} = ${}", quote_identifier(column), param))
+            .collect::<Vec<_>>();
+        let mut sql = format!("UPDATE {} SET {}", self.table.quoted(), assignments.join(", "));
+        render_filter(&mut sql, &self.filter);
+        render_returning(&mut sql, &self.returning);
+        (sql, self.params.into_args())
+    }
+}
+
+/// A `DELETE`, started by [`SpiQuery::delete_from()`]
+#[derive(Debug)]
+pub struct Delete {
+    table: TableName,
+    filter: Vec<Condition>,
+    returning: Vec<Column>,
+    params: Params,
+}
+
+impl Delete {
+    filter_methods!();
+
+    returning_methods!();
+
+    /// The statement's SQL and the values bound to its parameters
+    pub fn build(self) -> (String, SpiQueryArgs) {
+        let mut sql = format!("DELETE FROM {}", self.table.quoted());
+        render_filter(&mut sql, &self.filter);
+        render_returning(&mut sql, &self.returning);
+        (sql, self.params.into_args())
+    }
+}
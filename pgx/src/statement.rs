@@ -0,0 +1,285 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! What the backend is executing:  the client's query, its id, and how deeply nested in it the
+//! current code is
+//!
+//! The id and nesting level are noted by hooks that run for every statement, so an extension which
+//! uses them asks for those with [`track()`] while it's loading.  An auditing function might then
+//! record them like so:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//!
+//! #[pg_init]
+//! fn init() {
+//!     pgx::statement::track();
+//! }
+//!
+//! #[pg_extern]
+//! fn audit(event: &str) {
+//!     let query = pgx::current_query_string().unwrap_or_default();
+//!     let query_id = pgx::current_query_id().map(|id| id as i64);
+//!     Spi::run_with_args(
+//!         "INSERT INTO audit_log (event, query, query_id, nesting_level) VALUES ($1, $2, $3, $4)",
+//!         Some(vec![
+//!             (PgBuiltInOids::TEXTOID.oid(), event.into_datum()),
+//!             (PgBuiltInOids::TEXTOID.oid(), query.into_datum()),
+//!             (PgBuiltInOids::INT8OID.oid(), query_id.into_datum()),
+//!             (PgBuiltInOids::INT4OID.oid(), (pgx::nesting_level() as i32).into_datum()),
+//!         ]),
+//!     )
+//!     .unwrap();
+//! }
+//! ```
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::pg_guard;
+use crate::pg_sys;
+use core::ffi::CStr;
+
+static mut TRACKING: bool = false;
+static mut NESTING_LEVEL: u32 = 0;
+static mut TOP_LEVEL_QUERY_ID: u64 = 0;
+static mut SUBXACT_LEVELS: Vec<(pg_sys::SubTransactionId, u32)> = Vec::new();
+
+static mut PREV_EXECUTOR_RUN: pg_sys::ExecutorRun_hook_type = None;
+static mut PREV_EXECUTOR_FINISH: pg_sys::ExecutorFinish_hook_type = None;
+static mut PREV_PROCESS_UTILITY: pg_sys::ProcessUtility_hook_type = None;
+
+/// The text of the query the client sent, which is what SQL's `current_query()` returns
+///
+/// It's the whole query string, even when it's made of several statements, and it's the same
+/// however deeply nested the current code is.  `None` in a background worker, or when no query is
+/// running.  It's copied, as Postgres frees it once the query ends.
+pub fn current_query_string() -> Option<String> {
+    unsafe {
+        // SAFETY:  both are only ever written to by this backend's own thread
+        if pg_sys::IsBackgroundWorker || pg_sys::debug_query_string.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(pg_sys::debug_query_string).to_string_lossy().into_owned())
+    }
+}
+
+/// The id Postgres computed for the top-level statement that's executing, which is what
+/// `pg_stat_statements` and `pg_stat_activity` identify it by
+///
+/// Postgres only computes it when `compute_query_id` is `on` (on Postgres 14 and later) or an
+/// extension such as `pg_stat_statements` asks for it.  `None` when it didn't, in a background
+/// worker, when no statement is running, or unless the extension called [`track()`].
+pub fn current_query_id() -> Option<u64> {
+    unsafe {
+        // SAFETY:  both are only ever written to by this backend's own thread
+        if pg_sys::IsBackgroundWorker || TOP_LEVEL_QUERY_ID == 0 {
+            None
+        } else {
+            Some(TOP_LEVEL_QUERY_ID)
+        }
+    }
+}
+
+/// How many statements the current code is running inside of, counting those the executor runs
+/// and utility commands, the way `pg_stat_statements` counts them
+///
+/// It's `0` at the top level, such as in an `ExecutorStart` hook for a statement the client sent,
+/// `1` in a function called by that statement, and `2` in a function called by a statement which
+/// that function runs through [`Spi`](crate::Spi) or PL/pgSQL.  A hook is called for a nested
+/// statement when it's more than `0`, and a function is called by one when it's more than `1`.
+///
+/// It's counted from when the extension was loaded, so a statement which was already running then,
+/// such as the `LOAD` or `CREATE EXTENSION` which loaded it, isn't counted.  And it's always `0`
+/// unless the extension called [`track()`].
+pub fn nesting_level() -> u32 {
+    // SAFETY:  it's only ever written to by this backend's own thread
+    unsafe { NESTING_LEVEL }
+}
+
+/// Count [`nesting_level()`] and note [`current_query_id()`], which an extension that uses them
+/// asks for from its `_PG_init()` or a `#[pg_init]` function
+///
+/// That takes executor and utility hooks which run for every statement, so they're only installed
+/// for extensions that ask.  `#[pg_extern(memoize)]` functions ask for them themselves, and a
+/// [`StatementCache`](crate::memoize::StatementCache) used otherwise caches nothing without them.
+///
+/// # Panics
+///
+/// If it's first called once the extension has loaded, as the statements running by then would
+/// be miscounted.
+pub fn track() {
+    unsafe {
+        // SAFETY:  only this backend's own thread loads the extension
+        if !TRACKING && crate::init::has_loaded() {
+            panic!("`pgx::statement::track()` has to be called from `_PG_init()` or a `#[pg_init]` function");
+        }
+        TRACKING = true;
+    }
+}
+
+/// Install the hooks which count [`nesting_level()`] if the extension asked for them with
+/// [`track()`], which `_PG_init()` does once every `#[pg_init]` function has run
+pub(crate) unsafe fn install() {
+    if !TRACKING {
+        return;
+    }
+    PREV_EXECUTOR_RUN = pg_sys::ExecutorRun_hook.replace(executor_run);
+    PREV_EXECUTOR_FINISH = pg_sys::ExecutorFinish_hook.replace(executor_finish);
+    PREV_PROCESS_UTILITY = pg_sys::ProcessUtility_hook.replace(process_utility);
+    pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut());
+    pg_sys::RegisterSubXactCallback(Some(subxact_callback), std::ptr::null_mut());
+}
+
+/// Run `f` one level deeper, noting `query_id` if it's for the top-level statement
+///
+/// An `ERROR` raised by `f` skips the decrement, which the (sub)transaction callbacks make up for.
+unsafe fn nested(query_id: u64, f: impl FnOnce()) {
+    if NESTING_LEVEL == 0 {
+        TOP_LEVEL_QUERY_ID = query_id;
+    }
+    NESTING_LEVEL += 1;
+    f();
+    NESTING_LEVEL = NESTING_LEVEL.saturating_sub(1);
+    crate::memoize::statements_ended(NESTING_LEVEL);
+    if NESTING_LEVEL == 0 {
+        TOP_LEVEL_QUERY_ID = 0;
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn executor_run(
+    query_desc: *mut pg_sys::QueryDesc,
+    direction: pg_sys::ScanDirection,
+    count: u64,
+    execute_once: bool,
+) {
+    nested((*(*query_desc).plannedstmt).queryId, || match PREV_EXECUTOR_RUN {
+        Some(prev) => {
+            pg_sys::ffi::pg_guard_ffi_boundary(|| prev(query_desc, direction, count, execute_once))
+        }
+        None => pg_sys::standard_ExecutorRun(query_desc, direction, count, execute_once),
+    })
+}
+
+#[pg_guard]
+unsafe extern "C" fn executor_finish(query_desc: *mut pg_sys::QueryDesc) {
+    nested((*(*query_desc).plannedstmt).queryId, || match PREV_EXECUTOR_FINISH {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| prev(query_desc)),
+        None => pg_sys::standard_ExecutorFinish(query_desc),
+    })
+}
+
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+#[pg_guard]
+unsafe extern "C" fn process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const ::std::os::raw::c_char,
+    context: pg_sys::ProcessUtilityContext,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    completion_tag: *mut pg_sys::QueryCompletion,
+) {
+    nested((*pstmt).queryId, || match PREV_PROCESS_UTILITY {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| {
+            prev(pstmt, query_string, context, params, query_env, dest, completion_tag)
+        }),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            context,
+            params,
+            query_env,
+            dest,
+            completion_tag,
+        ),
+    })
+}
+
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+#[pg_guard]
+unsafe extern "C" fn process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const ::std::os::raw::c_char,
+    read_only_tree: bool,
+    context: pg_sys::ProcessUtilityContext,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    completion_tag: *mut pg_sys::QueryCompletion,
+) {
+    nested((*pstmt).queryId, || match PREV_PROCESS_UTILITY {
+        Some(prev) => pg_sys::ffi::pg_guard_ffi_boundary(|| {
+            prev(
+                pstmt,
+                query_string,
+                read_only_tree,
+                context,
+                params,
+                query_env,
+                dest,
+                completion_tag,
+            )
+        }),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            read_only_tree,
+            context,
+            params,
+            query_env,
+            dest,
+            completion_tag,
+        ),
+    })
+}
+
+#[pg_guard]
+unsafe extern "C" fn xact_callback(event: pg_sys::XactEvent, _arg: *mut std::os::raw::c_void) {
+    // a procedure's `COMMIT` ends the transaction while its `CALL` is still running, so only an
+    // abort says no statement is
+    if event == pg_sys::XactEvent_XACT_EVENT_ABORT {
+        NESTING_LEVEL = 0;
+        TOP_LEVEL_QUERY_ID = 0;
+    }
+    if matches!(
+        event,
+        pg_sys::XactEvent_XACT_EVENT_COMMIT
+            | pg_sys::XactEvent_XACT_EVENT_ABORT
+            | pg_sys::XactEvent_XACT_EVENT_PREPARE
+    ) {
+        SUBXACT_LEVELS.clear();
+        // the next transaction sees newer data, even in the same `CALL`
+        crate::memoize::statements_ended(0);
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn subxact_callback(
+    event: pg_sys::SubXactEvent,
+    my_subid: pg_sys::SubTransactionId,
+    _parent_subid: pg_sys::SubTransactionId,
+    _arg: *mut std::os::raw::c_void,
+) {
+    match event {
+        pg_sys::SubXactEvent_SUBXACT_EVENT_START_SUB => {
+            SUBXACT_LEVELS.push((my_subid, NESTING_LEVEL));
+        }
+        pg_sys::SubXactEvent_SUBXACT_EVENT_COMMIT_SUB => {
+            SUBXACT_LEVELS.retain(|(subid, _)| *subid != my_subid);
+        }
+        pg_sys::SubXactEvent_SUBXACT_EVENT_ABORT_SUB => {
+            if let Some(at) = SUBXACT_LEVELS.iter().position(|(subid, _)| *subid == my_subid) {
+                // the statements that were running when the ERROR was raised never finished
+                NESTING_LEVEL = SUBXACT_LEVELS[at].1;
+                SUBXACT_LEVELS.truncate(at);
+                crate::memoize::statements_ended(NESTING_LEVEL);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,290 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Reading the statistics `ANALYZE` gathers about a column, from `pg_statistic`
+//!
+//! These are what the planner estimates with, and what a selectivity or planner support function
+//! needs to make estimates of its own.  They're the same numbers the `pg_stats` view shows, read
+//! straight from the syscache, so they can be used while planning without SPI.
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//! use pgx::statistic::PgStatistics;
+//!
+//! fn most_common_ints(rel_oid: pg_sys::Oid, attnum: pg_sys::AttrNumber) -> Vec<(i32, f32)> {
+//!     let stats = match PgStatistics::for_column(rel_oid, attnum) {
+//!         Some(stats) => stats,
+//!         None => return vec![], // never analyzed
+//!     };
+//!     match stats.most_common_values() {
+//!         Some(mcv) => match mcv.values_as::<i32>() {
+//!             Ok(values) => values.into_iter().flatten().zip(mcv.numbers().iter().copied()).collect(),
+//!             Err(_) => vec![], // not an `int` column
+//!         },
+//!         None => vec![],
+//!     }
+//! }
+//! ```
+use crate::pg_sys::GETSTRUCT;
+use crate::{pg_sys, FromDatum, IntoDatum, PgRelation, TryFromDatumError};
+use std::marker::PhantomData;
+
+/// The statistics of one column of a table, from its row in `pg_statistic`
+///
+/// The row is held in the syscache, and released when this is dropped.
+pub struct PgStatistics {
+    tuple: pg_sys::HeapTuple,
+    reltuples: Option<f64>,
+}
+
+impl PgStatistics {
+    /// The statistics of the column `attnum` of the table `rel_oid`, or `None` if it hasn't
+    /// been analyzed.
+    ///
+    /// For a table with inheritance children, these are of the table alone, as they are for
+    /// `pg_stats.inherited = false`.  Use [`PgStatistics::for_column_inherited()`] for the
+    /// statistics of the whole inheritance tree.
+    pub fn for_column(rel_oid: pg_sys::Oid, attnum: pg_sys::AttrNumber) -> Option<Self> {
+        Self::lookup(rel_oid, attnum, false)
+    }
+
+    /// The statistics of the column `attnum` of the table `rel_oid` and its inheritance children,
+    /// or of a partitioned table's partitions, or `None` if they haven't been analyzed.
+    pub fn for_column_inherited(rel_oid: pg_sys::Oid, attnum: pg_sys::AttrNumber) -> Option<Self> {
+        Self::lookup(rel_oid, attnum, true)
+    }
+
+    fn lookup(rel_oid: pg_sys::Oid, attnum: pg_sys::AttrNumber, inherit: bool) -> Option<Self> {
+        let reltuples = unsafe {
+            // SAFETY:  the relation is locked for the rest of the transaction, which is what the
+            // planner already holds on the tables it plans for
+            PgRelation::with_lock(rel_oid, pg_sys::AccessShareLock as pg_sys::LOCKMODE)
+        }
+        .reltuples()
+        .filter(|reltuples| *reltuples > 0.0)
+        .map(f64::from);
+
+        let tuple = unsafe {
+            pg_sys::SearchSysCache3(
+                pg_sys::SysCacheIdentifier_STATRELATTINH as i32,
+                pg_sys::Datum::from(rel_oid),
+                pg_sys::Datum::from_i16(attnum),
+                pg_sys::Datum::from_bool(inherit),
+            )
+        };
+        if tuple.is_null() {
+            None
+        } else {
+            Some(PgStatistics { tuple, reltuples })
+        }
+    }
+
+    fn form(&self) -> &pg_sys::FormData_pg_statistic {
+        // SAFETY:  `tuple` is a valid `pg_statistic` row for as long as we hold it
+        unsafe { &*(GETSTRUCT(self.tuple) as *const pg_sys::FormData_pg_statistic) }
+    }
+
+    /// The fraction of the column's values which are `NULL`
+    pub fn null_frac(&self) -> f32 {
+        self.form().stanullfrac
+    }
+
+    /// The average width, in bytes, of the column's non-`NULL` values
+    pub fn avg_width(&self) -> i32 {
+        self.form().stawidth
+    }
+
+    /// The number of distinct non-`NULL` values, as it's stored:  a positive number is a count,
+    /// a negative one is minus the fraction of the table's rows, and `0.0` means it's unknown.
+    ///
+    /// See [`PgStatistics::estimated_distinct()`] for the count either way.
+    pub fn n_distinct(&self) -> f32 {
+        self.form().stadistinct
+    }
+
+    /// The estimated number of distinct non-`NULL` values, or `None` if it's unknown
+    pub fn estimated_distinct(&self) -> Option<f64> {
+        let n_distinct = f64::from(self.n_distinct());
+        if n_distinct > 0.0 {
+            Some(n_distinct)
+        } else if n_distinct < 0.0 {
+            self.reltuples.map(|reltuples| (-n_distinct * reltuples).round().max(1.0))
+        } else {
+            None
+        }
+    }
+
+    /// The most common values, and in [`StatsSlot::numbers()`] their frequencies, as in
+    /// `pg_stats.most_common_vals` and `most_common_freqs`
+    pub fn most_common_values(&self) -> Option<StatsSlot<'_>> {
+        self.slot(
+            pg_sys::STATISTIC_KIND_MCV,
+            pg_sys::ATTSTATSSLOT_VALUES | pg_sys::ATTSTATSSLOT_NUMBERS,
+        )
+    }
+
+    /// The bounds of the histogram of the values that aren't among the most common ones, as in
+    /// `pg_stats.histogram_bounds`
+    pub fn histogram_bounds(&self) -> Option<StatsSlot<'_>> {
+        self.slot(pg_sys::STATISTIC_KIND_HISTOGRAM, pg_sys::ATTSTATSSLOT_VALUES)
+    }
+
+    /// Any kind of slot, such as one a custom `ANALYZE` function stored, with the `flags` saying
+    /// which of its values and numbers to read
+    pub fn slot(&self, kind: u32, flags: u32) -> Option<StatsSlot<'_>> {
+        let mut slot = pg_sys::AttStatsSlot::default();
+        let found = unsafe {
+            pg_sys::get_attstatsslot(
+                &mut slot,
+                self.tuple,
+                kind as i32,
+                pg_sys::InvalidOid,
+                flags as i32,
+            )
+        };
+        if found {
+            Some(StatsSlot { slot, _statistics: PhantomData })
+        } else {
+            None
+        }
+    }
+
+    /// Estimate the fraction of rows for which `column = constant` is true, much the way the
+    /// planner's `eqsel()` does
+    ///
+    /// It's the constant's frequency if it's one of the most common values, and otherwise the
+    /// rows that aren't among them are assumed to be spread evenly among the other distinct values.
+    /// A `NULL` constant matches no rows.  If the constant isn't of the column's type, which the
+    /// equality operator the statistics were gathered with can't compare, it's Postgres' default
+    /// of [`pg_sys::DEFAULT_EQ_SEL`].
+    ///
+    /// # Safety
+    ///
+    /// `constant` must be a valid [`pg_sys::Const`], such as one from [`crate::make_const()`].
+    pub unsafe fn eq_selectivity(&self, constant: *const pg_sys::Const) -> f64 {
+        let constant = &*constant;
+        if constant.constisnull {
+            return 0.0;
+        }
+
+        let mut sum_common = 0.0;
+        let mut n_common = 0;
+        let mut least_common = 1.0f64;
+        if let Some(mcv) = self.most_common_values() {
+            if mcv.element_type() != constant.consttype {
+                return pg_sys::DEFAULT_EQ_SEL;
+            }
+            let eq = pg_sys::get_opcode(mcv.operator());
+            let collation = mcv.collation().unwrap_or(constant.constcollid);
+            for (value, frequency) in mcv.raw_values().iter().zip(mcv.numbers()) {
+                let matches =
+                    pg_sys::OidFunctionCall2Coll(eq, collation, *value, constant.constvalue);
+                if matches.value() != 0 {
+                    return f64::from(*frequency);
+                }
+                sum_common += f64::from(*frequency);
+                least_common = least_common.min(f64::from(*frequency));
+            }
+            n_common = mcv.raw_values().len();
+        }
+
+        let mut selectivity = (1.0 - sum_common - f64::from(self.null_frac())).clamp(0.0, 1.0);
+        let n_distinct =
+            self.estimated_distinct().unwrap_or(f64::from(pg_sys::DEFAULT_NUM_DISTINCT));
+        let other_distinct = n_distinct - n_common as f64;
+        if other_distinct > 1.0 {
+            selectivity /= other_distinct;
+        }
+        if n_common > 0 && selectivity > least_common {
+            selectivity = least_common;
+        }
+        selectivity.clamp(0.0, 1.0)
+    }
+}
+
+impl Drop for PgStatistics {
+    fn drop(&mut self) {
+        unsafe { pg_sys::ReleaseSysCache(self.tuple) }
+    }
+}
+
+/// One slot of a column's [`PgStatistics`], whose arrays are freed when it's dropped
+pub struct StatsSlot<'a> {
+    slot: pg_sys::AttStatsSlot,
+    _statistics: PhantomData<&'a PgStatistics>,
+}
+
+impl<'a> StatsSlot<'a> {
+    /// The type of the slot's values, which is the column's type, or for some kinds of slot, like
+    /// those of arrays, its element type
+    pub fn element_type(&self) -> pg_sys::Oid {
+        self.slot.valuetype
+    }
+
+    /// The operator the statistics were gathered with, such as `=` for the most common values
+    /// and `<` for a histogram
+    pub fn operator(&self) -> pg_sys::Oid {
+        self.slot.staop
+    }
+
+    /// The collation the statistics were gathered with, if the column's type is collatable
+    #[cfg(feature = "pg11")]
+    pub fn collation(&self) -> Option<pg_sys::Oid> {
+        None
+    }
+
+    /// The collation the statistics were gathered with, if the column's type is collatable
+    #[cfg(not(feature = "pg11"))]
+    pub fn collation(&self) -> Option<pg_sys::Oid> {
+        Some(self.slot.stacoll).filter(|collation| *collation != pg_sys::InvalidOid)
+    }
+
+    /// The slot's values, which are never `NULL`, as datums of [`StatsSlot::element_type()`]
+    pub fn raw_values(&self) -> &[pg_sys::Datum] {
+        if self.slot.values.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.slot.values, self.slot.nvalues as usize) }
+        }
+    }
+
+    /// The slot's values, as in `pg_stats`, ready for [`FromDatum::from_polymorphic_datum()`]
+    /// or [`crate::datum::debug_datum()`] with the [`StatsSlot::element_type()`]
+    pub fn values(&self) -> Vec<Option<pg_sys::Datum>> {
+        self.raw_values().iter().copied().map(Some).collect()
+    }
+
+    /// The slot's values, converted to `T`, or [`TryFromDatumError::IncompatibleTypes`] if `T`
+    /// isn't the Rust type of [`StatsSlot::element_type()`]
+    ///
+    /// A `T` that borrows from the datum, like `&str`, must not be used after the slot is dropped.
+    pub fn values_as<T: FromDatum + IntoDatum>(&self) -> Result<Vec<Option<T>>, TryFromDatumError> {
+        self.raw_values()
+            .iter()
+            // SAFETY:  `try_from_datum()` checks `T` against the element type before converting,
+            // and the slot's values are valid datums of that type
+            .map(|value| unsafe { T::try_from_datum(*value, false, self.element_type()) })
+            .collect()
+    }
+
+    /// The slot's numbers, such as the frequencies of the most common values
+    pub fn numbers(&self) -> &[f32] {
+        if self.slot.numbers.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.slot.numbers, self.slot.nnumbers as usize) }
+        }
+    }
+}
+
+impl<'a> Drop for StatsSlot<'a> {
+    fn drop(&mut self) {
+        unsafe { pg_sys::free_attstatsslot(&mut self.slot) }
+    }
+}
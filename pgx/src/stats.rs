@@ -0,0 +1,202 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Named counters that any backend can increment cheaply and that are visible cluster-wide.
+//!
+//! Counters are defined by name with [`define_counter`], which must be called from `_PG_init()`
+//! while the extension is being loaded through `shared_preload_libraries`.  Their values live in
+//! shared memory, behind a [`PgLwLock`].
+//!
+//! [`Counter::incr`] doesn't take that lock.  Instead it adds to a count that's pending in the
+//! current backend, and all of a backend's pending counts are added to shared memory at once when
+//! its transaction commits or aborts.  Outside of a transaction, such as in a background worker
+//! that isn't running one, increments are added to shared memory immediately.
+//!
+//! The [`pg_stats_counters!()`](crate::pg_stats_counters) macro creates a SQL function returning
+//! every counter as a `(name, value)` table.
+//!
+//! Postgres' cumulative statistics system doesn't let extensions register their own kinds of
+//! statistics in any version pgx supports, so counters are kept in pgx-managed shared memory on
+//! every version.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::once_cell::sync::OnceCell;
+//! use pgx::prelude::*;
+//! use pgx::stats::{define_counter, Counter};
+//!
+//! static CACHE_HITS: OnceCell<Counter> = OnceCell::new();
+//!
+//! #[pg_guard]
+//! pub extern "C" fn _PG_init() {
+//!     CACHE_HITS.set(define_counter("cache_hits")).unwrap();
+//! }
+//!
+//! #[pg_extern]
+//! fn lookup(key: &str) -> Option<String> {
+//!     CACHE_HITS.get().unwrap().incr(1);
+//!     None
+//! }
+//!
+//! // creates `my_extension_counters() RETURNS TABLE (name text, value bigint)`
+//! pgx::pg_stats_counters!(my_extension_counters);
+//! ```
+use crate as pgx; // for #[pg_guard] support from within ourself
+use crate::{
+    pg_guard, pg_shmem_init, pg_sys, register_xact_callback, PGXSharedMemory, PgLwLock,
+    PgSharedMemoryInitialization, PgXactCallbackEvent,
+};
+
+/// The most counters one extension can define
+pub const MAX_COUNTERS: usize = 64;
+
+static SHARED_COUNTERS: PgLwLock<SharedCounters> = PgLwLock::new();
+
+// these are all backend-local, and only ever accessed from the backend's main thread
+static mut NAMES: Vec<&'static str> = Vec::new();
+static mut PENDING: [u64; MAX_COUNTERS] = [0; MAX_COUNTERS];
+static mut FLUSH_REGISTERED: bool = false;
+
+#[derive(Copy, Clone)]
+struct SharedCounters {
+    values: [u64; MAX_COUNTERS],
+}
+
+impl Default for SharedCounters {
+    fn default() -> Self {
+        Self { values: [0; MAX_COUNTERS] }
+    }
+}
+
+unsafe impl PGXSharedMemory for SharedCounters {}
+
+/// A handle to a counter created by [`define_counter`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Counter {
+    index: usize,
+}
+
+impl Counter {
+    /// The name this counter was defined with
+    pub fn name(&self) -> &'static str {
+        unsafe { NAMES[self.index] }
+    }
+
+    /// Adds `n` to this counter.
+    ///
+    /// Inside a transaction this only updates backend-local memory; see the
+    /// [module documentation](crate::stats) for when it becomes visible to other backends.
+    pub fn incr(&self, n: u64) {
+        unsafe {
+            PENDING[self.index] = PENDING[self.index].wrapping_add(n);
+
+            if !pg_sys::IsTransactionState() {
+                flush_counters();
+            } else if !FLUSH_REGISTERED {
+                FLUSH_REGISTERED = true;
+                register_xact_callback(PgXactCallbackEvent::Commit, flush_counters);
+                register_xact_callback(PgXactCallbackEvent::Abort, flush_counters);
+            }
+        }
+    }
+
+    /// The counter's cluster-wide value, including any increments still pending in this backend
+    pub fn get(&self) -> u64 {
+        let shared = SHARED_COUNTERS.share().values[self.index];
+        shared.wrapping_add(unsafe { PENDING[self.index] })
+    }
+}
+
+/// Defines a new counter, starting at zero, and returns its handle.
+///
+/// # Panics
+///
+/// This function panics if it's not called from `_PG_init()` while the extension is being loaded
+/// through `shared_preload_libraries`, if a counter named `name` already exists, or if
+/// [`MAX_COUNTERS`] counters already exist.
+pub fn define_counter(name: &'static str) -> Counter {
+    unsafe {
+        assert!(
+            pg_sys::process_shared_preload_libraries_in_progress,
+            "counters can only be defined in `_PG_init()` of an extension loaded through `shared_preload_libraries`"
+        );
+        assert!(!NAMES.contains(&name), "a counter named `{name}` is already defined");
+        assert!(NAMES.len() < MAX_COUNTERS, "no more than {MAX_COUNTERS} counters can be defined");
+
+        if NAMES.is_empty() {
+            pg_shmem_init!(SHARED_COUNTERS);
+        }
+        NAMES.push(name);
+        Counter { index: NAMES.len() - 1 }
+    }
+}
+
+/// Finds the counter defined with `name`
+pub fn counter(name: &str) -> Option<Counter> {
+    unsafe { NAMES.iter().position(|&other| other == name).map(|index| Counter { index }) }
+}
+
+/// Returns the name and value of every counter, in the order they were defined.
+///
+/// Like [`Counter::get`], the values include increments still pending in this backend.
+pub fn counters() -> Vec<(&'static str, u64)> {
+    unsafe {
+        if NAMES.is_empty() {
+            return Vec::new();
+        }
+        let shared = SHARED_COUNTERS.share();
+        NAMES
+            .iter()
+            .enumerate()
+            .map(|(index, &name)| (name, shared.values[index].wrapping_add(PENDING[index])))
+            .collect()
+    }
+}
+
+/// Adds this backend's pending increments to shared memory now, rather than at the end of the
+/// current transaction
+pub fn flush_counters() {
+    unsafe {
+        FLUSH_REGISTERED = false;
+        if PENDING.iter().all(|&pending| pending == 0) {
+            return;
+        }
+
+        let mut shared = SHARED_COUNTERS.exclusive();
+        for (value, pending) in shared.values.iter_mut().zip(PENDING.iter_mut()) {
+            *value = value.wrapping_add(std::mem::take(pending));
+        }
+    }
+}
+
+/// Creates a `#[pg_extern]` function named `$fn_name` that returns every counter defined with
+/// [`define_counter`](crate::stats::define_counter) as a `TABLE (name text, value bigint)`.
+///
+/// Values larger than `i64::MAX` wrap around to negative numbers.
+///
+/// ```rust,no_run
+/// pgx::pg_stats_counters!(my_extension_counters);
+/// ```
+#[macro_export]
+macro_rules! pg_stats_counters {
+    ($fn_name:ident) => {
+        #[$crate::pg_extern]
+        fn $fn_name() -> $crate::iter::TableIterator<
+            'static,
+            ($crate::name!(name, String), $crate::name!(value, i64)),
+        > {
+            $crate::iter::TableIterator::new(
+                $crate::stats::counters()
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value as i64)),
+            )
+        }
+    };
+}
@@ -10,10 +10,17 @@ Use of this source code is governed by the MIT license that can be found in the
 //! A safe wrapper around Postgres `StringInfo` structure
 #![allow(dead_code, non_snake_case)]
 
-use crate::{pg_sys, AllocatedByPostgres, AllocatedByRust, PgBox, WhoAllocated};
+use crate::{
+    pg_sys, set_varsize, AllocatedByPostgres, AllocatedByRust, FromDatum, IntoDatum, PgBox,
+    PgMemoryContexts, WhoAllocated,
+};
 use core::fmt::{Display, Formatter};
 use core::str::Utf8Error;
+use pgx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
 use std::io::Error;
+use std::mem::ManuallyDrop;
 
 /// StringInfoData holds information about an extensible string that is allocated by Postgres'
 /// memory system, but generally follows Rust's drop semantics
@@ -46,6 +53,13 @@ impl<AllocatedBy: WhoAllocated> std::io::Write for StringInfo<AllocatedBy> {
     }
 }
 
+impl<AllocatedBy: WhoAllocated> core::fmt::Write for StringInfo<AllocatedBy> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
 impl<AllocatedBy: WhoAllocated> Display for StringInfo<AllocatedBy> {
     /// Convert this [`StringInfo`] into a Rust string.  This uses [`String::from_utf8_lossy`] as
     /// it's fine for a Postgres [`StringInfo`] to contain null bytes and also not even be proper
@@ -78,6 +92,28 @@ impl StringInfo<AllocatedByRust> {
         si.enlarge(len);
         si
     }
+
+    /// Construct a new `StringInfo` of its default size, allocated in the specified memory context
+    /// rather than `CurrentMemoryContext`.  It grows within that memory context, too.
+    ///
+    /// # Safety
+    ///
+    /// The memory context must outlive the returned `StringInfo`, or it'll be used, and `pfree()`'d
+    /// when dropped, after being freed.
+    pub unsafe fn new_in(mut memory_context: PgMemoryContexts) -> Self {
+        memory_context.switch_to(|_| StringInfo::new())
+    }
+
+    /// Construct a new `StringInfo`, allocated in the specified memory context rather than
+    /// `CurrentMemoryContext`, ensuring it has a capacity of the specified `len`.
+    ///
+    /// # Safety
+    ///
+    /// The memory context must outlive the returned `StringInfo`, or it'll be used, and `pfree()`'d
+    /// when dropped, after being freed.
+    pub unsafe fn with_capacity_in(len: i32, mut memory_context: PgMemoryContexts) -> Self {
+        memory_context.switch_to(|_| StringInfo::with_capacity(len))
+    }
 }
 
 impl StringInfo<AllocatedByPostgres> {
@@ -177,6 +213,16 @@ impl<AllocatedBy: WhoAllocated> StringInfo<AllocatedBy> {
         }
     }
 
+    /// Ensure that at least `additional` more bytes can be pushed without reallocating
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `additional` is larger than an `i32`
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.enlarge(additional.try_into().expect("additional doesn't fit in an i32"))
+    }
+
     /// A `&str` representation.
     ///
     /// # Errors
@@ -281,6 +327,110 @@ impl From<&[u8]> for StringInfo<AllocatedByRust> {
     }
 }
 
+/// A `StringInfo` is returned from a `#[pg_extern]` function as `text`.
+///
+/// Its buffer becomes the `text` datum:  its bytes are moved along within the buffer to make room
+/// for the varlena header, rather than copied into a second allocation as a `String`'s are, so only
+/// one buffer of the result's size is ever allocated.  The bytes are still moved once, though.  If
+/// the buffer has no room left for the header, it's enlarged first, and when `repalloc()` can't
+/// grow it in place, that's a copy into a new allocation after all.
+///
+/// As with any `text`, the contents should be valid in the database's encoding.
+impl<AllocatedBy: WhoAllocated> IntoDatum for StringInfo<AllocatedBy> {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        let len = self.len();
+        let sid = self.into_pg();
+        unsafe {
+            // SAFETY:  into_pg() gives us a valid StringInfoData pointer, whose buffer holds `len`
+            // bytes, and has room for the header after we enlarge it
+            pg_sys::enlargeStringInfo(sid, pg_sys::VARHDRSZ as i32);
+            let data = (*sid).data;
+            std::ptr::copy(data, data.add(pg_sys::VARHDRSZ), len);
+            set_varsize(data.cast(), (len + pg_sys::VARHDRSZ) as i32);
+
+            // the buffer is the datum's now, but the StringInfoData itself is ours to free
+            AllocatedBy::maybe_pfree(sid.cast());
+            Some(pg_sys::Datum::from(data))
+        }
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pg_sys::TEXTOID
+    }
+}
+
+/// A `&StringInfo` argument wraps an `internal` `StringInfo` pointer, such as the message buffer
+/// that Postgres passes to a type's binary receive function.
+impl<'a> FromDatum for &'a StringInfo {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null || datum.is_null() {
+            None
+        } else {
+            let si = wrap_postgres_stringinfo(datum);
+            Some(&**PgMemoryContexts::CurrentMemoryContext.leak_trivial_alloc(si))
+        }
+    }
+}
+
+/// Like `&StringInfo`, for receive functions, which advance the buffer's `cursor` as they read
+impl<'a> FromDatum for &'a mut StringInfo {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null || datum.is_null() {
+            None
+        } else {
+            let si = wrap_postgres_stringinfo(datum);
+            Some(&mut **PgMemoryContexts::CurrentMemoryContext.leak_trivial_alloc(si))
+        }
+    }
+}
+
+/// Postgres owns the StringInfo behind `datum`, so it's never dropped by us
+unsafe fn wrap_postgres_stringinfo(datum: pg_sys::Datum) -> ManuallyDrop<StringInfo> {
+    ManuallyDrop::new(StringInfo { inner: PgBox::from_rust(datum.cast_mut_ptr()) })
+}
+
+unsafe impl SqlTranslatable for StringInfo {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("text"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("text")))
+    }
+}
+
+unsafe impl<'a> SqlTranslatable for &'a StringInfo {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("internal"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("internal")))
+    }
+    // like `pgx::Internal`, don't strict upgrade
+    fn optional() -> bool {
+        true
+    }
+}
+
+unsafe impl<'a> SqlTranslatable for &'a mut StringInfo {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("internal"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("internal")))
+    }
+    fn optional() -> bool {
+        true
+    }
+}
+
 impl<AllocatedBy: WhoAllocated> Drop for StringInfo<AllocatedBy> {
     fn drop(&mut self) {
         unsafe {
@@ -0,0 +1,309 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Temporary files for spilling state that doesn't fit in `work_mem`, made with Postgres'
+//! `BufFile`s.
+//!
+//! Unlike a [`std::fs::File`] in `/tmp`, a [`PgTempFile`] is made in the `temp_tablespaces`,
+//! counts towards `temp_file_limit`, and is deleted by Postgres when the resource owner it
+//! belongs to is released, such as when the transaction ends, even if it ends in an `ERROR`.  It's [`Read`], [`Write`] and [`Seek`], so anything that serializes
+//! to a writer can be spilled to one, and [`PgTempFile::write_datum`] and
+//! [`PgTempFile::read_datum`] spill `Datum`s of any type.
+//!
+//! A `BufFile` buffers one block, so many small reads and writes are cheap.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use pgx::temp_file::PgTempFile;
+//! use std::io::{Read, Seek, SeekFrom, Write};
+//!
+//! // SAFETY: it's dropped before the transaction ends
+//! let mut file = unsafe { PgTempFile::new() };
+//! file.write_all(b"spilled").unwrap();
+//! file.seek(SeekFrom::Start(0)).unwrap();
+//!
+//! let mut spilled = String::new();
+//! file.read_to_string(&mut spilled).unwrap();
+//! assert_eq!(spilled, "spilled");
+//! ```
+use crate::{compat, pg_sys, FromDatum, IntoDatum};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ptr::NonNull;
+
+/// How big each of the physical files a `BufFile` is made of can get, which is `buffile.c`'s
+/// `MAX_PHYSICAL_FILESIZE`
+const SEGMENT_SIZE: u64 = 0x4000_0000;
+
+/// A temporary file, which is deleted when it's dropped or its resource owner is released.
+///
+/// A `BufFile` belongs to the resource owner that was current when it was made, which closes it
+/// and frees it when it's released, so a [`PgTempFile`] mustn't outlive that.  See
+/// [`PgTempFile::new`].
+pub struct PgTempFile {
+    file: NonNull<pg_sys::BufFile>,
+    /// How many bytes were written, as Postgres 11 can't seek to the end of a `BufFile`
+    len: u64,
+    /// Where the next read or write will be
+    position: u64,
+}
+
+impl PgTempFile {
+    /// Make a new, empty, temporary file in one of the `temp_tablespaces`.
+    ///
+    /// It's registered with the current resource owner, which closes and deletes it if the
+    /// transaction is aborted.
+    ///
+    /// ## Safety
+    ///
+    /// The file mustn't be used, or dropped, after the current resource owner is released, which
+    /// is at the end of the current transaction, subtransaction, or query, depending on where it's
+    /// called, nor after the current memory context, which its `BufFile` is allocated in, is
+    /// reset.  Dropping it while unwinding from an `ERROR` is fine, as it's left for the resource
+    /// owner to close then.
+    pub unsafe fn new() -> PgTempFile {
+        pg_sys::PrepareTempTablespaces();
+        let file = pg_sys::BufFileCreateTemp(false);
+        PgTempFile {
+            file: NonNull::new(file).expect("BufFileCreateTemp returned null"),
+            len: 0,
+            position: 0,
+        }
+    }
+
+    /// The underlying [`pg_sys::BufFile`], which is still owned by this [`PgTempFile`]
+    #[inline]
+    pub fn as_ptr(&self) -> *mut pg_sys::BufFile {
+        self.file.as_ptr()
+    }
+
+    /// How many bytes long the file is
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Is nothing in the file?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Go back to the start of the file, to read what was written to it.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    /// Write `datum`, a `Datum` of the type `typoid` or `None` for `NULL`, after what's been
+    /// written already, for [`PgTempFile::read_datum`] to read back.
+    ///
+    /// Pass-by-reference values are written in full, so a TOASTed value is detoasted first.
+    ///
+    /// ## Safety
+    ///
+    /// `datum` must be a valid `Datum` of the type `typoid`.
+    pub unsafe fn write_datum(
+        &mut self,
+        typoid: pg_sys::Oid,
+        datum: Option<pg_sys::Datum>,
+    ) -> io::Result<()> {
+        self.write_all(&u32::from(typoid).to_ne_bytes())?;
+        let datum = match datum {
+            None => return self.write_all(&[0]),
+            Some(datum) => datum,
+        };
+        self.write_all(&[1])?;
+
+        let (typlen, typbyval) = typlenbyval(typoid);
+        if typbyval {
+            return self.write_all(&datum.value().to_ne_bytes());
+        }
+        let (ptr, len) = match typlen {
+            -1 => {
+                let varlena = pg_sys::pg_detoast_datum_packed(datum.cast_mut_ptr());
+                (varlena as *const u8, crate::varlena::varsize_any(varlena))
+            }
+            -2 => {
+                let cstr = std::ffi::CStr::from_ptr(datum.cast_mut_ptr());
+                (cstr.as_ptr() as *const u8, cstr.to_bytes_with_nul().len())
+            }
+            typlen => (datum.cast_mut_ptr::<u8>() as *const u8, typlen as usize),
+        };
+        self.write_all(&(len as u64).to_ne_bytes())?;
+        self.write_all(std::slice::from_raw_parts(ptr, len))
+    }
+
+    /// Write `value`, for [`PgTempFile::read_value`] to read back.
+    pub fn write_value<T: IntoDatum>(&mut self, value: T) -> io::Result<()> {
+        let typoid = T::type_oid();
+        // SAFETY: `into_datum()` makes a Datum of the type `T::type_oid()`
+        unsafe { self.write_datum(typoid, value.into_datum()) }
+    }
+
+    /// Read the next `Datum` [`PgTempFile::write_datum`] wrote, and its type, or `None` at the end
+    /// of the file.
+    ///
+    /// A pass-by-reference value is read into memory `palloc`'d in the current memory context.
+    ///
+    /// ## Errors
+    ///
+    /// An [`io::ErrorKind::UnexpectedEof`] if the file ends in the middle of a `Datum`, which it
+    /// only does if it was read from the wrong place.
+    pub fn read_datum(&mut self) -> io::Result<Option<(pg_sys::Oid, Option<pg_sys::Datum>)>> {
+        let mut typoid = [0; 4];
+        match self.read(&mut typoid[..1])? {
+            0 => return Ok(None),
+            _ => self.read_exact(&mut typoid[1..])?,
+        }
+        // SAFETY: it's the oid of a type `write_datum()` was given
+        let typoid = unsafe { pg_sys::Oid::from_u32_unchecked(u32::from_ne_bytes(typoid)) };
+
+        let mut present = [0];
+        self.read_exact(&mut present)?;
+        if present[0] == 0 {
+            return Ok(Some((typoid, None)));
+        }
+
+        let (_, typbyval) = typlenbyval(typoid);
+        let mut word = [0; std::mem::size_of::<u64>()];
+        self.read_exact(&mut word)?;
+        if typbyval {
+            return Ok(Some((typoid, Some(pg_sys::Datum::from(usize::from_ne_bytes(word))))));
+        }
+        let len = u64::from_ne_bytes(word) as usize;
+        unsafe {
+            let ptr = pg_sys::palloc(len) as *mut u8;
+            self.read_exact(std::slice::from_raw_parts_mut(ptr, len))?;
+            Ok(Some((typoid, Some(pg_sys::Datum::from(ptr)))))
+        }
+    }
+
+    /// Read the next value [`PgTempFile::write_value`] wrote, or `None` at the end of the file.
+    ///
+    /// ## Errors
+    ///
+    /// An [`io::ErrorKind::InvalidData`] if the value isn't a `T`.
+    pub fn read_value<T: FromDatum + IntoDatum>(&mut self) -> io::Result<Option<Option<T>>> {
+        match self.read_datum()? {
+            None => Ok(None),
+            Some((typoid, datum)) => unsafe {
+                T::try_from_datum(datum.unwrap_or(pg_sys::Datum::from(0)), datum.is_none(), typoid)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            },
+        }
+    }
+}
+
+fn typlenbyval(typoid: pg_sys::Oid) -> (i16, bool) {
+    let mut typlen = 0;
+    let mut typbyval = false;
+    unsafe { pg_sys::get_typlenbyval(typoid, &mut typlen, &mut typbyval) };
+    (typlen, typbyval)
+}
+
+impl Read for PgTempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read =
+            unsafe { pg_sys::BufFileRead(self.file.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for PgTempFile {
+    /// Writes all of `buf`, as a `BufFile` raises an `ERROR` rather than writing less, such as when
+    /// it would go over `temp_file_limit`.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written =
+            unsafe { compat::buf_file_write(self.file.as_ptr(), buf.as_ptr().cast(), buf.len()) };
+        self.position += written as u64;
+        self.len = self.len.max(self.position);
+        if written == 0 && !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "couldn't write to temp file"));
+        }
+        Ok(written)
+    }
+
+    /// A `BufFile` writes its buffer out when it needs to, and when it's read from or seeked, so
+    /// there's nothing to flush.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for PgTempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => offset_by(self.position, offset),
+            SeekFrom::End(offset) => offset_by(self.len, offset),
+        };
+        let position = match position {
+            Some(position) if position <= self.len => position,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "can't seek before the start or after the end of a temp file",
+                ))
+            }
+        };
+
+        // the end of a file whose last segment is full is the end of that segment, not the start
+        // of a segment that doesn't exist
+        let (fileno, offset) = match (position / SEGMENT_SIZE, position % SEGMENT_SIZE) {
+            (fileno, 0) if fileno > 0 && position == self.len => (fileno - 1, SEGMENT_SIZE),
+            (fileno, offset) => (fileno, offset),
+        };
+        let result = unsafe {
+            pg_sys::BufFileSeek(
+                self.file.as_ptr(),
+                fileno as i32,
+                offset as pg_sys::off_t,
+                pg_sys::SEEK_SET as i32,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::new(io::ErrorKind::Other, "couldn't seek in temp file"));
+        }
+        self.position = position;
+        Ok(position)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+fn offset_by(position: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        position.checked_sub(offset.unsigned_abs())
+    } else {
+        position.checked_add(offset as u64)
+    }
+}
+
+impl Drop for PgTempFile {
+    fn drop(&mut self) {
+        // while unwinding from an `ERROR`, such as one raised while writing, closing the file
+        // could raise another, so it's left for the transaction's resource owner to close
+        if !std::thread::panicking() {
+            unsafe { pg_sys::BufFileClose(self.file.as_ptr()) }
+        }
+    }
+}
+
+impl std::fmt::Debug for PgTempFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PgTempFile")
+            .field("len", &self.len)
+            .field("position", &self.position)
+            .finish()
+    }
+}
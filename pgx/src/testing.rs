@@ -0,0 +1,405 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Support for `#[pg_test]` functions
+//!
+//! These live here, rather than in `pgx-tests`, because `#[pg_test]` functions are compiled into
+//! the extension itself, which only has `pgx-tests` as a dev-dependency.
+use crate::{
+    pg_sys, quote_identifier, spi, FromDatum, IntoDatum, PgBuiltInOids, PgOid, Spi,
+    TimestampWithTimeZone,
+};
+use core::ffi::CStr;
+use std::fmt::{Debug, Display, Formatter};
+
+/// The result of a `#[pg_test]` function whose errors are converted with `?`.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::testing::TestResult;
+///
+/// #[derive(Debug, thiserror::Error)]
+/// enum MyError {
+///     #[error("no rows")]
+///     NoRows,
+/// }
+///
+/// #[pg_test]
+/// fn test_both_errors() -> TestResult {
+///     let count = Spi::get_one::<i64>("SELECT count(*) FROM pg_class")?;
+///     count.ok_or(MyError::NoRows)?;
+///     Ok(())
+/// }
+/// ```
+pub type TestResult = Result<(), TestError>;
+
+/// Any error, which fails a `#[pg_test]` with the error's [`Display`] text.
+///
+/// Every [`std::error::Error`] converts into it, such as [`spi::Error`] and the error enums of the
+/// extension being tested, so they can all be returned with `?` from the same test.
+pub struct TestError(Box<dyn std::error::Error + 'static>);
+
+impl<E: std::error::Error + 'static> From<E> for TestError {
+    fn from(error: E) -> Self {
+        TestError(Box::new(error))
+    }
+}
+
+impl Display for TestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Debug for TestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+/// A fixture for `#[pg_test]` functions, which they get by taking a `&TestDb` argument.
+///
+/// Each test gets a schema of its own, named after the test, which is put at the front of the
+/// `search_path` for the rest of its transaction, so the tables it creates without a schema go
+/// in it.  It's dropped when the test returns.  A test that fails with an `ERROR` or a panic
+/// aborts its transaction instead, which takes the schema with it.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::testing::{TestDb, TestResult};
+///
+/// #[pg_test]
+/// fn test_with_fixture(db: &TestDb) -> TestResult {
+///     db.execute("CREATE TABLE t AS SELECT generate_series(1, 3) AS x")?;
+///     assert_eq!(db.query::<i32>("SELECT x FROM t ORDER BY x")?, vec![Some(1), Some(2), Some(3)]);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TestDb {
+    database: String,
+    schema: String,
+}
+
+impl TestDb {
+    /// Run `f` with a new [`TestDb`], for the test named `test_name`.  This is what `#[pg_test]`
+    /// calls for a test that takes a `&TestDb`.
+    pub fn with<R, F: FnOnce(&TestDb) -> R>(test_name: &str, f: F) -> R {
+        let db = TestDb::create(test_name).expect("couldn't create the test's scratch schema");
+        let result = f(&db);
+        Spi::run(&format!("DROP SCHEMA {} CASCADE", quote_identifier(&db.schema)))
+            .expect("couldn't drop the test's scratch schema");
+        result
+    }
+
+    fn create(test_name: &str) -> Result<TestDb, spi::Error> {
+        let schema = format!("pgx_test_{}", test_name);
+        Spi::run(&format!("CREATE SCHEMA {}", quote_identifier(&schema)))?;
+        Spi::run(&format!(
+            "SELECT set_config('search_path', {} || ', ' || current_setting('search_path'), true)",
+            crate::quote_literal(&quote_identifier(&schema))
+        ))?;
+        let database = Spi::get_one::<String>("SELECT current_database()::text")?
+            .expect("current_database() is never NULL");
+        Ok(TestDb { database, schema })
+    }
+
+    /// The name of the database the test is running in
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// The name of the test's scratch schema
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// Run `sql`, which can be any statement, discarding whatever it returns
+    pub fn execute(&self, sql: &str) -> Result<(), spi::Error> {
+        Spi::run(sql)
+    }
+
+    /// The first column of the first row `sql` returns, or `None` if it's `NULL`.  An
+    /// [`spi::Error::InvalidPosition`] if it returns no rows.
+    pub fn query_one<T: FromDatum + IntoDatum>(&self, sql: &str) -> Result<Option<T>, spi::Error> {
+        Spi::get_one::<T>(sql)
+    }
+
+    /// The first column of each row `sql` returns
+    pub fn query<T: FromDatum + IntoDatum>(&self, sql: &str) -> Result<Vec<Option<T>>, spi::Error> {
+        Spi::connect(|mut client| {
+            client.update(sql, None, None)?.map(|row| row.get::<T>(1)).collect()
+        })
+    }
+}
+
+/// The schema [`freeze_sql_time()`] puts its `now()` and friends in
+const FROZEN_TIME_SCHEMA: &str = "pgx_frozen_time";
+
+/// Keeps the time frozen, by [`freeze_time()`] or [`freeze_sql_time()`], until it's dropped
+#[must_use = "the time is only frozen until the `FrozenTime` is dropped"]
+pub struct FrozenTime {
+    previous: Option<pg_sys::TimestampTz>,
+    previous_search_path: Option<String>,
+}
+
+impl Drop for FrozenTime {
+    fn drop(&mut self) {
+        crate::clock::freeze(self.previous);
+        // a failed test aborts its transaction, which undoes the rest anyway
+        if let Some(search_path) = self.previous_search_path.take() {
+            if !std::thread::panicking() {
+                Spi::run_with_args(
+                    "SELECT set_config('search_path', $1, true)",
+                    Some(vec![(PgBuiltInOids::TEXTOID.oid(), search_path.into_datum())]),
+                )
+                .expect("couldn't restore the search_path");
+                Spi::run(&format!("DROP SCHEMA {} CASCADE", FROZEN_TIME_SCHEMA))
+                    .expect("couldn't drop the frozen time's schema");
+            }
+        }
+    }
+}
+
+/// Make the functions in [`pgx::clock`](crate::clock) return `at`, which is anything a
+/// `timestamptz` can be parsed from, until the returned guard is dropped.  This is what
+/// `#[pg_test(freeze_time = "..")]` does.
+///
+/// Only Rust code that asks `pgx::clock` for the time sees it.  Postgres itself doesn't:  SQL's
+/// `now()` is untouched, unless [`freeze_sql_time()`] is used instead, as are the times Postgres
+/// records for itself, such as commit timestamps, `pg_stat_activity` and `WAL` records.  Neither do
+/// `std::time` and calls straight to `pg_sys`, such as `GetCurrentTimestamp()`.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+///
+/// #[pg_test]
+/// fn test_frozen() {
+///     let _frozen = pgx::testing::freeze_time("2023-01-01T00:00:00Z");
+///     assert_eq!(pgx::clock::clock_timestamp(), pgx::clock::transaction_timestamp());
+/// }
+/// ```
+///
+/// # Panics
+///
+/// If `at` isn't a valid `timestamptz`.
+pub fn freeze_time(at: &str) -> FrozenTime {
+    let at = parse_frozen_time(at);
+    FrozenTime { previous: crate::clock::freeze(Some(at)), previous_search_path: None }
+}
+
+/// Like [`freeze_time()`], and also make SQL's `now()`, `transaction_timestamp()`,
+/// `statement_timestamp()` and `clock_timestamp()` return `at` for the rest of the transaction,
+/// or until the guard is dropped.  This is what `#[pg_test(freeze_time = "..", shadow_now)]` does.
+///
+/// It creates a `pgx_frozen_time` schema with SQL functions of those names, and puts it first in
+/// the `search_path`, ahead of `pg_catalog`.  So it only works for calls that go through the
+/// `search_path`:  `pg_catalog.now()`, the `CURRENT_TIMESTAMP`, `CURRENT_DATE`, `LOCALTIMESTAMP`
+/// and the like SQL keywords, column defaults and functions with a `SET search_path` of their
+/// own, and anything planned before it was called, still see the real time.
+///
+/// # Panics
+///
+/// If `at` isn't a valid `timestamptz`, or the schema can't be created.
+pub fn freeze_sql_time(at: &str) -> FrozenTime {
+    let frozen = parse_frozen_time(at);
+    let literal = crate::quote_literal(at);
+    let run = |sql: String| Spi::run(&sql).unwrap_or_else(|e| panic!("`{}` failed: {}", sql, e));
+
+    run(format!("CREATE SCHEMA {}", FROZEN_TIME_SCHEMA));
+    for function in ["now", "transaction_timestamp", "statement_timestamp", "clock_timestamp"] {
+        run(format!(
+            "CREATE FUNCTION {}.{}() RETURNS timestamptz STABLE LANGUAGE sql AS $$ SELECT {}::timestamptz $$",
+            FROZEN_TIME_SCHEMA, function, literal
+        ));
+    }
+    let previous_search_path = Spi::get_one::<String>("SELECT current_setting('search_path')")
+        .expect("couldn't read the search_path")
+        .unwrap_or_default();
+    run(format!(
+        "SELECT set_config('search_path', '{}, pg_catalog, ' || current_setting('search_path'), true)",
+        FROZEN_TIME_SCHEMA
+    ));
+
+    FrozenTime {
+        previous: crate::clock::freeze(Some(frozen)),
+        previous_search_path: Some(previous_search_path),
+    }
+}
+
+fn parse_frozen_time(at: &str) -> pg_sys::TimestampTz {
+    let parsed = Spi::get_one_with_args::<TimestampWithTimeZone>(
+        "SELECT $1::timestamptz",
+        vec![(PgBuiltInOids::TEXTOID.oid(), at.into_datum())],
+    )
+    .unwrap_or_else(|e| panic!("can't freeze the time at {:?}: {}", at, e))
+    .expect("a non-NULL text is never a NULL timestamptz");
+    i64::from(parsed)
+}
+
+/// Seed SQL's `random()` and [`pgx::random::pg_random()`](crate::random::pg_random) with any
+/// integer, for the rest of the session.  This is what `#[pg_test(seed = ..)]` does.
+///
+/// The same `seed` always gives the same numbers, on the same Postgres version.  It's mapped onto
+/// the `-1.0..1.0` range `setseed()` takes, so seeds that differ by a multiple of 2^31 are the
+/// same.
+pub fn seed_random(seed: i64) {
+    const RANGE: i64 = 1 << 31;
+    crate::random::set_seed((seed % RANGE) as f64 / RANGE as f64);
+}
+
+/// Assert that `value` comes back unchanged from its type's binary send and receive functions,
+/// both when they're called directly and when the server writes it with `COPY ... TO (FORMAT
+/// BINARY)` and reads it back with `COPY ... FROM`
+///
+/// It works for any type that has them, such as the built-in types and the
+/// `#[derive(PostgresType)]` types with `#[sendrecvfuncs]`.  The `COPY` also checks the bytes it
+/// writes are the ones the send function made.  It goes through a file in the data directory, as
+/// `COPY` can't go to and from the client from within a function, so it has to be run by a
+/// superuser, as `#[pg_test]`s are.
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::testing::assert_binary_roundtrip;
+///
+/// #[pg_test]
+/// fn test_binary_formats() {
+///     assert_binary_roundtrip(String::from("hello"));
+///     assert_binary_roundtrip(vec![Some(1), None, Some(3)]);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// If the type has no send or receive function, or anything comes back different.
+pub fn assert_binary_roundtrip<T>(value: T)
+where
+    T: IntoDatum + FromDatum + PartialEq + Debug + Clone,
+{
+    let type_oid = T::type_oid();
+    let bytes = binary_send(type_oid, value.clone());
+    let received = binary_recv::<T>(type_oid, &bytes);
+    assert_eq!(received, value, "the receive function didn't return the value that was sent");
+
+    let type_name = unsafe {
+        // SAFETY:  it's palloc'd in the current memory context, which outlives the copy we make
+        CStr::from_ptr(pg_sys::format_type_be(type_oid)).to_string_lossy().into_owned()
+    };
+    let path = unsafe {
+        // SAFETY:  it's set when the backend starts, and never changes
+        format!(
+            "{}/pgx_binary_roundtrip_{}.copy",
+            CStr::from_ptr(pg_sys::DataDir).to_string_lossy(),
+            std::process::id()
+        )
+    };
+    let _file = RemoveOnDrop(&path);
+    let table = "pg_temp.pgx_binary_roundtrip";
+    let run = |sql: String| Spi::run(&sql).unwrap_or_else(|e| panic!("`{}` failed: {}", sql, e));
+
+    run(format!("CREATE TABLE {} (value {})", table, type_name));
+    Spi::run_with_args(
+        &format!("INSERT INTO {} VALUES ($1)", table),
+        Some(vec![(PgOid::from(type_oid), value.clone().into_datum())]),
+    )
+    .expect("couldn't insert the value to copy");
+    run(format!("COPY {} TO {} (FORMAT BINARY)", table, crate::quote_literal(&path)));
+    let file = std::fs::read(&path).expect("couldn't read the file COPY wrote");
+    assert_eq!(
+        copy_binary_fields(&file),
+        vec![bytes],
+        "COPY didn't write the bytes the send function made"
+    );
+
+    run(format!("TRUNCATE {}", table));
+    run(format!("COPY {} FROM {} (FORMAT BINARY)", table, crate::quote_literal(&path)));
+    let copied = Spi::get_one::<T>(&format!("SELECT value FROM {}", table))
+        .expect("couldn't select the copied value");
+    assert_eq!(copied, Some(value), "COPY FROM didn't read back the value it wrote");
+
+    run(format!("DROP TABLE {}", table));
+}
+
+/// The file the server writes with `COPY ... TO`, which is removed even if the round trip panics
+/// or raises an `ERROR` partway through
+struct RemoveOnDrop<'a>(&'a str);
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        // it may not have been written yet
+        let _ = std::fs::remove_file(self.0);
+    }
+}
+
+/// The bytes `value`'s type's send function makes from it
+fn binary_send<T: IntoDatum>(type_oid: pg_sys::Oid, value: T) -> Vec<u8> {
+    let datum = value.into_datum().expect("can't send a NULL");
+    unsafe {
+        // SAFETY:  the send function is given a datum of its own type, and returns a bytea
+        let mut send_fn = pg_sys::InvalidOid;
+        let mut is_varlena = false;
+        pg_sys::getTypeBinaryOutputInfo(type_oid, &mut send_fn, &mut is_varlena);
+        let sent = pg_sys::OidSendFunctionCall(send_fn, datum);
+        <&[u8]>::from_datum(pg_sys::Datum::from(sent), false)
+            .expect("the send function returned NULL")
+            .to_vec()
+    }
+}
+
+/// The value `type_oid`'s receive function makes from `bytes`, all of which it has to read
+fn binary_recv<T: FromDatum>(type_oid: pg_sys::Oid, bytes: &[u8]) -> T {
+    unsafe {
+        // SAFETY:  the receive function is given a `StringInfo`, and returns a datum of its type
+        let mut recv_fn = pg_sys::InvalidOid;
+        let mut typioparam = pg_sys::InvalidOid;
+        pg_sys::getTypeBinaryInputInfo(type_oid, &mut recv_fn, &mut typioparam);
+        let buffer = pg_sys::makeStringInfo();
+        pg_sys::appendBinaryStringInfo(buffer, bytes.as_ptr().cast(), bytes.len() as i32);
+        let received = pg_sys::OidReceiveFunctionCall(recv_fn, buffer, typioparam, -1);
+        assert_eq!(
+            (*buffer).cursor,
+            (*buffer).len,
+            "the receive function didn't read all of the bytes it was sent"
+        );
+        T::from_polymorphic_datum(received, false, type_oid)
+            .expect("the receive function returned NULL")
+    }
+}
+
+/// The fields of the rows of a `COPY ... (FORMAT BINARY)` file, of a table with one column
+fn copy_binary_fields(file: &[u8]) -> Vec<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+    assert!(file.starts_with(SIGNATURE), "COPY didn't write its binary format");
+    fn take<'a>(rest: &mut &'a [u8], n: usize) -> &'a [u8] {
+        assert!(rest.len() >= n, "COPY's binary file ended early");
+        let (taken, remaining) = rest.split_at(n);
+        *rest = remaining;
+        taken
+    }
+    let int16 = |bytes: &[u8]| i16::from_be_bytes(bytes.try_into().unwrap());
+    let int32 = |bytes: &[u8]| i32::from_be_bytes(bytes.try_into().unwrap());
+
+    let mut rest = &file[SIGNATURE.len()..];
+    let _flags = take(&mut rest, 4);
+    let extension_length = int32(take(&mut rest, 4));
+    take(&mut rest, extension_length as usize);
+
+    let mut fields = Vec::new();
+    loop {
+        match int16(take(&mut rest, 2)) {
+            -1 => break,
+            1 => {}
+            count => panic!("COPY wrote {} fields, not 1", count),
+        }
+        let length = int32(take(&mut rest, 4));
+        assert!(length >= 0, "COPY wrote a NULL");
+        fields.push(take(&mut rest, length as usize).to_vec());
+    }
+    fields
+}
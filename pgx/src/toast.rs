@@ -0,0 +1,87 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Compressing varlenas the way Postgres does when it toasts them
+//!
+//! Postgres compresses a large value when it stores it in a column whose `STORAGE` allows it.  A
+//! value that's compressed beforehand, by [`compress_varlena()`], is stored as it is, and is
+//! decompressed as usual when it's read.
+//!
+//! On Postgres 14 and later, the method a column compresses with is set by its `COMPRESSION`, as in
+//! `ALTER TABLE .. ALTER COLUMN .. SET COMPRESSION lz4`, or else by `default_toast_compression`.
+use crate::{pg_sys, varatt_is_b8_c};
+
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+const TOAST_PGLZ_COMPRESSION: std::os::raw::c_char = b'p' as _;
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+const TOAST_LZ4_COMPRESSION: std::os::raw::c_char = b'l' as _;
+
+/// The method to compress a value with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    /// The `default_toast_compression` setting's, which is always pglz before Postgres 14
+    Default,
+    /// Postgres' own LZ compression
+    Pglz,
+    /// LZ4, when Postgres was built `--with-lz4`
+    #[cfg(any(feature = "pg14", feature = "pg15"))]
+    Lz4,
+}
+
+/// Is `varlena` compressed, rather than stored out of line or as it is?
+///
+/// # Safety
+///
+/// `varlena` must be a valid, non-null varlena.
+pub unsafe fn is_compressed(varlena: *const pg_sys::varlena) -> bool {
+    varatt_is_b8_c(varlena)
+}
+
+/// Compress `varlena` with `compression`, into a new varlena allocated in `CurrentMemoryContext`
+///
+/// A value that's already compressed, or toasted, is decompressed first.  `None` is returned if
+/// the value is too small to be worth compressing or didn't compress well, in which case it should
+/// be used as it is.
+///
+/// # Safety
+///
+/// `varlena` must be a valid, non-null varlena.
+///
+/// # Panics
+///
+/// If Postgres raises an ERROR, as it does if it wasn't built with support for `compression`.
+pub unsafe fn compress_varlena(
+    varlena: *const pg_sys::varlena,
+    compression: Compression,
+) -> Option<*mut pg_sys::varlena> {
+    let varlena = pg_sys::pg_detoast_datum_packed(varlena as *mut _);
+    let compressed = toast_compress_datum(pg_sys::Datum::from(varlena), compression);
+    if compressed.is_null() {
+        None
+    } else {
+        Some(compressed.cast_mut_ptr())
+    }
+}
+
+#[cfg(any(feature = "pg11", feature = "pg12", feature = "pg13"))]
+unsafe fn toast_compress_datum(value: pg_sys::Datum, compression: Compression) -> pg_sys::Datum {
+    match compression {
+        Compression::Default | Compression::Pglz => pg_sys::toast_compress_datum(value),
+    }
+}
+
+#[cfg(any(feature = "pg14", feature = "pg15"))]
+unsafe fn toast_compress_datum(value: pg_sys::Datum, compression: Compression) -> pg_sys::Datum {
+    let method = match compression {
+        Compression::Default => pg_sys::default_toast_compression as _,
+        Compression::Pglz => TOAST_PGLZ_COMPRESSION,
+        Compression::Lz4 => TOAST_LZ4_COMPRESSION,
+    };
+    pg_sys::toast_compress_datum(value, method)
+}
@@ -37,7 +37,7 @@ The `trigger_example` example above would generate something like the following
 CREATE FUNCTION "trigger_example"()
     RETURNS TRIGGER
     LANGUAGE c
-    AS 'MODULE_PATHNAME', 'trigger_example_wrapper';
+    AS 'MODULE_PATHNAME', 'triggers::trigger_example_wrapper';
 ```
 
 Users could then use it like so:
@@ -145,6 +145,25 @@ fn example_custom_error(trigger: &PgTrigger) -> Result<
 }
 ```
 
+# Returning no row
+
+A trigger function can also return an [`Option`] of a [`PgHeapTuple`][crate::PgHeapTuple].  `None`
+is SQL `NULL`, which a `FOR EACH ROW` `BEFORE` trigger returns to skip the operation on that row, and
+which is all a `FOR EACH STATEMENT` trigger, which has no row, can return:
+
+```rust,no_run
+use pgx::prelude::*;
+
+#[pg_trigger]
+fn example_statement(trigger: &PgTrigger) -> Result<
+    Option<PgHeapTuple<'_, AllocatedByPostgres>>,
+    PgTriggerError,
+> {
+    notice!("{} changed", unsafe { trigger.table_name() }?);
+    Ok(None)
+}
+```
+
 # Lifetimes
 
 Triggers are free to use lifetimes to hone their code, the generated wrapper is as generous as possible.
@@ -217,7 +236,26 @@ pub use pg_trigger_safe::PgTriggerSafe;
 pub use pg_trigger_when::PgTriggerWhen;
 pub use trigger_tuple::TriggerTuple;
 
-use crate::{is_a, pg_sys};
+use crate::heap_tuple::PgHeapTuple;
+use crate::{is_a, pg_sys, WhoAllocated};
+
+/// What a [`#[pg_trigger]`][crate::pg_trigger] function returns, when it doesn't fail:  a
+/// [`PgHeapTuple`], or an [`Option`] of one, whose `None` is SQL `NULL`
+pub trait IntoTriggerResult {
+    fn into_trigger_datum(self) -> Option<pg_sys::Datum>;
+}
+
+impl<'a, AllocatedBy: WhoAllocated> IntoTriggerResult for PgHeapTuple<'a, AllocatedBy> {
+    fn into_trigger_datum(self) -> Option<pg_sys::Datum> {
+        PgHeapTuple::into_trigger_datum(self)
+    }
+}
+
+impl<'a, AllocatedBy: WhoAllocated> IntoTriggerResult for Option<PgHeapTuple<'a, AllocatedBy>> {
+    fn into_trigger_datum(self) -> Option<pg_sys::Datum> {
+        self.and_then(PgHeapTuple::into_trigger_datum)
+    }
+}
 
 /// A newtype'd wrapper around a `pg_sys::TriggerData.tg_event` to prevent accidental misuse
 #[derive(Debug)]
@@ -174,6 +174,25 @@ impl PgTrigger {
         let relation = PgRelation::open(self.relation_data.rd_id);
         Ok(relation)
     }
+    /// The root of the partition tree the trigger's relation is in, or the relation itself if it's
+    /// not a partition.
+    ///
+    /// Row-level triggers on a partitioned table fire on the partition each row is stored in, so
+    /// [`PgTrigger::relation()`] is that partition, and this is the partitioned table.
+    ///
+    /// # Panics
+    ///
+    /// If the relation was recently deleted, this function will panic.
+    ///
+    /// # Safety
+    ///
+    /// The caller should already have at least AccessShareLock on the relation ID, else there are nasty race conditions.
+    ///
+    /// As such, this function is unsafe as we cannot guarantee that this requirement is true.
+    pub unsafe fn root_relation(&self) -> Result<crate::PgRelation, crate::spi::Error> {
+        let relation = PgRelation::open(self.relation_data.rd_id);
+        Ok(relation.partition_root()?.unwrap_or(relation))
+    }
     /// The name of the schema of the table that caused the trigger invocation
     ///
     /// # Panics
@@ -23,3 +23,17 @@ pub enum PgTriggerError {
     #[error("The `pgx::pg_sys::TriggerData`'s `tg_relation` field was a NULL pointer")]
     NullRelation,
 }
+
+impl crate::ErrorReportable for PgTriggerError {
+    fn sql_error_code(&self) -> crate::PgSqlErrorCode {
+        match self {
+            PgTriggerError::CoreUtf8(_) => {
+                crate::PgSqlErrorCode::ERRCODE_CHARACTER_NOT_IN_REPERTOIRE
+            }
+            PgTriggerError::TryFromInt(_) => {
+                crate::PgSqlErrorCode::ERRCODE_NUMERIC_VALUE_OUT_OF_RANGE
+            }
+            _ => crate::PgSqlErrorCode::ERRCODE_E_R_I_E_TRIGGER_PROTOCOL_VIOLATED,
+        }
+    }
+}
@@ -8,9 +8,11 @@ Use of this source code is governed by the MIT license that can be found in the
 */
 
 //! Provides a safe wrapper around Postgres' `pg_sys::TupleDescData` struct
-use crate::{pg_sys, void_mut_ptr, PgBox, PgRelation};
+use crate::{pg_sys, void_mut_ptr, PgBox, PgOid, PgRelation, PgSqlErrorCode};
 
 use pgx_pg_sys::AsPgCStr;
+use std::collections::HashSet;
+use std::ffi::CString;
 use std::ops::Deref;
 
 /// This struct is passed around within the backend to describe the structure
@@ -340,3 +342,134 @@ impl<'a> Iterator for TupleDescDataIntoIterator<'a> {
         Some(result)
     }
 }
+
+/// Describes errors that can occur when building a [`PgTupleDesc`] with a [`TupleDescBuilder`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum TupleDescBuilderError {
+    #[error("column name `{0}` is used more than once")]
+    DuplicateColumnName(String),
+
+    #[error("column name `{0:?}` is empty or contains a null byte")]
+    InvalidColumnName(String),
+
+    #[error("column name `{0}` is longer than 63 bytes")]
+    ColumnNameTooLong(String),
+
+    #[error("column `{0}` has unknown type oid {1}")]
+    UnknownType(String, pg_sys::Oid),
+}
+
+impl crate::ErrorReportable for TupleDescBuilderError {
+    fn sql_error_code(&self) -> PgSqlErrorCode {
+        match self {
+            TupleDescBuilderError::DuplicateColumnName(_) => {
+                PgSqlErrorCode::ERRCODE_DUPLICATE_COLUMN
+            }
+            TupleDescBuilderError::InvalidColumnName(_) => PgSqlErrorCode::ERRCODE_INVALID_NAME,
+            TupleDescBuilderError::ColumnNameTooLong(_) => PgSqlErrorCode::ERRCODE_NAME_TOO_LONG,
+            TupleDescBuilderError::UnknownType(..) => PgSqlErrorCode::ERRCODE_UNDEFINED_OBJECT,
+        }
+    }
+}
+
+/// Builds the [`PgTupleDesc`] of an anonymous record type at runtime, for functions that return
+/// `record` and only know the shape of their result when they're called.
+///
+/// [`TupleDescBuilder::bless`] registers the descriptor with Postgres' type cache, which is what
+/// allows a [`PgHeapTuple`](crate::heap_tuple::PgHeapTuple) built against it to be returned as a
+/// `record` Datum.  The caller of such a function has to describe the columns it expects with a
+/// column definition list, whose types must match the ones given here.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use pgx::prelude::*;
+/// use pgx::TupleDescBuilder;
+///
+/// // CREATE FUNCTION pair() RETURNS record ...;
+/// // SELECT * FROM pair() AS (a int, b numeric(10, 2));
+/// fn pair() -> pgx::composite_type!("record") {
+///     let tupdesc = TupleDescBuilder::new()
+///         .add("a", PgBuiltInOids::INT4OID)
+///         .add_with_typmod("b", PgBuiltInOids::NUMERICOID, ((10 << 16) | 2) + 4)
+///         .bless()
+///         .unwrap();
+///     PgHeapTuple::from_datums(tupdesc, [42.into_datum(), None]).unwrap()
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TupleDescBuilder {
+    columns: Vec<(String, pg_sys::Oid, i32)>,
+}
+
+impl TupleDescBuilder {
+    /// Start describing a record with no columns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a column named `name` of type `typoid`, without a typmod
+    pub fn add<T: Into<PgOid>>(self, name: &str, typoid: T) -> Self {
+        self.add_with_typmod(name, typoid, -1)
+    }
+
+    /// Add a column named `name` of type `typoid`, with the type modifier `typmod`, such as the
+    /// length of a `varchar(n)` or the precision and scale of a `numeric(p, s)`
+    pub fn add_with_typmod<T: Into<PgOid>>(mut self, name: &str, typoid: T, typmod: i32) -> Self {
+        self.columns.push((name.to_string(), typoid.into().value(), typmod));
+        self
+    }
+
+    /// Build the [`PgTupleDesc`] and register it with Postgres' type cache, giving it a typmod
+    /// that identifies this record shape for the rest of the backend's life.
+    ///
+    /// Column names are checked here, as Postgres itself would silently truncate ones that are too
+    /// long and doesn't check anonymous records for duplicates.
+    pub fn bless(self) -> Result<PgTupleDesc<'static>, TupleDescBuilderError> {
+        let mut names = HashSet::new();
+        let mut columns = Vec::with_capacity(self.columns.len());
+        for (name, typoid, typmod) in self.columns {
+            let cname = match CString::new(name.as_str()) {
+                Ok(cname) if !name.is_empty() => cname,
+                _ => return Err(TupleDescBuilderError::InvalidColumnName(name)),
+            };
+            if name.len() >= pg_sys::NAMEDATALEN as usize {
+                return Err(TupleDescBuilderError::ColumnNameTooLong(name));
+            }
+            if !names.insert(name.clone()) {
+                return Err(TupleDescBuilderError::DuplicateColumnName(name));
+            }
+            if unsafe { pg_sys::get_typtype(typoid) } == 0 {
+                return Err(TupleDescBuilderError::UnknownType(name, typoid));
+            }
+            columns.push((cname, typoid, typmod));
+        }
+
+        unsafe {
+            // SAFETY:  every column has a valid name and an existing type, and the blessed
+            // descriptor is our own, with the typcache keeping a copy of it
+            let tupdesc = create_template_tupdesc(columns.len());
+            for (i, (name, typoid, typmod)) in columns.iter().enumerate() {
+                pg_sys::TupleDescInitEntry(
+                    tupdesc,
+                    (i + 1) as pg_sys::AttrNumber,
+                    name.as_ptr(),
+                    *typoid,
+                    *typmod,
+                    0,
+                );
+            }
+            Ok(PgTupleDesc::from_pg_is_copy(pg_sys::BlessTupleDesc(tupdesc)))
+        }
+    }
+}
+
+#[cfg(feature = "pg11")]
+unsafe fn create_template_tupdesc(natts: usize) -> pg_sys::TupleDesc {
+    pg_sys::CreateTemplateTupleDesc(natts as _, false)
+}
+
+#[cfg(any(feature = "pg12", feature = "pg13", feature = "pg14", feature = "pg15"))]
+unsafe fn create_template_tupdesc(natts: usize) -> pg_sys::TupleDesc {
+    pg_sys::CreateTemplateTupleDesc(natts as _)
+}
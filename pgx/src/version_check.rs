@@ -0,0 +1,188 @@
+/*
+Portions Copyright 2019-2021 ZomboDB, LLC.
+Portions Copyright 2021-2022 Technology Concepts & Design, Inc. <support@tcdi.com>
+
+All rights reserved.
+
+Use of this source code is governed by the MIT license that can be found in the LICENSE file.
+*/
+
+//! Checking the extension's installed SQL matches the version of its shared library
+//!
+//! Installing a new version of an extension's library doesn't update its SQL objects:  that's
+//! what `ALTER EXTENSION ... UPDATE` is for.  Until it's run, the SQL may declare functions the
+//! library no longer has, or with different arguments, and calls to them fail in confusing ways,
+//! or worse, don't.
+//!
+//! A function which would rather raise a clear `ERROR` in that case can ask for the check:
+//!
+//! ```rust,no_run
+//! use pgx::prelude::*;
+//!
+//! #[pg_extern(check_version)]
+//! fn reindex_everything() -> i64 {
+//!     42
+//! }
+//! ```
+//!
+//! ```text
+//! ERROR:  extension my_extension SQL is 0.1.0 but library is 0.2.0
+//! HINT:  run `ALTER EXTENSION my_extension UPDATE`
+//! ```
+//!
+//! The extension is the one its control file names, which `CREATE EXTENSION` installed.  If any of
+//! its functions is `check_version`, it also has a `<extension>.check_version` GUC, which is `on`
+//! by default.  It can be turned `off`, for a database or role, or per session, to skip the check
+//! where every call counts.  Functions that aren't `check_version` are never checked, and cost
+//! nothing.
+//!
+//! The installed version is read from `pg_extension` once per backend, the first time a function
+//! asks, and the check doesn't read it again once it has matched.
+use crate::guc::{GucContext, GucRegistry, GucSetting};
+use crate::{IntoDatum, PgBuiltInOids, PgLogLevel, PgSqlErrorCode, Spi};
+
+static mut LIBRARY: Option<(&'static str, &'static str)> = None;
+static mut OPTED_IN: bool = false;
+static mut MATCHED: bool = false;
+static CHECK: GucSetting<bool> = GucSetting::new(true);
+
+/// The extension's installed SQL version is different from its library's version
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("extension {extension} SQL is {installed} but library is {library}")]
+pub struct VersionMismatch {
+    pub extension: String,
+    pub installed: String,
+    pub library: String,
+}
+
+impl VersionMismatch {
+    /// What to do about it, which depends on which of the two is newer
+    pub fn hint(&self) -> String {
+        if is_newer(&self.installed, &self.library) {
+            format!(
+                "the library is older than the installed SQL: install version {} of the library, and reconnect",
+                self.installed
+            )
+        } else {
+            format!("run `ALTER EXTENSION {} UPDATE`", self.extension)
+        }
+    }
+}
+
+/// Registers that a `check_version` function exists, when the library is loaded.  Not public API.
+#[doc(hidden)]
+pub fn __opt_in() {
+    // SAFETY:  called by the dynamic loader, one at a time, before `_PG_init()`
+    unsafe { OPTED_IN = true }
+}
+
+/// Registers the extension's name and library version, and defines its `check_version` GUC if a
+/// function is `check_version`.  Not public API.
+#[doc(hidden)]
+pub fn __register(extension: &'static str, version: &'static str) {
+    unsafe {
+        // SAFETY:  only called by `_PG_init()`
+        if LIBRARY.is_some() {
+            return;
+        }
+        LIBRARY = Some((extension, version));
+        if !OPTED_IN {
+            return;
+        }
+    }
+    GucRegistry::define_bool_guc(
+        &format!("{}.check_version", extension),
+        "Check the extension's installed SQL matches its library before calling its check_version functions",
+        "Raise an ERROR from the extension's check_version functions if `ALTER EXTENSION ... UPDATE` hasn't been run since its library was upgraded, or the library is older than its SQL.",
+        &CHECK,
+        GucContext::Userset,
+    );
+}
+
+/// The name of the extension, which its control file is named after, or `None` if its
+/// `_PG_init()` hasn't run
+pub fn extension_name() -> Option<&'static str> {
+    unsafe { LIBRARY.map(|(extension, _)| extension) }
+}
+
+/// The version of the extension's library, the crate version it was built from, or `None` if its
+/// `_PG_init()` hasn't run
+pub fn library_version() -> Option<&'static str> {
+    unsafe { LIBRARY.map(|(_, version)| version) }
+}
+
+/// The version of the extension's SQL installed in the current database, or `None` if the
+/// extension isn't installed in it
+pub fn installed_version() -> Option<String> {
+    let extension = extension_name()?;
+    Spi::get_one_with_args::<String>(
+        "SELECT extversion FROM pg_catalog.pg_extension WHERE extname = $1::pg_catalog.name",
+        vec![(PgBuiltInOids::TEXTOID.oid(), extension.into_datum())],
+    )
+    .unwrap_or_else(|e| panic!("couldn't read the version of extension {}: {}", extension, e))
+}
+
+/// Compare the installed SQL version with the library version, without raising an `ERROR`
+///
+/// An extension that isn't installed, or whose library wasn't loaded by its own `_PG_init()`,
+/// can't mismatch.
+pub fn compare_versions() -> Result<(), VersionMismatch> {
+    let (extension, library) = match unsafe { LIBRARY } {
+        Some(library) => library,
+        None => return Ok(()),
+    };
+    match installed_version() {
+        Some(installed) if installed != library => Err(VersionMismatch {
+            extension: extension.to_string(),
+            installed,
+            library: library.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Raise an `ERROR` if the installed SQL version isn't the library version
+///
+/// Once they've matched, this backend doesn't check again.
+pub fn check_extension_version() {
+    if unsafe { MATCHED } {
+        return;
+    }
+    match compare_versions() {
+        Ok(()) => unsafe { MATCHED = true },
+        Err(mismatch) => {
+            let hint = mismatch.hint();
+            crate::pg_sys::panic::ErrorReport::new(
+                PgSqlErrorCode::ERRCODE_OBJECT_NOT_IN_PREREQUISITE_STATE,
+                mismatch.to_string(),
+                "check_extension_version",
+            )
+            .set_hint(hint)
+            .report(PgLogLevel::ERROR);
+        }
+    }
+}
+
+/// Called first by every `check_version` function.  Not public API.
+#[doc(hidden)]
+#[inline]
+pub fn __check_version() {
+    if unsafe { !MATCHED } && CHECK.get() {
+        check_extension_version()
+    }
+}
+
+/// Is `a` a newer version than `b`, going by their dot-separated numbers?  Versions that aren't
+/// numbers can't be ordered, so neither is newer.
+fn is_newer(a: &str, b: &str) -> bool {
+    let numbers = |version: &str| {
+        version
+            .split(|c: char| c == '.' || c == '-')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    match (numbers(a), numbers(b)) {
+        (Some(a), Some(b)) => a > b,
+        _ => false,
+    }
+}